@@ -1,13 +1,13 @@
 use crate::service;
-use futures::{future::{select, Map}, FutureExt, TryFutureExt, channel::oneshot, compat::Future01CompatExt};
-use std::cell::RefCell;
+use futures::{future::{select, Either, Map}, FutureExt, TryFutureExt, channel::oneshot, compat::Future01CompatExt};
+use std::{cell::RefCell, time::Duration};
 use tokio::runtime::Runtime;
 pub use sc_cli::{VersionInfo, IntoExit, error};
 use sc_cli::{display_role, informant, parse_and_prepare, ParseAndPrepare, NoCustom};
 use sc_service::{AbstractService, Roles as ServiceRoles, Configuration};
 use sp_consensus_aura::sr25519::{AuthorityPair as AuraPair};
 use crate::chain_spec;
-use log::info;
+use log::{info, warn};
 
 /// Parse command line arguments into service configuration.
 pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()> where
@@ -46,9 +46,13 @@ pub fn run<I, T, E>(args: I, exit: E, version: VersionInfo) -> error::Result<()>
 			Ok(new_full_start!(config).0), load_spec, exit),
 		ParseAndPrepare::CheckBlock(cmd) => cmd.run_with_builder(|config: Config<_>|
 			Ok(new_full_start!(config).0), load_spec, exit),
-		ParseAndPrepare::PurgeChain(cmd) => cmd.run(load_spec),
+		ParseAndPrepare::PurgeChain(cmd) => cmd.run_with_builder(|config: Config<_>|
+			Ok(new_full_start!(config).0), load_spec),
+		ParseAndPrepare::MigrateDatabase(cmd) => cmd.run(load_spec),
+		ParseAndPrepare::Db(cmd) => cmd.run::<node_template_runtime::opaque::Block, _, _, _>(load_spec),
 		ParseAndPrepare::RevertChain(cmd) => cmd.run_with_builder(|config: Config<_>|
 			Ok(new_full_start!(config).0), load_spec),
+		ParseAndPrepare::Key(cmd) => cmd.run(load_spec),
 		ParseAndPrepare::CustomCommand(_) => Ok(())
 	}?;
 
@@ -62,6 +66,9 @@ fn load_spec(id: &str) -> Result<Option<chain_spec::ChainSpec>, String> {
 	})
 }
 
+/// Time given to the tasks of the service to stop once it exited.
+const TASKS_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
 fn run_until_exit<T, E>(
 	mut runtime: Runtime,
 	service: T,
@@ -84,6 +91,7 @@ where
 	// we eagerly drop the service so that the internal exit future is fired,
 	// but we need to keep holding a reference to the global telemetry guard
 	let _telemetry = service.telemetry();
+	let running_tasks = service.running_tasks();
 
 	let service_res = {
 		let exit = e.into_exit();
@@ -91,18 +99,24 @@ where
 			.map_err(|err| error::Error::Service(err))
 			.compat();
 		let select = select(service, exit)
-			.map(|_| Ok(()))
+			.map(|either| match either {
+				Either::Left((result, _)) => result,
+				Either::Right(_) => Ok(()),
+			})
 			.compat();
 		runtime.block_on(select)
 	};
 
 	let _ = exit_send.send(());
 
-	// TODO [andre]: timeout this future #1318
-
 	use futures01::Future;
 
-	let _ = runtime.shutdown_on_idle().wait();
+	if running_tasks.wait(TASKS_SHUTDOWN_TIMEOUT) {
+		let _ = runtime.shutdown_on_idle().wait();
+	} else {
+		warn!("{} tasks did not stop in time, shutting down anyway", running_tasks.count());
+		let _ = runtime.shutdown_now().wait();
+	}
 
 	service_res
 }
@@ -10,7 +10,7 @@ mod cli;
 
 pub use sc_cli::{VersionInfo, IntoExit, error};
 
-fn main() -> Result<(), cli::error::Error> {
+fn main() {
 	let version = VersionInfo {
 		name: "Substrate Node",
 		commit: env!("VERGEN_SHA_SHORT"),
@@ -21,5 +21,8 @@ fn main() -> Result<(), cli::error::Error> {
 		support_url: "support.anonymous.an",
 	};
 
-	cli::run(std::env::args(), cli::Exit, version)
+	if let Err(err) = cli::run(std::env::args(), cli::Exit, version) {
+		eprintln!("Error: {:?}", err);
+		std::process::exit(err.exit_code());
+	}
 }
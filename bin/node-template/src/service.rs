@@ -10,7 +10,10 @@ use sc_network::{construct_simple_protocol};
 use sc_executor::native_executor_instance;
 pub use sc_executor::NativeExecutor;
 use sp_consensus_aura::sr25519::{AuthorityPair as AuraPair};
-use grandpa::{self, FinalityProofProvider as GrandpaFinalityProofProvider};
+use grandpa::{
+	self, FinalityProofProvider as GrandpaFinalityProofProvider,
+	WarpSyncProofProvider as GrandpaWarpSyncProofProvider,
+};
 use sc_basic_authority;
 
 // Our native executor instance.
@@ -47,7 +50,7 @@ macro_rules! new_full_start {
 				let maintainable_pool = sp_transaction_pool::MaintainableTransactionPool::new(pool, maintainer);
 				Ok(maintainable_pool)
 			})?
-			.with_import_queue(|_config, client, mut select_chain, transaction_pool| {
+			.with_import_queue(|config, client, mut select_chain, transaction_pool| {
 				let select_chain = select_chain.take()
 					.ok_or_else(|| sc_service::Error::SelectChainRequired)?;
 
@@ -62,17 +65,32 @@ macro_rules! new_full_start {
 
 				let import_queue = sc_consensus_aura::import_queue::<_, _, _, AuraPair, _>(
 					sc_consensus_aura::SlotDuration::get_or_compute(&*client)?,
-					aura_block_import,
+					aura_block_import.clone(),
 					Some(Box::new(grandpa_block_import.clone())),
 					None,
 					client,
 					inherent_data_providers.clone(),
 					Some(transaction_pool),
+					config.prometheus_config.as_ref().map(|config| &config.registry),
+					config.import_verification_workers,
 				)?;
 
-				import_setup = Some((grandpa_block_import, grandpa_link));
+				// the blocks authored locally go through the Aura block import too, so that it
+				// tracks the authority set changes they signal.
+				import_setup = Some((aura_block_import, grandpa_link));
 
 				Ok(import_queue)
+			})?
+			.with_rpc_extensions(|client, _pool, _backend, _fetcher, _remote_blockchain|
+				-> Result<jsonrpc_core::IoHandler<sc_service::RpcMetadata>, _>
+			{
+				use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
+
+				let mut io = jsonrpc_core::IoHandler::default();
+				io.extend_with(TransactionPaymentApi::to_delegate(
+					TransactionPayment::<_, (Block, node_template_runtime::UncheckedExtrinsic)>::new(client)
+				));
+				Ok(io)
 			})?;
 
 		(builder, import_setup, inherent_data_providers)
@@ -103,13 +121,17 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 		.with_finality_proof_provider(|client, backend|
 			Ok(Arc::new(GrandpaFinalityProofProvider::new(backend, client)) as _)
 		)?
+		.with_warp_sync_provider(|client, backend|
+			Ok(Arc::new(GrandpaWarpSyncProofProvider::new(backend, client)?) as _)
+		)?
 		.build()?;
 
 	if participates_in_consensus {
-		let proposer = sc_basic_authority::ProposerFactory {
-			client: service.client(),
-			transaction_pool: service.transaction_pool(),
-		};
+		let proposer = sc_basic_authority::ProposerFactory::new(
+			service.client(),
+			service.transaction_pool(),
+			service.prometheus_registry().as_ref(),
+		);
 
 		let client = service.client();
 		let select_chain = service.select_chain()
@@ -129,6 +151,7 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 			force_authoring,
 			service.keystore(),
 			can_author_with,
+			sc_consensus_aura::BackoffStrategy::Disabled,
 		)?;
 
 		// the AURA authoring task is considered essential, i.e. if it
@@ -176,6 +199,7 @@ pub fn new_full<C: Send + Default + 'static>(config: Configuration<C, GenesisCon
 				telemetry_on_connect: Some(service.telemetry_on_connect_stream()),
 				voting_rule: grandpa::VotingRulesBuilder::default().build(),
 				executor: service.spawn_task_handle(),
+				shared_voter_state: grandpa::SharedVoterState::empty(),
 			};
 
 			// the GRANDPA voter task is considered infallible, i.e.
@@ -213,7 +237,7 @@ pub fn new_light<C: Send + Default + 'static>(config: Configuration<C, GenesisCo
 			let maintainable_pool = sp_transaction_pool::MaintainableTransactionPool::new(pool, maintainer);
 			Ok(maintainable_pool)
 		})?
-		.with_import_queue_and_fprb(|_config, client, backend, fetcher, _select_chain, _tx_pool| {
+		.with_import_queue_and_fprb(|config, client, backend, fetcher, _select_chain, _tx_pool| {
 			let fetch_checker = fetcher
 				.map(|fetcher| fetcher.checker().clone())
 				.ok_or_else(|| "Trying to start light import queue without active fetch checker")?;
@@ -232,6 +256,8 @@ pub fn new_light<C: Send + Default + 'static>(config: Configuration<C, GenesisCo
 				client,
 				inherent_data_providers.clone(),
 				None,
+				config.prometheus_config.as_ref().map(|config| &config.registry),
+				config.import_verification_workers,
 			)?;
 
 			Ok((import_queue, finality_proof_request_builder))
@@ -240,5 +266,16 @@ pub fn new_light<C: Send + Default + 'static>(config: Configuration<C, GenesisCo
 		.with_finality_proof_provider(|client, backend|
 			Ok(Arc::new(GrandpaFinalityProofProvider::new(backend, client)) as _)
 		)?
+		.with_rpc_extensions(|client, _pool, _backend, _fetcher, _remote_blockchain|
+			-> Result<jsonrpc_core::IoHandler<sc_service::RpcMetadata>, _>
+		{
+			use pallet_transaction_payment_rpc::{TransactionPayment, TransactionPaymentApi};
+
+			let mut io = jsonrpc_core::IoHandler::default();
+			io.extend_with(TransactionPaymentApi::to_delegate(
+				TransactionPayment::<_, (Block, node_template_runtime::UncheckedExtrinsic)>::new(client)
+			));
+			Ok(io)
+		})?
 		.build()
 }
@@ -43,7 +43,7 @@ impl sc_cli::IntoExit for Exit {
 	}
 }
 
-fn main() -> Result<(), sc_cli::error::Error> {
+fn main() {
 	let version = VersionInfo {
 		name: "Substrate Node",
 		commit: env!("VERGEN_SHA_SHORT"),
@@ -54,5 +54,8 @@ fn main() -> Result<(), sc_cli::error::Error> {
 		support_url: "https://github.com/paritytech/substrate/issues/new",
 	};
 
-	node_cli::run(std::env::args(), Exit, version)
+	if let Err(err) = node_cli::run(std::env::args(), Exit, version) {
+		eprintln!("Error: {:?}", err);
+		std::process::exit(err.exit_code());
+	}
 }
@@ -391,6 +391,14 @@ pub(crate) mod tests {
 		)
 	}
 
+	#[test]
+	fn test_system_rpc() {
+		sc_service_test::system_rpc(
+			integration_test_config_with_two_authorities(),
+			|config| new_full(config),
+		);
+	}
+
 	#[test]
 	#[ignore]
 	fn test_connectivity() {
@@ -17,9 +17,10 @@
 pub use sc_cli::VersionInfo;
 use tokio::prelude::Future;
 use tokio::runtime::{Builder as RuntimeBuilder, Runtime};
-use sc_cli::{IntoExit, NoCustom, SharedParams, ImportParams, error};
+use sc_cli::{IntoExit, NoCustom, SharedParams, ImportParams, CryptoScheme, error};
 use sc_service::{AbstractService, Roles as ServiceRoles, Configuration};
-use log::info;
+use log::{info, warn};
+use std::time::Duration;
 use structopt::StructOpt;
 use sc_cli::{display_role, parse_and_prepare, GetSharedParams, ParseAndPrepare};
 use crate::{service, ChainSpec, load_spec};
@@ -36,12 +37,21 @@ pub enum CustomSubcommands {
 		Only supported for development or local testnet."
 	)]
 	Factory(FactoryCmd),
+
+	/// The custom subcommand for creating signed transactions offline.
+	#[structopt(
+		name = "sign-transaction",
+		about = "Creates a transaction calling the given call, signs it with the given key \
+		and prints it hex-encoded, ready to be submitted."
+	)]
+	SignTransaction(SignTransactionCmd),
 }
 
 impl GetSharedParams for CustomSubcommands {
 	fn shared_params(&self) -> Option<&SharedParams> {
 		match self {
 			CustomSubcommands::Factory(cmd) => Some(&cmd.shared_params),
+			CustomSubcommands::SignTransaction(_) => None,
 		}
 	}
 }
@@ -88,6 +98,60 @@ pub struct FactoryCmd {
 	pub import_params: ImportParams,
 }
 
+/// The `sign-transaction` command used to create signed transactions offline.
+#[derive(Debug, StructOpt, Clone)]
+pub struct SignTransactionCmd {
+	/// Secret key URI of the signer, e.g. a secret phrase or `//Alice`.
+	#[structopt(long = "suri", value_name = "SURI")]
+	pub suri: String,
+
+	/// Password of the secret key URI.
+	#[structopt(long = "password", value_name = "PASSWORD")]
+	pub password: Option<String>,
+
+	/// Cryptography scheme of the signer key.
+	#[structopt(
+		long = "scheme",
+		value_name = "SCHEME",
+		possible_values = &CryptoScheme::variants(),
+		case_insensitive = true,
+		default_value = "Sr25519"
+	)]
+	pub scheme: CryptoScheme,
+
+	/// The SCALE encoded call to dispatch, as hex.
+	#[structopt(long = "call", value_name = "HEX")]
+	pub call: String,
+
+	/// Nonce of the signer account.
+	#[structopt(long = "nonce", value_name = "NONCE")]
+	pub nonce: node_primitives::Index,
+
+	/// Tip for the block author.
+	#[structopt(long = "tip", value_name = "TIP", default_value = "0")]
+	pub tip: node_primitives::Balance,
+
+	/// Hash of the genesis block of the chain, as hex.
+	#[structopt(long = "genesis", value_name = "HASH")]
+	pub genesis: String,
+
+	/// Number of blocks the transaction is valid for. Immortal when not given.
+	#[structopt(long = "era-period", value_name = "BLOCKS")]
+	pub era_period: Option<u64>,
+
+	/// Number of the block the era of a mortal transaction starts from.
+	#[structopt(long = "era-block-number", value_name = "NUMBER")]
+	pub era_block_number: Option<node_primitives::BlockNumber>,
+
+	/// Hash of the block the era of a mortal transaction starts from, as hex.
+	#[structopt(long = "era-block-hash", value_name = "HASH")]
+	pub era_block_hash: Option<String>,
+
+	/// Spec version of the runtime of the chain. Defaults to the one of the native runtime.
+	#[structopt(long = "spec-version", value_name = "VERSION")]
+	pub spec_version: Option<u32>,
+}
+
 /// Parse command line arguments into service configuration.
 pub fn run<I, T, E>(args: I, exit: E, version: sc_cli::VersionInfo) -> error::Result<()> where
 	I: IntoIterator<Item = T>,
@@ -127,9 +191,15 @@ pub fn run<I, T, E>(args: I, exit: E, version: sc_cli::VersionInfo) -> error::Re
 			Ok(new_full_start!(config).0), load_spec, exit),
 		ParseAndPrepare::CheckBlock(cmd) => cmd.run_with_builder(|config: Config<_, _>|
 			Ok(new_full_start!(config).0), load_spec, exit),
-		ParseAndPrepare::PurgeChain(cmd) => cmd.run(load_spec),
+		ParseAndPrepare::PurgeChain(cmd) => cmd.run_with_builder(|config: Config<_, _>|
+			Ok(new_full_start!(config).0), load_spec),
+		ParseAndPrepare::MigrateDatabase(cmd) => cmd.run(load_spec),
+		ParseAndPrepare::Db(cmd) => cmd.run::<node_primitives::Block, _, _, _>(load_spec),
 		ParseAndPrepare::RevertChain(cmd) => cmd.run_with_builder(|config: Config<_, _>|
 			Ok(new_full_start!(config).0), load_spec),
+		ParseAndPrepare::Key(cmd) => cmd.run(load_spec),
+		ParseAndPrepare::CustomCommand(CustomSubcommands::SignTransaction(cmd)) =>
+			crate::transaction::sign_transaction(&cmd),
 		ParseAndPrepare::CustomCommand(CustomSubcommands::Factory(cli_args)) => {
 			let mut config: Config<_, _> = sc_cli::create_config_with_db_path(
 				load_spec,
@@ -163,6 +233,9 @@ pub fn run<I, T, E>(args: I, exit: E, version: sc_cli::VersionInfo) -> error::Re
 	}
 }
 
+/// Time given to the tasks of the service to stop once it exited.
+const TASKS_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(30);
+
 fn run_until_exit<T, E>(
 	mut runtime: Runtime,
 	service: T,
@@ -172,7 +245,9 @@ where
 	T: AbstractService,
 	E: IntoExit,
 {
-	use futures::{FutureExt, TryFutureExt, channel::oneshot, future::select, compat::Future01CompatExt};
+	use futures::{
+		FutureExt, TryFutureExt, channel::oneshot, future::{select, Either}, compat::Future01CompatExt,
+	};
 
 	let (exit_send, exit) = oneshot::channel();
 
@@ -187,6 +262,7 @@ where
 	// we eagerly drop the service so that the internal exit future is fired,
 	// but we need to keep holding a reference to the global telemetry guard
 	let _telemetry = service.telemetry();
+	let running_tasks = service.running_tasks();
 
 	let service_res = {
 		let exit = e.into_exit();
@@ -194,15 +270,22 @@ where
 			.map_err(|err| error::Error::Service(err))
 			.compat();
 		let select = select(service, exit)
-			.map(|_| Ok(()))
+			.map(|either| match either {
+				Either::Left((result, _)) => result,
+				Either::Right(_) => Ok(()),
+			})
 			.compat();
 		runtime.block_on(select)
 	};
 
 	let _ = exit_send.send(());
 
-	// TODO [andre]: timeout this future #1318
-	let _ = runtime.shutdown_on_idle().wait();
+	if running_tasks.wait(TASKS_SHUTDOWN_TIMEOUT) {
+		let _ = runtime.shutdown_on_idle().wait();
+	} else {
+		warn!("{} tasks did not stop in time, shutting down anyway", running_tasks.count());
+		let _ = runtime.shutdown_now().wait();
+	}
 
 	service_res
 }
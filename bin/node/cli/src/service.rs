@@ -55,6 +55,10 @@ macro_rules! new_full_start {
 		type RpcExtension = jsonrpc_core::IoHandler<sc_rpc::Metadata>;
 		let mut import_setup = None;
 		let inherent_data_providers = sp_inherents::InherentDataProviders::new();
+		let shared_voter_state = grandpa::SharedVoterState::empty();
+		let rpc_voter_state = shared_voter_state.clone();
+		let shared_authority_discovery = sc_authority_discovery::SharedService::empty();
+		let rpc_authority_discovery = shared_authority_discovery.clone();
 
 		let builder = sc_service::ServiceBuilder::new_full::<
 			node_primitives::Block, node_runtime::RuntimeApi, node_executor::Executor
@@ -69,7 +73,7 @@ macro_rules! new_full_start {
 				let maintainable_pool = sp_transaction_pool::MaintainableTransactionPool::new(pool, maintainer);
 				Ok(maintainable_pool)
 			})?
-			.with_import_queue(|_config, client, mut select_chain, _transaction_pool| {
+			.with_import_queue(|config, client, mut select_chain, _transaction_pool| {
 				let select_chain = select_chain.take()
 					.ok_or_else(|| sc_service::Error::SelectChainRequired)?;
 				let (grandpa_block_import, grandpa_link) = grandpa::block_import(
@@ -94,16 +98,53 @@ macro_rules! new_full_start {
 					client.clone(),
 					client,
 					inherent_data_providers.clone(),
+					config.prometheus_config.as_ref().map(|config| &config.registry),
+					config.import_verification_workers,
 				)?;
 
 				import_setup = Some((block_import, grandpa_link, babe_link));
 				Ok(import_queue)
-			})?
-			.with_rpc_extensions(|client, pool, _backend, fetcher, _remote_blockchain| -> Result<RpcExtension, _> {
-				Ok(node_rpc::create(client, pool, node_rpc::LightDeps::none(fetcher)))
 			})?;
 
-		(builder, import_setup, inherent_data_providers)
+		let (_, _, babe_link) = import_setup.as_ref().expect("Link Half is set by the import queue builder above; qed");
+		let rpc_babe_link = babe_link.clone();
+		let rpc_keystore = builder.keystore().clone();
+
+		let builder = builder
+			.with_rpc_extensions_builder(|client, pool, backend, fetcher, _remote_blockchain| {
+				use sc_consensus_babe_rpc::{BabeApi, BabeRpcHandler};
+				use sc_finality_grandpa_rpc::{GrandpaApi, GrandpaRpcHandler};
+				use sc_authority_discovery_rpc::{AuthorityDiscoveryApi, AuthorityDiscoveryRpcHandler};
+
+				let finality_proof_provider = std::sync::Arc::new(
+					grandpa::FinalityProofProvider::new(backend, client.clone())
+				);
+				Ok(move |deny_unsafe: sc_rpc::DenyUnsafe| -> RpcExtension {
+					let babe_handler = BabeRpcHandler::new(
+						client.clone(),
+						rpc_babe_link.clone(),
+						rpc_keystore.clone(),
+						deny_unsafe,
+					);
+					let mut io = node_rpc::create(
+						client.clone(),
+						pool.clone(),
+						node_rpc::LightDeps::none(fetcher.clone()),
+					);
+					io.extend_with(GrandpaApi::to_delegate(GrandpaRpcHandler::new(
+						rpc_voter_state.clone(),
+						finality_proof_provider.clone(),
+						deny_unsafe,
+					)));
+					io.extend_with(BabeApi::to_delegate(babe_handler));
+					io.extend_with(AuthorityDiscoveryApi::to_delegate(
+						AuthorityDiscoveryRpcHandler::new(rpc_authority_discovery.clone(), deny_unsafe)
+					));
+					io
+				})
+			})?;
+
+		(builder, import_setup, inherent_data_providers, shared_voter_state, shared_authority_discovery)
 	}}
 }
 
@@ -127,12 +168,14 @@ macro_rules! new_full {
 			name,
 			disable_grandpa,
 			sentry_nodes,
+			allow_non_globals_in_dht,
 		) = (
 			$config.roles.is_authority(),
 			$config.force_authoring,
 			$config.name.clone(),
 			$config.disable_grandpa,
 			$config.network.sentry_nodes.clone(),
+			$config.network.allow_non_globals_in_dht,
 		);
 
 		// sentry nodes announce themselves as authorities to the network
@@ -140,7 +183,13 @@ macro_rules! new_full {
 		// never actively participate in any consensus process.
 		let participates_in_consensus = is_authority && !$config.sentry_mode;
 
-		let (builder, mut import_setup, inherent_data_providers) = new_full_start!($config);
+		let (
+			builder,
+			mut import_setup,
+			inherent_data_providers,
+			shared_voter_state,
+			shared_authority_discovery,
+		) = new_full_start!($config);
 
 		// Dht event channel from the network to the authority discovery module. Use bounded channel to ensure
 		// back-pressure. Authority discovery is triggering one event per authority within the current authority set.
@@ -153,6 +202,10 @@ macro_rules! new_full {
 			.with_finality_proof_provider(|client, backend|
 				Ok(Arc::new(grandpa::FinalityProofProvider::new(backend, client)) as _)
 			)?
+			.with_warp_sync_provider(|client, backend|
+				Ok(Arc::new(grandpa::WarpSyncProofProvider::new(backend, client)?) as _)
+			)?
+			.with_fork_ranking(|client| Ok(Arc::new(sc_consensus_babe::BabeForkRanking::new(client)) as _))?
 			.with_dht_event_tx(dht_event_tx)?
 			.build()?;
 
@@ -162,10 +215,11 @@ macro_rules! new_full {
 		($with_startup_data)(&block_import, &babe_link);
 
 		if participates_in_consensus {
-			let proposer = sc_basic_authority::ProposerFactory {
-				client: service.client(),
-				transaction_pool: service.transaction_pool(),
-			};
+			let proposer = sc_basic_authority::ProposerFactory::new(
+				service.client(),
+				service.transaction_pool(),
+				service.prometheus_registry().as_ref(),
+			);
 
 			let client = service.client();
 			let select_chain = service.select_chain()
@@ -185,11 +239,24 @@ macro_rules! new_full {
 				force_authoring,
 				babe_link,
 				can_author_with,
+				backoff_strategy: sc_consensus_babe::BackoffStrategy::Disabled,
 			};
 
 			let babe = sc_consensus_babe::start_babe(babe_config)?;
 			service.spawn_essential_task(babe);
+		}
+
+		// Sentry nodes don't hold authority discovery keys, thus they only discover other
+		// authorities.
+		let authority_discovery_role = if participates_in_consensus {
+			Some(sc_authority_discovery::Role::PublishAndDiscover)
+		} else if is_authority {
+			Some(sc_authority_discovery::Role::Discover)
+		} else {
+			None
+		};
 
+		if let Some(authority_discovery_role) = authority_discovery_role {
 			let future03_dht_event_rx = dht_event_rx.compat()
 				.map(|x| x.expect("<mpsc::channel::Receiver as Stream> never returns an error; qed"))
 				.boxed();
@@ -199,7 +266,15 @@ macro_rules! new_full {
 				sentry_nodes,
 				service.keystore(),
 				future03_dht_event_rx,
+				sc_authority_discovery::AuthorityDiscoveryConfig {
+					publish_non_global_ips: allow_non_globals_in_dht,
+					persist_address_cache: true,
+					..Default::default()
+				},
+				authority_discovery_role,
+				service.prometheus_registry(),
 			);
+			shared_authority_discovery.set(authority_discovery.service());
 			let future01_authority_discovery = authority_discovery.map(|x| Ok(x)).compat();
 
 			service.spawn_task(future01_authority_discovery);
@@ -245,6 +320,7 @@ macro_rules! new_full {
 					telemetry_on_connect: Some(service.telemetry_on_connect_stream()),
 					voting_rule: grandpa::VotingRulesBuilder::default().build(),
 					executor: service.spawn_task_handle(),
+					shared_voter_state,
 				};
 				// the GRANDPA voter task is considered infallible, i.e.
 				// if it fails we take down the service with it.
@@ -335,7 +411,7 @@ pub fn new_light<C: Send + Default + 'static>(config: NodeConfiguration<C>)
 			let maintainable_pool = sp_transaction_pool::MaintainableTransactionPool::new(pool, maintainer);
 			Ok(maintainable_pool)
 		})?
-		.with_import_queue_and_fprb(|_config, client, backend, fetcher, _select_chain, _tx_pool| {
+		.with_import_queue_and_fprb(|config, client, backend, fetcher, _select_chain, _tx_pool| {
 			let fetch_checker = fetcher
 				.map(|fetcher| fetcher.checker().clone())
 				.ok_or_else(|| "Trying to start light import queue without active fetch checker")?;
@@ -365,6 +441,8 @@ pub fn new_light<C: Send + Default + 'static>(config: NodeConfiguration<C>)
 				client.clone(),
 				client,
 				inherent_data_providers.clone(),
+				config.prometheus_config.as_ref().map(|config| &config.registry),
+				config.import_verification_workers,
 			)?;
 
 			Ok((import_queue, finality_proof_request_builder))
@@ -515,10 +593,11 @@ mod tests {
 
 				let parent_id = BlockId::number(service.client().info().chain.best_number);
 				let parent_header = service.client().header(&parent_id).unwrap().unwrap();
-				let mut proposer_factory = sc_basic_authority::ProposerFactory {
-					client: service.client(),
-					transaction_pool: service.transaction_pool(),
-				};
+				let mut proposer_factory = sc_basic_authority::ProposerFactory::new(
+					service.client(),
+					service.transaction_pool(),
+					None,
+				);
 
 				let mut digest = Digest::<H256>::default();
 
@@ -526,13 +605,14 @@ mod tests {
 				// so we must keep trying the next slots until we can claim one.
 				let babe_pre_digest = loop {
 					inherent_data.replace_data(sp_timestamp::INHERENT_IDENTIFIER, &(slot_num * SLOT_DURATION));
-					if let Some(babe_pre_digest) = sc_consensus_babe::test_helpers::claim_slot(
+					let claim = sc_consensus_babe::test_helpers::claim_slot(
 						slot_num,
 						&parent_header,
 						&*service.client(),
 						&keystore,
 						&babe_link,
-					) {
+					);
+					if let Some(babe_pre_digest) = futures::executor::block_on(claim) {
 						break babe_pre_digest;
 					}
 
@@ -570,6 +650,7 @@ mod tests {
 					fork_choice: ForkChoiceStrategy::LongestChain,
 					allow_missing_state: false,
 					import_existing: false,
+					state: None,
 				};
 
 				block_import.import_block(params, Default::default())
@@ -0,0 +1,174 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Offline creation of signed transactions.
+
+use codec::{Encode, Decode};
+use node_primitives::{Balance, Hash, Index, Signature};
+use node_runtime::{Call, SignedExtra, SignedPayload, UncheckedExtrinsic};
+use sc_cli::{CryptoScheme, error};
+use sp_core::{ed25519, sr25519, ecdsa, bytes::from_hex, crypto::Pair, hexdisplay::HexDisplay};
+use sp_runtime::{MultiSigner, generic::Era, traits::IdentifyAccount};
+
+use crate::cli::SignTransactionCmd;
+
+/// Creates the transaction described by the command and prints it, hex-encoded.
+pub fn sign_transaction(cmd: &SignTransactionCmd) -> error::Result<()> {
+	let call = from_hex(&cmd.call)
+		.map_err(|e| error::Error::Input(format!("Invalid hex call: {}", e)))?;
+	let call = Call::decode(&mut &call[..])
+		.map_err(|e| error::Error::Input(format!("Invalid call: {}", e.what())))?;
+	let genesis_hash = parse_hash(&cmd.genesis)?;
+	let (era, checkpoint) = match (cmd.era_period, cmd.era_block_number, &cmd.era_block_hash) {
+		(Some(period), Some(number), Some(hash)) =>
+			(Era::mortal(period, number.into()), parse_hash(hash)?),
+		(None, _, _) => (Era::Immortal, genesis_hash),
+		_ => return Err(error::Error::Input(
+			"A mortal transaction requires --era-block-number and --era-block-hash".into()
+		)),
+	};
+	let spec_version = cmd.spec_version.unwrap_or(node_runtime::VERSION.spec_version);
+	let password = cmd.password.as_ref().map(String::as_str);
+
+	let parameters = TransactionParameters {
+		nonce: cmd.nonce,
+		tip: cmd.tip,
+		era,
+		checkpoint,
+		genesis_hash,
+		spec_version,
+	};
+	let transaction = match cmd.scheme {
+		CryptoScheme::Ed25519 =>
+			create_signed_transaction(call, pair::<ed25519::Pair>(&cmd.suri, password)?, parameters),
+		CryptoScheme::Sr25519 =>
+			create_signed_transaction(call, pair::<sr25519::Pair>(&cmd.suri, password)?, parameters),
+		CryptoScheme::Ecdsa =>
+			create_signed_transaction(call, pair::<ecdsa::Pair>(&cmd.suri, password)?, parameters),
+	};
+
+	println!("0x{}", HexDisplay::from(&transaction.encode()));
+	Ok(())
+}
+
+/// Parameters of a signed transaction, besides its call and signer.
+pub struct TransactionParameters {
+	/// Nonce of the signer account.
+	pub nonce: Index,
+	/// Tip for the block author.
+	pub tip: Balance,
+	/// Period during which the transaction is valid.
+	pub era: Era,
+	/// Hash of the block the era starts from, or the genesis hash for immortal transactions.
+	pub checkpoint: Hash,
+	/// Hash of the genesis block of the chain.
+	pub genesis_hash: Hash,
+	/// Spec version of the runtime of the chain.
+	pub spec_version: u32,
+}
+
+/// Creates a transaction calling `function`, signed by `signer`.
+pub fn create_signed_transaction<P: Pair>(
+	function: Call,
+	signer: P,
+	parameters: TransactionParameters,
+) -> UncheckedExtrinsic where
+	P::Public: Into<MultiSigner>,
+	P::Signature: Into<Signature>,
+{
+	let extra: SignedExtra = (
+		frame_system::CheckVersion::new(),
+		frame_system::CheckGenesis::new(),
+		frame_system::CheckEra::from(parameters.era),
+		frame_system::CheckNonce::from(parameters.nonce),
+		frame_system::CheckWeight::new(),
+		pallet_transaction_payment::ChargeTransactionPayment::from(parameters.tip),
+		Default::default(),
+	);
+	let raw_payload = SignedPayload::from_raw(
+		function,
+		extra,
+		(
+			parameters.spec_version,
+			parameters.genesis_hash,
+			parameters.checkpoint,
+			(),
+			(),
+			(),
+			(),
+		),
+	);
+	let signature = raw_payload.using_encoded(|payload| signer.sign(payload));
+	let (function, extra, _) = raw_payload.deconstruct();
+
+	UncheckedExtrinsic::new_signed(
+		function,
+		signer.public().into().into_account().into(),
+		signature.into(),
+		extra,
+	)
+}
+
+fn pair<P: Pair>(suri: &str, password: Option<&str>) -> error::Result<P> {
+	P::from_string(suri, password)
+		.map_err(|_| error::Error::Input("Invalid secret key URI".into()))
+}
+
+fn parse_hash(hash: &str) -> error::Result<Hash> {
+	let bytes = from_hex(hash)
+		.map_err(|e| error::Error::Input(format!("Invalid hex block hash: {}", e)))?;
+	if bytes.len() != Hash::len_bytes() {
+		return Err(error::Error::Input(format!("Invalid block hash length: {}", bytes.len())));
+	}
+	Ok(Hash::from_slice(&bytes))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use node_runtime::{Address, BalancesCall};
+	use sp_runtime::traits::Verify;
+
+	#[test]
+	fn creates_transactions_with_a_valid_signature() {
+		let signer = sr25519::Pair::from_string("//Alice", None).unwrap();
+		let account = MultiSigner::from(signer.public()).into_account();
+		let to = sr25519::Pair::from_string("//Bob", None).unwrap().public();
+		let function = Call::Balances(
+			BalancesCall::transfer(MultiSigner::from(to).into_account().into(), 1)
+		);
+		let (genesis_hash, checkpoint) = (Hash::repeat_byte(1), Hash::repeat_byte(2));
+
+		let transaction = create_signed_transaction(function, signer, TransactionParameters {
+			nonce: 3,
+			tip: 0,
+			era: Era::mortal(64, 10),
+			checkpoint,
+			genesis_hash,
+			spec_version: 1,
+		});
+		let transaction = UncheckedExtrinsic::decode(&mut &transaction.encode()[..]).unwrap();
+
+		let (address, signature, extra) = transaction.signature.unwrap();
+		assert!(address == Address::from(account.clone()));
+		let payload = SignedPayload::from_raw(
+			transaction.function,
+			extra,
+			(1, genesis_hash, checkpoint, (), (), (), ()),
+		);
+		assert!(payload.using_encoded(|payload| signature.verify(payload, &account)));
+	}
+}
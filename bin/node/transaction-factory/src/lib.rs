@@ -200,6 +200,7 @@ fn import_block<Backend, Exec, Block, RtApi>(
 		fork_choice: ForkChoiceStrategy::LongestChain,
 		allow_missing_state: false,
 		import_existing: false,
+		state: None,
 	};
 	(&**client).import_block(import, HashMap::new()).expect("Failed to import block");
 }
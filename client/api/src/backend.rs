@@ -20,6 +20,7 @@ use std::sync::Arc;
 use std::collections::HashMap;
 use sp_core::ChangesTrieConfiguration;
 use sp_core::offchain::OffchainStorage;
+use sp_core::storage::StorageKey;
 use sp_runtime::{generic::BlockId, Justification, Storage};
 use sp_runtime::traits::{Block as BlockT, NumberFor};
 use sp_state_machine::backend::Backend as StateBackend;
@@ -29,6 +30,7 @@ use crate::{
 		Backend as BlockchainBackend, well_known_cache_keys
 	},
 	light::RemoteBlockchain,
+	BlockImportNotification,
 };
 use sp_blockchain;
 use sp_consensus::BlockOrigin;
@@ -41,6 +43,9 @@ pub type StorageCollection = Vec<(Vec<u8>, Option<Vec<u8>>)>;
 /// In memory arrays of storage values for multiple child tries.
 pub type ChildStorageCollection = Vec<(Vec<u8>, StorageCollection)>;
 
+/// In memory array of the values indexed in the offchain storage during block import.
+pub type OffchainChangesCollection = Vec<(Vec<u8>, Option<Vec<u8>>)>;
+
 /// Import operation summary.
 ///
 /// Contains information about the block that just got imported,
@@ -60,6 +65,19 @@ pub struct ImportSummary<Block: BlockT> {
 	pub retracted: Vec<Block::Hash>,
 }
 
+/// Part of the chain data that can be purged from a backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, derive_more::Display)]
+pub enum PurgeTarget {
+	/// The state of all blocks.
+	///
+	/// Blocks are kept, but no new block can be imported until the state is restored.
+	#[display(fmt = "state")]
+	State,
+	/// The bodies of all blocks. Headers and justifications are kept.
+	#[display(fmt = "block bodies")]
+	BlockBodies,
+}
+
 /// Import operation wrapper
 pub struct ClientImportOperation<
 	Block: BlockT,
@@ -72,6 +90,10 @@ pub struct ClientImportOperation<
 	pub notify_imported: Option<ImportSummary<Block>>,
 	/// A list of hashes of blocks that got finalized.
 	pub notify_finalized: Vec<Block::Hash>,
+	/// The imported block, notified whatever its origin.
+	pub imported: Option<BlockImportNotification<Block>>,
+	/// Hashes of all the blocks that got finalized, in ascending order.
+	pub finalized: Vec<Block::Hash>,
 }
 
 /// State of a new block.
@@ -143,6 +165,16 @@ pub trait BlockImportOperation<Block, H> where
 		child_update: ChildStorageCollection,
 	) -> sp_blockchain::Result<()>;
 
+	/// Set the values indexed in the offchain storage by the block.
+	///
+	/// Backends without offchain storage ignore them.
+	fn update_offchain_storage(
+		&mut self,
+		_offchain_update: OffchainChangesCollection,
+	) -> sp_blockchain::Result<()> {
+		Ok(())
+	}
+
 	/// Inject changes trie data into the database.
 	fn update_changes_trie(&mut self, update: ChangesTrieTransaction<H, NumberFor<Block>>) -> sp_blockchain::Result<()>;
 
@@ -217,6 +249,19 @@ pub trait AuxStore {
 	fn get_aux(&self, key: &[u8]) -> sp_blockchain::Result<Option<Vec<u8>>>;
 }
 
+/// Provides access to the storage of the blocks.
+pub trait StorageProvider<Block: BlockT> {
+	/// Given a `BlockId` and a key prefix, return at most `count` of the matching storage keys in
+	/// that block, in ascending order, starting after `start_key` if given.
+	fn storage_keys_paged(
+		&self,
+		id: &BlockId<Block>,
+		prefix: &StorageKey,
+		count: usize,
+		start_key: Option<&StorageKey>,
+	) -> sp_blockchain::Result<Vec<StorageKey>>;
+}
+
 /// Client backend.
 ///
 /// Manages the data layer.
@@ -283,10 +328,33 @@ pub trait Backend<Block, H>: AuxStore + Send + Sync where
 		Ok(())
 	}
 
-	/// Attempts to revert the chain by `n` blocks.
+	/// Prevent the state of the given block from being pruned, until `unpin_block` has been
+	/// called as many times as this.
+	///
+	/// Fails if the state of the block is not available.
+	fn pin_block(&self, hash: &Block::Hash) -> sp_blockchain::Result<()> {
+		self.state_at(BlockId::Hash(hash.clone())).map(|_| ())
+	}
+
+	/// Release a block pinned by `pin_block`.
+	fn unpin_block(&self, _hash: &Block::Hash) {}
+
+	/// Attempts to revert the chain by `n` blocks. Finalized blocks are only reverted if
+	/// `revert_finalized` is true, as far as their state is still known.
 	///
 	/// Returns the number of blocks that were successfully reverted.
-	fn revert(&self, n: NumberFor<Block>) -> sp_blockchain::Result<NumberFor<Block>>;
+	fn revert(
+		&self,
+		n: NumberFor<Block>,
+		revert_finalized: bool,
+	) -> sp_blockchain::Result<NumberFor<Block>>;
+
+	/// Remove the given part of the chain data, keeping everything else.
+	fn purge(&self, target: PurgeTarget) -> sp_blockchain::Result<()> {
+		Err(sp_blockchain::Error::Backend(
+			format!("Purging {} is not supported by this backend", target),
+		))
+	}
 
 	/// Insert auxiliary data into key-value store.
 	fn insert_aux<
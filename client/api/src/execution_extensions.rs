@@ -133,6 +133,11 @@ impl<Block: traits::Block> ExecutionExtensions<Block> {
 				self.strategies.other.get_manager(),
 		};
 
+		(manager, self.extensions(at, context))
+	}
+
+	/// Create the `Extensions` supporting the APIs available to the given offchain call.
+	pub fn extensions(&self, at: &BlockId<Block>, context: ExecutionContext) -> Extensions {
 		let capabilities = context.capabilities();
 
 		let mut extensions = Extensions::new();
@@ -158,7 +163,7 @@ impl<Block: traits::Block> ExecutionExtensions<Block> {
 			)
 		}
 
-		(manager, extensions)
+		extensions
 	}
 }
 
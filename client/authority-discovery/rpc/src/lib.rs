@@ -0,0 +1,138 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC interface for the authority discovery module.
+
+#![warn(missing_docs)]
+
+use futures::{FutureExt, TryFutureExt};
+use jsonrpc_core::{Error as RpcError, ErrorCode};
+use jsonrpc_derive::rpc;
+use libp2p::Multiaddr;
+use sc_authority_discovery::SharedService;
+use sc_rpc_api::DenyUnsafe;
+
+pub use self::gen_client::Client as AuthorityDiscoveryClient;
+
+/// Future's type for jsonrpc.
+type FutureResult<T> = Box<dyn jsonrpc_core::futures::Future<Item = T, Error = RpcError> + Send>;
+
+/// Substrate authority discovery RPC API
+#[rpc]
+pub trait AuthorityDiscoveryApi {
+	/// Replace the public addresses of the sentry nodes published on behalf of this node, e.g.
+	/// when sentries are rotated, and publish them right away. An empty set of addresses switches
+	/// to publishing the external addresses of this node itself.
+	///
+	/// This method is unsafe and must only be exposed to the operator of the node.
+	#[rpc(name = "authorityDiscovery_setSentryAddresses")]
+	fn set_sentry_addresses(&self, addresses: Vec<String>) -> FutureResult<()>;
+}
+
+/// Error type of this RPC api.
+pub enum Error {
+	/// The authority discovery module isn't running.
+	NotRunning,
+}
+
+impl From<Error> for i64 {
+	fn from(e: Error) -> i64 {
+		match e {
+			Error::NotRunning => 1,
+		}
+	}
+}
+
+/// A struct that implements the [`AuthorityDiscoveryApi`].
+pub struct AuthorityDiscoveryRpcHandler {
+	service: SharedService,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl AuthorityDiscoveryRpcHandler {
+	/// Create new `AuthorityDiscoveryRpcHandler` controlling the authority discovery sharing the
+	/// given `SharedService`.
+	pub fn new(service: SharedService, deny_unsafe: DenyUnsafe) -> Self {
+		AuthorityDiscoveryRpcHandler { service, deny_unsafe }
+	}
+}
+
+fn not_running() -> RpcError {
+	RpcError {
+		code: ErrorCode::ServerError(Error::NotRunning.into()),
+		message: "Authority discovery is not running.".into(),
+		data: None,
+	}
+}
+
+impl AuthorityDiscoveryApi for AuthorityDiscoveryRpcHandler {
+	fn set_sentry_addresses(&self, addresses: Vec<String>) -> FutureResult<()> {
+		if let Err(e) = self.deny_unsafe.check_if_safe() {
+			return Box::new(jsonrpc_core::futures::future::err(e.into()));
+		}
+
+		let addresses = addresses.iter()
+			.map(|address| address.parse::<Multiaddr>().map_err(|e| RpcError::invalid_params(
+				format!("Invalid address '{}': {}", address, e),
+			)))
+			.collect::<Result<Vec<_>, _>>();
+		let service = self.service.get();
+
+		let future = async move {
+			let addresses = addresses?;
+			let mut service = service.ok_or_else(not_running)?;
+			service.set_sentry_addresses(addresses).await.map_err(|_| not_running())
+		}.boxed();
+
+		Box::new(future.compat())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jsonrpc_core::futures::Future;
+
+	#[test]
+	fn set_sentry_addresses_fails_if_not_running() {
+		let handler = AuthorityDiscoveryRpcHandler::new(SharedService::empty(), DenyUnsafe::No);
+
+		let error = handler.set_sentry_addresses(vec!["/ip4/127.0.0.1/tcp/30333".into()])
+			.wait()
+			.unwrap_err();
+		assert_eq!(error.code, ErrorCode::ServerError(Error::NotRunning.into()));
+	}
+
+	#[test]
+	fn set_sentry_addresses_rejects_invalid_addresses() {
+		let handler = AuthorityDiscoveryRpcHandler::new(SharedService::empty(), DenyUnsafe::No);
+
+		let error = handler.set_sentry_addresses(vec!["not an address".into()])
+			.wait()
+			.unwrap_err();
+		assert_eq!(error.code, ErrorCode::InvalidParams);
+	}
+
+	#[test]
+	fn set_sentry_addresses_is_denied_to_external_callers() {
+		let handler = AuthorityDiscoveryRpcHandler::new(SharedService::empty(), DenyUnsafe::Yes);
+
+		let error = handler.set_sentry_addresses(vec!["/ip4/127.0.0.1/tcp/30333".into()])
+			.wait()
+			.unwrap_err();
+		assert_eq!(error.code, ErrorCode::MethodNotFound);
+	}
+}
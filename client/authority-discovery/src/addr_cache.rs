@@ -0,0 +1,116 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Size capped cache of the addresses of other authorities.
+
+use std::collections::HashSet;
+use std::convert::TryInto;
+
+use codec::{Decode, Encode};
+use libp2p::Multiaddr;
+use lru::LruCache;
+use sp_authority_discovery::AuthorityId;
+
+/// Cache of the addresses of other authorities.
+///
+/// Bounded both in the number of authorities and in the number of addresses per authority. Once
+/// full, the entry of the authority updated least recently is evicted.
+pub(crate) struct AddrCache {
+	cache: LruCache<AuthorityId, Vec<Multiaddr>>,
+	max_addresses_per_authority: usize,
+}
+
+impl AddrCache {
+	/// Create a new cache holding at most `max_authorities` entries of at most
+	/// `max_addresses_per_authority` addresses each.
+	pub fn new(max_authorities: usize, max_addresses_per_authority: usize) -> Self {
+		AddrCache {
+			cache: LruCache::new(max_authorities),
+			max_addresses_per_authority,
+		}
+	}
+
+	/// Insert or replace the addresses of the given authority.
+	///
+	/// Duplicate addresses are removed, retaining the order of first occurrence. Addresses above
+	/// the per authority limit are dropped.
+	pub fn insert(&mut self, authority_id: AuthorityId, addresses: Vec<Multiaddr>) {
+		let mut seen = HashSet::new();
+		let addresses = addresses.into_iter()
+			.filter(|a| seen.insert(a.clone()))
+			.take(self.max_addresses_per_authority)
+			.collect();
+
+		self.cache.put(authority_id, addresses);
+	}
+
+	/// Returns the addresses of the given authority, if any, without affecting eviction order.
+	pub fn get(&self, authority_id: &AuthorityId) -> Option<&Vec<Multiaddr>> {
+		self.cache.peek(authority_id)
+	}
+
+	/// Number of authorities in the cache.
+	pub fn len(&self) -> usize {
+		self.cache.len()
+	}
+
+	/// All addresses of all authorities in the cache, deduplicated.
+	pub fn addresses(&self) -> HashSet<Multiaddr> {
+		self.cache.iter()
+			.map(|(_authority_id, addresses)| addresses.iter().cloned())
+			.flatten()
+			.collect()
+	}
+
+	/// SCALE encode the cache for persistence, ordered from the least to the most recently updated
+	/// authority.
+	pub fn encode(&self) -> Vec<u8> {
+		let mut entries = self.cache.iter()
+			.map(|(id, addresses)| (id.clone(), addresses.iter().map(|a| a.to_vec()).collect()))
+			.collect::<Vec<(AuthorityId, Vec<Vec<u8>>)>>();
+		// Iteration starts with the most recently updated authority.
+		entries.reverse();
+
+		entries.encode()
+	}
+
+	/// Insert the entries of a cache encoded via [`AddrCache::encode`], retaining their order of
+	/// recency. Addresses which fail to parse are dropped.
+	pub fn insert_encoded(&mut self, mut encoded: &[u8]) -> Result<(), codec::Error> {
+		let entries = Vec::<(AuthorityId, Vec<Vec<u8>>)>::decode(&mut encoded)?;
+
+		for (id, addresses) in entries {
+			let addresses = addresses.into_iter()
+				.filter_map(|a| a.try_into().ok())
+				.collect();
+			self.insert(id, addresses);
+		}
+
+		Ok(())
+	}
+
+	/// Remove all authorities not in the given set.
+	pub fn retain_ids(&mut self, authority_ids: &[AuthorityId]) {
+		let to_remove = self.cache.iter()
+			.filter(|(id, _addresses)| !authority_ids.contains(id))
+			.map(|(id, _addresses)| id.clone())
+			.collect::<Vec<_>>();
+
+		for id in to_remove {
+			self.cache.pop(&id);
+		}
+	}
+}
@@ -38,8 +38,34 @@ pub enum Error {
 	EncodingProto(prost::EncodeError),
 	/// Failed to decode a protobuf payload.
 	DecodingProto(prost::DecodeError),
-	/// Failed to encode or decode scale payload
-	EncodingDecodingScale(codec::Error),
+	/// Failed to sign a dht payload via the key store.
+	#[from(ignore)]
+	Signing(String),
 	/// Failed to parse a libp2p multi address.
 	ParsingMultiaddress(libp2p::core::multiaddr::Error),
+	/// Failed to sign a dht payload with the local network identity.
+	SigningWithLocalIdentity(libp2p::identity::error::SigningError),
+	/// Failed to parse the libp2p public key of a dht payload peer signature.
+	ParsingLibp2pIdentity(libp2p::identity::error::DecodingError),
+	/// Failed to verify a dht payload with the given peer signature.
+	VerifyingPeerSignature,
+	/// The `PeerId` of the peer signature of a dht payload does not match the `/p2p/` suffix of
+	/// all of its addresses.
+	PeerIdMismatch,
+	/// A dht payload lacks a peer signature while unsigned payloads are not accepted.
+	MissingPeerSignature,
+	/// Failed to write the address cache to the auxiliary storage of the client.
+	#[from(ignore)]
+	PersistingAddressCache(sp_blockchain::Error),
+}
+
+impl Error {
+	/// Whether the error is likely to resolve itself shortly, e.g. a runtime api call failing
+	/// during major sync, thus the failed operation is worth retrying before its next interval.
+	pub fn is_transient(&self) -> bool {
+		match self {
+			Error::CallingRuntime(_) => true,
+			_ => false,
+		}
+	}
 }
@@ -29,7 +29,8 @@
 //!
 //!    2. Adds its network peer id to the addresses.
 //!
-//!    3. Signs the above.
+//!    3. Signs the above with its authority key and, unless publishing on behalf of sentry nodes,
+//!       with its network key.
 //!
 //!    4. Puts the signature and the addresses on the libp2p Kademlia DHT.
 //!
@@ -40,41 +41,56 @@
 //!
 //!    2. Starts DHT queries for the ids of the authorities.
 //!
-//!    3. Validates the signatures of the retrieved key value pairs.
+//!    3. Validates the signatures of the retrieved key value pairs. Records are only accepted if
+//!       they are signed with a network key and all addresses end with the `PeerId` of that key,
+//!       unless the configuration accepts unsigned records, e.g. published on behalf of sentry
+//!       nodes.
 //!
 //!    4. Adds the retrieved external addresses as priority nodes to the peerset.
-use std::collections::{HashMap, HashSet};
+//!
+//! Other subsystems can look up the addresses of a given authority through the clonable
+//! [`Service`] handle returned by [`AuthorityDiscovery::service`].
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryInto;
-use std::iter::FromIterator;
 use std::marker::PhantomData;
 use std::pin::Pin;
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
+use futures::channel::mpsc;
 use futures::task::{Context, Poll};
 use futures::{Future, FutureExt, Stream, StreamExt};
 use futures_timer::Delay;
-
-use sp_authority_discovery::{
-	AuthorityDiscoveryApi, AuthorityId, AuthoritySignature, AuthorityPair
-};
-use sc_client_api::blockchain::HeaderBackend;
-use codec::{Decode, Encode};
+use sp_authority_discovery::{AuthorityDiscoveryApi, AuthorityId, CRYPTO_ID};
+use sc_client_api::{blockchain::HeaderBackend, AuxStore, BlockchainEvents, ImportNotifications};
 use error::{Error, Result};
 use log::{debug, error, log_enabled, warn};
-use libp2p::Multiaddr;
+use libp2p::core::multiaddr::Protocol;
+use libp2p::identity::{error::SigningError, PublicKey};
+use libp2p::{Multiaddr, PeerId};
 use sc_network::specialization::NetworkSpecialization;
-use sc_network::{DhtEvent, ExHashT, NetworkStateInfo};
-use sp_core::crypto::{key_types, Pair};
+use sc_network::{config::AUTHORITIES_PRIORITY_GROUP, DhtEvent, ExHashT, NetworkStateInfo};
+use sp_core::crypto::{key_types, CryptoTypeId, CryptoTypePublicPair, Pair};
+use sp_core::{ecdsa, ed25519, sr25519};
 use sp_core::traits::BareCryptoStorePtr;
 use prost::Message;
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::{Block as BlockT, ProvideRuntimeApi};
 
+use addr_cache::AddrCache;
+use metrics::Metrics;
+use prometheus_endpoint::Registry;
+use service::ServiceToWorkerMsg;
+
+pub use service::{Service, SharedService};
+
 #[cfg(test)]
 mod tests;
 
+mod addr_cache;
 mod error;
+mod metrics;
+mod service;
 /// Dht payload schemas generated from Protobuf definitions via Prost crate in build.rs.
 mod schema {
 	include!(concat!(env!("OUT_DIR"), "/authority_discovery.rs"));
@@ -82,25 +98,146 @@ mod schema {
 
 type Interval = Box<dyn Stream<Item = ()> + Unpin + Send + Sync>;
 
+/// A record of the own addresses being signed, resolving to the Dht key and value to put.
+type PendingPublication =
+	Pin<Box<dyn Future<Output = Result<(libp2p::kad::record::Key, Vec<u8>)>> + Send>>;
+
 /// Upper bound estimation on how long one should wait before accessing the Kademlia DHT.
 const LIBP2P_KADEMLIA_BOOTSTRAP_TIME: Duration = Duration::from_secs(30);
 
-/// Name of the Substrate peerset priority group for authorities discovered through the authority
-/// discovery module.
-const AUTHORITIES_PRIORITY_GROUP_NAME: &'static str = "authorities";
+/// Interval on which to query for addresses of other authorities right after bootstrap or after
+/// the authority set changed. Doubled after each query until [`MAX_QUERY_INTERVAL`] is reached.
+const INITIAL_QUERY_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Interval on which to query for addresses of other authorities in the steady state.
+const MAX_QUERY_INTERVAL: Duration = Duration::from_secs(10 * 60);
+
+/// Format version of the records published on the Dht, see `schema::SignedAuthorityAddresses`.
+const RECORD_VERSION: u32 = 2;
+
+/// The default duration after the first startup during which published records remain readable by
+/// nodes only supporting version 1 records.
+const LEGACY_RECORD_GRACE_PERIOD: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Window over which the lookups of a single query round are spread, in [`LOOKUP_BATCHES`] batches.
+/// Shorter than [`INITIAL_QUERY_INTERVAL`], thus a round completes before the next one starts.
+const LOOKUP_SPREAD_WINDOW: Duration = Duration::from_secs(30);
+
+/// Number of batches the lookups of a single query round are split into.
+const LOOKUP_BATCHES: u32 = 10;
 
-/// The maximum number of sentry node public addresses that we accept per authority.
+/// Minimum number of lookups per batch. Small authority sets are looked up at once.
+const MIN_LOOKUP_BATCH_SIZE: usize = 10;
+
+/// Duration after which a lookup without any answer from the Dht is considered failed, allowing
+/// the same authority to be looked up again. Matches the Kademlia query timeout.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Key of the address cache in the auxiliary storage of the client.
+const ADDR_CACHE_AUX_KEY: &[u8] = b"authority_discovery_addr_cache";
+
+/// Key of the start of the legacy record grace period, in seconds since the unix epoch, in the
+/// auxiliary storage of the client.
+const LEGACY_RECORDS_SINCE_AUX_KEY: &[u8] = b"authority_discovery_legacy_records_since";
+
+/// Interval on which the address cache is persisted, if enabled and changed.
+const ADDR_CACHE_PERSIST_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+/// Delay after which publishing or querying is retried in case it failed due to a transient error,
+/// e.g. the runtime of the best block not yet providing the [`AuthorityDiscoveryApi`] during major
+/// sync.
+const TRANSIENT_ERROR_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// The default maximum number of sentry node public addresses that we accept per authority.
 ///
 /// Everything above this threshold should be dropped to prevent a single authority from filling up
 /// our peer set priority group.
 const MAX_NUM_SENTRY_ADDRESSES_PER_AUTHORITY: usize = 5;
 
+/// The default maximum number of authorities whose addresses are cached.
+///
+/// Estimates the authority set size to be somewhere below 10 000, see the Dht event channel size
+/// in the node service.
+const MAX_NUM_CACHED_AUTHORITIES: usize = 10_000;
+
+/// Buffer size of the channel between a [`Service`] and the [`AuthorityDiscovery`].
+const SERVICE_TO_WORKER_CHANNEL_SIZE: usize = 1_000;
+
+/// Configuration of an [`AuthorityDiscovery`].
+#[derive(Debug, Clone)]
+pub struct AuthorityDiscoveryConfig {
+	/// Maximum number of authorities whose addresses are cached. Once reached, the addresses of
+	/// the authority updated least recently are dropped.
+	pub max_cached_authorities: usize,
+	/// Maximum number of addresses accepted per authority. Everything above is dropped.
+	pub max_addresses_per_authority: usize,
+	/// Publish own addresses which are not globally reachable, i.e. private, loopback and
+	/// link-local addresses. Sentry node addresses are always published as configured.
+	pub publish_non_global_ips: bool,
+	/// Duration after the first startup during which published records additionally carry a
+	/// version 1 signature, keeping them readable by nodes not yet supporting the current record
+	/// version. The start of the grace period is persisted, thus restarts don't extend it.
+	pub legacy_record_grace_period: Duration,
+	/// Persist the address cache in the auxiliary storage of the client, restoring it on startup.
+	/// Allows connecting to other authorities right away instead of after the first query round.
+	pub persist_address_cache: bool,
+	/// Accept records which are not signed with the network key of their publisher, as published
+	/// on behalf of sentry nodes. Their addresses are only authenticated by the authority
+	/// discovery key, thus anyone holding it can announce any addresses.
+	pub accept_unsigned_records: bool,
+}
+
+impl Default for AuthorityDiscoveryConfig {
+	fn default() -> Self {
+		AuthorityDiscoveryConfig {
+			max_cached_authorities: MAX_NUM_CACHED_AUTHORITIES,
+			max_addresses_per_authority: MAX_NUM_SENTRY_ADDRESSES_PER_AUTHORITY,
+			publish_non_global_ips: false,
+			legacy_record_grace_period: LEGACY_RECORD_GRACE_PERIOD,
+			persist_address_cache: false,
+			accept_unsigned_records: false,
+		}
+	}
+}
+
+/// Role an [`AuthorityDiscovery`] is run in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+	/// Publish own addresses and discover addresses of other authorities.
+	PublishAndDiscover,
+	/// Only discover addresses of other authorities, e.g. on a sentry node which does not hold any
+	/// authority discovery keys to publish with.
+	Discover,
+	/// Only publish own addresses, e.g. on a node that does not need to connect to other
+	/// authorities.
+	Publish,
+}
+
+impl Role {
+	/// Whether the role includes publishing own addresses.
+	pub fn publishes(&self) -> bool {
+		match self {
+			Role::PublishAndDiscover | Role::Publish => true,
+			Role::Discover => false,
+		}
+	}
+
+	/// Whether the role includes discovering addresses of other authorities.
+	pub fn discovers(&self) -> bool {
+		match self {
+			Role::PublishAndDiscover | Role::Discover => true,
+			Role::Publish => false,
+		}
+	}
+}
+
 /// An `AuthorityDiscovery` makes a given authority discoverable and discovers other authorities.
 pub struct AuthorityDiscovery<Client, Network, Block>
 where
 	Block: BlockT + 'static,
 	Network: NetworkProvider,
-	Client: ProvideRuntimeApi + Send + Sync + 'static + HeaderBackend<Block>,
+	Client: ProvideRuntimeApi + Send + Sync + 'static + HeaderBackend<Block> + BlockchainEvents<Block>
+		+ AuxStore,
 	<Client as ProvideRuntimeApi>::Api: AuthorityDiscoveryApi<Block>,
 {
 	client: Arc<Client>,
@@ -113,23 +250,66 @@ where
 	//   - Some(vec![]): Addresses were specified, but none could be parsed as proper
 	//     Multiaddresses.
 	//   - Some(vec![a, b, c, ...]): Valid addresses were specified.
+	//
+	// Updated at runtime through [`Service::set_sentry_addresses`].
 	sentry_nodes: Option<Vec<Multiaddr>>,
 	/// Channel we receive Dht events on.
 	dht_event_rx: Pin<Box<dyn Stream<Item = DhtEvent> + Send>>,
 
+	/// Sending side of the channel handed out to [`Service`]s via [`AuthorityDiscovery::service`].
+	to_worker: mpsc::Sender<ServiceToWorkerMsg>,
+	/// Channel we receive requests from [`Service`]s on.
+	from_service: mpsc::Receiver<ServiceToWorkerMsg>,
+
 	key_store: BareCryptoStorePtr,
 
-	/// Interval to be proactive, publishing own addresses.
-	publish_interval: Interval,
-	/// Interval on which to query for addresses of other authorities.
-	query_interval: Interval,
+	role: Role,
+
+	/// Interval to be proactive, publishing own addresses. `None` if the [`Role`] does not include
+	/// publishing.
+	publish_interval: Option<Interval>,
+	/// Interval on which to query for addresses of other authorities. `None` if the [`Role`] does
+	/// not include discovering.
+	query_interval: Option<Interval>,
+	/// Retry of a publish which failed due to a transient error, independent of `publish_interval`.
+	publish_retry: Option<Delay>,
+	/// Records of the own addresses being signed by the key store, with the Dht key to put them
+	/// under once signed.
+	pending_publications: Vec<PendingPublication>,
+	/// Retry of a query which failed due to a transient error, independent of `query_interval`.
+	query_retry: Option<Delay>,
+	/// Block import notifications used to detect authority set changes, triggering an immediate
+	/// query for the addresses of the new authorities. `None` if the [`Role`] does not include
+	/// discovering.
+	import_notifications: Option<ImportNotifications<Block>>,
+	/// Authority set the last queries were issued for.
+	queried_authorities: Vec<AuthorityId>,
+	/// Authorities of the current query round not yet looked up on the Dht.
+	pending_lookups: VecDeque<AuthorityId>,
+	/// Number of lookups started per batch in the current query round.
+	lookup_batch_size: usize,
+	/// Fires when the next batch of `pending_lookups` is due. `None` if there are none.
+	next_lookup_batch: Option<Delay>,
+	/// Lookups started on the Dht and not yet answered, by hashed authority id, with the time they
+	/// were started.
+	in_flight_lookups: HashMap<libp2p::kad::record::Key, Instant>,
+
+	config: AuthorityDiscoveryConfig,
+	/// Until when to publish records readable by nodes only supporting version 1 records.
+	legacy_records_until: Instant,
 
 	/// The network peerset interface for priority groups lets us only set an entire group, but we
 	/// retrieve the addresses of other authorities one by one from the network. To use the peerset
 	/// interface we need to cache the addresses and always overwrite the entire peerset priority
-	/// group. To ensure this map doesn't grow indefinitely `purge_old_authorities_from_cache`
-	/// function is called each time we add a new entry.
-	address_cache: HashMap<AuthorityId, Vec<Multiaddr>>,
+	/// group. Next to the cache being bounded, `purge_old_authorities_from_cache` function is
+	/// called each time we add a new entry to drop authorities no longer in the authority set.
+	address_cache: AddrCache,
+	/// Interval on which to persist the address cache. `None` if persistence is disabled.
+	persist_interval: Option<Interval>,
+	/// Whether the address cache changed since it was last persisted.
+	address_cache_dirty: bool,
+
+	metrics: Option<Metrics>,
 
 	phantom: PhantomData<Block>,
 }
@@ -138,37 +318,63 @@ impl<Client, Network, Block> AuthorityDiscovery<Client, Network, Block>
 where
 	Block: BlockT + Unpin + 'static,
 	Network: NetworkProvider,
-	Client: ProvideRuntimeApi + Send + Sync + 'static + HeaderBackend<Block>,
+	Client: ProvideRuntimeApi + Send + Sync + 'static + HeaderBackend<Block> + BlockchainEvents<Block>
+		+ AuxStore,
 	<Client as ProvideRuntimeApi>::Api: AuthorityDiscoveryApi<Block, Error = sp_blockchain::Error>,
 	Self: Future<Output = ()>,
 {
 	/// Return a new authority discovery.
 	///
 	/// Note: When specifying `sentry_nodes` this module will not advertise the public addresses of
-	/// the node itself but only the public addresses of its sentry nodes.
+	/// the node itself but only the public addresses of its sentry nodes. These aren't signed with
+	/// the network key of the node, thus other nodes only accept them with
+	/// [`AuthorityDiscoveryConfig::accept_unsigned_records`].
+	///
+	/// Depending on the given [`Role`] either publishing or discovering is skipped entirely.
+	///
+	/// Metrics are only collected if a Prometheus registry is given.
 	pub fn new(
 		client: Arc<Client>,
 		network: Arc<Network>,
 		sentry_nodes: Vec<String>,
 		key_store: BareCryptoStorePtr,
 		dht_event_rx: Pin<Box<dyn Stream<Item = DhtEvent> + Send>>,
+		config: AuthorityDiscoveryConfig,
+		role: Role,
+		prometheus_registry: Option<Registry>,
 	) -> Self {
 		// Kademlia's default time-to-live for Dht records is 36h, republishing records every 24h.
 		// Given that a node could restart at any point in time, one can not depend on the
 		// republishing process, thus publishing own external addresses should happen on an interval
 		// < 36h.
-		let publish_interval = interval_at(
-			Instant::now() + LIBP2P_KADEMLIA_BOOTSTRAP_TIME,
-			Duration::from_secs(12 * 60 * 60),
-		);
+		let publish_interval = if role.publishes() {
+			Some(interval_at(
+				Instant::now() + LIBP2P_KADEMLIA_BOOTSTRAP_TIME,
+				Duration::from_secs(12 * 60 * 60),
+			))
+		} else {
+			None
+		};
 
 		// External addresses of other authorities can change at any given point in time. The
 		// interval on which to query for external addresses of other authorities is a trade off
-		// between efficiency and performance.
-		let query_interval = interval_at(
-			Instant::now() + LIBP2P_KADEMLIA_BOOTSTRAP_TIME,
-			Duration::from_secs(10 * 60),
-		);
+		// between efficiency and performance. Query often right after bootstrap to connect to
+		// other authorities quickly, backing off to keep the steady state Dht load low.
+		let query_interval = if role.discovers() {
+			Some(exp_inc_interval_at(
+				Instant::now() + LIBP2P_KADEMLIA_BOOTSTRAP_TIME,
+				INITIAL_QUERY_INTERVAL,
+				MAX_QUERY_INTERVAL,
+			))
+		} else {
+			None
+		};
+
+		let import_notifications = if role.discovers() {
+			Some(client.import_notification_stream())
+		} else {
+			None
+		};
 
 		let sentry_nodes = if !sentry_nodes.is_empty() {
 			let addrs = sentry_nodes.into_iter().filter_map(|a| match a.parse() {
@@ -182,12 +388,12 @@ where
 				}
 			}).collect::<Vec<Multiaddr>>();
 
-			if addrs.len() > MAX_NUM_SENTRY_ADDRESSES_PER_AUTHORITY {
+			if addrs.len() > config.max_addresses_per_authority {
 				warn!(
 					target: "sub-authority-discovery",
-					"More than MAX_NUM_SENTRY_ADDRESSES_PER_AUTHORITY ({:?}) were specified. Other \
-					nodes will likely ignore the remainder.",
-					MAX_NUM_SENTRY_ADDRESSES_PER_AUTHORITY,
+					"More than max_addresses_per_authority ({:?}) were specified. Other nodes will \
+					likely ignore the remainder.",
+					config.max_addresses_per_authority,
 				);
 			}
 
@@ -196,22 +402,84 @@ where
 			None
 		};
 
+		let mut address_cache = AddrCache::new(
+			config.max_cached_authorities,
+			config.max_addresses_per_authority,
+		);
 
-		let address_cache = HashMap::new();
+		let persist_interval = if config.persist_address_cache {
+			restore_address_cache(&*client, &mut address_cache);
+
+			// Addresses of authorities no longer in the authority set are purged with the first
+			// Dht value found.
+			if address_cache.len() > 0 && role.discovers() {
+				if let Err(e) = network.set_priority_group(
+					AUTHORITIES_PRIORITY_GROUP.to_string(),
+					address_cache.addresses(),
+				) {
+					warn!(
+						target: "sub-authority-discovery",
+						"Failed to apply restored priority group to peerset: {:?}", e,
+					);
+				}
+			}
+
+			Some(interval_at(
+				Instant::now() + ADDR_CACHE_PERSIST_INTERVAL,
+				ADDR_CACHE_PERSIST_INTERVAL,
+			))
+		} else {
+			None
+		};
+
+		let (to_worker, from_service) = mpsc::channel(SERVICE_TO_WORKER_CHANNEL_SIZE);
+
+		let metrics = match prometheus_registry {
+			Some(registry) => match Metrics::register(&registry) {
+				Ok(metrics) => Some(metrics),
+				Err(e) => {
+					error!(target: "sub-authority-discovery", "Failed to register metrics: {:?}", e);
+					None
+				},
+			},
+			None => None,
+		};
 
 		AuthorityDiscovery {
 			client,
 			network,
 			sentry_nodes,
 			dht_event_rx,
+			to_worker,
+			from_service,
 			key_store,
+			role,
 			publish_interval,
 			query_interval,
+			publish_retry: None,
+			pending_publications: Vec::new(),
+			query_retry: None,
+			import_notifications,
+			queried_authorities: Vec::new(),
+			pending_lookups: VecDeque::new(),
+			lookup_batch_size: MIN_LOOKUP_BATCH_SIZE,
+			next_lookup_batch: None,
+			in_flight_lookups: HashMap::new(),
+			legacy_records_until: legacy_records_until(&*client, config.legacy_record_grace_period),
+			config,
 			address_cache,
+			persist_interval,
+			address_cache_dirty: false,
+			metrics,
 			phantom: PhantomData,
 		}
 	}
 
+	/// Return a [`Service`] handle to query this authority discovery from other subsystems.
+	pub fn service(&self) -> Service {
+		Service::new(self.to_worker.clone())
+	}
+
 	/// Publish either our own or if specified the public addresses of our sentry nodes.
 	fn publish_ext_addresses(&mut self) -> Result<()> {
 		let addresses = match &self.sentry_nodes {
@@ -220,38 +488,113 @@ where
 				.collect(),
 			None => self.network.external_addresses()
 				.into_iter()
-				.map(|a| a.with(libp2p::core::multiaddr::Protocol::P2p(
+				.filter(|a| self.config.publish_non_global_ips || is_global_address(a))
+				.map(|a| a.with(Protocol::P2p(
 					self.network.local_peer_id().into(),
 				)))
 				.map(|a| a.to_vec())
 				.collect(),
 		};
 
+		if let Some(metrics) = &self.metrics {
+			metrics.publish.inc();
+		}
+
 		let mut serialized_addresses = vec![];
 		schema::AuthorityAddresses { addresses }
 			.encode(&mut serialized_addresses)
 			.map_err(Error::EncodingProto)?;
 
-		for key in self.get_priv_keys_within_authority_set()?.into_iter() {
-			let signature = key.sign(&serialized_addresses);
+		// The addresses of sentry nodes don't belong to the local network identity, thus there is
+		// nothing to prove by signing them with the local network key.
+		let peer_signature = match self.sentry_nodes {
+			Some(_) => None,
+			None => {
+				let (public_key, signature) = self.network
+					.sign_with_local_identity(&serialized_addresses)
+					.map_err(Error::SigningWithLocalIdentity)?;
+
+				Some(schema::PeerSignature {
+					signature,
+					public_key: public_key.into_protobuf_encoding(),
+				})
+			},
+		};
 
-			let mut signed_addresses = vec![];
-			schema::SignedAuthorityAddresses {
-				addresses: serialized_addresses.clone(),
-				signature: signature.encode(),
-			}
-			.encode(&mut signed_addresses)
-				.map_err(Error::EncodingProto)?;
+		let publish_legacy_signature = Instant::now() < self.legacy_records_until;
+		let versioned_payload = versioned_signing_payload(RECORD_VERSION, &serialized_addresses);
+
+		// A new publication supersedes the ones still being signed.
+		self.pending_publications.clear();
+
+		for key in self.get_own_public_keys_within_authority_set()?.into_iter() {
+			let dht_key = hash_authority_id(&key.1)?;
+			let (versioned_signature, signature) = {
+				let key_store = self.key_store.read();
+				let sign = |msg: &[u8]| key_store.sign_with(key_types::AUTHORITY_DISCOVERY, &key, msg);
+				// Nodes only supporting version 1 records ignore the unknown fields, verifying the
+				// plain signature over the addresses instead.
+				(
+					sign(&versioned_payload),
+					if publish_legacy_signature { Some(sign(&serialized_addresses)) } else { None },
+				)
+			};
+			let addresses = serialized_addresses.clone();
+			let peer_signature = peer_signature.clone();
+
+			self.pending_publications.push(Box::pin(async move {
+				let versioned_signature = versioned_signature.await.map_err(Error::Signing)?;
+				let signature = match signature {
+					Some(signature) => signature.await.map_err(Error::Signing)?,
+					None => vec![],
+				};
+
+				let mut signed_addresses = vec![];
+				schema::SignedAuthorityAddresses {
+					addresses,
+					signature,
+					peer_signature,
+					version: RECORD_VERSION,
+					versioned_signature,
+				}
+				.encode(&mut signed_addresses)
+					.map_err(Error::EncodingProto)?;
 
-			self.network.put_value(
-				hash_authority_id(key.public().as_ref())?,
-				signed_addresses,
-			);
+				Ok((dht_key, signed_addresses))
+			}));
 		}
 
 		Ok(())
 	}
 
+	/// Put the records of the own addresses signed by the key store onto the Dht.
+	fn put_signed_records(&mut self, cx: &mut Context) {
+		let mut pending = Vec::with_capacity(self.pending_publications.len());
+
+		for mut publication in self.pending_publications.drain(..) {
+			match publication.poll_unpin(cx) {
+				Poll::Ready(Ok((dht_key, signed_addresses))) => {
+					if let Some(metrics) = &self.metrics {
+						metrics.dht_put.inc();
+					}
+
+					self.network.put_value(dht_key, signed_addresses);
+				},
+				Poll::Ready(Err(e)) => error!(
+					target: "sub-authority-discovery",
+					"Failed to sign external addresses: {:?}", e,
+				),
+				Poll::Pending => pending.push(publication),
+			}
+		}
+
+		self.pending_publications = pending;
+	}
+
+	/// Start a new query round for the addresses of all authorities.
+	///
+	/// Instead of bursting all lookups at once, they are started in batches spread over
+	/// [`LOOKUP_SPREAD_WINDOW`], the first batch right away.
 	fn request_addresses_of_others(&mut self) -> Result<()> {
 		let id = BlockId::hash(self.client.info().best_hash);
 
@@ -261,18 +604,168 @@ where
 			.authorities(&id)
 			.map_err(Error::CallingRuntime)?;
 
-		for authority_id in authorities.iter() {
-			self.network
-				.get_value(&hash_authority_id(authority_id.as_ref())?);
+		// A new round supersedes the lookups left over from the previous one.
+		self.pending_lookups = authorities.iter().cloned().collect();
+		self.lookup_batch_size = std::cmp::max(
+			(authorities.len() + LOOKUP_BATCHES as usize - 1) / LOOKUP_BATCHES as usize,
+			MIN_LOOKUP_BATCH_SIZE,
+		);
+		self.queried_authorities = authorities;
+
+		self.start_next_lookup_batch()
+	}
+
+	/// Start the next batch of pending lookups, skipping authorities with a lookup still in flight,
+	/// and schedule the batch after.
+	fn start_next_lookup_batch(&mut self) -> Result<()> {
+		let now = Instant::now();
+		self.in_flight_lookups.retain(|_, started| now.duration_since(*started) < LOOKUP_TIMEOUT);
+
+		let batch_size = std::cmp::min(self.lookup_batch_size, self.pending_lookups.len());
+		for authority_id in self.pending_lookups.drain(..batch_size).collect::<Vec<_>>() {
+			let hash = hash_authority_id(authority_id.as_ref())?;
+
+			if self.in_flight_lookups.contains_key(&hash) {
+				debug!(
+					target: "sub-authority-discovery",
+					"Skipping lookup of authority {:?}, previous lookup still in flight.",
+					authority_id,
+				);
+				continue;
+			}
+
+			if let Some(metrics) = &self.metrics {
+				metrics.dht_get.inc();
+			}
+
+			self.network.get_value(&hash);
+			self.in_flight_lookups.insert(hash, now);
 		}
 
+		self.next_lookup_batch = if self.pending_lookups.is_empty() {
+			None
+		} else {
+			Some(Delay::new(LOOKUP_SPREAD_WINDOW / LOOKUP_BATCHES))
+		};
+
 		Ok(())
 	}
 
-	fn handle_dht_events(&mut self, cx: &mut Context) -> Result<()> {
+	/// Query for the addresses of other authorities right away in case the authority set changed
+	/// since the last queries were issued, restarting the query interval back off.
+	fn check_authority_set_change(&mut self) -> Result<()> {
+		let id = BlockId::hash(self.client.info().best_hash);
+
+		let authorities = self
+			.client
+			.runtime_api()
+			.authorities(&id)
+			.map_err(Error::CallingRuntime)?;
+
+		// Nothing to compare against before the initial queries, which are issued by the query
+		// interval once the Kademlia Dht is bootstrapped.
+		if self.queried_authorities.is_empty() || authorities == self.queried_authorities {
+			return Ok(());
+		}
+
+		debug!(
+			target: "sub-authority-discovery",
+			"Authority set changed, querying for addresses of new authorities.",
+		);
+
+		self.query_interval = Some(exp_inc_interval_at(
+			Instant::now() + INITIAL_QUERY_INTERVAL,
+			INITIAL_QUERY_INTERVAL * 2,
+			MAX_QUERY_INTERVAL,
+		));
+
+		self.request_addresses_of_others()
+	}
+
+	fn handle_import_notifications(&mut self, cx: &mut Context) -> Result<()> {
+		let mut new_best = false;
+
+		if let Some(import_notifications) = self.import_notifications.as_mut() {
+			while let Poll::Ready(Some(notification)) = import_notifications.poll_next_unpin(cx) {
+				new_best |= notification.is_new_best;
+			}
+		}
+
+		// Only look at the authority set once per batch of imported blocks.
+		if new_best {
+			self.check_authority_set_change()?;
+		}
+
+		Ok(())
+	}
+
+	fn handle_service_messages(&mut self, cx: &mut Context) {
+		while let Poll::Ready(Some(msg)) = self.from_service.poll_next_unpin(cx) {
+			match msg {
+				ServiceToWorkerMsg::GetAddressesByAuthorityId(authority, sender) => {
+					// The requester might have dropped the receiving side in the meantime.
+					let _ = sender.send(self.address_cache.get(&authority).cloned());
+				}
+				ServiceToWorkerMsg::SetSentryAddresses(addresses) => {
+					self.set_sentry_addresses(addresses);
+				}
+			}
+		}
+	}
+
+	/// Replace the published sentry node addresses, republishing right away.
+	fn set_sentry_addresses(&mut self, addresses: Vec<Multiaddr>) {
+		debug!(
+			target: "sub-authority-discovery",
+			"Updating sentry node addresses to {:?}.", addresses,
+		);
+
+		self.sentry_nodes = if addresses.is_empty() {
+			None
+		} else {
+			Some(addresses)
+		};
+
+		if !self.role.publishes() {
+			return;
+		}
+
+		if let Err(e) = self.publish_ext_addresses() {
+			error!(
+				target: "sub-authority-discovery",
+				"Failed to publish updated sentry node addresses: {:?}", e,
+			);
+
+			if e.is_transient() {
+				self.publish_retry = Some(Delay::new(TRANSIENT_ERROR_RETRY_DELAY));
+			}
+		}
+	}
+
+	/// Handle all pending Dht events. A failure to handle one event is logged and does not affect
+	/// the events following it.
+	fn handle_dht_events(&mut self, cx: &mut Context) {
 		while let Poll::Ready(Some(event)) = self.dht_event_rx.poll_next_unpin(cx) {
+			if let Some(metrics) = &self.metrics {
+				let name = match event {
+					DhtEvent::ValueFound(_) => "value_found",
+					DhtEvent::ValueNotFound(_) => "value_not_found",
+					DhtEvent::ValuePut(_) => "value_put",
+					DhtEvent::ValuePutFailed(_) => "value_put_failed",
+					DhtEvent::ProvidersFound(..) => "providers_found",
+					DhtEvent::ProvidersNotFound(_) => "providers_not_found",
+					DhtEvent::StartedProviding(_) => "started_providing",
+					DhtEvent::StartProvidingFailed(_) => "start_providing_failed",
+				};
+				metrics.dht_event_received.with_label_values(&[name]).inc();
+			}
+
 			match event {
 				DhtEvent::ValueFound(v) => {
+					for (hash, _value) in v.iter() {
+						self.in_flight_lookups.remove(hash);
+					}
+
 					if log_enabled!(log::Level::Debug) {
 						let hashes = v.iter().map(|(hash, _value)| hash.clone());
 						debug!(
@@ -281,12 +774,30 @@ where
 						);
 					}
 
-					self.handle_dht_value_found_event(v)?;
+					if !self.role.discovers() {
+						debug!(
+							target: "sub-authority-discovery",
+							"Ignoring Dht value found event, not discovering in role {:?}.",
+							self.role,
+						);
+						continue;
+					}
+
+					if let Err(e) = self.handle_dht_value_found_event(v) {
+						error!(
+							target: "sub-authority-discovery",
+							"Failed to handle Dht value found event: {:?}", e,
+						);
+					}
 				}
-				DhtEvent::ValueNotFound(hash) => debug!(
-					target: "sub-authority-discovery",
-					"Value for hash '{:?}' not found on Dht.", hash
-				),
+				DhtEvent::ValueNotFound(hash) => {
+					debug!(
+						target: "sub-authority-discovery",
+						"Value for hash '{:?}' not found on Dht.", hash
+					);
+
+					self.in_flight_lookups.remove(&hash);
+				},
 				DhtEvent::ValuePut(hash) => debug!(
 					target: "sub-authority-discovery",
 					"Successfully put hash '{:?}' on Dht.", hash),
@@ -294,10 +805,11 @@ where
 					target: "sub-authority-discovery",
 					"Failed to put hash '{:?}' on Dht.", hash
 				),
+				// Provider records aren't used by the authority discovery.
+				DhtEvent::ProvidersFound(..) | DhtEvent::ProvidersNotFound(_) |
+				DhtEvent::StartedProviding(_) | DhtEvent::StartProvidingFailed(_) => {},
 			}
 		}
-
-		Ok(())
 	}
 
 	fn handle_dht_value_found_event(
@@ -325,18 +837,25 @@ where
 				.get(key)
 				.ok_or(Error::MatchingHashedAuthorityIdWithAuthorityId)?;
 
-			let schema::SignedAuthorityAddresses {
-				signature,
-				addresses,
-			} = schema::SignedAuthorityAddresses::decode(value).map_err(Error::DecodingProto)?;
-			let signature = AuthoritySignature::decode(&mut &signature[..])
-				.map_err(Error::EncodingDecodingScale)?;
-
-			if !AuthorityPair::verify(&signature, &addresses, authority_id) {
-				return Err(Error::VerifyingDhtPayload);
+			let record = schema::SignedAuthorityAddresses::decode(value)
+				.map_err(Error::DecodingProto)?;
+
+			if let Err(e) = verify_record_signature(&record, authority_id) {
+				if let (Error::VerifyingDhtPayload, Some(metrics)) = (&e, &self.metrics) {
+					metrics.signature_verification_failure.inc();
+				}
+
+				return Err(e);
 			}
 
-			let mut addresses: Vec<libp2p::Multiaddr> = schema::AuthorityAddresses::decode(addresses)
+			let schema::SignedAuthorityAddresses {
+				addresses: serialized_addresses,
+				peer_signature,
+				..
+			} = record;
+
+			let addresses: Vec<libp2p::Multiaddr> =
+				schema::AuthorityAddresses::decode(&serialized_addresses)
 				.map(|a| a.addresses)
 				.map_err(Error::DecodingProto)?
 				.into_iter()
@@ -344,72 +863,90 @@ where
 				.collect::<std::result::Result<_, _>>()
 				.map_err(Error::ParsingMultiaddress)?;
 
-			if addresses.len() > MAX_NUM_SENTRY_ADDRESSES_PER_AUTHORITY {
+			let peer_signature_check = match peer_signature {
+				Some(peer_signature) =>
+					verify_peer_signature(&peer_signature, &serialized_addresses, &addresses),
+				// Records published on behalf of sentry nodes are not signed with a network
+				// identity.
+				None if self.config.accept_unsigned_records => Ok(()),
+				None => Err(Error::MissingPeerSignature),
+			};
+			if let Err(e) = peer_signature_check {
+				if let Some(metrics) = &self.metrics {
+					metrics.signature_verification_failure.inc();
+				}
+
+				return Err(e);
+			}
+
+			if addresses.len() > self.config.max_addresses_per_authority {
 				warn!(
 					target: "sub-authority-discovery",
-					"Got more than MAX_NUM_SENTRY_ADDRESSES_PER_AUTHORITY ({:?}) for Authority \
-					'{:?}' from DHT, dropping the remainder.",
-					MAX_NUM_SENTRY_ADDRESSES_PER_AUTHORITY,	authority_id,
+					"Got more than max_addresses_per_authority ({:?}) for Authority '{:?}' from \
+					DHT, dropping the remainder.",
+					self.config.max_addresses_per_authority, authority_id,
 				);
-				addresses = addresses.into_iter()
-					.take(MAX_NUM_SENTRY_ADDRESSES_PER_AUTHORITY)
-					.collect();
 			}
 
+			// The cache takes care of deduplicating and capping the addresses.
 			self.address_cache.insert(authority_id.clone(), addresses);
+			self.address_cache_dirty = true;
 		}
 
 		// Let's update the peerset priority group with all the addresses we have in our cache.
 
-		let addresses = HashSet::from_iter(
-			self.address_cache
-				.iter()
-				.map(|(_peer_id, addresses)| addresses.clone())
-				.flatten(),
-		);
+		let addresses = self.address_cache.addresses();
+
+		if let Some(metrics) = &self.metrics {
+			metrics.known_authorities_count.set(self.address_cache.len() as i64);
+			metrics.priority_group_size.set(addresses.len() as i64);
+		}
 
 		debug!(
 			target: "sub-authority-discovery",
 			"Applying priority group {:#?} to peerset.", addresses,
 		);
 		self.network
-			.set_priority_group(AUTHORITIES_PRIORITY_GROUP_NAME.to_string(), addresses)
+			.set_priority_group(AUTHORITIES_PRIORITY_GROUP.to_string(), addresses)
 			.map_err(Error::SettingPeersetPriorityGroup)?;
 
 		Ok(())
 	}
 
 	fn purge_old_authorities_from_cache(&mut self, current_authorities: &Vec<AuthorityId>) {
-		self.address_cache
-			.retain(|peer_id, _addresses| current_authorities.contains(peer_id))
+		self.address_cache.retain_ids(current_authorities);
+		self.address_cache_dirty = true;
 	}
 
-	/// Retrieve all local authority discovery private keys that are within the current authority
-	/// set.
-	fn get_priv_keys_within_authority_set(&mut self) -> Result<Vec<AuthorityPair>> {
-		let keys = self.get_own_public_keys_within_authority_set()?
-			.into_iter()
-			.map(std::convert::Into::into)
-			.filter_map(|pub_key| {
-				self.key_store.read().sr25519_key_pair(key_types::AUTHORITY_DISCOVERY, &pub_key)
-			})
-			.map(std::convert::Into::into)
-			.collect();
+	/// Write the address cache to the auxiliary storage of the client.
+	fn persist_address_cache(&mut self) -> Result<()> {
+		let encoded = self.address_cache.encode();
+		self.client
+			.insert_aux(&[(ADDR_CACHE_AUX_KEY, &encoded[..])], &[])
+			.map_err(Error::PersistingAddressCache)?;
 
-		Ok(keys)
+		self.address_cache_dirty = false;
+
+		Ok(())
 	}
 
 	/// Retrieve our public keys within the current authority set.
+	///
+	/// The keys are tagged with the crypto of [`AuthorityId`], thus they can be used to sign via
+	/// the key store without knowing the concrete crypto.
 	//
 	// A node might have multiple authority discovery keys within its keystore, e.g. an old one and
 	// one for the upcoming session. In addition it could be participating in the current authority
 	// set with two keys. The function does not return all of the local authority discovery public
 	// keys, but only the ones intersecting with the current authority set.
-	fn get_own_public_keys_within_authority_set(&mut self) -> Result<HashSet<AuthorityId>> {
+	fn get_own_public_keys_within_authority_set(
+		&mut self,
+	) -> Result<HashSet<CryptoTypePublicPair>> {
 		let local_pub_keys = self.key_store
 			.read()
-			.sr25519_public_keys(key_types::AUTHORITY_DISCOVERY)
+			.keys(key_types::AUTHORITY_DISCOVERY)
 			.into_iter()
+			.filter(|key| key.0 == CRYPTO_ID)
 			.collect::<HashSet<_>>();
 
 		let id = BlockId::hash(self.client.info().best_hash);
@@ -419,12 +956,11 @@ where
 			.authorities(&id)
 			.map_err(Error::CallingRuntime)?
 			.into_iter()
-			.map(std::convert::Into::into)
+			.map(|id| CryptoTypePublicPair(CRYPTO_ID, id.as_ref().to_vec()))
 			.collect::<HashSet<_>>();
 
 		let intersection = local_pub_keys.intersection(&current_authorities)
 			.cloned()
-			.map(std::convert::Into::into)
 			.collect();
 
 		Ok(intersection)
@@ -435,43 +971,94 @@ impl<Client, Network, Block> Future for AuthorityDiscovery<Client, Network, Bloc
 where
 	Block: BlockT + Unpin + 'static,
 	Network: NetworkProvider,
-	Client: ProvideRuntimeApi + Send + Sync + 'static + HeaderBackend<Block>,
+	Client: ProvideRuntimeApi + Send + Sync + 'static + HeaderBackend<Block> + BlockchainEvents<Block>
+		+ AuxStore,
 	<Client as ProvideRuntimeApi>::Api: AuthorityDiscoveryApi<Block, Error = sp_blockchain::Error>,
 {
 	type Output = ();
 
 	fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-		let mut inner = || -> Result<()> {
-			// Process incoming events before triggering new ones.
-			self.handle_dht_events(cx)?;
-
-			if let Poll::Ready(_) = self.publish_interval.poll_next_unpin(cx) {
-				// Make sure to call interval.poll until it returns Async::NotReady once. Otherwise,
-				// in case one of the function calls within this block do a `return`, we don't call
-				// `interval.poll` again and thereby the underlying Tokio task is never registered
-				// with Tokio's Reactor to be woken up on the next interval tick.
-				while let Poll::Ready(_) = self.publish_interval.poll_next_unpin(cx) {}
-
-				self.publish_ext_addresses()?;
+		// The steps below are independent of each other. A failure in one of them, e.g. a runtime
+		// api call failing while the best block does not yet provide the `AuthorityDiscoveryApi`,
+		// is logged and does not prevent the others from making progress.
+
+		// Answer queries of other subsystems independently of any DHT related failures below.
+		self.handle_service_messages(cx);
+
+		// Process incoming events before triggering new ones.
+		self.handle_dht_events(cx);
+
+		if let Err(e) = self.handle_import_notifications(cx) {
+			error!(
+				target: "sub-authority-discovery",
+				"Failed to handle block import notifications: {:?}", e,
+			);
+		}
+
+		let this = &mut *self;
+
+		if poll_interval_or_retry(this.publish_interval.as_mut(), &mut this.publish_retry, cx) {
+			if let Err(e) = this.publish_ext_addresses() {
+				error!(
+					target: "sub-authority-discovery",
+					"Failed to publish external addresses: {:?}", e,
+				);
+
+				if e.is_transient() {
+					this.publish_retry = Some(Delay::new(TRANSIENT_ERROR_RETRY_DELAY));
+				}
 			}
+		}
 
-			if let Poll::Ready(_) = self.query_interval.poll_next_unpin(cx) {
-				// Make sure to call interval.poll until it returns Async::NotReady once. Otherwise,
-				// in case one of the function calls within this block do a `return`, we don't call
-				// `interval.poll` again and thereby the underlying Tokio task is never registered
-				// with Tokio's Reactor to be woken up on the next interval tick.
-				while let Poll::Ready(_) = self.query_interval.poll_next_unpin(cx) {}
+		this.put_signed_records(cx);
 
-				self.request_addresses_of_others()?;
+		if poll_interval_or_retry(this.query_interval.as_mut(), &mut this.query_retry, cx) {
+			if let Err(e) = this.request_addresses_of_others() {
+				error!(
+					target: "sub-authority-discovery",
+					"Failed to request addresses of authorities: {:?}", e,
+				);
+
+				if e.is_transient() {
+					this.query_retry = Some(Delay::new(TRANSIENT_ERROR_RETRY_DELAY));
+				}
 			}
+		}
 
-			Ok(())
-		};
+		if poll_interval_or_retry(this.persist_interval.as_mut(), &mut None, cx)
+			&& this.address_cache_dirty
+		{
+			if let Err(e) = this.persist_address_cache() {
+				error!(
+					target: "sub-authority-discovery",
+					"Failed to persist address cache: {:?}", e,
+				);
+			}
+		}
 
-		match inner() {
-			Ok(()) => {}
-			Err(e) => error!(target: "sub-authority-discovery", "Poll failure: {:?}", e),
-		};
+		// Polling the delay of a freshly scheduled batch right away registers it for a wake up.
+		while let Some(Poll::Ready(())) = this.next_lookup_batch.as_mut().map(|d| d.poll_unpin(cx)) {
+			if let Err(e) = this.start_next_lookup_batch() {
+				error!(
+					target: "sub-authority-discovery",
+					"Failed to look up addresses of authorities: {:?}", e,
+				);
+
+				this.next_lookup_batch = None;
+			}
+		}
+
+		// A freshly scheduled retry needs to be polled once to register for a wake up.
+		if let Some(retry) = this.publish_retry.as_mut() {
+			if let Poll::Ready(()) = retry.poll_unpin(cx) {
+				cx.waker().wake_by_ref();
+			}
+		}
+		if let Some(retry) = this.query_retry.as_mut() {
+			if let Poll::Ready(()) = retry.poll_unpin(cx) {
+				cx.waker().wake_by_ref();
+			}
+		}
 
 		// Make sure to always return NotReady as this is a long running task with the same lifetime
 		// as the node itself.
@@ -490,6 +1077,26 @@ pub trait NetworkProvider: NetworkStateInfo {
 		peers: HashSet<libp2p::Multiaddr>,
 	) -> std::result::Result<(), String>;
 
+	/// Add peers to a peerset priority group, leaving the other members untouched.
+	fn add_to_priority_group(
+		&self,
+		group_id: String,
+		peers: HashSet<libp2p::Multiaddr>,
+	) -> std::result::Result<(), String>;
+
+	/// Remove peers from a peerset priority group, leaving the other members untouched.
+	fn remove_from_priority_group(
+		&self,
+		group_id: String,
+		peers: HashSet<libp2p::Multiaddr>,
+	) -> std::result::Result<(), String>;
+
+	/// Sign the given message with the network identity the local `PeerId` is derived from.
+	fn sign_with_local_identity(
+		&self,
+		msg: &[u8],
+	) -> std::result::Result<(PublicKey, Vec<u8>), SigningError>;
+
 	/// Start putting a value in the Dht.
 	fn put_value(&self, key: libp2p::kad::record::Key, value: Vec<u8>);
 
@@ -510,6 +1117,26 @@ where
 	) -> std::result::Result<(), String> {
 		self.set_priority_group(group_id, peers)
 	}
+	fn add_to_priority_group(
+		&self,
+		group_id: String,
+		peers: HashSet<libp2p::Multiaddr>,
+	) -> std::result::Result<(), String> {
+		self.add_to_priority_group(group_id, peers)
+	}
+	fn remove_from_priority_group(
+		&self,
+		group_id: String,
+		peers: HashSet<libp2p::Multiaddr>,
+	) -> std::result::Result<(), String> {
+		self.remove_from_priority_group(group_id, peers)
+	}
+	fn sign_with_local_identity(
+		&self,
+		msg: &[u8],
+	) -> std::result::Result<(PublicKey, Vec<u8>), SigningError> {
+		self.sign_with_local_identity(msg)
+	}
 	fn put_value(&self, key: libp2p::kad::record::Key, value: Vec<u8>) {
 		self.put_value(key, value)
 	}
@@ -524,6 +1151,207 @@ fn hash_authority_id(id: &[u8]) -> Result<libp2p::kad::record::Key> {
 		.map_err(Error::HashingAuthorityId)
 }
 
+/// Returns `false` if the given address is not globally reachable, i.e. is a private, loopback,
+/// link-local or unspecified IP address. Non IP addresses, e.g. DNS names, are considered global.
+fn is_global_address(address: &Multiaddr) -> bool {
+	match address.iter().next() {
+		Some(Protocol::Ip4(ip)) => !(
+			ip.is_private() || ip.is_loopback() || ip.is_link_local() || ip.is_unspecified()
+		),
+		Some(Protocol::Ip6(ip)) => {
+			let is_unique_local = (ip.segments()[0] & 0xfe00) == 0xfc00;
+			let is_unicast_link_local = (ip.segments()[0] & 0xffc0) == 0xfe80;
+
+			!(ip.is_loopback() || ip.is_unspecified() || is_unique_local || is_unicast_link_local)
+		},
+		_ => true,
+	}
+}
+
+/// Verify the network identity signature of the given serialized addresses and ensure the decoded
+/// addresses all belong to the `PeerId` of the signer, i.e. end with its `/p2p/` suffix.
+fn verify_peer_signature(
+	peer_signature: &schema::PeerSignature,
+	serialized_addresses: &[u8],
+	addresses: &[Multiaddr],
+) -> Result<()> {
+	let public_key = PublicKey::from_protobuf_encoding(&peer_signature.public_key)
+		.map_err(Error::ParsingLibp2pIdentity)?;
+
+	if !public_key.verify(serialized_addresses, &peer_signature.signature) {
+		return Err(Error::VerifyingPeerSignature);
+	}
+
+	let peer_id = public_key.into_peer_id();
+	let all_addresses_match = addresses.iter().all(|address| match address.iter().last() {
+		Some(Protocol::P2p(multihash)) => PeerId::from_multihash(multihash)
+			.map(|p| p == peer_id)
+			.unwrap_or(false),
+		_ => false,
+	});
+
+	if !all_addresses_match {
+		return Err(Error::PeerIdMismatch);
+	}
+
+	Ok(())
+}
+
+/// Message signed by the authority key in records of version 2 and above, binding the version to
+/// the addresses so it can not be altered without invalidating the signature.
+fn versioned_signing_payload(version: u32, serialized_addresses: &[u8]) -> Vec<u8> {
+	let mut payload = version.to_le_bytes().to_vec();
+	payload.extend_from_slice(serialized_addresses);
+	payload
+}
+
+/// Verify the authority signature of the given record according to its format version.
+fn verify_record_signature(
+	record: &schema::SignedAuthorityAddresses,
+	authority_id: &AuthorityId,
+) -> Result<()> {
+	let valid = match record.version {
+		// Records published before the introduction of versioning lack the version field.
+		0 | 1 => verify_signature(
+			CRYPTO_ID,
+			&record.signature,
+			&record.addresses,
+			authority_id.as_ref(),
+		),
+		// Newer versions only add fields, still signing the addresses along with the version.
+		version => verify_signature(
+			CRYPTO_ID,
+			&record.versioned_signature,
+			&versioned_signing_payload(version, &record.addresses),
+			authority_id.as_ref(),
+		),
+	};
+
+	if valid {
+		Ok(())
+	} else {
+		Err(Error::VerifyingDhtPayload)
+	}
+}
+
+/// Verify the raw `signature` of `message` by the raw `public` key of the given crypto.
+fn verify_signature(crypto: CryptoTypeId, signature: &[u8], message: &[u8], public: &[u8]) -> bool {
+	match crypto {
+		sr25519::CRYPTO_ID => sr25519::Pair::verify_weak(signature, message, public),
+		ed25519::CRYPTO_ID => ed25519::Pair::verify_weak(signature, message, public),
+		ecdsa::CRYPTO_ID => ecdsa::Pair::verify_weak(signature, message, public),
+		_ => false,
+	}
+}
+
+/// Restore the address cache previously persisted in the auxiliary storage of the client. Failures
+/// are logged, starting with an empty cache.
+fn restore_address_cache<Client: AuxStore>(client: &Client, address_cache: &mut AddrCache) {
+	match client.get_aux(ADDR_CACHE_AUX_KEY) {
+		Ok(Some(encoded)) => match address_cache.insert_encoded(&encoded) {
+			Ok(()) => debug!(
+				target: "sub-authority-discovery",
+				"Restored addresses of {} authorities from the database.", address_cache.len(),
+			),
+			Err(e) => warn!(
+				target: "sub-authority-discovery",
+				"Failed to decode persisted address cache: {:?}", e,
+			),
+		},
+		Ok(None) => {},
+		Err(e) => warn!(
+			target: "sub-authority-discovery",
+			"Failed to read persisted address cache: {:?}", e,
+		),
+	}
+}
+
+/// Returns until when to publish records readable by nodes only supporting version 1 records.
+///
+/// The grace period starts with the first startup, persisted in the auxiliary storage of the
+/// client. Failures are logged, starting the grace period now.
+fn legacy_records_until<Client: AuxStore>(client: &Client, grace_period: Duration) -> Instant {
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+	let since = match client.get_aux(LEGACY_RECORDS_SINCE_AUX_KEY) {
+		Ok(Some(encoded)) => match <u64 as codec::Decode>::decode(&mut &encoded[..]) {
+			Ok(since) => Some(since),
+			Err(e) => {
+				warn!(
+					target: "sub-authority-discovery",
+					"Failed to decode start of legacy record grace period: {:?}", e,
+				);
+				None
+			},
+		},
+		Ok(None) => None,
+		Err(e) => {
+			warn!(
+				target: "sub-authority-discovery",
+				"Failed to read start of legacy record grace period: {:?}", e,
+			);
+			None
+		},
+	};
+
+	let since = since.unwrap_or_else(|| {
+		let encoded = codec::Encode::encode(&now);
+		if let Err(e) = client.insert_aux(&[(LEGACY_RECORDS_SINCE_AUX_KEY, &encoded[..])], &[]) {
+			warn!(
+				target: "sub-authority-discovery",
+				"Failed to persist start of legacy record grace period: {:?}", e,
+			);
+		}
+		now
+	});
+
+	let elapsed = Duration::from_secs(now.saturating_sub(since));
+	Instant::now() + grace_period.checked_sub(elapsed).unwrap_or_default()
+}
+
+/// Returns `true` if either the given interval ticked or the given retry fired, in which case the
+/// retry is cleared.
+fn poll_interval_or_retry(
+	interval: Option<&mut Interval>,
+	retry: &mut Option<Delay>,
+	cx: &mut Context,
+) -> bool {
+	let mut ready = false;
+
+	if let Some(interval) = interval {
+		// Make sure to call interval.poll until it returns Poll::Pending once. Otherwise the
+		// underlying timer is never registered to wake up the task on the next interval tick.
+		while let Poll::Ready(Some(())) = interval.poll_next_unpin(cx) {
+			ready = true;
+		}
+	}
+
+	if let Some(delay) = retry.as_mut() {
+		if let Poll::Ready(()) = delay.poll_unpin(cx) {
+			ready = true;
+		}
+	}
+
+	if ready {
+		*retry = None;
+	}
+
+	ready
+}
+
+/// Interval firing first at `start`, then after `initial`, doubling the duration between two
+/// ticks each time until `max` is reached.
+fn exp_inc_interval_at(start: Instant, initial: Duration, max: Duration) -> Interval {
+	let stream = futures::stream::unfold((start, initial), move |(next, duration)| {
+		let time_until_next = next.saturating_duration_since(Instant::now());
+
+		Delay::new(time_until_next)
+			.map(move |_| Some(((), (next + duration, std::cmp::min(duration * 2, max)))))
+	});
+
+	Box::new(stream)
+}
+
 fn interval_at(start: Instant, duration: Duration) -> Interval {
 	let stream = futures::stream::unfold(start, move |next| {
 		let time_until_next =  next.saturating_duration_since(Instant::now());
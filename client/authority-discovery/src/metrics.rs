@@ -0,0 +1,100 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of the authority discovery module.
+
+use prometheus_endpoint::{
+	register, IntCounter, IntCounterVec, IntGauge, Opts, PrometheusError, Registry,
+};
+
+/// Prometheus metrics of an [`AuthorityDiscovery`](crate::AuthorityDiscovery).
+#[derive(Clone)]
+pub(crate) struct Metrics {
+	/// Number of times own addresses were published on the Dht.
+	pub publish: IntCounter,
+	/// Number of Dht put requests issued, one per own authority key and publish.
+	pub dht_put: IntCounter,
+	/// Number of Dht get requests issued, one per authority and query.
+	pub dht_get: IntCounter,
+	/// Number of Dht events received, labeled by event type.
+	pub dht_event_received: IntCounterVec,
+	/// Number of Dht records dropped due to an invalid signature.
+	pub signature_verification_failure: IntCounter,
+	/// Number of authorities with addresses in the address cache.
+	pub known_authorities_count: IntGauge,
+	/// Number of addresses in the authorities peerset priority group.
+	pub priority_group_size: IntGauge,
+}
+
+impl Metrics {
+	/// Create and register the metrics with the given registry.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			publish: register(
+				IntCounter::new(
+					"authority_discovery_times_published_total",
+					"Number of times authority discovery has published external addresses."
+				)?,
+				registry,
+			)?,
+			dht_put: register(
+				IntCounter::new(
+					"authority_discovery_dht_put_requests_total",
+					"Number of Dht put requests issued by authority discovery."
+				)?,
+				registry,
+			)?,
+			dht_get: register(
+				IntCounter::new(
+					"authority_discovery_dht_get_requests_total",
+					"Number of Dht get requests issued by authority discovery."
+				)?,
+				registry,
+			)?,
+			dht_event_received: register(
+				IntCounterVec::new(
+					Opts::new(
+						"authority_discovery_dht_event_received",
+						"Number of dht events received by authority discovery."
+					),
+					&["name"],
+				)?,
+				registry,
+			)?,
+			signature_verification_failure: register(
+				IntCounter::new(
+					"authority_discovery_signature_verification_failures_total",
+					"Number of Dht records with an invalid signature."
+				)?,
+				registry,
+			)?,
+			known_authorities_count: register(
+				IntGauge::new(
+					"authority_discovery_known_authorities_count",
+					"Number of authorities known by authority discovery."
+				)?,
+				registry,
+			)?,
+			priority_group_size: register(
+				IntGauge::new(
+					"authority_discovery_priority_group_size",
+					"Number of addresses passed to the peer set as a priority group."
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
@@ -0,0 +1,106 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Handle to query an [`AuthorityDiscovery`](crate::AuthorityDiscovery) from other subsystems.
+
+use std::sync::Arc;
+
+use futures::channel::{mpsc, oneshot};
+use futures::SinkExt;
+use parking_lot::RwLock;
+
+use libp2p::Multiaddr;
+use sp_authority_discovery::AuthorityId;
+
+/// Message send from a [`Service`] to the [`AuthorityDiscovery`](crate::AuthorityDiscovery).
+pub(crate) enum ServiceToWorkerMsg {
+	/// See [`Service::get_addresses_by_authority_id`].
+	GetAddressesByAuthorityId(AuthorityId, oneshot::Sender<Option<Vec<Multiaddr>>>),
+	/// See [`Service::set_sentry_addresses`].
+	SetSentryAddresses(Vec<Multiaddr>),
+}
+
+/// Service to interact with the [`AuthorityDiscovery`](crate::AuthorityDiscovery).
+///
+/// The service is cheap to clone. Each request is sent to the authority discovery future over a
+/// channel, thus requests are only answered as long as the future is being polled.
+#[derive(Clone)]
+pub struct Service {
+	to_worker: mpsc::Sender<ServiceToWorkerMsg>,
+}
+
+impl Service {
+	pub(crate) fn new(to_worker: mpsc::Sender<ServiceToWorkerMsg>) -> Self {
+		Service { to_worker }
+	}
+
+	/// Get the addresses for the given [`AuthorityId`] from the local address cache.
+	///
+	/// Returns `None` if no entry was present or the authority discovery future is gone.
+	pub async fn get_addresses_by_authority_id(
+		&mut self,
+		authority: AuthorityId,
+	) -> Option<Vec<Multiaddr>> {
+		let (tx, rx) = oneshot::channel();
+
+		self.to_worker
+			.send(ServiceToWorkerMsg::GetAddressesByAuthorityId(authority, tx))
+			.await
+			.ok()?;
+
+		rx.await.ok().and_then(|addresses| addresses)
+	}
+
+	/// Replace the public addresses of the sentry nodes published on behalf of this node, e.g.
+	/// when sentries are rotated, and publish them right away. An empty set of addresses switches
+	/// to publishing the external addresses of this node itself.
+	///
+	/// Returns an error if the authority discovery future is gone.
+	pub async fn set_sentry_addresses(
+		&mut self,
+		addresses: Vec<Multiaddr>,
+	) -> Result<(), mpsc::SendError> {
+		self.to_worker
+			.send(ServiceToWorkerMsg::SetSentryAddresses(addresses))
+			.await
+	}
+}
+
+/// A [`Service`] shared with subsystems created before the
+/// [`AuthorityDiscovery`](crate::AuthorityDiscovery) itself, e.g. RPC handlers.
+///
+/// Starts out empty and is set once the authority discovery is created.
+#[derive(Clone, Default)]
+pub struct SharedService {
+	inner: Arc<RwLock<Option<Service>>>,
+}
+
+impl SharedService {
+	/// Create a new empty `SharedService`.
+	pub fn empty() -> Self {
+		Default::default()
+	}
+
+	/// Set the [`Service`] of the authority discovery to share.
+	pub fn set(&self, service: Service) {
+		*self.inner.write() = Some(service);
+	}
+
+	/// Get the shared [`Service`], if the authority discovery was created.
+	pub fn get(&self) -> Option<Service> {
+		self.inner.read().clone()
+	}
+}
@@ -14,14 +14,18 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
+use std::iter::FromIterator;
 use std::sync::{Arc, Mutex};
 
 use futures::channel::mpsc::channel;
 use futures::executor::block_on;
 use futures::future::poll_fn;
+use futures::task::noop_waker_ref;
+use libp2p::identity::Keypair;
 use libp2p::{kad, PeerId};
 
 use sp_api::{ApiExt, Core, RuntimeVersion, StorageProof};
+use sp_authority_discovery::AuthorityPair;
 use sp_core::{testing::KeyStore, ExecutionContext, NativeOrEncoded};
 use sp_runtime::traits::Zero;
 use sp_runtime::traits::{ApiRef, Block as BlockT, NumberFor, ProvideRuntimeApi};
@@ -89,9 +93,60 @@ fn interval_at_with_initial_delay() {
 	);
 }
 
-#[derive(Clone)]
+#[test]
+fn exp_inc_interval_at_is_backing_off() {
+	let start = Instant::now();
+
+	let interval = exp_inc_interval_at(
+		start,
+		Duration::from_millis(50),
+		Duration::from_millis(100),
+	);
+
+	// Ticks at 0ms, 50ms, 150ms and 250ms as the duration between ticks is doubled once and then
+	// capped.
+	futures::executor::block_on(async {
+		interval.take(4).collect::<Vec<()>>().await;
+	});
+
+	let elapsed = Instant::now().saturating_duration_since(start);
+	assert!(
+		elapsed >= Duration::from_millis(250),
+		"Expected interval to back off between ticks.",
+	);
+	assert!(
+		elapsed < Duration::from_millis(450),
+		"Expected interval to cap the duration between ticks.",
+	);
+}
+
+#[derive(Clone, Default)]
 struct TestApi {
 	authorities: Vec<AuthorityId>,
+	aux: Arc<Mutex<HashMap<Vec<u8>, Vec<u8>>>>,
+}
+
+impl AuxStore for TestApi {
+	fn insert_aux<
+		'a,
+		'b: 'a,
+		'c: 'a,
+		I: IntoIterator<Item=&'a(&'c [u8], &'c [u8])>,
+		D: IntoIterator<Item=&'a &'b [u8]>,
+	>(&self, insert: I, delete: D) -> sp_blockchain::Result<()> {
+		let mut aux = self.aux.lock().unwrap();
+		for (key, value) in insert {
+			aux.insert(key.to_vec(), value.to_vec());
+		}
+		for key in delete {
+			aux.remove(*key);
+		}
+		Ok(())
+	}
+
+	fn get_aux(&self, key: &[u8]) -> sp_blockchain::Result<Option<Vec<u8>>> {
+		Ok(self.aux.lock().unwrap().get(key).cloned())
+	}
 }
 
 impl ProvideRuntimeApi for TestApi {
@@ -146,6 +201,26 @@ impl<Block: BlockT> HeaderBackend<Block> for TestApi {
 	}
 }
 
+impl<Block: BlockT> BlockchainEvents<Block> for TestApi {
+	fn import_notification_stream(&self) -> ImportNotifications<Block> {
+		futures::channel::mpsc::unbounded().1
+	}
+
+	fn finality_notification_stream(&self) -> sc_client_api::FinalityNotifications<Block> {
+		futures::channel::mpsc::unbounded().1
+	}
+
+	fn storage_changes_notification_stream(
+		&self,
+		_filter_keys: Option<&[sp_core::storage::StorageKey]>,
+		_child_filter_keys: Option<
+			&[(sp_core::storage::StorageKey, Option<Vec<sp_core::storage::StorageKey>>)]
+		>,
+	) -> sp_blockchain::Result<sc_client_api::StorageEventStream<Block::Hash>> {
+		unimplemented!("Not required for testing!")
+	}
+}
+
 struct RuntimeApi {
 	authorities: Vec<AuthorityId>,
 }
@@ -220,8 +295,8 @@ impl AuthorityDiscoveryApi<Block> for RuntimeApi {
 	}
 }
 
-#[derive(Default)]
 struct TestNetwork {
+	identity: Keypair,
 	// Whenever functions on `TestNetwork` are called, the function arguments are added to the
 	// vectors below.
 	pub put_value_call: Arc<Mutex<Vec<(kad::record::Key, Vec<u8>)>>>,
@@ -229,6 +304,17 @@ struct TestNetwork {
 	pub set_priority_group_call: Arc<Mutex<Vec<(String, HashSet<Multiaddr>)>>>,
 }
 
+impl Default for TestNetwork {
+	fn default() -> Self {
+		TestNetwork {
+			identity: Keypair::generate_ed25519(),
+			put_value_call: Default::default(),
+			get_value_call: Default::default(),
+			set_priority_group_call: Default::default(),
+		}
+	}
+}
+
 impl NetworkProvider for TestNetwork {
 	fn set_priority_group(
 		&self,
@@ -241,6 +327,26 @@ impl NetworkProvider for TestNetwork {
 			.push((group_id, peers));
 		Ok(())
 	}
+	fn add_to_priority_group(
+		&self,
+		_group_id: String,
+		_peers: HashSet<Multiaddr>,
+	) -> std::result::Result<(), String> {
+		unimplemented!("Not required for testing!")
+	}
+	fn remove_from_priority_group(
+		&self,
+		_group_id: String,
+		_peers: HashSet<Multiaddr>,
+	) -> std::result::Result<(), String> {
+		unimplemented!("Not required for testing!")
+	}
+	fn sign_with_local_identity(
+		&self,
+		msg: &[u8],
+	) -> std::result::Result<(PublicKey, Vec<u8>), SigningError> {
+		Ok((self.identity.public(), self.identity.sign(msg)?))
+	}
 	fn put_value(&self, key: kad::record::Key, value: Vec<u8>) {
 		self.put_value_call.lock().unwrap().push((key, value));
 	}
@@ -251,14 +357,43 @@ impl NetworkProvider for TestNetwork {
 
 impl NetworkStateInfo for TestNetwork {
 	fn local_peer_id(&self) -> PeerId {
-		PeerId::random()
+		self.identity.public().into_peer_id()
 	}
 
 	fn external_addresses(&self) -> Vec<Multiaddr> {
-		vec![]
+		vec!["/ip6/2001:db8::".parse().unwrap()]
 	}
 }
 
+/// Build a dht value announcing `addresses`, signed by the authority `key_pair` and, if given, by
+/// the network `identity`.
+fn build_dht_value(
+	key_pair: &AuthorityPair,
+	addresses: &[Multiaddr],
+	identity: Option<&Keypair>,
+) -> Vec<u8> {
+	let mut serialized_addresses = vec![];
+	schema::AuthorityAddresses { addresses: addresses.iter().map(|a| a.to_vec()).collect() }
+		.encode(&mut serialized_addresses)
+		.unwrap();
+
+	let peer_signature = identity.map(|identity| schema::PeerSignature {
+		signature: identity.sign(&serialized_addresses).unwrap(),
+		public_key: identity.public().into_protobuf_encoding(),
+	});
+
+	let mut signed_addresses = vec![];
+	schema::SignedAuthorityAddresses {
+		signature: key_pair.sign(serialized_addresses.as_ref()).as_ref().to_vec(),
+		addresses: serialized_addresses,
+		peer_signature,
+		..Default::default()
+	}
+	.encode(&mut signed_addresses)
+	.unwrap();
+	signed_addresses
+}
+
 #[test]
 fn publish_ext_addresses_puts_record_on_dht() {
 	let (_dht_event_tx, dht_event_rx) = channel(1000);
@@ -270,6 +405,7 @@ fn publish_ext_addresses_puts_record_on_dht() {
 		.unwrap();
 	let test_api = Arc::new(TestApi {
 		authorities: vec![public.into()],
+		..Default::default()
 	});
 
 	let mut authority_discovery = AuthorityDiscovery::new(
@@ -278,14 +414,233 @@ fn publish_ext_addresses_puts_record_on_dht() {
 		vec![],
 		key_store,
 		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
 	);
 
 	authority_discovery.publish_ext_addresses().unwrap();
+	authority_discovery.put_signed_records(&mut Context::from_waker(noop_waker_ref()));
 
 	// Expect authority discovery to put a new record onto the dht.
 	assert_eq!(network.put_value_call.lock().unwrap().len(), 1);
 }
 
+#[test]
+fn publish_ext_addresses_signs_record_verifiable_by_authority_id() {
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let key_store = KeyStore::new();
+	let public = key_store
+		.write()
+		.sr25519_generate_new(key_types::AUTHORITY_DISCOVERY, None)
+		.unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![public.into()],
+		..Default::default()
+	});
+
+	let mut authority_discovery = AuthorityDiscovery::new(
+		test_api,
+		network.clone(),
+		vec![],
+		key_store,
+		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
+	);
+
+	authority_discovery.publish_ext_addresses().unwrap();
+	authority_discovery.put_signed_records(&mut Context::from_waker(noop_waker_ref()));
+
+	let (key, value) = network.put_value_call.lock().unwrap().pop().unwrap();
+	assert_eq!(key, hash_authority_id(public.as_ref()).unwrap());
+
+	let record = schema::SignedAuthorityAddresses::decode(value).unwrap();
+	assert_eq!(record.version, RECORD_VERSION);
+	assert!(verify_record_signature(&record, &public.into()).is_ok());
+
+	// Within the grace period the record is readable by version 1 nodes as well.
+	let schema::SignedAuthorityAddresses { signature, addresses, peer_signature, .. } = record;
+	assert!(verify_signature(CRYPTO_ID, &signature, &addresses, public.as_ref()));
+
+	let decoded_addresses = schema::AuthorityAddresses::decode(&addresses)
+		.unwrap()
+		.addresses
+		.into_iter()
+		.map(|a| a.try_into().unwrap())
+		.collect::<Vec<Multiaddr>>();
+	assert!(verify_peer_signature(&peer_signature.unwrap(), &addresses, &decoded_addresses).is_ok());
+}
+
+#[test]
+fn publish_ext_addresses_omits_legacy_signature_after_grace_period() {
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let key_store = KeyStore::new();
+	let public = key_store
+		.write()
+		.sr25519_generate_new(key_types::AUTHORITY_DISCOVERY, None)
+		.unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![public.into()],
+		..Default::default()
+	});
+
+	let mut authority_discovery = AuthorityDiscovery::new(
+		test_api,
+		network.clone(),
+		vec![],
+		key_store,
+		dht_event_rx.boxed(),
+		AuthorityDiscoveryConfig {
+			legacy_record_grace_period: Duration::from_secs(0),
+			..Default::default()
+		},
+		Role::PublishAndDiscover,
+		None,
+	);
+
+	authority_discovery.publish_ext_addresses().unwrap();
+	authority_discovery.put_signed_records(&mut Context::from_waker(noop_waker_ref()));
+
+	let (_key, value) = network.put_value_call.lock().unwrap().pop().unwrap();
+	let record = schema::SignedAuthorityAddresses::decode(value).unwrap();
+	assert!(record.signature.is_empty());
+	assert!(verify_record_signature(&record, &public.into()).is_ok());
+}
+
+#[test]
+fn verify_record_signature_handles_all_versions() {
+	let key_pair = AuthorityPair::from_seed_slice(&[1; 32]).unwrap();
+	let addresses = b"addresses".to_vec();
+
+	let v1 = schema::SignedAuthorityAddresses {
+		signature: key_pair.sign(&addresses).as_ref().to_vec(),
+		addresses: addresses.clone(),
+		..Default::default()
+	};
+	assert!(verify_record_signature(&v1, &key_pair.public()).is_ok());
+
+	let v2 = schema::SignedAuthorityAddresses {
+		versioned_signature: key_pair.sign(&versioned_signing_payload(2, &addresses))
+			.as_ref()
+			.to_vec(),
+		addresses: addresses.clone(),
+		version: 2,
+		..Default::default()
+	};
+	assert!(verify_record_signature(&v2, &key_pair.public()).is_ok());
+
+	// Altering the version invalidates the signature.
+	let downgraded = schema::SignedAuthorityAddresses { version: 1, ..v2.clone() };
+	match verify_record_signature(&downgraded, &key_pair.public()) {
+		Err(Error::VerifyingDhtPayload) => {},
+		_ => panic!("Expected downgraded record to be rejected."),
+	}
+
+	// Newer versions are verified the same way as version 2.
+	let v3 = schema::SignedAuthorityAddresses {
+		versioned_signature: key_pair.sign(&versioned_signing_payload(3, &addresses))
+			.as_ref()
+			.to_vec(),
+		addresses: addresses.clone(),
+		version: 3,
+		..Default::default()
+	};
+	assert!(verify_record_signature(&v3, &key_pair.public()).is_ok());
+
+	let upgraded = schema::SignedAuthorityAddresses { version: 3, ..v2 };
+	match verify_record_signature(&upgraded, &key_pair.public()) {
+		Err(Error::VerifyingDhtPayload) => {},
+		_ => panic!("Expected upgraded record to be rejected."),
+	}
+}
+
+#[test]
+fn legacy_record_grace_period_survives_restarts() {
+	let client = TestApi::default();
+	let grace_period = Duration::from_secs(60 * 60);
+
+	// The first startup persists the start of the grace period.
+	assert!(legacy_records_until(&client, grace_period) > Instant::now());
+	assert!(client.get_aux(LEGACY_RECORDS_SINCE_AUX_KEY).unwrap().is_some());
+
+	// A restart after the grace period elapsed doesn't start it over.
+	let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+	let since = codec::Encode::encode(&(now - 2 * 60 * 60));
+	client.insert_aux(&[(LEGACY_RECORDS_SINCE_AUX_KEY, &since[..])], &[]).unwrap();
+	assert!(legacy_records_until(&client, grace_period) <= Instant::now());
+}
+
+#[test]
+fn verify_peer_signature_rejects_foreign_peer_id() {
+	let identity = Keypair::generate_ed25519();
+	let foreign_address: Multiaddr = "/ip6/2001:db8::".parse::<Multiaddr>()
+		.unwrap()
+		.with(Protocol::P2p(PeerId::random().into()));
+
+	let mut serialized_addresses = vec![];
+	schema::AuthorityAddresses { addresses: vec![foreign_address.to_vec()] }
+		.encode(&mut serialized_addresses)
+		.unwrap();
+
+	let peer_signature = schema::PeerSignature {
+		signature: identity.sign(&serialized_addresses).unwrap(),
+		public_key: identity.public().into_protobuf_encoding(),
+	};
+
+	match verify_peer_signature(&peer_signature, &serialized_addresses, &[foreign_address]) {
+		Err(Error::PeerIdMismatch) => {},
+		_ => panic!("Expected addresses of a foreign peer id to be rejected."),
+	}
+}
+
+#[test]
+fn is_global_address_rejects_non_global_ips() {
+	let non_global = [
+		"/ip4/10.0.0.1/tcp/30333",
+		"/ip4/192.168.1.1/tcp/30333",
+		"/ip4/127.0.0.1/tcp/30333",
+		"/ip4/169.254.0.1/tcp/30333",
+		"/ip4/0.0.0.0/tcp/30333",
+		"/ip6/::1/tcp/30333",
+		"/ip6/fe80::1/tcp/30333",
+		"/ip6/fd00::1/tcp/30333",
+	];
+	for address in non_global.iter() {
+		assert!(!is_global_address(&address.parse().unwrap()), "{} is not global", address);
+	}
+
+	let global = [
+		"/ip4/1.2.3.4/tcp/30333",
+		"/ip6/2001:db8::/tcp/30333",
+		"/dns4/example.com/tcp/30333",
+	];
+	for address in global.iter() {
+		assert!(is_global_address(&address.parse().unwrap()), "{} is global", address);
+	}
+}
+
+#[test]
+fn verify_signature_supports_all_cryptos() {
+	let msg = b"addresses";
+
+	let pair = sr25519::Pair::from_seed_slice(&[1; 32]).unwrap();
+	let signature = pair.sign(msg);
+	assert!(verify_signature(sr25519::CRYPTO_ID, signature.as_ref(), msg, pair.public().as_ref()));
+	assert!(!verify_signature(ed25519::CRYPTO_ID, signature.as_ref(), msg, pair.public().as_ref()));
+
+	let pair = ed25519::Pair::from_seed_slice(&[1; 32]).unwrap();
+	let signature = pair.sign(msg);
+	assert!(verify_signature(ed25519::CRYPTO_ID, signature.as_ref(), msg, pair.public().as_ref()));
+
+	let pair = ecdsa::Pair::from_seed_slice(&[1; 32]).unwrap();
+	let signature = pair.sign(msg);
+	assert!(verify_signature(ecdsa::CRYPTO_ID, signature.as_ref(), msg, pair.public().as_ref()));
+}
+
 #[test]
 fn request_addresses_of_others_triggers_dht_get_query() {
 	let _ = ::env_logger::try_init();
@@ -297,6 +652,7 @@ fn request_addresses_of_others_triggers_dht_get_query() {
 
 	let test_api = Arc::new(TestApi {
 		authorities: vec![authority_1_key_pair.public(), authority_2_key_pair.public()],
+		..Default::default()
 	});
 
 	let network: Arc<TestNetwork> = Arc::new(Default::default());
@@ -308,6 +664,9 @@ fn request_addresses_of_others_triggers_dht_get_query() {
 		vec![],
 		key_store,
 		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
 	);
 
 	authority_discovery.request_addresses_of_others().unwrap();
@@ -316,6 +675,74 @@ fn request_addresses_of_others_triggers_dht_get_query() {
 	assert_eq!(network.get_value_call.lock().unwrap().len(), 2);
 }
 
+#[test]
+fn request_addresses_of_others_skips_lookups_in_flight() {
+	let (mut dht_event_tx, dht_event_rx) = channel(1000);
+	let key_pair = AuthorityPair::from_seed_slice(&[1; 32]).unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![key_pair.public()],
+		..Default::default()
+	});
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+
+	let mut authority_discovery = AuthorityDiscovery::new(
+		test_api,
+		network.clone(),
+		vec![],
+		KeyStore::new(),
+		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
+	);
+
+	authority_discovery.request_addresses_of_others().unwrap();
+	authority_discovery.request_addresses_of_others().unwrap();
+	assert_eq!(network.get_value_call.lock().unwrap().len(), 1);
+
+	// Once the lookup is answered, the authority is looked up again.
+	let hash = hash_authority_id(key_pair.public().as_ref()).unwrap();
+	dht_event_tx.try_send(sc_network::DhtEvent::ValueNotFound(hash)).unwrap();
+	block_on(poll_fn(|cx| {
+		authority_discovery.handle_dht_events(cx);
+		Poll::Ready(())
+	}));
+
+	authority_discovery.request_addresses_of_others().unwrap();
+	assert_eq!(network.get_value_call.lock().unwrap().len(), 2);
+}
+
+#[test]
+fn request_addresses_of_others_spreads_lookups_in_batches() {
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let authorities = (0..150u8)
+		.map(|i| AuthorityPair::from_seed_slice(&[i; 32]).unwrap().public())
+		.collect::<Vec<_>>();
+	let test_api = Arc::new(TestApi { authorities, ..Default::default() });
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+
+	let mut authority_discovery = AuthorityDiscovery::new(
+		test_api,
+		network.clone(),
+		vec![],
+		KeyStore::new(),
+		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
+	);
+
+	authority_discovery.request_addresses_of_others().unwrap();
+	assert_eq!(network.get_value_call.lock().unwrap().len(), 15);
+	assert!(authority_discovery.next_lookup_batch.is_some());
+
+	for _ in 1..LOOKUP_BATCHES {
+		authority_discovery.start_next_lookup_batch().unwrap();
+	}
+	assert_eq!(network.get_value_call.lock().unwrap().len(), 150);
+	assert!(authority_discovery.next_lookup_batch.is_none());
+}
+
 #[test]
 fn handle_dht_events_with_value_found_should_call_set_priority_group() {
 	let _ = ::env_logger::try_init();
@@ -325,6 +752,7 @@ fn handle_dht_events_with_value_found_should_call_set_priority_group() {
 	let key_pair = AuthorityPair::from_seed_slice(&[1; 32]).unwrap();
 	let test_api = Arc::new(TestApi {
 		authorities: vec![key_pair.public()],
+		..Default::default()
 	});
 	let network: Arc<TestNetwork> = Arc::new(Default::default());
 	let key_store = KeyStore::new();
@@ -335,35 +763,26 @@ fn handle_dht_events_with_value_found_should_call_set_priority_group() {
 		vec![],
 		key_store,
 		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
 	);
 
 	// Create sample dht event.
 
 	let authority_id_1 = hash_authority_id(key_pair.public().as_ref()).unwrap();
-	let address_1: Multiaddr = "/ip6/2001:db8::".parse().unwrap();
-
-	let mut serialized_addresses = vec![];
-	schema::AuthorityAddresses {
-		addresses: vec![address_1.to_vec()],
-	}
-	.encode(&mut serialized_addresses)
-	.unwrap();
-
-	let signature = key_pair.sign(serialized_addresses.as_ref()).encode();
-	let mut signed_addresses = vec![];
-	schema::SignedAuthorityAddresses {
-		addresses: serialized_addresses,
-		signature: signature,
-	}
-	.encode(&mut signed_addresses)
-	.unwrap();
+	let identity = Keypair::generate_ed25519();
+	let address_1: Multiaddr = "/ip6/2001:db8::".parse::<Multiaddr>()
+		.unwrap()
+		.with(Protocol::P2p(identity.public().into_peer_id().into()));
+	let signed_addresses = build_dht_value(&key_pair, &[address_1.clone()], Some(&identity));
 
 	let dht_event = sc_network::DhtEvent::ValueFound(vec![(authority_id_1, signed_addresses)]);
 	dht_event_tx.try_send(dht_event).unwrap();
 
 	// Make authority discovery handle the event.
 	let f = |cx: &mut Context<'_>| -> Poll<()> {
-		authority_discovery.handle_dht_events(cx).unwrap();
+		authority_discovery.handle_dht_events(cx);
 
 		// Expect authority discovery to set the priority set.
 		assert_eq!(network.set_priority_group_call.lock().unwrap().len(), 1);
@@ -381,3 +800,430 @@ fn handle_dht_events_with_value_found_should_call_set_priority_group() {
 
 	let _ = block_on(poll_fn(f));
 }
+
+#[test]
+fn handle_dht_events_continues_after_invalid_value() {
+	let _ = ::env_logger::try_init();
+	let (mut dht_event_tx, dht_event_rx) = channel(1000);
+	let key_pair = AuthorityPair::from_seed_slice(&[1; 32]).unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![key_pair.public()],
+		..Default::default()
+	});
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+
+	let mut authority_discovery = AuthorityDiscovery::new(
+		test_api,
+		network.clone(),
+		vec![],
+		KeyStore::new(),
+		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
+	);
+
+	let authority_id = hash_authority_id(key_pair.public().as_ref()).unwrap();
+	let identity = Keypair::generate_ed25519();
+	let address: Multiaddr = "/ip6/2001:db8::".parse::<Multiaddr>()
+		.unwrap()
+		.with(Protocol::P2p(identity.public().into_peer_id().into()));
+	let signed_addresses = build_dht_value(&key_pair, &[address], Some(&identity));
+
+	// An undecodable value followed by a valid one.
+	dht_event_tx.try_send(sc_network::DhtEvent::ValueFound(
+		vec![(authority_id.clone(), vec![1, 2, 3])],
+	)).unwrap();
+	dht_event_tx.try_send(sc_network::DhtEvent::ValueFound(
+		vec![(authority_id, signed_addresses)],
+	)).unwrap();
+
+	block_on(poll_fn(|cx| {
+		authority_discovery.handle_dht_events(cx);
+		Poll::Ready(())
+	}));
+
+	// Expect the valid value to be handled despite the invalid one before.
+	assert_eq!(network.set_priority_group_call.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn handle_dht_events_drops_records_without_peer_signature_unless_accepted() {
+	let _ = ::env_logger::try_init();
+	let key_pair = AuthorityPair::from_seed_slice(&[1; 32]).unwrap();
+	let authority_id = hash_authority_id(key_pair.public().as_ref()).unwrap();
+	let address: Multiaddr = "/ip6/2001:db8::".parse().unwrap();
+	let unsigned_addresses = build_dht_value(&key_pair, &[address.clone()], None);
+
+	for &accept_unsigned_records in &[false, true] {
+		let (mut dht_event_tx, dht_event_rx) = channel(1000);
+		let test_api = Arc::new(TestApi {
+			authorities: vec![key_pair.public()],
+			..Default::default()
+		});
+		let network: Arc<TestNetwork> = Arc::new(Default::default());
+
+		let mut authority_discovery = AuthorityDiscovery::new(
+			test_api,
+			network.clone(),
+			vec![],
+			KeyStore::new(),
+			dht_event_rx.boxed(),
+			AuthorityDiscoveryConfig { accept_unsigned_records, ..Default::default() },
+			Role::PublishAndDiscover,
+			None,
+		);
+
+		dht_event_tx.try_send(sc_network::DhtEvent::ValueFound(
+			vec![(authority_id.clone(), unsigned_addresses.clone())],
+		)).unwrap();
+
+		block_on(poll_fn(|cx| {
+			authority_discovery.handle_dht_events(cx);
+			Poll::Ready(())
+		}));
+
+		let calls = network.set_priority_group_call.lock().unwrap();
+		if accept_unsigned_records {
+			assert_eq!(calls.len(), 1);
+			assert_eq!(calls[0].1, HashSet::from_iter(vec![address.clone()].into_iter()));
+		} else {
+			assert!(calls.is_empty());
+			assert_eq!(authority_discovery.address_cache.len(), 0);
+		}
+	}
+}
+
+#[test]
+fn poll_interval_or_retry_fires_on_retry_and_clears_it() {
+	let mut retry = Some(Delay::new(Duration::from_secs(0)));
+	block_on(poll_fn(|cx| match poll_interval_or_retry(None, &mut retry, cx) {
+		true => Poll::Ready(()),
+		false => Poll::Pending,
+	}));
+	assert!(retry.is_none());
+
+	// The interval ticking supersedes a pending retry.
+	let mut retry = Some(Delay::new(Duration::from_secs(60)));
+	let mut interval = interval_at(Instant::now(), Duration::from_secs(60));
+	block_on(poll_fn(|cx| match poll_interval_or_retry(Some(&mut interval), &mut retry, cx) {
+		true => Poll::Ready(()),
+		false => Poll::Pending,
+	}));
+	assert!(retry.is_none());
+
+	block_on(poll_fn(|cx| {
+		assert!(!poll_interval_or_retry(Some(&mut interval), &mut None, cx));
+		Poll::Ready(())
+	}));
+}
+
+#[test]
+fn service_get_addresses_by_authority_id_returns_cached_addresses() {
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let key_pair = AuthorityPair::from_seed_slice(&[1; 32]).unwrap();
+	let unknown_key_pair = AuthorityPair::from_seed_slice(&[2; 32]).unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![key_pair.public()],
+		..Default::default()
+	});
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let key_store = KeyStore::new();
+
+	let mut authority_discovery = AuthorityDiscovery::new(
+		test_api,
+		network.clone(),
+		vec![],
+		key_store,
+		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
+	);
+
+	let address: Multiaddr = "/ip6/2001:db8::".parse().unwrap();
+	authority_discovery.address_cache.insert(key_pair.public(), vec![address.clone()]);
+
+	let mut service = authority_discovery.service();
+
+	let mut query = |authority_discovery: &mut AuthorityDiscovery<_, _, _>, id: AuthorityId| {
+		let mut query = Box::pin(service.get_addresses_by_authority_id(id));
+		block_on(poll_fn(|cx: &mut Context<'_>| {
+			if let Poll::Ready(addresses) = query.poll_unpin(cx) {
+				return Poll::Ready(addresses);
+			}
+
+			authority_discovery.handle_service_messages(cx);
+			query.poll_unpin(cx)
+		}))
+	};
+
+	assert_eq!(
+		Some(vec![address]),
+		query(&mut authority_discovery, key_pair.public()),
+	);
+	assert_eq!(None, query(&mut authority_discovery, unknown_key_pair.public()));
+}
+
+#[test]
+fn service_set_sentry_addresses_republishes_them() {
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let key_store = KeyStore::new();
+	let public = key_store
+		.write()
+		.sr25519_generate_new(key_types::AUTHORITY_DISCOVERY, None)
+		.unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![public.into()],
+		..Default::default()
+	});
+
+	let mut authority_discovery = AuthorityDiscovery::new(
+		test_api,
+		network.clone(),
+		vec![],
+		key_store,
+		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
+	);
+
+	let sentry_address: Multiaddr = "/ip6/2001:db8::/tcp/30333".parse::<Multiaddr>()
+		.unwrap()
+		.with(Protocol::P2p(PeerId::random().into()));
+
+	let mut service = authority_discovery.service();
+	block_on(service.set_sentry_addresses(vec![sentry_address.clone()])).unwrap();
+	block_on(poll_fn(|cx| {
+		authority_discovery.handle_service_messages(cx);
+		authority_discovery.put_signed_records(cx);
+		Poll::Ready(())
+	}));
+
+	let (_key, value) = network.put_value_call.lock().unwrap().pop().unwrap();
+	let record = schema::SignedAuthorityAddresses::decode(value).unwrap();
+	let addresses = schema::AuthorityAddresses::decode(&record.addresses).unwrap().addresses;
+	assert_eq!(addresses, vec![sentry_address.to_vec()]);
+	// Sentry addresses don't belong to the local network identity.
+	assert!(record.peer_signature.is_none());
+
+	// An empty set switches back to publishing own addresses.
+	block_on(service.set_sentry_addresses(vec![])).unwrap();
+	block_on(poll_fn(|cx| {
+		authority_discovery.handle_service_messages(cx);
+		authority_discovery.put_signed_records(cx);
+		Poll::Ready(())
+	}));
+
+	let (_key, value) = network.put_value_call.lock().unwrap().pop().unwrap();
+	let record = schema::SignedAuthorityAddresses::decode(value).unwrap();
+	assert!(record.peer_signature.is_some());
+}
+
+#[test]
+fn role_skips_corresponding_intervals() {
+	let new_authority_discovery = |role| {
+		let (_dht_event_tx, dht_event_rx) = channel(1000);
+		let test_api = Arc::new(TestApi { authorities: vec![], ..Default::default() });
+		let network: Arc<TestNetwork> = Arc::new(Default::default());
+
+		AuthorityDiscovery::new(
+			test_api,
+			network,
+			vec![],
+			KeyStore::new(),
+			dht_event_rx.boxed(),
+			Default::default(),
+			role,
+			None,
+		)
+	};
+
+	let authority_discovery = new_authority_discovery(Role::PublishAndDiscover);
+	assert!(authority_discovery.publish_interval.is_some());
+	assert!(authority_discovery.query_interval.is_some());
+
+	let authority_discovery = new_authority_discovery(Role::Discover);
+	assert!(authority_discovery.publish_interval.is_none());
+	assert!(authority_discovery.query_interval.is_some());
+
+	let authority_discovery = new_authority_discovery(Role::Publish);
+	assert!(authority_discovery.publish_interval.is_some());
+	assert!(authority_discovery.query_interval.is_none());
+}
+
+#[test]
+fn check_authority_set_change_queries_new_authorities() {
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let authority_1_key_pair = AuthorityPair::from_seed_slice(&[1; 32]).unwrap();
+	let authority_2_key_pair = AuthorityPair::from_seed_slice(&[2; 32]).unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![authority_1_key_pair.public(), authority_2_key_pair.public()],
+		..Default::default()
+	});
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+
+	let mut authority_discovery = AuthorityDiscovery::new(
+		test_api,
+		network.clone(),
+		vec![],
+		KeyStore::new(),
+		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		None,
+	);
+
+	// Don't query before the initial queries were issued.
+	authority_discovery.check_authority_set_change().unwrap();
+	assert!(network.get_value_call.lock().unwrap().is_empty());
+
+	// Don't query again if the authority set did not change.
+	authority_discovery.request_addresses_of_others().unwrap();
+	authority_discovery.check_authority_set_change().unwrap();
+	assert_eq!(network.get_value_call.lock().unwrap().len(), 2);
+
+	// Query right away once the authority set changed, given the previous lookups were answered.
+	authority_discovery.in_flight_lookups.clear();
+	authority_discovery.queried_authorities = vec![authority_1_key_pair.public()];
+	authority_discovery.check_authority_set_change().unwrap();
+	assert_eq!(network.get_value_call.lock().unwrap().len(), 4);
+}
+
+#[test]
+fn metrics_are_registered_and_updated() {
+	let (_dht_event_tx, dht_event_rx) = channel(1000);
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let key_store = KeyStore::new();
+	let public = key_store
+		.write()
+		.sr25519_generate_new(key_types::AUTHORITY_DISCOVERY, None)
+		.unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![public.into()],
+		..Default::default()
+	});
+	let registry = Registry::new();
+
+	let mut authority_discovery = AuthorityDiscovery::new(
+		test_api,
+		network.clone(),
+		vec![],
+		key_store,
+		dht_event_rx.boxed(),
+		Default::default(),
+		Role::PublishAndDiscover,
+		Some(registry.clone()),
+	);
+
+	authority_discovery.publish_ext_addresses().unwrap();
+	authority_discovery.put_signed_records(&mut Context::from_waker(noop_waker_ref()));
+	authority_discovery.request_addresses_of_others().unwrap();
+
+	let metrics = authority_discovery.metrics.as_ref().unwrap();
+	assert_eq!(metrics.publish.get(), 1);
+	assert_eq!(metrics.dht_put.get(), 1);
+	assert_eq!(metrics.dht_get.get(), 1);
+	assert!(!registry.gather().is_empty());
+}
+
+#[test]
+fn addr_cache_is_bounded_and_deduplicates() {
+	let authority_1 = AuthorityPair::from_seed_slice(&[1; 32]).unwrap().public();
+	let authority_2 = AuthorityPair::from_seed_slice(&[2; 32]).unwrap().public();
+	let authority_3 = AuthorityPair::from_seed_slice(&[3; 32]).unwrap().public();
+
+	let address_1: Multiaddr = "/ip6/2001:db8::1".parse().unwrap();
+	let address_2: Multiaddr = "/ip6/2001:db8::2".parse().unwrap();
+	let address_3: Multiaddr = "/ip6/2001:db8::3".parse().unwrap();
+
+	let mut cache = AddrCache::new(2, 2);
+
+	cache.insert(
+		authority_1.clone(),
+		vec![address_1.clone(), address_1.clone(), address_2.clone(), address_3.clone()],
+	);
+	assert_eq!(cache.get(&authority_1), Some(&vec![address_1.clone(), address_2.clone()]));
+
+	cache.insert(authority_2.clone(), vec![address_3.clone()]);
+	cache.insert(authority_3.clone(), vec![address_3.clone()]);
+
+	// The least recently updated authority is evicted.
+	assert_eq!(cache.len(), 2);
+	assert_eq!(cache.get(&authority_1), None);
+	assert_eq!(cache.addresses(), HashSet::from_iter(vec![address_3].into_iter()));
+
+	cache.retain_ids(&[authority_2.clone()]);
+	assert_eq!(cache.len(), 1);
+	assert!(cache.get(&authority_2).is_some());
+}
+
+#[test]
+fn addr_cache_encoding_retains_recency_order() {
+	let authority_1 = AuthorityPair::from_seed_slice(&[1; 32]).unwrap().public();
+	let authority_2 = AuthorityPair::from_seed_slice(&[2; 32]).unwrap().public();
+	let authority_3 = AuthorityPair::from_seed_slice(&[3; 32]).unwrap().public();
+	let address: Multiaddr = "/ip6/2001:db8::".parse().unwrap();
+
+	let mut cache = AddrCache::new(3, 1);
+	cache.insert(authority_1.clone(), vec![address.clone()]);
+	cache.insert(authority_2.clone(), vec![address.clone()]);
+
+	let mut restored = AddrCache::new(2, 1);
+	restored.insert_encoded(&cache.encode()).unwrap();
+	assert_eq!(restored.get(&authority_1), Some(&vec![address.clone()]));
+	assert_eq!(restored.get(&authority_2), Some(&vec![address.clone()]));
+
+	// The least recently updated authority before encoding is evicted first.
+	restored.insert(authority_3, vec![address]);
+	assert_eq!(restored.get(&authority_1), None);
+	assert!(restored.get(&authority_2).is_some());
+}
+
+#[test]
+fn persisted_address_cache_is_restored_on_startup() {
+	let key_pair = AuthorityPair::from_seed_slice(&[1; 32]).unwrap();
+	let test_api = Arc::new(TestApi {
+		authorities: vec![key_pair.public()],
+		..Default::default()
+	});
+	let config = AuthorityDiscoveryConfig {
+		persist_address_cache: true,
+		..Default::default()
+	};
+	let address: Multiaddr = "/ip6/2001:db8::".parse().unwrap();
+
+	let new_authority_discovery = |network: Arc<TestNetwork>| {
+		let (_dht_event_tx, dht_event_rx) = channel(1000);
+		AuthorityDiscovery::new(
+			test_api.clone(),
+			network,
+			vec![],
+			KeyStore::new(),
+			dht_event_rx.boxed(),
+			config.clone(),
+			Role::PublishAndDiscover,
+			None,
+		)
+	};
+
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let mut authority_discovery = new_authority_discovery(network.clone());
+	assert!(network.set_priority_group_call.lock().unwrap().is_empty());
+
+	authority_discovery.address_cache.insert(key_pair.public(), vec![address.clone()]);
+	authority_discovery.persist_address_cache().unwrap();
+
+	let network: Arc<TestNetwork> = Arc::new(Default::default());
+	let authority_discovery = new_authority_discovery(network.clone());
+	assert_eq!(
+		authority_discovery.address_cache.get(&key_pair.public()),
+		Some(&vec![address.clone()]),
+	);
+	assert_eq!(
+		network.set_priority_group_call.lock().unwrap()[0],
+		("authorities".to_string(), HashSet::from_iter(vec![address].into_iter())),
+	);
+}
@@ -22,10 +22,11 @@ use std::{time, sync::Arc};
 use sc_client_api::CallExecutor;
 use sp_blockchain;
 use sc_client::Client as SubstrateClient;
-use codec::Decode;
+use codec::{Decode, Encode};
 use sp_consensus::{evaluation};
 use sp_inherents::InherentData;
 use log::{error, info, debug, trace};
+use prometheus_endpoint::Registry;
 use sp_core::{H256, Blake2Hasher, ExecutionContext};
 use sp_runtime::{
 	traits::{
@@ -37,12 +38,76 @@ use sp_transaction_pool::{TransactionPool, InPoolTransaction};
 use sc_telemetry::{telemetry, CONSENSUS_INFO};
 use sc_block_builder::BlockBuilderApi;
 
+use crate::metrics::Metrics;
+
+/// Default maximum size of the blocks proposed, in bytes.
+pub const DEFAULT_MAX_BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Default share of the proposal time, in percent, after which the proposer stops looking for
+/// transactions fitting in a full block.
+pub const DEFAULT_SOFT_DEADLINE_PERCENT: u8 = 50;
+
+/// Limits of the blocks proposed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ProposalLimits {
+	/// Share of the proposal time, in percent, after which the proposer stops looking for
+	/// transactions fitting in the block once it is full. Until then, the transactions that
+	/// don't fit are skipped in favour of the next ones. Transactions are never pulled after
+	/// two thirds of the proposal time, the rest being left to finalize the block.
+	pub soft_deadline_percent: u8,
+	/// Maximum estimated size of the block, in bytes. The digest items added when sealing the
+	/// block are not accounted for.
+	pub max_block_size: usize,
+	/// Total weight of the block, as reported by the runtime, after which the proposer stops
+	/// pulling transactions. The runtime still rejects the transactions exceeding its own
+	/// limit when `None`, or when it doesn't report the weight of the blocks.
+	pub max_block_weight: Option<u64>,
+}
+
+impl Default for ProposalLimits {
+	fn default() -> Self {
+		ProposalLimits {
+			soft_deadline_percent: DEFAULT_SOFT_DEADLINE_PERCENT,
+			max_block_size: DEFAULT_MAX_BLOCK_SIZE,
+			max_block_weight: None,
+		}
+	}
+}
+
 /// Proposer factory.
 pub struct ProposerFactory<C, A> where A: TransactionPool {
 	/// The client instance.
 	pub client: Arc<C>,
 	/// The transaction pool.
 	pub transaction_pool: Arc<A>,
+	/// Limits of the blocks proposed.
+	pub limits: ProposalLimits,
+	metrics: Option<Metrics>,
+}
+
+impl<C, A: TransactionPool> ProposerFactory<C, A> {
+	/// Create a new proposer factory, with the default limits. The fullness of the blocks
+	/// proposed is reported to the given Prometheus registry, if any.
+	pub fn new(
+		client: Arc<C>,
+		transaction_pool: Arc<A>,
+		prometheus_registry: Option<&Registry>,
+	) -> Self {
+		let metrics = prometheus_registry.and_then(|registry| match Metrics::register(registry) {
+			Ok(metrics) => Some(metrics),
+			Err(e) => {
+				error!("Failed to register proposer metrics: {:?}", e);
+				None
+			},
+		});
+
+		ProposerFactory {
+			client,
+			transaction_pool,
+			limits: Default::default(),
+			metrics,
+		}
+	}
 }
 
 impl<B, E, Block, RA, A> ProposerFactory<SubstrateClient<B, E, Block, RA>, A>
@@ -75,6 +140,8 @@ where
 				parent_number: *parent_header.number(),
 				transaction_pool: self.transaction_pool.clone(),
 				now,
+				limits: self.limits.clone(),
+				metrics: self.metrics.clone(),
 			}),
 		};
 
@@ -118,6 +185,28 @@ struct ProposerInner<Block: BlockT, C, A: TransactionPool> {
 	parent_number: <<Block as BlockT>::Header as HeaderT>::Number,
 	transaction_pool: Arc<A>,
 	now: Box<dyn Fn() -> time::Instant + Send + Sync>,
+	limits: ProposalLimits,
+	metrics: Option<Metrics>,
+}
+
+/// Reason the proposer stopped pulling transactions from the pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EndProposingReason {
+	NoMoreTransactions,
+	HitDeadline,
+	HitBlockSizeLimit,
+	HitBlockWeightLimit,
+}
+
+impl EndProposingReason {
+	fn as_str(&self) -> &'static str {
+		match self {
+			EndProposingReason::NoMoreTransactions => "no_more_transactions",
+			EndProposingReason::HitDeadline => "hit_deadline",
+			EndProposingReason::HitBlockSizeLimit => "hit_block_size_limit",
+			EndProposingReason::HitBlockWeightLimit => "hit_block_weight_limit",
+		}
+	}
 }
 
 impl<B, E, Block, RA, A> sp_consensus::Proposer<Block> for
@@ -143,9 +232,15 @@ where
 	) -> Self::Create {
 		let inner = self.inner.clone();
 		tokio_executor::blocking::run(move || {
+			let now = (inner.now)();
 			// leave some time for evaluation and block finalization (33%)
-			let deadline = (inner.now)() + max_duration - max_duration / 3;
-			inner.propose_with(inherent_data, inherent_digests, deadline)
+			let deadline = now + max_duration - max_duration / 3;
+			let soft_deadline_percent = u32::from(inner.limits.soft_deadline_percent.min(100));
+			let soft_deadline = std::cmp::min(
+				now + max_duration * soft_deadline_percent / 100,
+				deadline,
+			);
+			inner.propose_with(inherent_data, inherent_digests, soft_deadline, deadline)
 		})
 	}
 }
@@ -164,6 +259,7 @@ impl<Block, B, E, RA, A> ProposerInner<Block, SubstrateClient<B, E, Block, RA>,
 		&self,
 		inherent_data: InherentData,
 		inherent_digests: DigestFor<Block>,
+		soft_deadline: time::Instant,
 		deadline: time::Instant,
 	) -> Result<Block, sp_blockchain::Error> {
 		/// If the block is full we will attempt to push at most
@@ -188,36 +284,69 @@ impl<Block, B, E, RA, A> ProposerInner<Block, SubstrateClient<B, E, Block, RA>,
 		// proceed with transactions
 		let mut is_first = true;
 		let mut skipped = 0;
+		let mut transactions = 0;
 		let mut unqueue_invalid = Vec::new();
+		let mut end_reason = EndProposingReason::NoMoreTransactions;
 		let pending_iterator = self.transaction_pool.ready();
 
 		debug!("Attempting to push transactions from the pool.");
 		for pending_tx in pending_iterator {
-			if (self.now)() > deadline {
+			let now = (self.now)();
+			if now > deadline {
 				debug!("Consensus deadline reached when pushing block transactions, proceeding with proposing.");
+				end_reason = EndProposingReason::HitDeadline;
 				break;
 			}
 
 			let pending_tx_data = pending_tx.data().clone();
 			let pending_tx_hash = pending_tx.hash().clone();
+
+			let block_size = block_builder.estimate_block_size() + pending_tx_data.encoded_size();
+			if block_size > self.limits.max_block_size {
+				if skipped < MAX_SKIPPED_TRANSACTIONS || now < soft_deadline {
+					skipped += 1;
+					debug!(
+						"[{:?}] Transaction would overflow the block size limit, trying the next ones.",
+						pending_tx_hash,
+					);
+					continue;
+				} else {
+					debug!("Reached the block size limit, proceed with proposing.");
+					end_reason = EndProposingReason::HitBlockSizeLimit;
+					break;
+				}
+			}
+
 			trace!("[{:?}] Pushing to the block.", pending_tx_hash);
 			match sc_block_builder::BlockBuilder::push(&mut block_builder, pending_tx_data) {
 				Ok(()) => {
+					transactions += 1;
 					debug!("[{:?}] Pushed to the block.", pending_tx_hash);
+
+					if let Some(max_block_weight) = self.limits.max_block_weight {
+						if block_builder.block_weight()?.map_or(false, |weight| weight >= max_block_weight) {
+							debug!("Reached the block weight limit, proceed with proposing.");
+							end_reason = EndProposingReason::HitBlockWeightLimit;
+							break;
+						}
+					}
 				}
 				Err(sp_blockchain::Error::ApplyExtrinsicFailed(sp_blockchain::ApplyExtrinsicFailed::Validity(e)))
 						if e.exhausted_resources() => {
+					// the runtime rejects the transactions exceeding the weight limit of the block
+					// as exhausting its resources.
 					if is_first {
 						debug!("[{:?}] Invalid transaction: FullBlock on empty block", pending_tx_hash);
 						unqueue_invalid.push(pending_tx_hash);
-					} else if skipped < MAX_SKIPPED_TRANSACTIONS {
+					} else if skipped < MAX_SKIPPED_TRANSACTIONS || now < soft_deadline {
 						skipped += 1;
 						debug!(
 							"Block seems full, but will try {} more transactions before quitting.",
-							MAX_SKIPPED_TRANSACTIONS - skipped,
+							MAX_SKIPPED_TRANSACTIONS.saturating_sub(skipped),
 						);
 					} else {
 						debug!("Block is full, proceed with proposing.");
+						end_reason = EndProposingReason::HitBlockWeightLimit;
 						break;
 					}
 				}
@@ -232,8 +361,32 @@ impl<Block, B, E, RA, A> ProposerInner<Block, SubstrateClient<B, E, Block, RA>,
 
 		self.transaction_pool.remove_invalid(&unqueue_invalid);
 
+		let block_size = block_builder.estimate_block_size();
+		let block_weight = block_builder.block_weight()?;
 		let block = block_builder.bake()?;
 
+		let fullness = block_size.saturating_mul(100) / self.limits.max_block_size.max(1);
+		debug!(
+			"Proposed block of {} bytes ({}% of the size limit) and weight {:?} with {} transactions, \
+			{} skipped; stopped adding transactions: {}",
+			block_size,
+			fullness,
+			block_weight,
+			transactions,
+			skipped,
+			end_reason.as_str(),
+		);
+		if let Some(metrics) = self.metrics.as_ref() {
+			metrics.block_size.set(block_size as i64);
+			metrics.block_fullness.set(fullness as i64);
+			if let Some(block_weight) = block_weight {
+				metrics.block_weight.set(block_weight.min(i64::max_value() as u64) as i64);
+			}
+			metrics.block_transactions.set(transactions as i64);
+			metrics.skipped_transactions.set(skipped as i64);
+			metrics.end_proposing_reason.with_label_values(&[end_reason.as_str()]).inc();
+		}
+
 		info!("Prepared block for proposing at {} [hash: {:?}; parent_hash: {}; extrinsics: [{}]]",
 			block.header().number(),
 			<Block as BlockT>::Hash::from(block.header().hash()),
@@ -266,9 +419,10 @@ mod tests {
 	use super::*;
 
 	use parking_lot::Mutex;
-	use sp_consensus::Proposer;
-	use substrate_test_runtime_client::{self, runtime::{Extrinsic, Transfer}, AccountKeyring};
+	use sp_consensus::{Environment, Proposer};
+	use substrate_test_runtime_client::{self, runtime::{Extrinsic, Header, Transfer}, AccountKeyring};
 	use sc_transaction_pool::{BasicPool, FullChainApi};
+	use sp_runtime::transaction_validity::TransactionSource;
 
 	fn extrinsic(nonce: u64) -> Extrinsic {
 		Transfer {
@@ -286,13 +440,10 @@ mod tests {
 		let txpool = Arc::new(BasicPool::new(Default::default(), FullChainApi::new(client.clone())));
 
 		futures::executor::block_on(
-			txpool.submit_at(&BlockId::number(0), vec![extrinsic(0), extrinsic(1)])
+			txpool.submit_at(&BlockId::number(0), TransactionSource::External, vec![extrinsic(0), extrinsic(1)])
 		).unwrap();
 
-		let mut proposer_factory = ProposerFactory {
-			client: client.clone(),
-			transaction_pool: txpool.clone(),
-		};
+		let mut proposer_factory = ProposerFactory::new(client.clone(), txpool.clone(), None);
 
 		let cell = Mutex::new(time::Instant::now());
 		let mut proposer = proposer_factory.init_with_now(
@@ -316,4 +467,65 @@ mod tests {
 		assert_eq!(block.extrinsics().len(), 1);
 		assert_eq!(txpool.ready().count(), 2);
 	}
+	#[test]
+	fn should_not_exceed_the_block_size_limit() {
+		// given
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let txpool = Arc::new(BasicPool::new(Default::default(), FullChainApi::new(client.clone())));
+
+		futures::executor::block_on(
+			txpool.submit_at(&BlockId::number(0), TransactionSource::External, (0..10).map(extrinsic).collect::<Vec<_>>())
+		).unwrap();
+
+		// room for the header and exactly 3 transactions.
+		let genesis = client.header(&BlockId::number(0)).unwrap().unwrap();
+		let empty_block_size = <Header as HeaderT>::new(
+			1,
+			Default::default(),
+			Default::default(),
+			genesis.hash(),
+			Default::default(),
+		).encoded_size() + Vec::<Extrinsic>::new().encoded_size();
+		let mut proposer_factory = ProposerFactory::new(client.clone(), txpool.clone(), None);
+		proposer_factory.limits.max_block_size = empty_block_size + 3 * extrinsic(0).encoded_size();
+
+		let mut proposer = proposer_factory.init(&genesis).unwrap();
+
+		// when
+		let block = futures::executor::block_on(
+			proposer.propose(Default::default(), Default::default(), time::Duration::from_secs(10))
+		).unwrap();
+
+		// then
+		assert_eq!(block.extrinsics().len(), 3);
+		assert_eq!(txpool.ready().count(), 10);
+	}
+
+	#[test]
+	fn should_stop_at_the_block_weight_limit() {
+		// given
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let txpool = Arc::new(BasicPool::new(Default::default(), FullChainApi::new(client.clone())));
+
+		futures::executor::block_on(
+			txpool.submit_at(&BlockId::number(0), TransactionSource::External, (0..10).map(extrinsic).collect::<Vec<_>>())
+		).unwrap();
+
+		// each transaction of the test runtime weighs 1.
+		let mut proposer_factory = ProposerFactory::new(client.clone(), txpool.clone(), None);
+		proposer_factory.limits.max_block_weight = Some(4);
+
+		let mut proposer = proposer_factory.init(
+			&client.header(&BlockId::number(0)).unwrap().unwrap(),
+		).unwrap();
+
+		// when
+		let block = futures::executor::block_on(
+			proposer.propose(Default::default(), Default::default(), time::Duration::from_secs(10))
+		).unwrap();
+
+		// then
+		assert_eq!(block.extrinsics().len(), 4);
+		assert_eq!(txpool.ready().count(), 10);
+	}
 }
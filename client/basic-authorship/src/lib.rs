@@ -28,10 +28,7 @@
 //! # let client = Arc::new(substrate_test_runtime_client::new());
 //! # let txpool = Arc::new(BasicPool::new(Default::default(), FullChainApi::new(client.clone())));
 //! // The first step is to create a `ProposerFactory`.
-//! let mut proposer_factory = ProposerFactory {
-//! 	client: client.clone(),
-//! 	transaction_pool: txpool.clone(),
-//! };
+//! let mut proposer_factory = ProposerFactory::new(client.clone(), txpool.clone(), None);
 //!
 //! // From this factory, we create a `Proposer`.
 //! let mut proposer = proposer_factory.init(
@@ -39,7 +36,8 @@
 //! ).unwrap();
 //!
 //! // This `Proposer` allows us to create a block proposition.
-//! // The proposer will grab transactions from the transaction pool, and put them into the block.
+//! // The proposer will grab transactions from the transaction pool, and put them into the block,
+//! // until the block is full or the time is almost up.
 //! let future = proposer.propose(
 //! 	Default::default(),
 //! 	Default::default(),
@@ -53,5 +51,8 @@
 //!
 
 mod basic_authorship;
+mod metrics;
 
-pub use crate::basic_authorship::{ProposerFactory, Proposer};
+pub use crate::basic_authorship::{
+	ProposerFactory, Proposer, ProposalLimits, DEFAULT_MAX_BLOCK_SIZE, DEFAULT_SOFT_DEADLINE_PERCENT,
+};
@@ -0,0 +1,93 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of the blocks proposed.
+
+use prometheus_endpoint::{
+	register, IntCounterVec, IntGauge, Opts, PrometheusError, Registry,
+};
+
+/// Prometheus metrics of a [`ProposerFactory`](crate::ProposerFactory).
+#[derive(Clone)]
+pub(crate) struct Metrics {
+	/// Estimated size of the last block proposed, in bytes.
+	pub block_size: IntGauge,
+	/// Estimated size of the last block proposed, in percent of the size limit.
+	pub block_fullness: IntGauge,
+	/// Total weight of the last block proposed, as reported by the runtime.
+	pub block_weight: IntGauge,
+	/// Number of transactions of the last block proposed, inherents excluded.
+	pub block_transactions: IntGauge,
+	/// Number of transactions skipped while building the last block proposed, because they
+	/// didn't fit in it.
+	pub skipped_transactions: IntGauge,
+	/// Number of blocks proposed, labeled by the reason the proposer stopped pulling
+	/// transactions from the pool.
+	pub end_proposing_reason: IntCounterVec,
+}
+
+impl Metrics {
+	/// Create and register the metrics with the given registry.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			block_size: register(
+				IntGauge::new(
+					"proposer_block_size_bytes",
+					"Estimated size of the last block proposed."
+				)?,
+				registry,
+			)?,
+			block_fullness: register(
+				IntGauge::new(
+					"proposer_block_fullness_percent",
+					"Estimated size of the last block proposed, in percent of the size limit."
+				)?,
+				registry,
+			)?,
+			block_weight: register(
+				IntGauge::new(
+					"proposer_block_weight",
+					"Total weight of the last block proposed, as reported by the runtime."
+				)?,
+				registry,
+			)?,
+			block_transactions: register(
+				IntGauge::new(
+					"proposer_block_transactions",
+					"Number of transactions of the last block proposed."
+				)?,
+				registry,
+			)?,
+			skipped_transactions: register(
+				IntGauge::new(
+					"proposer_skipped_transactions",
+					"Number of transactions that didn't fit in the last block proposed."
+				)?,
+				registry,
+			)?,
+			end_proposing_reason: register(
+				IntCounterVec::new(
+					Opts::new(
+						"proposer_end_proposing_reason_total",
+						"Number of blocks proposed, by reason the proposer stopped adding transactions."
+					),
+					&["reason"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
@@ -142,6 +142,31 @@ where
 		}
 	}
 
+	/// The total weight of the extrinsics pushed so far, as reported by the runtime, or `None`
+	/// if the runtime doesn't report it.
+	pub fn block_weight(&self) -> Result<Option<u64>, ApiErrorFor<A, Block>> {
+		let reports_weight = self
+			.api
+			.has_api_with::<dyn BlockBuilderApi<Block, Error = ApiErrorFor<A, Block>>, _>(
+				&self.block_id,
+				|version| version >= 5,
+			)?;
+
+		if reports_weight {
+			self.api.block_weight_with_context(&self.block_id, ExecutionContext::BlockConstruction)
+				.map(Some)
+		} else {
+			Ok(None)
+		}
+	}
+
+	/// Estimate the size of the block in the current state, i.e. the encoded size of its header
+	/// and of the extrinsics pushed so far. The digest items added when the block is finalized
+	/// and sealed are not accounted for.
+	pub fn estimate_block_size(&self) -> usize {
+		self.header.encoded_size() + self.extrinsics.encoded_size()
+	}
+
 	/// Consume the builder to return a valid `Block` containing all pushed extrinsics.
 	pub fn bake(mut self) -> Result<Block, ApiErrorFor<A, Block>> {
 		self.bake_impl()?;
@@ -17,15 +17,15 @@
 //! Substrate chain configurations.
 
 use std::borrow::Cow;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::fs::File;
 use std::path::PathBuf;
 use std::rc::Rc;
 use serde::{Serialize, Deserialize};
-use sp_core::storage::{StorageKey, StorageData, ChildInfo, Storage, StorageChild};
+use sp_core::storage::{StorageKey, StorageData, ChildInfo, Storage, StorageChild, StorageMap};
 use sp_runtime::BuildStorage;
 use serde_json as json;
-use crate::RuntimeGenesis;
+use crate::{RuntimeGenesis, Extension};
 use sc_network::Multiaddr;
 use sc_telemetry::TelemetryEndpoints;
 
@@ -33,6 +33,7 @@ enum GenesisSource<G> {
 	File(PathBuf),
 	Binary(Cow<'static, [u8]>),
 	Factory(Rc<dyn Fn() -> G>),
+	Storage(Storage),
 }
 
 impl<G> Clone for GenesisSource<G> {
@@ -41,6 +42,7 @@ impl<G> Clone for GenesisSource<G> {
 			GenesisSource::File(ref path) => GenesisSource::File(path.clone()),
 			GenesisSource::Binary(ref d) => GenesisSource::Binary(d.clone()),
 			GenesisSource::Factory(ref f) => GenesisSource::Factory(f.clone()),
+			GenesisSource::Storage(ref s) => GenesisSource::Storage(s.clone()),
 		}
 	}
 }
@@ -66,6 +68,7 @@ impl<G: RuntimeGenesis> GenesisSource<G> {
 				Ok(genesis.genesis)
 			},
 			GenesisSource::Factory(f) => Ok(Genesis::Runtime(f())),
+			GenesisSource::Storage(storage) => Ok(Genesis::Raw(storage.clone().into())),
 		}
 	}
 }
@@ -121,6 +124,31 @@ struct RawGenesis {
 	pub children: HashMap<StorageKey, ChildRawStorage>,
 }
 
+impl From<Storage> for RawGenesis {
+	fn from(storage: Storage) -> Self {
+		let top = storage.top.into_iter()
+			.map(|(k, v)| (StorageKey(k), StorageData(v)))
+			.collect();
+		let children = storage.children.into_iter()
+			.map(|(sk, child)| {
+				let info = child.child_info.as_ref();
+				let (info, ci_type) = info.info();
+				(
+					StorageKey(sk),
+					ChildRawStorage {
+						data: child.data.into_iter()
+							.map(|(k, v)| (StorageKey(k), StorageData(v)))
+							.collect(),
+						child_info: info.to_vec(),
+						child_type: ci_type,
+					},
+			)})
+			.collect();
+
+		RawGenesis { top, children }
+	}
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 #[serde(deny_unknown_fields)]
@@ -204,6 +232,14 @@ impl<G, E> ChainSpec<G, E> {
 		self.client_spec.properties.as_ref().unwrap_or(&json::map::Map::new()).clone()
 	}
 
+	/// Properties of the chain decoded into a strongly-typed structure.
+	///
+	/// Missing 'properties' are decoded from an empty JSON object.
+	pub fn properties_as<T: serde::de::DeserializeOwned>(&self) -> Result<T, String> {
+		json::from_value(json::Value::Object(self.properties()))
+			.map_err(|e| format!("Error decoding chain spec properties: {}", e))
+	}
+
 	/// Add a bootnode to the list.
 	pub fn add_boot_node(&mut self, addr: Multiaddr) {
 		self.client_spec.boot_nodes.push(addr.to_string())
@@ -214,6 +250,11 @@ impl<G, E> ChainSpec<G, E> {
 		&self.client_spec.extensions
 	}
 
+	/// Returns a reference to the chain spec extension of the given type, if defined.
+	pub fn extension<T: 'static>(&self) -> Option<&T> where E: Extension {
+		self.client_spec.extensions.get::<T>()
+	}
+
 	/// Create hardcoded spec.
 	pub fn from_genesis<F: Fn() -> G + 'static>(
 		name: &str,
@@ -280,29 +321,7 @@ impl<G: RuntimeGenesis, E: serde::Serialize> ChainSpec<G, E> {
 
 		};
 		let genesis = match (raw, self.genesis.resolve()?) {
-			(true, Genesis::Runtime(g)) => {
-				let storage = g.build_storage()?;
-				let top = storage.top.into_iter()
-					.map(|(k, v)| (StorageKey(k), StorageData(v)))
-					.collect();
-				let children = storage.children.into_iter()
-					.map(|(sk, child)| {
-						let info = child.child_info.as_ref();
-						let (info, ci_type) = info.info();
-						(
-							StorageKey(sk),
-							ChildRawStorage {
-								data: child.data.into_iter()
-									.map(|(k, v)| (StorageKey(k), StorageData(v)))
-									.collect(),
-								child_info: info.to_vec(),
-								child_type: ci_type,
-							},
-					)})
-					.collect();
-
-				Genesis::Raw(RawGenesis { top, children })
-			},
+			(true, Genesis::Runtime(g)) => Genesis::Raw(g.build_storage()?.into()),
 			(_, genesis) => genesis,
 		};
 		let container = Container {
@@ -314,6 +333,95 @@ impl<G: RuntimeGenesis, E: serde::Serialize> ChainSpec<G, E> {
 	}
 }
 
+impl<G: RuntimeGenesis, E> ChainSpec<G, E> {
+	/// Convert into a spec whose genesis is the raw storage built from the runtime genesis config.
+	///
+	/// The genesis of the returned spec no longer depends on the runtime genesis config
+	/// code, so it can be exported and pinned.
+	pub fn into_raw(self) -> Result<Self, String> {
+		let storage = (&self).build_storage()?;
+		Ok(ChainSpec {
+			client_spec: self.client_spec,
+			genesis: GenesisSource::Storage(storage),
+		})
+	}
+
+	/// Compare the genesis storage of this spec with the one of `other`.
+	pub fn diff_storage<G2: RuntimeGenesis, E2>(
+		&self,
+		other: &ChainSpec<G2, E2>,
+	) -> Result<StorageDiff, String> {
+		Ok(diff_storage(&self.build_storage()?, &other.build_storage()?))
+	}
+}
+
+/// A storage key whose value differs between two genesis storages.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StorageValueDiff {
+	/// The storage key.
+	pub key: StorageKey,
+	/// Value in the first storage, `None` if the key is not set.
+	pub left: Option<StorageData>,
+	/// Value in the second storage, `None` if the key is not set.
+	pub right: Option<StorageData>,
+}
+
+/// Differences between two genesis storages.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct StorageDiff {
+	/// Differences of the top trie, ordered by key.
+	pub top: Vec<StorageValueDiff>,
+	/// Differences of the child tries, by child storage key.
+	pub children: BTreeMap<StorageKey, Vec<StorageValueDiff>>,
+}
+
+impl StorageDiff {
+	/// Returns true if both storages are identical.
+	pub fn is_empty(&self) -> bool {
+		self.top.is_empty() && self.children.is_empty()
+	}
+}
+
+/// Compare two genesis storages.
+pub fn diff_storage(left: &Storage, right: &Storage) -> StorageDiff {
+	let empty = StorageMap::new();
+	let child_keys: BTreeSet<_> = left.children.keys().chain(right.children.keys()).collect();
+	let children = child_keys.into_iter()
+		.filter_map(|child_key| {
+			let left = left.children.get(child_key).map(|child| &child.data).unwrap_or(&empty);
+			let right = right.children.get(child_key).map(|child| &child.data).unwrap_or(&empty);
+			let diff = diff_storage_map(left, right);
+			if diff.is_empty() {
+				None
+			} else {
+				Some((StorageKey(child_key.clone()), diff))
+			}
+		})
+		.collect();
+
+	StorageDiff {
+		top: diff_storage_map(&left.top, &right.top),
+		children,
+	}
+}
+
+fn diff_storage_map(left: &StorageMap, right: &StorageMap) -> Vec<StorageValueDiff> {
+	let keys: BTreeSet<_> = left.keys().chain(right.keys()).collect();
+	keys.into_iter()
+		.filter_map(|key| {
+			let (left, right) = (left.get(key), right.get(key));
+			if left == right {
+				return None;
+			}
+			Some(StorageValueDiff {
+				key: StorageKey(key.clone()),
+				left: left.cloned().map(StorageData),
+				right: right.cloned().map(StorageData),
+			})
+		})
+		.collect()
+}
+
 #[cfg(test)]
 mod tests {
 	use super::*;
@@ -363,4 +471,66 @@ mod tests {
 
 		assert_eq!(spec.extensions().my_property, "Test Extension");
 	}
+
+	fn test_spec(values: &'static [(&'static str, &'static str)]) -> TestSpec {
+		TestSpec::from_genesis(
+			"Test",
+			"test",
+			move || Genesis(values.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()),
+			Vec::new(),
+			None,
+			None,
+			None,
+			None,
+		)
+	}
+
+	#[test]
+	fn should_convert_to_raw_spec() {
+		let spec = test_spec(&[("a", "1"), ("b", "2")]);
+		let raw = spec.clone().into_raw().unwrap();
+
+		let json = |spec: TestSpec, raw|
+			json::from_str::<json::Value>(&spec.to_json(raw).unwrap()).unwrap();
+		let raw_json = json(raw, false);
+		assert!(raw_json["genesis"]["raw"].is_object());
+		assert_eq!(raw_json, json(spec, true));
+	}
+
+	#[test]
+	fn should_diff_genesis_storage() {
+		let spec1 = test_spec(&[("a", "1"), ("b", "2"), ("c", "3")]);
+		let spec2 = test_spec(&[("a", "1"), ("b", "4"), ("d", "5")]);
+
+		assert!(spec1.diff_storage(&spec1.clone().into_raw().unwrap()).unwrap().is_empty());
+
+		let value = |v: &str| Some(StorageData(v.as_bytes().to_vec()));
+		let diff = spec1.diff_storage(&spec2).unwrap();
+		assert_eq!(diff.top, vec![
+			StorageValueDiff { key: StorageKey(b"b".to_vec()), left: value("2"), right: value("4") },
+			StorageValueDiff { key: StorageKey(b"c".to_vec()), left: value("3"), right: None },
+			StorageValueDiff { key: StorageKey(b"d".to_vec()), left: None, right: value("5") },
+		]);
+		assert!(diff.children.is_empty());
+	}
+
+	#[derive(Debug, PartialEq, Deserialize)]
+	#[serde(rename_all = "camelCase")]
+	struct TokenProperties {
+		token_decimals: u8,
+		token_symbol: String,
+	}
+
+	#[test]
+	fn should_decode_typed_properties() {
+		let spec = TestSpec::from_json_bytes(Cow::Owned(
+			include_bytes!("../res/chain_spec.json").to_vec()
+		)).unwrap();
+
+		assert_eq!(
+			spec.properties_as::<TokenProperties>(),
+			Ok(TokenProperties { token_decimals: 15, token_symbol: "FIR".into() }),
+		);
+		assert!(spec.properties_as::<Extension1>().is_err());
+	}
 }
@@ -40,6 +40,11 @@
 //! pub type MyChainSpec<G> = ChainSpec<G, MyExtension>;
 //! ```
 //!
+//! The extension is accessed with [`ChainSpec::extensions`](./struct.ChainSpec.html#method.extensions),
+//! or, for a single field, with [`ChainSpec::extension`](./struct.ChainSpec.html#method.extension).
+//! The loosely-typed `properties` can be decoded into a structure with
+//! [`ChainSpec::properties_as`](./struct.ChainSpec.html#method.properties_as).
+//!
 //! Some parameters may require different values depending on the
 //! current blockchain height (a.k.a. forks). You can use `ChainSpecGroup`
 //! macro and provided [`Forks`](./struct.Forks.html) structure to put
@@ -111,7 +116,9 @@
 mod chain_spec;
 mod extension;
 
-pub use chain_spec::{ChainSpec, Properties, NoExtension};
+pub use chain_spec::{
+	ChainSpec, Properties, NoExtension, StorageDiff, StorageValueDiff, diff_storage,
+};
 pub use extension::{Group, Fork, Forks, Extension};
 pub use sc_chain_spec_derive::{ChainSpecExtension, ChainSpecGroup};
 
@@ -0,0 +1,106 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Configuration files given with `--config`.
+//!
+//! A configuration file is a TOML table mapping long option names to their values. It is turned
+//! into command line arguments which are parsed together with the explicit ones, so that the
+//! file goes through the exact same validation as the command line.
+
+use std::{fs, path::Path};
+use toml::{Value, value::Table};
+
+use crate::error;
+
+/// Reads the configuration file at `path` and returns the command line arguments it stands for.
+///
+/// Options for which `is_explicit` returns `true` were given on the command line and are skipped.
+pub fn arguments(path: &Path, is_explicit: impl Fn(&str) -> bool) -> error::Result<Vec<String>> {
+	let content = fs::read_to_string(path).map_err(|e| error::Error::Input(
+		format!("Cannot read the configuration file {:?}: {}", path, e)
+	))?;
+	parse_arguments(&content, is_explicit).map_err(|e| error::Error::Input(
+		format!("Invalid configuration file {:?}: {}", path, e)
+	))
+}
+
+fn parse_arguments(content: &str, is_explicit: impl Fn(&str) -> bool) -> Result<Vec<String>, String> {
+	let table: Table = toml::from_str(content).map_err(|e| e.to_string())?;
+	let mut arguments = Vec::new();
+
+	for (option, value) in table {
+		if option == "config" {
+			return Err("`config` cannot be set in a configuration file".into());
+		}
+		if is_explicit(&option) {
+			continue;
+		}
+
+		match value {
+			Value::Boolean(true) => arguments.push(format!("--{}", option)),
+			Value::Boolean(false) => {},
+			// Every value is given as a separate occurrence of the option.
+			Value::Array(values) => for value in values {
+				arguments.push(format!("--{}={}", option, scalar(&option, value)?));
+			},
+			value => arguments.push(format!("--{}={}", option, scalar(&option, value)?)),
+		}
+	}
+
+	Ok(arguments)
+}
+
+fn scalar(option: &str, value: Value) -> Result<String, String> {
+	match value {
+		Value::String(value) => Ok(value),
+		Value::Integer(value) => Ok(value.to_string()),
+		Value::Float(value) => Ok(value.to_string()),
+		_ => Err(format!("Invalid value for `{}`: {}", option, value)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn converts_options_to_arguments() {
+		let content = r#"
+			name = "node"
+			rpc-port = 9933
+			validator = true
+			no-grandpa = false
+			bootnodes = ["/ip4/127.0.0.1/tcp/30333", "/ip4/127.0.0.1/tcp/30334"]
+		"#;
+
+		assert_eq!(
+			parse_arguments(content, |option| option == "name").unwrap(),
+			vec![
+				"--bootnodes=/ip4/127.0.0.1/tcp/30333",
+				"--bootnodes=/ip4/127.0.0.1/tcp/30334",
+				"--rpc-port=9933",
+				"--validator",
+			],
+		);
+	}
+
+	#[test]
+	fn rejects_invalid_options() {
+		assert!(parse_arguments("config = \"other.toml\"", |_| false).is_err());
+		assert!(parse_arguments("[network]\nport = 1", |_| false).is_err());
+		assert!(parse_arguments("name = ", |_| false).is_err());
+	}
+}
@@ -16,6 +16,9 @@
 
 //! Initialization errors.
 
+/// Exit code of the node when an essential task of the service failed.
+pub const ESSENTIAL_TASK_FAILED_EXIT_CODE: i32 = 2;
+
 /// Result type alias for the CLI.
 pub type Result<T> = std::result::Result<T, Error>;
 
@@ -36,6 +39,9 @@ pub enum Error {
 	/// Invalid listen multiaddress
 	#[display(fmt="Invalid listen multiaddress")]
 	InvalidListenMultiaddress,
+	/// Invalid outbound proxy multiaddress
+	#[display(fmt="Invalid outbound proxy multiaddress")]
+	InvalidProxyMultiaddress,
 	/// Other uncategorized error.
 	#[from(ignore)]
 	Other(String),
@@ -49,6 +55,16 @@ impl std::convert::From<String> for Error {
 	}
 }
 
+impl Error {
+	/// Returns the exit code of the node failing with this error.
+	pub fn exit_code(&self) -> i32 {
+		match self {
+			Error::Service(sc_service::Error::EssentialTaskFailed) => ESSENTIAL_TASK_FAILED_EXIT_CODE,
+			_ => 1,
+		}
+	}
+}
+
 impl std::error::Error for Error {
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
 		match self {
@@ -58,6 +74,7 @@ impl std::error::Error for Error {
 			Error::Client(ref err) => Some(err),
 			Error::Input(_) => None,
 			Error::InvalidListenMultiaddress => None,
+			Error::InvalidProxyMultiaddress => None,
 			Error::Other(_) => None,
 		}
 	}
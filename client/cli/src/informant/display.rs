@@ -17,7 +17,7 @@
 use ansi_term::Colour;
 use sc_client_api::ClientInfo;
 use log::info;
-use sc_network::SyncState;
+use sc_network::SyncStateEvent;
 use sp_runtime::traits::{Block as BlockT, CheckedDiv, NumberFor, Zero, Saturating};
 use sc_service::NetworkStatus;
 use std::{convert::{TryFrom, TryInto}, fmt, time};
@@ -60,10 +60,11 @@ impl<B: BlockT> InformantDisplay<B> {
 		self.last_update = time::Instant::now();
 		self.last_number = Some(best_number);
 
-		let (status, target) = match (net_status.sync_state, net_status.best_seen_block) {
-			(SyncState::Idle, _) => ("Idle".into(), "".into()),
-			(SyncState::Downloading, None) => (format!("Syncing{}", speed), "".into()),
-			(SyncState::Downloading, Some(n)) => (format!("Syncing{}", speed), format!(", target=#{}", n)),
+		let (status, target) = match net_status.sync_progress {
+			SyncStateEvent::Starting | SyncStateEvent::Idle => ("Idle".into(), "".into()),
+			SyncStateEvent::Downloading { target, .. } =>
+				(format!("Syncing{}", speed), format!(", target=#{}", target)),
+			SyncStateEvent::Importing { queued } => (format!("Importing{}", speed), format!(", {} queued", queued)),
 		};
 
 		info!(
@@ -0,0 +1,304 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Implementation of the `key` subcommands.
+
+use std::{convert::TryFrom, io::{Read, stdin}, path::Path};
+use bip39::{Language, Mnemonic, MnemonicType};
+use sp_core::{
+	crypto::{KeyTypeId, Pair, Public, Ss58AddressFormat, Ss58Codec, set_default_ss58_version},
+	ed25519, sr25519, ecdsa, hexdisplay::HexDisplay, bytes::from_hex,
+};
+use sp_runtime::{MultiSigner, traits::IdentifyAccount};
+
+use crate::error;
+use crate::params::{CryptoScheme, KeyParams, MessageParams};
+
+/// Calls the given generic function with the `Pair` type of the given scheme.
+macro_rules! with_crypto_scheme {
+	($scheme:expr, $method:ident($($params:expr),*)) => {
+		match $scheme {
+			CryptoScheme::Ed25519 => $method::<ed25519::Pair>($($params),*),
+			CryptoScheme::Sr25519 => $method::<sr25519::Pair>($($params),*),
+			CryptoScheme::Ecdsa => $method::<ecdsa::Pair>($($params),*),
+		}
+	};
+}
+
+/// Generates a random key and prints its details.
+pub fn generate(words: usize, params: &KeyParams) -> error::Result<()> {
+	let words = MnemonicType::for_word_count(words).map_err(|_| error::Error::Input(
+		"Invalid number of words given for phrase: must be 12/15/18/21/24".into()
+	))?;
+	let mnemonic = Mnemonic::new(words, Language::English);
+	inspect(mnemonic.phrase(), params)
+}
+
+/// Prints the details of the key with the given URI.
+pub fn inspect(uri: &str, params: &KeyParams) -> error::Result<()> {
+	let network = params.network.as_ref()
+		.map(|network| Ss58AddressFormat::try_from(network.as_str())
+			.map_err(|_| error::Error::Input(format!("Invalid network name {}", network)))
+		)
+		.transpose()?;
+	if let Some(network) = network {
+		set_default_ss58_version(network);
+	}
+
+	let password = params.password.as_ref().map(String::as_str);
+	println!("{}", with_crypto_scheme!(params.scheme, describe_uri(uri, password, network))?);
+	Ok(())
+}
+
+/// Inserts the key with the given secret URI into the keystore at the given path.
+///
+/// The key is derived with the password of the keystore, like the keys generated by the node.
+pub fn insert(
+	suri: &str,
+	key_type: &str,
+	scheme: CryptoScheme,
+	keystore_path: &Path,
+	password: Option<sp_core::crypto::Protected<String>>,
+) -> error::Result<()> {
+	let key_type = KeyTypeId::try_from(key_type)
+		.map_err(|_| error::Error::Input(format!("Invalid key type {}", key_type)))?;
+	let keystore = sc_keystore::Store::open(keystore_path, password)
+		.map_err(|e| error::Error::Other(format!("Cannot open the keystore: {}", e)))?;
+
+	let public = with_crypto_scheme!(scheme, insert_into_keystore(&keystore, key_type, suri))?;
+	println!("Inserted key 0x{} into {:?}", HexDisplay::from(&public), keystore_path);
+	Ok(())
+}
+
+/// Changes the password of the keystore at the given path, re-encrypting all its keys.
+pub fn rotate_password(
+	keystore_path: &Path,
+	password: Option<sp_core::crypto::Protected<String>>,
+	new_password: sp_core::crypto::Protected<String>,
+) -> error::Result<()> {
+	let keystore = sc_keystore::Store::open(keystore_path, password)
+		.map_err(|e| error::Error::Other(format!("Cannot open the keystore: {}", e)))?;
+	keystore.write().rotate_password(new_password)
+		.map_err(|e| error::Error::Other(format!("Cannot change the keystore password: {}", e)))?;
+	println!("Changed the password of {:?}", keystore_path);
+	Ok(())
+}
+
+/// Reads the message to sign or verify, from the command line or from stdin.
+pub fn read_message(params: &MessageParams) -> error::Result<Vec<u8>> {
+	let message = match &params.message {
+		Some(message) => message.as_bytes().to_vec(),
+		None => {
+			let mut message = Vec::new();
+			stdin().lock().read_to_end(&mut message)?;
+			message
+		},
+	};
+
+	if params.hex {
+		let message = String::from_utf8(message)
+			.map_err(|_| error::Error::Input("Invalid hex message".into()))?;
+		from_hex(message.trim())
+			.map_err(|e| error::Error::Input(format!("Invalid hex message: {}", e)))
+	} else {
+		Ok(message)
+	}
+}
+
+/// Signs the message with the given secret key URI and prints the signature.
+pub fn sign(suri: &str, message: &[u8], params: &KeyParams) -> error::Result<()> {
+	let password = params.password.as_ref().map(String::as_str);
+	let signature = with_crypto_scheme!(params.scheme, sign_message(suri, password, message))?;
+	println!("0x{}", HexDisplay::from(&signature));
+	Ok(())
+}
+
+/// Verifies the signature of the message by the given public key.
+pub fn verify(signature: &str, uri: &str, message: &[u8], params: &KeyParams) -> error::Result<()> {
+	let signature = from_hex(signature)
+		.map_err(|e| error::Error::Input(format!("Invalid hex signature: {}", e)))?;
+	if with_crypto_scheme!(params.scheme, verify_message(&signature, uri, message))? {
+		println!("Signature verifies correctly.");
+		Ok(())
+	} else {
+		Err(error::Error::Input("Signature invalid.".into()))
+	}
+}
+
+fn sign_message<P: Pair>(
+	suri: &str,
+	password: Option<&str>,
+	message: &[u8],
+) -> error::Result<Vec<u8>> {
+	let pair = P::from_string(suri, password)
+		.map_err(|_| error::Error::Input("Invalid secret key URI".into()))?;
+	Ok(pair.sign(message).as_ref().to_vec())
+}
+
+fn verify_message<P: Pair>(
+	signature: &[u8],
+	uri: &str,
+	message: &[u8],
+) -> error::Result<bool> where
+	P::Signature: Default + AsMut<[u8]>,
+{
+	let mut typed_signature = P::Signature::default();
+	if typed_signature.as_ref().len() != signature.len() {
+		return Err(error::Error::Input(format!(
+			"Invalid signature length: expected {} bytes, got {}",
+			typed_signature.as_ref().len(),
+			signature.len(),
+		)));
+	}
+	typed_signature.as_mut().copy_from_slice(signature);
+
+	Ok(P::verify(&typed_signature, message, &public_from_uri::<P::Public>(uri)?))
+}
+
+/// Parses a public key given as hex, as SS58 address or as public key URI.
+fn public_from_uri<P: Public>(uri: &str) -> error::Result<P> {
+	if uri.starts_with("0x") {
+		let bytes = from_hex(uri)
+			.map_err(|e| error::Error::Input(format!("Invalid hex public key: {}", e)))?;
+		let mut public = P::default();
+		if public.as_ref().len() != bytes.len() {
+			return Err(error::Error::Input("Invalid public key length".into()));
+		}
+		public.as_mut().copy_from_slice(&bytes);
+		Ok(public)
+	} else {
+		P::from_string(uri).map_err(|_| error::Error::Input("Invalid public key URI".into()))
+	}
+}
+
+fn insert_into_keystore<P: Pair>(
+	keystore: &sc_keystore::KeyStorePtr,
+	key_type: KeyTypeId,
+	suri: &str,
+) -> error::Result<Vec<u8>> {
+	let pair = keystore.read().insert_by_type::<P>(key_type, suri)
+		.map_err(|e| error::Error::Input(format!("Cannot insert the key: {}", e)))?;
+	Ok(pair.public().as_ref().to_vec())
+}
+
+/// Describes the key with the given URI, which can be a secret phrase, a secret key URI or a
+/// public key URI.
+fn describe_uri<P: Pair>(
+	uri: &str,
+	password: Option<&str>,
+	network: Option<Ss58AddressFormat>,
+) -> error::Result<String> where
+	P::Public: Into<MultiSigner> + Ss58Codec,
+{
+	if let Ok((pair, seed)) = P::from_phrase(uri, password) {
+		Ok(describe_secret::<P>("Secret phrase", uri, Some(seed), pair.public()))
+	} else if let Ok((pair, seed)) = P::from_string_with_seed(uri, password) {
+		Ok(describe_secret::<P>("Secret Key URI", uri, seed, pair.public()))
+	} else if let Ok((public, version)) = P::Public::from_string_with_version(uri) {
+		let version = network.unwrap_or(version);
+		Ok(format!(
+			"Public Key URI `{}` is account:\n  \
+			Network ID/version: {}\n  \
+			Public key (hex):   0x{}\n  \
+			Account ID:         0x{}\n  \
+			SS58 Address:       {}",
+			uri,
+			String::from(version),
+			HexDisplay::from(&public.as_ref()),
+			HexDisplay::from(&account_id(public.clone()).as_ref()),
+			public.to_ss58check_with_version(version),
+		))
+	} else {
+		Err(error::Error::Input("Invalid phrase/URI given".into()))
+	}
+}
+
+fn describe_secret<P: Pair>(
+	kind: &str,
+	uri: &str,
+	seed: Option<P::Seed>,
+	public: P::Public,
+) -> String where
+	P::Public: Into<MultiSigner>,
+{
+	let account_id = account_id(public.clone());
+	let seed = seed
+		.map(|seed| format!("0x{}", HexDisplay::from(&seed.as_ref())))
+		.unwrap_or_else(|| "n/a".into());
+	format!(
+		"{} `{}` is account:\n  \
+		Secret seed:      {}\n  \
+		Public key (hex): 0x{}\n  \
+		Account ID:       0x{}\n  \
+		SS58 Address:     {}",
+		kind,
+		uri,
+		seed,
+		HexDisplay::from(&public.as_ref()),
+		HexDisplay::from(&account_id.as_ref()),
+		account_id.to_ss58check(),
+	)
+}
+
+fn account_id<Public: Into<MultiSigner>>(public: Public) -> sp_runtime::AccountId32 {
+	public.into().into_account()
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn describes_secret_and_public_uris_consistently() {
+		let secret = describe_uri::<sr25519::Pair>("//Alice", None, None).unwrap();
+		let public = sr25519::Pair::from_string("//Alice", None).unwrap().public();
+		assert!(secret.starts_with("Secret Key URI `//Alice` is account:"));
+		assert!(secret.contains(&format!("SS58 Address:     {}", public.to_ss58check())));
+
+		let described = describe_uri::<sr25519::Pair>(&public.to_ss58check(), None, None).unwrap();
+		let public_hex = format!("Public key (hex):   0x{}", HexDisplay::from(&public.0));
+		assert!(described.contains(&public_hex));
+	}
+
+	#[test]
+	fn verifies_signed_messages() {
+		let message = b"message";
+		let signature = sign_message::<ed25519::Pair>("//Alice", None, message).unwrap();
+		let public = ed25519::Pair::from_string("//Alice", None).unwrap().public();
+		let public_hex = format!("0x{}", HexDisplay::from(&public.0));
+
+		assert!(verify_message::<ed25519::Pair>(&signature, &public_hex, message).unwrap());
+		assert!(verify_message::<ed25519::Pair>(&signature, &public.to_ss58check(), message).unwrap());
+		assert!(!verify_message::<ed25519::Pair>(&signature, &public_hex, b"other").unwrap());
+		assert!(verify_message::<ed25519::Pair>(&signature[1..], &public_hex, message).is_err());
+	}
+
+	#[test]
+	fn rejects_invalid_uris() {
+		assert!(describe_uri::<ed25519::Pair>("not a key", None, None).is_err());
+	}
+
+	#[test]
+	fn inserts_keys_into_the_keystore() {
+		let path = tempfile::tempdir().unwrap();
+		insert("//Alice", "babe", CryptoScheme::Sr25519, path.path(), None).unwrap();
+
+		let keystore = sc_keystore::Store::open(path.path(), None).unwrap();
+		let public = sr25519::Pair::from_string("//Alice", None).unwrap().public();
+		let key_type = KeyTypeId(*b"babe");
+		assert!(keystore.read().key_pair_by_type::<sr25519::Pair>(&public, key_type).is_ok());
+	}
+}
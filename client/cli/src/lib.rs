@@ -23,14 +23,16 @@
 mod traits;
 mod params;
 mod execution_strategy;
+mod key;
+mod config_file;
 pub mod error;
 pub mod informant;
 
 use sc_client_api::execution_extensions::ExecutionStrategies;
 use sc_service::{
-	config::{Configuration, DatabaseConfig},
+	config::{Configuration, DatabaseConfig, PrometheusConfig},
 	ServiceBuilderCommand,
-	RuntimeGenesis, ChainSpecExtension, PruningMode, ChainSpec,
+	RuntimeGenesis, ChainSpecExtension, PruningMode, ChainSpec, PurgeTarget,
 };
 use sc_network::{
 	self,
@@ -42,8 +44,9 @@ use sc_network::{
 use sp_core::H256;
 
 use std::{
-	io::{Write, Read, Seek, Cursor, stdin, stdout, ErrorKind}, iter, fmt::Debug, fs::{self, File},
-	net::{Ipv4Addr, SocketAddr}, path::{Path, PathBuf}, str::FromStr, pin::Pin, task::Poll
+	io::{Write, Read, BufReader, BufWriter, stdin, stdout, ErrorKind}, iter, fmt::Debug, fs::{self, File},
+	net::{Ipv4Addr, SocketAddr}, path::{Path, PathBuf}, str::FromStr, pin::Pin, task::Poll,
+	time::Duration,
 };
 
 use names::{Generator, Name};
@@ -52,11 +55,14 @@ use structopt::{StructOpt, StructOptInternal, clap::AppSettings};
 #[doc(hidden)]
 pub use structopt::clap::App;
 use params::{
-	RunCmd, PurgeChainCmd, RevertCmd, ImportBlocksCmd, ExportBlocksCmd, BuildSpecCmd,
+	RunCmd, PurgeChainCmd, MigrateDatabaseCmd, RevertCmd, ImportBlocksCmd, ExportBlocksCmd,
+	BuildSpecCmd,
 	NetworkConfigurationParams, MergeParameters, TransactionPoolParams,
-	NodeKeyParams, NodeKeyType, Cors, CheckBlockCmd,
+	NodeKeyParams, NodeKeyType, Cors, CheckBlockCmd, KeyCmd, KeystoreParams, DbCmd,
+};
+pub use params::{
+	NoCustom, CoreParams, SharedParams, ImportParams, ExecutionStrategy, CryptoScheme, Database,
 };
-pub use params::{NoCustom, CoreParams, SharedParams, ImportParams, ExecutionStrategy};
 pub use traits::GetSharedParams;
 use app_dirs::{AppInfo, AppDataType};
 use log::info;
@@ -70,6 +76,8 @@ use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
 const DEFAULT_NETWORK_CONFIG_PATH : &'static str = "network";
 /// default sub directory to store database
 const DEFAULT_DB_CONFIG_PATH : &'static str = "db";
+/// default sub directory to store the ParityDb database
+const DEFAULT_PARITY_DB_CONFIG_PATH : &'static str = "paritydb";
 /// default sub directory for the key store
 const DEFAULT_KEYSTORE_CONFIG_PATH : &'static str =  "keystore";
 
@@ -206,16 +214,39 @@ where
 	);
 
 	sp_panic_handler::set(version.support_url, &full_version);
-	let matches = CoreParams::<CC, RP>::clap()
+	let long_version = full_version + "\n";
+	let app = || CoreParams::<CC, RP>::clap()
 		.name(version.executable_name)
 		.author(version.author)
 		.about(version.description)
-		.version(&(full_version + "\n")[..])
+		.version(&long_version[..])
 		.setting(AppSettings::GlobalVersion)
 		.setting(AppSettings::ArgsNegateSubcommands)
-		.setting(AppSettings::SubcommandsNegateReqs)
-		.get_matches_from(args);
-	let cli_args = CoreParams::<CC, RP>::from_clap(&matches);
+		.setting(AppSettings::SubcommandsNegateReqs);
+	let args = args.into_iter().map(Into::into).collect::<Vec<std::ffi::OsString>>();
+	let matches = app().get_matches_from(args.iter());
+	let mut cli_args = CoreParams::<CC, RP>::from_clap(&matches);
+
+	// The options of the configuration file are given before the explicit ones, which are
+	// skipped from the file.
+	if let params::CoreParams::Run(MergeParameters {
+		left: RunCmd { config_file: Some(ref path), .. }, ..
+	}) = cli_args {
+		// The options of the file are named after their long flag, while the matches are
+		// looked up by argument name, which is also set by the short flags.
+		let app = app();
+		let is_explicit = |option: &str| app.p.opts().map(|opt| (opt.b.name, opt.s.long))
+			.chain(app.p.flags().map(|flag| (flag.b.name, flag.s.long)))
+			.find(|(_, long)| *long == Some(option))
+			.map_or(false, |(name, _)| matches.occurrences_of(name) > 0);
+		let file_args = config_file::arguments(path, is_explicit).unwrap_or_else(|e|
+			clap::Error::with_description(&e.to_string(), clap::ErrorKind::InvalidValue).exit()
+		);
+		let args = args.iter().take(1).cloned()
+			.chain(file_args.into_iter().map(Into::into))
+			.chain(args.iter().skip(1).cloned());
+		cli_args = CoreParams::<CC, RP>::from_clap(&app().get_matches_from(args));
+	}
 	fdlimit::raise_fd_limit();
 
 	let args = match cli_args {
@@ -237,9 +268,18 @@ where
 		params::CoreParams::PurgeChain(params) => ParseAndPrepare::PurgeChain(
 			ParseAndPreparePurge { params, version }
 		),
+		params::CoreParams::MigrateDatabase(params) => ParseAndPrepare::MigrateDatabase(
+			ParseAndPrepareMigrateDatabase { params, version }
+		),
+		params::CoreParams::Db(params) => ParseAndPrepare::Db(
+			ParseAndPrepareDb { params, version }
+		),
 		params::CoreParams::Revert(params) => ParseAndPrepare::RevertChain(
 			ParseAndPrepareRevert { params, version }
 		),
+		params::CoreParams::Key(params) => ParseAndPrepare::Key(
+			ParseAndPrepareKey { params, version }
+		),
 		params::CoreParams::Custom(params) => ParseAndPrepare::CustomCommand(params),
 	};
 	init_logger(args.shared_params().and_then(|p| p.log.as_ref()).map(|v| v.as_ref()).unwrap_or(""));
@@ -272,8 +312,14 @@ pub enum ParseAndPrepare<'a, CC, RP> {
 	CheckBlock(CheckBlock<'a>),
 	/// Command ready to purge the chain.
 	PurgeChain(ParseAndPreparePurge<'a>),
+	/// Command ready to migrate the database to ParityDb.
+	MigrateDatabase(ParseAndPrepareMigrateDatabase<'a>),
+	/// Command ready to inspect or repair the database.
+	Db(ParseAndPrepareDb<'a>),
 	/// Command ready to revert the chain.
 	RevertChain(ParseAndPrepareRevert<'a>),
+	/// Command ready to manage keys.
+	Key(ParseAndPrepareKey<'a>),
 	/// An additional custom command passed to `parse_and_prepare`.
 	CustomCommand(CC),
 }
@@ -288,7 +334,13 @@ impl<'a, CC, RP> ParseAndPrepare<'a, CC, RP> where CC: GetSharedParams {
 			ParseAndPrepare::ImportBlocks(c) => Some(&c.params.shared_params),
 			ParseAndPrepare::CheckBlock(c) => Some(&c.params.shared_params),
 			ParseAndPrepare::PurgeChain(c) => Some(&c.params.shared_params),
+			ParseAndPrepare::MigrateDatabase(c) => Some(&c.params.shared_params),
+			ParseAndPrepare::Db(c) => Some(c.params.shared_params()),
 			ParseAndPrepare::RevertChain(c) => Some(&c.params.shared_params),
+			ParseAndPrepare::Key(c) => match &c.params {
+				KeyCmd::Insert(params) => Some(&params.shared_params),
+				_ => None,
+			},
 			ParseAndPrepare::CustomCommand(c) => c.shared_params(),
 		}
 	}
@@ -408,8 +460,8 @@ impl<'a> ParseAndPrepareExport<'a> {
 		let json = self.params.json;
 
 		let file: Box<dyn Write> = match self.params.output {
-			Some(filename) => Box::new(File::create(filename)?),
-			None => Box::new(stdout()),
+			Some(filename) => Box::new(BufWriter::new(File::create(filename)?)),
+			None => Box::new(BufWriter::new(stdout())),
 		};
 
 		// Note: while we would like the user to handle the exit themselves, we handle it here
@@ -462,13 +514,9 @@ impl<'a> ParseAndPrepareImport<'a> {
 		let mut config = create_config_with_db_path(spec_factory, &self.params.shared_params, self.version)?;
 		fill_import_params(&mut config, &self.params.import_params, sc_service::Roles::FULL)?;
 
-		let file: Box<dyn ReadPlusSeek + Send> = match self.params.input {
-			Some(filename) => Box::new(File::open(filename)?),
-			None => {
-				let mut buffer = Vec::new();
-				stdin().read_to_end(&mut buffer)?;
-				Box::new(Cursor::new(buffer))
-			},
+		let file: Box<dyn Read + Send> = match self.params.input {
+			Some(filename) => Box::new(BufReader::new(File::open(filename)?)),
+			None => Box::new(BufReader::new(stdin())),
 		};
 
 		// Note: while we would like the user to handle the exit themselves, we handle it here
@@ -481,7 +529,7 @@ impl<'a> ParseAndPrepareImport<'a> {
 		});
 
 		let mut import_fut = builder(config)?
-			.import_blocks(file, false)
+			.import_blocks(file, false, self.params.batch_size)
 			.compat();
 		let fut = futures::future::poll_fn(|cx| {
 			if exit_recv.try_recv().is_ok() {
@@ -550,6 +598,8 @@ pub struct ParseAndPreparePurge<'a> {
 
 impl<'a> ParseAndPreparePurge<'a> {
 	/// Runs the command and purges the chain.
+	///
+	/// Purging only a part of the chain data requires a service, see `run_with_builder`.
 	pub fn run<G, E, S>(
 		self,
 		spec_factory: S
@@ -558,32 +608,26 @@ impl<'a> ParseAndPreparePurge<'a> {
 		G: RuntimeGenesis,
 		E: ChainSpecExtension,
 	{
+		if let Some(target) = self.target() {
+			return Err(error::Error::Input(
+				format!("Purging only the {} is not supported by this node", target)
+			));
+		}
+
 		let config = create_config_with_db_path::<(), _, _, _>(
 			spec_factory, &self.params.shared_params, self.version
 		)?;
 		let db_path = match config.database {
-			DatabaseConfig::Path { path, .. } => path,
+			DatabaseConfig::Path { path, .. } | DatabaseConfig::ParityDb { path } => path,
 			_ => {
 				eprintln!("Cannot purge custom database implementation");
 				return Ok(());
 			}
 		};
 
-		if !self.params.yes {
-			print!("Are you sure to remove {:?}? [y/N]: ", &db_path);
-			stdout().flush().expect("failed to flush stdout");
-
-			let mut input = String::new();
-			stdin().read_line(&mut input)?;
-			let input = input.trim();
-
-			match input.chars().nth(0) {
-				Some('y') | Some('Y') => {},
-				_ => {
-					println!("Aborted");
-					return Ok(());
-				},
-			}
+		if !self.params.yes && !confirm(&format!("Are you sure to remove {:?}?", &db_path))? {
+			println!("Aborted");
+			return Ok(());
 		}
 
 		match fs::remove_dir_all(&db_path) {
@@ -598,6 +642,181 @@ impl<'a> ParseAndPreparePurge<'a> {
 			Result::Err(err) => Result::Err(err.into())
 		}
 	}
+
+	/// Runs the command and purges the chain, or only the part of its data selected with
+	/// `--state-only` or `--bodies-only`.
+	pub fn run_with_builder<C, G, E, F, B, S>(
+		self,
+		builder: F,
+		spec_factory: S
+	) -> error::Result<()> where
+		S: FnOnce(&str) -> Result<Option<ChainSpec<G, E>>, String>,
+		F: FnOnce(Configuration<C, G, E>) -> Result<B, error::Error>,
+		B: ServiceBuilderCommand,
+		C: Default,
+		G: RuntimeGenesis,
+		E: ChainSpecExtension,
+	{
+		let target = match self.target() {
+			Some(target) => target,
+			None => return self.run(spec_factory),
+		};
+
+		let config = create_config_with_db_path(
+			spec_factory, &self.params.shared_params, self.version
+		)?;
+		let question = format!("Are you sure to remove the {} of the chain?", target);
+		if !self.params.yes && !confirm(&question)? {
+			println!("Aborted");
+			return Ok(());
+		}
+
+		builder(config)?.purge_chain(target)?;
+		Ok(())
+	}
+
+	/// The part of the chain data to purge, if not the whole chain.
+	fn target(&self) -> Option<PurgeTarget> {
+		if self.params.state_only {
+			Some(PurgeTarget::State)
+		} else if self.params.bodies_only {
+			Some(PurgeTarget::BlockBodies)
+		} else {
+			None
+		}
+	}
+}
+
+/// Asks the user a yes/no question on the terminal, defaulting to no.
+fn confirm(question: &str) -> error::Result<bool> {
+	print!("{} [y/N]: ", question);
+	stdout().flush().expect("failed to flush stdout");
+
+	let mut input = String::new();
+	stdin().read_line(&mut input)?;
+	Ok(match input.trim().chars().nth(0) {
+		Some('y') | Some('Y') => true,
+		_ => false,
+	})
+}
+
+/// Command ready to migrate the database to ParityDb.
+pub struct ParseAndPrepareMigrateDatabase<'a> {
+	params: MigrateDatabaseCmd,
+	version: &'a VersionInfo,
+}
+
+impl<'a> ParseAndPrepareMigrateDatabase<'a> {
+	/// Runs the command and copies the RocksDB database into a new ParityDb database.
+	pub fn run<G, E, S>(
+		self,
+		spec_factory: S
+	) -> error::Result<()> where
+		S: FnOnce(&str) -> Result<Option<ChainSpec<G, E>>, String>,
+		G: RuntimeGenesis,
+		E: ChainSpecExtension,
+	{
+		let config = create_config_with_db_path::<(), _, _, _>(
+			spec_factory, &self.params.shared_params, self.version
+		)?;
+		let rocksdb_path = config.in_chain_config_dir(DEFAULT_DB_CONFIG_PATH)
+			.expect("We provided a base_path.");
+		let parity_db_path = config.in_chain_config_dir(DEFAULT_PARITY_DB_CONFIG_PATH)
+			.expect("We provided a base_path.");
+
+		info!("Copying {} into {}", rocksdb_path.display(), parity_db_path.display());
+		let start = std::time::Instant::now();
+		let copied = sc_service::migrate_to_parity_db(&rocksdb_path, &parity_db_path)?;
+		println!("Copied {} entries in {} s.", copied, start.elapsed().as_secs());
+		Ok(())
+	}
+}
+
+/// Command ready to inspect or repair the database.
+pub struct ParseAndPrepareDb<'a> {
+	params: DbCmd,
+	version: &'a VersionInfo,
+}
+
+impl<'a> ParseAndPrepareDb<'a> {
+	/// Runs the command on the database of a full node with blocks of type `Block`.
+	pub fn run<Block, G, E, S>(
+		self,
+		spec_factory: S
+	) -> error::Result<()> where
+		S: FnOnce(&str) -> Result<Option<ChainSpec<G, E>>, String>,
+		Block: BlockT<Hash=H256>,
+		<<Block as BlockT>::Header as HeaderT>::Number: FromStr,
+		<<<Block as BlockT>::Header as HeaderT>::Number as FromStr>::Err: Debug,
+		G: RuntimeGenesis,
+		E: ChainSpecExtension,
+	{
+		let config = create_config_with_db_path::<(), _, _, _>(
+			spec_factory, self.params.shared_params(), self.version
+		)?;
+		let settings = sc_client_db::DatabaseSettings {
+			state_cache_size: config.state_cache_size,
+			state_cache_child_ratio: None,
+			pruning: config.pruning.clone(),
+			column_cache_sizes: Default::default(),
+			offchain_indexing: false,
+			source: match config.database {
+				DatabaseConfig::Path { path, cache_size } => sc_client_db::DatabaseSettingsSrc::Path {
+					path,
+					cache_size: cache_size.map(|size| size as usize),
+				},
+				DatabaseConfig::ParityDb { path } => sc_client_db::DatabaseSettingsSrc::ParityDb { path },
+				DatabaseConfig::Custom(db) => sc_client_db::DatabaseSettingsSrc::Custom(db),
+			},
+		};
+
+		match self.params {
+			DbCmd::Stats(_) => {
+				for stats in sc_client_db::inspect::column_stats(&settings)? {
+					println!(
+						"{:<14} {:>12} entries {:>16} key bytes {:>16} value bytes",
+						stats.name,
+						stats.entries,
+						stats.key_bytes,
+						stats.value_bytes,
+					);
+				}
+				Ok(())
+			},
+			DbCmd::Check(params) => {
+				let from = params.from.parse()?;
+				let to = params.to.map(|to| to.parse()).transpose()?;
+				let inconsistencies = sc_client_db::inspect::check_blocks::<Block>(&settings, from, to)?;
+				for inconsistency in &inconsistencies {
+					println!("{:?}", inconsistency);
+				}
+				if inconsistencies.is_empty() {
+					println!("No inconsistency found.");
+					Ok(())
+				} else {
+					Err(error::Error::Other(format!("Found {} inconsistencies", inconsistencies.len())))
+				}
+			},
+			DbCmd::RepairMeta(params) => {
+				let question = "Are you sure to rebuild the metadata of the database?";
+				if !params.yes && !confirm(question)? {
+					println!("Aborted");
+					return Ok(());
+				}
+				let repaired = sc_client_db::inspect::repair_meta::<Block>(&settings)?;
+				println!(
+					"Best block: #{} ({}), finalized block: #{} ({}), {} leaves, {} invalid headers.",
+					repaired.best.0,
+					repaired.best.1,
+					repaired.finalized.0,
+					repaired.finalized.1,
+					repaired.leaves,
+					repaired.invalid_headers,
+				);
+				Ok(())
+			},
+		}
+	}
 }
 
 /// Command ready to revert the chain.
@@ -626,11 +845,82 @@ impl<'a> ParseAndPrepareRevert<'a> {
 			spec_factory, &self.params.shared_params, self.version
 		)?;
 		let blocks = self.params.num.parse()?;
-		builder(config)?.revert_chain(blocks)?;
+		builder(config)?.revert_chain(blocks, self.params.force)?;
 		Ok(())
 	}
 }
 
+/// Command ready to manage keys.
+pub struct ParseAndPrepareKey<'a> {
+	params: KeyCmd,
+	version: &'a VersionInfo,
+}
+
+impl<'a> ParseAndPrepareKey<'a> {
+	/// Runs the command.
+	///
+	/// The chain spec is only loaded to locate the keystore when inserting a key or changing its
+	/// password.
+	pub fn run<G, E, S>(
+		self,
+		spec_factory: S,
+	) -> error::Result<()> where
+		S: FnOnce(&str) -> Result<Option<ChainSpec<G, E>>, String>,
+		G: RuntimeGenesis,
+		E: ChainSpecExtension,
+	{
+		match self.params {
+			KeyCmd::Generate(params) => key::generate(params.words, &params.key_params),
+			KeyCmd::Inspect(params) => key::inspect(&params.uri, &params.key_params),
+			KeyCmd::Insert(params) => {
+				if params.keystore_params.keystore_uri.is_some() {
+					return Err(error::Error::Input(
+						"Keys of a remote signer cannot be inserted from the command line".into()
+					));
+				}
+				let config = create_config_with_db_path::<(), _, _, _>(
+					spec_factory, &params.shared_params, self.version
+				)?;
+				let keystore_path = params.keystore_params.keystore_path.clone()
+					.or_else(|| config.in_chain_config_dir(DEFAULT_KEYSTORE_CONFIG_PATH))
+					.ok_or_else(|| error::Error::Input("No keystore path given".into()))?;
+				let password = keystore_password(&params.keystore_params)?;
+				key::insert(&params.suri, &params.key_type, params.scheme, &keystore_path, password)
+			},
+			KeyCmd::RotatePassword(params) => {
+				if params.keystore_params.keystore_uri.is_some() {
+					return Err(error::Error::Input(
+						"The password of a remote signer cannot be changed from the command line".into()
+					));
+				}
+				let config = create_config_with_db_path::<(), _, _, _>(
+					spec_factory, &params.shared_params, self.version
+				)?;
+				let keystore_path = params.keystore_params.keystore_path.clone()
+					.or_else(|| config.in_chain_config_dir(DEFAULT_KEYSTORE_CONFIG_PATH))
+					.ok_or_else(|| error::Error::Input("No keystore path given".into()))?;
+				let password = keystore_password(&params.keystore_params)?;
+				let new_password = if let Some(ref file) = params.new_password_filename {
+					fs::read_to_string(file).map_err(|e| format!("{}", e))?
+				} else if let Some(ref new_password) = params.new_password {
+					new_password.clone()
+				} else {
+					input_new_keystore_password()?
+				};
+				key::rotate_password(&keystore_path, password, new_password.into())
+			},
+			KeyCmd::Sign(params) => {
+				let message = key::read_message(&params.message_params)?;
+				key::sign(&params.suri, &message, &params.key_params)
+			},
+			KeyCmd::Verify(params) => {
+				let message = key::read_message(&params.message_params)?;
+				key::verify(&params.signature, &params.uri, &message, &params.key_params)
+			},
+		}
+	}
+}
+
 /// Create a `NodeKeyConfig` from the given `NodeKeyParams` in the context
 /// of an optional network config storage directory.
 fn node_key_config<P>(params: NodeKeyParams, net_config_dir: &Option<P>)
@@ -683,6 +973,9 @@ fn fill_transaction_pool_configuration<C, G, E>(
 	options.transaction_pool.future.count = params.pool_limit / factor;
 	options.transaction_pool.future.total_bytes = params.pool_kbytes * 1024 / factor;
 
+	options.transaction_pool.ban_time = Duration::from_secs(params.pool_ban_time);
+	options.persist_local_transactions = params.pool_persist_local;
+
 	Ok(())
 }
 
@@ -723,6 +1016,11 @@ fn fill_network_configuration(
 		];
 	}
 
+	if let Some(proxy) = cli.outbound_proxy {
+		let proxy = proxy.parse().ok().ok_or(error::Error::InvalidProxyMultiaddress)?;
+		config.outbound_proxy = Some(proxy);
+	}
+
 	config.public_addresses = Vec::new();
 
 	config.client_version = client_id;
@@ -730,6 +1028,7 @@ fn fill_network_configuration(
 
 	config.in_peers = cli.in_peers;
 	config.out_peers = cli.out_peers;
+	config.reserved_authority_slots = cli.reserved_authority_slots;
 
 	config.transport = TransportConfig::Normal {
 		enable_mdns: !is_dev && !cli.no_mdns,
@@ -738,6 +1037,8 @@ fn fill_network_configuration(
 	};
 
 	config.max_parallel_downloads = cli.max_parallel_downloads;
+	config.sync_mode = cli.sync.into();
+	config.allow_non_globals_in_dht = is_dev || cli.discover_local;
 
 	Ok(())
 }
@@ -748,12 +1049,22 @@ fn input_keystore_password() -> Result<String, String> {
 		.map_err(|e| format!("{:?}", e))
 }
 
-/// Fill the password field of the given config instance.
-fn fill_config_keystore_password<C, G, E>(
-	config: &mut sc_service::Configuration<C, G, E>,
-	cli: &RunCmd,
-) -> Result<(), String> {
-	config.keystore_password = if cli.password_interactive {
+#[cfg(not(target_os = "unknown"))]
+fn input_new_keystore_password() -> Result<String, String> {
+	rpassword::read_password_from_tty(Some("New keystore password: "))
+		.map_err(|e| format!("{:?}", e))
+}
+
+#[cfg(target_os = "unknown")]
+fn input_new_keystore_password() -> Result<String, String> {
+	Err("A new password must be given with --new-password or --new-password-filename".into())
+}
+
+/// Read the keystore password given on the command line, if any.
+fn keystore_password(
+	cli: &KeystoreParams,
+) -> Result<Option<sp_core::crypto::Protected<String>>, String> {
+	Ok(if cli.password_interactive {
 		#[cfg(not(target_os = "unknown"))]
 		{
 			Some(input_keystore_password()?.into())
@@ -766,9 +1077,7 @@ fn fill_config_keystore_password<C, G, E>(
 		Some(password.clone().into())
 	} else {
 		None
-	};
-
-	Ok(())
+	})
 }
 
 /// Put block import CLI params into `config` object.
@@ -782,10 +1091,16 @@ pub fn fill_import_params<C, G, E>(
 		G: RuntimeGenesis,
 		E: ChainSpecExtension,
 {
+	let (backend_cache_size, column_cache_sizes) = split_database_cache(
+		cli.database_cache_size,
+		cli.database_cache_split.as_ref().map(|s| s.as_str()),
+	)?;
+	config.column_cache_sizes = column_cache_sizes;
+	config.offchain_indexing = cli.enable_offchain_indexing;
 	match config.database {
 		DatabaseConfig::Path { ref mut cache_size, .. } =>
-			*cache_size = Some(cli.database_cache_size),
-		DatabaseConfig::Custom(_) => {},
+			*cache_size = Some(backend_cache_size),
+		DatabaseConfig::ParityDb { .. } | DatabaseConfig::Custom(_) => {},
 	}
 
 	config.state_cache_size = cli.state_cache_size;
@@ -793,9 +1108,12 @@ pub fn fill_import_params<C, G, E>(
 	// by default we disable pruning if the node is an authority (i.e.
 	// `ArchiveAll`), otherwise we keep state for the last 256 blocks. if the
 	// node is an authority and pruning is enabled explicitly, then we error
-	// unless `unsafe_pruning` is set.
+	// unless `unsafe_pruning` is set. keeping the state of all the canonical
+	// blocks (i.e. `ArchiveCanonical`) is fine for authorities, since the state
+	// of the blocks which are not finalized yet is kept either way.
 	config.pruning = match &cli.pruning {
 		Some(ref s) if s == "archive" => PruningMode::ArchiveAll,
+		Some(ref s) if s == "archive-canonical" => PruningMode::ArchiveCanonical,
 		None if role == sc_service::Roles::AUTHORITY => PruningMode::ArchiveAll,
 		None => PruningMode::default(),
 		Some(s) => {
@@ -813,6 +1131,7 @@ pub fn fill_import_params<C, G, E>(
 	};
 
 	config.wasm_method = cli.wasm_method.into();
+	config.import_verification_workers = cli.import_verification_workers;
 
 	let exec = &cli.execution_strategies;
 	let exec_all_or = |strat: ExecutionStrategy| exec.execution.unwrap_or(strat).into();
@@ -837,7 +1156,7 @@ where
 {
 	let mut config = create_config_with_db_path(spec_factory, &cli.shared_params, &version)?;
 
-	fill_config_keystore_password(&mut config, &cli)?;
+	config.keystore_password = keystore_password(&cli.keystore_params)?;
 
 	let is_dev = cli.shared_params.dev;
 	let is_authority = cli.validator || cli.sentry || is_dev || cli.keyring.account.is_some();
@@ -872,7 +1191,9 @@ where
 		)?
 	}
 
-	config.keystore_path = cli.keystore_path.or_else(|| config.in_chain_config_dir(DEFAULT_KEYSTORE_CONFIG_PATH));
+	config.keystore_path = cli.keystore_params.keystore_path
+		.or_else(|| config.in_chain_config_dir(DEFAULT_KEYSTORE_CONFIG_PATH));
+	config.keystore_uri = cli.keystore_params.keystore_uri;
 
 	// set sentry mode (i.e. act as an authority but **never** actively participate)
 	config.sentry_mode = cli.sentry;
@@ -883,6 +1204,7 @@ where
 		(params::OffchainWorkerEnabled::Never, _) => false,
 		(params::OffchainWorkerEnabled::WhenValidating, _) => false,
 	};
+	config.offchain_worker_options.policy = cli.offchain_worker_policy.into();
 
 	config.roles = role;
 	config.disable_grandpa = cli.no_grandpa;
@@ -910,14 +1232,28 @@ where
 	let rpc_interface: &str = interface_str(cli.rpc_external, cli.unsafe_rpc_external, cli.validator)?;
 	let ws_interface: &str = interface_str(cli.ws_external, cli.unsafe_ws_external, cli.validator)?;
 	let grafana_interface: &str = if cli.grafana_external { "0.0.0.0" } else { "127.0.0.1" };
+	let prometheus_interface: &str = if cli.prometheus_external { "0.0.0.0" } else { "127.0.0.1" };
 
+	config.rpc_ipc = cli.ipc_path;
 	config.rpc_http = Some(parse_address(&format!("{}:{}", rpc_interface, 9933), cli.rpc_port)?);
 	config.rpc_ws = Some(parse_address(&format!("{}:{}", ws_interface, 9944), cli.ws_port)?);
 	config.grafana_port = Some(
 		parse_address(&format!("{}:{}", grafana_interface, 9955), cli.grafana_port)?
 	);
+	if cli.prometheus {
+		config.prometheus_config = Some(PrometheusConfig::new_with_default_registry(
+			parse_address(&format!("{}:{}", prometheus_interface, 9615), cli.prometheus_port)?
+		));
+	}
 
 	config.rpc_ws_max_connections = cli.ws_max_connections;
+	config.rpc_methods = cli.rpc_methods.into();
+	config.rpc_limits = sc_service::RpcLimits {
+		max_payload: cli.rpc_max_payload.map(|mib| mib.saturating_mul(1024 * 1024)),
+		max_batch_size: cli.rpc_max_batch_size,
+		max_response_size: cli.rpc_max_response_size.map(|mib| mib.saturating_mul(1024 * 1024)),
+		max_subscriptions_per_connection: cli.ws_max_subscriptions_per_connection,
+	};
 	config.rpc_cors = cli.rpc_cors.unwrap_or_else(|| if is_dev {
 		log::warn!("Running in --dev mode, RPC CORS has been disabled.");
 		Cors::All
@@ -969,6 +1305,36 @@ fn interface_str(
 	}
 }
 
+/// Splits the database cache of `cache_size` MiB according to the `--db-cache-split`
+/// percentages, returning the cache size left to the database backend in MiB, and the sizes of
+/// the column caches.
+fn split_database_cache(
+	cache_size: u32,
+	split: Option<&str>,
+) -> error::Result<(u32, sc_service::config::ColumnCacheSizes)> {
+	let split = match split {
+		Some(split) => split,
+		None => return Ok((cache_size, Default::default())),
+	};
+	let invalid = || error::Error::Input(format!("Invalid database cache split: {}", split));
+
+	let percents = split.split(',')
+		.map(|percent| percent.trim().parse::<u32>().map_err(|_| invalid()))
+		.collect::<error::Result<Vec<_>>>()?;
+	if percents.len() != 4 || percents.iter().sum::<u32>() > 100 {
+		return Err(invalid());
+	}
+
+	let bytes = |percent: u32| (cache_size as usize * 1024 * 1024) / 100 * percent as usize;
+	let sizes = sc_service::config::ColumnCacheSizes {
+		state: bytes(percents[0]),
+		headers: bytes(percents[1]),
+		bodies: bytes(percents[2]),
+		justifications: bytes(percents[3]),
+	};
+	Ok((cache_size * (100 - percents.iter().sum::<u32>()) / 100, sizes))
+}
+
 /// Creates a configuration including the database path.
 pub fn create_config_with_db_path<C, G, E, S>(
 	spec_factory: S, cli: &SharedParams, version: &VersionInfo,
@@ -987,19 +1353,20 @@ where
 		Some(base_path),
 	);
 
-	config.database = DatabaseConfig::Path {
-		path: config.in_chain_config_dir(DEFAULT_DB_CONFIG_PATH).expect("We provided a base_path."),
-		cache_size: None,
+	config.database = match cli.database {
+		Database::RocksDb => DatabaseConfig::Path {
+			path: config.in_chain_config_dir(DEFAULT_DB_CONFIG_PATH).expect("We provided a base_path."),
+			cache_size: None,
+		},
+		Database::ParityDb => DatabaseConfig::ParityDb {
+			path: config.in_chain_config_dir(DEFAULT_PARITY_DB_CONFIG_PATH)
+				.expect("We provided a base_path."),
+		},
 	};
 
 	Ok(config)
 }
 
-/// Internal trait used to cast to a dynamic type that implements Read and Seek.
-trait ReadPlusSeek: Read + Seek {}
-
-impl<T: Read + Seek> ReadPlusSeek for T {}
-
 fn parse_address(
 	address: &str,
 	port: Option<u16>,
@@ -1088,6 +1455,22 @@ mod tests {
 	use super::*;
 	use sc_network::config::identity::ed25519;
 
+	#[test]
+	fn database_cache_split() {
+		assert_eq!(split_database_cache(1000, None).unwrap().0, 1000);
+
+		let (backend, columns) = split_database_cache(1000, Some("50, 10,10,5")).unwrap();
+		assert_eq!(backend, 250);
+		assert_eq!(columns.state, 500 * 1024 * 1024);
+		assert_eq!(columns.headers, 100 * 1024 * 1024);
+		assert_eq!(columns.bodies, 100 * 1024 * 1024);
+		assert_eq!(columns.justifications, 50 * 1024 * 1024);
+
+		assert!(split_database_cache(1000, Some("50,10,10")).is_err());
+		assert!(split_database_cache(1000, Some("90,10,10,5")).is_err());
+		assert!(split_database_cache(1000, Some("50,ten,10,5")).is_err());
+	}
+
 	#[test]
 	fn tests_node_name_good() {
 		assert!(is_node_name_valid("short name").is_ok());
@@ -1198,4 +1581,36 @@ mod tests {
 		assert!(no_config_dir().is_ok());
 		assert!(some_config_dir("x".to_string()).is_ok());
 	}
+
+	#[test]
+	fn explicit_short_options_override_the_configuration_file() {
+		let version = VersionInfo {
+			name: "node-test",
+			version: "0.1.0",
+			commit: "some_commit",
+			executable_name: "node",
+			description: "description",
+			author: "author",
+			support_url: "http://example.org",
+		};
+		let tmp = tempfile::Builder::new().prefix("config").tempdir().unwrap();
+		let config = tmp.path().join("node.toml");
+		std::fs::write(&config, "base-path = \"/from/file\"\nname = \"file\"\n").unwrap();
+
+		let args = vec![
+			"node".into(),
+			"-d".into(),
+			"/explicit".into(),
+			"--config".into(),
+			config.into_os_string(),
+		];
+		match parse_and_prepare::<NoCustom, NoCustom, _>(&version, "node-test", args) {
+			ParseAndPrepare::Run(run) => {
+				let cmd = run.params.left;
+				assert_eq!(cmd.shared_params.base_path, Some(PathBuf::from("/explicit")));
+				assert_eq!(cmd.name, Some("file".into()));
+			},
+			_ => panic!("expected the run command"),
+		}
+	}
 }
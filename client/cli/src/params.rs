@@ -69,6 +69,18 @@ impl Into<sc_service::config::WasmExecutionMethod> for WasmExecutionMethod {
 	}
 }
 
+arg_enum! {
+	/// Database backend.
+	#[allow(missing_docs)]
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum Database {
+		// RocksDB, the default.
+		RocksDb,
+		// ParityDb, which avoids the compaction stalls of RocksDB.
+		ParityDb,
+	}
+}
+
 arg_enum! {
 	/// Whether off-chain workers are enabled.
 	#[allow(missing_docs)]
@@ -80,6 +92,79 @@ arg_enum! {
 	}
 }
 
+arg_enum! {
+	/// On which imported blocks off-chain workers are executed.
+	#[allow(missing_docs)]
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum OffchainWorkerPolicy {
+		// Only the blocks authored by the node.
+		OwnBlocks,
+		// The blocks which become the new best block.
+		NewBestBlocks,
+		// All imported blocks, except the ones of the initial sync.
+		AllBlocks,
+	}
+}
+
+impl Into<sc_service::OffchainWorkerPolicy> for OffchainWorkerPolicy {
+	fn into(self) -> sc_service::OffchainWorkerPolicy {
+		match self {
+			OffchainWorkerPolicy::OwnBlocks => sc_service::OffchainWorkerPolicy::OwnBlocks,
+			OffchainWorkerPolicy::NewBestBlocks => sc_service::OffchainWorkerPolicy::NewBestBlocks,
+			OffchainWorkerPolicy::AllBlocks => sc_service::OffchainWorkerPolicy::AllBlocks,
+		}
+	}
+}
+
+arg_enum! {
+	/// How to sync the chain on a fresh database.
+	#[allow(missing_docs)]
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum SyncMode {
+		// Download and import all blocks.
+		Full,
+		// Download the finality proofs of a recent block and its state, then sync from there.
+		Warp,
+		// Download the headers up to the latest finalized block and its state, then sync from there.
+		Fast,
+	}
+}
+
+impl Into<sc_network::config::SyncMode> for SyncMode {
+	fn into(self) -> sc_network::config::SyncMode {
+		match self {
+			SyncMode::Full => sc_network::config::SyncMode::Full,
+			SyncMode::Warp => sc_network::config::SyncMode::Warp,
+			SyncMode::Fast => sc_network::config::SyncMode::Fast,
+		}
+	}
+}
+
+arg_enum! {
+	/// Available RPC methods.
+	#[allow(missing_docs)]
+	#[derive(Debug, Copy, Clone, PartialEq)]
+	pub enum RpcMethods {
+		// Expose every RPC method only when RPC is listening on `localhost`,
+		// otherwise serve only safe RPC methods.
+		Auto,
+		// Allow only a safe subset of RPC methods.
+		Safe,
+		// Expose every RPC method (even potentially unsafe ones).
+		Unsafe,
+	}
+}
+
+impl Into<sc_service::config::RpcMethods> for RpcMethods {
+	fn into(self) -> sc_service::config::RpcMethods {
+		match self {
+			RpcMethods::Auto => sc_service::config::RpcMethods::Auto,
+			RpcMethods::Safe => sc_service::config::RpcMethods::Safe,
+			RpcMethods::Unsafe => sc_service::config::RpcMethods::Unsafe,
+		}
+	}
+}
+
 /// Shared parameters used by all `CoreParams`.
 #[derive(Debug, StructOpt, Clone)]
 pub struct SharedParams {
@@ -98,12 +183,31 @@ pub struct SharedParams {
 	/// Sets a custom logging filter.
 	#[structopt(short = "l", long = "log", value_name = "LOG_PATTERN")]
 	pub log: Option<String>,
+
+	/// Select the database backend to use.
+	///
+	/// The ParityDb database is kept next to the RocksDB one, and can be
+	/// created from it with the `migrate-database` subcommand. The node
+	/// cannot run on it yet, since ParityDb doesn't support iterating over
+	/// the columns of the database.
+	#[structopt(
+		long = "database",
+		value_name = "DB",
+		possible_values = &Database::variants(),
+		case_insensitive = true,
+		default_value = "RocksDb"
+	)]
+	pub database: Database,
 }
 
 /// Parameters for block import.
 #[derive(Debug, StructOpt, Clone)]
 pub struct ImportParams {
-	/// Specify the state pruning mode, a number of blocks to keep or 'archive'.
+	/// Specify the state pruning mode, a number of blocks to keep, 'archive' or
+	/// 'archive-canonical'.
+	///
+	/// 'archive-canonical' keeps the state of all the finalized blocks, but
+	/// prunes the state of the forks which were not finalized.
 	///
 	/// Default is to keep all block states if the node is running as a
 	/// validator (i.e. 'archive'), otherwise state is only kept for the last
@@ -137,9 +241,33 @@ pub struct ImportParams {
 	#[structopt(long = "db-cache", value_name = "MiB", default_value = "1024")]
 	pub database_cache_size: u32,
 
+	/// Split the database cache between the state, headers, bodies and
+	/// justifications caches, in percent of `--db-cache`, e.g. '50,10,10,5'.
+	///
+	/// The rest of the database cache is left to the database backend. By
+	/// default, the whole database cache is left to the database backend.
+	#[structopt(long = "db-cache-split", value_name = "STATE,HEADERS,BODIES,JUSTIFICATIONS")]
+	pub database_cache_split: Option<String>,
+
 	/// Specify the state cache size.
 	#[structopt(long = "state-cache-size", value_name = "Bytes", default_value = "67108864")]
 	pub state_cache_size: usize,
+
+	/// Number of workers verifying the blocks to import in parallel.
+	///
+	/// The blocks whose parent is not part of the same batch are verified on these workers
+	/// ahead of their import, which remains sequential. Blocks are verified on the import
+	/// thread when set to 1.
+	#[structopt(long = "import-verification-workers", value_name = "COUNT", default_value = "1")]
+	pub import_verification_workers: usize,
+
+	/// Enable offchain indexing.
+	///
+	/// The data indexed by the runtime while importing the blocks is written to
+	/// the offchain database, where the offchain workers can read it. The data is
+	/// not kept per fork: the block imported last overwrites it.
+	#[structopt(long = "enable-offchain-indexing")]
+	pub enable_offchain_indexing: bool,
 }
 
 /// Parameters used to create the network configuration.
@@ -169,6 +297,9 @@ pub struct NetworkConfigurationParams {
 	pub sentry_nodes: Vec<String>,
 
 	/// Listen on this multiaddress.
+	///
+	/// Only TCP addresses, optionally followed by `/ws`, are supported, e.g.
+	/// `/ip4/0.0.0.0/tcp/30333`. UDP based transports such as QUIC are not available.
 	#[structopt(long = "listen-addr", value_name = "LISTEN_ADDR")]
 	pub listen_addr: Vec<String>,
 
@@ -178,6 +309,11 @@ pub struct NetworkConfigurationParams {
 	#[structopt(long = "port", value_name = "PORT")]
 	pub port: Option<u16>,
 
+	/// Establish all outgoing connections through the SOCKS5 proxy at this multiaddress, e.g.
+	/// `/ip4/127.0.0.1/tcp/9050` for a local Tor client.
+	#[structopt(long = "outbound-proxy", value_name = "PROXY_ADDR")]
+	pub outbound_proxy: Option<String>,
+
 	/// Allow connecting to private IPv4 addresses (as specified in
 	/// [RFC1918](https://tools.ietf.org/html/rfc1918)), unless the address was passed with
 	/// `--reserved-nodes` or `--bootnodes`.
@@ -192,6 +328,13 @@ pub struct NetworkConfigurationParams {
 	#[structopt(long = "in-peers", value_name = "COUNT", default_value = "25")]
 	pub in_peers: u32,
 
+	/// Specify the number of connection slots, in each direction, reserved for authorities.
+	///
+	/// Authorities then share these and the regular slots, and can't be crowded out by other
+	/// nodes. By default, authorities don't count towards the connection limits at all.
+	#[structopt(long = "reserved-authority-slots", value_name = "COUNT")]
+	pub reserved_authority_slots: Option<u32>,
+
 	/// Disable mDNS discovery.
 	///
 	/// By default, the network will use mDNS to discover other nodes on the
@@ -199,6 +342,13 @@ pub struct NetworkConfigurationParams {
 	#[structopt(long = "no-mdns")]
 	pub no_mdns: bool,
 
+	/// Publish private, loopback and link-local addresses on the DHT.
+	///
+	/// By default only globally reachable addresses are published, e.g. by authority discovery.
+	/// Automatically implied when using --dev.
+	#[structopt(long = "discover-local")]
+	pub discover_local: bool,
+
 	/// Maximum number of peers to ask the same blocks in parallel.
 	///
 	/// This allows downlading announced blocks from multiple peers. Decrease to save
@@ -206,6 +356,24 @@ pub struct NetworkConfigurationParams {
 	#[structopt(long = "max-parallel-downloads", value_name = "COUNT", default_value = "5")]
 	pub max_parallel_downloads: u32,
 
+	/// How to sync the chain if the database is empty.
+	///
+	/// `Warp` downloads the proofs of the GRANDPA authority set changes up to the latest finalized
+	/// block, then the state of that block, instead of all blocks. It is only supported by chains
+	/// finalized by GRANDPA without forced authority set changes nor child storage. Falls back to
+	/// `Full` if it can't complete.
+	///
+	/// `Fast` downloads the headers up to the latest finalized block, then the state of that
+	/// block, and only executes the blocks after it. Chains with child storage aren't supported.
+	#[structopt(
+		long = "sync",
+		value_name = "SYNC_MODE",
+		possible_values = &SyncMode::variants(),
+		case_insensitive = true,
+		default_value = "Full"
+	)]
+	pub sync: SyncMode,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub node_key_params: NodeKeyParams,
@@ -290,6 +458,13 @@ pub struct TransactionPoolParams {
 	/// Maximum number of kilobytes of all transactions stored in the pool.
 	#[structopt(long = "pool-kbytes", value_name = "COUNT", default_value = "10240")]
 	pub pool_kbytes: usize,
+	/// Number of seconds the transactions found invalid are banned from the pool.
+	#[structopt(long = "pool-ban-time", value_name = "SECONDS", default_value = "1800")]
+	pub pool_ban_time: u64,
+	/// Persist the transactions submitted to this node until they are finalized, and submit
+	/// them again after a restart.
+	#[structopt(long = "pool-persist-local")]
+	pub pool_persist_local: bool,
 }
 
 arg_enum! {
@@ -422,7 +597,8 @@ pub struct RunCmd {
 	/// Listen to all RPC interfaces.
 	///
 	/// Default is local. Note: not all RPC methods are safe to be exposed publicly. Use a RPC proxy
-	/// server to filter out dangerous methods. More details: https://github.com/paritytech/substrate/wiki/Public-RPC.
+	/// server to filter out dangerous methods, or see `--rpc-methods`.
+	/// More details: https://github.com/paritytech/substrate/wiki/Public-RPC.
 	/// Use `--unsafe-rpc-external` to suppress the warning if you understand the risks.
 	#[structopt(long = "rpc-external")]
 	pub rpc_external: bool,
@@ -447,12 +623,40 @@ pub struct RunCmd {
 	#[structopt(long = "unsafe-ws-external")]
 	pub unsafe_ws_external: bool,
 
+	/// RPC methods to expose.
+	///
+	/// - `Unsafe`: Exposes every RPC method.
+	/// - `Safe`: Exposes only a safe subset of RPC methods, denying unsafe RPC methods such as
+	///   inserting keys or managing the reserved peers.
+	/// - `Auto`: Acts as `Safe` if RPC is served externally, e.g. when `--{rpc,ws}-external` is
+	///   passed, otherwise acts as `Unsafe`.
+	#[structopt(
+		long = "rpc-methods",
+		value_name = "METHOD SET",
+		possible_values = &RpcMethods::variants(),
+		case_insensitive = true,
+		default_value = "Auto"
+	)]
+	pub rpc_methods: RpcMethods,
+
 	/// Listen to all Grafana data source interfaces.
 	///
 	/// Default is local.
 	#[structopt(long = "grafana-external")]
 	pub grafana_external: bool,
 
+	/// Listen to all Prometheus endpoint interfaces.
+	///
+	/// Default is local.
+	#[structopt(long = "prometheus-external")]
+	pub prometheus_external: bool,
+
+	/// Specify IPC RPC server path.
+	///
+	/// The server listens on a Unix domain socket, or on a named pipe on Windows.
+	#[structopt(long = "ipc-path", value_name = "PATH")]
+	pub ipc_path: Option<String>,
+
 	/// Specify HTTP RPC server TCP port.
 	#[structopt(long = "rpc-port", value_name = "PORT")]
 	pub rpc_port: Option<u16>,
@@ -465,6 +669,24 @@ pub struct RunCmd {
 	#[structopt(long = "ws-max-connections", value_name = "COUNT")]
 	pub ws_max_connections: Option<usize>,
 
+	/// Maximum number of subscriptions a single WS RPC connection can keep open.
+	#[structopt(long = "ws-max-subscriptions-per-connection", value_name = "COUNT")]
+	pub ws_max_subscriptions_per_connection: Option<usize>,
+
+	/// Maximum size of a request payload of the HTTP & WS RPC servers, in MiB.
+	///
+	/// Defaults to 15 MiB.
+	#[structopt(long = "rpc-max-payload", value_name = "MiB")]
+	pub rpc_max_payload: Option<usize>,
+
+	/// Maximum number of calls in a batch request to the HTTP & WS RPC servers.
+	#[structopt(long = "rpc-max-batch-size", value_name = "COUNT")]
+	pub rpc_max_batch_size: Option<usize>,
+
+	/// Maximum size of the response to a single RPC call, in MiB.
+	#[structopt(long = "rpc-max-response-size", value_name = "MiB")]
+	pub rpc_max_response_size: Option<usize>,
+
 	/// Specify browser Origins allowed to access the HTTP & WS RPC servers.
 	///
 	/// A comma-separated list of origins (protocol://domain or special `null`
@@ -479,6 +701,17 @@ pub struct RunCmd {
 	#[structopt(long = "grafana-port", value_name = "PORT")]
 	pub grafana_port: Option<u16>,
 
+	/// Specify Prometheus endpoint TCP Port.
+	#[structopt(long = "prometheus-port", value_name = "PORT")]
+	pub prometheus_port: Option<u16>,
+
+	/// Expose a Prometheus metric endpoint.
+	///
+	/// The endpoint is disabled by default, and only listens locally unless
+	/// `--prometheus-external` is passed.
+	#[structopt(long = "prometheus")]
+	pub prometheus: bool,
+
 	/// The human-readable name for this node.
 	///
 	/// The node name will be reported to the telemetry server, if enabled.
@@ -512,6 +745,19 @@ pub struct RunCmd {
 	)]
 	pub offchain_worker: OffchainWorkerEnabled,
 
+	/// On which imported blocks offchain workers are executed, if enabled.
+	///
+	/// `AllBlocks` also executes them on the blocks of forks which don't become the best chain,
+	/// but not on the blocks imported at initial sync.
+	#[structopt(
+		long = "offchain-worker-policy",
+		value_name = "POLICY",
+		possible_values = &OffchainWorkerPolicy::variants(),
+		case_insensitive = true,
+		default_value = "AllBlocks"
+	)]
+	pub offchain_worker_policy: OffchainWorkerPolicy,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub shared_params: SharedParams,
@@ -550,10 +796,33 @@ pub struct RunCmd {
 	)]
 	pub tracing_receiver: TracingReceiver,
 
+	/// TOML file with default values for the options of this command.
+	///
+	/// The file is a table mapping long option names, without the leading `--`, to their values,
+	/// e.g. `rpc-port = 9933` or `bootnodes = ["/ip4/..."]`. Flags are set with `true`. Options
+	/// given explicitly on the command line take precedence over the ones of the file.
+	#[structopt(long = "config", value_name = "PATH", parse(from_os_str))]
+	pub config_file: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub keystore_params: KeystoreParams,
+}
+
+/// Parameters of the keystore.
+#[derive(Debug, StructOpt, Clone)]
+pub struct KeystoreParams {
 	/// Specify custom keystore path.
 	#[structopt(long = "keystore-path", value_name = "PATH", parse(from_os_str))]
 	pub keystore_path: Option<PathBuf>,
 
+	/// Use the remote signer at the given HTTPS URI instead of a local keystore.
+	///
+	/// The keystore password is the token authenticating the node to the signer. Plain HTTP URIs
+	/// are only accepted for a signer on the loopback interface, e.g. `http://127.0.0.1:9955`.
+	#[structopt(long = "keystore-uri", value_name = "URI", conflicts_with = "keystore-path")]
+	pub keystore_uri: Option<String>,
+
 	/// Use interactive shell for entering the password used by the keystore.
 	#[structopt(
 		long = "password-interactive",
@@ -792,6 +1061,12 @@ pub struct ImportBlocksCmd {
 	#[structopt(parse(from_os_str))]
 	pub input: Option<PathBuf>,
 
+	/// Number of blocks handed to the import queue at once.
+	///
+	/// Larger batches are verified and imported faster, at the cost of memory.
+	#[structopt(long = "batch-size", value_name = "COUNT", default_value = "64")]
+	pub batch_size: usize,
+
 	/// The default number of 64KB pages to ever allocate for Wasm execution.
 	///
 	/// Don't alter this unless you know what you're doing.
@@ -836,23 +1111,295 @@ pub struct RevertCmd {
 	#[structopt(default_value = "256")]
 	pub num: BlockNumber,
 
+	/// Revert finalized blocks as well, as far as their state is still known.
+	///
+	/// Without it, reverting below the last finalized block is refused.
+	#[structopt(long = "force")]
+	pub force: bool,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub shared_params: SharedParams,
 }
 
-/// The `purge-chain` command used to remove the whole chain.
+/// The `purge-chain` command used to remove the whole chain, or a part of its data.
 #[derive(Debug, StructOpt, Clone)]
 pub struct PurgeChainCmd {
 	/// Skip interactive prompt by answering yes automatically.
 	#[structopt(short = "y")]
 	pub yes: bool,
 
+	/// Only remove the state, keeping the blocks.
+	#[structopt(long = "state-only", conflicts_with = "bodies-only")]
+	pub state_only: bool,
+
+	/// Only remove the block bodies, keeping the headers, the justifications and the state.
+	#[structopt(long = "bodies-only")]
+	pub bodies_only: bool,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+/// The `migrate-database` command used to copy the RocksDB database into a new ParityDb one.
+#[derive(Debug, StructOpt, Clone)]
+pub struct MigrateDatabaseCmd {
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+/// The `db stats` command used to display the size of each column of the database.
+#[derive(Debug, StructOpt, Clone)]
+pub struct DbStatsCmd {
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+/// The `db check` command used to check the canonical blocks and the presence of their state.
+#[derive(Debug, StructOpt, Clone)]
+pub struct DbCheckCmd {
+	/// Check from this block.
+	#[structopt(long = "from", value_name = "BLOCK", default_value = "0")]
+	pub from: BlockNumber,
+
+	/// Check up to this block, or up to the best block if not given.
+	#[structopt(long = "to", value_name = "BLOCK")]
+	pub to: Option<BlockNumber>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+/// The `db repair-meta` command used to rebuild the metadata of the database from its blocks.
+#[derive(Debug, StructOpt, Clone)]
+pub struct DbRepairMetaCmd {
+	/// Skip interactive prompt by answering yes automatically.
+	#[structopt(short = "y")]
+	pub yes: bool,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+/// The `db` command used to inspect and repair the database of a full node.
+#[derive(Debug, StructOpt, Clone)]
+pub enum DbCmd {
+	/// Display the number of entries and the size of each column of the database.
+	#[structopt(name = "stats")]
+	Stats(DbStatsCmd),
+
+	/// Check that the canonical blocks are linked together and that their state is present.
+	#[structopt(name = "check")]
+	Check(DbCheckCmd),
+
+	/// Rebuild the best and finalized blocks, the leaves and the canonical chain index from the
+	/// block headers.
+	#[structopt(name = "repair-meta")]
+	RepairMeta(DbRepairMetaCmd),
+}
+
+impl DbCmd {
+	/// The shared parameters of the command.
+	pub fn shared_params(&self) -> &SharedParams {
+		match self {
+			DbCmd::Stats(params) => &params.shared_params,
+			DbCmd::Check(params) => &params.shared_params,
+			DbCmd::RepairMeta(params) => &params.shared_params,
+		}
+	}
+}
+
+arg_enum! {
+	/// Cryptography scheme of a key.
+	#[allow(missing_docs)]
+	#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+	pub enum CryptoScheme {
+		Ed25519,
+		Sr25519,
+		Ecdsa,
+	}
+}
+
+/// Parameters of the keys handled by the `key` subcommands.
+#[derive(Debug, StructOpt, Clone)]
+pub struct KeyParams {
+	/// Cryptography scheme of the key.
+	#[structopt(
+		long = "scheme",
+		value_name = "SCHEME",
+		possible_values = &CryptoScheme::variants(),
+		case_insensitive = true,
+		default_value = "Sr25519"
+	)]
+	pub scheme: CryptoScheme,
+
+	/// Network the SS58 addresses are encoded for, e.g. `polkadot`, `kusama` or `substrate`.
+	#[structopt(long = "network", value_name = "NETWORK")]
+	pub network: Option<String>,
+
+	/// Password of the secret key.
+	#[structopt(long = "password")]
+	pub password: Option<String>,
+}
+
+/// The `key generate` command used to generate a random key.
+#[derive(Debug, StructOpt, Clone)]
+pub struct GenerateKeyCmd {
+	/// Number of words of the mnemonic phrase: 12, 15, 18, 21 or 24.
+	#[structopt(long = "words", value_name = "COUNT", default_value = "12")]
+	pub words: usize,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub key_params: KeyParams,
+}
+
+/// The `key inspect` command used to display the public key and address of a key.
+#[derive(Debug, StructOpt, Clone)]
+pub struct InspectKeyCmd {
+	/// A mnemonic phrase, a secret seed or a public key, optionally followed by a derivation
+	/// path, e.g. `//Alice` or `"<phrase>//hard/soft"`.
+	#[structopt(value_name = "URI")]
+	pub uri: String,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub key_params: KeyParams,
+}
+
+/// The `key insert` command used to insert a key into the keystore of the node.
+#[derive(Debug, StructOpt, Clone)]
+pub struct InsertKeyCmd {
+	/// The secret key URI: a mnemonic phrase or a secret seed, optionally followed by a
+	/// derivation path.
+	#[structopt(long = "suri", value_name = "SURI")]
+	pub suri: String,
+
+	/// Key type of the key, e.g. `babe`, `gran` or `imon`.
+	#[structopt(long = "key-type", value_name = "TYPE")]
+	pub key_type: String,
+
+	/// Cryptography scheme of the key.
+	#[structopt(
+		long = "scheme",
+		value_name = "SCHEME",
+		possible_values = &CryptoScheme::variants(),
+		case_insensitive = true,
+		default_value = "Sr25519"
+	)]
+	pub scheme: CryptoScheme,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub keystore_params: KeystoreParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub shared_params: SharedParams,
+}
+
+/// The `key rotate-password` command used to change the password of the keystore of the node.
+#[derive(Debug, StructOpt, Clone)]
+pub struct RotatePasswordCmd {
+	/// The new password. Asked interactively if neither this nor `--new-password-filename` is
+	/// given.
+	#[structopt(long = "new-password", conflicts_with = "new-password-filename")]
+	pub new_password: Option<String>,
+
+	/// File that contains the new password.
+	#[structopt(long = "new-password-filename", value_name = "PATH", parse(from_os_str))]
+	pub new_password_filename: Option<PathBuf>,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub keystore_params: KeystoreParams,
+
 	#[allow(missing_docs)]
 	#[structopt(flatten)]
 	pub shared_params: SharedParams,
 }
 
+/// The message given to the `key sign` and `key verify` commands.
+#[derive(Debug, StructOpt, Clone)]
+pub struct MessageParams {
+	/// The message. Read from stdin if not given.
+	#[structopt(long = "message", value_name = "MESSAGE")]
+	pub message: Option<String>,
+
+	/// The message is hex-encoded.
+	#[structopt(long = "hex")]
+	pub hex: bool,
+}
+
+/// The `key sign` command used to sign a message.
+#[derive(Debug, StructOpt, Clone)]
+pub struct SignCmd {
+	/// The secret key URI of the signer.
+	#[structopt(long = "suri", value_name = "SURI")]
+	pub suri: String,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub message_params: MessageParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub key_params: KeyParams,
+}
+
+/// The `key verify` command used to verify the signature of a message.
+#[derive(Debug, StructOpt, Clone)]
+pub struct VerifyCmd {
+	/// The hex-encoded signature.
+	#[structopt(value_name = "SIGNATURE")]
+	pub signature: String,
+
+	/// The public key of the signer, as hex, SS58 address or public key URI.
+	#[structopt(value_name = "URI")]
+	pub uri: String,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub message_params: MessageParams,
+
+	#[allow(missing_docs)]
+	#[structopt(flatten)]
+	pub key_params: KeyParams,
+}
+
+/// The `key` command used to manage keys.
+#[derive(Debug, StructOpt, Clone)]
+pub enum KeyCmd {
+	/// Generate a random key with a mnemonic phrase.
+	#[structopt(name = "generate")]
+	Generate(GenerateKeyCmd),
+
+	/// Display the public key and the SS58 address of a key.
+	#[structopt(name = "inspect")]
+	Inspect(InspectKeyCmd),
+
+	/// Insert a key into the keystore of the node.
+	#[structopt(name = "insert")]
+	Insert(InsertKeyCmd),
+
+	/// Change the password the keystore of the node is encrypted with.
+	#[structopt(name = "rotate-password")]
+	RotatePassword(RotatePasswordCmd),
+
+	/// Sign a message with a secret key.
+	#[structopt(name = "sign")]
+	Sign(SignCmd),
+
+	/// Verify the signature of a message.
+	#[structopt(name = "verify")]
+	Verify(VerifyCmd),
+}
+
 /// All core commands that are provided by default.
 ///
 /// The core commands are split into multiple subcommands and `Run` is the default subcommand. From
@@ -881,6 +1428,15 @@ pub enum CoreParams<CC, RP> {
 	/// Remove the whole chain data.
 	PurgeChain(PurgeChainCmd),
 
+	/// Copy the RocksDB database into a new ParityDb database.
+	MigrateDatabase(MigrateDatabaseCmd),
+
+	/// Inspect and repair the database.
+	Db(DbCmd),
+
+	/// Manage keys.
+	Key(KeyCmd),
+
 	/// Further custom subcommands.
 	Custom(CC),
 }
@@ -920,6 +1476,18 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 			PurgeChainCmd::augment_clap(SubCommand::with_name("purge-chain"))
 				.about("Remove the whole chain data.")
 		)
+		.subcommand(
+			MigrateDatabaseCmd::augment_clap(SubCommand::with_name("migrate-database"))
+				.about("Copy the RocksDB database into a new ParityDb database.")
+		)
+		.subcommand(
+			DbCmd::augment_clap(SubCommand::with_name("db"))
+				.about("Inspect and repair the database of a full node.")
+		)
+		.subcommand(
+			KeyCmd::augment_clap(SubCommand::with_name("key"))
+				.about("Generate, inspect and insert keys, and sign and verify messages.")
+		)
 	}
 
 	fn from_clap(matches: &::structopt::clap::ArgMatches) -> Self {
@@ -935,6 +1503,10 @@ impl<CC, RP> StructOpt for CoreParams<CC, RP> where
 			("revert", Some(matches)) => CoreParams::Revert(RevertCmd::from_clap(matches)),
 			("purge-chain", Some(matches)) =>
 				CoreParams::PurgeChain(PurgeChainCmd::from_clap(matches)),
+			("migrate-database", Some(matches)) =>
+				CoreParams::MigrateDatabase(MigrateDatabaseCmd::from_clap(matches)),
+			("db", Some(matches)) => CoreParams::Db(DbCmd::from_clap(matches)),
+			("key", Some(matches)) => CoreParams::Key(KeyCmd::from_clap(matches)),
 			(_, None) => CoreParams::Run(MergeParameters::from_clap(matches)),
 			_ => CoreParams::Custom(CC::from_clap(matches)),
 		}
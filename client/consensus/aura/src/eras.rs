@@ -0,0 +1,215 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Tracking of the authority set changes scheduled through consensus digests.
+//!
+//! The runtime signals the authorities of the next era with a `ScheduledChange` log, to be
+//! enacted a number of blocks after the block carrying it. The signaled eras are kept in a
+//! persistent tree superimposed over the forks of the chain, so that the authorities of a block
+//! can be found without the state of its parent.
+
+use std::ops::Add;
+use codec::{Encode, Decode};
+use fork_tree::ForkTree;
+use sc_client_api::backend::AuxStore;
+use sp_blockchain::{Result as ClientResult, Error as ClientError};
+use sp_runtime::traits::{Block as BlockT, NumberFor, One};
+
+const AURA_AUTHORITY_ERAS: &[u8] = b"aura_authority_eras";
+
+/// The authorities of an era, signaled by a block.
+#[derive(Clone, Debug, PartialEq, Encode, Decode)]
+pub struct Era<Number, A> {
+	/// The number of the last block authored by the previous authorities.
+	pub enact_at: Number,
+	/// The authorities of the era.
+	pub authorities: Vec<A>,
+}
+
+/// The eras signaled on the forks of the chain.
+#[derive(Clone, Encode, Decode)]
+pub struct AuthorityEras<Hash, Number, A> {
+	inner: ForkTree<Hash, Number, Era<Number, A>>,
+}
+
+impl<Hash, Number, A> AuthorityEras<Hash, Number, A> where
+	Hash: PartialEq + AsRef<[u8]> + AsMut<[u8]> + Clone,
+	Number: Ord + One + Add<Output=Number> + Copy,
+	A: Clone,
+{
+	/// Create a new, empty, era tracker.
+	pub fn new() -> Self {
+		AuthorityEras { inner: ForkTree::new() }
+	}
+
+	/// Note the era signaled by the given block.
+	pub fn import<F, E>(
+		&mut self,
+		hash: Hash,
+		number: Number,
+		era: Era<Number, A>,
+		is_descendent_of: &F,
+	) -> Result<(), fork_tree::Error<E>> where
+		E: std::error::Error,
+		F: Fn(&Hash, &Hash) -> Result<bool, E>,
+	{
+		self.inner.import(hash, number, era, is_descendent_of).map(|_| ())
+	}
+
+	/// Returns the authorities of the children of the given block, if an era enacted on its
+	/// branch is known. The given `is_descendent_of` function must know about the child
+	/// returned by `fake_head_hash` for the parent.
+	pub fn authorities_for_child_of<F, E>(
+		&self,
+		parent_hash: &Hash,
+		parent_number: Number,
+		is_descendent_of: &F,
+	) -> Result<Option<&Vec<A>>, fork_tree::Error<E>> where
+		E: std::error::Error,
+		F: Fn(&Hash, &Hash) -> Result<bool, E>,
+	{
+		// the era signaled by the parent itself must be found too, so we look for the
+		// ancestors of a child of the parent.
+		let predicate = |era: &Era<Number, A>| era.enact_at <= parent_number;
+		self.inner.find_node_where(
+			&fake_head_hash(parent_hash),
+			&(parent_number + One::one()),
+			is_descendent_of,
+			&predicate,
+		).map(|node| node.map(|node| &node.data.authorities))
+	}
+
+	/// Prune out the eras that can't be enacted on the children of the given finalized block,
+	/// except for the one in force.
+	pub fn prune_finalized<F, E>(
+		&mut self,
+		hash: &Hash,
+		number: Number,
+		is_descendent_of: &F,
+	) -> Result<(), fork_tree::Error<E>> where
+		E: std::error::Error,
+		F: Fn(&Hash, &Hash) -> Result<bool, E>,
+	{
+		let predicate = |era: &Era<Number, A>| era.enact_at <= number;
+		self.inner.prune(hash, &number, is_descendent_of, &predicate)
+	}
+}
+
+/// Returns the hash of a block that doesn't exist, to be passed to the `is_descendent_of`
+/// functions as a child of the given parent.
+pub(crate) fn fake_head_hash<H: AsRef<[u8]> + AsMut<[u8]> + Clone>(parent_hash: &H) -> H {
+	let mut h = parent_hash.clone();
+	// flip the first bit of the parent hash to create a hash which has not been in the chain
+	// before (assuming a strong hash function).
+	h.as_mut()[0] ^= 0b10000000;
+	h
+}
+
+/// The eras tracked for the chain of the given block type.
+pub type AuthorityErasFor<Block, A> = AuthorityEras<<Block as BlockT>::Hash, NumberFor<Block>, A>;
+
+/// Load the eras tracked so far, or an empty tracker if none has been signaled yet.
+pub(crate) fn load_authority_eras<Block: BlockT, A: Decode + Clone, B: AuxStore>(
+	backend: &B,
+) -> ClientResult<AuthorityErasFor<Block, A>> {
+	match backend.get_aux(AURA_AUTHORITY_ERAS)? {
+		None => Ok(AuthorityEras::new()),
+		Some(encoded) => Decode::decode(&mut &encoded[..])
+			.map_err(|e| ClientError::Backend(format!("Aura DB is corrupted: {}", e))),
+	}
+}
+
+/// Write the eras to disk, through the given function, e.g. along with the auxiliary data of
+/// an imported block.
+pub(crate) fn write_authority_eras<Block: BlockT, A: Encode, F, R>(
+	eras: &AuthorityErasFor<Block, A>,
+	write_aux: F,
+) -> R where
+	F: FnOnce(&[(&'static [u8], &[u8])]) -> R,
+{
+	let encoded = eras.encode();
+	write_aux(&[(AURA_AUTHORITY_ERAS, encoded.as_slice())])
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[derive(Debug, PartialEq)]
+	pub struct TestError;
+
+	impl std::fmt::Display for TestError {
+		fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+			write!(f, "TestError")
+		}
+	}
+
+	impl std::error::Error for TestError {}
+
+	type Hash = [u8; 1];
+
+	#[test]
+	fn authorities_are_enacted_after_the_delay() {
+		//
+		// A - B - C - D
+		//  \
+		//   — E
+		//
+		// (where B signals an era enacted at D, and A an era without delay)
+		let ancestry = |block: u8| -> &'static [u8] {
+			match block {
+				b'A' => b"A",
+				b'B' => b"AB",
+				b'C' => b"ABC",
+				b'D' => b"ABCD",
+				b'E' => b"AE",
+				_ => b"",
+			}
+		};
+		let is_descendent_of = |base: &Hash, block: &Hash| -> Result<bool, TestError> {
+			// the fake head of a block descends from the block itself.
+			let fake_head = fake_head_hash(block);
+			if ancestry(fake_head[0]).is_empty() {
+				Ok(base[0] != block[0] && ancestry(block[0]).contains(&base[0]))
+			} else {
+				Ok(ancestry(fake_head[0]).contains(&base[0]))
+			}
+		};
+
+		let mut eras = AuthorityEras::<Hash, u64, u64>::new();
+		eras.import(*b"A", 1, Era { enact_at: 1, authorities: vec![1] }, &is_descendent_of).unwrap();
+		eras.import(*b"B", 2, Era { enact_at: 4, authorities: vec![2] }, &is_descendent_of).unwrap();
+
+		let authorities_for_child_of = |eras: &AuthorityEras<Hash, u64, u64>, hash: &Hash, number| {
+			eras.authorities_for_child_of(hash, number, &is_descendent_of).unwrap().cloned()
+		};
+
+		assert_eq!(authorities_for_child_of(&eras, b"A", 1), Some(vec![1]));
+		assert_eq!(authorities_for_child_of(&eras, b"B", 2), Some(vec![1]));
+		assert_eq!(authorities_for_child_of(&eras, b"C", 3), Some(vec![1]));
+		assert_eq!(authorities_for_child_of(&eras, b"D", 4), Some(vec![2]));
+		assert_eq!(authorities_for_child_of(&eras, b"E", 2), Some(vec![1]));
+
+		// the finalization of C prunes nothing, as the era of B is pending.
+		eras.prune_finalized(b"C", 3, &is_descendent_of).unwrap();
+		assert_eq!(authorities_for_child_of(&eras, b"D", 4), Some(vec![2]));
+
+		// the finalization of D prunes the era of A.
+		eras.prune_finalized(b"D", 4, &is_descendent_of).unwrap();
+		assert_eq!(eras.inner.iter().map(|(hash, _, _)| *hash).collect::<Vec<_>>(), vec![*b"B"]);
+		assert_eq!(authorities_for_child_of(&eras, b"D", 4), Some(vec![2]));
+	}
+}
@@ -26,6 +26,11 @@
 //! Blocks from future steps will be either deferred or rejected depending on how
 //! far in the future they are.
 //!
+//! The runtime may schedule changes of the list of authorities through consensus
+//! digests, enacted some blocks later. The changes are tracked on every fork by
+//! the `AuraBlockImport`, so that the authorities of a block are known without
+//! querying the runtime.
+//!
 //! NOTE: Aura itself is designed to be generic over the crypto used.
 #![forbid(missing_docs, unsafe_code)]
 use std::{
@@ -33,8 +38,9 @@ use std::{
 	collections::HashMap
 };
 
-use futures::prelude::*;
+use futures::{prelude::*, future};
 use parking_lot::Mutex;
+use prometheus_endpoint::Registry;
 use log::{debug, info, trace};
 
 use codec::{Encode, Decode, Codec};
@@ -46,22 +52,26 @@ use sp_consensus::{
 use sp_consensus::import_queue::{
 	Verifier, BasicQueue, BoxJustificationImport, BoxFinalityProofImport,
 };
-use sc_client_api::backend::AuxStore;
+use sc_client_api::{backend::AuxStore, utils::is_descendent_of};
 use sc_client::BlockOf;
 use sp_blockchain::{
 	self, Result as CResult, well_known_cache_keys::{self, Id as CacheKeyId},
-	ProvideCache, HeaderBackend,
+	ProvideCache, HeaderBackend, HeaderMetadata,
 };
 use sp_block_builder::BlockBuilder as BlockBuilderApi;
 use sp_runtime::{generic::{BlockId, OpaqueDigestItemId}, Justification};
-use sp_runtime::traits::{Block as BlockT, Header, DigestItemFor, ProvideRuntimeApi, Zero, Member};
-use sp_core::crypto::Pair;
+use sp_runtime::traits::{
+	Block as BlockT, Header, DigestItemFor, ProvideRuntimeApi, Zero, One, Member, NumberFor,
+};
+use sp_core::{crypto::Pair, H256};
 use sp_inherents::{InherentDataProviders, InherentData};
 use sp_timestamp::{
 	TimestampInherentData, InherentType as TimestampInherent, InherentError as TIError
 };
 use sc_telemetry::{telemetry, CONSENSUS_TRACE, CONSENSUS_DEBUG, CONSENSUS_INFO};
-use sc_consensus_slots::{CheckedHeader, SlotWorker, SlotInfo, SlotCompatible};
+use sc_consensus_slots::{
+	CheckedHeader, SlotWorker, SlotInfo, SlotCompatible, ProposalBackoff, SlotLenience, SealFuture,
+};
 use sc_consensus_slots::check_equivocation;
 use sc_keystore::KeyStorePtr;
 use sp_api::ApiExt;
@@ -74,9 +84,13 @@ pub use sp_consensus_aura::{
 	},
 };
 pub use sp_consensus::SyncOracle;
+pub use sc_consensus_slots::BackoffStrategy;
 pub use digest::CompatibleDigestItem;
 
+use eras::Era;
+
 mod digest;
+mod eras;
 
 type AuthorityId<P> = <P as Pair>::Public;
 
@@ -148,9 +162,11 @@ pub fn start_aura<B, C, SC, E, I, P, SO, CAW, Error, H>(
 	force_authoring: bool,
 	keystore: KeyStorePtr,
 	can_author_with: CAW,
+	backoff_strategy: BackoffStrategy,
 ) -> Result<impl futures01::Future<Item = (), Error = ()>, sp_consensus::Error> where
-	B: BlockT<Header=H>,
-	C: ProvideRuntimeApi + BlockOf + ProvideCache<B> + AuxStore + Send + Sync,
+	B: BlockT<Header=H, Hash=H256>,
+	C: ProvideRuntimeApi + BlockOf + ProvideCache<B> + AuxStore + HeaderBackend<B>
+		+ HeaderMetadata<B, Error=sp_blockchain::Error> + Send + Sync,
 	C::Api: AuraApi<B, AuthorityId<P>>,
 	SC: SelectChain<B>,
 	E: Environment<B, Error=Error> + Send + Sync + 'static,
@@ -172,6 +188,7 @@ pub fn start_aura<B, C, SC, E, I, P, SO, CAW, Error, H>(
 		keystore,
 		sync_oracle: sync_oracle.clone(),
 		force_authoring,
+		proposal_backoff: ProposalBackoff::new(backoff_strategy),
 		_key_type: PhantomData::<P>,
 	};
 	register_aura_inherent_data_provider(
@@ -196,12 +213,14 @@ struct AuraWorker<C, E, I, P, SO> {
 	keystore: KeyStorePtr,
 	sync_oracle: SO,
 	force_authoring: bool,
+	proposal_backoff: ProposalBackoff,
 	_key_type: PhantomData<P>,
 }
 
 impl<H, B, C, E, I, P, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for AuraWorker<C, E, I, P, SO> where
-	B: BlockT<Header=H>,
-	C: ProvideRuntimeApi + BlockOf + ProvideCache<B> + Sync,
+	B: BlockT<Header=H, Hash=H256>,
+	C: ProvideRuntimeApi + BlockOf + ProvideCache<B> + AuxStore + HeaderBackend<B>
+		+ HeaderMetadata<B, Error=sp_blockchain::Error> + Sync,
 	C::Api: AuraApi<B, AuthorityId<P>>,
 	E: Environment<B, Error=Error>,
 	E::Proposer: Proposer<B, Error=Error>,
@@ -229,7 +248,7 @@ impl<H, B, C, E, I, P, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for Au
 	}
 
 	fn epoch_data(&self, header: &B::Header, _slot_number: u64) -> Result<Self::EpochData, sp_consensus::Error> {
-		authorities(self.client.as_ref(), &BlockId::Hash(header.hash()))
+		authorities_for_child_of(self.client.as_ref(), header.hash(), *header.number())
 	}
 
 	fn authorities_len(&self, epoch_data: &Self::EpochData) -> usize {
@@ -261,14 +280,14 @@ impl<H, B, C, E, I, P, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for Au
 		&B::Hash,
 		Vec<B::Extrinsic>,
 		Self::Claim,
-	) -> sp_consensus::BlockImportParams<B> + Send> {
+	) -> SealFuture<B> + Send> {
 		Box::new(|header, header_hash, body, pair| {
 			// sign the pre-sealed hash of the block and then
 			// add it to a digest item.
 			let signature = pair.sign(header_hash.as_ref());
 			let signature_digest_item = <DigestItemFor<B> as CompatibleDigestItem<P>>::aura_seal(signature);
 
-			BlockImportParams {
+			Box::pin(future::ready(Ok(BlockImportParams {
 				origin: BlockOrigin::Own,
 				header,
 				justification: None,
@@ -279,7 +298,8 @@ impl<H, B, C, E, I, P, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for Au
 				fork_choice: ForkChoiceStrategy::LongestChain,
 				allow_missing_state: false,
 				import_existing: false,
-			}
+				state: None,
+			})))
 		})
 	}
 
@@ -297,36 +317,25 @@ impl<H, B, C, E, I, P, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for Au
 		})
 	}
 
-	fn proposing_remaining_duration(
-		&self,
-		head: &B::Header,
-		slot_info: &SlotInfo
-	) -> Option<std::time::Duration> {
-		// never give more than 20 times more lenience.
-		const BACKOFF_CAP: u64 = 20;
-
-		let slot_remaining = self.slot_remaining_duration(slot_info);
-		let parent_slot = match find_pre_digest::<B, P>(head) {
-			Err(_) => return Some(slot_remaining),
-			Ok(d) => d,
-		};
+	fn header_slot(&self, header: &B::Header) -> Option<u64> {
+		find_pre_digest::<B, P>(header).ok()
+	}
 
-		// we allow a lenience of the number of slots since the head of the
-		// chain was produced, minus 1 (since there is always a difference of at least 1)
-		//
-		// linear back-off.
-		// in normal cases we only attempt to issue blocks up to the end of the slot.
-		// when the chain has been stalled for a few slots, we give more lenience.
-		let slot_lenience = slot_info.number.saturating_sub(parent_slot + 1);
-		let slot_lenience = std::cmp::min(slot_lenience, BACKOFF_CAP);
-		let slot_lenience = Duration::from_secs(slot_lenience * slot_info.duration);
-		Some(slot_lenience + slot_remaining)
+	fn slot_lenience(&self) -> SlotLenience {
+		// linear back-off, never giving more than 20 slots of lenience.
+		SlotLenience::Linear { cap: 20 }
+	}
+
+	fn proposal_backoff(&self) -> Option<&ProposalBackoff> {
+		Some(&self.proposal_backoff)
+	}
 	}
 }
 
 impl<H, B: BlockT, C, E, I, P, Error, SO> SlotWorker<B> for AuraWorker<C, E, I, P, SO> where
-	B: BlockT<Header=H>,
-	C: ProvideRuntimeApi + BlockOf + ProvideCache<B> + Sync + Send,
+	B: BlockT<Header=H, Hash=H256>,
+	C: ProvideRuntimeApi + BlockOf + ProvideCache<B> + AuxStore + HeaderBackend<B>
+		+ HeaderMetadata<B, Error=sp_blockchain::Error> + Sync + Send,
 	C::Api: AuraApi<B, AuthorityId<P>>,
 	E: Environment<B, Error=Error> + Send + Sync,
 	E::Proposer: Proposer<B, Error=Error>,
@@ -479,6 +488,17 @@ pub struct AuraVerifier<C, P, T> {
 	transaction_pool: Option<Arc<T>>,
 }
 
+impl<C, P, T> Clone for AuraVerifier<C, P, T> {
+	fn clone(&self) -> Self {
+		AuraVerifier {
+			client: self.client.clone(),
+			phantom: PhantomData,
+			inherent_data_providers: self.inherent_data_providers.clone(),
+			transaction_pool: self.transaction_pool.clone(),
+		}
+	}
+}
+
 impl<C, P, T> AuraVerifier<C, P, T>
 	where P: Send + Sync + 'static
 {
@@ -534,8 +554,9 @@ impl<C, P, T> AuraVerifier<C, P, T>
 }
 
 #[forbid(deprecated)]
-impl<B: BlockT, C, P, T> Verifier<B> for AuraVerifier<C, P, T> where
-	C: ProvideRuntimeApi + Send + Sync + sc_client_api::backend::AuxStore + ProvideCache<B> + BlockOf,
+impl<B: BlockT<Hash=H256>, C, P, T> Verifier<B> for AuraVerifier<C, P, T> where
+	C: ProvideRuntimeApi + Send + Sync + sc_client_api::backend::AuxStore + ProvideCache<B> + BlockOf
+		+ HeaderBackend<B> + HeaderMetadata<B, Error=sp_blockchain::Error>,
 	C::Api: BlockBuilderApi<B> + AuraApi<B, AuthorityId<P>> + ApiExt<B, Error = sp_blockchain::Error>,
 	DigestItemFor<B>: CompatibleDigestItem<P>,
 	P: Pair + Send + Sync + 'static,
@@ -557,7 +578,8 @@ impl<B: BlockT, C, P, T> Verifier<B> for AuraVerifier<C, P, T> where
 			.map_err(|e| format!("Could not extract timestamp and slot: {:?}", e))?;
 		let hash = header.hash();
 		let parent_hash = *header.parent_hash();
-		let authorities = authorities(self.client.as_ref(), &BlockId::Hash(parent_hash))
+		let parent_number = *header.number() - One::one();
+		let authorities = authorities_for_child_of(self.client.as_ref(), parent_hash, parent_number)
 			.map_err(|e| format!("Could not fetch authorities at {:?}: {:?}", parent_hash, e))?;
 
 		// we add one to allow for some small drift.
@@ -629,6 +651,7 @@ impl<B: BlockT, C, P, T> Verifier<B> for AuraVerifier<C, P, T> where
 					fork_choice: ForkChoiceStrategy::LongestChain,
 					allow_missing_state: false,
 					import_existing: false,
+					state: None,
 				};
 
 				Ok((block_import_params, maybe_keys))
@@ -694,6 +717,53 @@ fn authorities<A, B, C>(client: &C, at: &BlockId<B>) -> Result<Vec<A>, Consensus
 		.ok_or_else(|| sp_consensus::Error::InvalidAuthoritiesSet.into())
 }
 
+/// Returns the authorities of the children of the given block: the ones of the latest era
+/// enacted on its branch if any, or the ones returned by the runtime otherwise.
+fn authorities_for_child_of<A, B, C>(
+	client: &C,
+	parent_hash: B::Hash,
+	parent_number: NumberFor<B>,
+) -> Result<Vec<A>, ConsensusError> where
+	A: Codec + Clone,
+	B: BlockT<Hash=H256>,
+	C: ProvideRuntimeApi + BlockOf + ProvideCache<B> + AuxStore + HeaderBackend<B>
+		+ HeaderMetadata<B, Error=sp_blockchain::Error>,
+	C::Api: AuraApi<B, A>,
+{
+	let eras = eras::load_authority_eras::<B, A, _>(client)
+		.map_err(|e| ConsensusError::ChainLookup(e.to_string()))?;
+	let fake_head_hash = eras::fake_head_hash(&parent_hash);
+	let enacted = eras.authorities_for_child_of(
+		&parent_hash,
+		parent_number,
+		&is_descendent_of(client, Some((fake_head_hash, parent_hash))),
+	).map_err(|e| ConsensusError::ChainLookup(format!("{:?}", e)))?;
+
+	match enacted {
+		Some(authorities) => Ok(authorities.clone()),
+		None => authorities(client, &BlockId::Hash(parent_hash)),
+	}
+}
+
+/// Returns the authority set change signaled by the given header, as the new authorities
+/// along with the delay of the change.
+fn find_scheduled_change<B: BlockT, P: Pair>(header: &B::Header) -> Option<(Vec<AuthorityId<P>>, u32)>
+	where P::Public: Decode,
+{
+	header.digest()
+		.logs()
+		.iter()
+		.filter_map(|l| l.try_to::<ConsensusLog<AuthorityId<P>>>(
+			OpaqueDigestItemId::Consensus(&AURA_ENGINE_ID)
+		))
+		.find_map(|l| match l {
+			ConsensusLog::ScheduledChange(change) => Some((change.next_authorities, change.delay)),
+			// the legacy changes are enacted right away.
+			ConsensusLog::AuthoritiesChange(authorities) => Some((authorities, 0)),
+			_ => None,
+		})
+}
+
 /// The Aura import queue type.
 pub type AuraImportQueue<B> = BasicQueue<B>;
 
@@ -716,6 +786,9 @@ fn register_aura_inherent_data_provider(
 pub struct AuraBlockImport<Block: BlockT, C, I: BlockImport<Block>, P> {
 	inner: I,
 	client: Arc<C>,
+	/// Held from reading the authority eras until the block writing them back is imported,
+	/// so that concurrent imports don't lose each other's changes.
+	eras_lock: Arc<Mutex<()>>,
 	_phantom: PhantomData<(Block, P)>,
 }
 
@@ -724,6 +797,7 @@ impl<Block: BlockT, C, I: Clone + BlockImport<Block>, P> Clone for AuraBlockImpo
 		AuraBlockImport {
 			inner: self.inner.clone(),
 			client: self.client.clone(),
+			eras_lock: self.eras_lock.clone(),
 			_phantom: PhantomData,
 		}
 	}
@@ -738,15 +812,16 @@ impl<Block: BlockT, C, I: BlockImport<Block>, P> AuraBlockImport<Block, C, I, P>
 		Self {
 			inner,
 			client,
+			eras_lock: Arc::new(Mutex::new(())),
 			_phantom: PhantomData,
 		}
 	}
 }
 
-impl<Block: BlockT, C, I, P> BlockImport<Block> for AuraBlockImport<Block, C, I, P> where
+impl<Block: BlockT<Hash=H256>, C, I, P> BlockImport<Block> for AuraBlockImport<Block, C, I, P> where
 	I: BlockImport<Block> + Send + Sync,
 	I::Error: Into<ConsensusError>,
-	C: HeaderBackend<Block>,
+	C: HeaderBackend<Block> + HeaderMetadata<Block, Error=sp_blockchain::Error> + AuxStore,
 	P: Pair + Send + Sync + 'static,
 	P::Public: Clone + Eq + Send + Sync + Hash + Debug + Encode + Decode,
 	P::Signature: Encode + Decode,
@@ -762,9 +837,15 @@ impl<Block: BlockT, C, I, P> BlockImport<Block> for AuraBlockImport<Block, C, I,
 
 	fn import_block(
 		&mut self,
-		block: BlockImportParams<Block>,
+		mut block: BlockImportParams<Block>,
 		new_cache: HashMap<CacheKeyId, Vec<u8>>,
 	) -> Result<ImportResult, Self::Error> {
+		// Blocks imported along with their state have no parent to compare the slot with, and
+		// their finality has already been proven.
+		if block.state.is_some() {
+			return self.inner.import_block(block, new_cache).map_err(Into::into)
+		}
+
 		let hash = block.post_header().hash();
 		let slot_number = find_pre_digest::<Block, P>(&block.header)
 			.expect("valid Aura headers must contain a predigest; \
@@ -790,6 +871,33 @@ impl<Block: BlockT, C, I, P> BlockImport<Block> for AuraBlockImport<Block, C, I,
 			);
 		}
 
+		// track the era signaled by the block, if any, writing it along with the block.
+		let mut _eras_guard = None;
+		if let Some((authorities, delay)) = find_scheduled_change::<Block, P>(&block.header) {
+			_eras_guard = Some(self.eras_lock.lock());
+			let number = *block.header.number();
+			let mut eras = eras::load_authority_eras::<Block, AuthorityId<P>, _>(&*self.client)
+				.map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+
+			eras.import(
+				hash,
+				number,
+				Era { enact_at: number + delay.into(), authorities },
+				&is_descendent_of(&*self.client, Some((hash, parent_hash))),
+			).map_err(|e| ConsensusError::ClientImport(format!("Error importing era: {:?}", e)))?;
+
+			let info = self.client.info();
+			eras.prune_finalized(
+				&info.finalized_hash,
+				info.finalized_number,
+				&is_descendent_of::<_, _, H256>(&*self.client, None),
+			).map_err(|e| ConsensusError::ClientImport(format!("Error pruning eras: {:?}", e)))?;
+
+			eras::write_authority_eras::<Block, _, _, _>(&eras, |insert| block.auxiliary.extend(
+				insert.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec())))
+			));
+		}
+
 		self.inner.import_block(block, new_cache)
 			.map_err(Into::into)
 	}
@@ -804,10 +912,13 @@ pub fn import_queue<B, I, C, P, T>(
 	client: Arc<C>,
 	inherent_data_providers: InherentDataProviders,
 	transaction_pool: Option<Arc<T>>,
+	registry: Option<&Registry>,
+	verification_workers: usize,
 ) -> Result<AuraImportQueue<B>, sp_consensus::Error> where
-	B: BlockT,
+	B: BlockT<Hash=H256>,
 	C::Api: BlockBuilderApi<B> + AuraApi<B, AuthorityId<P>> + ApiExt<B, Error = sp_blockchain::Error>,
-	C: 'static + ProvideRuntimeApi + BlockOf + ProvideCache<B> + Send + Sync + AuxStore + HeaderBackend<B>,
+	C: 'static + ProvideRuntimeApi + BlockOf + ProvideCache<B> + Send + Sync + AuxStore + HeaderBackend<B>
+		+ HeaderMetadata<B, Error=sp_blockchain::Error>,
 	I: BlockImport<B,Error=ConsensusError> + Send + Sync + 'static,
 	DigestItemFor<B>: CompatibleDigestItem<P>,
 	P: Pair + Send + Sync + 'static,
@@ -824,11 +935,13 @@ pub fn import_queue<B, I, C, P, T>(
 		phantom: PhantomData,
 		transaction_pool,
 	};
-	Ok(BasicQueue::new(
+	Ok(BasicQueue::new_with_verification_workers(
 		verifier,
 		Box::new(block_import),
 		justification_import,
 		finality_proof_import,
+		registry,
+		verification_workers,
 	))
 }
 
@@ -994,6 +1107,7 @@ mod tests {
 				false,
 				keystore,
 				sp_consensus::AlwaysCanAuthor,
+				BackoffStrategy::Disabled,
 			).expect("Starts aura");
 
 			runtime.spawn(aura);
@@ -0,0 +1,223 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC interface for the BABE consensus algorithm.
+
+#![warn(missing_docs)]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use futures::{FutureExt, TryFutureExt};
+use jsonrpc_core::{Error as RpcError, ErrorCode};
+use jsonrpc_derive::rpc;
+use serde::Serialize;
+use sc_consensus_babe::{AuthorityId, BabeLink, Epoch, SlotNumber};
+use sc_keystore::KeyStorePtr;
+use sc_rpc_api::DenyUnsafe;
+use sp_blockchain::{Error as ClientError, HeaderBackend, HeaderMetadata};
+use sp_core::{Bytes, H256};
+use sp_runtime::traits::Block as BlockT;
+
+pub use self::gen_client::Client as BabeClient;
+
+/// Future's type for jsonrpc.
+type FutureResult<T> = Box<dyn jsonrpc_core::futures::Future<Item = T, Error = RpcError> + Send>;
+
+/// Substrate BABE RPC API
+#[rpc]
+pub trait BabeApi {
+	/// Returns the data of the current epoch, along with the slots of the rest of the epoch the
+	/// keys of the node's keystore can author.
+	///
+	/// This method exposes the authoring slots of the node and must only be exposed to its
+	/// operator.
+	#[rpc(name = "babe_epochAuthorship")]
+	fn epoch_authorship(&self) -> FutureResult<EpochAuthorship>;
+}
+
+/// Slots of an epoch an authority can author.
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Slots {
+	/// The primary slots, claimed with the VRF.
+	pub primary: Vec<SlotNumber>,
+	/// The secondary slots, not including the ones claimed as primary.
+	pub secondary: Vec<SlotNumber>,
+}
+
+impl From<sc_consensus_babe::EpochAuthorship> for Slots {
+	fn from(authorship: sc_consensus_babe::EpochAuthorship) -> Self {
+		Slots {
+			primary: authorship.primary,
+			secondary: authorship.secondary,
+		}
+	}
+}
+
+/// The current epoch and the slots the local keys can author in it.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EpochAuthorship {
+	/// Index of the epoch.
+	pub epoch_index: u64,
+	/// First slot of the epoch.
+	pub start_slot: SlotNumber,
+	/// Number of slots of the epoch.
+	pub duration: SlotNumber,
+	/// The slot the node is currently at.
+	pub current_slot: SlotNumber,
+	/// Duration of a slot in milliseconds.
+	pub slot_duration: u64,
+	/// Randomness of the epoch.
+	pub randomness: Bytes,
+	/// Authorities of the epoch along with their weight.
+	pub authorities: Vec<(AuthorityId, u64)>,
+	/// Whether the chain runs with secondary slots.
+	pub secondary_slots: bool,
+	/// Slots from the current one to the end of the epoch the local keys can author.
+	pub authorship: HashMap<AuthorityId, Slots>,
+}
+
+/// Error type of this RPC api.
+pub enum Error {
+	/// The epoch of the best block couldn't be fetched.
+	FetchEpoch,
+}
+
+impl From<Error> for i64 {
+	fn from(e: Error) -> i64 {
+		match e {
+			Error::FetchEpoch => 1,
+		}
+	}
+}
+
+/// A struct that implements the [`BabeApi`].
+pub struct BabeRpcHandler<C, Block: BlockT> {
+	client: Arc<C>,
+	babe_link: BabeLink<Block>,
+	keystore: KeyStorePtr,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl<C, Block: BlockT> BabeRpcHandler<C, Block> {
+	/// Create new `BabeRpcHandler` reporting the epochs tracked by the given `BabeLink`, and
+	/// the authorship of the keys of the given keystore.
+	pub fn new(
+		client: Arc<C>,
+		babe_link: BabeLink<Block>,
+		keystore: KeyStorePtr,
+		deny_unsafe: DenyUnsafe,
+	) -> Self {
+		BabeRpcHandler { client, babe_link, keystore, deny_unsafe }
+	}
+}
+
+impl<C, Block> BabeApi for BabeRpcHandler<C, Block>
+	where
+		Block: BlockT<Hash=H256>,
+		C: HeaderBackend<Block> + HeaderMetadata<Block, Error=ClientError> + Send + Sync + 'static,
+{
+	fn epoch_authorship(&self) -> FutureResult<EpochAuthorship> {
+		if let Err(e) = self.deny_unsafe.check_if_safe() {
+			return Box::new(jsonrpc_core::futures::future::err(e.into()));
+		}
+		let config = self.babe_link.config().clone();
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+		let current_slot = now.as_millis() as u64 / config.slot_duration;
+
+		let info = self.client.info();
+		let epoch = self.babe_link.epoch_for_child_of(
+			&*self.client,
+			&info.best_hash,
+			info.best_number,
+			current_slot,
+		)
+			.map_err(|e| RpcError {
+				code: ErrorCode::ServerError(Error::FetchEpoch.into()),
+				message: "Unable to fetch the current epoch.".into(),
+				data: Some(format!("{:?}", e).into()),
+			})
+			.and_then(|epoch| epoch.ok_or_else(|| RpcError {
+				code: ErrorCode::ServerError(Error::FetchEpoch.into()),
+				message: "The best block is unknown to the epoch changes tree.".into(),
+				data: None,
+			}));
+		let epoch = match epoch {
+			Ok(epoch) => epoch,
+			Err(e) => return Box::new(jsonrpc_core::futures::future::err(e)),
+		};
+
+		// The keystore signs the VRF of every slot, which may take a while with a remote signer.
+		let keystore = self.keystore.clone();
+		let future = async move {
+			let authorship = sc_consensus_babe::epoch_authorship(current_slot, &epoch, &config, &keystore)
+				.await
+				.into_iter()
+				.map(|(id, authorship)| (id, authorship.into()))
+				.collect();
+
+			let Epoch { epoch_index, start_slot, duration, authorities, randomness } = epoch;
+			Ok(EpochAuthorship {
+				epoch_index,
+				start_slot,
+				duration,
+				current_slot,
+				slot_duration: config.slot_duration,
+				randomness: randomness.to_vec().into(),
+				authorities,
+				secondary_slots: config.secondary_slots,
+				authorship,
+			})
+		}.boxed();
+
+		Box::new(future.compat())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::crypto::Public;
+
+	#[test]
+	fn epoch_authorship_serialization() {
+		let authority = AuthorityId::from_slice(&[1; 32]);
+		let mut authorship = HashMap::new();
+		authorship.insert(authority.clone(), Slots { primary: vec![12], secondary: vec![10, 15] });
+		let epoch = EpochAuthorship {
+			epoch_index: 1,
+			start_slot: 10,
+			duration: 10,
+			current_slot: 10,
+			slot_duration: 6000,
+			randomness: vec![0; 32].into(),
+			authorities: vec![(authority.clone(), 1)],
+			secondary_slots: true,
+			authorship,
+		};
+
+		let json = serde_json::to_value(&epoch).unwrap();
+		let authority_json = serde_json::to_value(&authority).unwrap();
+		let authority_key = authority_json.as_str().unwrap();
+		assert_eq!(json["epochIndex"], 1);
+		assert_eq!(json["slotDuration"], 6000);
+		assert_eq!(json["secondarySlots"], true);
+		assert_eq!(json["authorship"][authority_key]["primary"], serde_json::json!([12]));
+		assert_eq!(json["authorship"][authority_key]["secondary"], serde_json::json!([10, 15]));
+	}
+}
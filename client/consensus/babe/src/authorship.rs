@@ -16,14 +16,20 @@
 
 //! BABE authority selection and slot claiming.
 
+use std::collections::HashMap;
+use futures::{Future, FutureExt, future};
 use merlin::Transcript;
 use sp_consensus_babe::{AuthorityId, BabeAuthorityWeight, BABE_ENGINE_ID, BABE_VRF_PREFIX};
-use sp_consensus_babe::{Epoch, SlotNumber, AuthorityPair, BabePreDigest, BabeConfiguration};
-use sp_core::{U256, blake2_256};
+use sp_consensus_babe::{Epoch, SlotNumber, BabePreDigest, BabeConfiguration};
+use sp_core::{
+	U256, blake2_256, sr25519, crypto::Public, traits::{BareCryptoStore, SignatureFuture},
+	vrf::{VRFTranscriptData, VRFTranscriptValue, VRFSignature},
+};
+use sp_application_crypto::AppKey;
 use codec::Encode;
 use schnorrkel::vrf::VRFInOut;
-use sp_core::Pair;
 use sc_keystore::KeyStorePtr;
+use log::debug;
 
 /// Calculates the primary selection threshold for a given authority, taking
 /// into account `c` (`1 - c` represents the probability of a slot being empty).
@@ -82,6 +88,22 @@ pub(super) fn secondary_slot_author(
 	Some(&expected_author.0)
 }
 
+/// Describes the transcript built by [`make_transcript`], for the keystore to sign it.
+fn make_transcript_data(
+	randomness: &[u8],
+	slot_number: u64,
+	epoch: u64,
+) -> VRFTranscriptData {
+	VRFTranscriptData {
+		label: &BABE_ENGINE_ID,
+		items: vec![
+			(&b"slot number"[..], VRFTranscriptValue::U64(slot_number)),
+			(&b"current epoch"[..], VRFTranscriptValue::U64(epoch)),
+			(&b"chain randomness"[..], VRFTranscriptValue::Bytes(randomness.to_vec())),
+		],
+	}
+}
+
 #[allow(deprecated)]
 pub(super) fn make_transcript(
 	randomness: &[u8],
@@ -96,6 +118,109 @@ pub(super) fn make_transcript(
 }
 
 
+/// Returns the authorities of the given list whose keys are in the keystore, with their index.
+fn local_authorities<'a>(
+	authorities: &'a [(AuthorityId, BabeAuthorityWeight)],
+	keystore: &KeyStorePtr,
+) -> Vec<(&'a AuthorityId, usize)> {
+	let keys = keystore.read().sr25519_public_keys(AuthorityId::ID);
+
+	authorities.iter()
+		.enumerate()
+		.filter(|(_, (id, _))| keys.iter().any(|key| key.as_slice() == id.as_slice()))
+		.map(|(i, (id, _))| (id, i))
+		.collect()
+}
+
+/// Requests the keystore to sign the VRF of the given slot with the key of the authority.
+fn sign_vrf(
+	slot_number: SlotNumber,
+	epoch: &Epoch,
+	keystore: &KeyStorePtr,
+	authority_id: &AuthorityId,
+) -> SignatureFuture<VRFSignature> {
+	let public = sr25519::Public::from_slice(authority_id.as_slice());
+	let transcript_data = make_transcript_data(&epoch.randomness, slot_number, epoch.epoch_index);
+	keystore.read().sr25519_vrf_sign(AuthorityId::ID, &public, transcript_data)
+}
+
+/// Requests the keystore to sign the VRF of the given slot with the keys of all the local
+/// authorities of the epoch. Resolves to the signatures the keystore made.
+fn sign_vrfs(
+	slot_number: SlotNumber,
+	epoch: &Epoch,
+	keystore: &KeyStorePtr,
+) -> impl Future<Output = HashMap<AuthorityId, VRFSignature>> {
+	let requests = local_authorities(&epoch.authorities, keystore).into_iter()
+		.map(|(authority_id, _)| {
+			let authority_id = authority_id.clone();
+			sign_vrf(slot_number, epoch, keystore, &authority_id)
+				.map(move |signature| (authority_id, signature))
+		})
+		.collect::<Vec<_>>();
+
+	future::join_all(requests).map(move |signatures| signatures.into_iter()
+		.filter_map(|(authority_id, signature)| signature
+			.map_err(|e| debug!(target: "babe", "Failed to sign the VRF of slot {}: {}", slot_number, e))
+			.ok()
+			.map(|signature| (authority_id, signature))
+		)
+		.collect()
+	)
+}
+
+/// Slot, epoch index, epoch randomness and authority of a VRF signature.
+type VrfSignatureKey = (SlotNumber, u64, [u8; 32], AuthorityId);
+
+/// VRF signatures requested from the keystore ahead of their slot.
+///
+/// The keystore may be remote and slow to answer, so the signatures of a slot are requested
+/// one slot in advance, and claiming a slot never waits for the keystore.
+#[derive(Default)]
+pub(super) struct VrfSignatures {
+	pending: HashMap<VrfSignatureKey, SignatureFuture<VRFSignature>>,
+}
+
+impl VrfSignatures {
+	/// Requests the signatures of the given slot by the local authorities of the epoch, unless
+	/// they were already requested.
+	fn request(&mut self, slot_number: SlotNumber, epoch: &Epoch, keystore: &KeyStorePtr) {
+		for (authority_id, _) in local_authorities(&epoch.authorities, keystore) {
+			let key = (slot_number, epoch.epoch_index, epoch.randomness, authority_id.clone());
+			if !self.pending.contains_key(&key) {
+				self.pending.insert(key, sign_vrf(slot_number, epoch, keystore, authority_id));
+			}
+		}
+	}
+
+	/// Takes the signature of the given slot by the authority, if the keystore already
+	/// answered.
+	fn take(
+		&mut self,
+		slot_number: SlotNumber,
+		epoch: &Epoch,
+		authority_id: &AuthorityId,
+	) -> Option<VRFSignature> {
+		let key = (slot_number, epoch.epoch_index, epoch.randomness, authority_id.clone());
+		match self.pending.remove(&key)?.now_or_never() {
+			Some(Ok(signature)) => Some(signature),
+			Some(Err(e)) => {
+				debug!(target: "babe", "Failed to sign the VRF of slot {}: {}", slot_number, e);
+				None
+			},
+			None => {
+				debug!(target: "babe", "The VRF of slot {} wasn't signed in time", slot_number);
+				None
+			},
+		}
+	}
+
+	/// Drops the signatures of the slots before the given one.
+	fn prune(&mut self, slot_number: SlotNumber) {
+		self.pending.retain(|(slot, ..), _| *slot >= slot_number);
+	}
+}
+
 /// Claim a secondary slot if it is our turn to propose, returning the
 /// pre-digest to use when authoring the block, or `None` if it is not our turn
 /// to propose.
@@ -104,7 +229,7 @@ fn claim_secondary_slot(
 	authorities: &[(AuthorityId, BabeAuthorityWeight)],
 	keystore: &KeyStorePtr,
 	randomness: [u8; 32],
-) -> Option<(BabePreDigest, AuthorityPair)> {
+) -> Option<(BabePreDigest, AuthorityId)> {
 	if authorities.is_empty() {
 		return None;
 	}
@@ -115,21 +240,14 @@ fn claim_secondary_slot(
 		randomness,
 	)?;
 
-	let keystore = keystore.read();
-
-	for (pair, authority_index) in authorities.iter()
-		.enumerate()
-		.flat_map(|(i, a)| {
-			keystore.key_pair::<AuthorityPair>(&a.0).ok().map(|kp| (kp, i))
-		})
-	{
-		if pair.public() == *expected_author {
+	for (authority_id, authority_index) in local_authorities(authorities, keystore) {
+		if authority_id == expected_author {
 			let pre_digest = BabePreDigest::Secondary {
 				slot_number,
 				authority_index: authority_index as u32,
 			};
 
-			return Some((pre_digest, pair));
+			return Some((pre_digest, authority_id.clone()));
 		}
 	}
 
@@ -140,13 +258,52 @@ fn claim_secondary_slot(
 /// a primary VRF based slot. If we are not able to claim it, then if we have
 /// secondary slots enabled for the given epoch, we will fallback to trying to
 /// claim a secondary slot.
-pub(super) fn claim_slot(
+///
+/// The key of the claiming authority stays in the keystore, which signs the VRF. The claim
+/// resolves once the keystore answered, see [`claim_slot_with_signatures`] for the variant
+/// which doesn't wait for it.
+pub(super) async fn claim_slot(
+	slot_number: SlotNumber,
+	epoch: &Epoch,
+	config: &BabeConfiguration,
+	keystore: &KeyStorePtr,
+) -> Option<(BabePreDigest, AuthorityId)> {
+	let mut signatures = sign_vrfs(slot_number, epoch, keystore).await;
+	claim_slot_with(slot_number, epoch, config, keystore, |authority_id| {
+		signatures.remove(authority_id)
+	})
+}
+
+/// Tries to claim the given slot number like [`claim_slot`], with the VRF signatures requested
+/// ahead of time. A primary slot whose signature isn't ready yet is not claimed.
+///
+/// The signatures of the next slot are requested, if it belongs to the same epoch.
+pub(super) fn claim_slot_with_signatures(
+	slot_number: SlotNumber,
+	epoch: &Epoch,
+	config: &BabeConfiguration,
+	keystore: &KeyStorePtr,
+	signatures: &mut VrfSignatures,
+) -> Option<(BabePreDigest, AuthorityId)> {
+	signatures.prune(slot_number);
+	signatures.request(slot_number, epoch, keystore);
+	if slot_number + 1 < epoch.start_slot + epoch.duration {
+		signatures.request(slot_number + 1, epoch, keystore);
+	}
+
+	claim_slot_with(slot_number, epoch, config, keystore, |authority_id| {
+		signatures.take(slot_number, epoch, authority_id)
+	})
+}
+
+fn claim_slot_with(
 	slot_number: SlotNumber,
 	epoch: &Epoch,
 	config: &BabeConfiguration,
 	keystore: &KeyStorePtr,
-) -> Option<(BabePreDigest, AuthorityPair)> {
-	claim_primary_slot(slot_number, epoch, config.c, keystore)
+	vrf_signature: impl FnMut(&AuthorityId) -> Option<VRFSignature>,
+) -> Option<(BabePreDigest, AuthorityId)> {
+	claim_primary_slot(slot_number, epoch, config.c, keystore, vrf_signature)
 		.or_else(|| {
 			if config.secondary_slots {
 				claim_secondary_slot(
@@ -161,11 +318,6 @@ pub(super) fn claim_slot(
 		})
 }
 
-fn get_keypair(q: &AuthorityPair) -> &schnorrkel::Keypair {
-	use sp_core::crypto::IsWrappedBy;
-	sp_core::sr25519::Pair::from_ref(q).as_ref()
-}
-
 /// Claim a primary slot if it is our turn.  Returns `None` if it is not our turn.
 /// This hashes the slot number, epoch, genesis hash, and chain randomness into
 /// the VRF.  If the VRF produces a value less than `threshold`, it is our turn,
@@ -175,40 +327,134 @@ fn claim_primary_slot(
 	epoch: &Epoch,
 	c: (u64, u64),
 	keystore: &KeyStorePtr,
-) -> Option<(BabePreDigest, AuthorityPair)> {
-	let Epoch { authorities, randomness, epoch_index, .. } = epoch;
-	let keystore = keystore.read();
-
-	for (pair, authority_index) in authorities.iter()
-		.enumerate()
-		.flat_map(|(i, a)| {
-			keystore.key_pair::<AuthorityPair>(&a.0).ok().map(|kp| (kp, i))
-		})
-	{
-		let transcript = super::authorship::make_transcript(randomness, slot_number, *epoch_index);
-
-		// Compute the threshold we will use.
-		//
-		// We already checked that authorities contains `key.public()`, so it can't
-		// be empty.  Therefore, this division in `calculate_threshold` is safe.
-		let threshold = super::authorship::calculate_primary_threshold(c, authorities, authority_index);
-
-		let pre_digest = get_keypair(&pair)
-			.vrf_sign_after_check(transcript, |inout| super::authorship::check_primary_threshold(inout, threshold))
-			.map(|s| {
-				BabePreDigest::Primary {
-					slot_number,
-					vrf_output: s.0.to_output(),
-					vrf_proof: s.1,
-					authority_index: authority_index as u32,
-				}
-			});
+	mut vrf_signature: impl FnMut(&AuthorityId) -> Option<VRFSignature>,
+) -> Option<(BabePreDigest, AuthorityId)> {
+	for (authority_id, authority_index) in local_authorities(&epoch.authorities, keystore) {
+		let signature = match vrf_signature(authority_id) {
+			Some(signature) => signature,
+			None => continue,
+		};
 
 		// early exit on first successful claim
-		if let Some(pre_digest) = pre_digest {
-			return Some((pre_digest, pair));
+		if let Some(pre_digest) = claim_primary_slot_with_key(
+			slot_number,
+			epoch,
+			c,
+			authority_id,
+			authority_index,
+			signature,
+		) {
+			return Some((pre_digest, authority_id.clone()));
 		}
 	}
 
 	None
 }
+
+/// Claim a primary slot with the key of the authority at the given index of the epoch, given
+/// the VRF signature of the slot by that key.
+fn claim_primary_slot_with_key(
+	slot_number: SlotNumber,
+	epoch: &Epoch,
+	c: (u64, u64),
+	authority_id: &AuthorityId,
+	authority_index: usize,
+	signature: VRFSignature,
+) -> Option<BabePreDigest> {
+	let Epoch { authorities, randomness, epoch_index, .. } = epoch;
+
+	// Compute the threshold we will use.
+	//
+	// The authority is part of `authorities`, so it can't be empty.
+	// Therefore, this division in `calculate_threshold` is safe.
+	let threshold = super::authorship::calculate_primary_threshold(c, authorities, authority_index);
+
+	let public = sr25519::Public::from_slice(authority_id.as_slice());
+
+	// The keystore isn't trusted with the threshold check, the output is checked against the
+	// transcript here.
+	let transcript = make_transcript(randomness, slot_number, *epoch_index);
+	let inout = schnorrkel::PublicKey::from_bytes(public.as_slice())
+		.and_then(|public| signature.output.attach_input_hash(&public, transcript))
+		.ok()?;
+
+	if super::authorship::check_primary_threshold(&inout, threshold) {
+		Some(BabePreDigest::Primary {
+			slot_number,
+			vrf_output: signature.output,
+			vrf_proof: signature.proof,
+			authority_index: authority_index as u32,
+		})
+	} else {
+		None
+	}
+}
+
+/// The slots of an epoch an authority can claim.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct EpochAuthorship {
+	/// The primary slots, claimed with the VRF.
+	pub primary: Vec<SlotNumber>,
+	/// The secondary slots, assigned to the authority in round-robin. Only filled if the chain
+	/// runs with secondary slots, and doesn't include the slots the authority can claim as
+	/// primary.
+	pub secondary: Vec<SlotNumber>,
+}
+
+/// Computes the slots of the given epoch, from `from_slot` onwards, the authorities whose keys
+/// are in the keystore can claim.
+///
+/// The VRF of every slot is signed by the keystore, the authorship resolves once it answered.
+pub async fn epoch_authorship(
+	from_slot: SlotNumber,
+	epoch: &Epoch,
+	config: &BabeConfiguration,
+	keystore: &KeyStorePtr,
+) -> Vec<(AuthorityId, EpochAuthorship)> {
+	let slots = from_slot.max(epoch.start_slot)..epoch.start_slot + epoch.duration;
+	let authorities = local_authorities(&epoch.authorities, keystore);
+
+	// The signatures of all the slots of an authority follow each other.
+	let requests = authorities.iter()
+		.flat_map(|(authority_id, _)| slots.clone().map(move |slot_number| (slot_number, *authority_id)))
+		.map(|(slot_number, authority_id)| sign_vrf(slot_number, epoch, keystore, authority_id))
+		.collect::<Vec<_>>();
+	let mut signatures = future::join_all(requests).await.into_iter();
+
+	authorities.into_iter()
+		.map(|(authority_id, authority_index)| {
+			let mut authorship = EpochAuthorship::default();
+			for slot_number in slots.clone() {
+				let primary = signatures.next()
+					.and_then(|signature| signature
+						.map_err(|e| debug!(
+							target: "babe",
+							"Failed to sign the VRF of slot {}: {}",
+							slot_number,
+							e,
+						))
+						.ok()
+					)
+					.and_then(|signature| claim_primary_slot_with_key(
+						slot_number,
+						epoch,
+						config.c,
+						authority_id,
+						authority_index,
+						signature,
+					));
+				if primary.is_some() {
+					authorship.primary.push(slot_number);
+				} else if config.secondary_slots && secondary_slot_author(
+					slot_number,
+					&epoch.authorities,
+					epoch.randomness,
+				) == Some(authority_id) {
+					authorship.secondary.push(slot_number);
+				}
+			}
+
+			(authority_id.clone(), authorship)
+		})
+		.collect()
+}
@@ -268,18 +268,25 @@ impl<Hash, Number> EpochChanges<Hash, Number> where
 			&is_descendent_of,
 			&predicate,
 		)
-			.map(|n| n.map(|node| ViableEpoch::Regular(match node.data {
+			.map(|n| n.map(|node| {
 				// Ok, we found our node.
 				// and here we figure out which of the internal epochs
 				// of a genesis node to use based on their start slot.
-				PersistedEpoch::Genesis(ref epoch_0, ref epoch_1) =>
-					if epoch_1.start_slot <= slot_number {
-						epoch_1.clone()
-					} else {
-						epoch_0.clone()
-					},
-				PersistedEpoch::Regular(ref epoch_n) => epoch_n.clone(),
-			})))
+				let epoch = match node.data {
+					PersistedEpoch::Genesis(ref epoch_0, ref epoch_1) =>
+						if epoch_1.start_slot <= slot_number {
+							epoch_1
+						} else {
+							epoch_0
+						},
+					PersistedEpoch::Regular(ref epoch_n) => epoch_n,
+				};
+
+				// if no block has been authored for a whole epoch, e.g. after a long
+				// outage, the slot is past the end of the epoch of our node and we
+				// skip to the epoch the slot falls in.
+				ViableEpoch::Regular(epoch.skip_to_slot(slot_number))
+			}))
 	}
 
 	/// Import a new epoch-change, signalled at the given block.
@@ -545,6 +552,72 @@ mod tests {
 		}
 	}
 
+	#[test]
+	fn epochs_are_skipped_after_an_outage() {
+		//
+		// 0 - A
+		//
+		let is_descendent_of = |base: &Hash, block: &Hash| -> Result<bool, TestError> {
+			match (base, *block) {
+				(b"0", _) => Ok(true),
+				_ => Ok(false),
+			}
+		};
+
+		let make_genesis = |slot| Epoch {
+			epoch_index: 0,
+			start_slot: slot,
+			duration: 100,
+			authorities: Vec::new(),
+			randomness: [0; 32],
+		};
+
+		let mut epoch_changes = EpochChanges::new();
+		let genesis_epoch = epoch_changes.epoch_for_child_of(
+			&is_descendent_of,
+			b"0",
+			0,
+			100,
+			&make_genesis,
+		).unwrap().unwrap();
+
+		let import_epoch_1 = genesis_epoch.increment(NextEpochDescriptor {
+			authorities: Vec::new(),
+			randomness: [1; 32],
+		});
+		let epoch_1 = import_epoch_1.as_ref().clone();
+
+		epoch_changes.import(
+			&is_descendent_of,
+			*b"A",
+			1,
+			*b"0",
+			import_epoch_1,
+		).unwrap();
+
+		// no block has been authored during epochs 1 to 3, the child of A is in epoch 4
+		// and uses the data of epoch 1.
+		let x = epoch_changes.epoch_for_child_of(
+			&is_descendent_of,
+			b"A",
+			1,
+			epoch_1.start_slot + 350,
+			&make_genesis,
+		).unwrap().unwrap().into_inner();
+
+		assert_eq!(x.epoch_index, 4);
+		assert_eq!(x.start_slot, epoch_1.start_slot + 300);
+		assert_eq!(x.randomness, epoch_1.randomness);
+
+		// the next epoch follows the skipped one.
+		let next = ViableEpoch::Regular(x.clone()).increment(NextEpochDescriptor {
+			authorities: Vec::new(),
+			randomness: [2; 32],
+		});
+		assert_eq!(next.as_ref().epoch_index, 5);
+		assert_eq!(next.as_ref().start_slot, x.end_slot());
+	}
+
 	#[test]
 	fn two_block_ones_dont_conflict() {
 		//     X - Y
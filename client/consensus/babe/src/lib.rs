@@ -63,7 +63,10 @@ pub use sp_consensus_babe::{
 	CompatibleDigestItem,
 };
 pub use sp_consensus::SyncOracle;
-use std::{collections::HashMap, sync::Arc, u64, pin::Pin, time::{Instant, Duration}};
+pub use sc_consensus_slots::BackoffStrategy;
+use std::{
+	collections::HashMap, convert::TryFrom, sync::Arc, u64, pin::Pin, time::{Instant, Duration},
+};
 use sp_consensus_babe;
 use sp_consensus::{ImportResult, CanAuthorWith};
 use sp_consensus::import_queue::{
@@ -72,11 +75,17 @@ use sp_consensus::import_queue::{
 use sp_runtime::{generic::{BlockId, OpaqueDigestItemId}, Justification};
 use sp_runtime::traits::{
 	Block as BlockT, Header, DigestItemFor, ProvideRuntimeApi,
-	Zero,
+	Zero, One, NumberFor,
 };
 use sc_keystore::KeyStorePtr;
 use parking_lot::Mutex;
-use sp_core::{Blake2Hasher, H256, Pair};
+use prometheus_endpoint::Registry;
+use sp_core::{
+	Blake2Hasher, H256, sr25519,
+	crypto::{CryptoTypePublicPair, Public}, traits::BareCryptoStore,
+};
+use sp_application_crypto::AppKey;
+use codec::Encode;
 use sp_inherents::{InherentDataProviders, InherentData};
 use sc_telemetry::{telemetry, CONSENSUS_TRACE, CONSENSUS_DEBUG};
 use sp_consensus::{
@@ -99,7 +108,9 @@ use sp_block_builder::BlockBuilder as BlockBuilderApi;
 use sc_consensus_slots::{CheckedHeader, check_equivocation};
 use futures::prelude::*;
 use log::{warn, debug, info, trace};
-use sc_consensus_slots::{SlotWorker, SlotInfo, SlotCompatible};
+use sc_consensus_slots::{
+	SlotWorker, SlotInfo, SlotCompatible, ProposalBackoff, SlotLenience, SealFuture,
+};
 use epoch_changes::descendent_query;
 use sp_blockchain::{
 	Result as ClientResult, Error as ClientError,
@@ -119,6 +130,7 @@ pub use sp_consensus_babe::{
 	AuthorityId, AuthorityPair, AuthoritySignature, Epoch, NextEpochDescriptor,
 };
 pub use epoch_changes::{EpochChanges, EpochChangesFor, SharedEpochChanges};
+pub use authorship::{EpochAuthorship, epoch_authorship};
 
 
 #[derive(derive_more::Display, Debug)]
@@ -273,6 +285,9 @@ pub struct BabeParams<B: BlockT, C, E, I, SO, SC, CAW> {
 
 	/// Checks if the current native implementation can author with a runtime at a given block.
 	pub can_author_with: CAW,
+
+	/// What to do after discarding a proposal that took too long.
+	pub backoff_strategy: BackoffStrategy,
 }
 
 /// Start the babe worker. The returned future should be run in a tokio runtime.
@@ -287,6 +302,7 @@ pub fn start_babe<B, C, SC, E, I, SO, CAW, Error>(BabeParams {
 	force_authoring,
 	babe_link,
 	can_author_with,
+	backoff_strategy,
 }: BabeParams<B, C, E, I, SO, SC, CAW>) -> Result<
 	impl futures01::Future<Item=(), Error=()>,
 	sp_consensus::Error,
@@ -314,6 +330,8 @@ pub fn start_babe<B, C, SC, E, I, SO, CAW, Error>(BabeParams {
 		keystore,
 		epoch_changes: babe_link.epoch_changes.clone(),
 		config: config.clone(),
+		proposal_backoff: ProposalBackoff::new(backoff_strategy),
+		vrf_signatures: Mutex::new(Default::default()),
 	};
 
 	register_babe_inherent_data_provider(&inherent_data_providers, config.slot_duration())?;
@@ -346,6 +364,8 @@ struct BabeWorker<B: BlockT, C, E, I, SO> {
 	keystore: KeyStorePtr,
 	epoch_changes: SharedEpochChanges<B>,
 	config: Config,
+	proposal_backoff: ProposalBackoff,
+	vrf_signatures: Mutex<authorship::VrfSignatures>,
 }
 
 impl<B, C, E, I, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for BabeWorker<B, C, E, I, SO> where
@@ -360,7 +380,7 @@ impl<B, C, E, I, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for BabeWork
 	Error: std::error::Error + Send + From<::sp_consensus::Error> + From<I::Error> + 'static,
 {
 	type EpochData = Epoch;
-	type Claim = (BabePreDigest, AuthorityPair);
+	type Claim = (BabePreDigest, AuthorityId);
 	type SyncOracle = SO;
 	type Proposer = E::Proposer;
 	type BlockImport = I;
@@ -397,11 +417,12 @@ impl<B, C, E, I, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for BabeWork
 		epoch_data: &Epoch,
 	) -> Option<Self::Claim> {
 		debug!(target: "babe", "Attempting to claim slot {}", slot_number);
-		let s = authorship::claim_slot(
+		let s = authorship::claim_slot_with_signatures(
 			slot_number,
 			epoch_data,
 			&*self.config,
 			&self.keystore,
+			&mut self.vrf_signatures.lock(),
 		);
 
 		if let Some(_) = s {
@@ -422,28 +443,43 @@ impl<B, C, E, I, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for BabeWork
 		&B::Hash,
 		Vec<B::Extrinsic>,
 		Self::Claim,
-	) -> sp_consensus::BlockImportParams<B> + Send> {
-		Box::new(|header, header_hash, body, (_, pair)| {
-			// sign the pre-sealed hash of the block and then
+	) -> SealFuture<B> + Send> {
+		let keystore = self.keystore.clone();
+		Box::new(move |header, header_hash, body, (_, authority_id)| {
+			// sign the pre-sealed hash of the block with the key in the keystore and then
 			// add it to a digest item.
-			let signature = pair.sign(header_hash.as_ref());
-			let signature_digest_item = <DigestItemFor<B> as CompatibleDigestItem>::babe_seal(signature);
-
-			BlockImportParams {
-				origin: BlockOrigin::Own,
-				header,
-				justification: None,
-				post_digests: vec![signature_digest_item],
-				body: Some(body),
-				finalized: false,
-				auxiliary: Vec::new(), // block-weight is written in block import.
-				// TODO: block-import handles fork choice and this shouldn't even have the
-				// option to specify one.
-				// https://github.com/paritytech/substrate/issues/3623
-				fork_choice: ForkChoiceStrategy::LongestChain,
-				allow_missing_state: false,
-				import_existing: false,
-			}
+			let public = CryptoTypePublicPair(sr25519::CRYPTO_ID, authority_id.to_raw_vec());
+			let signature = keystore.read().sign_with(AuthorityId::ID, &public, header_hash.as_ref());
+
+			Box::pin(async move {
+				let signature = signature.await
+					.map_err(|e| sp_consensus::Error::CannotSign(authority_id.to_raw_vec(), e))?;
+				let signature: AuthoritySignature = sr25519::Signature::try_from(&signature[..])
+					.map(Into::into)
+					.map_err(|_| sp_consensus::Error::CannotSign(
+						authority_id.to_raw_vec(),
+						"Invalid signature returned by the keystore".into(),
+					))?;
+				let signature_digest_item =
+					<DigestItemFor<B> as CompatibleDigestItem>::babe_seal(signature);
+
+				Ok(BlockImportParams {
+					origin: BlockOrigin::Own,
+					header,
+					justification: None,
+					post_digests: vec![signature_digest_item],
+					body: Some(body),
+					finalized: false,
+					auxiliary: Vec::new(), // block-weight is written in block import.
+					// TODO: block-import handles fork choice and this shouldn't even have the
+					// option to specify one.
+					// https://github.com/paritytech/substrate/issues/3623
+					fork_choice: ForkChoiceStrategy::LongestChain,
+					allow_missing_state: false,
+					import_existing: false,
+					state: None,
+				})
+			})
 		})
 	}
 
@@ -461,41 +497,19 @@ impl<B, C, E, I, Error, SO> sc_consensus_slots::SimpleSlotWorker<B> for BabeWork
 		})
 	}
 
-	fn proposing_remaining_duration(
-		&self,
-		head: &B::Header,
-		slot_info: &SlotInfo
-	) -> Option<std::time::Duration> {
-		// never give more than 2^this times the lenience.
-		const BACKOFF_CAP: u64 = 8;
-
-		// how many slots it takes before we double the lenience.
-		const BACKOFF_STEP: u64 = 2;
-
-		let slot_remaining = self.slot_remaining_duration(slot_info);
-		let parent_slot = match find_pre_digest::<B>(head) {
-			Err(_) => return Some(slot_remaining),
-			Ok(d) => d.slot_number(),
-		};
-
-		// we allow a lenience of the number of slots since the head of the
-		// chain was produced, minus 1 (since there is always a difference of at least 1)
-		//
-		// exponential back-off.
-		// in normal cases we only attempt to issue blocks up to the end of the slot.
-		// when the chain has been stalled for a few slots, we give more lenience.
-		let slot_lenience = slot_info.number.saturating_sub(parent_slot + 1);
-
-		let slot_lenience = std::cmp::min(slot_lenience, BACKOFF_CAP);
-		let slot_duration = slot_info.duration << (slot_lenience / BACKOFF_STEP);
+	fn header_slot(&self, header: &B::Header) -> Option<u64> {
+		find_pre_digest::<B>(header).ok().map(|d| d.slot_number())
+	}
 
-		if slot_lenience >= 1 {
-			debug!(target: "babe", "No block for {} slots. Applying 2^({}/{}) lenience",
-				slot_lenience, slot_lenience, BACKOFF_STEP);
-		}
+	fn slot_lenience(&self) -> SlotLenience {
+		// exponential back-off, doubling the lenience every 2 slots without block, and never
+		// giving more than 2^4 slots of lenience.
+		SlotLenience::Exponential { cap: 8, step: 2 }
+	}
 
-		let slot_lenience = Duration::from_secs(slot_duration);
-		Some(slot_lenience + slot_remaining)
+	fn proposal_backoff(&self) -> Option<&ProposalBackoff> {
+		Some(&self.proposal_backoff)
+	}
 	}
 }
 
@@ -586,6 +600,36 @@ pub struct BabeLink<Block: BlockT> {
 	epoch_changes: SharedEpochChanges<Block>,
 	config: Config,
 }
+
+impl<Block: BlockT<Hash=H256>> BabeLink<Block> {
+	/// The BABE configuration of the chain.
+	pub fn config(&self) -> &Config {
+		&self.config
+	}
+
+	/// Returns the epoch a child of the given parent at `slot_number` would be authored in, or
+	/// `None` if the parent isn't known to the epoch changes tree.
+	pub fn epoch_for_child_of<C>(
+		&self,
+		client: &C,
+		parent_hash: &Block::Hash,
+		parent_number: NumberFor<Block>,
+		slot_number: SlotNumber,
+	) -> Result<Option<Epoch>, ConsensusError> where
+		C: HeaderBackend<Block> + HeaderMetadata<Block, Error=ClientError>,
+	{
+		self.epoch_changes.lock().epoch_for_child_of(
+			descendent_query(client),
+			parent_hash,
+			parent_number,
+			slot_number,
+			|slot| self.config.genesis_epoch(slot),
+		)
+			.map(|epoch| epoch.map(|e| e.into_inner()))
+			.map_err(|e| ConsensusError::ChainLookup(format!("{:?}", e)))
+	}
+}
+
 /// A verifier for Babe blocks.
 pub struct BabeVerifier<B, E, Block: BlockT, RA, PRA> {
 	client: Arc<Client<B, E, Block, RA>>,
@@ -596,6 +640,19 @@ pub struct BabeVerifier<B, E, Block: BlockT, RA, PRA> {
 	time_source: TimeSource,
 }
 
+impl<B, E, Block: BlockT, RA, PRA> Clone for BabeVerifier<B, E, Block, RA, PRA> {
+	fn clone(&self) -> Self {
+		BabeVerifier {
+			client: self.client.clone(),
+			api: self.api.clone(),
+			inherent_data_providers: self.inherent_data_providers.clone(),
+			config: self.config.clone(),
+			epoch_changes: self.epoch_changes.clone(),
+			time_source: self.time_source.clone(),
+		}
+	}
+}
+
 impl<B, E, Block: BlockT, RA, PRA> BabeVerifier<B, E, Block, RA, PRA> {
 	fn check_inherents(
 		&self,
@@ -755,6 +812,20 @@ impl<B, E, Block, RA, PRA> Verifier<Block> for BabeVerifier<B, E, Block, RA, PRA
 						equivocation_proof.fst_header().hash(),
 						equivocation_proof.snd_header().hash(),
 					);
+
+					let equivocation_proof = sp_consensus_babe::EquivocationProof {
+						offender: author.clone(),
+						slot_number: babe_pre_digest.slot_number(),
+						first_header: equivocation_proof.fst_header().clone(),
+						second_header: equivocation_proof.snd_header().clone(),
+					};
+					let submitted = self.client.submit_equivocation_report(
+						"BabeApi_submit_report_equivocation_extrinsic",
+						&equivocation_proof.encode(),
+					);
+					if let Err(e) = submitted {
+						warn!(target: "babe", "Error reporting equivocation of {:?}: {:?}", author, e);
+					}
 				}
 
 				// if the body is passed through, we need to use the runtime
@@ -794,6 +865,7 @@ impl<B, E, Block, RA, PRA> Verifier<Block> for BabeVerifier<B, E, Block, RA, PRA
 					fork_choice: ForkChoiceStrategy::LongestChain,
 					allow_missing_state: false,
 					import_existing: false,
+					state: None,
 				};
 
 				Ok((block_import_params, Default::default()))
@@ -897,6 +969,10 @@ impl<B, E, Block, I, RA, PRA> BlockImport<Block> for BabeBlockImport<B, E, Block
 		// early exit if block already in chain, otherwise the check for
 		// epoch changes will error when trying to re-import an epoch change
 		match self.client.status(BlockId::Hash(hash)) {
+			// The state of a block whose header is known, as at the end of a fast sync, doesn't
+			// change the epochs tracked while importing the header.
+			Ok(sp_blockchain::BlockStatus::InChain) if block.state.is_some() =>
+				return self.inner.import_block(block, new_cache).map_err(Into::into),
 			Ok(sp_blockchain::BlockStatus::InChain) => return Ok(ImportResult::AlreadyInChain),
 			Ok(sp_blockchain::BlockStatus::Unknown) => {},
 			Err(e) => return Err(ConsensusError::ClientImport(e.to_string())),
@@ -1169,6 +1245,36 @@ pub fn block_import<B, E, Block: BlockT<Hash=H256>, I, RA, PRA>(
 	Ok((import, link))
 }
 
+/// Ranks competing forks by their number of primary slots, as the BABE fork choice rule does.
+///
+/// Only forks whose parent block was imported can be weighed, by the weight of their parent. The
+/// pre-digest of the announced header isn't verified yet, thus its slot doesn't count.
+pub struct BabeForkRanking<C> {
+	client: Arc<C>,
+}
+
+impl<C> BabeForkRanking<C> {
+	/// Create a new instance, reading the chain weights written by the `BabeBlockImport`.
+	pub fn new(client: Arc<C>) -> Self {
+		BabeForkRanking { client }
+	}
+}
+
+impl<Block: BlockT, C: AuxStore + Send + Sync> sp_consensus::ForkRanking<Block> for BabeForkRanking<C> {
+	fn fork_weight(&self, header: &Block::Header) -> Option<u64> {
+		if *header.number() == One::one() {
+			return Some(0)
+		}
+		match aux_schema::load_block_weight(&*self.client, header.parent_hash()) {
+			Ok(weight) => weight.map(u64::from),
+			Err(e) => {
+				debug!(target: "babe", "Failed to load the weight of block {}: {:?}", header.parent_hash(), e);
+				None
+			},
+		}
+	}
+}
+
 /// Start an import queue for the BABE consensus algorithm.
 ///
 /// This method returns the import queue, some data that needs to be passed to the block authoring
@@ -1186,6 +1292,8 @@ pub fn import_queue<B, E, Block: BlockT<Hash=H256>, I, RA, PRA>(
 	client: Arc<Client<B, E, Block, RA>>,
 	api: Arc<PRA>,
 	inherent_data_providers: InherentDataProviders,
+	registry: Option<&Registry>,
+	verification_workers: usize,
 ) -> ClientResult<BabeImportQueue<Block>> where
 	B: Backend<Block, Blake2Hasher> + 'static,
 	I: BlockImport<Block,Error=ConsensusError> + Send + Sync + 'static,
@@ -1205,11 +1313,13 @@ pub fn import_queue<B, E, Block: BlockT<Hash=H256>, I, RA, PRA>(
 		time_source: babe_link.time_source,
 	};
 
-	Ok(BasicQueue::new(
+	Ok(BasicQueue::new_with_verification_workers(
 		verifier,
 		Box::new(block_import),
 		justification_import,
 		finality_proof_import,
+		registry,
+		verification_workers,
 	))
 }
 
@@ -1219,8 +1329,8 @@ pub mod test_helpers {
 	use super::*;
 
 	/// Try to claim the given slot and return a `BabePreDigest` if
-	/// successful.
-	pub fn claim_slot<B, C>(
+	/// successful. Resolves once the keystore signed the VRF of the slot.
+	pub async fn claim_slot<B, C>(
 		slot_number: u64,
 		parent: &B::Header,
 		client: &C,
@@ -1244,6 +1354,6 @@ pub mod test_helpers {
 			epoch.as_ref(),
 			&link.config,
 			keystore,
-		).map(|(digest, _)| digest)
+		).await.map(|(digest, _)| digest)
 	}
 }
@@ -23,6 +23,7 @@ use super::*;
 use authorship::claim_slot;
 
 use sp_consensus_babe::{AuthorityPair, SlotNumber};
+use sp_core::Pair;
 use sc_block_builder::BlockBuilder;
 use sp_consensus::NoNetwork as DummyOracle;
 use sp_consensus::import_queue::{
@@ -403,6 +404,7 @@ fn run_one_test(
 			babe_link: data.link.clone(),
 			keystore,
 			can_author_with: sp_consensus::AlwaysCanAuthor,
+			backoff_strategy: BackoffStrategy::Disabled,
 		}).expect("Starts babe"));
 	}
 
@@ -505,7 +507,7 @@ fn can_author_block() {
 	};
 
 	// with secondary slots enabled it should never be empty
-	match claim_slot(i, &epoch, &config, &keystore) {
+	match futures::executor::block_on(claim_slot(i, &epoch, &config, &keystore)) {
 		None => i += 1,
 		Some(s) => debug!(target: "babe", "Authored block {:?}", s.0),
 	}
@@ -514,7 +516,7 @@ fn can_author_block() {
 	// of times.
 	config.secondary_slots = false;
 	loop {
-		match claim_slot(i, &epoch, &config, &keystore) {
+		match futures::executor::block_on(claim_slot(i, &epoch, &config, &keystore)) {
 			None => i += 1,
 			Some(s) => {
 				debug!(target: "babe", "Authored block {:?}", s.0);
@@ -579,6 +581,7 @@ fn propose_and_import_block(
 			fork_choice: ForkChoiceStrategy::LongestChain,
 			allow_missing_state: false,
 			import_existing: false,
+			state: None,
 		},
 		Default::default(),
 	).unwrap();
@@ -777,3 +780,50 @@ fn verify_slots_are_strictly_increasing() {
 		&mut block_import,
 	);
 }
+
+#[test]
+fn forks_are_ranked_by_the_weight_of_their_imported_parent() {
+	let mut net = BabeTestNet::new(1);
+
+	let peer = net.peer(0);
+	let data = peer.data.as_ref().expect("babe link set up during initialization");
+	let client = peer.client().as_full().expect("Only full clients are used in tests").clone();
+
+	let mut proposer_factory = DummyFactory {
+		client: client.clone(),
+		config: data.link.config.clone(),
+		epoch_changes: data.link.epoch_changes.clone(),
+		mutator: Arc::new(|_, _| ()),
+	};
+
+	let mut block_import = data.block_import.lock().take().expect("import set up during init");
+
+	let genesis_header = client.header(&BlockId::Number(0)).unwrap().unwrap();
+	let b1 = propose_and_import_block(
+		&genesis_header,
+		Some(999),
+		&mut proposer_factory,
+		&mut block_import,
+	);
+
+	let heavy_parent = H256::repeat_byte(1);
+	aux_schema::write_block_weight(heavy_parent, &3, |values| {
+		let values = values.iter().map(|(k, v)| (&k[..], *v)).collect::<Vec<_>>();
+		client.insert_aux(values.iter(), &[])
+	}).unwrap();
+
+	// The announced headers carry no pre-digest at all: only their imported parent is weighed.
+	let announced = |parent_hash| TestHeader::new(
+		2,
+		Default::default(),
+		Default::default(),
+		parent_hash,
+		Default::default(),
+	);
+	let ranking = BabeForkRanking::new(client.clone());
+	let weight = |parent_hash| sp_consensus::ForkRanking::<TestBlock>::fork_weight(&ranking, &announced(parent_hash));
+
+	assert_eq!(weight(b1), Some(0));
+	assert_eq!(weight(heavy_parent), Some(3));
+	assert_eq!(weight(H256::repeat_byte(2)), None);
+}
@@ -0,0 +1,94 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Errors of the manual seal engine, along with their conversion to RPC errors.
+
+use futures::channel::{mpsc, oneshot};
+use sp_consensus::{Error as ConsensusError, ImportResult};
+use sp_blockchain::Error as BlockchainError;
+use sp_inherents::Error as InherentsError;
+
+/// Error codes of the RPC errors.
+mod codes {
+	pub const SERVER_SHUTTING_DOWN: i64 = 10_000;
+	pub const BLOCK_IMPORT_FAILED: i64 = 11_000;
+	pub const EMPTY_TRANSACTION_POOL: i64 = 12_000;
+	pub const BLOCK_NOT_FOUND: i64 = 13_000;
+	pub const CONSENSUS_ERROR: i64 = 14_000;
+	pub const INHERENTS_ERROR: i64 = 15_000;
+	pub const BLOCKCHAIN_ERROR: i64 = 16_000;
+	pub const UNKNOWN_ERROR: i64 = 20_000;
+}
+
+/// Errors encountered by the manual seal engine.
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum Error {
+	/// The block couldn't be imported.
+	#[display(fmt = "Failed to import block: {:?}", _0)]
+	BlockImportError(ImportResult),
+	/// No block is authored without transactions, unless it is explicitly requested.
+	#[display(fmt = "Transaction pool is empty, set create_empty to true, if you want to create empty blocks")]
+	EmptyTransactionPool,
+	/// Consensus error.
+	ConsensusError(ConsensusError),
+	/// The inherent data couldn't be created.
+	InherentError(InherentsError),
+	/// Blockchain error.
+	BlockchainError(BlockchainError),
+	/// The block to author upon or to finalize isn't known.
+	#[from(ignore)]
+	#[display(fmt = "Block not found: {}", _0)]
+	BlockNotFound(String),
+	/// Any other error.
+	#[from(ignore)]
+	StringError(String),
+	/// The engine dropped the command before replying to it.
+	#[display(fmt = "Consensus process is terminating")]
+	Canceled(oneshot::Canceled),
+	/// The command couldn't be sent to the engine.
+	#[display(fmt = "Consensus process is not running")]
+	SendError(mpsc::SendError),
+	/// Boxed error.
+	Other(Box<dyn std::error::Error + Send>),
+}
+
+impl std::error::Error for Error {}
+
+impl Error {
+	fn to_code(&self) -> i64 {
+		use Error::*;
+		match self {
+			BlockImportError(_) => codes::BLOCK_IMPORT_FAILED,
+			BlockNotFound(_) => codes::BLOCK_NOT_FOUND,
+			EmptyTransactionPool => codes::EMPTY_TRANSACTION_POOL,
+			ConsensusError(_) => codes::CONSENSUS_ERROR,
+			InherentError(_) => codes::INHERENTS_ERROR,
+			BlockchainError(_) => codes::BLOCKCHAIN_ERROR,
+			Canceled(_) | SendError(_) => codes::SERVER_SHUTTING_DOWN,
+			StringError(_) | Other(_) => codes::UNKNOWN_ERROR,
+		}
+	}
+}
+
+impl From<Error> for jsonrpc_core::Error {
+	fn from(error: Error) -> Self {
+		jsonrpc_core::Error {
+			code: jsonrpc_core::ErrorCode::ServerError(error.to_code()),
+			message: format!("{}", error),
+			data: None,
+		}
+	}
+}
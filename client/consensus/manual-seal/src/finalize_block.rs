@@ -0,0 +1,46 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Finalization of a block on request.
+
+use sc_client_api::backend::{Backend as ClientBackend, Finalizer};
+use sp_core::{Blake2Hasher, H256};
+use sp_runtime::{Justification, generic::BlockId, traits::Block as BlockT};
+
+use crate::rpc;
+
+/// Finalize the given block, along with its ancestors, and report the result to the sender.
+pub async fn finalize_block<B, CB, F>(
+	hash: <B as BlockT>::Hash,
+	sender: rpc::Sender<bool>,
+	justification: Option<Justification>,
+	finalizer: &F,
+) where
+	B: BlockT<Hash=H256>,
+	CB: ClientBackend<B, Blake2Hasher>,
+	F: Finalizer<B, Blake2Hasher, CB>,
+{
+	match finalizer.finalize_block(BlockId::Hash(hash), justification, true) {
+		Err(e) => {
+			log::warn!("Failed to finalize block {:?}", e);
+			rpc::send_result(sender, Err(e.into()))
+		}
+		Ok(()) => {
+			log::info!("Successfully finalized block: {}", hash);
+			rpc::send_result(sender, Ok(true))
+		}
+	}
+}
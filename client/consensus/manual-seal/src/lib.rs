@@ -0,0 +1,291 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! A manual sealing engine: blocks are authored on demand rather than on slots, which is
+//! pointless to wait for on development chains and in integration tests.
+//!
+//! The engine authors a block for every `EngineCommand` it receives, and finalizes blocks on
+//! request. The commands are sent either through the `engine_createBlock` and
+//! `engine_finalizeBlock` RPC methods (`run_manual_seal`), or whenever a transaction enters the
+//! pool (`run_instant_seal`).
+
+use std::sync::Arc;
+use futures::prelude::*;
+use prometheus_endpoint::Registry;
+use sc_client_api::backend::{Backend as ClientBackend, Finalizer};
+use sp_blockchain::HeaderBackend;
+use sp_consensus::{
+	Environment, SelectChain, BlockImportParams, BlockOrigin, ForkChoiceStrategy,
+	import_queue::{Verifier, BasicQueue, CacheKeyId, BoxBlockImport},
+};
+use sp_core::{Blake2Hasher, H256};
+use sp_inherents::InherentDataProviders;
+use sp_runtime::{Justification, traits::Block as BlockT};
+use sp_transaction_pool::TransactionPool;
+
+mod error;
+mod finalize_block;
+mod seal_new_block;
+pub mod rpc;
+
+use self::{
+	finalize_block::finalize_block,
+	seal_new_block::{seal_new_block, SealBlockParams},
+};
+pub use self::{error::Error, rpc::{EngineCommand, CreatedBlock}};
+
+/// The verifier of the manual seal engine, which accepts every block as is.
+struct ManualSealVerifier;
+
+impl<B: BlockT> Verifier<B> for ManualSealVerifier {
+	fn verify(
+		&mut self,
+		origin: BlockOrigin,
+		header: B::Header,
+		justification: Option<Justification>,
+		body: Option<Vec<B::Extrinsic>>,
+	) -> Result<(BlockImportParams<B>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String> {
+		let import_params = BlockImportParams {
+			origin,
+			header,
+			justification,
+			post_digests: Vec::new(),
+			body,
+			finalized: false,
+			auxiliary: Vec::new(),
+			fork_choice: ForkChoiceStrategy::LongestChain,
+			allow_missing_state: false,
+			import_existing: false,
+			state: None,
+		};
+
+		Ok((import_params, None))
+	}
+}
+
+/// Instantiate the import queue for the manual seal consensus engine.
+pub fn import_queue<B: BlockT>(
+	block_import: BoxBlockImport<B>,
+	registry: Option<&Registry>,
+) -> BasicQueue<B> {
+	BasicQueue::new(
+		ManualSealVerifier,
+		block_import,
+		None,
+		None,
+		registry,
+	)
+}
+
+/// Run the manual seal engine, authoring and finalizing blocks on the commands of the given
+/// stream, e.g. the receiving end of the channel given to the `ManualSeal` RPC handler.
+pub async fn run_manual_seal<B, CB, E, C, A, SC, S>(
+	mut block_import: BoxBlockImport<B>,
+	mut env: E,
+	client: Arc<C>,
+	pool: Arc<A>,
+	mut commands_stream: S,
+	select_chain: SC,
+	inherent_data_providers: InherentDataProviders,
+) where
+	B: BlockT<Hash=H256> + 'static,
+	CB: ClientBackend<B, Blake2Hasher> + 'static,
+	E: Environment<B> + 'static,
+	E::Error: std::fmt::Debug,
+	C: HeaderBackend<B> + Finalizer<B, Blake2Hasher, CB> + 'static,
+	A: TransactionPool<Block = B> + 'static,
+	S: Stream<Item = EngineCommand<<B as BlockT>::Hash>> + Unpin + 'static,
+	SC: SelectChain<B> + 'static,
+{
+	while let Some(command) = commands_stream.next().await {
+		match command {
+			EngineCommand::SealNewBlock { create_empty, finalize, parent_hash, sender } => {
+				seal_new_block(
+					SealBlockParams {
+						sender,
+						parent_hash,
+						finalize,
+						create_empty,
+						env: &mut env,
+						select_chain: &select_chain,
+						block_import: &mut block_import,
+						inherent_data_provider: &inherent_data_providers,
+						pool: pool.clone(),
+						client: client.clone(),
+					}
+				).await;
+			}
+			EngineCommand::FinalizeBlock { hash, sender, justification } => {
+				finalize_block(hash, sender, justification, &*client).await;
+			}
+		}
+	}
+}
+
+/// Run the instant seal engine, authoring a block, without finalizing it, as soon as a
+/// transaction is imported into the pool.
+pub async fn run_instant_seal<B, CB, E, C, A, SC>(
+	block_import: BoxBlockImport<B>,
+	env: E,
+	client: Arc<C>,
+	pool: Arc<A>,
+	select_chain: SC,
+	inherent_data_providers: InherentDataProviders,
+) where
+	B: BlockT<Hash=H256> + 'static,
+	CB: ClientBackend<B, Blake2Hasher> + 'static,
+	E: Environment<B> + 'static,
+	E::Error: std::fmt::Debug,
+	C: HeaderBackend<B> + Finalizer<B, Blake2Hasher, CB> + 'static,
+	A: TransactionPool<Block = B> + 'static,
+	SC: SelectChain<B> + 'static,
+{
+	// instant-seal creates blocks as soon as transactions are imported
+	// into the transaction pool.
+	let commands_stream = pool.import_notification_stream()
+		.map(|_| EngineCommand::SealNewBlock {
+			create_empty: false,
+			finalize: false,
+			parent_hash: None,
+			sender: None,
+		});
+
+	run_manual_seal(
+		block_import,
+		env,
+		client,
+		pool,
+		commands_stream,
+		select_chain,
+		inherent_data_providers,
+	).await
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::{channel::{mpsc, oneshot}, future::{self, Either}};
+	use sc_basic_authority::ProposerFactory;
+	use sc_client_api::BlockchainEvents;
+	use sc_transaction_pool::{BasicPool, FullChainApi};
+	use sp_runtime::{generic::BlockId, transaction_validity::TransactionSource};
+	use substrate_test_runtime_client::{
+		prelude::*,
+		runtime::{Extrinsic, Transfer},
+	};
+
+	fn extrinsic(nonce: u64) -> Extrinsic {
+		Transfer {
+			amount: Default::default(),
+			nonce,
+			from: AccountKeyring::Alice.into(),
+			to: Default::default(),
+		}.into_signed_tx()
+	}
+
+	/// Drive the engine until the test future completes.
+	fn run_until<T>(engine: impl Future<Output = ()>, test: impl Future<Output = T>) -> T {
+		match futures::executor::block_on(future::select(engine.boxed_local(), test.boxed_local())) {
+			Either::Right((output, _)) => output,
+			Either::Left(_) => panic!("the engine stopped before the end of the test"),
+		}
+	}
+
+	#[test]
+	fn instant_seal() {
+		let (client, select_chain) = TestClientBuilder::new().build_with_longest_chain();
+		let client = Arc::new(client);
+		let pool = Arc::new(BasicPool::new(Default::default(), FullChainApi::new(client.clone())));
+		let env = ProposerFactory::new(client.clone(), pool.clone(), None);
+		let mut imported_blocks = client.import_notification_stream();
+
+		let engine = run_instant_seal(
+			Box::new(client.clone()),
+			env,
+			client.clone(),
+			pool.clone(),
+			select_chain,
+			InherentDataProviders::new(),
+		);
+		let imported = run_until(engine, async {
+			pool.submit_one(&BlockId::Number(0), TransactionSource::External, extrinsic(0)).await.unwrap();
+			imported_blocks.next().await.unwrap()
+		});
+
+		assert_eq!(imported.origin, BlockOrigin::Own);
+		assert!(imported.is_new_best);
+		assert_eq!(client.info().chain.best_hash, imported.hash);
+		assert_eq!(client.info().chain.finalized_number, 0);
+	}
+
+	#[test]
+	fn manual_seal_and_finalization() {
+		let (client, select_chain) = TestClientBuilder::new().build_with_longest_chain();
+		let client = Arc::new(client);
+		let pool = Arc::new(BasicPool::new(Default::default(), FullChainApi::new(client.clone())));
+		let env = ProposerFactory::new(client.clone(), pool.clone(), None);
+		let (mut commands, commands_stream) = mpsc::channel(1024);
+
+		let engine = run_manual_seal(
+			Box::new(client.clone()),
+			env,
+			client.clone(),
+			pool.clone(),
+			commands_stream,
+			select_chain,
+			InherentDataProviders::new(),
+		);
+		let (empty_pool, created, finalized) = run_until(engine, async {
+			// no block is authored out of an empty pool, unless requested.
+			let (sender, receiver) = oneshot::channel();
+			commands.send(EngineCommand::SealNewBlock {
+				create_empty: false,
+				finalize: false,
+				parent_hash: None,
+				sender: Some(sender),
+			}).await.unwrap();
+			let empty_pool = receiver.await.unwrap();
+
+			let (sender, receiver) = oneshot::channel();
+			commands.send(EngineCommand::SealNewBlock {
+				create_empty: true,
+				finalize: false,
+				parent_hash: None,
+				sender: Some(sender),
+			}).await.unwrap();
+			let created = receiver.await.unwrap().unwrap();
+
+			let (sender, receiver) = oneshot::channel();
+			commands.send(EngineCommand::FinalizeBlock {
+				hash: created.hash,
+				sender: Some(sender),
+				justification: None,
+			}).await.unwrap();
+			let finalized = receiver.await.unwrap().unwrap();
+
+			(empty_pool, created, finalized)
+		});
+
+		match empty_pool {
+			Err(Error::EmptyTransactionPool) => {},
+			other => panic!("unexpected result: {:?}", other),
+		}
+		assert!(created.is_new_best);
+		assert!(finalized);
+		assert_eq!(client.info().chain.best_number, 1);
+		assert_eq!(client.info().chain.finalized_hash, created.hash);
+	}
+}
@@ -0,0 +1,194 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC interface of the manual seal engine.
+
+use futures::{
+	FutureExt, SinkExt, TryFutureExt,
+	channel::{mpsc, oneshot},
+	compat::Future01CompatExt as _,
+};
+use jsonrpc_derive::rpc;
+use serde::{Deserialize, Serialize};
+use sc_rpc_api::DenyUnsafe;
+use sp_runtime::Justification;
+
+use crate::Error;
+
+pub use self::gen_client::Client as ManualSealClient;
+
+/// Future's type for jsonrpc.
+type FutureResult<T> = Box<dyn jsonrpc_core::futures::Future<Item = T, Error = jsonrpc_core::Error> + Send>;
+
+/// Sender passed to the engine along with a command, to get the result back.
+pub type Sender<T> = Option<oneshot::Sender<std::result::Result<T, Error>>>;
+
+/// Commands of the manual seal engine.
+pub enum EngineCommand<Hash> {
+	/// Author a new block and import it.
+	SealNewBlock {
+		/// Author the block even if the transaction pool is empty.
+		create_empty: bool,
+		/// Finalize the block once imported.
+		finalize: bool,
+		/// Author the block on top of the given parent, or on top of the best block if `None`.
+		parent_hash: Option<Hash>,
+		/// Sender to report the authored block, or the error, to.
+		sender: Sender<CreatedBlock<Hash>>,
+	},
+	/// Finalize a block.
+	FinalizeBlock {
+		/// Hash of the block to finalize.
+		hash: Hash,
+		/// Sender to report the result to.
+		sender: Sender<bool>,
+		/// Justification of the finality of the block.
+		justification: Option<Justification>,
+	},
+}
+
+/// Substrate manual seal RPC API
+#[rpc]
+pub trait ManualSealApi<Hash> {
+	/// Author a new block, on top of the given parent or of the best block, and import it.
+	///
+	/// This method is unsafe and must only be exposed to the operator of the node.
+	#[rpc(name = "engine_createBlock")]
+	fn create_block(
+		&self,
+		create_empty: bool,
+		finalize: bool,
+		parent_hash: Option<Hash>,
+	) -> FutureResult<CreatedBlock<Hash>>;
+
+	/// Finalize a block, along with its ancestors.
+	///
+	/// This method is unsafe and must only be exposed to the operator of the node.
+	#[rpc(name = "engine_finalizeBlock")]
+	fn finalize_block(
+		&self,
+		hash: Hash,
+		justification: Option<Justification>,
+	) -> FutureResult<bool>;
+}
+
+/// A struct that implements the [`ManualSealApi`].
+pub struct ManualSeal<Hash> {
+	import_block_channel: mpsc::Sender<EngineCommand<Hash>>,
+	deny_unsafe: DenyUnsafe,
+}
+
+/// The block authored by `engine_createBlock`.
+#[derive(Debug, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedBlock<Hash> {
+	/// Hash of the block.
+	pub hash: Hash,
+	/// Whether the block became the new best block.
+	pub is_new_best: bool,
+}
+
+impl<Hash> ManualSeal<Hash> {
+	/// Create new `ManualSeal` sending the commands to the engine through the given channel.
+	pub fn new(
+		import_block_channel: mpsc::Sender<EngineCommand<Hash>>,
+		deny_unsafe: DenyUnsafe,
+	) -> Self {
+		Self { import_block_channel, deny_unsafe }
+	}
+}
+
+impl<Hash: Send + 'static> ManualSealApi<Hash> for ManualSeal<Hash> {
+	fn create_block(
+		&self,
+		create_empty: bool,
+		finalize: bool,
+		parent_hash: Option<Hash>,
+	) -> FutureResult<CreatedBlock<Hash>> {
+		if let Err(e) = self.deny_unsafe.check_if_safe() {
+			return Box::new(jsonrpc_core::futures::future::err(e.into()));
+		}
+		let mut sink = self.import_block_channel.clone();
+		let future = async move {
+			let (sender, receiver) = oneshot::channel();
+			let command = EngineCommand::SealNewBlock {
+				create_empty,
+				finalize,
+				parent_hash,
+				sender: Some(sender),
+			};
+			sink.send(command).await?;
+			receiver.await?
+		}.boxed();
+
+		Box::new(future.map_err(Error::into).compat())
+	}
+
+	fn finalize_block(
+		&self,
+		hash: Hash,
+		justification: Option<Justification>,
+	) -> FutureResult<bool> {
+		if let Err(e) = self.deny_unsafe.check_if_safe() {
+			return Box::new(jsonrpc_core::futures::future::err(e.into()));
+		}
+		let mut sink = self.import_block_channel.clone();
+		let future = async move {
+			let (sender, receiver) = oneshot::channel();
+			sink.send(
+				EngineCommand::FinalizeBlock { hash, sender: Some(sender), justification }
+			).await?;
+
+			receiver.await?
+		}.boxed();
+
+		Box::new(future.map_err(Error::into).compat())
+	}
+}
+
+/// Send the result of a command to its sender, if any. The commands issued without a sender,
+/// e.g. by the instant seal engine, get their result logged instead.
+pub fn send_result<T: std::fmt::Debug>(
+	sender: Sender<T>,
+	result: std::result::Result<T, crate::Error>,
+) {
+	if let Some(sender) = sender {
+		if let Err(err) = sender.send(result) {
+			log::warn!("Server is shutting down: {:?}", err)
+		}
+	} else {
+		// instant seal doesn't report errors over rpc, simply log them.
+		match result {
+			Ok(r) => log::info!("Instant Seal success: {:?}", r),
+			Err(e) => log::error!("Instant Seal encountered an error: {}", e),
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+
+	#[test]
+	fn created_block_serialization() {
+		let created = CreatedBlock { hash: H256::repeat_byte(1), is_new_best: true };
+
+		let json = serde_json::to_value(&created).unwrap();
+		assert_eq!(json["hash"], serde_json::to_value(H256::repeat_byte(1)).unwrap());
+		assert_eq!(json["isNewBest"], true);
+	}
+}
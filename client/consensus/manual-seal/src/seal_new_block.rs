@@ -0,0 +1,131 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Authoring and import of a new block.
+
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use sp_blockchain::HeaderBackend;
+use sp_consensus::{
+	BlockImport, Environment, Proposer, BlockOrigin, SelectChain, BlockImportParams,
+	ForkChoiceStrategy, ImportResult, ImportedAux,
+	import_queue::BoxBlockImport,
+};
+use sp_inherents::InherentDataProviders;
+use sp_runtime::{generic::BlockId, traits::{Block as BlockT, Header as HeaderT}};
+use sp_transaction_pool::TransactionPool;
+
+use crate::{Error, rpc::{self, CreatedBlock}};
+
+/// Max duration for building a block, to keep the engine responsive.
+const MAX_PROPOSAL_DURATION: u64 = 10;
+
+/// Parameters of `seal_new_block`.
+pub struct SealBlockParams<'a, B: BlockT, SC, HB, E, P: TransactionPool> {
+	/// Author the block even if the transaction pool is empty.
+	pub create_empty: bool,
+	/// Finalize the block once imported.
+	pub finalize: bool,
+	/// Author the block on top of the given parent, or on top of the best block if `None`.
+	pub parent_hash: Option<<B as BlockT>::Hash>,
+	/// Sender to report the authored block, or the error, to.
+	pub sender: rpc::Sender<CreatedBlock<<B as BlockT>::Hash>>,
+	/// Transaction pool the block is authored from.
+	pub pool: Arc<P>,
+	/// Header backend, to fetch the parent.
+	pub client: Arc<HB>,
+	/// Select chain, to find the best block.
+	pub select_chain: &'a SC,
+	/// Providers of the inherent data of the block.
+	pub inherent_data_provider: &'a InherentDataProviders,
+	/// Proposer factory.
+	pub env: &'a mut E,
+	/// Block import the block is imported with.
+	pub block_import: &'a mut BoxBlockImport<B>,
+}
+
+/// Author a new block on top of the given parent, or of the best block, and import it.
+pub async fn seal_new_block<B, SC, HB, E, P>(
+	SealBlockParams {
+		create_empty,
+		finalize,
+		pool,
+		parent_hash,
+		client,
+		select_chain,
+		inherent_data_provider,
+		block_import,
+		env,
+		sender,
+	}: SealBlockParams<'_, B, SC, HB, E, P>
+) where
+	B: BlockT,
+	HB: HeaderBackend<B>,
+	E: Environment<B>,
+	<E as Environment<B>>::Error: std::fmt::Debug,
+	P: TransactionPool<Block = B>,
+	SC: SelectChain<B>,
+{
+	let future = async {
+		if pool.status().ready == 0 && !create_empty {
+			return Err(Error::EmptyTransactionPool);
+		}
+
+		// get the header to build this new block on.
+		// use the parent_hash supplied via `EngineCommand`
+		// or fetch the best_block.
+		let header = match parent_hash {
+			Some(hash) => {
+				match client.header(BlockId::Hash(hash))? {
+					Some(header) => header,
+					None => return Err(Error::BlockNotFound(format!("{:?}", hash))),
+				}
+			}
+			None => select_chain.best_chain()?,
+		};
+
+		let mut proposer = env.init(&header)
+			.map_err(|err| Error::StringError(format!("{:?}", err)))?;
+		let inherent_data = inherent_data_provider.create_inherent_data()?;
+		let max_duration = Duration::from_secs(MAX_PROPOSAL_DURATION);
+		let proposal = proposer.propose(inherent_data, Default::default(), max_duration)
+			.await
+			.map_err(|err| Error::StringError(format!("{:?}", err)))?;
+
+		let (header, body) = proposal.deconstruct();
+		let params = BlockImportParams {
+			origin: BlockOrigin::Own,
+			header: header.clone(),
+			justification: None,
+			post_digests: Vec::new(),
+			body: Some(body),
+			finalized: finalize,
+			auxiliary: Vec::new(),
+			fork_choice: ForkChoiceStrategy::LongestChain,
+			allow_missing_state: false,
+			import_existing: false,
+			state: None,
+		};
+
+		match block_import.import_block(params, HashMap::new())? {
+			ImportResult::Imported(ImportedAux { is_new_best, .. }) => {
+				Ok(CreatedBlock { hash: header.hash(), is_new_best })
+			},
+			other => Err(other.into()),
+		}
+	};
+
+	rpc::send_result(sender, future.await)
+}
@@ -0,0 +1,136 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC interface for the PoW consensus algorithm, to be used by external miners.
+
+#![warn(missing_docs)]
+
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use serde::Serialize;
+use sc_consensus_pow::{MiningHandle, PowAlgorithm};
+use sc_rpc_api::DenyUnsafe;
+use sp_core::{Bytes, H256};
+use sp_runtime::traits::Block as BlockT;
+
+pub use self::gen_client::Client as PowClient;
+
+/// Substrate PoW RPC API
+#[rpc]
+pub trait PowApi<Hash, Difficulty> {
+	/// Returns the metadata of the block being mined, or `None` if no block is being mined, e.g.
+	/// while the node is syncing.
+	#[rpc(name = "pow_miningMetadata")]
+	fn mining_metadata(&self) -> Result<Option<MiningMetadata<Hash, Difficulty>>>;
+
+	/// Submit a seal for the block being mined. The block is imported if the seal meets its
+	/// difficulty.
+	///
+	/// This method is unsafe and must only be exposed to the operator of the node and its miners.
+	#[rpc(name = "pow_submitSeal")]
+	fn submit_seal(&self, seal: Bytes) -> Result<()>;
+}
+
+/// Metadata of the block being mined.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MiningMetadata<Hash, Difficulty> {
+	/// Hash of the best block, the parent of the block being mined.
+	pub best_hash: Hash,
+	/// Hash of the block being mined, without its seal.
+	pub pre_hash: Hash,
+	/// Difficulty the seal of the block must meet.
+	pub difficulty: Difficulty,
+}
+
+impl<Hash, Difficulty> From<sc_consensus_pow::MiningMetadata<Hash, Difficulty>>
+	for MiningMetadata<Hash, Difficulty>
+{
+	fn from(metadata: sc_consensus_pow::MiningMetadata<Hash, Difficulty>) -> Self {
+		MiningMetadata {
+			best_hash: metadata.best_hash,
+			pre_hash: metadata.pre_hash,
+			difficulty: metadata.difficulty,
+		}
+	}
+}
+
+/// Error type of this RPC api.
+pub enum Error {
+	/// The seal couldn't be submitted.
+	SubmitSealFailed,
+}
+
+impl From<Error> for i64 {
+	fn from(e: Error) -> i64 {
+		match e {
+			Error::SubmitSealFailed => 1,
+		}
+	}
+}
+
+/// A struct that implements the [`PowApi`].
+pub struct PowRpcHandler<Block: BlockT<Hash=H256>, Algorithm: PowAlgorithm<Block>> {
+	mining_handle: MiningHandle<Block, Algorithm>,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl<Block: BlockT<Hash=H256>, Algorithm: PowAlgorithm<Block>> PowRpcHandler<Block, Algorithm> {
+	/// Create new `PowRpcHandler` handing out the block mined through the given handle.
+	pub fn new(mining_handle: MiningHandle<Block, Algorithm>, deny_unsafe: DenyUnsafe) -> Self {
+		PowRpcHandler { mining_handle, deny_unsafe }
+	}
+}
+
+impl<Block, Algorithm> PowApi<Block::Hash, Algorithm::Difficulty> for PowRpcHandler<Block, Algorithm>
+	where
+		Block: BlockT<Hash=H256>,
+		Algorithm: PowAlgorithm<Block> + Send + Sync + 'static,
+		Algorithm::Difficulty: Serialize + Send + Sync + 'static,
+{
+	fn mining_metadata(&self) -> Result<Option<MiningMetadata<Block::Hash, Algorithm::Difficulty>>> {
+		Ok(self.mining_handle.metadata().map(Into::into))
+	}
+
+	fn submit_seal(&self, seal: Bytes) -> Result<()> {
+		self.deny_unsafe.check_if_safe()?;
+		self.mining_handle.submit(seal.to_vec())
+			.map_err(|e| RpcError {
+				code: ErrorCode::ServerError(Error::SubmitSealFailed.into()),
+				message: "Unable to submit the seal.".into(),
+				data: Some(format!("{}", e).into()),
+			})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn mining_metadata_serialization() {
+		let metadata = MiningMetadata {
+			best_hash: H256::repeat_byte(1),
+			pre_hash: H256::repeat_byte(2),
+			difficulty: 10u128,
+		};
+
+		let json = serde_json::to_value(&metadata).unwrap();
+		assert_eq!(json["bestHash"], serde_json::to_value(H256::repeat_byte(1)).unwrap());
+		assert_eq!(json["preHash"], serde_json::to_value(H256::repeat_byte(2)).unwrap());
+		assert_eq!(json["difficulty"], 10);
+	}
+}
@@ -18,8 +18,16 @@
 //!
 //! To use this engine, you can need to have a struct that implements
 //! `PowAlgorithm`. After that, pass an instance of the struct, along
-//! with other necessary client references to `import_queue` to setup
-//! the queue. Use the `start_mine` function for basic CPU mining.
+//! with other necessary client references to `PowBlockImport`, which
+//! verifies the proof of work of the blocks and tracks their total
+//! difficulty, and pass the block import along with the algorithm to
+//! `import_queue` to setup the queue, whose verifier checks the proof
+//! of work of the blocks as well.
+//!
+//! Use the `start_mining_worker` function to keep a block to mine up to
+//! date with the best chain, and hand it out to external miners through the
+//! returned `MiningHandle`. The `start_mine` function does the same, and
+//! mines the block on the CPU.
 //!
 //! The auxiliary storage for PoW engine only stores the total difficulty.
 //! For other storage requirements for particular PoW algorithm (such as
@@ -32,6 +40,9 @@
 use std::sync::Arc;
 use std::thread;
 use std::collections::HashMap;
+use std::marker::PhantomData;
+use parking_lot::Mutex;
+use prometheus_endpoint::Registry;
 use sc_client_api::{BlockOf, backend::AuxStore};
 use sp_blockchain::{HeaderBackend, ProvideCache, well_known_cache_keys::Id as CacheKeyId};
 use sp_block_builder::BlockBuilder as BlockBuilderApi;
@@ -44,7 +55,7 @@ use sp_core::H256;
 use sp_inherents::{InherentDataProviders, InherentData};
 use sp_consensus::{
 	BlockImportParams, BlockOrigin, ForkChoiceStrategy, SyncOracle, Environment, Proposer,
-	SelectChain, Error as ConsensusError, CanAuthorWith,
+	SelectChain, Error as ConsensusError, CanAuthorWith, BlockImport, BlockCheckParams, ImportResult,
 };
 use sp_consensus::import_queue::{BoxBlockImport, BasicQueue, Verifier};
 use codec::{Encode, Decode};
@@ -77,6 +88,8 @@ pub enum Error<B: BlockT> {
 	CreateInherents(sp_inherents::Error),
 	#[display(fmt = "Checking inherents failed: {}", _0)]
 	CheckInherents(String),
+	#[display(fmt = "No block is being mined")]
+	NoBuild,
 	Client(sp_blockchain::Error),
 	Codec(codec::Error),
 	Environment(String),
@@ -89,6 +102,12 @@ impl<B: BlockT> std::convert::From<Error<B>> for String {
 	}
 }
 
+impl<B: BlockT> std::convert::From<Error<B>> for ConsensusError {
+	fn from(error: Error<B>) -> ConsensusError {
+		ConsensusError::ClientImport(error.to_string())
+	}
+}
+
 /// Auxiliary storage prefix for PoW engine.
 pub const POW_AUX_PREFIX: [u8; 4] = *b"PoW:";
 
@@ -148,30 +167,30 @@ pub trait PowAlgorithm<B: BlockT> {
 }
 
 /// A verifier for PoW blocks.
-pub struct PowVerifier<B: BlockT<Hash=H256>, C, S, Algorithm> {
+///
+/// It checks that the blocks are sealed by the PoW engine, that their seal meets the difficulty
+/// given by the `PowAlgorithm`, and checks their inherents.
+pub struct PowVerifier<B: BlockT<Hash=H256>, C, Algorithm> {
 	client: Arc<C>,
 	algorithm: Algorithm,
 	inherent_data_providers: sp_inherents::InherentDataProviders,
-	select_chain: Option<S>,
 	check_inherents_after: <<B as BlockT>::Header as HeaderT>::Number,
 }
 
-impl<B: BlockT<Hash=H256>, C, S, Algorithm> PowVerifier<B, C, S, Algorithm> {
+impl<B: BlockT<Hash=H256>, C, Algorithm> PowVerifier<B, C, Algorithm> {
 	pub fn new(
 		client: Arc<C>,
 		algorithm: Algorithm,
 		check_inherents_after: <<B as BlockT>::Header as HeaderT>::Number,
-		select_chain: Option<S>,
 		inherent_data_providers: sp_inherents::InherentDataProviders,
 	) -> Self {
-		Self { client, algorithm, inherent_data_providers, select_chain, check_inherents_after }
+		Self { client, algorithm, inherent_data_providers, check_inherents_after }
 	}
 
 	fn check_header(
 		&self,
 		mut header: B::Header,
-		parent_block_id: BlockId<B>,
-	) -> Result<(B::Header, Algorithm::Difficulty, DigestItem<H256>), Error<B>> where
+	) -> Result<(B::Header, DigestItem<H256>), Error<B>> where
 		Algorithm: PowAlgorithm<B>,
 	{
 		let hash = header.hash();
@@ -188,18 +207,14 @@ impl<B: BlockT<Hash=H256>, C, S, Algorithm> PowVerifier<B, C, S, Algorithm> {
 		};
 
 		let pre_hash = header.hash();
-		let difficulty = self.algorithm.difficulty(&parent_block_id)?;
+		let parent_id = BlockId::Hash(*header.parent_hash());
+		let difficulty = self.algorithm.difficulty(&parent_id)?;
 
-		if !self.algorithm.verify(
-			&parent_block_id,
-			&pre_hash,
-			&inner_seal,
-			difficulty,
-		)? {
+		if !self.algorithm.verify(&parent_id, &pre_hash, &inner_seal, difficulty)? {
 			return Err(Error::InvalidSeal);
 		}
 
-		Ok((header, difficulty, seal))
+		Ok((header, seal))
 	}
 
 	fn check_inherents(
@@ -245,10 +260,9 @@ impl<B: BlockT<Hash=H256>, C, S, Algorithm> PowVerifier<B, C, S, Algorithm> {
 	}
 }
 
-impl<B: BlockT<Hash=H256>, C, S, Algorithm> Verifier<B> for PowVerifier<B, C, S, Algorithm> where
+impl<B: BlockT<Hash=H256>, C, Algorithm> Verifier<B> for PowVerifier<B, C, Algorithm> where
 	C: ProvideRuntimeApi + Send + Sync + HeaderBackend<B> + AuxStore + ProvideCache<B> + BlockOf,
 	C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>,
-	S: SelectChain<B>,
 	Algorithm: PowAlgorithm<B> + Send + Sync,
 {
 	fn verify(
@@ -262,23 +276,8 @@ impl<B: BlockT<Hash=H256>, C, S, Algorithm> Verifier<B> for PowVerifier<B, C, S,
 			.create_inherent_data().map_err(|e| e.into_string())?;
 		let timestamp_now = inherent_data.timestamp_inherent_data().map_err(|e| e.into_string())?;
 
-		let best_hash = match self.select_chain.as_ref() {
-			Some(select_chain) => select_chain.best_chain()
-				.map_err(|e| format!("Fetch best chain failed via select chain: {:?}", e))?
-				.hash(),
-			None => self.client.info().best_hash,
-		};
-		let hash = header.hash();
 		let parent_hash = *header.parent_hash();
-		let best_aux = PowAux::read::<_, B>(self.client.as_ref(), &best_hash)?;
-		let mut aux = PowAux::read::<_, B>(self.client.as_ref(), &parent_hash)?;
-
-		let (checked_header, difficulty, seal) = self.check_header(
-			header,
-			BlockId::Hash(parent_hash),
-		)?;
-		aux.difficulty = difficulty;
-		aux.total_difficulty.increment(difficulty);
+		let (checked_header, seal) = self.check_header(header)?;
 
 		if let Some(inner_body) = body.take() {
 			let block = B::new(checked_header.clone(), inner_body);
@@ -293,7 +292,7 @@ impl<B: BlockT<Hash=H256>, C, S, Algorithm> Verifier<B> for PowVerifier<B, C, S,
 			let (_, inner_body) = block.deconstruct();
 			body = Some(inner_body);
 		}
-		let key = aux_key(&hash);
+
 		let import_block = BlockImportParams {
 			origin,
 			header: checked_header,
@@ -301,16 +300,130 @@ impl<B: BlockT<Hash=H256>, C, S, Algorithm> Verifier<B> for PowVerifier<B, C, S,
 			body,
 			finalized: false,
 			justification,
-			auxiliary: vec![(key, Some(aux.encode()))],
-			fork_choice: ForkChoiceStrategy::Custom(aux.total_difficulty > best_aux.total_difficulty),
+			auxiliary: Vec::new(),
+			// chosen by the `PowBlockImport`, along with the total difficulty of the block.
+			fork_choice: ForkChoiceStrategy::LongestChain,
 			allow_missing_state: false,
 			import_existing: false,
+			state: None,
 		};
 
 		Ok((import_block, None))
 	}
 }
 
+/// A block import verifying the seal of the blocks against the difficulty given by the
+/// `PowAlgorithm`, including the ones not imported through the import queue, such as the mined
+/// ones.
+///
+/// It writes the total difficulty of the imported blocks to the auxiliary storage, and makes
+/// the block with the most total difficulty the best one.
+pub struct PowBlockImport<B: BlockT<Hash=H256>, I, C, S, Algorithm> {
+	inner: I,
+	client: Arc<C>,
+	algorithm: Algorithm,
+	select_chain: Option<S>,
+	_phantom: PhantomData<B>,
+}
+
+impl<B, I, C, S, Algorithm> Clone for PowBlockImport<B, I, C, S, Algorithm> where
+	B: BlockT<Hash=H256>,
+	I: Clone,
+	S: Clone,
+	Algorithm: Clone,
+{
+	fn clone(&self) -> Self {
+		PowBlockImport {
+			inner: self.inner.clone(),
+			client: self.client.clone(),
+			algorithm: self.algorithm.clone(),
+			select_chain: self.select_chain.clone(),
+			_phantom: PhantomData,
+		}
+	}
+}
+
+impl<B: BlockT<Hash=H256>, I, C, S, Algorithm> PowBlockImport<B, I, C, S, Algorithm> {
+	/// Create a new block import wrapping the given one. The best block is fetched from the
+	/// select chain if any, or from the client otherwise.
+	pub fn new(
+		inner: I,
+		client: Arc<C>,
+		algorithm: Algorithm,
+		select_chain: Option<S>,
+	) -> Self {
+		PowBlockImport { inner, client, algorithm, select_chain, _phantom: PhantomData }
+	}
+}
+
+impl<B, I, C, S, Algorithm> BlockImport<B> for PowBlockImport<B, I, C, S, Algorithm> where
+	B: BlockT<Hash=H256>,
+	I: BlockImport<B> + Send + Sync,
+	I::Error: Into<ConsensusError>,
+	C: HeaderBackend<B> + AuxStore + Send + Sync,
+	S: SelectChain<B>,
+	Algorithm: PowAlgorithm<B>,
+{
+	type Error = ConsensusError;
+
+	fn check_block(
+		&mut self,
+		block: BlockCheckParams<B>,
+	) -> Result<ImportResult, Self::Error> {
+		self.inner.check_block(block).map_err(Into::into)
+	}
+
+	fn import_block(
+		&mut self,
+		mut block: BlockImportParams<B>,
+		new_cache: HashMap<CacheKeyId, Vec<u8>>,
+	) -> Result<ImportResult, Self::Error> {
+		let best_hash = match self.select_chain.as_ref() {
+			Some(select_chain) => select_chain.best_chain()
+				.map_err(Error::<B>::BestHeaderSelectChain)?
+				.hash(),
+			None => self.client.info().best_hash,
+		};
+		let hash = block.post_header().hash();
+		let parent_hash = *block.header.parent_hash();
+		let best_aux = PowAux::read::<_, B>(self.client.as_ref(), &best_hash)?;
+		let mut aux = PowAux::read::<_, B>(self.client.as_ref(), &parent_hash)?;
+
+		let inner_seal = match block.post_digests.last() {
+			Some(DigestItem::Seal(id, seal)) => {
+				if *id == POW_ENGINE_ID {
+					seal.clone()
+				} else {
+					return Err(Error::<B>::WrongEngine(*id).into())
+				}
+			},
+			_ => return Err(Error::<B>::HeaderUnsealed(hash).into()),
+		};
+
+		let pre_hash = block.header.hash();
+		let difficulty = self.algorithm.difficulty(&BlockId::Hash(parent_hash))?;
+
+		if !self.algorithm.verify(
+			&BlockId::Hash(parent_hash),
+			&pre_hash,
+			&inner_seal,
+			difficulty,
+		)? {
+			return Err(Error::<B>::InvalidSeal.into());
+		}
+
+		aux.difficulty = difficulty;
+		aux.total_difficulty.increment(difficulty);
+
+		block.auxiliary.push((aux_key(&hash), Some(aux.encode())));
+		block.fork_choice = ForkChoiceStrategy::Custom(
+			aux.total_difficulty > best_aux.total_difficulty
+		);
+
+		self.inner.import_block(block, new_cache).map_err(Into::into)
+	}
+}
+
 /// Register the PoW inherent data provider, if not registered already.
 pub fn register_pow_inherent_data_provider(
 	inherent_data_providers: &InherentDataProviders,
@@ -328,21 +441,22 @@ pub fn register_pow_inherent_data_provider(
 /// The PoW import queue type.
 pub type PowImportQueue<B> = BasicQueue<B>;
 
-/// Import queue for PoW engine.
-pub fn import_queue<B, C, S, Algorithm>(
+/// Import queue for PoW engine, verifying the seals of the blocks with the given `algorithm`.
+/// The given block import is expected to be a `PowBlockImport`, which writes the total
+/// difficulty of the blocks.
+pub fn import_queue<B, C, Algorithm>(
 	block_import: BoxBlockImport<B>,
 	client: Arc<C>,
 	algorithm: Algorithm,
 	check_inherents_after: <<B as BlockT>::Header as HeaderT>::Number,
-	select_chain: Option<S>,
 	inherent_data_providers: InherentDataProviders,
+	registry: Option<&Registry>,
 ) -> Result<PowImportQueue<B>, sp_consensus::Error> where
 	B: BlockT<Hash=H256>,
 	C: ProvideRuntimeApi + HeaderBackend<B> + BlockOf + ProvideCache<B> + AuxStore,
 	C: Send + Sync + AuxStore + 'static,
 	C::Api: BlockBuilderApi<B, Error = sp_blockchain::Error>,
 	Algorithm: PowAlgorithm<B> + Send + Sync + 'static,
-	S: SelectChain<B> + 'static,
 {
 	register_pow_inherent_data_provider(&inherent_data_providers)?;
 
@@ -350,7 +464,6 @@ pub fn import_queue<B, C, S, Algorithm>(
 		client.clone(),
 		algorithm,
 		check_inherents_after,
-		select_chain,
 		inherent_data_providers,
 	);
 
@@ -358,35 +471,122 @@ pub fn import_queue<B, C, S, Algorithm>(
 		verifier,
 		block_import,
 		None,
-		None
+		None,
+		registry,
 	))
 }
 
-/// Start the background mining thread for PoW. Note that because PoW mining
-/// is CPU-intensive, it is not possible to use an async future to define this.
-/// However, it's not recommended to use background threads in the rest of the
-/// codebase.
+/// Metadata of the block being mined, to be handed out to a miner.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct MiningMetadata<H, D> {
+	/// Hash of the best block, the parent of the block being mined.
+	pub best_hash: H,
+	/// Hash of the block being mined, without its seal.
+	pub pre_hash: H,
+	/// Difficulty the seal of the block must meet.
+	pub difficulty: D,
+}
+
+/// A block being mined.
+struct MiningBuild<B: BlockT, D> {
+	metadata: MiningMetadata<B::Hash, D>,
+	header: B::Header,
+	body: Vec<B::Extrinsic>,
+}
+
+/// Handle to the block being mined by the mining worker.
+///
+/// Miners fetch the metadata of the block through it, and submit a seal for the block once
+/// found, e.g. through RPC for external miners.
+pub struct MiningHandle<B: BlockT<Hash=H256>, Algorithm: PowAlgorithm<B>> {
+	algorithm: Arc<Algorithm>,
+	block_import: Arc<Mutex<BoxBlockImport<B>>>,
+	build: Arc<Mutex<Option<MiningBuild<B, Algorithm::Difficulty>>>>,
+}
+
+impl<B: BlockT<Hash=H256>, Algorithm: PowAlgorithm<B>> Clone for MiningHandle<B, Algorithm> {
+	fn clone(&self) -> Self {
+		MiningHandle {
+			algorithm: self.algorithm.clone(),
+			block_import: self.block_import.clone(),
+			build: self.build.clone(),
+		}
+	}
+}
+
+impl<B: BlockT<Hash=H256>, Algorithm: PowAlgorithm<B>> MiningHandle<B, Algorithm> {
+	/// Returns the metadata of the block being mined, or `None` if no block is being mined,
+	/// e.g. while the node is syncing.
+	pub fn metadata(&self) -> Option<MiningMetadata<B::Hash, Algorithm::Difficulty>> {
+		self.build.lock().as_ref().map(|build| build.metadata.clone())
+	}
+
+	/// Submit a seal for the block being mined, and import the sealed block if the seal meets
+	/// its difficulty. The worker then builds a new block on top of it.
+	pub fn submit(&self, seal: Seal) -> Result<(), Error<B>> {
+		let mut build = self.build.lock();
+		let metadata = match build.as_ref() {
+			Some(build) => build.metadata.clone(),
+			None => return Err(Error::NoBuild),
+		};
+
+		if !self.algorithm.verify(
+			&BlockId::Hash(metadata.best_hash),
+			&metadata.pre_hash,
+			&seal,
+			metadata.difficulty,
+		)? {
+			return Err(Error::InvalidSeal);
+		}
+
+		let MiningBuild { header, body, .. } = build.take()
+			.expect("the build has been checked to be present above; qed");
+		let import_block = BlockImportParams {
+			origin: BlockOrigin::Own,
+			header,
+			justification: None,
+			post_digests: vec![DigestItem::Seal(POW_ENGINE_ID, seal)],
+			body: Some(body),
+			finalized: false,
+			auxiliary: Vec::new(),
+			// chosen by the `PowBlockImport`, along with the total difficulty of the block.
+			fork_choice: ForkChoiceStrategy::LongestChain,
+			allow_missing_state: false,
+			import_existing: false,
+			state: None,
+		};
+
+		self.block_import.lock().import_block(import_block, HashMap::default())
+			.map(|_| ())
+			.map_err(|e| Error::BlockBuiltError(metadata.best_hash, e))
+	}
+}
+
+/// Start the background thread keeping the block to mine up to date with the best chain: a new
+/// block is proposed whenever the best block changes. Miners access the block through the
+/// returned handle.
+///
+/// The given block import is expected to be a `PowBlockImport`, which writes the total
+/// difficulty of the mined blocks.
 ///
 /// `preruntime` is a parameter that allows a custom additional pre-runtime
 /// digest to be inserted for blocks being built. This can encode authorship
-/// information, or just be a graffiti. `round` is for number of rounds the
-/// CPU miner runs each time. This parameter should be tweaked so that each
-/// mining round is within sub-second time.
-pub fn start_mine<B: BlockT<Hash=H256>, C, Algorithm, E, SO, S, CAW>(
-	mut block_import: BoxBlockImport<B>,
+/// information, or just be a graffiti.
+pub fn start_mining_worker<B: BlockT<Hash=H256>, C, Algorithm, E, SO, S, CAW>(
+	block_import: BoxBlockImport<B>,
 	client: Arc<C>,
 	algorithm: Algorithm,
 	mut env: E,
 	preruntime: Option<Vec<u8>>,
-	round: u32,
 	mut sync_oracle: SO,
 	build_time: std::time::Duration,
 	select_chain: Option<S>,
 	inherent_data_providers: sp_inherents::InherentDataProviders,
 	can_author_with: CAW,
-) where
+) -> MiningHandle<B, Algorithm> where
 	C: HeaderBackend<B> + AuxStore + 'static,
 	Algorithm: PowAlgorithm<B> + Send + Sync + 'static,
+	Algorithm::Difficulty: Send,
 	E: Environment<B> + Send + Sync + 'static,
 	E::Error: std::fmt::Debug,
 	SO: SyncOracle + Send + Sync + 'static,
@@ -397,15 +597,20 @@ pub fn start_mine<B: BlockT<Hash=H256>, C, Algorithm, E, SO, S, CAW>(
 		warn!("Registering inherent data provider for timestamp failed");
 	}
 
+	let handle = MiningHandle {
+		algorithm: Arc::new(algorithm),
+		block_import: Arc::new(Mutex::new(block_import)),
+		build: Arc::new(Mutex::new(None)),
+	};
+
+	let worker_handle = handle.clone();
 	thread::spawn(move || {
 		loop {
-			match mine_loop(
-				&mut block_import,
+			match update_build(
+				&worker_handle,
 				client.as_ref(),
-				&algorithm,
 				&mut env,
 				preruntime.as_ref(),
-				round,
 				&mut sync_oracle,
 				build_time.clone(),
 				select_chain.as_ref(),
@@ -414,22 +619,23 @@ pub fn start_mine<B: BlockT<Hash=H256>, C, Algorithm, E, SO, S, CAW>(
 			) {
 				Ok(()) => (),
 				Err(e) => error!(
-					"Mining block failed with {:?}. Sleep for 1 second before restarting...",
+					"Building block to mine failed with {:?}. Sleep for 1 second before retrying...",
 					e
 				),
 			}
 			std::thread::sleep(std::time::Duration::new(1, 0));
 		}
 	});
+
+	handle
 }
 
-fn mine_loop<B: BlockT<Hash=H256>, C, Algorithm, E, SO, S, CAW>(
-	block_import: &mut BoxBlockImport<B>,
+/// Propose a new block to mine if the best block has changed since the last one was proposed.
+fn update_build<B: BlockT<Hash=H256>, C, Algorithm, E, SO, S, CAW>(
+	handle: &MiningHandle<B, Algorithm>,
 	client: &C,
-	algorithm: &Algorithm,
 	env: &mut E,
 	preruntime: Option<&Vec<u8>>,
-	round: u32,
 	sync_oracle: &mut SO,
 	build_time: std::time::Duration,
 	select_chain: Option<&S>,
@@ -444,116 +650,274 @@ fn mine_loop<B: BlockT<Hash=H256>, C, Algorithm, E, SO, S, CAW>(
 	S: SelectChain<B>,
 	CAW: CanAuthorWith<B>,
 {
-	'outer: loop {
-		if sync_oracle.is_major_syncing() {
-			debug!(target: "pow", "Skipping proposal due to sync.");
-			std::thread::sleep(std::time::Duration::new(1, 0));
-			continue 'outer
-		}
+	if sync_oracle.is_major_syncing() {
+		debug!(target: "pow", "Skipping proposal due to sync.");
+		*handle.build.lock() = None;
+		return Ok(())
+	}
 
-		let (best_hash, best_header) = match select_chain {
-			Some(select_chain) => {
-				let header = select_chain.best_chain()
-					.map_err(Error::BestHeaderSelectChain)?;
-				let hash = header.hash();
-				(hash, header)
-			},
-			None => {
-				let hash = client.info().best_hash;
-				let header = client.header(BlockId::Hash(hash))
-					.map_err(Error::BestHeader)?
-					.ok_or(Error::NoBestHeader)?;
-				(hash, header)
-			},
-		};
+	let (best_hash, best_header) = match select_chain {
+		Some(select_chain) => {
+			let header = select_chain.best_chain()
+				.map_err(Error::BestHeaderSelectChain)?;
+			let hash = header.hash();
+			(hash, header)
+		},
+		None => {
+			let hash = client.info().best_hash;
+			let header = client.header(BlockId::Hash(hash))
+				.map_err(Error::BestHeader)?
+				.ok_or(Error::NoBestHeader)?;
+			(hash, header)
+		},
+	};
+
+	if handle.metadata().map_or(false, |metadata| metadata.best_hash == best_hash) {
+		return Ok(())
+	}
 
-		if let Err(err) = can_author_with.can_author_with(&BlockId::Hash(best_hash)) {
-			warn!(
-				target: "pow",
-				"Skipping proposal `can_author_with` returned: {} \
-				Probably a node update is required!",
-				err,
-			);
-			std::thread::sleep(std::time::Duration::from_secs(1));
-			continue 'outer
-		}
+	if let Err(err) = can_author_with.can_author_with(&BlockId::Hash(best_hash)) {
+		warn!(
+			target: "pow",
+			"Skipping proposal `can_author_with` returned: {} \
+			Probably a node update is required!",
+			err,
+		);
+		*handle.build.lock() = None;
+		return Ok(())
+	}
 
-		let mut aux = PowAux::read(client, &best_hash)?;
-		let mut proposer = env.init(&best_header)
-			.map_err(|e| Error::Environment(format!("{:?}", e)))?;
+	let mut proposer = env.init(&best_header)
+		.map_err(|e| Error::Environment(format!("{:?}", e)))?;
 
-		let inherent_data = inherent_data_providers
-			.create_inherent_data().map_err(Error::CreateInherents)?;
-		let mut inherent_digest = Digest::default();
-		if let Some(preruntime) = &preruntime {
-			inherent_digest.push(DigestItem::PreRuntime(POW_ENGINE_ID, preruntime.to_vec()));
-		}
-		let block = futures::executor::block_on(proposer.propose(
-			inherent_data,
-			inherent_digest,
-			build_time.clone(),
-		)).map_err(|e| Error::BlockProposingError(format!("{:?}", e)))?;
-
-		let (header, body) = block.deconstruct();
-		let (difficulty, seal) = {
-			let difficulty = algorithm.difficulty(
-				&BlockId::Hash(best_hash),
-			)?;
+	let inherent_data = inherent_data_providers
+		.create_inherent_data().map_err(Error::CreateInherents)?;
+	let mut inherent_digest = Digest::default();
+	if let Some(preruntime) = &preruntime {
+		inherent_digest.push(DigestItem::PreRuntime(POW_ENGINE_ID, preruntime.to_vec()));
+	}
+	let block = futures::executor::block_on(proposer.propose(
+		inherent_data,
+		inherent_digest,
+		build_time.clone(),
+	)).map_err(|e| Error::BlockProposingError(format!("{:?}", e)))?;
+
+	let (header, body) = block.deconstruct();
+	let difficulty = handle.algorithm.difficulty(&BlockId::Hash(best_hash))?;
+	let metadata = MiningMetadata {
+		best_hash,
+		pre_hash: header.hash(),
+		difficulty,
+	};
+
+	*handle.build.lock() = Some(MiningBuild { metadata, header, body });
+	Ok(())
+}
 
-			loop {
-				let seal = algorithm.mine(
-					&BlockId::Hash(best_hash),
-					&header.hash(),
-					difficulty,
-					round,
-				)?;
+/// Start the background mining thread for PoW. Note that because PoW mining
+/// is CPU-intensive, it is not possible to use an async future to define this.
+/// However, it's not recommended to use background threads in the rest of the
+/// codebase.
+///
+/// The block to mine is kept up to date by a mining worker, see `start_mining_worker`
+/// for the parameters it takes. `round` is for number of rounds the CPU miner runs
+/// each time. This parameter should be tweaked so that each mining round is within
+/// sub-second time.
+pub fn start_mine<B: BlockT<Hash=H256>, C, Algorithm, E, SO, S, CAW>(
+	block_import: BoxBlockImport<B>,
+	client: Arc<C>,
+	algorithm: Algorithm,
+	env: E,
+	preruntime: Option<Vec<u8>>,
+	round: u32,
+	sync_oracle: SO,
+	build_time: std::time::Duration,
+	select_chain: Option<S>,
+	inherent_data_providers: sp_inherents::InherentDataProviders,
+	can_author_with: CAW,
+) -> MiningHandle<B, Algorithm> where
+	C: HeaderBackend<B> + AuxStore + 'static,
+	Algorithm: PowAlgorithm<B> + Send + Sync + 'static,
+	Algorithm::Difficulty: Send,
+	E: Environment<B> + Send + Sync + 'static,
+	E::Error: std::fmt::Debug,
+	SO: SyncOracle + Send + Sync + 'static,
+	S: SelectChain<B> + 'static,
+	CAW: CanAuthorWith<B> + Send + 'static,
+{
+	let handle = start_mining_worker(
+		block_import,
+		client,
+		algorithm,
+		env,
+		preruntime,
+		sync_oracle,
+		build_time,
+		select_chain,
+		inherent_data_providers,
+		can_author_with,
+	);
 
-				if let Some(seal) = seal {
-					break (difficulty, seal)
-				}
+	let miner_handle = handle.clone();
+	thread::spawn(move || {
+		loop {
+			let metadata = match miner_handle.metadata() {
+				Some(metadata) => metadata,
+				None => {
+					std::thread::sleep(std::time::Duration::new(1, 0));
+					continue
+				},
+			};
+
+			let seal = miner_handle.algorithm.mine(
+				&BlockId::Hash(metadata.best_hash),
+				&metadata.pre_hash,
+				metadata.difficulty,
+				round,
+			);
 
-				if best_hash != client.info().best_hash {
-					continue 'outer
-				}
+			match seal {
+				Ok(Some(seal)) => if let Err(e) = miner_handle.submit(seal) {
+					error!("Importing mined block failed with {:?}.", e);
+				},
+				Ok(None) => (),
+				Err(e) => {
+					error!(
+						"Mining block failed with {:?}. Sleep for 1 second before restarting...",
+						e
+					);
+					std::thread::sleep(std::time::Duration::new(1, 0));
+				},
 			}
-		};
+		}
+	});
 
-		aux.difficulty = difficulty;
-		aux.total_difficulty.increment(difficulty);
-		let hash = {
-			let mut header = header.clone();
-			header.digest_mut().push(DigestItem::Seal(POW_ENGINE_ID, seal.clone()));
-			header.hash()
-		};
+	handle
+}
 
-		let key = aux_key(&hash);
-		let best_hash = match select_chain {
-			Some(select_chain) => select_chain.best_chain()
-				.map_err(Error::BestHashSelectChain)?
-				.hash(),
-			None => client.info().best_hash,
-		};
-		let best_aux = PowAux::<Algorithm::Difficulty>::read(client, &best_hash)?;
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use substrate_test_runtime_client::{
+		prelude::*,
+		runtime::{Block, Extrinsic, Header},
+	};
+
+	/// An algorithm whose valid seal is the hash of the block being sealed.
+	#[derive(Clone)]
+	struct TestAlgorithm;
+
+	impl PowAlgorithm<Block> for TestAlgorithm {
+		type Difficulty = u128;
 
-		// if the best block has changed in the meantime drop our proposal
-		if best_aux.total_difficulty > aux.total_difficulty {
-			continue 'outer
+		fn difficulty(&self, _parent: &BlockId<Block>) -> Result<u128, Error<Block>> {
+			Ok(1)
 		}
 
-		let import_block = BlockImportParams {
+		fn verify(
+			&self,
+			_parent: &BlockId<Block>,
+			pre_hash: &H256,
+			seal: &Seal,
+			_difficulty: u128,
+		) -> Result<bool, Error<Block>> {
+			Ok(seal[..] == pre_hash[..])
+		}
+
+		fn mine(
+			&self,
+			_parent: &BlockId<Block>,
+			pre_hash: &H256,
+			_difficulty: u128,
+			_round: u32,
+		) -> Result<Option<Seal>, Error<Block>> {
+			Ok(Some(pre_hash.as_bytes().to_vec()))
+		}
+	}
+
+	fn sealed(mut header: Header, seal: Seal) -> Header {
+		header.digest_mut().push(DigestItem::Seal(POW_ENGINE_ID, seal));
+		header
+	}
+
+	fn import_params(header: Header, body: Vec<Extrinsic>, seal: Seal) -> BlockImportParams<Block> {
+		BlockImportParams {
 			origin: BlockOrigin::Own,
 			header,
 			justification: None,
 			post_digests: vec![DigestItem::Seal(POW_ENGINE_ID, seal)],
 			body: Some(body),
 			finalized: false,
-			auxiliary: vec![(key, Some(aux.encode()))],
-			fork_choice: ForkChoiceStrategy::Custom(true),
+			auxiliary: Vec::new(),
+			fork_choice: ForkChoiceStrategy::LongestChain,
 			allow_missing_state: false,
 			import_existing: false,
-		};
+			state: None,
+		}
+	}
+
+	#[test]
+	fn verifier_checks_the_seal() {
+		let verifier = PowVerifier::<Block, (), _>::new(
+			Arc::new(()),
+			TestAlgorithm,
+			0,
+			InherentDataProviders::new(),
+		);
+		let header = Header::new(
+			1,
+			Default::default(),
+			Default::default(),
+			Default::default(),
+			Default::default(),
+		);
+		let valid_seal = header.hash().as_bytes().to_vec();
+
+		let (checked_header, seal) = verifier.check_header(sealed(header.clone(), valid_seal.clone()))
+			.unwrap();
+		assert_eq!(checked_header, header);
+		assert_eq!(seal, DigestItem::Seal(POW_ENGINE_ID, valid_seal));
+
+		assert_eq!(
+			verifier.check_header(sealed(header.clone(), vec![0; 32])).err().map(|e| e.to_string()),
+			Some(Error::<Block>::InvalidSeal.to_string()),
+		);
+		assert_eq!(
+			verifier.check_header(header.clone()).err().map(|e| e.to_string()),
+			Some(Error::<Block>::HeaderUnsealed(header.hash()).to_string()),
+		);
+	}
 
-		block_import.import_block(import_block, HashMap::default())
-			.map_err(|e| Error::BlockBuiltError(best_hash, e))?;
+	#[test]
+	fn block_import_checks_the_seal_and_tracks_total_difficulty() {
+		let (client, select_chain) = TestClientBuilder::new().build_with_longest_chain();
+		let client = Arc::new(client);
+		let mut block_import = PowBlockImport::new(
+			&*client,
+			client.clone(),
+			TestAlgorithm,
+			Some(select_chain),
+		);
+
+		let (header, body) = client.new_block(Default::default()).unwrap()
+			.bake().unwrap().deconstruct();
+		let valid_seal = header.hash().as_bytes().to_vec();
+
+		assert!(block_import.import_block(
+			import_params(header.clone(), body.clone(), vec![0; 32]),
+			HashMap::new(),
+		).is_err());
+		assert_eq!(client.info().chain.best_number, 0);
+
+		block_import.import_block(
+			import_params(header.clone(), body, valid_seal.clone()),
+			HashMap::new(),
+		).unwrap();
+
+		let hash = sealed(header, valid_seal).hash();
+		assert_eq!(client.info().chain.best_hash, hash);
+		let aux = PowAux::<u128>::read::<_, Block>(&*client, &hash).unwrap();
+		assert_eq!(aux.difficulty, 1);
+		assert_eq!(aux.total_difficulty, 1);
 	}
 }
@@ -0,0 +1,215 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Timing of the block proposals of the slot workers.
+//!
+//! The proposer of a slot is given the rest of the slot to build its block, plus some lenience
+//! when the chain has not progressed for a few slots. A proposal that takes longer than that is
+//! discarded, after which the worker can back off according to a `BackoffStrategy`.
+
+use std::{sync::Arc, time::Duration};
+use parking_lot::Mutex;
+
+use crate::SlotInfo;
+
+/// Maximum number of times the proposal time is shrunk by `BackoffStrategy::ShrinkProposalTime`.
+const MAX_SHRINK_STEPS: u32 = 4;
+
+/// Lenience given to the proposer on top of the rest of the slot, when the chain has not
+/// progressed for some slots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotLenience {
+	/// The proposer only gets the rest of the slot.
+	None,
+	/// One slot of lenience per slot without block, up to `cap` slots.
+	Linear {
+		/// Maximum number of slots of lenience.
+		cap: u64,
+	},
+	/// The lenience doubles every `step` slots without block, starting from one slot, and stops
+	/// growing after `cap` slots without block.
+	Exponential {
+		/// Number of slots without block after which the lenience stops growing.
+		cap: u64,
+		/// Number of slots without block it takes to double the lenience.
+		step: u64,
+	},
+}
+
+impl SlotLenience {
+	/// Returns the lenience given to the proposer of the given slot, on top of a block authored
+	/// at `parent_slot`.
+	pub fn lenience(&self, parent_slot: u64, slot_info: &SlotInfo) -> Duration {
+		// there is always a difference of at least one slot with the parent, so only the slots
+		// in between are without block.
+		let skipped_slots = slot_info.number.saturating_sub(parent_slot + 1);
+		if skipped_slots == 0 {
+			return Duration::from_millis(0);
+		}
+
+		let slots = match *self {
+			SlotLenience::None => 0,
+			SlotLenience::Linear { cap } => skipped_slots.min(cap),
+			SlotLenience::Exponential { cap, step } => 1 << (skipped_slots.min(cap) / step.max(1)),
+		};
+		Duration::from_millis(slots * slot_info.duration)
+	}
+}
+
+/// What a slot worker does after discarding a proposal that took too long.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BackoffStrategy {
+	/// Keep proposing as usual.
+	Disabled,
+	/// Skip the given number of slots after a discarded proposal.
+	SkipSlots(u64),
+	/// Give the proposer only the given percentage of the proposal time after a discarded
+	/// proposal, compounded for every consecutive discarded proposal, until a proposal
+	/// completes in time.
+	ShrinkProposalTime(u8),
+}
+
+impl Default for BackoffStrategy {
+	fn default() -> Self {
+		BackoffStrategy::Disabled
+	}
+}
+
+#[derive(Default)]
+struct BackoffState {
+	/// Number of the proposals discarded in a row.
+	timeouts: u32,
+	/// The last slot without proposal, when skipping slots.
+	skip_until: u64,
+}
+
+/// Backoff of a slot worker after its proposals took too long, along with the timeouts noted so
+/// far. Clones share the same state.
+#[derive(Clone, Default)]
+pub struct ProposalBackoff {
+	strategy: BackoffStrategy,
+	state: Arc<Mutex<BackoffState>>,
+}
+
+impl ProposalBackoff {
+	/// Create a new `ProposalBackoff` following the given strategy.
+	pub fn new(strategy: BackoffStrategy) -> Self {
+		ProposalBackoff { strategy, state: Default::default() }
+	}
+
+	/// Returns the strategy followed.
+	pub fn strategy(&self) -> BackoffStrategy {
+		self.strategy
+	}
+
+	/// Whether no block should be proposed at the given slot.
+	pub fn should_skip(&self, slot_number: u64) -> bool {
+		match self.strategy {
+			BackoffStrategy::SkipSlots(_) => slot_number <= self.state.lock().skip_until,
+			_ => false,
+		}
+	}
+
+	/// Returns the time to give to the proposer, out of the given duration.
+	pub fn proposal_duration(&self, duration: Duration) -> Duration {
+		match self.strategy {
+			BackoffStrategy::ShrinkProposalTime(percent) => {
+				let steps = self.state.lock().timeouts.min(MAX_SHRINK_STEPS);
+				(0..steps).fold(duration, |d, _| d * u32::from(percent.min(100)) / 100)
+			},
+			_ => duration,
+		}
+	}
+
+	/// Note that the proposal of the given slot was discarded for taking too long.
+	pub fn note_timeout(&self, slot_number: u64) {
+		let mut state = self.state.lock();
+		state.timeouts = state.timeouts.saturating_add(1);
+		if let BackoffStrategy::SkipSlots(slots) = self.strategy {
+			state.skip_until = slot_number.saturating_add(slots);
+		}
+	}
+
+	/// Note that a proposal completed in time.
+	pub fn note_success(&self) {
+		self.state.lock().timeouts = 0;
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Instant;
+
+	fn slot_info(number: u64) -> SlotInfo {
+		SlotInfo {
+			number,
+			last_number: number - 1,
+			timestamp: number * 1000,
+			ends_at: Instant::now(),
+			inherent_data: Default::default(),
+			duration: 1000,
+		}
+	}
+
+	#[test]
+	fn lenience_grows_with_the_slots_without_block() {
+		let linear = SlotLenience::Linear { cap: 3 };
+		let exponential = SlotLenience::Exponential { cap: 8, step: 2 };
+		let secs = Duration::from_secs;
+
+		for lenience in &[SlotLenience::None, linear, exponential] {
+			assert_eq!(lenience.lenience(9, &slot_info(10)), secs(0));
+		}
+		assert_eq!(SlotLenience::None.lenience(5, &slot_info(10)), secs(0));
+
+		assert_eq!(linear.lenience(8, &slot_info(10)), secs(1));
+		assert_eq!(linear.lenience(7, &slot_info(10)), secs(2));
+		assert_eq!(linear.lenience(1, &slot_info(10)), secs(3));
+
+		assert_eq!(exponential.lenience(8, &slot_info(10)), secs(1));
+		assert_eq!(exponential.lenience(7, &slot_info(10)), secs(2));
+		assert_eq!(exponential.lenience(5, &slot_info(10)), secs(4));
+		assert_eq!(exponential.lenience(1, &slot_info(20)), secs(16));
+	}
+
+	#[test]
+	fn backoff_follows_the_strategy() {
+		let full = Duration::from_secs(8);
+
+		let disabled = ProposalBackoff::default();
+		disabled.note_timeout(10);
+		assert!(!disabled.should_skip(11));
+		assert_eq!(disabled.proposal_duration(full), full);
+
+		let skip = ProposalBackoff::new(BackoffStrategy::SkipSlots(2));
+		assert!(!skip.should_skip(10));
+		skip.clone().note_timeout(10);
+		assert!(skip.should_skip(11));
+		assert!(skip.should_skip(12));
+		assert!(!skip.should_skip(13));
+		assert_eq!(skip.proposal_duration(full), full);
+
+		let shrink = ProposalBackoff::new(BackoffStrategy::ShrinkProposalTime(50));
+		shrink.note_timeout(10);
+		assert!(!shrink.should_skip(11));
+		assert_eq!(shrink.proposal_duration(full), Duration::from_secs(4));
+		shrink.note_timeout(11);
+		assert_eq!(shrink.proposal_duration(full), Duration::from_secs(2));
+		shrink.note_success();
+		assert_eq!(shrink.proposal_duration(full), full);
+	}
+}
@@ -25,8 +25,10 @@
 
 mod slots;
 mod aux_schema;
+mod backoff;
 
 pub use slots::{SignedDuration, SlotInfo};
+pub use backoff::{BackoffStrategy, ProposalBackoff, SlotLenience};
 use slots::Slots;
 pub use aux_schema::{check_equivocation, MAX_SLOT_CAPACITY, PRUNING_BOUND};
 
@@ -43,6 +45,11 @@ use sc_telemetry::{telemetry, CONSENSUS_DEBUG, CONSENSUS_WARN, CONSENSUS_INFO};
 use parking_lot::Mutex;
 use sc_client_api;
 
+/// Future resolving to the `BlockImportParams` of a sealed block.
+pub type SealFuture<B> = Pin<Box<
+	dyn Future<Output = Result<sp_consensus::BlockImportParams<B>, sp_consensus::Error>> + Send
+>>;
+
 /// A worker that should be invoked at every new slot.
 pub trait SlotWorker<B: BlockT> {
 	/// The type of the future that will be returned when a new slot is
@@ -96,13 +103,14 @@ pub trait SimpleSlotWorker<B: BlockT> {
 	/// Return the pre digest data to include in a block authored with the given claim.
 	fn pre_digest_data(&self, slot_number: u64, claim: &Self::Claim) -> Vec<sp_runtime::DigestItem<B::Hash>>;
 
-	/// Returns a function which produces a `BlockImportParams`.
+	/// Returns a function which produces a `BlockImportParams`, sealing the block with the key
+	/// of the claim. The seal is signed asynchronously, as the key may be held out of process.
 	fn block_import_params(&self) -> Box<dyn Fn(
 		B::Header,
 		&B::Hash,
 		Vec<B::Extrinsic>,
 		Self::Claim,
-	) -> sp_consensus::BlockImportParams<B> + Send>;
+	) -> SealFuture<B> + Send>;
 
 	/// Whether to force authoring if offline.
 	fn force_authoring(&self) -> bool;
@@ -123,13 +131,45 @@ pub trait SimpleSlotWorker<B: BlockT> {
 		}
 	}
 
+	/// Returns the slot the given block was authored at, if known.
+	fn header_slot(&self, _header: &B::Header) -> Option<u64> {
+		None
+	}
+
+	/// Lenience given to the proposer when the chain has not progressed for some slots.
+	fn slot_lenience(&self) -> SlotLenience {
+		SlotLenience::None
+	}
+
+	/// Backoff to apply after discarding proposals that took too long, if any.
+	fn proposal_backoff(&self) -> Option<&ProposalBackoff> {
+		None
+	}
+
 	/// Remaining duration for proposing. None means unlimited.
 	fn proposing_remaining_duration(
 		&self,
-		_head: &B::Header,
+		head: &B::Header,
 		slot_info: &SlotInfo
 	) -> Option<Duration> {
-		Some(self.slot_remaining_duration(slot_info))
+		let slot_remaining = self.slot_remaining_duration(slot_info);
+		let parent_slot = match self.header_slot(head) {
+			Some(slot) => slot,
+			None => return Some(slot_remaining),
+		};
+
+		// in normal cases we only attempt to issue blocks up to the end of the slot.
+		// when the chain has been stalled for a few slots, we give more lenience.
+		let lenience = self.slot_lenience().lenience(parent_slot, slot_info);
+		if lenience > Duration::from_millis(0) {
+			debug!(target: self.logging_target(),
+				"No block for {} slots. Applying {:?} of lenience",
+				slot_info.number.saturating_sub(parent_slot + 1),
+				lenience,
+			);
+		}
+
+		Some(slot_remaining + lenience)
 	}
 
 	/// Implements the `on_slot` functionality from `SlotWorker`.
@@ -187,6 +227,19 @@ pub trait SimpleSlotWorker<B: BlockT> {
 			Some(claim) => claim,
 		};
 
+		let backoff = self.proposal_backoff().cloned();
+		if backoff.as_ref().map_or(false, |backoff| backoff.should_skip(slot_number)) {
+			debug!(target: self.logging_target(),
+				"Skipping proposal slot {} after a proposal that took too long",
+				slot_number,
+			);
+			telemetry!(CONSENSUS_DEBUG; "slots.skipping_proposal_slot_backoff";
+				"slot" => slot_number,
+			);
+
+			return Box::pin(future::ready(Ok(())));
+		}
+
 		debug!(
 			target: self.logging_target(), "Starting authorship at slot {}; timestamp = {}",
 			slot_number,
@@ -211,7 +264,10 @@ pub trait SimpleSlotWorker<B: BlockT> {
 			},
 		};
 
-		let slot_remaining_duration = self.slot_remaining_duration(&slot_info);
+		let mut slot_remaining_duration = self.slot_remaining_duration(&slot_info);
+		if let Some(backoff) = backoff.as_ref() {
+			slot_remaining_duration = backoff.proposal_duration(slot_remaining_duration);
+		}
 		let proposing_remaining_duration = self.proposing_remaining_duration(&chain_head, &slot_info);
 		let logs = self.pre_digest_data(slot_number, &claim);
 
@@ -230,9 +286,17 @@ pub trait SimpleSlotWorker<B: BlockT> {
 
 		let proposal_work =
 			Box::new(futures::future::select(proposing, delay).map(move |v| match v {
-				futures::future::Either::Left((b, _)) => b.map(|b| (b, claim)),
+				futures::future::Either::Left((b, _)) => {
+					if let Some(backoff) = backoff.as_ref() {
+						backoff.note_success();
+					}
+					b.map(|b| (b, claim))
+				},
 				futures::future::Either::Right(_) => {
 					info!("Discarding proposal for slot {}; block production took too long", slot_number);
+					if let Some(backoff) = backoff.as_ref() {
+						backoff.note_timeout(slot_number);
+					}
 					// If the node was compiled with debug, tell the user to use release optimizations.
 					#[cfg(build_type="debug")]
 					info!("Recompile your node in `--release` mode to mitigate this problem.");
@@ -247,18 +311,30 @@ pub trait SimpleSlotWorker<B: BlockT> {
 		let block_import = self.block_import();
 		let logging_target = self.logging_target();
 
-		Box::pin(proposal_work.map_ok(move |(block, claim)| {
+		Box::pin(async move {
+			let (block, claim) = proposal_work.await?;
 			let (header, body) = block.deconstruct();
 			let header_num = *header.number();
 			let header_hash = header.hash();
 			let parent_hash = *header.parent_hash();
 
-			let block_import_params = block_import_params_maker(
+			let sealing = block_import_params_maker(
 				header,
 				&header_hash,
 				body,
 				claim,
 			);
+			let block_import_params = match sealing.await {
+				Ok(params) => params,
+				Err(err) => {
+					warn!(target: logging_target,
+						"Failed to seal block built on {:?}: {:?}",
+						parent_hash,
+						err,
+					);
+					return Ok(());
+				},
+			};
 
 			info!("Pre-sealed block for proposal at {}. Hash now {:?}, previously {:?}.",
 					header_num,
@@ -283,7 +359,9 @@ pub trait SimpleSlotWorker<B: BlockT> {
 					"hash" => ?parent_hash, "err" => ?err,
 				);
 			}
-		}))
+
+			Ok(())
+		})
 	}
 }
 
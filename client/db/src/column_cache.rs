@@ -0,0 +1,305 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Independently sized read caches of the block and state columns of the database.
+
+use std::{io, sync::Arc};
+
+use kvdb::{KeyValueDB, DBTransaction, DBOp, DBValue};
+use linked_hash_map::LinkedHashMap;
+use log::warn;
+use parking_lot::Mutex;
+use prometheus_endpoint::{register, IntCounterVec, Opts, PrometheusError, Registry};
+
+use crate::columns;
+
+/// Sizes of the caches of the block and state columns, in bytes.
+///
+/// A size of zero disables the cache of the column.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ColumnCacheSizes {
+	/// Cache of the state trie nodes.
+	pub state: usize,
+	/// Cache of the block headers.
+	pub headers: usize,
+	/// Cache of the block bodies.
+	pub bodies: usize,
+	/// Cache of the justifications.
+	pub justifications: usize,
+}
+
+/// Prometheus metrics of the column caches.
+struct Metrics {
+	hits: IntCounterVec,
+	misses: IntCounterVec,
+}
+
+impl Metrics {
+	fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			hits: register(
+				IntCounterVec::new(
+					Opts::new("client_db_cache_hits", "Number of reads served by a column cache."),
+					&["column"],
+				)?,
+				registry,
+			)?,
+			misses: register(
+				IntCounterVec::new(
+					Opts::new("client_db_cache_misses", "Number of reads missed by a column cache."),
+					&["column"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Least recently read values of a column, bounded by their size.
+struct Cache {
+	name: &'static str,
+	values: LinkedHashMap<Vec<u8>, DBValue>,
+	used: usize,
+	limit: usize,
+	/// Incremented by every write to the column, so that a value read from the database is not
+	/// cached if the column was written meanwhile.
+	generation: u64,
+}
+
+impl Cache {
+	fn insert(&mut self, key: Vec<u8>, value: DBValue) {
+		self.remove(&key);
+		self.used += key.len() + value.len();
+		self.values.insert(key, value);
+
+		while self.used > self.limit {
+			match self.values.pop_front() {
+				Some((key, value)) => self.used -= key.len() + value.len(),
+				None => break,
+			}
+		}
+	}
+
+	fn remove(&mut self, key: &[u8]) {
+		if let Some(value) = self.values.remove(key) {
+			self.used -= key.len() + value.len();
+		}
+	}
+
+	fn clear(&mut self) {
+		self.values.clear();
+		self.used = 0;
+		self.generation += 1;
+	}
+}
+
+/// A database caching the reads of some of its columns.
+struct ColumnCacheDb {
+	db: Arc<dyn KeyValueDB>,
+	caches: Vec<(Option<u32>, Mutex<Cache>)>,
+	metrics: Option<Metrics>,
+}
+
+/// Wrap the full database `db` to cache the reads of its block and state columns.
+///
+/// The cache hits and misses are reported to `registry`, if any.
+pub fn wrap(
+	db: Arc<dyn KeyValueDB>,
+	sizes: &ColumnCacheSizes,
+	registry: Option<&Registry>,
+) -> Arc<dyn KeyValueDB> {
+	let caches = [
+		(columns::STATE, "state", sizes.state),
+		(columns::HEADER, "headers", sizes.headers),
+		(columns::BODY, "bodies", sizes.bodies),
+		(columns::JUSTIFICATION, "justifications", sizes.justifications),
+	].iter()
+		.filter(|(_, _, limit)| *limit > 0)
+		.map(|&(column, name, limit)| (column, Mutex::new(Cache {
+			name,
+			values: LinkedHashMap::new(),
+			used: 0,
+			limit,
+			generation: 0,
+		})))
+		.collect::<Vec<_>>();
+
+	if caches.is_empty() {
+		return db;
+	}
+
+	let metrics = registry.and_then(|registry| Metrics::register(registry)
+		.map_err(|err| warn!("Failed to register the database cache metrics: {:?}", err))
+		.ok()
+	);
+	Arc::new(ColumnCacheDb { db, caches, metrics })
+}
+
+impl ColumnCacheDb {
+	fn cache(&self, col: Option<u32>) -> Option<&Mutex<Cache>> {
+		self.caches.iter().find(|(column, _)| *column == col).map(|(_, cache)| cache)
+	}
+
+	fn update_caches(&self, transaction: &DBTransaction) {
+		for op in &transaction.ops {
+			let cache = match self.cache(op.col()) {
+				Some(cache) => cache,
+				None => continue,
+			};
+			let mut cache = cache.lock();
+			cache.generation += 1;
+			match op {
+				DBOp::Insert { key, value, .. } => cache.insert(key.to_vec(), value.clone()),
+				DBOp::Delete { key, .. } => cache.remove(key),
+			}
+		}
+	}
+}
+
+impl KeyValueDB for ColumnCacheDb {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<DBValue>> {
+		let cache = match self.cache(col) {
+			Some(cache) => cache,
+			None => return self.db.get(col, key),
+		};
+
+		let (name, generation) = {
+			let mut cache = cache.lock();
+			if let Some(value) = cache.values.get_refresh(key) {
+				let value = value.clone();
+				if let Some(metrics) = &self.metrics {
+					metrics.hits.with_label_values(&[cache.name]).inc();
+				}
+				return Ok(Some(value));
+			}
+			(cache.name, cache.generation)
+		};
+		if let Some(metrics) = &self.metrics {
+			metrics.misses.with_label_values(&[name]).inc();
+		}
+
+		let value = self.db.get(col, key)?;
+		if let Some(value) = &value {
+			let mut cache = cache.lock();
+			if cache.generation == generation {
+				cache.insert(key.to_vec(), value.clone());
+			}
+		}
+		Ok(value)
+	}
+
+	fn get_by_prefix(&self, col: Option<u32>, prefix: &[u8]) -> Option<Box<[u8]>> {
+		self.db.get_by_prefix(col, prefix)
+	}
+
+	fn write_buffered(&self, transaction: DBTransaction) {
+		self.update_caches(&transaction);
+		self.db.write_buffered(transaction)
+	}
+
+	fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+		self.update_caches(&transaction);
+		self.db.write(transaction)
+	}
+
+	fn flush(&self) -> io::Result<()> {
+		self.db.flush()
+	}
+
+	fn iter<'a>(&'a self, col: Option<u32>)
+		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+	{
+		self.db.iter(col)
+	}
+
+	fn iter_from_prefix<'a>(&'a self, col: Option<u32>, prefix: &'a [u8])
+		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+	{
+		self.db.iter_from_prefix(col, prefix)
+	}
+
+	fn restore(&self, new_db: &str) -> io::Result<()> {
+		for (_, cache) in &self.caches {
+			cache.lock().clear();
+		}
+		self.db.restore(new_db)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn sizes(headers: usize) -> ColumnCacheSizes {
+		ColumnCacheSizes { headers, ..Default::default() }
+	}
+
+	#[test]
+	fn reads_are_cached_and_writes_update_the_cache() {
+		let backing = Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS));
+		let db = wrap(backing.clone(), &sizes(1024), None);
+
+		let mut transaction = DBTransaction::new();
+		transaction.put(columns::HEADER, b"key", b"value");
+		backing.write(transaction).unwrap();
+		assert_eq!(db.get(columns::HEADER, b"key").unwrap().unwrap().to_vec(), b"value".to_vec());
+
+		// Served from the cache.
+		let mut transaction = DBTransaction::new();
+		transaction.delete(columns::HEADER, b"key");
+		backing.write(transaction).unwrap();
+		assert!(db.get(columns::HEADER, b"key").unwrap().is_some());
+
+		// Writes through the cache are seen.
+		let mut transaction = DBTransaction::new();
+		transaction.put(columns::HEADER, b"key", b"other");
+		db.write(transaction).unwrap();
+		assert_eq!(db.get(columns::HEADER, b"key").unwrap().unwrap().to_vec(), b"other".to_vec());
+		let mut transaction = DBTransaction::new();
+		transaction.delete(columns::HEADER, b"key");
+		db.write(transaction).unwrap();
+		assert!(db.get(columns::HEADER, b"key").unwrap().is_none());
+	}
+
+	#[test]
+	fn cache_is_bounded_by_size() {
+		let backing = Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS));
+		let db = wrap(backing.clone(), &sizes(8), None);
+
+		let mut transaction = DBTransaction::new();
+		transaction.put(columns::HEADER, b"a", b"111");
+		transaction.put(columns::HEADER, b"b", b"222");
+		transaction.put(columns::HEADER, b"c", b"333");
+		db.write(transaction).unwrap();
+
+		// Only the last two entries fit in the cache.
+		let mut transaction = DBTransaction::new();
+		transaction.delete(columns::HEADER, b"a");
+		transaction.delete(columns::HEADER, b"b");
+		transaction.delete(columns::HEADER, b"c");
+		backing.write(transaction).unwrap();
+		assert!(db.get(columns::HEADER, b"a").unwrap().is_none());
+		assert!(db.get(columns::HEADER, b"b").unwrap().is_some());
+		assert!(db.get(columns::HEADER, b"c").unwrap().is_some());
+	}
+
+	#[test]
+	fn disabled_caches_do_not_wrap() {
+		let backing: Arc<dyn KeyValueDB> = Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS));
+		let db = wrap(backing.clone(), &ColumnCacheSizes::default(), None);
+		assert!(Arc::ptr_eq(&backing, &db));
+	}
+}
@@ -0,0 +1,469 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Offline inspection and repair of the database of a full node.
+//!
+//! These functions open the database directly, without reading its metadata, so that they still
+//! work when the metadata is corrupted. They rely on iterating over the columns in the order of
+//! their keys, which ParityDb doesn't support.
+
+use std::{collections::HashMap, mem};
+
+use codec::{Decode, Encode};
+use kvdb::{KeyValueDB, DBTransaction};
+use sc_client::leaves::LeafSet;
+use sp_blockchain::{Result as ClientResult, Error as ClientError};
+use sp_core::{H256, Blake2Hasher};
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, Zero, One, SaturatedConversion};
+use sp_state_machine::Storage;
+use sp_trie::{TrieConfiguration, EMPTY_PREFIX, trie_types::Layout};
+use sc_state_db::StateDb;
+
+use crate::{columns, DatabaseSettings, StateMetaDb, StorageDb};
+use crate::utils::{self, db_err, meta_keys};
+
+/// Number of operations written to the database at once when repairing the metadata.
+const REPAIR_BATCH_SIZE: usize = 10_000;
+
+/// Names of the columns of the database of a full node, by index.
+const COLUMN_NAMES: [&str; utils::NUM_COLUMNS as usize] = [
+	"meta",
+	"state",
+	"state_meta",
+	"key_lookup",
+	"header",
+	"body",
+	"justification",
+	"changes_trie",
+	"aux",
+	"offchain",
+];
+
+/// Size of a column of the database.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnStats {
+	/// Name of the column.
+	pub name: &'static str,
+	/// Number of entries.
+	pub entries: u64,
+	/// Total size of the keys, in bytes.
+	pub key_bytes: u64,
+	/// Total size of the values, in bytes.
+	pub value_bytes: u64,
+}
+
+/// An inconsistency between the blocks of the canonical chain and their state.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Inconsistency<Block: BlockT> {
+	/// There is no canonical block with this number.
+	MissingBlock(NumberFor<Block>),
+	/// The header of the canonical block with this number is missing or can't be decoded.
+	InvalidHeader(NumberFor<Block>),
+	/// The header of the block doesn't have the hash it is indexed by.
+	HashMismatch(NumberFor<Block>, Block::Hash),
+	/// The parent of the block isn't the previous canonical block.
+	ParentMismatch(NumberFor<Block>, Block::Hash),
+	/// The root node of the state of the block is missing, either pruned or lost.
+	MissingState(NumberFor<Block>, Block::Hash),
+}
+
+/// Metadata rebuilt by [`repair_meta`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RepairedMeta<Block: BlockT> {
+	/// Number and hash of the best block.
+	pub best: (NumberFor<Block>, Block::Hash),
+	/// Number and hash of the finalized block.
+	pub finalized: (NumberFor<Block>, Block::Hash),
+	/// Number of leaves of the block tree.
+	pub leaves: usize,
+	/// Number of headers that couldn't be decoded, and were ignored.
+	pub invalid_headers: usize,
+}
+
+fn open(settings: &DatabaseSettings) -> ClientResult<std::sync::Arc<dyn KeyValueDB>> {
+	utils::open_database(settings, columns::META, "full")
+}
+
+/// Read the header of the block with the given number and hash, without going through the
+/// indexes of the database, which may be corrupted.
+fn read_header<Block: BlockT>(
+	db: &dyn KeyValueDB,
+	number: NumberFor<Block>,
+	hash: Block::Hash,
+) -> ClientResult<Option<Block::Header>> {
+	let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
+	Ok(db.get(columns::HEADER, &lookup_key).map_err(db_err)?
+		.and_then(|header| Block::Header::decode(&mut &header[..]).ok()))
+}
+
+/// Write `transaction` to the database once it is large enough, so that it isn't kept in memory.
+fn write_batch(db: &dyn KeyValueDB, transaction: &mut DBTransaction) -> ClientResult<()> {
+	if transaction.ops.len() >= REPAIR_BATCH_SIZE {
+		db.write(mem::replace(transaction, DBTransaction::new())).map_err(db_err)?;
+	}
+	Ok(())
+}
+
+/// Returns the size of each column of the database.
+pub fn column_stats(settings: &DatabaseSettings) -> ClientResult<Vec<ColumnStats>> {
+	let db = open(settings)?;
+	Ok(COLUMN_NAMES.iter().enumerate().map(|(column, name)| {
+		let mut stats = ColumnStats { name, entries: 0, key_bytes: 0, value_bytes: 0 };
+		for (key, value) in db.iter(Some(column as u32)) {
+			stats.entries += 1;
+			stats.key_bytes += key.len() as u64;
+			stats.value_bytes += value.len() as u64;
+		}
+		stats
+	}).collect())
+}
+
+/// Check the canonical blocks from `from` to `to` included, or to the best block, and the
+/// presence of their state.
+///
+/// The state of the blocks pruned according to the pruning mode of `settings` isn't checked.
+pub fn check_blocks<Block: BlockT<Hash=H256>>(
+	settings: &DatabaseSettings,
+	from: NumberFor<Block>,
+	to: Option<NumberFor<Block>>,
+) -> ClientResult<Vec<Inconsistency<Block>>> {
+	let db = open(settings)?;
+	let state_db = StateDb::new(settings.pruning.clone(), &StateMetaDb(&*db))
+		.map_err(|e| ClientError::Backend(format!("State database error: {:?}", e)))?;
+	let storage = StorageDb::<Block> { db: db.clone(), state_db };
+	let to = match to {
+		Some(to) => to,
+		None => utils::read_meta::<Block>(&*db, columns::META, columns::HEADER)?.best_number,
+	};
+	let empty_root = Layout::<Blake2Hasher>::trie_root(std::iter::empty::<(Vec<u8>, Vec<u8>)>());
+
+	let mut inconsistencies = Vec::new();
+	let mut parent_hash = None;
+	let mut number = from;
+	while number <= to {
+		let lookup_key = match db.get(columns::KEY_LOOKUP, &utils::number_index_key(number)?)
+			.map_err(db_err)?
+		{
+			Some(lookup_key) => lookup_key,
+			None => {
+				inconsistencies.push(Inconsistency::MissingBlock(number));
+				parent_hash = None;
+				number += One::one();
+				continue;
+			},
+		};
+		let header = db.get(columns::HEADER, &lookup_key).map_err(db_err)?
+			.and_then(|header| Block::Header::decode(&mut &header[..]).ok());
+		let header = match header {
+			Some(header) => header,
+			None => {
+				inconsistencies.push(Inconsistency::InvalidHeader(number));
+				parent_hash = None;
+				number += One::one();
+				continue;
+			},
+		};
+
+		let hash = header.hash();
+		if lookup_key.get(4..) != Some(hash.as_ref()) {
+			inconsistencies.push(Inconsistency::HashMismatch(number, hash));
+		}
+		if parent_hash.map_or(false, |parent_hash| parent_hash != *header.parent_hash()) {
+			inconsistencies.push(Inconsistency::ParentMismatch(number, hash));
+		}
+		let state_root = *header.state_root();
+		let pruned = storage.state_db.is_pruned(&hash, number.saturated_into::<u64>());
+		if !pruned && state_root != empty_root && storage.get(&state_root, EMPTY_PREFIX)
+			.map_err(ClientError::Backend)?
+			.is_none()
+		{
+			inconsistencies.push(Inconsistency::MissingState(number, hash));
+		}
+
+		parent_hash = Some(hash);
+		number += One::one();
+	}
+
+	Ok(inconsistencies)
+}
+
+/// Rebuild the leaves, the children, the best and finalized blocks and the canonical chain index
+/// from the headers of the database.
+///
+/// The finalized block is kept if its header is still present, otherwise the genesis block is
+/// used. The best block is the highest block descending from the finalized block.
+///
+/// The headers are streamed in the order of their numbers, and the chain is walked through the
+/// parents of the blocks, so that they aren't all kept in memory. The indexes are thus written in
+/// several batches, and the repair can be run again if it is interrupted.
+pub fn repair_meta<Block: BlockT>(
+	settings: &DatabaseSettings,
+) -> ClientResult<RepairedMeta<Block>> {
+	let db = open(settings)?;
+
+	let mut transaction = DBTransaction::new();
+	for prefix in &[&meta_keys::LEAF_PREFIX[..], &meta_keys::CHILDREN_PREFIX[..]] {
+		for (key, _) in db.iter_from_prefix(columns::META, prefix) {
+			if !key.starts_with(prefix) { break }
+			transaction.delete(columns::META, &key);
+			write_batch(&*db, &mut transaction)?;
+		}
+	}
+
+	// The headers are keyed by the number of their block first, so the parents come before their
+	// children, and all the children of a block come together.
+	let mut invalid_headers = 0;
+	let mut genesis_hash = None;
+	let mut highest_number = Zero::zero();
+	let mut leaves = LeafSet::new();
+	// The numbers of the leaves, which are only known from their headers.
+	let mut leaf_numbers = HashMap::new();
+	let mut children = HashMap::<_, Vec<Block::Hash>>::new();
+	for (_, header) in db.iter(columns::HEADER) {
+		let header = match Block::Header::decode(&mut &header[..]) {
+			Ok(header) => header,
+			Err(_) => {
+				invalid_headers += 1;
+				continue;
+			},
+		};
+		let (number, hash, parent_hash) = (*header.number(), header.hash(), *header.parent_hash());
+
+		if number.is_zero() {
+			genesis_hash.get_or_insert(hash);
+		} else {
+			if number > highest_number {
+				write_children(&mut transaction, mem::replace(&mut children, HashMap::new()));
+			}
+			children.entry(parent_hash).or_default().push(hash);
+		}
+		leaves.import(hash, number, parent_hash);
+		leaf_numbers.remove(&parent_hash);
+		leaf_numbers.insert(hash, number);
+		utils::insert_hash_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash)?;
+		write_batch(&*db, &mut transaction)?;
+		highest_number = number;
+	}
+	write_children(&mut transaction, children);
+
+	let genesis_hash = genesis_hash
+		.ok_or_else(|| ClientError::Backend("The genesis header is missing".into()))?;
+	let finalized = db.get(columns::META, meta_keys::FINALIZED_BLOCK).map_err(db_err)?
+		.and_then(|lookup_key| db.get(columns::HEADER, &lookup_key).ok().and_then(|header| header))
+		.and_then(|header| Block::Header::decode(&mut &header[..]).ok())
+		.map(|header| (*header.number(), header.hash()))
+		.unwrap_or((Zero::zero(), genesis_hash));
+
+	let descends_from_finalized = |(mut number, mut hash): (NumberFor<Block>, Block::Hash)|
+		-> ClientResult<bool>
+	{
+		while number > finalized.0 {
+			match read_header::<Block>(&*db, number, hash)? {
+				Some(header) => hash = *header.parent_hash(),
+				None => return Ok(false),
+			}
+			number -= One::one();
+		}
+		Ok(hash == finalized.1)
+	};
+
+	// The leaves below the finalized block are pruned, as after a finalization.
+	let _displaced = leaves.finalize_height(finalized.0);
+	let mut best = finalized;
+	for hash in leaves.hashes() {
+		let number = leaf_numbers[&hash];
+		if descends_from_finalized((number, hash))? {
+			best = (number, hash);
+			break;
+		}
+	}
+	leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
+
+	// Index the numbers of the canonical blocks.
+	let mut number = best.0 + One::one();
+	while number <= highest_number {
+		utils::remove_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number)?;
+		write_batch(&*db, &mut transaction)?;
+		number += One::one();
+	}
+	let (mut number, mut hash) = best;
+	while let Some(header) = read_header::<Block>(&*db, number, hash)? {
+		utils::insert_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash)?;
+		write_batch(&*db, &mut transaction)?;
+		if number.is_zero() { break }
+		hash = *header.parent_hash();
+		number -= One::one();
+	}
+
+	transaction.put(columns::META, meta_keys::GENESIS_HASH, genesis_hash.as_ref());
+	transaction.put_vec(
+		columns::META,
+		meta_keys::BEST_BLOCK,
+		utils::number_and_hash_to_lookup_key(best.0, best.1)?,
+	);
+	transaction.put_vec(
+		columns::META,
+		meta_keys::FINALIZED_BLOCK,
+		utils::number_and_hash_to_lookup_key(finalized.0, finalized.1)?,
+	);
+	db.write(transaction).map_err(db_err)?;
+
+	Ok(RepairedMeta { best, finalized, leaves: leaves.hashes().len(), invalid_headers })
+}
+
+/// Write the children of the blocks to the metadata.
+fn write_children<Hash: Eq + std::hash::Hash + Clone + Encode + Decode>(
+	transaction: &mut DBTransaction,
+	children: HashMap<Hash, Vec<Hash>>,
+) {
+	for (parent_hash, children) in children {
+		crate::children::write_children(
+			transaction,
+			columns::META,
+			meta_keys::CHILDREN_PREFIX,
+			parent_hash,
+			children,
+		);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::Arc;
+	use sc_client_api::backend::{Backend as _, BlockImportOperation as _, NewBlockState};
+	use sc_client::blockchain::Backend as _;
+	use sp_blockchain::HeaderBackend;
+	use sp_core::storage::Storage as RawStorage;
+	use sp_runtime::{generic::BlockId, testing::{Block as RawBlock, ExtrinsicWrapper, Header}};
+	use sp_state_machine::Backend as _;
+	use sp_trie::prefixed_key;
+	use crate::{Backend, DatabaseSettingsSrc, PruningMode};
+
+	type Block = RawBlock<ExtrinsicWrapper<u64>>;
+
+	fn settings(db: Arc<dyn KeyValueDB>) -> DatabaseSettings {
+		pruned_settings(db, PruningMode::ArchiveAll)
+	}
+
+	fn pruned_settings(db: Arc<dyn KeyValueDB>, pruning: PruningMode) -> DatabaseSettings {
+		DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: None,
+			pruning,
+			column_cache_sizes: Default::default(),
+			offchain_indexing: false,
+			source: DatabaseSettingsSrc::Custom(db),
+		}
+	}
+
+	fn insert_block(
+		backend: &Backend<Block>,
+		number: u64,
+		parent_hash: H256,
+		extrinsics_root: H256,
+	) -> H256 {
+		let header = Header {
+			number,
+			parent_hash,
+			state_root: Layout::<Blake2Hasher>::trie_root(std::iter::empty::<(Vec<u8>, Vec<u8>)>()),
+			digest: Default::default(),
+			extrinsics_root,
+		};
+		let hash = header.hash();
+		let mut op = backend.begin_operation().unwrap();
+		backend.begin_state_operation(&mut op, BlockId::Hash(parent_hash)).unwrap();
+		op.set_block_data(header, Some(vec![]), None, NewBlockState::Best).unwrap();
+		backend.commit_operation(op).unwrap();
+		hash
+	}
+
+	#[test]
+	fn repair_meta_restores_best_block_and_leaves() {
+		let db: Arc<dyn KeyValueDB> = Arc::new(kvdb_memorydb::create(utils::NUM_COLUMNS));
+		let (genesis, best) = {
+			let backend = Backend::<Block>::new(settings(db.clone()), 0).unwrap();
+			let genesis = insert_block(&backend, 0, Default::default(), Default::default());
+			let block1 = insert_block(&backend, 1, genesis, Default::default());
+			let fork = insert_block(&backend, 1, genesis, H256::repeat_byte(1));
+			let block2 = insert_block(&backend, 2, block1, Default::default());
+			assert_ne!(fork, block1);
+			(genesis, block2)
+		};
+
+		// Lose the best block and the leaves.
+		let mut transaction = DBTransaction::new();
+		transaction.delete(columns::META, meta_keys::BEST_BLOCK);
+		for (key, _) in db.iter_from_prefix(columns::META, meta_keys::LEAF_PREFIX) {
+			transaction.delete(columns::META, &key);
+		}
+		db.write(transaction).unwrap();
+
+		let repaired = repair_meta::<Block>(&settings(db.clone())).unwrap();
+		assert_eq!(repaired.best, (2, best));
+		assert_eq!(repaired.finalized, (0, genesis));
+		assert_eq!(repaired.leaves, 2);
+
+		let backend = Backend::<Block>::new(settings(db.clone()), 0).unwrap();
+		assert_eq!(backend.blockchain().info().best_hash, best);
+		assert_eq!(backend.blockchain().leaves().unwrap().len(), 2);
+		assert!(check_blocks::<Block>(&settings(db), 0, None).unwrap().is_empty());
+	}
+
+	#[test]
+	fn check_blocks_skips_pruned_blocks() {
+		let db: Arc<dyn KeyValueDB> = Arc::new(kvdb_memorydb::create(utils::NUM_COLUMNS));
+		let pruning = PruningMode::keep_blocks(1);
+		let genesis_root = {
+			let backend = Backend::<Block>::new(pruned_settings(db.clone(), pruning.clone()), 0)
+				.unwrap();
+			let mut parent_hash = Default::default();
+			let mut genesis_root = None;
+			for number in 0..4u64 {
+				let mut op = backend.begin_operation().unwrap();
+				backend.begin_state_operation(&mut op, BlockId::Hash(parent_hash)).unwrap();
+				let storage = vec![(vec![1], Some(vec![number as u8]))];
+				let (root, overlay) = op.old_state.storage_root(storage.iter().cloned());
+				if number == 0 {
+					op.reset_storage(RawStorage {
+						top: vec![(vec![1], vec![0])].into_iter().collect(),
+						children: Default::default(),
+					}).unwrap();
+				} else {
+					op.update_db_storage(overlay).unwrap();
+				}
+				let header = Header {
+					number,
+					parent_hash,
+					state_root: root,
+					digest: Default::default(),
+					extrinsics_root: Default::default(),
+				};
+				parent_hash = header.hash();
+				op.set_block_data(header, Some(vec![]), None, NewBlockState::Final).unwrap();
+				backend.commit_operation(op).unwrap();
+				genesis_root.get_or_insert(root);
+			}
+			genesis_root.unwrap()
+		};
+
+		// The state of the genesis block was pruned, which isn't an inconsistency.
+		assert!(db.get(columns::STATE, &prefixed_key::<Blake2Hasher>(&genesis_root, EMPTY_PREFIX))
+			.unwrap()
+			.is_none());
+		assert!(check_blocks::<Block>(&pruned_settings(db, pruning), 0, None).unwrap().is_empty());
+	}
+}
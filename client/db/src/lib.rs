@@ -28,20 +28,25 @@
 
 pub mod light;
 pub mod offchain;
+pub mod inspect;
 
 mod children;
 mod cache;
+mod column_cache;
 mod storage_cache;
 mod utils;
+#[cfg(feature = "parity-db")]
+mod parity_db;
 
 use std::sync::Arc;
 use std::path::PathBuf;
 use std::io;
+use std::mem;
 use std::collections::{HashMap, HashSet};
 
 use sc_client_api::{execution_extensions::ExecutionExtensions, ForkBlocks};
-use sc_client_api::backend::NewBlockState;
-use sc_client_api::backend::{StorageCollection, ChildStorageCollection};
+use sc_client_api::backend::{NewBlockState, PurgeTarget};
+use sc_client_api::backend::{StorageCollection, ChildStorageCollection, OffchainChangesCollection};
 use sp_blockchain::{
 	Result as ClientResult, Error as ClientError,
 	well_known_cache_keys, HeaderBackend,
@@ -51,6 +56,7 @@ use hash_db::{Hasher, Prefix};
 use kvdb::{KeyValueDB, DBTransaction};
 use sp_trie::{MemoryDB, PrefixedMemoryDB, prefixed_key};
 use parking_lot::{Mutex, RwLock};
+use prometheus_endpoint::Registry;
 use sp_core::{H256, Blake2Hasher, ChangesTrieConfiguration, convert_hash, traits::CodeExecutor};
 use sp_core::storage::{well_known_keys, ChildInfo};
 use sp_runtime::{
@@ -66,9 +72,11 @@ use sp_state_machine::{
 	backend::Backend as StateBackend,
 };
 use crate::utils::{Meta, db_err, meta_keys, read_db, read_meta};
+pub use crate::utils::migrate_to_parity_db;
+pub use crate::column_cache::ColumnCacheSizes;
 use sc_client::leaves::{LeafSet, FinalizationDisplaced};
 use sc_state_db::StateDb;
-use sp_blockchain::{CachedHeaderMetadata, HeaderMetadata, HeaderMetadataCache};
+use sp_blockchain::{CachedHeaderMetadata, HeaderMetadata, HeaderMetadataCache, TreeRoute};
 use crate::storage_cache::{CachingState, SharedCache, new_shared_cache};
 use log::{trace, debug, warn};
 pub use sc_state_db::PruningMode;
@@ -78,6 +86,23 @@ use sc_client::in_mem::Backend as InMemoryBackend;
 
 const CANONICALIZATION_DELAY: u64 = 4096;
 const MIN_BLOCKS_TO_KEEP_CHANGES_TRIES_FOR: u32 = 32768;
+/// Number of keys deleted at once when purging a column.
+const PURGE_BATCH_SIZE: usize = 10_000;
+
+/// Returns the number of blocks whose changes tries are kept with the given state pruning, or
+/// `None` if they are never pruned.
+///
+/// Changes tries are kept at least as long as the state of their blocks, so that the changes of
+/// a key can be found without replaying the blocks whose state is available.
+fn changes_tries_blocks_to_keep(pruning: &PruningMode) -> Option<u32> {
+	match pruning {
+		PruningMode::ArchiveAll | PruningMode::ArchiveCanonical => None,
+		PruningMode::Constrained(constraints) => Some(std::cmp::max(
+			constraints.max_blocks.unwrap_or(0),
+			MIN_BLOCKS_TO_KEEP_CHANGES_TRIES_FOR,
+		)),
+	}
+}
 
 /// Default value for storage cache child ratio.
 const DEFAULT_CHILD_RATIO: (usize, usize) = (1, 10);
@@ -252,6 +277,11 @@ pub struct DatabaseSettings {
 	pub state_cache_child_ratio: Option<(usize, usize)>,
 	/// Pruning mode.
 	pub pruning: PruningMode,
+	/// Sizes of the caches of the block and state columns. Only used by full nodes.
+	pub column_cache_sizes: ColumnCacheSizes,
+	/// Whether the data indexed by the runtime during block import is written to the offchain
+	/// storage. Only used by full nodes.
+	pub offchain_indexing: bool,
 	/// Where to find the database.
 	pub source: DatabaseSettingsSrc,
 }
@@ -266,6 +296,12 @@ pub enum DatabaseSettingsSrc {
 		cache_size: Option<usize>,
 	},
 
+	/// Load a ParityDb database from a given path.
+	ParityDb {
+		/// Path to the database.
+		path: PathBuf,
+	},
+
 	/// Use a custom already-open database.
 	Custom(Arc<dyn KeyValueDB>),
 }
@@ -277,6 +313,7 @@ pub fn new_client<E, S, Block, RA>(
 	genesis_storage: S,
 	fork_blocks: ForkBlocks<Block>,
 	execution_extensions: ExecutionExtensions<Block>,
+	prometheus_registry: Option<Registry>,
 ) -> Result<(
 		sc_client::Client<
 			Backend<Block>,
@@ -293,10 +330,19 @@ pub fn new_client<E, S, Block, RA>(
 		E: CodeExecutor + RuntimeInfo,
 		S: BuildStorage,
 {
-	let backend = Arc::new(Backend::new(settings, CANONICALIZATION_DELAY)?);
+	let backend = Arc::new(
+		Backend::new_with_registry(settings, CANONICALIZATION_DELAY, prometheus_registry.as_ref())?
+	);
 	let executor = sc_client::LocalCallExecutor::new(backend.clone(), executor);
 	Ok((
-		sc_client::Client::new(backend.clone(), executor, genesis_storage, fork_blocks, execution_extensions)?,
+		sc_client::Client::new(
+			backend.clone(),
+			executor,
+			genesis_storage,
+			fork_blocks,
+			execution_extensions,
+			prometheus_registry,
+		)?,
 		backend,
 	))
 }
@@ -494,6 +540,14 @@ impl<Block: BlockT> HeaderMetadata<Block> for BlockchainDb<Block> {
 	fn remove_header_metadata(&self, hash: Block::Hash) {
 		self.header_metadata_cache.remove_header_metadata(hash);
 	}
+
+	fn cached_tree_route(&self, from: Block::Hash, to: Block::Hash) -> Option<TreeRoute<Block>> {
+		self.header_metadata_cache.cached_tree_route(from, to)
+	}
+
+	fn cache_tree_route(&self, route: &TreeRoute<Block>) {
+		self.header_metadata_cache.cache_tree_route(route)
+	}
 }
 
 /// Database transaction
@@ -502,6 +556,7 @@ pub struct BlockImportOperation<Block: BlockT, H: Hasher> {
 	db_updates: PrefixedMemoryDB<H>,
 	storage_updates: StorageCollection,
 	child_storage_updates: ChildStorageCollection,
+	offchain_storage_updates: OffchainChangesCollection,
 	changes_trie_updates: MemoryDB<H>,
 	changes_trie_cache_update: Option<ChangesTrieCacheAction<H::Out, NumberFor<Block>>>,
 	pending_block: Option<PendingBlock<Block>>,
@@ -509,9 +564,22 @@ pub struct BlockImportOperation<Block: BlockT, H: Hasher> {
 	finalized_blocks: Vec<(BlockId<Block>, Option<Justification>)>,
 	set_head: Option<BlockId<Block>>,
 	commit_state: bool,
+	reset_storage: bool,
 }
 
 impl<Block: BlockT, H: Hasher> BlockImportOperation<Block, H> {
+	fn apply_offchain(&mut self, transaction: &mut DBTransaction) {
+		// the entries are not keyed by block: the blocks of competing forks overwrite each
+		// other's entries, and the block imported last wins.
+		for (key, maybe_val) in self.offchain_storage_updates.drain(..) {
+			let key: Vec<u8> = sp_offchain::STORAGE_PREFIX.iter().chain(&key).cloned().collect();
+			match maybe_val {
+				Some(val) => transaction.put_vec(columns::OFFCHAIN, &key, val),
+				None => transaction.delete(columns::OFFCHAIN, &key),
+			}
+		}
+	}
+
 	fn apply_aux(&mut self, transaction: &mut DBTransaction) {
 		for (key, maybe_val) in self.aux_ops.drain(..) {
 			match maybe_val {
@@ -584,6 +652,7 @@ impl<Block> sc_client_api::backend::BlockImportOperation<Block, Blake2Hasher>
 
 		self.db_updates = transaction;
 		self.commit_state = true;
+		self.reset_storage = true;
 		Ok(root)
 	}
 
@@ -613,6 +682,14 @@ impl<Block> sc_client_api::backend::BlockImportOperation<Block, Blake2Hasher>
 		Ok(())
 	}
 
+	fn update_offchain_storage(
+		&mut self,
+		offchain_update: OffchainChangesCollection,
+	) -> ClientResult<()> {
+		self.offchain_storage_updates = offchain_update;
+		Ok(())
+	}
+
 	fn mark_finalized(&mut self, block: BlockId<Block>, justification: Option<Justification>) -> ClientResult<()> {
 		self.finalized_blocks.push((block, justification));
 		Ok(())
@@ -837,6 +914,7 @@ pub struct Backend<Block: BlockT> {
 	shared_cache: SharedCache<Block, Blake2Hasher>,
 	import_lock: RwLock<()>,
 	is_archive: bool,
+	offchain_indexing: bool,
 }
 
 impl<Block: BlockT<Hash=H256>> Backend<Block> {
@@ -844,8 +922,19 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 	///
 	/// The pruning window is how old a block must be before the state is pruned.
 	pub fn new(config: DatabaseSettings, canonicalization_delay: u64) -> ClientResult<Self> {
+		Self::new_with_registry(config, canonicalization_delay, None)
+	}
+
+	/// Create a new instance of database backend, reporting the hits and misses of the column
+	/// caches to `registry`.
+	pub fn new_with_registry(
+		config: DatabaseSettings,
+		canonicalization_delay: u64,
+		registry: Option<&Registry>,
+	) -> ClientResult<Self> {
 		let db = crate::utils::open_database(&config, columns::META, "full")?;
-		Self::from_kvdb(db as Arc<_>, canonicalization_delay, &config)
+		let db = column_cache::wrap(db, &config.column_cache_sizes, registry);
+		Self::from_kvdb(db, canonicalization_delay, &config)
 	}
 
 	/// Create new memory-backed client backend for tests.
@@ -856,6 +945,8 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			state_cache_size: 16777216,
 			state_cache_child_ratio: Some((50, 100)),
 			pruning: PruningMode::keep_blocks(keep_blocks),
+			column_cache_sizes: Default::default(),
+			offchain_indexing: false,
 			source: DatabaseSettingsSrc::Custom(db),
 		};
 
@@ -867,7 +958,6 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 		canonicalization_delay: u64,
 		config: &DatabaseSettings
 	) -> ClientResult<Self> {
-		let is_archive_pruning = config.pruning.is_archive();
 		let blockchain = BlockchainDb::new(db.clone())?;
 		let meta = blockchain.meta.clone();
 		let map_e = |e: sc_state_db::Error<io::Error>| ::sp_blockchain::Error::from(format!("State database error: {:?}", e));
@@ -880,7 +970,7 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 		let changes_tries_storage = DbChangesTrieStorage {
 			db,
 			meta,
-			min_blocks_to_keep: if is_archive_pruning { None } else { Some(MIN_BLOCKS_TO_KEEP_CHANGES_TRIES_FOR) },
+			min_blocks_to_keep: changes_tries_blocks_to_keep(&config.pruning),
 			cache: RwLock::new(ChangesTrieBuildCache::new()),
 			_phantom: Default::default(),
 		};
@@ -897,7 +987,8 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				config.state_cache_child_ratio.unwrap_or(DEFAULT_CHILD_RATIO),
 			),
 			import_lock: Default::default(),
-			is_archive: is_archive_pruning,
+			is_archive: config.pruning.is_archive(),
+			offchain_indexing: config.offchain_indexing,
 		})
 	}
 
@@ -1130,6 +1221,9 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 		let mut finalization_displaced_leaves = None;
 
 		operation.apply_aux(&mut transaction);
+		if self.offchain_indexing {
+			operation.apply_offchain(&mut transaction);
+		}
 
 		let mut meta_updates = Vec::with_capacity(operation.finalized_blocks.len());
 		let mut last_finalized_hash = self.blockchain.meta.read().finalized_hash;
@@ -1157,7 +1251,19 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			// blocks are keyed by number + hash.
 			let lookup_key = utils::number_and_hash_to_lookup_key(number, hash)?;
 
-			let (enacted, retracted) = if pending_block.leaf_state.is_best() {
+			// A block imported along with its full state, other than genesis, has no known
+			// ancestry. It becomes the best and finalized block without any route to it.
+			let imported_state = operation.reset_storage && !number.is_zero();
+			// The state may also be imported for a block whose header is already in the chain,
+			// in which case it's already a leaf and a child of its parent.
+			let existing_header = imported_state &&
+				self.blockchain.header(BlockId::Hash(hash))?.is_some();
+
+			let (enacted, retracted) = if imported_state {
+				transaction.put(columns::META, meta_keys::BEST_BLOCK, &lookup_key);
+				utils::insert_number_to_key_mapping(&mut transaction, columns::KEY_LOOKUP, number, hash)?;
+				(Default::default(), Default::default())
+			} else if pending_block.leaf_state.is_best() {
 				self.set_head_with_transaction(&mut transaction, parent_hash, (number, hash))?
 			} else {
 				(Default::default(), Default::default())
@@ -1199,8 +1305,11 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 					}
 				}
 				let number_u64 = number.saturated_into::<u64>();
-				let commit = self.storage.state_db.insert_block(&hash, number_u64, &pending_block.header.parent_hash(), changeset)
-					.map_err(|e: sc_state_db::Error<io::Error>| sp_blockchain::Error::from(format!("State database error: {:?}", e)))?;
+				let commit = if imported_state {
+					self.storage.state_db.import_canonical_block(&hash, number_u64, changeset)
+				} else {
+					self.storage.state_db.insert_block(&hash, number_u64, &pending_block.header.parent_hash(), changeset)
+				}.map_err(|e: sc_state_db::Error<io::Error>| sp_blockchain::Error::from(format!("State database error: {:?}", e)))?;
 				apply_state_commit(&mut transaction, commit);
 
 				// Check if need to finalize. Genesis is always finalized instantly.
@@ -1217,7 +1326,15 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			self.changes_tries_storage.commit(&mut transaction, changes_trie_updates);
 			let cache = operation.old_state.release(); // release state reference so that it can be finalized
 
-			if finalized {
+			if imported_state {
+				// The state of the block has been canonicalized on insertion above.
+				transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &lookup_key);
+				let displaced = self.blockchain.leaves.write().finalize_height(number);
+				match finalization_displaced_leaves {
+					None => finalization_displaced_leaves = Some(displaced),
+					Some(ref mut existing) => existing.merge(displaced),
+				}
+			} else if finalized {
 				// TODO: ensure best chain contains this block.
 				self.ensure_sequential_finalization(header, Some(last_finalized_hash))?;
 				self.note_finalized(
@@ -1226,14 +1343,17 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 					hash,
 					&mut finalization_displaced_leaves,
 				)?;
-			} else {
+			} else if operation.commit_state {
 				// canonicalize blocks which are old enough, regardless of finality.
+				// Blocks imported without their state have nothing to canonicalize.
 				self.force_delayed_canonicalize(&mut transaction, hash, *header.number())?
 			}
 
 			debug!(target: "db", "DB Commit {:?} ({}), best = {}", hash, number, is_best);
 
-			let displaced_leaf = {
+			let displaced_leaf = if existing_header {
+				None
+			} else {
 				let mut leaves = self.blockchain.leaves.write();
 				let displaced_leaf = leaves.import(hash, number, parent_hash);
 				leaves.prepare_transaction(&mut transaction, columns::META, meta_keys::LEAF_PREFIX);
@@ -1241,9 +1361,11 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 				displaced_leaf
 			};
 
-			let mut children = children::read_children(&*self.storage.db, columns::META, meta_keys::CHILDREN_PREFIX, parent_hash)?;
-			children.push(hash);
-			children::write_children(&mut transaction, columns::META, meta_keys::CHILDREN_PREFIX, parent_hash, children);
+			if !existing_header {
+				let mut children = children::read_children(&*self.storage.db, columns::META, meta_keys::CHILDREN_PREFIX, parent_hash)?;
+				children.push(hash);
+				children::write_children(&mut transaction, columns::META, meta_keys::CHILDREN_PREFIX, parent_hash, children);
+			}
 
 			meta_updates.push((hash, number, pending_block.leaf_state.is_best(), finalized));
 
@@ -1335,13 +1457,17 @@ impl<Block: BlockT<Hash=H256>> Backend<Block> {
 			let lookup_key = utils::number_and_hash_to_lookup_key(f_num, f_hash.clone())?;
 			transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &lookup_key);
 
-			let commit = self.storage.state_db.canonicalize_block(&f_hash)
-				.map_err(|e: sc_state_db::Error<io::Error>| sp_blockchain::Error::from(format!("State database error: {:?}", e)))?;
-			apply_state_commit(transaction, commit);
+			// Blocks imported without their state, as during fast sync, are finalized without
+			// canonicalizing anything.
+			if !self.storage.state_db.is_pruned(&f_hash, f_num.saturated_into::<u64>()) {
+				let commit = self.storage.state_db.canonicalize_block(&f_hash)
+					.map_err(|e: sc_state_db::Error<io::Error>| sp_blockchain::Error::from(format!("State database error: {:?}", e)))?;
+				apply_state_commit(transaction, commit);
 
-			let changes_trie_config = self.changes_trie_config(parent_hash)?;
-			if let Some(changes_trie_config) = changes_trie_config {
-				self.changes_tries_storage.prune(&changes_trie_config, transaction, f_hash, f_num);
+				let changes_trie_config = self.changes_trie_config(parent_hash)?;
+				if let Some(changes_trie_config) = changes_trie_config {
+					self.changes_tries_storage.prune(&changes_trie_config, transaction, f_hash, f_num);
+				}
 			}
 		}
 
@@ -1409,12 +1535,14 @@ impl<Block> sc_client_api::backend::Backend<Block, Blake2Hasher> for Backend<Blo
 			db_updates: PrefixedMemoryDB::default(),
 			storage_updates: Default::default(),
 			child_storage_updates: Default::default(),
+			offchain_storage_updates: Default::default(),
 			changes_trie_updates: MemoryDB::default(),
 			changes_trie_cache_update: None,
 			aux_ops: Vec::new(),
 			finalized_blocks: Vec::new(),
 			set_head: None,
 			commit_state: false,
+			reset_storage: false,
 		})
 	}
 
@@ -1484,10 +1612,10 @@ impl<Block> sc_client_api::backend::Backend<Block, Blake2Hasher> for Backend<Blo
 		Some(self.offchain_storage.clone())
 	}
 
-	fn revert(&self, n: NumberFor<Block>) -> ClientResult<NumberFor<Block>> {
+	fn revert(&self, n: NumberFor<Block>, revert_finalized: bool) -> ClientResult<NumberFor<Block>> {
 		let mut best = self.blockchain.info().best_number;
 		let finalized = self.blockchain.info().finalized_number;
-		let revertible = best - finalized;
+		let revertible = if revert_finalized { best } else { best - finalized };
 		let n = if revertible < n { revertible } else { n };
 
 		for c in 0 .. n.saturated_into::<u64>() {
@@ -1508,11 +1636,17 @@ impl<Block> sc_client_api::backend::Backend<Block, Blake2Hasher> for Backend<Blo
 							format!("Error reverting to {}. Block hash not found.", best)))?;
 					let key = utils::number_and_hash_to_lookup_key(best.clone(), &hash)?;
 					transaction.put(columns::META, meta_keys::BEST_BLOCK, &key);
+					// the parent of a reverted finalized block becomes the last finalized block.
+					let is_finalized = best < finalized;
+					if is_finalized {
+						transaction.put(columns::META, meta_keys::FINALIZED_BLOCK, &key);
+					}
 					transaction.delete(columns::KEY_LOOKUP, removed.hash().as_ref());
 					children::remove_children(&mut transaction, columns::META, meta_keys::CHILDREN_PREFIX, hash);
 					self.storage.db.write(transaction).map_err(db_err)?;
-					self.blockchain.update_meta(hash, best, true, false);
+					self.blockchain.update_meta(hash, best, true, is_finalized);
 					self.blockchain.leaves.write().revert(removed.hash().clone(), removed.number().clone(), removed.parent_hash().clone());
+					self.blockchain.remove_header_metadata(removed.hash());
 				}
 				None => return Ok(c.saturated_into::<NumberFor<Block>>())
 			}
@@ -1520,6 +1654,30 @@ impl<Block> sc_client_api::backend::Backend<Block, Blake2Hasher> for Backend<Blo
 		Ok(n)
 	}
 
+	fn purge(&self, target: PurgeTarget) -> ClientResult<()> {
+		let columns: &[Option<u32>] = match target {
+			PurgeTarget::State => &[columns::STATE, columns::STATE_META],
+			PurgeTarget::BlockBodies => &[columns::BODY],
+		};
+
+		// the keys are deleted in batches, a whole column wouldn't fit in memory.
+		for column in columns {
+			let mut transaction = DBTransaction::new();
+			let mut batched = 0;
+			for (key, _) in self.storage.db.iter(*column) {
+				transaction.delete(*column, &key);
+				batched += 1;
+				if batched == PURGE_BATCH_SIZE {
+					self.storage.db.write(mem::replace(&mut transaction, DBTransaction::new()))
+						.map_err(db_err)?;
+					batched = 0;
+				}
+			}
+			self.storage.db.write(transaction).map_err(db_err)?;
+		}
+		Ok(())
+	}
+
 	fn blockchain(&self) -> &BlockchainDb<Block> {
 		&self.blockchain
 	}
@@ -1577,6 +1735,24 @@ impl<Block> sc_client_api::backend::Backend<Block, Blake2Hasher> for Backend<Blo
 		}
 	}
 
+	fn pin_block(&self, hash: &Block::Hash) -> ClientResult<()> {
+		use sc_client::blockchain::HeaderBackend as BcHeaderBackend;
+
+		let discarded = || sp_blockchain::Error::UnknownBlock(
+			format!("State already discarded for {:?}", hash)
+		);
+		let number = self.blockchain.number(hash.clone())?
+			.ok_or_else(|| sp_blockchain::Error::UnknownBlock(format!("Unknown block {:?}", hash)))?;
+		if !self.have_state_at(hash, number) {
+			return Err(discarded());
+		}
+		self.storage.state_db.pin(hash).map_err(|_| discarded())
+	}
+
+	fn unpin_block(&self, hash: &Block::Hash) {
+		self.storage.state_db.unpin(hash)
+	}
+
 	fn destroy_state(&self, state: Self::State) -> ClientResult<()> {
 		if let Some(hash) = state.cache.parent_hash.clone() {
 			let is_best = self.blockchain.meta.read().best_hash == hash;
@@ -1666,6 +1842,99 @@ mod tests {
 		header_hash
 	}
 
+	#[test]
+	fn only_blocks_with_state_are_pinned() {
+		let backend = Backend::<Block>::new_test(1, 0);
+		let block0 = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+
+		backend.pin_block(&block0).unwrap();
+		backend.pin_block(&block0).unwrap();
+		backend.unpin_block(&block0);
+		backend.unpin_block(&block0);
+		assert!(backend.pin_block(&H256::from_low_u64_be(42)).is_err());
+	}
+
+	#[test]
+	fn offchain_changes_are_only_written_when_indexing() {
+		use sp_core::offchain::OffchainStorage;
+
+		for &offchain_indexing in &[false, true] {
+			let backend = Backend::<Block>::new(DatabaseSettings {
+				state_cache_size: 16777216,
+				state_cache_child_ratio: Some((50, 100)),
+				pruning: PruningMode::keep_blocks(1),
+				column_cache_sizes: Default::default(),
+				offchain_indexing,
+				source: DatabaseSettingsSrc::Custom(Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS))),
+			}, 0).unwrap();
+			backend.offchain_storage().unwrap().set(sp_offchain::STORAGE_PREFIX, b"removed", b"old");
+
+			let header = Header {
+				number: 0,
+				parent_hash: Default::default(),
+				state_root: BlakeTwo256::trie_root(Vec::new()),
+				digest: Default::default(),
+				extrinsics_root: Default::default(),
+			};
+			let mut op = backend.begin_operation().unwrap();
+			backend.begin_state_operation(&mut op, BlockId::Hash(Default::default())).unwrap();
+			op.set_block_data(header, Some(Vec::new()), None, NewBlockState::Best).unwrap();
+			op.update_offchain_storage(vec![
+				(b"indexed".to_vec(), Some(b"value".to_vec())),
+				(b"removed".to_vec(), None),
+			]).unwrap();
+			backend.commit_operation(op).unwrap();
+
+			let storage = backend.offchain_storage().unwrap();
+			if offchain_indexing {
+				assert_eq!(storage.get(sp_offchain::STORAGE_PREFIX, b"indexed"), Some(b"value".to_vec()));
+				assert_eq!(storage.get(sp_offchain::STORAGE_PREFIX, b"removed"), None);
+			} else {
+				assert_eq!(storage.get(sp_offchain::STORAGE_PREFIX, b"indexed"), None);
+				assert_eq!(storage.get(sp_offchain::STORAGE_PREFIX, b"removed"), Some(b"old".to_vec()));
+			}
+		}
+	}
+
+	#[test]
+	fn offchain_changes_of_the_last_imported_fork_win() {
+		use sp_core::offchain::OffchainStorage;
+
+		let backend = Backend::<Block>::new(DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			pruning: PruningMode::keep_blocks(1),
+			column_cache_sizes: Default::default(),
+			offchain_indexing: true,
+			source: DatabaseSettingsSrc::Custom(Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS))),
+		}, 0).unwrap();
+
+		let import = |number, parent_hash, extrinsics_root, value: &[u8]| {
+			let header = Header {
+				number,
+				parent_hash,
+				state_root: BlakeTwo256::trie_root(Vec::new()),
+				digest: Default::default(),
+				extrinsics_root,
+			};
+			let hash = header.hash();
+			let parent = if number == 0 { Default::default() } else { parent_hash };
+			let mut op = backend.begin_operation().unwrap();
+			backend.begin_state_operation(&mut op, BlockId::Hash(parent)).unwrap();
+			op.set_block_data(header, Some(Vec::new()), None, NewBlockState::Best).unwrap();
+			op.update_offchain_storage(vec![(b"indexed".to_vec(), Some(value.to_vec()))]).unwrap();
+			backend.commit_operation(op).unwrap();
+			hash
+		};
+
+		let block0 = import(0, Default::default(), Default::default(), b"genesis");
+		import(1, block0, Default::default(), b"fork a");
+		import(1, block0, H256::from_low_u64_be(1), b"fork b");
+
+		let storage = backend.offchain_storage().unwrap();
+		assert_eq!(storage.get(sp_offchain::STORAGE_PREFIX, b"indexed"), Some(b"fork b".to_vec()));
+	}
+
 	#[test]
 	fn block_hash_inserted_correctly() {
 		let backing = {
@@ -1712,6 +1981,8 @@ mod tests {
 			state_cache_size: 16777216,
 			state_cache_child_ratio: Some((50, 100)),
 			pruning: PruningMode::keep_blocks(1),
+			column_cache_sizes: Default::default(),
+			offchain_indexing: false,
 			source: DatabaseSettingsSrc::Custom(backing),
 		}, 0).unwrap();
 		assert_eq!(backend.blockchain().info().best_number, 9);
@@ -2061,6 +2332,17 @@ mod tests {
 		assert_eq!(backend.changes_tries_storage.root(&anchor, 3), Ok(Some(changes2_2_0_root)));
 	}
 
+	#[test]
+	fn changes_tries_are_kept_as_long_as_the_state() {
+		assert_eq!(changes_tries_blocks_to_keep(&PruningMode::ArchiveAll), None);
+		assert_eq!(changes_tries_blocks_to_keep(&PruningMode::ArchiveCanonical), None);
+		assert_eq!(
+			changes_tries_blocks_to_keep(&PruningMode::keep_blocks(256)),
+			Some(MIN_BLOCKS_TO_KEEP_CHANGES_TRIES_FOR),
+		);
+		assert_eq!(changes_tries_blocks_to_keep(&PruningMode::keep_blocks(100_000)), Some(100_000));
+	}
+
 	#[test]
 	fn changes_tries_with_digest_are_pruned_on_finalization() {
 		let mut backend = Backend::<Block>::new_test(1000, 100);
@@ -2362,6 +2644,48 @@ mod tests {
 		substrate_test_runtime_client::trait_tests::test_blockchain_query_by_number_gets_canonical(backend);
 	}
 
+	#[test]
+	fn purge_block_bodies_keeps_headers() {
+		let backend = Backend::<Block>::new_test(10, 10);
+		let genesis = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+		let block1 = insert_header(&backend, 1, genesis, Vec::new(), Default::default());
+		assert!(backend.blockchain().body(BlockId::Hash(block1)).unwrap().is_some());
+
+		backend.purge(PurgeTarget::BlockBodies).unwrap();
+
+		assert!(backend.blockchain().body(BlockId::Hash(genesis)).unwrap().is_none());
+		assert!(backend.blockchain().body(BlockId::Hash(block1)).unwrap().is_none());
+		assert!(backend.blockchain().header(BlockId::Hash(block1)).unwrap().is_some());
+		assert_eq!(backend.blockchain().info().best_hash, block1);
+	}
+
+	#[test]
+	fn reverts_finalized_blocks_only_when_asked() {
+		let backend = Backend::<Block>::new(DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			pruning: PruningMode::ArchiveAll,
+			column_cache_sizes: Default::default(),
+			offchain_indexing: false,
+			source: DatabaseSettingsSrc::Custom(Arc::new(kvdb_memorydb::create(crate::utils::NUM_COLUMNS))),
+		}, 0).unwrap();
+		let block0 = insert_header(&backend, 0, Default::default(), Vec::new(), Default::default());
+		let block1 = insert_header(&backend, 1, block0, Vec::new(), Default::default());
+		let block2 = insert_header(&backend, 2, block1, Vec::new(), Default::default());
+		let block3 = insert_header(&backend, 3, block2, Vec::new(), Default::default());
+		backend.finalize_block(BlockId::hash(block2), None).unwrap();
+		assert_eq!(backend.blockchain().info().best_hash, block3);
+
+		assert_eq!(backend.revert(3, false).unwrap(), 1);
+		assert_eq!(backend.blockchain().info().best_hash, block2);
+		assert_eq!(backend.blockchain().info().finalized_hash, block2);
+
+		assert_eq!(backend.revert(1, true).unwrap(), 1);
+		assert_eq!(backend.blockchain().info().best_hash, block1);
+		assert_eq!(backend.blockchain().info().finalized_hash, block1);
+		assert_eq!(backend.blockchain().info().finalized_number, 1);
+	}
+
 	#[test]
 	fn test_leaves_pruned_on_finality() {
 		let backend: Backend<Block> = Backend::new_test(10, 10);
@@ -2386,6 +2710,33 @@ mod tests {
 		assert_eq!(backend.blockchain().leaves().unwrap(), vec![block2_a, block2_b, block2_c]);
 	}
 
+	#[test]
+	fn tree_routes_are_cached() {
+		let backend: Backend<Block> = Backend::new_test(10, 10);
+		let block0 = insert_header(&backend, 0, Default::default(), Default::default(), Default::default());
+		let block1 = insert_header(&backend, 1, block0, Default::default(), Default::default());
+		let blockchain = backend.blockchain();
+
+		assert!(blockchain.cached_tree_route(block0, block1).is_none());
+		let route = tree_route(blockchain, block0, block1).unwrap();
+		let cached = blockchain.cached_tree_route(block0, block1).unwrap();
+		assert_eq!(cached.common_block().hash, route.common_block().hash);
+		assert_eq!(cached.enacted().len(), 1);
+		assert!(blockchain.cached_tree_route(block1, block0).is_none());
+	}
+
+	#[test]
+	fn cached_tree_routes_are_dropped_on_revert() {
+		let backend: Backend<Block> = Backend::new_test(10, 10);
+		let block0 = insert_header(&backend, 0, Default::default(), Default::default(), Default::default());
+		let block1 = insert_header(&backend, 1, block0, Default::default(), Default::default());
+
+		tree_route(backend.blockchain(), block0, block1).unwrap();
+		assert_eq!(backend.revert(1, false).unwrap(), 1);
+		assert!(backend.blockchain().cached_tree_route(block0, block1).is_none());
+		assert!(tree_route(backend.blockchain(), block0, block1).is_err());
+	}
+
 	#[test]
 	fn test_aux() {
 		let backend: Backend<substrate_test_runtime_client::runtime::Block> = Backend::new_test(0, 0);
@@ -28,7 +28,7 @@ use sc_client::blockchain::{
 };
 use sc_client::cht;
 use sp_blockchain::{
-	CachedHeaderMetadata, HeaderMetadata, HeaderMetadataCache,
+	CachedHeaderMetadata, HeaderMetadata, HeaderMetadataCache, TreeRoute,
 	Error as ClientError, Result as ClientResult,
 	HeaderBackend as BlockchainHeaderBackend, 
 	well_known_cache_keys,
@@ -208,6 +208,14 @@ impl<Block: BlockT> HeaderMetadata<Block> for LightStorage<Block> {
 	fn remove_header_metadata(&self, hash: Block::Hash) {
 		self.header_metadata_cache.remove_header_metadata(hash);
 	}
+
+	fn cached_tree_route(&self, from: Block::Hash, to: Block::Hash) -> Option<TreeRoute<Block>> {
+		self.header_metadata_cache.cached_tree_route(from, to)
+	}
+
+	fn cache_tree_route(&self, route: &TreeRoute<Block>) {
+		self.header_metadata_cache.cache_tree_route(route)
+	}
 }
 
 impl<Block: BlockT> LightStorage<Block> {
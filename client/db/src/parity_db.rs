@@ -0,0 +1,138 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! ParityDb backend of the client database.
+//!
+//! ParityDb has no column `None`, so it is stored in the first column and the column `Some(i)`
+//! in the column `i + 1`.
+
+use std::{io, path::Path, sync::Arc};
+
+use kvdb::{KeyValueDB, DBTransaction, DBOp, DBValue};
+use log::warn;
+use parking_lot::Mutex;
+
+/// A ParityDb database behind the `KeyValueDB` interface.
+///
+/// ParityDb indexes the hashes of the keys, so iterating over a column or looking up a key by
+/// its prefix is not supported. The adapter is thus only used to copy a database into ParityDb,
+/// and the client refuses to open ParityDb databases.
+struct DbAdapter {
+	db: parity_db::Db,
+	/// Error of the last buffered write, reported by the next flush.
+	buffered_error: Mutex<Option<io::Error>>,
+}
+
+/// Open the ParityDb database at `path` with the given number of columns, creating it if needed.
+pub fn open(path: &Path, num_columns: u32) -> io::Result<Arc<dyn KeyValueDB>> {
+	let options = parity_db::Options::with_columns(path, num_columns as u8 + 1);
+	let db = parity_db::Db::open(&options).map_err(db_err)?;
+	Ok(Arc::new(DbAdapter { db, buffered_error: Mutex::new(None) }))
+}
+
+fn column(col: Option<u32>) -> u8 {
+	col.map_or(0, |col| col as u8 + 1)
+}
+
+fn db_err(err: parity_db::Error) -> io::Error {
+	io::Error::new(io::ErrorKind::Other, format!("ParityDb error: {:?}", err))
+}
+
+impl DbAdapter {
+	fn commit(&self, transaction: DBTransaction) -> io::Result<()> {
+		self.db.commit(transaction.ops.into_iter().map(|op| match op {
+			DBOp::Insert { col, key, value } => (column(col), key.to_vec(), Some(value.to_vec())),
+			DBOp::Delete { col, key } => (column(col), key.to_vec(), None),
+		})).map_err(db_err)
+	}
+}
+
+impl KeyValueDB for DbAdapter {
+	fn get(&self, col: Option<u32>, key: &[u8]) -> io::Result<Option<DBValue>> {
+		self.db.get(column(col), key)
+			.map(|value| value.map(DBValue::from_vec))
+			.map_err(db_err)
+	}
+
+	fn get_by_prefix(&self, _col: Option<u32>, _prefix: &[u8]) -> Option<Box<[u8]>> {
+		warn!("Looking up a key by prefix is not supported by ParityDb");
+		None
+	}
+
+	fn write_buffered(&self, transaction: DBTransaction) {
+		if let Err(e) = self.commit(transaction) {
+			*self.buffered_error.lock() = Some(e);
+		}
+	}
+
+	fn write(&self, transaction: DBTransaction) -> io::Result<()> {
+		self.commit(transaction)
+	}
+
+	fn flush(&self) -> io::Result<()> {
+		match self.buffered_error.lock().take() {
+			Some(e) => Err(e),
+			None => Ok(()),
+		}
+	}
+
+	fn iter<'a>(&'a self, _col: Option<u32>)
+		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+	{
+		warn!("Iterating over a column is not supported by ParityDb");
+		Box::new(std::iter::empty())
+	}
+
+	fn iter_from_prefix<'a>(&'a self, _col: Option<u32>, _prefix: &'a [u8])
+		-> Box<dyn Iterator<Item=(Box<[u8]>, Box<[u8]>)> + 'a>
+	{
+		warn!("Iterating over a column is not supported by ParityDb");
+		Box::new(std::iter::empty())
+	}
+
+	fn restore(&self, _new_db: &str) -> io::Result<()> {
+		Err(io::Error::new(io::ErrorKind::Other, "Restoring is not supported by ParityDb"))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn columns_are_kept_apart() {
+		let dir = tempfile::tempdir().unwrap();
+		let db = open(dir.path(), 2).unwrap();
+
+		let mut transaction = DBTransaction::new();
+		transaction.put(None, b"key", b"none");
+		transaction.put(Some(0), b"key", b"zero");
+		transaction.put(Some(1), b"other", b"one");
+		db.write(transaction).unwrap();
+
+		assert_eq!(db.get(None, b"key").unwrap().unwrap().to_vec(), b"none".to_vec());
+		assert_eq!(db.get(Some(0), b"key").unwrap().unwrap().to_vec(), b"zero".to_vec());
+		assert!(db.get(Some(1), b"key").unwrap().is_none());
+
+		let mut transaction = DBTransaction::new();
+		transaction.delete(Some(0), b"key");
+		db.write_buffered(transaction);
+		db.flush().unwrap();
+
+		assert!(db.get(Some(0), b"key").unwrap().is_none());
+		assert_eq!(db.get(None, b"key").unwrap().unwrap().to_vec(), b"none".to_vec());
+	}
+}
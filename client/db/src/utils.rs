@@ -18,7 +18,7 @@
 //! full and light storages.
 
 use std::sync::Arc;
-use std::{io, convert::TryInto};
+use std::{io, convert::TryInto, path::Path};
 
 use kvdb::{KeyValueDB, DBTransaction};
 #[cfg(feature = "kvdb-rocksdb")]
@@ -237,6 +237,13 @@ pub fn open_database(
 			let msg = "Try to open RocksDB database with RocksDB disabled".into();
 			return Err(sp_blockchain::Error::Backend(msg));
 		},
+		// The client iterates over the columns of its database, for instance to load the leaves
+		// of the chain, which ParityDb doesn't support yet.
+		DatabaseSettingsSrc::ParityDb { .. } => {
+			let msg = "ParityDb cannot be used as the client database until it supports \
+				iterating over a column".into();
+			return Err(sp_blockchain::Error::Backend(msg));
+		},
 		DatabaseSettingsSrc::Custom(db) => db.clone(),
 	};
 
@@ -258,6 +265,59 @@ pub fn open_database(
 	Ok(db)
 }
 
+/// Copy the content of the RocksDB database at `rocksdb_path` into a new ParityDb database at
+/// `parity_db_path`.
+///
+/// The client cannot open the copy until ParityDb supports iterating over a column, so the
+/// RocksDB database is left untouched.
+///
+/// Returns the number of entries copied.
+#[cfg(all(feature = "kvdb-rocksdb", feature = "parity-db"))]
+pub fn migrate_to_parity_db(rocksdb_path: &Path, parity_db_path: &Path) -> sp_blockchain::Result<u64> {
+	/// Number of entries written to ParityDb at once.
+	const BATCH_SIZE: usize = 64 * 1024;
+
+	if parity_db_path.exists() {
+		return Err(sp_blockchain::Error::Backend(
+			format!("The ParityDb database {} already exists", parity_db_path.display())
+		));
+	}
+	let rocksdb_path = rocksdb_path.to_str()
+		.ok_or_else(|| sp_blockchain::Error::Backend("Invalid database path".into()))?;
+	let source = Database::open(&DatabaseConfig::with_columns(Some(NUM_COLUMNS)), rocksdb_path)
+		.map_err(db_err)?;
+	let destination = crate::parity_db::open(parity_db_path, NUM_COLUMNS).map_err(db_err)?;
+
+	let mut copied = 0;
+	for col in std::iter::once(None).chain((0..NUM_COLUMNS).map(Some)) {
+		let mut transaction = DBTransaction::new();
+		for (key, value) in source.iter(col) {
+			transaction.put(col, &key, &value);
+			if transaction.ops.len() == BATCH_SIZE {
+				copied += transaction.ops.len() as u64;
+				destination.write(std::mem::replace(&mut transaction, DBTransaction::new()))
+					.map_err(db_err)?;
+			}
+		}
+		copied += transaction.ops.len() as u64;
+		destination.write(transaction).map_err(db_err)?;
+		log::info!("Copied column {:?}, {} entries in total", col, copied);
+	}
+
+	Ok(copied)
+}
+
+/// Copy the content of the RocksDB database at `rocksdb_path` into a new ParityDb database at
+/// `parity_db_path`.
+///
+/// Returns the number of entries copied.
+#[cfg(not(all(feature = "kvdb-rocksdb", feature = "parity-db")))]
+pub fn migrate_to_parity_db(_rocksdb_path: &Path, _parity_db_path: &Path) -> sp_blockchain::Result<u64> {
+	Err(sp_blockchain::Error::Backend(
+		"Migrating to ParityDb requires both RocksDB and ParityDb to be enabled".into()
+	))
+}
+
 /// Read database column entry for the given block.
 pub fn read_db<Block>(
 	db: &dyn KeyValueDB,
@@ -360,6 +420,21 @@ mod tests {
 	use sp_runtime::testing::{Block as RawBlock, ExtrinsicWrapper};
 	type Block = RawBlock<ExtrinsicWrapper<u32>>;
 
+	#[test]
+	fn parity_db_is_rejected() {
+		let dir = tempfile::tempdir().unwrap();
+		let settings = DatabaseSettings {
+			state_cache_size: 16777216,
+			state_cache_child_ratio: Some((50, 100)),
+			pruning: sc_state_db::PruningMode::keep_blocks(1),
+			column_cache_sizes: Default::default(),
+			offchain_indexing: false,
+			source: DatabaseSettingsSrc::ParityDb { path: dir.path().into() },
+		};
+
+		assert!(open_database(&settings, crate::columns::META, "full").is_err());
+	}
+
 	#[test]
 	fn number_index_key_doesnt_panic() {
 		let id = BlockId::<Block>::Number(72340207214430721);
@@ -16,7 +16,7 @@
 
 //! Definitions for a wasm runtime.
 
-use crate::error::Error;
+use crate::error::{Error, WasmError};
 use sp_core::traits::Externalities;
 use sp_wasm_interface::Function;
 
@@ -37,3 +37,11 @@ pub trait WasmRuntime {
 	fn call(&mut self, ext: &mut dyn Externalities, method: &str, data: &[u8])
 		-> Result<Vec<u8>, Error>;
 }
+
+/// A prepared wasm module, from which runtime instances are created.
+///
+/// A module is shared by all the threads, while each instance is only used by one thread.
+pub trait WasmModule: Send + Sync {
+	/// Create a new instance of the module, ready to be called.
+	fn new_instance(&self) -> Result<Box<dyn WasmRuntime>, WasmError>;
+}
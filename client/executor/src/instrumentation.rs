@@ -0,0 +1,204 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Timing of the runtime calls and of the host functions they call.
+//!
+//! The timings are reported to Prometheus when a registry is given, and logged under the
+//! [`LOG_TARGET`] target at the debug level.
+
+use std::{cell::RefCell, collections::HashMap, mem, time::{Duration, Instant}};
+use log::debug;
+use prometheus_endpoint::{register, HistogramOpts, HistogramVec, PrometheusError, Registry};
+use sp_wasm_interface::{Function, FunctionContext, Result, Signature, Value};
+
+/// The log target of the runtime timings.
+pub const LOG_TARGET: &str = "runtime-timings";
+
+thread_local! {
+	/// Number of calls and time spent per host function, during the current runtime call.
+	static HOST_FUNCTION_TIMES: RefCell<HashMap<&'static str, (u32, Duration)>> =
+		RefCell::new(HashMap::new());
+}
+
+/// Prometheus metrics of the runtime calls.
+struct Metrics {
+	/// Time spent per runtime call, labeled by entry point and execution.
+	runtime_call_time: HistogramVec,
+	/// Time spent per host function during a runtime call, labeled by host function.
+	host_function_time: HistogramVec,
+}
+
+impl Metrics {
+	fn register(registry: &Registry) -> std::result::Result<Self, PrometheusError> {
+		Ok(Self {
+			runtime_call_time: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"executor_runtime_call_time",
+						"Time taken by a call into the runtime, in seconds."
+					),
+					&["method", "execution"],
+				)?,
+				registry,
+			)?,
+			host_function_time: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"executor_host_function_time",
+						"Time spent in a host function during a call into the runtime, in seconds."
+					),
+					&["function"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+}
+
+/// Records the timings of the runtime calls of an executor.
+pub(crate) struct Instrumentation {
+	metrics: Option<Metrics>,
+}
+
+impl Instrumentation {
+	/// Create the instrumentation, registering its metrics with `registry` if any.
+	pub fn new(registry: Option<&Registry>) -> Self {
+		let metrics = registry.and_then(|registry| Metrics::register(registry)
+			.map_err(|err| log::warn!("Failed to register the executor metrics: {:?}", err))
+			.ok()
+		);
+		Instrumentation { metrics }
+	}
+
+	/// Wrap the host functions to record the time spent in them.
+	///
+	/// The wrappers live as long as the process, so this should only be done once per executor.
+	pub fn instrument_host_functions(
+		host_functions: Vec<&'static dyn Function>,
+	) -> Vec<&'static dyn Function> {
+		host_functions.into_iter()
+			.map(|function| -> &'static dyn Function {
+				Box::leak(Box::new(TimedFunction { function }))
+			})
+			.collect()
+	}
+
+	/// Execute the runtime call `f` of `method`, recording its timings.
+	///
+	/// `execution` is `native` or `wasm`, and is decided by `f`.
+	pub fn time_call<R>(&self, method: &str, f: impl FnOnce() -> (R, &'static str)) -> R {
+		// Runtime calls can be nested, so the times of the outer call are put aside.
+		let outer_times = HOST_FUNCTION_TIMES.with(|times| {
+			mem::replace(&mut *times.borrow_mut(), HashMap::new())
+		});
+		let started = Instant::now();
+		let (result, execution) = f();
+		let elapsed = started.elapsed();
+		let times = HOST_FUNCTION_TIMES.with(|times| {
+			mem::replace(&mut *times.borrow_mut(), outer_times)
+		});
+
+		if let Some(metrics) = &self.metrics {
+			metrics.runtime_call_time
+				.with_label_values(&[method, execution])
+				.observe(elapsed.as_secs_f64());
+			for (function, (_, time)) in &times {
+				metrics.host_function_time
+					.with_label_values(&[function])
+					.observe(time.as_secs_f64());
+			}
+		}
+
+		if log::log_enabled!(target: LOG_TARGET, log::Level::Debug) {
+			let mut times = times.into_iter().collect::<Vec<_>>();
+			times.sort_by(|a, b| (b.1).1.cmp(&(a.1).1));
+			let host_functions = times.iter()
+				.map(|(function, (calls, time))| format!("{}: {} calls in {:?}", function, calls, time))
+				.collect::<Vec<_>>();
+			debug!(
+				target: LOG_TARGET,
+				"{} ({}) took {:?}, host functions: [{}]",
+				method,
+				execution,
+				elapsed,
+				host_functions.join(", "),
+			);
+		}
+
+		result
+	}
+}
+
+/// A host function recording the time spent in it.
+struct TimedFunction {
+	function: &'static dyn Function,
+}
+
+impl Function for TimedFunction {
+	fn name(&self) -> &str {
+		self.function.name()
+	}
+
+	fn signature(&self) -> Signature {
+		self.function.signature()
+	}
+
+	fn execute(
+		&self,
+		context: &mut dyn FunctionContext,
+		args: &mut dyn Iterator<Item = Value>,
+	) -> Result<Option<Value>> {
+		let started = Instant::now();
+		let result = self.function.execute(context, args);
+		let elapsed = started.elapsed();
+
+		HOST_FUNCTION_TIMES.with(|times| {
+			let mut times = times.borrow_mut();
+			let entry = times.entry(self.function.name()).or_default();
+			entry.0 += 1;
+			entry.1 += elapsed;
+		});
+		result
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn nested_calls_record_their_own_host_functions() {
+		let record = |name: &'static str| HOST_FUNCTION_TIMES.with(|times| {
+			times.borrow_mut().entry(name).or_default().0 += 1;
+		});
+		let instrumentation = Instrumentation::new(None);
+
+		let inner_calls = instrumentation.time_call("outer", || {
+			record("outer_function");
+			instrumentation.time_call("inner", || {
+				record("inner_function");
+				((), "wasm")
+			});
+			let calls = HOST_FUNCTION_TIMES.with(|times| {
+				times.borrow().keys().cloned().collect::<Vec<_>>()
+			});
+			(calls, "wasm")
+		});
+
+		assert_eq!(inner_calls, vec!["outer_function"]);
+		assert!(HOST_FUNCTION_TIMES.with(|times| times.borrow().is_empty()));
+	}
+}
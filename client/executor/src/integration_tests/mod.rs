@@ -498,3 +498,28 @@ fn offchain_http_should_work(wasm_method: WasmExecutionMethod) {
 		true.encode(),
 	);
 }
+
+#[test_case(WasmExecutionMethod::Interpreted)]
+#[cfg_attr(feature = "wasmtime", test_case(WasmExecutionMethod::Compiled))]
+fn infinite_recursion_should_trap(wasm_method: WasmExecutionMethod) {
+	let mut ext = TestExternalities::default();
+	let mut ext = ext.ext();
+	let test_code = wabt::wat2wasm(r#"
+		(module
+			(memory (export "memory") 1)
+			(global (export "__heap_base") i32 (i32.const 1024))
+			(func $recurse (export "recurse") (param i32 i32) (result i64)
+				(call $recurse (get_local 0) (get_local 1))
+			)
+		)
+	"#).unwrap();
+
+	assert!(call_in_wasm(
+		"recurse",
+		&[],
+		wasm_method,
+		&mut ext,
+		&test_code[..],
+		8,
+	).is_err());
+}
@@ -34,6 +34,7 @@ mod wasm_utils;
 #[macro_use]
 mod native_executor;
 pub mod deprecated_host_interface;
+mod instrumentation;
 mod wasm_runtime;
 #[cfg(test)]
 mod integration_tests;
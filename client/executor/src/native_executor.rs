@@ -16,18 +16,19 @@
 
 use crate::{
 	RuntimeInfo, error::{Error, Result},
+	instrumentation::{self, Instrumentation},
 	wasm_runtime::{RuntimesCache, WasmExecutionMethod},
 };
 use sp_version::{NativeVersion, RuntimeVersion};
 use codec::{Decode, Encode};
 use sp_core::{NativeOrEncoded, traits::{CodeExecutor, Externalities}};
 use log::trace;
-use std::{result, cell::RefCell, panic::{UnwindSafe, AssertUnwindSafe}};
+use std::{result, sync::Arc, panic::{UnwindSafe, AssertUnwindSafe}};
 use sp_wasm_interface::{HostFunctions, Function};
 use sc_executor_common::wasm_runtime::WasmRuntime;
 
-thread_local! {
-	static RUNTIMES_CACHE: RefCell<RuntimesCache> = RefCell::new(RuntimesCache::new());
+lazy_static::lazy_static! {
+	static ref RUNTIMES_CACHE: RuntimesCache = RuntimesCache::new();
 }
 
 /// Default num of pages for the heap
@@ -81,6 +82,8 @@ pub struct NativeExecutor<D> {
 	default_heap_pages: u64,
 	/// The host functions registered with this instance.
 	host_functions: Vec<&'static dyn Function>,
+	/// The timing of the runtime calls, if enabled.
+	instrumentation: Option<Arc<Instrumentation>>,
 }
 
 impl<D: NativeExecutionDispatch> NativeExecutor<D> {
@@ -102,26 +105,48 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 		// Add the custom host functions provided by the user.
 		host_functions.extend(D::ExtendHostFunctions::host_functions());
 
-		NativeExecutor {
+		let executor = NativeExecutor {
 			_dummy: Default::default(),
 			fallback_method,
 			native_version: D::native_version(),
 			default_heap_pages: default_heap_pages.unwrap_or(DEFAULT_HEAP_PAGES),
 			host_functions,
+			instrumentation: None,
+		};
+
+		executor.with_instrumentation(None)
+	}
+
+	/// Record the time spent per runtime call and per host function.
+	///
+	/// The timings are reported to the metrics registered with `registry` if any, and logged
+	/// under the `runtime-timings` target at the debug level, e.g. with
+	/// `--log runtime-timings=debug`. Nothing is recorded if there is no registry and the target
+	/// isn't logged.
+	pub fn with_instrumentation(mut self, registry: Option<&prometheus_endpoint::Registry>) -> Self {
+		let logged = log::log_enabled!(target: instrumentation::LOG_TARGET, log::Level::Debug);
+		if registry.is_none() && !logged {
+			return self;
+		}
+
+		if self.instrumentation.is_none() {
+			self.host_functions = Instrumentation::instrument_host_functions(self.host_functions);
 		}
+		self.instrumentation = Some(Arc::new(Instrumentation::new(registry)));
+		self
 	}
 
 	/// Execute the given closure `f` with the latest runtime (based on the `CODE` key in `ext`).
 	///
 	/// The closure `f` is expected to return `Err(_)` when there happened a `panic!` in native code
-	/// while executing the runtime in Wasm. If a `panic!` occurred, the runtime is invalidated to
-	/// prevent any poisoned state. Native runtime execution does not need to report back
+	/// while executing the runtime in Wasm. If a `panic!` occurred, the runtime instance is dropped
+	/// to prevent any poisoned state. Native runtime execution does not need to report back
 	/// any `panic!`.
 	///
 	/// # Safety
 	///
 	/// `runtime` and `ext` are given as `AssertUnwindSafe` to the closure. As described above, the
-	/// runtime is invalidated on any `panic!` to prevent a poisoned state. `ext` is already
+	/// runtime instance is dropped on any `panic!` to prevent a poisoned state. `ext` is already
 	/// implicitly handled as unwind safe, as we store it in a global variable while executing the
 	/// native runtime.
 	fn with_runtime<E, R>(
@@ -133,26 +158,13 @@ impl<D: NativeExecutionDispatch> NativeExecutor<D> {
 			AssertUnwindSafe<&'a mut E>,
 		) -> Result<Result<R>>,
 	) -> Result<R> where E: Externalities {
-		RUNTIMES_CACHE.with(|cache| {
-			let mut cache = cache.borrow_mut();
-			let (runtime, version, code_hash) = cache.fetch_runtime(
-				ext,
-				self.fallback_method,
-				self.default_heap_pages,
-				&self.host_functions,
-			)?;
-
-			let runtime = AssertUnwindSafe(runtime);
-			let ext = AssertUnwindSafe(ext);
-
-			match f(runtime, version, ext) {
-				Ok(res) => res,
-				Err(e) => {
-					cache.invalidate_runtime(self.fallback_method, code_hash);
-					Err(e)
-				}
-			}
-		})
+		RUNTIMES_CACHE.with_instance(
+			ext,
+			self.fallback_method,
+			self.default_heap_pages,
+			&self.host_functions,
+			f,
+		)
 	}
 }
 
@@ -164,6 +176,7 @@ impl<D: NativeExecutionDispatch> Clone for NativeExecutor<D> {
 			native_version: D::native_version(),
 			default_heap_pages: self.default_heap_pages,
 			host_functions: self.host_functions.clone(),
+			instrumentation: self.instrumentation.clone(),
 		}
 	}
 }
@@ -197,6 +210,33 @@ impl<D: NativeExecutionDispatch> CodeExecutor for NativeExecutor<D> {
 		use_native: bool,
 		native_call: Option<NC>,
 	) -> (Result<NativeOrEncoded<R>>, bool){
+		match &self.instrumentation {
+			Some(instrumentation) => instrumentation.time_call(method, || {
+				let (result, used_native) = self.call_runtime(ext, method, data, use_native, native_call);
+				let execution = if used_native { "native" } else { "wasm" };
+				((result, used_native), execution)
+			}),
+			None => self.call_runtime(ext, method, data, use_native, native_call),
+		}
+	}
+}
+
+impl<D: NativeExecutionDispatch> NativeExecutor<D> {
+	/// Call `method` of the runtime, natively if possible and requested.
+	///
+	/// Returns the result and whether the native runtime was used.
+	fn call_runtime<
+		E: Externalities,
+		R: Decode + Encode + PartialEq,
+		NC: FnOnce() -> result::Result<R, String> + UnwindSafe,
+	>(
+		&self,
+		ext: &mut E,
+		method: &str,
+		data: &[u8],
+		use_native: bool,
+		native_call: Option<NC>,
+	) -> (Result<NativeOrEncoded<R>>, bool) {
 		let mut used_native = false;
 		let result = self.with_runtime(ext, |mut runtime, onchain_version, mut ext| {
 			match (
@@ -24,8 +24,10 @@ use log::{trace, warn};
 use codec::Decode;
 use sp_core::{storage::well_known_keys, traits::Externalities};
 use sp_version::RuntimeVersion;
-use std::{collections::hash_map::{Entry, HashMap}, panic::AssertUnwindSafe};
-use sc_executor_common::wasm_runtime::WasmRuntime;
+use parking_lot::Mutex;
+use once_cell::sync::OnceCell;
+use std::{cell::RefCell, collections::HashMap, panic::AssertUnwindSafe, sync::Arc};
+use sc_executor_common::wasm_runtime::{WasmModule, WasmRuntime};
 
 use sp_wasm_interface::Function;
 
@@ -39,48 +41,72 @@ pub enum WasmExecutionMethod {
 	Compiled,
 }
 
-/// A Wasm runtime object along with its cached runtime version.
-struct VersionedRuntime {
-	runtime: Box<dyn WasmRuntime>,
+/// A prepared Wasm module along with its cached runtime version.
+struct VersionedModule {
+	module: Arc<dyn WasmModule>,
 	/// Runtime version according to `Core_version`.
 	version: RuntimeVersion,
+	/// The number of heap pages the module was prepared with.
+	heap_pages: u64,
+	/// The host functions the module was prepared with.
+	host_functions: Vec<&'static dyn Function>,
+}
+
+/// The cell of a cached module, holding the module once it is prepared.
+type ModuleCell = OnceCell<Result<Arc<VersionedModule>, WasmError>>;
+
+/// A runtime instance kept for reuse, along with the module it was created from.
+struct PooledInstance {
+	module: Arc<VersionedModule>,
+	runtime: Box<dyn WasmRuntime>,
+}
+
+thread_local! {
+	/// The instances ready to be reused by the calls made on this thread.
+	///
+	/// Instances are keyed by the Wasm execution method and the hash of their code.
+	static INSTANCES: RefCell<HashMap<(WasmExecutionMethod, Vec<u8>), PooledInstance>> =
+		RefCell::new(HashMap::new());
 }
 
 /// Cache for the runtimes.
 ///
-/// When an instance is requested for the first time it is added to this cache. Metadata is kept
-/// with the instance so that it can be efficiently reinitialized.
+/// When a runtime is requested for the first time, its code is loaded into a module which is
+/// added to this cache, along with the runtime version. The module is shared by all the threads,
+/// so that the code is only prepared once. The module is prepared without holding the lock of the
+/// cache, so that the calls to the other runtimes are not blocked meanwhile.
 ///
-/// When using the Wasmi interpreter execution method, the metadata includes the initial memory and
-/// values of mutable globals. Follow-up requests to fetch a runtime return this one instance with
-/// the memory reset to the initial memory. So, one runtime instance is reused for every fetch
-/// request.
+/// Each thread keeps the instances it created from the modules, and reuses them for the following
+/// calls. When using the Wasmi interpreter execution method, the memory of a reused instance is
+/// reset to its initial memory and the values of its mutable globals are restored.
 ///
 /// For now the cache grows indefinitely, but that should be fine for now since runtimes can only be
 /// upgraded rarely and there are no other ways to make the node to execute some other runtime.
 pub struct RuntimesCache {
-	/// A cache of runtime instances along with metadata, ready to be reused.
+	/// A cache of runtime modules along with their versions.
 	///
-	/// Instances are keyed by the Wasm execution method and the hash of their code.
-	instances: HashMap<(WasmExecutionMethod, Vec<u8>), Result<VersionedRuntime, WasmError>>,
+	/// Modules are keyed by the Wasm execution method and the hash of their code. The cell of a
+	/// key is initialized by the first thread requesting it, while the others wait for it.
+	modules: Mutex<HashMap<(WasmExecutionMethod, Vec<u8>), Arc<ModuleCell>>>,
 }
 
 impl RuntimesCache {
 	/// Creates a new instance of a runtimes cache.
 	pub fn new() -> RuntimesCache {
 		RuntimesCache {
-			instances: HashMap::new(),
+			modules: Mutex::new(HashMap::new()),
 		}
 	}
 
-	/// Fetches an instance of the runtime.
+	/// Executes `f` with an instance of the runtime.
 	///
-	/// On first use we create a new runtime instance, save it to the cache
-	/// and persist its initial memory.
+	/// The instance is taken from the instances of the current thread, or created from the cached
+	/// module of the runtime. On first use, the module is created, saved to the cache, and its
+	/// runtime version is determined.
 	///
-	/// Each subsequent request will return this instance, with its memory restored
-	/// to the persisted initial memory. Thus, we reuse one single runtime instance
-	/// for every `fetch_runtime` invocation.
+	/// `f` is expected to return `Err(_)` when there happened a `panic!` in native code while
+	/// executing the runtime. The instance is then dropped instead of being reused, to prevent any
+	/// poisoned state.
 	///
 	/// # Parameters
 	///
@@ -93,8 +119,7 @@ impl RuntimesCache {
 	///
 	/// # Return value
 	///
-	/// If no error occurred a tuple `(&mut WasmRuntime, H256)` is
-	/// returned. `H256` is the hash of the runtime code.
+	/// If no error occurred, the result of `f` is returned.
 	///
 	/// In case of failure one of two errors can be returned:
 	///
@@ -102,13 +127,18 @@ impl RuntimesCache {
 	///
 	/// `Error::InvalidMemoryReference` is returned if no memory export with the
 	/// identifier `memory` can be found in the runtime.
-	pub fn fetch_runtime<E: Externalities>(
-		&mut self,
+	pub fn with_instance<E, R>(
+		&self,
 		ext: &mut E,
 		wasm_method: WasmExecutionMethod,
 		default_heap_pages: u64,
 		host_functions: &[&'static dyn Function],
-	) -> Result<(&mut (dyn WasmRuntime + 'static), &RuntimeVersion, Vec<u8>), Error> {
+		f: impl for<'a> FnOnce(
+			AssertUnwindSafe<&'a mut (dyn WasmRuntime + 'static)>,
+			&'a RuntimeVersion,
+			AssertUnwindSafe<&'a mut E>,
+		) -> Result<Result<R, Error>, Error>,
+	) -> Result<R, Error> where E: Externalities {
 		let code_hash = ext
 			.original_storage_hash(well_known_keys::CODE)
 			.ok_or(Error::InvalidCode("`CODE` not found in storage.".into()))?;
@@ -118,70 +148,101 @@ impl RuntimesCache {
 			.and_then(|pages| u64::decode(&mut &pages[..]).ok())
 			.unwrap_or(default_heap_pages);
 
-		let result = match self.instances.entry((wasm_method, code_hash.clone())) {
-			Entry::Occupied(o) => {
-				let result = o.into_mut();
-				if let Ok(ref mut cached_runtime) = result {
-					let heap_pages_changed = !cached_runtime.runtime.update_heap_pages(heap_pages);
-					let host_functions_changed = cached_runtime.runtime.host_functions()
-						!= host_functions;
-					if heap_pages_changed || host_functions_changed {
-						let changed = if heap_pages_changed {
-							"heap_pages"
-						} else {
-							"host functions"
-						};
-
-						trace!(
-							target: "runtimes_cache",
-							"{} were changed. Reinstantiating the instance",
-							changed,
-						);
-						*result = create_versioned_wasm_runtime(
-							ext,
-							wasm_method,
-							heap_pages,
-							host_functions.into(),
-						);
-						if let Err(ref err) = result {
-							warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
-						}
-					}
-				}
-				result
+		let key = (wasm_method, code_hash);
+		let (module, new_runtime) = self.fetch_module(ext, &key, heap_pages, host_functions)?;
+
+		// The instance is taken out of the pool while it is used, so that nested calls create
+		// their own instance.
+		let pooled = INSTANCES.with(|instances| instances.borrow_mut().remove(&key))
+			.filter(|pooled| Arc::ptr_eq(&pooled.module, &module));
+		let mut runtime = match (new_runtime, pooled) {
+			(Some(runtime), _) => runtime,
+			(None, Some(pooled)) => pooled.runtime,
+			(None, None) => {
+				trace!(target: "runtimes_cache", "no instance found on this thread, creating now.");
+				module.module.new_instance()
+					.map_err(|e| Error::InvalidCode(format!("{:?}", e)))?
 			},
-			Entry::Vacant(v) => {
-				trace!(target: "runtimes_cache", "no instance found in cache, creating now.");
-				let result = create_versioned_wasm_runtime(
-					ext,
-					wasm_method,
-					heap_pages,
-					host_functions.into(),
-				);
-				if let Err(ref err) = result {
-					warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
-				}
-				v.insert(result)
-			}
 		};
 
-		result.as_mut()
-			.map(|entry| (entry.runtime.as_mut(), &entry.version, code_hash))
-			.map_err(|ref e| Error::InvalidCode(format!("{:?}", e)))
+		let result = f(AssertUnwindSafe(runtime.as_mut()), &module.version, AssertUnwindSafe(ext));
+		match result {
+			Ok(res) => {
+				INSTANCES.with(|instances| {
+					instances.borrow_mut().insert(key, PooledInstance { module, runtime })
+				});
+				res
+			},
+			Err(e) => {
+				trace!(target: "runtimes_cache", "dropping the instance after a panic.");
+				Err(e)
+			},
+		}
 	}
 
-	/// Invalidate the runtime for the given `wasm_method` and `code_hash`.
+	/// Fetches the module of the runtime, creating it if it isn't cached yet.
 	///
-	/// Invalidation of a runtime is useful when there was a `panic!` in native while executing it.
-	/// The `panic!` maybe have brought the runtime into a poisoned state and so, it is better to
-	/// invalidate this runtime instance.
-	pub fn invalidate_runtime(
-		&mut self,
-		wasm_method: WasmExecutionMethod,
-		code_hash: Vec<u8>,
-	) {
-		// Just remove the instance, it will be re-created the next time it is requested.
-		self.instances.remove(&(wasm_method, code_hash));
+	/// When the module is created, the instance used to determine its runtime version is returned
+	/// as well.
+	fn fetch_module<E: Externalities>(
+		&self,
+		ext: &mut E,
+		key: &(WasmExecutionMethod, Vec<u8>),
+		heap_pages: u64,
+		host_functions: &[&'static dyn Function],
+	) -> Result<(Arc<VersionedModule>, Option<Box<dyn WasmRuntime>>), Error> {
+		let cell = {
+			let mut modules = self.modules.lock();
+			let cell = modules.entry(key.clone()).or_default().clone();
+			match cell.get() {
+				Some(Ok(cached_module)) => {
+					let heap_pages_changed = cached_module.heap_pages != heap_pages;
+					let host_functions_changed = &cached_module.host_functions[..] != host_functions;
+					if !heap_pages_changed && !host_functions_changed {
+						return Ok((cached_module.clone(), None));
+					}
+
+					let changed = if heap_pages_changed {
+						"heap_pages"
+					} else {
+						"host functions"
+					};
+
+					trace!(
+						target: "runtimes_cache",
+						"{} were changed. Recreating the module",
+						changed,
+					);
+					let cell = Arc::new(ModuleCell::new());
+					modules.insert(key.clone(), cell.clone());
+					cell
+				},
+				Some(Err(err)) => return Err(Error::InvalidCode(format!("{:?}", err))),
+				None => cell,
+			}
+		};
+
+		// The module is created outside of the lock of the cache. Threads requesting the same
+		// module wait for the cell to be initialized.
+		let mut new_runtime = None;
+		let module = cell.get_or_init(|| {
+			trace!(target: "runtimes_cache", "no module found in cache, creating now.");
+			match create_versioned_wasm_module(ext, key.0, heap_pages, host_functions.into()) {
+				Ok((module, runtime)) => {
+					new_runtime = Some(runtime);
+					Ok(Arc::new(module))
+				},
+				Err(err) => {
+					warn!(target: "runtimes_cache", "cannot create a runtime: {:?}", err);
+					Err(err)
+				},
+			}
+		});
+
+		match module {
+			Ok(module) => Ok((module.clone(), new_runtime)),
+			Err(err) => Err(Error::InvalidCode(format!("{:?}", err))),
+		}
 	}
 }
 
@@ -203,16 +264,35 @@ pub fn create_wasm_runtime_with_code(
 	}
 }
 
-fn create_versioned_wasm_runtime<E: Externalities>(
+/// Create a wasm module with the given `code`, to create runtime instances of it.
+pub fn create_wasm_module_with_code(
+	wasm_method: WasmExecutionMethod,
+	heap_pages: u64,
+	code: &[u8],
+	host_functions: Vec<&'static dyn Function>,
+) -> Result<Arc<dyn WasmModule>, WasmError> {
+	match wasm_method {
+		WasmExecutionMethod::Interpreted =>
+			sc_executor_wasmi::create_module(code, heap_pages, host_functions)
+				.map(|module| -> Arc<dyn WasmModule> { Arc::new(module) }),
+		#[cfg(feature = "wasmtime")]
+		WasmExecutionMethod::Compiled =>
+			sc_executor_wasmtime::create_module(code, heap_pages, host_functions)
+				.map(|module| -> Arc<dyn WasmModule> { Arc::new(module) }),
+	}
+}
+
+fn create_versioned_wasm_module<E: Externalities>(
 	ext: &mut E,
 	wasm_method: WasmExecutionMethod,
 	heap_pages: u64,
 	host_functions: Vec<&'static dyn Function>,
-) -> Result<VersionedRuntime, WasmError> {
+) -> Result<(VersionedModule, Box<dyn WasmRuntime>), WasmError> {
 	let code = ext
 		.original_storage(well_known_keys::CODE)
 		.ok_or(WasmError::CodeNotFound)?;
-	let mut runtime = create_wasm_runtime_with_code(wasm_method, heap_pages, &code, host_functions)?;
+	let module = create_wasm_module_with_code(wasm_method, heap_pages, &code, host_functions.clone())?;
+	let mut runtime = module.new_instance()?;
 
 	// Call to determine runtime version.
 	let version_result = {
@@ -231,15 +311,53 @@ fn create_versioned_wasm_runtime<E: Externalities>(
 	let version = RuntimeVersion::decode(&mut encoded_version.as_slice())
 		.map_err(|_| WasmError::Instantiation("failed to decode \"Core_version\" result".into()))?;
 
-	Ok(VersionedRuntime {
-		runtime,
+	let module = VersionedModule {
+		module,
 		version,
-	})
+		heap_pages,
+		host_functions,
+	};
+	Ok((module, runtime))
 }
 
 #[cfg(test)]
 mod tests {
+	use super::*;
 	use sp_wasm_interface::HostFunctions;
+	use substrate_test_runtime::WASM_BINARY;
+
+	type TestExternalities = sp_state_machine::TestExternalities<sp_core::Blake2Hasher, u64>;
+
+	fn runtime_version(cache: &RuntimesCache) -> RuntimeVersion {
+		let mut host_functions = sp_io::SubstrateHostFunctions::host_functions();
+		host_functions.extend(
+			crate::deprecated_host_interface::SubstrateExternals::host_functions(),
+		);
+		let mut ext = TestExternalities::new_with_code(WASM_BINARY, Default::default());
+		cache.with_instance(
+			&mut ext.ext(),
+			WasmExecutionMethod::Interpreted,
+			1024,
+			&host_functions,
+			|_runtime, version, _ext| Ok(Ok(version.clone())),
+		).expect("The test runtime is valid")
+	}
+
+	#[test]
+	fn modules_are_shared_between_threads() {
+		let cache = Arc::new(RuntimesCache::new());
+		let version = runtime_version(&cache);
+
+		let thread_cache = cache.clone();
+		let thread_version = std::thread::spawn(move || runtime_version(&thread_cache))
+			.join()
+			.unwrap();
+
+		assert_eq!(version, thread_version);
+		assert_eq!(cache.modules.lock().len(), 1);
+		// The instance created for the first call is kept for reuse by this thread.
+		assert_eq!(INSTANCES.with(|instances| instances.borrow().len()), 1);
+	}
 
 	#[test]
 	fn host_functions_are_equal() {
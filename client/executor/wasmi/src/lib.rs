@@ -34,7 +34,7 @@ use sp_wasm_interface::{
 	FunctionContext, Pointer, WordSize, Sandbox, MemoryId, Result as WResult, Function,
 };
 use sp_runtime_interface::unpack_ptr_and_len;
-use sc_executor_common::wasm_runtime::WasmRuntime;
+use sc_executor_common::wasm_runtime::{WasmModule, WasmRuntime};
 
 struct FunctionExecutor<'a> {
 	sandbox_store: sandbox::Store<wasmi::FuncRef>,
@@ -565,11 +565,54 @@ impl WasmRuntime for WasmiRuntime {
 	}
 }
 
-pub fn create_instance(
+/// A validated wasm module along with its data segments, shared by the instances created from it.
+pub struct WasmiModule {
+	/// The validated wasm module.
+	module: Module,
+	/// The data segments of the module, restored on each call.
+	data_segments: Vec<DataSegment>,
+	/// The number of heap pages of the instances.
+	heap_pages: u64,
+	/// The host functions registered for the instances.
+	host_functions: Vec<&'static dyn Function>,
+}
+
+impl WasmiModule {
+	/// Instantiate the module and take a snapshot of the instance's state.
+	fn instantiate(&self) -> Result<WasmiRuntime, WasmError> {
+		let instance = instantiate_module(self.heap_pages as usize, &self.module, &self.host_functions)
+			.map_err(|e| WasmError::Instantiation(e.to_string()))?;
+
+		// Take state snapshot before executing anything.
+		let state_snapshot = StateSnapshot::take(&instance, self.data_segments.clone(), self.heap_pages)
+			.expect(
+				"`take` returns `Err` if the module is not valid;
+					we already loaded module in `create_module`, thus the `Module` is proven to be valid
+					at this point;
+					qed
+					",
+			);
+
+		Ok(WasmiRuntime {
+			instance,
+			state_snapshot,
+			host_functions: self.host_functions.clone(),
+		})
+	}
+}
+
+impl WasmModule for WasmiModule {
+	fn new_instance(&self) -> Result<Box<dyn WasmRuntime>, WasmError> {
+		self.instantiate().map(|runtime| -> Box<dyn WasmRuntime> { Box::new(runtime) })
+	}
+}
+
+/// Load and validate the given wasm `code`, to create instances of it later on.
+pub fn create_module(
 	code: &[u8],
 	heap_pages: u64,
 	host_functions: Vec<&'static dyn Function>,
-) -> Result<WasmiRuntime, WasmError> {
+) -> Result<WasmiModule, WasmError> {
 	let module = Module::from_buffer(&code).map_err(|_| WasmError::InvalidModule)?;
 
 	// Extract the data segments from the wasm code.
@@ -578,26 +621,22 @@ pub fn create_instance(
 	// we just loaded and validated the `module` above.
 	let data_segments = extract_data_segments(&code)?;
 
-	// Instantiate this module.
-	let instance = instantiate_module(heap_pages as usize, &module, &host_functions)
-		.map_err(|e| WasmError::Instantiation(e.to_string()))?;
-
-	// Take state snapshot before executing anything.
-	let state_snapshot = StateSnapshot::take(&instance, data_segments, heap_pages)
-		.expect(
-			"`take` returns `Err` if the module is not valid;
-				we already loaded module above, thus the `Module` is proven to be valid at this point;
-				qed
-				",
-		);
-
-	Ok(WasmiRuntime {
-		instance,
-		state_snapshot,
+	Ok(WasmiModule {
+		module,
+		data_segments,
+		heap_pages,
 		host_functions,
 	})
 }
 
+pub fn create_instance(
+	code: &[u8],
+	heap_pages: u64,
+	host_functions: Vec<&'static dyn Function>,
+) -> Result<WasmiRuntime, WasmError> {
+	create_module(code, heap_pages, host_functions)?.instantiate()
+}
+
 /// Extract the data segments from the given wasm code.
 ///
 /// Returns `Err` if the given wasm code cannot be deserialized.
@@ -22,7 +22,7 @@ use crate::util::{cranelift_ir_signature, read_memory_into, write_memory_from};
 
 use sc_executor_common::{
 	error::{Error, Result, WasmError},
-	wasm_runtime::WasmRuntime,
+	wasm_runtime::{WasmModule, WasmRuntime},
 };
 use sp_core::traits::Externalities;
 use sp_wasm_interface::{Pointer, WordSize, Function};
@@ -32,6 +32,7 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 use std::convert::TryFrom;
 use std::rc::Rc;
+use std::sync::{Arc, Mutex};
 
 use cranelift_codegen::ir;
 use cranelift_codegen::isa::TargetIsa;
@@ -44,20 +45,49 @@ use wasmtime_jit::{
 };
 use wasmtime_runtime::{Export, Imports, InstanceHandle, VMFunctionBody};
 
-/// A `WasmRuntime` implementation using the Wasmtime JIT to compile the runtime module to native
-/// and execute the compiled code.
-pub struct WasmtimeRuntime {
+/// The maximum height of the stack of the runtime, as computed by `pwasm_utils::stack_height`.
+///
+/// The compiled code uses the native stack, whose size depends on the machine. Exceeding this
+/// limit traps instead, so that all the nodes fail the same calls.
+const MAX_STACK_HEIGHT: u32 = 64 * 1024;
+
+/// A runtime module compiled by the Wasmtime JIT, along with the context owning its code.
+struct CompiledUnit {
 	module: CompiledModule,
 	context: Context,
 	max_heap_pages: Option<u32>,
+}
+
+// The compiled module and its context reference each other through `Rc`s, and instances only
+// live for the duration of a call. The unit is thus only ever moved as a whole between threads,
+// without any of its references being left behind.
+unsafe impl Send for CompiledUnit {}
+
+/// The compiled units of a module which are not used by any instance.
+type CompiledUnits = Arc<Mutex<Vec<CompiledUnit>>>;
+
+/// A `WasmRuntime` implementation using the Wasmtime JIT to compile the runtime module to native
+/// and execute the compiled code.
+pub struct WasmtimeRuntime {
+	/// Only taken when the runtime is dropped.
+	unit: Option<CompiledUnit>,
+	/// The units of the module this instance was created from, if any, where the compiled unit
+	/// is given back when the instance is dropped.
+	units: Option<CompiledUnits>,
 	heap_pages: u32,
 	/// The host functions registered for this instance.
 	host_functions: Vec<&'static dyn Function>,
 }
 
+impl WasmtimeRuntime {
+	fn unit(&mut self) -> &mut CompiledUnit {
+		self.unit.as_mut().expect("the unit is only taken when the runtime is dropped; qed")
+	}
+}
+
 impl WasmRuntime for WasmtimeRuntime {
 	fn update_heap_pages(&mut self, heap_pages: u64) -> bool {
-		match heap_pages_valid(heap_pages, self.max_heap_pages) {
+		match heap_pages_valid(heap_pages, self.unit().max_heap_pages) {
 			Some(heap_pages) => {
 				self.heap_pages = heap_pages;
 				true
@@ -71,17 +101,80 @@ impl WasmRuntime for WasmtimeRuntime {
 	}
 
 	fn call(&mut self, ext: &mut dyn Externalities, method: &str, data: &[u8]) -> Result<Vec<u8>> {
+		let heap_pages = self.heap_pages;
+		let unit = self.unit();
 		call_method(
-			&mut self.context,
-			&mut self.module,
+			&mut unit.context,
+			&mut unit.module,
 			ext,
 			method,
 			data,
-			self.heap_pages,
+			heap_pages,
 		)
 	}
 }
 
+impl Drop for WasmtimeRuntime {
+	fn drop(&mut self) {
+		// Every call instantiates the compiled module anew, so the unit can be reused as is.
+		if let (Some(unit), Some(units)) = (self.unit.take(), self.units.as_ref()) {
+			if let Ok(mut units) = units.lock() {
+				units.push(unit);
+			}
+		}
+	}
+}
+
+/// A runtime module compiled by the Wasmtime JIT, to create instances of it.
+///
+/// The compiled code of Wasmtime is owned by the context it was compiled in, which cannot be used
+/// by several instances at once. The module thus keeps the compiled units which are not in use,
+/// and only compiles the code again when they are all used by some instances.
+pub struct WasmtimeModule {
+	/// The code of the module, instrumented to limit the height of its stack.
+	code: Vec<u8>,
+	heap_pages: u64,
+	/// The host functions registered for the instances.
+	host_functions: Vec<&'static dyn Function>,
+	units: CompiledUnits,
+}
+
+impl WasmModule for WasmtimeModule {
+	fn new_instance(&self) -> std::result::Result<Box<dyn WasmRuntime>, WasmError> {
+		let unit = self.units.lock()
+			.map_err(|_| WasmError::Other("the compiled units are poisoned".into()))?
+			.pop();
+		let unit = match unit {
+			Some(unit) => unit,
+			None => compile(&self.code, &self.host_functions)?,
+		};
+		let runtime = runtime_from_unit(
+			unit,
+			Some(self.units.clone()),
+			self.heap_pages,
+			self.host_functions.clone(),
+		)?;
+		Ok(Box::new(runtime))
+	}
+}
+
+/// Create a new `WasmtimeModule` given the code, to create instances of it later on. This
+/// function performs translation from Wasm to machine code, which can be computationally heavy.
+pub fn create_module(
+	code: &[u8],
+	heap_pages: u64,
+	host_functions: Vec<&'static dyn Function>,
+) -> std::result::Result<WasmtimeModule, WasmError> {
+	let code = inject_stack_limiter(code)?;
+	let unit = compile(&code, &host_functions)?;
+	Ok(WasmtimeModule {
+		code,
+		heap_pages,
+		host_functions,
+		units: Arc::new(Mutex::new(vec![unit])),
+	})
+}
+
 /// Create a new `WasmtimeRuntime` given the code. This function performs translation from Wasm to
 /// machine code, which can be computationally heavy.
 pub fn create_instance(
@@ -89,11 +182,39 @@ pub fn create_instance(
 	heap_pages: u64,
 	host_functions: Vec<&'static dyn Function>,
 ) -> std::result::Result<WasmtimeRuntime, WasmError> {
-	let (compiled_module, context) = create_compiled_unit(code, &host_functions)?;
+	let code = inject_stack_limiter(code)?;
+	let unit = compile(&code, &host_functions)?;
+	runtime_from_unit(unit, None, heap_pages, host_functions)
+}
+
+fn runtime_from_unit(
+	unit: CompiledUnit,
+	units: Option<CompiledUnits>,
+	heap_pages: u64,
+	host_functions: Vec<&'static dyn Function>,
+) -> std::result::Result<WasmtimeRuntime, WasmError> {
+	// Check that heap_pages is within the allowed range.
+	let heap_pages = heap_pages_valid(heap_pages, unit.max_heap_pages)
+		.ok_or_else(|| WasmError::InvalidHeapPages)?;
+
+	Ok(WasmtimeRuntime {
+		unit: Some(unit),
+		units,
+		heap_pages,
+		host_functions,
+	})
+}
+
+/// Compile the instrumented wasm `code`.
+fn compile(
+	code: &[u8],
+	host_functions: &[&'static dyn Function],
+) -> std::result::Result<CompiledUnit, WasmError> {
+	let (module, context) = create_compiled_unit(code, host_functions)?;
 
 	// Inspect the module for the min and max memory sizes.
 	let (min_memory_size, max_memory_size) = {
-		let module = compiled_module.module_ref();
+		let module = module.module_ref();
 		let memory_index = match module.exports.get("memory") {
 			Some(wasmtime_environ::Export::Memory(memory_index)) => *memory_index,
 			_ => return Err(WasmError::InvalidMemory),
@@ -102,19 +223,9 @@ pub fn create_instance(
 			.expect("memory_index is retrieved from the module's exports map; qed");
 		(memory_plan.memory.minimum, memory_plan.memory.maximum)
 	};
-
-	// Check that heap_pages is within the allowed range.
 	let max_heap_pages = max_memory_size.map(|max| max.saturating_sub(min_memory_size));
-	let heap_pages = heap_pages_valid(heap_pages, max_heap_pages)
-		.ok_or_else(|| WasmError::InvalidHeapPages)?;
 
-	Ok(WasmtimeRuntime {
-		module: compiled_module,
-		context,
-		max_heap_pages,
-		heap_pages,
-		host_functions,
-	})
+	Ok(CompiledUnit { module, context, max_heap_pages })
 }
 
 fn create_compiled_unit(
@@ -136,12 +247,22 @@ fn create_compiled_unit(
 	context.name_instance("env".to_owned(), env_module);
 
 	// Compile the wasm module.
-	let module = context.compile_module(&code)
+	let module = context.compile_module(code)
 		.map_err(|e| WasmError::Other(format!("module compile error: {}", e)))?;
 
 	Ok((module, context))
 }
 
+/// Instrument the wasm `code` to trap when the height of its stack exceeds `MAX_STACK_HEIGHT`.
+fn inject_stack_limiter(code: &[u8]) -> std::result::Result<Vec<u8>, WasmError> {
+	let module = parity_wasm::deserialize_buffer(code)
+		.map_err(|_| WasmError::CantDeserializeWasm)?;
+	let module = pwasm_utils::stack_height::inject_limiter(module, MAX_STACK_HEIGHT)
+		.map_err(|_| WasmError::InvalidModule)?;
+	parity_wasm::serialize(module)
+		.map_err(|e| WasmError::Other(format!("cannot serialize the instrumented module: {}", e)))
+}
+
 /// Call a function inside a precompiled Wasm module.
 fn call_method(
 	context: &mut Context,
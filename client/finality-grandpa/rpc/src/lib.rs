@@ -0,0 +1,243 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! RPC interface for the GRANDPA finality gadget.
+
+#![warn(missing_docs)]
+
+use std::sync::Arc;
+use finality_grandpa::BlockNumberOps;
+use jsonrpc_core::{Error as RpcError, ErrorCode, Result};
+use jsonrpc_derive::rpc;
+use serde::Serialize;
+use sc_client_api::backend::Backend;
+use sc_rpc_api::DenyUnsafe;
+use sc_finality_grandpa::{AuthorityId, FinalityProofProvider, SharedVoterState};
+use sp_core::{Blake2Hasher, Bytes, H256};
+use sp_runtime::traits::{Block as BlockT, NumberFor};
+
+pub use self::gen_client::Client as GrandpaClient;
+
+/// Substrate GRANDPA RPC API
+#[rpc]
+pub trait GrandpaApi<Hash> {
+	/// Returns the state of the rounds the voter is running: the weight of the prevotes and
+	/// precommits received so far and the authorities that haven't voted yet, along with the
+	/// current authority set.
+	#[rpc(name = "grandpa_roundState")]
+	fn round_state(&self) -> Result<ReportedRoundStates>;
+
+	/// Prove the finality of the best possible block in the range (begin; end], for a client
+	/// that knows the finality of `begin` and the authority set with id `authorities_set_id`.
+	///
+	/// Returns the SCALE-encoded finality proof, to be checked with a `FinalityProofVerifier`,
+	/// or `None` if no block of the range can be proven final yet.
+	#[rpc(name = "grandpa_proveFinality")]
+	fn prove_finality(&self, begin: Hash, end: Hash, authorities_set_id: u64) -> Result<Option<Bytes>>;
+
+	/// Make the voter stop casting votes, without stopping the node: it keeps following the
+	/// rounds and finalizing the blocks committed by the other voters.
+	///
+	/// This method is unsafe and must only be exposed to the operator of the node.
+	#[rpc(name = "grandpa_pause")]
+	fn pause(&self) -> Result<()>;
+
+	/// Make the voter cast votes again after `grandpa_pause`.
+	///
+	/// This method is unsafe and must only be exposed to the operator of the node.
+	#[rpc(name = "grandpa_resume")]
+	fn resume(&self) -> Result<()>;
+}
+
+/// Votes of one kind received in a round.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Votes {
+	/// Weight of the authorities that have voted.
+	pub current_weight: u64,
+	/// Authorities that haven't voted.
+	pub missing: Vec<AuthorityId>,
+}
+
+/// State of a round.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RoundState {
+	/// Number of the round.
+	pub round: u64,
+	/// Total weight of the authority set.
+	pub total_weight: u64,
+	/// Weight a vote must gather to be supermajority.
+	pub threshold_weight: u64,
+	/// Prevotes received so far.
+	pub prevotes: Votes,
+	/// Precommits received so far.
+	pub precommits: Votes,
+}
+
+impl From<sc_finality_grandpa::RoundState> for RoundState {
+	fn from(state: sc_finality_grandpa::RoundState) -> Self {
+		RoundState {
+			round: state.round,
+			total_weight: state.total_weight,
+			threshold_weight: state.threshold_weight,
+			prevotes: Votes {
+				current_weight: state.prevote_current_weight,
+				missing: state.prevote_missing,
+			},
+			precommits: Votes {
+				current_weight: state.precommit_current_weight,
+				missing: state.precommit_missing,
+			},
+		}
+	}
+}
+
+/// State of the rounds the voter is running.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportedRoundStates {
+	/// Id of the current authority set.
+	pub set_id: u64,
+	/// Authorities of the current set along with their weight.
+	pub authorities: Vec<(AuthorityId, u64)>,
+	/// The most recent round.
+	pub best: RoundState,
+	/// The older rounds still running in the background, most recent first.
+	pub background: Vec<RoundState>,
+}
+
+impl From<sc_finality_grandpa::VoterState> for ReportedRoundStates {
+	fn from(state: sc_finality_grandpa::VoterState) -> Self {
+		ReportedRoundStates {
+			set_id: state.set_id,
+			authorities: state.authorities,
+			best: state.best_round.into(),
+			background: state.background_rounds.into_iter().map(Into::into).collect(),
+		}
+	}
+}
+
+/// Error type of this RPC api.
+pub enum Error {
+	/// Building the proof failed.
+	ProveFinalityFailed,
+	/// The voter hasn't started any round.
+	VoterNotRunning,
+}
+
+impl From<Error> for i64 {
+	fn from(e: Error) -> i64 {
+		match e {
+			Error::ProveFinalityFailed => 1,
+			Error::VoterNotRunning => 2,
+		}
+	}
+}
+
+/// A struct that implements the [`GrandpaApi`].
+pub struct GrandpaRpcHandler<B, Block: BlockT<Hash=H256>> {
+	shared_voter_state: SharedVoterState,
+	finality_proof_provider: Arc<FinalityProofProvider<B, Block>>,
+	deny_unsafe: DenyUnsafe,
+}
+
+impl<B, Block: BlockT<Hash=H256>> GrandpaRpcHandler<B, Block> {
+	/// Create new `GrandpaRpcHandler` reporting the state of the voter sharing the given
+	/// `SharedVoterState`, and serving the proofs of the given provider.
+	pub fn new(
+		shared_voter_state: SharedVoterState,
+		finality_proof_provider: Arc<FinalityProofProvider<B, Block>>,
+		deny_unsafe: DenyUnsafe,
+	) -> Self {
+		GrandpaRpcHandler { shared_voter_state, finality_proof_provider, deny_unsafe }
+	}
+}
+
+impl<B, Block> GrandpaApi<Block::Hash> for GrandpaRpcHandler<B, Block>
+	where
+		Block: BlockT<Hash=H256>,
+		NumberFor<Block>: BlockNumberOps,
+		B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+{
+	fn round_state(&self) -> Result<ReportedRoundStates> {
+		self.shared_voter_state.voter_state()
+			.map(Into::into)
+			.ok_or_else(|| RpcError {
+				code: ErrorCode::ServerError(Error::VoterNotRunning.into()),
+				message: "GRANDPA voter is not running.".into(),
+				data: None,
+			})
+	}
+
+	fn prove_finality(
+		&self,
+		begin: Block::Hash,
+		end: Block::Hash,
+		authorities_set_id: u64,
+	) -> Result<Option<Bytes>> {
+		// The RPC clients are recent enough to check compact proofs.
+		self.finality_proof_provider.prove_finality_range(begin, end, authorities_set_id, true)
+			.map(|proof| proof.map(Into::into))
+			.map_err(|e| RpcError {
+				code: ErrorCode::ServerError(Error::ProveFinalityFailed.into()),
+				message: "Unable to prove finality.".into(),
+				data: Some(format!("{:?}", e).into()),
+			})
+	}
+
+	fn pause(&self) -> Result<()> {
+		self.deny_unsafe.check_if_safe()?;
+		self.shared_voter_state.pause_voting();
+		Ok(())
+	}
+
+	fn resume(&self) -> Result<()> {
+		self.deny_unsafe.check_if_safe()?;
+		self.shared_voter_state.resume_voting();
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::crypto::Public;
+
+	#[test]
+	fn round_state_serialization() {
+		let voter = AuthorityId::from_slice(&[1; 32]);
+		let state = ReportedRoundStates {
+			set_id: 1,
+			authorities: vec![(voter.clone(), 1)],
+			best: RoundState {
+				round: 2,
+				total_weight: 1,
+				threshold_weight: 1,
+				prevotes: Votes { current_weight: 1, missing: Vec::new() },
+				precommits: Votes { current_weight: 0, missing: vec![voter.clone()] },
+			},
+			background: Vec::new(),
+		};
+
+		let json = serde_json::to_value(&state).unwrap();
+		assert_eq!(json["setId"], 1);
+		assert_eq!(json["best"]["thresholdWeight"], 1);
+		assert_eq!(json["best"]["prevotes"]["currentWeight"], 1);
+		assert_eq!(json["best"]["precommits"]["missing"][0], serde_json::to_value(&voter).unwrap());
+		assert_eq!(json["background"], serde_json::json!([]));
+	}
+}
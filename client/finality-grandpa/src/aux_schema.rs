@@ -39,6 +39,7 @@ const SET_STATE_KEY: &[u8] = b"grandpa_completed_round";
 const CONCLUDED_ROUNDS: &[u8] = b"grandpa_concluded_rounds";
 const AUTHORITY_SET_KEY: &[u8] = b"grandpa_voters";
 const CONSENSUS_CHANGES_KEY: &[u8] = b"grandpa_consensus_changes";
+const WARP_SYNC_TARGET_KEY: &[u8] = b"grandpa_warp_sync_target";
 
 const CURRENT_VERSION: u32 = 2;
 
@@ -430,6 +431,31 @@ pub(crate) fn update_consensus_changes<H, N, F, R>(
 	write_aux(&[(CONSENSUS_CHANGES_KEY, set.encode().as_slice())])
 }
 
+/// Write the authority set that a warp sync proof has proven to be live at the given block, to
+/// be applied once the block is imported along with its state.
+pub(crate) fn write_warp_sync_target<H: Encode, B: AuxStore>(
+	backend: &B,
+	hash: H,
+	set_id: SetId,
+	authorities: &AuthorityList,
+) -> ClientResult<()> {
+	backend.insert_aux(&[(WARP_SYNC_TARGET_KEY, (hash, set_id, authorities).encode().as_slice())], &[])
+}
+
+/// Load the authority set written by `write_warp_sync_target`, if any.
+pub(crate) fn load_warp_sync_target<H: Decode, B: AuxStore>(backend: &B)
+	-> ClientResult<Option<(H, SetId, AuthorityList)>>
+{
+	load_decode(backend, WARP_SYNC_TARGET_KEY)
+}
+
+/// Remove the authority set written by `write_warp_sync_target`.
+pub(crate) fn clear_warp_sync_target<F, R>(write_aux: F) -> R where
+	F: FnOnce(&[&'static [u8]]) -> R,
+{
+	write_aux(&[WARP_SYNC_TARGET_KEY])
+}
+
 #[cfg(test)]
 pub(crate) fn load_authorities<B: AuxStore, H: Decode, N: Decode>(backend: &B)
 	-> Option<AuthoritySet<H, N>> {
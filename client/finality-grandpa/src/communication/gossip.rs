@@ -86,7 +86,7 @@ use sp_runtime::traits::{NumberFor, Block as BlockT, Zero};
 use sc_network_gossip::{GossipEngine, MessageIntent, ValidatorContext};
 use sc_network::{config::Roles, PeerId, ReputationChange};
 use parity_scale_codec::{Encode, Decode};
-use sp_finality_grandpa::AuthorityId;
+use sp_finality_grandpa::{AuthorityId, EquivocationProof, RoundNumber, VoteEquivocation};
 
 use sc_telemetry::{telemetry, CONSENSUS_DEBUG};
 use log::{trace, debug, warn};
@@ -614,6 +614,25 @@ impl CatchUpConfig {
 	}
 }
 
+/// Sends the detected equivocations to the task reporting them.
+pub(crate) type EquivocationReporter<Block> = mpsc::UnboundedSender<
+	EquivocationProof<<Block as BlockT>::Hash, NumberFor<Block>>
+>;
+
+/// The kinds of votes whose equivocations are detected.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum VoteKind {
+	Prevote,
+	Precommit,
+}
+
+/// The first vote of a kind received from a voter in a round, and whether an equivocation of
+/// the voter has already been detected.
+struct SeenVote<Block: BlockT> {
+	vote: SignedMessage<Block>,
+	equivocated: bool,
+}
+
 struct Inner<Block: BlockT> {
 	local_view: Option<View<NumberFor<Block>>>,
 	peers: Peers<NumberFor<Block>>,
@@ -624,6 +643,7 @@ struct Inner<Block: BlockT> {
 	next_rebroadcast: Instant,
 	pending_catch_up: PendingCatchUp,
 	catch_up_config: CatchUpConfig,
+	seen_votes: HashMap<(RoundNumber, AuthorityId, VoteKind), SeenVote<Block>>,
 }
 
 type MaybeMessage<Block> = Option<(Vec<PeerId>, NeighborPacket<NumberFor<Block>>)>;
@@ -657,6 +677,7 @@ impl<Block: BlockT> Inner<Block> {
 			pending_catch_up: PendingCatchUp::None,
 			catch_up_config,
 			config,
+			seen_votes: HashMap::new(),
 		}
 	}
 
@@ -679,6 +700,8 @@ impl<Block: BlockT> Inner<Block> {
 
 			local_view.round = round;
 
+			// only the votes of the current and previous rounds are accepted.
+			self.seen_votes.retain(|&(seen_round, _, _), _| seen_round + 1 >= round.0);
 			self.live_topics.push(round, set_id);
 			self.round_start = Instant::now();
 			self.peers.reshuffle();
@@ -704,6 +727,7 @@ impl<Block: BlockT> Inner<Block> {
 			};
 
 			local_view.update_set(set_id);
+			self.seen_votes.clear();
 			self.live_topics.push(Round(1), set_id);
 			self.authorities = authorities;
 		}
@@ -774,6 +798,59 @@ impl<Block: BlockT> Inner<Block> {
 		Action::Keep(topic, benefit::ROUND_MESSAGE)
 	}
 
+	/// Note a valid vote of the current set. Returns a proof of equivocation if the voter has
+	/// already cast a different vote of the same kind in that round. Only the first
+	/// equivocation of a voter in a round is returned.
+	fn note_vote(&mut self, full: &VoteMessage<Block>)
+		-> Option<EquivocationProof<Block::Hash, NumberFor<Block>>>
+	{
+		let kind = match full.message.message {
+			finality_grandpa::Message::Prevote(_) => VoteKind::Prevote,
+			finality_grandpa::Message::Precommit(_) => VoteKind::Precommit,
+			finality_grandpa::Message::PrimaryPropose(_) => return None,
+		};
+
+		let seen = self.seen_votes
+			.entry((full.round.0, full.message.id.clone(), kind))
+			.or_insert_with(|| SeenVote { vote: full.message.clone(), equivocated: false });
+		if seen.equivocated || seen.vote.message == full.message.message {
+			return None;
+		}
+		seen.equivocated = true;
+
+		let (first, second) = (&seen.vote, &full.message);
+		let equivocation = match (&first.message, &second.message) {
+			(finality_grandpa::Message::Prevote(first_vote), finality_grandpa::Message::Prevote(second_vote)) => {
+				let prevote = |vote: &crate::Prevote<Block>| sp_finality_grandpa::Prevote {
+					target_hash: vote.target_hash.clone(),
+					target_number: vote.target_number.clone(),
+				};
+				sp_finality_grandpa::Equivocation::Prevote(VoteEquivocation {
+					round_number: full.round.0,
+					identity: first.id.clone(),
+					first: (prevote(first_vote), first.signature.clone()),
+					second: (prevote(second_vote), second.signature.clone()),
+				})
+			},
+			(finality_grandpa::Message::Precommit(first_vote), finality_grandpa::Message::Precommit(second_vote)) => {
+				let precommit = |vote: &crate::Precommit<Block>| sp_finality_grandpa::Precommit {
+					target_hash: vote.target_hash.clone(),
+					target_number: vote.target_number.clone(),
+				};
+				sp_finality_grandpa::Equivocation::Precommit(VoteEquivocation {
+					round_number: full.round.0,
+					identity: first.id.clone(),
+					first: (precommit(first_vote), first.signature.clone()),
+					second: (precommit(second_vote), second.signature.clone()),
+				})
+			},
+			_ => return None,
+		};
+
+		debug!(target: "afg", "Detected equivocation of {} in round {:?}", first.id, full.round);
+		Some(EquivocationProof::new(full.set_id.0, equivocation))
+	}
+
 	fn validate_commit_message(&mut self, who: &PeerId, full: &FullCommitMessage<Block>)
 		-> Action<Block::Hash>
 	{
@@ -1169,6 +1246,7 @@ pub(super) struct GossipValidator<Block: BlockT> {
 	inner: parking_lot::RwLock<Inner<Block>>,
 	set_state: environment::SharedVoterSetState<Block>,
 	report_sender: mpsc::UnboundedSender<PeerReport>,
+	equivocation_reporter: parking_lot::RwLock<Option<EquivocationReporter<Block>>>,
 }
 
 impl<Block: BlockT> GossipValidator<Block> {
@@ -1184,6 +1262,7 @@ impl<Block: BlockT> GossipValidator<Block> {
 			inner: parking_lot::RwLock::new(Inner::new(config)),
 			set_state,
 			report_sender: tx,
+			equivocation_reporter: parking_lot::RwLock::new(None),
 		};
 
 		(val, ReportStream { reports: rx })
@@ -1225,6 +1304,21 @@ impl<Block: BlockT> GossipValidator<Block> {
 		self.inner.write().note_catch_up_message_processed();
 	}
 
+	/// Set the channel the detected equivocations are sent to.
+	pub(super) fn set_equivocation_reporter(&self, reporter: EquivocationReporter<Block>) {
+		*self.equivocation_reporter.write() = Some(reporter);
+	}
+
+	/// Send an equivocation to the reporting task, if any.
+	pub(super) fn report_equivocation(
+		&self,
+		equivocation_proof: EquivocationProof<Block::Hash, NumberFor<Block>>,
+	) {
+		if let Some(reporter) = self.equivocation_reporter.read().as_ref() {
+			let _ = reporter.unbounded_send(equivocation_proof);
+		}
+	}
+
 	fn report(&self, who: PeerId, cost_benefit: ReputationChange) {
 		let _ = self.report_sender.unbounded_send(PeerReport { who, cost_benefit });
 	}
@@ -1234,11 +1328,18 @@ impl<Block: BlockT> GossipValidator<Block> {
 	{
 		let mut broadcast_topics = Vec::new();
 		let mut peer_reply = None;
+		let mut equivocation = None;
 
 		let action = {
 			match GossipMessage::<Block>::decode(&mut data) {
-				Ok(GossipMessage::Vote(ref message))
-					=> self.inner.write().validate_round_message(who, message),
+				Ok(GossipMessage::Vote(ref message)) => {
+					let mut inner = self.inner.write();
+					let action = inner.validate_round_message(who, message);
+					if let Action::Keep(..) = action {
+						equivocation = inner.note_vote(message);
+					}
+					action
+				}
 				Ok(GossipMessage::Commit(ref message)) => self.inner.write().validate_commit_message(who, message),
 				Ok(GossipMessage::Neighbor(update)) => {
 					let (topics, action, catch_up, report) = self.inner.write().import_neighbor_message(
@@ -1276,6 +1377,10 @@ impl<Block: BlockT> GossipValidator<Block> {
 			}
 		};
 
+		if let Some(equivocation_proof) = equivocation {
+			self.report_equivocation(equivocation_proof);
+		}
+
 		(action, broadcast_topics, peer_reply)
 	}
 }
@@ -1752,6 +1857,54 @@ mod tests {
 		assert_eq!(bad_sig, Action::Discard(cost::BAD_SIGNATURE));
 	}
 
+	#[test]
+	fn detects_equivocations_once() {
+		let (val, _) = GossipValidator::<Block>::new(
+			config(),
+			voter_set_state(),
+		);
+		let auth = AuthorityId::from_slice(&[1u8; 32]);
+		val.note_set(SetId(1), vec![auth.clone()], |_, _| {});
+		val.note_round(Round(1), |_, _| {});
+
+		let vote = |message| VoteMessage {
+			round: Round(1),
+			set_id: SetId(1),
+			message: SignedMessage::<Block> {
+				message,
+				signature: Default::default(),
+				id: auth.clone(),
+			}
+		};
+		let prevote = |target_number| vote(finality_grandpa::Message::Prevote(
+			finality_grandpa::Prevote { target_hash: Default::default(), target_number }
+		));
+		let precommit = |target_number| vote(finality_grandpa::Message::Precommit(
+			finality_grandpa::Precommit { target_hash: Default::default(), target_number }
+		));
+
+		let mut inner = val.inner.write();
+		assert!(inner.note_vote(&prevote(10)).is_none());
+		assert!(inner.note_vote(&prevote(10)).is_none());
+		assert!(inner.note_vote(&precommit(11)).is_none());
+
+		let proof = inner.note_vote(&prevote(11)).unwrap();
+		assert_eq!(proof.set_id, 1);
+		assert_eq!(proof.round_number(), 1);
+		assert_eq!(proof.offender(), &auth);
+		match proof.equivocation {
+			sp_finality_grandpa::Equivocation::Prevote(equivocation) => {
+				assert_eq!(equivocation.first.0.target_number, 10);
+				assert_eq!(equivocation.second.0.target_number, 11);
+			},
+			_ => panic!("expected a prevote equivocation"),
+		}
+
+		// the equivocation is only reported once.
+		assert!(inner.note_vote(&prevote(12)).is_none());
+		assert!(inner.note_vote(&precommit(12)).is_some());
+	}
+
 	#[test]
 	fn unsolicited_catch_up_messages_discarded() {
 		let (val, _) = GossipValidator::<Block>::new(
@@ -27,17 +27,20 @@
 //! In the future, there will be a fallback for allowing sending the same message
 //! under certain conditions that are used to un-stick the protocol.
 
-use std::sync::Arc;
+use std::{convert::TryFrom, sync::Arc};
 
 use futures::{prelude::*, future::Executor as _, sync::mpsc};
 use futures03::{compat::Compat, stream::StreamExt, future::FutureExt as _, future::TryFutureExt as _};
 use finality_grandpa::Message::{Prevote, Precommit, PrimaryPropose};
 use finality_grandpa::{voter, voter_set::VoterSet};
-use log::{debug, trace};
+use log::{debug, trace, warn};
 use sc_network::{NetworkService, ReputationChange};
 use sc_network_gossip::{GossipEngine, Network as GossipNetwork};
 use parity_scale_codec::{Encode, Decode};
-use sp_core::Pair;
+use sp_core::{
+	Pair, ed25519, crypto::{CryptoTypePublicPair, Public, key_types::GRANDPA}, traits::BareCryptoStore,
+};
+use sc_keystore::KeyStorePtr;
 use sp_runtime::traits::{Block as BlockT, Hash as HashT, Header as HeaderT, NumberFor};
 use sc_telemetry::{telemetry, CONSENSUS_DEBUG, CONSENSUS_INFO};
 
@@ -209,6 +212,19 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 		bridge
 	}
 
+	/// Set the channel the equivocations detected by the `GossipValidator` are sent to.
+	pub(crate) fn set_equivocation_reporter(&self, reporter: gossip::EquivocationReporter<B>) {
+		self.validator.set_equivocation_reporter(reporter);
+	}
+
+	/// Send an equivocation detected by the voter to the same channel.
+	pub(crate) fn report_equivocation(
+		&self,
+		equivocation_proof: sp_finality_grandpa::EquivocationProof<B::Hash, NumberFor<B>>,
+	) {
+		self.validator.report_equivocation(equivocation_proof);
+	}
+
 	/// Note the beginning of a new round to the `GossipValidator`.
 	pub(crate) fn note_round(
 		&self,
@@ -236,7 +252,7 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 		round: Round,
 		set_id: SetId,
 		voters: Arc<VoterSet<AuthorityId>>,
-		local_key: Option<AuthorityPair>,
+		local_key: Option<(AuthorityId, KeyStorePtr)>,
 		has_voted: HasVoted<B>,
 	) -> (
 		impl Stream<Item=SignedMessage<B>,Error=Error>,
@@ -248,14 +264,7 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 			&*voters,
 		);
 
-		let locals = local_key.and_then(|pair| {
-			let id = pair.public();
-			if voters.contains_key(&id) {
-				Some((pair, id))
-			} else {
-				None
-			}
-		});
+		let locals = local_key.filter(|(id, _)| voters.contains_key(id));
 
 		let topic = round_topic::<B>(round.0, set_id.0);
 		let incoming = Compat::new(self.gossip_engine.messages_for(topic)
@@ -317,6 +326,7 @@ impl<B: BlockT, N: Network<B>> NetworkBridge<B, N> {
 			set_id: set_id.0,
 			network: self.gossip_engine.clone(),
 			locals,
+			pending: None,
 			sender: tx,
 			has_voted,
 		};
@@ -564,6 +574,25 @@ pub(crate) fn check_message_sig<Block: BlockT>(
 	}
 }
 
+/// Signs the given payload with the key of the given authority, which is held by the keystore.
+fn sign_with_keystore(
+	keystore: &KeyStorePtr,
+	id: &AuthorityId,
+	payload: &[u8],
+) -> SignatureFuture {
+	let key = CryptoTypePublicPair(ed25519::CRYPTO_ID, id.to_raw_vec());
+	let signature = keystore.read().sign_with(GRANDPA, &key, payload)
+		.map(|signature| signature.and_then(|signature|
+			ed25519::Signature::try_from(&signature[..])
+				.map(Into::into)
+				.map_err(|_| "Invalid signature returned by the keystore".into())
+		));
+	Box::new(Compat::new(Box::pin(signature)))
+}
+
+/// Future of the signature of a vote by the keystore.
+type SignatureFuture = Box<dyn Future<Item = AuthoritySignature, Error = String> + Send>;
+
 /// A sink for outgoing messages to the network. Any messages that are sent will
 /// be replaced, as appropriate, according to the given `HasVoted`.
 /// NOTE: The votes are stored unsigned, which means that the signatures need to
@@ -571,21 +600,76 @@ pub(crate) fn check_message_sig<Block: BlockT>(
 /// use the same raw message and key to sign. This is currently true for
 /// `ed25519` and `BLS` signatures (which we might use in the future), care must
 /// be taken when switching to different key types.
+///
+/// Votes are signed asynchronously by the keystore, one at a time: the sink isn't ready for the
+/// next vote until the previous one is signed and sent.
 struct OutgoingMessages<Block: BlockT> {
 	round: RoundNumber,
 	set_id: SetIdNumber,
-	locals: Option<(AuthorityPair, AuthorityId)>,
+	locals: Option<(AuthorityId, KeyStorePtr)>,
+	/// The vote being signed by the keystore.
+	pending: Option<(Message<Block>, SignatureFuture)>,
 	sender: mpsc::UnboundedSender<SignedMessage<Block>>,
 	network: GossipEngine<Block>,
 	has_voted: HasVoted<Block>,
 }
 
+impl<Block: BlockT> OutgoingMessages<Block> {
+	/// Send the signed vote to the network and to the inner sender.
+	fn send_signed(
+		&mut self,
+		msg: Message<Block>,
+		signature: AuthoritySignature,
+		local_id: AuthorityId,
+	) {
+		let target_hash = msg.target().0.clone();
+		let signed = SignedMessage::<Block> {
+			message: msg,
+			signature,
+			id: local_id,
+		};
+
+		let message = GossipMessage::Vote(VoteMessage::<Block> {
+			message: signed.clone(),
+			round: Round(self.round),
+			set_id: SetId(self.set_id),
+		});
+
+		debug!(
+			target: "afg",
+			"Announcing block {} to peers which we voted on in round {} in set {}",
+			target_hash,
+			self.round,
+			self.set_id,
+		);
+
+		telemetry!(
+			CONSENSUS_DEBUG; "afg.announcing_blocks_to_voted_peers";
+			"block" => ?target_hash, "round" => ?self.round, "set_id" => ?self.set_id,
+		);
+
+		// announce the block we voted on to our peers.
+		self.network.announce(target_hash, Vec::new());
+
+		// propagate the message to peers
+		let topic = round_topic::<Block>(self.round, self.set_id);
+		self.network.gossip_message(topic, message.encode(), false);
+
+		// forward the message to the inner sender.
+		let _ = self.sender.unbounded_send(signed);
+	}
+}
+
 impl<Block: BlockT> Sink for OutgoingMessages<Block>
 {
 	type SinkItem = Message<Block>;
 	type SinkError = Error;
 
 	fn start_send(&mut self, mut msg: Message<Block>) -> StartSend<Message<Block>, Error> {
+		if let Async::NotReady = self.poll_complete()? {
+			return Ok(AsyncSink::NotReady(msg));
+		}
+
 		// if we've voted on this round previously under the same key, send that vote instead
 		match &mut msg {
 			finality_grandpa::Message::PrimaryPropose(ref mut vote) =>
@@ -603,53 +687,39 @@ impl<Block: BlockT> Sink for OutgoingMessages<Block>
 		}
 
 		// when locals exist, sign messages on import
-		if let Some((ref pair, ref local_id)) = self.locals {
+		if let Some((ref local_id, ref keystore)) = self.locals {
 			let encoded = localized_payload(self.round, self.set_id, &msg);
-			let signature = pair.sign(&encoded[..]);
-
-			let target_hash = msg.target().0.clone();
-			let signed = SignedMessage::<Block> {
-				message: msg,
-				signature,
-				id: local_id.clone(),
-			};
-
-			let message = GossipMessage::Vote(VoteMessage::<Block> {
-				message: signed.clone(),
-				round: Round(self.round),
-				set_id: SetId(self.set_id),
-			});
-
-			debug!(
-				target: "afg",
-				"Announcing block {} to peers which we voted on in round {} in set {}",
-				target_hash,
-				self.round,
-				self.set_id,
-			);
-
-			telemetry!(
-				CONSENSUS_DEBUG; "afg.announcing_blocks_to_voted_peers";
-				"block" => ?target_hash, "round" => ?self.round, "set_id" => ?self.set_id,
-			);
-
-			// announce the block we voted on to our peers.
-			self.network.announce(target_hash, Vec::new());
-
-			// propagate the message to peers
-			let topic = round_topic::<Block>(self.round, self.set_id);
-			self.network.gossip_message(topic, message.encode(), false);
-
-			// forward the message to the inner sender.
-			let _ = self.sender.unbounded_send(signed);
+			self.pending = Some((msg, sign_with_keystore(keystore, local_id, &encoded[..])));
+
+			// a keystore signing locally is done already.
+			self.poll_complete()?;
 		}
 
 		Ok(AsyncSink::Ready)
 	}
 
-	fn poll_complete(&mut self) -> Poll<(), Error> { Ok(Async::Ready(())) }
+	fn poll_complete(&mut self) -> Poll<(), Error> {
+		let signature = match self.pending.as_mut().map(|(_, signature)| signature.poll()) {
+			None => return Ok(Async::Ready(())),
+			Some(Ok(Async::NotReady)) => return Ok(Async::NotReady),
+			Some(signature) => signature,
+		};
+
+		let (msg, _) = self.pending.take().expect("pending vote is polled above; qed");
+		match (signature, self.locals.as_ref()) {
+			(Ok(Async::Ready(signature)), Some((local_id, _))) => {
+				let local_id = local_id.clone();
+				self.send_signed(msg, signature, local_id);
+			},
+			(Err(e), _) => warn!(target: "afg", "Failed to sign vote in round {}: {}", self.round, e),
+			_ => {},
+		}
+
+		Ok(Async::Ready(()))
+	}
 
 	fn close(&mut self) -> Poll<(), Error> {
+		futures::try_ready!(self.poll_complete());
 		// ignore errors since we allow this inner sender to be closed already.
 		self.sender.close().or_else(|_| Ok(Async::Ready(())))
 	}
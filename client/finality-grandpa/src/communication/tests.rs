@@ -61,6 +61,10 @@ impl sc_network_gossip::Network<Block> for TestNetwork {
 		let _ = self.sender.unbounded_send(Event::WriteNotification(who, message));
 	}
 
+	fn num_queued_messages(&self, _: &PeerId) -> usize {
+		0
+	}
+
 	fn register_notifications_protocol(&self, _: ConsensusEngineId) {}
 
 	fn announce(&self, block: Hash, _associated_data: Vec<u8>) {
@@ -278,6 +282,7 @@ fn good_commit_leads_to_relay() {
 						remote: sender_id.clone(),
 						engine_id: GRANDPA_ENGINE_ID,
 						roles: Roles::FULL,
+						handshake: Vec::new(),
 					});
 
 					let _ = sender.unbounded_send(NetworkEvent::NotificationsReceived {
@@ -290,6 +295,7 @@ fn good_commit_leads_to_relay() {
 						remote: sc_network::PeerId::random(),
 						engine_id: GRANDPA_ENGINE_ID,
 						roles: Roles::FULL,
+						handshake: Vec::new(),
 					});
 
 					true
@@ -403,6 +409,7 @@ fn bad_commit_leads_to_report() {
 						remote: sender_id.clone(),
 						engine_id: GRANDPA_ENGINE_ID,
 						roles: Roles::FULL,
+						handshake: Vec::new(),
 					});
 					let _ = sender.unbounded_send(NetworkEvent::NotificationsReceived {
 						remote: sender_id.clone(),
@@ -41,7 +41,7 @@ use finality_grandpa::{
 	BlockNumberOps, Equivocation, Error as GrandpaError, round::State as RoundState,
 	voter, voter_set::VoterSet,
 };
-use sp_core::{Blake2Hasher, H256, Pair};
+use sp_core::{Blake2Hasher, H256};
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::{
 	Block as BlockT, Header as HeaderT, NumberFor, One, Zero,
@@ -60,8 +60,11 @@ use crate::communication::Network as NetworkT;
 use crate::consensus_changes::SharedConsensusChanges;
 use crate::justification::GrandpaJustification;
 use crate::until_imported::UntilVoteTargetImported;
+use crate::voter_state::SharedVoterState;
 use crate::voting_rule::VotingRule;
-use sp_finality_grandpa::{AuthorityId, AuthoritySignature, SetId, RoundNumber};
+use sp_finality_grandpa::{
+	AuthorityId, AuthoritySignature, EquivocationProof, SetId, RoundNumber, VoteEquivocation,
+};
 
 type HistoricalVotes<Block> = finality_grandpa::HistoricalVotes<
 	<Block as BlockT>::Hash,
@@ -387,6 +390,7 @@ pub(crate) struct Environment<B, E, Block: BlockT, N: NetworkT<Block>, RA, SC, V
 	pub(crate) network: crate::communication::NetworkBridge<Block, N>,
 	pub(crate) set_id: SetId,
 	pub(crate) voter_set_state: SharedVoterSetState<Block>,
+	pub(crate) voter_state: SharedVoterState,
 	pub(crate) voting_rule: VR,
 }
 
@@ -592,11 +596,16 @@ where
 		let prevote_timer = Delay::new(self.config.gossip_duration * 2);
 		let precommit_timer = Delay::new(self.config.gossip_duration * 4);
 
-		let local_key = crate::is_voter(&self.voters, &self.config.keystore);
+		// while the voting is paused, new rounds are run as if we weren't a voter.
+		let local_id = if self.voter_state.is_voting_paused() {
+			None
+		} else {
+			crate::is_voter(&self.voters, &self.config.keystore)
+		};
 
 		let has_voted = match self.voter_set_state.has_voted(round) {
 			HasVoted::Yes(id, vote) => {
-				if local_key.as_ref().map(|k| k.public() == id).unwrap_or(false) {
+				if local_id.as_ref().map(|k| k == &id).unwrap_or(false) {
 					HasVoted::Yes(id, vote)
 				} else {
 					HasVoted::No
@@ -609,7 +618,7 @@ where
 			crate::communication::Round(round),
 			crate::communication::SetId(self.set_id),
 			self.voters.clone(),
-			local_key.clone(),
+			local_id.clone().and_then(|id| self.config.keystore.clone().map(|keystore| (id, keystore))),
 			has_voted,
 		);
 
@@ -623,11 +632,27 @@ where
 			"round",
 		).map_err(Into::into));
 
+		// note the votes the voter processes in the shared voter state.
+		self.voter_state.new_round(round);
+		let voter_state = self.voter_state.clone();
+		let incoming = Box::new(incoming.inspect(move |signed| match signed.message {
+			finality_grandpa::Message::Prevote(_) => voter_state.note_prevote(round, &signed.id),
+			finality_grandpa::Message::Precommit(_) => voter_state.note_precommit(round, &signed.id),
+			finality_grandpa::Message::PrimaryPropose(_) => {},
+		}));
+
+		// drop the votes cast while the voting is paused, before they are signed.
+		let voter_state = self.voter_state.clone();
+		let outgoing = outgoing.sink_map_err(Into::into).with_flat_map(move |message| {
+			let message = if voter_state.is_voting_paused() { None } else { Some(message) };
+			futures::stream::iter_ok(message)
+		});
+
 		// schedule network message cleanup when sink drops.
-		let outgoing = Box::new(outgoing.sink_map_err(Into::into));
+		let outgoing = Box::new(outgoing);
 
 		voter::RoundData {
-			voter_id: local_key.map(|pair| pair.public()),
+			voter_id: local_id,
 			prevote_timer: Box::new(prevote_timer.map(Ok).compat()),
 			precommit_timer: Box::new(precommit_timer.map(Ok).compat()),
 			incoming,
@@ -639,7 +664,7 @@ where
 		let local_id = crate::is_voter(&self.voters, &self.config.keystore);
 
 		let local_id = match local_id {
-			Some(id) => id.public(),
+			Some(id) => id,
 			None => return Ok(()),
 		};
 
@@ -678,9 +703,10 @@ where
 		let local_id = crate::is_voter(&self.voters, &self.config.keystore);
 
 		let local_id = match local_id {
-			Some(id) => id.public(),
+			Some(id) => id,
 			None => return Ok(()),
 		};
+		self.voter_state.note_prevote(round, &local_id);
 
 		self.update_voter_set_state(|voter_set_state| {
 			let (completed_rounds, current_rounds) = voter_set_state.with_current_round(round)?;
@@ -719,9 +745,10 @@ where
 		let local_id = crate::is_voter(&self.voters, &self.config.keystore);
 
 		let local_id = match local_id {
-			Some(id) => id.public(),
+			Some(id) => id,
 			None => return Ok(()),
 		};
+		self.voter_state.note_precommit(round, &local_id);
 
 		self.update_voter_set_state(|voter_set_state| {
 			let (completed_rounds, current_rounds) = voter_set_state.with_current_round(round)?;
@@ -912,7 +939,12 @@ where
 		equivocation: ::finality_grandpa::Equivocation<Self::Id, Prevote<Block>, Self::Signature>
 	) {
 		warn!(target: "afg", "Detected prevote equivocation in the finality worker: {:?}", equivocation);
-		// nothing yet; this could craft misbehavior reports of some kind.
+		self.report_equivocation(sp_finality_grandpa::Equivocation::Prevote(
+			vote_equivocation(equivocation, |prevote| sp_finality_grandpa::Prevote {
+				target_hash: prevote.target_hash,
+				target_number: prevote.target_number,
+			})
+		));
 	}
 
 	fn precommit_equivocation(
@@ -921,7 +953,33 @@ where
 		equivocation: Equivocation<Self::Id, Precommit<Block>, Self::Signature>
 	) {
 		warn!(target: "afg", "Detected precommit equivocation in the finality worker: {:?}", equivocation);
-		// nothing yet
+		self.report_equivocation(sp_finality_grandpa::Equivocation::Precommit(
+			vote_equivocation(equivocation, |precommit| sp_finality_grandpa::Precommit {
+				target_hash: precommit.target_hash,
+				target_number: precommit.target_number,
+			})
+		));
+	}
+}
+
+impl<B, E, Block: BlockT<Hash=H256>, N: NetworkT<Block>, RA, SC, VR> Environment<B, E, Block, N, RA, SC, VR> {
+	/// Hand an equivocation of the current set over to the equivocation reporting task, which
+	/// reports it to the runtime if we are a voter of the set.
+	fn report_equivocation(&self, equivocation: sp_finality_grandpa::Equivocation<Block::Hash, NumberFor<Block>>) {
+		self.network.report_equivocation(EquivocationProof::new(self.set_id, equivocation));
+	}
+}
+
+/// Convert an equivocation detected by the voter to the format of the runtime.
+pub(crate) fn vote_equivocation<V, W>(
+	equivocation: Equivocation<AuthorityId, V, AuthoritySignature>,
+	convert: impl Fn(V) -> W,
+) -> VoteEquivocation<W> {
+	VoteEquivocation {
+		round_number: equivocation.round_number,
+		identity: equivocation.identity,
+		first: (convert(equivocation.first.0), equivocation.first.1),
+		second: (convert(equivocation.second.0), equivocation.second.1),
 	}
 }
 
@@ -33,14 +33,19 @@
 //! Finality proof provider can choose how to provide finality proof on its own. The incomplete
 //! finality proof (that finalizes some block C that is ancestor of the B and descendant
 //! of the U) could be returned.
+//!
+//! Proofs are served to the network with the `FinalityProofRequest` message and on the
+//! finality proof request-response protocol, and to RPC clients with `grandpa_proveFinality`.
+//! `FinalityProofVerifier` checks them on the receiving side.
 
 use std::iter;
 use std::sync::Arc;
 use log::{trace, warn};
+use parking_lot::Mutex;
 
 use sp_blockchain::{Backend as BlockchainBackend, Error as ClientError, Result as ClientResult};
 use sc_client_api::{
-	backend::Backend, CallExecutor, StorageProof,
+	backend::Backend, CallExecutor, StorageProof, CompactProof,
 	light::{FetchChecker, RemoteReadRequest},
 };
 use sc_client::Client;
@@ -154,6 +159,37 @@ impl<B, Block: BlockT<Hash=H256>> FinalityProofProvider<B, Block>
 	}
 }
 
+impl<B, Block> FinalityProofProvider<B, Block>
+	where
+		Block: BlockT<Hash=H256>,
+		NumberFor<Block>: BlockNumberOps,
+		B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+{
+	/// Prove the finality of the best possible block in the range (begin; end], for a caller
+	/// that knows the finality of `begin` and the authority set `authorities_set_id` live at it.
+	///
+	/// The proofs of the authority set changes are compact if `compact_proofs` is set, which the
+	/// callers predating compact proofs can't check.
+	///
+	/// Returns `None` if no block of the range can be proven final yet.
+	pub fn prove_finality_range(
+		&self,
+		begin: Block::Hash,
+		end: Block::Hash,
+		authorities_set_id: u64,
+		compact_proofs: bool,
+	) -> ClientResult<Option<Vec<u8>>> {
+		prove_finality::<_, _, GrandpaJustification<Block>>(
+			&*self.backend.blockchain(),
+			&*self.authority_provider,
+			authorities_set_id,
+			begin,
+			end,
+			compact_proofs,
+		)
+	}
+}
+
 impl<B, Block> sc_network::FinalityProofProvider<Block> for FinalityProofProvider<B, Block>
 	where
 		Block: BlockT<Hash=H256>,
@@ -164,6 +200,7 @@ impl<B, Block> sc_network::FinalityProofProvider<Block> for FinalityProofProvide
 		&self,
 		for_block: Block::Hash,
 		request: &[u8],
+		compact_proofs: bool,
 	) -> Result<Option<Vec<u8>>, ClientError> {
 		let request: FinalityProofRequest<Block::Hash> = Decode::decode(&mut &request[..])
 			.map_err(|e| {
@@ -171,17 +208,85 @@ impl<B, Block> sc_network::FinalityProofProvider<Block> for FinalityProofProvide
 				ClientError::Backend(format!("Invalid finality proof request"))
 			})?;
 		match request {
-			FinalityProofRequest::Original(request) => prove_finality::<_, _, GrandpaJustification<Block>>(
-				&*self.backend.blockchain(),
-				&*self.authority_provider,
-				request.authorities_set_id,
-				request.last_finalized,
-				for_block,
-			),
+			FinalityProofRequest::Original(request) =>
+				self.prove_finality_range(
+					request.last_finalized,
+					for_block,
+					request.authorities_set_id,
+					compact_proofs,
+				),
 		}
 	}
 }
 
+/// Verifier of the finality proofs built by `FinalityProofProvider`.
+///
+/// Keeps track of the authority set live at the last block proven final, which must have signed
+/// the first justification of the next proof.
+pub struct FinalityProofVerifier<B, Block: BlockT> {
+	blockchain: Arc<B>,
+	authority_checker: Arc<dyn AuthoritySetForFinalityChecker<Block>>,
+	authority_set: Mutex<(u64, AuthorityList)>,
+}
+
+impl<B, Block> FinalityProofVerifier<B, Block>
+	where
+		Block: BlockT<Hash=H256>,
+		NumberFor<Block>: BlockNumberOps,
+		B: BlockchainBackend<Block>,
+{
+	/// Create a verifier starting from the authority set with the given id.
+	///
+	/// The blockchain gives the headers of the proven blocks the caller already knows.
+	pub fn new(
+		blockchain: Arc<B>,
+		authority_checker: Arc<dyn AuthoritySetForFinalityChecker<Block>>,
+		set_id: u64,
+		authorities: AuthorityList,
+	) -> Self {
+		FinalityProofVerifier {
+			blockchain,
+			authority_checker,
+			authority_set: Mutex::new((set_id, authorities)),
+		}
+	}
+
+	/// Returns the id and the authorities of the authority set the next proof must start with.
+	pub fn authority_set(&self) -> (u64, AuthorityList) {
+		self.authority_set.lock().clone()
+	}
+
+	/// Build the request data of a `FinalityProofRequest` for a proof starting after the given
+	/// finalized block.
+	pub fn make_request(&self, last_finalized: Block::Hash) -> Vec<u8> {
+		make_finality_proof_request(last_finalized, self.authority_set.lock().0)
+	}
+
+	/// Verify a finality proof, and move on to the authority set live at the block it finalizes.
+	///
+	/// The headers of the returned effects must be imported before the finalized block.
+	pub fn verify(&self, proof: &[u8]) -> ClientResult<FinalityEffects<Block::Header>> {
+		self.do_verify::<GrandpaJustification<Block>>(proof)
+	}
+
+	fn do_verify<J>(&self, proof: &[u8]) -> ClientResult<FinalityEffects<Block::Header>>
+		where J: ProvableJustification<Block::Header>,
+	{
+		let mut authority_set = self.authority_set.lock();
+		let (set_id, authorities) = authority_set.clone();
+		let effects = do_check_finality_proof::<_, _, J>(
+			&*self.blockchain,
+			set_id,
+			authorities,
+			&*self.authority_checker,
+			proof.to_vec(),
+		)?;
+
+		*authority_set = (effects.new_set_id, effects.new_authorities.clone());
+		Ok(effects)
+	}
+}
+
 /// The effects of block finality.
 #[derive(Debug, PartialEq)]
 pub struct FinalityEffects<Header: HeaderT> {
@@ -212,7 +317,46 @@ struct FinalityProofFragment<Header: HeaderT> {
 	/// The set of headers in the range (U; F] that we believe are unknown to the caller. Ordered.
 	pub unknown_headers: Vec<Header>,
 	/// Optional proof of execution of GRANDPA::authorities() at the `block`.
-	pub authorities_proof: Option<StorageProof>,
+	pub authorities_proof: Option<AuthoritiesProof>,
+}
+
+/// Proof of GRANDPA::authorities() at a block, as a list of trie nodes.
+///
+/// The peers supporting compact proofs get a `CompactProof`, and the older ones a `StorageProof`.
+/// Both are encoded as a list of nodes, so a proof is checked as a compact one first, and as a
+/// plain one if that fails.
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct AuthoritiesProof(Vec<Vec<u8>>);
+
+impl AuthoritiesProof {
+	fn new(proof: StorageProof, compact: bool) -> ClientResult<Self> {
+		if compact {
+			let proof = proof.into_compact_proof::<Blake2Hasher>()
+				.map_err(|e| ClientError::Execution(Box::new(e)))?;
+			Ok(AuthoritiesProof(proof.encoded_nodes))
+		} else {
+			Ok(AuthoritiesProof(proof.iter_nodes().collect()))
+		}
+	}
+
+	fn check<Block: BlockT>(
+		self,
+		authorities_provider: &dyn AuthoritySetForFinalityChecker<Block>,
+		hash: Block::Hash,
+		header: Block::Header,
+	) -> ClientResult<AuthorityList> {
+		let proof = CompactProof { encoded_nodes: self.0 };
+		StorageProof::from_compact_proof::<Blake2Hasher>(&proof)
+			.map_err(|e| ClientError::Execution(Box::new(e)))
+			.and_then(|restored| {
+				authorities_provider.check_authorities_proof(hash, header.clone(), restored)
+			})
+			.or_else(|_| authorities_provider.check_authorities_proof(
+				hash,
+				header,
+				StorageProof::new(proof.encoded_nodes),
+			))
+	}
 }
 
 /// Proof of finality is the ordered set of finality fragments, where:
@@ -258,6 +402,7 @@ pub(crate) fn prove_finality<Block: BlockT<Hash=H256>, B: BlockchainBackend<Bloc
 	authorities_set_id: u64,
 	begin: Block::Hash,
 	end: Block::Hash,
+	compact_proofs: bool,
 ) -> ::sp_blockchain::Result<Option<Vec<u8>>>
 	where
 		J: ProvableJustification<Block::Header>,
@@ -317,7 +462,8 @@ pub(crate) fn prove_finality<Block: BlockT<Hash=H256>, B: BlockchainBackend<Bloc
 			let new_authorities = authorities_provider.authorities(&current_id)?;
 			let new_authorities_proof = if current_authorities != new_authorities {
 				current_authorities = new_authorities;
-				Some(authorities_provider.prove_authorities(&current_id)?)
+				let proof = authorities_provider.prove_authorities(&current_id)?;
+				Some(AuthoritiesProof::new(proof, compact_proofs)?)
 			} else {
 				None
 			};
@@ -511,10 +657,10 @@ fn check_finality_proof_fragment<Block: BlockT<Hash=H256>, B, J>(
 			Some(header) => header,
 			None => blockchain.expect_header(BlockId::Hash(proof_fragment.block))?,
 		};
-		current_authorities = authorities_provider.check_authorities_proof(
+		current_authorities = new_authorities_proof.check(
+			authorities_provider,
 			proof_fragment.block,
 			header,
-			new_authorities_proof,
 		)?;
 
 		current_set_id = current_set_id + 1;
@@ -631,6 +777,10 @@ pub(crate) mod tests {
 		}
 	}
 
+	fn authorities_proof(marker: u8) -> AuthoritiesProof {
+		AuthoritiesProof(vec![vec![marker]])
+	}
+
 	fn header(number: u64) -> Header {
 		let parent_hash = match number {
 			0 => Default::default(),
@@ -684,6 +834,7 @@ pub(crate) mod tests {
 			0,
 			header(2).hash(),
 			header(2).hash(),
+			false,
 		).unwrap_err();
 	}
 
@@ -704,6 +855,7 @@ pub(crate) mod tests {
 			0,
 			header(3).hash(),
 			header(4).hash(),
+			false,
 		).unwrap();
 		assert_eq!(proof_of_4, None);
 	}
@@ -730,6 +882,7 @@ pub(crate) mod tests {
 			0,
 			side_header(4).hash(),
 			second_side_header(5).hash(),
+			false,
 		).unwrap_err();
 	}
 
@@ -749,6 +902,7 @@ pub(crate) mod tests {
 			0,
 			header(3).hash(),
 			header(4).hash(),
+			false,
 		).unwrap();
 		assert_eq!(proof_of_4, None);
 	}
@@ -773,6 +927,7 @@ pub(crate) mod tests {
 			0,
 			header(3).hash(),
 			header(5).hash(),
+			false,
 		).unwrap().unwrap()[..]).unwrap();
 		assert_eq!(proof_of_5, vec![FinalityProofFragment {
 			block: header(5).hash(),
@@ -799,6 +954,7 @@ pub(crate) mod tests {
 			0,
 			header(3).hash(),
 			header(5).hash(),
+			false,
 		).unwrap().unwrap()[..]).unwrap();
 		assert_eq!(proof_of_5, vec![FinalityProofFragment {
 			block: header(4).hash(),
@@ -843,6 +999,7 @@ pub(crate) mod tests {
 			0,
 			header(3).hash(),
 			header(6).hash(),
+			false,
 		).unwrap().unwrap()[..]).unwrap();
 		// initial authorities set (which start acting from #0) is [3; 32]
 		assert_eq!(proof_of_6, vec![
@@ -852,14 +1009,14 @@ pub(crate) mod tests {
 				block: header(5).hash(),
 				justification: just5,
 				unknown_headers: Vec::new(),
-				authorities_proof: Some(StorageProof::new(vec![vec![50]])),
+				authorities_proof: Some(authorities_proof(50)),
 			},
 			// last fragment provides justification for #7 && unknown#7
 			FinalityProofFragment {
 				block: header(7).hash(),
 				justification: just7.clone(),
 				unknown_headers: vec![header(7)],
-				authorities_proof: Some(StorageProof::new(vec![vec![70]])),
+				authorities_proof: Some(authorities_proof(70)),
 			},
 		]);
 
@@ -873,7 +1030,7 @@ pub(crate) mod tests {
 			0,
 			auth3,
 			&ClosureAuthoritySetForFinalityChecker(
-				|hash, _header, proof: StorageProof| match proof.clone().iter_nodes().next().map(|x| x[0]) {
+				|hash, _header, proof: StorageProof| match proof.iter_nodes().next().map(|x| x[0]) {
 					Some(50) => Ok(auth5.clone()),
 					Some(70) => Ok(auth7.clone()),
 					_ => unreachable!("no other proofs should be checked: {}", hash),
@@ -934,7 +1091,7 @@ pub(crate) mod tests {
 				block: header(4).hash(),
 				justification: TestJustification((0, authorities.clone()), vec![7]).encode(),
 				unknown_headers: vec![header(4)],
-				authorities_proof: Some(StorageProof::new(vec![vec![42]])),
+				authorities_proof: Some(authorities_proof(42)),
 			}, FinalityProofFragment {
 				block: header(5).hash(),
 				justification: TestJustification((0, authorities), vec![8]).encode(),
@@ -984,7 +1141,7 @@ pub(crate) mod tests {
 				block: header(2).hash(),
 				justification: TestJustification((1, initial_authorities.clone()), vec![7]).encode(),
 				unknown_headers: Vec::new(),
-				authorities_proof: Some(StorageProof::new(vec![vec![42]])),
+				authorities_proof: Some(authorities_proof(42)),
 			}, FinalityProofFragment {
 				block: header(4).hash(),
 				justification: TestJustification((2, next_authorities.clone()), vec![8]).encode(),
@@ -1020,7 +1177,78 @@ pub(crate) mod tests {
 			0,
 			header(3).hash(),
 			header(4).hash(),
+			false,
 		).unwrap();
 		assert!(proof_of_4.is_none());
 	}
+
+	#[test]
+	fn finality_proof_verifier_follows_authority_set_changes() {
+		let blockchain = Arc::new(test_blockchain());
+
+		let initial_authorities = vec![(AuthorityId::from_slice(&[3u8; 32]), 1u64)];
+		let next_authorities = vec![(AuthorityId::from_slice(&[4u8; 32]), 1u64)];
+		let checker_authorities = next_authorities.clone();
+		let verifier = FinalityProofVerifier::new(
+			blockchain,
+			Arc::new(ClosureAuthoritySetForFinalityChecker(move |_, _, _| Ok(checker_authorities.clone()))),
+			1,
+			initial_authorities.clone(),
+		);
+		assert_eq!(verifier.make_request(header(1).hash()), make_finality_proof_request(header(1).hash(), 1));
+
+		let proof = vec![FinalityProofFragment {
+			block: header(2).hash(),
+			justification: TestJustification((1, initial_authorities.clone()), vec![7]).encode(),
+			unknown_headers: Vec::new(),
+			authorities_proof: Some(authorities_proof(42)),
+		}, FinalityProofFragment {
+			block: header(4).hash(),
+			justification: TestJustification((2, next_authorities.clone()), vec![8]).encode(),
+			unknown_headers: vec![header(4)],
+			authorities_proof: None,
+		}].encode();
+		let effects = verifier.do_verify::<TestJustification>(&proof).unwrap();
+		assert_eq!(effects.block, header(4).hash());
+		assert_eq!(verifier.authority_set(), (2, next_authorities.clone()));
+		assert_eq!(verifier.make_request(header(4).hash()), make_finality_proof_request(header(4).hash(), 2));
+
+		// The same proof isn't signed by the new authority set, and changes nothing.
+		verifier.do_verify::<TestJustification>(&proof).unwrap_err();
+		assert_eq!(verifier.authority_set(), (2, next_authorities));
+	}
+
+	#[test]
+	fn compact_and_plain_authorities_proofs_are_checked() {
+		use sp_state_machine::{Backend as _, backend::InMemory, prove_read, read_proof_check};
+
+		let authorities = vec![(AuthorityId::from_slice(&[1u8; 32]), 1u64)];
+		let backend = <InMemory<Blake2Hasher>>::from(vec![
+			(None, vec![(GRANDPA_AUTHORITIES_KEY.to_vec(), Some(authorities.encode()))])
+		]);
+		let state_root = backend.storage_root(iter::empty()).0;
+		let proof = prove_read(backend, vec![GRANDPA_AUTHORITIES_KEY]).unwrap();
+		let header = Header::new(1, Default::default(), state_root, Default::default(), Default::default());
+		let checker = ClosureAuthoritySetForFinalityChecker(
+			|_hash: H256, header: Header, proof: StorageProof| {
+				let values = read_proof_check::<Blake2Hasher, _>(
+					*header.state_root(),
+					proof,
+					vec![GRANDPA_AUTHORITIES_KEY],
+				).map_err(|e| ClientError::Backend(e.to_string()))?;
+				values.get(GRANDPA_AUTHORITIES_KEY)
+					.and_then(|value| value.as_ref())
+					.and_then(|value| AuthorityList::decode(&mut &value[..]).ok())
+					.ok_or(ClientError::InvalidAuthoritiesSet)
+			},
+		);
+
+		let plain = AuthoritiesProof::new(proof.clone(), false).unwrap();
+		let compact = AuthoritiesProof::new(proof, true).unwrap();
+		assert_ne!(plain, compact);
+
+		// Peers predating compact proofs send plain proofs, which are still accepted.
+		assert_eq!(plain.check(&checker, header.hash(), header.clone()).unwrap(), authorities);
+		assert_eq!(compact.check(&checker, header.hash(), header).unwrap(), authorities);
+	}
 }
@@ -171,7 +171,7 @@ impl<'a, Block: 'a + BlockT> Drop for PendingSetChanges<'a, Block> {
 	}
 }
 
-fn find_scheduled_change<B: BlockT>(header: &B::Header)
+pub(crate) fn find_scheduled_change<B: BlockT>(header: &B::Header)
 	-> Option<ScheduledChange<NumberFor<B>>>
 {
 	let id = OpaqueDigestItemId::Consensus(&GRANDPA_ENGINE_ID);
@@ -397,6 +397,10 @@ impl<B, E, Block: BlockT<Hash=H256>, RA, SC> BlockImport<Block>
 		let hash = block.post_header().hash();
 		let number = block.header.number().clone();
 
+		if block.state.is_some() {
+			return self.import_state(block, hash, number, new_cache);
+		}
+
 		// early exit if block already in chain, otherwise the check for
 		// authority changes will error when trying to re-import a change block
 		match self.inner.status(BlockId::Hash(hash)) {
@@ -509,6 +513,74 @@ impl<B, E, Block: BlockT<Hash=H256>, RA, SC> BlockImport<Block>
 	}
 }
 
+impl<B, E, Block: BlockT<Hash=H256>, RA, SC>
+	GrandpaBlockImport<B, E, Block, RA, SC>
+where
+	NumberFor<Block>: finality_grandpa::BlockNumberOps,
+	B: Backend<Block, Blake2Hasher> + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + 'static + Clone + Send + Sync,
+	RA: Send + Sync,
+{
+	/// Import a block along with its state, as downloaded by warp sync. The authority set that
+	/// the warp sync proof has proven live at the block replaces the current one.
+	fn import_state(
+		&mut self,
+		mut block: BlockImportParams<Block>,
+		hash: Block::Hash,
+		number: NumberFor<Block>,
+		new_cache: HashMap<well_known_cache_keys::Id, Vec<u8>>,
+	) -> Result<ImportResult, ConsensusError> {
+		// The state of a block whose header was imported beforehand, as at the end of a fast
+		// sync, doesn't change the authority set tracked while importing the headers.
+		match self.inner.status(BlockId::Hash(hash)) {
+			Ok(BlockStatus::InChain) => return (&*self.inner).import_block(block, new_cache)
+				.map_err(|e| ConsensusError::ClientImport(e.to_string())),
+			Ok(BlockStatus::Unknown) => {},
+			Err(e) => return Err(ConsensusError::ClientImport(e.to_string())),
+		}
+
+		let target = crate::aux_schema::load_warp_sync_target::<Block::Hash, _>(&*self.inner)
+			.map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+		let (set_id, authorities) = match target {
+			Some((target_hash, set_id, authorities)) if target_hash == hash => (set_id, authorities),
+			_ => return Err(ConsensusError::ClientImport(
+				format!("No authority set proven for the state of block {:?}", hash)
+			)),
+		};
+
+		let mut authority_set = AuthoritySet::genesis(authorities.clone());
+		authority_set.set_id = set_id;
+		let new_set = NewAuthoritySet {
+			canon_number: number,
+			canon_hash: hash,
+			set_id,
+			authorities,
+		};
+
+		crate::aux_schema::update_authority_set::<Block, _, _>(
+			&authority_set,
+			Some(&new_set),
+			|insert| block.auxiliary.extend(
+				insert.iter().map(|(k, v)| (k.to_vec(), Some(v.to_vec())))
+			)
+		);
+		crate::aux_schema::clear_warp_sync_target(
+			|delete| block.auxiliary.extend(delete.iter().map(|k| (k.to_vec(), None)))
+		);
+
+		let import_result = (&*self.inner).import_block(block, new_cache)
+			.map_err(|e| ConsensusError::ClientImport(e.to_string()))?;
+
+		if let ImportResult::Imported(_) = import_result {
+			info!(target: "afg", "Imported state of block #{} with authority set {}", number, set_id);
+			*self.authority_set.inner().write() = authority_set;
+			let _ = self.send_voter_commands.unbounded_send(VoterCommand::ChangeAuthorities(new_set));
+		}
+
+		Ok(import_result)
+	}
+}
+
 impl<B, E, Block: BlockT<Hash=H256>, RA, SC>
 	GrandpaBlockImport<B, E, Block, RA, SC>
 {
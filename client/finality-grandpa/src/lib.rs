@@ -53,7 +53,7 @@
 //! included in the newly-finalized chain.
 
 use futures::prelude::*;
-use log::{debug, error, info};
+use log::{debug, error, info, warn};
 use futures::sync::mpsc;
 use sc_client_api::{BlockchainEvents, CallExecutor, backend::Backend, ExecutionStrategy};
 use sp_blockchain::{HeaderBackend, Error as ClientError};
@@ -64,7 +64,7 @@ use sp_runtime::traits::{NumberFor, Block as BlockT, DigestFor, Zero};
 use sc_keystore::KeyStorePtr;
 use sp_inherents::InherentDataProviders;
 use sp_consensus::SelectChain;
-use sp_core::{H256, Blake2Hasher, Pair};
+use sp_core::{H256, Blake2Hasher, crypto::{Public, key_types::GRANDPA}, traits::BareCryptoStore};
 use sc_telemetry::{telemetry, CONSENSUS_INFO, CONSENSUS_DEBUG, CONSENSUS_WARN};
 use serde_json;
 
@@ -88,22 +88,29 @@ mod justification;
 mod light_import;
 mod observer;
 mod until_imported;
+mod voter_state;
 mod voting_rule;
+mod warp_proof;
 
-pub use finality_proof::FinalityProofProvider;
+pub use finality_proof::{
+	AuthoritySetForFinalityChecker, FinalityEffects, FinalityProofProvider, FinalityProofVerifier,
+};
 pub use justification::GrandpaJustification;
 pub use light_import::light_block_import;
 pub use observer::run_grandpa_observer;
+pub use warp_proof::WarpSyncProofProvider;
+pub use voter_state::{RoundState, SharedVoterState, VoterState};
 pub use voting_rule::{
 	BeforeBestBlock, ThreeQuartersOfTheUnfinalizedChain, VotingRule, VotingRulesBuilder
 };
 
+use authorities::SharedAuthoritySet;
 use aux_schema::PersistentData;
 use environment::{Environment, VoterSetState};
 use import::GrandpaBlockImport;
 use until_imported::UntilGlobalMessageBlocksImported;
 use communication::{NetworkBridge, Network as NetworkT};
-use sp_finality_grandpa::{AuthorityList, AuthorityPair, AuthoritySignature, SetId};
+use sp_finality_grandpa::{AuthorityList, AuthoritySignature, EquivocationProof, SetId};
 
 // Re-export these two because it's just so damn convenient.
 pub use sp_finality_grandpa::{AuthorityId, ScheduledChange};
@@ -539,6 +546,8 @@ pub struct GrandpaParams<B, E, Block: BlockT<Hash=H256>, N, RA, SC, VR, X, Sp> {
 	pub voting_rule: VR,
 	/// How to spawn background tasks.
 	pub executor: Sp,
+	/// Updated with the state of the voter as it runs.
+	pub shared_voter_state: SharedVoterState,
 }
 
 /// Run a GRANDPA voter as a task. Provide configuration and a link to a
@@ -567,6 +576,7 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, VR, X, Sp>(
 		telemetry_on_connect,
 		voting_rule,
 		executor,
+		shared_voter_state,
 	} = grandpa_params;
 
 	let LinkHalf {
@@ -584,6 +594,25 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, VR, X, Sp>(
 		on_exit.clone(),
 	);
 
+	if config.keystore.is_some() {
+		let (equivocation_tx, equivocation_rx) = mpsc::unbounded();
+		network.set_equivocation_reporter(equivocation_tx);
+
+		let reporting_task = report_equivocations(
+			client.clone(),
+			persistent_data.authority_set.clone(),
+			config.keystore.clone(),
+			equivocation_rx,
+		);
+
+		use futures03::{FutureExt, TryFutureExt};
+		use futures::future::Executor as _;
+
+		futures03::compat::Compat::new(&executor)
+			.execute(Box::new(reporting_task.select(on_exit.clone().map(Ok).compat()).then(|_| Ok(()))))
+			.map_err(|_| ClientError::Msg("failed to spawn the grandpa equivocation reporting task".into()))?;
+	}
+
 	register_finality_tracker_inherent_data_provider(client.clone(), &inherent_data_providers)?;
 
 	let conf = config.clone();
@@ -622,6 +651,7 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, VR, X, Sp>(
 		voting_rule,
 		persistent_data,
 		voter_commands_rx,
+		shared_voter_state,
 	);
 
 	let voter_work = voter_work
@@ -640,6 +670,46 @@ pub fn run_grandpa_voter<B, E, Block: BlockT<Hash=H256>, N, RA, SC, VR, X, Sp>(
 	Ok(voter_work.select(on_exit.map(Ok).compat()).select2(telemetry_task).then(|_| Ok(())))
 }
 
+/// Report the equivocations sent to `equivocations` to the runtime of the best block.
+///
+/// The runtime calls block, so they are made from their own task rather than from the gossip
+/// validator or the voter. Only equivocations of a voter of the current set are reported, and
+/// only if we are a voter of the set as well.
+fn report_equivocations<B, E, Block: BlockT<Hash=H256>, RA>(
+	client: Arc<Client<B, E, Block, RA>>,
+	authority_set: SharedAuthoritySet<Block::Hash, NumberFor<Block>>,
+	keystore: Option<KeyStorePtr>,
+	equivocations: mpsc::UnboundedReceiver<EquivocationProof<Block::Hash, NumberFor<Block>>>,
+) -> impl Future<Item=(), Error=()> + Send + 'static where
+	B: Backend<Block, Blake2Hasher> + 'static,
+	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
+	RA: Send + Sync + 'static,
+{
+	equivocations.for_each(move |equivocation_proof| {
+		let voters = Arc::new(authority_set.current_authorities());
+		if equivocation_proof.set_id != authority_set.set_id() ||
+			!voters.contains_key(equivocation_proof.offender())
+		{
+			debug!(target: "afg", "Ignoring equivocation of {:?} not in the current set", equivocation_proof.offender());
+			return Ok(());
+		}
+
+		if is_voter(&voters, &keystore).is_none() {
+			return Ok(());
+		}
+
+		let submitted = client.submit_equivocation_report(
+			"GrandpaApi_submit_report_equivocation_extrinsic",
+			&equivocation_proof.encode(),
+		);
+		if let Err(e) = submitted {
+			warn!(target: "afg", "Failed to report equivocation: {:?}", e);
+		}
+
+		Ok(())
+	})
+}
+
 /// Future that powers the voter.
 #[must_use]
 struct VoterWork<B, E, Block: BlockT, N: NetworkT<Block>, RA, SC, VR> {
@@ -667,20 +737,24 @@ where
 		voting_rule: VR,
 		persistent_data: PersistentData<Block>,
 		voter_commands_rx: mpsc::UnboundedReceiver<VoterCommand<Block::Hash, NumberFor<Block>>>,
+		voter_state: SharedVoterState,
 	) -> Self {
 
-		let voters = persistent_data.authority_set.current_authorities();
+		let voters = Arc::new(persistent_data.authority_set.current_authorities());
+		let set_id = persistent_data.authority_set.set_id();
+		voter_state.new_set(set_id, voters.clone());
 		let env = Arc::new(Environment {
 			client,
 			select_chain,
 			voting_rule,
-			voters: Arc::new(voters),
+			voters,
 			config,
 			network,
-			set_id: persistent_data.authority_set.set_id(),
+			set_id,
 			authority_set: persistent_data.authority_set.clone(),
 			consensus_changes: persistent_data.consensus_changes.clone(),
 			voter_set_state: persistent_data.set_state.clone(),
+			voter_state,
 		});
 
 		let mut work = VoterWork {
@@ -701,7 +775,6 @@ where
 		debug!(target: "afg", "{}: Starting new voter with set ID {}", self.env.config.name(), self.env.set_id);
 
 		let authority_id = is_voter(&self.env.voters, &self.env.config.keystore)
-			.map(|ap| ap.public())
 			.unwrap_or(Default::default());
 
 		telemetry!(CONSENSUS_DEBUG; "afg.starting_new_voter";
@@ -787,10 +860,13 @@ where
 					Ok(Some(set_state))
 				})?;
 
+				let voters = Arc::new(new.authorities.into_iter().collect());
+				self.env.voter_state.new_set(new.set_id, Arc::clone(&voters));
 				self.env = Arc::new(Environment {
-					voters: Arc::new(new.authorities.into_iter().collect()),
+					voters,
 					set_id: new.set_id,
 					voter_set_state: self.env.voter_set_state.clone(),
+					voter_state: self.env.voter_state.clone(),
 					// Fields below are simply transferred and not updated.
 					client: self.env.client.clone(),
 					select_chain: self.env.select_chain.clone(),
@@ -926,14 +1002,19 @@ pub fn setup_disabled_grandpa<B, E, Block: BlockT<Hash=H256>, RA, N>(
 
 /// Checks if this node is a voter in the given voter set.
 ///
-/// Returns the key pair of the node that is being used in the current voter set or `None`.
+/// Returns the authority id of the node that is being used in the current voter set or `None`.
+/// Only the public key is looked up, the key pair stays in the keystore which signs the votes.
 fn is_voter(
 	voters: &Arc<VoterSet<AuthorityId>>,
 	keystore: &Option<KeyStorePtr>,
-) -> Option<AuthorityPair> {
+) -> Option<AuthorityId> {
 	match keystore {
-		Some(keystore) => voters.voters().iter()
-			.find_map(|(p, _)| keystore.read().key_pair::<AuthorityPair>(&p).ok()),
+		Some(keystore) => {
+			let local_keys = keystore.read().ed25519_public_keys(GRANDPA);
+			voters.voters().iter()
+				.find(|(p, _)| local_keys.iter().any(|k| k.as_slice() == p.as_slice()))
+				.map(|(p, _)| p.clone())
+		},
 		None => None,
 	}
 }
@@ -947,12 +1028,10 @@ fn authority_id<'a, I>(
 {
 	match keystore {
 		Some(keystore) => {
+			let local_keys = keystore.read().ed25519_public_keys(GRANDPA);
 			authorities
-				.find_map(|p| {
-					keystore.read().key_pair::<AuthorityPair>(&p)
-						.ok()
-						.map(|ap| ap.public())
-				})
+				.find(|p| local_keys.iter().any(|k| k.as_slice() == p.as_slice()))
+				.cloned()
 		}
 		None => None,
 	}
@@ -660,6 +660,7 @@ pub mod tests {
 			fork_choice: ForkChoiceStrategy::LongestChain,
 			allow_missing_state: true,
 			import_existing: false,
+			state: None,
 		};
 		do_import_block::<_, _, _, TestJustification>(
 			&client,
@@ -38,7 +38,7 @@ use parity_scale_codec::Decode;
 use sp_runtime::traits::{ApiRef, ProvideRuntimeApi, Header as HeaderT};
 use sp_runtime::generic::{BlockId, DigestItem};
 use sp_core::{NativeOrEncoded, ExecutionContext, crypto::Public};
-use sp_finality_grandpa::{GRANDPA_ENGINE_ID, AuthorityList, GrandpaApi};
+use sp_finality_grandpa::{GRANDPA_ENGINE_ID, AuthorityList, AuthorityPair, EquivocationProof, GrandpaApi};
 use sp_state_machine::{backend::InMemory, prove_read, read_proof_check};
 use std::{pin::Pin, task};
 
@@ -275,6 +275,16 @@ impl GrandpaApi<Block> for RuntimeApi {
 	) -> Result<NativeOrEncoded<AuthorityList>> {
 		Ok(self.inner.genesis_authorities.clone()).map(NativeOrEncoded::Native)
 	}
+
+	fn GrandpaApi_submit_report_equivocation_extrinsic_runtime_api_impl(
+		&self,
+		_: &BlockId<Block>,
+		_: ExecutionContext,
+		_: Option<EquivocationProof<Hash, NumberFor<Block>>>,
+		_: Vec<u8>,
+	) -> Result<NativeOrEncoded<Option<()>>> {
+		Ok(NativeOrEncoded::Native(None))
+	}
 }
 
 impl GenesisAuthoritySetProvider<Block> for TestApi {
@@ -408,6 +418,7 @@ fn run_to_completion_with<F>(
 			telemetry_on_connect: None,
 			voting_rule: (),
 			executor: threads_pool.clone(),
+			shared_voter_state: SharedVoterState::empty(),
 		};
 		let voter = run_grandpa_voter(grandpa_params).expect("all in order with client and network");
 
@@ -548,6 +559,7 @@ fn finalize_3_voters_1_full_observer() {
 			telemetry_on_connect: None,
 			voting_rule: (),
 			executor: threads_pool.clone(),
+			shared_voter_state: SharedVoterState::empty(),
 		};
 
 		voters.push(run_grandpa_voter(grandpa_params).expect("all in order with client and network"));
@@ -717,6 +729,7 @@ fn transition_3_voters_twice_1_full_observer() {
 			telemetry_on_connect: None,
 			voting_rule: (),
 			executor: threads_pool.clone(),
+			shared_voter_state: SharedVoterState::empty(),
 		};
 		let voter = run_grandpa_voter(grandpa_params).expect("all in order with client and network");
 
@@ -996,6 +1009,7 @@ fn allows_reimporting_change_blocks() {
 			fork_choice: ForkChoiceStrategy::LongestChain,
 			allow_missing_state: false,
 			import_existing: false,
+			state: None,
 		}
 	};
 
@@ -1050,6 +1064,7 @@ fn test_bad_justification() {
 			fork_choice: ForkChoiceStrategy::LongestChain,
 			allow_missing_state: false,
 			import_existing: false,
+			state: None,
 		}
 	};
 
@@ -1155,6 +1170,7 @@ fn voter_persists_its_votes() {
 							telemetry_on_connect: None,
 							voting_rule: VotingRulesBuilder::default().build(),
 							executor: self.threads_pool.clone(),
+							shared_voter_state: SharedVoterState::empty(),
 						};
 
 						let voter = run_grandpa_voter(grandpa_params)
@@ -1227,7 +1243,7 @@ fn voter_persists_its_votes() {
 			communication::Round(1),
 			communication::SetId(0),
 			Arc::new(VoterSet::from_iter(voters)),
-			Some(peers[1].pair().into()),
+			config.keystore.clone().map(|keystore| (peers[1].public().into(), keystore)),
 			HasVoted::No,
 		);
 
@@ -1505,6 +1521,7 @@ fn voter_catches_up_to_latest_round_when_behind() {
 			telemetry_on_connect: None,
 			voting_rule: (),
 			executor: threads_pool.clone(),
+			shared_voter_state: SharedVoterState::empty(),
 		};
 
 		Box::new(run_grandpa_voter(grandpa_params).expect("all in order with client and network"))
@@ -1637,6 +1654,7 @@ fn grandpa_environment_respects_voting_rules() {
 			set_id: authority_set.set_id(),
 			voter_set_state: set_state.clone(),
 			voters: Arc::new(authority_set.current_authorities()),
+			voter_state: SharedVoterState::empty(),
 			network,
 			voting_rule,
 		}
@@ -1781,6 +1799,7 @@ fn imports_justification_for_regular_blocks_on_import() {
 		fork_choice: ForkChoiceStrategy::LongestChain,
 		allow_missing_state: false,
 		import_existing: false,
+		state: None,
 	};
 
 	assert_eq!(
@@ -0,0 +1,254 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! State of the votes of the rounds the voter is running, shared with the outside world.
+//!
+//! The voter notes every prevote and precommit it processes, its own included, so that the
+//! operator can tell which authorities haven't voted in a stalled round. The operator can also
+//! pause the voting of the node through the same handle, e.g. during a key rotation.
+
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use log::info;
+use parking_lot::RwLock;
+
+use finality_grandpa::voter_set::VoterSet;
+use sp_finality_grandpa::{AuthorityId, RoundNumber, SetId};
+
+/// Number of rounds whose votes are kept: the best round and the background ones still running.
+const MAX_TRACKED_ROUNDS: usize = 2;
+
+/// Votes received in a round so far.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RoundState {
+	/// Number of the round.
+	pub round: RoundNumber,
+	/// Total weight of the authority set.
+	pub total_weight: u64,
+	/// Weight a vote must gather to be supermajority.
+	pub threshold_weight: u64,
+	/// Weight of the authorities that have prevoted.
+	pub prevote_current_weight: u64,
+	/// Authorities that haven't prevoted.
+	pub prevote_missing: Vec<AuthorityId>,
+	/// Weight of the authorities that have precommitted.
+	pub precommit_current_weight: u64,
+	/// Authorities that haven't precommitted.
+	pub precommit_missing: Vec<AuthorityId>,
+}
+
+/// State of the voter.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VoterState {
+	/// Id of the current authority set.
+	pub set_id: SetId,
+	/// Authorities of the current set along with their weight.
+	pub authorities: Vec<(AuthorityId, u64)>,
+	/// The most recent round.
+	pub best_round: RoundState,
+	/// The older rounds still running in the background, most recent first.
+	pub background_rounds: Vec<RoundState>,
+}
+
+#[derive(Default)]
+struct RoundVotes {
+	prevotes: HashSet<AuthorityId>,
+	precommits: HashSet<AuthorityId>,
+}
+
+struct Inner {
+	set_id: SetId,
+	voters: Arc<VoterSet<AuthorityId>>,
+	rounds: BTreeMap<RoundNumber, RoundVotes>,
+}
+
+/// Handle to the state of the voter, updated as it runs.
+///
+/// Pass a clone of it to the `GrandpaParams` to read the state of the voter from elsewhere, e.g.
+/// the `grandpa_roundState` RPC.
+#[derive(Clone, Default)]
+pub struct SharedVoterState {
+	inner: Arc<RwLock<Option<Inner>>>,
+	voting_paused: Arc<AtomicBool>,
+}
+
+impl SharedVoterState {
+	/// Create a handle to a voter that hasn't started yet.
+	pub fn empty() -> Self {
+		Self::default()
+	}
+
+	/// Returns the current state of the voter, or `None` if it hasn't started any round.
+	pub fn voter_state(&self) -> Option<VoterState> {
+		let inner = self.inner.read();
+		let inner = inner.as_ref()?;
+		let mut rounds = inner.rounds.iter().rev()
+			.map(|(round, votes)| round_state(&inner.voters, *round, votes));
+		let best_round = rounds.next()?;
+
+		Some(VoterState {
+			set_id: inner.set_id,
+			authorities: inner.voters.voters().to_vec(),
+			best_round,
+			background_rounds: rounds.collect(),
+		})
+	}
+
+	/// Make the voter stop casting votes, without stopping it: it keeps following the rounds and
+	/// finalizing the blocks committed by the other voters. The votes the voter would have cast
+	/// are dropped before being signed.
+	pub fn pause_voting(&self) {
+		if !self.voting_paused.swap(true, Ordering::SeqCst) {
+			info!(target: "afg", "Pausing GRANDPA voting");
+		}
+	}
+
+	/// Make the voter cast votes again, starting with the next vote it would cast.
+	pub fn resume_voting(&self) {
+		if self.voting_paused.swap(false, Ordering::SeqCst) {
+			info!(target: "afg", "Resuming GRANDPA voting");
+		}
+	}
+
+	/// Returns true if the voting has been paused.
+	pub fn is_voting_paused(&self) -> bool {
+		self.voting_paused.load(Ordering::SeqCst)
+	}
+
+	/// Note that the voter of a new authority set is starting.
+	pub(crate) fn new_set(&self, set_id: SetId, voters: Arc<VoterSet<AuthorityId>>) {
+		*self.inner.write() = Some(Inner { set_id, voters, rounds: BTreeMap::new() });
+	}
+
+	/// Note that the voter is starting a round.
+	pub(crate) fn new_round(&self, round: RoundNumber) {
+		if let Some(inner) = self.inner.write().as_mut() {
+			inner.rounds.entry(round).or_default();
+			while inner.rounds.len() > MAX_TRACKED_ROUNDS {
+				let oldest = *inner.rounds.keys().next().expect("more rounds than the maximum; qed");
+				inner.rounds.remove(&oldest);
+			}
+		}
+	}
+
+	/// Note a prevote of the given authority.
+	pub(crate) fn note_prevote(&self, round: RoundNumber, id: &AuthorityId) {
+		self.with_round(round, id, |votes| &mut votes.prevotes);
+	}
+
+	/// Note a precommit of the given authority.
+	pub(crate) fn note_precommit(&self, round: RoundNumber, id: &AuthorityId) {
+		self.with_round(round, id, |votes| &mut votes.precommits);
+	}
+
+	fn with_round(
+		&self,
+		round: RoundNumber,
+		id: &AuthorityId,
+		votes: impl FnOnce(&mut RoundVotes) -> &mut HashSet<AuthorityId>,
+	) {
+		if let Some(inner) = self.inner.write().as_mut() {
+			if inner.voters.info(id).is_none() {
+				return;
+			}
+			if let Some(round_votes) = inner.rounds.get_mut(&round) {
+				votes(round_votes).insert(id.clone());
+			}
+		}
+	}
+}
+
+fn round_state(voters: &VoterSet<AuthorityId>, round: RoundNumber, votes: &RoundVotes) -> RoundState {
+	let tally = |voted: &HashSet<AuthorityId>| {
+		let mut weight = 0;
+		let mut missing = Vec::new();
+		for (id, voter_weight) in voters.voters() {
+			if voted.contains(id) {
+				weight += voter_weight;
+			} else {
+				missing.push(id.clone());
+			}
+		}
+		(weight, missing)
+	};
+	let (prevote_current_weight, prevote_missing) = tally(&votes.prevotes);
+	let (precommit_current_weight, precommit_missing) = tally(&votes.precommits);
+
+	RoundState {
+		round,
+		total_weight: voters.total_weight(),
+		threshold_weight: voters.threshold(),
+		prevote_current_weight,
+		prevote_missing,
+		precommit_current_weight,
+		precommit_missing,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::crypto::Public;
+
+	#[test]
+	fn tallies_votes_of_tracked_rounds() {
+		let ids: Vec<AuthorityId> = (0..3u8).map(|i| AuthorityId::from_slice(&[i; 32])).collect();
+		let voters: VoterSet<AuthorityId> = ids.iter().cloned().map(|id| (id, 1)).collect();
+		let state = SharedVoterState::empty();
+		assert!(state.voter_state().is_none());
+
+		state.new_set(1, Arc::new(voters));
+		assert!(state.voter_state().is_none());
+
+		state.new_round(1);
+		state.new_round(2);
+		state.note_prevote(2, &ids[0]);
+		state.note_prevote(2, &ids[1]);
+		state.note_precommit(2, &ids[1]);
+		state.note_prevote(2, &AuthorityId::from_slice(&[9; 32]));
+
+		let voter_state = state.voter_state().unwrap();
+		assert_eq!(voter_state.set_id, 1);
+		assert_eq!(voter_state.best_round.round, 2);
+		assert_eq!(voter_state.best_round.prevote_current_weight, 2);
+		assert_eq!(voter_state.best_round.prevote_missing, vec![ids[2].clone()]);
+		assert_eq!(voter_state.best_round.precommit_current_weight, 1);
+		assert_eq!(voter_state.background_rounds.len(), 1);
+		assert_eq!(voter_state.background_rounds[0].round, 1);
+
+		state.new_round(3);
+		let voter_state = state.voter_state().unwrap();
+		assert_eq!(voter_state.best_round.round, 3);
+		assert_eq!(voter_state.background_rounds[0].round, 2);
+		assert_eq!(voter_state.background_rounds.len(), 1);
+	}
+
+	#[test]
+	fn voting_pause_is_shared_between_clones() {
+		let state = SharedVoterState::empty();
+		let handle = state.clone();
+		assert!(!state.is_voting_paused());
+
+		handle.pause_voting();
+		assert!(state.is_voting_paused());
+		handle.pause_voting();
+		assert!(state.is_voting_paused());
+
+		state.resume_voting();
+		assert!(!handle.is_voting_paused());
+	}
+}
@@ -0,0 +1,259 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! GRANDPA warp sync proof generation and check.
+//!
+//! A warp sync proof proves the finality of a recent block to a node that only knows the
+//! genesis block, without having it download the blocks in between. It is made of:
+//! 1) the justification of each block enacting a standard authority set change, along with a
+//! proof of GRANDPA::authorities() at that block, giving the set that signs the next
+//! justifications;
+//! 2) the justification of the latest block finalized by the last authority set, if any.
+//!
+//! Proofs are limited in size, in which case the node requests another proof starting at the
+//! last authority set change it verified. The authority set proven live at the final block is
+//! stored in the aux db, and applied when that block is imported along with its state.
+//!
+//! Forced authority set changes can't be proven this way. Warp sync fails on chains that had
+//! any.
+
+use std::iter;
+use std::sync::Arc;
+use log::debug;
+use parking_lot::Mutex;
+
+use sp_blockchain::{Backend as BlockchainBackend, Error as ClientError, Result as ClientResult};
+use sc_client_api::{backend::Backend, StorageProof, CompactProof};
+use parity_scale_codec::{Encode, Decode};
+use finality_grandpa::{BlockNumberOps, voter_set::VoterSet};
+use sp_runtime::{
+	Justification, generic::BlockId,
+	traits::{NumberFor, Block as BlockT, Header as HeaderT, One, Zero},
+};
+use sp_core::{H256, Blake2Hasher};
+use sp_finality_grandpa::{AuthorityList, SetId, VersionedAuthorityList, GRANDPA_AUTHORITIES_KEY};
+
+use crate::aux_schema;
+use crate::finality_proof::AuthoritySetForFinalityProver;
+use crate::import::find_scheduled_change;
+use crate::justification::GrandpaJustification;
+
+/// Maximum number of authority set changes proven by a single warp sync proof.
+const MAX_FRAGMENTS_IN_PROOF: usize = 64;
+
+/// Justification of a block, along with the proof of the new authority set if the block enacts
+/// an authority set change.
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WarpSyncFragment<Header: HeaderT> {
+	/// The header of the justified block.
+	pub header: Header,
+	/// Justification of the block, signed by the authority set of the previous fragment.
+	pub justification: Justification,
+	/// Proof of GRANDPA::authorities() at the block, if it enacts an authority set change.
+	pub authorities_proof: Option<CompactProof>,
+}
+
+/// Warp sync proof, the ordered list of fragments starting after the requested block.
+#[derive(Debug, PartialEq, Encode, Decode)]
+struct WarpSyncProof<Header: HeaderT> {
+	/// The fragments. Only the last one may come without an authorities proof.
+	pub fragments: Vec<WarpSyncFragment<Header>>,
+	/// Whether the last fragment is the latest one available. Otherwise another proof has to be
+	/// requested, starting at the last fragment.
+	pub is_finished: bool,
+}
+
+/// Warp sync proof provider for serving network requests and verifying their responses.
+pub struct WarpSyncProofProvider<B, Block: BlockT<Hash=H256>> {
+	backend: Arc<B>,
+	authority_provider: Arc<dyn AuthoritySetForFinalityProver<Block>>,
+	/// Number of the last verified block and the authority set live at that block.
+	verified: Mutex<(NumberFor<Block>, SetId, AuthorityList)>,
+}
+
+impl<B, Block: BlockT<Hash=H256>> WarpSyncProofProvider<B, Block>
+	where B: Backend<Block, Blake2Hasher> + Send + Sync + 'static
+{
+	/// Create new warp sync proof provider using:
+	///
+	/// - backend for accessing blockchain data;
+	/// - authority_provider for reading and proving the authority sets.
+	///
+	/// The genesis authority set is the starting point of the verification.
+	pub fn new(
+		backend: Arc<B>,
+		authority_provider: Arc<dyn AuthoritySetForFinalityProver<Block>>,
+	) -> ClientResult<Self> {
+		let genesis_authorities = authority_provider.authorities(&BlockId::Number(Zero::zero()))?;
+		Ok(WarpSyncProofProvider {
+			backend,
+			authority_provider,
+			verified: Mutex::new((Zero::zero(), 0, genesis_authorities)),
+		})
+	}
+}
+
+impl<B, Block> sc_network::WarpSyncProvider<Block> for WarpSyncProofProvider<B, Block>
+	where
+		Block: BlockT<Hash=H256>,
+		NumberFor<Block>: BlockNumberOps,
+		B: Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+{
+	fn generate(&self, begin: Block::Hash) -> ClientResult<Vec<u8>> {
+		generate_warp_sync_proof::<Block, _>(&*self.backend.blockchain(), &*self.authority_provider, begin)
+			.map(|proof| proof.encode())
+	}
+
+	fn verify(&self, proof: &[u8]) -> ClientResult<sc_network::WarpSyncVerification<Block>> {
+		let proof = WarpSyncProof::<Block::Header>::decode(&mut &proof[..])
+			.map_err(|_| ClientError::BadJustification("failed to decode warp sync proof".into()))?;
+
+		let mut verified = self.verified.lock();
+		let (number, set_id, authorities) = verify_warp_sync_proof::<Block>(&*verified, &proof)?;
+		let last = proof.fragments.last().expect("verified proofs have at least one fragment; qed");
+		let hash = last.header.hash();
+
+		let verification = if proof.is_finished {
+			aux_schema::write_warp_sync_target(&*self.backend, hash, set_id, &authorities)?;
+			sc_network::WarpSyncVerification::Complete(last.header.clone(), last.justification.clone())
+		} else {
+			sc_network::WarpSyncVerification::Partial(hash)
+		};
+
+		*verified = (number, set_id, authorities);
+		Ok(verification)
+	}
+}
+
+/// Prove the finality of the blocks enacting authority set changes after the given finalized
+/// block, and of the latest possible finalized block.
+fn generate_warp_sync_proof<Block: BlockT<Hash=H256>, B: BlockchainBackend<Block>>(
+	blockchain: &B,
+	authority_provider: &dyn AuthoritySetForFinalityProver<Block>,
+	begin: Block::Hash,
+) -> ClientResult<WarpSyncProof<Block::Header>> {
+	let begin_number = blockchain.expect_block_number_from_id(&BlockId::Hash(begin))?;
+	let finalized_number = blockchain.info().finalized_number;
+	if begin_number > finalized_number || blockchain.hash(begin_number)? != Some(begin) {
+		return Err(ClientError::Backend(
+			format!("Warp sync proof requested from {:?}, which isn't finalized", begin)
+		));
+	}
+
+	let mut fragments = Vec::new();
+	let mut latest = None;
+	let mut pending_enactment = None;
+	let mut number = begin_number;
+	while number < finalized_number {
+		number = number + One::one();
+		let header = blockchain.expect_header(BlockId::Number(number))?;
+		if let Some(change) = find_scheduled_change::<Block>(&header) {
+			pending_enactment = Some(number + change.delay);
+		}
+
+		let justification = blockchain.justification(BlockId::Number(number))?;
+		if pending_enactment == Some(number) {
+			let justification = justification.ok_or_else(|| ClientError::Backend(
+				format!("Missing justification for block #{} enacting an authority set change", number)
+			))?;
+			let authorities_proof = authority_provider.prove_authorities(&BlockId::Number(number))?;
+			fragments.push(WarpSyncFragment { header, justification, authorities_proof: Some(authorities_proof) });
+			pending_enactment = None;
+			latest = None;
+
+			if fragments.len() == MAX_FRAGMENTS_IN_PROOF {
+				return Ok(WarpSyncProof { fragments, is_finished: false });
+			}
+		} else if pending_enactment.is_none() {
+			// Blocks importing a signaled but not yet enacted change are left out, as the node
+			// importing their state wouldn't know about the change.
+			if let Some(justification) = justification {
+				latest = Some(WarpSyncFragment { header, justification, authorities_proof: None });
+			}
+		}
+	}
+
+	fragments.extend(latest);
+	if fragments.is_empty() {
+		return Err(ClientError::Backend(format!("No justified block after #{}", begin_number)));
+	}
+
+	debug!(target: "afg", "Generated warp sync proof of {} fragments after #{}", fragments.len(), begin_number);
+	Ok(WarpSyncProof { fragments, is_finished: true })
+}
+
+/// Verify a warp sync proof starting at a block with the given number and authority set.
+/// Returns the number of the last block of the proof and the authority set live at that block.
+fn verify_warp_sync_proof<Block: BlockT<Hash=H256>>(
+	verified: &(NumberFor<Block>, SetId, AuthorityList),
+	proof: &WarpSyncProof<Block::Header>,
+) -> ClientResult<(NumberFor<Block>, SetId, AuthorityList)>
+	where NumberFor<Block>: BlockNumberOps,
+{
+	let (mut number, mut set_id, mut authorities) = verified.clone();
+	if proof.fragments.is_empty() {
+		return Err(ClientError::BadJustification("empty warp sync proof".into()));
+	}
+
+	for (index, fragment) in proof.fragments.iter().enumerate() {
+		if *fragment.header.number() <= number {
+			return Err(ClientError::BadJustification("warp sync proof fragments out of order".into()));
+		}
+
+		let voters: VoterSet<_> = authorities.iter().cloned().collect();
+		GrandpaJustification::<Block>::decode_and_verify_finalizes(
+			&fragment.justification,
+			(fragment.header.hash(), *fragment.header.number()),
+			set_id,
+			&voters,
+		)?;
+
+		match fragment.authorities_proof {
+			Some(ref authorities_proof) => {
+				authorities = check_authorities_proof::<Block>(&fragment.header, authorities_proof.clone())?;
+				set_id += 1;
+			},
+			None if index + 1 == proof.fragments.len() && proof.is_finished => {},
+			None => return Err(ClientError::BadJustification(
+				"missing authorities proof in warp sync proof".into()
+			)),
+		}
+
+		number = *fragment.header.number();
+	}
+
+	Ok((number, set_id, authorities))
+}
+
+/// Check the proof of GRANDPA::authorities() against the state root of the given header.
+fn check_authorities_proof<Block: BlockT<Hash=H256>>(
+	header: &Block::Header,
+	proof: CompactProof,
+) -> ClientResult<AuthorityList> {
+	let proof = StorageProof::from_compact_proof::<Blake2Hasher>(&proof)
+		.map_err(|e| ClientError::Execution(Box::new(e)))?;
+	let mut values = sp_state_machine::read_proof_check::<Blake2Hasher, _>(
+		*header.state_root(),
+		proof,
+		iter::once(GRANDPA_AUTHORITIES_KEY),
+	).map_err(ClientError::from_state)?;
+
+	values.remove(GRANDPA_AUTHORITIES_KEY)
+		.and_then(|value| value)
+		.and_then(|encoded| VersionedAuthorityList::decode(&mut encoded.as_slice()).ok())
+		.map(|versioned| versioned.into())
+		.ok_or(ClientError::InvalidAuthoritiesSet)
+}
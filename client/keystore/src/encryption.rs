@@ -0,0 +1,156 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Encryption of the key files.
+//!
+//! The encryption and MAC keys are derived from the password with PBKDF2-HMAC-SHA256. The
+//! secret is encrypted with AES-256-CTR, then authenticated with HMAC-SHA256.
+
+use aes_ctr::{Aes256Ctr, stream_cipher::{NewStreamCipher, SyncStreamCipher}};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Serialize, Deserialize};
+use sha2::Sha256;
+
+/// Number of PBKDF2 rounds for newly encrypted files.
+#[cfg(not(test))]
+const KDF_ROUNDS: u32 = 600_000;
+/// Number of PBKDF2 rounds in tests, which would be too slow otherwise.
+#[cfg(test)]
+const KDF_ROUNDS: u32 = 1_024;
+
+/// Maximum number of PBKDF2 rounds accepted in a key file, so that a tampered file cannot make
+/// the node spin on it.
+const MAX_KDF_ROUNDS: u32 = 10_000_000;
+
+/// Content of an encrypted key file, with hex-encoded binary fields.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Encrypted {
+	version: u32,
+	salt: String,
+	rounds: u32,
+	nonce: String,
+	ciphertext: String,
+	mac: String,
+}
+
+impl Encrypted {
+	/// Returns true if the file is encrypted with fewer rounds than newly encrypted files, and
+	/// should thus be encrypted again.
+	pub fn is_outdated(&self) -> bool {
+		self.rounds < KDF_ROUNDS
+	}
+}
+
+/// Encrypts `plaintext` with a key derived from `password`.
+pub fn encrypt(password: &str, plaintext: &[u8]) -> Encrypted {
+	encrypt_with_rounds(password, plaintext, KDF_ROUNDS)
+}
+
+fn encrypt_with_rounds(password: &str, plaintext: &[u8], rounds: u32) -> Encrypted {
+	let mut salt = [0u8; 32];
+	let mut nonce = [0u8; 16];
+	rand::thread_rng().fill_bytes(&mut salt);
+	rand::thread_rng().fill_bytes(&mut nonce);
+
+	let (encryption_key, mac_key) = derive_keys(password, &salt, rounds);
+	let mut ciphertext = plaintext.to_vec();
+	apply_keystream(&encryption_key, &nonce, &mut ciphertext);
+
+	Encrypted {
+		version: 1,
+		salt: hex::encode(salt),
+		rounds,
+		nonce: hex::encode(nonce),
+		mac: hex::encode(mac(&mac_key, &nonce, &ciphertext).result().code()),
+		ciphertext: hex::encode(ciphertext),
+	}
+}
+
+/// Decrypts the file with a key derived from `password`.
+///
+/// Returns `None` if the file is malformed, e.g. has more than `MAX_KDF_ROUNDS` rounds, or the
+/// password is wrong.
+pub fn decrypt(password: &str, encrypted: &Encrypted) -> Option<Vec<u8>> {
+	if encrypted.version != 1 || encrypted.rounds == 0 || encrypted.rounds > MAX_KDF_ROUNDS {
+		return None;
+	}
+	let salt = hex::decode(&encrypted.salt).ok()?;
+	let nonce = hex::decode(&encrypted.nonce).ok()?;
+	let mut data = hex::decode(&encrypted.ciphertext).ok()?;
+	let expected_mac = hex::decode(&encrypted.mac).ok()?;
+
+	let (encryption_key, mac_key) = derive_keys(password, &salt, encrypted.rounds);
+	mac(&mac_key, &nonce, &data).verify(&expected_mac).ok()?;
+	if nonce.len() != 16 {
+		return None;
+	}
+	apply_keystream(&encryption_key, &nonce, &mut data);
+	Some(data)
+}
+
+fn derive_keys(password: &str, salt: &[u8], rounds: u32) -> ([u8; 32], [u8; 32]) {
+	let mut derived = [0u8; 64];
+	pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, rounds as usize, &mut derived);
+
+	let (mut encryption_key, mut mac_key) = ([0u8; 32], [0u8; 32]);
+	encryption_key.copy_from_slice(&derived[..32]);
+	mac_key.copy_from_slice(&derived[32..]);
+	(encryption_key, mac_key)
+}
+
+fn apply_keystream(key: &[u8; 32], nonce: &[u8], data: &mut [u8]) {
+	Aes256Ctr::new_var(key, nonce)
+		.expect("Key and nonce have the lengths of AES-256-CTR; qed")
+		.apply_keystream(data);
+}
+
+fn mac(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) -> Hmac<Sha256> {
+	let mut mac = Hmac::<Sha256>::new_varkey(key).expect("HMAC accepts keys of any length; qed");
+	mac.input(nonce);
+	mac.input(ciphertext);
+	mac
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn decrypts_only_with_the_right_password() {
+		let encrypted = encrypt("password", b"secret phrase");
+
+		assert_eq!(decrypt("password", &encrypted), Some(b"secret phrase".to_vec()));
+		assert_eq!(decrypt("other password", &encrypted), None);
+		assert!(!encrypted.is_outdated());
+	}
+
+	#[test]
+	fn rejects_unbounded_rounds() {
+		let mut encrypted = encrypt_with_rounds("password", b"secret phrase", MAX_KDF_ROUNDS + 1);
+		assert_eq!(decrypt("password", &encrypted), None);
+
+		encrypted.rounds = 0;
+		assert_eq!(decrypt("password", &encrypted), None);
+	}
+
+	#[test]
+	fn files_with_fewer_rounds_are_outdated() {
+		let encrypted = encrypt_with_rounds("password", b"secret phrase", KDF_ROUNDS - 1);
+		assert_eq!(decrypt("password", &encrypted), Some(b"secret phrase".to_vec()));
+		assert!(encrypted.is_outdated());
+	}
+}
@@ -18,15 +18,28 @@
 
 #![warn(missing_docs)]
 
-use std::{collections::HashMap, path::PathBuf, fs::{self, File}, io::{self, Write}, sync::Arc};
+pub mod remote;
+mod encryption;
+
+use std::{
+	collections::HashMap, convert::TryFrom, path::{Path, PathBuf}, fs::{self, File}, io::{self, Write},
+	sync::Arc,
+};
 
 use sp_core::{
-	crypto::{KeyTypeId, Pair as PairT, Public, IsWrappedBy, Protected}, traits::BareCryptoStore,
+	crypto::{
+		KeyTypeId, Pair as PairT, Public, IsWrappedBy, Protected, CryptoTypeId, CryptoTypePublicPair,
+	},
+	traits::{BareCryptoStore, SignatureFuture, sign_with_key_pair}, ecdsa, bls381,
+	vrf::{self, VRFTranscriptData, VRFSignature},
 };
 
 use sp_application_crypto::{AppKey, AppPublic, AppPair, ed25519, sr25519};
 
-use parking_lot::RwLock;
+use futures::future;
+use parking_lot::{Mutex, RwLock};
+
+use serde::{Serialize, Deserialize};
 
 /// Keystore pointer
 pub type KeyStorePtr = Arc<RwLock<Store>>;
@@ -50,11 +63,35 @@ pub enum Error {
 	/// Keystore unavailable
 	#[display(fmt="Keystore unavailable")]
 	Unavailable,
+	/// Remote signer error.
+	#[display(fmt="Remote signer error: {}", _0)]
+	#[from(ignore)]
+	Remote(String),
 }
 
 /// Keystore Result
 pub type Result<T> = std::result::Result<T, Error>;
 
+/// Secret of a key.
+#[derive(Clone, Serialize, Deserialize)]
+struct KeySecret {
+	/// Secret URI of the key.
+	suri: String,
+	/// Password the key pair is derived with, which is the password of the store when the key
+	/// was added. It stays the same when the password of the store changes.
+	password: Option<String>,
+}
+
+/// Content of a key file.
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum KeyFile {
+	/// Secret URI in plain text, written by stores without password.
+	Plain(String),
+	/// [`KeySecret`] encrypted with the password of the store.
+	Encrypted(encryption::Encrypted),
+}
+
 impl std::error::Error for Error {
 	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
 		match self {
@@ -70,24 +107,69 @@ impl std::error::Error for Error {
 /// Stores key pairs in a file system store + short lived key pairs in memory.
 ///
 /// Every pair that is being generated by a `seed`, will be placed in memory.
+///
+/// The key files of a store with a password are encrypted with it. The secrets are decrypted
+/// once per session, and kept in memory afterwards.
+///
+/// A store opened with [`Store::open_remote`] holds no key itself, and delegates generating keys
+/// and signing to a remote signer instead. Key pairs cannot be retrieved from such a store.
 pub struct Store {
 	path: PathBuf,
 	additional: HashMap<(KeyTypeId, Vec<u8>), Vec<u8>>,
 	password: Option<Protected<String>>,
+	remote: Option<remote::RemoteSigner>,
+	/// Secrets of the encrypted key files read so far, by path.
+	decrypted: Mutex<HashMap<PathBuf, KeySecret>>,
 }
 
 impl Store {
 	/// Open the store at the given path.
 	///
-	/// Optionally takes a password that will be used to encrypt/decrypt the keys.
+	/// Optionally takes a password that will be used to encrypt/decrypt the keys. Key files
+	/// written in plain text, before the store had a password, are encrypted with it.
 	pub fn open<T: Into<PathBuf>>(path: T, password: Option<Protected<String>>) -> Result<KeyStorePtr> {
 		let path = path.into();
 		fs::create_dir_all(&path)?;
 
-		let instance = Self { path, additional: HashMap::new(), password };
+		let instance = Self {
+			path,
+			additional: HashMap::new(),
+			password,
+			remote: None,
+			decrypted: Mutex::new(HashMap::new()),
+		};
+		if instance.password.is_some() {
+			instance.encrypt_plain_key_files()?;
+		}
+		Ok(Arc::new(RwLock::new(instance)))
+	}
+
+	/// Open a store backed by the remote signer at the given URI, see [`remote::RemoteSigner::connect`].
+	///
+	/// The password, if any, is the token authenticating the node to the signer.
+	pub fn open_remote(uri: &str, password: Option<Protected<String>>) -> Result<KeyStorePtr> {
+		let token = password.map(|p| p.as_str().to_owned()).unwrap_or_default();
+		let remote = remote::RemoteSigner::connect(uri, token).map_err(Error::Remote)?;
+
+		let instance = Self {
+			path: PathBuf::new(),
+			additional: HashMap::new(),
+			password: None,
+			remote: Some(remote),
+			decrypted: Mutex::new(HashMap::new()),
+		};
 		Ok(Arc::new(RwLock::new(instance)))
 	}
 
+	/// Fail for the operations a remote store cannot perform, because they need the secret keys.
+	fn ensure_local(&self) -> Result<()> {
+		if self.remote.is_some() {
+			Err(Error::Unavailable)
+		} else {
+			Ok(())
+		}
+	}
+
 	/// Get the public/private key pair for the given public key and key type.
 	fn get_additional_pair<Pair: PairT>(
 		&self,
@@ -113,16 +195,18 @@ impl Store {
 	///
 	/// Places it into the file system store.
 	fn insert_unknown(&self, key_type: KeyTypeId, suri: &str, public: &[u8]) -> Result<()> {
-		let mut file = File::create(self.key_file_path(public, key_type)).map_err(Error::Io)?;
-		serde_json::to_writer(&file, &suri).map_err(Error::Json)?;
-		file.flush().map_err(Error::Io)?;
-		Ok(())
+		if let Some(remote) = &self.remote {
+			return remote.insert_key(key_type, suri, public).map_err(Error::Remote);
+		}
+
+		self.write_key_file(&self.key_file_path(public, key_type), &self.new_secret(suri))
 	}
 
 	/// Insert a new key.
 	///
 	/// Places it into the file system store.
 	pub fn insert_by_type<Pair: PairT>(&self, key_type: KeyTypeId, suri: &str) -> Result<Pair> {
+		self.ensure_local()?;
 		let pair = Pair::from_string(
 			suri,
 			self.password.as_ref().map(|p| &***p)
@@ -143,10 +227,10 @@ impl Store {
 	///
 	/// Places it into the file system store.
 	pub fn generate_by_type<Pair: PairT>(&self, key_type: KeyTypeId) -> Result<Pair> {
+		self.ensure_local()?;
 		let (pair, phrase, _) = Pair::generate_with_phrase(self.password.as_ref().map(|p| &***p));
-		let mut file = File::create(self.key_file_path(pair.public().as_slice(), key_type))?;
-		serde_json::to_writer(&file, &phrase)?;
-		file.flush()?;
+		let path = self.key_file_path(pair.public().as_slice(), key_type);
+		self.write_key_file(&path, &self.new_secret(&phrase))?;
 		Ok(pair)
 	}
 
@@ -165,6 +249,7 @@ impl Store {
 		seed: &str,
 		key_type: KeyTypeId,
 	) -> Result<Pair> {
+		self.ensure_local()?;
 		let pair = Pair::from_string(seed, None).map_err(|_| Error::InvalidSeed)?;
 		self.insert_ephemeral_pair(&pair, key_type);
 		Ok(pair)
@@ -182,17 +267,16 @@ impl Store {
 		public: &Pair::Public,
 		key_type: KeyTypeId,
 	) -> Result<Pair> {
+		self.ensure_local()?;
 		if let Some(pair) = self.get_additional_pair(public, key_type)? {
 			return Ok(pair)
 		}
 
 		let path = self.key_file_path(public.as_slice(), key_type);
-		let file = File::open(path)?;
-
-		let phrase: String = serde_json::from_reader(&file)?;
+		let secret = self.read_key_file(&path)?;
 		let pair = Pair::from_string(
-			&phrase,
-			self.password.as_ref().map(|p| &***p),
+			&secret.suri,
+			secret.password.as_ref().map(String::as_str),
 		).map_err(|_| Error::InvalidPhrase)?;
 
 		if &pair.public() == public {
@@ -208,15 +292,27 @@ impl Store {
 	}
 
 	/// Get public keys of all stored keys that match the given key type.
+	///
+	/// Not available for a remote store, which needs to know the crypto of the keys.
 	pub fn public_keys_by_type<TPublic: Public>(&self, key_type: KeyTypeId) -> Result<Vec<TPublic>> {
-		let mut public_keys: Vec<TPublic> = self.additional.keys()
-			.filter_map(|(ty, public)| {
-				if *ty == key_type {
-					Some(TPublic::from_slice(public))
-				} else {
-					None
-				}
-			})
+		// Keys of other cryptos can share the key type, skip the ones of another length.
+		let length = TPublic::default().as_ref().len();
+		Ok(self.raw_public_keys(key_type)?
+			.into_iter()
+			.filter(|public| public.len() == length)
+			.map(|public| TPublic::from_slice(&public))
+			.collect())
+	}
+
+	/// Get the raw public keys of all stored keys that match the given key type.
+	///
+	/// In contrast to [`Store::public_keys_by_type`] no assumption on the crypto, and thereby the
+	/// length, of the keys is made.
+	fn raw_public_keys(&self, key_type: KeyTypeId) -> Result<Vec<Vec<u8>>> {
+		self.ensure_local()?;
+		let mut public_keys: Vec<Vec<u8>> = self.additional.keys()
+			.filter(|(ty, _)| *ty == key_type)
+			.map(|(_, public)| public.clone())
 			.collect();
 
 		for entry in fs::read_dir(&self.path)? {
@@ -228,8 +324,7 @@ impl Store {
 				match hex::decode(name) {
 					Ok(ref hex) if hex.len() > 4 => {
 						if &hex[0..4] != &key_type.0 { continue	}
-						let public = TPublic::from_slice(&hex[4..]);
-						public_keys.push(public);
+						public_keys.push(hex[4..].to_vec());
 					}
 					_ => continue,
 				}
@@ -250,6 +345,120 @@ impl Store {
 			.map(|v| v.into_iter().map(Into::into).collect())
 	}
 
+	/// Change the password of the store, and re-encrypt every key file with the new password.
+	///
+	/// The key pairs stay the same, as they are still derived with the password they were added
+	/// with. Nothing is changed if a key file cannot be read with the current password.
+	pub fn rotate_password(&mut self, password: Protected<String>) -> Result<()> {
+		self.ensure_local()?;
+		let secrets = self.key_file_paths()?
+			.into_iter()
+			.map(|path| self.read_key_file(&path).map(|secret| (path, secret)))
+			.collect::<Result<Vec<_>>>()?;
+
+		self.password = Some(password);
+		for (path, secret) in secrets {
+			self.write_key_file(&path, &secret)?;
+		}
+		Ok(())
+	}
+
+	/// Encrypt the key files written in plain text with the password of the store.
+	///
+	/// Only the keys actually derived with the password are encrypted, so that opening the store
+	/// with a wrong password doesn't lock the keys behind it.
+	fn encrypt_plain_key_files(&self) -> Result<()> {
+		for path in self.key_file_paths()? {
+			let file = File::open(&path)?;
+			if let KeyFile::Plain(_) = serde_json::from_reader(&file)? {
+				let secret = self.read_key_file(&path)?;
+				let public = path.file_name()
+					.and_then(|name| name.to_str())
+					.and_then(|name| hex::decode(name).ok())
+					.map(|hex| hex[4..].to_vec())
+					.unwrap_or_default();
+				if derives_public(&secret, &public) {
+					self.write_key_file(&path, &secret)?;
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// The secret of a key added to the store now.
+	fn new_secret(&self, suri: &str) -> KeySecret {
+		KeySecret {
+			suri: suri.to_owned(),
+			password: self.password.as_ref().map(|p| p.as_str().to_owned()),
+		}
+	}
+
+	/// Read the secret from the given key file.
+	///
+	/// Encrypted files are only decrypted the first time they are read. They are encrypted again
+	/// if they were encrypted with fewer KDF rounds than new files, on a best-effort basis.
+	fn read_key_file(&self, path: &Path) -> Result<KeySecret> {
+		let file = File::open(path)?;
+		if let Some(secret) = self.decrypted.lock().get(path) {
+			return Ok(secret.clone());
+		}
+
+		match serde_json::from_reader(&file)? {
+			KeyFile::Plain(suri) => Ok(self.new_secret(&suri)),
+			KeyFile::Encrypted(encrypted) => {
+				let password = self.password.as_ref().ok_or(Error::InvalidPassword)?;
+				let secret = encryption::decrypt(password.as_str(), &encrypted).ok_or(Error::InvalidPassword)?;
+				let secret: KeySecret = serde_json::from_slice(&secret)?;
+				self.decrypted.lock().insert(path.to_owned(), secret.clone());
+				if encrypted.is_outdated() {
+					let _ = self.write_key_file(path, &secret);
+				}
+				Ok(secret)
+			},
+		}
+	}
+
+	/// Write the secret to the given key file, encrypted if the store has a password.
+	///
+	/// The file is replaced atomically, so that a failure never loses an existing key.
+	fn write_key_file(&self, path: &Path, secret: &KeySecret) -> Result<()> {
+		let content = match &self.password {
+			Some(password) => KeyFile::Encrypted(
+				encryption::encrypt(password.as_str(), &serde_json::to_vec(secret)?)
+			),
+			None => KeyFile::Plain(secret.suri.clone()),
+		};
+		self.decrypted.lock().remove(path);
+
+		// Not a valid key file name, so never taken for a key.
+		let temporary_path = path.with_extension("tmp");
+		let mut file = File::create(&temporary_path)?;
+		serde_json::to_writer(&file, &content)?;
+		file.flush()?;
+		fs::rename(&temporary_path, path)?;
+		if self.password.is_some() {
+			self.decrypted.lock().insert(path.to_owned(), secret.clone());
+		}
+		Ok(())
+	}
+
+	/// Returns the paths of all key files.
+	fn key_file_paths(&self) -> Result<Vec<PathBuf>> {
+		let mut paths = Vec::new();
+		for entry in fs::read_dir(&self.path)? {
+			let path = entry?.path();
+
+			// skip directories and non-unicode file names (hex is unicode)
+			if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
+				match hex::decode(name) {
+					Ok(ref hex) if hex.len() > 4 => paths.push(path),
+					_ => continue,
+				}
+			}
+		}
+		Ok(paths)
+	}
+
 	/// Returns the file path for the given public key and key type.
 	fn key_file_path(&self, public: &[u8], key_type: KeyTypeId) -> PathBuf {
 		let mut buf = self.path.clone();
@@ -258,10 +467,83 @@ impl Store {
 		buf.push(key_type + key.as_str());
 		buf
 	}
+
+	/// Get the public keys of the given key type and crypto from the remote signer.
+	///
+	/// Keys which cannot be converted to the crypto's public key are skipped.
+	fn remote_public_keys<TPublic>(
+		remote: &remote::RemoteSigner,
+		key_type: KeyTypeId,
+		crypto: CryptoTypeId,
+	) -> Vec<TPublic> where
+		TPublic: for<'a> TryFrom<&'a [u8]>,
+	{
+		remote.public_keys(key_type, crypto)
+			.iter()
+			.filter_map(|k| TPublic::try_from(&k[..]).ok())
+			.collect()
+	}
+
+	/// Returns the crypto of the stored key, if its secret derives it with a supported crypto.
+	///
+	/// Public keys of different cryptos can have the same length, so the length of a key is not
+	/// enough to know its crypto.
+	fn crypto_of_key(&self, key_type: KeyTypeId, public: &[u8]) -> Option<CryptoTypeId> {
+		fn derives<Pair: PairT>(store: &Store, key_type: KeyTypeId, public: &[u8]) -> bool
+			where Pair::Public: for<'a> TryFrom<&'a [u8]>,
+		{
+			Pair::Public::try_from(public)
+				.map(|public| store.key_pair_by_type::<Pair>(&public, key_type).is_ok())
+				.unwrap_or(false)
+		}
+
+		if derives::<sr25519::Pair>(self, key_type, public) {
+			Some(sr25519::CRYPTO_ID)
+		} else if derives::<ed25519::Pair>(self, key_type, public) {
+			Some(ed25519::CRYPTO_ID)
+		} else if derives::<ecdsa::Pair>(self, key_type, public) {
+			Some(ecdsa::CRYPTO_ID)
+		} else if derives::<bls381::Pair>(self, key_type, public) {
+			Some(bls381::CRYPTO_ID)
+		} else {
+			None
+		}
+	}
+}
+
+/// Whether the secret derives the given public key, with any of the supported cryptos.
+fn derives_public(secret: &KeySecret, public: &[u8]) -> bool {
+	fn derives<Pair: PairT>(secret: &KeySecret, public: &[u8]) -> bool {
+		Pair::from_string(&secret.suri, secret.password.as_ref().map(String::as_str))
+			.map(|pair| pair.public().as_slice() == public)
+			.unwrap_or(false)
+	}
+
+	derives::<sr25519::Pair>(secret, public)
+		|| derives::<ed25519::Pair>(secret, public)
+		|| derives::<ecdsa::Pair>(secret, public)
+		|| derives::<bls381::Pair>(secret, public)
+}
+
+/// Generate a key with the remote signer. Keys generated from a seed are kept in memory by local
+/// stores, which a remote signer cannot do.
+fn remote_generate(
+	remote: &remote::RemoteSigner,
+	key_type: KeyTypeId,
+	crypto: CryptoTypeId,
+	seed: Option<&str>,
+) -> std::result::Result<Vec<u8>, String> {
+	match seed {
+		Some(_) => Err("Keys cannot be generated from a seed by a remote signer".into()),
+		None => remote.generate_key(key_type, crypto),
+	}
 }
 
 impl BareCryptoStore for Store {
 	fn sr25519_public_keys(&self, key_type: KeyTypeId) -> Vec<sr25519::Public> {
+		if let Some(remote) = &self.remote {
+			return Self::remote_public_keys(remote, key_type, sr25519::CRYPTO_ID);
+		}
 		self.public_keys_by_type::<sr25519::Public>(key_type).unwrap_or_default()
 	}
 
@@ -270,6 +552,11 @@ impl BareCryptoStore for Store {
 		id: KeyTypeId,
 		seed: Option<&str>,
 	) -> std::result::Result<sr25519::Public, String> {
+		if let Some(remote) = &self.remote {
+			let public = remote_generate(remote, id, sr25519::CRYPTO_ID, seed)?;
+			return Ok(sr25519::Public::from_slice(&public));
+		}
+
 		let pair = match seed {
 			Some(seed) => self.insert_ephemeral_from_seed_by_type::<sr25519::Pair>(seed, id),
 			None => self.generate_by_type::<sr25519::Pair>(id),
@@ -283,6 +570,9 @@ impl BareCryptoStore for Store {
 	}
 
 	fn ed25519_public_keys(&self, key_type: KeyTypeId) -> Vec<ed25519::Public> {
+		if let Some(remote) = &self.remote {
+			return Self::remote_public_keys(remote, key_type, ed25519::CRYPTO_ID);
+		}
 		self.public_keys_by_type::<ed25519::Public>(key_type).unwrap_or_default()
 	}
 
@@ -291,6 +581,11 @@ impl BareCryptoStore for Store {
 		id: KeyTypeId,
 		seed: Option<&str>,
 	) -> std::result::Result<ed25519::Public, String> {
+		if let Some(remote) = &self.remote {
+			let public = remote_generate(remote, id, ed25519::CRYPTO_ID, seed)?;
+			return Ok(ed25519::Public::from_slice(&public));
+		}
+
 		let pair = match seed {
 			Some(seed) => self.insert_ephemeral_from_seed_by_type::<ed25519::Pair>(seed, id),
 			None => self.generate_by_type::<ed25519::Pair>(id),
@@ -303,6 +598,135 @@ impl BareCryptoStore for Store {
 		self.key_pair_by_type::<ed25519::Pair>(pub_key, id).ok()
 	}
 
+	fn ecdsa_public_keys(&self, key_type: KeyTypeId) -> Vec<ecdsa::Public> {
+		if let Some(remote) = &self.remote {
+			return Self::remote_public_keys(remote, key_type, ecdsa::CRYPTO_ID);
+		}
+		// ECDSA public keys differ in length from sr25519 and ed25519 keys. Only return the ones
+		// that can actually be ecdsa keys instead of reinterpreting every key of the key type.
+		self.raw_public_keys(key_type)
+			.map(|keys| keys.iter().filter_map(|k| ecdsa::Public::try_from(&k[..]).ok()).collect())
+			.unwrap_or_default()
+	}
+
+	fn ecdsa_generate_new(
+		&mut self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> std::result::Result<ecdsa::Public, String> {
+		if let Some(remote) = &self.remote {
+			let public = remote_generate(remote, id, ecdsa::CRYPTO_ID, seed)?;
+			return ecdsa::Public::try_from(&public[..])
+				.map_err(|()| "Invalid ecdsa public key returned by the remote signer".into());
+		}
+
+		let pair = match seed {
+			Some(seed) => self.insert_ephemeral_from_seed_by_type::<ecdsa::Pair>(seed, id),
+			None => self.generate_by_type::<ecdsa::Pair>(id),
+		}.map_err(|e| e.to_string())?;
+
+		Ok(pair.public())
+	}
+
+	fn ecdsa_key_pair(&self, id: KeyTypeId, pub_key: &ecdsa::Public) -> Option<ecdsa::Pair> {
+		self.key_pair_by_type::<ecdsa::Pair>(pub_key, id).ok()
+	}
+
+	fn bls381_public_keys(&self, key_type: KeyTypeId) -> Vec<bls381::Public> {
+		if let Some(remote) = &self.remote {
+			return Self::remote_public_keys(remote, key_type, bls381::CRYPTO_ID);
+		}
+		// Like ECDSA keys, BLS12-381 public keys have their own length.
+		self.raw_public_keys(key_type)
+			.map(|keys| keys.iter().filter_map(|k| bls381::Public::try_from(&k[..]).ok()).collect())
+			.unwrap_or_default()
+	}
+
+	fn bls381_generate_new(
+		&mut self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> std::result::Result<bls381::Public, String> {
+		if let Some(remote) = &self.remote {
+			let public = remote_generate(remote, id, bls381::CRYPTO_ID, seed)?;
+			return bls381::Public::try_from(&public[..])
+				.map_err(|()| "Invalid bls381 public key returned by the remote signer".into());
+		}
+
+		let pair = match seed {
+			Some(seed) => self.insert_ephemeral_from_seed_by_type::<bls381::Pair>(seed, id),
+			None => self.generate_by_type::<bls381::Pair>(id),
+		}.map_err(|e| e.to_string())?;
+
+		Ok(pair.public())
+	}
+
+	fn bls381_key_pair(&self, id: KeyTypeId, pub_key: &bls381::Public) -> Option<bls381::Pair> {
+		self.key_pair_by_type::<bls381::Pair>(pub_key, id).ok()
+	}
+
+	fn keys(&self, id: KeyTypeId) -> Vec<CryptoTypePublicPair> {
+		if let Some(remote) = &self.remote {
+			// The remote signer lists the keys of each crypto apart.
+			return [sr25519::CRYPTO_ID, ed25519::CRYPTO_ID, ecdsa::CRYPTO_ID, bls381::CRYPTO_ID].iter()
+				.flat_map(|crypto| remote.public_keys(id, *crypto)
+					.into_iter()
+					.map(move |public| CryptoTypePublicPair(*crypto, public))
+				)
+				.collect();
+		}
+		// The sr25519 and ed25519 keys have the same length, so both getters return all of them.
+		// Each key is listed once, with the crypto deriving it from its secret.
+		self.raw_public_keys(id)
+			.map(|keys| keys.into_iter()
+				.filter_map(|public| self.crypto_of_key(id, &public)
+					.map(|crypto| CryptoTypePublicPair(crypto, public))
+				)
+				.collect()
+			)
+			.unwrap_or_default()
+	}
+
+	fn sign_with(
+		&self,
+		id: KeyTypeId,
+		key: &CryptoTypePublicPair,
+		msg: &[u8],
+	) -> SignatureFuture<Vec<u8>> {
+		match &self.remote {
+			Some(remote) => remote.sign_with(id, key, msg),
+			None => Box::pin(future::ready(sign_with_key_pair(self, id, key, msg))),
+		}
+	}
+
+	fn sign_with_blocking(
+		&self,
+		id: KeyTypeId,
+		key: &CryptoTypePublicPair,
+		msg: &[u8],
+	) -> std::result::Result<Vec<u8>, String> {
+		match &self.remote {
+			Some(remote) => remote.sign_with_blocking(id, key, msg),
+			None => sign_with_key_pair(self, id, key, msg),
+		}
+	}
+
+	fn sr25519_vrf_sign(
+		&self,
+		id: KeyTypeId,
+		public: &sr25519::Public,
+		transcript_data: VRFTranscriptData,
+	) -> SignatureFuture<VRFSignature> {
+		match &self.remote {
+			Some(remote) => remote.sr25519_vrf_sign(id, public.as_ref(), transcript_data),
+			None => Box::pin(future::ready(
+				self.key_pair_by_type::<sr25519::Pair>(public, id)
+					.map(|pair| vrf::sr25519_vrf_sign(&pair, transcript_data))
+					.map_err(|e| e.to_string())
+			)),
+		}
+	}
+
 	fn insert_unknown(&mut self, key_type: KeyTypeId, suri: &str, public: &[u8])
 		-> std::result::Result<(), ()>
 	{
@@ -318,7 +742,8 @@ impl BareCryptoStore for Store {
 mod tests {
 	use super::*;
 	use tempfile::TempDir;
-	use sp_core::{testing::{SR25519}, crypto::{Ss58Codec}};
+	use sp_core::{testing::{SR25519}, crypto::{Ss58Codec, CryptoTypePublicPair}};
+	use futures::executor::block_on;
 
 	#[test]
 	fn basic_store() {
@@ -378,6 +803,56 @@ mod tests {
 		);
 	}
 
+	#[test]
+	fn plain_key_files_are_encrypted_with_the_password() {
+		let temp_dir = TempDir::new().unwrap();
+		let store = Store::open(temp_dir.path(), None).unwrap();
+		let pair: ed25519::AppPair = store.write().generate().unwrap();
+		let path = store.read().key_file_path(pair.public().as_ref(), ed25519::AppPair::ID);
+		assert!(fs::read_to_string(&path).unwrap().starts_with('"'));
+
+		let store = Store::open(temp_dir.path(), Some(String::from("password").into())).unwrap();
+		assert!(fs::read_to_string(&path).unwrap().starts_with('{'));
+		assert_eq!(
+			pair.public(),
+			store.read().key_pair::<ed25519::AppPair>(&pair.public()).unwrap().public(),
+		);
+	}
+
+	#[test]
+	fn decrypted_secrets_are_kept_for_the_session() {
+		let temp_dir = TempDir::new().unwrap();
+		let store = Store::open(temp_dir.path(), Some(String::from("password").into())).unwrap();
+		let pair: ed25519::AppPair = store.write().generate().unwrap();
+		let path = store.read().key_file_path(pair.public().as_ref(), ed25519::AppPair::ID);
+
+		fs::write(&path, "{}").unwrap();
+		assert_eq!(
+			pair.public(),
+			store.read().key_pair::<ed25519::AppPair>(&pair.public()).unwrap().public(),
+		);
+
+		let store = Store::open(temp_dir.path(), Some(String::from("password").into())).unwrap();
+		assert!(store.read().key_pair::<ed25519::AppPair>(&pair.public()).is_err());
+	}
+
+	#[test]
+	fn rotating_the_password_keeps_the_keys() {
+		let temp_dir = TempDir::new().unwrap();
+		let store = Store::open(temp_dir.path(), Some(String::from("old").into())).unwrap();
+		let pair: ed25519::AppPair = store.write().generate().unwrap();
+
+		store.write().rotate_password(String::from("new").into()).unwrap();
+
+		let store = Store::open(temp_dir.path(), Some(String::from("old").into())).unwrap();
+		assert!(store.read().key_pair::<ed25519::AppPair>(&pair.public()).is_err());
+		let store = Store::open(temp_dir.path(), Some(String::from("new").into())).unwrap();
+		assert_eq!(
+			pair.public(),
+			store.read().key_pair::<ed25519::AppPair>(&pair.public()).unwrap().public(),
+		);
+	}
+
 	#[test]
 	fn public_keys_are_returned() {
 		let temp_dir = TempDir::new().unwrap();
@@ -435,4 +910,44 @@ mod tests {
 			store.read().public_keys_by_type::<sr25519::AppPublic>(SR25519).unwrap().is_empty(),
 		);
 	}
+
+	#[test]
+	fn sign_with_supports_all_cryptos() {
+		let temp_dir = TempDir::new().unwrap();
+		let store = Store::open(temp_dir.path(), None).unwrap();
+		let msg = b"authority addresses";
+
+		let sr25519_key = store.write().sr25519_generate_new(SR25519, None).unwrap();
+		let ed25519_key = store.write().ed25519_generate_new(SR25519, None).unwrap();
+		let ecdsa_key = store.write().ecdsa_generate_new(SR25519, None).unwrap();
+		let bls381_key = store.write().bls381_generate_new(SR25519, None).unwrap();
+
+		let keys = store.read().keys(SR25519);
+		let sr25519_key = CryptoTypePublicPair(sr25519::CRYPTO_ID, sr25519_key.to_raw_vec());
+		let ed25519_key = CryptoTypePublicPair(ed25519::CRYPTO_ID, ed25519_key.to_raw_vec());
+		let ecdsa_key = CryptoTypePublicPair(ecdsa::CRYPTO_ID, ecdsa_key.to_raw_vec());
+		let bls381_key = CryptoTypePublicPair(bls381::CRYPTO_ID, bls381_key.to_raw_vec());
+
+		assert!(keys.contains(&sr25519_key));
+		assert!(keys.contains(&ed25519_key));
+		assert!(keys.contains(&ecdsa_key));
+		assert!(keys.contains(&bls381_key));
+		assert_eq!(keys.len(), 4);
+
+		// A key of the wrong length is an error rather than a panic.
+		let invalid_key = CryptoTypePublicPair(sr25519::CRYPTO_ID, ecdsa_key.1.clone());
+		assert!(block_on(store.read().sign_with(SR25519, &invalid_key, msg)).is_err());
+
+		let signature = block_on(store.read().sign_with(SR25519, &sr25519_key, msg)).unwrap();
+		assert!(sr25519::Pair::verify_weak(&signature, msg, &sr25519_key.1));
+
+		let signature = block_on(store.read().sign_with(SR25519, &ed25519_key, msg)).unwrap();
+		assert!(ed25519::Pair::verify_weak(&signature, msg, &ed25519_key.1));
+
+		let signature = block_on(store.read().sign_with(SR25519, &ecdsa_key, msg)).unwrap();
+		assert!(ecdsa::Pair::verify_weak(&signature, msg, &ecdsa_key.1));
+
+		let signature = block_on(store.read().sign_with(SR25519, &bls381_key, msg)).unwrap();
+		assert!(bls381::Pair::verify_weak(&signature, msg, &bls381_key.1));
+	}
 }
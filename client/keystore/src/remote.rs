@@ -0,0 +1,595 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Remote signer protocol.
+//!
+//! A remote signer holds the keys of the node, e.g. in an HSM, and signs on its behalf. The node
+//! talks to it with the JSON-RPC methods of [`RemoteSignerApi`] over HTTPS, and authenticates
+//! itself with a token given as first parameter of every call. Plain HTTP is only accepted for a
+//! signer on the loopback interface.
+//!
+//! Key types and crypto ids are given as their four character string, e.g. `babe` and `sr25`.
+
+use std::{collections::HashMap, net::IpAddr, sync::{Arc, mpsc}, thread, time::Duration};
+
+use futures::{FutureExt, channel::oneshot, future};
+use jsonrpc_core::Result as RpcResult;
+use jsonrpc_core_client::{RpcError, transports::http};
+use jsonrpc_derive::rpc;
+use log::warn;
+use parking_lot::{Mutex, RwLock};
+use serde::{Serialize, Deserialize};
+use sp_core::{
+	Bytes, crypto::{KeyTypeId, CryptoTypeId, CryptoTypePublicPair}, traits::SignatureFuture,
+	vrf::{VRFTranscriptData, VRFTranscriptValue, VRFSignature, VRFOutput, VRFProof},
+};
+use tokio::{runtime::Runtime, timer::Timeout};
+
+pub use self::gen_client::Client as RemoteSignerClient;
+
+/// Value of an item of a VRF transcript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TranscriptValue {
+	/// Raw bytes.
+	Bytes(Bytes),
+	/// A number, appended as its little endian bytes.
+	U64(u64),
+}
+
+/// Label and items of a VRF transcript.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Transcript {
+	/// Label of the transcript.
+	pub label: Bytes,
+	/// Labeled items appended to the transcript, in order.
+	pub items: Vec<(Bytes, TranscriptValue)>,
+}
+
+impl From<VRFTranscriptData> for Transcript {
+	fn from(data: VRFTranscriptData) -> Self {
+		Transcript {
+			label: data.label.to_vec().into(),
+			items: data.items.into_iter()
+				.map(|(label, value)| (label.to_vec().into(), match value {
+					VRFTranscriptValue::Bytes(bytes) => TranscriptValue::Bytes(bytes.into()),
+					VRFTranscriptValue::U64(value) => TranscriptValue::U64(value),
+				}))
+				.collect(),
+		}
+	}
+}
+
+/// Output and proof of a VRF signature.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VrfSignature {
+	/// The VRF output.
+	pub output: Bytes,
+	/// The proof of the output.
+	pub proof: Bytes,
+}
+
+/// JSON-RPC methods of a remote signer.
+#[rpc]
+pub trait RemoteSignerApi {
+	/// Returns the public keys of the given key type and crypto.
+	#[rpc(name = "signer_publicKeys")]
+	fn public_keys(&self, token: String, key_type: String, crypto: String) -> RpcResult<Vec<Bytes>>;
+
+	/// Generates a new key of the given key type and crypto, and returns its public key.
+	#[rpc(name = "signer_generateKey")]
+	fn generate_key(&self, token: String, key_type: String, crypto: String) -> RpcResult<Bytes>;
+
+	/// Inserts the key with the given secret URI and public key.
+	#[rpc(name = "signer_insertKey")]
+	fn insert_key(&self, token: String, key_type: String, suri: String, public: Bytes) -> RpcResult<()>;
+
+	/// Signs the message with the given key and returns the raw signature.
+	#[rpc(name = "signer_signWith")]
+	fn sign_with(
+		&self,
+		token: String,
+		key_type: String,
+		crypto: String,
+		public: Bytes,
+		message: Bytes,
+	) -> RpcResult<Bytes>;
+
+	/// Signs the transcript with the VRF of the given sr25519 key.
+	#[rpc(name = "signer_sr25519VrfSign")]
+	fn sr25519_vrf_sign(
+		&self,
+		token: String,
+		key_type: String,
+		public: Bytes,
+		transcript: Transcript,
+	) -> RpcResult<VrfSignature>;
+}
+
+type Job = Box<dyn FnOnce(&mut Runtime, &RemoteSignerClient) + Send>;
+
+/// How long to wait for the signer to answer a request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How often the cached public keys are requested again from the signer.
+const KEYS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+/// The public keys of each key type and crypto.
+type PublicKeys = HashMap<(KeyTypeId, CryptoTypeId), Vec<Vec<u8>>>;
+
+/// Connection to a remote signer.
+///
+/// Requests are made by a dedicated thread, and fail if the signer doesn't answer in time.
+/// Signing returns a future resolving to the answer of the signer, while managing keys blocks
+/// until the signer answers.
+///
+/// The public keys are cached, so that looking them up, e.g. to claim a slot, doesn't wait for
+/// the signer. The cache is refreshed in the background by another thread.
+pub struct RemoteSigner {
+	inner: Arc<Inner>,
+}
+
+struct Inner {
+	jobs: Mutex<mpsc::Sender<Job>>,
+	token: String,
+	request_timeout: Duration,
+	public_keys: RwLock<PublicKeys>,
+}
+
+impl RemoteSigner {
+	/// Connects to the remote signer at the given URI, authenticating with `token`.
+	///
+	/// The URI must be an `https://` one, unless the signer is on the loopback interface, as the
+	/// token and the payloads to sign would be sent in clear otherwise.
+	pub fn connect(uri: &str, token: String) -> Result<Self, String> {
+		Self::connect_with(uri, token, REQUEST_TIMEOUT, KEYS_REFRESH_INTERVAL)
+	}
+
+	fn connect_with(
+		uri: &str,
+		token: String,
+		request_timeout: Duration,
+		refresh_interval: Duration,
+	) -> Result<Self, String> {
+		check_uri(uri)?;
+
+		let (jobs, receiver) = mpsc::channel::<Job>();
+		let (started, started_receiver) = mpsc::channel();
+		let uri = uri.to_owned();
+
+		thread::Builder::new()
+			.name("remote-signer".into())
+			.spawn(move || {
+				let connection = Runtime::new()
+					.map_err(|e| format!("Cannot start the remote signer runtime: {}", e))
+					.and_then(|mut runtime| runtime.block_on(http::connect::<RemoteSignerClient>(&uri))
+						.map(|client| (runtime, client))
+						.map_err(|e| format!("Invalid remote signer URI {}: {}", uri, e))
+					);
+				let (mut runtime, client) = match connection {
+					Ok(connection) => {
+						let _ = started.send(Ok(()));
+						connection
+					},
+					Err(e) => {
+						let _ = started.send(Err(e));
+						return;
+					},
+				};
+
+				for job in receiver {
+					job(&mut runtime, &client);
+				}
+			})
+			.map_err(|e| format!("Cannot start the remote signer thread: {}", e))?;
+
+		started_receiver.recv().map_err(|_| "The remote signer thread stopped".to_string())??;
+
+		let inner = Arc::new(Inner {
+			jobs: Mutex::new(jobs),
+			token,
+			request_timeout,
+			public_keys: RwLock::new(HashMap::new()),
+		});
+		let refreshed = Arc::downgrade(&inner);
+		thread::Builder::new()
+			.name("remote-signer-keys".into())
+			.spawn(move || loop {
+				thread::sleep(refresh_interval);
+				// The signer has been dropped.
+				let inner = match refreshed.upgrade() {
+					Some(inner) => inner,
+					None => return,
+				};
+				inner.refresh_public_keys();
+			})
+			.map_err(|e| format!("Cannot start the remote signer keys thread: {}", e))?;
+
+		Ok(RemoteSigner { inner })
+	}
+
+	/// Returns the raw public keys of the given key type and crypto.
+	///
+	/// Only the first lookup of a key type and crypto waits for the signer, later ones return
+	/// the cached keys. No key is returned if the signer cannot be reached.
+	pub fn public_keys(&self, key_type: KeyTypeId, crypto: CryptoTypeId) -> Vec<Vec<u8>> {
+		if let Some(keys) = self.inner.public_keys.read().get(&(key_type, crypto)) {
+			return keys.clone();
+		}
+
+		// The keys are cached even if the request failed, to be requested again by the refresh.
+		let keys = self.inner.request_public_keys(key_type, crypto)
+			.unwrap_or_else(|e| {
+				warn!(target: "keystore", "Cannot get the public keys from the remote signer: {}", e);
+				Vec::new()
+			});
+		self.inner.public_keys.write().insert((key_type, crypto), keys.clone());
+		keys
+	}
+
+	/// Generates a new key of the given key type and crypto, and returns its raw public key.
+	pub fn generate_key(&self, key_type: KeyTypeId, crypto: CryptoTypeId) -> Result<Vec<u8>, String> {
+		let (key_type_id, crypto_id) = (id_string(&key_type.0), id_string(&crypto.0));
+		let public = self.inner.call_blocking(move |client, token|
+			client.generate_key(token, key_type_id, crypto_id)
+		)?.0;
+
+		if let Some(keys) = self.inner.public_keys.write().get_mut(&(key_type, crypto)) {
+			if !keys.contains(&public) {
+				keys.push(public.clone());
+			}
+		}
+		Ok(public)
+	}
+
+	/// Inserts the key with the given secret URI and raw public key.
+	pub fn insert_key(&self, key_type: KeyTypeId, suri: &str, public: &[u8]) -> Result<(), String> {
+		let (key_type_id, suri, public) = (id_string(&key_type.0), suri.to_owned(), public.to_vec().into());
+		self.inner.call_blocking(move |client, token| client.insert_key(token, key_type_id, suri, public))?;
+
+		// The crypto of the key is unknown, so the keys of every crypto are requested again.
+		self.inner.public_keys.write().retain(|(cached_key_type, _), _| *cached_key_type != key_type);
+		Ok(())
+	}
+
+	/// Signs the message with the given key and returns the raw signature.
+	pub fn sign_with(
+		&self,
+		key_type: KeyTypeId,
+		key: &CryptoTypePublicPair,
+		message: &[u8],
+	) -> SignatureFuture<Vec<u8>> {
+		let (key_type, crypto) = (id_string(&key_type.0), id_string(&(key.0).0));
+		let (public, message) = (key.1.clone().into(), message.to_vec().into());
+		let signature = self.inner.call(move |client, token|
+			client.sign_with(token, key_type, crypto, public, message)
+		);
+		Box::pin(signature.map(|signature| signature.map(|signature| signature.0)))
+	}
+
+	/// Signs the message with the given key, waits for the signer and returns the raw signature.
+	pub fn sign_with_blocking(
+		&self,
+		key_type: KeyTypeId,
+		key: &CryptoTypePublicPair,
+		message: &[u8],
+	) -> Result<Vec<u8>, String> {
+		let (key_type, crypto) = (id_string(&key_type.0), id_string(&(key.0).0));
+		let (public, message) = (key.1.clone().into(), message.to_vec().into());
+		self.inner.call_blocking(move |client, token|
+			client.sign_with(token, key_type, crypto, public, message)
+		).map(|signature| signature.0)
+	}
+
+	/// Signs the transcript with the VRF of the given raw sr25519 public key.
+	pub fn sr25519_vrf_sign(
+		&self,
+		key_type: KeyTypeId,
+		public: &[u8],
+		transcript_data: VRFTranscriptData,
+	) -> SignatureFuture<VRFSignature> {
+		let (key_type, public) = (id_string(&key_type.0), public.to_vec().into());
+		let transcript = Transcript::from(transcript_data);
+		let signature = self.inner.call(move |client, token|
+			client.sr25519_vrf_sign(token, key_type, public, transcript)
+		);
+
+		Box::pin(signature.map(|signature| {
+			let signature = signature?;
+			Ok(VRFSignature {
+				output: VRFOutput::from_bytes(&signature.output)
+					.map_err(|e| format!("Invalid VRF output returned by the remote signer: {:?}", e))?,
+				proof: VRFProof::from_bytes(&signature.proof)
+					.map_err(|e| format!("Invalid VRF proof returned by the remote signer: {:?}", e))?,
+			})
+		}))
+	}
+}
+
+impl Inner {
+	/// Makes a request to the signer, and returns a future resolving to its result.
+	fn call<R, F>(
+		&self,
+		request: impl FnOnce(&RemoteSignerClient, String) -> F + Send + 'static,
+	) -> SignatureFuture<R> where
+		R: Send + 'static,
+		F: futures01::Future<Item = R, Error = RpcError> + Send + 'static,
+	{
+		let (sender, receiver) = oneshot::channel();
+		if !self.send(request, move |result| { let _ = sender.send(result); }) {
+			return Box::pin(future::ready(Err("The remote signer thread stopped".into())));
+		}
+		Box::pin(receiver.map(|result| result
+			.unwrap_or_else(|_| Err("The remote signer thread stopped".into()))
+		))
+	}
+
+	/// Makes a request to the signer, and waits for its result.
+	fn call_blocking<R, F>(
+		&self,
+		request: impl FnOnce(&RemoteSignerClient, String) -> F + Send + 'static,
+	) -> Result<R, String> where
+		R: Send + 'static,
+		F: futures01::Future<Item = R, Error = RpcError> + Send + 'static,
+	{
+		let (sender, receiver) = mpsc::channel();
+		if !self.send(request, move |result| { let _ = sender.send(result); }) {
+			return Err("The remote signer thread stopped".into());
+		}
+		receiver.recv().unwrap_or_else(|_| Err("The remote signer thread stopped".into()))
+	}
+
+	/// Sends a request to the thread of the signer, which gives its result to `respond`.
+	///
+	/// Returns `false` if the thread stopped.
+	fn send<R, F>(
+		&self,
+		request: impl FnOnce(&RemoteSignerClient, String) -> F + Send + 'static,
+		respond: impl FnOnce(Result<R, String>) + Send + 'static,
+	) -> bool where
+		R: Send + 'static,
+		F: futures01::Future<Item = R, Error = RpcError> + Send + 'static,
+	{
+		let token = self.token.clone();
+		let request_timeout = self.request_timeout;
+		let job: Job = Box::new(move |runtime, client| {
+			let result = runtime.block_on(Timeout::new(request(client, token), request_timeout))
+				.map_err(|e| match e.into_inner() {
+					Some(e) => format!("Remote signer error: {}", e),
+					None => format!("The remote signer did not answer within {:?}", request_timeout),
+				});
+			respond(result);
+		});
+
+		self.jobs.lock().send(job).is_ok()
+	}
+
+	/// Requests the raw public keys of the given key type and crypto, and waits for them.
+	fn request_public_keys(&self, key_type: KeyTypeId, crypto: CryptoTypeId) -> Result<Vec<Vec<u8>>, String> {
+		let (key_type, crypto) = (id_string(&key_type.0), id_string(&crypto.0));
+		self.call_blocking(move |client, token| client.public_keys(token, key_type, crypto))
+			.map(|keys| keys.into_iter().map(|key| key.0).collect())
+	}
+
+	/// Requests again every cached kind of public keys. The previous keys are kept if the
+	/// request fails.
+	fn refresh_public_keys(&self) {
+		let cached = self.public_keys.read().keys().cloned().collect::<Vec<_>>();
+		for (key_type, crypto) in cached {
+			match self.request_public_keys(key_type, crypto) {
+				Ok(keys) => {
+					self.public_keys.write().insert((key_type, crypto), keys);
+				},
+				Err(e) => warn!(
+					target: "keystore",
+					"Cannot refresh the {} {} public keys from the remote signer: {}",
+					id_string(&key_type.0),
+					id_string(&crypto.0),
+					e,
+				),
+			}
+		}
+	}
+}
+
+/// Returns the four character string of a key type or crypto id.
+fn id_string(id: &[u8; 4]) -> String {
+	String::from_utf8_lossy(id).into_owned()
+}
+
+/// Checks that the URI of the signer uses TLS, or points to the loopback interface.
+fn check_uri(uri: &str) -> Result<(), String> {
+	let has_scheme = |scheme: &str| uri.get(..scheme.len()).map_or(false, |s| s.eq_ignore_ascii_case(scheme));
+	if has_scheme("https://") {
+		return Ok(());
+	}
+	if !has_scheme("http://") {
+		return Err(format!("Invalid remote signer URI {}: expected an https:// URI", uri));
+	}
+
+	let authority = uri["http://".len()..].split(|c| c == '/' || c == '?' || c == '#').next().unwrap_or("");
+	let host_and_port = authority.rsplit('@').next().unwrap_or("");
+	let host = if host_and_port.starts_with('[') {
+		host_and_port[1..].split(']').next().unwrap_or("")
+	} else {
+		host_and_port.split(':').next().unwrap_or("")
+	};
+	let is_loopback = host.eq_ignore_ascii_case("localhost") ||
+		host.parse::<IpAddr>().map_or(false, |ip| ip.is_loopback());
+
+	if is_loopback {
+		Ok(())
+	} else {
+		Err(format!(
+			"Refusing to send the remote signer token in clear to {}: use an https:// URI",
+			uri,
+		))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::sync::atomic::{AtomicUsize, Ordering};
+	use futures::executor::block_on;
+	use jsonrpc_http_server::{Server, ServerBuilder};
+
+	const KEY_TYPE: KeyTypeId = KeyTypeId(*b"test");
+	const CRYPTO: CryptoTypeId = CryptoTypeId(*b"sr25");
+
+	/// A signer holding public keys only, and never signing in time.
+	#[derive(Default)]
+	struct MockSigner {
+		keys: Arc<Mutex<Vec<Bytes>>>,
+		key_requests: Arc<AtomicUsize>,
+	}
+
+	impl RemoteSignerApi for MockSigner {
+		fn public_keys(&self, _: String, _: String, _: String) -> RpcResult<Vec<Bytes>> {
+			self.key_requests.fetch_add(1, Ordering::SeqCst);
+			Ok(self.keys.lock().clone())
+		}
+
+		fn generate_key(&self, _: String, _: String, _: String) -> RpcResult<Bytes> {
+			let mut keys = self.keys.lock();
+			let public = Bytes(vec![keys.len() as u8 + 1; 32]);
+			keys.push(public.clone());
+			Ok(public)
+		}
+
+		fn insert_key(&self, _: String, _: String, _: String, public: Bytes) -> RpcResult<()> {
+			self.keys.lock().push(public);
+			Ok(())
+		}
+
+		fn sign_with(&self, _: String, _: String, _: String, _: Bytes, message: Bytes) -> RpcResult<Bytes> {
+			thread::sleep(Duration::from_secs(1));
+			Ok(message)
+		}
+
+		fn sr25519_vrf_sign(&self, _: String, _: String, _: Bytes, _: Transcript) -> RpcResult<VrfSignature> {
+			Err(jsonrpc_core::Error::method_not_found())
+		}
+	}
+
+	fn start(signer: MockSigner) -> (Server, String) {
+		let mut io = jsonrpc_core::IoHandler::new();
+		io.extend_with(signer.to_delegate());
+		let server = ServerBuilder::new(io).start_http(&"127.0.0.1:0".parse().unwrap()).unwrap();
+		let uri = format!("http://{}", server.address());
+		(server, uri)
+	}
+
+	#[test]
+	fn only_loopback_signers_are_reached_without_tls() {
+		assert!(check_uri("https://signer.example.com:8443").is_ok());
+		assert!(check_uri("HTTPS://10.0.0.1/").is_ok());
+		assert!(check_uri("http://127.0.0.1:9955").is_ok());
+		assert!(check_uri("http://localhost/signer").is_ok());
+		assert!(check_uri("http://[::1]:9955").is_ok());
+
+		assert!(check_uri("http://signer.example.com").is_err());
+		assert!(check_uri("http://10.0.0.1:9955").is_err());
+		assert!(check_uri("http://127.0.0.1.example.com").is_err());
+		assert!(check_uri("http://localhost@signer.example.com").is_err());
+		assert!(check_uri("ws://127.0.0.1:9955").is_err());
+	}
+
+	#[test]
+	fn public_keys_are_cached() {
+		let signer = MockSigner::default();
+		let (keys, key_requests) = (signer.keys.clone(), signer.key_requests.clone());
+		keys.lock().push(Bytes(vec![1; 32]));
+		let (_server, uri) = start(signer);
+		let remote = RemoteSigner::connect_with(
+			&uri,
+			"token".into(),
+			Duration::from_secs(10),
+			Duration::from_secs(3600),
+		).unwrap();
+
+		assert_eq!(remote.public_keys(KEY_TYPE, CRYPTO), vec![vec![1; 32]]);
+		assert_eq!(remote.public_keys(KEY_TYPE, CRYPTO), vec![vec![1; 32]]);
+		assert_eq!(key_requests.load(Ordering::SeqCst), 1);
+
+		let public = remote.generate_key(KEY_TYPE, CRYPTO).unwrap();
+		assert_eq!(remote.public_keys(KEY_TYPE, CRYPTO), vec![vec![1; 32], public]);
+		assert_eq!(key_requests.load(Ordering::SeqCst), 1);
+
+		remote.insert_key(KEY_TYPE, "//Alice", &[3; 32]).unwrap();
+		assert_eq!(remote.public_keys(KEY_TYPE, CRYPTO).len(), 3);
+		assert_eq!(key_requests.load(Ordering::SeqCst), 2);
+	}
+
+	#[test]
+	fn public_keys_are_refreshed_in_the_background() {
+		let signer = MockSigner::default();
+		let keys = signer.keys.clone();
+		let (_server, uri) = start(signer);
+		let remote = RemoteSigner::connect_with(
+			&uri,
+			"token".into(),
+			Duration::from_secs(10),
+			Duration::from_millis(50),
+		).unwrap();
+		assert!(remote.public_keys(KEY_TYPE, CRYPTO).is_empty());
+
+		keys.lock().push(Bytes(vec![1; 32]));
+		let refreshed = (0..100).any(|_| {
+			thread::sleep(Duration::from_millis(50));
+			!remote.public_keys(KEY_TYPE, CRYPTO).is_empty()
+		});
+		assert!(refreshed);
+	}
+
+	#[test]
+	fn requests_time_out() {
+		let (_server, uri) = start(MockSigner::default());
+		let remote = RemoteSigner::connect_with(
+			&uri,
+			"token".into(),
+			Duration::from_millis(100),
+			Duration::from_secs(3600),
+		).unwrap();
+
+		let signature = block_on(remote.sign_with(KEY_TYPE, &CryptoTypePublicPair(CRYPTO, vec![1; 32]), b"message"));
+		assert!(signature.unwrap_err().contains("did not answer"));
+	}
+
+	#[test]
+	fn transcript_data_converts_to_json() {
+		let data = VRFTranscriptData {
+			label: b"BABE",
+			items: vec![
+				(&b"slot number"[..], VRFTranscriptValue::U64(1)),
+				(&b"chain randomness"[..], VRFTranscriptValue::Bytes(vec![1, 2])),
+			],
+		};
+
+		assert_eq!(
+			serde_json::to_value(Transcript::from(data)).unwrap(),
+			serde_json::json!({
+				"label": "0x42414245",
+				"items": [
+					["0x736c6f74206e756d626572", { "u64": 1 }],
+					["0x636861696e2072616e646f6d6e657373", { "bytes": "0x0102" }],
+				],
+			}),
+		);
+	}
+}
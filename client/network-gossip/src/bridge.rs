@@ -15,7 +15,8 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::Network;
-use crate::state_machine::{ConsensusGossip, Validator, TopicNotification};
+use crate::send_queues::{SendQueueConfig, SendQueueStats, SendQueues};
+use crate::state_machine::{ConsensusGossip, DedupConfig, GossipStats, Validator, TopicNotification};
 
 use sc_network::Context;
 use sc_network::message::generic::ConsensusMessage;
@@ -27,6 +28,9 @@ use parking_lot::Mutex;
 use sp_runtime::{traits::Block as BlockT, ConsensusEngineId};
 use std::{sync::Arc, time::Duration};
 
+/// Interval at which the messages waiting in the send queues are handed to the network.
+const SEND_QUEUES_FLUSH_INTERVAL: Duration = Duration::from_millis(100);
+
 /// Wraps around an implementation of the `Network` crate and provides gossiping capabilities on
 /// top of it.
 pub struct GossipEngine<B: BlockT> {
@@ -38,6 +42,7 @@ struct GossipEngineInner<B: BlockT> {
 	state_machine: ConsensusGossip<B>,
 	context: Box<dyn Context<B> + Send>,
 	context_ext: Box<dyn ContextExt<B> + Send>,
+	send_queues: Arc<Mutex<SendQueues>>,
 }
 
 impl<B: BlockT> GossipEngine<B> {
@@ -49,11 +54,14 @@ impl<B: BlockT> GossipEngine<B> {
 		validator: Arc<dyn Validator<B>>,
 	) -> Self where B: 'static {
 		let mut state_machine = ConsensusGossip::new();
+		let send_queues = Arc::new(Mutex::new(SendQueues::new(SendQueueConfig::default())));
 		let mut context = Box::new(ContextOverService {
 			network: network.clone(),
+			send_queues: send_queues.clone(),
 		});
 		let context_ext = Box::new(ContextOverService {
 			network: network.clone(),
+			send_queues: send_queues.clone(),
 		});
 
 		// We grab the event stream before registering the notifications protocol, otherwise we
@@ -67,6 +75,7 @@ impl<B: BlockT> GossipEngine<B> {
 			state_machine,
 			context,
 			context_ext,
+			send_queues: send_queues.clone(),
 		}));
 
 		let gossip_engine = GossipEngine {
@@ -97,11 +106,35 @@ impl<B: BlockT> GossipEngine<B> {
 			log::error!(target: "gossip", "Failed to spawn background task");
 		}
 
+		let res = executor.spawn({
+			let send_queues = Arc::downgrade(&send_queues);
+			let network = network.clone();
+			async move {
+				loop {
+					let _ = futures_timer::Delay::new(SEND_QUEUES_FLUSH_INTERVAL).await;
+					if let Some(send_queues) = send_queues.upgrade() {
+						send_queues.lock().flush(
+							|who| network.num_queued_messages(who),
+							|who, message| network.write_notification(who, message.engine_id, message.data),
+						);
+					} else {
+						// The engine has been dropped.
+						break;
+					}
+				}
+			}
+		});
+
+		// Note: we consider the chances of an error to spawn a background task almost null.
+		if res.is_err() {
+			log::error!(target: "gossip", "Failed to spawn background task");
+		}
+
 		let res = executor.spawn(async move {
 			let mut stream = Compat01As03::new(event_stream);
 			while let Some(Ok(event)) = stream.next().await {
 				match event {
-					Event::NotificationStreamOpened { remote, engine_id: msg_engine_id, roles } => {
+					Event::NotificationStreamOpened { remote, engine_id: msg_engine_id, roles, .. } => {
 						if msg_engine_id != engine_id {
 							continue;
 						}
@@ -115,6 +148,7 @@ impl<B: BlockT> GossipEngine<B> {
 						}
 						let mut inner = inner.lock();
 						let inner = &mut *inner;
+						inner.send_queues.lock().remove_peer(&remote);
 						inner.state_machine.peer_disconnected(&mut *inner.context, remote);
 					},
 					Event::NotificationsReceived { remote, messages } => {
@@ -227,6 +261,34 @@ impl<B: BlockT> GossipEngine<B> {
 		}
 	}
 
+	/// Use a dedicated duplicate detection for the messages of the given topic. See
+	/// [`DedupConfig`].
+	pub fn set_topic_dedup_config(&self, topic: B::Hash, config: DedupConfig) {
+		self.inner.lock().state_machine.set_topic_dedup_config(topic, config);
+	}
+
+	/// Go back to the default duplicate detection for the messages of the given topic.
+	pub fn remove_topic_dedup_config(&self, topic: &B::Hash) {
+		self.inner.lock().state_machine.remove_topic_dedup_config(topic);
+	}
+
+	/// Returns the number of duplicate, expired and propagated messages since the engine was
+	/// created.
+	pub fn stats(&self) -> GossipStats {
+		self.inner.lock().state_machine.stats()
+	}
+
+	/// Changes the bound and the overflow policy of the queues of messages waiting to be sent to
+	/// each peer. See [`SendQueueConfig`].
+	pub fn set_send_queue_config(&self, config: SendQueueConfig) {
+		self.inner.lock().send_queues.lock().set_config(config);
+	}
+
+	/// Returns the current depth of the send queues and the number of messages they dropped.
+	pub fn send_queue_stats(&self) -> SendQueueStats {
+		self.inner.lock().send_queues.lock().stats()
+	}
+
 	/// Notify everyone we're connected to that we have the given block.
 	///
 	/// Note: this method isn't strictly related to gossiping and should eventually be moved
@@ -247,6 +309,7 @@ impl<B: BlockT> Clone for GossipEngine<B> {
 
 struct ContextOverService<N> {
 	network: N,
+	send_queues: Arc<Mutex<SendQueues>>,
 }
 
 impl<B: BlockT, N: Network<B>> Context<B> for ContextOverService<N> {
@@ -259,9 +322,20 @@ impl<B: BlockT, N: Network<B>> Context<B> for ContextOverService<N> {
 	}
 
 	fn send_consensus(&mut self, who: PeerId, messages: Vec<ConsensusMessage>) {
+		let mut send_queues = self.send_queues.lock();
 		for message in messages {
-			self.network.write_notification(who.clone(), message.engine_id, message.data);
+			if !send_queues.push(&who, message) {
+				self.network.disconnect_peer(who);
+				return;
+			}
 		}
+
+		let network = &self.network;
+		send_queues.flush_peer(
+			&who,
+			|who| network.num_queued_messages(who),
+			|who, message| network.write_notification(who, message.engine_id, message.data),
+		);
 	}
 
 	fn send_chain_specific(&mut self, _: PeerId, _: Vec<u8>) {
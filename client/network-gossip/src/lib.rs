@@ -56,6 +56,8 @@
 
 pub use self::bridge::GossipEngine;
 pub use self::state_machine::{TopicNotification, MessageIntent};
+pub use self::state_machine::{DedupConfig, GossipStats};
+pub use self::send_queues::{QueueFullPolicy, SendQueueConfig, SendQueueStats};
 pub use self::state_machine::{Validator, ValidatorContext, ValidationResult};
 pub use self::state_machine::DiscardAll;
 
@@ -64,6 +66,7 @@ use sp_runtime::{traits::Block as BlockT, ConsensusEngineId};
 use std::sync::Arc;
 
 mod bridge;
+mod send_queues;
 mod state_machine;
 
 /// Abstraction over a network.
@@ -80,6 +83,9 @@ pub trait Network<B: BlockT> {
 	/// Send a notification to a peer.
 	fn write_notification(&self, who: PeerId, engine_id: ConsensusEngineId, message: Vec<u8>);
 
+	/// Returns the number of messages handed to the network and waiting to be sent to a peer.
+	fn num_queued_messages(&self, who: &PeerId) -> usize;
+
 	/// Registers a notifications protocol.
 	///
 	/// See the documentation of [`NetworkService:register_notifications_protocol`] for more information.
@@ -112,6 +118,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Network<B> for Arc<Netw
 		NetworkService::write_notification(self, who, engine_id, message)
 	}
 
+	fn num_queued_messages(&self, who: &PeerId) -> usize {
+		NetworkService::num_queued_messages(self, who)
+	}
+
 	fn register_notifications_protocol(
 		&self,
 		engine_id: ConsensusEngineId,
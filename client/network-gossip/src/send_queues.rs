@@ -0,0 +1,235 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Bounded per-peer queues of the messages waiting to be handed to the network.
+//!
+//! Messages are only handed to the network while it has fewer than
+//! `MAX_NETWORK_QUEUED_MESSAGES` messages waiting to be sent to the peer. The messages for a peer
+//! that doesn't keep up thus accumulate here, where they are bounded, instead of in the network.
+
+use libp2p::PeerId;
+use log::debug;
+use sc_network::message::generic::ConsensusMessage;
+use std::collections::{HashMap, VecDeque};
+
+/// Number of messages waiting in the network above which we stop handing messages over to it.
+const MAX_NETWORK_QUEUED_MESSAGES: usize = 256;
+
+/// What to do when a message is queued for a peer whose queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum QueueFullPolicy {
+	/// Drop the oldest message of the queue to make room for the new one.
+	DropOldest,
+	/// Drop all the queued messages and disconnect the peer.
+	Disconnect,
+}
+
+/// Configuration of the per-peer send queues.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendQueueConfig {
+	/// Maximum number of messages queued for a single peer.
+	pub max_len: usize,
+	/// What to do once `max_len` is reached.
+	pub policy: QueueFullPolicy,
+}
+
+impl Default for SendQueueConfig {
+	fn default() -> Self {
+		SendQueueConfig {
+			max_len: 4096,
+			policy: QueueFullPolicy::DropOldest,
+		}
+	}
+}
+
+/// Depth of the send queues and counters of the messages they had to drop.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SendQueueStats {
+	/// Number of messages currently queued, over all peers.
+	pub queued: usize,
+	/// Number of messages currently queued for the peer with the longest queue.
+	pub max_queued: usize,
+	/// Messages dropped because a queue was full, since the creation of the queues.
+	pub dropped: u64,
+	/// Peers disconnected because their queue was full, since the creation of the queues.
+	pub disconnected: u64,
+}
+
+pub(crate) struct SendQueues {
+	config: SendQueueConfig,
+	queues: HashMap<PeerId, VecDeque<ConsensusMessage>>,
+	dropped: u64,
+	disconnected: u64,
+}
+
+impl SendQueues {
+	pub fn new(config: SendQueueConfig) -> Self {
+		SendQueues {
+			config,
+			queues: HashMap::new(),
+			dropped: 0,
+			disconnected: 0,
+		}
+	}
+
+	/// Changes the configuration. Queues above the new maximum lose their oldest messages.
+	pub fn set_config(&mut self, config: SendQueueConfig) {
+		self.config = config;
+		for queue in self.queues.values_mut() {
+			while queue.len() > config.max_len {
+				queue.pop_front();
+				self.dropped += 1;
+			}
+		}
+	}
+
+	/// Queues a message for the given peer.
+	///
+	/// Returns `false` if the queue of the peer is full and the policy is to disconnect it, in
+	/// which case all its queued messages, including this one, are dropped.
+	pub fn push(&mut self, who: &PeerId, message: ConsensusMessage) -> bool {
+		let queue = self.queues.entry(who.clone()).or_default();
+		if queue.len() < self.config.max_len {
+			queue.push_back(message);
+			return true;
+		}
+
+		match self.config.policy {
+			QueueFullPolicy::DropOldest => {
+				queue.pop_front();
+				queue.push_back(message);
+				self.dropped += 1;
+				true
+			},
+			QueueFullPolicy::Disconnect => {
+				debug!(target: "gossip", "Send queue of {} is full, disconnecting", who);
+				self.dropped += queue.len() as u64 + 1;
+				self.disconnected += 1;
+				self.queues.remove(who);
+				false
+			},
+		}
+	}
+
+	/// Hands the messages queued for the given peer to `send`, as long as `num_queued` reports
+	/// that the network has room for them.
+	pub fn flush_peer(
+		&mut self,
+		who: &PeerId,
+		num_queued: impl Fn(&PeerId) -> usize,
+		mut send: impl FnMut(PeerId, ConsensusMessage),
+	) {
+		let queue = match self.queues.get_mut(who) {
+			Some(queue) => queue,
+			None => return,
+		};
+
+		let room = MAX_NETWORK_QUEUED_MESSAGES.saturating_sub(num_queued(who));
+		for message in queue.drain(..room.min(queue.len())) {
+			send(who.clone(), message);
+		}
+
+		if queue.is_empty() {
+			self.queues.remove(who);
+		}
+	}
+
+	/// Same as `flush_peer`, for all peers.
+	pub fn flush(
+		&mut self,
+		num_queued: impl Fn(&PeerId) -> usize,
+		mut send: impl FnMut(PeerId, ConsensusMessage),
+	) {
+		let peers = self.queues.keys().cloned().collect::<Vec<_>>();
+		for who in &peers {
+			self.flush_peer(who, &num_queued, &mut send);
+		}
+	}
+
+	/// Drops the messages queued for a peer we're no longer connected to.
+	pub fn remove_peer(&mut self, who: &PeerId) {
+		self.queues.remove(who);
+	}
+
+	pub fn stats(&self) -> SendQueueStats {
+		SendQueueStats {
+			queued: self.queues.values().map(|queue| queue.len()).sum(),
+			max_queued: self.queues.values().map(|queue| queue.len()).max().unwrap_or(0),
+			dropped: self.dropped,
+			disconnected: self.disconnected,
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn message(data: u8) -> ConsensusMessage {
+		ConsensusMessage { engine_id: *b"TEST", data: vec![data] }
+	}
+
+	#[test]
+	fn only_hands_over_what_the_network_has_room_for() {
+		let mut queues = SendQueues::new(SendQueueConfig::default());
+		let slow = PeerId::random();
+		let fast = PeerId::random();
+
+		for data in 0..3 {
+			assert!(queues.push(&slow, message(data)));
+			assert!(queues.push(&fast, message(data)));
+		}
+
+		let mut sent = Vec::new();
+		queues.flush(
+			|who| if *who == slow { MAX_NETWORK_QUEUED_MESSAGES - 1 } else { 0 },
+			|who, message| sent.push((who, message.data[0])),
+		);
+
+		assert_eq!(sent.iter().filter(|(who, _)| *who == fast).count(), 3);
+		assert_eq!(
+			sent.iter().filter(|(who, _)| *who == slow).map(|(_, data)| *data).collect::<Vec<_>>(),
+			vec![0],
+		);
+		assert_eq!(queues.stats(), SendQueueStats { queued: 2, max_queued: 2, dropped: 0, disconnected: 0 });
+	}
+
+	#[test]
+	fn full_queue_drops_oldest() {
+		let mut queues = SendQueues::new(SendQueueConfig { max_len: 2, policy: QueueFullPolicy::DropOldest });
+		let peer = PeerId::random();
+
+		for data in 0..3 {
+			assert!(queues.push(&peer, message(data)));
+		}
+
+		let mut sent = Vec::new();
+		queues.flush_peer(&peer, |_| 0, |_, message| sent.push(message.data[0]));
+		assert_eq!(sent, vec![1, 2]);
+		assert_eq!(queues.stats(), SendQueueStats { queued: 0, max_queued: 0, dropped: 1, disconnected: 0 });
+	}
+
+	#[test]
+	fn full_queue_disconnects() {
+		let mut queues = SendQueues::new(SendQueueConfig { max_len: 2, policy: QueueFullPolicy::Disconnect });
+		let peer = PeerId::random();
+
+		assert!(queues.push(&peer, message(0)));
+		assert!(queues.push(&peer, message(1)));
+		assert!(!queues.push(&peer, message(2)));
+		assert_eq!(queues.stats(), SendQueueStats { queued: 0, max_queued: 0, dropped: 3, disconnected: 1 });
+	}
+}
@@ -51,6 +51,120 @@ struct PeerConsensus<H> {
 	roles: Roles,
 }
 
+/// Configuration of the duplicate detection for the messages of a topic.
+///
+/// By default the hashes of all messages are remembered in a single cache of
+/// `KNOWN_MESSAGES_CACHE_SIZE` entries shared by all topics.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DedupConfig {
+	/// Maximum number of message hashes remembered for the topic. Once reached, the hash
+	/// remembered for the longest time is forgotten first.
+	pub size: usize,
+	/// Duration after which a message hash is forgotten, if any. Expired hashes are removed on
+	/// the next garbage collection, after which the message is accepted again.
+	pub window: Option<time::Duration>,
+}
+
+/// Counters of the messages handled by the gossip state machine since its creation.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GossipStats {
+	/// Incoming messages dropped because they were already known.
+	pub duplicates_dropped: u64,
+	/// Messages removed from the store because their validator considered them expired.
+	pub expired: u64,
+	/// Messages sent to peers, counted once per peer.
+	pub propagated: u64,
+}
+
+struct TopicKnownMessages<H> {
+	config: DedupConfig,
+	/// Hashes of the known messages with the time they were first seen.
+	cache: LruCache<H, time::Instant>,
+}
+
+/// Known messages of all the topics with a dedicated duplicate detection.
+struct TopicsKnownMessages<H> {
+	topics: HashMap<H, TopicKnownMessages<H>>,
+	/// Topic whose cache holds each known message hash, so that looking up a hash doesn't scan
+	/// the cache of every topic. A hash is remembered by a single topic at a time.
+	topic_of: HashMap<H, H>,
+}
+
+impl<H: std::hash::Hash + Eq + Clone> TopicsKnownMessages<H> {
+	fn new() -> Self {
+		TopicsKnownMessages {
+			topics: HashMap::new(),
+			topic_of: HashMap::new(),
+		}
+	}
+
+	fn set_config(&mut self, topic: H, config: DedupConfig) {
+		self.remove_topic(&topic);
+		self.topics.insert(topic, TopicKnownMessages {
+			config,
+			cache: LruCache::new(config.size),
+		});
+	}
+
+	fn remove_topic(&mut self, topic: &H) {
+		if let Some(known) = self.topics.remove(topic) {
+			for (hash, _) in known.cache.iter() {
+				self.topic_of.remove(hash);
+			}
+		}
+	}
+
+	fn contains(&self, message_hash: &H) -> bool {
+		self.topic_of.contains_key(message_hash)
+	}
+
+	fn len(&self) -> usize {
+		self.topic_of.len()
+	}
+
+	/// Remembers a message of the given topic. Returns `None` if the topic has no dedicated
+	/// duplicate detection, and otherwise whether the message wasn't known to the topic yet.
+	fn insert(&mut self, topic: &H, message_hash: H, now: time::Instant) -> Option<bool> {
+		if !self.topics.contains_key(topic) {
+			return None;
+		}
+
+		let previous_topic = self.topic_of.get(&message_hash).filter(|t| *t != topic).cloned();
+		if let Some(previous_topic) = previous_topic {
+			if let Some(previous) = self.topics.get_mut(&previous_topic) {
+				previous.cache.pop(&message_hash);
+			}
+		}
+
+		let known = self.topics.get_mut(topic).expect("checked above; qed");
+		if !known.cache.contains(&message_hash) && known.cache.len() == known.cache.cap() {
+			// Evict explicitly to keep the index in sync with the cache.
+			if let Some((evicted, _)) = known.cache.pop_lru() {
+				self.topic_of.remove(&evicted);
+			}
+		}
+
+		self.topic_of.insert(message_hash.clone(), topic.clone());
+		Some(known.cache.put(message_hash, now).is_none())
+	}
+
+	/// Forgets the messages seen longer ago than the window of their topic.
+	fn collect_garbage(&mut self, now: time::Instant) {
+		for known in self.topics.values_mut() {
+			if let Some(window) = known.config.window {
+				let stale = known.cache.iter()
+					.filter(|(_, seen)| now.duration_since(**seen) >= window)
+					.map(|(hash, _)| hash.clone())
+					.collect::<Vec<_>>();
+				for hash in stale {
+					known.cache.pop(&hash);
+					self.topic_of.remove(&hash);
+				}
+			}
+		}
+	}
+}
+
 /// Topic stream message with sender.
 #[derive(Debug, Eq, PartialEq)]
 pub struct TopicNotification {
@@ -149,7 +263,7 @@ fn propagate<'a, B: BlockT, I>(
 	intent: MessageIntent,
 	peers: &mut HashMap<PeerId, PeerConsensus<B::Hash>>,
 	validators: &HashMap<ConsensusEngineId, Arc<dyn Validator<B>>>,
-)
+) -> u64
 	// (msg_hash, topic, message)
 	where I: Clone + IntoIterator<Item=(&'a B::Hash, &'a B::Hash, &'a ConsensusMessage)>,
 {
@@ -167,6 +281,7 @@ fn propagate<'a, B: BlockT, I>(
 		(check_fn)(who, intent, topic, &message.data)
 	};
 
+	let mut propagated = 0;
 	for (id, ref mut peer) in peers.iter_mut() {
 		let mut batch = Vec::new();
 		for (message_hash, topic, message) in messages.clone() {
@@ -197,8 +312,19 @@ fn propagate<'a, B: BlockT, I>(
 			trace!(target: "gossip", "Propagating to {}: {:?}", id, message);
 			batch.push(message.clone())
 		}
+		propagated += batch.len() as u64;
 		protocol.send_consensus(id.clone(), batch);
 	}
+
+	propagated
+}
+
+fn is_known<H: std::hash::Hash + Eq>(
+	known_messages: &LruCache<H, ()>,
+	topic_known_messages: &TopicsKnownMessages<H>,
+	message_hash: &H,
+) -> bool {
+	known_messages.contains(message_hash) || topic_known_messages.contains(message_hash)
 }
 
 /// Validates consensus messages.
@@ -236,8 +362,10 @@ pub struct ConsensusGossip<B: BlockT> {
 	live_message_sinks: HashMap<(ConsensusEngineId, B::Hash), Vec<mpsc::UnboundedSender<TopicNotification>>>,
 	messages: Vec<MessageEntry<B>>,
 	known_messages: LruCache<B::Hash, ()>,
+	topic_known_messages: TopicsKnownMessages<B::Hash>,
 	validators: HashMap<ConsensusEngineId, Arc<dyn Validator<B>>>,
 	next_broadcast: time::Instant,
+	stats: GossipStats,
 }
 
 impl<B: BlockT> ConsensusGossip<B> {
@@ -248,11 +376,30 @@ impl<B: BlockT> ConsensusGossip<B> {
 			live_message_sinks: HashMap::new(),
 			messages: Default::default(),
 			known_messages: LruCache::new(KNOWN_MESSAGES_CACHE_SIZE),
+			topic_known_messages: TopicsKnownMessages::new(),
 			validators: Default::default(),
 			next_broadcast: time::Instant::now() + REBROADCAST_INTERVAL,
+			stats: GossipStats::default(),
 		}
 	}
 
+	/// Use a dedicated duplicate detection for the messages of the given topic, replacing any
+	/// previous configuration of the topic. Messages of the topic registered so far are forgotten.
+	pub fn set_topic_dedup_config(&mut self, topic: B::Hash, config: DedupConfig) {
+		self.topic_known_messages.set_config(topic, config);
+	}
+
+	/// Go back to the default duplicate detection for the messages of the given topic, e.g. once
+	/// the topic is no longer relevant.
+	pub fn remove_topic_dedup_config(&mut self, topic: &B::Hash) {
+		self.topic_known_messages.remove_topic(topic);
+	}
+
+	/// Returns the counters of the messages handled so far.
+	pub fn stats(&self) -> GossipStats {
+		self.stats
+	}
+
 	/// Closes all notification streams.
 	pub fn abort(&mut self) {
 		self.live_message_sinks.clear();
@@ -302,7 +449,13 @@ impl<B: BlockT> ConsensusGossip<B> {
 		message: ConsensusMessage,
 		sender: Option<PeerId>,
 	) {
-		if self.known_messages.put(message_hash.clone(), ()).is_none() {
+		let now = time::Instant::now();
+		let newly_known = match self.topic_known_messages.insert(&topic, message_hash.clone(), now) {
+			Some(newly_known) => newly_known,
+			None => self.known_messages.put(message_hash.clone(), ()).is_none(),
+		};
+
+		if newly_known {
 			self.messages.push(MessageEntry {
 				message_hash,
 				topic,
@@ -347,7 +500,8 @@ impl<B: BlockT> ConsensusGossip<B> {
 	fn rebroadcast(&mut self, protocol: &mut dyn Context<B>) {
 		let messages = self.messages.iter()
 			.map(|entry| (&entry.message_hash, &entry.topic, &entry.message));
-		propagate(protocol, messages, MessageIntent::PeriodicRebroadcast, &mut self.peers, &self.validators);
+		self.stats.propagated +=
+			propagate(protocol, messages, MessageIntent::PeriodicRebroadcast, &mut self.peers, &self.validators);
 	}
 
 	/// Broadcast all messages with given topic.
@@ -357,7 +511,7 @@ impl<B: BlockT> ConsensusGossip<B> {
 				if entry.topic == topic { Some((&entry.message_hash, &entry.topic, &entry.message)) } else { None }
 			);
 		let intent = if force { MessageIntent::ForcedBroadcast } else { MessageIntent::broadcast() };
-		propagate(protocol, messages, intent, &mut self.peers, &self.validators);
+		self.stats.propagated += propagate(protocol, messages, intent, &mut self.peers, &self.validators);
 	}
 
 	/// Prune old or no longer relevant consensus messages. Provide a predicate
@@ -368,7 +522,6 @@ impl<B: BlockT> ConsensusGossip<B> {
 			!sinks.is_empty()
 		});
 
-		let known_messages = &mut self.known_messages;
 		let before = self.messages.len();
 		let validators = &self.validators;
 
@@ -387,15 +540,21 @@ impl<B: BlockT> ConsensusGossip<B> {
 		};
 
 		self.messages.retain(|entry| !message_expired(entry));
+		self.stats.expired += (before - self.messages.len()) as u64;
+
+		self.topic_known_messages.collect_garbage(time::Instant::now());
+
+		let known_messages = &self.known_messages;
+		let topic_known_messages = &self.topic_known_messages;
 
 		trace!(target: "gossip", "Cleaned up {} stale messages, {} left ({} known)",
 			before - self.messages.len(),
 			self.messages.len(),
-			known_messages.len(),
+			known_messages.len() + topic_known_messages.len(),
 		);
 
 		for (_, ref mut peer) in self.peers.iter_mut() {
-			peer.known_messages.retain(|h| known_messages.contains(h));
+			peer.known_messages.retain(|h| is_known(known_messages, topic_known_messages, h));
 		}
 	}
 
@@ -433,8 +592,9 @@ impl<B: BlockT> ConsensusGossip<B> {
 		for message in messages {
 			let message_hash = HashFor::<B>::hash(&message.data[..]);
 
-			if self.known_messages.contains(&message_hash) {
+			if is_known(&self.known_messages, &self.topic_known_messages, &message_hash) {
 				trace!(target:"gossip", "Ignored already known message from {}", who);
+				self.stats.duplicates_dropped += 1;
 				protocol.report_peer(who.clone(), rep::DUPLICATE_GOSSIP);
 				continue;
 			}
@@ -532,6 +692,7 @@ impl<B: BlockT> ConsensusGossip<B> {
 					data: entry.message.data.clone(),
 				});
 			}
+			self.stats.propagated += batch.len() as u64;
 			protocol.send_consensus(who.clone(), batch);
 		}
 	}
@@ -547,7 +708,13 @@ impl<B: BlockT> ConsensusGossip<B> {
 		let message_hash = HashFor::<B>::hash(&message.data);
 		self.register_message_hashed(message_hash, topic, message.clone(), None);
 		let intent = if force { MessageIntent::ForcedBroadcast } else { MessageIntent::broadcast() };
-		propagate(protocol, iter::once((&message_hash, &topic, &message)), intent, &mut self.peers, &self.validators);
+		self.stats.propagated += propagate(
+			protocol,
+			iter::once((&message_hash, &topic, &message)),
+			intent,
+			&mut self.peers,
+			&self.validators,
+		);
 	}
 
 	/// Send addressed message to a peer. The message is not kept or multicast
@@ -617,6 +784,14 @@ mod tests {
 		}
 	}
 
+	struct NoOpContext;
+	impl Context<Block> for NoOpContext {
+		fn report_peer(&mut self, _: PeerId, _: sc_network::ReputationChange) {}
+		fn disconnect_peer(&mut self, _: PeerId) {}
+		fn send_consensus(&mut self, _: PeerId, _: Vec<ConsensusMessage>) {}
+		fn send_chain_specific(&mut self, _: PeerId, _: Vec<u8>) {}
+	}
+
 	struct AllowAll;
 	impl Validator<Block> for AllowAll {
 		fn validate(
@@ -745,4 +920,97 @@ mod tests {
 		let _ = consensus.live_message_sinks.remove(&([0, 0, 0, 0], topic));
 		assert_eq!(stream.next(), None);
 	}
+
+	#[test]
+	fn counts_duplicate_expired_and_propagated_messages() {
+		let mut consensus = ConsensusGossip::<Block>::new();
+		let mut context = NoOpContext;
+		consensus.register_validator_internal([0, 0, 0, 0], Arc::new(AllowAll));
+
+		let peer = PeerId::random();
+		consensus.new_peer(&mut context, peer.clone(), Roles::FULL);
+
+		let message = ConsensusMessage { data: vec![1, 2, 3], engine_id: [0, 0, 0, 0] };
+		consensus.on_incoming(&mut context, peer.clone(), vec![message.clone()]);
+		consensus.on_incoming(&mut context, peer.clone(), vec![message]);
+		assert_eq!(consensus.stats().duplicates_dropped, 1);
+
+		// The peer already knows the message it sent us.
+		consensus.broadcast_topic(&mut context, H256::default(), false);
+		assert_eq!(consensus.stats().propagated, 0);
+
+		let topic = [1; 32].into();
+		let message = ConsensusMessage { data: vec![4, 5, 6], engine_id: [0, 0, 0, 0] };
+		consensus.multicast(&mut context, topic, message, false);
+		assert_eq!(consensus.stats().propagated, 1);
+
+		// Messages of engines without a validator are expired.
+		let message = ConsensusMessage { data: vec![7, 8, 9], engine_id: [0, 0, 0, 1] };
+		consensus.register_message(topic, message);
+		consensus.collect_garbage();
+		assert_eq!(consensus.stats(), GossipStats { duplicates_dropped: 1, expired: 1, propagated: 1 });
+	}
+
+	#[test]
+	fn topic_dedup_config_bounds_known_messages() {
+		let mut consensus = ConsensusGossip::<Block>::new();
+		consensus.register_validator_internal([0, 0, 0, 0], Arc::new(AllowAll));
+
+		let topic = [1; 32].into();
+		consensus.set_topic_dedup_config(topic, DedupConfig { size: 1, window: None });
+
+		let msg_a = ConsensusMessage { data: vec![1, 2, 3], engine_id: [0, 0, 0, 0] };
+		let msg_b = ConsensusMessage { data: vec![4, 5, 6], engine_id: [0, 0, 0, 0] };
+		let hash_a = HashFor::<Block>::hash(&msg_a.data);
+		let hash_b = HashFor::<Block>::hash(&msg_b.data);
+
+		consensus.register_message(topic, msg_a);
+		consensus.register_message(topic, msg_b);
+		assert_eq!(consensus.messages.len(), 2);
+		assert!(!is_known(&consensus.known_messages, &consensus.topic_known_messages, &hash_a));
+		assert!(is_known(&consensus.known_messages, &consensus.topic_known_messages, &hash_b));
+		assert_eq!(consensus.topic_known_messages.len(), 1);
+
+		consensus.set_topic_dedup_config(topic, DedupConfig {
+			size: 16,
+			window: Some(time::Duration::from_millis(0)),
+		});
+		let msg_c = ConsensusMessage { data: vec![7, 8, 9], engine_id: [0, 0, 0, 0] };
+		let hash_c = HashFor::<Block>::hash(&msg_c.data);
+		consensus.register_message(topic, msg_c);
+		assert!(is_known(&consensus.known_messages, &consensus.topic_known_messages, &hash_c));
+
+		consensus.collect_garbage();
+		assert!(!is_known(&consensus.known_messages, &consensus.topic_known_messages, &hash_c));
+		assert_eq!(consensus.topic_known_messages.len(), 0);
+	}
+
+	#[test]
+	fn topic_known_messages_index_follows_the_caches() {
+		let mut consensus = ConsensusGossip::<Block>::new();
+		consensus.register_validator_internal([0, 0, 0, 0], Arc::new(AllowAll));
+
+		let topic_a = [1; 32].into();
+		let topic_b = [2; 32].into();
+		consensus.set_topic_dedup_config(topic_a, DedupConfig { size: 16, window: None });
+		consensus.set_topic_dedup_config(topic_b, DedupConfig { size: 16, window: None });
+
+		let msg = ConsensusMessage { data: vec![1, 2, 3], engine_id: [0, 0, 0, 0] };
+		let hash = HashFor::<Block>::hash(&msg.data);
+
+		// The same message registered on another topic is remembered by the latter only.
+		consensus.register_message(topic_a, msg.clone());
+		consensus.register_message(topic_b, msg);
+		assert_eq!(consensus.topic_known_messages.len(), 1);
+		assert!(!consensus.topic_known_messages.topics[&topic_a].cache.contains(&hash));
+		assert!(is_known(&consensus.known_messages, &consensus.topic_known_messages, &hash));
+
+		// Removing the other topic doesn't forget the message.
+		consensus.remove_topic_dedup_config(&topic_a);
+		assert!(is_known(&consensus.known_messages, &consensus.topic_known_messages, &hash));
+
+		consensus.remove_topic_dedup_config(&topic_b);
+		assert!(!is_known(&consensus.known_messages, &consensus.topic_known_messages, &hash));
+		assert_eq!(consensus.topic_known_messages.len(), 0);
+	}
 }
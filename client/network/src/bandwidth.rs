@@ -0,0 +1,290 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Accounting of the bandwidth used by the individual protocols running on top of the network,
+//! with optional limits on the outbound rate of the gossip protocols.
+//!
+//! In contrast to the transport level `BandwidthSinks`, which only knows about the total number
+//! of bytes going through the sockets, the sizes recorded here are the sizes of the messages
+//! handed to and received from the protocols, excluding any transport overhead.
+
+use log::warn;
+use parking_lot::Mutex;
+use sp_runtime::ConsensusEngineId;
+use std::{collections::HashMap, fmt, sync::Arc, time::Instant};
+
+/// Protocol that bandwidth is accounted for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BandwidthProtocol {
+	/// Announcements of new blocks.
+	BlockAnnounce,
+	/// Propagation of transactions.
+	Transactions,
+	/// Block, justification and finality proof requests and responses, as well as the handshake.
+	Sync,
+	/// Requests and responses of the light client protocol.
+	LightClient,
+	/// Messages of the consensus engine with the given identifier, e.g. `*b"FRNK"` for GRANDPA.
+	Consensus(ConsensusEngineId),
+	/// Records put into and retrieved from the Kademlia DHT.
+	Dht,
+	/// Chain specific messages.
+	Other,
+}
+
+impl BandwidthProtocol {
+	/// Whether the protocol gossips messages, which can be dropped when over its rate limit.
+	///
+	/// The other protocols exchange requests and responses, dropping them would stall the peers
+	/// waiting for them, thus they are accounted for but never limited.
+	pub fn is_gossip(&self) -> bool {
+		match self {
+			BandwidthProtocol::BlockAnnounce |
+			BandwidthProtocol::Transactions |
+			BandwidthProtocol::Consensus(_) => true,
+			BandwidthProtocol::Sync |
+			BandwidthProtocol::LightClient |
+			BandwidthProtocol::Dht |
+			BandwidthProtocol::Other => false,
+		}
+	}
+}
+
+impl fmt::Display for BandwidthProtocol {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			BandwidthProtocol::BlockAnnounce => write!(f, "block-announce"),
+			BandwidthProtocol::Transactions => write!(f, "transactions"),
+			BandwidthProtocol::Sync => write!(f, "sync"),
+			BandwidthProtocol::LightClient => write!(f, "light-client"),
+			BandwidthProtocol::Consensus(engine_id) =>
+				write!(f, "consensus-{}", String::from_utf8_lossy(&engine_id[..])),
+			BandwidthProtocol::Dht => write!(f, "dht"),
+			BandwidthProtocol::Other => write!(f, "other"),
+		}
+	}
+}
+
+/// Number of bytes sent and received on a protocol since the network was started.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ProtocolBandwidth {
+	/// Total number of bytes received.
+	pub bytes_in: u64,
+	/// Total number of bytes sent.
+	pub bytes_out: u64,
+}
+
+/// Bandwidth accounting shared between the network worker and the `NetworkService`.
+#[derive(Clone)]
+pub(crate) struct ProtocolBandwidthSinks {
+	inner: Arc<Mutex<Inner>>,
+}
+
+struct Inner {
+	totals: HashMap<BandwidthProtocol, ProtocolBandwidth>,
+	limiters: HashMap<BandwidthProtocol, RateLimiter>,
+}
+
+impl ProtocolBandwidthSinks {
+	/// Create new sinks, limiting the outbound rate of the given protocols to the given number of
+	/// bytes per second. Limits of protocols which don't gossip are ignored.
+	pub fn new(rate_limits: &HashMap<BandwidthProtocol, u64>) -> Self {
+		let limiters = rate_limits.iter()
+			.filter(|(protocol, _)| if protocol.is_gossip() {
+				true
+			} else {
+				warn!(target: "sub-libp2p", "Ignoring rate limit of non-gossip protocol {}", protocol);
+				false
+			})
+			.map(|(protocol, bytes_per_sec)| (*protocol, RateLimiter::new(*bytes_per_sec, Instant::now())))
+			.collect();
+
+		ProtocolBandwidthSinks {
+			inner: Arc::new(Mutex::new(Inner { totals: HashMap::new(), limiters })),
+		}
+	}
+
+	/// Record the reception of a message, split into the number of bytes used by each protocol.
+	pub fn inbound(&self, usage: &[(BandwidthProtocol, u64)]) {
+		let mut inner = self.inner.lock();
+		for (protocol, bytes) in usage {
+			inner.totals.entry(*protocol).or_default().bytes_in += bytes;
+		}
+	}
+
+	/// Ask for permission to send a message, split into the number of bytes used by each
+	/// protocol.
+	///
+	/// Returns `false` if any of the protocols involved is over its rate limit, in which case the
+	/// message must be dropped. Otherwise the message is accounted for and `true` is returned.
+	/// Only gossip protocols are ever over their rate limit.
+	pub fn outbound(&self, usage: &[(BandwidthProtocol, u64)]) -> bool {
+		self.outbound_at(usage, Instant::now())
+	}
+
+	/// Record the sending of a message which can't be dropped, e.g. a request or a response,
+	/// split into the number of bytes used by each protocol.
+	pub fn record_outbound(&self, usage: &[(BandwidthProtocol, u64)]) {
+		let mut inner = self.inner.lock();
+		for (protocol, bytes) in usage {
+			inner.totals.entry(*protocol).or_default().bytes_out += bytes;
+		}
+	}
+
+	fn outbound_at(&self, usage: &[(BandwidthProtocol, u64)], now: Instant) -> bool {
+		let mut inner = self.inner.lock();
+		let Inner { totals, limiters } = &mut *inner;
+
+		let allowed = usage.iter().all(|(protocol, bytes)| {
+			limiters.get_mut(protocol).map_or(true, |limiter| limiter.can_consume(*bytes, now))
+		});
+		if !allowed {
+			return false;
+		}
+
+		for (protocol, bytes) in usage {
+			if let Some(limiter) = limiters.get_mut(protocol) {
+				limiter.consume(*bytes);
+			}
+			totals.entry(*protocol).or_default().bytes_out += bytes;
+		}
+
+		true
+	}
+
+	/// Returns the number of bytes sent and received so far on each protocol.
+	pub fn by_protocol(&self) -> HashMap<BandwidthProtocol, ProtocolBandwidth> {
+		self.inner.lock().totals.clone()
+	}
+}
+
+/// Token bucket allowing bursts of up to one second worth of bytes.
+struct RateLimiter {
+	bytes_per_sec: u64,
+	available: u64,
+	last_refill: Instant,
+}
+
+impl RateLimiter {
+	fn new(bytes_per_sec: u64, now: Instant) -> Self {
+		RateLimiter { bytes_per_sec, available: bytes_per_sec, last_refill: now }
+	}
+
+	fn refill(&mut self, now: Instant) {
+		let elapsed = now.saturating_duration_since(self.last_refill);
+		let refill = elapsed.as_millis() as u64 * self.bytes_per_sec / 1000;
+		if refill > 0 {
+			self.available = self.available.saturating_add(refill).min(self.bytes_per_sec);
+			self.last_refill = now;
+		}
+	}
+
+	/// Whether `bytes` can be sent at `now`. Messages larger than the bucket are let through
+	/// whenever the bucket is full, so that they aren't starved forever.
+	fn can_consume(&mut self, bytes: u64, now: Instant) -> bool {
+		self.refill(now);
+		bytes <= self.available || self.available == self.bytes_per_sec
+	}
+
+	fn consume(&mut self, bytes: u64) {
+		self.available = self.available.saturating_sub(bytes);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::time::Duration;
+
+	const GRANDPA: BandwidthProtocol = BandwidthProtocol::Consensus(*b"FRNK");
+
+	#[test]
+	fn accounts_bytes_per_protocol() {
+		let sinks = ProtocolBandwidthSinks::new(&HashMap::new());
+
+		sinks.inbound(&[(BandwidthProtocol::BlockAnnounce, 10)]);
+		sinks.inbound(&[(BandwidthProtocol::BlockAnnounce, 5), (GRANDPA, 7)]);
+		assert!(sinks.outbound(&[(GRANDPA, 3)]));
+		assert!(sinks.outbound(&[(BandwidthProtocol::Dht, 100)]));
+
+		let by_protocol = sinks.by_protocol();
+		assert_eq!(by_protocol[&BandwidthProtocol::BlockAnnounce], ProtocolBandwidth { bytes_in: 15, bytes_out: 0 });
+		assert_eq!(by_protocol[&GRANDPA], ProtocolBandwidth { bytes_in: 7, bytes_out: 3 });
+		assert_eq!(by_protocol[&BandwidthProtocol::Dht], ProtocolBandwidth { bytes_in: 0, bytes_out: 100 });
+		assert!(!by_protocol.contains_key(&BandwidthProtocol::Transactions));
+	}
+
+	#[test]
+	fn rate_limit_drops_messages_until_refilled() {
+		let mut limits = HashMap::new();
+		limits.insert(BandwidthProtocol::Transactions, 1000);
+		let sinks = ProtocolBandwidthSinks::new(&limits);
+		let start = Instant::now();
+
+		assert!(sinks.outbound_at(&[(BandwidthProtocol::Transactions, 800)], start));
+		assert!(!sinks.outbound_at(&[(BandwidthProtocol::Transactions, 800)], start));
+		// Protocols without a limit are unaffected.
+		assert!(sinks.outbound_at(&[(BandwidthProtocol::BlockAnnounce, 10_000)], start));
+
+		let later = start + Duration::from_millis(700);
+		assert!(sinks.outbound_at(&[(BandwidthProtocol::Transactions, 800)], later));
+
+		// Dropped messages are not accounted for.
+		assert_eq!(sinks.by_protocol()[&BandwidthProtocol::Transactions].bytes_out, 1600);
+	}
+
+	#[test]
+	fn rate_limit_rejects_whole_message_if_any_protocol_is_over_limit() {
+		let mut limits = HashMap::new();
+		limits.insert(GRANDPA, 100);
+		let sinks = ProtocolBandwidthSinks::new(&limits);
+		let other_engine = BandwidthProtocol::Consensus(*b"BABE");
+		let now = Instant::now();
+
+		assert!(sinks.outbound_at(&[(GRANDPA, 100)], now));
+		assert!(!sinks.outbound_at(&[(other_engine, 10), (GRANDPA, 10)], now));
+		assert!(!sinks.by_protocol().contains_key(&other_engine));
+	}
+
+	#[test]
+	fn requests_and_responses_are_never_limited() {
+		let mut limits = HashMap::new();
+		limits.insert(BandwidthProtocol::Sync, 100);
+		limits.insert(BandwidthProtocol::Dht, 100);
+		let sinks = ProtocolBandwidthSinks::new(&limits);
+		let now = Instant::now();
+
+		assert!(sinks.outbound_at(&[(BandwidthProtocol::Sync, 1_000)], now));
+		assert!(sinks.outbound_at(&[(BandwidthProtocol::Sync, 1_000)], now));
+		sinks.record_outbound(&[(BandwidthProtocol::Dht, 1_000)]);
+
+		let by_protocol = sinks.by_protocol();
+		assert_eq!(by_protocol[&BandwidthProtocol::Sync].bytes_out, 2_000);
+		assert_eq!(by_protocol[&BandwidthProtocol::Dht].bytes_out, 1_000);
+	}
+
+	#[test]
+	fn oversized_message_passes_when_bucket_is_full() {
+		let mut limits = HashMap::new();
+		limits.insert(BandwidthProtocol::BlockAnnounce, 100);
+		let sinks = ProtocolBandwidthSinks::new(&limits);
+		let start = Instant::now();
+
+		assert!(sinks.outbound_at(&[(BandwidthProtocol::BlockAnnounce, 500)], start));
+		assert!(!sinks.outbound_at(&[(BandwidthProtocol::BlockAnnounce, 500)], start + Duration::from_millis(500)));
+		assert!(sinks.outbound_at(&[(BandwidthProtocol::BlockAnnounce, 500)], start + Duration::from_secs(1)));
+	}
+}
@@ -19,8 +19,11 @@ use crate::{
 	Event, protocol::event::DhtEvent
 };
 use crate::{ExHashT, specialization::NetworkSpecialization};
+use crate::bandwidth::{BandwidthProtocol, ProtocolBandwidthSinks};
 use crate::protocol::{CustomMessageOutcome, Protocol};
+use crate::request_responses::{ProtocolConfig, RequestFailure, RequestResponsesBehaviour};
 use futures::prelude::*;
+use futures03::channel::oneshot;
 use libp2p::NetworkBehaviour;
 use libp2p::core::{Multiaddr, PeerId, PublicKey};
 use libp2p::kad::record;
@@ -29,7 +32,7 @@ use libp2p::core::{nodes::Substream, muxing::StreamMuxerBox};
 use log::{debug, warn};
 use sp_consensus::{BlockOrigin, import_queue::{IncomingBlock, Origin}};
 use sp_runtime::{traits::{Block as BlockT, NumberFor}, Justification};
-use std::iter;
+use std::{iter, time::Duration};
 use void;
 
 /// General behaviour of the network. Combines all protocols together.
@@ -43,10 +46,15 @@ pub struct Behaviour<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 	debug_info: debug_info::DebugInfoBehaviour<Substream<StreamMuxerBox>>,
 	/// Discovers nodes of the network.
 	discovery: DiscoveryBehaviour<Substream<StreamMuxerBox>>,
+	/// Generic request-response protocols.
+	request_responses: RequestResponsesBehaviour<Substream<StreamMuxerBox>>,
 
 	/// Queue of events to produce for the outside.
 	#[behaviour(ignore)]
 	events: Vec<BehaviourOut<B>>,
+	/// Bytes sent and received per protocol, used to account for the DHT.
+	#[behaviour(ignore)]
+	bandwidth: ProtocolBandwidthSinks,
 }
 
 /// Event generated by `Behaviour`.
@@ -66,6 +74,9 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Behaviour<B, S, H> {
 		known_addresses: Vec<(PeerId, Multiaddr)>,
 		enable_mdns: bool,
 		allow_private_ipv4: bool,
+		record_ttl: Option<Duration>,
+		record_publication_interval: Option<Duration>,
+		bandwidth: ProtocolBandwidthSinks,
 	) -> Self {
 		Behaviour {
 			substrate,
@@ -74,9 +85,13 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Behaviour<B, S, H> {
 				local_public_key,
 				known_addresses,
 				enable_mdns,
-				allow_private_ipv4
+				allow_private_ipv4,
+				record_ttl,
+				record_publication_interval,
 			),
+			request_responses: RequestResponsesBehaviour::new(),
 			events: Vec::new(),
+			bandwidth,
 		}
 	}
 
@@ -109,15 +124,59 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Behaviour<B, S, H> {
 		&mut self.substrate
 	}
 
+	/// Registers a new request-response protocol, replacing any previous protocol with the same
+	/// name.
+	pub fn register_request_response_protocol(&mut self, config: ProtocolConfig) {
+		self.request_responses.register_protocol(config)
+	}
+
+	/// Sends a request to a peer we are connected to on a registered request-response protocol.
+	/// The outcome is sent on `pending_response`.
+	pub fn send_request(
+		&mut self,
+		target: &PeerId,
+		protocol: &str,
+		request: Vec<u8>,
+		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+	) {
+		self.request_responses.send_request(target, protocol, request, pending_response)
+	}
+
 	/// Start querying a record from the DHT. Will later produce either a `ValueFound` or a `ValueNotFound` event.
 	pub fn get_value(&mut self, key: &record::Key) {
+		self.bandwidth.record_outbound(&[(BandwidthProtocol::Dht, key.as_ref().len() as u64)]);
+
 		self.discovery.get_value(key);
 	}
 
 	/// Starts putting a record into DHT. Will later produce either a `ValuePut` or a `ValuePutFailed` event.
 	pub fn put_value(&mut self, key: record::Key, value: Vec<u8>) {
+		let len = key.as_ref().len() + value.len();
+		self.bandwidth.record_outbound(&[(BandwidthProtocol::Dht, len as u64)]);
+
 		self.discovery.put_value(key, value);
 	}
+
+	/// Starts announcing the local node as a provider of the given key. Will later produce either
+	/// a `StartedProviding` or a `StartProvidingFailed` event.
+	pub fn start_providing(&mut self, key: record::Key) {
+		self.bandwidth.record_outbound(&[(BandwidthProtocol::Dht, key.as_ref().len() as u64)]);
+
+		self.discovery.start_providing(key);
+	}
+
+	/// Stops announcing the local node as a provider of the given key.
+	pub fn stop_providing(&mut self, key: &record::Key) {
+		self.discovery.stop_providing(key);
+	}
+
+	/// Start querying the providers of the given key from the DHT. Will later produce either a
+	/// `ProvidersFound` or a `ProvidersNotFound` event.
+	pub fn get_providers(&mut self, key: record::Key) {
+		self.bandwidth.record_outbound(&[(BandwidthProtocol::Dht, key.as_ref().len() as u64)]);
+
+		self.discovery.get_providers(key);
+	}
 }
 
 impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviourEventProcess<void::Void> for
@@ -138,11 +197,12 @@ Behaviour<B, S, H> {
 			CustomMessageOutcome::FinalityProofImport(origin, hash, nb, proof) =>
 				self.events.push(BehaviourOut::FinalityProofImport(origin, hash, nb, proof)),
 			CustomMessageOutcome::NotificationStreamOpened { remote, protocols, roles } =>
-				for engine_id in protocols {
+				for (engine_id, handshake) in protocols {
 					self.events.push(BehaviourOut::Event(Event::NotificationStreamOpened {
 						remote: remote.clone(),
 						engine_id,
 						roles,
+						handshake,
 					}));
 				},
 			CustomMessageOutcome::NotificationsStreamClosed { remote, protocols } =>
@@ -196,6 +256,8 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviourEventPr
 				self.substrate.add_discovered_nodes(iter::once(peer_id));
 			}
 			DiscoveryOut::ValueFound(results) => {
+				let len = results.iter().map(|(key, value)| key.as_ref().len() + value.len()).sum::<usize>();
+				self.bandwidth.inbound(&[(BandwidthProtocol::Dht, len as u64)]);
 				self.events.push(BehaviourOut::Event(Event::Dht(DhtEvent::ValueFound(results))));
 			}
 			DiscoveryOut::ValueNotFound(key) => {
@@ -207,6 +269,18 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviourEventPr
 			DiscoveryOut::ValuePutFailed(key) => {
 				self.events.push(BehaviourOut::Event(Event::Dht(DhtEvent::ValuePutFailed(key))));
 			}
+			DiscoveryOut::ProvidersFound(key, providers) => {
+				self.events.push(BehaviourOut::Event(Event::Dht(DhtEvent::ProvidersFound(key, providers))));
+			}
+			DiscoveryOut::ProvidersNotFound(key) => {
+				self.events.push(BehaviourOut::Event(Event::Dht(DhtEvent::ProvidersNotFound(key))));
+			}
+			DiscoveryOut::StartedProviding(key) => {
+				self.events.push(BehaviourOut::Event(Event::Dht(DhtEvent::StartedProviding(key))));
+			}
+			DiscoveryOut::StartProvidingFailed(key) => {
+				self.events.push(BehaviourOut::Event(Event::Dht(DhtEvent::StartProvidingFailed(key))));
+			}
 		}
 	}
 }
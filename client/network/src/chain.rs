@@ -78,20 +78,61 @@ pub trait Client<Block: BlockT>: Send + Sync {
 
 	/// Returns `true` if the given `block` is a descendent of `base`.
 	fn is_descendent_of(&self, base: &Block::Hash, block: &Block::Hash) -> Result<bool, Error>;
+
+	/// Get the top-level storage entries following `start_key` at the given block, up to
+	/// `max_size` bytes. The boolean is `true` if no entries are left after the returned ones.
+	fn state_entries(
+		&self,
+		block: &Block::Hash,
+		start_key: &[u8],
+		max_size: usize,
+	) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Error>;
 }
 
 /// Finality proof provider.
 pub trait FinalityProofProvider<Block: BlockT>: Send + Sync {
 	/// Prove finality of the block.
-	fn prove_finality(&self, for_block: Block::Hash, request: &[u8]) -> Result<Option<Vec<u8>>, Error>;
+	///
+	/// `compact_proofs` is set if the requesting peer supports compact storage proofs.
+	fn prove_finality(
+		&self,
+		for_block: Block::Hash,
+		request: &[u8],
+		compact_proofs: bool,
+	) -> Result<Option<Vec<u8>>, Error>;
 }
 
 impl<Block: BlockT> FinalityProofProvider<Block> for () {
-	fn prove_finality(&self, _for_block: Block::Hash, _request: &[u8]) -> Result<Option<Vec<u8>>, Error> {
+	fn prove_finality(
+		&self,
+		_for_block: Block::Hash,
+		_request: &[u8],
+		_compact_proofs: bool,
+	) -> Result<Option<Vec<u8>>, Error> {
 		Ok(None)
 	}
 }
 
+/// Result of a successful warp sync proof verification.
+pub enum WarpSyncVerification<Block: BlockT> {
+	/// The proof is valid, but more proofs are required, starting at the given block.
+	Partial(Block::Hash),
+	/// The proof is valid and proves the finality of the given block, whose state can now be
+	/// downloaded.
+	Complete(Block::Header, Justification),
+}
+
+/// Warp sync proof provider.
+pub trait WarpSyncProvider<Block: BlockT>: Send + Sync {
+	/// Generate a proof of finality of the latest possible block, starting at the given
+	/// finalized block.
+	fn generate(&self, begin: Block::Hash) -> Result<Vec<u8>, Error>;
+
+	/// Verify a proof generated by a remote node, starting at the genesis block or at the last
+	/// block of the previously verified proof.
+	fn verify(&self, proof: &[u8]) -> Result<WarpSyncVerification<Block>, Error>;
+}
+
 impl<B, E, Block, RA> Client<Block> for SubstrateClient<B, E, Block, RA> where
 	B: sc_client_api::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
 	E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static,
@@ -169,4 +210,14 @@ impl<B, E, Block, RA> Client<Block> for SubstrateClient<B, E, Block, RA> where
 
 		Ok(ancestor.hash == *base)
 	}
+
+	fn state_entries(
+		&self,
+		block: &Block::Hash,
+		start_key: &[u8],
+		max_size: usize,
+	) -> Result<(Vec<(Vec<u8>, Vec<u8>)>, bool), Error> {
+		(self as &SubstrateClient<B, E, Block, RA>)
+			.storage_entries_after(&BlockId::Hash(block.clone()), start_key, max_size)
+	}
 }
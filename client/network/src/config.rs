@@ -20,21 +20,27 @@
 //! See the documentation of [`Params`].
 
 pub use crate::protocol::ProtocolConfig;
+pub use crate::bandwidth::BandwidthProtocol;
 pub use libp2p::{identity, core::PublicKey, wasm_ext::ExtTransport, build_multiaddr};
 
-use crate::chain::{Client, FinalityProofProvider};
+use crate::chain::{Client, FinalityProofProvider, WarpSyncProvider};
 use crate::on_demand_layer::OnDemand;
 use crate::service::{ExHashT, TransactionPool};
 use bitflags::bitflags;
-use sp_consensus::{block_validation::BlockAnnounceValidator, import_queue::ImportQueue};
+use sp_consensus::{block_validation::BlockAnnounceValidator, import_queue::ImportQueue, ForkRanking};
 use sp_runtime::traits::{Block as BlockT};
 use libp2p::identity::{Keypair, ed25519};
 use libp2p::wasm_ext;
 use libp2p::{PeerId, Multiaddr, multiaddr};
 use core::{fmt, iter};
-use std::{error::Error, fs, io::{self, Write}, net::Ipv4Addr, path::{Path, PathBuf}, sync::Arc};
+use std::{collections::HashMap, error::Error, fs, io::{self, Write}, net::Ipv4Addr, path::{Path, PathBuf}, sync::Arc};
+use std::time::Duration;
 use zeroize::Zeroize;
 
+/// Name of the priority group containing the authorities of the chain, as filled by the
+/// authority discovery module.
+pub const AUTHORITIES_PRIORITY_GROUP: &str = "authorities";
+
 /// Network initialization parameters.
 pub struct Params<B: BlockT, S, H: ExHashT> {
 	/// Assigned roles for our node (full, light, ...).
@@ -57,6 +63,18 @@ pub struct Params<B: BlockT, S, H: ExHashT> {
 	/// This object, if `Some`, is used when we need a proof of finality from another node.
 	pub finality_proof_request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
 
+	/// Warp sync proof provider.
+	///
+	/// This object, if `Some`, is used to answer warp sync requests from other nodes, and to
+	/// verify their answers when syncing in [`SyncMode::Warp`].
+	pub warp_sync_provider: Option<Arc<dyn WarpSyncProvider<B>>>,
+
+	/// Ranking of competing forks, as defined by the consensus engine.
+	///
+	/// This object, if `Some`, is used to download the most likely canonical fork first when
+	/// several are announced. Otherwise forks are ranked by block number.
+	pub fork_ranking: Option<Arc<dyn ForkRanking<B>>>,
+
 	/// The `OnDemand` object acts as a "receiver" for block data requests from the client.
 	/// If `Some`, the network worker will process these requests and answer them.
 	/// Normally used only for light clients.
@@ -239,7 +257,8 @@ pub struct NetworkConfiguration {
 	pub config_path: Option<String>,
 	/// Directory path to store network-specific configuration. None means nothing will be saved.
 	pub net_config_path: Option<String>,
-	/// Multiaddresses to listen for incoming connections.
+	/// Multiaddresses to listen for incoming connections. Addresses listening on all IPv4
+	/// interfaces (`0.0.0.0`) implicitly listen on all IPv6 interfaces (`::`) as well.
 	pub listen_addresses: Vec<Multiaddr>,
 	/// Multiaddresses to advertise. Detected automatically if empty.
 	pub public_addresses: Vec<Multiaddr>,
@@ -251,6 +270,10 @@ pub struct NetworkConfiguration {
 	pub in_peers: u32,
 	/// Number of outgoing connections we're trying to maintain.
 	pub out_peers: u32,
+	/// Number of connection slots, in each direction, dedicated to the members of the
+	/// [`AUTHORITIES_PRIORITY_GROUP`] and unavailable to other nodes. Authorities must then share
+	/// these and the regular slots. `None` means that authorities don't use any slot at all.
+	pub reserved_authority_slots: Option<u32>,
 	/// List of reserved node addresses.
 	pub reserved_nodes: Vec<String>,
 	/// The non-reserved peer mode.
@@ -265,6 +288,27 @@ pub struct NetworkConfiguration {
 	pub transport: TransportConfig,
 	/// Maximum number of peers to ask the same blocks in parallel.
 	pub max_parallel_downloads: u32,
+	/// If true, addresses which are not globally reachable (private, loopback, link-local) may be
+	/// published on the DHT, e.g. by authority discovery.
+	pub allow_non_globals_in_dht: bool,
+	/// Time-to-live of records stored in the Kademlia DHT. Records are expected to be
+	/// republished by their publisher well before. `None` means records never expire.
+	pub record_ttl: Option<Duration>,
+	/// Interval on which the Kademlia DHT records stored by this node are republished to the nodes
+	/// closest to their key, renewing their time-to-live. Should be significantly shorter than
+	/// `record_ttl`. `None` means records are never republished.
+	pub record_publication_interval: Option<Duration>,
+	/// Maximum number of bytes per second sent on the given gossip protocols, i.e. block
+	/// announces, transactions and consensus engines. Messages exceeding the limit are dropped.
+	/// Protocols not in the map are not limited, neither are requests and responses, whose
+	/// limits are ignored.
+	pub protocol_rate_limits: HashMap<BandwidthProtocol, u64>,
+	/// Address of a SOCKS5 proxy, e.g. `/ip4/127.0.0.1/tcp/9050` for a local Tor client, through
+	/// which all outgoing TCP connections are established, including those of the DHT. Listening
+	/// is unaffected. `None` means connecting directly.
+	pub outbound_proxy: Option<Multiaddr>,
+	/// How to sync the chain on a fresh database.
+	pub sync_mode: SyncMode,
 }
 
 impl Default for NetworkConfiguration {
@@ -278,6 +322,7 @@ impl Default for NetworkConfiguration {
 			node_key: NodeKeyConfig::Ed25519(Secret::New),
 			in_peers: 25,
 			out_peers: 75,
+			reserved_authority_slots: None,
 			reserved_nodes: Vec::new(),
 			non_reserved_mode: NonReservedPeerMode::Accept,
 			sentry_nodes: Vec::new(),
@@ -289,6 +334,12 @@ impl Default for NetworkConfiguration {
 				wasm_external_transport: None,
 			},
 			max_parallel_downloads: 5,
+			allow_non_globals_in_dht: false,
+			record_ttl: Some(Duration::from_secs(36 * 60 * 60)),
+			record_publication_interval: Some(Duration::from_secs(24 * 60 * 60)),
+			protocol_rate_limits: HashMap::new(),
+			outbound_proxy: None,
+			sync_mode: SyncMode::Full,
 		}
 	}
 }
@@ -332,7 +383,8 @@ pub enum TransportConfig {
 		enable_mdns: bool,
 
 		/// If true, allow connecting to private IPv4 addresses (as defined in
-		/// [RFC1918](https://tools.ietf.org/html/rfc1918)), unless the address has been passed in
+		/// [RFC1918](https://tools.ietf.org/html/rfc1918)), as well as to unique local and
+		/// link-local IPv6 addresses, unless the address has been passed in
 		/// [`NetworkConfiguration::reserved_nodes`] or [`NetworkConfiguration::boot_nodes`].
 		allow_private_ipv4: bool,
 
@@ -342,6 +394,10 @@ pub enum TransportConfig {
 		///
 		/// This parameter exists whatever the target platform is, but it is expected to be set to
 		/// `Some` only when compiling for WASM.
+		///
+		/// There is no native WebRTC transport. Browser nodes can only dial full nodes that listen
+		/// on a WebSocket address, or use a JavaScript transport (e.g. WebRTC through a signalling
+		/// server) passed here, as long as the remote supports that same transport.
 		wasm_external_transport: Option<wasm_ext::ExtTransport>,
 	},
 
@@ -350,6 +406,32 @@ pub enum TransportConfig {
 	MemoryOnly,
 }
 
+/// How a node with an empty database syncs the chain.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SyncMode {
+	/// Download and import every block. This is the default.
+	Full,
+	/// Download proofs of the GRANDPA authority set changes up to the latest finalized block,
+	/// then the state of that block, and sync normally from there. Requires a warp sync
+	/// provider. Falls back to full sync if the proofs or the state can't be obtained.
+	Warp,
+	/// Download the headers and justifications up to the latest finalized block, then the
+	/// state of that block, and sync normally from there. Blocks before it aren't executed.
+	Fast,
+}
+
+impl SyncMode {
+	/// Attempt to parse the sync mode from a string.
+	pub fn parse(s: &str) -> Option<Self> {
+		match s {
+			"full" => Some(SyncMode::Full),
+			"warp" => Some(SyncMode::Warp),
+			"fast" => Some(SyncMode::Fast),
+			_ => None,
+		}
+	}
+}
+
 /// The policy for connections to non-reserved peers.
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub enum NonReservedPeerMode {
@@ -50,8 +50,8 @@ use futures_timer::Delay;
 use futures03::{compat::Compat, TryFutureExt as _};
 use libp2p::core::{ConnectedPoint, Multiaddr, PeerId, PublicKey};
 use libp2p::swarm::{ProtocolsHandler, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
-use libp2p::kad::{Kademlia, KademliaEvent, Quorum, Record};
-use libp2p::kad::GetClosestPeersError;
+use libp2p::kad::{Kademlia, KademliaConfig, KademliaEvent, Quorum, Record};
+use libp2p::kad::{GetClosestPeersError, GetProvidersError};
 use libp2p::kad::record::{self, store::MemoryStore};
 #[cfg(not(target_os = "unknown"))]
 use libp2p::{swarm::toggle::Toggle};
@@ -61,7 +61,7 @@ use libp2p::core::{nodes::Substream, muxing::StreamMuxerBox};
 use libp2p::mdns::{Mdns, MdnsEvent};
 use libp2p::multiaddr::Protocol;
 use log::{debug, info, trace, warn};
-use std::{cmp, collections::VecDeque, time::Duration};
+use std::{cmp, collections::VecDeque, net::Ipv6Addr, time::Duration};
 use tokio_io::{AsyncRead, AsyncWrite};
 use sp_core::hexdisplay::HexDisplay;
 
@@ -94,11 +94,16 @@ impl<TSubstream> DiscoveryBehaviour<TSubstream> {
 	/// Builds a new `DiscoveryBehaviour`.
 	///
 	/// `user_defined` is a list of known address for nodes that never expire.
+	///
+	/// `record_ttl` and `record_publication_interval` configure the lifetime and republication of
+	/// the records stored in the Kademlia DHT.
 	pub fn new(
 		local_public_key: PublicKey,
 		user_defined: Vec<(PeerId, Multiaddr)>,
 		enable_mdns: bool,
 		allow_private_ipv4: bool,
+		record_ttl: Option<Duration>,
+		record_publication_interval: Option<Duration>,
 	) -> Self {
 		if enable_mdns {
 			#[cfg(target_os = "unknown")]
@@ -107,7 +112,11 @@ impl<TSubstream> DiscoveryBehaviour<TSubstream> {
 
 		let local_id = local_public_key.clone().into_peer_id();
 		let store = MemoryStore::new(local_id.clone());
-		let mut kademlia = Kademlia::new(local_id.clone(), store);
+		let mut kademlia_config = KademliaConfig::default();
+		kademlia_config
+			.set_record_ttl(record_ttl)
+			.set_publication_interval(record_publication_interval);
+		let mut kademlia = Kademlia::with_config(local_id.clone(), store, kademlia_config);
 		for (peer_id, addr) in &user_defined {
 			kademlia.add_address(peer_id, addr.clone());
 		}
@@ -175,6 +184,27 @@ impl<TSubstream> DiscoveryBehaviour<TSubstream> {
 	pub fn put_value(&mut self, key: record::Key, value: Vec<u8>) {
 		self.kademlia.put_record(Record::new(key, value), Quorum::All);
 	}
+
+	/// Start announcing the local node as a provider of the given key. The announcement is
+	/// republished periodically until `stop_providing` is called.
+	///
+	/// A corresponding `StartedProviding` or `StartProvidingFailed` event will later be generated.
+	pub fn start_providing(&mut self, key: record::Key) {
+		self.kademlia.start_providing(key)
+	}
+
+	/// Stop announcing the local node as a provider of the given key. Provider records already
+	/// stored by other nodes remain until they expire.
+	pub fn stop_providing(&mut self, key: &record::Key) {
+		self.kademlia.stop_providing(key)
+	}
+
+	/// Start looking for the providers of the given key.
+	///
+	/// A corresponding `ProvidersFound` or `ProvidersNotFound` event will later be generated.
+	pub fn get_providers(&mut self, key: record::Key) {
+		self.kademlia.get_providers(key)
+	}
 }
 
 /// Event generated by the `DiscoveryBehaviour`.
@@ -202,6 +232,18 @@ pub enum DiscoveryOut {
 
 	/// Inserting a value into the DHT failed.
 	ValuePutFailed(record::Key),
+
+	/// The DHT yielded nodes providing the requested key.
+	ProvidersFound(record::Key, Vec<PeerId>),
+
+	/// No provider of the requested key was found in the DHT.
+	ProvidersNotFound(record::Key),
+
+	/// The local node has been announced as a provider of the key.
+	StartedProviding(record::Key),
+
+	/// Announcing the local node as a provider of the key failed.
+	StartProvidingFailed(record::Key),
 }
 
 impl<TSubstream> NetworkBehaviour for DiscoveryBehaviour<TSubstream>
@@ -226,14 +268,10 @@ where
 			list_to_filter.extend(self.mdns.addresses_of_peer(peer_id));
 
 			if !self.allow_private_ipv4 {
-				list_to_filter.retain(|addr| {
-					if let Some(Protocol::Ip4(addr)) = addr.iter().next() {
-						if addr.is_private() {
-							return false;
-						}
-					}
-
-					true
+				list_to_filter.retain(|addr| match addr.iter().next() {
+					Some(Protocol::Ip4(addr)) => !addr.is_private(),
+					Some(Protocol::Ip6(addr)) => !is_private_ipv6(&addr),
+					_ => true,
 				});
 			}
 
@@ -380,6 +418,41 @@ where
 						};
 						return Async::Ready(NetworkBehaviourAction::GenerateEvent(ev));
 					}
+					KademliaEvent::GetProvidersResult(res) => {
+						let ev = match res {
+							Ok(ok) => DiscoveryOut::ProvidersFound(ok.key, ok.providers),
+							// A timed out lookup may still have found some providers.
+							Err(GetProvidersError::Timeout { key, providers, .. }) =>
+								if providers.is_empty() {
+									DiscoveryOut::ProvidersNotFound(key)
+								} else {
+									DiscoveryOut::ProvidersFound(key, providers)
+								},
+						};
+						return Async::Ready(NetworkBehaviourAction::GenerateEvent(ev));
+					}
+					KademliaEvent::StartProvidingResult(res) => {
+						let ev = match res {
+							Ok(ok) => DiscoveryOut::StartedProviding(ok.key),
+							Err(e) => {
+								debug!(target: "sub-libp2p",
+									"Libp2p => Announcing provider of {:?} failed with: {:?}",
+									e.key(), e);
+								DiscoveryOut::StartProvidingFailed(e.into_key())
+							}
+						};
+						return Async::Ready(NetworkBehaviourAction::GenerateEvent(ev));
+					}
+					KademliaEvent::RepublishProviderResult(res) => {
+						match res {
+							Ok(ok) => debug!(target: "sub-libp2p",
+								"Libp2p => Provider record republished: {:?}",
+								ok.key),
+							Err(e) => warn!(target: "sub-libp2p",
+								"Libp2p => Republishing of provider record {:?} failed with: {:?}",
+								e.key(), e)
+						}
+					}
 					KademliaEvent::RepublishRecordResult(res) => {
 						match res {
 							Ok(ok) => debug!(target: "sub-libp2p",
@@ -441,6 +514,14 @@ where
 	}
 }
 
+/// Returns `true` for the IPv6 counterparts of private IPv4 addresses, i.e. unique local
+/// (`fc00::/7`) and link-local (`fe80::/10`) addresses, which peers can't be dialed at from the
+/// outside either.
+fn is_private_ipv6(addr: &Ipv6Addr) -> bool {
+	let first_segment = addr.segments()[0];
+	(first_segment & 0xfe00) == 0xfc00 || (first_segment & 0xffc0) == 0xfe80
+}
+
 #[cfg(test)]
 mod tests {
 	use futures::prelude::*;
@@ -450,8 +531,9 @@ mod tests {
 	use libp2p::core::transport::{Transport, MemoryTransport};
 	use libp2p::core::upgrade::{InboundUpgradeExt, OutboundUpgradeExt};
 	use libp2p::swarm::Swarm;
+	use libp2p::kad::record;
 	use std::collections::HashSet;
-	use super::{DiscoveryBehaviour, DiscoveryOut};
+	use super::{DiscoveryBehaviour, DiscoveryOut, is_private_ipv6};
 
 	#[test]
 	fn discovery_working() {
@@ -480,7 +562,14 @@ mod tests {
 					upgrade::apply(stream, upgrade, endpoint, libp2p::core::upgrade::Version::V1)
 				});
 
-			let behaviour = DiscoveryBehaviour::new(keypair.public(), user_defined.clone(), false, true);
+			let behaviour = DiscoveryBehaviour::new(
+				keypair.public(),
+				user_defined.clone(),
+				false,
+				true,
+				None,
+				None,
+			);
 			let mut swarm = Swarm::new(transport, behaviour, keypair.public().into_peer_id());
 			let listen_addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
 
@@ -538,4 +627,101 @@ mod tests {
 
 		tokio::runtime::Runtime::new().unwrap().block_on(fut).unwrap();
 	}
+
+	#[test]
+	fn providers_are_found() {
+		let key = record::Key::new(&b"state-range".to_vec());
+		let mut user_defined = Vec::new();
+
+		let mut swarms = (0..2).map(|_| {
+			let keypair = Keypair::generate_ed25519();
+			let keypair2 = keypair.clone();
+
+			let transport = MemoryTransport
+				.and_then(move |out, endpoint| {
+					let secio = libp2p::secio::SecioConfig::new(keypair2);
+					upgrade::apply(out, secio, endpoint, upgrade::Version::V1)
+				})
+				.and_then(move |(peer_id, stream), endpoint| {
+					let peer_id2 = peer_id.clone();
+					let upgrade = libp2p::yamux::Config::default()
+						.map_inbound(move |muxer| (peer_id, muxer))
+						.map_outbound(move |muxer| (peer_id2, muxer));
+					upgrade::apply(stream, upgrade, endpoint, upgrade::Version::V1)
+				});
+
+			let behaviour = DiscoveryBehaviour::new(
+				keypair.public(),
+				user_defined.clone(),
+				false,
+				true,
+				None,
+				None,
+			);
+			let mut swarm = Swarm::new(transport, behaviour, keypair.public().into_peer_id());
+			let listen_addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
+			user_defined.push((keypair.public().into_peer_id(), listen_addr.clone()));
+
+			Swarm::listen_on(&mut swarm, listen_addr.clone()).unwrap();
+			(swarm, listen_addr)
+		}).collect::<Vec<_>>();
+
+		let provider = Swarm::local_peer_id(&swarms[1].0).clone();
+		swarms[1].0.start_providing(key.clone());
+
+		let fut = futures::future::poll_fn::<_, (), _>(move || {
+			'polling: loop {
+				for swarm_n in 0..swarms.len() {
+					match swarms[swarm_n].0.poll().unwrap() {
+						Async::Ready(Some(e)) => {
+							match e {
+								DiscoveryOut::UnroutablePeer(other) => {
+									let addr = swarms.iter().find_map(|(s, a)|
+										if s.local_peer_id == other {
+											Some(a.clone())
+										} else {
+											None
+										})
+										.unwrap();
+									swarms[swarm_n].0.add_self_reported_address(&other, addr);
+								},
+								DiscoveryOut::StartedProviding(k) => {
+									assert_eq!(k, key);
+									swarms[0].0.get_providers(key.clone());
+								},
+								DiscoveryOut::StartProvidingFailed(_) => {
+									swarms[1].0.start_providing(key.clone());
+								},
+								// The lookup can end before the first node knows the provider.
+								DiscoveryOut::ProvidersNotFound(k) => {
+									swarms[0].0.get_providers(k);
+								},
+								DiscoveryOut::ProvidersFound(k, providers) => {
+									assert_eq!(k, key);
+									assert_eq!(providers, vec![provider.clone()]);
+									return Ok(Async::Ready(()));
+								},
+								_ => {}
+							}
+							continue 'polling
+						}
+						_ => {}
+					}
+				}
+				break
+			}
+
+			Ok(Async::NotReady)
+		});
+
+		tokio::runtime::Runtime::new().unwrap().block_on(fut).unwrap();
+	}
+
+	#[test]
+	fn private_ipv6_addresses() {
+		assert!(is_private_ipv6(&"fd00::1".parse().unwrap()));
+		assert!(is_private_ipv6(&"fe80::1".parse().unwrap()));
+		assert!(!is_private_ipv6(&"2001:db8::1".parse().unwrap()));
+		assert!(!is_private_ipv6(&"::1".parse().unwrap()));
+	}
 }
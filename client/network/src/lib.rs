@@ -109,6 +109,11 @@
 //! protocol negotiated is based on the *protocol ID* passed as part of the network configuration.
 //! This protocol ID should be unique for each chain and prevents nodes from different chains from
 //! connecting to each other. More information below.
+//! - Full nodes answer requests for the state of their blocks on ephemeral
+//! `/<protocol id>/sync/state` substreams, and for warp sync proofs on `/<protocol id>/sync/warp`
+//! substreams if they have a warp sync provider. Nodes with an empty database use them in
+//! place of downloading every block when configured with `SyncMode::Warp`, and download the state
+//! of the latest finalized block after its headers when configured with `SyncMode::Fast`.
 //!
 //! ## The Substrate substream
 //!
@@ -168,12 +173,14 @@
 //! More precise usage details are still being worked on and will likely change in the future.
 //!
 
+mod bandwidth;
 mod behaviour;
 mod chain;
 mod debug_info;
 mod discovery;
 mod on_demand_layer;
 mod protocol;
+mod request_responses;
 mod service;
 mod transport;
 mod utils;
@@ -181,21 +188,24 @@ mod utils;
 pub mod config;
 pub mod error;
 
-pub use chain::{Client as ClientHandle, FinalityProofProvider};
+pub use bandwidth::{BandwidthProtocol, ProtocolBandwidth};
+pub use request_responses::{IncomingRequest, RequestFailure};
+pub use chain::{Client as ClientHandle, FinalityProofProvider, WarpSyncProvider, WarpSyncVerification};
 pub use service::{
 	NetworkService, NetworkWorker, TransactionPool, ExHashT, ReportHandle,
 	NetworkStateInfo,
 };
-pub use protocol::{PeerInfo, Context, ProtocolConfig, message, specialization};
-pub use protocol::event::{Event, DhtEvent};
-pub use protocol::sync::SyncState;
+pub use protocol::{PeerInfo, Context, ProtocolConfig, finality_proof_protocol_name, message, specialization};
+pub use protocol::event::{Event, DhtEvent, DhtError};
+pub use protocol::notifications::{HandshakeValidator, NotificationsProtocolConfig};
+pub use protocol::sync::{SyncState, SyncStateEvent};
 pub use libp2p::{Multiaddr, PeerId};
 #[doc(inline)]
 pub use libp2p::multiaddr;
 
 pub use message::{generic as generic_message, RequestId, Status as StatusMessage};
 pub use on_demand_layer::{OnDemand, RemoteResponse};
-pub use sc_peerset::ReputationChange;
+pub use sc_peerset::{PeerReputation, ReputationChange};
 
 // Used by the `construct_simple_protocol!` macro.
 #[doc(hidden)]
@@ -15,7 +15,9 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{DiscoveryNetBehaviour, config::ProtocolId};
+use crate::bandwidth::{BandwidthProtocol, ProtocolBandwidthSinks};
 use legacy_proto::{LegacyProto, LegacyProtoOut};
+pub use legacy_proto::SendQueueLens;
 use crate::utils::interval;
 use bytes::{Bytes, BytesMut};
 use futures::prelude::*;
@@ -24,9 +26,9 @@ use libp2p::{Multiaddr, PeerId};
 use libp2p::core::{ConnectedPoint, nodes::Substream, muxing::StreamMuxerBox};
 use libp2p::swarm::{ProtocolsHandler, IntoProtocolsHandler};
 use libp2p::swarm::{NetworkBehaviour, NetworkBehaviourAction, PollParameters};
-use sp_core::storage::{StorageKey, ChildInfo};
+use sp_core::{Blake2Hasher, storage::{StorageKey, ChildInfo}};
 use sp_consensus::{
-	BlockOrigin,
+	BlockOrigin, ForkRanking,
 	block_validation::BlockAnnounceValidator,
 	import_queue::{BlockImportResult, BlockImportError, IncomingBlock, Origin}
 };
@@ -40,17 +42,19 @@ use message::{BlockAnnounce, BlockAttributes, Direction, FromBlock, Message, Req
 use message::generic::{Message as GenericMessage, ConsensusMessage};
 use light_dispatch::{LightDispatch, LightDispatchNetwork, RequestData};
 use specialization::NetworkSpecialization;
-use sync::{ChainSync, SyncState};
+use sync::{ChainSync, SyncState, SyncStateEvent};
+use sync::warp::{self, WarpSyncRequest};
 use crate::service::{TransactionPool, ExHashT};
-use crate::config::{BoxFinalityProofRequestBuilder, Roles};
+use crate::config::{BoxFinalityProofRequestBuilder, Roles, SyncMode};
+use crate::request_responses::RequestFailure;
 use rustc_hex::ToHex;
-use std::collections::{BTreeMap, HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use std::fmt::Write;
 use std::{cmp, num::NonZeroUsize, time};
 use log::{log, Level, trace, debug, warn, error};
-use crate::chain::{Client, FinalityProofProvider};
-use sc_client_api::{FetchChecker, ChangesProof, StorageProof};
+use crate::chain::{Client, FinalityProofProvider, WarpSyncProvider};
+use sc_client_api::{FetchChecker, ChangesProof, StorageProof, CompactProof};
 use crate::error;
 use util::LruHashSet;
 
@@ -60,6 +64,7 @@ mod util;
 pub mod message;
 pub mod event;
 pub mod light_dispatch;
+pub mod notifications;
 pub mod specialization;
 pub mod sync;
 
@@ -75,7 +80,7 @@ const MAX_KNOWN_BLOCKS: usize = 1024; // ~32kb per peer + LruHashSet overhead
 const MAX_KNOWN_EXTRINSICS: usize = 4096; // ~128kb per peer + overhead
 
 /// Current protocol version.
-pub(crate) const CURRENT_VERSION: u32 = 5;
+pub(crate) const CURRENT_VERSION: u32 = 8;
 /// Lowest version we support
 pub(crate) const MIN_VERSION: u32 = 3;
 
@@ -83,6 +88,15 @@ pub(crate) const MIN_VERSION: u32 = 3;
 const MAX_BLOCK_DATA_RESPONSE: u32 = 128;
 // Maximum allowed entries in `ConsensusBatch`
 const MAX_CONSENSUS_MESSAGES: usize = 256;
+/// Lowest version of the peers we exchange notifications protocols handshakes with.
+const MIN_NOTIFICATIONS_HANDSHAKE_VERSION: u32 = 6;
+/// Lowest version of the peers we send compact call, read and finality proofs to.
+const MIN_COMPACT_PROOF_VERSION: u32 = 7;
+/// Lowest version of the peers we ask to close a notifications protocol.
+const MIN_NOTIFICATIONS_CLOSE_VERSION: u32 = 8;
+/// Maximum number of handshakes kept per peer for the notifications protocols that aren't
+/// registered yet.
+const MAX_UNREGISTERED_HANDSHAKES: usize = 16;
 /// When light node connects to the full node and the full node is behind light node
 /// for at least `LIGHT_MAXIMAL_BLOCKS_DIFFERENCE` blocks, we consider it unuseful
 /// and disconnect to free connection slot.
@@ -119,6 +133,10 @@ mod rep {
 	pub const BAD_PROTOCOL: Rep = Rep::new_fatal("Unsupported protocol");
 	/// Peer role does not match (e.g. light peer connecting to another light peer).
 	pub const BAD_ROLE: Rep = Rep::new_fatal("Unsupported role");
+	/// Peer sent us a block announcement rejected by the `BlockAnnounceValidator`.
+	pub const BAD_BLOCK_ANNOUNCEMENT: Rep = Rep::new(-(1 << 12), "Bad block announcement");
+	/// Peer sent us a notifications protocol handshake before its status.
+	pub const UNEXPECTED_HANDSHAKE: Rep = Rep::new(-(1 << 12), "Unexpected notifications handshake");
 }
 
 // Lock must always be taken in order declared here.
@@ -146,8 +164,10 @@ pub struct Protocol<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> {
 	finality_proof_provider: Option<Arc<dyn FinalityProofProvider<B>>>,
 	/// Handles opening the unique substream and sending and receiving raw messages.
 	behaviour: LegacyProto<Substream<StreamMuxerBox>>,
-	/// List of notification protocols that have been registered.
-	registered_notif_protocols: HashSet<ConsensusEngineId>,
+	/// Notification protocols that have been registered, and the peers they are open with.
+	registered_notif_protocols: HashMap<ConsensusEngineId, notifications::RegisteredProtocol>,
+	/// Notifications protocols opened with peers that were waiting for a free slot, to report.
+	pending_notif_outcomes: VecDeque<CustomMessageOutcome<B>>,
 }
 
 #[derive(Default)]
@@ -178,6 +198,9 @@ struct Peer<B: BlockT, H: ExHashT> {
 	known_blocks: LruHashSet<B::Hash>,
 	/// Request counter,
 	next_request_id: message::RequestId,
+	/// Handshakes received for notifications protocols that weren't registered yet, replayed if
+	/// they are registered later on.
+	unregistered_handshakes: HashMap<ConsensusEngineId, Vec<u8>>,
 }
 
 /// Info about a peer's known state.
@@ -195,6 +218,7 @@ pub struct PeerInfo<B: BlockT> {
 
 struct LightDispatchIn<'a> {
 	behaviour: &'a mut LegacyProto<Substream<StreamMuxerBox>>,
+	bandwidth: &'a ProtocolBandwidthSinks,
 	peerset: sc_peerset::PeersetHandle,
 }
 
@@ -213,7 +237,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			block,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		send_packet::<B>(self.behaviour, self.bandwidth, who, &message);
 	}
 
 	fn send_read_request(
@@ -229,7 +253,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			keys,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		send_packet::<B>(self.behaviour, self.bandwidth, who, &message);
 	}
 
 	fn send_read_child_request(
@@ -251,7 +275,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			keys,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		send_packet::<B>(self.behaviour, self.bandwidth, who, &message);
 	}
 
 	fn send_call_request(
@@ -269,7 +293,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			data,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		send_packet::<B>(self.behaviour, self.bandwidth, who, &message);
 	}
 
 	fn send_changes_request(
@@ -293,7 +317,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			key,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		send_packet::<B>(self.behaviour, self.bandwidth, who, &message);
 	}
 
 	fn send_body_request(
@@ -315,7 +339,7 @@ impl<'a, B: BlockT> LightDispatchNetwork<B> for LightDispatchIn<'a> {
 			max,
 		});
 
-		self.behaviour.send_packet(who, message.encode())
+		send_packet::<B>(self.behaviour, self.bandwidth, who, &message);
 	}
 }
 
@@ -372,6 +396,7 @@ impl<'a, B: BlockT + 'a, H: ExHashT + 'a> Context<B> for ProtocolContext<'a, B,
 					send_message::<B> (
 						self.behaviour,
 						&mut self.context_data.stats,
+						&self.context_data.bandwidth,
 						&who,
 						GenericMessage::ConsensusBatch(std::mem::replace(&mut batch, Vec::new())),
 					)
@@ -383,6 +408,7 @@ impl<'a, B: BlockT + 'a, H: ExHashT + 'a> Context<B> for ProtocolContext<'a, B,
 				send_message::<B> (
 					self.behaviour,
 					&mut self.context_data.stats,
+					&self.context_data.bandwidth,
 					&who,
 					GenericMessage::Consensus(message)
 				)
@@ -394,6 +420,7 @@ impl<'a, B: BlockT + 'a, H: ExHashT + 'a> Context<B> for ProtocolContext<'a, B,
 		send_message::<B> (
 			self.behaviour,
 			&mut self.context_data.stats,
+			&self.context_data.bandwidth,
 			&who,
 			GenericMessage::ChainSpecific(message)
 		)
@@ -405,6 +432,8 @@ struct ContextData<B: BlockT, H: ExHashT> {
 	// All connected peers
 	peers: HashMap<PeerId, Peer<B, H>>,
 	stats: HashMap<&'static str, PacketStats>,
+	/// Bytes sent and received per protocol, shared with the `NetworkService`.
+	bandwidth: ProtocolBandwidthSinks,
 	pub chain: Arc<dyn Client<B>>,
 }
 
@@ -415,6 +444,8 @@ pub struct ProtocolConfig {
 	pub roles: Roles,
 	/// Maximum number of peers to ask the same blocks in parallel.
 	pub max_parallel_downloads: u32,
+	/// How to sync the chain if the database is empty.
+	pub sync_mode: SyncMode,
 }
 
 impl Default for ProtocolConfig {
@@ -422,6 +453,7 @@ impl Default for ProtocolConfig {
 		ProtocolConfig {
 			roles: Roles::FULL,
 			max_parallel_downloads: 5,
+			sync_mode: SyncMode::Full,
 		}
 	}
 }
@@ -436,9 +468,12 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		transaction_pool: Arc<dyn TransactionPool<H, B>>,
 		finality_proof_provider: Option<Arc<dyn FinalityProofProvider<B>>>,
 		finality_proof_request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
+		warp_sync_provider: Option<Arc<dyn WarpSyncProvider<B>>>,
+		fork_ranking: Option<Arc<dyn ForkRanking<B>>>,
 		protocol_id: ProtocolId,
 		peerset_config: sc_peerset::PeersetConfig,
-		block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>
+		block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>,
+		bandwidth: ProtocolBandwidthSinks,
 	) -> error::Result<(Protocol<B, S, H>, sc_peerset::PeersetHandle)> {
 		let info = chain.info();
 		let sync = ChainSync::new(
@@ -448,6 +483,9 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			finality_proof_request_builder,
 			block_announce_validator,
 			config.max_parallel_downloads,
+			warp_sync_provider.filter(|_| config.sync_mode == SyncMode::Warp),
+			config.sync_mode == SyncMode::Fast,
+			fork_ranking,
 		);
 
 		let important_peers = {
@@ -470,6 +508,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			context_data: ContextData {
 				peers: HashMap::new(),
 				stats: HashMap::new(),
+				bandwidth,
 				chain,
 			},
 			light_dispatch: LightDispatch::new(checker),
@@ -482,7 +521,8 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			finality_proof_provider,
 			peerset_handle: peerset_handle.clone(),
 			behaviour,
-			registered_notif_protocols: HashSet::new(),
+			registered_notif_protocols: HashMap::new(),
+			pending_notif_outcomes: VecDeque::new(),
 		};
 
 		Ok((protocol, peerset_handle))
@@ -498,6 +538,11 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.behaviour.is_open(peer_id)
 	}
 
+	/// Returns the number of messages waiting to be sent to each peer.
+	pub fn send_queue_lens(&self) -> SendQueueLens {
+		self.behaviour.send_queue_lens()
+	}
+
 	/// Disconnects the given peer if we are connected to it.
 	pub fn disconnect_peer(&mut self, peer_id: &PeerId) {
 		self.behaviour.disconnect_peer(peer_id)
@@ -513,6 +558,11 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.behaviour.peerset_debug_info()
 	}
 
+	/// Returns the reputation of all nodes known to the peerset manager.
+	pub fn peer_reputations(&mut self) -> Vec<sc_peerset::PeerReputation> {
+		self.behaviour.peer_reputations()
+	}
+
 	/// Returns the number of peers we're connected to.
 	pub fn num_connected_peers(&self) -> usize {
 		self.context_data.peers.values().count()
@@ -547,12 +597,28 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		self.sync.status().queued_blocks
 	}
 
+	/// Progress of the sync.
+	pub fn sync_state_event(&self) -> SyncStateEvent<NumberFor<B>> {
+		let status = self.sync.status();
+		match (status.state, status.best_seen_block) {
+			_ if status.num_peers == 0 => SyncStateEvent::Starting,
+			(SyncState::Downloading, Some(target)) => SyncStateEvent::Downloading {
+				target,
+				current: self.context_data.chain.info().chain.best_number,
+				peers: status.num_peers,
+			},
+			_ if status.queued_blocks > 0 => SyncStateEvent::Importing { queued: status.queued_blocks },
+			_ => SyncStateEvent::Idle,
+		}
+	}
+
 	/// Starts a new data demand request.
 	///
 	/// The parameter contains a `Sender` where the result, once received, must be sent.
 	pub(crate) fn add_light_client_request(&mut self, rq: RequestData<B>) {
 		self.light_dispatch.add_request(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			bandwidth: &self.context_data.bandwidth,
 			peerset: self.peerset_handle.clone(),
 		}, rq);
 	}
@@ -615,6 +681,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		let mut stats = self.context_data.stats.entry(message.id()).or_default();
 		stats.bytes_in += data.len() as u64;
 		stats.count_in += 1;
+		self.context_data.bandwidth.inbound(&bandwidth_usage(&message, data.len()));
 
 		match message {
 			GenericMessage::Status(s) => return self.on_status_message(who, s),
@@ -645,6 +712,14 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				self.on_remote_read_request(who, request),
 			GenericMessage::RemoteReadResponse(response) =>
 				self.on_remote_read_response(who, response),
+			GenericMessage::RemoteCallCompactResponse(response) => {
+				let proof = self.restore_proof(&who, response.proof);
+				self.on_remote_call_response(who, message::RemoteCallResponse { id: response.id, proof })
+			},
+			GenericMessage::RemoteReadCompactResponse(response) => {
+				let proof = self.restore_proof(&who, response.proof);
+				self.on_remote_read_response(who, message::RemoteReadResponse { id: response.id, proof })
+			},
 			GenericMessage::RemoteHeaderRequest(request) =>
 				self.on_remote_header_request(who, request),
 			GenericMessage::RemoteHeaderResponse(response) =>
@@ -660,23 +735,21 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			GenericMessage::RemoteReadChildRequest(request) =>
 				self.on_remote_read_child_request(who, request),
 			GenericMessage::Consensus(msg) =>
-				return if self.registered_notif_protocols.contains(&msg.engine_id) {
+				return if self.is_notif_protocol_open(&who, &msg.engine_id) {
 					CustomMessageOutcome::NotificationsReceived {
 						remote: who.clone(),
 						messages: vec![(msg.engine_id, From::from(msg.data))],
 					}
 				} else {
-					warn!(target: "sync", "Received message on non-registered protocol: {:?}", msg.engine_id);
 					CustomMessageOutcome::None
 				},
 			GenericMessage::ConsensusBatch(messages) => {
 				let messages = messages
 					.into_iter()
 					.filter_map(|msg| {
-						if self.registered_notif_protocols.contains(&msg.engine_id) {
+						if self.is_notif_protocol_open(&who, &msg.engine_id) {
 							Some((msg.engine_id, From::from(msg.data)))
 						} else {
-							warn!(target: "sync", "Received message on non-registered protocol: {:?}", msg.engine_id);
 							None
 						}
 					})
@@ -691,6 +764,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 					CustomMessageOutcome::None
 				};
 			},
+			GenericMessage::NotificationsHandshake(msg) =>
+				return self.on_notifications_handshake(who, msg),
+			GenericMessage::NotificationsClose(engine_id) =>
+				return self.on_notifications_close(who, engine_id),
 			GenericMessage::ChainSpecific(msg) => self.specialization.on_message(
 				&mut ProtocolContext::new(&mut self.context_data, &mut self.behaviour, &self.peerset_handle),
 				who,
@@ -705,6 +782,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		send_request::<B, H>(
 			&mut self.behaviour,
 			&mut self.context_data.stats,
+			&self.context_data.bandwidth,
 			&mut self.context_data.peers,
 			who,
 			message,
@@ -715,6 +793,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		send_message::<B>(
 			&mut self.behaviour,
 			&mut self.context_data.stats,
+			&self.context_data.bandwidth,
 			who,
 			message,
 		);
@@ -754,6 +833,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			self.specialization.on_disconnect(&mut context, peer.clone());
 			self.light_dispatch.on_disconnect(LightDispatchIn {
 				behaviour: &mut self.behaviour,
+				bandwidth: &self.context_data.bandwidth,
 				peerset: self.peerset_handle.clone(),
 			}, peer);
 		}
@@ -913,8 +993,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 	/// > **Note**: This method normally doesn't have to be called except for testing purposes.
 	pub fn tick(&mut self) {
 		self.maintain_peers();
+		self.sync.tick();
 		self.light_dispatch.maintain_peers(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			bandwidth: &self.context_data.bandwidth,
 			peerset: self.peerset_handle.clone(),
 		});
 	}
@@ -1047,6 +1129,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 					.expect("Constant is nonzero")),
 				next_request_id: 0,
 				obsolete_requests: HashMap::new(),
+				unregistered_handshakes: HashMap::new(),
 			};
 			self.context_data.peers.insert(who.clone(), peer);
 
@@ -1057,6 +1140,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		let info = self.context_data.peers.get(&who).expect("We just inserted above; QED").info.clone();
 		self.light_dispatch.on_connect(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			bandwidth: &self.context_data.bandwidth,
 			peerset: self.peerset_handle.clone(),
 		}, who.clone(), status.roles, status.best_number);
 		if info.roles.is_full() {
@@ -1073,14 +1157,135 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		let mut context = ProtocolContext::new(&mut self.context_data, &mut self.behaviour, &self.peerset_handle);
 		self.specialization.on_connect(&mut context, who.clone(), status);
 
-		// Notify all the notification protocols as open.
+		// Protocols without handshake are open right away, the others once we receive the
+		// handshake of the remote.
+		let mut protocols = Vec::new();
+		let mut handshakes = Vec::new();
+		for (engine_id, protocol) in self.registered_notif_protocols.iter_mut() {
+			match protocol.handshake() {
+				None => if protocol.open(&who, info.roles, &[]) == notifications::OpenOutcome::Opened {
+					protocols.push((*engine_id, Vec::new()));
+				},
+				Some(handshake) => if info.protocol_version >= MIN_NOTIFICATIONS_HANDSHAKE_VERSION {
+					handshakes.push(ConsensusMessage { engine_id: *engine_id, data: handshake.to_vec() });
+				},
+			}
+		}
+		for handshake in handshakes {
+			self.send_message(&who, GenericMessage::NotificationsHandshake(handshake));
+		}
+
 		CustomMessageOutcome::NotificationStreamOpened {
 			remote: who,
-			protocols: self.registered_notif_protocols.iter().cloned().collect(),
+			protocols,
 			roles: info.roles,
 		}
 	}
 
+	/// Called when a peer sends us its handshake for a notifications protocol.
+	fn on_notifications_handshake(&mut self, who: PeerId, handshake: ConsensusMessage) -> CustomMessageOutcome<B> {
+		let peer = match self.context_data.peers.get_mut(&who) {
+			Some(peer) => peer,
+			None => {
+				debug!(target: "sync", "Notifications handshake from {} before its status", who);
+				self.peerset_handle.report_peer(who, rep::UNEXPECTED_HANDSHAKE);
+				return CustomMessageOutcome::None;
+			}
+		};
+		let roles = peer.info.roles;
+
+		let protocol = match self.registered_notif_protocols.get_mut(&handshake.engine_id) {
+			Some(protocol) if protocol.handshake().is_some() => protocol,
+			Some(_) => {
+				trace!(
+					target: "sync",
+					"Ignoring handshake of {} on protocol {:?} without handshake",
+					who,
+					handshake.engine_id,
+				);
+				return CustomMessageOutcome::None;
+			}
+			None => {
+				// Kept in case the protocol gets registered later on.
+				let handshakes = &mut peer.unregistered_handshakes;
+				if handshakes.len() < MAX_UNREGISTERED_HANDSHAKES || handshakes.contains_key(&handshake.engine_id) {
+					handshakes.insert(handshake.engine_id, handshake.data);
+				} else {
+					trace!(target: "sync", "Dropping handshake of {} on unknown protocol {:?}", who, handshake.engine_id);
+				}
+				return CustomMessageOutcome::None;
+			}
+		};
+
+		match protocol.open(&who, roles, &handshake.data) {
+			notifications::OpenOutcome::Opened => CustomMessageOutcome::NotificationStreamOpened {
+				remote: who,
+				protocols: vec![(handshake.engine_id, handshake.data)],
+				roles,
+			},
+			notifications::OpenOutcome::Rejected => {
+				self.send_notifications_close(&who, handshake.engine_id);
+				CustomMessageOutcome::None
+			},
+			notifications::OpenOutcome::AlreadyOpen |
+			notifications::OpenOutcome::Pending => CustomMessageOutcome::None,
+		}
+	}
+
+	/// Called when a peer closes a notifications protocol on its side, e.g. after rejecting our
+	/// handshake.
+	fn on_notifications_close(&mut self, who: PeerId, engine_id: ConsensusEngineId) -> CustomMessageOutcome<B> {
+		let closed = match self.registered_notif_protocols.get_mut(&engine_id) {
+			Some(protocol) => protocol.close(&who),
+			None => false,
+		};
+		if !closed {
+			trace!(target: "sync", "Ignoring close of protocol {:?} by {} as it isn't open", engine_id, who);
+			return CustomMessageOutcome::None;
+		}
+
+		debug!(target: "sync", "Protocol {:?} closed by {}", engine_id, who);
+		self.open_pending_notif_protocols();
+		CustomMessageOutcome::NotificationsStreamClosed {
+			remote: who,
+			protocols: vec![engine_id],
+		}
+	}
+
+	/// Asks a peer to close a notifications protocol, if it supports it.
+	fn send_notifications_close(&mut self, who: &PeerId, engine_id: ConsensusEngineId) {
+		let supported = self.context_data.peers.get(who)
+			.map_or(false, |peer| peer.info.protocol_version >= MIN_NOTIFICATIONS_CLOSE_VERSION);
+		if supported {
+			self.send_message(who, GenericMessage::NotificationsClose(engine_id));
+		}
+	}
+
+	/// Opens the notifications protocols with the peers waiting for a slot, if some were freed.
+	/// The resulting events are reported on the next calls to `poll`.
+	fn open_pending_notif_protocols(&mut self) {
+		for (engine_id, protocol) in self.registered_notif_protocols.iter_mut() {
+			for (remote, roles, handshake) in protocol.open_pending() {
+				self.pending_notif_outcomes.push_back(CustomMessageOutcome::NotificationStreamOpened {
+					remote,
+					protocols: vec![(*engine_id, handshake)],
+					roles,
+				});
+			}
+		}
+	}
+
+	/// Returns true if the given notifications protocol is open with the given peer.
+	fn is_notif_protocol_open(&self, who: &PeerId, engine_id: &ConsensusEngineId) -> bool {
+		match self.registered_notif_protocols.get(engine_id) {
+			Some(protocol) => protocol.is_open(who),
+			None => {
+				warn!(target: "sync", "Received message on non-registered protocol: {:?}", engine_id);
+				false
+			}
+		}
+	}
+
 	/// Send a notification to the given peer we're connected to.
 	///
 	/// Doesn't do anything if we don't have a notifications substream for that protocol with that
@@ -1091,12 +1296,24 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		engine_id: ConsensusEngineId,
 		message: impl Into<Vec<u8>>
 	) {
-		if !self.registered_notif_protocols.contains(&engine_id) {
-			error!(
-				target: "sub-libp2p",
-				"Sending a notification with a protocol that wasn't registered: {:?}",
-				engine_id
-			);
+		match self.registered_notif_protocols.get(&engine_id) {
+			Some(protocol) => if !protocol.is_open(&target) {
+				trace!(
+					target: "sub-libp2p",
+					"Dropping notification to {} on protocol {:?} not open with it",
+					target,
+					engine_id,
+				);
+				return;
+			},
+			None => {
+				error!(
+					target: "sub-libp2p",
+					"Sending a notification with a protocol that wasn't registered: {:?}",
+					engine_id
+				);
+				return;
+			}
 		}
 
 		self.send_message(&target, GenericMessage::Consensus(ConsensusMessage {
@@ -1111,22 +1328,63 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 	/// will retain the protocols that were registered then, and not any new one.
 	pub fn register_notifications_protocol(
 		&mut self,
-		engine_id: ConsensusEngineId,
+		config: notifications::NotificationsProtocolConfig,
 	) -> Vec<event::Event> {
-		if !self.registered_notif_protocols.insert(engine_id) {
+		let engine_id = config.engine_id;
+		if self.registered_notif_protocols.contains_key(&engine_id) {
 			error!(target: "sub-libp2p", "Notifications protocol already registered: {:?}", engine_id);
+			return Vec::new();
 		}
 
+		let mut protocol = notifications::RegisteredProtocol::new(config);
 		// Registering a protocol while we already have open connections isn't great, but for now
-		// we handle it by notifying that we opened channels with everyone.
-		self.context_data.peers.iter()
-			.map(|(peer_id, peer)|
-				event::Event::NotificationStreamOpened {
+		// we handle it by opening the protocol with everyone if it has no handshake. Otherwise we
+		// send our handshake now, and use the handshakes the remotes have already sent us.
+		let mut events = Vec::new();
+		let mut rejected = Vec::new();
+		for (peer_id, peer) in self.context_data.peers.iter_mut() {
+			let roles = peer.info.roles;
+			let handshake = match protocol.handshake() {
+				None => Vec::new(),
+				Some(handshake) => {
+					if peer.info.protocol_version < MIN_NOTIFICATIONS_HANDSHAKE_VERSION {
+						continue;
+					}
+					send_message::<B>(
+						&mut self.behaviour,
+						&mut self.context_data.stats,
+						&self.context_data.bandwidth,
+						peer_id,
+						GenericMessage::NotificationsHandshake(ConsensusMessage {
+							engine_id,
+							data: handshake.to_vec(),
+						}),
+					);
+					match peer.unregistered_handshakes.remove(&engine_id) {
+						Some(remote_handshake) => remote_handshake,
+						None => continue,
+					}
+				},
+			};
+
+			match protocol.open(peer_id, roles, &handshake) {
+				notifications::OpenOutcome::Opened => events.push(event::Event::NotificationStreamOpened {
 					remote: peer_id.clone(),
 					engine_id,
-					roles: peer.info.roles,
-				})
-			.collect()
+					roles,
+					handshake,
+				}),
+				notifications::OpenOutcome::Rejected => rejected.push(peer_id.clone()),
+				notifications::OpenOutcome::AlreadyOpen |
+				notifications::OpenOutcome::Pending => {},
+			}
+		}
+
+		self.registered_notif_protocols.insert(engine_id, protocol);
+		for peer_id in rejected {
+			self.send_notifications_close(&peer_id, engine_id);
+		}
+		events
 	}
 
 	/// Called when peer sends us new extrinsics
@@ -1201,6 +1459,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				send_message::<B> (
 					&mut self.behaviour,
 					&mut self.context_data.stats,
+					&self.context_data.bandwidth,
 					&who,
 					GenericMessage::Transactions(to_send)
 				)
@@ -1265,6 +1524,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				send_message::<B> (
 					&mut self.behaviour,
 					&mut self.context_data.stats,
+					&self.context_data.bandwidth,
 					&who,
 					message,
 				)
@@ -1295,6 +1555,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		}
 		self.light_dispatch.update_best_number(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			bandwidth: &self.context_data.bandwidth,
 			peerset: self.peerset_handle.clone(),
 		}, who.clone(), *announce.header.number());
 
@@ -1313,6 +1574,10 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				// 2) parent block is already imported and not pruned.
 				return CustomMessageOutcome::None
 			}
+			sync::OnBlockAnnounce::Rejected => {
+				self.peerset_handle.report_peer(who, rep::BAD_BLOCK_ANNOUNCEMENT);
+				return CustomMessageOutcome::None
+			}
 			sync::OnBlockAnnounce::ImportHeader => () // We proceed with the import.
 		}
 
@@ -1413,13 +1678,45 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 			}
 		};
 
-		self.send_message(
-			&who,
-			GenericMessage::RemoteCallResponse(message::RemoteCallResponse {
-				id: request.id,
-				proof,
-			}),
-		);
+		let message = match self.compact_proof(&who, proof) {
+			Ok(proof) => GenericMessage::RemoteCallCompactResponse(
+				message::RemoteCompactProofResponse { id: request.id, proof }
+			),
+			Err(proof) => GenericMessage::RemoteCallResponse(
+				message::RemoteCallResponse { id: request.id, proof }
+			),
+		};
+		self.send_message(&who, message);
+	}
+
+	/// Returns whether `who` supports compact proofs.
+	///
+	/// Peers we have no status of are assumed not to support them.
+	fn supports_compact_proofs(&self, who: &PeerId) -> bool {
+		self.context_data.peers.get(who)
+			.map_or(false, |peer| peer.info.protocol_version >= MIN_COMPACT_PROOF_VERSION)
+	}
+
+	/// Compact `proof` if `who` supports compact proofs, or returns it unchanged.
+	fn compact_proof(&self, who: &PeerId, proof: StorageProof) -> Result<CompactProof, StorageProof> {
+		if !self.supports_compact_proofs(who) {
+			return Err(proof);
+		}
+		proof.clone().into_compact_proof::<Blake2Hasher>().map_err(|error| {
+			warn!(target: "sync", "Failed to compact a proof for {}: {:?}", who, error);
+			proof
+		})
+	}
+
+	/// Restore a compact proof received from `who`, reporting the peer if it is invalid.
+	///
+	/// An invalid proof is replaced by an empty one, so that the request fails its check.
+	fn restore_proof(&self, who: &PeerId, proof: CompactProof) -> StorageProof {
+		StorageProof::from_compact_proof::<Blake2Hasher>(&proof).unwrap_or_else(|error| {
+			debug!(target: "sync", "Invalid compact proof from {}: {:?}", who, error);
+			self.peerset_handle.report_peer(who.clone(), rep::BAD_MESSAGE);
+			StorageProof::empty()
+		})
 	}
 
 	/// Request a justification for the given block.
@@ -1458,6 +1755,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 					send_request(
 						&mut self.behaviour,
 						&mut self.context_data.stats,
+						&self.context_data.bandwidth,
 						&mut self.context_data.peers,
 						&id,
 						msg
@@ -1500,6 +1798,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		trace!(target: "sync", "Remote call response {} from {}", response.id, who);
 		self.light_dispatch.on_remote_call_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			bandwidth: &self.context_data.bandwidth,
 			peerset: self.peerset_handle.clone(),
 		}, who, response);
 	}
@@ -1533,13 +1832,15 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 				StorageProof::empty()
 			}
 		};
-		self.send_message(
-			&who,
-			GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
-				id: request.id,
-				proof,
-			}),
-		);
+		let message = match self.compact_proof(&who, proof) {
+			Ok(proof) => GenericMessage::RemoteReadCompactResponse(
+				message::RemoteCompactProofResponse { id: request.id, proof }
+			),
+			Err(proof) => GenericMessage::RemoteReadResponse(
+				message::RemoteReadResponse { id: request.id, proof }
+			),
+		};
+		self.send_message(&who, message);
 	}
 
 	fn on_remote_read_child_request(
@@ -1590,13 +1891,15 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 
 			StorageProof::empty()
 		};
-		self.send_message(
-			&who,
-			GenericMessage::RemoteReadResponse(message::RemoteReadResponse {
-				id: request.id,
-				proof,
-			}),
-		);
+		let message = match self.compact_proof(&who, proof) {
+			Ok(proof) => GenericMessage::RemoteReadCompactResponse(
+				message::RemoteCompactProofResponse { id: request.id, proof }
+			),
+			Err(proof) => GenericMessage::RemoteReadResponse(
+				message::RemoteReadResponse { id: request.id, proof }
+			),
+		};
+		self.send_message(&who, message);
 	}
 
 	fn on_remote_read_response(
@@ -1607,6 +1910,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		trace!(target: "sync", "Remote read response {} from {}", response.id, who);
 		self.light_dispatch.on_remote_read_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			bandwidth: &self.context_data.bandwidth,
 			peerset: self.peerset_handle.clone(),
 		}, who, response);
 	}
@@ -1648,6 +1952,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		trace!(target: "sync", "Remote header proof response {} from {}", response.id, who);
 		self.light_dispatch.on_remote_header_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			bandwidth: &self.context_data.bandwidth,
 			peerset: self.peerset_handle.clone(),
 		}, who, response);
 	}
@@ -1724,6 +2029,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		);
 		self.light_dispatch.on_remote_changes_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			bandwidth: &self.context_data.bandwidth,
 			peerset: self.peerset_handle.clone(),
 		}, who, response);
 	}
@@ -1734,22 +2040,12 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		request: message::FinalityProofRequest<B::Hash>,
 	) {
 		trace!(target: "sync", "Finality proof request from {} for {}", who, request.block);
-		let finality_proof = self.finality_proof_provider.as_ref()
-			.ok_or_else(|| String::from("Finality provider is not configured"))
-			.and_then(|provider|
-				provider.prove_finality(request.block, &request.request).map_err(|e| e.to_string())
-			);
-		let finality_proof = match finality_proof {
-			Ok(finality_proof) => finality_proof,
-			Err(error) => {
-				trace!(target: "sync", "Finality proof request from {} for {} failed with: {}",
-					who,
-					request.block,
-					error
-				);
-				None
-			},
-		};
+		let finality_proof = prove_finality(
+			self.finality_proof_provider.as_ref(),
+			&who,
+			&request,
+			self.supports_compact_proofs(&who),
+		);
 		self.send_message(
 			&who,
 			GenericMessage::FinalityProofResponse(message::FinalityProofResponse {
@@ -1760,6 +2056,29 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 		);
 	}
 
+	/// Get the next warp sync request to send, if any.
+	pub fn warp_sync_request(&mut self) -> Option<(PeerId, WarpSyncRequest<B>)> {
+		self.sync.warp_sync_request()
+	}
+
+	/// Handle the response to a warp sync request.
+	///
+	/// Returns the block to import along with its state once the warp sync is complete.
+	pub fn on_warp_sync_response(
+		&mut self,
+		who: PeerId,
+		response: Result<Vec<u8>, RequestFailure>,
+	) -> Option<IncomingBlock<B>> {
+		match self.sync.on_warp_sync_response(who, response) {
+			Ok(block) => block,
+			Err(sync::BadPeer(id, repu)) => {
+				self.behaviour.disconnect_peer(&id);
+				self.peerset_handle.report_peer(id, repu);
+				None
+			},
+		}
+	}
+
 	fn on_finality_proof_response(
 		&mut self,
 		who: PeerId,
@@ -1785,6 +2104,7 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 	) {
 		self.light_dispatch.on_remote_body_response(LightDispatchIn {
 			behaviour: &mut self.behaviour,
+			bandwidth: &self.context_data.bandwidth,
 			peerset: self.peerset_handle.clone(),
 		}, peer, response);
 	}
@@ -1806,14 +2126,150 @@ impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Protocol<B, S, H> {
 	}
 }
 
+/// Name of the request-response protocol on which finality proofs are requested.
+pub fn finality_proof_protocol_name(protocol_id: &ProtocolId) -> String {
+	format!("/{}/finality-proof", String::from_utf8_lossy(protocol_id.as_bytes()))
+}
+
+/// Answers the requests received on the finality proof, warp sync proof and state protocols.
+///
+/// Building a response reads the database and may take a while, thus this is used away from the
+/// network worker.
+pub struct RequestHandler<B: BlockT> {
+	chain: Arc<dyn Client<B>>,
+	finality_proof_provider: Option<Arc<dyn FinalityProofProvider<B>>>,
+	warp_sync_provider: Option<Arc<dyn WarpSyncProvider<B>>>,
+	peerset_handle: sc_peerset::PeersetHandle,
+}
+
+impl<B: BlockT> RequestHandler<B> {
+	/// Create a new instance.
+	pub fn new(
+		chain: Arc<dyn Client<B>>,
+		finality_proof_provider: Option<Arc<dyn FinalityProofProvider<B>>>,
+		warp_sync_provider: Option<Arc<dyn WarpSyncProvider<B>>>,
+		peerset_handle: sc_peerset::PeersetHandle,
+	) -> Self {
+		RequestHandler { chain, finality_proof_provider, warp_sync_provider, peerset_handle }
+	}
+
+	/// Answer a request received on the finality proof protocol. Returns `None` to refuse it.
+	///
+	/// The request and the response are the same as the `FinalityProofRequest` and
+	/// `FinalityProofResponse` messages.
+	pub fn on_finality_proof_request(&self, who: &PeerId, request: &[u8]) -> Option<Vec<u8>> {
+		let request = match message::FinalityProofRequest::<B::Hash>::decode(&mut &request[..]) {
+			Ok(request) => request,
+			Err(e) => {
+				debug!(target: "sync", "Failed to decode finality proof request from {}: {:?}", who, e);
+				self.peerset_handle.report_peer(who.clone(), rep::BAD_MESSAGE);
+				return None
+			},
+		};
+		trace!(target: "sync", "Finality proof protocol request from {} for {}", who, request.block);
+
+		let proof = prove_finality(
+			self.finality_proof_provider.as_ref(),
+			who,
+			&request,
+			self.supports_compact_proofs(who),
+		);
+		let response = message::FinalityProofResponse { id: request.id, block: request.block, proof }.encode();
+		check_response_size(who, response)
+	}
+
+	/// Answer a request received on the warp sync proof protocol. Returns `None` to refuse it.
+	pub fn on_warp_proof_request(&self, who: &PeerId, request: &[u8]) -> Option<Vec<u8>> {
+		let request = match warp::WarpProofRequest::<B::Hash>::decode(&mut &request[..]) {
+			Ok(request) => request,
+			Err(e) => {
+				debug!(target: "sync", "Failed to decode warp sync proof request from {}: {:?}", who, e);
+				self.peerset_handle.report_peer(who.clone(), rep::BAD_MESSAGE);
+				return None
+			},
+		};
+		trace!(target: "sync", "Warp sync proof request from {} starting at {}", who, request.begin);
+
+		let provider = self.warp_sync_provider.as_ref()?;
+		match provider.generate(request.begin) {
+			Ok(proof) => check_response_size(who, proof),
+			Err(e) => {
+				trace!(target: "sync", "Warp sync proof request from {} failed with: {}", who, e);
+				None
+			},
+		}
+	}
+
+	/// Answer a request received on the state protocol. Returns `None` to refuse it.
+	pub fn on_state_request(&self, who: &PeerId, request: &[u8]) -> Option<Vec<u8>> {
+		let request = match warp::StateRequest::<B::Hash>::decode(&mut &request[..]) {
+			Ok(request) => request,
+			Err(e) => {
+				debug!(target: "sync", "Failed to decode state request from {}: {:?}", who, e);
+				self.peerset_handle.report_peer(who.clone(), rep::BAD_MESSAGE);
+				return None
+			},
+		};
+		trace!(target: "sync", "State request from {} for {}", who, request.block);
+
+		match self.chain.state_entries(&request.block, &request.start, warp::STATE_RESPONSE_SIZE) {
+			Ok((entries, complete)) => check_response_size(who, warp::StateResponse { entries, complete }.encode()),
+			Err(e) => {
+				trace!(target: "sync", "State request from {} for {} failed with: {}", who, request.block, e);
+				None
+			},
+		}
+	}
+}
+
+/// Refuse responses which the requester would reject for being too large.
+fn check_response_size(who: &PeerId, response: Vec<u8>) -> Option<Vec<u8>> {
+	if response.len() > warp::MAX_RESPONSE_SIZE {
+		debug!(
+			target: "sync",
+			"Not answering the request of {}, the response of {} bytes is too large",
+			who,
+			response.len(),
+		);
+		return None
+	}
+	Some(response)
+}
+
+/// Prove the finality of a block with the given `FinalityProofProvider`, if any.
+fn prove_finality<B: BlockT>(
+	finality_proof_provider: Option<&Arc<dyn FinalityProofProvider<B>>>,
+	who: &PeerId,
+	request: &message::FinalityProofRequest<B::Hash>,
+	compact_proofs: bool,
+) -> Option<Vec<u8>> {
+	let finality_proof = finality_proof_provider
+		.ok_or_else(|| String::from("Finality provider is not configured"))
+		.and_then(|provider|
+			provider.prove_finality(request.block, &request.request, compact_proofs)
+				.map_err(|e| e.to_string())
+		);
+	match finality_proof {
+		Ok(finality_proof) => finality_proof,
+		Err(error) => {
+			trace!(target: "sync", "Finality proof request from {} for {} failed with: {}",
+				who,
+				request.block,
+				error
+			);
+			None
+		},
+	}
+}
+
 /// Outcome of an incoming custom message.
 #[derive(Debug)]
 pub enum CustomMessageOutcome<B: BlockT> {
 	BlockImport(BlockOrigin, Vec<IncomingBlock<B>>),
 	JustificationImport(Origin, B::Hash, NumberFor<B>, Justification),
 	FinalityProofImport(Origin, B::Hash, NumberFor<B>, Vec<u8>),
-	/// Notification protocols have been opened with a remote.
-	NotificationStreamOpened { remote: PeerId, protocols: Vec<ConsensusEngineId>, roles: Roles },
+	/// Notification protocols have been opened with a remote, along with the handshake it sent.
+	NotificationStreamOpened { remote: PeerId, protocols: Vec<(ConsensusEngineId, Vec<u8>)>, roles: Roles },
 	/// Notification protocols have been closed with a remote.
 	NotificationsStreamClosed { remote: PeerId, protocols: Vec<ConsensusEngineId> },
 	/// Messages have been received on one or more notifications protocols.
@@ -1824,6 +2280,7 @@ pub enum CustomMessageOutcome<B: BlockT> {
 fn send_request<B: BlockT, H: ExHashT>(
 	behaviour: &mut LegacyProto<Substream<StreamMuxerBox>>,
 	stats: &mut HashMap<&'static str, PacketStats>,
+	bandwidth: &ProtocolBandwidthSinks,
 	peers: &mut HashMap<PeerId, Peer<B, H>>,
 	who: &PeerId,
 	mut message: Message<B>,
@@ -1839,20 +2296,83 @@ fn send_request<B: BlockT, H: ExHashT>(
 			peer.block_request = Some((time::Instant::now(), r.clone()));
 		}
 	}
-	send_message::<B>(behaviour, stats, who, message)
+	send_message::<B>(behaviour, stats, bandwidth, who, message)
 }
 
 fn send_message<B: BlockT>(
 	behaviour: &mut LegacyProto<Substream<StreamMuxerBox>>,
 	stats: &mut HashMap<&'static str, PacketStats>,
+	bandwidth: &ProtocolBandwidthSinks,
 	who: &PeerId,
 	message: Message<B>,
 ) {
+	if let Some(len) = send_packet::<B>(behaviour, bandwidth, who, &message) {
+		let mut stats = stats.entry(message.id()).or_default();
+		stats.bytes_out += len as u64;
+		stats.count_out += 1;
+	}
+}
+
+/// Encodes and sends a message, unless the rate limit of one of the gossip protocols it belongs
+/// to is exceeded. Returns the number of bytes sent, if any.
+fn send_packet<B: BlockT>(
+	behaviour: &mut LegacyProto<Substream<StreamMuxerBox>>,
+	bandwidth: &ProtocolBandwidthSinks,
+	who: &PeerId,
+	message: &Message<B>,
+) -> Option<usize> {
 	let encoded = message.encode();
-	let mut stats = stats.entry(message.id()).or_default();
-	stats.bytes_out += encoded.len() as u64;
-	stats.count_out += 1;
+	let len = encoded.len();
+	if !bandwidth.outbound(&bandwidth_usage(message, len)) {
+		debug!(
+			target: "sub-libp2p",
+			"Dropping {} message of {} bytes to {}: protocol rate limit exceeded",
+			message.id(), len, who,
+		);
+		return None;
+	}
+
 	behaviour.send_packet(who, encoded);
+	Some(len)
+}
+
+/// Splits the size of an encoded message into the number of bytes used by each protocol.
+///
+/// The messages of a consensus batch are accounted to their respective engines, leaving out the
+/// overhead of the batch itself.
+fn bandwidth_usage<B: BlockT>(message: &Message<B>, encoded_len: usize) -> Vec<(BandwidthProtocol, u64)> {
+	let protocol = match message {
+		GenericMessage::BlockAnnounce(_) => BandwidthProtocol::BlockAnnounce,
+		GenericMessage::Transactions(_) => BandwidthProtocol::Transactions,
+		GenericMessage::Consensus(message) |
+		GenericMessage::NotificationsHandshake(message) => BandwidthProtocol::Consensus(message.engine_id),
+		GenericMessage::NotificationsClose(engine_id) => BandwidthProtocol::Consensus(*engine_id),
+		GenericMessage::ConsensusBatch(messages) => return messages.iter()
+			.map(|m| (
+				BandwidthProtocol::Consensus(m.engine_id),
+				(m.engine_id.len() + m.data.len()) as u64,
+			))
+			.collect(),
+		GenericMessage::Status(_) |
+		GenericMessage::BlockRequest(_) |
+		GenericMessage::BlockResponse(_) |
+		GenericMessage::FinalityProofRequest(_) |
+		GenericMessage::FinalityProofResponse(_) => BandwidthProtocol::Sync,
+		GenericMessage::RemoteCallRequest(_) |
+		GenericMessage::RemoteCallResponse(_) |
+		GenericMessage::RemoteReadRequest(_) |
+		GenericMessage::RemoteReadResponse(_) |
+		GenericMessage::RemoteCallCompactResponse(_) |
+		GenericMessage::RemoteReadCompactResponse(_) |
+		GenericMessage::RemoteReadChildRequest(_) |
+		GenericMessage::RemoteHeaderRequest(_) |
+		GenericMessage::RemoteHeaderResponse(_) |
+		GenericMessage::RemoteChangesRequest(_) |
+		GenericMessage::RemoteChangesResponse(_) => BandwidthProtocol::LightClient,
+		GenericMessage::ChainSpecific(_) => BandwidthProtocol::Other,
+	};
+
+	vec![(protocol, encoded_len as u64)]
 }
 
 impl<B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> NetworkBehaviour for
@@ -1905,6 +2425,7 @@ Protocol<B, S, H> {
 			send_request(
 				&mut self.behaviour,
 				&mut self.context_data.stats,
+				&self.context_data.bandwidth,
 				&mut self.context_data.peers,
 				&id,
 				GenericMessage::BlockRequest(r)
@@ -1914,6 +2435,7 @@ Protocol<B, S, H> {
 			send_request(
 				&mut self.behaviour,
 				&mut self.context_data.stats,
+				&self.context_data.bandwidth,
 				&mut self.context_data.peers,
 				&id,
 				GenericMessage::BlockRequest(r)
@@ -1923,11 +2445,16 @@ Protocol<B, S, H> {
 			send_request(
 				&mut self.behaviour,
 				&mut self.context_data.stats,
+				&self.context_data.bandwidth,
 				&mut self.context_data.peers,
 				&id,
 				GenericMessage::FinalityProofRequest(r))
 		}
 
+		if let Some(outcome) = self.pending_notif_outcomes.pop_front() {
+			return Async::Ready(NetworkBehaviourAction::GenerateEvent(outcome));
+		}
+
 		let event = match self.behaviour.poll(params) {
 			Async::NotReady => return Async::NotReady,
 			Async::Ready(NetworkBehaviourAction::GenerateEvent(ev)) => ev,
@@ -1952,10 +2479,18 @@ Protocol<B, S, H> {
 			}
 			LegacyProtoOut::CustomProtocolClosed { peer_id, .. } => {
 				self.on_peer_disconnected(peer_id.clone());
-				// Notify all the notification protocols as closed.
+				// Notify the notification protocols that were open as closed.
+				let protocols = self.registered_notif_protocols.iter_mut()
+					.filter_map(|(engine_id, protocol)| if protocol.close(&peer_id) {
+						Some(*engine_id)
+					} else {
+						None
+					})
+					.collect();
+				self.open_pending_notif_protocols();
 				CustomMessageOutcome::NotificationsStreamClosed {
 					remote: peer_id,
-					protocols: self.registered_notif_protocols.iter().cloned().collect(),
+					protocols,
 				}
 			},
 			LegacyProtoOut::CustomMessage { peer_id, message } =>
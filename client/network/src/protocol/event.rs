@@ -23,7 +23,8 @@ use libp2p::core::PeerId;
 use libp2p::kad::record::Key;
 use sp_runtime::ConsensusEngineId;
 
-/// Events generated by DHT as a response to get_value and put_value requests.
+/// Events generated by DHT as a response to get_value, put_value, get_providers and
+/// start_providing requests.
 #[derive(Debug, Clone)]
 #[must_use]
 pub enum DhtEvent {
@@ -38,8 +39,40 @@ pub enum DhtEvent {
 
 	/// An error has occured while putting a record into the DHT.
 	ValuePutFailed(Key),
+
+	/// Nodes providing the key have been found.
+	ProvidersFound(Key, Vec<PeerId>),
+
+	/// No node providing the key has been found.
+	ProvidersNotFound(Key),
+
+	/// The local node has been announced as a provider of the key.
+	StartedProviding(Key),
+
+	/// An error has occured while announcing the local node as a provider of the key.
+	StartProvidingFailed(Key),
 }
 
+/// Error of a DHT request started through `NetworkService::get_value_async` or
+/// `NetworkService::put_value_async`.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum DhtError {
+	/// The requested record has not been found in the DHT.
+	#[display(fmt = "Record not found in the DHT.")]
+	NotFound,
+	/// An error has occured while putting a record into the DHT.
+	#[display(fmt = "Failed to put record into the DHT.")]
+	PutFailed,
+	/// The DHT did not answer the request in time.
+	#[display(fmt = "DHT request timed out.")]
+	Timeout,
+	/// The network worker shut down before answering the request.
+	#[display(fmt = "Network worker shut down.")]
+	WorkerShutdown,
+}
+
+impl std::error::Error for DhtError {}
+
 /// Type for events generated by networking layer.
 #[derive(Debug, Clone)]
 #[must_use]
@@ -57,6 +90,9 @@ pub enum Event {
 		engine_id: ConsensusEngineId,
 		/// Roles that the remote .
 		roles: Roles,
+		/// Handshake sent by the remote when opening the protocol. Empty for the protocols
+		/// registered without handshake.
+		handshake: Vec<u8>,
 	},
 
 	/// Closed a substream with the given node. Always matches a corresponding previous
@@ -21,6 +21,7 @@
 //! network, then performs the Substrate protocol handling on top.
 
 pub use self::behaviour::{LegacyProto, LegacyProtoOut};
+pub use self::handler::SendQueueLens;
 
 mod behaviour;
 mod handler;
@@ -15,7 +15,7 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{DiscoveryNetBehaviour, config::ProtocolId};
-use crate::protocol::legacy_proto::handler::{CustomProtoHandlerProto, CustomProtoHandlerOut, CustomProtoHandlerIn};
+use crate::protocol::legacy_proto::handler::{CustomProtoHandlerProto, CustomProtoHandlerOut, CustomProtoHandlerIn, SendQueueLens};
 use crate::protocol::legacy_proto::upgrade::RegisteredProtocol;
 use bytes::BytesMut;
 use fnv::FnvHashMap;
@@ -81,6 +81,9 @@ pub struct LegacyProto< TSubstream> {
 	/// Events to produce from `poll()`.
 	events: SmallVec<[NetworkBehaviourAction<CustomProtoHandlerIn, LegacyProtoOut>; 4]>,
 
+	/// Number of messages waiting to be sent to each peer, shared with the handlers.
+	send_queue_lens: SendQueueLens,
+
 	/// Marker to pin the generics.
 	marker: PhantomData<TSubstream>,
 }
@@ -241,6 +244,7 @@ impl<TSubstream> LegacyProto<TSubstream> {
 			incoming: SmallVec::new(),
 			next_incoming_index: sc_peerset::IncomingIndex(0),
 			events: SmallVec::new(),
+			send_queue_lens: Default::default(),
 			marker: PhantomData,
 		}
 	}
@@ -250,6 +254,12 @@ impl<TSubstream> LegacyProto<TSubstream> {
 		self.peers.iter().filter(|(_, state)| state.is_open()).map(|(id, _)| id)
 	}
 
+	/// Returns the number of messages waiting to be sent to each peer, as updated by the
+	/// connection handlers.
+	pub fn send_queue_lens(&self) -> SendQueueLens {
+		self.send_queue_lens.clone()
+	}
+
 	/// Returns true if we have a channel open with this node.
 	pub fn is_open(&self, peer_id: &PeerId) -> bool {
 		self.peers.get(peer_id).map(|p| p.is_open()).unwrap_or(false)
@@ -369,6 +379,11 @@ impl<TSubstream> LegacyProto<TSubstream> {
 		self.peerset.debug_info()
 	}
 
+	/// Returns the reputation of all nodes known to the peerset manager.
+	pub fn peer_reputations(&mut self) -> Vec<sc_peerset::PeerReputation> {
+		self.peerset.peer_reputations()
+	}
+
 	/// Function that is called when the peerset wants us to connect to a node.
 	fn peerset_report_connect(&mut self, peer_id: PeerId) {
 		let mut occ_entry = match self.peers.entry(peer_id) {
@@ -622,7 +637,7 @@ where
 	type OutEvent = LegacyProtoOut;
 
 	fn new_handler(&mut self) -> Self::ProtocolsHandler {
-		CustomProtoHandlerProto::new(self.protocol.clone())
+		CustomProtoHandlerProto::new(self.protocol.clone(), self.send_queue_lens.clone())
 	}
 
 	fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
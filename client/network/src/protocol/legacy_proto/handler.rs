@@ -16,6 +16,7 @@
 
 use super::upgrade::{RegisteredProtocol, RegisteredProtocolEvent, RegisteredProtocolSubstream};
 use bytes::BytesMut;
+use fnv::FnvHashMap;
 use futures::prelude::*;
 use futures03::{compat::Compat, TryFutureExt as _};
 use futures_timer::Delay;
@@ -29,10 +30,16 @@ use libp2p::swarm::{
 	SubstreamProtocol,
 };
 use log::{debug, error};
+use parking_lot::Mutex;
 use smallvec::{smallvec, SmallVec};
 use std::{borrow::Cow, error, fmt, io, marker::PhantomData, mem, time::Duration};
+use std::sync::{Arc, atomic::{AtomicUsize, Ordering}};
 use tokio_io::{AsyncRead, AsyncWrite};
 
+/// Number of messages waiting to be sent to each peer, kept up to date by the handlers. Peers
+/// without any handler are removed.
+pub type SendQueueLens = Arc<Mutex<FnvHashMap<PeerId, Arc<AtomicUsize>>>>;
+
 /// Implements the `IntoProtocolsHandler` trait of libp2p.
 ///
 /// Every time a connection with a remote starts, an instance of this struct is created and
@@ -91,6 +98,9 @@ pub struct CustomProtoHandlerProto<TSubstream> {
 	/// Configuration for the protocol upgrade to negotiate.
 	protocol: RegisteredProtocol,
 
+	/// Where to report the number of messages waiting to be sent.
+	send_queue_lens: SendQueueLens,
+
 	/// Marker to pin the generic type.
 	marker: PhantomData<TSubstream>,
 }
@@ -100,9 +110,10 @@ where
 	TSubstream: AsyncRead + AsyncWrite,
 {
 	/// Builds a new `CustomProtoHandlerProto`.
-	pub fn new(protocol: RegisteredProtocol) -> Self {
+	pub fn new(protocol: RegisteredProtocol, send_queue_lens: SendQueueLens) -> Self {
 		CustomProtoHandlerProto {
 			protocol,
+			send_queue_lens,
 			marker: PhantomData,
 		}
 	}
@@ -119,8 +130,16 @@ where
 	}
 
 	fn into_handler(self, remote_peer_id: &PeerId, connected_point: &ConnectedPoint) -> Self::Handler {
+		let send_queue_len = self.send_queue_lens.lock()
+			.entry(remote_peer_id.clone())
+			.or_default()
+			.clone();
+
 		CustomProtoHandler {
 			protocol: self.protocol,
+			send_queue_lens: self.send_queue_lens,
+			send_queue_len,
+			reported_send_queue_len: 0,
 			endpoint: connected_point.to_endpoint(),
 			remote_peer_id: remote_peer_id.clone(),
 			state: ProtocolState::Init {
@@ -153,6 +172,16 @@ pub struct CustomProtoHandler<TSubstream> {
 	/// This queue must only ever be modified to insert elements at the back, or remove the first
 	/// element.
 	events_queue: SmallVec<[ProtocolsHandlerEvent<RegisteredProtocol, (), CustomProtoHandlerOut>; 16]>,
+
+	/// Map containing `send_queue_len`, shared with the other handlers.
+	send_queue_lens: SendQueueLens,
+
+	/// Number of messages waiting to be sent to the remote, summed over all the handlers of the
+	/// remote.
+	send_queue_len: Arc<AtomicUsize>,
+
+	/// Contribution of this handler to `send_queue_len`.
+	reported_send_queue_len: usize,
 }
 
 /// State of the handler.
@@ -520,6 +549,24 @@ where
 			_ => debug!(target: "sub-libp2p", "Tried to send message over closed protocol \
 				with {:?}", self.remote_peer_id)
 		}
+
+		self.report_send_queue_len();
+	}
+
+	/// Updates `send_queue_len` with the number of messages waiting in our substreams.
+	fn report_send_queue_len(&mut self) {
+		let len = match self.state {
+			ProtocolState::Normal { ref substreams, .. } =>
+				substreams.iter().map(|s| s.send_queue_len()).sum(),
+			_ => 0,
+		};
+
+		if len > self.reported_send_queue_len {
+			self.send_queue_len.fetch_add(len - self.reported_send_queue_len, Ordering::Relaxed);
+		} else {
+			self.send_queue_len.fetch_sub(self.reported_send_queue_len - len, Ordering::Relaxed);
+		}
+		self.reported_send_queue_len = len;
 	}
 }
 
@@ -601,7 +648,9 @@ where TSubstream: AsyncRead + AsyncWrite {
 		}
 
 		// Process all the substreams.
-		if let Some(event) = self.poll_state() {
+		let event = self.poll_state();
+		self.report_send_queue_len();
+		if let Some(event) = event {
 			return Ok(Async::Ready(event))
 		}
 
@@ -609,6 +658,17 @@ where TSubstream: AsyncRead + AsyncWrite {
 	}
 }
 
+impl<TSubstream> Drop for CustomProtoHandler<TSubstream> {
+	fn drop(&mut self) {
+		let mut send_queue_lens = self.send_queue_lens.lock();
+		self.send_queue_len.fetch_sub(self.reported_send_queue_len, Ordering::Relaxed);
+		// Only the map and `self` are left holding the counter.
+		if Arc::strong_count(&self.send_queue_len) == 2 {
+			send_queue_lens.remove(&self.remote_peer_id);
+		}
+	}
+}
+
 impl<TSubstream> fmt::Debug for CustomProtoHandler<TSubstream>
 where
 	TSubstream: AsyncRead + AsyncWrite,
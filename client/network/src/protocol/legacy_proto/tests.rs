@@ -82,6 +82,7 @@ fn build_nodes()
 			},
 			reserved_only: false,
 			reserved_nodes: Vec::new(),
+			priority_group_slots: Default::default(),
 		});
 
 		let behaviour = CustomProtoWithAddr {
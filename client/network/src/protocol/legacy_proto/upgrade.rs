@@ -102,6 +102,11 @@ impl<TSubstream> RegisteredProtocolSubstream<TSubstream> {
 		self.endpoint
 	}
 
+	/// Returns the number of messages waiting to be sent.
+	pub fn send_queue_len(&self) -> usize {
+		self.send_queue.len()
+	}
+
 	/// Starts a graceful shutdown process on this substream.
 	///
 	/// Note that "graceful" means that we sent a closing message. We don't wait for any
@@ -19,7 +19,7 @@
 //! Handles requests for data coming from our local light client and that must be answered by
 //! nodes on the network.
 
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::sync::Arc;
 use std::time::{Instant, Duration};
 use log::{trace, info};
@@ -37,7 +37,7 @@ use sc_peerset::ReputationChange;
 
 /// Remote request timeout.
 const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
-/// Default request retry count.
+/// Default request retry count, used unless the request asks for another one.
 const RETRY_COUNT: usize = 1;
 /// Reputation change for a peer when a request timed out.
 const TIMEOUT_REPUTATION_CHANGE: i32 = -(1 << 8);
@@ -135,6 +135,8 @@ struct Request<Block: BlockT> {
 	/// Number of remaining attempts to fulfill this request. If it reaches 0, we interrupt the
 	/// attempt.
 	retry_count: usize,
+	/// Peers which failed to fulfill this request. The request is retried against other peers.
+	failed_peers: HashSet<PeerId>,
 	data: RequestData<Block>,
 }
 
@@ -240,12 +242,13 @@ impl<B: BlockT> LightDispatch<B> where
 
 	/// Inserts a new request in the list of requests to execute.
 	pub(crate) fn add_request(&mut self, network: impl LightDispatchNetwork<B>, data: RequestData<B>) {
-		self.insert(RETRY_COUNT, data);
+		let retry_count = data.retry_count().unwrap_or(RETRY_COUNT);
+		self.insert(retry_count, HashSet::new(), data);
 		self.dispatch(network);
 	}
 
 	/// Inserts a new request in the list of requests to execute.
-	fn insert(&mut self, retry_count: usize, data: RequestData<B>) {
+	fn insert(&mut self, retry_count: usize, failed_peers: HashSet<PeerId>, data: RequestData<B>) {
 		let request_id = self.next_request_id;
 		self.next_request_id += 1;
 
@@ -253,6 +256,7 @@ impl<B: BlockT> LightDispatch<B> where
 			id: request_id,
 			timestamp: Instant::now(),
 			retry_count,
+			failed_peers,
 			data,
 		});
 	}
@@ -278,12 +282,14 @@ impl<B: BlockT> LightDispatch<B> where
 		};
 
 		let retry_count = request.retry_count;
+		let mut failed_peers = request.failed_peers.clone();
 		let (retry_count, retry_request_data) = match try_accept(request, &self.checker) {
 			Accept::Ok => (retry_count, None),
 			Accept::CheckFailed(error, retry_request_data) => {
 				info!("Failed to check remote {} response from peer {}: {}", rtype, peer, error);
 				network.report_peer(&peer, ReputationChange::new_fatal("Failed remote response check"));
 				network.disconnect_peer(&peer);
+				failed_peers.insert(peer.clone());
 				self.remove_peer(peer);
 
 				if retry_count > 0 {
@@ -298,6 +304,7 @@ impl<B: BlockT> LightDispatch<B> where
 				info!("Unexpected response to remote {} from peer", rtype);
 				network.report_peer(&peer, ReputationChange::new_fatal("Unexpected remote response"));
 				network.disconnect_peer(&peer);
+				failed_peers.insert(peer.clone());
 				self.remove_peer(peer);
 
 				(retry_count, Some(retry_request_data))
@@ -305,7 +312,7 @@ impl<B: BlockT> LightDispatch<B> where
 		};
 
 		if let Some(request_data) = retry_request_data {
-			self.insert(retry_count, request_data);
+			self.insert(retry_count, failed_peers, request_data);
 		}
 
 		self.dispatch(network);
@@ -351,10 +358,19 @@ impl<B: BlockT> LightDispatch<B> where
 				_ => break,
 			}
 
-			let (bad_peer, request) = self.active_peers.pop_front().expect("front() is Some as checked above");
-			self.pending_requests.push_front(request);
+			let (bad_peer, mut request) = self.active_peers.pop_front().expect("front() is Some as checked above");
 			network.report_peer(&bad_peer, ReputationChange::new(TIMEOUT_REPUTATION_CHANGE, "Light request timeout"));
 			network.disconnect_peer(&bad_peer);
+
+			// a timeout counts as a failed attempt, so that a request is not retried forever
+			if request.retry_count > 0 {
+				request.retry_count -= 1;
+				request.failed_peers.insert(bad_peer);
+				self.pending_requests.push_front(request);
+			} else {
+				trace!(target: "sync", "Remote request {} timed out for given number of retries", request.id);
+				request.data.fail(ClientError::RemoteFetchFailed.into());
+			}
 		}
 
 		self.dispatch(network);
@@ -546,20 +562,26 @@ impl<B: BlockT> LightDispatch<B> where
 				None => break,
 			};
 
-			// check if request can (optimistically) be processed by the peer
+			// check if request can (optimistically) be processed by the peer, which has not
+			// already failed to process it
 			let can_be_processed_by_peer = {
-				let request = match self.pending_requests.front() {
+				let request = match self.pending_requests.front_mut() {
 					Some(r) => r,
 					None => {
 						self.idle_peers.push_front(peer);
 						break;
 					},
 				};
+				// once every connected peer has failed to process the request, it is retried
+				// against them rather than waiting for another peer which may never connect
+				if self.best_blocks.keys().all(|connected| request.failed_peers.contains(connected)) {
+					request.failed_peers.clear();
+				}
 				let peer_best_block = self.best_blocks.get(&peer)
 					.expect("entries are inserted into best_blocks when peer is connected;
 						entries are removed from best_blocks when peer is disconnected;
 						peer is in idle_peers and thus connected; qed");
-				request.required_block() <= *peer_best_block
+				request.required_block() <= *peer_best_block && !request.failed_peers.contains(&peer)
 			};
 
 			if !can_be_processed_by_peer {
@@ -662,6 +684,18 @@ impl<Block: BlockT> Request<Block> {
 }
 
 impl<Block: BlockT> RequestData<Block> {
+	/// Returns the number of retries asked by the client, if any.
+	fn retry_count(&self) -> Option<usize> {
+		match self {
+			RequestData::RemoteHeader(ref data, _) => data.retry_count,
+			RequestData::RemoteRead(ref data, _) => data.retry_count,
+			RequestData::RemoteReadChild(ref data, _) => data.retry_count,
+			RequestData::RemoteCall(ref data, _) => data.retry_count,
+			RequestData::RemoteChanges(ref data, _) => data.retry_count,
+			RequestData::RemoteBody(ref data, _) => data.retry_count,
+		}
+	}
+
 	fn fail(self, error: ClientError) {
 		// don't care if anyone is listening
 		match self {
@@ -974,7 +1008,7 @@ pub mod tests {
 			retry_count: Some(retry_count)
 		}, tx));
 
-		for i in 0..retry_count {
+		for i in 0..retry_count+1 {
 			assert!(response.try_recv().unwrap().is_none());
 			receive_call_response(&mut network_interface, &mut light_dispatch, peer_ids[i].clone(), i as u64);
 		}
@@ -982,6 +1016,80 @@ pub mod tests {
 		assert!(response.try_recv().unwrap().unwrap().is_err());
 	}
 
+	#[test]
+	fn does_not_retry_request_with_peer_which_failed_it() {
+		let mut light_dispatch = dummy(false);
+		let mut network_interface = DummyNetwork::default();
+		let peer0 = PeerId::random();
+		let peer1 = PeerId::random();
+		let peer2 = PeerId::random();
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		light_dispatch.on_connect(&mut network_interface, peer1.clone(), Roles::FULL, 1000);
+
+		let call_request = || RequestData::RemoteCall(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			retry_count: Some(1),
+		}, oneshot::channel().0);
+		// the second request keeps peer1 busy
+		light_dispatch.add_request(&mut network_interface, call_request());
+		light_dispatch.add_request(&mut network_interface, call_request());
+		receive_call_response(&mut network_interface, &mut light_dispatch, peer0.clone(), 0);
+
+		// the request waits for another peer than the one which failed it
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		assert_eq!(light_dispatch.pending_requests.len(), 1);
+		light_dispatch.on_connect(&mut network_interface, peer2.clone(), Roles::FULL, 1000);
+		assert!(light_dispatch.pending_requests.is_empty());
+		assert!(light_dispatch.active_peers.contains_key(&peer2));
+		assert!(!light_dispatch.active_peers.contains_key(&peer0));
+	}
+
+	#[test]
+	fn retries_request_with_failed_peers_when_no_other_peer_is_connected() {
+		let mut light_dispatch = dummy(false);
+		let mut network_interface = DummyNetwork::default();
+		let peer0 = PeerId::random();
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+
+		light_dispatch.add_request(&mut network_interface, RequestData::RemoteCall(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			retry_count: Some(1),
+		}, oneshot::channel().0));
+		receive_call_response(&mut network_interface, &mut light_dispatch, peer0.clone(), 0);
+
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+		assert!(light_dispatch.pending_requests.is_empty());
+		assert_eq!(vec![peer0], light_dispatch.active_peers.keys().cloned().collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn receives_remote_failure_after_retry_count_timeouts() {
+		let mut light_dispatch = dummy(true);
+		let mut network_interface = DummyNetwork::default();
+		let peer0 = PeerId::random();
+		light_dispatch.on_connect(&mut network_interface, peer0.clone(), Roles::FULL, 1000);
+
+		let (tx, mut response) = oneshot::channel();
+		light_dispatch.add_request(&mut network_interface, RequestData::RemoteCall(RemoteCallRequest {
+			block: Default::default(),
+			header: dummy_header(),
+			method: "test".into(),
+			call_data: vec![],
+			retry_count: Some(0),
+		}, tx));
+
+		light_dispatch.active_peers[&peer0].timestamp = Instant::now() - REQUEST_TIMEOUT - REQUEST_TIMEOUT;
+		light_dispatch.maintain_peers(&mut network_interface);
+		assert!(light_dispatch.pending_requests.is_empty());
+		assert!(response.try_recv().unwrap().unwrap().is_err());
+	}
+
 	#[test]
 	fn receives_remote_call_response() {
 		let mut light_dispatch = dummy(true);
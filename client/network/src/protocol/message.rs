@@ -26,7 +26,7 @@ pub use self::generic::{
 	FinalityProofRequest, FinalityProofResponse,
 	FromBlock, RemoteReadChildRequest,
 };
-use sc_client_api::StorageProof;
+use sc_client_api::{StorageProof, CompactProof};
 
 /// A unique ID of a request.
 pub type RequestId = u64;
@@ -135,6 +135,15 @@ pub struct RemoteReadResponse {
 	pub proof: StorageProof,
 }
 
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+/// Remote call or read response, with a compact proof.
+pub struct RemoteCompactProofResponse {
+	/// Id of a request this response was made for.
+	pub id: RequestId,
+	/// Execution or read proof.
+	pub proof: CompactProof,
+}
+
 /// Generic types.
 pub mod generic {
 	use codec::{Encode, Decode, Input, Output};
@@ -143,7 +152,7 @@ pub mod generic {
 	use super::{
 		RemoteReadResponse, Transactions, Direction,
 		RequestId, BlockAttributes, RemoteCallResponse, ConsensusEngineId,
-		BlockState, StorageProof,
+		BlockState, StorageProof, RemoteCompactProofResponse,
 	};
 	/// Consensus is mostly opaque to us
 	#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
@@ -219,6 +228,14 @@ pub mod generic {
 		FinalityProofResponse(FinalityProofResponse<Hash>),
 		/// Batch of consensus protocol messages.
 		ConsensusBatch(Vec<ConsensusMessage>),
+		/// Handshake of a notifications protocol, sent once after the status.
+		NotificationsHandshake(ConsensusMessage),
+		/// Remote method call response, with a compact proof.
+		RemoteCallCompactResponse(RemoteCompactProofResponse),
+		/// Remote storage read response, with a compact proof.
+		RemoteReadCompactResponse(RemoteCompactProofResponse),
+		/// Closes a notifications protocol, e.g. after rejecting the handshake of the remote.
+		NotificationsClose(ConsensusEngineId),
 		/// Chain-specific message.
 		#[codec(index = "255")]
 		ChainSpecific(Vec<u8>),
@@ -246,6 +263,10 @@ pub mod generic {
 				Message::FinalityProofRequest(_) => "FinalityProofRequest",
 				Message::FinalityProofResponse(_) => "FinalityProofResponse",
 				Message::ConsensusBatch(_) => "ConsensusBatch",
+				Message::NotificationsHandshake(_) => "NotificationsHandshake",
+				Message::RemoteCallCompactResponse(_) => "RemoteCallCompactResponse",
+				Message::RemoteReadCompactResponse(_) => "RemoteReadCompactResponse",
+				Message::NotificationsClose(_) => "NotificationsClose",
 				Message::ChainSpecific(_) => "ChainSpecific",
 			}
 		}
@@ -0,0 +1,251 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Configuration and state of the notifications protocols.
+//!
+//! Notifications protocols are multiplexed over the substream of the legacy protocol and
+//! identified by their `ConsensusEngineId`. A protocol may come with a handshake, in which case
+//! each side sends its handshake payload once the connection is established, and the protocol is
+//! only considered open with a remote after its handshake has been received and accepted.
+//! Each protocol also has its own set of peers, which can be bounded independently of the total
+//! number of connections. The handshakes received while the protocol has no slot left are kept
+//! until a slot is freed, and a remote whose handshake is rejected is told to close the protocol.
+
+use crate::config::Roles;
+use libp2p::PeerId;
+use log::{debug, trace};
+use sp_runtime::ConsensusEngineId;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Checks the handshake sent by a remote opening a notifications protocol.
+pub trait HandshakeValidator: Send + Sync {
+	/// Returns `true` if the protocol should be opened with the remote, given its handshake.
+	///
+	/// Rejecting a handshake doesn't close the connection, but the remote won't receive or send
+	/// any notification on that protocol, and is asked to close it on its side.
+	fn validate(&self, remote: &PeerId, roles: Roles, handshake: &[u8]) -> bool;
+}
+
+impl<F> HandshakeValidator for F
+	where F: Fn(&PeerId, Roles, &[u8]) -> bool + Send + Sync
+{
+	fn validate(&self, remote: &PeerId, roles: Roles, handshake: &[u8]) -> bool {
+		(self)(remote, roles, handshake)
+	}
+}
+
+/// Configuration of a notifications protocol.
+#[derive(Clone)]
+pub struct NotificationsProtocolConfig {
+	/// Identifier of the protocol.
+	pub engine_id: ConsensusEngineId,
+	/// Payload sent to every remote when the connection is established, e.g. the para id of a
+	/// collation protocol. If `None`, the protocol is open with everyone as soon as the
+	/// connection is established, and the remote isn't expected to send any handshake.
+	pub handshake: Option<Vec<u8>>,
+	/// Checks the handshakes sent by remotes. All the handshakes are accepted if `None`.
+	pub handshake_validator: Option<Arc<dyn HandshakeValidator>>,
+	/// Maximum number of peers the protocol is open with at the same time, not counting the
+	/// reserved ones. Unbounded if `None`. Once reached, the protocol is opened with the next
+	/// remotes in the order their handshakes were received, as the open ones get closed.
+	pub max_peers: Option<usize>,
+	/// Peers the protocol is always open with, regardless of `max_peers`.
+	pub reserved_peers: HashSet<PeerId>,
+}
+
+impl NotificationsProtocolConfig {
+	/// Configuration of a protocol without handshake, open with all the peers.
+	pub fn new(engine_id: ConsensusEngineId) -> Self {
+		NotificationsProtocolConfig {
+			engine_id,
+			handshake: None,
+			handshake_validator: None,
+			max_peers: None,
+			reserved_peers: HashSet::new(),
+		}
+	}
+}
+
+/// Result of [`RegisteredProtocol::open`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OpenOutcome {
+	/// The protocol has been opened with the remote.
+	Opened,
+	/// The protocol was already open with the remote.
+	AlreadyOpen,
+	/// The handshake is valid but there is no slot left. The protocol is opened with the remote
+	/// once one is freed, as returned by `open_pending`.
+	Pending,
+	/// The handshake has been rejected.
+	Rejected,
+}
+
+/// A registered notifications protocol and the peers it is open with.
+pub(crate) struct RegisteredProtocol {
+	config: NotificationsProtocolConfig,
+	/// Peers the protocol is open with.
+	peers: HashSet<PeerId>,
+	/// Number of peers in `peers` that aren't reserved.
+	num_non_reserved: usize,
+	/// Peers waiting for a slot, with their roles and handshake, in the order they arrived.
+	pending: Vec<(PeerId, Roles, Vec<u8>)>,
+}
+
+impl RegisteredProtocol {
+	pub fn new(config: NotificationsProtocolConfig) -> Self {
+		RegisteredProtocol {
+			config,
+			peers: HashSet::new(),
+			num_non_reserved: 0,
+			pending: Vec::new(),
+		}
+	}
+
+	/// Our handshake, if the protocol has one.
+	pub fn handshake(&self) -> Option<&[u8]> {
+		self.config.handshake.as_ref().map(|h| &h[..])
+	}
+
+	/// Returns true if the protocol is open with the given peer.
+	pub fn is_open(&self, who: &PeerId) -> bool {
+		self.peers.contains(who)
+	}
+
+	/// Tries to open the protocol with a peer, after checking its handshake if the protocol has
+	/// one.
+	pub fn open(&mut self, who: &PeerId, roles: Roles, handshake: &[u8]) -> OpenOutcome {
+		let engine_id = self.config.engine_id;
+		if self.peers.contains(who) {
+			debug!(target: "sub-libp2p", "Protocol {:?} already open with {}", engine_id, who);
+			return OpenOutcome::AlreadyOpen;
+		}
+
+		self.pending.retain(|(id, _, _)| id != who);
+
+		if let Some(validator) = self.config.handshake_validator.as_ref() {
+			if !validator.validate(who, roles, handshake) {
+				debug!(target: "sub-libp2p", "Rejected handshake of {} on protocol {:?}", who, engine_id);
+				return OpenOutcome::Rejected;
+			}
+		}
+
+		let reserved = self.config.reserved_peers.contains(who);
+		if !reserved && !self.has_free_slot() {
+			trace!(target: "sub-libp2p", "No slot left on protocol {:?} for {}", engine_id, who);
+			self.pending.push((who.clone(), roles, handshake.to_vec()));
+			return OpenOutcome::Pending;
+		}
+
+		self.peers.insert(who.clone());
+		if !reserved {
+			self.num_non_reserved += 1;
+		}
+		OpenOutcome::Opened
+	}
+
+	/// Opens the protocol with the pending peers, as long as there are free slots. Returns the
+	/// peers it has been opened with, along with their roles and handshake.
+	pub fn open_pending(&mut self) -> Vec<(PeerId, Roles, Vec<u8>)> {
+		let mut opened = Vec::new();
+		while self.has_free_slot() && !self.pending.is_empty() {
+			let (who, roles, handshake) = self.pending.remove(0);
+			if self.open(&who, roles, &handshake) == OpenOutcome::Opened {
+				opened.push((who, roles, handshake));
+			}
+		}
+		opened
+	}
+
+	/// Closes the protocol with a peer, and forgets its handshake if it was pending. Returns true
+	/// if the protocol was open.
+	pub fn close(&mut self, who: &PeerId) -> bool {
+		self.pending.retain(|(id, _, _)| id != who);
+		if !self.peers.remove(who) {
+			return false;
+		}
+		if !self.config.reserved_peers.contains(who) {
+			self.num_non_reserved -= 1;
+		}
+		true
+	}
+
+	fn has_free_slot(&self) -> bool {
+		self.config.max_peers.map_or(true, |max| self.num_non_reserved < max)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn config(max_peers: Option<usize>) -> NotificationsProtocolConfig {
+		let validator = |_: &PeerId, _: Roles, handshake: &[u8]| handshake == b"para 1";
+		NotificationsProtocolConfig {
+			handshake: Some(b"para 1".to_vec()),
+			handshake_validator: Some(Arc::new(validator)),
+			max_peers,
+			..NotificationsProtocolConfig::new(*b"coll")
+		}
+	}
+
+	#[test]
+	fn rejects_invalid_handshakes() {
+		let mut protocol = RegisteredProtocol::new(config(None));
+		let peer = PeerId::random();
+		assert_eq!(protocol.open(&peer, Roles::FULL, b"para 2"), OpenOutcome::Rejected);
+		assert!(!protocol.is_open(&peer));
+		assert_eq!(protocol.open(&peer, Roles::FULL, b"para 1"), OpenOutcome::Opened);
+		assert!(protocol.is_open(&peer));
+		assert_eq!(protocol.open(&peer, Roles::FULL, b"para 1"), OpenOutcome::AlreadyOpen);
+	}
+
+	#[test]
+	fn reserved_peers_dont_use_slots() {
+		let reserved = PeerId::random();
+		let mut config = config(Some(1));
+		config.reserved_peers.insert(reserved.clone());
+		let mut protocol = RegisteredProtocol::new(config);
+
+		let (first, second) = (PeerId::random(), PeerId::random());
+		assert_eq!(protocol.open(&first, Roles::FULL, b"para 1"), OpenOutcome::Opened);
+		assert_eq!(protocol.open(&second, Roles::FULL, b"para 1"), OpenOutcome::Pending);
+		assert_eq!(protocol.open(&reserved, Roles::FULL, b"para 1"), OpenOutcome::Opened);
+		assert!(protocol.open_pending().is_empty());
+
+		assert!(protocol.close(&first));
+		assert!(!protocol.close(&first));
+		assert_eq!(protocol.open_pending(), vec![(second.clone(), Roles::FULL, b"para 1".to_vec())]);
+		assert!(protocol.is_open(&second));
+	}
+
+	#[test]
+	fn pending_peers_are_opened_in_order_and_forgotten_on_close() {
+		let mut protocol = RegisteredProtocol::new(config(Some(1)));
+
+		let (open, gone, waiting) = (PeerId::random(), PeerId::random(), PeerId::random());
+		assert_eq!(protocol.open(&open, Roles::FULL, b"para 1"), OpenOutcome::Opened);
+		assert_eq!(protocol.open(&gone, Roles::FULL, b"para 1"), OpenOutcome::Pending);
+		assert_eq!(protocol.open(&waiting, Roles::FULL, b"para 1"), OpenOutcome::Pending);
+		assert_eq!(protocol.open(&PeerId::random(), Roles::FULL, b"para 2"), OpenOutcome::Rejected);
+
+		// A pending peer that disconnects doesn't take the freed slot.
+		assert!(!protocol.close(&gone));
+		assert!(protocol.close(&open));
+		assert_eq!(protocol.open_pending(), vec![(waiting.clone(), Roles::FULL, b"para 1".to_vec())]);
+		assert!(!protocol.is_open(&gone));
+	}
+}
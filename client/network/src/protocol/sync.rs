@@ -28,14 +28,17 @@
 //!
 
 use blocks::BlockCollection;
+use peer_scores::PeerScores;
 use sc_client_api::ClientInfo;
 use sp_blockchain::Error as ClientError;
-use sp_consensus::{BlockOrigin, BlockStatus,
+use sp_consensus::{BlockOrigin, BlockStatus, ForkRanking,
 	block_validation::{BlockAnnounceValidator, Validation},
 	import_queue::{IncomingBlock, BlockImportResult, BlockImportError}
 };
 use crate::{
+	chain::WarpSyncProvider,
 	config::{Roles, BoxFinalityProofRequestBuilder},
+	request_responses::RequestFailure,
 	message::{self, generic::FinalityProofRequest, BlockAnnounce, BlockAttributes, BlockRequest, BlockResponse,
 	FinalityProofResponse},
 };
@@ -48,20 +51,31 @@ use sp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, Header, NumberFor, Zero, One, CheckedSub, SaturatedConversion}
 };
-use std::{fmt, ops::Range, collections::{HashMap, HashSet, VecDeque}, sync::Arc};
+use std::{fmt, ops::Range, collections::{HashMap, HashSet, VecDeque}, sync::Arc, time::Instant};
+use warp::{WarpSync, WarpSyncRequest, OnWarpSyncResponse};
 
 mod blocks;
 mod extra_requests;
+mod peer_scores;
+pub(crate) mod warp;
 
 /// Maximum blocks to request in a single packet.
 const MAX_BLOCKS_TO_REQUEST: usize = 128;
 
+/// Minimum blocks to request in a single packet, even from the slowest peers.
+const MIN_BLOCKS_TO_REQUEST: usize = 16;
+
 /// Maximum blocks to store in the import queue.
 const MAX_IMPORTING_BLOCKS: usize = 2048;
 
 /// Maximum blocks to download ahead of any gap.
 const MAX_DOWNLOAD_AHEAD: u32 = 2048;
 
+/// Maximum headers held back during the header phase of a fast sync, waiting for a justification.
+/// Once reached, the state of the last finalized block is downloaded and the blocks after it are
+/// synced in full.
+const MAX_HELD_FAST_SYNC_HEADERS: usize = 8192;
+
 /// We use a heuristic that with a high likelihood, by the time
 /// `MAJOR_SYNC_BLOCKS` have been imported we'll be on the same
 /// chain as (or at least closer to) the peer so we want to delay
@@ -99,6 +113,12 @@ mod rep {
 
 	/// Reputation change when a peer sent us invlid ancestry result.
 	pub const UNKNOWN_ANCESTOR:Rep = Rep::new(-(1 << 16), "DB Error");
+
+	/// Reputation change for peers which send us a warp sync proof we fail to verify.
+	pub const BAD_WARP_PROOF: Rep = Rep::new(-(1 << 29), "Bad warp sync proof");
+
+	/// Reputation change for peers which send us invalid state entries.
+	pub const BAD_STATE: Rep = Rep::new(-(1 << 29), "Bad state");
 }
 
 /// The main data structure which contains all the state for a chains
@@ -110,6 +130,8 @@ pub struct ChainSync<B: BlockT> {
 	peers: HashMap<PeerId, PeerSync<B>>,
 	/// A `BlockCollection` of blocks that are being downloaded from peers
 	blocks: BlockCollection<B>,
+	/// The download rate of the peers, used to size their block requests.
+	peer_scores: PeerScores,
 	/// The best block number in our queue of blocks to import
 	best_queued_number: NumberFor<B>,
 	/// The best block hash in our queue of blocks to import
@@ -139,6 +161,15 @@ pub struct ChainSync<B: BlockT> {
 	block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>,
 	/// Maximum number of peers to ask the same blocks in parallel.
 	max_parallel_downloads: u32,
+	/// Warp sync in progress, if any, or the state download of a fast sync. No blocks are
+	/// requested until it completes.
+	warp_sync: Option<WarpSync<B>>,
+	/// Headers downloaded after the last one with a justification, during the header phase of a
+	/// fast sync. Headers are only imported up to a justified block, as the state of the last
+	/// one is downloaded once all headers are known.
+	fast_sync_headers: Option<Vec<IncomingBlock<B>>>,
+	/// Ranking of competing forks, if provided by the consensus engine.
+	fork_ranking: Option<Arc<dyn ForkRanking<B>>>,
 }
 
 /// All the data we have about a Peer that we are trying to sync with
@@ -171,6 +202,8 @@ pub struct PeerInfo<B: BlockT> {
 struct ForkTarget<B: BlockT> {
 	number: NumberFor<B>,
 	parent_hash: Option<B::Hash>,
+	/// Weight of the fork given by the `ForkRanking`, if known. Heavier forks are downloaded first.
+	weight: Option<u64>,
 	peers: HashSet<PeerId>,
 }
 
@@ -215,6 +248,29 @@ pub enum SyncState {
 	Downloading
 }
 
+/// Progress of the sync, as streamed by `NetworkService::sync_state_stream`.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub enum SyncStateEvent<N> {
+	/// No peer to sync from yet.
+	Starting,
+	/// Actively catching up with the chain.
+	Downloading {
+		/// Best block number seen among our peers.
+		target: N,
+		/// Our best block number.
+		current: N,
+		/// Number of peers participating in syncing.
+		peers: u32,
+	},
+	/// Caught up with the chain, the last downloaded blocks are being imported.
+	Importing {
+		/// Number of blocks queued for import.
+		queued: u32,
+	},
+	/// Caught up with the chain.
+	Idle,
+}
+
 /// Syncing status and statistics.
 #[derive(Clone)]
 pub struct Status<B: BlockT> {
@@ -256,6 +312,8 @@ pub enum OnBlockAnnounce {
 	Nothing,
 	/// The announcement header should be imported.
 	ImportHeader,
+	/// The announcement was rejected by the `BlockAnnounceValidator` and was ignored.
+	Rejected,
 }
 
 /// Result of [`ChainSync::on_block_justification`].
@@ -295,10 +353,28 @@ impl<B: BlockT> ChainSync<B> {
 		request_builder: Option<BoxFinalityProofRequestBuilder<B>>,
 		block_announce_validator: Box<dyn BlockAnnounceValidator<B> + Send>,
 		max_parallel_downloads: u32,
+		warp_sync_provider: Option<Arc<dyn WarpSyncProvider<B>>>,
+		fast_sync: bool,
+		fork_ranking: Option<Arc<dyn ForkRanking<B>>>,
 	) -> Self {
 		let mut required_block_attributes = BlockAttributes::HEADER | BlockAttributes::JUSTIFICATION;
 
-		if role.is_full() {
+		// Only a full node with an empty database can warp or fast sync. A fast sync interrupted
+		// before the state was downloaded is resumed, whatever the sync mode.
+		let empty_database = role.is_full() && info.chain.best_number.is_zero();
+		let warp_sync = warp_sync_provider
+			.filter(|_| empty_database)
+			.map(|provider| WarpSync::new(provider, info.chain.genesis_hash));
+		let resume_fast_sync = role.is_full() && !info.chain.best_number.is_zero() &&
+			client.block_status(&BlockId::Hash(info.chain.best_hash)).ok() == Some(BlockStatus::InChainPruned);
+		let fast_sync_headers = if (fast_sync && empty_database && warp_sync.is_none()) || resume_fast_sync {
+			info!("Fast sync: downloading the headers up to the latest finalized block");
+			Some(Vec::new())
+		} else {
+			None
+		};
+
+		if role.is_full() && fast_sync_headers.is_none() {
 			required_block_attributes |= BlockAttributes::BODY
 		}
 
@@ -306,6 +382,7 @@ impl<B: BlockT> ChainSync<B> {
 			client,
 			peers: HashMap::new(),
 			blocks: BlockCollection::new(),
+			peer_scores: PeerScores::new(),
 			best_queued_hash: info.chain.best_hash,
 			best_queued_number: info.chain.best_number,
 			best_imported_number: info.chain.best_number,
@@ -319,6 +396,9 @@ impl<B: BlockT> ChainSync<B> {
 			is_idle: false,
 			block_announce_validator,
 			max_parallel_downloads,
+			warp_sync,
+			fast_sync_headers,
+			fork_ranking,
 		}
 	}
 
@@ -333,7 +413,9 @@ impl<B: BlockT> ChainSync<B> {
 	pub fn status(&self) -> Status<B> {
 		let best_seen = self.peers.values().max_by_key(|p| p.best_number).map(|p| p.best_number);
 		let sync_state =
-			if let Some(n) = best_seen {
+			if self.warp_sync.is_some() && best_seen.is_some() {
+				SyncState::Downloading
+			} else if let Some(n) = best_seen {
 				// A chain is classified as downloading if the provided best block is
 				// more than `MAJOR_SYNC_BLOCKS` behind the best queued block.
 				if n > self.best_queued_number && n - self.best_queued_number > MAJOR_SYNC_BLOCKS.into() {
@@ -513,10 +595,106 @@ impl<B: BlockT> ChainSync<B> {
 				number,
 				peers: Default::default(),
 				parent_hash: None,
+				weight: None,
 			})
 			.peers.extend(peers);
 	}
 
+	/// Get the next warp sync request to send, if any.
+	///
+	/// This also starts the state download of a fast sync once all headers are imported.
+	pub fn warp_sync_request(&mut self) -> Option<(PeerId, WarpSyncRequest<B>)> {
+		self.check_fast_sync_headers();
+		let peers = self.peers.iter().map(|(who, peer)| (who, peer.best_number));
+		self.warp_sync.as_mut()?.next_request(peers)
+	}
+
+	/// Handle the response to a warp sync request.
+	///
+	/// Returns the block to import along with its state once the warp sync is complete.
+	pub fn on_warp_sync_response(
+		&mut self,
+		who: PeerId,
+		response: Result<Vec<u8>, RequestFailure>,
+	) -> Result<Option<IncomingBlock<B>>, BadPeer> {
+		let warp_sync = match self.warp_sync.as_mut() {
+			Some(warp_sync) => warp_sync,
+			None => return Ok(None),
+		};
+
+		match warp_sync.on_response(who, response) {
+			OnWarpSyncResponse::Nothing => Ok(None),
+			OnWarpSyncResponse::Import(block) => {
+				self.queue_blocks.insert(block.hash);
+				Ok(Some(block))
+			},
+			OnWarpSyncResponse::BadPeer(bad_peer) => Err(bad_peer),
+			OnWarpSyncResponse::Abort => {
+				if !warp_sync.can_fall_back() {
+					error!("Fast sync can't complete, the database must be purged to sync all blocks");
+				}
+				self.warp_sync = None;
+				self.is_idle = false;
+				Ok(None)
+			},
+		}
+	}
+
+	/// Move from the header phase of a fast sync to the download of the state of the last
+	/// finalized block, once the headers are imported up to the best block of our peers, or once
+	/// too many headers are held back.
+	fn check_fast_sync_headers(&mut self) {
+		let held = match self.fast_sync_headers {
+			Some(ref held) => held.len(),
+			None => return,
+		};
+		if self.peers.is_empty() || !self.queue_blocks.is_empty() {
+			return
+		}
+		if held < MAX_HELD_FAST_SYNC_HEADERS && self.status().state == SyncState::Downloading {
+			return
+		}
+
+		let info = self.client.info().chain;
+		let header = match self.client.header(&BlockId::Hash(info.finalized_hash)) {
+			Ok(Some(header)) => header,
+			Ok(None) | Err(_) => {
+				debug!(target: "sync", "Failed to read the header of the last finalized block {}", info.finalized_hash);
+				return
+			},
+		};
+
+		// The downloads in progress and the headers held back are dropped, and downloaded again
+		// with their body once the state is imported.
+		self.fast_sync_headers = None;
+		self.blocks.clear();
+		self.best_queued_hash = info.best_hash;
+		self.best_queued_number = info.best_number;
+		for peer in self.peers.values_mut() {
+			peer.state = PeerSyncState::Available;
+			peer.common_number = std::cmp::min(peer.common_number, info.best_number);
+		}
+		self.is_idle = false;
+
+		if info.finalized_number.is_zero() {
+			info!("No finalized block to fast sync to, syncing all blocks instead");
+			self.required_block_attributes |= BlockAttributes::BODY;
+		} else {
+			info!("Fast sync: downloading the state of block #{} ({})", info.finalized_number, info.finalized_hash);
+			self.warp_sync = Some(WarpSync::new_state(header));
+		}
+	}
+
+	/// During the header phase of a fast sync, hold back the downloaded headers following the last
+	/// one with a justification, so that no header is imported past the block whose state is
+	/// downloaded.
+	fn hold_back_fast_sync_headers(&mut self, new_blocks: Vec<IncomingBlock<B>>) -> Vec<IncomingBlock<B>> {
+		match self.fast_sync_headers {
+			Some(ref mut held) => hold_back_unjustified(held, new_blocks),
+			None => new_blocks,
+		}
+	}
+
 	/// Get an iterator over all scheduled justification requests.
 	pub fn justification_requests(&mut self) -> impl Iterator<Item = (PeerId, BlockRequest<B>)> + '_ {
 		let peers = &mut self.peers;
@@ -567,15 +745,35 @@ impl<B: BlockT> ChainSync<B> {
 
 	/// Get an iterator over all block requests of all peers.
 	pub fn block_requests(&mut self) -> impl Iterator<Item = (PeerId, BlockRequest<B>)> + '_ {
-		if self.is_idle {
+		if self.is_idle || self.warp_sync.is_some() {
 			return Either::Left(std::iter::empty())
 		}
+		if let Some(ref held) = self.fast_sync_headers {
+			// Only the finalized chain is imported during the header phase of a fast sync.
+			self.fork_targets.clear();
+			if held.len() >= MAX_HELD_FAST_SYNC_HEADERS {
+				trace!(target: "sync", "Too many headers held back.");
+				return Either::Left(std::iter::empty())
+			}
+		}
 		if self.queue_blocks.len() > MAX_IMPORTING_BLOCKS {
 			trace!(target: "sync", "Too many blocks in the queue.");
 			return Either::Left(std::iter::empty())
 		}
 		let major_sync = self.status().state == SyncState::Downloading;
+		let now = Instant::now();
+		let peer_scores = &mut self.peer_scores;
 		let blocks = &mut self.blocks;
+		// During a major sync each range is downloaded from a single peer. If the range that holds
+		// back the import of all the others is only being downloaded from late peers, one of the
+		// available peers is asked for it as well.
+		let mut late_range = if major_sync {
+			blocks.first_pending_range()
+				.filter(|(_, downloaders)| downloaders.iter().all(|who| peer_scores.is_late(who, now)))
+				.map(|(range, downloaders)| (range, downloaders.into_iter().cloned().collect::<Vec<_>>()))
+		} else {
+			None
+		};
 		let attrs = &self.required_block_attributes;
 		let fork_targets = &mut self.fork_targets;
 		let mut have_requests = false;
@@ -605,15 +803,36 @@ impl<B: BlockT> ChainSync<B> {
 				peer.state = PeerSyncState::DownloadingStale(hash);
 				have_requests = true;
 				Some((id.clone(), req))
+			} else if let Some((range, req)) = late_range.as_ref().and_then(|late_range| late_range_request(
+				id,
+				peer,
+				blocks,
+				attrs,
+				late_range,
+				last_finalized,
+			)) {
+				late_range = None;
+				peer.state = PeerSyncState::DownloadingNew(range.start);
+				peer_scores.on_request(id, (range.end - range.start).saturated_into::<u32>(), now);
+				debug!(
+					target: "sync",
+					"Also requesting late blocks {:?} from {}",
+					range,
+					id,
+				);
+				have_requests = true;
+				Some((id.clone(), req))
 			} else if let Some((range, req)) = peer_block_request(
 				id,
 				peer,
 				blocks,
 				attrs,
+				peer_scores.request_size(id, MIN_BLOCKS_TO_REQUEST, MAX_BLOCKS_TO_REQUEST),
 				max_parallel,
 				last_finalized
 			) {
 				peer.state = PeerSyncState::DownloadingNew(range.start);
+				peer_scores.on_request(id, (range.end - range.start).saturated_into::<u32>(), now);
 				trace!(
 					target: "sync",
 					"New block request for {}, (best:{}, common:{}) {:?}",
@@ -643,6 +862,7 @@ impl<B: BlockT> ChainSync<B> {
 	pub fn on_block_data
 		(&mut self, who: PeerId, request: BlockRequest<B>, response: BlockResponse<B>) -> Result<OnBlockData<B>, BadPeer>
 	{
+		let header_phase = self.fast_sync_headers.is_some();
 		let new_blocks: Vec<IncomingBlock<B>> =
 			if let Some(peer) = self.peers.get_mut(&who) {
 				let mut blocks = response.blocks;
@@ -653,8 +873,12 @@ impl<B: BlockT> ChainSync<B> {
 				self.is_idle = false;
 				match &mut peer.state {
 					PeerSyncState::DownloadingNew(start_block) => {
-						self.blocks.clear_peer_download(&who);
-						self.blocks.insert(*start_block, blocks, who);
+						self.peer_scores.on_response(&who, blocks.len(), Instant::now());
+						if self.blocks.clear_peer_download(&who) {
+							self.blocks.insert(*start_block, blocks, who);
+						} else {
+							trace!(target: "sync", "Ignored block data from {} already downloaded from another peer", who);
+						}
 						peer.state = PeerSyncState::Available;
 						self.blocks
 							.drain(self.best_queued_number + One::one())
@@ -666,8 +890,9 @@ impl<B: BlockT> ChainSync<B> {
 									body: block_data.block.body,
 									justification: block_data.block.justification,
 									origin: block_data.origin,
-									allow_missing_state: false,
+									allow_missing_state: header_phase,
 									import_existing: false,
+									state: None,
 								}
 							}).collect()
 					}
@@ -682,6 +907,7 @@ impl<B: BlockT> ChainSync<B> {
 								origin: Some(who.clone()),
 								allow_missing_state: true,
 								import_existing: false,
+								state: None,
 							}
 						}).collect()
 					}
@@ -730,6 +956,7 @@ impl<B: BlockT> ChainSync<B> {
 									.or_insert_with(|| ForkTarget {
 										number: peer.best_number,
 										parent_hash: None,
+										weight: None,
 										peers: Default::default(),
 									})
 								.peers.insert(who);
@@ -760,11 +987,13 @@ impl<B: BlockT> ChainSync<B> {
 				BlockOrigin::NetworkInitialSync
 			};
 
-		if let Some((h, n)) = new_blocks.last().and_then(|b| b.header.as_ref().map(|h| (&b.hash, *h.number()))) {
+		if let Some((h, n)) = new_blocks.last().and_then(|b| b.header.as_ref().map(|h| (b.hash, *h.number()))) {
 			trace!(target:"sync", "Accepted {} blocks ({:?}) with origin {:?}", new_blocks.len(), h, origin);
-			self.on_block_queued(h, n)
+			self.on_block_queued(&h, n)
 		}
 
+		let new_blocks = self.hold_back_fast_sync_headers(new_blocks);
+
 		self.queue_blocks.extend(new_blocks.iter().map(|b| b.hash));
 
 		Ok(OnBlockData::Import(origin, new_blocks))
@@ -869,6 +1098,27 @@ impl<B: BlockT> ChainSync<B> {
 		for (result, hash) in results {
 			hashes.push(hash);
 
+			if self.warp_sync.as_ref().map_or(false, |warp_sync| warp_sync.is_importing(&hash)) {
+				let can_fall_back = self.warp_sync.take().map_or(true, |warp_sync| warp_sync.can_fall_back());
+				match result {
+					Ok(_) => info!("Imported the state of block {}, syncing from there", hash),
+					Err(e) if !can_fall_back => {
+						warn!("Failed to import the state of block {}: {:?}, downloading it again", hash, e);
+						self.queue_blocks.remove(&hash);
+						if let Ok(Some(header)) = self.client.header(&BlockId::Hash(hash)) {
+							self.warp_sync = Some(WarpSync::new_state(header));
+						}
+						continue;
+					},
+					Err(e) => warn!("Failed to import warp synced block {}: {:?}, falling back to full sync", hash, e),
+				}
+				if self.role.is_full() {
+					self.required_block_attributes |= BlockAttributes::BODY;
+				}
+				output.extend(self.restart());
+				continue;
+			}
+
 			if has_error {
 				continue;
 			}
@@ -1031,6 +1281,9 @@ impl<B: BlockT> ChainSync<B> {
 	/// header (call `on_block_data`). The network request isn't sent
 	/// in this case. Both hash and header is passed as an optimization
 	/// to avoid rehashing the header.
+	///
+	/// The announcement is checked by the `BlockAnnounceValidator` first, including any data the
+	/// announcing node attached to it. Rejected announcements are otherwise ignored.
 	pub fn on_block_announce(&mut self, who: PeerId, hash: B::Hash, announce: &BlockAnnounce<B::Header>, is_best: bool)
 		-> OnBlockAnnounce
 	{
@@ -1041,6 +1294,22 @@ impl<B: BlockT> ChainSync<B> {
 			warn!(target: "sync", "Ignored genesis block (#0) announcement from {}: {}", who, hash);
 			return OnBlockAnnounce::Nothing
 		}
+
+		// Let external validator check the block announcement before it affects the state of the
+		// peer.
+		let assoc_data = announce.data.as_ref().map_or(&[][..], |v| v.as_slice());
+		match self.block_announce_validator.validate(&header, assoc_data) {
+			Ok(Validation::Success) => (),
+			Ok(Validation::Failure) => {
+				debug!(target: "sync", "Block announcement validation of block {} from {} failed", hash, who);
+				return OnBlockAnnounce::Rejected
+			}
+			Err(e) => {
+				error!(target: "sync", "Block announcement validation errored: {}", e);
+				return OnBlockAnnounce::Nothing
+			}
+		}
+
 		let parent_status = self.block_status(header.parent_hash()).ok().unwrap_or(BlockStatus::Unknown);
 		let known_parent = parent_status != BlockStatus::Unknown;
 		let ancient_parent = parent_status == BlockStatus::InChainPruned;
@@ -1084,20 +1353,6 @@ impl<B: BlockT> ChainSync<B> {
 			return OnBlockAnnounce::Nothing
 		}
 
-		// Let external validator check the block announcement.
-		let assoc_data = announce.data.as_ref().map_or(&[][..], |v| v.as_slice());
-		match self.block_announce_validator.validate(&header, assoc_data) {
-			Ok(Validation::Success) => (),
-			Ok(Validation::Failure) => {
-				debug!(target: "sync", "Block announcement validation of block {} from {} failed", hash, who);
-				return OnBlockAnnounce::Nothing
-			}
-			Err(e) => {
-				error!(target: "sync", "Block announcement validation errored: {}", e);
-				return OnBlockAnnounce::Nothing
-			}
-		}
-
 		if ancient_parent {
 			trace!(target: "sync", "Ignored ancient block announced from {}: {} {:?}", who, hash, header);
 			return OnBlockAnnounce::Nothing
@@ -1114,11 +1369,13 @@ impl<B: BlockT> ChainSync<B> {
 				target: "sync",
 				"Added sync target for block announced from {}: {} {:?}", who, hash, header
 			);
+			let fork_ranking = &self.fork_ranking;
 			self.fork_targets
 				.entry(hash.clone())
 				.or_insert_with(|| ForkTarget {
 					number,
 					parent_hash: Some(header.parent_hash().clone()),
+					weight: fork_ranking.as_ref().and_then(|ranking| ranking.fork_weight(header)),
 					peers: Default::default(),
 				})
 				.peers.insert(who);
@@ -1130,17 +1387,35 @@ impl<B: BlockT> ChainSync<B> {
 	/// Call when a peer has disconnected.
 	pub fn peer_disconnected(&mut self, who: PeerId) {
 		self.blocks.clear_peer_download(&who);
+		self.peer_scores.peer_disconnected(&who);
 		self.peers.remove(&who);
 		self.extra_justifications.peer_disconnected(&who);
 		self.extra_finality_proofs.peer_disconnected(&who);
 		self.is_idle = false;
 	}
 
+	/// Perform time based maintenance.
+	///
+	/// Resumes block requests if the range that holds back the import is only being downloaded
+	/// from late peers, such that another peer can be asked for it.
+	pub fn tick(&mut self) {
+		let now = Instant::now();
+		let peer_scores = &self.peer_scores;
+		let late = self.blocks.first_pending_range()
+			.map_or(false, |(_, downloaders)| downloaders.iter().all(|who| peer_scores.is_late(who, now)));
+		if late {
+			self.is_idle = false;
+		}
+	}
+
 	/// Restart the sync process.
 	fn restart<'a>(&'a mut self) -> impl Iterator<Item = Result<(PeerId, BlockRequest<B>), BadPeer>> + 'a
 	{
 		self.queue_blocks.clear();
 		self.blocks.clear();
+		if let Some(ref mut held) = self.fast_sync_headers {
+			held.clear();
+		}
 		let info = self.client.info();
 		self.best_queued_hash = info.chain.best_hash;
 		self.best_queued_number = std::cmp::max(info.chain.best_number, self.best_imported_number);
@@ -1254,6 +1529,7 @@ fn peer_block_request<B: BlockT>(
 	peer: &PeerSync<B>,
 	blocks: &mut BlockCollection<B>,
 	attrs: &message::BlockAttributes,
+	max_blocks: usize,
 	max_parallel_downloads: u32,
 	finalized: NumberFor<B>,
 ) -> Option<(Range<NumberFor<B>>, BlockRequest<B>)> {
@@ -1262,26 +1538,55 @@ fn peer_block_request<B: BlockT>(
 	}
 	if let Some(range) = blocks.needed_blocks(
 		id.clone(),
-		MAX_BLOCKS_TO_REQUEST,
+		max_blocks,
 		peer.best_number,
 		peer.common_number,
 		max_parallel_downloads,
 		MAX_DOWNLOAD_AHEAD,
 	) {
-		let request = message::generic::BlockRequest {
-			id: 0,
-			fields: attrs.clone(),
-			from: message::FromBlock::Number(range.start),
-			to: None,
-			direction: message::Direction::Ascending,
-			max: Some((range.end - range.start).saturated_into::<u32>())
-		};
+		let request = range_block_request(&range, attrs);
 		Some((range, request))
 	} else {
 		None
 	}
 }
 
+/// Get a request for the given range, being downloaded from late peers only, if the peer can
+/// download it as well.
+fn late_range_request<B: BlockT>(
+	id: &PeerId,
+	peer: &PeerSync<B>,
+	blocks: &mut BlockCollection<B>,
+	attrs: &message::BlockAttributes,
+	(range, downloaders): &(Range<NumberFor<B>>, Vec<PeerId>),
+	finalized: NumberFor<B>,
+) -> Option<(Range<NumberFor<B>>, BlockRequest<B>)> {
+	if peer.common_number < finalized || downloaders.contains(id) {
+		return None;
+	}
+	if peer.best_number < range.end - One::one() {
+		return None;
+	}
+	let range = blocks.add_downloader(id.clone(), range.start)?;
+	let request = range_block_request(&range, attrs);
+	Some((range, request))
+}
+
+/// Build an ascending request for all the blocks of the given range.
+fn range_block_request<B: BlockT>(
+	range: &Range<NumberFor<B>>,
+	attrs: &message::BlockAttributes,
+) -> BlockRequest<B> {
+	message::generic::BlockRequest {
+		id: 0,
+		fields: attrs.clone(),
+		from: message::FromBlock::Number(range.start),
+		to: None,
+		direction: message::Direction::Ascending,
+		max: Some((range.end - range.start).saturated_into::<u32>())
+	}
+}
+
 /// Get pending fork sync targets for a peer.
 fn fork_sync_request<B: BlockT>(
 	id: &PeerId,
@@ -1298,27 +1603,117 @@ fn fork_sync_request<B: BlockT>(
 		trace!(target: "sync", "Removed expired fork sync request {:?} (#{})", hash, r.number);
 		false
 	});
-	for (hash, r) in targets {
-		if !r.peers.contains(id) {
-			continue
-		}
-		if r.number <= best_num {
-			let parent_status = r.parent_hash.as_ref().map_or(BlockStatus::Unknown, check_block);
-			let mut count = (r.number - finalized).saturated_into::<u32>(); // up to the last finalized block
-			if parent_status != BlockStatus::Unknown {
-				// request only single block
-				count = 1;
-			}
-			trace!(target: "sync", "Downloading requested fork {:?} from {}, {} blocks", hash, id, count);
-			return Some((hash.clone(), message::generic::BlockRequest {
-				id: 0,
-				fields: attributes.clone(),
-				from: message::FromBlock::Hash(hash.clone()),
-				to: None,
-				direction: message::Direction::Descending,
-				max: Some(count),
-			}))
-		}
+	// The heaviest fork the peer can serve is downloaded first, then the highest.
+	let (hash, r) = targets.iter()
+		.filter(|(_, r)| r.peers.contains(id) && r.number <= best_num)
+		.max_by_key(|(_, r)| (r.weight, r.number))?;
+	let parent_status = r.parent_hash.as_ref().map_or(BlockStatus::Unknown, check_block);
+	let mut count = (r.number - finalized).saturated_into::<u32>(); // up to the last finalized block
+	if parent_status != BlockStatus::Unknown {
+		// request only single block
+		count = 1;
+	}
+	trace!(target: "sync", "Downloading requested fork {:?} from {}, {} blocks", hash, id, count);
+	Some((hash.clone(), message::generic::BlockRequest {
+		id: 0,
+		fields: attributes.clone(),
+		from: message::FromBlock::Hash(hash.clone()),
+		to: None,
+		direction: message::Direction::Descending,
+		max: Some(count),
+	}))
+}
+
+/// Append `new_blocks` to the `held` ones, and return those up to the last one with a
+/// justification, holding back the others.
+fn hold_back_unjustified<B: BlockT>(
+	held: &mut Vec<IncomingBlock<B>>,
+	new_blocks: Vec<IncomingBlock<B>>,
+) -> Vec<IncomingBlock<B>> {
+	held.extend(new_blocks);
+	let ready = held.iter().rposition(|b| b.justification.is_some()).map_or(0, |i| i + 1);
+	let rest = held.split_off(ready);
+	std::mem::replace(held, rest)
+}
+
+#[cfg(test)]
+mod test {
+	use super::{ForkTarget, fork_sync_request, hold_back_unjustified};
+	use crate::protocol::message::BlockAttributes;
+	use libp2p::PeerId;
+	use sp_consensus::{BlockStatus, import_queue::IncomingBlock};
+	use sp_core::H256;
+	use std::collections::HashMap;
+	use sp_runtime::testing::{Block as RawBlock, ExtrinsicWrapper};
+
+	type Block = RawBlock<ExtrinsicWrapper<u64>>;
+
+	fn blocks(first: u64, justified: &[bool]) -> Vec<IncomingBlock<Block>> {
+		justified.iter().enumerate().map(|(i, justified)| IncomingBlock {
+			hash: H256::from_low_u64_be(first + i as u64),
+			header: None,
+			body: None,
+			justification: if *justified { Some(Vec::new()) } else { None },
+			origin: None,
+			allow_missing_state: true,
+			import_existing: false,
+			state: None,
+		}).collect()
+	}
+
+	fn hashes(blocks: &[IncomingBlock<Block>]) -> Vec<u64> {
+		blocks.iter().map(|b| b.hash.to_low_u64_be()).collect()
+	}
+
+	#[test]
+	fn headers_are_held_back_until_justified() {
+		let mut held = Vec::new();
+
+		let ready = hold_back_unjustified(&mut held, blocks(1, &[false, false, false]));
+		assert!(ready.is_empty());
+		assert_eq!(hashes(&held), vec![1, 2, 3]);
+
+		let ready = hold_back_unjustified(&mut held, blocks(4, &[false, true, false]));
+		assert_eq!(hashes(&ready), vec![1, 2, 3, 4, 5]);
+		assert_eq!(hashes(&held), vec![6]);
+
+		let ready = hold_back_unjustified(&mut held, blocks(7, &[true]));
+		assert_eq!(hashes(&ready), vec![6, 7]);
+		assert!(held.is_empty());
+	}
+
+	#[test]
+	fn heaviest_fork_is_downloaded_first() {
+		let peer = PeerId::random();
+		let other_peer = PeerId::random();
+		let target = |number, weight, peer: &PeerId| ForkTarget::<Block> {
+			number,
+			parent_hash: None,
+			weight,
+			peers: vec![peer.clone()].into_iter().collect(),
+		};
+		let mut targets = HashMap::new();
+		targets.insert(H256::from_low_u64_be(1), target(12, None, &peer));
+		targets.insert(H256::from_low_u64_be(2), target(10, Some(3), &peer));
+		targets.insert(H256::from_low_u64_be(3), target(11, Some(3), &peer));
+		targets.insert(H256::from_low_u64_be(4), target(10, Some(5), &other_peer));
+		targets.insert(H256::from_low_u64_be(5), target(20, Some(9), &peer));
+
+		let next = |targets: &mut HashMap<_, _>| fork_sync_request::<Block>(
+			&peer,
+			targets,
+			15,
+			0,
+			&BlockAttributes::HEADER,
+			|_| BlockStatus::Unknown,
+		).map(|(hash, _)| hash.to_low_u64_be());
+
+		// Heavier forks beyond the best block of the peer or only known to other peers are skipped,
+		// ties are broken by number, and forks which can't be weighed come last.
+		assert_eq!(next(&mut targets), Some(3));
+		targets.remove(&H256::from_low_u64_be(3));
+		assert_eq!(next(&mut targets), Some(2));
+		targets.remove(&H256::from_low_u64_be(2));
+		assert_eq!(next(&mut targets), Some(1));
 	}
-	None
 }
@@ -186,27 +186,58 @@ impl<B: BlockT> BlockCollection<B> {
 		drained
 	}
 
-	pub fn clear_peer_download(&mut self, who: &PeerId) {
+	/// Returns the first range if it is still being downloaded, along with the peers downloading
+	/// it. None of the blocks downloaded after it can be drained before it completes.
+	pub fn first_pending_range(&self) -> Option<(Range<NumberFor<B>>, Vec<&PeerId>)> {
+		match self.blocks.iter().next() {
+			Some((start, BlockRangeState::Downloading { len, .. })) => {
+				let peers = self.peer_requests.iter()
+					.filter(|(_, s)| *s == start)
+					.map(|(who, _)| who)
+					.collect();
+				Some((*start .. *start + *len, peers))
+			},
+			_ => None,
+		}
+	}
+
+	/// Marks the range being downloaded starting at `start` as also being downloaded from `who`.
+	/// Returns the range, or `None` if there is no such range.
+	pub fn add_downloader(&mut self, who: PeerId, start: NumberFor<B>) -> Option<Range<NumberFor<B>>> {
+		match self.blocks.get_mut(&start) {
+			Some(BlockRangeState::Downloading { len, downloading }) => {
+				*downloading += 1;
+				self.peer_requests.insert(who, start);
+				Some(start .. start + *len)
+			},
+			_ => None,
+		}
+	}
+
+	/// Forgets the range requested from `who`. Returns whether the range was still being
+	/// downloaded, i.e. whether no other peer downloading it has answered first.
+	pub fn clear_peer_download(&mut self, who: &PeerId) -> bool {
 		match self.peer_requests.entry(who.clone()) {
 			Entry::Occupied(entry) => {
 				let start = entry.remove();
-				let remove = match self.blocks.get_mut(&start) {
+				let (remove, pending) = match self.blocks.get_mut(&start) {
 					Some(&mut BlockRangeState::Downloading { ref mut downloading, .. }) if *downloading > 1 => {
 						*downloading = *downloading - 1;
-						false
+						(false, true)
 					},
 					Some(&mut BlockRangeState::Downloading { .. }) => {
-						true
+						(true, true)
 					},
 					_ => {
-						false
+						(false, false)
 					}
 				};
 				if remove {
 					self.blocks.remove(&start);
 				}
+				pending
 			},
-			_ => (),
+			_ => false,
 		}
 	}
 }
@@ -294,6 +325,56 @@ mod test {
 			.map(|b| BlockData { block: b.clone(), origin: Some(peer1.clone()) }).collect::<Vec<_>>()[..]);
 	}
 
+	#[test]
+	fn pending_range_can_be_downloaded_from_another_peer() {
+		let mut bc: BlockCollection<Block> = BlockCollection::new();
+		let peer0 = PeerId::random();
+		let peer1 = PeerId::random();
+		let peer2 = PeerId::random();
+
+		assert_eq!(bc.first_pending_range(), None);
+		assert_eq!(bc.needed_blocks(peer0.clone(), 40, 150, 0, 1, 200), Some(1 .. 41));
+		assert_eq!(bc.needed_blocks(peer1.clone(), 40, 150, 0, 1, 200), Some(41 .. 81));
+		assert_eq!(bc.first_pending_range(), Some((1 .. 41, vec![&peer0])));
+
+		assert_eq!(bc.add_downloader(peer2.clone(), 1), Some(1 .. 41));
+		assert_eq!(bc.add_downloader(peer2.clone(), 2), None);
+		let (range, mut peers) = bc.first_pending_range().unwrap();
+		peers.sort_by_key(|p| p.to_base58());
+		let mut expected = vec![&peer0, &peer2];
+		expected.sort_by_key(|p| p.to_base58());
+		assert_eq!((range, peers), (1 .. 41, expected));
+
+		// Whichever peer answers first completes the range.
+		let blocks = generate_blocks(40);
+		assert!(bc.clear_peer_download(&peer2));
+		bc.insert(1, blocks.clone(), peer2.clone());
+		assert_eq!(bc.first_pending_range(), None);
+		assert!(!bc.clear_peer_download(&peer0));
+		assert_eq!(bc.drain(1), blocks.into_iter()
+			.map(|b| BlockData { block: b, origin: Some(peer2.clone()) }).collect::<Vec<_>>());
+	}
+
+	#[test]
+	fn late_response_of_drained_range_is_not_pending() {
+		let mut bc: BlockCollection<Block> = BlockCollection::new();
+		let peer0 = PeerId::random();
+		let peer1 = PeerId::random();
+
+		assert_eq!(bc.needed_blocks(peer0.clone(), 40, 150, 0, 1, 200), Some(1 .. 41));
+		assert_eq!(bc.add_downloader(peer1.clone(), 1), Some(1 .. 41));
+
+		let blocks = generate_blocks(40);
+		assert!(bc.clear_peer_download(&peer1));
+		bc.insert(1, blocks.clone(), peer1.clone());
+		assert_eq!(bc.drain(1).len(), 40);
+
+		// The slower peer answers once the range has already been drained.
+		assert!(!bc.clear_peer_download(&peer0));
+		assert!(bc.blocks.is_empty());
+		assert!(bc.drain(1).is_empty());
+	}
+
 	#[test]
 	fn large_gap() {
 		let mut bc: BlockCollection<Block> = BlockCollection::new();
@@ -0,0 +1,167 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+use libp2p::PeerId;
+use std::cmp;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// Weight of the latest measurement in the download rate of a peer.
+const RATE_SMOOTHING: f64 = 0.3;
+
+/// A block request is considered late if it isn't answered within this factor of the time the
+/// peer is expected to need for it.
+const LATE_FACTOR: u32 = 3;
+
+/// Minimum time after which a block request is considered late.
+const MIN_LATE_DELAY: Duration = Duration::from_secs(5);
+
+/// Download rate of the peers we request blocks from, used to size the requests sent to each
+/// peer and to detect the peers that hold back the import of the blocks downloaded from others.
+#[derive(Default)]
+pub struct PeerScores {
+	peers: HashMap<PeerId, PeerScore>,
+}
+
+#[derive(Default)]
+struct PeerScore {
+	/// Smoothed number of blocks per second received from the peer, if measured yet.
+	blocks_per_sec: Option<f64>,
+	/// When the pending block request was sent and how many blocks it asked for.
+	pending: Option<(Instant, u32)>,
+}
+
+impl PeerScores {
+	/// Create a new instance.
+	pub fn new() -> Self {
+		PeerScores::default()
+	}
+
+	/// Number of blocks to request from the given peer, between `min` and `max`.
+	///
+	/// Peers are asked for a number of blocks proportional to their download rate, relatively to
+	/// the fastest peer, such that slower peers are given smaller ranges. Peers whose rate is
+	/// unknown are given `max` blocks.
+	pub fn request_size(&self, who: &PeerId, min: usize, max: usize) -> usize {
+		let rate = match self.peers.get(who).and_then(|p| p.blocks_per_sec) {
+			Some(rate) => rate,
+			None => return max,
+		};
+		let best = self.peers.values()
+			.filter_map(|p| p.blocks_per_sec)
+			.fold(rate, f64::max);
+
+		let size = if best > 0.0 { (max as f64 * rate / best) as usize } else { max };
+		cmp::max(min, cmp::min(max, size))
+	}
+
+	/// Call when a request for `num_blocks` blocks is sent to the given peer.
+	pub fn on_request(&mut self, who: &PeerId, num_blocks: u32, now: Instant) {
+		self.peers.entry(who.clone()).or_default().pending = Some((now, num_blocks));
+	}
+
+	/// Call when the response to the pending request of the given peer is received.
+	pub fn on_response(&mut self, who: &PeerId, num_blocks: usize, now: Instant) {
+		let score = self.peers.entry(who.clone()).or_default();
+		let started = match score.pending.take() {
+			Some((started, _)) => started,
+			None => return,
+		};
+
+		let elapsed = now.saturating_duration_since(started).as_millis().max(1) as f64 / 1000.0;
+		let rate = num_blocks as f64 / elapsed;
+		score.blocks_per_sec = Some(match score.blocks_per_sec {
+			Some(prev) => prev + RATE_SMOOTHING * (rate - prev),
+			None => rate,
+		});
+	}
+
+	/// Returns whether the pending request of the given peer takes significantly longer than what
+	/// its download rate suggests.
+	pub fn is_late(&self, who: &PeerId, now: Instant) -> bool {
+		let score = match self.peers.get(who) {
+			Some(score) => score,
+			None => return false,
+		};
+		let (started, num_blocks) = match score.pending {
+			Some(pending) => pending,
+			None => return false,
+		};
+
+		let expected = match score.blocks_per_sec {
+			Some(rate) if rate > 0.0 => Duration::from_millis((num_blocks as f64 / rate * 1000.0) as u64),
+			_ => Duration::from_secs(0),
+		};
+		now.saturating_duration_since(started) > cmp::max(MIN_LATE_DELAY, expected * LATE_FACTOR)
+	}
+
+	/// Call when a peer has disconnected.
+	pub fn peer_disconnected(&mut self, who: &PeerId) {
+		self.peers.remove(who);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn slower_peers_get_smaller_requests() {
+		let mut scores = PeerScores::new();
+		let fast = PeerId::random();
+		let slow = PeerId::random();
+		let unknown = PeerId::random();
+		let now = Instant::now();
+
+		scores.on_request(&fast, 128, now);
+		scores.on_request(&slow, 128, now);
+		scores.on_response(&fast, 128, now + Duration::from_secs(1));
+		scores.on_response(&slow, 128, now + Duration::from_secs(4));
+
+		assert_eq!(scores.request_size(&fast, 16, 128), 128);
+		assert_eq!(scores.request_size(&slow, 16, 128), 32);
+		assert_eq!(scores.request_size(&unknown, 16, 128), 128);
+
+		// Very slow peers are still asked for the minimum.
+		let very_slow = PeerId::random();
+		scores.on_request(&very_slow, 128, now);
+		scores.on_response(&very_slow, 1, now + Duration::from_secs(10));
+		assert_eq!(scores.request_size(&very_slow, 16, 128), 16);
+	}
+
+	#[test]
+	fn detects_late_requests() {
+		let mut scores = PeerScores::new();
+		let peer = PeerId::random();
+		let now = Instant::now();
+
+		assert!(!scores.is_late(&peer, now));
+
+		scores.on_request(&peer, 10, now);
+		assert!(!scores.is_late(&peer, now + MIN_LATE_DELAY));
+		assert!(scores.is_late(&peer, now + MIN_LATE_DELAY + Duration::from_secs(1)));
+
+		// One block per second measured, so 10 blocks are expected within 10 seconds.
+		scores.on_response(&peer, 10, now + Duration::from_secs(10));
+		let now = now + Duration::from_secs(10);
+		scores.on_request(&peer, 10, now);
+		assert!(!scores.is_late(&peer, now + Duration::from_secs(29)));
+		assert!(scores.is_late(&peer, now + Duration::from_secs(31)));
+
+		scores.on_response(&peer, 10, now + Duration::from_secs(31));
+		assert!(!scores.is_late(&peer, now + Duration::from_secs(100)));
+	}
+}
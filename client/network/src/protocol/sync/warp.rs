@@ -0,0 +1,477 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Warp sync of a node with an empty database.
+//!
+//! Instead of downloading and executing every block, the node downloads proofs of finality of a
+//! recent block from its peers, as provided by the [`WarpSyncProvider`], then the state of that
+//! block. The block is imported along with its state, and the regular sync resumes from there.
+//!
+//! Both steps use request-response protocols: `/<protocol id>/sync/warp` for the proofs and
+//! `/<protocol id>/sync/state` for the state, which is downloaded in chunks of ordered key-value
+//! pairs and checked against the state root of the block once complete.
+//!
+//! The state download alone also completes a fast sync, once the headers of the chain have been
+//! imported up to the latest finalized block.
+
+use codec::{Encode, Decode};
+use libp2p::PeerId;
+use log::{debug, info, trace, warn};
+use sp_consensus::import_queue::IncomingBlock;
+use sp_core::storage::well_known_keys;
+use sp_runtime::{Justification, traits::{Block as BlockT, Hash as HashT, Header, NumberFor}};
+use std::{collections::HashSet, sync::Arc};
+use crate::chain::{WarpSyncProvider, WarpSyncVerification};
+use crate::config::ProtocolId;
+use crate::request_responses::RequestFailure;
+use super::{BadPeer, rep};
+
+/// Minimum number of connected peers before the first warp sync proof is requested.
+const MIN_PEERS_TO_START_WARP_SYNC: usize = 3;
+
+/// Number of peers that may fail a request before warp sync is given up for a full sync.
+const MAX_FAILED_PEERS: usize = 8;
+
+/// Maximum size of a warp sync request.
+pub const MAX_REQUEST_SIZE: usize = 1024;
+
+/// Maximum size of a warp sync response, either a proof or a chunk of state.
+pub const MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+/// Size of the keys and values returned in a single state response.
+pub const STATE_RESPONSE_SIZE: usize = 8 * 1024 * 1024;
+
+/// Maximum size of the keys and values of a downloaded state. The state is kept in memory until
+/// it is complete, thus the download is given up beyond.
+const MAX_STATE_SIZE: usize = 2 * 1024 * 1024 * 1024;
+
+/// Name of the protocol on which warp sync proofs are requested.
+pub fn warp_proof_protocol_name(protocol_id: &ProtocolId) -> String {
+	format!("/{}/sync/warp", String::from_utf8_lossy(protocol_id.as_bytes()))
+}
+
+/// Name of the protocol on which the state of a block is requested.
+pub fn state_protocol_name(protocol_id: &ProtocolId) -> String {
+	format!("/{}/sync/state", String::from_utf8_lossy(protocol_id.as_bytes()))
+}
+
+/// Request for a warp sync proof starting at the given finalized block.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct WarpProofRequest<H> {
+	/// Hash of the first block of the proof.
+	pub begin: H,
+}
+
+/// Request for the storage entries of a block.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct StateRequest<H> {
+	/// Hash of the block.
+	pub block: H,
+	/// Entries are returned starting after this key.
+	pub start: Vec<u8>,
+}
+
+/// Response to a [`StateRequest`].
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode)]
+pub struct StateResponse {
+	/// Storage entries, in ascending key order.
+	pub entries: Vec<(Vec<u8>, Vec<u8>)>,
+	/// Whether these are the last entries of the state.
+	pub complete: bool,
+}
+
+/// Request to send as part of the warp sync.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WarpSyncRequest<B: BlockT> {
+	/// To send on the warp sync proof protocol.
+	WarpProof(WarpProofRequest<B::Hash>),
+	/// To send on the state protocol.
+	State(StateRequest<B::Hash>),
+}
+
+/// Result of [`WarpSync::on_response`].
+#[derive(Debug)]
+pub enum OnWarpSyncResponse<B: BlockT> {
+	/// The response needs no further handling.
+	Nothing,
+	/// The state of the block is complete. The block should be imported.
+	Import(IncomingBlock<B>),
+	/// The response was invalid.
+	BadPeer(BadPeer),
+	/// The sync can't complete. After a warp sync, the node should sync all blocks instead.
+	Abort,
+}
+
+enum Phase<B: BlockT> {
+	/// Downloading proofs, starting at the given block.
+	WarpProof(B::Hash, Arc<dyn WarpSyncProvider<B>>),
+	/// Downloading the state of the block proven final by the warp sync proofs, or of the last
+	/// finalized block of a fast sync.
+	State {
+		header: B::Header,
+		justification: Option<Justification>,
+		entries: Vec<(Vec<u8>, Vec<u8>)>,
+		/// Size of the keys and values of `entries`.
+		size: usize,
+	},
+	/// The block with the given hash is being imported with its state.
+	Importing(B::Hash),
+}
+
+/// State of the warp sync.
+pub struct WarpSync<B: BlockT> {
+	phase: Phase<B>,
+	/// Peer we sent the pending request to, if any.
+	pending: Option<PeerId>,
+	/// Peers that failed a request and are not asked again.
+	failed: HashSet<PeerId>,
+	/// Whether the node can fall back to a full sync if too many peers fail.
+	can_fall_back: bool,
+}
+
+impl<B: BlockT> WarpSync<B> {
+	/// Create a new instance, starting with the proofs from the genesis block.
+	pub fn new(provider: Arc<dyn WarpSyncProvider<B>>, genesis_hash: B::Hash) -> Self {
+		WarpSync {
+			phase: Phase::WarpProof(genesis_hash, provider),
+			pending: None,
+			failed: HashSet::new(),
+			can_fall_back: true,
+		}
+	}
+
+	/// Create a new instance downloading the state of the given block, whose header and finality
+	/// have already been imported.
+	///
+	/// There is no falling back to a full sync from there, as the blocks before have been imported
+	/// without their state. Peers are asked again after all of them failed.
+	pub fn new_state(header: B::Header) -> Self {
+		WarpSync {
+			phase: Phase::State { header, justification: None, entries: Vec::new(), size: 0 },
+			pending: None,
+			failed: HashSet::new(),
+			can_fall_back: false,
+		}
+	}
+
+	/// Returns `true` if the node can sync all blocks instead, should this sync fail.
+	pub fn can_fall_back(&self) -> bool {
+		self.can_fall_back
+	}
+
+	/// Returns `true` if the given block is being imported with its state.
+	pub fn is_importing(&self, hash: &B::Hash) -> bool {
+		match self.phase {
+			Phase::Importing(ref importing) => importing == hash,
+			_ => false,
+		}
+	}
+
+	/// Returns the next request to send, given the connected peers and their best block number.
+	pub fn next_request<'a>(
+		&mut self,
+		peers: impl Iterator<Item = (&'a PeerId, NumberFor<B>)>,
+	) -> Option<(PeerId, WarpSyncRequest<B>)> {
+		if self.pending.is_some() {
+			return None
+		}
+
+		let failed = &self.failed;
+		let mut peers = peers.filter(|(who, _)| !failed.contains(who)).collect::<Vec<_>>();
+		let (who, request) = match self.phase {
+			Phase::WarpProof(begin, _) => {
+				if peers.len() < MIN_PEERS_TO_START_WARP_SYNC {
+					return None
+				}
+				peers.sort_by_key(|(_, best_number)| *best_number);
+				let (who, _) = peers.pop()?;
+				(who.clone(), WarpSyncRequest::WarpProof(WarpProofRequest { begin }))
+			},
+			Phase::State { ref header, ref entries, .. } => {
+				let (who, _) = peers.into_iter().find(|(_, best_number)| best_number >= header.number())?;
+				let start = entries.last().map(|(key, _)| key.clone()).unwrap_or_default();
+				(who.clone(), WarpSyncRequest::State(StateRequest { block: header.hash(), start }))
+			},
+			Phase::Importing(_) => return None,
+		};
+
+		trace!(target: "sync", "Warp sync request to {}: {:?}", who, request);
+		self.pending = Some(who.clone());
+		Some((who, request))
+	}
+
+	/// Handle the response to the last request returned by [`WarpSync::next_request`].
+	pub fn on_response(
+		&mut self,
+		who: PeerId,
+		response: Result<Vec<u8>, RequestFailure>,
+	) -> OnWarpSyncResponse<B> {
+		if self.pending.as_ref() != Some(&who) {
+			debug!(target: "sync", "Unexpected warp sync response from {}", who);
+			return OnWarpSyncResponse::Nothing
+		}
+		self.pending = None;
+
+		let response = match response {
+			Ok(response) => response,
+			Err(e) => {
+				debug!(target: "sync", "Warp sync request to {} failed: {}", who, e);
+				return self.on_failure(who, None)
+			},
+		};
+
+		match self.phase {
+			Phase::WarpProof(_, ref provider) => match provider.verify(&response) {
+				Ok(WarpSyncVerification::Partial(begin)) => {
+					debug!(target: "sync", "Verified partial warp sync proof from {}, continuing at {}", who, begin);
+					self.phase = Phase::WarpProof(begin, provider.clone());
+					OnWarpSyncResponse::Nothing
+				},
+				Ok(WarpSyncVerification::Complete(header, justification)) => {
+					info!(
+						"Warp sync proven finality of block #{} ({}), downloading its state",
+						header.number(),
+						header.hash(),
+					);
+					self.phase = Phase::State {
+						header,
+						justification: Some(justification),
+						entries: Vec::new(),
+						size: 0,
+					};
+					OnWarpSyncResponse::Nothing
+				},
+				Err(e) => {
+					debug!(target: "sync", "Bad warp sync proof from {}: {:?}", who, e);
+					self.on_failure(who, Some(rep::BAD_WARP_PROOF))
+				},
+			},
+			Phase::State { .. } => {
+				let response = match StateResponse::decode(&mut &response[..]) {
+					Ok(response) => response,
+					Err(e) => {
+						debug!(target: "sync", "Failed to decode state response from {}: {:?}", who, e);
+						return self.on_failure(who, Some(rep::BAD_STATE))
+					},
+				};
+				self.on_state_response(who, response)
+			},
+			Phase::Importing(_) => OnWarpSyncResponse::Nothing,
+		}
+	}
+
+	fn on_state_response(&mut self, who: PeerId, response: StateResponse) -> OnWarpSyncResponse<B> {
+		let hash = match self.phase {
+			Phase::State { ref header, .. } => header.hash(),
+			_ => return OnWarpSyncResponse::Nothing,
+		};
+		let (header, justification, mut entries, mut size) = match std::mem::replace(&mut self.phase, Phase::Importing(hash)) {
+			Phase::State { header, justification, entries, size } => (header, justification, entries, size),
+			_ => unreachable!("the phase was checked above; qed"),
+		};
+
+		if let Err(rep) = check_state_entries(entries.last().map(|(key, _)| &key[..]), &response.entries) {
+			debug!(target: "sync", "Invalid state entries from {}", who);
+			self.phase = Phase::State { header, justification, entries: Vec::new(), size: 0 };
+			return self.on_failure(who, Some(rep))
+		}
+		if response.entries.iter().any(|(key, _)| well_known_keys::is_child_storage_key(key)) {
+			warn!("Downloading the state of chains with child storage isn't supported");
+			return OnWarpSyncResponse::Abort
+		}
+
+		size += response.entries.iter().map(|(key, value)| key.len() + value.len()).sum::<usize>();
+		if size > MAX_STATE_SIZE {
+			warn!(
+				"The state of block #{} ({}) exceeds {} bytes, giving up downloading it",
+				header.number(),
+				hash,
+				MAX_STATE_SIZE,
+			);
+			return OnWarpSyncResponse::Abort
+		}
+
+		entries.extend(response.entries);
+		if !response.complete {
+			trace!(target: "sync", "Downloaded {} state entries of block {}", entries.len(), hash);
+			self.phase = Phase::State { header, justification, entries, size };
+			return OnWarpSyncResponse::Nothing
+		}
+
+		let root = <<B::Header as Header>::Hashing as HashT>::trie_root(entries.clone());
+		if root != *header.state_root() {
+			debug!(target: "sync", "State of block {} from {} doesn't match its root", hash, who);
+			self.phase = Phase::State { header, justification, entries: Vec::new(), size: 0 };
+			return self.on_failure(who, Some(rep::BAD_STATE))
+		}
+
+		info!("Downloaded the state of block #{} ({}), {} entries", header.number(), hash, entries.len());
+		OnWarpSyncResponse::Import(IncomingBlock {
+			hash,
+			header: Some(header),
+			body: None,
+			justification,
+			origin: Some(who),
+			allow_missing_state: false,
+			import_existing: false,
+			state: Some(entries),
+		})
+	}
+
+	/// Don't ask the peer again, and give up if too many peers failed.
+	///
+	/// The state downloaded so far, if any, is discarded so that the state is eventually
+	/// downloaded from a single peer, which is the one to blame if it doesn't match its root.
+	fn on_failure(&mut self, who: PeerId, rep: Option<sc_peerset::ReputationChange>) -> OnWarpSyncResponse<B> {
+		if let Phase::State { ref mut entries, ref mut size, .. } = self.phase {
+			entries.clear();
+			*size = 0;
+		}
+		self.failed.insert(who.clone());
+		if self.failed.len() >= MAX_FAILED_PEERS {
+			if self.can_fall_back {
+				warn!("Warp sync failed with {} peers, falling back to full sync", self.failed.len());
+				return OnWarpSyncResponse::Abort
+			}
+			warn!("Downloading the state failed with {} peers, asking them again", self.failed.len());
+			self.failed.clear();
+		}
+
+		match rep {
+			Some(rep) => OnWarpSyncResponse::BadPeer(BadPeer(who, rep)),
+			None => OnWarpSyncResponse::Nothing,
+		}
+	}
+}
+
+/// Check that the keys of `entries` are in strictly ascending order, after `last_key` if any.
+fn check_state_entries(
+	last_key: Option<&[u8]>,
+	entries: &[(Vec<u8>, Vec<u8>)],
+) -> Result<(), sc_peerset::ReputationChange> {
+	let mut previous = last_key;
+	for (key, _) in entries {
+		if previous.map_or(false, |previous| previous >= &key[..]) {
+			return Err(rep::BAD_STATE)
+		}
+		previous = Some(key);
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use sp_runtime::testing::{Block as RawBlock, ExtrinsicWrapper, Header as TestHeader};
+	use sp_runtime::traits::BlakeTwo256;
+
+	type Block = RawBlock<ExtrinsicWrapper<u64>>;
+
+	fn header(state: &[(Vec<u8>, Vec<u8>)]) -> TestHeader {
+		TestHeader::new(
+			10,
+			Default::default(),
+			BlakeTwo256::trie_root(state.to_vec()),
+			Default::default(),
+			Default::default(),
+		)
+	}
+
+	fn state_request(sync: &mut WarpSync<Block>, who: &PeerId) -> StateRequest<<Block as BlockT>::Hash> {
+		match sync.next_request(std::iter::once((who, 10))) {
+			Some((to, WarpSyncRequest::State(request))) if to == *who => request,
+			other => panic!("Unexpected request {:?}", other),
+		}
+	}
+
+	fn respond(
+		sync: &mut WarpSync<Block>,
+		who: &PeerId,
+		entries: &[(Vec<u8>, Vec<u8>)],
+		complete: bool,
+	) -> OnWarpSyncResponse<Block> {
+		let response = StateResponse { entries: entries.to_vec(), complete }.encode();
+		sync.on_response(who.clone(), Ok(response))
+	}
+
+	fn entries(keys: &[&[u8]]) -> Vec<(Vec<u8>, Vec<u8>)> {
+		keys.iter().map(|key| (key.to_vec(), Vec::new())).collect()
+	}
+
+	#[test]
+	fn state_entries_must_be_ordered() {
+		assert!(check_state_entries(None, &[]).is_ok());
+		assert!(check_state_entries(None, &entries(&[b"a", b"b", b"ba"])).is_ok());
+		assert!(check_state_entries(Some(b"a"), &entries(&[b"b", b"c"])).is_ok());
+
+		assert!(check_state_entries(None, &entries(&[b"b", b"a"])).is_err());
+		assert!(check_state_entries(None, &entries(&[b"a", b"a"])).is_err());
+		assert!(check_state_entries(Some(b"b"), &entries(&[b"b", b"c"])).is_err());
+		assert!(check_state_entries(Some(b"c"), &entries(&[b"b"])).is_err());
+	}
+
+	#[test]
+	fn state_is_downloaded_in_chunks() {
+		let state = entries(&[b"a", b"b", b"c"]);
+		let header = header(&state);
+		let mut sync = WarpSync::<Block>::new_state(header.clone());
+		let peer = PeerId::random();
+
+		assert_eq!(state_request(&mut sync, &peer), StateRequest { block: header.hash(), start: Vec::new() });
+		assert!(sync.next_request(std::iter::once((&peer, 10))).is_none());
+		match respond(&mut sync, &peer, &state[..2], false) {
+			OnWarpSyncResponse::Nothing => {},
+			other => panic!("Unexpected outcome {:?}", other),
+		}
+
+		assert_eq!(state_request(&mut sync, &peer), StateRequest { block: header.hash(), start: b"b".to_vec() });
+		match respond(&mut sync, &peer, &state[2..], true) {
+			OnWarpSyncResponse::Import(block) => {
+				assert_eq!(block.hash, header.hash());
+				assert_eq!(block.state, Some(state));
+			},
+			other => panic!("Unexpected outcome {:?}", other),
+		}
+		assert!(sync.is_importing(&header.hash()));
+	}
+
+	#[test]
+	fn state_not_matching_the_root_is_rejected() {
+		let header = header(&entries(&[b"a", b"b"]));
+		let mut sync = WarpSync::<Block>::new_state(header);
+		let peer = PeerId::random();
+		let other_peer = PeerId::random();
+
+		state_request(&mut sync, &peer);
+		match respond(&mut sync, &peer, &entries(&[b"a"]), true) {
+			OnWarpSyncResponse::BadPeer(BadPeer(who, _)) => assert_eq!(who, peer),
+			other => panic!("Unexpected outcome {:?}", other),
+		}
+
+		// The peer isn't asked again, and the download starts over from another one.
+		assert!(sync.next_request(std::iter::once((&peer, 10))).is_none());
+		assert_eq!(state_request(&mut sync, &other_peer).start, Vec::<u8>::new());
+	}
+
+	#[test]
+	fn state_is_only_requested_from_peers_with_the_block() {
+		let mut sync = WarpSync::<Block>::new_state(header(&entries(&[b"a"])));
+		let peer = PeerId::random();
+
+		assert!(sync.next_request(std::iter::once((&peer, 9))).is_none());
+		assert!(sync.next_request(std::iter::once((&peer, 10))).is_some());
+	}
+}
@@ -0,0 +1,567 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Generic request-response protocols, allowing to send a request to a specific peer and to
+//! receive its answer.
+//!
+//! Each request is sent on a new substream, negotiated under the name of its protocol. The request
+//! is written on the substream prefixed with its length as an unsigned varint, after which the
+//! writing side is closed. The remote answers in the same way, or closes the substream to refuse.
+//!
+//! Protocols are registered at runtime with [`RequestResponsesBehaviour::register_protocol`].
+//! Incoming requests are pushed on the channel passed at registration and are answered by sending
+//! the response through the [`IncomingRequest::pending_response`] sender. Connections that are
+//! already open keep the set of protocols that were registered when they were established.
+
+use futures::prelude::*;
+use futures03::{channel::{mpsc, oneshot}, compat::Compat, TryFutureExt as _};
+use futures_timer::Delay;
+use libp2p::core::{ConnectedPoint, Multiaddr, Negotiated, PeerId};
+use libp2p::core::upgrade::{self, InboundUpgrade, OutboundUpgrade, ReadOneError, UpgradeError, UpgradeInfo};
+use libp2p::swarm::{KeepAlive, NetworkBehaviour, NetworkBehaviourAction, PollParameters};
+use libp2p::swarm::{ProtocolsHandler, ProtocolsHandlerEvent, ProtocolsHandlerUpgrErr, SubstreamProtocol};
+use log::debug;
+use std::{collections::{HashMap, HashSet, VecDeque}, io, iter, marker::PhantomData, sync::Arc, time::Duration};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// Time after which an outgoing request fails with [`RequestFailure::Timeout`] if it hasn't been
+/// answered.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(20);
+/// Time given to the local handler of a protocol to answer an incoming request.
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// Configuration of a request-response protocol.
+#[derive(Debug, Clone)]
+pub struct ProtocolConfig {
+	/// Name of the protocol as negotiated on the substreams, e.g. `/polkadot/collation/1`.
+	pub name: String,
+	/// Maximum size of a request in bytes, both sent and received.
+	pub max_request_size: usize,
+	/// Maximum size of a response in bytes, both sent and received.
+	pub max_response_size: usize,
+	/// Channel on which incoming requests are pushed. Requests are dropped if it is full.
+	pub inbound_queue: mpsc::Sender<IncomingRequest>,
+}
+
+/// Request received from a remote.
+#[derive(Debug)]
+pub struct IncomingRequest {
+	/// Peer that sent the request.
+	pub peer: PeerId,
+	/// Content of the request.
+	pub payload: Vec<u8>,
+	/// Sender for the response. Dropping it refuses the request, as does answering with a response
+	/// larger than the maximum response size of the protocol.
+	pub pending_response: oneshot::Sender<Vec<u8>>,
+}
+
+/// Reason why sending a request failed.
+#[derive(Debug, Clone, PartialEq, Eq, derive_more::Display)]
+pub enum RequestFailure {
+	/// We are not connected to the target, or the connection closed before the response arrived.
+	#[display(fmt = "Not connected to the target")]
+	NotConnected,
+	/// The protocol hasn't been registered locally.
+	#[display(fmt = "Unknown protocol")]
+	UnknownProtocol,
+	/// The request is larger than the maximum request size of the protocol.
+	#[display(fmt = "Request too large")]
+	RequestTooLarge,
+	/// The remote doesn't support the protocol.
+	#[display(fmt = "Protocol not supported by the remote")]
+	UnsupportedByRemote,
+	/// The remote closed the substream without answering.
+	#[display(fmt = "Request refused by the remote")]
+	Refused,
+	/// The response is larger than the maximum response size of the protocol.
+	#[display(fmt = "Response too large")]
+	ResponseTooLarge,
+	/// The remote didn't answer in time.
+	#[display(fmt = "Request timed out")]
+	Timeout,
+}
+
+impl std::error::Error for RequestFailure {}
+
+/// Size limits of a registered protocol, shared with the handlers.
+#[derive(Debug, Clone)]
+struct ProtocolLimits {
+	max_request_size: usize,
+	max_response_size: usize,
+}
+
+/// Implementation of `NetworkBehaviour` handling the request-response protocols.
+pub struct RequestResponsesBehaviour<TSubstream> {
+	/// Registered protocols, by name. Shared with the handlers created since the last change.
+	protocols: Arc<HashMap<String, ProtocolLimits>>,
+	/// Channels of the registered protocols on which incoming requests are pushed.
+	inbound_queues: HashMap<String, mpsc::Sender<IncomingRequest>>,
+	/// Peers we are connected to.
+	connected: HashSet<PeerId>,
+	/// Requests waiting to be handed to the handler of their target.
+	pending_requests: VecDeque<(PeerId, HandlerIn)>,
+	/// Marker to pin the generics.
+	marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> RequestResponsesBehaviour<TSubstream> {
+	/// Creates a new behaviour without any protocol.
+	pub fn new() -> Self {
+		RequestResponsesBehaviour {
+			protocols: Arc::new(HashMap::new()),
+			inbound_queues: HashMap::new(),
+			connected: HashSet::new(),
+			pending_requests: VecDeque::new(),
+			marker: PhantomData,
+		}
+	}
+
+	/// Registers a new protocol, replacing any previous protocol with the same name.
+	pub fn register_protocol(&mut self, config: ProtocolConfig) {
+		Arc::make_mut(&mut self.protocols).insert(config.name.clone(), ProtocolLimits {
+			max_request_size: config.max_request_size,
+			max_response_size: config.max_response_size,
+		});
+		self.inbound_queues.insert(config.name, config.inbound_queue);
+	}
+
+	/// Sends a request to the given peer on the given protocol. The response, or the reason of the
+	/// failure, is sent on `pending_response`.
+	pub fn send_request(
+		&mut self,
+		target: &PeerId,
+		protocol: &str,
+		request: Vec<u8>,
+		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+	) {
+		let limits = match self.protocols.get(protocol) {
+			Some(limits) => limits,
+			None => {
+				let _ = pending_response.send(Err(RequestFailure::UnknownProtocol));
+				return;
+			}
+		};
+
+		if request.len() > limits.max_request_size {
+			let _ = pending_response.send(Err(RequestFailure::RequestTooLarge));
+			return;
+		}
+
+		if !self.connected.contains(target) {
+			let _ = pending_response.send(Err(RequestFailure::NotConnected));
+			return;
+		}
+
+		self.pending_requests.push_back((target.clone(), HandlerIn {
+			upgrade: OutboundRequest {
+				protocol: protocol.to_string(),
+				request,
+				max_response_size: limits.max_response_size,
+			},
+			pending_response,
+		}));
+	}
+}
+
+impl<TSubstream> NetworkBehaviour for RequestResponsesBehaviour<TSubstream>
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type ProtocolsHandler = RequestResponsesHandler<TSubstream>;
+	type OutEvent = void::Void;
+
+	fn new_handler(&mut self) -> Self::ProtocolsHandler {
+		RequestResponsesHandler::new(self.protocols.clone())
+	}
+
+	fn addresses_of_peer(&mut self, _: &PeerId) -> Vec<Multiaddr> {
+		Vec::new()
+	}
+
+	fn inject_connected(&mut self, peer_id: PeerId, _: ConnectedPoint) {
+		self.connected.insert(peer_id);
+	}
+
+	fn inject_disconnected(&mut self, peer_id: &PeerId, _: ConnectedPoint) {
+		self.connected.remove(peer_id);
+	}
+
+	fn inject_node_event(&mut self, peer_id: PeerId, event: HandlerOut) {
+		let HandlerOut::InboundRequest { protocol, payload, pending_response } = event;
+
+		if let Some(inbound_queue) = self.inbound_queues.get_mut(&protocol) {
+			let request = IncomingRequest { peer: peer_id.clone(), payload, pending_response };
+			if inbound_queue.try_send(request).is_err() {
+				debug!(target: "sub-libp2p", "Dropping request from {:?} on {}: queue full or closed",
+					peer_id, protocol);
+			}
+		}
+	}
+
+	fn poll(
+		&mut self,
+		_: &mut impl PollParameters,
+	) -> Async<NetworkBehaviourAction<HandlerIn, Self::OutEvent>> {
+		if let Some((peer_id, event)) = self.pending_requests.pop_front() {
+			return Async::Ready(NetworkBehaviourAction::SendEvent { peer_id, event });
+		}
+
+		Async::NotReady
+	}
+}
+
+/// Event sent from the behaviour to the handler: a request to send.
+pub struct HandlerIn {
+	upgrade: OutboundRequest,
+	pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
+}
+
+/// Event sent from the handler to the behaviour.
+pub enum HandlerOut {
+	/// The remote sent a request.
+	InboundRequest {
+		protocol: String,
+		payload: Vec<u8>,
+		pending_response: oneshot::Sender<Vec<u8>>,
+	},
+}
+
+/// Implementation of `ProtocolsHandler` for the request-response protocols.
+///
+/// Contrary to `OneShotHandler`, failed requests are reported to their sender instead of closing
+/// the connection.
+pub struct RequestResponsesHandler<TSubstream> {
+	/// Protocols registered when the connection was established.
+	protocols: Arc<HashMap<String, ProtocolLimits>>,
+	/// Requests to open a substream for.
+	pending_requests: VecDeque<HandlerIn>,
+	/// Number of outbound substreams being opened or waiting for their response.
+	requests_in_flight: usize,
+	/// Events to report to the behaviour.
+	events: VecDeque<HandlerOut>,
+	/// Futures waiting for the local answer to an incoming request, then sending it.
+	pending_responses: Vec<Box<dyn Future<Item = (), Error = io::Error> + Send>>,
+	/// Marker to pin the generics.
+	marker: PhantomData<TSubstream>,
+}
+
+impl<TSubstream> RequestResponsesHandler<TSubstream> {
+	fn new(protocols: Arc<HashMap<String, ProtocolLimits>>) -> Self {
+		RequestResponsesHandler {
+			protocols,
+			pending_requests: VecDeque::new(),
+			requests_in_flight: 0,
+			events: VecDeque::new(),
+			pending_responses: Vec::new(),
+			marker: PhantomData,
+		}
+	}
+}
+
+impl<TSubstream> ProtocolsHandler for RequestResponsesHandler<TSubstream>
+where
+	TSubstream: AsyncRead + AsyncWrite + Send + 'static,
+{
+	type InEvent = HandlerIn;
+	type OutEvent = HandlerOut;
+	type Error = void::Void;
+	type Substream = TSubstream;
+	type InboundProtocol = InboundRequests;
+	type OutboundProtocol = OutboundRequest;
+	type OutboundOpenInfo = oneshot::Sender<Result<Vec<u8>, RequestFailure>>;
+
+	fn listen_protocol(&self) -> SubstreamProtocol<Self::InboundProtocol> {
+		SubstreamProtocol::new(InboundRequests { protocols: self.protocols.clone() })
+	}
+
+	fn inject_fully_negotiated_inbound(
+		&mut self,
+		(protocol, payload, substream): (String, Vec<u8>, Negotiated<TSubstream>),
+	) {
+		let max_response_size = match self.protocols.get(&protocol) {
+			Some(limits) => limits.max_response_size,
+			None => return,
+		};
+
+		let (tx, rx) = oneshot::channel();
+		let response = Compat::new(rx)
+			.map_err(|_| io::Error::new(io::ErrorKind::Other, "Request refused by the local handler"))
+			.and_then(move |response: Vec<u8>| if response.len() <= max_response_size {
+				Ok(response)
+			} else {
+				Err(io::Error::new(io::ErrorKind::InvalidData, "Response too large"))
+			})
+			.and_then(move |response| upgrade::write_one(substream, response));
+		let timeout = Delay::new(RESPONSE_TIMEOUT).compat()
+			.then(|_| Err::<(), _>(io::Error::new(io::ErrorKind::TimedOut, "Request not answered in time")));
+
+		self.pending_responses.push(Box::new(
+			response.select(timeout).map(|_| ()).map_err(|(err, _)| err)
+		));
+		self.events.push_back(HandlerOut::InboundRequest { protocol, payload, pending_response: tx });
+	}
+
+	fn inject_fully_negotiated_outbound(
+		&mut self,
+		response: Vec<u8>,
+		pending_response: Self::OutboundOpenInfo,
+	) {
+		self.requests_in_flight -= 1;
+		let _ = pending_response.send(Ok(response));
+	}
+
+	fn inject_event(&mut self, request: HandlerIn) {
+		self.pending_requests.push_back(request);
+	}
+
+	fn inject_dial_upgrade_error(
+		&mut self,
+		pending_response: Self::OutboundOpenInfo,
+		error: ProtocolsHandlerUpgrErr<ReadOneError>,
+	) {
+		self.requests_in_flight -= 1;
+		let failure = match error {
+			ProtocolsHandlerUpgrErr::Timeout | ProtocolsHandlerUpgrErr::Timer => RequestFailure::Timeout,
+			ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Select(_)) => RequestFailure::UnsupportedByRemote,
+			ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(ReadOneError::TooLarge { .. })) =>
+				RequestFailure::ResponseTooLarge,
+			ProtocolsHandlerUpgrErr::Upgrade(UpgradeError::Apply(ReadOneError::Io(_))) =>
+				RequestFailure::Refused,
+		};
+		let _ = pending_response.send(Err(failure));
+	}
+
+	fn connection_keep_alive(&self) -> KeepAlive {
+		// Only keep the connection alive while requests are being processed; the other protocols
+		// decide whether idle connections are kept.
+		if self.requests_in_flight > 0 || !self.pending_requests.is_empty() ||
+			!self.pending_responses.is_empty()
+		{
+			KeepAlive::Yes
+		} else {
+			KeepAlive::No
+		}
+	}
+
+	fn poll(
+		&mut self,
+	) -> Poll<ProtocolsHandlerEvent<OutboundRequest, Self::OutboundOpenInfo, HandlerOut>, void::Void> {
+		for n in (0..self.pending_responses.len()).rev() {
+			let mut response = self.pending_responses.swap_remove(n);
+			match response.poll() {
+				Ok(Async::NotReady) => self.pending_responses.push(response),
+				Ok(Async::Ready(())) => {},
+				Err(err) => debug!(target: "sub-libp2p", "Failed to answer request: {}", err),
+			}
+		}
+
+		if let Some(event) = self.events.pop_front() {
+			return Ok(Async::Ready(ProtocolsHandlerEvent::Custom(event)));
+		}
+
+		if let Some(HandlerIn { upgrade, pending_response }) = self.pending_requests.pop_front() {
+			self.requests_in_flight += 1;
+			return Ok(Async::Ready(ProtocolsHandlerEvent::OutboundSubstreamRequest {
+				protocol: SubstreamProtocol::new(upgrade).with_timeout(REQUEST_TIMEOUT),
+				info: pending_response,
+			}));
+		}
+
+		Ok(Async::NotReady)
+	}
+}
+
+/// Upgrade accepting the incoming substreams of all the registered protocols and reading the
+/// request.
+#[derive(Clone)]
+pub struct InboundRequests {
+	protocols: Arc<HashMap<String, ProtocolLimits>>,
+}
+
+impl UpgradeInfo for InboundRequests {
+	type Info = String;
+	type InfoIter = Vec<String>;
+
+	fn protocol_info(&self) -> Self::InfoIter {
+		self.protocols.keys().cloned().collect()
+	}
+}
+
+impl<TSubstream> InboundUpgrade<TSubstream> for InboundRequests
+where
+	TSubstream: AsyncRead + AsyncWrite,
+{
+	type Output = (String, Vec<u8>, Negotiated<TSubstream>);
+	type Error = ReadOneError;
+	type Future = upgrade::ReadRespond<
+		Negotiated<TSubstream>,
+		String,
+		fn(Negotiated<TSubstream>, Vec<u8>, String) -> Result<Self::Output, ReadOneError>,
+	>;
+
+	fn upgrade_inbound(self, substream: Negotiated<TSubstream>, protocol: String) -> Self::Future {
+		let max_request_size = self.protocols.get(&protocol).map_or(0, |limits| limits.max_request_size);
+		upgrade::read_respond(substream, max_request_size, protocol, |substream, request, protocol| {
+			Ok((protocol, request, substream))
+		})
+	}
+}
+
+/// Upgrade sending a request and reading the response.
+pub struct OutboundRequest {
+	protocol: String,
+	request: Vec<u8>,
+	max_response_size: usize,
+}
+
+impl UpgradeInfo for OutboundRequest {
+	type Info = String;
+	type InfoIter = iter::Once<String>;
+
+	fn protocol_info(&self) -> Self::InfoIter {
+		iter::once(self.protocol.clone())
+	}
+}
+
+impl<TSubstream> OutboundUpgrade<TSubstream> for OutboundRequest
+where
+	TSubstream: AsyncRead + AsyncWrite,
+{
+	type Output = Vec<u8>;
+	type Error = ReadOneError;
+	type Future = upgrade::RequestResponse<
+		Negotiated<TSubstream>,
+		(),
+		fn(Vec<u8>, ()) -> Result<Vec<u8>, ReadOneError>,
+		Vec<u8>,
+	>;
+
+	fn upgrade_outbound(self, substream: Negotiated<TSubstream>, _: String) -> Self::Future {
+		upgrade::request_response(substream, self.request, self.max_response_size, (), |response, ()| {
+			Ok(response)
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::future;
+	use futures03::{StreamExt as _, TryStreamExt as _};
+	use libp2p::core::{nodes::Substream, muxing::StreamMuxerBox, transport::{boxed::Boxed, MemoryTransport}};
+	use libp2p::swarm::Swarm;
+	use libp2p::{identity::Keypair, Transport};
+
+	type TestSwarm = Swarm<
+		Boxed<(PeerId, StreamMuxerBox), io::Error>,
+		RequestResponsesBehaviour<Substream<StreamMuxerBox>>,
+	>;
+
+	fn build_swarm() -> (TestSwarm, Multiaddr) {
+		let keypair = Keypair::generate_ed25519();
+		let local_peer_id = keypair.public().into_peer_id();
+
+		let transport = MemoryTransport
+			.and_then(move |out, endpoint| {
+				let secio = libp2p::secio::SecioConfig::new(keypair);
+				upgrade::apply(out, secio, endpoint, upgrade::Version::V1)
+			})
+			.and_then(move |(peer_id, stream), endpoint| {
+				upgrade::apply(stream, libp2p::yamux::Config::default(), endpoint, upgrade::Version::V1)
+					.map(|muxer| (peer_id, StreamMuxerBox::new(muxer)))
+			})
+			.timeout(Duration::from_secs(20))
+			.map_err(|err| io::Error::new(io::ErrorKind::Other, err))
+			.boxed();
+
+		let mut swarm = Swarm::new(transport, RequestResponsesBehaviour::new(), local_peer_id);
+		let addr: Multiaddr = format!("/memory/{}", rand::random::<u64>()).parse().unwrap();
+		Swarm::listen_on(&mut swarm, addr.clone()).unwrap();
+		(swarm, addr)
+	}
+
+	fn protocol_config(inbound_queue: mpsc::Sender<IncomingRequest>) -> ProtocolConfig {
+		ProtocolConfig {
+			name: "/test/req-resp/1".to_string(),
+			max_request_size: 1024,
+			max_response_size: 1024,
+			inbound_queue,
+		}
+	}
+
+	#[test]
+	fn request_is_answered() {
+		let (mut requester, _) = build_swarm();
+		let (mut responder, responder_addr) = build_swarm();
+		let responder_id = Swarm::local_peer_id(&responder).clone();
+
+		let (tx, _rx) = mpsc::channel(1);
+		requester.register_protocol(protocol_config(tx));
+		let (tx, rx) = mpsc::channel(1);
+		responder.register_protocol(protocol_config(tx));
+
+		Swarm::dial_addr(&mut requester, responder_addr).unwrap();
+
+		let mut incoming_requests = rx.map(Ok::<_, ()>).compat();
+		let (response_tx, response_rx) = oneshot::channel();
+		let mut response_tx = Some(response_tx);
+		let mut response_rx = Compat::new(response_rx);
+
+		let response = future::poll_fn(move || -> Poll<_, ()> {
+			for swarm in &mut [&mut requester, &mut responder] {
+				while let Async::Ready(Some(event)) = Stream::poll(&mut **swarm).unwrap() {
+					void::unreachable(event);
+				}
+			}
+
+			if requester.connected.contains(&responder_id) {
+				if let Some(response_tx) = response_tx.take() {
+					requester.send_request(&responder_id, "/test/req-resp/1", b"ping".to_vec(), response_tx);
+					futures::task::current().notify();
+				}
+			}
+
+			while let Async::Ready(Some(request)) = incoming_requests.poll().unwrap() {
+				assert_eq!(request.payload, b"ping".to_vec());
+				let _ = request.pending_response.send(b"pong".to_vec());
+			}
+
+			response_rx.poll().map_err(|_| ())
+		});
+
+		let mut runtime = tokio::runtime::Runtime::new().unwrap();
+		assert_eq!(runtime.block_on(response).unwrap(), Ok(b"pong".to_vec()));
+	}
+
+	#[test]
+	fn invalid_requests_fail_immediately() {
+		let mut behaviour = RequestResponsesBehaviour::<Substream<StreamMuxerBox>>::new();
+		let peer_id = PeerId::random();
+		let (tx, _rx) = mpsc::channel(1);
+		behaviour.register_protocol(protocol_config(tx));
+
+		let mut send = |protocol: &str, request: Vec<u8>| {
+			let (tx, rx) = oneshot::channel();
+			behaviour.send_request(&peer_id, protocol, request, tx);
+			futures03::executor::block_on(rx).unwrap()
+		};
+
+		assert_eq!(send("/test/unknown/1", Vec::new()), Err(RequestFailure::UnknownProtocol));
+		assert_eq!(send("/test/req-resp/1", vec![0; 1025]), Err(RequestFailure::RequestTooLarge));
+		assert_eq!(send("/test/req-resp/1", Vec::new()), Err(RequestFailure::NotConnected));
+		assert!(behaviour.pending_requests.is_empty());
+	}
+}
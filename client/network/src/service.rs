@@ -25,30 +25,50 @@
 //! The methods of the [`NetworkService`] are implemented by sending a message over a channel,
 //! which is then processed by [`NetworkWorker::poll`].
 
-use std::{collections::{HashMap, HashSet}, fs, marker::PhantomData, io, path::Path};
+use std::{collections::{HashMap, HashSet}, fs, iter, marker::PhantomData, io, path::Path};
+use std::net::Ipv6Addr;
 use std::sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}};
+use std::time::{Duration, Instant};
 
+use codec::Encode;
+use sp_consensus::BlockOrigin;
 use sp_consensus::import_queue::{ImportQueue, Link};
 use sp_consensus::import_queue::{BlockImportResult, BlockImportError};
 use futures::{prelude::*, sync::mpsc};
-use futures03::TryFutureExt as _;
-use log::{warn, error, info};
-use libp2p::{PeerId, Multiaddr, kad::record};
+use futures03::{channel::oneshot, compat::Compat, FutureExt as _, StreamExt as _, TryFutureExt as _, TryStreamExt as _};
+use log::{debug, warn, error, info};
+use libp2p::{PeerId, Multiaddr, kad::record, multiaddr};
+use libp2p::identity::{Keypair, PublicKey, error::SigningError};
 use libp2p::core::{transport::boxed::Boxed, muxing::StreamMuxerBox};
 use libp2p::swarm::NetworkBehaviour;
 use parking_lot::Mutex;
-use sc_peerset::PeersetHandle;
+use sc_peerset::{PeerReputation, PeersetHandle};
 use sp_runtime::{traits::{Block as BlockT, NumberFor}, ConsensusEngineId};
 
 use crate::{behaviour::{Behaviour, BehaviourOut}, config::{parse_str_addr, parse_addr}};
+use crate::bandwidth::{BandwidthProtocol, ProtocolBandwidth, ProtocolBandwidthSinks};
 use crate::{NetworkState, NetworkStateNotConnectedPeer, NetworkStatePeer};
 use crate::{transport, config::NonReservedPeerMode, ReputationChange};
-use crate::config::{Params, TransportConfig};
+use crate::config::{Params, SyncMode, TransportConfig, AUTHORITIES_PRIORITY_GROUP};
 use crate::error::Error;
-use crate::protocol::{self, Protocol, Context, PeerInfo};
-use crate::protocol::{event::Event, light_dispatch::{AlwaysBadChecker, RequestData}};
-use crate::protocol::specialization::NetworkSpecialization;
-use crate::protocol::sync::SyncState;
+use crate::protocol::{self, Protocol, Context, PeerInfo, SendQueueLens};
+use crate::protocol::{event::{DhtError, DhtEvent, Event}, light_dispatch::{AlwaysBadChecker, RequestData}};
+use crate::protocol::{notifications::NotificationsProtocolConfig, specialization::NetworkSpecialization};
+use crate::request_responses::{self, IncomingRequest, RequestFailure};
+use crate::protocol::sync::{SyncState, SyncStateEvent, warp::{self, WarpSyncRequest}};
+use crate::utils::interval;
+
+/// Time after which DHT requests started through [`NetworkService::get_value_async`] or
+/// [`NetworkService::put_value_async`] fail with [`DhtError::Timeout`] if still unanswered.
+const DHT_REQUEST_TIMEOUT: Duration = Duration::from_secs(2 * 60);
+/// Interval at which pending DHT requests are checked for timeouts.
+const DHT_REQUEST_TIMEOUT_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+/// Number of incoming requests buffered per protocol served by the node. Requests beyond are
+/// dropped.
+const INCOMING_REQUESTS_QUEUE_SIZE: usize = 16;
+
+/// Result of a DHT lookup, all the records found for the key.
+type DhtGetResult = Result<Vec<(record::Key, Vec<u8>)>, DhtError>;
 
 /// Minimum Requirements for a Hash within Networking
 pub trait ExHashT: std::hash::Hash + Eq + std::fmt::Debug + Clone + Send + Sync + 'static {}
@@ -108,8 +128,16 @@ pub struct NetworkService<B: BlockT + 'static, S: NetworkSpecialization<B>, H: E
 	is_major_syncing: Arc<AtomicBool>,
 	/// Local copy of the `PeerId` of the local node.
 	local_peer_id: PeerId,
+	/// The `KeyPair` that defines the `PeerId` of the local node.
+	local_identity: Keypair,
 	/// Bandwidth logging system. Can be queried to know the average bandwidth consumed.
 	bandwidth: Arc<transport::BandwidthSinks>,
+	/// Bytes sent and received per protocol.
+	protocol_bandwidth: ProtocolBandwidthSinks,
+	/// Number of messages waiting to be sent to each peer.
+	send_queue_lens: SendQueueLens,
+	/// Number of notifications to each peer passed to the `NetworkWorker` and not yet processed.
+	pending_notifications: Arc<Mutex<HashMap<PeerId, usize>>>,
 	/// Peerset manager (PSM); manages the reputation of nodes and indicates the network which
 	/// nodes it should be connected to or not.
 	peerset: PeersetHandle,
@@ -182,6 +210,10 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			bootnodes,
 			reserved_only: params.network_config.non_reserved_mode == NonReservedPeerMode::Deny,
 			reserved_nodes,
+			priority_group_slots: params.network_config.reserved_authority_slots
+				.map(|slots| (AUTHORITIES_PRIORITY_GROUP.to_string(), slots))
+				.into_iter()
+				.collect(),
 		};
 
 		// Private and public keys configuration.
@@ -190,12 +222,21 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 		let local_peer_id = local_public.clone().into_peer_id();
 		info!(target: "sub-libp2p", "Local node identity is: {}", local_peer_id.to_base58());
 
+		if params.network_config.sync_mode == SyncMode::Warp && params.warp_sync_provider.is_none() {
+			warn!(target: "sync", "Warp sync isn't supported by this node, syncing all blocks instead");
+		}
+
 		let num_connected = Arc::new(AtomicUsize::new(0));
 		let is_major_syncing = Arc::new(AtomicBool::new(false));
+		let serves_finality_proofs = params.finality_proof_provider.is_some();
+		let protocol_bandwidth = ProtocolBandwidthSinks::new(&params.network_config.protocol_rate_limits);
+		let chain = params.chain.clone();
+		let finality_proof_provider = params.finality_proof_provider.clone();
 		let (protocol, peerset_handle) = Protocol::new(
 			protocol::ProtocolConfig {
 				roles: params.roles,
 				max_parallel_downloads: params.network_config.max_parallel_downloads,
+				sync_mode: params.network_config.sync_mode,
 			},
 			params.chain,
 			params.on_demand.as_ref().map(|od| od.checker().clone())
@@ -204,11 +245,16 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			params.transaction_pool,
 			params.finality_proof_provider,
 			params.finality_proof_request_builder,
-			params.protocol_id,
+			params.warp_sync_provider.clone(),
+			params.fork_ranking,
+			params.protocol_id.clone(),
 			peerset_config,
-			params.block_announce_validator
+			params.block_announce_validator,
+			protocol_bandwidth.clone(),
 		)?;
 
+		let send_queue_lens = protocol.send_queue_lens();
+
 		// Build the swarm.
 		let (mut swarm, bandwidth) = {
 			let user_agent = format!(
@@ -229,6 +275,9 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 					TransportConfig::MemoryOnly => false,
 					TransportConfig::Normal { allow_private_ipv4, .. } => allow_private_ipv4,
 				},
+				params.network_config.record_ttl,
+				params.network_config.record_publication_interval,
+				protocol_bandwidth.clone(),
 			);
 			let (transport, bandwidth) = {
 				let (config_mem, config_wasm) = match params.network_config.transport {
@@ -236,15 +285,102 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 					TransportConfig::Normal { wasm_external_transport, .. } =>
 						(false, wasm_external_transport)
 				};
-				transport::build_transport(local_identity, config_mem, config_wasm)
+				transport::build_transport(
+					local_identity.clone(),
+					config_mem,
+					config_wasm,
+					params.network_config.outbound_proxy.clone(),
+				)
 			};
 			(Swarm::<B, S, H>::new(transport, behaviour, local_peer_id.clone()), bandwidth)
 		};
 
-		// Listen on multiaddresses.
-		for addr in &params.network_config.listen_addresses {
-			if let Err(err) = Swarm::<B, S, H>::listen_on(&mut swarm, addr.clone()) {
-				warn!(target: "sub-libp2p", "Can't listen on {} because: {:?}", addr, err)
+		// Full nodes serve the state of their blocks, and warp sync and finality proofs if they
+		// can build them.
+		let warp_proof_protocol = warp::warp_proof_protocol_name(&params.protocol_id);
+		let state_protocol = warp::state_protocol_name(&params.protocol_id);
+		let finality_proof_protocol = protocol::finality_proof_protocol_name(&params.protocol_id);
+		let mut served_protocols = Vec::new();
+		let mut register_served_protocol = |swarm: &mut Swarm<B, S, H>, name: &String| {
+			let (tx, rx) = futures03::channel::mpsc::channel(INCOMING_REQUESTS_QUEUE_SIZE);
+			swarm.register_request_response_protocol(request_responses::ProtocolConfig {
+				name: name.clone(),
+				max_request_size: warp::MAX_REQUEST_SIZE,
+				max_response_size: warp::MAX_RESPONSE_SIZE,
+				inbound_queue: tx,
+			});
+			served_protocols.push((name.clone(), rx));
+		};
+		if params.roles.is_full() {
+			register_served_protocol(&mut swarm, &state_protocol);
+			if params.warp_sync_provider.is_some() {
+				register_served_protocol(&mut swarm, &warp_proof_protocol);
+			}
+			if serves_finality_proofs {
+				register_served_protocol(&mut swarm, &finality_proof_protocol);
+			}
+		}
+		if !served_protocols.is_empty() {
+			let handler = protocol::RequestHandler::new(
+				chain,
+				finality_proof_provider,
+				params.warp_sync_provider.clone(),
+				peerset_handle.clone(),
+			);
+			let incoming_requests = futures03::stream::select_all(
+				served_protocols.into_iter().map(|(name, rx)| rx.map(move |request| (name.clone(), request)))
+			);
+			let warp_proof_protocol = warp_proof_protocol.clone();
+			let finality_proof_protocol = finality_proof_protocol.clone();
+			// Answered one at a time, the requests beyond `INCOMING_REQUESTS_QUEUE_SIZE` being
+			// dropped meanwhile. The thread stops once the swarm is dropped.
+			std::thread::Builder::new()
+				.name("network-requests".into())
+				.spawn(move || futures03::executor::block_on(incoming_requests.for_each(|(protocol, request)| {
+					let IncomingRequest { peer, payload, pending_response } = request;
+					let response = if protocol == warp_proof_protocol {
+						handler.on_warp_proof_request(&peer, &payload)
+					} else if protocol == finality_proof_protocol {
+						handler.on_finality_proof_request(&peer, &payload)
+					} else {
+						handler.on_state_request(&peer, &payload)
+					};
+					if let Some(response) = response {
+						let _ = pending_response.send(response);
+					}
+					futures03::future::ready(())
+				})))?;
+		}
+
+		// Listen on multiaddresses, on both address families when listening on all interfaces.
+		//
+		// The TCP transport doesn't let us set `IPV6_V6ONLY`, thus on dual-stack systems the IPv6
+		// socket accepts IPv4 connections as well and the IPv4 address can't be bound next to it.
+		// The IPv6 counterpart is bound first, leaving the IPv4 address to systems where it isn't
+		// covered already, or where IPv6 isn't available.
+		let mut covered_by_ipv6 = HashSet::new();
+		for (addr, counterpart_of) in dual_stack_listen_addresses(&params.network_config.listen_addresses) {
+			match Swarm::<B, S, H>::listen_on(&mut swarm, addr.clone()) {
+				Ok(_) => if let Some(ip4_addr) = counterpart_of {
+					covered_by_ipv6.insert(ip4_addr);
+				},
+				Err(err) => if counterpart_of.is_some() {
+					info!(
+						target: "sub-libp2p",
+						"Can't additionally listen on {} because: {:?}. Pass an IPv6 listen address \
+						explicitly to accept IPv6 connections.",
+						addr, err,
+					)
+				} else if covered_by_ipv6.contains(&addr) {
+					debug!(
+						target: "sub-libp2p",
+						"Not listening on {} separately, it's covered by its dual-stack IPv6 \
+						counterpart: {:?}",
+						addr, err,
+					)
+				} else {
+					warn!(target: "sub-libp2p", "Can't listen on {} because: {:?}", addr, err)
+				},
 			}
 		}
 
@@ -257,11 +393,15 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 
 		let service = Arc::new(NetworkService {
 			bandwidth,
+			protocol_bandwidth,
+			send_queue_lens,
+			pending_notifications: Default::default(),
 			external_addresses: external_addresses.clone(),
 			num_connected: num_connected.clone(),
 			is_major_syncing: is_major_syncing.clone(),
 			peerset: peerset_handle,
 			local_peer_id,
+			local_identity,
 			to_worker: to_worker.clone(),
 			_marker: PhantomData,
 		});
@@ -276,6 +416,15 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 			from_worker,
 			light_client_rqs: params.on_demand.and_then(|od| od.extract_receiver()),
 			event_streams: Vec::new(),
+			sync_state_streams: Vec::new(),
+			last_sync_state: SyncStateEvent::Starting,
+			pending_dht_requests: PendingDhtRequests::default(),
+			dht_request_timeout_check: Box::new(
+				interval(DHT_REQUEST_TIMEOUT_CHECK_INTERVAL).map(|()| Ok(())).compat()
+			),
+			warp_proof_protocol,
+			state_protocol,
+			pending_warp_sync_response: None,
 		})
 	}
 
@@ -340,6 +489,21 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 		self.network_service.user_protocol_mut().on_block_finalized(hash, &header);
 	}
 
+	/// Returns the addresses the node is listening on.
+	pub fn listen_addresses(&self) -> impl Iterator<Item = &Multiaddr> {
+		Swarm::<B, S, H>::listeners(&self.network_service)
+	}
+
+	/// Returns the external addresses of the node, as observed by its peers.
+	pub fn external_addresses(&self) -> impl Iterator<Item = &Multiaddr> {
+		Swarm::<B, S, H>::external_addresses(&self.network_service)
+	}
+
+	/// Returns the local `PeerId`.
+	pub fn local_peer_id(&self) -> &PeerId {
+		Swarm::<B, S, H>::local_peer_id(&self.network_service)
+	}
+
 	/// Get network state.
 	///
 	/// **Note**: Use this only for debugging. This API is unstable. There are warnings literaly
@@ -419,6 +583,16 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkWorker
 	pub fn add_reserved_peer(&self, peer: String) -> Result<(), String> {
 		self.service.add_reserved_peer(peer)
 	}
+
+	/// Switch between only connecting to reserved peers and also accepting unreserved peers.
+	pub fn set_reserved_only(&self, reserved_only: bool) {
+		self.service.set_reserved_only(reserved_only)
+	}
+
+	/// Returns the reputation of all nodes known to the peerset manager.
+	pub fn peer_reputations(&mut self) -> Vec<PeerReputation> {
+		self.network_service.user_protocol_mut().peer_reputations()
+	}
 }
 
 impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkService<B, S, H> {
@@ -434,11 +608,38 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 	/// The protocol must have been registered with `register_notifications_protocol`.
 	///
 	pub fn write_notification(&self, target: PeerId, engine_id: ConsensusEngineId, message: Vec<u8>) {
-		let _ = self.to_worker.unbounded_send(ServerToWorkerMsg::WriteNotification {
-			target,
+		let mut pending_notifications = self.pending_notifications.lock();
+		let sent = self.to_worker.unbounded_send(ServerToWorkerMsg::WriteNotification {
+			target: target.clone(),
 			engine_id,
 			message,
 		});
+		if sent.is_ok() {
+			*pending_notifications.entry(target).or_default() += 1;
+		}
+	}
+
+	/// Returns the number of messages, including notifications, that have been handed to the
+	/// connection with the given peer and are waiting to be sent.
+	///
+	/// A value that keeps growing indicates that the remote can't keep up with what we send.
+	/// Includes the notifications passed to `write_notification` which haven't been processed by
+	/// the `NetworkWorker` yet.
+	pub fn num_queued_messages(&self, target: &PeerId) -> usize {
+		let pending = self.pending_notifications.lock().get(target).cloned().unwrap_or(0);
+		let queued = self.send_queue_lens.lock().get(target).map_or(0, |len| len.load(Ordering::Relaxed));
+		pending + queued
+	}
+
+	/// Notes that the `NetworkWorker` processed a notification passed to `write_notification`.
+	fn notification_processed(&self, target: &PeerId) {
+		let mut pending_notifications = self.pending_notifications.lock();
+		if let Some(pending) = pending_notifications.get_mut(target) {
+			*pending -= 1;
+			if *pending == 0 {
+				pending_notifications.remove(target);
+			}
+		}
 	}
 
 	/// Returns a stream containing the events that happen on the network.
@@ -453,6 +654,18 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 		rx
 	}
 
+	/// Returns a stream of the progress of the sync, starting with the current state.
+	///
+	/// An event is produced each time the state changes, which includes every block imported
+	/// while downloading. If this method is called multiple times, the events are duplicated.
+	///
+	/// The stream never ends (unless the `NetworkWorker` gets shut down).
+	pub fn sync_state_stream(&self) -> impl Stream<Item = SyncStateEvent<NumberFor<B>>, Error = ()> {
+		let (tx, rx) = mpsc::unbounded();
+		let _ = self.to_worker.unbounded_send(ServerToWorkerMsg::SyncStateStream(tx));
+		rx
+	}
+
 	/// Registers a new notifications protocol.
 	///
 	/// After that, you can call `write_notifications`.
@@ -466,9 +679,69 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 		&self,
 		engine_id: ConsensusEngineId,
 	) {
-		let _ = self.to_worker.unbounded_send(ServerToWorkerMsg::RegisterNotifProtocol {
-			engine_id,
+		self.register_notifications_protocol_with_config(NotificationsProtocolConfig::new(engine_id))
+	}
+
+	/// Registers a new notifications protocol with a handshake and its own set of peers.
+	///
+	/// The handshake of the configuration is sent to every remote we connect to, and the protocol
+	/// is only open with those whose handshake passes the `handshake_validator`, within the limit
+	/// of `max_peers`. The remotes whose handshake is rejected are asked to close the protocol.
+	/// The handshake of the remote is then part of the `Event::NotificationStreamOpened`.
+	///
+	/// The same remarks as for `register_notifications_protocol` apply. On connections open
+	/// before the registration, our handshake is sent right away and the protocol is opened with
+	/// the remotes that have sent theirs.
+	pub fn register_notifications_protocol_with_config(
+		&self,
+		config: NotificationsProtocolConfig,
+	) {
+		let _ = self.to_worker.unbounded_send(ServerToWorkerMsg::RegisterNotifProtocol(config));
+	}
+
+	/// Registers a request-response protocol, allowing to send requests to a specific peer with
+	/// [`NetworkService::request`] and to answer the requests of others.
+	///
+	/// Requests received on the protocol are pushed on `handler_channel` and are answered through
+	/// their [`IncomingRequest::pending_response`]. Requests are dropped if the channel is full.
+	/// `max_request_size` and `max_response_size` are in bytes and apply in both directions.
+	///
+	/// As with notifications protocols, connections that are already open don't accept requests
+	/// on protocols registered after they were established. Call this method early on.
+	pub fn register_request_response_protocol(
+		&self,
+		name: impl Into<String>,
+		max_request_size: usize,
+		max_response_size: usize,
+		handler_channel: futures03::channel::mpsc::Sender<IncomingRequest>,
+	) {
+		let config = request_responses::ProtocolConfig {
+			name: name.into(),
+			max_request_size,
+			max_response_size,
+			inbound_queue: handler_channel,
+		};
+		let _ = self.to_worker.unbounded_send(ServerToWorkerMsg::RegisterRequestResponseProtocol(config));
+	}
+
+	/// Sends a request to a peer we are connected to on a protocol registered with
+	/// [`NetworkService::register_request_response_protocol`], and returns a future resolving to
+	/// its response.
+	pub fn request(
+		&self,
+		target: PeerId,
+		protocol: impl Into<String>,
+		request: Vec<u8>,
+	) -> impl futures03::Future<Output = Result<Vec<u8>, RequestFailure>> {
+		let (tx, rx) = oneshot::channel();
+		let _ = self.to_worker.unbounded_send(ServerToWorkerMsg::Request {
+			target,
+			protocol: protocol.into(),
+			request,
+			pending_response: tx,
 		});
+
+		rx.map(|result| result.unwrap_or(Err(RequestFailure::NotConnected)))
 	}
 
 	/// You must call this when new transactons are imported by the transaction pool.
@@ -483,6 +756,9 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 	///
 	/// In chain-based consensus, we often need to make sure non-best forks are
 	/// at least temporarily synced. This function forces such an announcement.
+	///
+	/// `data` is attached to the announcement as is and passed to the `BlockAnnounceValidator`
+	/// of the receiving nodes.
 	pub fn announce_block(&self, hash: B::Hash, data: Vec<u8>) {
 		let _ = self.to_worker.unbounded_send(ServerToWorkerMsg::AnnounceBlock(hash, data));
 	}
@@ -524,6 +800,18 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 		self.is_major_syncing.load(Ordering::Relaxed)
 	}
 
+	/// Signs the message with the `KeyPair` that defines the local `PeerId`.
+	///
+	/// Returns the public key of the `KeyPair` next to the signature, allowing others to both
+	/// verify the signature and derive the `PeerId` of the signer.
+	pub fn sign_with_local_identity(
+		&self,
+		msg: &[u8],
+	) -> Result<(PublicKey, Vec<u8>), SigningError> {
+		let signature = self.local_identity.sign(msg)?;
+		Ok((self.local_identity.public(), signature))
+	}
+
 	/// Start getting a value from the DHT.
 	///
 	/// This will generate either a `ValueFound` or a `ValueNotFound` event and pass it as an
@@ -544,6 +832,82 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 			.unbounded_send(ServerToWorkerMsg::PutValue(key, value));
 	}
 
+	/// Start announcing the local node as a provider of the given key, e.g. the hash of a state
+	/// range or a proof that the node is able to serve. The announcement is republished
+	/// periodically until [`NetworkService::stop_providing`] is called.
+	///
+	/// This will generate either a `StartedProviding` or a `StartProvidingFailed` event and pass
+	/// it as an item on the [`NetworkWorker`] stream.
+	pub fn start_providing(&self, key: record::Key) {
+		let _ = self
+			.to_worker
+			.unbounded_send(ServerToWorkerMsg::StartProviding(key));
+	}
+
+	/// Stop announcing the local node as a provider of the given key. Other nodes keep the
+	/// provider records they have already received until these expire.
+	pub fn stop_providing(&self, key: record::Key) {
+		let _ = self
+			.to_worker
+			.unbounded_send(ServerToWorkerMsg::StopProviding(key));
+	}
+
+	/// Start looking for the nodes providing the given key in the DHT.
+	///
+	/// This will generate either a `ProvidersFound` or a `ProvidersNotFound` event and pass it as
+	/// an item on the [`NetworkWorker`] stream.
+	pub fn get_providers(&self, key: record::Key) {
+		let _ = self
+			.to_worker
+			.unbounded_send(ServerToWorkerMsg::GetProviders(key));
+	}
+
+	/// Get a value from the DHT, returning a future resolving to the records found.
+	///
+	/// Next to resolving the future, the usual `ValueFound` or `ValueNotFound` event is passed
+	/// as an item on the [`NetworkWorker`] stream. Concurrent requests for the same key are
+	/// answered by the same lookup result.
+	pub fn get_value_async(
+		&self,
+		key: &record::Key,
+	) -> impl futures03::Future<Output = DhtGetResult> {
+		let (tx, rx) = oneshot::channel();
+		let _ = self
+			.to_worker
+			.unbounded_send(ServerToWorkerMsg::GetValueAsync(key.clone(), tx));
+
+		rx.map(|result| result.unwrap_or(Err(DhtError::WorkerShutdown)))
+	}
+
+	/// Put a value in the DHT, returning a future resolving once the record was stored.
+	///
+	/// Next to resolving the future, the usual `ValuePut` or `ValuePutFailed` event is passed
+	/// as an item on the [`NetworkWorker`] stream.
+	pub fn put_value_async(
+		&self,
+		key: record::Key,
+		value: Vec<u8>,
+	) -> impl futures03::Future<Output = Result<(), DhtError>> {
+		let (tx, rx) = oneshot::channel();
+		let _ = self
+			.to_worker
+			.unbounded_send(ServerToWorkerMsg::PutValueAsync(key, value, tx));
+
+		rx.map(|result| result.unwrap_or(Err(DhtError::WorkerShutdown)))
+	}
+
+	/// Returns the reputation of all nodes known to the peerset manager, including whether they
+	/// are banned and the reason of the most recent reputation change.
+	///
+	/// The future resolves to an error if the [`NetworkWorker`] is gone.
+	pub fn peer_reputations(
+		&self,
+	) -> impl futures03::Future<Output = Result<Vec<PeerReputation>, oneshot::Canceled>> {
+		let (tx, rx) = oneshot::channel();
+		let _ = self.to_worker.unbounded_send(ServerToWorkerMsg::PeerReputations(tx));
+		rx
+	}
+
 	/// Connect to unreserved peers and allow unreserved peers to connect.
 	pub fn accept_unreserved_peers(&self) {
 		self.peerset.set_reserved_only(false);
@@ -554,6 +918,14 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 		self.peerset.set_reserved_only(true);
 	}
 
+	/// Switch between only connecting to reserved peers and also accepting unreserved peers,
+	/// equivalent to toggling `--reserved-only` without a restart.
+	///
+	/// When switching to reserved-only mode, all unreserved peers are disconnected.
+	pub fn set_reserved_only(&self, reserved_only: bool) {
+		self.peerset.set_reserved_only(reserved_only);
+	}
+
 	/// Removes a `PeerId` from the list of reserved peers.
 	pub fn remove_reserved_peer(&self, peer: PeerId) {
 		self.peerset.remove_reserved_peer(peer);
@@ -600,10 +972,54 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> NetworkServic
 		Ok(())
 	}
 
+	/// Add peers to a peerset priority group, leaving the other members of the group untouched.
+	///
+	/// Each address must end with a `/p2p/` component specifying the `PeerId` of the peer.
+	pub fn add_to_priority_group(&self, group_id: String, peers: HashSet<Multiaddr>) -> Result<(), String> {
+		let peers = peers.into_iter().map(|p| {
+			parse_addr(p).map_err(|e| format!("{:?}", e))
+		}).collect::<Result<Vec<(PeerId, Multiaddr)>, String>>()?;
+
+		for (peer_id, addr) in peers.into_iter() {
+			self.peerset.add_to_priority_group(group_id.clone(), peer_id.clone());
+
+			let _ = self
+				.to_worker
+				.unbounded_send(ServerToWorkerMsg::AddKnownAddress(peer_id, addr));
+		}
+
+		Ok(())
+	}
+
+	/// Remove peers from a peerset priority group, leaving the other members of the group
+	/// untouched.
+	///
+	/// Each address must end with a `/p2p/` component specifying the `PeerId` of the peer. A peer
+	/// is removed from the group as a whole, even if the group was given multiple addresses of it.
+	pub fn remove_from_priority_group(&self, group_id: String, peers: HashSet<Multiaddr>) -> Result<(), String> {
+		let peer_ids = peers.into_iter().map(|p| {
+			parse_addr(p).map(|(peer_id, _addr)| peer_id).map_err(|e| format!("{:?}", e))
+		}).collect::<Result<HashSet<PeerId>, String>>()?;
+
+		for peer_id in peer_ids.into_iter() {
+			self.peerset.remove_from_priority_group(group_id.clone(), peer_id);
+		}
+
+		Ok(())
+	}
+
 	/// Returns the number of peers we're connected to.
 	pub fn num_connected(&self) -> usize {
 		self.num_connected.load(Ordering::Relaxed)
 	}
+
+	/// Returns the number of bytes sent and received so far on each protocol, e.g. block
+	/// announces, transactions, each consensus engine and the DHT.
+	///
+	/// Sizes are those of the messages exchanged by the protocols and exclude transport overhead.
+	pub fn bandwidth_by_protocol(&self) -> HashMap<BandwidthProtocol, ProtocolBandwidth> {
+		self.protocol_bandwidth.by_protocol()
+	}
 }
 
 impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> sp_consensus::SyncOracle
@@ -666,16 +1082,28 @@ enum ServerToWorkerMsg<B: BlockT, S: NetworkSpecialization<B>> {
 	ExecuteWithSpec(Box<dyn FnOnce(&mut S, &mut dyn Context<B>) + Send>),
 	GetValue(record::Key),
 	PutValue(record::Key, Vec<u8>),
+	GetValueAsync(record::Key, oneshot::Sender<DhtGetResult>),
+	PutValueAsync(record::Key, Vec<u8>, oneshot::Sender<Result<(), DhtError>>),
+	StartProviding(record::Key),
+	StopProviding(record::Key),
+	GetProviders(record::Key),
+	PeerReputations(oneshot::Sender<Vec<PeerReputation>>),
 	AddKnownAddress(PeerId, Multiaddr),
 	SyncFork(Vec<PeerId>, B::Hash, NumberFor<B>),
 	EventStream(mpsc::UnboundedSender<Event>),
+	SyncStateStream(mpsc::UnboundedSender<SyncStateEvent<NumberFor<B>>>),
 	WriteNotification {
 		message: Vec<u8>,
 		engine_id: ConsensusEngineId,
 		target: PeerId,
 	},
-	RegisterNotifProtocol {
-		engine_id: ConsensusEngineId,
+	RegisterNotifProtocol(NotificationsProtocolConfig),
+	RegisterRequestResponseProtocol(request_responses::ProtocolConfig),
+	Request {
+		target: PeerId,
+		protocol: String,
+		request: Vec<u8>,
+		pending_response: oneshot::Sender<Result<Vec<u8>, RequestFailure>>,
 	},
 	DisconnectPeer(PeerId),
 }
@@ -703,6 +1131,20 @@ pub struct NetworkWorker<B: BlockT + 'static, S: NetworkSpecialization<B>, H: Ex
 	light_client_rqs: Option<mpsc::UnboundedReceiver<RequestData<B>>>,
 	/// Senders for events that happen on the network.
 	event_streams: Vec<mpsc::UnboundedSender<Event>>,
+	/// Senders for the progress of the sync.
+	sync_state_streams: Vec<mpsc::UnboundedSender<SyncStateEvent<NumberFor<B>>>>,
+	/// Last progress of the sync sent to `sync_state_streams`.
+	last_sync_state: SyncStateEvent<NumberFor<B>>,
+	/// DHT requests started through the `NetworkService` awaiting an answer.
+	pending_dht_requests: PendingDhtRequests,
+	/// Interval at which we fail timed out `pending_dht_requests`.
+	dht_request_timeout_check: Box<dyn Stream<Item = (), Error = ()> + Send>,
+	/// Name of the protocol serving warp sync proofs.
+	warp_proof_protocol: String,
+	/// Name of the protocol serving the state of blocks.
+	state_protocol: String,
+	/// Peer and response of our pending warp sync request, if any.
+	pending_warp_sync_response: Option<(PeerId, Compat<oneshot::Receiver<Result<Vec<u8>, RequestFailure>>>)>,
 }
 
 impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Future for NetworkWorker<B, S, H> {
@@ -749,20 +1191,49 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Future for Ne
 					self.network_service.get_value(&key),
 				ServerToWorkerMsg::PutValue(key, value) =>
 					self.network_service.put_value(key, value),
+				ServerToWorkerMsg::GetValueAsync(key, sender) => {
+					self.network_service.get_value(&key);
+					self.pending_dht_requests.insert_get(key, sender);
+				},
+				ServerToWorkerMsg::PutValueAsync(key, value, sender) => {
+					self.network_service.put_value(key.clone(), value);
+					self.pending_dht_requests.insert_put(key, sender);
+				},
+				ServerToWorkerMsg::StartProviding(key) =>
+					self.network_service.start_providing(key),
+				ServerToWorkerMsg::StopProviding(key) =>
+					self.network_service.stop_providing(&key),
+				ServerToWorkerMsg::GetProviders(key) =>
+					self.network_service.get_providers(key),
+				ServerToWorkerMsg::PeerReputations(sender) => {
+					let _ = sender.send(self.network_service.user_protocol_mut().peer_reputations());
+				},
 				ServerToWorkerMsg::AddKnownAddress(peer_id, addr) =>
 					self.network_service.add_known_address(peer_id, addr),
 				ServerToWorkerMsg::SyncFork(peer_ids, hash, number) =>
 					self.network_service.user_protocol_mut().set_sync_fork_request(peer_ids, &hash, number),
 				ServerToWorkerMsg::EventStream(sender) =>
 					self.event_streams.push(sender),
-				ServerToWorkerMsg::WriteNotification { message, engine_id, target } =>
-					self.network_service.user_protocol_mut().write_notification(target, engine_id, message),
-				ServerToWorkerMsg::RegisterNotifProtocol { engine_id } => {
-					let events = self.network_service.user_protocol_mut().register_notifications_protocol(engine_id);
+				ServerToWorkerMsg::SyncStateStream(sender) => {
+					if sender.unbounded_send(self.last_sync_state.clone()).is_ok() {
+						self.sync_state_streams.push(sender);
+					}
+				},
+				ServerToWorkerMsg::WriteNotification { message, engine_id, target } => {
+					self.network_service.user_protocol_mut()
+						.write_notification(target.clone(), engine_id, message);
+					self.service.notification_processed(&target);
+				},
+				ServerToWorkerMsg::RegisterNotifProtocol(config) => {
+					let events = self.network_service.user_protocol_mut().register_notifications_protocol(config);
 					for event in events {
 						self.event_streams.retain(|sender| sender.unbounded_send(event.clone()).is_ok());
 					}
 				},
+				ServerToWorkerMsg::RegisterRequestResponseProtocol(config) =>
+					self.network_service.register_request_response_protocol(config),
+				ServerToWorkerMsg::Request { target, protocol, request, pending_response } =>
+					self.network_service.send_request(&target, &protocol, request, pending_response),
 				ServerToWorkerMsg::DisconnectPeer(who) =>
 					self.network_service.user_protocol_mut().disconnect_peer(&who),
 			}
@@ -781,6 +1252,9 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Future for Ne
 				Ok(Async::Ready(Some(BehaviourOut::FinalityProofImport(origin, hash, nb, proof)))) =>
 					self.import_queue.import_finality_proof(origin, hash, nb, proof),
 				Ok(Async::Ready(Some(BehaviourOut::Event(ev)))) => {
+					if let Event::Dht(dht_event) = &ev {
+						self.pending_dht_requests.on_dht_event(dht_event);
+					}
 					self.event_streams.retain(|sender| sender.unbounded_send(ev.clone()).is_ok());
 				},
 				Ok(Async::Ready(None)) => {},
@@ -791,6 +1265,40 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Future for Ne
 			};
 		}
 
+		while let Ok(Async::Ready(Some(()))) = self.dht_request_timeout_check.poll() {
+			self.pending_dht_requests.expire(Instant::now());
+		}
+
+		// Drive our own warp sync, one request at a time.
+		loop {
+			if let Some((peer, mut response)) = self.pending_warp_sync_response.take() {
+				let response = match response.poll() {
+					Ok(Async::Ready(response)) => response,
+					Ok(Async::NotReady) => {
+						self.pending_warp_sync_response = Some((peer, response));
+						break
+					},
+					Err(_) => Err(RequestFailure::NotConnected),
+				};
+				let block = self.network_service.user_protocol_mut().on_warp_sync_response(peer, response);
+				if let Some(block) = block {
+					self.import_queue.import_blocks(BlockOrigin::NetworkInitialSync, vec![block]);
+				}
+			}
+
+			let (peer, request) = match self.network_service.user_protocol_mut().warp_sync_request() {
+				Some(request) => request,
+				None => break,
+			};
+			let (protocol, request) = match request {
+				WarpSyncRequest::WarpProof(request) => (&self.warp_proof_protocol, request.encode()),
+				WarpSyncRequest::State(request) => (&self.state_protocol, request.encode()),
+			};
+			let (tx, rx) = oneshot::channel();
+			self.network_service.send_request(&peer, protocol, request, tx);
+			self.pending_warp_sync_response = Some((peer, Compat::new(rx)));
+		}
+
 		// Update the variables shared with the `NetworkService`.
 		self.num_connected.store(self.network_service.user_protocol_mut().num_connected_peers(), Ordering::Relaxed);
 		{
@@ -802,10 +1310,90 @@ impl<B: BlockT + 'static, S: NetworkSpecialization<B>, H: ExHashT> Future for Ne
 			SyncState::Downloading => true,
 		}, Ordering::Relaxed);
 
+		let sync_state = self.network_service.user_protocol().sync_state_event();
+		if sync_state != self.last_sync_state {
+			self.sync_state_streams.retain(|sender| sender.unbounded_send(sync_state.clone()).is_ok());
+			self.last_sync_state = sync_state;
+		}
+
 		Ok(Async::NotReady)
 	}
 }
 
+/// DHT requests started through the `NetworkService`, answered as the corresponding DHT events
+/// come in.
+#[derive(Default)]
+struct PendingDhtRequests {
+	gets: HashMap<record::Key, Vec<(Instant, oneshot::Sender<DhtGetResult>)>>,
+	puts: HashMap<record::Key, Vec<(Instant, oneshot::Sender<Result<(), DhtError>>)>>,
+}
+
+impl PendingDhtRequests {
+	fn insert_get(&mut self, key: record::Key, sender: oneshot::Sender<DhtGetResult>) {
+		self.gets.entry(key).or_default().push((Instant::now(), sender));
+	}
+
+	fn insert_put(&mut self, key: record::Key, sender: oneshot::Sender<Result<(), DhtError>>) {
+		self.puts.entry(key).or_default().push((Instant::now(), sender));
+	}
+
+	/// Answer all requests for the key of the given event.
+	fn on_dht_event(&mut self, event: &DhtEvent) {
+		// The requester might have dropped the receiving side in the meantime.
+		match event {
+			DhtEvent::ValueFound(values) => {
+				// All the records found by a single lookup share the key of the lookup.
+				if let Some((key, _)) = values.first() {
+					for (_, sender) in self.gets.remove(key).unwrap_or_default() {
+						let _ = sender.send(Ok(values.clone()));
+					}
+				}
+			},
+			DhtEvent::ValueNotFound(key) => {
+				for (_, sender) in self.gets.remove(key).unwrap_or_default() {
+					let _ = sender.send(Err(DhtError::NotFound));
+				}
+			},
+			DhtEvent::ValuePut(key) => {
+				for (_, sender) in self.puts.remove(key).unwrap_or_default() {
+					let _ = sender.send(Ok(()));
+				}
+			},
+			DhtEvent::ValuePutFailed(key) => {
+				for (_, sender) in self.puts.remove(key).unwrap_or_default() {
+					let _ = sender.send(Err(DhtError::PutFailed));
+				}
+			},
+			DhtEvent::ProvidersFound(..) | DhtEvent::ProvidersNotFound(_) |
+			DhtEvent::StartedProviding(_) | DhtEvent::StartProvidingFailed(_) => {},
+		}
+	}
+
+	/// Fail all requests started more than [`DHT_REQUEST_TIMEOUT`] before `now` with
+	/// [`DhtError::Timeout`].
+	fn expire(&mut self, now: Instant) {
+		fn expire_requests<T>(
+			requests: &mut HashMap<record::Key, Vec<(Instant, oneshot::Sender<Result<T, DhtError>>)>>,
+			now: Instant,
+		) {
+			for senders in requests.values_mut() {
+				let (expired, pending): (Vec<_>, Vec<_>) = senders.drain(..)
+					.partition(|(started, _)| now.duration_since(*started) >= DHT_REQUEST_TIMEOUT);
+				*senders = pending;
+
+				for (_, sender) in expired {
+					let _ = sender.send(Err(DhtError::Timeout));
+				}
+			}
+
+			requests.retain(|_, senders| !senders.is_empty());
+		}
+
+		expire_requests(&mut self.gets, now);
+		expire_requests(&mut self.puts, now);
+	}
+}
+
 /// The libp2p swarm, customized for our needs.
 type Swarm<B, S, H> = libp2p::swarm::Swarm<
 	Boxed<(PeerId, StreamMuxerBox), io::Error>,
@@ -855,3 +1443,101 @@ impl<'a, B: BlockT, S: NetworkSpecialization<B>, H: ExHashT> Link<B> for Network
 		}
 	}
 }
+
+/// Returns the given listen addresses, complemented with the IPv6 counterpart (`::`) of every
+/// address listening on all IPv4 interfaces (`0.0.0.0`), unless listed already. Each counterpart
+/// directly precedes its IPv4 address.
+///
+/// The second element of each tuple is the IPv4 address an added counterpart belongs to.
+fn dual_stack_listen_addresses(addresses: &[Multiaddr]) -> Vec<(Multiaddr, Option<Multiaddr>)> {
+	let mut all = Vec::with_capacity(addresses.len());
+
+	for addr in addresses {
+		let mut protocols = addr.iter();
+		match protocols.next() {
+			Some(multiaddr::Protocol::Ip4(ip)) if ip.is_unspecified() => {
+				let ip6_addr = iter::once(multiaddr::Protocol::Ip6(Ipv6Addr::UNSPECIFIED))
+					.chain(protocols)
+					.collect::<Multiaddr>();
+				if !addresses.contains(&ip6_addr) && !all.iter().any(|(a, _)| *a == ip6_addr) {
+					all.push((ip6_addr, Some(addr.clone())));
+				}
+			},
+			_ => {},
+		}
+		all.push((addr.clone(), None));
+	}
+
+	all
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures03::executor::block_on;
+
+	#[test]
+	fn pending_dht_requests_are_answered_by_events() {
+		let mut pending = PendingDhtRequests::default();
+		let key = record::Key::new(&b"key".to_vec());
+
+		let (get_tx_1, get_rx_1) = oneshot::channel();
+		let (get_tx_2, get_rx_2) = oneshot::channel();
+		let (put_tx, put_rx) = oneshot::channel();
+		pending.insert_get(key.clone(), get_tx_1);
+		pending.insert_get(key.clone(), get_tx_2);
+		pending.insert_put(key.clone(), put_tx);
+
+		let values = vec![(key.clone(), b"value".to_vec())];
+		pending.on_dht_event(&DhtEvent::ValueFound(values.clone()));
+		assert_eq!(block_on(get_rx_1).unwrap(), Ok(values.clone()));
+		assert_eq!(block_on(get_rx_2).unwrap(), Ok(values));
+
+		pending.on_dht_event(&DhtEvent::ValuePutFailed(key));
+		assert_eq!(block_on(put_rx).unwrap(), Err(DhtError::PutFailed));
+
+		assert!(pending.gets.is_empty());
+		assert!(pending.puts.is_empty());
+	}
+
+	#[test]
+	fn pending_dht_requests_time_out() {
+		let mut pending = PendingDhtRequests::default();
+		let key = record::Key::new(&b"key".to_vec());
+
+		let (tx, mut rx) = oneshot::channel();
+		pending.insert_get(key, tx);
+
+		pending.expire(Instant::now());
+		assert_eq!(rx.try_recv(), Ok(None));
+
+		pending.expire(Instant::now() + DHT_REQUEST_TIMEOUT);
+		assert_eq!(rx.try_recv(), Ok(Some(Err(DhtError::Timeout))));
+		assert!(pending.gets.is_empty());
+	}
+
+	#[test]
+	fn dual_stack_listen_addresses_adds_ipv6_counterpart() {
+		let any_ip4: Multiaddr = "/ip4/0.0.0.0/tcp/30333".parse().unwrap();
+		let any_ip6: Multiaddr = "/ip6/::/tcp/30333".parse().unwrap();
+		let specific_ip4: Multiaddr = "/ip4/192.0.2.1/tcp/30334".parse().unwrap();
+		let any_ip4_ws: Multiaddr = "/ip4/0.0.0.0/tcp/30335/ws".parse().unwrap();
+
+		assert_eq!(
+			dual_stack_listen_addresses(&[any_ip4.clone(), specific_ip4.clone(), any_ip4_ws.clone()]),
+			vec![
+				(any_ip6.clone(), Some(any_ip4.clone())),
+				(any_ip4.clone(), None),
+				(specific_ip4, None),
+				("/ip6/::/tcp/30335/ws".parse().unwrap(), Some(any_ip4_ws.clone())),
+				(any_ip4_ws, None),
+			],
+		);
+
+		// An explicitly configured IPv6 address isn't duplicated.
+		assert_eq!(
+			dual_stack_listen_addresses(&[any_ip4.clone(), any_ip6.clone()]),
+			vec![(any_ip4, None), (any_ip6, None)],
+		);
+	}
+}
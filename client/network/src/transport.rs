@@ -16,7 +16,7 @@
 
 use futures::prelude::*;
 use libp2p::{
-	InboundUpgradeExt, OutboundUpgradeExt, PeerId, Transport,
+	InboundUpgradeExt, OutboundUpgradeExt, Multiaddr, PeerId, Transport,
 	mplex, identity, secio, yamux, bandwidth, wasm_ext
 };
 #[cfg(not(target_os = "unknown"))]
@@ -28,17 +28,24 @@ use std::{io, sync::Arc, time::Duration, usize};
 
 pub use self::bandwidth::BandwidthSinks;
 
+#[cfg(not(target_os = "unknown"))]
+mod socks5;
+
 /// Builds the transport that serves as a common ground for all connections.
 ///
 /// If `memory_only` is true, then only communication within the same process are allowed. Only
 /// addresses with the format `/memory/...` are allowed.
 ///
+/// If `outbound_proxy` is `Some`, all outgoing TCP connections are established through the SOCKS5
+/// proxy listening on that address. Domain names are then resolved by the proxy.
+///
 /// Returns a `BandwidthSinks` object that allows querying the average bandwidth produced by all
 /// the connections spawned with this transport.
 pub fn build_transport(
 	keypair: identity::Keypair,
 	memory_only: bool,
-	wasm_external_transport: Option<wasm_ext::ExtTransport>
+	wasm_external_transport: Option<wasm_ext::ExtTransport>,
+	outbound_proxy: Option<Multiaddr>,
 ) -> (Boxed<(PeerId, StreamMuxerBox), io::Error>, Arc<bandwidth::BandwidthSinks>) {
 	// Build configuration objects for encryption mechanisms.
 	#[cfg(not(target_os = "unknown"))]
@@ -67,7 +74,7 @@ pub fn build_transport(
 		OptionalTransport::none()
 	};
 	#[cfg(not(target_os = "unknown"))]
-	let transport = transport.or_transport(if !memory_only {
+	let transport = transport.or_transport(if !memory_only && outbound_proxy.is_none() {
 		let desktop_trans = tcp::TcpConfig::new();
 		let desktop_trans = websocket::WsConfig::new(desktop_trans.clone())
 			.or_transport(desktop_trans);
@@ -75,6 +82,18 @@ pub fn build_transport(
 	} else {
 		OptionalTransport::none()
 	});
+	#[cfg(not(target_os = "unknown"))]
+	let transport = transport.or_transport(match outbound_proxy {
+		Some(proxy) if !memory_only => {
+			let desktop_trans = socks5::Socks5Config::new(tcp::TcpConfig::new(), proxy);
+			let desktop_trans = websocket::WsConfig::new(desktop_trans.clone())
+				.or_transport(desktop_trans);
+			OptionalTransport::some(desktop_trans)
+		},
+		_ => OptionalTransport::none(),
+	});
+	#[cfg(target_os = "unknown")]
+	let _ = outbound_proxy;
 
 	let transport = transport.or_transport(if memory_only {
 		OptionalTransport::some(libp2p::core::transport::MemoryTransport::default())
@@ -0,0 +1,261 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! TCP transport dialing through a SOCKS5 proxy, as specified in
+//! [RFC 1928](https://tools.ietf.org/html/rfc1928).
+//!
+//! Only the `CONNECT` command without authentication is supported. Domain names of `/dns4` and
+//! `/dns6` addresses are resolved by the proxy, so that no DNS requests leave the node. Listening
+//! is delegated to the inner TCP transport.
+
+use futures::{future, prelude::*};
+use libp2p::core::{Transport, transport::TransportError};
+use libp2p::multiaddr::{Multiaddr, Protocol};
+use libp2p::tcp::{TcpConfig, TcpTransStream};
+use std::io;
+use tokio_io::io::{read_exact, write_all};
+
+const SOCKS_VERSION: u8 = 5;
+const NO_AUTHENTICATION: u8 = 0;
+const CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN_NAME: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+const SUCCEEDED: u8 = 0;
+
+/// TCP transport establishing all outgoing connections through a SOCKS5 proxy.
+#[derive(Debug, Clone)]
+pub struct Socks5Config {
+	inner: TcpConfig,
+	proxy: Multiaddr,
+}
+
+impl Socks5Config {
+	/// Dial through the proxy listening on the given `/ip4/.../tcp/...` or `/ip6/.../tcp/...`
+	/// address, using `inner` to connect to the proxy and to listen.
+	pub fn new(inner: TcpConfig, proxy: Multiaddr) -> Self {
+		Socks5Config { inner, proxy }
+	}
+}
+
+impl Transport for Socks5Config {
+	type Output = TcpTransStream;
+	type Error = io::Error;
+	type Listener = <TcpConfig as Transport>::Listener;
+	type ListenerUpgrade = <TcpConfig as Transport>::ListenerUpgrade;
+	type Dial = Box<dyn Future<Item = TcpTransStream, Error = io::Error> + Send>;
+
+	fn listen_on(self, addr: Multiaddr) -> Result<Self::Listener, TransportError<Self::Error>> {
+		self.inner.listen_on(addr)
+	}
+
+	fn dial(self, addr: Multiaddr) -> Result<Self::Dial, TransportError<Self::Error>> {
+		let request = match connect_request(&addr) {
+			Some(request) => request,
+			None => return Err(TransportError::MultiaddrNotSupported(addr)),
+		};
+
+		let proxy = self.proxy.clone();
+		let connection = self.inner.dial(self.proxy).map_err(|err| match err {
+			TransportError::MultiaddrNotSupported(_) => TransportError::Other(io::Error::new(
+				io::ErrorKind::InvalidInput,
+				format!("Unsupported SOCKS5 proxy address: {}", proxy),
+			)),
+			TransportError::Other(err) => TransportError::Other(err),
+		})?;
+
+		let dial = connection
+			.and_then(|stream| write_all(stream, [SOCKS_VERSION, 1, NO_AUTHENTICATION]))
+			.and_then(|(stream, _)| read_exact(stream, [0u8; 2]))
+			.and_then(|(stream, reply)| if reply == [SOCKS_VERSION, NO_AUTHENTICATION] {
+				Ok(stream)
+			} else {
+				Err(io::Error::new(io::ErrorKind::Other, "SOCKS5 proxy requires authentication"))
+			})
+			.and_then(move |stream| write_all(stream, request))
+			.and_then(|(stream, _)| read_exact(stream, [0u8; 4]))
+			.and_then(|(stream, reply)| -> Box<dyn Future<Item = _, Error = _> + Send> {
+				if reply[0] != SOCKS_VERSION {
+					return Box::new(future::err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						"Invalid SOCKS5 proxy reply",
+					)));
+				}
+				if reply[1] != SUCCEEDED {
+					return Box::new(future::err(reply_error(reply[1])));
+				}
+
+				// Length of the address the proxy bound to, including the port.
+				match reply[3] {
+					ATYP_IPV4 => Box::new(future::ok((stream, 4 + 2))),
+					ATYP_IPV6 => Box::new(future::ok((stream, 16 + 2))),
+					ATYP_DOMAIN_NAME => Box::new(
+						read_exact(stream, [0u8; 1]).map(|(stream, len)| (stream, len[0] as usize + 2))
+					),
+					_ => Box::new(future::err(io::Error::new(
+						io::ErrorKind::InvalidData,
+						"Invalid address type in SOCKS5 proxy reply",
+					))),
+				}
+			})
+			.and_then(|(stream, len)| read_exact(stream, vec![0u8; len]))
+			.map(|(stream, _)| stream);
+
+		Ok(Box::new(dial))
+	}
+}
+
+/// Builds the `CONNECT` request for the given address, or returns `None` if the address isn't a
+/// plain TCP address.
+fn connect_request(addr: &Multiaddr) -> Option<Vec<u8>> {
+	let mut protocols = addr.iter();
+	let mut request = vec![SOCKS_VERSION, CONNECT, 0];
+
+	match protocols.next()? {
+		Protocol::Ip4(ip) => {
+			request.push(ATYP_IPV4);
+			request.extend_from_slice(&ip.octets());
+		},
+		Protocol::Ip6(ip) => {
+			request.push(ATYP_IPV6);
+			request.extend_from_slice(&ip.octets());
+		},
+		Protocol::Dns4(name) | Protocol::Dns6(name) => {
+			if name.len() > u8::max_value() as usize {
+				return None;
+			}
+			request.push(ATYP_DOMAIN_NAME);
+			request.push(name.len() as u8);
+			request.extend_from_slice(name.as_bytes());
+		},
+		_ => return None,
+	}
+
+	match protocols.next()? {
+		Protocol::Tcp(port) => request.extend_from_slice(&port.to_be_bytes()),
+		_ => return None,
+	}
+
+	if protocols.next().is_some() {
+		return None;
+	}
+
+	Some(request)
+}
+
+fn reply_error(code: u8) -> io::Error {
+	let (kind, reason) = match code {
+		2 => (io::ErrorKind::PermissionDenied, "connection not allowed by ruleset"),
+		3 => (io::ErrorKind::Other, "network unreachable"),
+		4 => (io::ErrorKind::Other, "host unreachable"),
+		5 => (io::ErrorKind::ConnectionRefused, "connection refused"),
+		6 => (io::ErrorKind::TimedOut, "TTL expired"),
+		7 => (io::ErrorKind::Other, "command not supported"),
+		8 => (io::ErrorKind::Other, "address type not supported"),
+		_ => (io::ErrorKind::Other, "general failure"),
+	};
+
+	io::Error::new(kind, format!("SOCKS5 proxy failed to connect: {}", reason))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::{io::{Read, Write}, net::TcpListener, thread};
+
+	#[test]
+	fn connect_request_encoding() {
+		assert_eq!(
+			connect_request(&"/ip4/192.0.2.1/tcp/30333".parse().unwrap()),
+			Some(vec![5, 1, 0, 1, 192, 0, 2, 1, 0x76, 0x7d]),
+		);
+		assert_eq!(
+			connect_request(&"/ip6/::1/tcp/30333".parse().unwrap()),
+			Some(vec![5, 1, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0x76, 0x7d]),
+		);
+		assert_eq!(
+			connect_request(&"/dns4/example.com/tcp/30333".parse().unwrap()),
+			Some([&[5, 1, 0, 3, 11][..], b"example.com", &[0x76, 0x7d]].concat()),
+		);
+		assert_eq!(connect_request(&"/ip4/192.0.2.1/udp/30333".parse().unwrap()), None);
+		assert_eq!(connect_request(&"/ip4/192.0.2.1/tcp/30333/ws".parse().unwrap()), None);
+	}
+
+	#[test]
+	fn dials_through_proxy() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let proxy_port = listener.local_addr().unwrap().port();
+
+		let proxy = thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+
+			let mut greeting = [0u8; 3];
+			stream.read_exact(&mut greeting).unwrap();
+			assert_eq!(greeting, [5, 1, 0]);
+			stream.write_all(&[5, 0]).unwrap();
+
+			let mut request = [0u8; 10];
+			stream.read_exact(&mut request).unwrap();
+			assert_eq!(request, [5, 1, 0, 1, 192, 0, 2, 1, 0x76, 0x7d]);
+			stream.write_all(&[5, 0, 0, 1, 127, 0, 0, 1, 0, 0]).unwrap();
+
+			stream.write_all(b"hello").unwrap();
+		});
+
+		let transport = Socks5Config::new(
+			TcpConfig::new(),
+			format!("/ip4/127.0.0.1/tcp/{}", proxy_port).parse().unwrap(),
+		);
+		let dial = transport.dial("/ip4/192.0.2.1/tcp/30333".parse().unwrap()).unwrap()
+			.and_then(|stream| read_exact(stream, [0u8; 5]));
+
+		let mut runtime = tokio::runtime::Runtime::new().unwrap();
+		let (_, received) = runtime.block_on(dial).unwrap();
+		assert_eq!(&received, b"hello");
+
+		proxy.join().unwrap();
+	}
+
+	#[test]
+	fn reports_proxy_failure() {
+		let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+		let proxy_port = listener.local_addr().unwrap().port();
+
+		let proxy = thread::spawn(move || {
+			let (mut stream, _) = listener.accept().unwrap();
+
+			let mut greeting = [0u8; 3];
+			stream.read_exact(&mut greeting).unwrap();
+			stream.write_all(&[5, 0]).unwrap();
+
+			let mut request = [0u8; 10];
+			stream.read_exact(&mut request).unwrap();
+			stream.write_all(&[5, 5, 0, 1, 0, 0, 0, 0, 0, 0]).unwrap();
+		});
+
+		let transport = Socks5Config::new(
+			TcpConfig::new(),
+			format!("/ip4/127.0.0.1/tcp/{}", proxy_port).parse().unwrap(),
+		);
+		let dial = transport.dial("/ip4/192.0.2.1/tcp/30333".parse().unwrap()).unwrap();
+
+		let mut runtime = tokio::runtime::Runtime::new().unwrap();
+		let err = runtime.block_on(dial).map(|_| ()).unwrap_err();
+		assert_eq!(err.kind(), io::ErrorKind::ConnectionRefused);
+
+		proxy.join().unwrap();
+	}
+}
@@ -42,6 +42,7 @@ fn prepare_good_block() -> (TestClient, Hash, u64, PeerId, IncomingBlock<Block>)
 		origin: Some(peer_id.clone()),
 		allow_missing_state: false,
 		import_existing: false,
+		state: None,
 	})
 }
 
@@ -83,7 +84,7 @@ fn async_import_queue_drops() {
 	// Perform this test multiple times since it exhibits non-deterministic behavior.
 	for _ in 0..100 {
 		let verifier = PassThroughVerifier(true);
-		let queue = BasicQueue::new(verifier, Box::new(substrate_test_runtime_client::new()), None, None);
+		let queue = BasicQueue::new(verifier, Box::new(substrate_test_runtime_client::new()), None, None, None);
 		drop(queue);
 	}
 }
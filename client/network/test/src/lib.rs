@@ -39,7 +39,7 @@ use sc_client_api::{
 use sc_block_builder::BlockBuilder;
 use sc_client::LongestChain;
 use sc_network::config::Roles;
-use sp_consensus::block_validation::DefaultBlockAnnounceValidator;
+use sp_consensus::block_validation::{BlockAnnounceValidator, DefaultBlockAnnounceValidator};
 use sp_consensus::import_queue::BasicQueue;
 use sp_consensus::import_queue::{
 	BoxBlockImport, BoxJustificationImport, Verifier, BoxFinalityProofImport,
@@ -98,6 +98,7 @@ impl<B: BlockT> Verifier<B> for PassThroughVerifier {
 			fork_choice: ForkChoiceStrategy::LongestChain,
 			allow_missing_state: false,
 			import_existing: false,
+			state: None,
 		}, maybe_keys))
 	}
 }
@@ -500,6 +501,11 @@ pub trait TestNetFactory: Sized {
 		(client.as_block_import(), None, None, None, Default::default())
 	}
 
+	/// Get custom validator of the block announcements received by a fresh peer.
+	fn make_block_announce_validator(&self) -> Option<Box<dyn BlockAnnounceValidator<Block> + Send>> {
+		None
+	}
+
 	/// Get finality proof provider (if supported).
 	fn make_finality_proof_provider(&self, _client: PeersClient) -> Option<Arc<dyn FinalityProofProvider<Block>>> {
 		None
@@ -557,6 +563,7 @@ pub trait TestNetFactory: Sized {
 			Box::new(block_import.clone()),
 			justification_import,
 			finality_proof_import,
+			None,
 		));
 
 		let listen_addr = build_multiaddr![Memory(rand::random::<u64>())];
@@ -571,12 +578,15 @@ pub trait TestNetFactory: Sized {
 			chain: client.clone(),
 			finality_proof_provider: self.make_finality_proof_provider(PeersClient::Full(client.clone(), backend.clone())),
 			finality_proof_request_builder,
+			warp_sync_provider: None,
+			fork_ranking: None,
 			on_demand: None,
 			transaction_pool: Arc::new(EmptyTransactionPool),
 			protocol_id: ProtocolId::from(&b"test-protocol-name"[..]),
 			import_queue,
 			specialization: self::SpecializationFactory::create(),
-			block_announce_validator: Box::new(DefaultBlockAnnounceValidator::new(client.clone()))
+			block_announce_validator: self.make_block_announce_validator()
+				.unwrap_or_else(|| Box::new(DefaultBlockAnnounceValidator::new(client.clone()))),
 		}).unwrap();
 
 		self.mut_peers(|peers| {
@@ -631,6 +641,7 @@ pub trait TestNetFactory: Sized {
 			Box::new(block_import.clone()),
 			justification_import,
 			finality_proof_import,
+			None,
 		));
 
 		let listen_addr = build_multiaddr![Memory(rand::random::<u64>())];
@@ -645,12 +656,15 @@ pub trait TestNetFactory: Sized {
 			chain: client.clone(),
 			finality_proof_provider: self.make_finality_proof_provider(PeersClient::Light(client.clone(), backend.clone())),
 			finality_proof_request_builder,
+			warp_sync_provider: None,
+			fork_ranking: None,
 			on_demand: None,
 			transaction_pool: Arc::new(EmptyTransactionPool),
 			protocol_id: ProtocolId::from(&b"test-protocol-name"[..]),
 			import_queue,
 			specialization: self::SpecializationFactory::create(),
-			block_announce_validator: Box::new(DefaultBlockAnnounceValidator::new(client.clone()))
+			block_announce_validator: self.make_block_announce_validator()
+				.unwrap_or_else(|| Box::new(DefaultBlockAnnounceValidator::new(client.clone()))),
 		}).unwrap();
 
 		self.mut_peers(|peers| {
@@ -16,6 +16,7 @@
 
 use sc_network::config::Roles;
 use sp_consensus::BlockOrigin;
+use sp_consensus::block_validation::{BlockAnnounceValidator, Validation};
 use futures03::TryFutureExt as _;
 use std::time::Duration;
 use tokio::runtime::current_thread;
@@ -660,3 +661,79 @@ fn does_not_sync_announced_old_best_block() {
 	})).unwrap();
 	assert!(!net.peer(1).is_major_syncing());
 }
+
+/// Validator accepting only the block announcements carrying `b"valid"` as data.
+struct DataBlockAnnounceValidator;
+
+impl BlockAnnounceValidator<Block> for DataBlockAnnounceValidator {
+	fn validate(
+		&mut self,
+		_header: &<Block as BlockT>::Header,
+		data: &[u8],
+	) -> Result<Validation, Box<dyn std::error::Error + Send>> {
+		if data == b"valid" {
+			Ok(Validation::Success)
+		} else {
+			Ok(Validation::Failure)
+		}
+	}
+}
+
+/// Test network whose peers validate block announcements with `DataBlockAnnounceValidator`.
+struct ValidatingTestNet {
+	peers: Vec<Peer<(), DummySpecialization>>,
+}
+
+impl TestNetFactory for ValidatingTestNet {
+	type Specialization = DummySpecialization;
+	type Verifier = PassThroughVerifier;
+	type PeerData = ();
+
+	fn from_config(_config: &ProtocolConfig) -> Self {
+		ValidatingTestNet { peers: Vec::new() }
+	}
+
+	fn make_verifier(&self, _client: PeersClient, _config: &ProtocolConfig, _peer_data: &())
+		-> Self::Verifier
+	{
+		PassThroughVerifier(false)
+	}
+
+	fn make_block_announce_validator(&self) -> Option<Box<dyn BlockAnnounceValidator<Block> + Send>> {
+		Some(Box::new(DataBlockAnnounceValidator))
+	}
+
+	fn peer(&mut self, i: usize) -> &mut Peer<(), Self::Specialization> {
+		&mut self.peers[i]
+	}
+
+	fn peers(&self) -> &Vec<Peer<(), Self::Specialization>> {
+		&self.peers
+	}
+
+	fn mut_peers<F: FnOnce(&mut Vec<Peer<(), Self::Specialization>>)>(&mut self, closure: F) {
+		closure(&mut self.peers);
+	}
+}
+
+#[test]
+fn rejected_block_announcements_are_ignored() {
+	let _ = ::env_logger::try_init();
+	let mut runtime = current_thread::Runtime::new().unwrap();
+	let mut net = ValidatingTestNet::new(2);
+	net.block_until_sync(&mut runtime); // connect'em
+
+	// Blocks are announced without data, which peer 1 rejects.
+	let hash = net.peer(0).push_blocks(1, false);
+	let mut delay = futures_timer::Delay::new(Duration::from_secs(2)).compat();
+	runtime.block_on(futures::future::poll_fn::<(), (), _>(|| {
+		net.poll();
+		delay.poll().map_err(|_| ())
+	})).unwrap();
+	assert_eq!(net.peer(1).client.info().chain.best_number, 0);
+
+	// Announcing the block along with the expected data makes peer 1 sync it.
+	net.peer(0).announce_block(hash, b"valid".to_vec());
+	net.block_until_sync(&mut runtime);
+	assert_eq!(net.peer(1).client.info().chain.best_number, 1);
+}
@@ -42,6 +42,8 @@ mod http_dummy;
 
 mod timestamp;
 
+pub(crate) use http::SharedClient;
+
 /// Asynchronous offchain API.
 ///
 /// NOTE this is done to prevent recursive calls into the runtime (which are not supported currently).
@@ -253,8 +255,9 @@ impl AsyncApi {
 		db: S,
 		network_state: Arc<dyn NetworkStateInfo + Send + Sync>,
 		is_validator: bool,
+		shared_client: SharedClient,
 	) -> (Api<S>, AsyncApi) {
-		let (http_api, http_worker) = http::http();
+		let (http_api, http_worker) = http::http(shared_client);
 
 		let api = Api {
 			db,
@@ -306,6 +309,7 @@ mod tests {
 			db,
 			mock,
 			false,
+			SharedClient::default(),
 		)
 	}
 
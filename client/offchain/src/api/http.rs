@@ -33,8 +33,18 @@ use log::error;
 use sp_core::offchain::{HttpRequestId, Timestamp, HttpRequestStatus, HttpError};
 use std::{fmt, io::Read as _, mem, pin::Pin, task::Context, task::Poll};
 
-/// Creates a pair of [`HttpApi`] and [`HttpWorker`].
-pub fn http() -> (HttpApi, HttpWorker) {
+/// HTTP client shared by the offchain workers, so that they reuse its connections.
+#[derive(Clone)]
+pub struct SharedClient(hyper::Client<hyper_rustls::HttpsConnector<hyper::client::HttpConnector>, hyper::Body>);
+
+impl Default for SharedClient {
+	fn default() -> Self {
+		SharedClient(hyper::Client::builder().build(hyper_rustls::HttpsConnector::new(1)))
+	}
+}
+
+/// Creates a pair of [`HttpApi`] and [`HttpWorker`], sending the requests through `shared_client`.
+pub fn http(shared_client: SharedClient) -> (HttpApi, HttpWorker) {
 	let (to_worker, from_api) = mpsc::unbounded();
 	let (to_api, from_worker) = mpsc::unbounded();
 
@@ -50,7 +60,7 @@ pub fn http() -> (HttpApi, HttpWorker) {
 	let engine = HttpWorker {
 		to_api,
 		from_api,
-		http_client: hyper::Client::builder().build(hyper_rustls::HttpsConnector::new(1)),
+		http_client: shared_client.0,
 		requests: Vec::new(),
 	};
 
@@ -693,7 +703,7 @@ impl fmt::Debug for HttpWorkerRequest {
 #[cfg(test)]
 mod tests {
 	use crate::api::timestamp;
-	use super::http;
+	use super::{http, SharedClient};
 	use futures::prelude::*;
 	use futures01::Future as _;
 	use sp_core::offchain::{HttpError, HttpRequestId, HttpRequestStatus, Duration};
@@ -702,7 +712,7 @@ mod tests {
 	// server that runs in the background as well.
 	macro_rules! build_api_server {
 		() => {{
-			let (api, worker) = http();
+			let (api, worker) = http(SharedClient::default());
 			// Note: we have to use tokio because hyper still uses old futures.
 			std::thread::spawn(move || {
 				tokio::run(futures::compat::Compat::new(worker.map(|()| Ok::<(), ()>(()))))
@@ -746,6 +756,48 @@ mod tests {
 		assert_eq!(&buf[..n], b"Hello World!");
 	}
 
+	#[test]
+	fn worker_runs_share_connections() {
+		let deadline = timestamp::now().add(Duration::from_millis(10_000));
+
+		let connections = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+		let (addr_tx, addr_rx) = std::sync::mpsc::channel();
+		let server_connections = connections.clone();
+		std::thread::spawn(move || {
+			let server = hyper::Server::bind(&"127.0.0.1:0".parse().unwrap())
+				.serve(move || {
+					server_connections.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+					hyper::service::service_fn_ok(move |_: hyper::Request<hyper::Body>| {
+						hyper::Response::new(hyper::Body::from("Hello World!"))
+					})
+				});
+			let _ = addr_tx.send(server.local_addr());
+			hyper::rt::run(server.map_err(|e| panic!("{:?}", e)));
+		});
+		let addr = addr_rx.recv().unwrap();
+
+		// The connections of the shared client are driven by the runtime of the workers, which
+		// has to outlive both runs.
+		let mut runtime = tokio::runtime::Runtime::new().unwrap();
+		let shared_client = SharedClient::default();
+		for _ in 0..2 {
+			let (mut api, worker) = http(shared_client.clone());
+			runtime.spawn(futures::compat::Compat::new(worker.map(|()| Ok::<(), ()>(()))));
+
+			let id = api.request_start("GET", &format!("http://{}", addr)).unwrap();
+			api.request_write_body(id, &[], Some(deadline)).unwrap();
+			let mut buf = vec![0; 2048];
+			let n = api.response_read_body(id, &mut buf, Some(deadline)).unwrap();
+			assert_eq!(&buf[..n], b"Hello World!");
+			assert_eq!(api.response_read_body(id, &mut buf, Some(deadline)), Ok(0));
+
+			// Give the connection time to go back to the pool.
+			std::thread::sleep(std::time::Duration::from_millis(100));
+		}
+
+		assert_eq!(connections.load(std::sync::atomic::Ordering::SeqCst), 1);
+	}
+
 	#[test]
 	fn request_start_invalid_call() {
 		let (mut api, addr) = build_api_server!();
@@ -41,29 +41,76 @@ use sp_api::ApiExt;
 use futures::future::Future;
 use log::{debug, warn};
 use sc_network::NetworkStateInfo;
+use sp_consensus::BlockOrigin;
 use sp_core::{offchain::{self, OffchainStorage}, ExecutionContext};
-use sp_runtime::{generic::BlockId, traits::{self, ProvideRuntimeApi}};
+use sp_runtime::{generic::BlockId, traits::{self, Header as HeaderT, ProvideRuntimeApi}};
 
 mod api;
 
 pub use sp_offchain::{OffchainWorkerApi, STORAGE_PREFIX};
 
+/// The imported blocks on which the offchain workers are run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OffchainWorkerPolicy {
+	/// Only the blocks authored by the node.
+	OwnBlocks,
+	/// The blocks which become the new best block.
+	NewBestBlocks,
+	/// All the blocks the node is notified of, including the ones of forks which aren't the best
+	/// chain. The blocks imported at initial sync are skipped, since no notification is sent for
+	/// them.
+	AllBlocks,
+}
+
+impl Default for OffchainWorkerPolicy {
+	fn default() -> Self {
+		OffchainWorkerPolicy::AllBlocks
+	}
+}
+
+impl OffchainWorkerPolicy {
+	/// Returns whether the offchain workers are run on a block imported from `origin`.
+	pub fn should_run(&self, origin: BlockOrigin, is_new_best: bool) -> bool {
+		match self {
+			OffchainWorkerPolicy::OwnBlocks => origin == BlockOrigin::Own,
+			OffchainWorkerPolicy::NewBestBlocks => is_new_best,
+			OffchainWorkerPolicy::AllBlocks => true,
+		}
+	}
+}
+
+/// Options of the offchain workers.
+#[derive(Debug, Clone, Default)]
+pub struct OffchainWorkerOptions {
+	/// The imported blocks on which the offchain workers are run.
+	pub policy: OffchainWorkerPolicy,
+}
+
 /// An offchain workers manager.
 pub struct OffchainWorkers<Client, Storage, Block: traits::Block> {
 	client: Arc<Client>,
 	db: Storage,
+	options: OffchainWorkerOptions,
 	_block: PhantomData<Block>,
 	thread_pool: Mutex<ThreadPool>,
+	shared_client: api::SharedClient,
 }
 
 impl<Client, Storage, Block: traits::Block> OffchainWorkers<Client, Storage, Block> {
 	/// Creates new `OffchainWorkers`.
 	pub fn new(client: Arc<Client>, db: Storage) -> Self {
+		Self::new_with_options(client, db, Default::default())
+	}
+
+	/// Creates new `OffchainWorkers`, run on the blocks selected by `options`.
+	pub fn new_with_options(client: Arc<Client>, db: Storage, options: OffchainWorkerOptions) -> Self {
 		Self {
 			client,
 			db,
+			options,
 			_block: PhantomData,
 			thread_pool: Mutex::new(ThreadPool::new(num_cpus::get())),
+			shared_client: api::SharedClient::default(),
 		}
 	}
 }
@@ -88,37 +135,60 @@ impl<Client, Storage, Block> OffchainWorkers<
 	Client::Api: OffchainWorkerApi<Block>,
 	Storage: OffchainStorage + 'static,
 {
-	/// Start the offchain workers after given block.
+	/// Start the offchain workers after given block, if the policy of the workers selects it.
+	///
+	/// Runtimes implementing the version 2 of the offchain worker API are given the header of
+	/// the block, so they know its hash as well as its number.
 	#[must_use]
 	pub fn on_block_imported(
 		&self,
-		number: &<Block::Header as traits::Header>::Number,
+		header: &Block::Header,
+		origin: BlockOrigin,
+		is_new_best: bool,
 		network_state: Arc<dyn NetworkStateInfo + Send + Sync>,
 		is_validator: bool,
 	) -> impl Future<Output = ()> {
+		let at = BlockId::hash(header.hash());
+		if !self.options.policy.should_run(origin, is_new_best) {
+			debug!("Skipping offchain workers at {:?}", at);
+			return futures::future::Either::Right(futures::future::ready(()));
+		}
+
 		let runtime = self.client.runtime_api();
-		let at = BlockId::number(*number);
-		let has_api = runtime.has_api::<dyn OffchainWorkerApi<Block, Error = ()>>(&at);
-		debug!("Checking offchain workers at {:?}: {:?}", at, has_api);
+		let has_api_v1 = runtime.has_api_with::<dyn OffchainWorkerApi<Block, Error = ()>, _>(
+			&at, |v| v == 1
+		);
+		let has_api_v2 = runtime.has_api_with::<dyn OffchainWorkerApi<Block, Error = ()>, _>(
+			&at, |v| v >= 2
+		);
+		let version = match (has_api_v1, has_api_v2) {
+			(_, Ok(true)) => 2,
+			(Ok(true), _) => 1,
+			_ => 0,
+		};
+		debug!("Checking offchain workers at {:?}: version {}", at, version);
 
-		if has_api.unwrap_or(false) {
+		if version > 0 {
 			let (api, runner) = api::AsyncApi::new(
 				self.db.clone(),
 				network_state.clone(),
 				is_validator,
+				self.shared_client.clone(),
 			);
 			debug!("Spawning offchain workers at {:?}", at);
-			let number = *number;
+			let header = header.clone();
 			let client = self.client.clone();
 			self.spawn_worker(move || {
 				let runtime = client.runtime_api();
 				let api = Box::new(api);
 				debug!("Running offchain workers at {:?}", at);
-				let run = runtime.offchain_worker_with_context(
-					&at,
-					ExecutionContext::OffchainCall(Some((api, offchain::Capabilities::all()))),
-					number,
-				);
+				let context = ExecutionContext::OffchainCall(Some((api, offchain::Capabilities::all())));
+				let run = if version == 2 {
+					runtime.offchain_worker_with_context(&at, context, &header)
+				} else {
+					#[allow(deprecated)]
+					runtime.offchain_worker_before_version_2_with_context(&at, context, *header.number())
+				};
 				if let Err(e) =	run {
 					log::error!("Error running offchain workers at {:?}: {:?}", at, e);
 				}
@@ -149,6 +219,7 @@ mod tests {
 	use sc_network::{Multiaddr, PeerId};
 	use substrate_test_runtime_client::runtime::Block;
 	use sc_transaction_pool::{BasicPool, FullChainApi};
+	use sp_runtime::transaction_validity::TransactionSource;
 	use sp_transaction_pool::{TransactionPool, InPoolTransaction};
 
 	struct MockNetworkStateInfo();
@@ -171,7 +242,7 @@ mod tests {
 			at: &BlockId<Block>,
 			extrinsic: <Block as sp_runtime::traits::Block>::Extrinsic,
 		) -> Result<(), ()> {
-			futures::executor::block_on(self.0.submit_one(&at, extrinsic))
+			futures::executor::block_on(self.0.submit_one(&at, TransactionSource::Local, extrinsic))
 				.map(|_| ())
 				.map_err(|_| ())
 		}
@@ -188,12 +259,52 @@ mod tests {
 		let db = sc_client_db::offchain::LocalStorage::new_test();
 		let network_state = Arc::new(MockNetworkStateInfo());
 
+		let header = client.header(&BlockId::number(0)).unwrap().unwrap();
+
 		// when
 		let offchain = OffchainWorkers::new(client, db);
-		futures::executor::block_on(offchain.on_block_imported(&0u64, network_state, false));
+		futures::executor::block_on(
+			offchain.on_block_imported(&header, BlockOrigin::Own, true, network_state, false)
+		);
 
 		// then
 		assert_eq!(pool.0.status().ready, 1);
 		assert_eq!(pool.0.ready().next().unwrap().is_propagateable(), false);
+		assert_eq!(pool.0.ready().next().unwrap().source(), TransactionSource::Local);
+	}
+
+	#[test]
+	fn should_not_run_on_blocks_not_selected_by_the_policy() {
+		// given
+		let _ = env_logger::try_init();
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let pool = Arc::new(TestPool(BasicPool::new(Default::default(), FullChainApi::new(client.clone()))));
+		client.execution_extensions()
+			.register_transaction_pool(Arc::downgrade(&pool.clone()) as _);
+		let db = sc_client_db::offchain::LocalStorage::new_test();
+		let network_state = Arc::new(MockNetworkStateInfo());
+		let header = client.header(&BlockId::number(0)).unwrap().unwrap();
+		let options = OffchainWorkerOptions { policy: OffchainWorkerPolicy::OwnBlocks };
+
+		// when
+		let offchain = OffchainWorkers::new_with_options(client, db, options);
+		futures::executor::block_on(
+			offchain.on_block_imported(&header, BlockOrigin::NetworkBroadcast, true, network_state, false)
+		);
+
+		// then
+		assert_eq!(pool.0.status().ready, 0);
+	}
+
+	#[test]
+	fn policies_select_blocks_by_origin_and_best() {
+		use OffchainWorkerPolicy::*;
+
+		assert!(OwnBlocks.should_run(BlockOrigin::Own, false));
+		assert!(!OwnBlocks.should_run(BlockOrigin::NetworkBroadcast, true));
+		assert!(NewBestBlocks.should_run(BlockOrigin::NetworkBroadcast, true));
+		assert!(!NewBestBlocks.should_run(BlockOrigin::Own, false));
+		assert!(AllBlocks.should_run(BlockOrigin::NetworkInitialSync, false));
+		assert_eq!(OffchainWorkerPolicy::default(), AllBlocks);
 	}
 }
@@ -116,6 +116,21 @@ impl PeersetHandle {
 	}
 }
 
+/// Reputation of a node known to the peer set manager (PSM).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerReputation {
+	/// Identity of the node.
+	pub peer_id: PeerId,
+	/// Current reputation. Decays towards zero over time.
+	pub reputation: i32,
+	/// Whether we are connected to the node.
+	pub connected: bool,
+	/// Whether the reputation is low enough for connections to and from the node to be refused.
+	pub banned: bool,
+	/// Reason given by the most recent report on the node, if any.
+	pub last_report_reason: Option<&'static str>,
+}
+
 /// Message that can be sent by the peer set manager (PSM).
 #[derive(Debug, PartialEq)]
 pub enum Message {
@@ -166,6 +181,14 @@ pub struct PeersetConfig {
 	/// > **Note**: Keep in mind that the networking has to know an address for these nodes,
 	/// >			otherwise it will not be able to connect to them.
 	pub reserved_nodes: Vec<PeerId>,
+
+	/// Number of slots in each direction dedicated to the members of the given priority groups,
+	/// e.g. to the authorities found by the authority discovery.
+	///
+	/// Members of these groups don't bypass the slot limits like the members of other priority
+	/// groups do. Instead, they first use the slots dedicated to their group, then the regular
+	/// `in_peers` and `out_peers` slots. Other nodes can never use the dedicated slots.
+	pub priority_group_slots: HashMap<String, u32>,
 }
 
 /// Side of the peer set manager owned by the network. In other words, the "receiving" side.
@@ -187,6 +210,9 @@ pub struct Peerset {
 	created: Instant,
 	/// Last time when we updated the reputations of connected nodes.
 	latest_time_update: Instant,
+	/// Reason of the most recent reputation change of each node. Only kept for nodes which are
+	/// connected or banned, see `prune_report_reasons`.
+	report_reasons: HashMap<PeerId, &'static str>,
 }
 
 impl Peerset {
@@ -206,8 +232,12 @@ impl Peerset {
 			message_queue: VecDeque::new(),
 			created: Instant::now(),
 			latest_time_update: Instant::now(),
+			report_reasons: HashMap::new(),
 		};
 
+		for (group_id, slots) in config.priority_group_slots {
+			peerset.data.set_group_slots(&group_id, slots);
+		}
 		peerset.data.set_priority_group(RESERVED_NODES, config.reserved_nodes.into_iter().collect());
 		for peer_id in config.bootnodes {
 			if let peersstate::Peer::Unknown(entry) = peerset.data.peer(&peer_id) {
@@ -282,6 +312,7 @@ impl Peerset {
 	fn on_report_peer(&mut self, peer_id: PeerId, change: ReputationChange) {
 		// We want reputations to be up-to-date before adjusting them.
 		self.update_time();
+		self.report_reasons.insert(peer_id.clone(), change.reason);
 
 		match self.data.peer(&peer_id) {
 			peersstate::Peer::Connected(mut peer) => {
@@ -350,6 +381,20 @@ impl Peerset {
 				};
 			}
 		}
+
+		self.prune_report_reasons();
+	}
+
+	/// Forgets the reasons of the reputation changes of the nodes which are neither connected nor
+	/// banned, e.g. once they were dropped or their ban expired, as nodes are never forgotten
+	/// otherwise.
+	fn prune_report_reasons(&mut self) {
+		let data = &mut self.data;
+		self.report_reasons.retain(|peer_id, _| match data.peer(peer_id) {
+			peersstate::Peer::Connected(_) => true,
+			peersstate::Peer::NotConnected(peer) => peer.reputation() < BANNED_THRESHOLD,
+			peersstate::Peer::Unknown(_) => false,
+		});
 	}
 
 	/// Try to fill available out slots with nodes.
@@ -370,6 +415,20 @@ impl Peerset {
 			}
 		}
 
+		// Then fill the slots dedicated to some priority groups.
+		while let Some(next) = {
+			if self.reserved_only {
+				None
+			} else {
+				self.data.group_slot_not_connected_peer()
+			}
+		} {
+			match next.try_outgoing() {
+				Ok(conn) => self.message_queue.push_back(Message::Connect(conn.into_peer_id())),
+				Err(_) => break,
+			}
+		}
+
 		loop {
 			if self.reserved_only {
 				break
@@ -501,6 +560,28 @@ impl Peerset {
 		})
 	}
 
+	/// Returns the reputation of all nodes known to the peerset manager.
+	pub fn peer_reputations(&mut self) -> Vec<PeerReputation> {
+		self.update_time();
+
+		self.data.peers().cloned().collect::<Vec<_>>().into_iter().map(|peer_id| {
+			let (reputation, connected) = match self.data.peer(&peer_id) {
+				peersstate::Peer::Connected(entry) => (entry.reputation(), true),
+				peersstate::Peer::NotConnected(entry) => (entry.reputation(), false),
+				peersstate::Peer::Unknown(_) =>
+					unreachable!("We iterate over the known peers; QED")
+			};
+
+			PeerReputation {
+				last_report_reason: self.report_reasons.get(&peer_id).cloned(),
+				peer_id,
+				reputation,
+				connected,
+				banned: reputation < BANNED_THRESHOLD,
+			}
+		}).collect()
+	}
+
 	/// Returns priority group by id.
 	pub fn get_priority_group(&self, group_id: &str) -> Option<HashSet<PeerId>> {
 		self.data.get_priority_group(group_id)
@@ -547,7 +628,8 @@ mod tests {
 	use libp2p::PeerId;
 	use futures::prelude::*;
 	use super::{PeersetConfig, Peerset, Message, IncomingIndex, ReputationChange, BANNED_THRESHOLD};
-	use std::{pin::Pin, task::Poll, thread, time::Duration};
+	use super::PeerReputation;
+	use std::{collections::HashMap, pin::Pin, task::Poll, thread, time::Duration};
 
 	fn assert_messages(mut peerset: Peerset, messages: Vec<Message>) -> Peerset {
 		for expected_message in messages {
@@ -576,6 +658,7 @@ mod tests {
 			bootnodes: vec![bootnode],
 			reserved_only: true,
 			reserved_nodes: Vec::new(),
+			priority_group_slots: HashMap::new(),
 		};
 
 		let (peerset, handle) = Peerset::from_config(config);
@@ -604,6 +687,7 @@ mod tests {
 			bootnodes: vec![bootnode.clone()],
 			reserved_only: false,
 			reserved_nodes: Vec::new(),
+			priority_group_slots: HashMap::new(),
 		};
 
 		let (mut peerset, _handle) = Peerset::from_config(config);
@@ -631,6 +715,7 @@ mod tests {
 			bootnodes: vec![bootnode.clone()],
 			reserved_only: false,
 			reserved_nodes: vec![],
+			priority_group_slots: HashMap::new(),
 		};
 
 		let (mut peerset, _handle) = Peerset::from_config(config);
@@ -652,6 +737,7 @@ mod tests {
 			bootnodes: vec![],
 			reserved_only: false,
 			reserved_nodes: vec![],
+			priority_group_slots: HashMap::new(),
 		});
 
 		// We ban a node by setting its reputation under the threshold.
@@ -684,5 +770,114 @@ mod tests {
 
 		futures::executor::block_on(fut);
 	}
-}
 
+	#[test]
+	fn test_peerset_peer_reputations() {
+		let (mut peerset, handle) = Peerset::from_config(PeersetConfig {
+			in_peers: 25,
+			out_peers: 25,
+			bootnodes: vec![],
+			reserved_only: false,
+			reserved_nodes: vec![],
+			priority_group_slots: HashMap::new(),
+		});
+
+		let peer_id = PeerId::random();
+		handle.report_peer(peer_id.clone(), ReputationChange::new(BANNED_THRESHOLD - 1, "Misbehaved"));
+
+		let fut = futures::future::poll_fn(move |cx| {
+			// We need one polling for the message to be processed.
+			assert_eq!(Stream::poll_next(Pin::new(&mut peerset), cx), Poll::Pending);
+
+			let reputations = peerset.peer_reputations();
+			assert_eq!(reputations.len(), 1);
+			let PeerReputation { peer_id: id, reputation, connected, banned, last_report_reason } =
+				reputations[0].clone();
+			assert_eq!(id, peer_id);
+			assert!(reputation < BANNED_THRESHOLD);
+			assert!(!connected);
+			assert!(banned);
+			assert_eq!(last_report_reason, Some("Misbehaved"));
+
+			Poll::Ready(())
+		});
+
+		futures::executor::block_on(fut);
+	}
+
+	#[test]
+	fn test_peerset_forgets_report_reasons_of_dropped_peers() {
+		let bootnode = PeerId::random();
+		let (mut peerset, handle) = Peerset::from_config(PeersetConfig {
+			in_peers: 0,
+			out_peers: 1,
+			bootnodes: vec![bootnode.clone()],
+			reserved_only: false,
+			reserved_nodes: vec![],
+			priority_group_slots: HashMap::new(),
+		});
+
+		handle.report_peer(bootnode.clone(), ReputationChange::new(-1, "Slow"));
+
+		let fut = futures::future::poll_fn(move |cx| {
+			while let Poll::Ready(msg) = Stream::poll_next(Pin::new(&mut peerset), cx) {
+				assert_eq!(msg.unwrap(), Message::Connect(bootnode.clone()));
+			}
+			assert_eq!(peerset.peer_reputations()[0].last_report_reason, Some("Slow"));
+
+			// The node isn't banned, thus the reason is forgotten once it is dropped.
+			peerset.dropped(bootnode.clone());
+			assert_eq!(peerset.peer_reputations()[0].last_report_reason, None);
+			assert!(peerset.report_reasons.is_empty());
+
+			Poll::Ready(())
+		});
+
+		futures::executor::block_on(fut);
+	}
+
+	#[test]
+	fn test_peerset_add_and_remove_priority_group_members() {
+		let (mut peerset, handle) = Peerset::from_config(PeersetConfig {
+			in_peers: 25,
+			out_peers: 25,
+			bootnodes: vec![],
+			reserved_only: false,
+			reserved_nodes: vec![],
+			priority_group_slots: HashMap::new(),
+		});
+
+		let member1 = PeerId::random();
+		let member2 = PeerId::random();
+		let added1 = PeerId::random();
+		let added2 = PeerId::random();
+		let group = "group".to_string();
+
+		handle.set_priority_group(group.clone(), vec![member1.clone(), member2.clone()].into_iter().collect());
+		handle.add_to_priority_group(group.clone(), added1.clone());
+		handle.add_to_priority_group(group.clone(), added2.clone());
+		handle.add_to_priority_group(group.clone(), member1.clone());
+
+		let fut = futures::future::poll_fn(move |cx| {
+			while let Poll::Ready(_) = Stream::poll_next(Pin::new(&mut peerset), cx) {}
+			assert_eq!(
+				peerset.get_priority_group(&group),
+				Some(vec![member1.clone(), member2.clone(), added1.clone(), added2.clone()].into_iter().collect()),
+			);
+
+			handle.remove_from_priority_group(group.clone(), added1.clone());
+			handle.remove_from_priority_group(group.clone(), added2.clone());
+			while let Poll::Ready(_) = Stream::poll_next(Pin::new(&mut peerset), cx) {}
+
+			// Only the removed peers leave the group; the previous members are untouched.
+			assert_eq!(
+				peerset.get_priority_group(&group),
+				Some(vec![member1.clone(), member2.clone()].into_iter().collect()),
+			);
+
+			Poll::Ready(())
+		});
+
+		futures::executor::block_on(fut);
+	}
+}
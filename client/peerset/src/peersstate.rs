@@ -17,7 +17,7 @@
 //! Contains the state storage behind the peerset.
 
 use libp2p::PeerId;
-use std::{borrow::Cow, collections::{HashSet, HashMap}};
+use std::{borrow::Cow, cmp, collections::{HashSet, HashMap}};
 use log::warn;
 
 /// State storage behind the peerset.
@@ -36,21 +36,32 @@ pub struct PeersState {
 	/// 			sort, to make the logic easier.
 	nodes: HashMap<PeerId, Node>,
 
-	/// Number of non-priority nodes for which the `ConnectionState` is `In`.
+	/// Number of non-priority nodes for which the `ConnectionState` is `In`, including the
+	/// members of groups with dedicated slots.
 	num_in: u32,
 
-	/// Number of non-priority nodes for which the `ConnectionState` is `In`.
+	/// Number of non-priority nodes for which the `ConnectionState` is `Out`, including the
+	/// members of groups with dedicated slots.
 	num_out: u32,
 
-	/// Maximum allowed number of non-priority nodes for which the `ConnectionState` is `In`.
+	/// Maximum allowed number of non-priority nodes for which the `ConnectionState` is `In`, not
+	/// counting the nodes using dedicated slots.
 	max_in: u32,
 
-	/// Maximum allowed number of non-priority nodes for which the `ConnectionState` is `Out`.
+	/// Maximum allowed number of non-priority nodes for which the `ConnectionState` is `Out`, not
+	/// counting the nodes using dedicated slots.
 	max_out: u32,
 
 	/// Priority groups. Each group is identified by a string ID and contains a set of peer IDs.
 	priority_nodes: HashMap<String, HashSet<PeerId>>,
 
+	/// Number of slots in each direction dedicated to the members of the given priority groups.
+	///
+	/// Contrary to the members of other priority groups, the members of these groups don't
+	/// bypass the slot limits. They use the dedicated slots of their group first, then the regular
+	/// slots. Other nodes never use dedicated slots.
+	group_slots: HashMap<String, u32>,
+
 	/// Only allow connections to/from peers in a priority group.
 	priority_only: bool,
 }
@@ -107,10 +118,28 @@ impl PeersState {
 			max_in: in_peers,
 			max_out: out_peers,
 			priority_nodes: HashMap::new(),
+			group_slots: HashMap::new(),
 			priority_only,
 		}
 	}
 
+	/// Dedicates the given number of slots in each direction to the members of a priority group.
+	/// See `group_slots`.
+	pub fn set_group_slots(&mut self, group_id: &str, slots: u32) {
+		self.group_slots.insert(group_id.into(), slots);
+
+		// Whether a node bypasses the slot limits might have changed.
+		let (num_in, num_out) = self.nodes.iter()
+			.filter(|(peer_id, _)| !self.is_exempt(peer_id))
+			.fold((0, 0), |(num_in, num_out), (_, node)| match node.connection_state {
+				ConnectionState::In => (num_in + 1, num_out),
+				ConnectionState::Out => (num_in, num_out + 1),
+				ConnectionState::NotConnected => (num_in, num_out),
+			});
+		self.num_in = num_in;
+		self.num_out = num_out;
+	}
+
 	/// Returns an object that grants access to the state of a peer.
 	pub fn peer<'a>(&'a mut self, peer_id: &'a PeerId) -> Peer<'a> {
 		match self.nodes.get_mut(peer_id) {
@@ -150,12 +179,15 @@ impl PeersState {
 			.map(|(p, _)| p)
 	}
 
-	/// Returns the first priority peer that we are not connected to.
+	/// Returns the first priority peer that we are not connected to, ignoring the groups with
+	/// dedicated slots.
 	///
 	/// If multiple nodes are prioritized, which one is returned is unspecified.
 	pub fn priority_not_connected_peer(&mut self) -> Option<NotConnectedPeer> {
-		let id = self.priority_nodes.values()
-			.flatten()
+		let group_slots = &self.group_slots;
+		let id = self.priority_nodes.iter()
+			.filter(|(group_id, _)| !group_slots.contains_key(*group_id))
+			.flat_map(|(_, group)| group)
 			.find(|id| self.nodes.get(id).map_or(false, |node| !node.connection_state.is_connected()))
 			.cloned();
 		id.map(move |id| NotConnectedPeer {
@@ -178,6 +210,24 @@ impl PeersState {
 		})
 	}
 
+	/// Returns the first member of a priority group with dedicated slots that we are not connected
+	/// to, and for which a dedicated outgoing slot is available.
+	///
+	/// If multiple nodes qualify, which one is returned is unspecified.
+	pub fn group_slot_not_connected_peer(&mut self) -> Option<NotConnectedPeer> {
+		let id = self.group_slots.iter()
+			.filter(|(group_id, slots)| self.group_slots_used(group_id, ConnectionState::Out) < **slots)
+			.filter_map(|(group_id, _)| self.priority_nodes.get(group_id))
+			.flatten()
+			.find(|id| !self.is_exempt(id) &&
+				self.nodes.get(id).map_or(false, |node| !node.connection_state.is_connected()))
+			.cloned();
+		id.map(move |id| NotConnectedPeer {
+			state: self,
+			peer_id: Cow::Owned(id),
+		})
+	}
+
 	/// Returns the peer with the highest reputation and that we are not connected to.
 	///
 	/// If multiple nodes have the same reputation, which one is returned is unspecified.
@@ -206,9 +256,9 @@ impl PeersState {
 	}
 
 	fn disconnect(&mut self, peer_id: &PeerId) {
-		let is_priority = self.is_priority(peer_id);
+		let is_exempt = self.is_exempt(peer_id);
 		if let Some(mut node) = self.nodes.get_mut(peer_id) {
-			if !is_priority {
+			if !is_exempt {
 				match node.connection_state {
 					ConnectionState::In => self.num_in -= 1,
 					ConnectionState::Out => self.num_out -= 1,
@@ -231,15 +281,14 @@ impl PeersState {
 			return false;
 		}
 
-		// Note that it is possible for num_out to be strictly superior to the max, in case we were
-		// connected to reserved node then marked them as not reserved.
-		if self.num_out >= self.max_out && !is_priority {
+		let is_exempt = self.is_exempt(peer_id);
+		if !is_exempt && !self.has_free_slot(peer_id, ConnectionState::Out) {
 			return false;
 		}
 
 		if let Some(mut peer) = self.nodes.get_mut(peer_id) {
 			peer.connection_state = ConnectionState::Out;
-			if !is_priority {
+			if !is_exempt {
 				self.num_out += 1;
 			}
 			return true;
@@ -261,14 +310,13 @@ impl PeersState {
 			return false;
 		}
 
-		// Note that it is possible for num_in to be strictly superior to the max, in case we were
-		// connected to reserved node then marked them as not reserved.
-		if self.num_in >= self.max_in && !is_priority {
+		let is_exempt = self.is_exempt(peer_id);
+		if !is_exempt && !self.has_free_slot(peer_id, ConnectionState::In) {
 			return false;
 		}
 		if let Some(mut peer) = self.nodes.get_mut(peer_id) {
 			peer.connection_state = ConnectionState::In;
-			if !is_priority {
+			if !is_exempt {
 				self.num_in += 1;
 			}
 			return true;
@@ -278,10 +326,20 @@ impl PeersState {
 
 	/// Sets priority group
 	pub fn set_priority_group(&mut self, group_id: &str, peers: HashSet<PeerId>) {
+		// Members of groups with dedicated slots always use slots.
+		if self.group_slots.contains_key(group_id) {
+			for id in &peers {
+				self.nodes.entry(id.clone()).or_default();
+			}
+			self.priority_nodes.insert(group_id.into(), peers);
+			return;
+		}
+
 		// update slot counters
+		let group_slots = &self.group_slots;
 		let all_other_groups: HashSet<_> = self.priority_nodes
 			.iter()
-			.filter(|(g, _)| *g != group_id)
+			.filter(|(g, _)| *g != group_id && !group_slots.contains_key(*g))
 			.flat_map(|(_, id)| id.clone())
 			.collect();
 		let existing_group = self.priority_nodes.remove(group_id).unwrap_or_default();
@@ -345,6 +403,45 @@ impl PeersState {
 		self.priority_nodes.iter().any(|(_, group)| group.contains(peer_id))
 	}
 
+	/// Check that node is in a priority group without dedicated slots, and thus doesn't use any
+	/// slot.
+	fn is_exempt(&self, peer_id: &PeerId) -> bool {
+		self.priority_nodes.iter()
+			.any(|(group_id, group)| !self.group_slots.contains_key(group_id) && group.contains(peer_id))
+	}
+
+	/// Number of members of the given group using a slot in the given direction.
+	fn group_slots_used(&self, group_id: &str, direction: ConnectionState) -> u32 {
+		self.priority_nodes.get(group_id).map_or(0, |group| group.iter()
+			.filter(|id| !self.is_exempt(id) &&
+				self.nodes.get(id).map_or(false, |node| node.connection_state == direction))
+			.count() as u32)
+	}
+
+	/// Whether a node that doesn't bypass the slot limits can be connected in the given direction,
+	/// either on a dedicated slot of one of its groups or on a regular slot.
+	fn has_free_slot(&self, peer_id: &PeerId, direction: ConnectionState) -> bool {
+		let (num, max) = match direction {
+			ConnectionState::In => (self.num_in, self.max_in),
+			ConnectionState::Out => (self.num_out, self.max_out),
+			ConnectionState::NotConnected => return true,
+		};
+
+		let mut dedicated = 0;
+		for (group_id, slots) in &self.group_slots {
+			let used = self.group_slots_used(group_id, direction);
+			let is_member = self.priority_nodes.get(group_id).map_or(false, |group| group.contains(peer_id));
+			if is_member && used < *slots {
+				return true;
+			}
+			dedicated += cmp::min(used, *slots);
+		}
+
+		// Note that it is possible for `num` to be strictly superior to the max, in case we were
+		// connected to reserved node then marked them as not reserved.
+		num.saturating_sub(dedicated) < max
+	}
+
 	/// Returns the reputation value of the node.
 	fn reputation(&self, peer_id: &PeerId) -> i32 {
 		self.nodes.get(peer_id).map_or(0, |p| p.reputation)
@@ -717,4 +814,44 @@ mod tests {
 		peers_state.remove_from_priority_group("TEST_GROUP", &id);
 		assert!(!test_connection(&mut peers_state, &id));
 	}
+
+	#[test]
+	fn group_slots_are_dedicated() {
+		let mut peers_state = PeersState::new(1, 1, false);
+		let authority1 = PeerId::random();
+		let authority2 = PeerId::random();
+		let other1 = PeerId::random();
+		let other2 = PeerId::random();
+
+		peers_state.set_group_slots("authorities", 1);
+		peers_state.set_priority_group("authorities", vec![authority1.clone(), authority2.clone()].into_iter().collect());
+
+		// Other nodes can't use the dedicated slot.
+		assert!(peers_state.peer(&other1).into_unknown().unwrap().discover().try_accept_incoming().is_ok());
+		assert!(peers_state.peer(&other2).into_unknown().unwrap().discover().try_accept_incoming().is_err());
+
+		// Members of the group don't bypass the limits.
+		assert!(peers_state.peer(&authority1).into_not_connected().unwrap().try_accept_incoming().is_ok());
+		assert!(peers_state.peer(&authority2).into_not_connected().unwrap().try_accept_incoming().is_err());
+
+		// But they can use the regular slots once free.
+		peers_state.peer(&other1).into_connected().unwrap().disconnect();
+		assert!(peers_state.peer(&authority2).into_not_connected().unwrap().try_accept_incoming().is_ok());
+		assert!(peers_state.peer(&other2).into_not_connected().unwrap().try_accept_incoming().is_err());
+		assert_eq!(peers_state.num_in, 2);
+	}
+
+	#[test]
+	fn group_slot_not_connected_peer() {
+		let mut peers_state = PeersState::new(0, 0, false);
+		let authority1 = PeerId::random();
+		let authority2 = PeerId::random();
+
+		peers_state.set_group_slots("authorities", 1);
+		peers_state.set_priority_group("authorities", vec![authority1, authority2].into_iter().collect());
+		assert!(peers_state.priority_not_connected_peer().is_none());
+
+		assert!(peers_state.group_slot_not_connected_peer().unwrap().try_outgoing().is_ok());
+		assert!(peers_state.group_slot_not_connected_peer().is_none());
+	}
 }
@@ -18,6 +18,7 @@
 
 pub mod error;
 pub mod hash;
+pub mod pending;
 
 use jsonrpc_derive::rpc;
 use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
@@ -53,6 +54,14 @@ pub trait AuthorApi<Hash, BlockHash> {
 	#[rpc(name = "author_pendingExtrinsics")]
 	fn pending_extrinsics(&self) -> Result<Vec<Bytes>>;
 
+	/// Returns all pending extrinsics, grouped by chains of dependent extrinsics, along with the
+	/// reason they are not included yet.
+	///
+	/// With account nonces as tags, a group holds the extrinsics of a single sender: either the
+	/// ones that are ready, or the ones waiting for a missing nonce.
+	#[rpc(name = "author_pendingExtrinsicsGroups")]
+	fn pending_extrinsics_groups(&self) -> Result<Vec<pending::PendingExtrinsics<Hash>>>;
+
 	/// Remove given extrinsic from the pool and temporarily ban it to prevent reimporting.
 	#[rpc(name = "author_removeExtrinsic")]
 	fn remove_extrinsic(&self,
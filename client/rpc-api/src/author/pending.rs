@@ -0,0 +1,57 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Pending extrinsics types of the author RPC module.
+
+use sp_core::Bytes;
+use serde::{Serialize, Deserialize};
+
+/// Reason why pending extrinsics are not included in a block yet.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PendingReason {
+	/// The extrinsics are ready, and wait for a block to include them.
+	Ready,
+	/// The extrinsics wait for the given tags, that no extrinsic of the pool provides,
+	/// e.g. a missing nonce of the sender.
+	Future(Vec<Bytes>),
+}
+
+/// An extrinsic of the pool.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingExtrinsic<Hash> {
+	/// The hash of the extrinsic.
+	pub hash: Hash,
+	/// Raw extrinsic bytes.
+	pub extrinsic: Bytes,
+	/// The priority of the extrinsic.
+	pub priority: u64,
+	/// The tags required by the extrinsic.
+	pub requires: Vec<Bytes>,
+	/// The tags provided by the extrinsic.
+	pub provides: Vec<Bytes>,
+}
+
+/// Pending extrinsics depending on each other.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingExtrinsics<Hash> {
+	/// Why the extrinsics are not included yet.
+	pub reason: PendingReason,
+	/// The extrinsics, ready ones in the order in which they can be included.
+	pub extrinsics: Vec<PendingExtrinsic<Hash>>,
+}
@@ -27,6 +27,9 @@ use self::error::{FutureResult, Result};
 
 pub use self::gen_client::Client as ChainClient;
 
+/// Maximal number of headers returned by a single `chain_getHeaderRange` call.
+pub const MAX_HEADER_RANGE: u32 = 1024;
+
 /// Substrate blockchain API
 #[rpc]
 pub trait ChainApi<Number, Hash, Header, SignedBlock> {
@@ -37,9 +40,24 @@ pub trait ChainApi<Number, Hash, Header, SignedBlock> {
 	#[rpc(name = "chain_getHeader")]
 	fn header(&self, hash: Option<Hash>) -> FutureResult<Option<Header>>;
 
+	/// Get headers of a range of blocks in the canon chain.
+	///
+	/// Returns the headers of the blocks numbered from `from` to `to`, both included, in
+	/// ascending order. The range stops at the best block and holds at most `limit` headers,
+	/// up to `MAX_HEADER_RANGE`.
+	#[rpc(name = "chain_getHeaderRange")]
+	fn header_range(
+		&self,
+		from: NumberOrHex<Number>,
+		to: NumberOrHex<Number>,
+		limit: Option<u32>,
+	) -> Result<Vec<Header>>;
+
 	/// Get header and body of a relay chain block.
+	///
+	/// The justification of the block is included unless `justification` is `false`.
 	#[rpc(name = "chain_getBlock")]
-	fn block(&self, hash: Option<Hash>) -> FutureResult<Option<SignedBlock>>;
+	fn block(&self, hash: Option<Hash>, justification: Option<bool>) -> FutureResult<Option<SignedBlock>>;
 
 	/// Get hash of the n-th block in the canon chain.
 	///
@@ -22,13 +22,17 @@
 
 mod errors;
 mod helpers;
+mod policy;
 mod subscriptions;
 
 pub use jsonrpc_core::IoHandlerExtension as RpcExtension;
-pub use subscriptions::{Subscriptions, TaskExecutor};
+pub use subscriptions::{Subscriptions, SubscriptionObserver, TaskExecutor};
 pub use helpers::Receiver;
+pub use policy::{DenyUnsafe, UnsafeRpcError};
 
 pub mod author;
 pub mod chain;
+pub mod child_state;
+pub mod offchain;
 pub mod state;
 pub mod system;
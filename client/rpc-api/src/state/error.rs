@@ -41,6 +41,22 @@ pub enum Error {
 		/// Details of the error message.
 		details: String,
 	},
+	/// The subscription covers more storage keys than allowed.
+	#[display(fmt = "Subscription covers more than {} storage keys", limit)]
+	#[from(ignore)]
+	TooManyKeys {
+		/// Maximal number of keys of a subscription.
+		limit: usize,
+	},
+	/// The requested number of keys is larger than allowed.
+	#[display(fmt = "Requested {} keys, more than the maximum of {}", count, max)]
+	#[from(ignore)]
+	InvalidCount {
+		/// Requested number of keys.
+		count: u32,
+		/// Maximal number of keys.
+		max: u32,
+	},
 }
 
 impl std::error::Error for Error {
@@ -63,6 +79,16 @@ impl From<Error> for rpc::Error {
 				message: format!("{}", e),
 				data: None,
 			},
+			Error::TooManyKeys { .. } => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 2),
+				message: format!("{}", e),
+				data: None,
+			},
+			Error::InvalidCount { .. } => rpc::Error {
+				code: rpc::ErrorCode::ServerError(BASE_ERROR + 3),
+				message: format!("{}", e),
+				data: None,
+			},
 			e => errors::internal(e),
 		}
 	}
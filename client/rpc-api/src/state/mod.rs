@@ -29,6 +29,20 @@ use self::error::FutureResult;
 
 pub use self::gen_client::Client as StateClient;
 
+/// Maximal number of storage keys a single storage subscription may watch.
+pub const MAX_SUBSCRIPTION_KEYS: usize = 16 * 1024;
+
+/// Number of entries per notification of the initial values of a storage prefix subscription,
+/// if not given by the subscriber.
+pub const DEFAULT_SNAPSHOT_PAGE_SIZE: u32 = 256;
+
+/// Maximal number of entries per notification of the initial values of a storage prefix
+/// subscription.
+pub const MAX_SNAPSHOT_PAGE_SIZE: u32 = 1024;
+
+/// Maximal number of keys returned by a single `state_getKeysPaged` call.
+pub const MAX_KEYS_PAGE_SIZE: u32 = 1000;
+
 /// Substrate state API
 #[rpc]
 pub trait StateApi<Hash> {
@@ -43,6 +57,18 @@ pub trait StateApi<Hash> {
 	#[rpc(name = "state_getKeys")]
 	fn storage_keys(&self, prefix: StorageKey, hash: Option<Hash>) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns at most `count` keys with prefix, starting after `start_key` if given.
+	///
+	/// Requesting more than `MAX_KEYS_PAGE_SIZE` keys is rejected.
+	#[rpc(name = "state_getKeysPaged", alias("state_getKeysPagedAt"))]
+	fn storage_keys_paged(
+		&self,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		hash: Option<Hash>,
+	) -> FutureResult<Vec<StorageKey>>;
+
 	/// Returns a storage entry at a specific block's state.
 	#[rpc(name = "state_getStorage", alias("state_getStorageAt"))]
 	fn storage(&self, key: StorageKey, hash: Option<Hash>) -> FutureResult<Option<StorageData>>;
@@ -109,6 +135,9 @@ pub trait StateApi<Hash> {
 
 	/// Query historical storage entries (by key) starting from a block given as the second parameter.
 	///
+	/// The range is inclusive and ends at the block given as the third parameter, or at the best
+	/// block if omitted. Blocks that don't change any of the keys are skipped.
+	///
 	/// NOTE This first returned result contains the initial state of storage for all keys.
 	/// Subsequent values in the vector represent changes to the previous state (diffs).
 	#[rpc(name = "state_queryStorage")]
@@ -138,6 +167,8 @@ pub trait StateApi<Hash> {
 	fn unsubscribe_runtime_version(&self, metadata: Option<Self::Metadata>, id: SubscriptionId) -> RpcResult<bool>;
 
 	/// New storage subscription
+	///
+	/// Subscribing to more than `MAX_SUBSCRIPTION_KEYS` keys is rejected.
 	#[pubsub(subscription = "state_storage", subscribe, name = "state_subscribeStorage")]
 	fn subscribe_storage(
 		&self, metadata: Self::Metadata, subscriber: Subscriber<StorageChangeSet<Hash>>, keys: Option<Vec<StorageKey>>
@@ -148,4 +179,33 @@ pub trait StateApi<Hash> {
 	fn unsubscribe_storage(
 		&self, metadata: Option<Self::Metadata>, id: SubscriptionId
 	) -> RpcResult<bool>;
+
+	/// New storage subscription to all the keys starting with the given prefix.
+	///
+	/// The current values of the keys at the best block are sent first, split into notifications
+	/// of at most `page_size` entries (`DEFAULT_SNAPSHOT_PAGE_SIZE` if omitted, and never more than
+	/// `MAX_SNAPSHOT_PAGE_SIZE`). The subscription is rejected if the prefix covers more than
+	/// `MAX_SUBSCRIPTION_KEYS` keys.
+	#[pubsub(
+		subscription = "state_storagePrefix",
+		subscribe,
+		name = "state_subscribeStoragePrefix"
+	)]
+	fn subscribe_storage_prefix(
+		&self,
+		metadata: Self::Metadata,
+		subscriber: Subscriber<StorageChangeSet<Hash>>,
+		prefix: StorageKey,
+		page_size: Option<u32>
+	);
+
+	/// Unsubscribe from storage prefix subscription
+	#[pubsub(
+		subscription = "state_storagePrefix",
+		unsubscribe,
+		name = "state_unsubscribeStoragePrefix"
+	)]
+	fn unsubscribe_storage_prefix(
+		&self, metadata: Option<Self::Metadata>, id: SubscriptionId
+	) -> RpcResult<bool>;
 }
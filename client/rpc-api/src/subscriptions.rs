@@ -28,6 +28,17 @@ type Id = u64;
 /// Alias for a an implementation of `futures::future::Executor`.
 pub type TaskExecutor = Arc<dyn future::Executor<Box<dyn Future<Item = (), Error = ()> + Send>> + Send + Sync>;
 
+/// Notified of the start and the end of the subscriptions driven by `Subscriptions`.
+///
+/// A subscription ends when it is cancelled, or when its stream of notifications is over.
+pub trait SubscriptionObserver: Send + Sync {
+	/// The subscription with the given id is about to be opened.
+	fn started(&self, id: &SubscriptionId);
+
+	/// The subscription with the given id ended.
+	fn ended(&self, id: &SubscriptionId);
+}
+
 /// Generate unique ids for subscriptions.
 #[derive(Clone, Debug)]
 pub struct IdProvider {
@@ -57,6 +68,7 @@ pub struct Subscriptions {
 	next_id: IdProvider,
 	active_subscriptions: Arc<Mutex<HashMap<Id, oneshot::Sender<()>>>>,
 	executor: TaskExecutor,
+	observer: Option<Arc<dyn SubscriptionObserver>>,
 }
 
 impl Subscriptions {
@@ -66,9 +78,16 @@ impl Subscriptions {
 			next_id: Default::default(),
 			active_subscriptions: Default::default(),
 			executor,
+			observer: None,
 		}
 	}
 
+	/// Notify the given observer of the start and the end of every subscription.
+	pub fn with_observer(mut self, observer: Arc<dyn SubscriptionObserver>) -> Self {
+		self.observer = Some(observer);
+		self
+	}
+
 	/// Borrows the internal task executor.
 	///
 	/// This can be used to spawn additional tasks on the underyling event loop.
@@ -88,17 +107,40 @@ impl Subscriptions {
 	{
 		let id = self.next_id.next_id();
 		let subscription_id: SubscriptionId = id.into();
-		if let Ok(sink) = subscriber.assign_id(subscription_id.clone()) {
-			let (tx, rx) = oneshot::channel();
-			let future = into_future(sink)
-				.into_future()
-				.select(rx.map_err(|e| warn!("Error timeing out: {:?}", e)))
-				.then(|_| Ok(()));
-
-			self.active_subscriptions.lock().insert(id, tx);
-			if self.executor.execute(Box::new(future)).is_err() {
-				error!("Failed to spawn RPC subscription task");
+		if let Some(observer) = &self.observer {
+			observer.started(&subscription_id);
+		}
+		let end = {
+			let active_subscriptions = self.active_subscriptions.clone();
+			let observer = self.observer.clone();
+			let subscription_id = subscription_id.clone();
+			move || {
+				active_subscriptions.lock().remove(&id);
+				if let Some(observer) = &observer {
+					observer.ended(&subscription_id);
+				}
 			}
+		};
+
+		match subscriber.assign_id(subscription_id.clone()) {
+			Ok(sink) => {
+				let (tx, rx) = oneshot::channel();
+				let on_completion = end.clone();
+				let future = into_future(sink)
+					.into_future()
+					.select(rx.map_err(|e| warn!("Error timeing out: {:?}", e)))
+					.then(move |_| {
+						on_completion();
+						Ok(())
+					});
+
+				self.active_subscriptions.lock().insert(id, tx);
+				if self.executor.execute(Box::new(future)).is_err() {
+					error!("Failed to spawn RPC subscription task");
+					end();
+				}
+			},
+			Err(()) => end(),
 		}
 
 		subscription_id
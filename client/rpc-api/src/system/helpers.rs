@@ -74,6 +74,58 @@ pub struct PeerInfo<Hash, Number> {
 	pub best_number: Number,
 }
 
+/// Reputation of a node known to the peer set manager
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerReputationInfo {
+	/// Peer ID
+	pub peer_id: String,
+	/// Current reputation
+	pub reputation: i32,
+	/// Are we connected to the peer
+	pub connected: bool,
+	/// Is the peer banned due to its low reputation
+	pub banned: bool,
+	/// Reason of the most recent reputation change, if any
+	pub last_report_reason: Option<String>,
+}
+
+/// Progress of the sync returned by the RPC
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "state")]
+pub enum SyncState<Number> {
+	/// No peer to sync from yet
+	Starting,
+	/// Catching up with the chain
+	Downloading {
+		/// Best block number seen among the peers
+		target: Number,
+		/// Best block number of the node
+		current: Number,
+		/// Number of peers participating in syncing
+		peers: u32,
+	},
+	/// Caught up with the chain, importing the last downloaded blocks
+	Importing {
+		/// Number of blocks queued for import
+		queued: u32,
+	},
+	/// Caught up with the chain
+	Idle,
+}
+
+/// Block numbers describing the progress of the sync
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SyncProgress<Number> {
+	/// Best block number of the node when it started
+	pub starting_block: Number,
+	/// Current best block number of the node
+	pub current_block: Number,
+	/// Best block number seen among the peers, if any
+	pub highest_block: Option<Number>,
+}
+
 /// The role the node is running as
 #[derive(Debug, PartialEq, Serialize, Deserialize)]
 pub enum NodeRole {
@@ -116,4 +168,42 @@ mod tests {
 			r#"{"peerId":"2","roles":"a","protocolVersion":2,"bestHash":5,"bestNumber":6}"#,
 		);
 	}
+
+	#[test]
+	fn should_serialize_sync_state() {
+		assert_eq!(
+			::serde_json::to_string(&SyncState::Downloading { target: 10u32, current: 4u32, peers: 3 }).unwrap(),
+			r#"{"state":"downloading","target":10,"current":4,"peers":3}"#,
+		);
+		assert_eq!(
+			::serde_json::to_string(&SyncState::<u32>::Idle).unwrap(),
+			r#"{"state":"idle"}"#,
+		);
+	}
+
+	#[test]
+	fn should_serialize_sync_progress() {
+		assert_eq!(
+			::serde_json::to_string(&SyncProgress {
+				starting_block: 12u32,
+				current_block: 50,
+				highest_block: Some(128),
+			}).unwrap(),
+			r#"{"startingBlock":12,"currentBlock":50,"highestBlock":128}"#,
+		);
+	}
+
+	#[test]
+	fn should_serialize_peer_reputation_info() {
+		assert_eq!(
+			::serde_json::to_string(&PeerReputationInfo {
+				peer_id: "2".into(),
+				reputation: -5,
+				connected: false,
+				banned: true,
+				last_report_reason: Some("Bad message".into()),
+			}).unwrap(),
+			r#"{"peerId":"2","reputation":-5,"connected":false,"banned":true,"lastReportReason":"Bad message"}"#,
+		);
+	}
 }
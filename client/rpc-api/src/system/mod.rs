@@ -25,7 +25,9 @@ use futures::{future::BoxFuture, compat::Compat};
 
 use self::error::Result as SystemResult;
 
-pub use self::helpers::{Properties, SystemInfo, Health, PeerInfo, NodeRole};
+pub use self::helpers::{
+	Properties, SystemInfo, Health, PeerInfo, PeerReputationInfo, NodeRole, SyncState, SyncProgress,
+};
 pub use self::gen_client::Client as SystemClient;
 
 /// Substrate system RPC API
@@ -55,16 +57,41 @@ pub trait SystemApi<Hash, Number> {
 	#[rpc(name = "system_health", returns = "Health")]
 	fn system_health(&self) -> Receiver<Health>;
 
+	/// Returns the progress of the sync.
+	#[rpc(name = "system_syncState", returns = "SyncState<Number>")]
+	fn system_sync_state(&self) -> Receiver<SyncState<Number>>;
+
+	/// Returns the best block number of the node when it started, its current best block number
+	/// and the best block number seen among its peers.
+	#[rpc(name = "system_syncProgress", returns = "SyncProgress<Number>")]
+	fn system_sync_progress(&self) -> Receiver<SyncProgress<Number>>;
+
+	/// Returns the addresses the node is listening on, followed by its external addresses as
+	/// observed by its peers.
+	///
+	/// The addresses end with the `PeerId` of the node, so that they can be used to connect to it
+	/// directly, e.g. as boot nodes.
+	#[rpc(name = "system_localListenAddresses", returns = "Vec<String>")]
+	fn system_local_listen_addresses(&self) -> Receiver<Vec<String>>;
+
 	/// Returns currently connected peers
 	#[rpc(name = "system_peers", returns = "Vec<PeerInfo<Hash, Number>>")]
-	fn system_peers(&self) -> Receiver<Vec<PeerInfo<Hash, Number>>>;
+	fn system_peers(&self)
+		-> Compat<BoxFuture<'static, Result<Vec<PeerInfo<Hash, Number>>, jsonrpc_core::Error>>>;
+
+	/// Returns the reputation of all peers known to the node, whether they are banned and the
+	/// reason of their most recent reputation change.
+	#[rpc(name = "system_peerReputations", returns = "Vec<PeerReputationInfo>")]
+	fn system_peer_reputations(&self)
+		-> Compat<BoxFuture<'static, Result<Vec<PeerReputationInfo>, jsonrpc_core::Error>>>;
 
 	/// Returns current state of the network.
 	///
 	/// **Warning**: This API is not stable.
 	// TODO: make this stable and move structs https://github.com/paritytech/substrate/issues/1890
 	#[rpc(name = "system_networkState", returns = "jsonrpc_core::Value")]
-	fn system_network_state(&self) -> Receiver<jsonrpc_core::Value>;
+	fn system_network_state(&self)
+		-> Compat<BoxFuture<'static, Result<jsonrpc_core::Value, jsonrpc_core::Error>>>;
 
 	/// Adds a reserved peer. Returns the empty string or an error. The string
 	/// parameter should encode a `p2p` multiaddr.
@@ -81,6 +108,12 @@ pub trait SystemApi<Hash, Number> {
 	fn system_remove_reserved_peer(&self, peer_id: String)
 		-> Compat<BoxFuture<'static, Result<(), jsonrpc_core::Error>>>;
 
+	/// Only connect to reserved peers if `true`, otherwise also accept unreserved peers. Switching
+	/// to reserved-only mode disconnects all unreserved peers.
+	#[rpc(name = "system_setReservedOnly", returns = "()")]
+	fn system_set_reserved_only(&self, reserved_only: bool)
+		-> Compat<BoxFuture<'static, Result<(), jsonrpc_core::Error>>>;
+
 	/// Returns the roles the node is running as.
 	#[rpc(name = "system_nodeRoles", returns = "Vec<NodeRole>")]
 	fn system_node_roles(&self) -> Receiver<Vec<NodeRole>>;
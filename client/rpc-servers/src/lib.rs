@@ -18,27 +18,32 @@
 
 #![warn(missing_docs)]
 
+mod middleware;
+
 use std::io;
-use jsonrpc_core::IoHandlerExtension;
+use jsonrpc_core::{IoHandlerExtension, MetaIoHandler};
 use log::error;
 use pubsub::PubSubMetadata;
 
-/// Maximal payload accepted by RPC servers.
+pub use self::middleware::{RpcLimits, RpcMiddleware};
+
+/// Default maximal payload accepted by RPC servers.
 const MAX_PAYLOAD: usize = 15 * 1024 * 1024;
 
 /// Default maximum number of connections for WS RPC servers.
 const WS_MAX_CONNECTIONS: usize = 100;
 
 /// The RPC IoHandler containing all requested APIs.
-pub type RpcHandler<T> = pubsub::PubSubHandler<T>;
+pub type RpcHandler<T> = pubsub::PubSubHandler<T, RpcMiddleware>;
 
 pub use self::inner::*;
 
-/// Construct rpc `IoHandler`
+/// Construct rpc `IoHandler` enforcing the limits of the given middleware.
 pub fn rpc_handler<M: PubSubMetadata>(
-	extension: impl IoHandlerExtension<M>
+	extension: impl IoHandlerExtension<M>,
+	middleware: RpcMiddleware,
 ) -> RpcHandler<M> {
-	let mut io = pubsub::PubSubHandler::default();
+	let mut io = pubsub::PubSubHandler::new(MetaIoHandler::with_middleware(middleware));
 	extension.augment(&mut io);
 
 	// add an endpoint to list all available methods.
@@ -64,6 +69,8 @@ mod inner {
 	pub type HttpServer = http::Server;
 	/// Type alias for ws server
 	pub type WsServer = ws::Server;
+	/// Type alias for ipc server
+	pub type IpcServer = ipc::Server;
 
 	/// Start HTTP server listening on given address.
 	///
@@ -71,6 +78,7 @@ mod inner {
 	pub fn start_http<M: pubsub::PubSubMetadata + Default>(
 		addr: &std::net::SocketAddr,
 		cors: Option<&Vec<String>>,
+		max_payload: Option<usize>,
 		io: RpcHandler<M>,
 	) -> io::Result<http::Server> {
 		http::ServerBuilder::new(io)
@@ -83,7 +91,7 @@ mod inner {
 				http::RestApi::Unsecure
 			})
 			.cors(map_cors::<http::AccessControlAllowOrigin>(cors))
-			.max_request_body_size(MAX_PAYLOAD)
+			.max_request_body_size(max_payload.unwrap_or(MAX_PAYLOAD))
 			.start_http(addr)
 	}
 
@@ -94,10 +102,11 @@ mod inner {
 		addr: &std::net::SocketAddr,
 		max_connections: Option<usize>,
 		cors: Option<&Vec<String>>,
+		max_payload: Option<usize>,
 		io: RpcHandler<M>,
 	) -> io::Result<ws::Server> {
 		ws::ServerBuilder::with_meta_extractor(io, |context: &ws::RequestContext| context.sender().into())
-			.max_payload(MAX_PAYLOAD)
+			.max_payload(max_payload.unwrap_or(MAX_PAYLOAD))
 			.max_connections(max_connections.unwrap_or(WS_MAX_CONNECTIONS))
 			.allowed_origins(map_cors(cors))
 			.allowed_hosts(hosts_filtering(cors.is_some()))
@@ -112,6 +121,49 @@ mod inner {
 			})
 	}
 
+	/// Start IPC server listening on given path.
+	///
+	/// The server listens on a Unix domain socket, or on a named pipe on Windows. On Unix, the
+	/// socket is only accessible to the user running the node, from its creation on.
+	///
+	/// **Note**: Only available if `not(target_os = "unknown")`.
+	pub fn start_ipc<M: pubsub::PubSubMetadata + From<jsonrpc_core::futures::sync::mpsc::Sender<String>>> (
+		path: &str,
+		io: RpcHandler<M>,
+	) -> io::Result<ipc::Server> {
+		let builder = ipc::ServerBuilder::with_meta_extractor(io, |context: &ipc::RequestContext| {
+			context.sender.clone().into()
+		});
+
+		#[cfg(unix)]
+		let builder = builder.set_security_attributes(ipc::SecurityAttributes::empty().set_mode(0o600)?);
+		// The socket is created with the permissions left by the umask and only restricted
+		// afterwards, so mask the permissions of the group and others until then. `start`
+		// returns once the socket is created.
+		#[cfg(unix)]
+		let _umask = Umask::set(0o177);
+
+		builder.start(path)
+	}
+
+	/// Restores the previous umask of the process when dropped.
+	#[cfg(unix)]
+	struct Umask(libc::mode_t);
+
+	#[cfg(unix)]
+	impl Umask {
+		fn set(mask: libc::mode_t) -> Self {
+			Umask(unsafe { libc::umask(mask) })
+		}
+	}
+
+	#[cfg(unix)]
+	impl Drop for Umask {
+		fn drop(&mut self) {
+			unsafe { libc::umask(self.0); }
+		}
+	}
+
 	fn map_cors<T: for<'a> From<&'a str>>(
 		cors: Option<&Vec<String>>
 	) -> http::DomainsValidation<T> {
@@ -133,3 +185,50 @@ mod inner {
 #[cfg(target_os = "unknown")]
 mod inner {
 }
+
+#[cfg(all(test, unix))]
+mod tests {
+	use super::*;
+	use std::{io::{BufRead, BufReader, Write}, os::unix::{fs::PermissionsExt, net::UnixStream}, sync::Arc};
+	use jsonrpc_core::futures::sync::mpsc;
+	use pubsub::Session;
+
+	#[derive(Clone, Default)]
+	struct Metadata(Option<Arc<Session>>);
+
+	impl jsonrpc_core::Metadata for Metadata {}
+	impl PubSubMetadata for Metadata {
+		fn session(&self) -> Option<Arc<Session>> {
+			self.0.clone()
+		}
+	}
+
+	impl From<mpsc::Sender<String>> for Metadata {
+		fn from(sender: mpsc::Sender<String>) -> Self {
+			Metadata(Some(Arc::new(Session::new(sender))))
+		}
+	}
+
+	#[test]
+	fn ipc_socket_is_only_accessible_to_the_owner() {
+		let dir = tempfile::tempdir().unwrap();
+		let path = dir.path().join("rpc.ipc");
+		let io = rpc_handler(jsonrpc_core::IoHandler::<Metadata>::default(), RpcMiddleware::new(Default::default()));
+
+		let previous = unsafe { libc::umask(0o000) };
+		let server = start_ipc(path.to_str().unwrap(), io).unwrap();
+		unsafe { libc::umask(previous); }
+
+		let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+		assert_eq!(mode & 0o777, 0o600);
+
+		let mut stream = UnixStream::connect(&path).unwrap();
+		stream.write_all(br#"{"jsonrpc":"2.0","method":"rpc_methods","params":[],"id":1}"#).unwrap();
+		let mut response = String::new();
+		BufReader::new(stream).read_line(&mut response).unwrap();
+		let response: serde_json::Value = serde_json::from_str(&response).unwrap();
+		assert_eq!(response["result"]["methods"], serde_json::json!(["rpc_methods"]));
+
+		server.close();
+	}
+}
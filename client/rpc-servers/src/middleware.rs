@@ -0,0 +1,510 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Middleware enforcing the per-connection limits of the RPC servers.
+
+use std::{collections::{HashMap, HashSet}, sync::{Arc, Mutex, MutexGuard}};
+use jsonrpc_core::{
+	Call, Error, ErrorCode, Failure, Id, Middleware, Output, Params, Request, Response, Value, Version,
+	futures::{Future, future::{self, Either}},
+};
+use pubsub::{PubSubMetadata, Session, SubscriptionId};
+use sc_rpc_api::SubscriptionObserver;
+
+/// Error code returned when a batch holds too many calls.
+const BATCH_TOO_LARGE: i64 = -32010;
+/// Error code returned when a response exceeds the maximal response size.
+const RESPONSE_TOO_LARGE: i64 = -32011;
+/// Error code returned when a connection opened too many subscriptions.
+const TOO_MANY_SUBSCRIPTIONS: i64 = -32012;
+
+/// Methods opening a subscription.
+const SUBSCRIBE_METHODS: &[&str] = &[
+	"author_submitAndWatchExtrinsic",
+	"chain_subscribeFinalisedHeads",
+	"chain_subscribeFinalizedHeads",
+	"chain_subscribeNewHead",
+	"chain_subscribeNewHeads",
+	"chain_subscribeRuntimeVersion",
+	"state_subscribeRuntimeVersion",
+	"state_subscribeStorage",
+	"state_subscribeStoragePrefix",
+	"subscribe_newHead",
+];
+
+/// Methods cancelling a subscription.
+const UNSUBSCRIBE_METHODS: &[&str] = &[
+	"author_unwatchExtrinsic",
+	"chain_unsubscribeFinalisedHeads",
+	"chain_unsubscribeFinalizedHeads",
+	"chain_unsubscribeNewHead",
+	"chain_unsubscribeNewHeads",
+	"chain_unsubscribeRuntimeVersion",
+	"state_unsubscribeRuntimeVersion",
+	"state_unsubscribeStorage",
+	"state_unsubscribeStoragePrefix",
+	"unsubscribe_newHead",
+];
+
+/// Limits applied to every connection of the RPC servers.
+///
+/// `None` means that the corresponding limit is not enforced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RpcLimits {
+	/// Maximal size of a request payload, in bytes.
+	pub max_payload: Option<usize>,
+	/// Maximal number of calls in a batch request.
+	pub max_batch_size: Option<usize>,
+	/// Maximal size of the response to a single call, in bytes.
+	pub max_response_size: Option<usize>,
+	/// Maximal number of subscriptions a single connection can keep open.
+	pub max_subscriptions_per_connection: Option<usize>,
+}
+
+/// Whether a method opens or closes a subscription.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SubscriptionCall {
+	Subscribe,
+	Unsubscribe,
+}
+
+/// Classify a method according to the lists of the pub-sub methods.
+fn subscription_call(method: &str) -> Option<SubscriptionCall> {
+	if SUBSCRIBE_METHODS.contains(&method) {
+		Some(SubscriptionCall::Subscribe)
+	} else if UNSUBSCRIBE_METHODS.contains(&method) {
+		Some(SubscriptionCall::Unsubscribe)
+	} else {
+		None
+	}
+}
+
+fn error(code: i64, message: String) -> Error {
+	Error {
+		code: ErrorCode::ServerError(code),
+		message,
+		data: None,
+	}
+}
+
+/// The subscriptions of the connections.
+///
+/// Connections are numbered, rather than identified by the address of their session, which may
+/// be reused by a later connection while the subscriptions of the previous one are ending.
+#[derive(Debug, Default)]
+struct SubscriptionSlots {
+	/// Number of each open connection, keyed by the address of its session.
+	connections: HashMap<usize, u64>,
+	/// Number of the next connection.
+	next_connection: u64,
+	/// Slots taken by each connection, including the subscriptions being opened.
+	taken: HashMap<u64, usize>,
+	/// Connection of each open subscription.
+	open: HashMap<SubscriptionId, u64>,
+	/// Subscriptions reported by a `SubscriptionObserver`, whose end is reported as well.
+	observed: HashSet<SubscriptionId>,
+	/// Observed subscriptions which ended before the response opening them.
+	ended: HashSet<SubscriptionId>,
+}
+
+impl SubscriptionSlots {
+	/// Give back a slot of the given connection.
+	fn release(&mut self, connection: u64) {
+		if let Some(taken) = self.taken.get_mut(&connection) {
+			*taken = taken.saturating_sub(1);
+		}
+	}
+
+	/// The subscription with the given id was opened by the given connection.
+	fn opened(&mut self, connection: u64, id: SubscriptionId) {
+		if self.ended.remove(&id) {
+			self.release(connection);
+		} else {
+			self.open.insert(id, connection);
+		}
+	}
+
+	/// The subscription with the given id was cancelled by its connection.
+	fn cancelled(&mut self, id: &SubscriptionId) {
+		// the slot of an observed subscription is given back once it is reported to have ended.
+		if self.observed.contains(id) {
+			return;
+		}
+		if let Some(connection) = self.open.remove(id) {
+			self.release(connection);
+		}
+	}
+
+	/// The observed subscription with the given id ended.
+	fn ended(&mut self, id: &SubscriptionId) {
+		if !self.observed.remove(id) {
+			return;
+		}
+		match self.open.remove(id) {
+			Some(connection) => self.release(connection),
+			None => {
+				self.ended.insert(id.clone());
+			},
+		}
+	}
+
+	/// The connection was closed.
+	fn closed(&mut self, connection: u64) {
+		self.taken.remove(&connection);
+		// observed subscriptions are cancelled along with the connection, and end later on.
+		let observed = &self.observed;
+		self.open.retain(|id, open| *open != connection || observed.contains(id));
+	}
+}
+
+fn lock(subscriptions: &Mutex<SubscriptionSlots>) -> MutexGuard<SubscriptionSlots> {
+	// the slots stay consistent even if a thread panicked while holding the lock.
+	subscriptions.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// RPC middleware enforcing the `RpcLimits`.
+///
+/// Subscriptions count against the quota of a connection until they are cancelled, they end or
+/// the connection is closed. The end of a subscription is only known if it is reported to the
+/// middleware as a `SubscriptionObserver`, otherwise its slot is given back when it is
+/// cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct RpcMiddleware {
+	limits: RpcLimits,
+	subscriptions: Arc<Mutex<SubscriptionSlots>>,
+}
+
+impl RpcMiddleware {
+	/// Create a new middleware enforcing the given limits.
+	pub fn new(limits: RpcLimits) -> Self {
+		RpcMiddleware {
+			limits,
+			subscriptions: Default::default(),
+		}
+	}
+
+	/// Reserve a subscription slot for the connection of the given session.
+	///
+	/// Returns the number of the connection, or `None` if it already reached its quota.
+	fn reserve_subscription(&self, session: &Arc<Session>, max: usize) -> Option<u64> {
+		let key = &**session as *const Session as usize;
+		let mut slots = lock(&self.subscriptions);
+		let connection = match slots.connections.get(&key) {
+			Some(connection) => *connection,
+			None => {
+				let connection = slots.next_connection;
+				slots.next_connection += 1;
+				slots.connections.insert(key, connection);
+				let subscriptions = self.subscriptions.clone();
+				session.on_drop(move || {
+					let mut slots = lock(&subscriptions);
+					slots.connections.remove(&key);
+					slots.closed(connection);
+				});
+				connection
+			},
+		};
+		let taken = slots.taken.entry(connection).or_insert(0);
+		if *taken >= max {
+			return None;
+		}
+		*taken += 1;
+		Some(connection)
+	}
+
+	fn tracks_subscriptions(&self) -> bool {
+		self.limits.max_subscriptions_per_connection.is_some()
+	}
+}
+
+impl SubscriptionObserver for RpcMiddleware {
+	fn started(&self, id: &SubscriptionId) {
+		if self.tracks_subscriptions() {
+			lock(&self.subscriptions).observed.insert(id.clone());
+		}
+	}
+
+	fn ended(&self, id: &SubscriptionId) {
+		if self.tracks_subscriptions() {
+			lock(&self.subscriptions).ended(id);
+		}
+	}
+}
+
+/// The subscription call tracked by the middleware.
+enum Tracked {
+	/// A subscription is being opened by the given connection.
+	Subscribe(u64),
+	/// The subscription with the given id is being cancelled.
+	Unsubscribe(SubscriptionId),
+}
+
+/// Replace the output with an error if its serialization exceeds `max` bytes.
+fn limit_response_size(output: Output, max: usize) -> Output {
+	let size = serde_json::to_vec(&output).map(|bytes| bytes.len()).unwrap_or(0);
+	if size <= max {
+		return output;
+	}
+
+	let (id, jsonrpc) = match output {
+		Output::Success(success) => (success.id, success.jsonrpc),
+		Output::Failure(failure) => (failure.id, failure.jsonrpc),
+	};
+	Output::Failure(Failure {
+		jsonrpc,
+		error: error(RESPONSE_TOO_LARGE, format!("Response is larger than {} bytes", max)),
+		id,
+	})
+}
+
+impl<M: PubSubMetadata> Middleware<M> for RpcMiddleware {
+	type Future = Box<dyn Future<Item = Option<Response>, Error = ()> + Send>;
+	type CallFuture = Box<dyn Future<Item = Option<Output>, Error = ()> + Send>;
+
+	fn on_request<F, X>(&self, request: Request, meta: M, next: F) -> Either<Self::Future, X> where
+		F: Fn(Request, M) -> X + Send + Sync,
+		X: Future<Item = Option<Response>, Error = ()> + Send + 'static,
+	{
+		match (&request, self.limits.max_batch_size) {
+			(Request::Batch(calls), Some(max)) if calls.len() > max => {
+				let error = error(BATCH_TOO_LARGE, format!("Batch holds more than {} calls", max));
+				Either::A(Box::new(future::ok(Some(Response::from(error, Some(Version::V2))))))
+			},
+			_ => Either::B(next(request, meta)),
+		}
+	}
+
+	fn on_call<F, X>(&self, call: Call, meta: M, next: F) -> Either<Self::CallFuture, X> where
+		F: Fn(Call, M) -> X + Send + Sync,
+		X: Future<Item = Option<Output>, Error = ()> + Send + 'static,
+	{
+		let (kind, id, jsonrpc, cancelled) = match &call {
+			Call::MethodCall(method_call) => {
+				let cancelled = match &method_call.params {
+					Params::Array(params) => params.first().and_then(SubscriptionId::parse_value),
+					_ => None,
+				};
+				(subscription_call(&method_call.method), method_call.id.clone(), method_call.jsonrpc, cancelled)
+			},
+			_ => (None, Id::Null, None, None),
+		};
+		let session = meta.session();
+		let max_subscriptions = self.limits.max_subscriptions_per_connection;
+		let max_response_size = self.limits.max_response_size;
+
+		let tracked = match (kind, session, max_subscriptions) {
+			(Some(SubscriptionCall::Subscribe), Some(session), Some(max)) => {
+				match self.reserve_subscription(&session, max) {
+					Some(connection) => Some(Tracked::Subscribe(connection)),
+					None => {
+						let error = error(
+							TOO_MANY_SUBSCRIPTIONS,
+							format!("Connection cannot open more than {} subscriptions", max),
+						);
+						return Either::A(Box::new(future::ok(Some(Output::from(Err(error), id, jsonrpc)))));
+					},
+				}
+			},
+			(Some(SubscriptionCall::Unsubscribe), Some(_), Some(_)) => cancelled.map(Tracked::Unsubscribe),
+			_ => None,
+		};
+
+		if tracked.is_none() && max_response_size.is_none() {
+			return Either::B(next(call, meta));
+		}
+
+		let subscriptions = self.subscriptions.clone();
+		Either::A(Box::new(next(call, meta).map(move |output| {
+			match (tracked, &output) {
+				(Some(Tracked::Subscribe(connection)), Some(Output::Success(success))) => {
+					let mut slots = lock(&subscriptions);
+					match SubscriptionId::parse_value(&success.result) {
+						Some(id) => slots.opened(connection, id),
+						None => slots.release(connection),
+					}
+				},
+				// the slot reserved by a failed subscription is given back.
+				(Some(Tracked::Subscribe(connection)), _) => lock(&subscriptions).release(connection),
+				(Some(Tracked::Unsubscribe(id)), Some(Output::Success(success)))
+					if success.result == Value::Bool(true) => lock(&subscriptions).cancelled(&id),
+				_ => {},
+			}
+
+			match max_response_size {
+				Some(max) => output.map(|output| limit_response_size(output, max)),
+				None => output,
+			}
+		})))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use jsonrpc_core::futures::sync::mpsc;
+	use pubsub::{PubSubHandler, Subscriber};
+
+	#[derive(Clone, Default)]
+	struct Metadata(Option<Arc<Session>>);
+
+	impl jsonrpc_core::Metadata for Metadata {}
+	impl PubSubMetadata for Metadata {
+		fn session(&self) -> Option<Arc<Session>> {
+			self.0.clone()
+		}
+	}
+
+	fn handler(middleware: RpcMiddleware) -> PubSubHandler<Metadata, RpcMiddleware> {
+		let observer = middleware.clone();
+		let mut io = PubSubHandler::new(jsonrpc_core::MetaIoHandler::with_middleware(middleware));
+		io.add_method("test_echo", |params: jsonrpc_core::Params| {
+			params.parse::<(String,)>().map(|(value,)| Value::String(value))
+		});
+		io.add_subscription(
+			"test_notification",
+			("state_subscribeStorage", |_params, _meta, subscriber: Subscriber| {
+				let _ = subscriber.assign_id(SubscriptionId::Number(5));
+			}),
+			("state_unsubscribeStorage", |_id: SubscriptionId, _meta: Option<Metadata>| {
+				Ok::<_, Error>(Value::Bool(true))
+			}),
+		);
+		// subscriptions reporting their end, as driven by `Subscriptions`.
+		io.add_subscription(
+			"test_observed",
+			("chain_subscribeNewHeads", move |_params, _meta, subscriber: Subscriber| {
+				let id = SubscriptionId::Number(7);
+				observer.started(&id);
+				let _ = subscriber.assign_id(id);
+			}),
+			("chain_unsubscribeNewHeads", |_id: SubscriptionId, _meta: Option<Metadata>| {
+				Ok::<_, Error>(Value::Bool(true))
+			}),
+		);
+		io
+	}
+
+	fn limited_subscriptions() -> RpcMiddleware {
+		RpcMiddleware::new(RpcLimits { max_subscriptions_per_connection: Some(1), ..Default::default() })
+	}
+
+	fn connection() -> Metadata {
+		let (tx, _rx) = mpsc::channel(1);
+		Metadata(Some(Arc::new(Session::new(tx))))
+	}
+
+	fn request(io: &PubSubHandler<Metadata, RpcMiddleware>, meta: &Metadata, request: &str) -> String {
+		io.handle_request_sync(request, meta.clone()).expect("Every request has a response")
+	}
+
+	#[test]
+	fn should_classify_subscription_calls() {
+		assert_eq!(subscription_call("chain_subscribeNewHeads"), Some(SubscriptionCall::Subscribe));
+		assert_eq!(subscription_call("subscribe_newHead"), Some(SubscriptionCall::Subscribe));
+		assert_eq!(subscription_call("author_submitAndWatchExtrinsic"), Some(SubscriptionCall::Subscribe));
+		assert_eq!(subscription_call("state_unsubscribeStorage"), Some(SubscriptionCall::Unsubscribe));
+		assert_eq!(subscription_call("author_unwatchExtrinsic"), Some(SubscriptionCall::Unsubscribe));
+		assert_eq!(subscription_call("author_submitExtrinsic"), None);
+		assert_eq!(subscription_call("system_unsubscribedPeers"), None);
+	}
+
+	#[test]
+	fn should_reject_large_batches() {
+		let io = handler(RpcMiddleware::new(RpcLimits { max_batch_size: Some(1), ..Default::default() }));
+		let meta = Metadata::default();
+		let call = r#"{"jsonrpc":"2.0","method":"test_echo","params":["a"],"id":1}"#;
+
+		assert_eq!(
+			request(&io, &meta, &format!("[{}]", call)),
+			r#"[{"jsonrpc":"2.0","result":"a","id":1}]"#,
+		);
+		assert_eq!(
+			request(&io, &meta, &format!("[{},{}]", call, call)),
+			r#"{"jsonrpc":"2.0","error":{"code":-32010,"message":"Batch holds more than 1 calls"},"id":null}"#,
+		);
+	}
+
+	#[test]
+	fn should_reject_large_responses() {
+		let io = handler(RpcMiddleware::new(RpcLimits { max_response_size: Some(48), ..Default::default() }));
+		let meta = Metadata::default();
+
+		assert_eq!(
+			request(&io, &meta, r#"{"jsonrpc":"2.0","method":"test_echo","params":["a"],"id":1}"#),
+			r#"{"jsonrpc":"2.0","result":"a","id":1}"#,
+		);
+		assert_eq!(
+			request(&io, &meta, r#"{"jsonrpc":"2.0","method":"test_echo","params":["aaaaaaaaaaaaaaaaaaaa"],"id":1}"#),
+			r#"{"jsonrpc":"2.0","error":{"code":-32011,"message":"Response is larger than 48 bytes"},"id":1}"#,
+		);
+	}
+
+	#[test]
+	fn should_limit_subscriptions_per_connection() {
+		let io = handler(limited_subscriptions());
+		let subscribe = r#"{"jsonrpc":"2.0","method":"state_subscribeStorage","params":[],"id":1}"#;
+		let unsubscribe = r#"{"jsonrpc":"2.0","method":"state_unsubscribeStorage","params":[5],"id":2}"#;
+		let subscribed = r#"{"jsonrpc":"2.0","result":5,"id":1}"#;
+		let rejected = r#"{"jsonrpc":"2.0","error":{"code":-32012,"message":"Connection cannot open more than 1 subscriptions"},"id":1}"#;
+
+		let first = connection();
+		assert_eq!(request(&io, &first, subscribe), subscribed);
+		assert_eq!(request(&io, &first, subscribe), rejected);
+
+		// the quota is per connection.
+		let second = connection();
+		assert_eq!(request(&io, &second, subscribe), subscribed);
+
+		// cancelling a subscription frees its slot.
+		assert_eq!(request(&io, &first, unsubscribe), r#"{"jsonrpc":"2.0","result":true,"id":2}"#);
+		assert_eq!(request(&io, &first, subscribe), subscribed);
+	}
+
+	#[test]
+	fn should_free_the_slot_of_a_subscription_ended_by_the_server() {
+		let middleware = limited_subscriptions();
+		let io = handler(middleware.clone());
+		let subscribe = r#"{"jsonrpc":"2.0","method":"chain_subscribeNewHeads","params":[],"id":1}"#;
+		let subscribed = r#"{"jsonrpc":"2.0","result":7,"id":1}"#;
+
+		let meta = connection();
+		assert_eq!(request(&io, &meta, subscribe), subscribed);
+		assert!(request(&io, &meta, subscribe).contains("-32012"));
+
+		// the stream of notifications is over.
+		middleware.ended(&SubscriptionId::Number(7));
+		assert_eq!(request(&io, &meta, subscribe), subscribed);
+	}
+
+	#[test]
+	fn should_free_the_slot_of_an_observed_subscription_once_it_ends() {
+		let middleware = limited_subscriptions();
+		let io = handler(middleware.clone());
+		let subscribe = r#"{"jsonrpc":"2.0","method":"chain_subscribeNewHeads","params":[],"id":1}"#;
+		let unsubscribe = r#"{"jsonrpc":"2.0","method":"chain_unsubscribeNewHeads","params":[7],"id":2}"#;
+		let subscribed = r#"{"jsonrpc":"2.0","result":7,"id":1}"#;
+
+		let meta = connection();
+		assert_eq!(request(&io, &meta, subscribe), subscribed);
+		assert_eq!(request(&io, &meta, unsubscribe), r#"{"jsonrpc":"2.0","result":true,"id":2}"#);
+
+		// the cancelled subscription ends, and only then gives back its slot, once.
+		middleware.ended(&SubscriptionId::Number(7));
+		middleware.ended(&SubscriptionId::Number(7));
+		assert_eq!(request(&io, &meta, subscribe), subscribed);
+		assert!(request(&io, &meta, subscribe).contains("-32012"));
+	}
+}
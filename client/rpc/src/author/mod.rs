@@ -19,7 +19,7 @@
 #[cfg(test)]
 mod tests;
 
-use std::{sync::Arc, convert::TryInto};
+use std::{sync::Arc, convert::TryInto, collections::{BTreeMap, HashMap}};
 use log::warn;
 
 use sc_client::Client;
@@ -30,15 +30,19 @@ use rpc::futures::{
 	future::result,
 };
 use futures::{StreamExt as _, compat::Compat};
-use futures::future::{ready, FutureExt, TryFutureExt};
-use sc_rpc_api::Subscriptions;
+use futures::future::{ready, Either, FutureExt, TryFutureExt};
+use sc_rpc_api::{DenyUnsafe, Subscriptions};
 use jsonrpc_pubsub::{typed::Subscriber, SubscriptionId};
 use codec::{Encode, Decode};
 use sp_core::{Bytes, Blake2Hasher, H256, traits::BareCryptoStorePtr};
 use sp_api::ConstructRuntimeApi;
-use sp_runtime::{generic, traits::{self, ProvideRuntimeApi}};
+use sp_runtime::{
+	generic, traits::{self, ProvideRuntimeApi},
+	transaction_validity::{TransactionTag, TransactionSource},
+};
+use sc_transaction_pool::LocalTransactions;
 use sp_transaction_pool::{
-	TransactionPool, InPoolTransaction, TransactionStatus,
+	TransactionPool, InPoolTransaction, TransactionStatus, TransactionStatusStreamFor,
 	BlockHash, TxHash, TransactionFor, error::IntoPoolError,
 };
 use sp_session::SessionKeys;
@@ -57,6 +61,10 @@ pub struct Author<B, E, P, Block: traits::Block, RA> {
 	subscriptions: Subscriptions,
 	/// The key store.
 	keystore: BareCryptoStorePtr,
+	/// The persisted local transactions, if enabled.
+	local_transactions: Option<Arc<LocalTransactions<Client<B, E, Block, RA>, Block>>>,
+	/// Whether to deny unsafe calls
+	deny_unsafe: DenyUnsafe,
 }
 
 impl<B, E, P, Block: traits::Block, RA> Author<B, E, P, Block, RA> {
@@ -66,14 +74,27 @@ impl<B, E, P, Block: traits::Block, RA> Author<B, E, P, Block, RA> {
 		pool: Arc<P>,
 		subscriptions: Subscriptions,
 		keystore: BareCryptoStorePtr,
+		deny_unsafe: DenyUnsafe,
 	) -> Self {
 		Author {
 			client,
 			pool,
 			subscriptions,
 			keystore,
+			local_transactions: None,
+			deny_unsafe,
 		}
 	}
+
+	/// Persist the submitted transactions until they are finalized, so that they are submitted
+	/// again after a restart.
+	pub fn with_local_transactions(
+		mut self,
+		local_transactions: Arc<LocalTransactions<Client<B, E, Block, RA>, Block>>,
+	) -> Self {
+		self.local_transactions = Some(local_transactions);
+		self
+	}
 }
 
 impl<B, E, P, Block, RA> AuthorApi<Block::Hash, Block::Hash> for Author<B, E, P, Block, RA> where
@@ -94,6 +115,8 @@ impl<B, E, P, Block, RA> AuthorApi<Block::Hash, Block::Hash> for Author<B, E, P,
 		suri: String,
 		public: Bytes,
 	) -> Result<()> {
+		self.deny_unsafe.check_if_safe()?;
+
 		let key_type = key_type.as_str().try_into().map_err(|_| Error::BadKeyType)?;
 		let mut keystore = self.keystore.write();
 		keystore.insert_unknown(key_type, &suri, &public[..])
@@ -102,6 +125,8 @@ impl<B, E, P, Block, RA> AuthorApi<Block::Hash, Block::Hash> for Author<B, E, P,
 	}
 
 	fn rotate_keys(&self) -> Result<Bytes> {
+		self.deny_unsafe.check_if_safe()?;
+
 		let best_block_hash = self.client.info().chain.best_hash;
 		self.client.runtime_api().generate_session_keys(
 			&generic::BlockId::Hash(best_block_hash),
@@ -115,8 +140,30 @@ impl<B, E, P, Block, RA> AuthorApi<Block::Hash, Block::Hash> for Author<B, E, P,
 			Err(err) => return Box::new(result(Err(err.into()))),
 		};
 		let best_block_hash = self.client.info().chain.best_hash;
-		Box::new(self.pool
-			.submit_one(&generic::BlockId::hash(best_block_hash), xt)
+		let at = generic::BlockId::hash(best_block_hash);
+		let submission = match self.local_transactions.clone() {
+			Some(local_transactions) => {
+				// watch the transaction to know when to forget it.
+				let hash = self.pool.hash_of(&xt);
+				let subscriptions = self.subscriptions.clone();
+				Either::Left(self.pool
+					.submit_and_watch(&at, TransactionSource::External, xt.clone())
+					.map_ok(move |statuses| {
+						local_transactions.insert(hash, xt);
+						let track = local_transactions.track(hash, statuses);
+						let res = subscriptions.executor()
+							.execute(Box::new(Compat::new(track.map(|_| Ok(())))));
+						if res.is_err() {
+							warn!("Error spawning local transaction tracking task.");
+						}
+						hash
+					})
+				)
+			},
+			None => Either::Right(self.pool.submit_one(&at, TransactionSource::External, xt)),
+		};
+
+		Box::new(submission
 			.compat()
 			.map_err(|e| e.into_pool_error()
 				.map(Into::into)
@@ -128,10 +175,16 @@ impl<B, E, P, Block, RA> AuthorApi<Block::Hash, Block::Hash> for Author<B, E, P,
 		Ok(self.pool.ready().map(|tx| tx.data().encode().into()).collect())
 	}
 
+	fn pending_extrinsics_groups(&self) -> Result<Vec<pending::PendingExtrinsics<TxHash<P>>>> {
+		Ok(group_pending_extrinsics(self.pool.ready(), self.pool.futures()))
+	}
+
 	fn remove_extrinsic(
 		&self,
 		bytes_or_hash: Vec<hash::ExtrinsicOrHash<TxHash<P>>>,
 	) -> Result<Vec<TxHash<P>>> {
+		self.deny_unsafe.check_if_safe()?;
+
 		let hashes = bytes_or_hash.into_iter()
 			.map(|x| match x {
 				hash::ExtrinsicOrHash::Hash(h) => Ok(h),
@@ -156,17 +209,30 @@ impl<B, E, P, Block, RA> AuthorApi<Block::Hash, Block::Hash> for Author<B, E, P,
 		subscriber: Subscriber<TransactionStatus<TxHash<P>, BlockHash<P>>>,
 		xt: Bytes,
 	) {
+		let local_transactions = self.local_transactions.clone();
 		let submit = || -> Result<_> {
 			let best_block_hash = self.client.info().chain.best_hash;
 			let dxt = TransactionFor::<P>::decode(&mut &xt[..])
 				.map_err(error::Error::from)?;
+			let local = local_transactions.map(|local| (local, self.pool.hash_of(&dxt), dxt.clone()));
 			Ok(
 				self.pool
-					.submit_and_watch(&generic::BlockId::hash(best_block_hash), dxt)
+					.submit_and_watch(
+						&generic::BlockId::hash(best_block_hash),
+						TransactionSource::External,
+						dxt,
+					)
 					.map_err(|e| e.into_pool_error()
 						.map(error::Error::from)
 						.unwrap_or_else(|e| error::Error::Verification(Box::new(e)).into())
 					)
+					.map_ok(move |watcher| match local {
+						Some((local, hash, dxt)) => {
+							local.insert(hash, dxt);
+							Box::new(local.watch(hash, watcher)) as Box<TransactionStatusStreamFor<P>>
+						},
+						None => watcher,
+					})
 			)
 		};
 
@@ -204,3 +270,53 @@ impl<B, E, P, Block, RA> AuthorApi<Block::Hash, Block::Hash> for Author<B, E, P,
 		Ok(self.subscriptions.cancel(id))
 	}
 }
+
+/// Group the ready transactions by chains of transactions depending on each other, and the
+/// future ones by the tags they wait for.
+fn group_pending_extrinsics<T>(
+	ready: impl Iterator<Item=Arc<T>>,
+	futures: Vec<(Arc<T>, Vec<TransactionTag>)>,
+) -> Vec<pending::PendingExtrinsics<T::Hash>> where
+	T: InPoolTransaction,
+	T::Transaction: Encode,
+	T::Hash: Clone,
+{
+	let pending_extrinsic = |tx: &T| pending::PendingExtrinsic {
+		hash: tx.hash().clone(),
+		extrinsic: tx.data().encode().into(),
+		priority: *tx.priority(),
+		requires: tx.requires().iter().cloned().map(Bytes).collect(),
+		provides: tx.provides().iter().cloned().map(Bytes).collect(),
+	};
+
+	// ready transactions come after the ones providing the tags they require.
+	let mut groups = Vec::new();
+	let mut group_of_tag = HashMap::<TransactionTag, usize>::new();
+	for tx in ready {
+		let group = match tx.requires().iter().find_map(|tag| group_of_tag.get(tag)) {
+			Some(group) => *group,
+			None => {
+				groups.push(pending::PendingExtrinsics {
+					reason: pending::PendingReason::Ready,
+					extrinsics: Vec::new(),
+				});
+				groups.len() - 1
+			},
+		};
+		for tag in tx.provides() {
+			group_of_tag.insert(tag.clone(), group);
+		}
+		groups[group].extrinsics.push(pending_extrinsic(&*tx));
+	}
+
+	let mut waiting = BTreeMap::<Vec<TransactionTag>, Vec<_>>::new();
+	for (tx, tags) in futures {
+		waiting.entry(tags).or_default().push(pending_extrinsic(&*tx));
+	}
+	groups.extend(waiting.into_iter().map(|(tags, extrinsics)| pending::PendingExtrinsics {
+		reason: pending::PendingReason::Future(tags.into_iter().map(Bytes).collect()),
+		extrinsics,
+	}));
+
+	groups
+}
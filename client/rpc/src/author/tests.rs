@@ -74,6 +74,8 @@ impl TestSetup {
 			pool: self.pool.clone(),
 			subscriptions: Subscriptions::new(Arc::new(self.runtime.executor())),
 			keystore: self.keystore.clone(),
+			local_transactions: None,
+			deny_unsafe: DenyUnsafe::No,
 		}
 	}
 }
@@ -172,6 +174,56 @@ fn should_return_pending_extrinsics() {
 	);
 }
 
+#[test]
+fn should_return_pending_extrinsics_groups() {
+	let p = TestSetup::default().author();
+
+	let alice = (0..2).map(|nonce| uxt(AccountKeyring::Alice, nonce)).collect::<Vec<_>>();
+	let bob = uxt(AccountKeyring::Bob, 0);
+	let alice_future = uxt(AccountKeyring::Alice, 5);
+	for ex in alice.iter().chain(Some(&bob)).chain(Some(&alice_future)) {
+		AuthorApi::submit_extrinsic(&p, ex.encode().into()).wait().unwrap();
+	}
+
+	let groups = p.pending_extrinsics_groups().unwrap();
+	let extrinsics = |group: &pending::PendingExtrinsics<H256>| group.extrinsics.iter()
+		.map(|ex| ex.extrinsic.clone())
+		.collect::<Vec<_>>();
+
+	assert_eq!(groups.len(), 3);
+	let mut ready = groups[..2].iter().map(|group| {
+		assert_eq!(group.reason, pending::PendingReason::Ready);
+		extrinsics(group)
+	}).collect::<Vec<_>>();
+	ready.sort_by_key(|group| group.len());
+	assert_eq!(ready, vec![
+		vec![Bytes(bob.encode())],
+		alice.iter().map(|ex| Bytes(ex.encode())).collect(),
+	]);
+
+	let alice_id: substrate_test_runtime_client::runtime::AccountId = AccountKeyring::Alice.into();
+	assert_eq!(
+		groups[2].reason,
+		pending::PendingReason::Future(vec![Bytes((alice_id, 4u64).encode())]),
+	);
+	assert_eq!(extrinsics(&groups[2]), vec![Bytes(alice_future.encode())]);
+}
+
+#[test]
+fn should_persist_local_extrinsics() {
+	let setup = TestSetup::default();
+	let local = Arc::new(LocalTransactions::load(setup.client.clone()).unwrap());
+	let p = setup.author().with_local_transactions(local);
+	let xt = uxt(AccountKeyring::Alice, 0);
+
+	// when
+	AuthorApi::submit_extrinsic(&p, xt.encode().into()).wait().unwrap();
+
+	// then
+	let persisted = LocalTransactions::<_, Block>::load(setup.client.clone()).unwrap();
+	assert_eq!(persisted.transactions(), vec![xt]);
+}
+
 #[test]
 fn should_remove_extrinsics() {
 	let setup = TestSetup::default();
@@ -237,3 +289,24 @@ fn should_rotate_keys() {
 	assert_eq!(session_keys.ed25519, ed25519_key_pair.public().into());
 	assert_eq!(session_keys.sr25519, sr25519_key_pair.public().into());
 }
+
+#[test]
+fn should_deny_unsafe_calls() {
+	let setup = TestSetup::default();
+	let p = Author {
+		deny_unsafe: DenyUnsafe::Yes,
+		..setup.author()
+	};
+
+	assert_matches!(p.rotate_keys(), Err(Error::UnsafeRpcCalled(_)));
+	assert_matches!(
+		p.insert_key(
+			String::from_utf8(ED25519.0.to_vec()).expect("Keytype is a valid string"),
+			"//Alice".into(),
+			Default::default(),
+		),
+		Err(Error::UnsafeRpcCalled(_))
+	);
+	assert_matches!(p.remove_extrinsic(vec![]), Err(Error::UnsafeRpcCalled(_)));
+	assert!(setup.keystore.read().ed25519_public_keys(ED25519).is_empty());
+}
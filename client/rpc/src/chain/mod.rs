@@ -40,7 +40,7 @@ use sp_core::{H256, Blake2Hasher};
 use sp_rpc::{number::NumberOrHex, list::ListOrValue};
 use sp_runtime::{
 	generic::{BlockId, SignedBlock},
-	traits::{Block as BlockT, Header, NumberFor},
+	traits::{Block as BlockT, Header, NumberFor, One},
 };
 
 use self::error::{Result, Error, FutureResult};
@@ -90,6 +90,34 @@ trait ChainBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 		})
 	}
 
+	/// Get headers of a range of blocks in the canon chain.
+	fn header_range(
+		&self,
+		from: NumberOrHex<NumberFor<Block>>,
+		to: NumberOrHex<NumberFor<Block>>,
+		limit: Option<u32>,
+	) -> Result<Vec<Block::Header>> {
+		let from = from.to_number()?;
+		let to = to.to_number()?;
+		if from > to {
+			return Err(Error::Other("from number > to number".into()));
+		}
+
+		let limit = limit.unwrap_or(MAX_HEADER_RANGE).min(MAX_HEADER_RANGE) as usize;
+		let to = to.min(self.client().info().chain.best_number);
+		let mut headers = Vec::new();
+		let mut number = from;
+		while number <= to && headers.len() < limit {
+			match self.client().header(&BlockId::number(number)).map_err(client_err)? {
+				Some(header) => headers.push(header),
+				None => break,
+			}
+			number += One::one();
+		}
+
+		Ok(headers)
+	}
+
 	/// Get hash of the last finalized block in the canon chain.
 	fn finalized_head(&self) -> Result<Block::Hash> {
 		Ok(self.client().info().chain.finalized_hash)
@@ -206,9 +234,29 @@ impl<B, E, Block, RA> ChainApi<NumberFor<Block>, Block::Hash, Block::Header, Sig
 		self.backend.header(hash)
 	}
 
-	fn block(&self, hash: Option<Block::Hash>) -> FutureResult<Option<SignedBlock<Block>>>
-	{
-		self.backend.block(hash)
+	fn header_range(
+		&self,
+		from: NumberOrHex<NumberFor<Block>>,
+		to: NumberOrHex<NumberFor<Block>>,
+		limit: Option<u32>,
+	) -> Result<Vec<Block::Header>> {
+		self.backend.header_range(from, to, limit)
+	}
+
+	fn block(
+		&self,
+		hash: Option<Block::Hash>,
+		justification: Option<bool>,
+	) -> FutureResult<Option<SignedBlock<Block>>> {
+		let block = self.backend.block(hash);
+		if justification.unwrap_or(true) {
+			return block;
+		}
+
+		Box::new(block.map(|block| block.map(|block| SignedBlock {
+			justification: None,
+			..block
+		})))
 	}
 
 	fn block_hash(
@@ -22,6 +22,7 @@ use substrate_test_runtime_client::{
 	runtime::{H256, Block, Header},
 };
 use sp_rpc::list::ListOrValue;
+use sp_runtime::traits::Header as _;
 
 #[test]
 fn should_return_header() {
@@ -73,12 +74,12 @@ fn should_return_a_block() {
 
 	// Genesis block is not justified
 	assert_matches!(
-		api.block(Some(client.genesis_hash()).into()).wait(),
+		api.block(Some(client.genesis_hash()).into(), None).wait(),
 		Ok(Some(SignedBlock { justification: None, .. }))
 	);
 
 	assert_matches!(
-		api.block(Some(block_hash).into()).wait(),
+		api.block(Some(block_hash).into(), None).wait(),
 		Ok(Some(ref x)) if x.block == Block {
 			header: Header {
 				parent_hash: client.genesis_hash(),
@@ -92,7 +93,7 @@ fn should_return_a_block() {
 	);
 
 	assert_matches!(
-		api.block(None.into()).wait(),
+		api.block(None.into(), None).wait(),
 		Ok(Some(ref x)) if x.block == Block {
 			header: Header {
 				parent_hash: client.genesis_hash(),
@@ -106,11 +107,63 @@ fn should_return_a_block() {
 	);
 
 	assert_matches!(
-		api.block(Some(H256::from_low_u64_be(5)).into()).wait(),
+		api.block(Some(H256::from_low_u64_be(5)).into(), None).wait(),
 		Ok(None)
 	);
 }
 
+#[test]
+fn should_return_a_block_without_its_justification() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+
+	let client = Arc::new(substrate_test_runtime_client::new());
+	let api = new_full(client.clone(), Subscriptions::new(Arc::new(remote)));
+
+	let block = client.new_block(Default::default()).unwrap().bake().unwrap();
+	let block_hash = block.hash();
+	client.import_justified(BlockOrigin::Own, block, vec![1, 2, 3]).unwrap();
+
+	assert_matches!(
+		api.block(Some(block_hash).into(), None).wait(),
+		Ok(Some(SignedBlock { justification: Some(ref j), .. })) if j == &[1, 2, 3]
+	);
+	assert_matches!(
+		api.block(Some(block_hash).into(), Some(true)).wait(),
+		Ok(Some(SignedBlock { justification: Some(ref j), .. })) if j == &[1, 2, 3]
+	);
+	assert_matches!(
+		api.block(Some(block_hash).into(), Some(false)).wait(),
+		Ok(Some(SignedBlock { justification: None, ref block })) if block.header.hash() == block_hash
+	);
+}
+
+#[test]
+fn should_return_header_range() {
+	let core = ::tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+
+	let client = Arc::new(substrate_test_runtime_client::new());
+	let api = new_full(client.clone(), Subscriptions::new(Arc::new(remote)));
+
+	let mut hashes = vec![client.genesis_hash()];
+	for _ in 0..3 {
+		let block = client.new_block(Default::default()).unwrap().bake().unwrap();
+		hashes.push(block.hash());
+		client.import(BlockOrigin::Own, block).unwrap();
+	}
+	let range = |from: u64, to: u64, limit: Option<u32>| api
+		.header_range(from.into(), to.into(), limit)
+		.map(|headers| headers.iter().map(|header| header.hash()).collect::<Vec<_>>());
+
+	assert_eq!(range(0, 3, None).unwrap(), hashes);
+	assert_eq!(range(1, 1, None).unwrap(), &hashes[1..2]);
+	assert_eq!(range(1, 3, Some(2)).unwrap(), &hashes[1..3]);
+	// the range stops at the best block.
+	assert_eq!(range(2, 10, None).unwrap(), &hashes[2..]);
+	assert_matches!(range(2, 1, None), Err(Error::Other(_)));
+}
+
 #[test]
 fn should_return_block_hash() {
 	let core = ::tokio::runtime::Runtime::new().unwrap();
@@ -22,11 +22,12 @@
 
 mod metadata;
 
-pub use sc_rpc_api::Subscriptions;
+pub use sc_rpc_api::{DenyUnsafe, Subscriptions};
 pub use self::metadata::Metadata;
 pub use rpc::IoHandlerExtension as RpcExtension;
 
 pub mod author;
 pub mod chain;
+pub mod offchain;
 pub mod state;
 pub mod system;
@@ -42,6 +42,7 @@ use sp_api::Metadata;
 use self::error::{Error, FutureResult};
 
 pub use sc_rpc_api::state::*;
+pub use sc_rpc_api::child_state::*;
 
 /// State backend API.
 pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
@@ -66,6 +67,15 @@ pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 		prefix: StorageKey,
 	) -> FutureResult<Vec<StorageKey>>;
 
+	/// Returns at most `count` keys with prefix, starting after `start_key` if given.
+	fn storage_keys_paged(
+		&self,
+		block: Option<Block::Hash>,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageKey>>;
+
 	/// Returns a storage entry at a specific block's state.
 	fn storage(
 		&self,
@@ -178,13 +188,31 @@ pub trait StateBackend<B, E, Block: BlockT, RA>: Send + Sync + 'static
 		_meta: Option<crate::metadata::Metadata>,
 		id: SubscriptionId,
 	) -> RpcResult<bool>;
+
+	/// New storage subscription to all the keys starting with the given prefix
+	fn subscribe_storage_prefix(
+		&self,
+		_meta: crate::metadata::Metadata,
+		subscriber: Subscriber<StorageChangeSet<Block::Hash>>,
+		prefix: StorageKey,
+		page_size: u32,
+	);
+
+	/// Unsubscribe from storage prefix subscription
+	fn unsubscribe_storage_prefix(
+		&self,
+		_meta: Option<crate::metadata::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool>;
 }
 
 /// Create new state API that works on full node.
+///
+/// The returned child state API shares the backend of the state API.
 pub fn new_full<B, E, Block: BlockT, RA>(
 	client: Arc<Client<B, E, Block, RA>>,
 	subscriptions: Subscriptions,
-) -> State<B, E, Block, RA>
+) -> (State<B, E, Block, RA>, ChildState<B, E, Block, RA>)
 	where
 		Block: BlockT<Hash=H256> + 'static,
 		B: sc_client_api::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
@@ -194,18 +222,20 @@ pub fn new_full<B, E, Block: BlockT, RA>(
 		<Client<B, E, Block, RA> as ProvideRuntimeApi>::Api:
 			Metadata<Block, Error = sp_blockchain::Error>,
 {
-	State {
-		backend: Box::new(self::state_full::FullState::new(client, subscriptions)),
-	}
+	let backend: Arc<dyn StateBackend<B, E, Block, RA>> =
+		Arc::new(self::state_full::FullState::new(client, subscriptions));
+	(State { backend: backend.clone() }, ChildState { backend })
 }
 
 /// Create new state API that works on light node.
+///
+/// The returned child state API shares the backend of the state API.
 pub fn new_light<B, E, Block: BlockT, RA, F: Fetcher<Block>>(
 	client: Arc<Client<B, E, Block, RA>>,
 	subscriptions: Subscriptions,
 	remote_blockchain: Arc<dyn RemoteBlockchain<Block>>,
 	fetcher: Arc<F>,
-) -> State<B, E, Block, RA>
+) -> (State<B, E, Block, RA>, ChildState<B, E, Block, RA>)
 	where
 		Block: BlockT<Hash=H256> + 'static,
 		B: sc_client_api::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
@@ -213,19 +243,77 @@ pub fn new_light<B, E, Block: BlockT, RA, F: Fetcher<Block>>(
 		RA: Send + Sync + 'static,
 		F: Send + Sync + 'static,
 {
-	State {
-		backend: Box::new(self::state_light::LightState::new(
+	let backend: Arc<dyn StateBackend<B, E, Block, RA>> = Arc::new(
+		self::state_light::LightState::new(
 			client,
 			subscriptions,
 			remote_blockchain,
 			fetcher,
-		)),
-	}
+		)
+	);
+	(State { backend: backend.clone() }, ChildState { backend })
 }
 
 /// State API with subscriptions support.
 pub struct State<B, E, Block, RA> {
-	backend: Box<dyn StateBackend<B, E, Block, RA>>,
+	backend: Arc<dyn StateBackend<B, E, Block, RA>>,
+}
+
+/// Child state API.
+pub struct ChildState<B, E, Block, RA> {
+	backend: Arc<dyn StateBackend<B, E, Block, RA>>,
+}
+
+impl<B, E, Block, RA> ChildStateApi<Block::Hash> for ChildState<B, E, Block, RA>
+	where
+		Block: BlockT<Hash=H256> + 'static,
+		B: sc_client_api::backend::Backend<Block, Blake2Hasher> + Send + Sync + 'static,
+		E: CallExecutor<Block, Blake2Hasher> + Send + Sync + 'static + Clone,
+		RA: Send + Sync + 'static,
+{
+	fn storage_keys(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		key_prefix: StorageKey,
+		block: Option<Block::Hash>
+	) -> FutureResult<Vec<StorageKey>> {
+		self.backend.child_storage_keys(block, child_storage_key, child_info, child_type, key_prefix)
+	}
+
+	fn storage(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		key: StorageKey,
+		block: Option<Block::Hash>
+	) -> FutureResult<Option<StorageData>> {
+		self.backend.child_storage(block, child_storage_key, child_info, child_type, key)
+	}
+
+	fn storage_hash(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		key: StorageKey,
+		block: Option<Block::Hash>
+	) -> FutureResult<Option<Block::Hash>> {
+		self.backend.child_storage_hash(block, child_storage_key, child_info, child_type, key)
+	}
+
+	fn storage_size(
+		&self,
+		child_storage_key: StorageKey,
+		child_info: StorageKey,
+		child_type: u32,
+		key: StorageKey,
+		block: Option<Block::Hash>
+	) -> FutureResult<Option<u64>> {
+		self.backend.child_storage_size(block, child_storage_key, child_info, child_type, key)
+	}
 }
 
 impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
@@ -249,6 +337,21 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
 		self.backend.storage_keys(block, key_prefix)
 	}
 
+	fn storage_keys_paged(
+		&self,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+		block: Option<Block::Hash>,
+	) -> FutureResult<Vec<StorageKey>> {
+		if count > MAX_KEYS_PAGE_SIZE {
+			return Box::new(rpc::futures::future::err(
+				Error::InvalidCount { count, max: MAX_KEYS_PAGE_SIZE }
+			));
+		}
+		self.backend.storage_keys_paged(block, prefix, count, start_key)
+	}
+
 	fn storage(&self, key: StorageKey, block: Option<Block::Hash>) -> FutureResult<Option<StorageData>> {
 		self.backend.storage(block, key)
 	}
@@ -324,6 +427,11 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
 		subscriber: Subscriber<StorageChangeSet<Block::Hash>>,
 		keys: Option<Vec<StorageKey>>
 	) {
+		if keys.as_ref().map_or(false, |keys| keys.len() > MAX_SUBSCRIPTION_KEYS) {
+			let _ = subscriber.reject(Error::TooManyKeys { limit: MAX_SUBSCRIPTION_KEYS }.into());
+			return;
+		}
+
 		self.backend.subscribe_storage(meta, subscriber, keys);
 	}
 
@@ -331,6 +439,28 @@ impl<B, E, Block, RA> StateApi<Block::Hash> for State<B, E, Block, RA>
 		self.backend.unsubscribe_storage(meta, id)
 	}
 
+	fn subscribe_storage_prefix(
+		&self,
+		meta: Self::Metadata,
+		subscriber: Subscriber<StorageChangeSet<Block::Hash>>,
+		prefix: StorageKey,
+		page_size: Option<u32>
+	) {
+		let page_size = page_size
+			.unwrap_or(DEFAULT_SNAPSHOT_PAGE_SIZE)
+			.max(1)
+			.min(MAX_SNAPSHOT_PAGE_SIZE);
+		self.backend.subscribe_storage_prefix(meta, subscriber, prefix, page_size);
+	}
+
+	fn unsubscribe_storage_prefix(
+		&self,
+		meta: Option<Self::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool> {
+		self.backend.unsubscribe_storage_prefix(meta, id)
+	}
+
 	fn runtime_version(&self, at: Option<Block::Hash>) -> FutureResult<RuntimeVersion> {
 		self.backend.runtime_version(at)
 	}
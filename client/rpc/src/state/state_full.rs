@@ -28,7 +28,7 @@ use rpc::{
 };
 
 use sc_rpc_api::Subscriptions;
-use sc_client_api::backend::Backend;
+use sc_client_api::backend::{Backend, StorageProvider};
 use sp_blockchain::{
 	Result as ClientResult, Error as ClientError, HeaderMetadata, CachedHeaderMetadata
 };
@@ -48,7 +48,10 @@ use sp_runtime::{
 
 use sp_api::Metadata;
 
-use super::{StateBackend, error::{FutureResult, Error, Result}, client_err, child_resolution_error};
+use super::{
+	StateBackend, MAX_SUBSCRIPTION_KEYS, error::{FutureResult, Error, Result}, client_err,
+	child_resolution_error,
+};
 
 /// Ranges to query in state_queryStorage.
 struct QueryStorageRange<Block: BlockT> {
@@ -64,6 +67,47 @@ struct QueryStorageRange<Block: BlockT> {
 	pub filtered_range: Option<Range<usize>>,
 }
 
+/// Blocks whose state is pinned until the guard is dropped.
+struct PinnedBlocks<'a, B, E, Block: BlockT, RA>
+	where
+		Block: BlockT<Hash=H256>,
+		B: Backend<Block, Blake2Hasher>,
+		E: CallExecutor<Block, Blake2Hasher>,
+{
+	client: &'a Client<B, E, Block, RA>,
+	hashes: Vec<Block::Hash>,
+}
+
+impl<'a, B, E, Block, RA> PinnedBlocks<'a, B, E, Block, RA>
+	where
+		Block: BlockT<Hash=H256>,
+		B: Backend<Block, Blake2Hasher>,
+		E: CallExecutor<Block, Blake2Hasher>,
+{
+	/// Pin the state of the given blocks, failing if the state of one of them is not available.
+	fn pin(client: &'a Client<B, E, Block, RA>, hashes: &[Block::Hash]) -> ClientResult<Self> {
+		let mut pinned = PinnedBlocks { client, hashes: Vec::with_capacity(hashes.len()) };
+		for hash in hashes {
+			client.pin_block(hash)?;
+			pinned.hashes.push(hash.clone());
+		}
+		Ok(pinned)
+	}
+}
+
+impl<'a, B, E, Block, RA> Drop for PinnedBlocks<'a, B, E, Block, RA>
+	where
+		Block: BlockT<Hash=H256>,
+		B: Backend<Block, Blake2Hasher>,
+		E: CallExecutor<Block, Blake2Hasher>,
+{
+	fn drop(&mut self) {
+		for hash in &self.hashes {
+			self.client.unpin_block(hash);
+		}
+	}
+}
+
 /// State API backend for full nodes.
 pub struct FullState<B, E, Block: BlockT, RA> {
 	client: Arc<Client<B, E, Block, RA>>,
@@ -100,8 +144,8 @@ impl<B, E, Block: BlockT, RA> FullState<B, E, Block, RA>
 		let from_meta = self.client.header_metadata(from).map_err(invalid_block_err)?;
 		let to_meta = self.client.header_metadata(to).map_err(invalid_block_err)?;
 
-		if from_meta.number >= to_meta.number {
-			return Err(invalid_block_range(&from_meta, &to_meta, "from number >= to number".to_owned()))
+		if from_meta.number > to_meta.number {
+			return Err(invalid_block_range(&from_meta, &to_meta, "from number > to number".to_owned()))
 		}
 
 		// check if we can get from `to` to `from` by going through parent_hashes.
@@ -139,6 +183,30 @@ impl<B, E, Block: BlockT, RA> FullState<B, E, Block, RA>
 		})
 	}
 
+	/// Reads the values of all the keys starting with the given prefix at the given block.
+	///
+	/// Fails if the prefix covers more than `MAX_SUBSCRIPTION_KEYS` keys.
+	fn storage_prefix_snapshot(
+		&self,
+		block: Block::Hash,
+		prefix: &StorageKey,
+	) -> Result<Vec<(StorageKey, Option<StorageData>)>> {
+		let id = BlockId::Hash(block);
+		// read one key more than the limit, rather than all the keys with the prefix
+		let keys = self.client.storage_keys_paged(&id, prefix, MAX_SUBSCRIPTION_KEYS + 1, None)
+			.map_err(client_err)?;
+		if keys.len() > MAX_SUBSCRIPTION_KEYS {
+			return Err(Error::TooManyKeys { limit: MAX_SUBSCRIPTION_KEYS });
+		}
+
+		keys.into_iter()
+			.map(|key| {
+				let value = self.client.storage(&id, &key).map_err(client_err)?;
+				Ok((key, value))
+			})
+			.collect()
+	}
+
 	/// Iterates through range.unfiltered_range and check each block for changes of keys' values.
 	fn query_storage_unfiltered(
 		&self,
@@ -262,6 +330,24 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 				.map_err(client_err)))
 	}
 
+	fn storage_keys_paged(
+		&self,
+		block: Option<Block::Hash>,
+		prefix: Option<StorageKey>,
+		count: u32,
+		start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageKey>> {
+		Box::new(result(
+			self.block_or_best(block)
+				.and_then(|block| self.client.storage_keys_paged(
+					&BlockId::Hash(block),
+					&prefix.unwrap_or_else(|| StorageKey(Vec::new())),
+					count as usize,
+					start_key.as_ref(),
+				))
+				.map_err(client_err)))
+	}
+
 	fn storage(
 		&self,
 		block: Option<Block::Hash>,
@@ -368,6 +454,8 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 	) -> FutureResult<Vec<StorageChangeSet<Block::Hash>>> {
 		let call_fn = move || {
 			let range = self.split_query_storage_range(from, to)?;
+			// the states of the range must not be pruned while they are read
+			let _pinned = PinnedBlocks::pin(&self.client, &range.hashes).map_err(client_err)?;
 			let mut changes = Vec::new();
 			let mut last_values = HashMap::new();
 			self.query_storage_unfiltered(&range, &keys, &mut last_values, &mut changes)?;
@@ -495,6 +583,72 @@ impl<B, E, Block, RA> StateBackend<B, E, Block, RA> for FullState<B, E, Block, R
 	) -> RpcResult<bool> {
 		Ok(self.subscriptions.cancel(id))
 	}
+
+	fn subscribe_storage_prefix(
+		&self,
+		_meta: crate::metadata::Metadata,
+		subscriber: Subscriber<StorageChangeSet<Block::Hash>>,
+		prefix: StorageKey,
+		page_size: u32,
+	) {
+		let stream = match self.client.storage_changes_notification_stream(None, None) {
+			Ok(stream) => stream,
+			Err(err) => {
+				let _ = subscriber.reject(client_err(err).into());
+				return;
+			},
+		};
+
+		// initial values, split into pages
+		let block = self.client.info().chain.best_hash;
+		let initial = match self.storage_prefix_snapshot(block, &prefix) {
+			Ok(initial) => initial,
+			Err(err) => {
+				let _ = subscriber.reject(err.into());
+				return;
+			},
+		};
+		let mut pages = initial
+			.chunks(page_size as usize)
+			.map(|changes| StorageChangeSet { block, changes: changes.to_vec() })
+			.collect::<Vec<_>>();
+		if pages.is_empty() {
+			// always send the snapshot block, even if no key matches the prefix
+			pages.push(StorageChangeSet { block, changes: Vec::new() });
+		}
+		let initial = stream::iter_result(pages.into_iter().map(|page| Ok(Ok(page))));
+
+		self.subscriptions.add(subscriber, |sink| {
+			let stream = stream
+				.filter_map(move |(block, changes)| {
+					let changes = changes.iter()
+						.filter_map(|(o_sk, k, v)| if o_sk.is_none() && k.0.starts_with(&prefix.0) {
+							Some((k.clone(), v.cloned()))
+						} else { None })
+						.collect::<Vec<_>>();
+					future::ready(if changes.is_empty() {
+						None
+					} else {
+						Some(Ok::<_, ()>(Ok(StorageChangeSet { block, changes })))
+					})
+				})
+				.compat();
+
+			sink
+				.sink_map_err(|e| warn!("Error sending notifications: {:?}", e))
+				.send_all(initial.chain(stream))
+				// we ignore the resulting Stream (if the first stream is over we are unsubscribed)
+				.map(|_| ())
+		});
+	}
+
+	fn unsubscribe_storage_prefix(
+		&self,
+		_meta: Option<crate::metadata::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
 }
 
 /// Splits passed range into two subranges where:
@@ -55,7 +55,7 @@ use sp_core::{
 use sp_version::RuntimeVersion;
 use sp_runtime::{
 	generic::BlockId,
-	traits::Block as BlockT,
+	traits::{Block as BlockT, Header as HeaderT},
 };
 
 use super::{StateBackend, error::{FutureResult, Error}, client_err};
@@ -63,6 +63,11 @@ use super::{StateBackend, error::{FutureResult, Error}, client_err};
 /// Storage data map of storage keys => (optional) storage value.
 type StorageMap = HashMap<StorageKey, Option<StorageData>>;
 
+/// Maximal number of blocks a `state_queryStorage` request can span on a light client.
+///
+/// Every block of the range costs a remote read request, so the range is kept small.
+const MAX_QUERY_STORAGE_RANGE: usize = 256;
+
 /// State API backend for light nodes.
 pub struct LightState<Block: BlockT, F: Fetcher<Block>, B, E, RA> {
 	client: Arc<Client<B, E, Block, RA>>,
@@ -170,6 +175,49 @@ impl<Block: BlockT, F: Fetcher<Block> + 'static, B, E, RA> LightState<Block, F,
 	fn block_or_best(&self, hash: Option<Block::Hash>) -> Block::Hash {
 		hash.unwrap_or_else(|| self.client.info().chain.best_hash)
 	}
+
+	/// Returns the hashes of the blocks in the `from..=to` range, oldest first.
+	///
+	/// The range is resolved using the headers known locally, by going from `to` to `from`
+	/// through parent hashes.
+	fn query_storage_range(
+		&self,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+	) -> Result<Vec<Block::Hash>, Error> {
+		let to = self.block_or_best(to);
+		let invalid_range = |details: String| Error::InvalidBlockRange {
+			from: format!("{:?}", from),
+			to: format!("{:?}", to),
+			details,
+		};
+		let header = |hash: Block::Hash| self.client.header(&BlockId::Hash(hash))
+			.map_err(|e| invalid_range(e.to_string()))?
+			.ok_or_else(|| invalid_range(format!("Unknown block {:?}", hash)));
+
+		let from_number = *header(from)?.number();
+		let mut last = header(to)?;
+		if from_number > *last.number() {
+			return Err(invalid_range("from number > to number".to_owned()));
+		}
+
+		let mut hashes = vec![to];
+		while *last.number() > from_number {
+			if hashes.len() >= MAX_QUERY_STORAGE_RANGE {
+				return Err(invalid_range(
+					format!("Light clients can query at most {} blocks", MAX_QUERY_STORAGE_RANGE),
+				));
+			}
+			last = header(*last.parent_hash())?;
+			hashes.push(last.hash());
+		}
+		if last.hash() != from {
+			return Err(invalid_range("from and to are on different forks".to_owned()));
+		}
+
+		hashes.reverse();
+		Ok(hashes)
+	}
 }
 
 impl<Block, F, B, E, RA> StateBackend<B, E, Block, RA> for LightState<Block, F, B, E, RA>
@@ -203,6 +251,16 @@ impl<Block, F, B, E, RA> StateBackend<B, E, Block, RA> for LightState<Block, F,
 		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
 	}
 
+	fn storage_keys_paged(
+		&self,
+		_block: Option<Block::Hash>,
+		_prefix: Option<StorageKey>,
+		_count: u32,
+		_start_key: Option<StorageKey>,
+	) -> FutureResult<Vec<StorageKey>> {
+		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+	}
+
 	fn storage(
 		&self,
 		block: Option<Block::Hash>,
@@ -315,11 +373,28 @@ impl<Block, F, B, E, RA> StateBackend<B, E, Block, RA> for LightState<Block, F,
 
 	fn query_storage(
 		&self,
-		_from: Block::Hash,
-		_to: Option<Block::Hash>,
-		_keys: Vec<StorageKey>,
+		from: Block::Hash,
+		to: Option<Block::Hash>,
+		keys: Vec<StorageKey>,
 	) -> FutureResult<Vec<StorageChangeSet<Block::Hash>>> {
-		Box::new(result(Err(client_err(ClientError::NotAvailableOnLightClient))))
+		let blocks = match self.query_storage_range(from, to) {
+			Ok(blocks) => blocks,
+			Err(error) => return Box::new(result(Err(error))),
+		};
+
+		let raw_keys: Vec<_> = keys.iter().map(|key| key.0.clone()).collect();
+		let reads = blocks.into_iter().map(|block| storage(
+			&*self.remote_blockchain,
+			self.fetcher.clone(),
+			block,
+			raw_keys.clone(),
+		).map_ok(move |values| (block, values)));
+
+		Box::new(futures::future::try_join_all(reads)
+			.map_ok(move |values| storage_change_sets(&keys, values))
+			.boxed()
+			.compat()
+		)
 	}
 
 	fn subscribe_storage(
@@ -445,6 +520,25 @@ impl<Block, F, B, E, RA> StateBackend<B, E, Block, RA> for LightState<Block, F,
 		Ok(true)
 	}
 
+	fn subscribe_storage_prefix(
+		&self,
+		_meta: crate::metadata::Metadata,
+		subscriber: Subscriber<StorageChangeSet<Block::Hash>>,
+		_prefix: StorageKey,
+		_page_size: u32,
+	) {
+		// the keys under a prefix can't be enumerated without downloading the whole state
+		let _ = subscriber.reject(client_err(ClientError::NotAvailableOnLightClient).into());
+	}
+
+	fn unsubscribe_storage_prefix(
+		&self,
+		_meta: Option<crate::metadata::Metadata>,
+		id: SubscriptionId,
+	) -> RpcResult<bool> {
+		Ok(self.subscriptions.cancel(id))
+	}
+
 	fn subscribe_runtime_version(
 		&self,
 		_meta: crate::metadata::Metadata,
@@ -560,6 +654,34 @@ fn runtime_version<Block: BlockT, F: Fetcher<Block>>(
 	)))
 }
 
+/// Converts the values of the keys at every block of a range into the change sets
+/// returned by `state_queryStorage`.
+///
+/// The first block reports the value of every key, the next ones only the changed values.
+fn storage_change_sets<Hash>(
+	keys: &[StorageKey],
+	values: Vec<(Hash, StorageMap)>,
+) -> Vec<StorageChangeSet<Hash>> {
+	let mut last_values: HashMap<&StorageKey, Option<StorageData>> = HashMap::new();
+	let mut change_sets = Vec::with_capacity(values.len());
+	for (block, mut block_values) in values {
+		let is_first = change_sets.is_empty();
+		let mut changes = Vec::new();
+		for key in keys {
+			let value = block_values.remove(key).unwrap_or(None);
+			let changed = is_first || last_values.get(key) != Some(&value);
+			if changed {
+				changes.push((key.clone(), value.clone()));
+			}
+			last_values.insert(key, value);
+		}
+		if is_first || !changes.is_empty() {
+			change_sets.push(StorageChangeSet { block, changes });
+		}
+	}
+	change_sets
+}
+
 /// Get storage value at given key at given block.
 fn storage<Block: BlockT, F: Fetcher<Block>>(
 	remote_blockchain: &dyn RemoteBlockchain<Block>,
@@ -714,6 +836,38 @@ mod tests {
 	use substrate_test_runtime_client::runtime::Block;
 	use super::*;
 
+	#[test]
+	fn storage_change_sets_only_report_changes() {
+		let key = StorageKey(vec![1]);
+		let other_key = StorageKey(vec![2]);
+		let values = |value: Option<u8>, other_value: Option<u8>| vec![
+			(key.clone(), value.map(|v| StorageData(vec![v]))),
+			(other_key.clone(), other_value.map(|v| StorageData(vec![v]))),
+		].into_iter().collect::<StorageMap>();
+
+		let change_sets = storage_change_sets(&[key.clone(), other_key.clone()], vec![
+			(1u64, values(Some(1), None)),
+			(2, values(Some(1), None)),
+			(3, values(Some(2), None)),
+			(4, values(None, Some(3))),
+		]);
+
+		assert_eq!(change_sets, vec![
+			StorageChangeSet {
+				block: 1,
+				changes: vec![(key.clone(), Some(StorageData(vec![1]))), (other_key.clone(), None)],
+			},
+			StorageChangeSet {
+				block: 3,
+				changes: vec![(key.clone(), Some(StorageData(vec![2])))],
+			},
+			StorageChangeSet {
+				block: 4,
+				changes: vec![(key, None), (other_key, Some(StorageData(vec![3])))],
+			},
+		]);
+	}
+
 	#[test]
 	fn subscription_stream_works() {
 		let stream = subscription_stream::<Block, _, _, _, _, _, _, _, _>(
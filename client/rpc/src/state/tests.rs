@@ -45,7 +45,7 @@ fn should_return_storage() {
 		.add_extra_child_storage(STORAGE_KEY.to_vec(), CHILD_INFO, KEY.to_vec(), CHILD_VALUE.to_vec())
 		.build();
 	let genesis_hash = client.genesis_hash();
-	let client = new_full(Arc::new(client), Subscriptions::new(Arc::new(core.executor())));
+	let (client, _child) = new_full(Arc::new(client), Subscriptions::new(Arc::new(core.executor())));
 	let key = StorageKey(KEY.to_vec());
 	let storage_key = StorageKey(STORAGE_KEY.to_vec());
 	let (child_info, child_type) = CHILD_INFO.info();
@@ -84,7 +84,7 @@ fn should_return_child_storage() {
 		.add_child_storage("test", "key", CHILD_INFO, vec![42_u8])
 		.build());
 	let genesis_hash = client.genesis_hash();
-	let client = new_full(client, Subscriptions::new(Arc::new(core.executor())));
+	let (client, child) = new_full(client, Subscriptions::new(Arc::new(core.executor())));
 	let child_key = StorageKey(
 		well_known_keys::CHILD_STORAGE_KEY_PREFIX.iter().chain(b"test").cloned().collect()
 	);
@@ -121,6 +121,42 @@ fn should_return_child_storage() {
 		).wait(),
 		Ok(Some(1))
 	);
+
+	// the same entry through the child state API
+	assert_eq!(
+		child.storage_keys(
+			child_key.clone(),
+			child_info.clone(),
+			child_type,
+			StorageKey(vec![]),
+			None,
+		).wait().unwrap(),
+		vec![key.clone()],
+	);
+	assert_matches!(
+		child.storage(
+			child_key.clone(),
+			child_info.clone(),
+			child_type,
+			key.clone(),
+			Some(genesis_hash).into(),
+		).wait(),
+		Ok(Some(StorageData(ref d))) if d[0] == 42 && d.len() == 1
+	);
+	assert_matches!(
+		child.storage_hash(
+			child_key.clone(),
+			child_info.clone(),
+			child_type,
+			key.clone(),
+			Some(genesis_hash).into(),
+		).wait().map(|x| x.is_some()),
+		Ok(true)
+	);
+	assert_matches!(
+		child.storage_size(child_key, child_info, child_type, key, None).wait(),
+		Ok(Some(1))
+	);
 }
 
 #[test]
@@ -128,7 +164,7 @@ fn should_call_contract() {
 	let core = tokio::runtime::Runtime::new().unwrap();
 	let client = Arc::new(substrate_test_runtime_client::new());
 	let genesis_hash = client.genesis_hash();
-	let client = new_full(client, Subscriptions::new(Arc::new(core.executor())));
+	let (client, _child) = new_full(client, Subscriptions::new(Arc::new(core.executor())));
 
 	assert_matches!(
 		client.call("balanceOf".into(), Bytes(vec![1,2,3]), Some(genesis_hash).into()).wait(),
@@ -144,7 +180,7 @@ fn should_notify_about_storage_changes() {
 
 	{
 		let client = Arc::new(substrate_test_runtime_client::new());
-		let api = new_full(client.clone(), Subscriptions::new(Arc::new(remote)));
+		let (api, _child) = new_full(client.clone(), Subscriptions::new(Arc::new(remote)));
 
 		api.subscribe_storage(Default::default(), subscriber, None.into());
 
@@ -176,7 +212,7 @@ fn should_send_initial_storage_changes_and_notifications() {
 
 	{
 		let client = Arc::new(substrate_test_runtime_client::new());
-		let api = new_full(client.clone(), Subscriptions::new(Arc::new(remote)));
+		let (api, _child) = new_full(client.clone(), Subscriptions::new(Arc::new(remote)));
 
 		let alice_balance_key = blake2_256(&runtime::system::balance_of_key(AccountKeyring::Alice.into()));
 
@@ -207,11 +243,110 @@ fn should_send_initial_storage_changes_and_notifications() {
 	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
 }
 
+#[test]
+fn should_send_initial_storage_pages_and_notifications_for_prefix() {
+	let mut core = tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+	let (subscriber, id, transport) = Subscriber::new_test("test");
+
+	{
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let (api, _child) = new_full(client.clone(), Subscriptions::new(Arc::new(remote)));
+
+		let mut builder = client.new_block(Default::default()).unwrap();
+		builder.push_storage_change(vec![1, 1], Some(vec![1])).unwrap();
+		builder.push_storage_change(vec![1, 2], Some(vec![2])).unwrap();
+		builder.push_storage_change(vec![2], Some(vec![3])).unwrap();
+		client.import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+
+		api.subscribe_storage_prefix(Default::default(), subscriber, StorageKey(vec![1]), Some(1));
+
+		// assert id assigned
+		assert_eq!(core.block_on(id), Ok(Ok(SubscriptionId::Number(1))));
+
+		// changes a key under the prefix
+		let mut builder = client.new_block(Default::default()).unwrap();
+		builder.push_storage_change(vec![1, 1], Some(vec![4])).unwrap();
+		builder.push_storage_change(vec![2], Some(vec![5])).unwrap();
+		client.import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+
+		// changes no key under the prefix
+		let mut builder = client.new_block(Default::default()).unwrap();
+		builder.push_storage_change(vec![2], Some(vec![6])).unwrap();
+		client.import(BlockOrigin::Own, builder.bake().unwrap()).unwrap();
+	}
+
+	// assert initial values sent to transport, one per page
+	let (notification, next) = core.block_on(transport.into_future()).unwrap();
+	assert!(notification.unwrap().contains("0x0101"));
+	let (notification, next) = core.block_on(next.into_future()).unwrap();
+	assert!(notification.unwrap().contains("0x0102"));
+	// assert notification of the change under the prefix sent to transport
+	let (notification, next) = core.block_on(next.into_future()).unwrap();
+	let notification = notification.unwrap();
+	assert!(notification.contains("0x0101") && !notification.contains("\"0x02\""));
+	// no more notifications on this channel
+	assert_eq!(core.block_on(next.into_future()).unwrap().0, None);
+}
+
+#[test]
+fn should_return_storage_keys_paged() {
+	let core = tokio::runtime::Runtime::new().unwrap();
+	let client = TestClientBuilder::new()
+		.add_extra_storage(b":map".to_vec(), b"prefix".to_vec())
+		.add_extra_storage(b":map:a".to_vec(), b"1".to_vec())
+		.add_extra_storage(b":map:b".to_vec(), b"2".to_vec())
+		.add_extra_storage(b":map:c".to_vec(), b"3".to_vec())
+		.add_extra_storage(b":mat".to_vec(), b"other".to_vec())
+		.build();
+	let (api, _child) = new_full(Arc::new(client), Subscriptions::new(Arc::new(core.executor())));
+	let prefix = Some(StorageKey(b":map".to_vec()));
+	let keys = |keys: &[&str]| keys.iter()
+		.map(|key| StorageKey(key.as_bytes().to_vec()))
+		.collect::<Vec<_>>();
+
+	assert_eq!(
+		api.storage_keys_paged(prefix.clone(), 2, None, None).wait().unwrap(),
+		keys(&[":map", ":map:a"]),
+	);
+	assert_eq!(
+		api.storage_keys_paged(prefix.clone(), 2, Some(StorageKey(b":map:a".to_vec())), None)
+			.wait().unwrap(),
+		keys(&[":map:b", ":map:c"]),
+	);
+	assert_eq!(
+		api.storage_keys_paged(prefix.clone(), 2, Some(StorageKey(b":map:c".to_vec())), None)
+			.wait().unwrap(),
+		keys(&[]),
+	);
+	assert_matches!(
+		api.storage_keys_paged(prefix, MAX_KEYS_PAGE_SIZE + 1, None, None).wait(),
+		Err(Error::InvalidCount { .. })
+	);
+}
+
+#[test]
+fn should_reject_storage_subscription_with_too_many_keys() {
+	let mut core = tokio::runtime::Runtime::new().unwrap();
+	let remote = core.executor();
+	let (subscriber, id, _transport) = Subscriber::new_test("test");
+
+	let client = Arc::new(substrate_test_runtime_client::new());
+	let (api, _child) = new_full(client, Subscriptions::new(Arc::new(remote)));
+
+	let keys = (0..=MAX_SUBSCRIPTION_KEYS as u32)
+		.map(|key| StorageKey(key.to_le_bytes().to_vec()))
+		.collect::<Vec<_>>();
+	api.subscribe_storage(Default::default(), subscriber, Some(keys));
+
+	assert_matches!(core.block_on(id), Ok(Err(_)));
+}
+
 #[test]
 fn should_query_storage() {
 	fn run_tests(client: Arc<TestClient>) {
 		let core = tokio::runtime::Runtime::new().unwrap();
-		let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
+		let (api, _child) = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
 
 		let add_block = |nonce| {
 			let mut builder = client.new_block(Default::default()).unwrap();
@@ -291,6 +426,24 @@ fn should_query_storage() {
 
 		assert_eq!(result.wait().unwrap(), expected);
 
+		// Query changes of a single block.
+		let result = api.query_storage(
+			keys.clone(),
+			block2_hash,
+			Some(block2_hash),
+		);
+
+		assert_eq!(result.wait().unwrap(), vec![StorageChangeSet {
+			block: block2_hash,
+			changes: vec![
+				(StorageKey(vec![1]), None),
+				(StorageKey(vec![2]), Some(StorageData(vec![2]))),
+				(StorageKey(vec![3]), None),
+				(StorageKey(vec![4]), Some(StorageData(vec![4]))),
+				(StorageKey(vec![5]), Some(StorageData(vec![1]))),
+			],
+		}]);
+
 		// Inverted range.
 		let result = api.query_storage(
 			keys.clone(),
@@ -303,7 +456,7 @@ fn should_query_storage() {
 			Err(Error::InvalidBlockRange {
 				from: format!("1 ({:?})", block1_hash),
 				to: format!("0 ({:?})", genesis_hash),
-				details: "from number >= to number".to_owned(),
+				details: "from number > to number".to_owned(),
 			}).map_err(|e| e.to_string())
 		);
 
@@ -394,7 +547,7 @@ fn should_return_runtime_version() {
 	let core = tokio::runtime::Runtime::new().unwrap();
 
 	let client = Arc::new(substrate_test_runtime_client::new());
-	let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
+	let (api, _child) = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
 
 	let result = "{\"specName\":\"test\",\"implName\":\"parity-test\",\"authoringVersion\":1,\
 		\"specVersion\":1,\"implVersion\":1,\"apis\":[[\"0xdf6acb689907609b\",2],\
@@ -417,7 +570,7 @@ fn should_notify_on_runtime_version_initially() {
 
 	{
 		let client = Arc::new(substrate_test_runtime_client::new());
-		let api = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
+		let (api, _child) = new_full(client.clone(), Subscriptions::new(Arc::new(core.executor())));
 
 		api.subscribe_runtime_version(Default::default(), subscriber);
 
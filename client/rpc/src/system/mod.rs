@@ -21,33 +21,46 @@ mod tests;
 
 use futures::{future::BoxFuture, FutureExt, TryFutureExt};
 use futures::{channel::{mpsc, oneshot}, compat::Compat};
-use sc_rpc_api::Receiver;
+use sc_rpc_api::{DenyUnsafe, Receiver};
 use sp_runtime::traits::{self, Header as HeaderT};
 
 use self::error::Result;
 
 pub use sc_rpc_api::system::*;
-pub use self::helpers::{Properties, SystemInfo, Health, PeerInfo, NodeRole};
+pub use self::helpers::{
+	Properties, SystemInfo, Health, PeerInfo, PeerReputationInfo, NodeRole, SyncState, SyncProgress,
+};
 pub use self::gen_client::Client as SystemClient;
 
 /// System API implementation
 pub struct System<B: traits::Block> {
 	info: SystemInfo,
 	send_back: mpsc::UnboundedSender<Request<B>>,
+	deny_unsafe: DenyUnsafe,
 }
 
 /// Request to be processed.
 pub enum Request<B: traits::Block> {
 	/// Must return the health of the network.
 	Health(oneshot::Sender<Health>),
+	/// Must return the progress of the sync.
+	SyncState(oneshot::Sender<SyncState<<B::Header as HeaderT>::Number>>),
+	/// Must return the block numbers describing the progress of the sync.
+	SyncProgress(oneshot::Sender<SyncProgress<<B::Header as HeaderT>::Number>>),
+	/// Must return the listen addresses of the node followed by its external addresses.
+	LocalListenAddresses(oneshot::Sender<Vec<String>>),
 	/// Must return information about the peers we are connected to.
 	Peers(oneshot::Sender<Vec<PeerInfo<B::Hash, <B::Header as HeaderT>::Number>>>),
+	/// Must return the reputation of the peers known to the network.
+	PeerReputations(oneshot::Sender<Vec<PeerReputationInfo>>),
 	/// Must return the state of the network.
 	NetworkState(oneshot::Sender<rpc::Value>),
 	/// Must return any potential parse error.
 	NetworkAddReservedPeer(String, oneshot::Sender<Result<()>>),
 	/// Must return any potential parse error.
 	NetworkRemoveReservedPeer(String, oneshot::Sender<Result<()>>),
+	/// Must switch the reserved-only mode of the network.
+	NetworkSetReservedOnly(bool, oneshot::Sender<()>),
 	/// Must return the node role.
 	NodeRoles(oneshot::Sender<Vec<NodeRole>>)
 }
@@ -60,10 +73,12 @@ impl<B: traits::Block> System<B> {
 	pub fn new(
 		info: SystemInfo,
 		send_back: mpsc::UnboundedSender<Request<B>>,
+		deny_unsafe: DenyUnsafe,
 	) -> Self {
 		System {
 			info,
 			send_back,
+			deny_unsafe,
 		}
 	}
 }
@@ -91,21 +106,71 @@ impl<B: traits::Block> SystemApi<B::Hash, <B::Header as HeaderT>::Number> for Sy
 		Receiver(Compat::new(rx))
 	}
 
-	fn system_peers(&self) -> Receiver<Vec<PeerInfo<B::Hash, <B::Header as HeaderT>::Number>>> {
+	fn system_sync_state(&self) -> Receiver<SyncState<<B::Header as HeaderT>::Number>> {
 		let (tx, rx) = oneshot::channel();
-		let _ = self.send_back.unbounded_send(Request::Peers(tx));
+		let _ = self.send_back.unbounded_send(Request::SyncState(tx));
 		Receiver(Compat::new(rx))
 	}
 
-	fn system_network_state(&self) -> Receiver<rpc::Value> {
+	fn system_sync_progress(&self) -> Receiver<SyncProgress<<B::Header as HeaderT>::Number>> {
 		let (tx, rx) = oneshot::channel();
-		let _ = self.send_back.unbounded_send(Request::NetworkState(tx));
+		let _ = self.send_back.unbounded_send(Request::SyncProgress(tx));
+		Receiver(Compat::new(rx))
+	}
+
+	fn system_local_listen_addresses(&self) -> Receiver<Vec<String>> {
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::LocalListenAddresses(tx));
 		Receiver(Compat::new(rx))
 	}
 
+	fn system_peers(&self)
+		-> Compat<BoxFuture<'static, rpc::Result<Vec<PeerInfo<B::Hash, <B::Header as HeaderT>::Number>>>>>
+	{
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return async move { Err(err.into()) }.boxed().compat();
+		}
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::Peers(tx));
+		async move {
+			rx.await.map_err(|_| rpc::Error::internal_error())
+		}.boxed().compat()
+	}
+
+	fn system_peer_reputations(&self)
+		-> Compat<BoxFuture<'static, rpc::Result<Vec<PeerReputationInfo>>>>
+	{
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return async move { Err(err.into()) }.boxed().compat();
+		}
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::PeerReputations(tx));
+		async move {
+			rx.await.map_err(|_| rpc::Error::internal_error())
+		}.boxed().compat()
+	}
+
+	fn system_network_state(&self) -> Compat<BoxFuture<'static, rpc::Result<rpc::Value>>> {
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return async move { Err(err.into()) }.boxed().compat();
+		}
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkState(tx));
+		async move {
+			rx.await.map_err(|_| rpc::Error::internal_error())
+		}.boxed().compat()
+	}
+
 	fn system_add_reserved_peer(&self, peer: String)
 		-> Compat<BoxFuture<'static, std::result::Result<(), rpc::Error>>>
 	{
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return async move { Err(err.into()) }.boxed().compat();
+		}
+
 		let (tx, rx) = oneshot::channel();
 		let _ = self.send_back.unbounded_send(Request::NetworkAddReservedPeer(peer, tx));
 		async move {
@@ -120,6 +185,10 @@ impl<B: traits::Block> SystemApi<B::Hash, <B::Header as HeaderT>::Number> for Sy
 	fn system_remove_reserved_peer(&self, peer: String)
 		-> Compat<BoxFuture<'static, std::result::Result<(), rpc::Error>>>
 	{
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return async move { Err(err.into()) }.boxed().compat();
+		}
+
 		let (tx, rx) = oneshot::channel();
 		let _ = self.send_back.unbounded_send(Request::NetworkRemoveReservedPeer(peer, tx));
 		async move {
@@ -131,6 +200,20 @@ impl<B: traits::Block> SystemApi<B::Hash, <B::Header as HeaderT>::Number> for Sy
 		}.boxed().compat()
 	}
 
+	fn system_set_reserved_only(&self, reserved_only: bool)
+		-> Compat<BoxFuture<'static, std::result::Result<(), rpc::Error>>>
+	{
+		if let Err(err) = self.deny_unsafe.check_if_safe() {
+			return async move { Err(err.into()) }.boxed().compat();
+		}
+
+		let (tx, rx) = oneshot::channel();
+		let _ = self.send_back.unbounded_send(Request::NetworkSetReservedOnly(reserved_only, tx));
+		async move {
+			rx.await.map_err(|_| rpc::Error::internal_error())
+		}.boxed().compat()
+	}
+
 	fn system_node_roles(&self) -> Receiver<Vec<NodeRole>> {
 		let (tx, rx) = oneshot::channel();
 		let _ = self.send_back.unbounded_send(Request::NodeRoles(tx));
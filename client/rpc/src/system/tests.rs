@@ -42,6 +42,10 @@ impl Default for Status {
 }
 
 fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
+	api_with_policy(sync, DenyUnsafe::No)
+}
+
+fn api_with_policy<T: Into<Option<Status>>>(sync: T, deny_unsafe: DenyUnsafe) -> System<Block> {
 	let status = sync.into().unwrap_or_default();
 	let should_have_peers = !status.is_dev;
 	let (tx, rx) = mpsc::unbounded();
@@ -55,6 +59,25 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 						should_have_peers,
 					});
 				},
+				Request::SyncState(sender) => {
+					let _ = sender.send(if status.is_syncing {
+						SyncState::Downloading { target: 10, current: 1, peers: status.peers as u32 }
+					} else {
+						SyncState::Idle
+					});
+				},
+				Request::SyncProgress(sender) => {
+					let _ = sender.send(SyncProgress {
+						starting_block: 1,
+						current_block: 2,
+						highest_block: if status.peers > 0 { Some(10) } else { None },
+					});
+				},
+				Request::LocalListenAddresses(sender) => {
+					let _ = sender.send(vec![
+						format!("/ip4/127.0.0.1/tcp/30333/p2p/{}", status.peer_id.to_base58()),
+					]);
+				},
 				Request::Peers(sender) => {
 					let mut peers = vec![];
 					for _peer in 0..status.peers {
@@ -68,6 +91,15 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 					}
 					let _ = sender.send(peers);
 				}
+				Request::PeerReputations(sender) => {
+					let _ = sender.send(vec![PeerReputationInfo {
+						peer_id: status.peer_id.to_base58(),
+						reputation: -10,
+						connected: true,
+						banned: false,
+						last_report_reason: Some("Timeout".into()),
+					}]);
+				}
 				Request::NetworkState(sender) => {
 					let _ = sender.send(serde_json::to_value(&sc_network::NetworkState {
 						peer_id: String::new(),
@@ -92,6 +124,9 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 						Err(s) => sender.send(Err(error::Error::MalformattedPeerArg(s.to_string()))),
 					};
 				}
+				Request::NetworkSetReservedOnly(_, sender) => {
+					let _ = sender.send(());
+				}
 				Request::NodeRoles(sender) => {
 					let _ = sender.send(vec![NodeRole::Authority]);
 				}
@@ -105,10 +140,10 @@ fn api<T: Into<Option<Status>>>(sync: T) -> System<Block> {
 		impl_version: "0.2.0".into(),
 		chain_name: "testchain".into(),
 		properties: Default::default(),
-	}, tx)
+	}, tx, deny_unsafe)
 }
 
-fn wait_receiver<T>(rx: Receiver<T>) -> T {
+fn wait_receiver<T>(rx: impl rpc::futures::Future<Item = T, Error = rpc::Error>) -> T {
 	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
 	runtime.block_on(rx).unwrap()
 }
@@ -219,6 +254,70 @@ fn system_peers() {
 	);
 }
 
+#[test]
+fn system_peer_reputations() {
+	let peer_id = PeerId::random();
+	assert_eq!(
+		wait_receiver(api(Status {
+			peer_id: peer_id.clone(),
+			..Default::default()
+		}).system_peer_reputations()),
+		vec![PeerReputationInfo {
+			peer_id: peer_id.to_base58(),
+			reputation: -10,
+			connected: true,
+			banned: false,
+			last_report_reason: Some("Timeout".into()),
+		}]
+	);
+}
+
+#[test]
+fn system_sync_state() {
+	assert_eq!(
+		wait_receiver(api(None).system_sync_state()),
+		SyncState::Idle,
+	);
+
+	assert_eq!(
+		wait_receiver(api(Status {
+			peers: 5,
+			is_syncing: true,
+			..Default::default()
+		}).system_sync_state()),
+		SyncState::Downloading { target: 10, current: 1, peers: 5 },
+	);
+}
+
+#[test]
+fn system_sync_progress() {
+	assert_eq!(
+		wait_receiver(api(None).system_sync_progress()),
+		SyncProgress { starting_block: 1, current_block: 2, highest_block: None },
+	);
+
+	assert_eq!(
+		wait_receiver(api(Status {
+			peers: 5,
+			is_syncing: true,
+			..Default::default()
+		}).system_sync_progress()),
+		SyncProgress { starting_block: 1, current_block: 2, highest_block: Some(10) },
+	);
+}
+
+#[test]
+fn system_local_listen_addresses() {
+	let peer_id = PeerId::random();
+	assert_eq!(
+		wait_receiver(api(Status {
+			peer_id: peer_id.clone(),
+			..Default::default()
+		}).system_local_listen_addresses()),
+		vec![format!("/ip4/127.0.0.1/tcp/30333/p2p/{}", peer_id.to_base58())],
+	);
+}
+
 #[test]
 fn system_network_state() {
 	let res = wait_receiver(api(None).system_network_state());
@@ -268,3 +367,27 @@ fn system_network_remove_reserved() {
 	assert_eq!(runtime.block_on(good_fut), Ok(()));
 	assert!(runtime.block_on(bad_fut).is_err());
 }
+
+#[test]
+fn system_network_set_reserved_only() {
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+
+	assert_eq!(runtime.block_on(api(None).system_set_reserved_only(true)), Ok(()));
+	assert_eq!(runtime.block_on(api(None).system_set_reserved_only(false)), Ok(()));
+}
+
+#[test]
+fn system_unsafe_calls_are_denied() {
+	let mut runtime = tokio::runtime::current_thread::Runtime::new().unwrap();
+	let api = api_with_policy(None, DenyUnsafe::Yes);
+
+	assert!(runtime.block_on(api.system_peers()).is_err());
+	assert!(runtime.block_on(api.system_peer_reputations()).is_err());
+	assert!(runtime.block_on(api.system_network_state()).is_err());
+	assert!(runtime.block_on(api.system_add_reserved_peer(
+		"/ip4/198.51.100.19/tcp/30333/p2p/QmSk5HQbn6LhUwDiNMseVUjuRYhEtYj4aUZ6WfWoGURpdV".into()
+	)).is_err());
+	assert!(runtime.block_on(api.system_set_reserved_only(true)).is_err());
+	// safe calls are still answered
+	assert_eq!(wait_receiver(api.system_node_roles()), vec![NodeRole::Authority]);
+}
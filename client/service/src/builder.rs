@@ -15,17 +15,17 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use crate::{Service, NetworkStatus, NetworkState, error::Error, DEFAULT_PROTOCOL_ID};
-use crate::{SpawnTaskHandle, start_rpc_servers, build_network_future, TransactionPoolAdapter};
+use crate::{TaskManager, start_rpc_servers, build_network_future, TransactionPoolAdapter};
 use crate::status_sinks;
-use crate::config::{Configuration, DatabaseConfig};
+use crate::config::{Configuration, DatabaseConfig, PrometheusConfig};
 use sc_client_api::{
 	self,
-	BlockchainEvents,
-	backend::RemoteBackend, light::RemoteBlockchain,
+	BlockchainEvents, BlockImportNotification, FinalityNotification,
+	backend::{RemoteBackend, PurgeTarget}, light::RemoteBlockchain,
 };
 use sc_client::Client;
 use sc_chain_spec::{RuntimeGenesis, Extension};
-use sp_consensus::import_queue::ImportQueue;
+use sp_consensus::{ForkRanking, import_queue::ImportQueue};
 use futures::{prelude::*, sync::mpsc};
 use futures03::{
 	compat::Compat,
@@ -35,7 +35,7 @@ use futures03::{
 };
 use sc_keystore::{Store as Keystore};
 use log::{info, warn, error};
-use sc_network::{FinalityProofProvider, OnDemand, NetworkService, NetworkStateInfo, DhtEvent};
+use sc_network::{FinalityProofProvider, WarpSyncProvider, OnDemand, NetworkService, NetworkStateInfo, DhtEvent};
 use sc_network::{config::BoxFinalityProofRequestBuilder, specialization::NetworkSpecialization};
 use parking_lot::{Mutex, RwLock};
 use sp_core::{Blake2Hasher, H256, Hasher};
@@ -43,11 +43,12 @@ use sc_rpc;
 use sp_api::ConstructRuntimeApi;
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::{
-	Block as BlockT, ProvideRuntimeApi, NumberFor, Header, SaturatedConversion,
+	Block as BlockT, ProvideRuntimeApi, NumberFor, SaturatedConversion,
 };
 use sc_executor::{NativeExecutor, NativeExecutionDispatch};
 use std::{
-	io::{Read, Write, Seek},
+	collections::HashMap,
+	io::{Read, Write},
 	marker::PhantomData, sync::Arc, time::SystemTime
 };
 use sysinfo::{get_current_pid, ProcessExt, System, SystemExt};
@@ -67,6 +68,10 @@ use grafana_data_source::{self, record_metrics};
 /// - [`with_import_queue`](ServiceBuilder::with_import_queue)
 /// - [`with_network_protocol`](ServiceBuilder::with_network_protocol)
 /// - [`with_finality_proof_provider`](ServiceBuilder::with_finality_proof_provider)
+/// - [`with_warp_sync_provider`](ServiceBuilder::with_warp_sync_provider)
+/// - [`with_fork_ranking`](ServiceBuilder::with_fork_ranking)
+/// - [`with_block_import_hook`](ServiceBuilder::with_block_import_hook)
+/// - [`with_finality_hook`](ServiceBuilder::with_finality_hook)
 /// - [`with_transaction_pool`](ServiceBuilder::with_transaction_pool)
 ///
 /// After this is done, call [`build`](ServiceBuilder::build) to construct the service.
@@ -91,9 +96,56 @@ pub struct ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImp
 	rpc_extensions: TRpc,
 	remote_backend: Option<Arc<dyn RemoteBlockchain<TBl>>>,
 	dht_event_tx: Option<mpsc::Sender<DhtEvent>>,
+	warp_sync_provider: Option<Arc<dyn WarpSyncProvider<TBl>>>,
+	fork_ranking: Option<Arc<dyn ForkRanking<TBl>>>,
+	block_import_hooks: Vec<BlockImportHook<TBl>>,
+	finality_hooks: Vec<FinalityHook<TBl>>,
 	marker: PhantomData<(TBl, TRtApi)>,
 }
 
+/// A callback invoked on every block imported by the client.
+pub type BlockImportHook<TBl> = Box<dyn FnMut(&BlockImportNotification<TBl>) + Send>;
+
+/// A callback invoked on every block finalized by the client.
+pub type FinalityHook<TBl> = Box<dyn FnMut(&FinalityNotification<TBl>) + Send>;
+
+/// Builds the RPC extensions of the node for one RPC interface.
+///
+/// Whether the interface is exposed externally is only known when the RPC servers are started,
+/// so the extensions are built once per interface, knowing which unsafe methods to deny.
+pub trait RpcExtensionBuilder {
+	/// The RPC extensions built.
+	type Output: sc_rpc::RpcExtension<sc_rpc::Metadata>;
+
+	/// Returns the RPC extensions of an interface denying the unsafe methods or not.
+	fn build(&self, deny_unsafe: sc_rpc::DenyUnsafe) -> Self::Output;
+}
+
+impl<F, R> RpcExtensionBuilder for F where
+	F: Fn(sc_rpc::DenyUnsafe) -> R,
+	R: sc_rpc::RpcExtension<sc_rpc::Metadata>,
+{
+	type Output = R;
+
+	fn build(&self, deny_unsafe: sc_rpc::DenyUnsafe) -> R {
+		(*self)(deny_unsafe)
+	}
+}
+
+/// RPC extensions with no unsafe methods, shared by all the RPC interfaces.
+#[derive(Default)]
+pub struct NoopRpcExtensionBuilder<R>(pub R);
+
+impl<R> RpcExtensionBuilder for NoopRpcExtensionBuilder<R> where
+	R: sc_rpc::RpcExtension<sc_rpc::Metadata> + Clone,
+{
+	type Output = R;
+
+	fn build(&self, _: sc_rpc::DenyUnsafe) -> R {
+		self.0.clone()
+	}
+}
+
 /// Full client type.
 pub type TFullClient<TBl, TRtApi, TExecDisp> = Client<
 	TFullBackend<TBl>,
@@ -140,6 +192,20 @@ pub type TLightCallExecutor<TBl, TExecDisp> = sc_client::light::call_executor::G
 	>,
 >;
 
+/// Opens the keystore of the configuration, or connects to its remote signer.
+fn open_keystore<TCfg, TGen, TCSExt>(
+	config: &Configuration<TCfg, TGen, TCSExt>,
+) -> Result<Arc<RwLock<Keystore>>, Error> {
+	let keystore = match &config.keystore_uri {
+		Some(uri) => Keystore::open_remote(uri, config.keystore_password.clone())?,
+		None => Keystore::open(
+			config.keystore_path.clone().ok_or("No basepath configured")?,
+			config.keystore_password.clone()
+		)?,
+	};
+	Ok(keystore)
+}
+
 impl<TCfg, TGen, TCSExt> ServiceBuilder<(), (), TCfg, TGen, TCSExt, (), (), (), (), (), (), (), (), (), ()>
 where TGen: RuntimeGenesis, TCSExt: Extension {
 	/// Start the service builder with a configuration.
@@ -159,18 +225,15 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 		Arc<dyn FinalityProofProvider<TBl>>,
 		(),
 		(),
-		(),
+		NoopRpcExtensionBuilder<()>,
 		TFullBackend<TBl>,
 	>, Error> {
-		let keystore = Keystore::open(
-			config.keystore_path.clone().ok_or("No basepath configured")?,
-			config.keystore_password.clone()
-		)?;
+		let keystore = open_keystore(&config)?;
 
 		let executor = NativeExecutor::<TExecDisp>::new(
 			config.wasm_method,
 			config.default_heap_pages,
-		);
+		).with_instrumentation(config.prometheus_config.as_ref().map(|config| &config.registry));
 
 		let fork_blocks = config.chain_spec
 			.extensions()
@@ -184,12 +247,16 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 				state_cache_child_ratio:
 					config.state_cache_child_ratio.map(|v| (v, 100)),
 				pruning: config.pruning.clone(),
+				column_cache_sizes: config.column_cache_sizes.clone(),
+				offchain_indexing: config.offchain_indexing,
 				source: match &config.database {
 					DatabaseConfig::Path { path, cache_size } =>
 						sc_client_db::DatabaseSettingsSrc::Path {
 							path: path.clone(),
 							cache_size: cache_size.clone().map(|u| u as usize),
 						},
+					DatabaseConfig::ParityDb { path } =>
+						sc_client_db::DatabaseSettingsSrc::ParityDb { path: path.clone() },
 					DatabaseConfig::Custom(db) =>
 						sc_client_db::DatabaseSettingsSrc::Custom(db.clone()),
 				},
@@ -206,6 +273,7 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 				&config.chain_spec,
 				fork_blocks,
 				extensions,
+				config.prometheus_config.as_ref().map(|config| config.registry.clone()),
 			)?
 		};
 
@@ -226,6 +294,10 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 			rpc_extensions: Default::default(),
 			remote_backend: None,
 			dht_event_tx: None,
+			warp_sync_provider: None,
+			fork_ranking: None,
+			block_import_hooks: Vec::new(),
+			finality_hooks: Vec::new(),
 			marker: PhantomData,
 		})
 	}
@@ -247,18 +319,15 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 		Arc<dyn FinalityProofProvider<TBl>>,
 		(),
 		(),
-		(),
+		NoopRpcExtensionBuilder<()>,
 		TLightBackend<TBl>,
 	>, Error> {
-		let keystore = Keystore::open(
-			config.keystore_path.clone().ok_or("No basepath configured")?,
-			config.keystore_password.clone()
-		)?;
+		let keystore = open_keystore(&config)?;
 
 		let executor = NativeExecutor::<TExecDisp>::new(
 			config.wasm_method,
 			config.default_heap_pages,
-		);
+		).with_instrumentation(config.prometheus_config.as_ref().map(|config| &config.registry));
 
 		let db_storage = {
 			let db_settings = sc_client_db::DatabaseSettings {
@@ -266,12 +335,16 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 				state_cache_child_ratio:
 					config.state_cache_child_ratio.map(|v| (v, 100)),
 				pruning: config.pruning.clone(),
+				column_cache_sizes: config.column_cache_sizes.clone(),
+				offchain_indexing: config.offchain_indexing,
 				source: match &config.database {
 					DatabaseConfig::Path { path, cache_size } =>
 						sc_client_db::DatabaseSettingsSrc::Path {
 							path: path.clone(),
 							cache_size: cache_size.clone().map(|u| u as usize),
 						},
+					DatabaseConfig::ParityDb { path } =>
+						sc_client_db::DatabaseSettingsSrc::ParityDb { path: path.clone() },
 					DatabaseConfig::Custom(db) =>
 						sc_client_db::DatabaseSettingsSrc::Custom(db.clone()),
 				},
@@ -287,6 +360,7 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 			backend.clone(),
 			&config.chain_spec,
 			executor,
+			config.prometheus_config.as_ref().map(|config| config.registry.clone()),
 		)?);
 
 		Ok(ServiceBuilder {
@@ -304,6 +378,10 @@ where TGen: RuntimeGenesis, TCSExt: Extension {
 			rpc_extensions: Default::default(),
 			remote_backend: Some(remote_blockchain),
 			dht_event_tx: None,
+			warp_sync_provider: None,
+			fork_ranking: None,
+			block_import_hooks: Vec::new(),
+			finality_hooks: Vec::new(),
 			marker: PhantomData,
 		})
 	}
@@ -323,6 +401,11 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 		&self.backend
 	}
 
+	/// Returns a reference to the keystore that was opened by this builder.
+	pub fn keystore(&self) -> &Arc<RwLock<Keystore>> {
+		&self.keystore
+	}
+
 	/// Returns a reference to the select-chain that was stored in this builder.
 	pub fn select_chain(&self) -> Option<&TSc> {
 		self.select_chain.as_ref()
@@ -353,6 +436,10 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
 			dht_event_tx: self.dht_event_tx,
+			warp_sync_provider: self.warp_sync_provider,
+			fork_ranking: self.fork_ranking,
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
 			marker: self.marker,
 		})
 	}
@@ -396,6 +483,10 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
 			dht_event_tx: self.dht_event_tx,
+			warp_sync_provider: self.warp_sync_provider,
+			fork_ranking: self.fork_ranking,
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
 			marker: self.marker,
 		})
 	}
@@ -423,6 +514,10 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
 			dht_event_tx: self.dht_event_tx,
+			warp_sync_provider: self.warp_sync_provider,
+			fork_ranking: self.fork_ranking,
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
 			marker: self.marker,
 		})
 	}
@@ -465,6 +560,10 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
 			dht_event_tx: self.dht_event_tx,
+			warp_sync_provider: self.warp_sync_provider,
+			fork_ranking: self.fork_ranking,
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
 			marker: self.marker,
 		})
 	}
@@ -531,6 +630,10 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
 			dht_event_tx: self.dht_event_tx,
+			warp_sync_provider: self.warp_sync_provider,
+			fork_ranking: self.fork_ranking,
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
 			marker: self.marker,
 		})
 	}
@@ -587,11 +690,18 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
 			dht_event_tx: self.dht_event_tx,
+			warp_sync_provider: self.warp_sync_provider,
+			fork_ranking: self.fork_ranking,
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
 			marker: self.marker,
 		})
 	}
 
-	/// Defines the RPC extensions to use.
+	/// Defines the RPC extensions to use, shared by all the RPC interfaces.
+	///
+	/// The extensions must not have unsafe methods, see
+	/// [`with_rpc_extensions_builder`](ServiceBuilder::with_rpc_extensions_builder) otherwise.
 	pub fn with_rpc_extensions<URpc>(
 		self,
 		rpc_ext_builder: impl FnOnce(
@@ -602,7 +712,26 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			Option<Arc<dyn RemoteBlockchain<TBl>>>,
 		) -> Result<URpc, Error>,
 	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
-		TNetP, TExPool, URpc, Backend>, Error>
+		TNetP, TExPool, NoopRpcExtensionBuilder<URpc>, Backend>, Error>
+	where TSc: Clone, TFchr: Clone {
+		self.with_rpc_extensions_builder(|client, pool, backend, fetcher, remote_backend| {
+			rpc_ext_builder(client, pool, backend, fetcher, remote_backend).map(NoopRpcExtensionBuilder)
+		})
+	}
+
+	/// Defines the builder of the RPC extensions to use, called for every RPC interface with
+	/// whether the interface must deny the unsafe methods.
+	pub fn with_rpc_extensions_builder<URpcBuilder>(
+		self,
+		rpc_ext_builder: impl FnOnce(
+			Arc<TCl>,
+			Arc<TExPool>,
+			Arc<Backend>,
+			Option<TFchr>,
+			Option<Arc<dyn RemoteBlockchain<TBl>>>,
+		) -> Result<URpcBuilder, Error>,
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+		TNetP, TExPool, URpcBuilder, Backend>, Error>
 	where TSc: Clone, TFchr: Clone {
 		let rpc_extensions = rpc_ext_builder(
 			self.client.clone(),
@@ -627,6 +756,10 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			rpc_extensions,
 			remote_backend: self.remote_backend,
 			dht_event_tx: self.dht_event_tx,
+			warp_sync_provider: self.warp_sync_provider,
+			fork_ranking: self.fork_ranking,
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
 			marker: self.marker,
 		})
 	}
@@ -653,9 +786,102 @@ impl<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp, TNet
 			rpc_extensions: self.rpc_extensions,
 			remote_backend: self.remote_backend,
 			dht_event_tx: Some(dht_event_tx),
+			warp_sync_provider: self.warp_sync_provider,
+			fork_ranking: self.fork_ranking,
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
+			marker: self.marker,
+		})
+	}
+
+	/// Defines the warp sync proof provider, used to answer warp sync requests and to warp sync
+	/// when the network is configured with `SyncMode::Warp`.
+	pub fn with_warp_sync_provider(
+		self,
+		build: impl FnOnce(Arc<TCl>, Arc<Backend>) -> Result<Arc<dyn WarpSyncProvider<TBl>>, Error>
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+								TNetP, TExPool, TRpc, Backend>, Error> {
+		let warp_sync_provider = build(self.client.clone(), self.backend.clone())?;
+
+		Ok(ServiceBuilder {
+			config: self.config,
+			client: self.client,
+			backend: self.backend,
+			keystore: self.keystore,
+			fetcher: self.fetcher,
+			select_chain: self.select_chain,
+			import_queue: self.import_queue,
+			finality_proof_request_builder: self.finality_proof_request_builder,
+			finality_proof_provider: self.finality_proof_provider,
+			network_protocol: self.network_protocol,
+			transaction_pool: self.transaction_pool,
+			rpc_extensions: self.rpc_extensions,
+			remote_backend: self.remote_backend,
+			dht_event_tx: self.dht_event_tx,
+			warp_sync_provider: Some(warp_sync_provider),
+			fork_ranking: self.fork_ranking,
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
 			marker: self.marker,
 		})
 	}
+
+	/// Defines the ranking of competing forks, used by the sync to download the most likely
+	/// canonical fork first.
+	pub fn with_fork_ranking(
+		self,
+		build: impl FnOnce(Arc<TCl>) -> Result<Arc<dyn ForkRanking<TBl>>, Error>
+	) -> Result<ServiceBuilder<TBl, TRtApi, TCfg, TGen, TCSExt, TCl, TFchr, TSc, TImpQu, TFprb, TFpp,
+								TNetP, TExPool, TRpc, Backend>, Error> {
+		let fork_ranking = build(self.client.clone())?;
+
+		Ok(ServiceBuilder {
+			config: self.config,
+			client: self.client,
+			backend: self.backend,
+			keystore: self.keystore,
+			fetcher: self.fetcher,
+			select_chain: self.select_chain,
+			import_queue: self.import_queue,
+			finality_proof_request_builder: self.finality_proof_request_builder,
+			finality_proof_provider: self.finality_proof_provider,
+			network_protocol: self.network_protocol,
+			transaction_pool: self.transaction_pool,
+			rpc_extensions: self.rpc_extensions,
+			remote_backend: self.remote_backend,
+			dht_event_tx: self.dht_event_tx,
+			warp_sync_provider: self.warp_sync_provider,
+			fork_ranking: Some(fork_ranking),
+			block_import_hooks: self.block_import_hooks,
+			finality_hooks: self.finality_hooks,
+			marker: self.marker,
+		})
+	}
+
+	/// Registers a callback invoked on every block imported by the client, e.g. to index the
+	/// blocks or to maintain auxiliary storage.
+	///
+	/// Blocks of every origin are notified, including the ones imported during the initial sync.
+	/// The callbacks are invoked in the order they are registered, from a task of the service.
+	pub fn with_block_import_hook(
+		mut self,
+		hook: impl FnMut(&BlockImportNotification<TBl>) + Send + 'static,
+	) -> Result<Self, Error> {
+		self.block_import_hooks.push(Box::new(hook));
+		Ok(self)
+	}
+
+	/// Registers a callback invoked on every block finalized by the client.
+	///
+	/// The blocks finalized at once are all notified, in ascending order.
+	/// The callbacks are invoked in the order they are registered, from a task of the service.
+	pub fn with_finality_hook(
+		mut self,
+		hook: impl FnMut(&FinalityNotification<TBl>) + Send + 'static,
+	) -> Result<Self, Error> {
+		self.finality_hooks.push(Box::new(hook));
+		Ok(self)
+	}
 }
 
 /// Implemented on `ServiceBuilder`. Allows running block commands, such as import/export/validate
@@ -664,10 +890,13 @@ pub trait ServiceBuilderCommand {
 	/// Block type this API operates on.
 	type Block: BlockT;
 	/// Starts the process of importing blocks.
+	///
+	/// Blocks are handed to the import queue by batches of `batch_size` blocks.
 	fn import_blocks(
 		self,
-		input: impl Read + Seek + Send + 'static,
+		input: impl Read + Send + 'static,
 		force: bool,
+		batch_size: usize,
 	) -> Box<dyn Future<Item = (), Error = Error> + Send>;
 
 	/// Performs the blocks export.
@@ -680,11 +909,18 @@ pub trait ServiceBuilderCommand {
 	) -> Box<dyn Future<Item = (), Error = Error>>;
 
 	/// Performs a revert of `blocks` blocks.
+	///
+	/// Reverting finalized blocks is refused unless `force` is true. Finalized blocks can only
+	/// be reverted as far as their state is still known.
 	fn revert_chain(
 		&self,
-		blocks: NumberFor<Self::Block>
+		blocks: NumberFor<Self::Block>,
+		force: bool,
 	) -> Result<(), Error>;
 
+	/// Removes the given part of the chain data.
+	fn purge_chain(&self, target: PurgeTarget) -> Result<(), Error>;
+
 	/// Re-validate known block.
 	fn check_block(
 		self,
@@ -730,7 +966,7 @@ ServiceBuilder<
 	TExPool: 'static
 		+ TransactionPool<Block=TBl, Hash = <TBl as BlockT>::Hash>
 		+ TransactionPoolMaintainer<Block=TBl, Hash = <TBl as BlockT>::Hash>,
-	TRpc: sc_rpc::RpcExtension<sc_rpc::Metadata> + Clone,
+	TRpc: RpcExtensionBuilder,
 {
 	/// Builds the service.
 	pub fn build(self) -> Result<Service<
@@ -762,6 +998,10 @@ ServiceBuilder<
 			rpc_extensions,
 			remote_backend,
 			dht_event_tx,
+			warp_sync_provider,
+			fork_ranking,
+			mut block_import_hooks,
+			mut finality_hooks,
 		} = self;
 
 		sp_session::generate_initial_session_keys(
@@ -770,14 +1010,10 @@ ServiceBuilder<
 			config.dev_key_seed.clone().map(|s| vec![s]).unwrap_or_default(),
 		)?;
 
-		let (signal, exit) = exit_future::signal();
-
-		// List of asynchronous tasks to spawn. We collect them, then spawn them all at once.
-		let (to_spawn_tx, to_spawn_rx) =
-			mpsc::unbounded::<Box<dyn Future<Item = (), Error = ()> + Send>>();
-
-		// A side-channel for essential tasks to communicate shutdown.
-		let (essential_failed_tx, essential_failed_rx) = mpsc::unbounded();
+		// The tasks of the service. We collect them, then spawn them all at once.
+		let task_manager = TaskManager::new();
+		let spawn_handle = task_manager.spawn_handle();
+		let exit = task_manager.on_exit();
 
 		let import_queue = Box::new(import_queue);
 		let chain_info = client.info().chain;
@@ -799,7 +1035,7 @@ ServiceBuilder<
 			imports_external_transactions: !config.roles.is_light(),
 			pool: transaction_pool.clone(),
 			client: client.clone(),
-			executor: Arc::new(SpawnTaskHandle { sender: to_spawn_tx.clone(), on_exit: exit.clone() }),
+			executor: Arc::new(spawn_handle.clone()),
 		});
 
 		let protocol_id = {
@@ -824,6 +1060,8 @@ ServiceBuilder<
 			chain: client.clone(),
 			finality_proof_provider,
 			finality_proof_request_builder,
+			warp_sync_provider,
+			fork_ranking,
 			on_demand: on_demand.clone(),
 			transaction_pool: transaction_pool_adapter.clone() as _,
 			import_queue,
@@ -838,9 +1076,13 @@ ServiceBuilder<
 		let network_status_sinks = Arc::new(Mutex::new(status_sinks::StatusSinks::new()));
 
 		let offchain_storage = backend.offchain_storage();
-		let offchain_workers = match (config.offchain_worker, offchain_storage) {
+		let offchain_workers = match (config.offchain_worker, offchain_storage.clone()) {
 			(true, Some(db)) => {
-				Some(Arc::new(sc_offchain::OffchainWorkers::new(client.clone(), db)))
+				Some(Arc::new(sc_offchain::OffchainWorkers::new_with_options(
+					client.clone(),
+					db,
+					config.offchain_worker_options.clone(),
+				)))
 			},
 			(true, None) => {
 				log::warn!("Offchain workers disabled, due to lack of offchain storage support in backend.");
@@ -853,14 +1095,13 @@ ServiceBuilder<
 			// block notifications
 			let txpool = Arc::downgrade(&transaction_pool);
 			let offchain = offchain_workers.as_ref().map(Arc::downgrade);
-			let to_spawn_tx_ = to_spawn_tx.clone();
+			let spawn_handle_ = spawn_handle.clone();
 			let network_state_info: Arc<dyn NetworkStateInfo + Send + Sync> = network.clone();
 			let is_validator = config.roles.is_authority();
 
 			let events = client.import_notification_stream()
 				.map(|v| Ok::<_, ()>(v)).compat()
 				.for_each(move |notification| {
-					let number = *notification.header.number();
 					let txpool = txpool.upgrade();
 
 					if let Some(txpool) = txpool.as_ref() {
@@ -868,21 +1109,73 @@ ServiceBuilder<
 							&BlockId::hash(notification.hash),
 							&notification.retracted,
 						).map(|_| Ok(())).compat();
-						let _ = to_spawn_tx_.unbounded_send(Box::new(future));
+						spawn_handle_.spawn(future);
 					}
 
 					let offchain = offchain.as_ref().and_then(|o| o.upgrade());
 					if let Some(offchain) = offchain {
-						let future = offchain.on_block_imported(&number, network_state_info.clone(), is_validator)
-							.map(|()| Ok(()));
-						let _ = to_spawn_tx_.unbounded_send(Box::new(Compat::new(future)));
+						let future = offchain.on_block_imported(
+							&notification.header,
+							notification.origin,
+							notification.is_new_best,
+							network_state_info.clone(),
+							is_validator,
+						).map(|()| Ok(()));
+						spawn_handle_.spawn(Compat::new(future));
 					}
 
 					Ok(())
 				})
 				.select(exit.clone().map(Ok).compat())
 				.then(|_| Ok(()));
-			let _ = to_spawn_tx.unbounded_send(Box::new(events));
+			spawn_handle.spawn(events);
+		}
+
+		{
+			// finality notifications
+			let txpool = Arc::downgrade(&transaction_pool);
+
+			let events = client.finality_notification_stream()
+				.map(|v| Ok::<_, ()>(v)).compat()
+				.for_each(move |notification| {
+					if let Some(txpool) = txpool.upgrade() {
+						txpool.on_block_finalized(&notification.hash);
+					}
+					Ok(())
+				})
+				.select(exit.clone().map(Ok).compat())
+				.then(|_| Ok(()));
+			spawn_handle.spawn(events);
+		}
+
+		if !block_import_hooks.is_empty() {
+			// hooks of every imported block, including the ones of the initial sync
+			let events = client.every_import_notification_stream()
+				.map(|v| Ok::<_, ()>(v)).compat()
+				.for_each(move |notification| {
+					for hook in block_import_hooks.iter_mut() {
+						hook(&notification);
+					}
+					Ok(())
+				})
+				.select(exit.clone().map(Ok).compat())
+				.then(|_| Ok(()));
+			spawn_handle.spawn(events);
+		}
+
+		if !finality_hooks.is_empty() {
+			// hooks of every finalized block
+			let events = client.every_finality_notification_stream()
+				.map(|v| Ok::<_, ()>(v)).compat()
+				.for_each(move |notification| {
+					for hook in finality_hooks.iter_mut() {
+						hook(&notification);
+					}
+					Ok(())
+				})
+				.select(exit.clone().map(Ok).compat())
+				.then(|_| Ok(()));
+			spawn_handle.spawn(events);
 		}
 
 		{
@@ -905,7 +1198,33 @@ ServiceBuilder<
 				.select(exit.clone().map(Ok).compat())
 				.then(|_| Ok(()));
 
-			let _ = to_spawn_tx.unbounded_send(Box::new(events));
+			spawn_handle.spawn(events);
+		}
+
+		// Transactions submitted locally before the last restart.
+		let local_transactions = if config.persist_local_transactions {
+			match sc_transaction_pool::LocalTransactions::load(client.clone()) {
+				Ok(local_transactions) => Some(Arc::new(local_transactions)),
+				Err(e) => {
+					warn!("Failed to load the local transactions: {:?}", e);
+					None
+				},
+			}
+		} else {
+			None
+		};
+
+		if let Some(local_transactions) = local_transactions.as_ref() {
+			let best_block_id = BlockId::hash(client.info().chain.best_hash);
+			let resubmission = local_transactions.clone()
+				.resubmit(transaction_pool.clone(), best_block_id)
+				.flatten()
+				.unit_error()
+				.boxed()
+				.compat()
+				.select(exit.clone().map(Ok).compat())
+				.then(|_| Ok(()));
+			spawn_handle.spawn(resubmission);
 		}
 
 		// Periodically notify the telemetry.
@@ -968,7 +1287,7 @@ ServiceBuilder<
 
 			Ok(())
 		}).select(exit.clone().map(Ok).compat()).then(|_| Ok(()));
-		let _ = to_spawn_tx.unbounded_send(Box::new(tel_task));
+		spawn_handle.spawn(tel_task);
 
 		// Periodically send the network state to the telemetry.
 		let (netstat_tx, netstat_rx) = mpsc::unbounded::<(NetworkStatus<_>, NetworkState)>();
@@ -981,12 +1300,12 @@ ServiceBuilder<
 			);
 			Ok(())
 		}).select(exit.clone().map(Ok).compat()).then(|_| Ok(()));
-		let _ = to_spawn_tx.unbounded_send(Box::new(tel_task_2));
+		spawn_handle.spawn(tel_task_2);
 
 		// RPC
 		let (system_rpc_tx, system_rpc_rx) = futures03::channel::mpsc::unbounded();
-		let gen_handler = || {
-			use sc_rpc::{chain, state, author, system};
+		let gen_handler = |deny_unsafe: sc_rpc::DenyUnsafe, limits: sc_rpc_server::RpcLimits| {
+			use sc_rpc::{chain, state, author, system, offchain};
 
 			let system_info = sc_rpc::system::SystemInfo {
 				chain_name: config.chain_spec.name().into(),
@@ -995,12 +1314,12 @@ ServiceBuilder<
 				properties: config.chain_spec.properties().clone(),
 			};
 
-			let subscriptions = sc_rpc::Subscriptions::new(Arc::new(SpawnTaskHandle {
-				sender: to_spawn_tx.clone(),
-				on_exit: exit.clone()
-			}));
+			// the middleware frees the subscription slot of a connection when a subscription ends.
+			let middleware = sc_rpc_server::RpcMiddleware::new(limits);
+			let subscriptions = sc_rpc::Subscriptions::new(Arc::new(spawn_handle.clone()))
+				.with_observer(Arc::new(middleware.clone()));
 
-			let (chain, state) = if let (Some(remote_backend), Some(on_demand)) =
+			let (chain, state, child_state) = if let (Some(remote_backend), Some(on_demand)) =
 				(remote_backend.as_ref(), on_demand.as_ref()) {
 				// Light clients
 				let chain = sc_rpc::chain::new_light(
@@ -1009,19 +1328,19 @@ ServiceBuilder<
 					remote_backend.clone(),
 					on_demand.clone()
 				);
-				let state = sc_rpc::state::new_light(
+				let (state, child_state) = sc_rpc::state::new_light(
 					client.clone(),
 					subscriptions.clone(),
 					remote_backend.clone(),
 					on_demand.clone()
 				);
-				(chain, state)
+				(chain, state, child_state)
 
 			} else {
 				// Full nodes
 				let chain = sc_rpc::chain::new_full(client.clone(), subscriptions.clone());
-				let state = sc_rpc::state::new_full(client.clone(), subscriptions.clone());
-				(chain, state)
+				let (state, child_state) = sc_rpc::state::new_full(client.clone(), subscriptions.clone());
+				(chain, state, child_state)
 			};
 
 			let author = sc_rpc::author::Author::new(
@@ -1029,22 +1348,38 @@ ServiceBuilder<
 				transaction_pool.clone(),
 				subscriptions,
 				keystore.clone(),
+				deny_unsafe,
 			);
-			let system = system::System::new(system_info, system_rpc_tx.clone());
+			let author = match local_transactions.as_ref() {
+				Some(local_transactions) => author.with_local_transactions(local_transactions.clone()),
+				None => author,
+			};
+			let system = system::System::new(system_info, system_rpc_tx.clone(), deny_unsafe);
+
+			let maybe_offchain_rpc = offchain_storage.clone()
+				.map(|storage| {
+					let offchain = sc_rpc::offchain::Offchain::new(storage, deny_unsafe);
+					// `IoHandlerExtension` is not implemented for `Option` in jsonrpc 14.0.
+					offchain::OffchainApi::to_delegate(offchain).into_iter().collect::<HashMap<_, _>>()
+				})
+				.unwrap_or_default();
 
 			sc_rpc_server::rpc_handler((
 				state::StateApi::to_delegate(state),
+				state::ChildStateApi::to_delegate(child_state),
 				chain::ChainApi::to_delegate(chain),
 				author::AuthorApi::to_delegate(author),
 				system::SystemApi::to_delegate(system),
-				rpc_extensions.clone(),
-			))
+				maybe_offchain_rpc,
+				rpc_extensions.build(deny_unsafe),
+			), middleware)
 		};
-		let rpc_handlers = gen_handler();
+		// the in-memory RPC handler is only reachable from within the node
+		let rpc_handlers = gen_handler(sc_rpc::DenyUnsafe::No, Default::default());
 		let rpc = start_rpc_servers(&config, gen_handler)?;
 
 
-		let _ = to_spawn_tx.unbounded_send(Box::new(build_network_future(
+		spawn_handle.spawn(build_network_future(
 			config.roles,
 			network_mut,
 			client.clone(),
@@ -1055,7 +1390,7 @@ ServiceBuilder<
 		)
 			.map_err(|_| ())
 			.select(exit.clone().map(Ok).compat())
-			.then(|_| Ok(()))));
+			.then(|_| Ok(())));
 
 		let telemetry_connection_sinks: Arc<Mutex<Vec<mpsc::UnboundedSender<()>>>> = Default::default();
 
@@ -1098,9 +1433,9 @@ ServiceBuilder<
 					});
 					Ok(())
 				});
-			let _ = to_spawn_tx.unbounded_send(Box::new(future
+			spawn_handle.spawn(future
 				.select(exit.clone().map(Ok).compat())
-				.then(|_| Ok(()))));
+				.then(|_| Ok(())));
 			telemetry
 		});
 
@@ -1114,9 +1449,26 @@ ServiceBuilder<
 				Either::Right(_) => Ok(())
 			}).compat();
 
-			let _ = to_spawn_tx.unbounded_send(Box::new(future));
+			spawn_handle.spawn(future);
     }
 
+		// Prometheus endpoint
+		let prometheus_registry = if let Some(PrometheusConfig { port, registry }) = config.prometheus_config.clone() {
+			let future = select(
+				prometheus_endpoint::init_prometheus(port, registry.clone()).boxed(),
+				exit.clone()
+			).map(|either| match either {
+				Either::Left((result, _)) => result.map_err(|_| ()),
+				Either::Right(_) => Ok(())
+			}).compat();
+
+			spawn_handle.spawn(future);
+
+			Some(registry)
+		} else {
+			None
+		};
+
 		// Instrumentation
 		if let Some(tracing_targets) = config.tracing_targets.as_ref() {
 			let subscriber = sc_tracing::ProfilingSubscriber::new(
@@ -1134,19 +1486,14 @@ ServiceBuilder<
 			network_status_sinks,
 			select_chain,
 			transaction_pool,
-			exit,
-			signal: Some(signal),
-			essential_failed_tx,
-			essential_failed_rx,
-			to_spawn_tx,
-			to_spawn_rx,
-			to_poll: Vec::new(),
+			task_manager,
 			rpc_handlers,
 			_rpc: rpc,
 			_telemetry: telemetry,
 			_offchain_workers: offchain_workers,
 			_telemetry_on_connect_sinks: telemetry_connection_sinks.clone(),
 			keystore,
+			prometheus_registry,
 			marker: PhantomData::<TBl>,
 		})
 	}
@@ -19,6 +19,7 @@
 use crate::error;
 use crate::builder::{ServiceBuilderCommand, ServiceBuilder};
 use crate::error::Error;
+use crate::PurgeTarget;
 use sc_chain_spec::{ChainSpec, RuntimeGenesis, Extension};
 use log::{warn, info};
 use futures::{future, prelude::*};
@@ -36,11 +37,18 @@ use sp_consensus::import_queue::{IncomingBlock, Link, BlockImportError, BlockImp
 use sp_consensus::BlockOrigin;
 
 use std::{
-	io::{Read, Write, Seek},
+	io::{Read, Write},
+	time::Instant,
 };
 
 use sc_network::message;
 
+/// Maximal number of batches handed to the import queue and not imported yet.
+///
+/// Reading the input is paused when the import queue lags behind, so that the input isn't
+/// buffered in memory.
+const MAX_PENDING_IMPORT_BATCHES: u64 = 4;
+
 /// Build a chain spec json
 pub fn build_spec<G, E>(spec: ChainSpec<G, E>, raw: bool) -> error::Result<String> where
 	G: RuntimeGenesis,
@@ -67,8 +75,9 @@ impl<
 
 	fn import_blocks(
 		self,
-		input: impl Read + Seek + Send + 'static,
+		input: impl Read + Send + 'static,
 		force: bool,
+		batch_size: usize,
 	) -> Box<dyn Future<Item = (), Error = Error> + Send> {
 		struct WaitLink {
 			imported_blocks: u64,
@@ -110,6 +119,10 @@ impl<
 		let mut count = None::<u64>;
 		let mut read_block_count = 0;
 		let mut link = WaitLink::new();
+		let batch_size = batch_size.max(1);
+		let max_pending_blocks = batch_size as u64 * MAX_PENDING_IMPORT_BATCHES;
+		let mut batch = Vec::with_capacity(batch_size);
+		let started = Instant::now();
 
 		// Importing blocks is implemented as a future, because we want the operation to be
 		// interruptible.
@@ -136,8 +149,9 @@ impl<
 				}
 			};
 
-			// Read blocks from the input.
-			if read_block_count < count {
+			// Read blocks from the input, unless the import queue lags behind.
+			let pending_blocks = read_block_count.saturating_sub(link.imported_blocks);
+			if read_block_count < count && pending_blocks < max_pending_blocks {
 				match SignedBlock::<Self::Block>::decode(&mut io_reader_input) {
 					Ok(signed) => {
 						let (header, extrinsics) = signed.block.deconstruct();
@@ -150,18 +164,16 @@ impl<
 							receipt: None,
 							message_queue: None
 						};
-						// import queue handles verification and importing it into the client
-						queue.import_blocks(BlockOrigin::File, vec![
-							IncomingBlock::<Self::Block> {
-								hash: block.hash,
-								header: block.header,
-								body: block.body,
-								justification: block.justification,
-								origin: None,
-								allow_missing_state: false,
-								import_existing: force,
-							}
-						]);
+						batch.push(IncomingBlock::<Self::Block> {
+							hash: block.hash,
+							header: block.header,
+							body: block.body,
+							justification: block.justification,
+							origin: None,
+							allow_missing_state: false,
+							import_existing: force,
+							state: None,
+						});
 					}
 					Err(e) => {
 						warn!("Error reading block data at {}: {}", read_block_count, e);
@@ -170,6 +182,11 @@ impl<
 				}
 
 				read_block_count += 1;
+				if batch.len() >= batch_size || read_block_count == count {
+					// import queue handles verification and importing it into the client
+					let blocks = std::mem::replace(&mut batch, Vec::with_capacity(batch_size));
+					queue.import_blocks(BlockOrigin::File, blocks);
+				}
 				if read_block_count % 1000 == 0 {
 					info!("#{} blocks were added to the queue", read_block_count);
 				}
@@ -190,13 +207,21 @@ impl<
 			}
 
 			if link.imported_blocks / 1000 != blocks_before / 1000 {
+				let elapsed = started.elapsed().as_secs().max(1);
 				info!(
-					"#{} blocks were imported (#{} left)",
+					"#{} blocks were imported (#{} left, {} blocks/s)",
 					link.imported_blocks,
-					count - link.imported_blocks
+					count - link.imported_blocks,
+					link.imported_blocks / elapsed,
 				);
 			}
 
+			// Resume reading the input once the import queue caught up.
+			let pending_blocks = read_block_count.saturating_sub(link.imported_blocks);
+			if read_block_count < count && pending_blocks < max_pending_blocks {
+				cx.waker().wake_by_ref();
+			}
+
 			if link.imported_blocks >= count {
 				info!("Imported {} blocks. Best: #{}", read_block_count, client.info().chain.best_number);
 				return std::task::Poll::Ready(Ok(()));
@@ -260,12 +285,16 @@ impl<
 					}
 				},
 				// Reached end of the chain.
-				None => return std::task::Poll::Ready(Ok(())),
+				None => {
+					output.flush()?;
+					return std::task::Poll::Ready(Ok(()));
+				},
 			}
 			if (block % 10000.into()).is_zero() {
-				info!("#{}", block);
+				info!("#{} blocks were exported (#{} left)", block, last - block);
 			}
 			if block == last {
+				output.flush()?;
 				return std::task::Poll::Ready(Ok(()));
 			}
 			block += One::one();
@@ -280,19 +309,42 @@ impl<
 
 	fn revert_chain(
 		&self,
-		blocks: NumberFor<TBl>
+		blocks: NumberFor<TBl>,
+		force: bool,
 	) -> Result<(), Error> {
-		let reverted = self.client.revert(blocks)?;
+		let info = self.client.info().chain;
+		let revertible = info.best_number - info.finalized_number;
+		if blocks > revertible && !force {
+			return Err(Error::Other(format!(
+				"Cannot revert {} blocks: only {} blocks are above the last finalized block #{}. \
+				Use --force to revert finalized blocks as well.",
+				blocks, revertible, info.finalized_number,
+			)));
+		}
+
+		let reverted = self.client.revert(blocks, force)?;
 		let info = self.client.info().chain;
 
 		if reverted.is_zero() {
-			info!("There aren't any non-finalized blocks to revert.");
+			info!("There aren't any blocks to revert.");
 		} else {
-			info!("Reverted {} blocks. Best: #{} ({})", reverted, info.best_number, info.best_hash);
+			info!(
+				"Reverted {} blocks. Best: #{} ({}), finalized: #{} ({})",
+				reverted, info.best_number, info.best_hash, info.finalized_number, info.finalized_hash,
+			);
+		}
+		if reverted < blocks {
+			warn!("Only {} of the {} blocks could be reverted.", reverted, blocks);
 		}
 		Ok(())
 	}
 
+	fn purge_chain(&self, target: PurgeTarget) -> Result<(), Error> {
+		self.client.purge(target)?;
+		info!("Purged {}.", target);
+		Ok(())
+	}
+
 	fn check_block(
 		self,
 		block_id: BlockId<TBl>
@@ -303,7 +355,7 @@ impl<
 				1u64.encode_to(&mut buf);
 				block.encode_to(&mut buf);
 				let reader = std::io::Cursor::new(buf);
-				self.import_blocks(reader, true)
+				self.import_blocks(reader, true, 1)
 			}
 			Ok(None) => Box::new(future::err("Unknown block".into())),
 			Err(e) => Box::new(future::err(format!("Error reading block: {:?}", e).into())),
@@ -17,16 +17,58 @@
 //! Service configuration.
 
 pub use sc_client::ExecutionStrategies;
-pub use sc_client_db::{kvdb::KeyValueDB, PruningMode};
+pub use sc_client_db::{kvdb::KeyValueDB, PruningMode, ColumnCacheSizes};
 pub use sc_network::config::{ExtTransport, NetworkConfiguration, Roles};
 pub use sc_executor::WasmExecutionMethod;
+pub use sc_rpc_server::RpcLimits;
 
 use std::{path::PathBuf, net::SocketAddr, sync::Arc};
 pub use sc_transaction_pool::txpool::Options as TransactionPoolOptions;
+pub use sc_offchain::{OffchainWorkerOptions, OffchainWorkerPolicy};
 use sc_chain_spec::{ChainSpec, RuntimeGenesis, Extension, NoExtension};
 use sp_core::crypto::Protected;
 use target_info::Target;
 use sc_telemetry::TelemetryEndpoints;
+use prometheus_endpoint::Registry;
+
+/// Configuration of the Prometheus endpoint.
+#[derive(Clone)]
+pub struct PrometheusConfig {
+	/// Address the endpoint listens on.
+	pub port: SocketAddr,
+	/// Registry the metrics are registered with. It is created along with the configuration, so
+	/// that the client and the import queue can register their metrics before the service starts.
+	pub registry: Registry,
+}
+
+impl PrometheusConfig {
+	/// Create a configuration with a new registry, whose metrics are prefixed with `substrate`.
+	pub fn new_with_default_registry(port: SocketAddr) -> Self {
+		PrometheusConfig {
+			port,
+			registry: Registry::new_custom(Some("substrate".into()), None)
+				.expect("the prefix is not empty, which is the only reason for this to fail; qed"),
+		}
+	}
+}
+
+/// The RPC methods that are exposed over the HTTP and WS servers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RpcMethods {
+	/// Expose every RPC method only when the servers listen on a localhost interface, otherwise
+	/// deny the unsafe ones.
+	Auto,
+	/// Only expose the safe RPC methods, denying the unsafe ones.
+	Safe,
+	/// Expose every RPC method, unsafe ones included.
+	Unsafe,
+}
+
+impl Default for RpcMethods {
+	fn default() -> RpcMethods {
+		RpcMethods::Auto
+	}
+}
 
 /// Service configuration.
 #[derive(Clone)]
@@ -41,14 +83,21 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub roles: Roles,
 	/// Extrinsic pool configuration.
 	pub transaction_pool: TransactionPoolOptions,
+	/// Persist the extrinsics submitted locally until they are finalized, and submit them again
+	/// on startup.
+	pub persist_local_transactions: bool,
 	/// Network configuration.
 	pub network: NetworkConfiguration,
 	/// Path to the base configuration directory.
 	pub config_dir: Option<PathBuf>,
 	/// Path to key files.
 	pub keystore_path: Option<PathBuf>,
+	/// URI of a remote signer holding the keys, used instead of the keystore at `keystore_path`.
+	pub keystore_uri: Option<String>,
 	/// Configuration for the database.
 	pub database: DatabaseConfig,
+	/// Sizes of the caches of the block and state columns of the database, in bytes.
+	pub column_cache_sizes: ColumnCacheSizes,
 	/// Size of internal state cache in Bytes
 	pub state_cache_size: usize,
 	/// Size in percent of cache size dedicated to child tries
@@ -65,6 +114,11 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub wasm_method: WasmExecutionMethod,
 	/// Execution strategies.
 	pub execution_strategies: ExecutionStrategies,
+	/// Number of workers verifying the blocks to import in parallel. The blocks are verified on
+	/// the import thread if less than two.
+	pub import_verification_workers: usize,
+	/// RPC over IPC binding path. `None` if disabled.
+	pub rpc_ipc: Option<String>,
 	/// RPC over HTTP binding address. `None` if disabled.
 	pub rpc_http: Option<SocketAddr>,
 	/// RPC over Websockets binding address. `None` if disabled.
@@ -73,8 +127,14 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub rpc_ws_max_connections: Option<usize>,
 	/// CORS settings for HTTP & WS servers. `None` if all origins are allowed.
 	pub rpc_cors: Option<Vec<String>>,
+	/// RPC methods to expose over the HTTP & WS servers.
+	pub rpc_methods: RpcMethods,
+	/// Per-connection limits enforced by the HTTP & WS servers.
+	pub rpc_limits: RpcLimits,
 	/// Grafana data source http port. `None` if disabled.
 	pub grafana_port: Option<SocketAddr>,
+	/// Prometheus endpoint configuration. `None` if disabled.
+	pub prometheus_config: Option<PrometheusConfig>,
 	/// Telemetry service URL. `None` if disabled.
 	pub telemetry_endpoints: Option<TelemetryEndpoints>,
 	/// External WASM transport for the telemetry. If `Some`, when connection to a telemetry
@@ -84,6 +144,11 @@ pub struct Configuration<C, G, E = NoExtension> {
 	pub default_heap_pages: Option<u64>,
 	/// Should offchain workers be executed.
 	pub offchain_worker: bool,
+	/// Options of the offchain workers, if executed.
+	pub offchain_worker_options: OffchainWorkerOptions,
+	/// Should the data indexed by the runtime during block import be written to the offchain
+	/// database.
+	pub offchain_indexing: bool,
 	/// Sentry mode is enabled, the node's role is AUTHORITY but it should not
 	/// actively participate in consensus (i.e. no keystores should be passed to
 	/// consensus modules).
@@ -117,6 +182,12 @@ pub enum DatabaseConfig {
 		cache_size: Option<u32>,
 	},
 
+	/// ParityDb database at a specific path.
+	ParityDb {
+		/// Path to the database.
+		path: PathBuf,
+	},
+
 	/// A custom implementation of an already-open database.
 	Custom(Arc<dyn KeyValueDB>),
 }
@@ -137,27 +208,37 @@ impl<C, G, E> Configuration<C, G, E> where
 			name: Default::default(),
 			roles: Roles::FULL,
 			transaction_pool: Default::default(),
+			persist_local_transactions: false,
 			network: Default::default(),
 			keystore_path: config_dir.map(|c| c.join("keystore")),
+			keystore_uri: None,
 			database: DatabaseConfig::Path {
 				path: Default::default(),
 				cache_size: Default::default(),
 			},
+			column_cache_sizes: Default::default(),
 			state_cache_size: Default::default(),
 			state_cache_child_ratio: Default::default(),
 			custom: Default::default(),
 			pruning: PruningMode::default(),
 			wasm_method: WasmExecutionMethod::Interpreted,
 			execution_strategies: Default::default(),
+			import_verification_workers: 1,
+			rpc_ipc: None,
 			rpc_http: None,
 			rpc_ws: None,
 			rpc_ws_max_connections: None,
 			rpc_cors: Some(vec![]),
+			rpc_methods: Default::default(),
+			rpc_limits: Default::default(),
 			grafana_port: None,
+			prometheus_config: None,
 			telemetry_endpoints: None,
 			telemetry_external_transport: None,
 			default_heap_pages: None,
 			offchain_worker: Default::default(),
+			offchain_worker_options: Default::default(),
+			offchain_indexing: false,
 			sentry_mode: false,
 			force_authoring: false,
 			disable_grandpa: false,
@@ -26,6 +26,7 @@ pub mod error;
 
 mod builder;
 mod status_sinks;
+mod task_manager;
 
 use std::io;
 use std::marker::PhantomData;
@@ -36,7 +37,6 @@ use futures::sync::mpsc;
 use parking_lot::Mutex;
 
 use sc_client::Client;
-use exit_future::Signal;
 use futures::prelude::*;
 use futures03::{
 	future::{ready, FutureExt as _, TryFutureExt as _},
@@ -46,22 +46,29 @@ use sc_network::{
 	NetworkService, NetworkState, specialization::NetworkSpecialization,
 	Event, DhtEvent, PeerId, ReportHandle,
 };
-use log::{log, warn, debug, error, Level};
+use log::{log, warn, debug, Level};
 use codec::{Encode, Decode};
 use sp_core::{Blake2Hasher, H256};
 use sp_runtime::generic::BlockId;
 use sp_runtime::traits::{NumberFor, Block as BlockT};
+use sp_runtime::transaction_validity::TransactionSource;
 
 pub use self::error::Error;
 pub use self::builder::{
 	ServiceBuilder, ServiceBuilderCommand, TFullClient, TLightClient, TFullBackend, TLightBackend,
-	TFullCallExecutor, TLightCallExecutor,
+	TFullCallExecutor, TLightCallExecutor, BlockImportHook, FinalityHook,
+	RpcExtensionBuilder, NoopRpcExtensionBuilder,
 };
-pub use config::{Configuration, Roles, PruningMode};
+pub use config::{
+	Configuration, Roles, PruningMode, RpcMethods, RpcLimits, OffchainWorkerOptions, OffchainWorkerPolicy,
+};
+pub use task_manager::{TaskManager, SpawnTaskHandle, RunningTasks};
 pub use sc_chain_spec::{ChainSpec, Properties, RuntimeGenesis, Extension as ChainSpecExtension};
 pub use sp_transaction_pool::{TransactionPool, TransactionPoolMaintainer, InPoolTransaction, error::IntoPoolError};
 pub use sc_transaction_pool::txpool::Options as TransactionPoolOptions;
 pub use sc_client::FinalityNotifications;
+pub use sc_client_api::backend::PurgeTarget;
+pub use sc_client_db::migrate_to_parity_db;
 pub use sc_rpc::Metadata as RpcMetadata;
 #[doc(hidden)]
 pub use std::{ops::Deref, result::Result, sync::Arc};
@@ -81,67 +88,21 @@ pub struct Service<TBl, TCl, TSc, TNetStatus, TNet, TTxPool, TOc> {
 	/// For each element, every time the `Interval` fires we push an element on the sender.
 	network_status_sinks: Arc<Mutex<status_sinks::StatusSinks<(TNetStatus, NetworkState)>>>,
 	transaction_pool: Arc<TTxPool>,
-	/// A future that resolves when the service has exited, this is useful to
-	/// make sure any internally spawned futures stop when the service does.
-	exit: exit_future::Exit,
-	/// A signal that makes the exit future above resolve, fired on service drop.
-	signal: Option<Signal>,
-	/// Send a signal when a spawned essential task has concluded. The next time
-	/// the service future is polled it should complete with an error.
-	essential_failed_tx: mpsc::UnboundedSender<()>,
-	/// A receiver for spawned essential-tasks concluding.
-	essential_failed_rx: mpsc::UnboundedReceiver<()>,
-	/// Sender for futures that must be spawned as background tasks.
-	to_spawn_tx: mpsc::UnboundedSender<Box<dyn Future<Item = (), Error = ()> + Send>>,
-	/// Receiver for futures that must be spawned as background tasks.
-	to_spawn_rx: mpsc::UnboundedReceiver<Box<dyn Future<Item = (), Error = ()> + Send>>,
-	/// List of futures to poll from `poll`.
-	/// If spawning a background task is not possible, we instead push the task into this `Vec`.
-	/// The elements must then be polled manually.
-	to_poll: Vec<Box<dyn Future<Item = (), Error = ()> + Send>>,
+	/// The tasks spawned by the service, stopped on service drop.
+	task_manager: TaskManager,
 	rpc_handlers: sc_rpc_server::RpcHandler<sc_rpc::Metadata>,
 	_rpc: Box<dyn std::any::Any + Send + Sync>,
 	_telemetry: Option<sc_telemetry::Telemetry>,
 	_telemetry_on_connect_sinks: Arc<Mutex<Vec<mpsc::UnboundedSender<()>>>>,
 	_offchain_workers: Option<Arc<TOc>>,
 	keystore: sc_keystore::KeyStorePtr,
+	prometheus_registry: Option<prometheus_endpoint::Registry>,
 	marker: PhantomData<TBl>,
 }
 
 /// Alias for a an implementation of `futures::future::Executor`.
 pub type TaskExecutor = Arc<dyn Executor<Box<dyn Future<Item = (), Error = ()> + Send>> + Send + Sync>;
 
-/// An handle for spawning tasks in the service.
-#[derive(Clone)]
-pub struct SpawnTaskHandle {
-	sender: mpsc::UnboundedSender<Box<dyn Future<Item = (), Error = ()> + Send>>,
-	on_exit: exit_future::Exit,
-}
-
-impl Executor<Box<dyn Future<Item = (), Error = ()> + Send>> for SpawnTaskHandle {
-	fn execute(
-		&self,
-		future: Box<dyn Future<Item = (), Error = ()> + Send>,
-	) -> Result<(), futures::future::ExecuteError<Box<dyn Future<Item = (), Error = ()> + Send>>> {
-		let exit = self.on_exit.clone().map(Ok).compat();
-		let future = Box::new(future.select(exit).then(|_| Ok(())));
-		if let Err(err) = self.sender.unbounded_send(future) {
-			let kind = futures::future::ExecuteErrorKind::Shutdown;
-			Err(futures::future::ExecuteError::new(kind, err.into_inner()))
-		} else {
-			Ok(())
-		}
-	}
-}
-
-impl futures03::task::Spawn for SpawnTaskHandle {
-	fn spawn_obj(&self, future: futures03::task::FutureObj<'static, ()>)
-	-> Result<(), futures03::task::SpawnError> {
-		self.execute(Box::new(futures03::compat::Compat::new(future.unit_error())))
-			.map_err(|_| futures03::task::SpawnError::shutdown())
-	}
-}
-
 /// Abstraction over a Substrate service.
 pub trait AbstractService: 'static + Future<Item = (), Error = Error> +
 	Executor<Box<dyn Future<Item = (), Error = ()> + Send>> + Send {
@@ -178,9 +139,18 @@ pub trait AbstractService: 'static + Future<Item = (), Error = Error> +
 	/// Returns a handle for spawning tasks.
 	fn spawn_task_handle(&self) -> SpawnTaskHandle;
 
+	/// Returns a handle tracking the spawned tasks which did not conclude yet.
+	///
+	/// Once the service is dropped, it allows to wait for its tasks to stop.
+	fn running_tasks(&self) -> RunningTasks;
+
 	/// Returns the keystore that stores keys.
 	fn keystore(&self) -> sc_keystore::KeyStorePtr;
 
+	/// Returns the Prometheus registry to register metrics with. `None` if the Prometheus endpoint
+	/// is disabled.
+	fn prometheus_registry(&self) -> Option<prometheus_endpoint::Registry>;
+
 	/// Starts an RPC query.
 	///
 	/// The query is passed as a string and must be a JSON text similar to what an HTTP client
@@ -247,32 +217,24 @@ where
 		self.keystore.clone()
 	}
 
+	fn prometheus_registry(&self) -> Option<prometheus_endpoint::Registry> {
+		self.prometheus_registry.clone()
+	}
+
 	fn spawn_task(&self, task: impl Future<Item = (), Error = ()> + Send + 'static) {
-		let exit = self.on_exit().map(Ok).compat();
-		let task = task.select(exit).then(|_| Ok(()));
-		let _ = self.to_spawn_tx.unbounded_send(Box::new(task));
+		self.task_manager.spawn(task)
 	}
 
 	fn spawn_essential_task(&self, task: impl Future<Item = (), Error = ()> + Send + 'static) {
-		let essential_failed = self.essential_failed_tx.clone();
-		let essential_task = std::panic::AssertUnwindSafe(task)
-			.catch_unwind()
-			.then(move |_| {
-				error!("Essential task failed. Shutting down service.");
-				let _ = essential_failed.send(());
-				Ok(())
-			});
-		let exit = self.on_exit().map(Ok::<_, ()>).compat();
-		let task = essential_task.select(exit).then(|_| Ok(()));
-
-		let _ = self.to_spawn_tx.unbounded_send(Box::new(task));
+		self.task_manager.spawn_essential(task)
 	}
 
 	fn spawn_task_handle(&self) -> SpawnTaskHandle {
-		SpawnTaskHandle {
-			sender: self.to_spawn_tx.clone(),
-			on_exit: self.on_exit(),
-		}
+		self.task_manager.spawn_handle()
+	}
+
+	fn running_tasks(&self) -> RunningTasks {
+		self.task_manager.running_tasks()
 	}
 
 	fn rpc_query(&self, mem: &RpcSession, request: &str) -> Box<dyn Future<Item = Option<String>, Error = ()> + Send> {
@@ -302,7 +264,7 @@ where
 	}
 
 	fn on_exit(&self) -> exit_future::Exit {
-		self.exit.clone()
+		self.task_manager.on_exit()
 	}
 }
 
@@ -313,34 +275,8 @@ impl<TBl, TCl, TSc, TNetStatus, TNet, TTxPool, TOc> Future for
 	type Error = Error;
 
 	fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-		match self.essential_failed_rx.poll() {
-			Ok(Async::NotReady) => {},
-			Ok(Async::Ready(_)) | Err(_) => {
-				// Ready(None) should not be possible since we hold a live
-				// sender.
-				return Err(Error::Other("Essential task failed.".into()));
-			}
-		}
-
-		while let Ok(Async::Ready(Some(task_to_spawn))) = self.to_spawn_rx.poll() {
-			let executor = tokio_executor::DefaultExecutor::current();
-			if let Err(err) = executor.execute(task_to_spawn) {
-				debug!(
-					target: "service",
-					"Failed to spawn background task: {:?}; falling back to manual polling",
-					err
-				);
-				self.to_poll.push(err.into_future());
-			}
-		}
-
-		// Polling all the `to_poll` futures.
-		while let Some(pos) = self.to_poll.iter_mut().position(|t| t.poll().map(|t| t.is_ready()).unwrap_or(true)) {
-			let _ = self.to_poll.remove(pos);
-		}
-
-		// The service future never ends.
-		Ok(Async::NotReady)
+		// The service future never ends, unless an essential task failed.
+		self.task_manager.poll()
 	}
 }
 
@@ -351,12 +287,7 @@ impl<TBl, TCl, TSc, TNetStatus, TNet, TTxPool, TOc> Executor<Box<dyn Future<Item
 		&self,
 		future: Box<dyn Future<Item = (), Error = ()> + Send>
 	) -> Result<(), futures::future::ExecuteError<Box<dyn Future<Item = (), Error = ()> + Send>>> {
-		if let Err(err) = self.to_spawn_tx.unbounded_send(future) {
-			let kind = futures::future::ExecuteErrorKind::Shutdown;
-			Err(futures::future::ExecuteError::new(kind, err.into_inner()))
-		} else {
-			Ok(())
-		}
+		self.task_manager.spawn_handle().execute(future)
 	}
 }
 
@@ -365,7 +296,7 @@ impl<TBl, TCl, TSc, TNetStatus, TNet, TTxPool, TOc> Executor<Box<dyn Future<Item
 /// The `status_sink` contain a list of senders to send a periodic network status to.
 fn build_network_future<
 	B: BlockT,
-	C: sc_client::BlockchainEvents<B>,
+	C: sc_client::BlockchainEvents<B> + sp_blockchain::HeaderBackend<B>,
 	S: sc_network::specialization::NetworkSpecialization<B>,
 	H: sc_network::ExHashT
 > (
@@ -389,6 +320,11 @@ fn build_network_future<
 	// Initializing a stream in order to obtain DHT events from the network.
 	let mut event_stream = network.service().event_stream();
 
+	// The progress of the sync reported to the RPC and to the status sinks.
+	let mut sync_state_stream = network.service().sync_state_stream();
+	let mut sync_state = sc_network::SyncStateEvent::Starting;
+	let starting_block = client.info().best_number;
+
 	futures::future::poll_fn(move || {
 		let before_polling = Instant::now();
 
@@ -406,6 +342,10 @@ fn build_network_future<
 			network.on_block_finalized(notification.hash, notification.header);
 		}
 
+		while let Ok(Async::Ready(Some(event))) = sync_state_stream.poll() {
+			sync_state = event;
+		}
+
 		// Poll the RPC requests and answer them.
 		while let Ok(Async::Ready(Some(request))) = rpc_rx.poll() {
 			match request {
@@ -416,6 +356,35 @@ fn build_network_future<
 						should_have_peers,
 					});
 				},
+				sc_rpc::system::Request::SyncState(sender) => {
+					let _ = sender.send(match sync_state {
+						sc_network::SyncStateEvent::Starting => sc_rpc::system::SyncState::Starting,
+						sc_network::SyncStateEvent::Downloading { target, current, peers } =>
+							sc_rpc::system::SyncState::Downloading { target, current, peers },
+						sc_network::SyncStateEvent::Importing { queued } =>
+							sc_rpc::system::SyncState::Importing { queued },
+						sc_network::SyncStateEvent::Idle => sc_rpc::system::SyncState::Idle,
+					});
+				},
+				sc_rpc::system::Request::SyncProgress(sender) => {
+					let _ = sender.send(sc_rpc::system::SyncProgress {
+						starting_block,
+						current_block: client.info().best_number,
+						highest_block: network.best_seen_block(),
+					});
+				},
+				sc_rpc::system::Request::LocalListenAddresses(sender) => {
+					let peer_id = network.local_peer_id().clone().into();
+					let p2p_proto_suffix = sc_network::multiaddr::Protocol::P2p(peer_id);
+					let mut addresses = Vec::<String>::new();
+					for address in network.listen_addresses().chain(network.external_addresses()) {
+						let address = address.clone().with(p2p_proto_suffix.clone()).to_string();
+						if !addresses.contains(&address) {
+							addresses.push(address);
+						}
+					}
+					let _ = sender.send(addresses);
+				},
 				sc_rpc::system::Request::Peers(sender) => {
 					let _ = sender.send(network.peers_debug_info().into_iter().map(|(peer_id, p)|
 						sc_rpc::system::PeerInfo {
@@ -427,6 +396,17 @@ fn build_network_future<
 						}
 					).collect());
 				}
+				sc_rpc::system::Request::PeerReputations(sender) => {
+					let _ = sender.send(network.peer_reputations().into_iter().map(|p|
+						sc_rpc::system::PeerReputationInfo {
+							peer_id: p.peer_id.to_base58(),
+							reputation: p.reputation,
+							connected: p.connected,
+							banned: p.banned,
+							last_report_reason: p.last_report_reason.map(Into::into),
+						}
+					).collect());
+				}
 				sc_rpc::system::Request::NetworkState(sender) => {
 					if let Some(network_state) = serde_json::to_value(&network.network_state()).ok() {
 						let _ = sender.send(network_state);
@@ -448,6 +428,10 @@ fn build_network_future<
 						))),
 					};
 				}
+				sc_rpc::system::Request::NetworkSetReservedOnly(reserved_only, sender) => {
+					network.set_reserved_only(reserved_only);
+					let _ = sender.send(());
+				}
 				sc_rpc::system::Request::NodeRoles(sender) => {
 					use sc_rpc::system::NodeRole;
 
@@ -470,6 +454,7 @@ fn build_network_future<
 		status_sinks.lock().poll(|| {
 			let status = NetworkStatus {
 				sync_state: network.sync_state(),
+				sync_progress: sync_state.clone(),
 				best_seen_block: network.best_seen_block(),
 				num_sync_peers: network.num_sync_peers(),
 				num_connected_peers: network.num_connected_peers(),
@@ -526,6 +511,8 @@ fn build_network_future<
 pub struct NetworkStatus<B: BlockT> {
 	/// Current global sync state.
 	pub sync_state: sc_network::SyncState,
+	/// Progress of the sync, as last reported by the network.
+	pub sync_progress: sc_network::SyncStateEvent<NumberFor<B>>,
 	/// Target sync block number.
 	pub best_seen_block: Option<NumberFor<B>>,
 	/// Number of peers participating in syncing.
@@ -545,15 +532,23 @@ impl<TBl, TCl, TSc, TNetStatus, TNet, TTxPool, TOc> Drop for
 {
 	fn drop(&mut self) {
 		debug!(target: "service", "Substrate service shutdown");
-		if let Some(signal) = self.signal.take() {
-			let _ = signal.fire();
-		}
+		self.task_manager.terminate();
+	}
+}
+
+/// Whether the unsafe RPC methods should be denied on a server listening on the given address.
+#[cfg(not(target_os = "unknown"))]
+fn deny_unsafe(address: &SocketAddr, methods: RpcMethods) -> sc_rpc::DenyUnsafe {
+	let is_exposed_addr = !address.ip().is_loopback();
+	match (is_exposed_addr, methods) {
+		(_, RpcMethods::Unsafe) | (false, RpcMethods::Auto) => sc_rpc::DenyUnsafe::No,
+		_ => sc_rpc::DenyUnsafe::Yes,
 	}
 }
 
 /// Starts RPC servers that run in their own thread, and returns an opaque object that keeps them alive.
 #[cfg(not(target_os = "unknown"))]
-fn start_rpc_servers<C, G, E, H: FnMut() -> sc_rpc_server::RpcHandler<sc_rpc::Metadata>>(
+fn start_rpc_servers<C, G, E, H: FnMut(sc_rpc::DenyUnsafe, RpcLimits) -> sc_rpc_server::RpcHandler<sc_rpc::Metadata>>(
 	config: &Configuration<C, G, E>,
 	mut gen_handler: H
 ) -> Result<Box<dyn std::any::Any + Send + Sync>, error::Error> {
@@ -575,10 +570,25 @@ fn start_rpc_servers<C, G, E, H: FnMut() -> sc_rpc_server::RpcHandler<sc_rpc::Me
 		})
 	}
 
+	// the IPC server is local to the machine, like a server listening on a loopback address.
+	let ipc_deny_unsafe = match config.rpc_methods {
+		RpcMethods::Safe => sc_rpc::DenyUnsafe::Yes,
+		RpcMethods::Auto | RpcMethods::Unsafe => sc_rpc::DenyUnsafe::No,
+	};
+
 	Ok(Box::new((
+		config.rpc_ipc.as_ref()
+			.map(|path| sc_rpc_server::start_ipc(path, gen_handler(ipc_deny_unsafe, config.rpc_limits)))
+			.transpose()?
+			.map(Mutex::new),
 		maybe_start_server(
 			config.rpc_http,
-			|address| sc_rpc_server::start_http(address, config.rpc_cors.as_ref(), gen_handler()),
+			|address| sc_rpc_server::start_http(
+				address,
+				config.rpc_cors.as_ref(),
+				config.rpc_limits.max_payload,
+				gen_handler(deny_unsafe(address, config.rpc_methods), config.rpc_limits),
+			),
 		)?,
 		maybe_start_server(
 			config.rpc_ws,
@@ -586,7 +596,8 @@ fn start_rpc_servers<C, G, E, H: FnMut() -> sc_rpc_server::RpcHandler<sc_rpc::Me
 				address,
 				config.rpc_ws_max_connections,
 				config.rpc_cors.as_ref(),
-				gen_handler(),
+				config.rpc_limits.max_payload,
+				gen_handler(deny_unsafe(address, config.rpc_methods), config.rpc_limits),
 			),
 		)?.map(Mutex::new),
 	)))
@@ -594,7 +605,7 @@ fn start_rpc_servers<C, G, E, H: FnMut() -> sc_rpc_server::RpcHandler<sc_rpc::Me
 
 /// Starts RPC servers that run in their own thread, and returns an opaque object that keeps them alive.
 #[cfg(target_os = "unknown")]
-fn start_rpc_servers<C, G, E, H: FnMut() -> sc_rpc_server::RpcHandler<sc_rpc::Metadata>>(
+fn start_rpc_servers<C, G, E, H: FnMut(sc_rpc::DenyUnsafe, RpcLimits) -> sc_rpc_server::RpcHandler<sc_rpc::Metadata>>(
 	_: &Configuration<C, G, E>,
 	_: H
 ) -> Result<Box<dyn std::any::Any + Send + Sync>, error::Error> {
@@ -685,7 +696,7 @@ where
 		match Decode::decode(&mut &encoded[..]) {
 			Ok(uxt) => {
 				let best_block_id = BlockId::hash(self.client.info().chain.best_hash);
-				let import_future = self.pool.submit_one(&best_block_id, uxt);
+				let import_future = self.pool.submit_one(&best_block_id, TransactionSource::External, uxt);
 				let import_future = import_future
 					.then(move |import_result| {
 						match import_result {
@@ -738,8 +749,10 @@ mod tests {
 			from: AccountKeyring::Alice.into(),
 			to: Default::default(),
 		}.into_signed_tx();
-		block_on(pool.submit_one(&BlockId::hash(best.hash()), transaction.clone())).unwrap();
-		block_on(pool.submit_one(&BlockId::hash(best.hash()), Extrinsic::IncludeData(vec![1]))).unwrap();
+		let source = TransactionSource::External;
+		block_on(pool.submit_one(&BlockId::hash(best.hash()), source, transaction.clone())).unwrap();
+		block_on(pool.submit_one(&BlockId::hash(best.hash()), source, Extrinsic::IncludeData(vec![1])))
+			.unwrap();
 		assert_eq!(pool.status().ready, 2);
 
 		// when
@@ -0,0 +1,308 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Management of the tasks spawned by the service.
+//!
+//! The `TaskManager` owns every task spawned by the service. Dropping it fires the exit signal,
+//! which stops the tasks, and the `RunningTasks` handle allows to wait for them to conclude.
+
+use std::{panic, sync::Arc, time::{Duration, Instant}};
+use exit_future::Signal;
+use futures::{
+	prelude::*,
+	sync::mpsc,
+	future::{Executor, ExecuteError, ExecuteErrorKind},
+};
+use futures03::{FutureExt as _, TryFutureExt as _};
+use log::{debug, error};
+use parking_lot::{Condvar, Mutex};
+
+use crate::error::Error;
+
+/// A task spawned by the service.
+type BoxedTask = Box<dyn Future<Item = (), Error = ()> + Send>;
+
+/// Tracks the tasks of a service which did not conclude yet.
+#[derive(Clone, Default)]
+pub struct RunningTasks {
+	inner: Arc<(Mutex<usize>, Condvar)>,
+}
+
+impl RunningTasks {
+	/// Returns the number of tasks which did not conclude yet.
+	pub fn count(&self) -> usize {
+		*self.inner.0.lock()
+	}
+
+	/// Blocks the current thread until all the tasks concluded or `timeout` elapsed.
+	///
+	/// Returns `false` if some tasks are still running after `timeout`.
+	pub fn wait(&self, timeout: Duration) -> bool {
+		let (count, concluded) = &*self.inner;
+		let deadline = Instant::now() + timeout;
+		let mut count = count.lock();
+		while *count > 0 {
+			if concluded.wait_until(&mut count, deadline).timed_out() {
+				return *count == 0;
+			}
+		}
+		true
+	}
+
+	/// Counts a new task as running until the returned guard is dropped.
+	fn track(&self) -> TaskGuard {
+		*self.inner.0.lock() += 1;
+		TaskGuard(self.clone())
+	}
+}
+
+/// Counts a task as running as long as it is alive.
+struct TaskGuard(RunningTasks);
+
+impl Drop for TaskGuard {
+	fn drop(&mut self) {
+		let (count, concluded) = &*(self.0).inner;
+		let mut count = count.lock();
+		*count -= 1;
+		if *count == 0 {
+			concluded.notify_all();
+		}
+	}
+}
+
+/// An handle for spawning tasks in the service.
+#[derive(Clone)]
+pub struct SpawnTaskHandle {
+	sender: mpsc::UnboundedSender<BoxedTask>,
+	on_exit: exit_future::Exit,
+	running: RunningTasks,
+}
+
+impl SpawnTaskHandle {
+	/// Spawns a task in the background. The task is stopped when the service exits.
+	pub fn spawn(&self, task: impl Future<Item = (), Error = ()> + Send + 'static) {
+		let _ = self.execute(Box::new(task));
+	}
+}
+
+impl Executor<BoxedTask> for SpawnTaskHandle {
+	fn execute(&self, future: BoxedTask) -> Result<(), ExecuteError<BoxedTask>> {
+		// the guard is dropped once the task concluded, or along with the task if it never runs.
+		let guard = self.running.track();
+		let exit = self.on_exit.clone().map(Ok).compat();
+		let future = Box::new(future.select(exit).then(move |_| {
+			drop(guard);
+			Ok(())
+		}));
+		if let Err(err) = self.sender.unbounded_send(future) {
+			let kind = ExecuteErrorKind::Shutdown;
+			Err(ExecuteError::new(kind, err.into_inner()))
+		} else {
+			Ok(())
+		}
+	}
+}
+
+impl futures03::task::Spawn for SpawnTaskHandle {
+	fn spawn_obj(&self, future: futures03::task::FutureObj<'static, ()>)
+	-> Result<(), futures03::task::SpawnError> {
+		self.execute(Box::new(futures03::compat::Compat::new(future.unit_error())))
+			.map_err(|_| futures03::task::SpawnError::shutdown())
+	}
+}
+
+/// Owns the tasks spawned by the service.
+///
+/// The tasks are spawned on the default executor when the manager is polled, and stopped when
+/// the manager is terminated or dropped.
+pub struct TaskManager {
+	/// A future that resolves when the service has exited, this is useful to
+	/// make sure any internally spawned futures stop when the service does.
+	on_exit: exit_future::Exit,
+	/// A signal that makes the exit future above resolve, fired on termination.
+	signal: Option<Signal>,
+	/// Sender for futures that must be spawned as background tasks.
+	to_spawn_tx: mpsc::UnboundedSender<BoxedTask>,
+	/// Receiver for futures that must be spawned as background tasks.
+	to_spawn_rx: mpsc::UnboundedReceiver<BoxedTask>,
+	/// List of futures to poll from `poll`.
+	/// If spawning a background task is not possible, we instead push the task into this `Vec`.
+	/// The elements must then be polled manually.
+	to_poll: Vec<BoxedTask>,
+	/// Send a signal when a spawned essential task has concluded. The next time
+	/// the manager is polled it should complete with an error.
+	essential_failed_tx: mpsc::UnboundedSender<()>,
+	/// A receiver for spawned essential-tasks concluding.
+	essential_failed_rx: mpsc::UnboundedReceiver<()>,
+	/// The tasks which did not conclude yet.
+	running: RunningTasks,
+}
+
+impl TaskManager {
+	/// Creates a new task manager.
+	pub(crate) fn new() -> Self {
+		let (signal, on_exit) = exit_future::signal();
+		let (to_spawn_tx, to_spawn_rx) = mpsc::unbounded();
+		let (essential_failed_tx, essential_failed_rx) = mpsc::unbounded();
+
+		TaskManager {
+			on_exit,
+			signal: Some(signal),
+			to_spawn_tx,
+			to_spawn_rx,
+			to_poll: Vec::new(),
+			essential_failed_tx,
+			essential_failed_rx,
+			running: Default::default(),
+		}
+	}
+
+	/// Returns a handle for spawning tasks.
+	pub fn spawn_handle(&self) -> SpawnTaskHandle {
+		SpawnTaskHandle {
+			sender: self.to_spawn_tx.clone(),
+			on_exit: self.on_exit.clone(),
+			running: self.running.clone(),
+		}
+	}
+
+	/// Spawns a task in the background. The task is stopped when the service exits.
+	pub fn spawn(&self, task: impl Future<Item = (), Error = ()> + Send + 'static) {
+		self.spawn_handle().spawn(task)
+	}
+
+	/// Spawns a task in the background. The given task is considered essential, i.e. if it
+	/// concludes or panics the manager completes with `Error::EssentialTaskFailed`.
+	pub fn spawn_essential(&self, task: impl Future<Item = (), Error = ()> + Send + 'static) {
+		let essential_failed = self.essential_failed_tx.clone();
+		let essential_task = panic::AssertUnwindSafe(task)
+			.catch_unwind()
+			.then(move |_| {
+				error!("Essential task failed. Shutting down service.");
+				let _ = essential_failed.unbounded_send(());
+				Ok(())
+			});
+
+		self.spawn(essential_task)
+	}
+
+	/// Returns a future that resolves once the tasks are requested to stop.
+	pub fn on_exit(&self) -> exit_future::Exit {
+		self.on_exit.clone()
+	}
+
+	/// Returns a handle tracking the tasks which did not conclude yet.
+	pub fn running_tasks(&self) -> RunningTasks {
+		self.running.clone()
+	}
+
+	/// Requests all the tasks to stop.
+	pub fn terminate(&mut self) {
+		if let Some(signal) = self.signal.take() {
+			let _ = signal.fire();
+		}
+	}
+
+	/// Spawns the pending tasks and polls the ones which could not be spawned.
+	///
+	/// Never resolves, but fails with `Error::EssentialTaskFailed` once an essential task
+	/// concluded.
+	pub(crate) fn poll(&mut self) -> Poll<(), Error> {
+		match self.essential_failed_rx.poll() {
+			Ok(Async::NotReady) => {},
+			Ok(Async::Ready(_)) | Err(_) => {
+				// Ready(None) should not be possible since we hold a live
+				// sender.
+				return Err(Error::EssentialTaskFailed);
+			}
+		}
+
+		while let Ok(Async::Ready(Some(task_to_spawn))) = self.to_spawn_rx.poll() {
+			let executor = tokio_executor::DefaultExecutor::current();
+			if let Err(err) = executor.execute(task_to_spawn) {
+				debug!(
+					target: "service",
+					"Failed to spawn background task: {:?}; falling back to manual polling",
+					err
+				);
+				self.to_poll.push(err.into_future());
+			}
+		}
+
+		// Polling all the `to_poll` futures.
+		while let Some(pos) = self.to_poll.iter_mut().position(|t| t.poll().map(|t| t.is_ready()).unwrap_or(true)) {
+			let _ = self.to_poll.remove(pos);
+		}
+
+		Ok(Async::NotReady)
+	}
+}
+
+impl Drop for TaskManager {
+	fn drop(&mut self) {
+		self.terminate();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn running_tasks_are_counted_until_they_conclude() {
+		let running = RunningTasks::default();
+		assert!(running.wait(Duration::from_millis(0)));
+
+		let first = running.track();
+		let second = running.track();
+		assert_eq!(running.count(), 2);
+		assert!(!running.wait(Duration::from_millis(10)));
+
+		drop(first);
+		let waiter = {
+			let running = running.clone();
+			std::thread::spawn(move || running.wait(Duration::from_secs(10)))
+		};
+		drop(second);
+		assert!(waiter.join().unwrap());
+		assert_eq!(running.count(), 0);
+	}
+
+	#[test]
+	fn dropped_tasks_are_not_running() {
+		let manager = TaskManager::new();
+		let running = manager.running_tasks();
+		manager.spawn(futures::future::empty());
+		assert_eq!(running.count(), 1);
+
+		// the task never got spawned and is dropped along with the manager.
+		drop(manager);
+		assert_eq!(running.count(), 0);
+	}
+
+	#[test]
+	fn concluded_essential_task_fails_the_manager() {
+		let mut runtime = tokio::runtime::Runtime::new().unwrap();
+		let mut manager = TaskManager::new();
+		manager.spawn_essential(futures::future::ok(()));
+
+		let result = runtime.block_on(futures::future::poll_fn(move || manager.poll()));
+		assert!(match result {
+			Err(Error::EssentialTaskFailed) => true,
+			_ => false,
+		});
+	}
+}
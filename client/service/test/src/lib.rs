@@ -19,7 +19,7 @@
 use std::iter;
 use std::sync::{Arc, Mutex, MutexGuard};
 use std::net::Ipv4Addr;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use log::info;
 use futures::{Future, Stream, Poll};
 use tempfile::TempDir;
@@ -32,10 +32,11 @@ use sc_service::{
 	config::DatabaseConfig,
 	Roles,
 	Error,
+	RpcSession,
 };
 use sc_network::{multiaddr, Multiaddr, NetworkStateInfo};
-use sc_network::config::{NetworkConfiguration, TransportConfig, NodeKeyConfig, Secret, NonReservedPeerMode};
-use sp_runtime::{generic::BlockId, traits::Block as BlockT};
+use sc_network::config::{NetworkConfiguration, TransportConfig, NodeKeyConfig, Secret, NonReservedPeerMode, SyncMode};
+use sp_runtime::{generic::BlockId, traits::Block as BlockT, transaction_validity::TransactionSource};
 use sp_transaction_pool::TransactionPool;
 
 /// Maximum duration of single wait call.
@@ -153,6 +154,7 @@ fn node_config<G, E: Clone> (
 		node_key: NodeKeyConfig::Ed25519(Secret::New),
 		in_peers: 50,
 		out_peers: 450,
+		reserved_authority_slots: None,
 		reserved_nodes: vec![],
 		non_reserved_mode: NonReservedPeerMode::Accept,
 		sentry_nodes: vec![],
@@ -164,6 +166,12 @@ fn node_config<G, E: Clone> (
 			wasm_external_transport: None,
 		},
 		max_parallel_downloads: NetworkConfiguration::default().max_parallel_downloads,
+		allow_non_globals_in_dht: true,
+		record_ttl: NetworkConfiguration::default().record_ttl,
+		record_publication_interval: NetworkConfiguration::default().record_publication_interval,
+		protocol_rate_limits: Default::default(),
+		outbound_proxy: None,
+		sync_mode: SyncMode::Full,
 	};
 
 	Configuration {
@@ -172,14 +180,17 @@ fn node_config<G, E: Clone> (
 		impl_commit: "",
 		roles: role,
 		transaction_pool: Default::default(),
+		persist_local_transactions: false,
 		network: network_config,
 		keystore_path: Some(root.join("key")),
+		keystore_uri: None,
 		keystore_password: None,
 		config_dir: Some(root.clone()),
 		database: DatabaseConfig::Path {
 			path: root.join("db"),
 			cache_size: None
 		},
+		column_cache_sizes: Default::default(),
 		state_cache_size: 16777216,
 		state_cache_child_ratio: None,
 		pruning: Default::default(),
@@ -188,15 +199,22 @@ fn node_config<G, E: Clone> (
 		name: format!("Node {}", index),
 		wasm_method: sc_service::config::WasmExecutionMethod::Interpreted,
 		execution_strategies: Default::default(),
+		import_verification_workers: 1,
+		rpc_ipc: None,
 		rpc_http: None,
 		rpc_ws: None,
 		rpc_ws_max_connections: None,
 		rpc_cors: None,
+		rpc_methods: Default::default(),
+		rpc_limits: Default::default(),
 		grafana_port: None,
+		prometheus_config: None,
 		telemetry_endpoints: None,
 		telemetry_external_transport: None,
 		default_heap_pages: None,
 		offchain_worker: false,
+		offchain_worker_options: Default::default(),
+		offchain_indexing: false,
 		sentry_mode: false,
 		force_authoring: false,
 		disable_grandpa: false,
@@ -393,6 +411,63 @@ pub fn connectivity<G, E, Fb, F, Lb, L>(
 	}
 }
 
+pub fn system_rpc<G, E, Fb, F>(
+	spec: ChainSpec<G, E>,
+	full_builder: Fb,
+) where
+	E: Clone,
+	Fb: Fn(Configuration<(), G, E>) -> Result<F, Error>,
+	F: AbstractService,
+{
+	let temp = tempdir_with_prefix("substrate-system-rpc-test");
+	{
+		let network = TestNet::new(
+			&temp,
+			spec,
+			iter::once(|cfg| full_builder(cfg).map(|s| (s, ()))),
+			// Note: these iterators are empty but we can't just use `iter::empty()`, otherwise
+			// the type of the closures cannot be inferred.
+			(0..0).map(|_| { |cfg| full_builder(cfg) }),
+			(0..0).map(|_| (String::new(), { |cfg| full_builder(cfg).map(|s| (s, ())) })),
+			30600,
+		);
+		let (_, service, _, address) = &network.full_nodes[0];
+		let (sender, _receiver) = futures::sync::mpsc::channel(0);
+		let session = RpcSession::new(sender);
+		let query = |method: &str| -> serde_json::Value {
+			let request = format!(r#"{{"jsonrpc":"2.0","method":"{}","params":[],"id":1}}"#, method);
+			// The service is locked while polled, so the lock must be released before waiting.
+			let response = service.get().rpc_query(&session, &request);
+			let response = response.wait()
+				.expect("RPC queries don't fail")
+				.expect("the query has an id, thus a response");
+			let mut response: serde_json::Value = serde_json::from_str(&response)
+				.expect("responses are valid JSON");
+			response["result"].take()
+		};
+
+		info!("Checking system_syncProgress");
+		let progress = query("system_syncProgress");
+		assert_eq!(progress["startingBlock"], 0);
+		assert_eq!(progress["currentBlock"], 0);
+		assert!(progress["highestBlock"].is_null());
+
+		info!("Checking system_localListenAddresses");
+		let started = Instant::now();
+		loop {
+			let addresses = query("system_localListenAddresses");
+			let addresses = addresses.as_array().expect("the addresses are a list");
+			if !addresses.is_empty() {
+				assert_eq!(addresses, &vec![serde_json::Value::from(address.to_string())]);
+				break;
+			}
+			assert!(started.elapsed() < MAX_WAIT_TIME, "Waited for too long");
+			std::thread::sleep(Duration::from_millis(100));
+		}
+	}
+	temp.close().expect("Error removing temp dir");
+}
+
 pub fn sync<G, E, Fb, F, Lb, L, B, ExF, U>(
 	spec: ChainSpec<G, E>,
 	full_builder: Fb,
@@ -456,7 +531,9 @@ pub fn sync<G, E, Fb, F, Lb, L, B, ExF, U>(
 	let first_user_data = &network.full_nodes[0].2;
 	let best_block = BlockId::number(first_service.get().client().info().chain.best_number);
 	let extrinsic = extrinsic_factory(&first_service.get(), first_user_data);
-	futures03::executor::block_on(first_service.get().transaction_pool().submit_one(&best_block, extrinsic)).unwrap();
+	futures03::executor::block_on(
+		first_service.get().transaction_pool().submit_one(&best_block, TransactionSource::External, extrinsic)
+	).unwrap();
 	network.run_until_all_full(
 		|_index, service| service.get().transaction_pool().ready().count() == 1,
 		|_index, _service| true,
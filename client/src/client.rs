@@ -18,17 +18,18 @@
 
 use std::{
 	marker::PhantomData, collections::{HashSet, BTreeMap, HashMap}, sync::Arc,
-	panic::UnwindSafe, result, cell::RefCell,
+	panic::UnwindSafe, result, cell::RefCell, time::Instant,
 };
 use log::{info, trace, warn};
 use futures::channel::mpsc;
 use parking_lot::{Mutex, RwLock};
+use prometheus_endpoint::Registry;
 use codec::{Encode, Decode};
 use hash_db::{Hasher, Prefix};
 use sp_core::{
 	Blake2Hasher, H256, ChangesTrieConfiguration, convert_hash,
 	NeverNativeValue, ExecutionContext, NativeOrEncoded,
-	storage::{StorageKey, StorageData, well_known_keys, ChildInfo},
+	storage::{StorageKey, StorageData, Storage, well_known_keys, ChildInfo},
 	traits::CodeExecutor,
 };
 use sc_telemetry::{telemetry, SUBSTRATE_INFO};
@@ -49,14 +50,14 @@ use sp_state_machine::{
 use sc_executor::{RuntimeVersion, RuntimeInfo};
 use sp_consensus::{
 	Error as ConsensusError, BlockStatus, BlockImportParams, BlockCheckParams,
-	ImportResult, BlockOrigin, ForkChoiceStrategy,
+	ImportResult, BlockOrigin, ForkChoiceStrategy, ImportedState,
 	SelectChain, self,
 };
 use sp_blockchain::{self as blockchain,
 	Backend as ChainBackend,
 	HeaderBackend as ChainHeaderBackend, ProvideCache, Cache,
 	well_known_cache_keys::Id as CacheKeyId,
-	HeaderMetadata, CachedHeaderMetadata,
+	HeaderMetadata, CachedHeaderMetadata, TreeRoute,
 };
 
 use sp_api::{CallRuntimeAt, ConstructRuntimeApi, Core as CoreApi, ProofRecorder, InitializeBlock};
@@ -65,7 +66,8 @@ use sc_block_builder::BlockBuilderApi;
 pub use sc_client_api::{
 	backend::{
 		self, BlockImportOperation, PrunableStateChangesTrieStorage,
-		ClientImportOperation, Finalizer, ImportSummary, NewBlockState,
+		ClientImportOperation, Finalizer, ImportSummary, NewBlockState, PurgeTarget,
+		StorageProvider,
 	},
 	client::{
 		ImportNotifications, FinalityNotification, FinalityNotifications, BlockImportNotification,
@@ -82,8 +84,12 @@ use crate::{
 	call_executor::LocalCallExecutor,
 	light::{call_executor::prove_execution, fetcher::ChangesProof},
 	in_mem, genesis, cht,
+	metrics::Metrics,
 };
 
+/// Metrics label of the import operations that don't import a block.
+const OTHER_ORIGIN_LABEL: &str = "other";
+
 type StorageUpdate<B, Block> = <
 	<
 		<B as backend::Backend<Block, Blake2Hasher>>::BlockImportOperation
@@ -98,10 +104,13 @@ pub struct Client<B, E, Block, RA> where Block: BlockT {
 	storage_notifications: Mutex<StorageNotifications<Block>>,
 	import_notification_sinks: Mutex<Vec<mpsc::UnboundedSender<BlockImportNotification<Block>>>>,
 	finality_notification_sinks: Mutex<Vec<mpsc::UnboundedSender<FinalityNotification<Block>>>>,
+	every_import_notification_sinks: Mutex<Vec<mpsc::UnboundedSender<BlockImportNotification<Block>>>>,
+	every_finality_notification_sinks: Mutex<Vec<mpsc::UnboundedSender<FinalityNotification<Block>>>>,
 	// holds the block hash currently being imported. TODO: replace this with block queue
 	importing_block: RwLock<Option<Block::Hash>>,
 	fork_blocks: ForkBlocks<Block>,
 	execution_extensions: ExecutionExtensions<Block>,
+	metrics: Option<Metrics>,
 	_phantom: PhantomData<RA>,
 }
 
@@ -174,7 +183,7 @@ pub fn new_with_backend<B, E, Block, S, RA>(
 {
 	let call_executor = LocalCallExecutor::new(backend.clone(), executor);
 	let extensions = ExecutionExtensions::new(Default::default(), keystore);
-	Client::new(backend, call_executor, build_genesis_storage, Default::default(), extensions)
+	Client::new(backend, call_executor, build_genesis_storage, Default::default(), extensions, None)
 }
 
 impl<B, E, Block, RA> BlockOf for Client<B, E, Block, RA> where
@@ -191,12 +200,16 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 	Block: BlockT<Hash=H256>,
 {
 	/// Creates new Substrate Client with given blockchain and code executor.
+	///
+	/// The time spent in the stages of the block import is reported to the given Prometheus
+	/// registry, if any.
 	pub fn new<S: BuildStorage>(
 		backend: Arc<B>,
 		executor: E,
 		build_genesis_storage: S,
 		fork_blocks: ForkBlocks<Block>,
 		execution_extensions: ExecutionExtensions<Block>,
+		prometheus_registry: Option<Registry>,
 	) -> sp_blockchain::Result<Self> {
 		if backend.blockchain().header(BlockId::Number(Zero::zero()))?.is_none() {
 			let genesis_storage = build_genesis_storage.build_storage()?;
@@ -217,15 +230,24 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			backend.commit_operation(op)?;
 		}
 
+		let metrics = prometheus_registry.and_then(|registry| {
+			Metrics::register(&registry)
+				.map_err(|err| warn!("Failed to register the client metrics: {:?}", err))
+				.ok()
+		});
+
 		Ok(Client {
 			backend,
 			executor,
 			storage_notifications: Default::default(),
 			import_notification_sinks: Default::default(),
 			finality_notification_sinks: Default::default(),
+			every_import_notification_sinks: Default::default(),
+			every_finality_notification_sinks: Default::default(),
 			importing_block: Default::default(),
 			fork_blocks,
 			execution_extensions,
+			metrics,
 			_phantom: Default::default(),
 		})
 	}
@@ -240,12 +262,52 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		self.backend.state_at(*block)
 	}
 
+	/// Prevent the state of the given block from being pruned, until `unpin_block` has been called
+	/// as many times as this.
+	pub fn pin_block(&self, hash: &Block::Hash) -> sp_blockchain::Result<()> {
+		self.backend.pin_block(hash)
+	}
+
+	/// Release a block pinned by `pin_block`.
+	pub fn unpin_block(&self, hash: &Block::Hash) {
+		self.backend.unpin_block(hash)
+	}
+
 	/// Given a `BlockId` and a key prefix, return the matching child storage keys in that block.
 	pub fn storage_keys(&self, id: &BlockId<Block>, key_prefix: &StorageKey) -> sp_blockchain::Result<Vec<StorageKey>> {
 		let keys = self.state_at(id)?.keys(&key_prefix.0).into_iter().map(StorageKey).collect();
 		Ok(keys)
 	}
 
+	/// Given a `BlockId` and a key, return the storage entries that follow the key in that block,
+	/// in ascending key order, up to `max_size` bytes of keys and values. The returned boolean is
+	/// `true` if there are no entries left after the returned ones.
+	pub fn storage_entries_after(
+		&self,
+		id: &BlockId<Block>,
+		start_key: &[u8],
+		max_size: usize,
+	) -> sp_blockchain::Result<(Vec<(Vec<u8>, Vec<u8>)>, bool)> {
+		let state = self.state_at(id)?;
+		let mut entries = Vec::new();
+		let mut size = 0;
+		let mut key = start_key.to_vec();
+		loop {
+			key = match state.next_storage_key(&key).map_err(|e| sp_blockchain::Error::from_state(Box::new(e)))? {
+				Some(key) => key,
+				None => return Ok((entries, true)),
+			};
+			let value = state.storage(&key).map_err(|e| sp_blockchain::Error::from_state(Box::new(e)))?
+				.unwrap_or_default();
+			// An entry larger than `max_size` on its own is still returned, alone.
+			if !entries.is_empty() && size + key.len() + value.len() > max_size {
+				return Ok((entries, false))
+			}
+			size += key.len() + value.len();
+			entries.push((key.clone(), value));
+		}
+	}
+
 	/// Given a `BlockId` and a key, return the value under the key in that block.
 	pub fn storage(&self, id: &BlockId<Block>, key: &StorageKey) -> sp_blockchain::Result<Option<StorageData>> {
 		Ok(self.state_at(id)?
@@ -323,6 +385,31 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		&self.executor
 	}
 
+	/// Submit a transaction reporting an equivocation to the transaction pool, by calling the
+	/// runtime API function `method` with the encoded `equivocation_proof` at the best block.
+	///
+	/// The function must return an `Option<()>`, `None` if the report couldn't be submitted.
+	pub fn submit_equivocation_report(
+		&self,
+		method: &str,
+		equivocation_proof: &[u8],
+	) -> sp_blockchain::Result<()> {
+		let at = BlockId::Hash(self.info().chain.best_hash);
+		let extensions = self.execution_extensions()
+			.extensions(&at, ExecutionContext::OffchainCall(None));
+
+		let submitted = self.executor
+			.call(&at, method, equivocation_proof, ExecutionStrategy::NativeElseWasm, Some(extensions))
+			.and_then(|call_result| {
+				<Option<()>>::decode(&mut &call_result[..])
+					.map_err(|err| sp_blockchain::Error::CallResultDecode(
+						"failed to decode the result of the equivocation report", err
+					))
+			})?;
+
+		submitted.ok_or_else(|| sp_blockchain::Error::Msg("the runtime didn't submit the equivocation report".into()))
+	}
+
 	/// Reads storage value at a given block + key, returning read proof.
 	pub fn read_proof<I>(&self, id: &BlockId<Block>, keys: I) -> sp_blockchain::Result<StorageProof> where
 		I: IntoIterator,
@@ -714,6 +801,20 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		F: FnOnce(&mut ClientImportOperation<Block, Blake2Hasher, B>) -> Result<R, Err>,
 		Err: From<sp_blockchain::Error>,
 	{
+		self.lock_import_and_run_with_origin(None, f)
+	}
+
+	/// Same as `lock_import_and_run`, with the origin of the block imported by the operation, if
+	/// any, for the metrics and traces of its commit and notifications.
+	fn lock_import_and_run_with_origin<R, Err, F>(
+		&self,
+		origin: Option<BlockOrigin>,
+		f: F,
+	) -> Result<R, Err> where
+		F: FnOnce(&mut ClientImportOperation<Block, Blake2Hasher, B>) -> Result<R, Err>,
+		Err: From<sp_blockchain::Error>,
+	{
+		let origin_label = origin.as_ref().map_or(OTHER_ORIGIN_LABEL, BlockOrigin::metric_label);
 		let inner = || {
 			let _import_lock = self.backend.get_import_lock().write();
 
@@ -721,16 +822,47 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				op: self.backend.begin_operation()?,
 				notify_imported: None,
 				notify_finalized: Vec::new(),
+				imported: None,
+				finalized: Vec::new(),
 			};
 
 			let r = f(&mut op)?;
 
-			let ClientImportOperation { op, notify_imported, notify_finalized } = op;
-			self.backend.commit_operation(op)?;
-			self.notify_finalized(notify_finalized)?;
+			let ClientImportOperation { op, notify_imported, notify_finalized, imported, finalized } = op;
+			{
+				let span = tracing::span!(tracing::Level::DEBUG, "commit_operation", origin = origin_label);
+				let _enter = span.enter();
+				let started = Instant::now();
+				self.backend.commit_operation(op)?;
+				if let Some(metrics) = &self.metrics {
+					metrics.report_commit(origin_label, started.elapsed());
+				}
+			}
+
+			let span = tracing::span!(tracing::Level::DEBUG, "notify", origin = origin_label);
+			let _enter = span.enter();
+			if !notify_finalized.is_empty() {
+				let started = Instant::now();
+				self.notify_finalized(notify_finalized)?;
+				if let Some(metrics) = &self.metrics {
+					metrics.report_notification(origin_label, "finality", started.elapsed());
+				}
+			}
 
 			if let Some(notify_imported) = notify_imported {
+				let started = Instant::now();
 				self.notify_imported(notify_imported)?;
+				if let Some(metrics) = &self.metrics {
+					metrics.report_notification(origin_label, "import", started.elapsed());
+				}
+			}
+
+			if !finalized.is_empty() {
+				self.notify_every_finalized(finalized)?;
+			}
+			if let Some(imported) = imported {
+				self.every_import_notification_sinks.lock()
+					.retain(|sink| sink.unbounded_send(imported.clone()).is_ok());
 			}
 
 			Ok(r)
@@ -763,10 +895,15 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 			fork_choice,
 			allow_missing_state,
 			import_existing,
+			state,
 		} = import_block;
 
 		assert!(justification.is_some() && finalized || justification.is_none());
 
+		if let Some(state) = state {
+			return self.import_state_block(operation, origin, header, justification, body, auxiliary, state);
+		}
+
 		let parent_hash = header.parent_hash().clone();
 		let mut enact_state = true;
 
@@ -823,6 +960,55 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		result
 	}
 
+	/// Import a block along with its full state instead of executing it on top of its parent,
+	/// which doesn't need to be known. The block becomes the best and last finalized block.
+	fn import_state_block(
+		&self,
+		operation: &mut ClientImportOperation<Block, Blake2Hasher, B>,
+		origin: BlockOrigin,
+		header: Block::Header,
+		justification: Option<Justification>,
+		body: Option<Vec<Block::Extrinsic>>,
+		aux: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+		state: ImportedState,
+	) -> sp_blockchain::Result<ImportResult> {
+		let hash = header.hash();
+		if let blockchain::BlockStatus::InChain = self.backend.blockchain().status(BlockId::Hash(hash))? {
+			if self.backend.have_state_at(&hash, *header.number()) {
+				return Ok(ImportResult::AlreadyInChain);
+			}
+			// The state of a block imported without it can only be added to the tip of the
+			// finalized chain, as after downloading the headers during a fast sync.
+			let info = self.backend.blockchain().info();
+			if info.best_hash != hash || info.finalized_hash != hash {
+				return Err(Error::Msg(format!(
+					"Can't import the state of block {:?}, which isn't both the best and the last finalized block",
+					hash,
+				)));
+			}
+		}
+
+		if state.iter().any(|(key, _)| well_known_keys::is_child_storage_key(key)) {
+			return Err(Error::Msg("Importing the state of child tries is not supported".into()));
+		}
+
+		self.backend.begin_state_operation(&mut operation.op, BlockId::Hash(Default::default()))?;
+		let state_root = operation.op.reset_storage(Storage {
+			top: state.into_iter().collect(),
+			children: Default::default(),
+		})?;
+		if state_root != *header.state_root() {
+			return Err(Error::InvalidStateRoot);
+		}
+
+		info!("Imported state of block #{} ({}), origin={:?}", header.number(), hash, origin);
+
+		operation.op.set_block_data(header, body, justification, NewBlockState::Final)?;
+		operation.op.insert_aux(aux)?;
+
+		Ok(ImportResult::imported(true))
+	}
+
 	fn execute_and_import_block(
 		&self,
 		operation: &mut ClientImportOperation<Block, Blake2Hasher, B>,
@@ -876,7 +1062,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				}
 
 				// FIXME #1232: correct path logic for when to execute this function
-				let (storage_update, changes_update, storage_changes) = self.block_execution(
+				let (storage_update, changes_update, storage_changes, offchain_changes) = self.block_execution(
 					&operation.op,
 					&import_headers,
 					origin,
@@ -891,6 +1077,7 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 				if let Some(storage_changes) = storage_changes.clone() {
 					operation.op.update_storage(storage_changes.0, storage_changes.1)?;
 				}
+				operation.op.update_offchain_storage(offchain_changes)?;
 				if let Some(Some(changes_update)) = changes_update {
 					operation.op.update_changes_trie(changes_update)?;
 				}
@@ -934,6 +1121,19 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 
 		operation.op.insert_aux(aux)?;
 
+		if !self.every_import_notification_sinks.lock().is_empty() {
+			operation.imported = Some(BlockImportNotification {
+				hash,
+				origin,
+				header: import_headers.post().clone(),
+				is_new_best,
+				retracted: retracted.clone(),
+			});
+		}
+		if finalized && !self.every_finality_notification_sinks.lock().is_empty() {
+			operation.finalized.push(hash);
+		}
+
 		if make_notifications {
 			if finalized {
 				operation.notify_finalized.push(hash);
@@ -965,7 +1165,8 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Option<(
 			Vec<(Vec<u8>, Option<Vec<u8>>)>,
 			Vec<(Vec<u8>, Vec<(Vec<u8>, Option<Vec<u8>>)>)>
-		)>
+		)>,
+		backend::OffchainChangesCollection,
 	)>
 		where
 			E: CallExecutor<Block, Blake2Hasher> + Send + Sync + Clone,
@@ -999,6 +1200,9 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 					body,
 				);
 
+				let span = tracing::span!(tracing::Level::DEBUG, "execute_block", origin = origin.metric_label());
+				let _enter = span.enter();
+				let started = Instant::now();
 				let (_, storage_update, changes_update) = self.executor
 					.call_at_state::<_, _, NeverNativeValue, fn() -> _>(
 						transaction_state,
@@ -1014,18 +1218,27 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 						None,
 						None,
 					)?;
+				if let Some(metrics) = &self.metrics {
+					metrics.report_execution(origin.metric_label(), started.elapsed());
+				}
 
 				overlay.commit_prospective();
 
+				let offchain_changes = overlay.drain_offchain_storage();
 				let (top, children) = overlay.into_committed();
 				let children = children.map(|(sk, it)| (sk, it.0.collect())).collect();
 				if import_headers.post().state_root() != &storage_update.1 {
 					return Err(sp_blockchain::Error::InvalidStateRoot);
 				}
 
-				Ok((Some(storage_update.0), Some(changes_update), Some((top.collect(), children))))
+				Ok((
+					Some(storage_update.0),
+					Some(changes_update),
+					Some((top.collect(), children)),
+					offchain_changes,
+				))
 			},
-			None => Ok((None, None, None))
+			None => Ok((None, None, None, Vec::new()))
 		}
 	}
 
@@ -1077,6 +1290,10 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		assert_eq!(enacted.last().map(|e| e.hash), Some(block));
 		operation.op.mark_finalized(BlockId::Hash(block), justification)?;
 
+		if !self.every_finality_notification_sinks.lock().is_empty() {
+			operation.finalized.extend(enacted.iter().map(|finalized| finalized.hash));
+		}
+
 		if notify {
 			// sometimes when syncing, tons of blocks can be finalized at once.
 			// we'll send notifications spuriously in that case.
@@ -1117,6 +1334,19 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Ok(())
 	}
 
+	fn notify_every_finalized(&self, finalized: Vec<Block::Hash>) -> sp_blockchain::Result<()> {
+		let mut sinks = self.every_finality_notification_sinks.lock();
+
+		for hash in finalized {
+			let header = self.header(&BlockId::Hash(hash))?
+				.expect("header already known to exist in DB because it is indicated in the tree route; qed");
+			let notification = FinalityNotification { header, hash };
+			sinks.retain(|sink| sink.unbounded_send(notification.clone()).is_ok());
+		}
+
+		Ok(())
+	}
+
 	fn notify_imported(&self, notify_import: ImportSummary<Block>) -> sp_blockchain::Result<()> {
 		if let Some(storage_changes) = notify_import.storage_changes {
 			// TODO [ToDr] How to handle re-orgs? Should we re-emit all storage changes?
@@ -1142,10 +1372,39 @@ impl<B, E, Block, RA> Client<B, E, Block, RA> where
 		Ok(())
 	}
 
-	/// Attempts to revert the chain by `n` blocks. Returns the number of blocks that were
-	/// successfully reverted.
-	pub fn revert(&self, n: NumberFor<Block>) -> sp_blockchain::Result<NumberFor<Block>> {
-		Ok(self.backend.revert(n)?)
+	/// Get a stream of notifications of every imported block, whatever its origin.
+	///
+	/// Unlike `import_notification_stream`, blocks imported during the initial sync or from a
+	/// file are notified as well.
+	pub fn every_import_notification_stream(&self) -> ImportNotifications<Block> {
+		let (sink, stream) = mpsc::unbounded();
+		self.every_import_notification_sinks.lock().push(sink);
+		stream
+	}
+
+	/// Get a stream of notifications of every finalized block, in ascending order.
+	///
+	/// Unlike `finality_notification_stream`, all the blocks finalized at once are notified,
+	/// including during the initial sync.
+	pub fn every_finality_notification_stream(&self) -> FinalityNotifications<Block> {
+		let (sink, stream) = mpsc::unbounded();
+		self.every_finality_notification_sinks.lock().push(sink);
+		stream
+	}
+
+	/// Attempts to revert the chain by `n` blocks, including finalized blocks if
+	/// `revert_finalized` is true. Returns the number of blocks that were successfully reverted.
+	pub fn revert(
+		&self,
+		n: NumberFor<Block>,
+		revert_finalized: bool,
+	) -> sp_blockchain::Result<NumberFor<Block>> {
+		Ok(self.backend.revert(n, revert_finalized)?)
+	}
+
+	/// Removes the given part of the chain data from the backend.
+	pub fn purge(&self, target: PurgeTarget) -> sp_blockchain::Result<()> {
+		self.backend.purge(target)
 	}
 
 	/// Get blockchain info.
@@ -1276,6 +1535,58 @@ impl<B, E, Block, RA> HeaderMetadata<Block> for Client<B, E, Block, RA> where
 	fn remove_header_metadata(&self, hash: Block::Hash) {
 		self.backend.blockchain().remove_header_metadata(hash)
 	}
+
+	fn cached_tree_route(&self, from: Block::Hash, to: Block::Hash) -> Option<TreeRoute<Block>> {
+		self.backend.blockchain().cached_tree_route(from, to)
+	}
+
+	fn cache_tree_route(&self, route: &TreeRoute<Block>) {
+		self.backend.blockchain().cache_tree_route(route)
+	}
+}
+
+impl<B, E, Block, RA> backend::StorageProvider<Block> for Client<B, E, Block, RA> where
+	B: backend::Backend<Block, Blake2Hasher>,
+	E: CallExecutor<Block, Blake2Hasher>,
+	Block: BlockT<Hash=H256>,
+{
+	/// The keys are read one by one from the trie, so that a page of keys can be fetched without
+	/// loading all the keys with the prefix.
+	fn storage_keys_paged(
+		&self,
+		id: &BlockId<Block>,
+		prefix: &StorageKey,
+		count: usize,
+		start_key: Option<&StorageKey>,
+	) -> sp_blockchain::Result<Vec<StorageKey>> {
+		let state = self.state_at(id)?;
+		let mut keys = Vec::new();
+		if count == 0 {
+			return Ok(keys);
+		}
+
+		let mut key = match start_key {
+			Some(start_key) if start_key.0 >= prefix.0 => start_key.0.clone(),
+			_ => {
+				// The prefix is itself a key with the prefix, but is not returned by
+				// `next_storage_key`.
+				let exists = state.exists_storage(&prefix.0)
+					.map_err(|e| sp_blockchain::Error::from_state(Box::new(e)))?;
+				if exists {
+					keys.push(prefix.clone());
+				}
+				prefix.0.clone()
+			},
+		};
+		while keys.len() < count {
+			key = match state.next_storage_key(&key).map_err(|e| sp_blockchain::Error::from_state(Box::new(e)))? {
+				Some(key) if key.starts_with(&prefix.0) => key,
+				_ => break,
+			};
+			keys.push(StorageKey(key.clone()));
+		}
+		Ok(keys)
+	}
 }
 
 impl<B, E, Block, RA> ProvideUncles<Block> for Client<B, E, Block, RA> where
@@ -1453,7 +1764,8 @@ impl<'a, B, E, Block, RA> sp_consensus::BlockImport<Block> for &'a Client<B, E,
 		import_block: BlockImportParams<Block>,
 		new_cache: HashMap<CacheKeyId, Vec<u8>>,
 	) -> Result<ImportResult, Self::Error> {
-		self.lock_import_and_run(|operation| {
+		let origin = import_block.origin;
+		self.lock_import_and_run_with_origin(Some(origin), |operation| {
 			self.apply_block(operation, import_block, new_cache)
 		}).map_err(|e| {
 			warn!("Block import error:\n{:?}", e);
@@ -2666,6 +2978,8 @@ pub(crate) mod tests {
 				state_cache_size: 1 << 20,
 				state_cache_child_ratio: None,
 				pruning: PruningMode::ArchiveAll,
+				column_cache_sizes: Default::default(),
+				offchain_indexing: false,
 				source: DatabaseSettingsSrc::Path {
 					path: tmp.path().into(),
 					cache_size: None,
@@ -2759,6 +3073,8 @@ pub(crate) mod tests {
 					state_cache_size: 1 << 20,
 					state_cache_child_ratio: None,
 					pruning: PruningMode::keep_blocks(1),
+					column_cache_sizes: Default::default(),
+					offchain_indexing: false,
 					source: DatabaseSettingsSrc::Path {
 						path: tmp.path().into(),
 						cache_size: None,
@@ -2789,6 +3105,7 @@ pub(crate) mod tests {
 			parent_hash: a1.header().parent_hash().clone(),
 			allow_missing_state: false,
 			import_existing: false,
+			state: None,
 		};
 
 		assert_eq!(client.check_block(check_block_a1.clone()).unwrap(), ImportResult::imported(false));
@@ -2809,6 +3126,7 @@ pub(crate) mod tests {
 			parent_hash: a1.header().parent_hash().clone(),
 			allow_missing_state: false,
 			import_existing: false,
+			state: None,
 		};
 
 		assert_eq!(client.check_block(check_block_a1.clone()).unwrap(), ImportResult::AlreadyInChain);
@@ -2826,6 +3144,7 @@ pub(crate) mod tests {
 			parent_hash: a2.header().parent_hash().clone(),
 			allow_missing_state: false,
 			import_existing: false,
+			state: None,
 		};
 
 		// a1 and a2 are both pruned at this point
@@ -2842,6 +3161,7 @@ pub(crate) mod tests {
 			parent_hash: b1.header().parent_hash().clone(),
 			allow_missing_state: false,
 			import_existing: false,
+			state: None,
 		};
 		assert_eq!(client.check_block(check_block_b1.clone()).unwrap(), ImportResult::MissingState);
 		check_block_b1.allow_missing_state = true;
@@ -2849,4 +3169,32 @@ pub(crate) mod tests {
 		check_block_b1.parent_hash = H256::random();
 		assert_eq!(client.check_block(check_block_b1.clone()).unwrap(), ImportResult::UnknownParent);
 	}
+
+	#[test]
+	fn notifies_every_imported_and_finalized_block() {
+		let client = substrate_test_runtime_client::new();
+		let mut regular = client.import_notification_stream();
+		let mut imported = client.every_import_notification_stream();
+		let mut finalized = client.every_finality_notification_stream();
+
+		let mut hashes = Vec::new();
+		for _ in 0..3 {
+			let block = client.new_block(Default::default()).unwrap().bake().unwrap();
+			hashes.push(block.hash());
+			client.import(BlockOrigin::NetworkInitialSync, block).unwrap();
+		}
+		Finalizer::finalize_block(&client, BlockId::Hash(hashes[2]), None, false).unwrap();
+
+		// the blocks of the initial sync are only notified to the streams of every block.
+		assert!(regular.try_next().is_err());
+		let imported_hashes = (0..3)
+			.map(|_| imported.try_next().unwrap().unwrap().hash)
+			.collect::<Vec<_>>();
+		assert_eq!(imported_hashes, hashes);
+		let finalized_hashes = (0..3)
+			.map(|_| finalized.try_next().unwrap().unwrap().hash)
+			.collect::<Vec<_>>();
+		assert_eq!(finalized_hashes, hashes);
+		assert!(finalized.try_next().is_err());
+	}
 }
@@ -82,6 +82,7 @@ pub mod light;
 pub mod leaves;
 mod call_executor;
 mod client;
+mod metrics;
 
 pub use sc_client_api::{
 	blockchain,
@@ -98,7 +99,7 @@ pub use crate::{
 		new_in_mem,
 		BlockBody, ImportNotifications, FinalityNotifications, BlockchainEvents,
 		BlockImportNotification, Client, ClientInfo, ExecutionStrategies, FinalityNotification,
-		LongestChain, BlockOf, ProvideUncles, ForkBlocks, apply_aux,
+		LongestChain, BlockOf, ProvideUncles, ForkBlocks, StorageProvider, apply_aux,
 	},
 	leaves::LeafSet,
 };
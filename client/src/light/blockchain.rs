@@ -24,7 +24,7 @@ use sp_runtime::{Justification, generic::BlockId};
 use sp_runtime::traits::{Block as BlockT, Header as HeaderT, NumberFor, Zero};
 
 use sp_blockchain::{
-	HeaderMetadata, CachedHeaderMetadata,
+	HeaderMetadata, CachedHeaderMetadata, TreeRoute,
 	Error as ClientError, Result as ClientResult,
 };
 pub use sc_client_api::{
@@ -102,6 +102,14 @@ impl<S, Block> HeaderMetadata<Block> for Blockchain<S> where Block: BlockT, S: S
 	fn remove_header_metadata(&self, hash: Block::Hash) {
 		self.storage.remove_header_metadata(hash)
 	}
+
+	fn cached_tree_route(&self, from: Block::Hash, to: Block::Hash) -> Option<TreeRoute<Block>> {
+		self.storage.cached_tree_route(from, to)
+	}
+
+	fn cache_tree_route(&self, route: &TreeRoute<Block>) {
+		self.storage.cache_tree_route(route)
+	}
 }
 
 impl<S, Block> BlockchainBackend<Block> for Blockchain<S> where Block: BlockT, S: Storage<Block> {
@@ -22,7 +22,8 @@ use std::marker::PhantomData;
 
 use hash_db::{HashDB, Hasher, EMPTY_PREFIX};
 use codec::{Decode, Encode};
-use sp_core::{convert_hash, traits::CodeExecutor, H256};
+use sp_core::{convert_hash, traits::CodeExecutor, H256, ChangesTrieConfiguration};
+use sp_runtime::generic::{BlockId, DigestItem};
 use sp_runtime::traits::{
 	Block as BlockT, Header as HeaderT, Hash, HashFor, NumberFor,
 	SimpleArithmetic, CheckedConversion, Zero,
@@ -33,7 +34,7 @@ use sp_state_machine::{
 	read_child_proof_check,
 };
 pub use sp_state_machine::StorageProof;
-use sp_blockchain::{Error as ClientError, Result as ClientResult};
+use sp_blockchain::{Error as ClientError, Result as ClientResult, HeaderBackend};
 
 use crate::cht;
 pub use sc_client_api::{
@@ -279,6 +280,83 @@ impl<E, Block, H, S> FetchChecker<Block> for LightDataChecker<E, H, Block, S>
 	}
 }
 
+/// Build the request for the blocks of the `first_block..=last` range at which `key` has changed.
+///
+/// The changes trie roots of the range are read from the headers known locally, so that the
+/// changes proof of the remote node is checked against them rather than against roots that the
+/// remote node provides. The headers of the older blocks of the range may have been pruned and
+/// replaced by CHTs: the remote node then proves their roots against the local CHT roots.
+pub fn remote_changes_request<Block: BlockT>(
+	blockchain: &dyn HeaderBackend<Block>,
+	changes_trie_config: ChangesTrieConfiguration,
+	first_block: (NumberFor<Block>, Block::Hash),
+	last: Block::Hash,
+	storage_key: Option<Vec<u8>>,
+	key: Vec<u8>,
+) -> ClientResult<RemoteChangesRequest<Block::Header>> {
+	let mut current = blockchain.header(BlockId::Hash(last))?
+		.ok_or_else(|| ClientError::UnknownBlock(format!("{}", last)))?;
+	let last_number = *current.number();
+	if first_block.0 > last_number {
+		return Err(ClientError::ChangesTrieAccessFailed("Invalid changes trie range".into()));
+	}
+
+	let mut roots = Vec::new();
+	let tries_roots_block = loop {
+		let root = current.digest().log(DigestItem::as_changes_trie_root).cloned()
+			.ok_or_else(|| ClientError::ChangesTrieAccessFailed(
+				format!("No changes trie root in the header of block {}", current.hash()),
+			))?;
+		roots.push(root);
+		if *current.number() == first_block.0 {
+			if current.hash() != first_block.1 {
+				return Err(ClientError::ChangesTrieAccessFailed(
+					"The blocks of the changes trie range are on different forks".into(),
+				));
+			}
+			break first_block;
+		}
+		match blockchain.header(BlockId::Hash(*current.parent_hash()))? {
+			Some(parent) => current = parent,
+			None => break (*current.number(), current.hash()),
+		}
+	};
+	roots.reverse();
+
+	Ok(RemoteChangesRequest {
+		changes_trie_config,
+		first_block,
+		last_block: (last_number, last),
+		max_block: (last_number, last),
+		tries_roots: (tries_roots_block.0, tries_roots_block.1, roots),
+		storage_key,
+		key,
+		retry_count: None,
+	})
+}
+
+/// Fetch the blocks of the `first_block..=last` range at which `key` has changed, with the
+/// request built by `remote_changes_request`.
+pub fn remote_key_changes<Block: BlockT, F: Fetcher<Block>>(
+	blockchain: &dyn HeaderBackend<Block>,
+	fetcher: &F,
+	changes_trie_config: ChangesTrieConfiguration,
+	first_block: (NumberFor<Block>, Block::Hash),
+	last: Block::Hash,
+	storage_key: Option<Vec<u8>>,
+	key: Vec<u8>,
+) -> ClientResult<F::RemoteChangesResult> {
+	let request = remote_changes_request(
+		blockchain,
+		changes_trie_config,
+		first_block,
+		last,
+		storage_key,
+		key,
+	)?;
+	Ok(fetcher.remote_changes(request))
+}
+
 /// A view of BTreeMap<Number, Hash> as a changes trie roots storage.
 struct RootsStorage<'a, Number: SimpleArithmetic, Hash: 'a> {
 	roots: (Number, &'a [Hash]),
@@ -591,6 +669,43 @@ pub mod tests {
 		}
 	}
 
+	#[test]
+	fn changes_proof_is_checked_against_locally_known_roots() {
+		let (remote_client, _, test_cases) = prepare_client_with_key_changes();
+		let local_checker = TestChecker::new(
+			Arc::new(DummyBlockchain::new(DummyStorage::new())),
+			local_executor(),
+		);
+		let local_checker = &local_checker as &dyn FetchChecker<Block>;
+
+		for (index, (begin, end, key, expected_result)) in test_cases.into_iter().enumerate() {
+			let begin_hash = remote_client.block_hash(begin).unwrap().unwrap();
+			let end_hash = remote_client.block_hash(end).unwrap().unwrap();
+
+			// the headers of the remote client are the ones known locally
+			let request = remote_changes_request::<Block>(
+				&remote_client,
+				runtime::changes_trie_config(),
+				(begin, begin_hash),
+				end_hash,
+				None,
+				key.clone(),
+			).unwrap();
+			let remote_proof = remote_client.key_changes_proof(
+				begin_hash, end_hash, begin_hash, end_hash, None, &StorageKey(key),
+			).unwrap();
+
+			let local_result = local_checker.check_changes_proof(&request, remote_proof).unwrap();
+			assert_eq!(local_result, expected_result, "Failed test {}", index);
+		}
+
+		let block1 = remote_client.block_hash(1).unwrap().unwrap();
+		let block2 = remote_client.block_hash(2).unwrap().unwrap();
+		assert!(remote_changes_request::<Block>(
+			&remote_client, runtime::changes_trie_config(), (2, block2), block1, None, vec![],
+		).is_err());
+	}
+
 	#[test]
 	fn changes_proof_is_generated_and_checked_when_headers_are_pruned() {
 		// we're testing this test case here:
@@ -630,6 +745,16 @@ pub mod tests {
 			key: dave.0,
 			retry_count: None,
 		};
+
+		// the headers of b1 and b2 have been pruned locally
+		let local_headers = InMemoryBlockchain::<Block>::new();
+		for block in &[b3, b4] {
+			let header = remote_client.header(&BlockId::Hash(*block)).unwrap().unwrap();
+			local_headers.insert(*block, header, None, None, NewBlockState::Normal).unwrap();
+		}
+		assert_eq!(remote_changes_request::<Block>(
+			&local_headers, runtime::changes_trie_config(), (1, b1), b4, None, request.key.clone(),
+		).unwrap(), request);
 		let local_result = local_checker.check_changes_proof_with_cht_size(&request, ChangesProof {
 			max_block: remote_proof.max_block,
 			proof: remote_proof.proof,
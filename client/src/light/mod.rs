@@ -28,6 +28,7 @@ use sp_core::{H256, Blake2Hasher, traits::CodeExecutor};
 use sp_runtime::BuildStorage;
 use sp_runtime::traits::Block as BlockT;
 use sp_blockchain::Result as ClientResult;
+use prometheus_endpoint::Registry;
 
 use crate::call_executor::LocalCallExecutor;
 use crate::client::Client;
@@ -58,6 +59,7 @@ pub fn new_light<B, S, GS, RA, E>(
 	backend: Arc<Backend<S, Blake2Hasher>>,
 	genesis_storage: GS,
 	code_executor: E,
+	prometheus_registry: Option<Registry>,
 ) -> ClientResult<Client<Backend<S, Blake2Hasher>, GenesisCallExecutor<
 	Backend<S, Blake2Hasher>,
 	LocalCallExecutor<Backend<S, Blake2Hasher>, E>
@@ -70,7 +72,7 @@ pub fn new_light<B, S, GS, RA, E>(
 {
 	let local_executor = LocalCallExecutor::new(backend.clone(), code_executor);
 	let executor = GenesisCallExecutor::new(backend.clone(), local_executor);
-	Client::new(backend, executor, genesis_storage, Default::default(), Default::default())
+	Client::new(backend, executor, genesis_storage, Default::default(), Default::default(), prometheus_registry)
 }
 
 /// Create an instance of fetch data checker.
@@ -0,0 +1,86 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of the block import stages of the client.
+
+use std::time::Duration;
+use prometheus_endpoint::{register, HistogramOpts, HistogramVec, PrometheusError, Registry};
+
+/// Prometheus metrics of a [`Client`](crate::Client).
+///
+/// All the metrics are labeled by the origin of the block imported, or `other` for the
+/// operations that don't import a block, such as a finalization.
+pub(crate) struct Metrics {
+	/// Time spent executing the blocks.
+	pub block_execution_time: HistogramVec,
+	/// Time spent committing the import operations to the backend.
+	pub block_commit_time: HistogramVec,
+	/// Time spent notifying the import and finality notification sinks, labeled by kind.
+	pub notification_time: HistogramVec,
+}
+
+impl Metrics {
+	/// Create and register the metrics with the given registry.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			block_execution_time: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"client_block_execution_time",
+						"Time taken to execute a block being imported, in seconds."
+					),
+					&["origin"],
+				)?,
+				registry,
+			)?,
+			block_commit_time: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"client_block_commit_time",
+						"Time taken to commit an import operation to the backend, in seconds."
+					),
+					&["origin"],
+				)?,
+				registry,
+			)?,
+			notification_time: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"client_notification_time",
+						"Time taken to fan the import and finality notifications out, in seconds."
+					),
+					&["origin", "kind"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	/// Report the time taken to execute a block.
+	pub fn report_execution(&self, origin: &str, time: Duration) {
+		self.block_execution_time.with_label_values(&[origin]).observe(time.as_secs_f64());
+	}
+
+	/// Report the time taken to commit an import operation.
+	pub fn report_commit(&self, origin: &str, time: Duration) {
+		self.block_commit_time.with_label_values(&[origin]).observe(time.as_secs_f64());
+	}
+
+	/// Report the time taken to send the notifications of the given kind, `import` or `finality`.
+	pub fn report_notification(&self, origin: &str, kind: &str, time: Duration) {
+		self.notification_time.with_label_values(&[origin, kind]).observe(time.as_secs_f64());
+	}
+}
@@ -280,6 +280,26 @@ impl<BlockHash: Hash, Key: Hash> StateDbSync<BlockHash, Key> {
 		Ok(commit)
 	}
 
+	pub fn import_canonical_block<E: fmt::Debug>(&mut self, hash: &BlockHash, number: u64, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		let mut commit = CommitSet {
+			data: changeset,
+			meta: ChangeSet::default(),
+		};
+		if self.mode == PruningMode::ArchiveAll {
+			commit.data.deleted.clear();
+			return Ok(commit)
+		}
+		self.non_canonical.import_canonical(hash, number, &mut commit)?;
+		if self.mode == PruningMode::ArchiveCanonical {
+			commit.data.deleted.clear();
+		}
+		if let Some(ref mut pruning) = self.pruning {
+			pruning.note_canonical(&hash, &mut commit);
+		}
+		self.prune(&mut commit);
+		Ok(commit)
+	}
+
 	pub fn best_canonical(&self) -> Option<u64> {
 		return self.non_canonical.last_canonicalized_block_number()
 	}
@@ -423,6 +443,12 @@ impl<BlockHash: Hash, Key: Hash> StateDb<BlockHash, Key> {
 		self.db.write().canonicalize_block(hash)
 	}
 
+	/// Add a block whose state is known in full, such as after warp sync, as the last canonical
+	/// block. Its parent doesn't need to be known, but there must be no non-canonical blocks.
+	pub fn import_canonical_block<E: fmt::Debug>(&self, hash: &BlockHash, number: u64, changeset: ChangeSet<Key>) -> Result<CommitSet<Key>, Error<E>> {
+		self.db.write().import_canonical_block(hash, number, changeset)
+	}
+
 	/// Prevents pruning of specified block and its descendants.
 	pub fn pin(&self, hash: &BlockHash) -> Result<(), PinError> {
 		self.db.write().pin(hash)
@@ -589,6 +615,38 @@ mod tests {
 		assert!(db.data_eq(&make_db(&[1, 21, 3, 921, 922, 93, 94])));
 	}
 
+	#[test]
+	fn imports_canonical_block_with_unknown_parent() {
+		let (mut db, state_db) = make_test_db(PruningMode::Constrained(Constraints {
+			max_blocks: Some(2),
+			max_mem: None,
+		}));
+		let h10 = H256::from_low_u64_be(10);
+
+		// Block 4 is still non-canonical.
+		assert!(state_db.import_canonical_block::<io::Error>(&h10, 10, make_changeset(&[10], &[])).is_err());
+		db.commit(&state_db.canonicalize_block::<io::Error>(&H256::from_low_u64_be(4)).unwrap());
+		state_db.apply_pending();
+
+		db.commit(&state_db.import_canonical_block::<io::Error>(&h10, 10, make_changeset(&[10], &[])).unwrap());
+		state_db.apply_pending();
+		assert_eq!(state_db.best_canonical(), Some(10));
+		assert!(!state_db.is_pruned(&h10, 10));
+		assert!(db.data.contains_key(&h10));
+
+		db.commit(
+			&state_db
+				.insert_block::<io::Error>(
+					&H256::from_low_u64_be(11),
+					11,
+					&h10,
+					make_changeset(&[11], &[]),
+				)
+				.unwrap(),
+		);
+		state_db.apply_pending();
+	}
+
 	#[test]
 	fn detects_incompatible_mode() {
 		let mut db = make_db(&[]);
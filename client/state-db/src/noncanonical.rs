@@ -326,6 +326,25 @@ impl<BlockHash: Hash, Key: Hash> NonCanonicalOverlay<BlockHash, Key> {
 		Ok(())
 	}
 
+	/// Make the given block the last canonicalized one, without any overlay. Its state is
+	/// expected to be written directly to the backing database. Fails if there are any
+	/// non-canonical blocks.
+	pub fn import_canonical<E: fmt::Debug>(
+		&mut self,
+		hash: &BlockHash,
+		number: u64,
+		commit: &mut CommitSet<Key>,
+	) -> Result<(), Error<E>> {
+		if !self.levels.is_empty() || !self.pending_canonicalizations.is_empty() || !self.pending_insertions.is_empty() {
+			return Err(Error::InvalidBlock);
+		}
+		trace!(target: "state-db", "Importing canonical {:?} (#{})", hash, number);
+		let canonicalized = (hash.clone(), number);
+		commit.meta.inserted.push((to_meta_key(LAST_CANONICAL, &()), canonicalized.encode()));
+		self.last_canonicalized = Some(canonicalized);
+		Ok(())
+	}
+
 	fn apply_canonicalizations(&mut self) {
 		let last = self.pending_canonicalizations.last().cloned();
 		let count = self.pending_canonicalizations.len() as u64;
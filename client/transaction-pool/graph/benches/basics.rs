@@ -23,7 +23,7 @@ use codec::Encode;
 use substrate_test_runtime::{Block, Extrinsic, Transfer, H256, AccountId};
 use sp_runtime::{
 	generic::BlockId,
-	transaction_validity::{TransactionValidity, TransactionTag as Tag},
+	transaction_validity::{TransactionValidity, TransactionTag as Tag, TransactionSource},
 };
 use sp_core::blake2_256;
 
@@ -54,6 +54,7 @@ impl ChainApi for TestApi {
 	fn validate_transaction(
 		&self,
 		at: &BlockId<Self::Block>,
+		_source: TransactionSource,
 		uxt: ExtrinsicFor<Self>,
 	) -> Self::ValidationFuture {
 		let nonce = uxt.transfer().nonce;
@@ -124,7 +125,7 @@ fn bench_configured(pool: Pool<TestApi>, number: u64) {
 		});
 
 		tags.push(to_tag(nonce, AccountId::from_h256(H256::from_low_u64_be(1))));
-		futures.push(pool.submit_one(&BlockId::Number(1), xt));
+		futures.push(pool.submit_one(&BlockId::Number(1), TransactionSource::External, xt));
 	}
 
 	let res = block_on(futures::future::join_all(futures.into_iter()));
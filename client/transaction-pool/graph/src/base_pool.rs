@@ -33,6 +33,7 @@ use sp_runtime::transaction_validity::{
 	TransactionTag as Tag,
 	TransactionLongevity as Longevity,
 	TransactionPriority as Priority,
+	TransactionSource,
 };
 use sp_transaction_pool::{error, PoolStatus, InPoolTransaction};
 
@@ -102,6 +103,8 @@ pub struct Transaction<Hash, Extrinsic> {
 	pub provides: Vec<Tag>,
 	/// Should that transaction be propagated.
 	pub propagate: bool,
+	/// Source of the transaction.
+	pub source: TransactionSource,
 }
 
 impl<Hash, Extrinsic> AsRef<Extrinsic> for Transaction<Hash, Extrinsic> {
@@ -141,6 +144,10 @@ impl<Hash, Extrinsic> InPoolTransaction for Transaction<Hash, Extrinsic> {
 	fn is_propagateable(&self) -> bool {
 		self.propagate
 	}
+
+	fn source(&self) -> TransactionSource {
+		self.source
+	}
 }
 
 impl<Hash: Clone, Extrinsic: Clone> Transaction<Hash, Extrinsic> {
@@ -159,6 +166,7 @@ impl<Hash: Clone, Extrinsic: Clone> Transaction<Hash, Extrinsic> {
 			requires: self.requires.clone(),
 			provides: self.provides.clone(),
 			propagate: self.propagate,
+			source: self.source,
 		}
 	}
 }
@@ -185,6 +193,7 @@ impl<Hash, Extrinsic> fmt::Debug for Transaction<Hash, Extrinsic> where
 		write!(fmt, "valid_till: {:?}, ", &self.valid_till)?;
 		write!(fmt, "bytes: {:?}, ", &self.bytes)?;
 		write!(fmt, "propagate: {:?}, ", &self.propagate)?;
+		write!(fmt, "source: {:?}, ", &self.source)?;
 		write!(fmt, "requires: [")?;
 		print_tags(fmt, &self.requires)?;
 		write!(fmt, "], provides: [")?;
@@ -212,6 +221,8 @@ const RECENTLY_PRUNED_TAGS: usize = 2;
 #[derive(Debug)]
 pub struct BasePool<Hash: hash::Hash + Eq, Ex> {
 	reject_future_transactions: bool,
+	/// Maximal number of future transactions waiting for the same gap.
+	future_per_gap: usize,
 	future: FutureTransactions<Hash, Ex>,
 	ready: ReadyTransactions<Hash, Ex>,
 	/// Store recently pruned tags (for last two invocations).
@@ -233,6 +244,7 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 	pub fn new(reject_future_transactions: bool) -> Self {
 		BasePool {
 			reject_future_transactions,
+			future_per_gap: usize::max_value(),
 			future: Default::default(),
 			ready: Default::default(),
 			recently_pruned: Default::default(),
@@ -240,6 +252,14 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 		}
 	}
 
+	/// Limit the number of future transactions that wait for the same gap.
+	///
+	/// A gap is a tag that no transaction of the pool provides. With account nonces as tags,
+	/// this limits the number of future transactions of a single sender.
+	pub fn set_future_per_gap_limit(&mut self, limit: usize) {
+		self.future_per_gap = limit;
+	}
+
 	/// Temporary enables future transactions, runs closure and then restores
 	/// `reject_future_transactions` flag back to previous value.
 	///
@@ -287,6 +307,14 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 				return Err(error::Error::RejectedFutureTransaction);
 			}
 
+			// the transaction joins the transactions waiting for the same gaps, along with the
+			// ones waiting for the transaction itself.
+			let gaps = self.future.gaps(&tx);
+			let waiting = self.future.waiting_for(gaps.iter().chain(&tx.transaction.provides));
+			if waiting.len() >= self.future_per_gap {
+				return Err(error::Error::TooManyFutureTransactions);
+			}
+
 			let hash = tx.transaction.hash.clone();
 			self.future.import(tx);
 			return Ok(Imported::Future { hash });
@@ -365,11 +393,22 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 		self.ready.get()
 	}
 
+	/// Returns at most `max` ready transactions, the ones that are longer in the pool first.
+	pub fn oldest_ready(&self, max: usize) -> Vec<Arc<Transaction<Hash, Ex>>> {
+		self.ready.oldest(max)
+	}
+
 	/// Returns an iterator over future transactions in the pool.
 	pub fn futures(&self) -> impl Iterator<Item=&Transaction<Hash, Ex>> {
 		self.future.all()
 	}
 
+	/// Returns the future transactions of the pool, along with the gaps they wait for: the tags
+	/// they miss, directly or through other future transactions, that no transaction provides.
+	pub fn futures_with_gaps(&self) -> Vec<(Arc<Transaction<Hash, Ex>>, HashSet<Tag>)> {
+		self.future.all_with_gaps().collect()
+	}
+
 	/// Returns pool transactions given list of hashes.
 	///
 	/// Includes both ready and future pool. For every hash in the `hashes`
@@ -389,7 +428,9 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 	///
 	/// Removes and returns worst transactions from the queues and all transactions that depend on them.
 	/// Technically the worst transaction should be evaluated by computing the entire pending set.
-	/// We use a simplified approach to remove the transaction that occupies the pool for the longest time.
+	/// We use a simplified approach: transactions submitted locally are kept over the other ones,
+	/// then the worst transaction is the one with the lowest priority, then the one that becomes
+	/// invalid the soonest, then the one that occupies the pool for the longest time.
 	pub fn enforce_limits(&mut self, ready: &Limit, future: &Limit) -> Vec<Arc<Transaction<Hash, Ex>>> {
 		let mut removed = vec![];
 
@@ -400,7 +441,8 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 					let transaction = &current.transaction;
 					match minimal {
 						None => Some(transaction.clone()),
-						Some(ref tx) if tx.insertion_id > transaction.insertion_id => {
+						Some(ref tx) if eviction_key(&transaction.transaction, transaction.insertion_id)
+							< eviction_key(&tx.transaction, tx.insertion_id) => {
 							Some(transaction.clone())
 						},
 						other => other,
@@ -420,7 +462,8 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 				.fold(|minimal, current| {
 					match minimal {
 						None => Some(current.clone()),
-						Some(ref tx) if tx.imported_at > current.imported_at => {
+						Some(ref tx) if eviction_key(&current.transaction, current.imported_at)
+							< eviction_key(&tx.transaction, tx.imported_at) => {
 							Some(current.clone())
 						},
 						other => other,
@@ -509,6 +552,15 @@ impl<Hash: hash::Hash + Member + Serialize, Ex: std::fmt::Debug> BasePool<Hash,
 	}
 }
 
+/// Order in which the transactions are evicted when the pool is over its limits, lowest first.
+fn eviction_key<Hash, Ex, T: Ord>(
+	transaction: &Transaction<Hash, Ex>,
+	inserted: T,
+) -> (bool, Priority, Longevity, T) {
+	let local = transaction.source == TransactionSource::Local;
+	(local, transaction.priority, transaction.valid_till, inserted)
+}
+
 /// Queue limits
 #[derive(Debug, Clone)]
 pub struct Limit {
@@ -550,6 +602,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![vec![1]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 
 		// then
@@ -572,6 +625,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![vec![1]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![1u8],
@@ -582,6 +636,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![vec![1]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap_err();
 
 		// then
@@ -605,6 +660,7 @@ mod tests {
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 0);
 		assert_eq!(pool.ready.len(), 0);
@@ -617,6 +673,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![vec![0]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 
 		// then
@@ -639,6 +696,7 @@ mod tests {
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -649,6 +707,7 @@ mod tests {
 			requires: vec![vec![2]],
 			provides: vec![],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![2u8],
@@ -659,6 +718,7 @@ mod tests {
 			requires: vec![vec![1]],
 			provides: vec![vec![3], vec![2]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![4u8],
@@ -669,6 +729,7 @@ mod tests {
 			requires: vec![vec![3], vec![4]],
 			provides: vec![],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 0);
 		assert_eq!(pool.ready.len(), 0);
@@ -682,6 +743,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![vec![0], vec![4]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 
 		// then
@@ -714,6 +776,7 @@ mod tests {
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -724,6 +787,7 @@ mod tests {
 			requires: vec![vec![1]],
 			provides: vec![vec![2]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 0);
 		assert_eq!(pool.ready.len(), 0);
@@ -738,6 +802,7 @@ mod tests {
 			requires: vec![vec![2]],
 			provides: vec![vec![0]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 
 		// then
@@ -758,6 +823,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![vec![0]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		let mut it = pool.ready().into_iter().map(|tx| tx.data[0]);
 		assert_eq!(it.next(), Some(4));
@@ -786,6 +852,7 @@ mod tests {
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -796,6 +863,7 @@ mod tests {
 			requires: vec![vec![1]],
 			provides: vec![vec![2]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 0);
 		assert_eq!(pool.ready.len(), 0);
@@ -810,6 +878,7 @@ mod tests {
 			requires: vec![vec![2]],
 			provides: vec![vec![0]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 
 		// then
@@ -830,6 +899,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![vec![0]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap_err();
 		let mut it = pool.ready().into_iter().map(|tx| tx.data[0]);
 		assert_eq!(it.next(), None);
@@ -854,6 +924,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![vec![0], vec![4]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![1u8],
@@ -864,6 +935,7 @@ mod tests {
 			requires: vec![vec![0]],
 			provides: vec![vec![1]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -874,6 +946,7 @@ mod tests {
 			requires: vec![vec![2]],
 			provides: vec![],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![2u8],
@@ -884,6 +957,7 @@ mod tests {
 			requires: vec![vec![1]],
 			provides: vec![vec![3], vec![2]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![4u8],
@@ -894,6 +968,7 @@ mod tests {
 			requires: vec![vec![3], vec![4]],
 			provides: vec![],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		// future
 		pool.import(Transaction {
@@ -905,6 +980,7 @@ mod tests {
 			requires: vec![vec![11]],
 			provides: vec![],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		assert_eq!(pool.ready().count(), 5);
 		assert_eq!(pool.future.len(), 1);
@@ -931,6 +1007,7 @@ mod tests {
 			requires: vec![vec![0]],
 			provides: vec![vec![100]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		// ready
 		pool.import(Transaction {
@@ -942,6 +1019,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![vec![1]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![2u8],
@@ -952,6 +1030,7 @@ mod tests {
 			requires: vec![vec![2]],
 			provides: vec![vec![3]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![3u8],
@@ -962,6 +1041,7 @@ mod tests {
 			requires: vec![vec![1]],
 			provides: vec![vec![2]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 		pool.import(Transaction {
 			data: vec![4u8],
@@ -972,6 +1052,7 @@ mod tests {
 			requires: vec![vec![3], vec![2]],
 			provides: vec![vec![4]],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 
 		assert_eq!(pool.ready().count(), 4);
@@ -1007,9 +1088,10 @@ mod tests {
 				requires: vec![vec![3], vec![2]],
 				provides: vec![vec![4]],
 				propagate: true,
+				source: TransactionSource::External,
 			}),
 			"Transaction { \
-hash: 4, priority: 1000, valid_till: 64, bytes: 1, propagate: true, \
+hash: 4, priority: 1000, valid_till: 64, bytes: 1, propagate: true, source: External, \
 requires: [03,02], provides: [04], data: [4]}".to_owned()
 		);
 	}
@@ -1025,6 +1107,7 @@ requires: [03,02], provides: [04], data: [4]}".to_owned()
 				requires: vec![vec![3], vec![2]],
 				provides: vec![vec![4]],
 				propagate: true,
+				source: TransactionSource::External,
 		}.is_propagateable(), true);
 
 		assert_eq!(Transaction {
@@ -1036,6 +1119,7 @@ requires: [03,02], provides: [04], data: [4]}".to_owned()
 				requires: vec![vec![3], vec![2]],
 				provides: vec![vec![4]],
 				propagate: false,
+				source: TransactionSource::External,
 		}.is_propagateable(), false);
 	}
 
@@ -1057,6 +1141,7 @@ requires: [03,02], provides: [04], data: [4]}".to_owned()
 			requires: vec![vec![0]],
 			provides: vec![],
 			propagate: true,
+			source: TransactionSource::External,
 		});
 
 		if let Err(error::Error::RejectedFutureTransaction) = err {
@@ -1080,6 +1165,7 @@ requires: [03,02], provides: [04], data: [4]}".to_owned()
 			requires: vec![vec![0]],
 			provides: vec![],
 			propagate: true,
+			source: TransactionSource::External,
 		}).unwrap();
 
 		// then
@@ -1109,6 +1195,7 @@ requires: [03,02], provides: [04], data: [4]}".to_owned()
 				requires: vec![vec![0]],
 				provides: vec![],
 				propagate: true,
+				source: TransactionSource::External,
 			}).unwrap();
 
 			flag
@@ -1119,4 +1206,105 @@ requires: [03,02], provides: [04], data: [4]}".to_owned()
 		assert_eq!(pool.reject_future_transactions, true);
 		assert_eq!(pool.future.len(), 1);
 	}
+
+	#[test]
+	fn should_evict_lowest_priority_transactions_first() {
+		// given
+		let mut pool = pool();
+		let tx = |hash: u64, priority: u64, valid_till: u64| Transaction {
+			data: vec![hash as u8],
+			bytes: 1,
+			hash,
+			priority,
+			valid_till,
+			requires: vec![],
+			provides: vec![vec![hash as u8]],
+			propagate: true,
+			source: TransactionSource::External,
+		};
+		pool.import(tx(1, 5, 64)).unwrap();
+		pool.import(tx(2, 1, 64)).unwrap();
+		pool.import(tx(3, 5, 32)).unwrap();
+		pool.import(tx(4, 10, 64)).unwrap();
+		let limit = |count| Limit { count, total_bytes: 1024 };
+
+		// when
+		let removed = pool.enforce_limits(&limit(3), &limit(3));
+
+		// then
+		assert_eq!(removed.iter().map(|tx| tx.hash).collect::<Vec<_>>(), vec![2]);
+
+		// when
+		let removed = pool.enforce_limits(&limit(1), &limit(1));
+
+		// then
+		assert_eq!(removed.iter().map(|tx| tx.hash).collect::<Vec<_>>(), vec![3, 1]);
+		assert_eq!(pool.ready().map(|tx| tx.hash).collect::<Vec<_>>(), vec![4]);
+	}
+
+	#[test]
+	fn should_evict_local_transactions_last() {
+		// given
+		let mut pool = pool();
+		let tx = |hash: u64, priority: u64, source| Transaction {
+			data: vec![hash as u8],
+			bytes: 1,
+			hash,
+			priority,
+			valid_till: 64u64,
+			requires: vec![],
+			provides: vec![vec![hash as u8]],
+			propagate: true,
+			source,
+		};
+		pool.import(tx(1, 1, TransactionSource::Local)).unwrap();
+		pool.import(tx(2, 5, TransactionSource::External)).unwrap();
+		let limit = |count| Limit { count, total_bytes: 1024 };
+
+		// when
+		let removed = pool.enforce_limits(&limit(1), &limit(1));
+
+		// then
+		assert_eq!(removed.iter().map(|tx| tx.hash).collect::<Vec<_>>(), vec![2]);
+		assert_eq!(pool.ready().map(|tx| tx.hash).collect::<Vec<_>>(), vec![1]);
+	}
+
+	#[test]
+	fn should_limit_future_transactions_waiting_for_the_same_gap() {
+		// given
+		let mut pool = pool();
+		pool.set_future_per_gap_limit(2);
+		let tx = |hash: u64, requires: u8, provides: u8| Transaction {
+			data: vec![hash as u8],
+			bytes: 1,
+			hash,
+			priority: 5u64,
+			valid_till: 64u64,
+			requires: vec![vec![requires]],
+			provides: vec![vec![provides]],
+			propagate: true,
+			source: TransactionSource::External,
+		};
+		pool.import(tx(3, 3, 4)).unwrap();
+		pool.import(tx(2, 2, 3)).unwrap();
+
+		// when
+		let err = pool.import(tx(1, 1, 2));
+
+		// then
+		if let Err(error::Error::TooManyFutureTransactions) = err {
+		} else {
+			assert!(false, "Invalid error kind: {:?}", err);
+		}
+
+		// transactions waiting for another gap are still accepted
+		pool.import(tx(10, 10, 11)).unwrap();
+		assert_eq!(pool.future.len(), 3);
+		let mut gaps = pool.futures_with_gaps()
+			.into_iter()
+			.map(|(tx, gaps)| (tx.hash, gaps.into_iter().collect::<Vec<_>>()))
+			.collect::<Vec<_>>();
+		gaps.sort();
+		assert_eq!(gaps, vec![(2, vec![vec![2]]), (3, vec![vec![2]]), (10, vec![vec![10]])]);
+	}
 }
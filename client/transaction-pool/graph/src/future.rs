@@ -159,6 +159,58 @@ impl<Hash: hash::Hash + Eq + Clone, Ex> FutureTransactions<Hash, Ex> {
 		self.waiting.contains_key(hash)
 	}
 
+	/// Returns the gaps the transaction waits for: the tags that it misses, directly or through
+	/// other transactions of the queue, and that no transaction of the queue provides.
+	///
+	/// With account nonces as tags, these are the nonces missing for the transaction of a sender
+	/// to become ready.
+	pub fn gaps(&self, tx: &WaitingTransaction<Hash, Ex>) -> HashSet<Tag> {
+		let mut gaps = HashSet::new();
+		let mut visited = HashSet::new();
+		let mut to_visit = tx.missing_tags.iter().cloned().collect::<Vec<_>>();
+
+		while let Some(tag) = to_visit.pop() {
+			if !visited.insert(tag.clone()) {
+				continue;
+			}
+
+			let mut providers = self.waiting.values()
+				.filter(|waiting| waiting.transaction.provides.contains(&tag))
+				.peekable();
+			if providers.peek().is_none() {
+				gaps.insert(tag);
+			}
+			for provider in providers {
+				to_visit.extend(provider.missing_tags.iter().cloned());
+			}
+		}
+
+		gaps
+	}
+
+	/// Returns the transactions that wait for any of the given tags, directly or through other
+	/// transactions of the queue.
+	pub fn waiting_for<'a>(&self, tags: impl IntoIterator<Item=&'a Tag>) -> HashSet<Hash> {
+		let mut waiting = HashSet::new();
+		let mut to_visit = tags.into_iter().cloned().collect::<Vec<_>>();
+		let mut visited = HashSet::new();
+
+		while let Some(tag) = to_visit.pop() {
+			if !visited.insert(tag.clone()) {
+				continue;
+			}
+
+			for hash in self.wanted_tags.get(&tag).into_iter().flatten() {
+				if waiting.insert(hash.clone()) {
+					let tx = self.waiting.get(hash).expect(WAITING_PROOF);
+					to_visit.extend(tx.transaction.provides.iter().cloned());
+				}
+			}
+		}
+
+		waiting
+	}
+
 	/// Returns a list of known transactions
 	pub fn by_hash(&self, hashes: &[Hash]) -> Vec<Option<Arc<Transaction<Hash, Ex>>>> {
 		hashes.iter().map(|h| self.waiting.get(h).map(|x| x.transaction.clone())).collect()
@@ -227,6 +279,11 @@ impl<Hash: hash::Hash + Eq + Clone, Ex> FutureTransactions<Hash, Ex> {
 		self.waiting.values().map(|waiting| &*waiting.transaction)
 	}
 
+	/// Returns iterator over all future transactions, along with the gaps they wait for.
+	pub fn all_with_gaps(&self) -> impl Iterator<Item=(Arc<Transaction<Hash, Ex>>, HashSet<Tag>)> + '_ {
+		self.waiting.values().map(move |waiting| (waiting.transaction.clone(), self.gaps(waiting)))
+	}
+
 	/// Removes and returns all future transactions.
 	pub fn clear(&mut self) -> Vec<Arc<Transaction<Hash, Ex>>> {
 		self.wanted_tags.clear();
@@ -16,7 +16,7 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 use std::{
-	collections::HashMap,
+	collections::{HashMap, VecDeque},
 	fmt,
 	hash,
 };
@@ -25,20 +25,33 @@ use crate::watcher;
 use sp_runtime::traits;
 use log::{debug, trace, warn};
 
+/// Maximum number of blocks whose watched extrinsics are kept waiting for the finality of the
+/// block.
+const MAX_FINALITY_WATCHERS: usize = 512;
+
 /// Extrinsic pool default listener.
-pub struct Listener<H: hash::Hash + Eq, H2> {
-	watchers: HashMap<H, watcher::Sender<H, H2>>
+pub struct Listener<H: hash::Hash + Eq, H2: hash::Hash + Eq> {
+	watchers: HashMap<H, watcher::Sender<H, H2>>,
+	/// Watched extrinsics included in blocks that are not finalized yet, by block.
+	finality_watchers: HashMap<H2, Vec<H>>,
+	/// The blocks of `finality_watchers`, oldest first.
+	finality_order: VecDeque<H2>,
 }
 
-impl<H: hash::Hash + Eq, H2> Default for Listener<H, H2> {
+impl<H: hash::Hash + Eq, H2: hash::Hash + Eq> Default for Listener<H, H2> {
 	fn default() -> Self {
 		Listener {
 			watchers: Default::default(),
+			finality_watchers: Default::default(),
+			finality_order: Default::default(),
 		}
 	}
 }
 
-impl<H: hash::Hash + traits::Member + Serialize, H2: Clone + fmt::Debug> Listener<H, H2> {
+impl<H, H2> Listener<H, H2> where
+	H: hash::Hash + traits::Member + Serialize,
+	H2: hash::Hash + Eq + Clone + fmt::Debug,
+{
 	fn fire<F>(&mut self, hash: &H, fun: F) where F: FnOnce(&mut watcher::Sender<H, H2>) {
 		let clean = if let Some(h) = self.watchers.get_mut(hash) {
 			fun(h);
@@ -103,6 +116,51 @@ impl<H: hash::Hash + traits::Member + Serialize, H2: Clone + fmt::Debug> Listene
 	/// Transaction was pruned from the pool.
 	pub fn pruned(&mut self, header_hash: H2, tx: &H) {
 		debug!(target: "txpool", "[{:?}] Pruned at {:?}", tx, header_hash);
-		self.fire(tx, |watcher| watcher.in_block(header_hash))
+		self.fire(tx, |watcher| watcher.in_block(header_hash.clone()));
+
+		// keep watching the extrinsic until the block is finalized.
+		if self.watchers.contains_key(tx) {
+			if !self.finality_watchers.contains_key(&header_hash) {
+				self.finality_order.push_back(header_hash.clone());
+			}
+			self.finality_watchers.entry(header_hash).or_default().push(tx.clone());
+
+			while self.finality_order.len() > MAX_FINALITY_WATCHERS {
+				let oldest = match self.finality_order.pop_front() {
+					Some(oldest) => oldest,
+					None => break,
+				};
+				for tx in self.finality_watchers.remove(&oldest).unwrap_or_default() {
+					debug!(target: "txpool", "[{:?}] Finality of {:?} not reached in time", tx, oldest);
+					self.watchers.remove(&tx);
+				}
+			}
+		}
+	}
+
+	/// The block with given hash was retracted.
+	pub fn retracted(&mut self, block_hash: H2) {
+		for tx in self.take_finality_watchers(&block_hash) {
+			trace!(target: "txpool", "[{:?}] Retracted at {:?}", tx, block_hash);
+			self.fire(&tx, |watcher| watcher.retracted(block_hash.clone()));
+		}
+	}
+
+	/// The block with given hash was finalized.
+	pub fn finalized(&mut self, block_hash: H2) {
+		for tx in self.take_finality_watchers(&block_hash) {
+			trace!(target: "txpool", "[{:?}] Finalized at {:?}", tx, block_hash);
+			self.fire(&tx, |watcher| watcher.finalized(block_hash.clone()));
+		}
+	}
+
+	fn take_finality_watchers(&mut self, block_hash: &H2) -> Vec<H> {
+		match self.finality_watchers.remove(block_hash) {
+			Some(txs) => {
+				self.finality_order.retain(|hash| hash != block_hash);
+				txs
+			},
+			None => Vec::new(),
+		}
 	}
 }
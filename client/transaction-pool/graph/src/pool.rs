@@ -18,6 +18,7 @@ use std::{
 	hash,
 	collections::HashMap,
 	sync::Arc,
+	time::Duration,
 };
 
 use crate::base_pool as base;
@@ -32,7 +33,9 @@ use futures::{
 use sp_runtime::{
 	generic::BlockId,
 	traits::{self, SaturatedConversion},
-	transaction_validity::{TransactionValidity, TransactionTag as Tag, TransactionValidityError},
+	transaction_validity::{
+		TransactionValidity, TransactionTag as Tag, TransactionValidityError, TransactionSource,
+	},
 };
 use sp_transaction_pool::{error, PoolStatus};
 
@@ -69,10 +72,11 @@ pub trait ChainApi: Send + Sync {
 	/// Validate transaction future.
 	type ValidationFuture: Future<Output=Result<TransactionValidity, Self::Error>> + Send + Unpin;
 
-	/// Verify extrinsic at given block.
+	/// Verify extrinsic, coming from the given source, at given block.
 	fn validate_transaction(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		uxt: ExtrinsicFor<Self>,
 	) -> Self::ValidationFuture;
 
@@ -95,6 +99,13 @@ pub struct Options {
 	pub future: base::Limit,
 	/// Reject future transactions.
 	pub reject_future_transactions: bool,
+	/// Maximum number of future transactions waiting for the same missing tag, directly or
+	/// through other future transactions. With account nonces as tags, this bounds the number
+	/// of future transactions of a single sender.
+	pub future_per_gap: usize,
+	/// How long the transactions that have been found invalid, or dropped because of the limits,
+	/// are banned from the pool.
+	pub ban_time: Duration,
 }
 
 impl Default for Options {
@@ -109,6 +120,8 @@ impl Default for Options {
 				total_bytes: 1 * 1024 * 1024,
 			},
 			reject_future_transactions: false,
+			future_per_gap: 32,
+			ban_time: Duration::from_secs(60 * 30),
 		}
 	}
 }
@@ -126,14 +139,14 @@ impl<B: ChainApi> Pool<B> {
 		}
 	}
 
-	/// Imports a bunch of unverified extrinsics to the pool
-	pub fn submit_at<T>(&self, at: &BlockId<B::Block>, xts: T, force: bool)
+	/// Imports a bunch of unverified extrinsics, coming from the given source, to the pool
+	pub fn submit_at<T>(&self, at: &BlockId<B::Block>, source: TransactionSource, xts: T, force: bool)
 		-> impl Future<Output=Result<Vec<Result<ExHash<B>, B::Error>>, B::Error>>
 	where
 		T: IntoIterator<Item=ExtrinsicFor<B>>
 	{
 		let validated_pool = self.validated_pool.clone();
-		self.verify(at, xts, force)
+		self.verify(at, xts.into_iter().map(move |xt| (source, xt)), force)
 			.map(move |validated_transactions| validated_transactions
 				.map(|validated_transactions| validated_pool.submit(validated_transactions
 					.into_iter()
@@ -144,9 +157,10 @@ impl<B: ChainApi> Pool<B> {
 	pub fn submit_one(
 		&self,
 		at: &BlockId<B::Block>,
+		source: TransactionSource,
 		xt: ExtrinsicFor<B>,
 	) -> impl Future<Output=Result<ExHash<B>, B::Error>> {
-		self.submit_at(at, std::iter::once(xt), false)
+		self.submit_at(at, source, std::iter::once(xt), false)
 			.map(|import_result| import_result.and_then(|mut import_result| import_result
 				.pop()
 				.expect("One extrinsic passed; one result returned; qed")
@@ -157,6 +171,7 @@ impl<B: ChainApi> Pool<B> {
 	pub fn submit_and_watch(
 		&self,
 		at: &BlockId<B::Block>,
+		source: TransactionSource,
 		xt: ExtrinsicFor<B>,
 	) -> impl Future<Output=Result<Watcher<ExHash<B>, BlockHash<B>>, B::Error>> {
 		let block_number = match self.resolve_block_number(at) {
@@ -166,7 +181,7 @@ impl<B: ChainApi> Pool<B> {
 
 		let validated_pool = self.validated_pool.clone();
 		Either::Right(
-			self.verify_one(at, block_number, xt, false)
+			self.verify_one(at, block_number, source, xt, false)
 				.map(move |validated_transactions| validated_pool.submit_and_watch(validated_transactions.1))
 		)
 	}
@@ -180,18 +195,48 @@ impl<B: ChainApi> Pool<B> {
 		at: &BlockId<B::Block>,
 		max: Option<usize>,
 	) -> impl Future<Output=Result<(), B::Error>> {
-		use std::time::Instant;
 		log::debug!(target: "txpool",
 			"Fetching ready transactions (up to: {})",
 			max.map(|x| format!("{}", x)).unwrap_or_else(|| "all".into())
 		);
-		let validated_pool = self.validated_pool.clone();
 		let ready = self.validated_pool.ready()
-			.map(|tx| tx.data.clone())
-			.take(max.unwrap_or_else(usize::max_value));
+			.map(|tx| (tx.source, tx.data.clone()))
+			.take(max.unwrap_or_else(usize::max_value))
+			.collect::<Vec<_>>();
+
+		self.revalidate(at, ready)
+	}
+
+	/// Revalidate the `max` ready transactions that are in the pool for the longest time.
+	///
+	/// Revalidated transactions are resubmitted to the pool, so that they become the youngest
+	/// ones: calling this repeatedly cycles over all the ready transactions. Returns a future
+	/// that resolves to the number of transactions revalidated.
+	pub fn revalidate_oldest_ready(
+		&self,
+		at: &BlockId<B::Block>,
+		max: usize,
+	) -> impl Future<Output=Result<usize, B::Error>> {
+		let oldest = self.validated_pool.oldest_ready(max)
+			.into_iter()
+			.map(|tx| (tx.source, tx.data.clone()))
+			.collect::<Vec<_>>();
+		let count = oldest.len();
+
+		self.revalidate(at, oldest).map(move |result| result.map(|_| count))
+	}
+
+	/// Validate the given transactions again and resubmit them to the pool.
+	fn revalidate(
+		&self,
+		at: &BlockId<B::Block>,
+		transactions: Vec<(TransactionSource, ExtrinsicFor<B>)>,
+	) -> impl Future<Output=Result<(), B::Error>> {
+		use std::time::Instant;
+		let validated_pool = self.validated_pool.clone();
 
 		let now = Instant::now();
-		self.verify(at, ready, false)
+		self.verify(at, transactions, false)
 			.map(move |revalidated_transactions| {
 				log::debug!(target: "txpool",
 					"Re-verified transactions, took {} ms. Resubmitting.",
@@ -262,7 +307,8 @@ impl<B: ChainApi> Pool<B> {
 					),
 					// if it's not found in the pool query the runtime at parent block
 					// to get validity info and tags that the extrinsic provides.
-					None => Either::Right(self.validated_pool.api().validate_transaction(parent, extrinsic.clone())
+					None => Either::Right(self.validated_pool.api()
+						.validate_transaction(parent, TransactionSource::InBlock, extrinsic.clone())
 						.then(|validity| ready(match validity {
 							Ok(Ok(validity)) => validity.provides,
 							// silently ignore invalid extrinsics,
@@ -324,7 +370,7 @@ impl<B: ChainApi> Pool<B> {
 		// Try to re-validate pruned transactions since some of them might be still valid.
 		// note that `known_imported_hashes` will be rejected here due to temporary ban.
 		let pruned_hashes = prune_status.pruned.iter().map(|tx| tx.hash.clone()).collect::<Vec<_>>();
-		let pruned_transactions = prune_status.pruned.into_iter().map(|tx| tx.data.clone());
+		let pruned_transactions = prune_status.pruned.into_iter().map(|tx| (tx.source, tx.data.clone()));
 		let reverify_future = self.verify(at, pruned_transactions, false);
 
 		log::trace!(target: "txpool", "Prunning at {:?}. Resubmitting transactions.", at);
@@ -355,6 +401,18 @@ impl<B: ChainApi> Pool<B> {
 		self.validated_pool.on_broadcasted(propagated)
 	}
 
+	/// Notify the watchers of the extrinsics included in the given block about its finalization.
+	pub fn on_block_finalized(&self, block_hash: BlockHash<B>) {
+		self.validated_pool.on_block_finalized(block_hash)
+	}
+
+	/// Notify the watchers of the extrinsics included in the given block about its retraction.
+	///
+	/// This must be called before the extrinsics of the block are re-submitted to the pool.
+	pub fn on_block_retracted(&self, block_hash: BlockHash<B>) {
+		self.validated_pool.on_block_retracted(block_hash)
+	}
+
 	/// Remove invalid transactions from the pool.
 	pub fn remove_invalid(&self, hashes: &[ExHash<B>]) -> Vec<TransactionFor<B>> {
 		self.validated_pool.remove_invalid(hashes)
@@ -365,6 +423,12 @@ impl<B: ChainApi> Pool<B> {
 		self.validated_pool.ready()
 	}
 
+	/// Returns the future transactions, along with the tags they wait for and that no transaction
+	/// of the pool provides.
+	pub fn futures(&self) -> Vec<(TransactionFor<B>, Vec<Tag>)> {
+		self.validated_pool.futures()
+	}
+
 	/// Returns pool status.
 	pub fn status(&self) -> PoolStatus {
 		self.validated_pool.status()
@@ -382,11 +446,12 @@ impl<B: ChainApi> Pool<B> {
 				error::Error::InvalidBlockId(format!("{:?}", at)).into()))
 	}
 
-	/// Returns future that validates a bunch of transactions at given block.
+	/// Returns future that validates a bunch of transactions, along with their source, at given
+	/// block.
 	fn verify(
 		&self,
 		at: &BlockId<B::Block>,
-		xts: impl IntoIterator<Item=ExtrinsicFor<B>>,
+		xts: impl IntoIterator<Item=(TransactionSource, ExtrinsicFor<B>)>,
 		force: bool,
 	) -> impl Future<Output=Result<HashMap<ExHash<B>, ValidatedTransactionFor<B>>, B::Error>> {
 		// we need a block number to compute tx validity
@@ -396,8 +461,8 @@ impl<B: ChainApi> Pool<B> {
 		};
 
 		// for each xt, prepare a validation future
-		let validation_futures = xts.into_iter().map(move |xt|
-			self.verify_one(at, block_number, xt, force)
+		let validation_futures = xts.into_iter().map(move |(source, xt)|
+			self.verify_one(at, block_number, source, xt, force)
 		);
 
 		// make single validation future that waits all until all extrinsics are validated
@@ -409,6 +474,7 @@ impl<B: ChainApi> Pool<B> {
 		&self,
 		block_id: &BlockId<B::Block>,
 		block_number: NumberFor<B>,
+		source: TransactionSource,
 		xt: ExtrinsicFor<B>,
 		force: bool,
 	) -> impl Future<Output=(ExHash<B>, ValidatedTransactionFor<B>)> {
@@ -420,7 +486,7 @@ impl<B: ChainApi> Pool<B> {
 			)))
 		}
 
-		Either::Right(self.validated_pool.api().validate_transaction(block_id, xt.clone())
+		Either::Right(self.validated_pool.api().validate_transaction(block_id, source, xt.clone())
 			.then(move |validation_result| ready((hash.clone(), match validation_result {
 				Ok(validity) => match validity {
 					Ok(validity) => if validity.provides.is_empty() {
@@ -434,6 +500,7 @@ impl<B: ChainApi> Pool<B> {
 							requires: validity.requires,
 							provides: validity.provides,
 							propagate: validity.propagate,
+							source,
 							valid_till: block_number
 								.saturated_into::<u64>()
 								.saturating_add(validity.longevity),
@@ -474,6 +541,7 @@ mod tests {
 	use crate::base_pool::Limit;
 
 	const INVALID_NONCE: u64 = 254;
+	const SOURCE: TransactionSource = TransactionSource::External;
 
 	#[derive(Clone, Debug, Default)]
 	struct TestApi {
@@ -493,6 +561,7 @@ mod tests {
 		fn validate_transaction(
 			&self,
 			at: &BlockId<Self::Block>,
+			_source: TransactionSource,
 			uxt: ExtrinsicFor<Self>,
 		) -> Self::ValidationFuture {
 			let hash = self.hash_and_length(&uxt).0;
@@ -576,7 +645,7 @@ mod tests {
 		let pool = pool();
 
 		// when
-		let hash = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+		let hash = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 			from: AccountId::from_h256(H256::from_low_u64_be(1)),
 			to: AccountId::from_h256(H256::from_low_u64_be(2)),
 			amount: 5,
@@ -600,7 +669,7 @@ mod tests {
 
 		// when
 		pool.validated_pool.rotator().ban(&Instant::now(), vec![pool.hash_of(&uxt)]);
-		let res = block_on(pool.submit_one(&BlockId::Number(0), uxt));
+		let res = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt));
 		assert_eq!(pool.status().ready, 0);
 		assert_eq!(pool.status().future, 0);
 
@@ -616,20 +685,20 @@ mod tests {
 			let stream = pool.import_notification_stream();
 
 			// when
-			let _hash = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			let _hash = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 				from: AccountId::from_h256(H256::from_low_u64_be(1)),
 				to: AccountId::from_h256(H256::from_low_u64_be(2)),
 				amount: 5,
 				nonce: 0,
 			}))).unwrap();
-			let _hash = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			let _hash = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 				from: AccountId::from_h256(H256::from_low_u64_be(1)),
 				to: AccountId::from_h256(H256::from_low_u64_be(2)),
 				amount: 5,
 				nonce: 1,
 			}))).unwrap();
 			// future doesn't count
-			let _hash = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			let _hash = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 				from: AccountId::from_h256(H256::from_low_u64_be(1)),
 				to: AccountId::from_h256(H256::from_low_u64_be(2)),
 				amount: 5,
@@ -652,19 +721,19 @@ mod tests {
 	fn should_clear_stale_transactions() {
 		// given
 		let pool = pool();
-		let hash1 = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+		let hash1 = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 			from: AccountId::from_h256(H256::from_low_u64_be(1)),
 			to: AccountId::from_h256(H256::from_low_u64_be(2)),
 			amount: 5,
 			nonce: 0,
 		}))).unwrap();
-		let hash2 = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+		let hash2 = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 			from: AccountId::from_h256(H256::from_low_u64_be(1)),
 			to: AccountId::from_h256(H256::from_low_u64_be(2)),
 			amount: 5,
 			nonce: 1,
 		}))).unwrap();
-		let hash3 = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+		let hash3 = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 			from: AccountId::from_h256(H256::from_low_u64_be(1)),
 			to: AccountId::from_h256(H256::from_low_u64_be(2)),
 			amount: 5,
@@ -688,7 +757,7 @@ mod tests {
 	fn should_ban_mined_transactions() {
 		// given
 		let pool = pool();
-		let hash1 = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+		let hash1 = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 			from: AccountId::from_h256(H256::from_low_u64_be(1)),
 			to: AccountId::from_h256(H256::from_low_u64_be(2)),
 			amount: 5,
@@ -715,7 +784,7 @@ mod tests {
 			..Default::default()
 		}, TestApi::default());
 
-		let hash1 = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+		let hash1 = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 			from: AccountId::from_h256(H256::from_low_u64_be(1)),
 			to: AccountId::from_h256(H256::from_low_u64_be(2)),
 			amount: 5,
@@ -724,7 +793,7 @@ mod tests {
 		assert_eq!(pool.status().future, 1);
 
 		// when
-		let hash2 = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+		let hash2 = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 			from: AccountId::from_h256(H256::from_low_u64_be(2)),
 			to: AccountId::from_h256(H256::from_low_u64_be(2)),
 			amount: 5,
@@ -751,7 +820,7 @@ mod tests {
 		}, TestApi::default());
 
 		// when
-		block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+		block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 			from: AccountId::from_h256(H256::from_low_u64_be(1)),
 			to: AccountId::from_h256(H256::from_low_u64_be(2)),
 			amount: 5,
@@ -769,7 +838,7 @@ mod tests {
 		let pool = pool();
 
 		// when
-		let err = block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+		let err = block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 			from: AccountId::from_h256(H256::from_low_u64_be(1)),
 			to: AccountId::from_h256(H256::from_low_u64_be(2)),
 			amount: 5,
@@ -789,7 +858,7 @@ mod tests {
 		fn should_trigger_ready_and_finalized() {
 			// given
 			let pool = pool();
-			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, uxt(Transfer {
 				from: AccountId::from_h256(H256::from_low_u64_be(1)),
 				to: AccountId::from_h256(H256::from_low_u64_be(2)),
 				amount: 5,
@@ -802,11 +871,13 @@ mod tests {
 			block_on(pool.prune_tags(&BlockId::Number(2), vec![vec![0u8]], vec![])).unwrap();
 			assert_eq!(pool.status().ready, 0);
 			assert_eq!(pool.status().future, 0);
+			pool.on_block_finalized(H256::from_low_u64_be(2).into());
 
 			// then
 			let mut stream = futures::executor::block_on_stream(watcher.into_stream());
 			assert_eq!(stream.next(), Some(TransactionStatus::Ready));
 			assert_eq!(stream.next(), Some(TransactionStatus::InBlock(H256::from_low_u64_be(2).into())));
+			assert_eq!(stream.next(), Some(TransactionStatus::Finalized(H256::from_low_u64_be(2).into())));
 			assert_eq!(stream.next(), None);
 		}
 
@@ -814,7 +885,7 @@ mod tests {
 		fn should_trigger_ready_and_finalized_when_pruning_via_hash() {
 			// given
 			let pool = pool();
-			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, uxt(Transfer {
 				from: AccountId::from_h256(H256::from_low_u64_be(1)),
 				to: AccountId::from_h256(H256::from_low_u64_be(2)),
 				amount: 5,
@@ -827,11 +898,43 @@ mod tests {
 			block_on(pool.prune_tags(&BlockId::Number(2), vec![vec![0u8]], vec![2u64])).unwrap();
 			assert_eq!(pool.status().ready, 0);
 			assert_eq!(pool.status().future, 0);
+			pool.on_block_finalized(H256::from_low_u64_be(2).into());
 
 			// then
 			let mut stream = futures::executor::block_on_stream(watcher.into_stream());
 			assert_eq!(stream.next(), Some(TransactionStatus::Ready));
 			assert_eq!(stream.next(), Some(TransactionStatus::InBlock(H256::from_low_u64_be(2).into())));
+			assert_eq!(stream.next(), Some(TransactionStatus::Finalized(H256::from_low_u64_be(2).into())));
+			assert_eq!(stream.next(), None);
+		}
+
+		#[test]
+		fn should_trigger_retracted_and_finalized_when_included_again() {
+			// given
+			let pool = pool();
+			let xt = uxt(Transfer {
+				from: AccountId::from_h256(H256::from_low_u64_be(1)),
+				to: AccountId::from_h256(H256::from_low_u64_be(2)),
+				amount: 5,
+				nonce: 0,
+			});
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, xt.clone())).unwrap();
+
+			// when
+			block_on(pool.prune_tags(&BlockId::Number(2), vec![vec![0u8]], vec![])).unwrap();
+			pool.on_block_retracted(H256::from_low_u64_be(2).into());
+			block_on(pool.submit_at(&BlockId::Number(0), SOURCE, vec![xt], true)).unwrap();
+			block_on(pool.prune_tags(&BlockId::Number(3), vec![vec![0u8]], vec![])).unwrap();
+			pool.on_block_finalized(H256::from_low_u64_be(3).into());
+
+			// then
+			let mut stream = futures::executor::block_on_stream(watcher.into_stream());
+			assert_eq!(stream.next(), Some(TransactionStatus::Ready));
+			assert_eq!(stream.next(), Some(TransactionStatus::InBlock(H256::from_low_u64_be(2).into())));
+			assert_eq!(stream.next(), Some(TransactionStatus::Retracted(H256::from_low_u64_be(2).into())));
+			assert_eq!(stream.next(), Some(TransactionStatus::Ready));
+			assert_eq!(stream.next(), Some(TransactionStatus::InBlock(H256::from_low_u64_be(3).into())));
+			assert_eq!(stream.next(), Some(TransactionStatus::Finalized(H256::from_low_u64_be(3).into())));
 			assert_eq!(stream.next(), None);
 		}
 
@@ -839,7 +942,7 @@ mod tests {
 		fn should_trigger_future_and_ready_after_promoted() {
 			// given
 			let pool = pool();
-			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), uxt(Transfer {
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, uxt(Transfer {
 				from: AccountId::from_h256(H256::from_low_u64_be(1)),
 				to: AccountId::from_h256(H256::from_low_u64_be(2)),
 				amount: 5,
@@ -849,7 +952,7 @@ mod tests {
 			assert_eq!(pool.status().future, 1);
 
 			// when
-			block_on(pool.submit_one(&BlockId::Number(0), uxt(Transfer {
+			block_on(pool.submit_one(&BlockId::Number(0), SOURCE, uxt(Transfer {
 				from: AccountId::from_h256(H256::from_low_u64_be(1)),
 				to: AccountId::from_h256(H256::from_low_u64_be(2)),
 				amount: 5,
@@ -873,7 +976,7 @@ mod tests {
 				amount: 5,
 				nonce: 0,
 			});
-			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), uxt)).unwrap();
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, uxt)).unwrap();
 			assert_eq!(pool.status().ready, 1);
 
 			// when
@@ -897,7 +1000,7 @@ mod tests {
 				amount: 5,
 				nonce: 0,
 			});
-			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), uxt)).unwrap();
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, uxt)).unwrap();
 			assert_eq!(pool.status().ready, 1);
 
 			// when
@@ -932,7 +1035,7 @@ mod tests {
 				amount: 5,
 				nonce: 0,
 			});
-			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), xt)).unwrap();
+			let watcher = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, xt)).unwrap();
 			assert_eq!(pool.status().ready, 1);
 
 			// when
@@ -942,7 +1045,7 @@ mod tests {
 				amount: 4,
 				nonce: 1,
 			});
-			block_on(pool.submit_one(&BlockId::Number(1), xt)).unwrap();
+			block_on(pool.submit_one(&BlockId::Number(1), SOURCE, xt)).unwrap();
 			assert_eq!(pool.status().ready, 1);
 
 			// then
@@ -971,7 +1074,7 @@ mod tests {
 			// This transaction should go to future, since we use `nonce: 1`
 			let pool2 = pool.clone();
 			std::thread::spawn(move || {
-				block_on(pool2.submit_one(&BlockId::Number(0), xt)).unwrap();
+				block_on(pool2.submit_one(&BlockId::Number(0), SOURCE, xt)).unwrap();
 				ready.send(()).unwrap();
 			});
 
@@ -985,7 +1088,7 @@ mod tests {
 			});
 			// The tag the above transaction provides (TestApi is using just nonce as u8)
 			let provides = vec![0_u8];
-			block_on(pool.submit_one(&BlockId::Number(0), xt)).unwrap();
+			block_on(pool.submit_one(&BlockId::Number(0), SOURCE, xt)).unwrap();
 			assert_eq!(pool.status().ready, 1);
 
 			// Now block import happens before the second transaction is able to finish verification.
@@ -1020,19 +1123,19 @@ mod tests {
 		let pool = pool();
 		let tx0 = transfer(0);
 		let hash0 = pool.validated_pool.api().hash_and_length(&tx0).0;
-		let watcher0 = block_on(pool.submit_and_watch(&BlockId::Number(0), tx0)).unwrap();
+		let watcher0 = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, tx0)).unwrap();
 		let tx1 = transfer(1);
 		let hash1 = pool.validated_pool.api().hash_and_length(&tx1).0;
-		let watcher1 = block_on(pool.submit_and_watch(&BlockId::Number(0), tx1)).unwrap();
+		let watcher1 = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, tx1)).unwrap();
 		let tx2 = transfer(2);
 		let hash2 = pool.validated_pool.api().hash_and_length(&tx2).0;
-		let watcher2 = block_on(pool.submit_and_watch(&BlockId::Number(0), tx2)).unwrap();
+		let watcher2 = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, tx2)).unwrap();
 		let tx3 = transfer(3);
 		let hash3 = pool.validated_pool.api().hash_and_length(&tx3).0;
-		let watcher3 = block_on(pool.submit_and_watch(&BlockId::Number(0), tx3)).unwrap();
+		let watcher3 = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, tx3)).unwrap();
 		let tx4 = transfer(4);
 		let hash4 = pool.validated_pool.api().hash_and_length(&tx4).0;
-		let watcher4 = block_on(pool.submit_and_watch(&BlockId::Number(0), tx4)).unwrap();
+		let watcher4 = block_on(pool.submit_and_watch(&BlockId::Number(0), SOURCE, tx4)).unwrap();
 		assert_eq!(pool.status().ready, 5);
 
 		// when
@@ -152,6 +152,17 @@ impl<Hash: hash::Hash + Member + Serialize, Ex> ReadyTransactions<Hash, Ex> {
 		}
 	}
 
+	/// Returns at most `max` ready transactions, the ones that are longer in the queue first.
+	pub fn oldest(&self, max: usize) -> Vec<Arc<Transaction<Hash, Ex>>> {
+		let mut transactions = self.ready
+			.read()
+			.values()
+			.map(|tx| tx.transaction.clone())
+			.collect::<Vec<_>>();
+		transactions.sort_by_key(|tx| tx.insertion_id);
+		transactions.into_iter().take(max).map(|tx| tx.transaction).collect()
+	}
+
 	/// Imports transactions to the pool of ready transactions.
 	///
 	/// The transaction needs to have all tags satisfied (be ready) by transactions
@@ -540,6 +551,7 @@ fn remove_item<T: PartialEq>(vec: &mut Vec<T>, item: &T) {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use sp_runtime::transaction_validity::TransactionSource;
 
 	fn tx(id: u8) -> Transaction<u64, Vec<u8>> {
 		Transaction {
@@ -551,6 +563,7 @@ mod tests {
 			requires: vec![vec![1], vec![2]],
 			provides: vec![vec![3], vec![4]],
 			propagate: true,
+			source: TransactionSource::External,
 		}
 	}
 
@@ -651,6 +664,7 @@ mod tests {
 			requires: vec![tx1.provides[0].clone()],
 			provides: vec![],
 			propagate: true,
+			source: TransactionSource::External,
 		};
 
 		// when
@@ -52,6 +52,16 @@ impl<Hash: hash::Hash + Eq> Default for PoolRotator<Hash> {
 	}
 }
 
+impl<Hash: hash::Hash + Eq> PoolRotator<Hash> {
+	/// New rotator instance with specified ban time.
+	pub fn new(ban_time: Duration) -> Self {
+		PoolRotator {
+			ban_time,
+			banned_until: Default::default(),
+		}
+	}
+}
+
 impl<Hash: hash::Hash + Eq + Clone> PoolRotator<Hash> {
 	/// Returns `true` if extrinsic hash is currently banned.
 	pub fn is_banned(&self, hash: &Hash) -> bool {
@@ -59,6 +69,9 @@ impl<Hash: hash::Hash + Eq + Clone> PoolRotator<Hash> {
 	}
 
 	/// Bans given set of hashes.
+	///
+	/// A hash that is banned again, e.g. because the transaction has been found invalid once
+	/// more, has its ban extended.
 	pub fn ban(&self, now: &Instant, hashes: impl IntoIterator<Item=Hash>) {
 		let mut banned = self.banned_until.write();
 
@@ -99,6 +112,7 @@ impl<Hash: hash::Hash + Eq + Clone> PoolRotator<Hash> {
 #[cfg(test)]
 mod tests {
 	use super::*;
+	use sp_runtime::transaction_validity::TransactionSource;
 
 	type Hash = u64;
 	type Ex = ();
@@ -121,6 +135,7 @@ mod tests {
 			requires: vec![],
 			provides: vec![],
 			propagate: true,
+			source: TransactionSource::External,
 		};
 
 		(hash, tx)
@@ -187,6 +202,7 @@ mod tests {
 				requires: vec![],
 				provides: vec![],
 				propagate: true,
+				source: TransactionSource::External,
 			}
 		}
 
@@ -77,14 +77,15 @@ pub(crate) struct ValidatedPool<B: ChainApi> {
 impl<B: ChainApi> ValidatedPool<B> {
 	/// Create a new transaction pool.
 	pub fn new(options: Options, api: B) -> Self {
-		let base_pool = base::BasePool::new(options.reject_future_transactions);
+		let mut base_pool = base::BasePool::new(options.reject_future_transactions);
+		base_pool.set_future_per_gap_limit(options.future_per_gap);
 		ValidatedPool {
+			rotator: PoolRotator::new(options.ban_time),
 			api,
 			options,
 			listener: Default::default(),
 			pool: RwLock::new(base_pool),
 			import_notification_sinks: Default::default(),
-			rotator: Default::default(),
 		}
 	}
 
@@ -458,6 +459,16 @@ impl<B: ChainApi> ValidatedPool<B> {
 		}
 	}
 
+	/// Invoked when a block is finalized.
+	pub fn on_block_finalized(&self, block_hash: BlockHash<B>) {
+		self.listener.write().finalized(block_hash);
+	}
+
+	/// Invoked when a block is retracted.
+	pub fn on_block_retracted(&self, block_hash: BlockHash<B>) {
+		self.listener.write().retracted(block_hash);
+	}
+
 	/// Remove a subtree of transactions from the pool and mark them invalid.
 	///
 	/// The transactions passed as an argument will be additionally banned
@@ -489,6 +500,24 @@ impl<B: ChainApi> ValidatedPool<B> {
 		self.pool.read().ready()
 	}
 
+	/// Returns at most `max` ready transactions, the ones that are longer in the pool first.
+	pub fn oldest_ready(&self, max: usize) -> Vec<TransactionFor<B>> {
+		self.pool.read().oldest_ready(max)
+	}
+
+	/// Returns the future transactions, along with the tags they wait for and that no transaction
+	/// of the pool provides.
+	pub fn futures(&self) -> Vec<(TransactionFor<B>, Vec<Tag>)> {
+		self.pool.read().futures_with_gaps()
+			.into_iter()
+			.map(|(tx, gaps)| {
+				let mut gaps = gaps.into_iter().collect::<Vec<_>>();
+				gaps.sort();
+				(tx, gaps)
+			})
+			.collect()
+	}
+
 	/// Returns pool status.
 	pub fn status(&self) -> PoolStatus {
 		self.pool.read().status()
@@ -89,8 +89,20 @@ impl<H: Clone, H2: Clone> Sender<H, H2> {
 	}
 
 	/// Extrinsic has been included in block with given hash.
+	///
+	/// The extrinsic is still watched, until the block is finalized.
 	pub fn in_block(&mut self, hash: H2) {
 		self.send(TransactionStatus::InBlock(hash));
+	}
+
+	/// The block with given hash, which included the extrinsic, has been retracted.
+	pub fn retracted(&mut self, hash: H2) {
+		self.send(TransactionStatus::Retracted(hash));
+	}
+
+	/// The block with given hash, which included the extrinsic, has been finalized.
+	pub fn finalized(&mut self, hash: H2) {
+		self.send(TransactionStatus::Finalized(hash));
 		self.finalized = true;
 	}
 
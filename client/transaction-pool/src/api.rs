@@ -18,14 +18,18 @@
 
 use std::{marker::PhantomData, pin::Pin, sync::Arc};
 use codec::{Decode, Encode};
-use futures::{channel::oneshot, executor::{ThreadPool, ThreadPoolBuilder}, future::{Future, FutureExt, ready}};
+use futures::{channel::oneshot, executor::{ThreadPool, ThreadPoolBuilder}, future::{Future, ready}};
 
 use sc_client_api::{
 	blockchain::HeaderBackend,
 	light::{Fetcher, RemoteCallRequest}
 };
 use sp_core::{H256, Blake2Hasher, Hasher};
-use sp_runtime::{generic::BlockId, traits::{self, Block as BlockT}, transaction_validity::TransactionValidity};
+use sp_api::{ApiExt, RuntimeVersion};
+use sp_runtime::{
+	generic::BlockId, traits::{self, Block as BlockT},
+	transaction_validity::{TransactionValidity, TransactionSource},
+};
 use sp_transaction_pool::runtime_api::TaggedTransactionQueue;
 
 use crate::error::{self, Error};
@@ -68,6 +72,7 @@ impl<T, Block> sc_transaction_graph::ChainApi for FullChainApi<T, Block> where
 	fn validate_transaction(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		uxt: sc_transaction_graph::ExtrinsicFor<Self>,
 	) -> Self::ValidationFuture {
 		let (tx, rx) = oneshot::channel();
@@ -75,7 +80,16 @@ impl<T, Block> sc_transaction_graph::ChainApi for FullChainApi<T, Block> where
 		let at = at.clone();
 
 		self.pool.spawn_ok(async move {
-			let res = client.runtime_api().validate_transaction(&at, uxt)
+			let runtime_api = client.runtime_api();
+			let res = runtime_api
+				.has_api_with::<dyn TaggedTransactionQueue<Block, Error = ()>, _>(&at, |v| v >= 2)
+				.and_then(|has_source| if has_source {
+					runtime_api.validate_transaction(&at, source, uxt)
+				} else {
+					// older runtimes don't know about the source of the transactions.
+					#[allow(deprecated)]
+					runtime_api.validate_transaction_before_version_2(&at, uxt)
+				})
 				.map_err(|e| Error::RuntimeApi(format!("{:?}", e)));
 			if let Err(e) = tx.send(res) {
 				log::warn!("Unable to send a validate transaction result: {:?}", e);
@@ -141,11 +155,12 @@ impl<T, F, Block> sc_transaction_graph::ChainApi for LightChainApi<T, F, Block>
 	type Block = Block;
 	type Hash = H256;
 	type Error = error::Error;
-	type ValidationFuture = Box<dyn Future<Output = error::Result<TransactionValidity>> + Send + Unpin>;
+	type ValidationFuture = Pin<Box<dyn Future<Output = error::Result<TransactionValidity>> + Send>>;
 
 	fn validate_transaction(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		uxt: sc_transaction_graph::ExtrinsicFor<Self>,
 	) -> Self::ValidationFuture {
 		let header_hash = self.client.expect_block_hash_from_id(at);
@@ -154,27 +169,38 @@ impl<T, F, Block> sc_transaction_graph::ChainApi for LightChainApi<T, F, Block>
 				.map(|header| (header_hash, header)));
 		let (block, header) = match header_and_hash {
 			Ok((header_hash, header)) => (header_hash, header),
-			Err(err) => return Box::new(ready(Err(err.into()))),
+			Err(err) => return Box::pin(ready(Err(err.into()))),
 		};
-		let remote_validation_request = self.fetcher.remote_call(RemoteCallRequest {
-			block,
-			header,
-			method: "TaggedTransactionQueue_validate_transaction".into(),
-			call_data: uxt.encode(),
-			retry_count: None,
-		});
-		let remote_validation_request = remote_validation_request.then(move |result| {
-			let result: error::Result<TransactionValidity> = result
-				.map_err(Into::into)
-				.and_then(|result| Decode::decode(&mut &result[..])
-					.map_err(|e| Error::RuntimeApi(
-						format!("Error decoding tx validation result: {:?}", e)
-					))
-				);
-			ready(result)
-		});
+		let fetcher = self.fetcher.clone();
 
-		Box::new(remote_validation_request)
+		Box::pin(async move {
+			let version = fetcher.remote_call(RemoteCallRequest {
+				block,
+				header: header.clone(),
+				method: "Core_version".into(),
+				call_data: Vec::new(),
+				retry_count: None,
+			}).await?;
+			let version = RuntimeVersion::decode(&mut &version[..])
+				.map_err(|e| Error::RuntimeApi(format!("Error decoding runtime version: {:?}", e)))?;
+
+			// older runtimes don't know about the source of the transactions.
+			let call_data = if version.has_api_with::<dyn TaggedTransactionQueue<Block, Error = ()>, _>(|v| v >= 2) {
+				(source, uxt).encode()
+			} else {
+				uxt.encode()
+			};
+
+			let result = fetcher.remote_call(RemoteCallRequest {
+				block,
+				header,
+				method: "TaggedTransactionQueue_validate_transaction".into(),
+				call_data,
+				retry_count: None,
+			}).await?;
+			Decode::decode(&mut &result[..])
+				.map_err(|e| Error::RuntimeApi(format!("Error decoding tx validation result: {:?}", e)))
+		})
 	}
 
 	fn block_id_to_number(&self, at: &BlockId<Self::Block>) -> error::Result<Option<sc_transaction_graph::NumberFor<Self>>> {
@@ -20,7 +20,9 @@
 #![warn(unused_extern_crates)]
 
 mod api;
+mod local;
 mod maintainer;
+mod revalidation;
 
 pub mod error;
 #[cfg(test)]
@@ -28,6 +30,7 @@ mod tests;
 
 pub use sc_transaction_graph as txpool;
 pub use crate::api::{FullChainApi, LightChainApi};
+pub use crate::local::LocalTransactions;
 pub use crate::maintainer::{FullBasicPoolMaintainer, LightBasicPoolMaintainer};
 
 use std::{collections::HashMap, sync::Arc};
@@ -36,6 +39,7 @@ use futures::{Future, FutureExt};
 use sp_runtime::{
 	generic::BlockId,
 	traits::Block as BlockT,
+	transaction_validity::{TransactionTag, TransactionSource},
 };
 use sp_transaction_pool::{
 	TransactionPool, PoolStatus, ImportNotificationStream,
@@ -82,26 +86,29 @@ impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
 	fn submit_at(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		xts: impl IntoIterator<Item=TransactionFor<Self>> + 'static,
 	) -> Box<dyn Future<Output=Result<Vec<Result<TxHash<Self>, Self::Error>>, Self::Error>> + Send + Unpin> {
-		Box::new(self.pool.submit_at(at, xts, false))
+		Box::new(self.pool.submit_at(at, source, xts, false))
 	}
 
 	fn submit_one(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		xt: TransactionFor<Self>,
 	) -> Box<dyn Future<Output=Result<TxHash<Self>, Self::Error>> + Send + Unpin> {
-		Box::new(self.pool.submit_one(at, xt))
+		Box::new(self.pool.submit_one(at, source, xt))
 	}
 
 	fn submit_and_watch(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		xt: TransactionFor<Self>,
 	) -> Box<dyn Future<Output=Result<Box<TransactionStatusStreamFor<Self>>, Self::Error>> + Send + Unpin> {
 		Box::new(
-			self.pool.submit_and_watch(at, xt)
+			self.pool.submit_and_watch(at, source, xt)
 				.map(|result| result.map(|watcher| Box::new(watcher.into_stream()) as _))
 		)
 	}
@@ -118,6 +125,10 @@ impl<PoolApi, Block> TransactionPool for BasicPool<PoolApi, Block>
 		Box::new(self.pool.ready())
 	}
 
+	fn futures(&self) -> Vec<(Arc<Self::InPoolTransaction>, Vec<TransactionTag>)> {
+		self.pool.futures()
+	}
+
 	fn import_notification_stream(&self) -> ImportNotificationStream {
 		self.pool.import_notification_stream()
 	}
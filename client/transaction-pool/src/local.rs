@@ -0,0 +1,310 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Persistence of the transactions submitted locally.
+//!
+//! Transactions submitted through the APIs of the node are kept in the auxiliary storage until
+//! the block including them is finalized, or until the pool gives up on them, so that they can be
+//! submitted again after a restart of the node. Each transaction is stored under its own key,
+//! along with the list of the hashes of the local transactions.
+
+use std::sync::Arc;
+use codec::{Encode, Decode};
+use futures::{future::join_all, prelude::*};
+use log::{debug, warn};
+use parking_lot::Mutex;
+
+use sc_client_api::backend::AuxStore;
+use sp_runtime::{
+	generic::BlockId, traits::Block as BlockT, transaction_validity::TransactionSource,
+};
+use sp_transaction_pool::{TransactionPool, TransactionStatus};
+
+/// Key of the hashes of the local transactions in the auxiliary storage, oldest first.
+const LOCAL_TRANSACTION_HASHES_KEY: &[u8] = b"txpool_local_transaction_hashes";
+/// Prefix of the key of each local transaction in the auxiliary storage.
+const LOCAL_TRANSACTION_PREFIX: &[u8] = b"txpool_local_transaction";
+
+/// Maximum number of local transactions kept. The oldest ones are forgotten first.
+const MAX_LOCAL_TRANSACTIONS: usize = 4096;
+
+/// Transactions submitted locally that are not finalized yet, persisted in the auxiliary storage.
+pub struct LocalTransactions<Client, Block: BlockT> {
+	client: Arc<Client>,
+	/// The transactions, along with their hash, oldest first.
+	transactions: Mutex<Vec<(Block::Hash, Block::Extrinsic)>>,
+}
+
+impl<Client, Block> LocalTransactions<Client, Block> where
+	Client: AuxStore + Send + Sync + 'static,
+	Block: BlockT,
+{
+	/// Load the local transactions persisted by the given client.
+	pub fn load(client: Arc<Client>) -> sp_blockchain::Result<Self> {
+		let corrupted = |e| sp_blockchain::Error::Backend(
+			format!("Local transactions are corrupted: {:?}", e)
+		);
+		let hashes: Vec<Block::Hash> = match client.get_aux(LOCAL_TRANSACTION_HASHES_KEY)? {
+			Some(encoded) => Decode::decode(&mut &encoded[..]).map_err(corrupted)?,
+			None => Vec::new(),
+		};
+
+		let mut transactions = Vec::with_capacity(hashes.len());
+		for hash in hashes {
+			match client.get_aux(&transaction_key(&hash))? {
+				Some(encoded) => transactions.push((hash, Decode::decode(&mut &encoded[..]).map_err(corrupted)?)),
+				None => warn!(target: "txpool", "[{:?}] Local transaction is missing from the storage", hash),
+			}
+		}
+
+		Ok(LocalTransactions {
+			client,
+			transactions: Mutex::new(transactions),
+		})
+	}
+
+	/// Returns the local transactions, oldest first.
+	pub fn transactions(&self) -> Vec<Block::Extrinsic> {
+		self.transactions.lock().iter().map(|(_, xt)| xt.clone()).collect()
+	}
+
+	/// Persist a transaction submitted locally.
+	pub fn insert(&self, hash: Block::Hash, xt: Block::Extrinsic) {
+		let mut transactions = self.transactions.lock();
+		if transactions.iter().any(|(known, _)| *known == hash) {
+			return;
+		}
+
+		transactions.push((hash, xt.clone()));
+		let mut forgotten = Vec::new();
+		if transactions.len() > MAX_LOCAL_TRANSACTIONS {
+			let excess = transactions.len() - MAX_LOCAL_TRANSACTIONS;
+			warn!(target: "txpool", "Too many local transactions, forgetting the {} oldest ones.", excess);
+			forgotten.extend(transactions.drain(..excess).map(|(hash, _)| hash));
+		}
+		self.persist(&transactions, Some((&hash, &xt)), &forgotten);
+	}
+
+	/// Forget a local transaction.
+	pub fn remove(&self, hash: &Block::Hash) {
+		let mut transactions = self.transactions.lock();
+		let len = transactions.len();
+		transactions.retain(|(known, _)| known != hash);
+		if transactions.len() != len {
+			self.persist(&transactions, None, &[hash.clone()]);
+		}
+	}
+
+	/// Note a new status of a local transaction, forgetting it once it is finalized or no longer
+	/// in the pool.
+	pub fn on_status(&self, hash: &Block::Hash, status: &TransactionStatus<Block::Hash, Block::Hash>) {
+		match status {
+			TransactionStatus::Finalized(_)
+				| TransactionStatus::Usurped(_)
+				| TransactionStatus::Dropped
+				| TransactionStatus::Invalid => self.remove(hash),
+			TransactionStatus::Future
+				| TransactionStatus::Ready
+				| TransactionStatus::Broadcast(_)
+				| TransactionStatus::InBlock(_)
+				| TransactionStatus::Retracted(_) => {},
+		}
+	}
+
+	/// Returns a future following the statuses of a local transaction, until it is forgotten.
+	pub fn track<S>(self: Arc<Self>, hash: Block::Hash, statuses: S) -> impl Future<Output=()> where
+		S: Stream<Item=TransactionStatus<Block::Hash, Block::Hash>>,
+	{
+		statuses.for_each(move |status| {
+			self.on_status(&hash, &status);
+			future::ready(())
+		})
+	}
+
+	/// Follows the statuses of a local transaction on behalf of a subscriber. The transaction is
+	/// forgotten once it is finalized or no longer in the pool, and as soon as the returned stream
+	/// is dropped, e.g. because the subscriber went away, even if it wasn't polled to the end.
+	pub fn watch<S>(
+		self: Arc<Self>,
+		hash: Block::Hash,
+		statuses: S,
+	) -> impl Stream<Item=TransactionStatus<Block::Hash, Block::Hash>> where
+		S: Stream<Item=TransactionStatus<Block::Hash, Block::Hash>>,
+	{
+		let guard = ForgetOnDrop { local: self, hash };
+		statuses.inspect(move |status| guard.local.on_status(&guard.hash, status))
+	}
+
+	/// Submit the local transactions to the pool again, at the given block.
+	///
+	/// The returned future resolves once the pool has processed the submissions, to a future
+	/// following the statuses of the transactions until they are forgotten. Transactions that
+	/// are refused by the pool, e.g. because they have been included in the meantime, are
+	/// forgotten right away.
+	pub fn resubmit<P>(
+		self: Arc<Self>,
+		pool: Arc<P>,
+		at: BlockId<Block>,
+	) -> impl Future<Output=impl Future<Output=()>> where
+		P: TransactionPool<Block=Block, Hash=Block::Hash> + 'static,
+	{
+		let submissions = self.transactions().into_iter().map(|xt| {
+			let hash = pool.hash_of(&xt);
+			pool.submit_and_watch(&at, TransactionSource::External, xt).map(move |result| (hash, result))
+		}).collect::<Vec<_>>();
+
+		join_all(submissions).map(move |results| {
+			let tracked = results.into_iter().filter_map(|(hash, result)| match result {
+				Ok(statuses) => Some(self.clone().track(hash, statuses)),
+				Err(e) => {
+					debug!(target: "txpool", "[{:?}] Forgetting local transaction: {:?}", hash, e);
+					self.remove(&hash);
+					None
+				},
+			}).collect::<Vec<_>>();
+
+			join_all(tracked).map(|_| ())
+		})
+	}
+
+	/// Stores the hashes of the local transactions, along with the transaction that has been
+	/// inserted, if any, and deletes the ones that have been removed.
+	fn persist(
+		&self,
+		transactions: &[(Block::Hash, Block::Extrinsic)],
+		inserted: Option<(&Block::Hash, &Block::Extrinsic)>,
+		removed: &[Block::Hash],
+	) {
+		let hashes = transactions.iter().map(|(hash, _)| hash).collect::<Vec<_>>().encode();
+		let inserted = inserted.map(|(hash, xt)| (transaction_key(hash), xt.encode()));
+		let removed = removed.iter().map(transaction_key).collect::<Vec<_>>();
+
+		let mut insert = vec![(LOCAL_TRANSACTION_HASHES_KEY, &hashes[..])];
+		if let Some((key, encoded)) = inserted.as_ref() {
+			insert.push((&key[..], &encoded[..]));
+		}
+		let delete = removed.iter().map(|key| &key[..]).collect::<Vec<_>>();
+		if let Err(e) = self.client.insert_aux(&insert, &delete) {
+			warn!(target: "txpool", "Failed to persist the local transactions: {:?}", e);
+		}
+	}
+}
+
+fn transaction_key<H: Encode>(hash: &H) -> Vec<u8> {
+	(LOCAL_TRANSACTION_PREFIX, hash).encode()
+}
+
+/// Forgets a local transaction when dropped.
+struct ForgetOnDrop<Client, Block: BlockT> where
+	Client: AuxStore + Send + Sync + 'static,
+{
+	local: Arc<LocalTransactions<Client, Block>>,
+	hash: Block::Hash,
+}
+
+impl<Client, Block> Drop for ForgetOnDrop<Client, Block> where
+	Client: AuxStore + Send + Sync + 'static,
+	Block: BlockT,
+{
+	fn drop(&mut self) {
+		self.local.remove(&self.hash);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use substrate_test_runtime_client::{
+		prelude::*,
+		runtime::{Block, Extrinsic, Transfer},
+	};
+	use crate::{BasicPool, FullChainApi};
+
+	fn transfer(from: AccountKeyring, nonce: u64) -> Extrinsic {
+		Transfer {
+			amount: 5,
+			nonce,
+			from: from.into(),
+			to: Default::default(),
+		}.into_signed_tx()
+	}
+
+	#[test]
+	fn should_persist_local_transactions() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let local = LocalTransactions::<_, Block>::load(client.clone()).unwrap();
+		let (first, second) = (transfer(AccountKeyring::Alice, 0), transfer(AccountKeyring::Bob, 0));
+
+		// when
+		local.insert(Default::default(), first.clone());
+		local.insert([1; 32].into(), second);
+		local.on_status(&Default::default(), &TransactionStatus::InBlock(Default::default()));
+		local.on_status(&[1; 32].into(), &TransactionStatus::Finalized(Default::default()));
+
+		// then
+		let loaded = LocalTransactions::<_, Block>::load(client.clone()).unwrap();
+		assert_eq!(loaded.transactions(), vec![first]);
+		assert!(client.get_aux(&transaction_key(&Block::Hash::default())).unwrap().is_some());
+		assert!(client.get_aux(&transaction_key(&Block::Hash::from([1; 32]))).unwrap().is_none());
+	}
+
+	#[test]
+	fn should_forget_watched_transactions_once_the_watcher_is_dropped() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let local = Arc::new(LocalTransactions::<_, Block>::load(client.clone()).unwrap());
+		let xt = transfer(AccountKeyring::Alice, 0);
+		local.insert(Default::default(), xt.clone());
+
+		// when
+		let (tx, rx) = futures::channel::mpsc::unbounded();
+		let mut watcher = local.clone().watch(Default::default(), rx);
+		tx.unbounded_send(TransactionStatus::Ready).unwrap();
+		assert_eq!(block_on(watcher.next()), Some(TransactionStatus::Ready));
+		assert_eq!(local.transactions(), vec![xt]);
+		drop(watcher);
+
+		// then
+		assert!(local.transactions().is_empty());
+		let loaded = LocalTransactions::<_, Block>::load(client).unwrap();
+		assert!(loaded.transactions().is_empty());
+	}
+
+	#[test]
+	fn should_resubmit_local_transactions() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let pool = Arc::new(BasicPool::new(Default::default(), FullChainApi::new(client.clone())));
+		let local = Arc::new(LocalTransactions::<_, Block>::load(client.clone()).unwrap());
+		let valid = transfer(AccountKeyring::Alice, 0);
+		let invalid = match transfer(AccountKeyring::Bob, 0) {
+			Extrinsic::Transfer(transfer, _) => {
+				let signature = AccountKeyring::Alice.sign(&transfer.encode());
+				Extrinsic::Transfer(transfer, signature.into())
+			},
+			_ => unreachable!(),
+		};
+		local.insert(pool.hash_of(&valid), valid.clone());
+		local.insert(pool.hash_of(&invalid), invalid);
+
+		// when
+		let best = BlockId::hash(client.info().chain.best_hash);
+		let _tracked = block_on(local.clone().resubmit(pool.clone(), best));
+
+		// then
+		assert_eq!(pool.status().ready, 1);
+		assert_eq!(local.transactions(), vec![valid]);
+	}
+}
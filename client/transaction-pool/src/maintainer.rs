@@ -34,6 +34,7 @@ use sp_core::{Blake2Hasher, H256};
 use sp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, Extrinsic, Header, NumberFor, ProvideRuntimeApi, SimpleArithmetic},
+	transaction_validity::TransactionSource,
 };
 use sp_blockchain::HeaderBackend;
 use sp_transaction_pool::TransactionPoolMaintainer;
@@ -41,19 +42,38 @@ use sp_transaction_pool::runtime_api::TaggedTransactionQueue;
 
 use sc_transaction_graph::{self, ChainApi};
 
+use crate::revalidation::{RevalidationQueue, DEFAULT_BATCH_SIZE};
+
 /// Basic transaction pool maintainer for full clients.
+///
+/// The ready transactions are revalidated in the background, so that the maintenance that
+/// happens at every new block only prunes the pool.
 pub struct FullBasicPoolMaintainer<Client, PoolApi: ChainApi> {
 	pool: Arc<sc_transaction_graph::Pool<PoolApi>>,
 	client: Arc<Client>,
+	revalidation_queue: RevalidationQueue<PoolApi>,
 }
 
-impl<Client, PoolApi: ChainApi> FullBasicPoolMaintainer<Client, PoolApi> {
+impl<Client, PoolApi: ChainApi + 'static> FullBasicPoolMaintainer<Client, PoolApi> {
 	/// Create new basic full pool maintainer.
+	///
+	/// The ready transactions are revalidated by batches of `DEFAULT_BATCH_SIZE`.
 	pub fn new(
 		pool: Arc<sc_transaction_graph::Pool<PoolApi>>,
 		client: Arc<Client>,
 	) -> Self {
-		FullBasicPoolMaintainer { pool, client }
+		Self::with_revalidation_batch_size(pool, client, DEFAULT_BATCH_SIZE)
+	}
+
+	/// Create new basic full pool maintainer, revalidating the ready transactions by batches of
+	/// given size.
+	pub fn with_revalidation_batch_size(
+		pool: Arc<sc_transaction_graph::Pool<PoolApi>>,
+		client: Arc<Client>,
+		batch_size: usize,
+	) -> Self {
+		let revalidation_queue = RevalidationQueue::new_background_thread(pool.clone(), batch_size);
+		FullBasicPoolMaintainer { pool, client, revalidation_queue }
 	}
 }
 
@@ -79,6 +99,11 @@ where
 
 		let id = *id;
 		trace!(target: "txpool", "[{:?}] Starting pool maintainance", id);
+		// Notify the watchers of the transactions of retracted blocks before these are re-submitted.
+		for hash in retracted {
+			self.pool.on_block_retracted(*hash);
+		}
+
 		// Put transactions from retracted blocks back into the pool.
 		let client_copy = self.client.clone();
 		let retracted_transactions = retracted.to_vec().into_iter()
@@ -87,7 +112,7 @@ where
 			// if signed information is not present, attempt to resubmit anyway.
 			.filter(|tx| tx.is_signed().unwrap_or(true));
 		let resubmit_future = self.pool
-			.submit_at(&id, retracted_transactions, true)
+			.submit_at(&id, TransactionSource::InBlock, retracted_transactions, true)
 			.then(move |resubmit_result| ready(match resubmit_result {
 				Ok(_) => trace!(target: "txpool",
 					"[{:?}] Re-submitting retracted done. {}", id, took()
@@ -126,19 +151,15 @@ where
 			},
 		};
 
-		let revalidate_future = self.pool
-			.revalidate_ready(&id, Some(16))
-			.then(move |result| ready(match result {
-				Ok(_) => debug!(target: "txpool",
-					"[{:?}] Revalidation done: {}", id, took()
-				),
-				Err(e) => warn!(target: "txpool",
-					"[{:?}] Encountered errors while revalidating transactions: {:?}", id, e
-				),
-			}));
+		// Revalidation is expensive, leave it to the revalidation queue.
+		let revalidate_future = self.revalidation_queue.revalidate_later(id);
 
 		Box::new(prune_future.then(|_| revalidate_future))
 	}
+
+	fn on_block_finalized(&self, hash: &Block::Hash) {
+		self.pool.on_block_finalized(*hash)
+	}
 }
 
 /// Basic transaction pool maintainer for light clients.
@@ -273,8 +294,12 @@ where
 	fn maintain(
 		&self,
 		id: &BlockId<Block>,
-		_retracted: &[Block::Hash],
+		retracted: &[Block::Hash],
 	) -> Box<dyn Future<Output=()> + Send + Unpin> {
+		for hash in retracted {
+			self.pool.on_block_retracted(*hash);
+		}
+
 		// Do nothing if transaction pool is empty.
 		if self.pool.status().is_empty() {
 			self.revalidation_status.lock().clear();
@@ -303,6 +328,10 @@ where
 
 		Box::new(maintain_future)
 	}
+
+	fn on_block_finalized(&self, hash: &Block::Hash) {
+		self.pool.on_block_finalized(*hash)
+	}
 }
 
 /// The status of transactions revalidation at light tx pool.
@@ -359,6 +388,8 @@ mod tests {
 	use sp_transaction_pool::PoolStatus;
 	use crate::api::{FullChainApi, LightChainApi};
 
+	const SOURCE: TransactionSource = TransactionSource::External;
+
 	#[test]
 	fn should_remove_transactions_from_the_full_pool() {
 		let (client, longest_chain) = TestClientBuilder::new().build_with_longest_chain();
@@ -374,7 +405,7 @@ mod tests {
 		let best = longest_chain.best_chain().unwrap();
 
 		// store the transaction in the pool
-		block_on(pool.submit_one(&BlockId::hash(best.hash()), transaction.clone())).unwrap();
+		block_on(pool.submit_one(&BlockId::hash(best.hash()), SOURCE, transaction.clone())).unwrap();
 
 		// import the block
 		let mut builder = client.new_block(Default::default()).unwrap();
@@ -425,7 +456,7 @@ mod tests {
 		let best = longest_chain.best_chain().unwrap();
 
 		// store the transaction in the pool
-		block_on(pool.submit_one(&BlockId::hash(best.hash()), transaction.clone())).unwrap();
+		block_on(pool.submit_one(&BlockId::hash(best.hash()), SOURCE, transaction.clone())).unwrap();
 
 		// fire notification - this should clean up the queue
 		assert_eq!(pool.status().ready, 1);
@@ -524,6 +555,7 @@ mod tests {
 			// store the transaction in the pool
 			block_on(pool.submit_one(
 				&BlockId::hash(best.hash()),
+				SOURCE,
 				Transfer {
 					amount: 5,
 					nonce: 0,
@@ -574,7 +606,7 @@ mod tests {
 		let best = longest_chain.best_chain().unwrap();
 
 		// store the transaction in the pool
-		block_on(pool.submit_one(&BlockId::hash(best.hash()), transaction.clone())).unwrap();
+		block_on(pool.submit_one(&BlockId::hash(best.hash()), SOURCE, transaction.clone())).unwrap();
 
 		// import the block
 		let mut builder = client.new_block(Default::default()).unwrap();
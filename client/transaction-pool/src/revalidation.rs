@@ -0,0 +1,241 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Revalidation of the ready transactions of the pool.
+//!
+//! Ready transactions are revalidated in batches, the ones that are in the pool for the longest
+//! time first, against the best block known when the batch starts. The revalidation either runs
+//! in the background, off the block import notification path, or inline for a single batch.
+
+use std::{pin::Pin, sync::Arc};
+use futures::{channel::mpsc, future::{self, Either}, prelude::*};
+use log::{debug, warn};
+
+use sc_transaction_graph::{ChainApi, Pool};
+use sp_runtime::generic::BlockId;
+
+/// Default number of transactions revalidated at once.
+pub const DEFAULT_BATCH_SIZE: usize = 16;
+
+/// Queue of the revalidations of the ready transactions, requested at every new block.
+pub struct RevalidationQueue<Api: ChainApi> {
+	pool: Arc<Pool<Api>>,
+	batch_size: usize,
+	background: Option<mpsc::UnboundedSender<BlockId<Api::Block>>>,
+}
+
+impl<Api: ChainApi + 'static> RevalidationQueue<Api> {
+	/// New queue revalidating a single batch of transactions in the future returned by
+	/// `revalidate_later`.
+	pub fn new(pool: Arc<Pool<Api>>, batch_size: usize) -> Self {
+		RevalidationQueue {
+			pool,
+			batch_size,
+			background: None,
+		}
+	}
+
+	/// New queue revalidating the transactions in the background.
+	///
+	/// Returns the worker future, which must be spawned for the revalidation to happen. It ends
+	/// when the queue is dropped.
+	pub fn new_background(
+		pool: Arc<Pool<Api>>,
+		batch_size: usize,
+	) -> (Self, Pin<Box<dyn Future<Output=()> + Send>>) {
+		let (to_worker, from_queue) = mpsc::unbounded();
+		let worker = run_worker(pool.clone(), batch_size, from_queue).boxed();
+		let queue = RevalidationQueue {
+			pool,
+			batch_size,
+			background: Some(to_worker),
+		};
+
+		(queue, worker)
+	}
+
+	/// New queue revalidating the transactions in the background, on a dedicated thread.
+	///
+	/// Falls back to revalidating a single batch inline if the thread can't be started.
+	pub fn new_background_thread(pool: Arc<Pool<Api>>, batch_size: usize) -> Self {
+		let (queue, worker) = Self::new_background(pool.clone(), batch_size);
+		let spawned = std::thread::Builder::new()
+			.name("txpool-revalidation".into())
+			.spawn(move || futures::executor::block_on(worker));
+
+		match spawned {
+			Ok(_) => queue,
+			Err(e) => {
+				warn!(target: "txpool", "Failed to start the transaction revalidation thread: {:?}", e);
+				Self::new(pool, batch_size)
+			},
+		}
+	}
+
+	/// Request the revalidation of the ready transactions against the given block.
+	///
+	/// In the background, the returned future resolves immediately and all the ready
+	/// transactions are eventually revalidated, in batches. Otherwise it revalidates a single
+	/// batch.
+	pub fn revalidate_later(&self, at: BlockId<Api::Block>) -> impl Future<Output=()> + Send {
+		if let Some(ref to_worker) = self.background {
+			if to_worker.unbounded_send(at).is_err() {
+				warn!(target: "txpool", "Transaction revalidation worker has stopped.");
+			}
+			return Either::Left(future::ready(()));
+		}
+
+		Either::Right(revalidate_batch(&self.pool, at, self.batch_size).map(|_| ()))
+	}
+}
+
+/// Revalidate the oldest ready transactions of the pool. Returns the number of transactions
+/// revalidated.
+fn revalidate_batch<Api: ChainApi + 'static>(
+	pool: &Pool<Api>,
+	at: BlockId<Api::Block>,
+	batch_size: usize,
+) -> impl Future<Output=usize> + Send {
+	pool.revalidate_oldest_ready(&at, batch_size).map(move |result| match result {
+		Ok(count) => {
+			debug!(target: "txpool", "[{:?}] Revalidated {} transactions", at, count);
+			count
+		},
+		Err(e) => {
+			warn!(target: "txpool",
+				"[{:?}] Encountered errors while revalidating transactions: {:?}", at, e
+			);
+			0
+		},
+	})
+}
+
+/// Revalidate all the ready transactions at every new block, in batches.
+///
+/// A round over the ready transactions restarts against the latest block whenever a new block
+/// arrives, so that no batch is validated against a stale block.
+async fn run_worker<Api: ChainApi + 'static>(
+	pool: Arc<Pool<Api>>,
+	batch_size: usize,
+	mut blocks: mpsc::UnboundedReceiver<BlockId<Api::Block>>,
+) {
+	let batch_size = batch_size.max(1);
+
+	while let Some(mut at) = blocks.next().await {
+		let mut left = pool.status().ready;
+		while left > 0 {
+			// skip to the latest block.
+			let mut restarted = false;
+			while let Ok(Some(latest)) = blocks.try_next() {
+				at = latest;
+				restarted = true;
+			}
+			if restarted {
+				left = pool.status().ready;
+			}
+
+			let revalidated = revalidate_batch(&pool, at, batch_size).await;
+			if revalidated == 0 {
+				break;
+			}
+			left = left.saturating_sub(revalidated);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use futures::executor::block_on;
+	use sp_runtime::traits::{Block as BlockT, Header as HeaderT};
+	use substrate_test_runtime_client::{
+		prelude::*,
+		runtime::{Block, Extrinsic, Transfer},
+		sp_consensus::BlockOrigin,
+	};
+	use sp_runtime::transaction_validity::TransactionSource;
+	use crate::api::FullChainApi;
+
+	const SOURCE: TransactionSource = TransactionSource::External;
+
+	fn transfer(from: AccountKeyring) -> Extrinsic {
+		Transfer {
+			amount: 5,
+			nonce: 0,
+			from: from.into(),
+			to: Default::default(),
+		}.into_signed_tx()
+	}
+
+	/// Import a block including the given transactions, without telling the pool about it.
+	fn import_block(client: &TestClient, transactions: &[Extrinsic]) -> BlockId<Block> {
+		let mut builder = client.new_block(Default::default()).unwrap();
+		for transaction in transactions {
+			builder.push(transaction.clone()).unwrap();
+		}
+		let block = builder.bake().unwrap();
+		let id = BlockId::hash(block.header().hash());
+		client.import(BlockOrigin::Own, block).unwrap();
+		id
+	}
+
+	#[test]
+	fn should_revalidate_the_oldest_transactions_first() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let pool = Arc::new(Pool::new(Default::default(), FullChainApi::new(client.clone())));
+		let best = BlockId::hash(client.info().chain.best_hash);
+		block_on(pool.submit_one(&best, SOURCE, transfer(AccountKeyring::Bob))).unwrap();
+		block_on(pool.submit_one(&best, SOURCE, transfer(AccountKeyring::Alice))).unwrap();
+		let queue = RevalidationQueue::new(pool.clone(), 1);
+
+		// when
+		let id = import_block(&client, &[transfer(AccountKeyring::Alice)]);
+		block_on(queue.revalidate_later(id));
+
+		// then only the transaction of Bob has been revalidated
+		assert_eq!(pool.status().ready, 2);
+
+		// when
+		block_on(queue.revalidate_later(id));
+
+		// then
+		assert_eq!(pool.status().ready, 1);
+	}
+
+	#[test]
+	fn should_revalidate_all_transactions_in_the_background() {
+		let client = Arc::new(substrate_test_runtime_client::new());
+		let pool = Arc::new(Pool::new(Default::default(), FullChainApi::new(client.clone())));
+		let best = BlockId::hash(client.info().chain.best_hash);
+		for who in &[AccountKeyring::Alice, AccountKeyring::Bob, AccountKeyring::Charlie] {
+			block_on(pool.submit_one(&best, SOURCE, transfer(*who))).unwrap();
+		}
+		let (queue, worker) = RevalidationQueue::new_background(pool.clone(), 2);
+
+		// when
+		let id = import_block(
+			&client,
+			&[transfer(AccountKeyring::Alice), transfer(AccountKeyring::Charlie)],
+		);
+		block_on(queue.revalidate_later(id));
+		drop(queue);
+		block_on(worker);
+
+		// then
+		assert_eq!(pool.status().ready, 1);
+		assert_eq!(pool.status().future, 0);
+	}
+}
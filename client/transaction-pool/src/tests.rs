@@ -24,9 +24,11 @@ use substrate_test_runtime_client::{runtime::{AccountId, Block, Hash, Index, Ext
 use sp_runtime::{
 	generic::{self, BlockId},
 	traits::{Hash as HashT, BlakeTwo256},
-	transaction_validity::{TransactionValidity, ValidTransaction},
+	transaction_validity::{TransactionValidity, ValidTransaction, TransactionSource},
 };
 
+const SOURCE: TransactionSource = TransactionSource::External;
+
 struct TestApi {
 	pub modifier: Box<dyn Fn(&mut ValidTransaction) + Send + Sync>,
 }
@@ -48,6 +50,7 @@ impl sc_transaction_graph::ChainApi for TestApi {
 	fn validate_transaction(
 		&self,
 		at: &BlockId<Self::Block>,
+		_source: TransactionSource,
 		uxt: sc_transaction_graph::ExtrinsicFor<Self>,
 	) -> Self::ValidationFuture {
 		let expected = index(at);
@@ -121,7 +124,7 @@ fn pool() -> Pool<TestApi> {
 fn submission_should_work() {
 	let pool = pool();
 	assert_eq!(209, index(&BlockId::number(0)));
-	block_on(pool.submit_one(&BlockId::number(0), uxt(Alice, 209))).unwrap();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt(Alice, 209))).unwrap();
 
 	let pending: Vec<_> = pool.ready().map(|a| a.data.transfer().nonce).collect();
 	assert_eq!(pending, vec![209]);
@@ -130,8 +133,8 @@ fn submission_should_work() {
 #[test]
 fn multiple_submission_should_work() {
 	let pool = pool();
-	block_on(pool.submit_one(&BlockId::number(0), uxt(Alice, 209))).unwrap();
-	block_on(pool.submit_one(&BlockId::number(0), uxt(Alice, 210))).unwrap();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt(Alice, 209))).unwrap();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt(Alice, 210))).unwrap();
 
 	let pending: Vec<_> = pool.ready().map(|a| a.data.transfer().nonce).collect();
 	assert_eq!(pending, vec![209, 210]);
@@ -140,7 +143,7 @@ fn multiple_submission_should_work() {
 #[test]
 fn early_nonce_should_be_culled() {
 	let pool = pool();
-	block_on(pool.submit_one(&BlockId::number(0), uxt(Alice, 208))).unwrap();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt(Alice, 208))).unwrap();
 
 	let pending: Vec<_> = pool.ready().map(|a| a.data.transfer().nonce).collect();
 	assert_eq!(pending, Vec::<Index>::new());
@@ -150,11 +153,11 @@ fn early_nonce_should_be_culled() {
 fn late_nonce_should_be_queued() {
 	let pool = pool();
 
-	block_on(pool.submit_one(&BlockId::number(0), uxt(Alice, 210))).unwrap();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt(Alice, 210))).unwrap();
 	let pending: Vec<_> = pool.ready().map(|a| a.data.transfer().nonce).collect();
 	assert_eq!(pending, Vec::<Index>::new());
 
-	block_on(pool.submit_one(&BlockId::number(0), uxt(Alice, 209))).unwrap();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt(Alice, 209))).unwrap();
 	let pending: Vec<_> = pool.ready().map(|a| a.data.transfer().nonce).collect();
 	assert_eq!(pending, vec![209, 210]);
 }
@@ -162,8 +165,8 @@ fn late_nonce_should_be_queued() {
 #[test]
 fn prune_tags_should_work() {
 	let pool = pool();
-	block_on(pool.submit_one(&BlockId::number(0), uxt(Alice, 209))).unwrap();
-	block_on(pool.submit_one(&BlockId::number(0), uxt(Alice, 210))).unwrap();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt(Alice, 209))).unwrap();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt(Alice, 210))).unwrap();
 
 	let pending: Vec<_> = pool.ready().map(|a| a.data.transfer().nonce).collect();
 	assert_eq!(pending, vec![209, 210]);
@@ -178,16 +181,16 @@ fn prune_tags_should_work() {
 fn should_ban_invalid_transactions() {
 	let pool = pool();
 	let uxt = uxt(Alice, 209);
-	let hash = block_on(pool.submit_one(&BlockId::number(0), uxt.clone())).unwrap();
+	let hash = block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt.clone())).unwrap();
 	pool.remove_invalid(&[hash]);
-	block_on(pool.submit_one(&BlockId::number(0), uxt.clone())).unwrap_err();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt.clone())).unwrap_err();
 
 	// when
 	let pending: Vec<_> = pool.ready().map(|a| a.data.transfer().nonce).collect();
 	assert_eq!(pending, Vec::<Index>::new());
 
 	// then
-	block_on(pool.submit_one(&BlockId::number(0), uxt.clone())).unwrap_err();
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, uxt.clone())).unwrap_err();
 }
 
 #[test]
@@ -198,7 +201,7 @@ fn should_correctly_prune_transactions_providing_more_than_one_tag() {
 	});
 	let pool = Pool::new(Default::default(), api);
 	let xt = uxt(Alice, 209);
-	block_on(pool.submit_one(&BlockId::number(0), xt.clone())).expect("1. Imported");
+	block_on(pool.submit_one(&BlockId::number(0), SOURCE, xt.clone())).expect("1. Imported");
 	assert_eq!(pool.status().ready, 1);
 
 	// remove the transaction that just got imported.
@@ -209,7 +212,7 @@ fn should_correctly_prune_transactions_providing_more_than_one_tag() {
 
 	// so now let's insert another transaction that also provides the 155
 	let xt = uxt(Alice, 211);
-	block_on(pool.submit_one(&BlockId::number(2), xt.clone())).expect("2. Imported");
+	block_on(pool.submit_one(&BlockId::number(2), SOURCE, xt.clone())).expect("2. Imported");
 	assert_eq!(pool.status().ready, 1);
 	assert_eq!(pool.status().future, 1);
 	let pending: Vec<_> = pool.ready().map(|a| a.data.transfer().nonce).collect();
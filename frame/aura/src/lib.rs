@@ -28,6 +28,8 @@
 //! ### Public Functions
 //!
 //! - `slot_duration` - Determine the Aura slot-duration based on the Timestamp module configuration.
+//! - `schedule_change` - Schedule a change of the authority set, signaled to the nodes through a
+//! consensus log.
 //!
 //! ## Related Modules
 //!
@@ -50,17 +52,17 @@ use pallet_timestamp;
 use sp_std::{result, prelude::*};
 use codec::{Encode, Decode};
 use frame_support::{
-	decl_storage, decl_module, Parameter, traits::{Get, FindAuthor},
+	decl_storage, decl_module, decl_error, Parameter, traits::{Get, FindAuthor},
 	ConsensusEngineId,
 };
 use sp_runtime::{
-	RuntimeAppPublic,
+	RuntimeAppPublic, DispatchResult,
 	traits::{SaturatedConversion, Saturating, Zero, Member, IsMember}, generic::DigestItem,
 };
 use sp_timestamp::OnTimestampSet;
 use sp_inherents::{InherentIdentifier, InherentData, ProvideInherent, MakeFatalError};
 use sp_consensus_aura::{
-	AURA_ENGINE_ID, ConsensusLog, AuthorityIndex,
+	AURA_ENGINE_ID, ConsensusLog, AuthorityIndex, ScheduledChange,
 	inherents::{INHERENT_IDENTIFIER, AuraInherentData},
 };
 
@@ -79,6 +81,10 @@ decl_storage! {
 
 		/// The current authorities
 		pub Authorities get(fn authorities): Vec<T::AuthorityId>;
+
+		/// The authorities of a scheduled change, along with the block at the end of which they
+		/// are enacted.
+		PendingChange get(fn pending_change): Option<(T::BlockNumber, Vec<T::AuthorityId>)>;
 	}
 	add_extra_genesis {
 		config(authorities): Vec<T::AuthorityId>;
@@ -86,19 +92,62 @@ decl_storage! {
 	}
 }
 
+decl_error! {
+	pub enum Error for Module<T: Trait> {
+		/// Attempt to schedule a delayed change with one already pending.
+		ChangePending,
+	}
+}
+
 decl_module! {
-	pub struct Module<T: Trait> for enum Call where origin: T::Origin { }
+	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
+		fn on_finalize(block_number: T::BlockNumber) {
+			if let Some((enact_at, next_authorities)) = <PendingChange<T>>::get() {
+				if block_number >= enact_at {
+					<Authorities<T>>::put(next_authorities);
+					<PendingChange<T>>::kill();
+				}
+			}
+		}
+	}
 }
 
 impl<T: Trait> Module<T> {
-	fn change_authorities(new: Vec<T::AuthorityId>) {
-		<Authorities<T>>::put(&new);
+	/// Schedule a change of the authority set.
+	///
+	/// The change is signaled to the nodes by a `ScheduledChange` log deposited in the current
+	/// block, and enacted at the end of the block `in_blocks` after it: the new authorities
+	/// author its children. If `in_blocks` is zero, the authorities are changed right away and
+	/// any pending change is discarded, in line with the nodes tracking the most recent change
+	/// of each fork.
+	///
+	/// Returns an error if a delayed change is scheduled while another one is pending.
+	pub fn schedule_change(
+		next_authorities: Vec<T::AuthorityId>,
+		in_blocks: T::BlockNumber,
+	) -> DispatchResult {
+		if in_blocks.is_zero() {
+			<Authorities<T>>::put(&next_authorities);
+			<PendingChange<T>>::kill();
+		} else if <PendingChange<T>>::exists() {
+			Err(Error::<T>::ChangePending)?
+		} else {
+			let enact_at = <frame_system::Module<T>>::block_number() + in_blocks;
+			<PendingChange<T>>::put((enact_at, next_authorities.clone()));
+		}
 
 		let log: DigestItem<T::Hash> = DigestItem::Consensus(
 			AURA_ENGINE_ID,
-			ConsensusLog::AuthoritiesChange(new).encode()
+			ConsensusLog::ScheduledChange(ScheduledChange {
+				next_authorities,
+				delay: in_blocks.saturated_into(),
+			}).encode()
 		);
 		<frame_system::Module<T>>::deposit_log(log.into());
+
+		Ok(())
 	}
 
 	fn initialize_authorities(authorities: &[T::AuthorityId]) {
@@ -131,7 +180,8 @@ impl<T: Trait> pallet_session::OneSessionHandler<T::AccountId> for Module<T> {
 			let next_authorities = validators.map(|(_, k)| k).collect::<Vec<_>>();
 			let last_authorities = <Module<T>>::authorities();
 			if next_authorities != last_authorities {
-				Self::change_authorities(next_authorities);
+				// changes without delay can't fail.
+				let _ = Self::schedule_change(next_authorities, Zero::zero());
 			}
 		}
 	}
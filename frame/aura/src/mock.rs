@@ -82,3 +82,4 @@ pub fn new_test_ext(authorities: Vec<u64>) -> sp_io::TestExternalities {
 }
 
 pub type Aura = Module<Test>;
+pub type System = frame_system::Module<Test>;
@@ -18,7 +18,19 @@
 
 #![cfg(test)]
 
-use crate::mock::{Aura, new_test_ext};
+use codec::Encode;
+use frame_support::assert_noop;
+use sp_consensus_aura::{AURA_ENGINE_ID, ConsensusLog, ScheduledChange, ed25519::AuthorityId};
+use sp_runtime::{
+	generic::DigestItem,
+	testing::{Digest, UintAuthorityId},
+	traits::{Header as _, OnFinalize},
+};
+use crate::{Error, mock::{Aura, System, Test, new_test_ext}};
+
+fn authorities(ids: Vec<u64>) -> Vec<AuthorityId> {
+	ids.into_iter().map(|id| UintAuthorityId(id).to_public_key()).collect()
+}
 
 #[test]
 fn initial_values() {
@@ -27,3 +39,48 @@ fn initial_values() {
 		assert_eq!(Aura::authorities().len(), 4);
 	});
 }
+
+#[test]
+fn scheduled_changes_are_logged_and_enacted_after_delay() {
+	new_test_ext(vec![0, 1, 2, 3]).execute_with(|| {
+		let next_authorities = authorities(vec![4, 5]);
+		System::initialize(&1, &Default::default(), &Default::default(), &Default::default());
+		Aura::schedule_change(next_authorities.clone(), 2).unwrap();
+		assert_noop!(Aura::schedule_change(authorities(vec![6]), 1), Error::<Test>::ChangePending);
+
+		Aura::on_finalize(1);
+		let header = System::finalize();
+		assert_eq!(header.digest, Digest {
+			logs: vec![
+				DigestItem::Consensus(AURA_ENGINE_ID, ConsensusLog::ScheduledChange(ScheduledChange {
+					next_authorities: next_authorities.clone(),
+					delay: 2,
+				}).encode()),
+			],
+		});
+		assert_eq!(Aura::authorities().len(), 4);
+
+		System::initialize(&2, &header.hash(), &Default::default(), &Default::default());
+		Aura::on_finalize(2);
+		assert_eq!(Aura::authorities().len(), 4);
+
+		System::initialize(&3, &header.hash(), &Default::default(), &Default::default());
+		Aura::on_finalize(3);
+		assert_eq!(Aura::authorities(), next_authorities);
+		assert!(Aura::pending_change().is_none());
+	});
+}
+
+#[test]
+fn changes_without_delay_discard_the_pending_one() {
+	new_test_ext(vec![0, 1, 2, 3]).execute_with(|| {
+		System::initialize(&1, &Default::default(), &Default::default(), &Default::default());
+		Aura::schedule_change(authorities(vec![4, 5]), 2).unwrap();
+		Aura::schedule_change(authorities(vec![6]), 0).unwrap();
+		assert_eq!(Aura::authorities(), authorities(vec![6]));
+		assert!(Aura::pending_change().is_none());
+
+		Aura::on_finalize(3);
+		assert_eq!(Aura::authorities(), authorities(vec![6]));
+	});
+}
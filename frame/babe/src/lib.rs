@@ -15,7 +15,9 @@
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
 //! Consensus extension module for BABE consensus. Collects on-chain randomness
-//! from VRF outputs and manages epoch transitions.
+//! from VRF outputs and manages epoch transitions. It also handles the reports
+//! of authorities having authored two blocks at the same slot, and reports the
+//! offences they prove.
 
 #![cfg_attr(not(feature = "std"), no_std)]
 #![forbid(unused_must_use, unsafe_code, unused_variables, unused_must_use)]
@@ -23,14 +25,20 @@
 pub use pallet_timestamp;
 
 use sp_std::{result, prelude::*};
-use frame_support::{decl_storage, decl_module, traits::FindAuthor, traits::Get};
+use frame_support::{decl_storage, decl_module, decl_error, traits::FindAuthor, traits::Get};
+use frame_system::{ensure_none, offchain::SubmitUnsignedTransaction};
 use sp_timestamp::OnTimestampSet;
-use sp_runtime::{generic::DigestItem, ConsensusEngineId, Perbill};
-use sp_runtime::traits::{IsMember, SaturatedConversion, Saturating, RandomnessBeacon};
+use sp_runtime::{generic::DigestItem, ConsensusEngineId, DispatchResult, Perbill, RuntimeAppPublic};
+use sp_runtime::traits::{Convert, IsMember, SaturatedConversion, Saturating, RandomnessBeacon};
+use sp_runtime::transaction_validity::{
+	TransactionValidity, ValidTransaction, InvalidTransaction, TransactionPriority,
+	TransactionLongevity, TransactionSource,
+};
 use sp_staking::{
 	SessionIndex,
-	offence::{Offence, Kind},
+	offence::{Offence, Kind, ReportOffence},
 };
+use pallet_session::historical::IdentificationTuple;
 
 use codec::{Encode, Decode};
 use sp_inherents::{InherentIdentifier, InherentData, ProvideInherent, MakeFatalError};
@@ -38,7 +46,7 @@ use sp_consensus_babe::{
 	BABE_ENGINE_ID, ConsensusLog, BabeAuthorityWeight, NextEpochDescriptor, RawBabePreDigest,
 	SlotNumber, inherents::{INHERENT_IDENTIFIER, BabeInherentData}
 };
-pub use sp_consensus_babe::{AuthorityId, VRF_OUTPUT_LENGTH, PUBLIC_KEY_LENGTH};
+pub use sp_consensus_babe::{AuthorityId, EquivocationProof, VRF_OUTPUT_LENGTH, PUBLIC_KEY_LENGTH};
 
 #[cfg(all(feature = "std", test))]
 mod tests;
@@ -63,6 +71,70 @@ pub trait Trait: pallet_timestamp::Trait {
 	/// Typically, the `ExternalTrigger` type should be used. An internal trigger should only be used
 	/// when no other module is responsible for changing authority set.
 	type EpochChangeTrigger: EpochChangeTrigger;
+
+	/// Reports the offences proven by equivocations and submits the transactions reporting
+	/// them. Equivocations are ignored if set to `()`.
+	type HandleEquivocation: HandleEquivocation<Self>;
+}
+
+/// The number of blocks an equivocation report stays valid in the transaction pool.
+const EQUIVOCATION_REPORT_LONGEVITY: TransactionLongevity = 64;
+
+/// Handles the equivocations of the BABE authorities.
+pub trait HandleEquivocation<T: Trait> {
+	/// Report the offence of the given authority, proven to have authored two blocks at the
+	/// given slot.
+	fn report_offence(offender: &AuthorityId, slot: SlotNumber) -> DispatchResult;
+
+	/// Submit the given call as an unsigned transaction. Only works in an offchain context.
+	fn submit_unsigned_transaction(call: Call<T>) -> Result<(), ()>;
+}
+
+impl<T: Trait> HandleEquivocation<T> for () {
+	fn report_offence(_offender: &AuthorityId, _slot: SlotNumber) -> DispatchResult {
+		Ok(())
+	}
+
+	fn submit_unsigned_transaction(_call: Call<T>) -> Result<(), ()> {
+		Err(())
+	}
+}
+
+/// Handles the equivocations of the validators of the current session: the offences are
+/// reported with `R`, and the reports are submitted as unsigned transactions of the runtime
+/// call `C` with `S`.
+pub struct EquivocationHandler<R, S, C>(sp_std::marker::PhantomData<(R, S, C)>);
+
+impl<T, R, S, C> HandleEquivocation<T> for EquivocationHandler<R, S, C> where
+	T: Trait + pallet_session::historical::Trait,
+	R: ReportOffence<
+		T::AccountId,
+		IdentificationTuple<T>,
+		BabeEquivocationOffence<IdentificationTuple<T>>,
+	>,
+	S: SubmitUnsignedTransaction<T, C>,
+	C: From<Call<T>>,
+{
+	fn report_offence(offender: &AuthorityId, slot: SlotNumber) -> DispatchResult {
+		let validator = <pallet_session::Module<T>>::key_owner(AuthorityId::ID, offender.as_ref())
+			.ok_or(Error::<T>::UnknownOffender)?;
+		let full_identification = T::FullIdentificationOf::convert(validator.clone())
+			.ok_or(Error::<T>::UnknownOffender)?;
+
+		let offence = BabeEquivocationOffence {
+			slot,
+			session_index: <pallet_session::Module<T>>::current_index(),
+			validator_set_count: <pallet_session::Module<T>>::validators().len() as u32,
+			offender: (validator, full_identification),
+		};
+		R::report_offence(Vec::new(), offence);
+
+		Ok(())
+	}
+
+	fn submit_unsigned_transaction(call: Call<T>) -> Result<(), ()> {
+		S::submit_unsigned(call)
+	}
 }
 
 /// Trigger an epoch change, if any should take place.
@@ -157,9 +229,24 @@ decl_storage! {
 	}
 }
 
+decl_error! {
+	/// Error for the BABE module.
+	pub enum Error for Module<T: Trait> {
+		/// The equivocation report is about a slot outside of the current epoch.
+		StaleEquivocationReport,
+		/// The headers of the equivocation report aren't different, aren't both authored at
+		/// the reported slot or aren't both sealed by the offender.
+		InvalidEquivocationProof,
+		/// The offender of the equivocation report isn't an authority of the current epoch.
+		UnknownOffender,
+	}
+}
+
 decl_module! {
 	/// The BABE SRML module
 	pub struct Module<T: Trait> for enum Call where origin: T::Origin {
+		type Error = Error<T>;
+
 		/// The number of **slots** that an epoch takes. We couple sessions to
 		/// epochs, i.e. we start a new session once the new epoch begins.
 		const EpochDuration: u64 = T::EpochDuration::get();
@@ -187,6 +274,19 @@ decl_module! {
 				Self::deposit_vrf_output(&vrf_output);
 			}
 		}
+
+		/// Report an authority of the current epoch having authored two blocks at the same
+		/// slot, so that the offence is slashed. Submitted as an unsigned transaction by the
+		/// nodes that imported both blocks.
+		fn report_equivocation(origin, equivocation_proof: EquivocationProof<T::Header>) {
+			ensure_none(origin)?;
+			Self::check_equivocation_report(&equivocation_proof)?;
+
+			T::HandleEquivocation::report_offence(
+				&equivocation_proof.offender,
+				equivocation_proof.slot_number,
+			)?;
+		}
 	}
 }
 
@@ -239,20 +339,18 @@ impl<T: Trait> pallet_session::ShouldEndSession<T::BlockNumber> for Module<T> {
 	}
 }
 
-// TODO [slashing]: @marcio use this, remove the dead_code annotation.
 /// A BABE equivocation offence report.
 ///
 /// When a validator released two or more blocks at the same slot.
-#[allow(dead_code)]
-struct BabeEquivocationOffence<FullIdentification> {
+pub struct BabeEquivocationOffence<FullIdentification> {
 	/// A babe slot number in which this incident happened.
-	slot: u64,
+	pub slot: u64,
 	/// The session index in which the incident happened.
-	session_index: SessionIndex,
+	pub session_index: SessionIndex,
 	/// The size of the validator set at the time of the offence.
-	validator_set_count: u32,
+	pub validator_set_count: u32,
 	/// The authority that produced the equivocation.
-	offender: FullIdentification,
+	pub offender: FullIdentification,
 }
 
 impl<FullIdentification: Clone> Offence<FullIdentification> for BabeEquivocationOffence<FullIdentification> {
@@ -327,10 +425,12 @@ impl<T: Trait> Module<T> {
 			assert!(Self::initialized().is_some())
 		}
 
-		// Update epoch index
+		// Update epoch index. If no block has been authored for a whole epoch, the
+		// epochs without blocks are skipped, the same way the nodes derive them.
 		let epoch_index = EpochIndex::get()
 			.checked_add(1)
-			.expect("epoch indices will never reach 2^64 before the death of the universe; qed");
+			.expect("epoch indices will never reach 2^64 before the death of the universe; qed")
+			.max(Self::epoch_index_at(CurrentSlot::get()));
 
 		EpochIndex::put(epoch_index);
 		Authorities::put(authorities);
@@ -357,6 +457,44 @@ impl<T: Trait> Module<T> {
 		Self::deposit_consensus(ConsensusLog::NextEpochData(next))
 	}
 
+	/// Submit an unsigned transaction reporting the given equivocation. Meant to be called by
+	/// the `BabeApi::submit_report_equivocation_extrinsic` runtime api, in an offchain context.
+	pub fn submit_report_equivocation_extrinsic(
+		equivocation_proof: EquivocationProof<T::Header>,
+	) -> Option<()> {
+		T::HandleEquivocation::submit_unsigned_transaction(
+			Call::report_equivocation(equivocation_proof)
+		).ok()
+	}
+
+	/// Check that the given equivocation report is about a slot of the current epoch, that
+	/// the offender is one of its authorities and that both headers have been sealed by it.
+	fn check_equivocation_report(
+		equivocation_proof: &EquivocationProof<T::Header>,
+	) -> Result<(), Error<T>> {
+		let epoch_start = Self::current_epoch_start();
+		let slot_number = equivocation_proof.slot_number;
+		if slot_number < epoch_start || slot_number >= epoch_start + T::EpochDuration::get() {
+			return Err(Error::<T>::StaleEquivocationReport);
+		}
+
+		if !Self::is_member(&equivocation_proof.offender) {
+			return Err(Error::<T>::UnknownOffender);
+		}
+
+		if !sp_consensus_babe::check_equivocation_proof(equivocation_proof.clone()) {
+			return Err(Error::<T>::InvalidEquivocationProof);
+		}
+
+		Ok(())
+	}
+
+	// finds the index of the epoch the given slot falls in, given that the
+	// epochs are numbered from the first block of the chain.
+	fn epoch_index_at(slot: SlotNumber) -> u64 {
+		slot.saturating_sub(GenesisSlot::get()) / T::EpochDuration::get()
+	}
+
 	// finds the start slot of the current epoch. only guaranteed to
 	// give correct results after `do_initialize` of the first block
 	// in the chain (as its result is based off of `GenesisSlot`).
@@ -550,3 +688,38 @@ impl<T: Trait> ProvideInherent for Module<T> {
 		}
 	}
 }
+
+#[allow(deprecated)]
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+		if let Call::report_equivocation(equivocation_proof) = call {
+			// equivocation reports are only submitted by the offchain context of the node which
+			// witnessed the equivocation, and included in the blocks it authors.
+			match source {
+				TransactionSource::Local | TransactionSource::InBlock => {},
+				TransactionSource::External => return InvalidTransaction::Call.into(),
+			}
+
+			match Self::check_equivocation_report(equivocation_proof) {
+				Ok(()) => {},
+				Err(Error::<T>::StaleEquivocationReport) => return InvalidTransaction::Stale.into(),
+				Err(_) => return InvalidTransaction::BadProof.into(),
+			}
+
+			Ok(ValidTransaction {
+				priority: TransactionPriority::max_value(),
+				requires: vec![],
+				provides: vec![(
+					equivocation_proof.slot_number,
+					&equivocation_proof.offender,
+				).encode()],
+				longevity: EQUIVOCATION_REPORT_LONGEVITY,
+				propagate: false,
+			})
+		} else {
+			InvalidTransaction::Call.into()
+		}
+	}
+}
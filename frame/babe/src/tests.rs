@@ -17,8 +17,9 @@
 //! Consensus extension module tests for BABE consensus.
 
 use super::*;
-use mock::{new_test_ext, Babe, Test};
-use sp_runtime::{traits::OnFinalize, testing::{Digest, DigestItem}};
+use mock::{new_test_ext, Babe, EpochDuration, Origin, Test};
+use sp_runtime::{traits::{Header as _, OnFinalize}, testing::{Digest, DigestItem, Header, UintAuthorityId}};
+use frame_support::assert_noop;
 use pallet_session::ShouldEndSession;
 
 const EMPTY_RANDOMNESS: [u8; 32] = [
@@ -124,3 +125,55 @@ fn authority_index() {
 			"Trivially invalid authorities are ignored")
 	})
 }
+
+#[test]
+fn equivocation_reports_are_checked() {
+	new_test_ext(vec![0, 1, 2, 3]).execute_with(|| {
+		let header = |slot_number, parent_hash| {
+			let mut digest = make_pre_digest(0, slot_number, [1; 32], [0xff; 64]);
+			digest.logs.push(DigestItem::Seal(BABE_ENGINE_ID, vec![0; 64]));
+			Header::new(1, Default::default(), Default::default(), parent_hash, digest)
+		};
+		let proof = |offender, slot_number, first_parent: u8, second_parent: u8| EquivocationProof {
+			offender,
+			slot_number,
+			first_header: header(slot_number, [first_parent; 32].into()),
+			second_header: header(slot_number, [second_parent; 32].into()),
+		};
+		let report = |proof| Babe::report_equivocation(Origin::system(frame_system::RawOrigin::None), proof);
+		let authority = Babe::authorities()[0].0.clone();
+
+		assert_noop!(report(proof(authority.clone(), 10, 1, 2)), Error::<Test>::StaleEquivocationReport);
+		assert_noop!(
+			report(proof(UintAuthorityId(9).to_public_key(), 1, 1, 2)),
+			Error::<Test>::UnknownOffender,
+		);
+		assert_noop!(report(proof(authority.clone(), 1, 1, 1)), Error::<Test>::InvalidEquivocationProof);
+		// the headers aren't sealed by the offender.
+		assert_noop!(report(proof(authority, 1, 1, 2)), Error::<Test>::InvalidEquivocationProof);
+	})
+}
+
+#[test]
+fn epochs_without_blocks_are_skipped() {
+	new_test_ext(vec![0, 1, 2, 3]).execute_with(|| {
+		let genesis_slot = 100;
+		let pre_digest = make_pre_digest(0, genesis_slot, [1; 32], [0xff; 64]);
+		System::initialize(&1, &Default::default(), &Default::default(), &pre_digest);
+		assert!(!Babe::should_end_session(1));
+		Babe::on_finalize(1);
+		let header = System::finalize();
+
+		// the next block is authored ten epochs later.
+		let slot = genesis_slot + 10 * EpochDuration::get() + 1;
+		let pre_digest = make_pre_digest(0, slot, [1; 32], [0xff; 64]);
+		System::initialize(&2, &header.hash(), &Default::default(), &pre_digest);
+		assert!(Babe::should_end_session(2));
+
+		let authorities = Babe::authorities();
+		Babe::enact_epoch_change(authorities.clone(), authorities);
+		assert_eq!(Babe::epoch_index(), 10);
+		assert_eq!(Babe::current_epoch_start(), genesis_slot + 10 * EpochDuration::get());
+		assert!(!Babe::should_epoch_change(3));
+	})
+}
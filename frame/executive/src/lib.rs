@@ -59,14 +59,16 @@
 //! # pub type Balances = u64;
 //! # pub type AllModules = u64;
 //! # pub enum Runtime {};
-//! # use sp_runtime::transaction_validity::{TransactionValidity, UnknownTransaction};
+//! # use sp_runtime::transaction_validity::{
+//! # 	TransactionValidity, UnknownTransaction, TransactionSource,
+//! # };
 //!	# #[allow(deprecated)]
 //! # use sp_runtime::traits::ValidateUnsigned;
 //!	# #[allow(deprecated)]
 //! # impl ValidateUnsigned for Runtime {
 //! # 	type Call = ();
 //! #
-//! # 	fn validate_unsigned(_call: &Self::Call) -> TransactionValidity {
+//! # 	fn validate_unsigned(_source: TransactionSource, _call: &Self::Call) -> TransactionValidity {
 //! # 		UnknownTransaction::NoUnsignedValidator.into()
 //! # 	}
 //! # }
@@ -84,7 +86,7 @@ use sp_runtime::{
 		self, Header, Zero, One, Checkable, Applyable, CheckEqual, OnFinalize, OnInitialize,
 		NumberFor, Block as BlockT, OffchainWorker, Dispatchable,
 	},
-	transaction_validity::TransactionValidity,
+	transaction_validity::{TransactionValidity, TransactionSource},
 };
 #[allow(deprecated)]
 use sp_runtime::traits::ValidateUnsigned;
@@ -199,9 +201,13 @@ where
 		// any initial checks
 		Self::initial_checks(&block);
 
-		// execute extrinsics
+		// execute extrinsics, verifying their signatures in one batch
+		let signature_batching = sp_runtime::SignatureBatching::start();
 		let (header, extrinsics) = block.deconstruct();
 		Self::execute_extrinsics_with_book_keeping(extrinsics, *header.number());
+		if !signature_batching.verify() {
+			panic!("Signature verification failed.");
+		}
 
 		// any final checks
 		Self::final_checks(&header);
@@ -297,21 +303,29 @@ where
 		assert!(header.state_root() == storage_root, "Storage root must match that calculated.");
 	}
 
-	/// Check a given signed transaction for validity. This doesn't execute any
-	/// side-effects; it merely checks whether the transaction would panic if it were included or not.
+	/// Check a given signed transaction, coming from the given source, for validity. This doesn't
+	/// execute any side-effects; it merely checks whether the transaction would panic if it were
+	/// included or not.
 	///
 	/// Changes made to storage should be discarded.
-	pub fn validate_transaction(uxt: Block::Extrinsic) -> TransactionValidity {
+	pub fn validate_transaction(
+		source: TransactionSource,
+		uxt: Block::Extrinsic,
+	) -> TransactionValidity {
 		let encoded_len = uxt.using_encoded(|d| d.len());
 		let xt = uxt.check(&Default::default())?;
 
 		let dispatch_info = xt.get_dispatch_info();
-		xt.validate::<UnsignedValidator>(dispatch_info, encoded_len)
+		xt.validate::<UnsignedValidator>(source, dispatch_info, encoded_len)
 	}
 
 	/// Start an offchain worker and generate extrinsics.
-	pub fn offchain_worker(n: System::BlockNumber) {
-		<AllModules as OffchainWorker<System::BlockNumber>>::offchain_worker(n)
+	///
+	/// The hash of the block is available to the modules through `frame_system::Module::block_hash`.
+	pub fn offchain_worker(header: &System::Header) {
+		// The hash of a block isn't known while it is built, so it is only inserted here.
+		<frame_system::BlockHash<System>>::insert(header.number(), header.hash());
+		<AllModules as OffchainWorker<System::BlockNumber>>::offchain_worker(*header.number())
 	}
 }
 
@@ -456,7 +470,7 @@ mod tests {
 			Ok(())
 		}
 
-		fn validate_unsigned(call: &Self::Call) -> TransactionValidity {
+		fn validate_unsigned(_source: TransactionSource, call: &Self::Call) -> TransactionValidity {
 			match call {
 				Call::Balances(BalancesCall::set_balance(_, _, _)) => Ok(Default::default()),
 				_ => UnknownTransaction::NoUnsignedValidator.into(),
@@ -660,7 +674,10 @@ mod tests {
 		let mut t = new_test_ext(1);
 
 		t.execute_with(|| {
-			assert_eq!(Executive::validate_transaction(xt.clone()), Ok(Default::default()));
+			assert_eq!(
+				Executive::validate_transaction(TransactionSource::External, xt.clone()),
+				Ok(Default::default()),
+			);
 			assert_eq!(Executive::apply_extrinsic(xt), Ok(Err(DispatchError::BadOrigin)));
 		});
 	}
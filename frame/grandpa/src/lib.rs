@@ -19,8 +19,9 @@
 //! This manages the GRANDPA authority set ready for the native code.
 //! These authorities are only for GRANDPA finality, not for consensus overall.
 //!
-//! In the future, it will also handle misbehavior reports, and on-chain
-//! finality notifications.
+//! It also handles the reports of equivocations of the authorities, submitted by
+//! the voters as unsigned transactions, and reports the offences they prove. In
+//! the future, it will also handle on-chain finality notifications.
 //!
 //! For full integration with GRANDPA, the `GrandpaApi` should be implemented.
 //! The necessary items are re-exported via the `fg_primitives` crate.
@@ -34,17 +35,26 @@ use sp_std::prelude::*;
 use codec::{self as codec, Encode, Decode};
 use frame_support::{decl_event, decl_storage, decl_module, decl_error, storage};
 use sp_runtime::{
-	DispatchResult, generic::{DigestItem, OpaqueDigestItemId}, traits::Zero, Perbill,
+	DispatchResult, generic::{DigestItem, OpaqueDigestItemId}, traits::{Convert, Zero}, Perbill,
+	RuntimeAppPublic,
+	transaction_validity::{
+		TransactionValidity, ValidTransaction, InvalidTransaction, TransactionPriority,
+		TransactionLongevity, TransactionSource,
+	},
 };
 use sp_staking::{
 	SessionIndex,
-	offence::{Offence, Kind},
+	offence::{Offence, Kind, ReportOffence},
 };
 use fg_primitives::{
 	GRANDPA_AUTHORITIES_KEY, GRANDPA_ENGINE_ID, ScheduledChange, ConsensusLog, SetId, RoundNumber,
 };
-pub use fg_primitives::{AuthorityId, AuthorityList, AuthorityWeight, VersionedAuthorityList};
-use frame_system::{self as system, ensure_signed, DigestOf};
+pub use fg_primitives::{
+	AuthorityId, AuthorityList, AuthorityWeight, VersionedAuthorityList, EquivocationProof,
+};
+use frame_system::{self as system, ensure_signed, ensure_none, DigestOf};
+use frame_system::offchain::SubmitUnsignedTransaction;
+use pallet_session::historical::IdentificationTuple;
 
 mod mock;
 mod tests;
@@ -52,6 +62,70 @@ mod tests;
 pub trait Trait: frame_system::Trait {
 	/// The event type of this module.
 	type Event: From<Event> + Into<<Self as frame_system::Trait>::Event>;
+
+	/// Reports the offences proven by equivocations and submits the transactions reporting
+	/// them. Equivocations are ignored if set to `()`.
+	type HandleEquivocation: HandleEquivocation<Self>;
+}
+
+/// The number of blocks an equivocation report stays valid in the transaction pool.
+const EQUIVOCATION_REPORT_LONGEVITY: TransactionLongevity = 64;
+
+/// Handles the equivocations of the GRANDPA authorities.
+pub trait HandleEquivocation<T: Trait> {
+	/// Report the offence of the given authority, proven to have equivocated at the given time
+	/// slot.
+	fn report_offence(offender: &AuthorityId, time_slot: GrandpaTimeSlot) -> DispatchResult;
+
+	/// Submit the given call as an unsigned transaction. Only works in an offchain context.
+	fn submit_unsigned_transaction(call: Call<T>) -> Result<(), ()>;
+}
+
+impl<T: Trait> HandleEquivocation<T> for () {
+	fn report_offence(_offender: &AuthorityId, _time_slot: GrandpaTimeSlot) -> DispatchResult {
+		Ok(())
+	}
+
+	fn submit_unsigned_transaction(_call: Call<T>) -> Result<(), ()> {
+		Err(())
+	}
+}
+
+/// Handles the equivocations of the validators of the current session: the offences are
+/// reported with `R`, and the reports are submitted as unsigned transactions of the runtime
+/// call `C` with `S`.
+pub struct EquivocationHandler<R, S, C>(sp_std::marker::PhantomData<(R, S, C)>);
+
+impl<T, R, S, C> HandleEquivocation<T> for EquivocationHandler<R, S, C> where
+	T: Trait + pallet_session::historical::Trait,
+	R: ReportOffence<
+		T::AccountId,
+		IdentificationTuple<T>,
+		GrandpaEquivocationOffence<IdentificationTuple<T>>,
+	>,
+	S: SubmitUnsignedTransaction<T, C>,
+	C: From<Call<T>>,
+{
+	fn report_offence(offender: &AuthorityId, time_slot: GrandpaTimeSlot) -> DispatchResult {
+		let validator = <pallet_session::Module<T>>::key_owner(AuthorityId::ID, offender.as_ref())
+			.ok_or(Error::<T>::UnknownOffender)?;
+		let full_identification = T::FullIdentificationOf::convert(validator.clone())
+			.ok_or(Error::<T>::UnknownOffender)?;
+
+		let offence = GrandpaEquivocationOffence {
+			time_slot,
+			session_index: <pallet_session::Module<T>>::current_index(),
+			validator_set_count: <pallet_session::Module<T>>::validators().len() as u32,
+			offender: (validator, full_identification),
+		};
+		R::report_offence(Vec::new(), offence);
+
+		Ok(())
+	}
+
+	fn submit_unsigned_transaction(call: Call<T>) -> Result<(), ()> {
+		S::submit_unsigned(call)
+	}
 }
 
 /// A stored pending change, old format.
@@ -146,6 +220,13 @@ decl_error! {
 		ChangePending,
 		/// Cannot signal forced change so soon after last.
 		TooSoon,
+		/// The equivocation report is about another authority set than the current one.
+		StaleEquivocationReport,
+		/// The votes of the equivocation report aren't different or aren't both signed by the
+		/// offender.
+		InvalidEquivocationProof,
+		/// The offender of the equivocation report isn't a known validator.
+		UnknownOffender,
 	}
 }
 
@@ -195,6 +276,19 @@ decl_module! {
 			// FIXME: https://github.com/paritytech/substrate/issues/1112
 		}
 
+		/// Report an equivocation of an authority of the current set, so that the offence is
+		/// slashed. Submitted as an unsigned transaction by the voters that detected it.
+		fn report_equivocation(origin, equivocation_proof: EquivocationProof<T::Hash, T::BlockNumber>) {
+			ensure_none(origin)?;
+			Self::check_equivocation_report(&equivocation_proof)?;
+
+			let time_slot = GrandpaTimeSlot {
+				set_id: equivocation_proof.set_id,
+				round: equivocation_proof.round_number(),
+			};
+			T::HandleEquivocation::report_offence(equivocation_proof.offender(), time_slot)?;
+		}
+
 		fn on_initialize() {
 			#[cfg(feature = "migrate-authorities")]
 			Self::migrate_authorities();
@@ -355,6 +449,33 @@ impl<T: Trait> Module<T> {
 		}
 	}
 
+	/// Submit an unsigned transaction reporting the given equivocation. Meant to be called by
+	/// the `GrandpaApi::submit_report_equivocation_extrinsic` runtime api, in an offchain
+	/// context.
+	pub fn submit_report_equivocation_extrinsic(
+		equivocation_proof: EquivocationProof<T::Hash, T::BlockNumber>,
+	) -> Option<()> {
+		T::HandleEquivocation::submit_unsigned_transaction(
+			Call::report_equivocation(equivocation_proof)
+		).ok()
+	}
+
+	/// Check that the given equivocation report is about the current set and that its votes
+	/// have been signed by the offender.
+	fn check_equivocation_report(
+		equivocation_proof: &EquivocationProof<T::Hash, T::BlockNumber>,
+	) -> Result<(), Error<T>> {
+		if equivocation_proof.set_id != Self::current_set_id() {
+			return Err(Error::<T>::StaleEquivocationReport);
+		}
+
+		if !fg_primitives::check_equivocation_proof(equivocation_proof.clone()) {
+			return Err(Error::<T>::InvalidEquivocationProof);
+		}
+
+		Ok(())
+	}
+
 	/// Deposit one of this module's logs.
 	fn deposit_log(log: ConsensusLog<T::BlockNumber>) {
 		let log: DigestItem<T::Hash> = DigestItem::Consensus(GRANDPA_ENGINE_ID, log.encode());
@@ -473,24 +594,24 @@ impl<T: Trait> pallet_finality_tracker::OnFinalizationStalled<T::BlockNumber> fo
 
 /// A round number and set id which point on the time of an offence.
 #[derive(Copy, Clone, PartialOrd, Ord, Eq, PartialEq, Encode, Decode)]
-struct GrandpaTimeSlot {
+pub struct GrandpaTimeSlot {
 	// The order of these matters for `derive(Ord)`.
-	set_id: SetId,
-	round: RoundNumber,
+	/// Grandpa Set ID.
+	pub set_id: SetId,
+	/// Round number.
+	pub round: RoundNumber,
 }
 
-// TODO [slashing]: Integrate this.
 /// A grandpa equivocation offence report.
-#[allow(dead_code)]
-struct GrandpaEquivocationOffence<FullIdentification> {
+pub struct GrandpaEquivocationOffence<FullIdentification> {
 	/// Time slot at which this incident happened.
-	time_slot: GrandpaTimeSlot,
+	pub time_slot: GrandpaTimeSlot,
 	/// The session index in which the incident happened.
-	session_index: SessionIndex,
+	pub session_index: SessionIndex,
 	/// The size of the validator set at the time of the offence.
-	validator_set_count: u32,
+	pub validator_set_count: u32,
 	/// The authority which produced this equivocation.
-	offender: FullIdentification,
+	pub offender: FullIdentification,
 }
 
 impl<FullIdentification: Clone> Offence<FullIdentification> for GrandpaEquivocationOffence<FullIdentification> {
@@ -523,3 +644,39 @@ impl<FullIdentification: Clone> Offence<FullIdentification> for GrandpaEquivocat
 		x.square()
 	}
 }
+
+#[allow(deprecated)]
+impl<T: Trait> frame_support::unsigned::ValidateUnsigned for Module<T> {
+	type Call = Call<T>;
+
+	fn validate_unsigned(source: TransactionSource, call: &Self::Call) -> TransactionValidity {
+		if let Call::report_equivocation(equivocation_proof) = call {
+			// equivocation reports are only submitted by the offchain context of the node which
+			// witnessed the equivocation, and included in the blocks it authors.
+			match source {
+				TransactionSource::Local | TransactionSource::InBlock => {},
+				TransactionSource::External => return InvalidTransaction::Call.into(),
+			}
+
+			match Self::check_equivocation_report(equivocation_proof) {
+				Ok(()) => {},
+				Err(Error::<T>::StaleEquivocationReport) => return InvalidTransaction::Stale.into(),
+				Err(_) => return InvalidTransaction::BadProof.into(),
+			}
+
+			Ok(ValidTransaction {
+				priority: TransactionPriority::max_value(),
+				requires: vec![],
+				provides: vec![(
+					equivocation_proof.set_id,
+					equivocation_proof.round_number(),
+					equivocation_proof.offender(),
+				).encode()],
+				longevity: EQUIVOCATION_REPORT_LONGEVITY,
+				propagate: false,
+			})
+		} else {
+			InvalidTransaction::Call.into()
+		}
+	}
+}
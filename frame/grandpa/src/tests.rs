@@ -19,10 +19,12 @@
 #![cfg(test)]
 
 use sp_runtime::{testing::Digest, traits::{Header, OnFinalize}};
+use sp_core::H256;
 use crate::mock::*;
 use frame_system::{EventRecord, Phase};
+use frame_support::assert_noop;
 use codec::{Decode, Encode};
-use fg_primitives::ScheduledChange;
+use fg_primitives::{ScheduledChange, Equivocation, Prevote, VoteEquivocation};
 use super::*;
 
 #[test]
@@ -281,6 +283,59 @@ fn schedule_resume_only_when_paused() {
 	});
 }
 
+#[test]
+fn equivocation_reports_are_checked() {
+	new_test_ext(vec![(1, 1), (2, 1), (3, 1)]).execute_with(|| {
+		let proof = |set_id, first: u8, second: u8| {
+			let vote = |target| (
+				Prevote { target_hash: H256::repeat_byte(target), target_number: 1 },
+				Default::default(),
+			);
+			EquivocationProof::new(set_id, Equivocation::Prevote(VoteEquivocation {
+				round_number: 1,
+				identity: to_authorities(vec![(1, 1)])[0].0.clone(),
+				first: vote(first),
+				second: vote(second),
+			}))
+		};
+		let report = |proof| Grandpa::report_equivocation(Origin::system(frame_system::RawOrigin::None), proof);
+
+		assert_noop!(report(proof(1, 1, 2)), Error::<Test>::StaleEquivocationReport);
+		assert_noop!(report(proof(0, 1, 1)), Error::<Test>::InvalidEquivocationProof);
+		// the votes aren't signed.
+		assert_noop!(report(proof(0, 1, 2)), Error::<Test>::InvalidEquivocationProof);
+	});
+}
+
+#[test]
+fn equivocation_reports_are_only_accepted_from_the_node() {
+	#[allow(deprecated)]
+	use frame_support::unsigned::ValidateUnsigned;
+
+	new_test_ext(vec![(1, 1), (2, 1), (3, 1)]).execute_with(|| {
+		let vote = |target| (
+			Prevote { target_hash: H256::repeat_byte(target), target_number: 1 },
+			Default::default(),
+		);
+		let call = Call::<Test>::report_equivocation(EquivocationProof::new(1, Equivocation::Prevote(
+			VoteEquivocation {
+				round_number: 1,
+				identity: to_authorities(vec![(1, 1)])[0].0.clone(),
+				first: vote(1),
+				second: vote(2),
+			}
+		)));
+
+		#[allow(deprecated)] // Allow ValidateUnsigned
+		let validate = |source| Grandpa::validate_unsigned(source, &call);
+
+		assert_eq!(validate(TransactionSource::External), InvalidTransaction::Call.into());
+		// the report of the node itself is checked.
+		assert_eq!(validate(TransactionSource::Local), InvalidTransaction::Stale.into());
+		assert_eq!(validate(TransactionSource::InBlock), InvalidTransaction::Stale.into());
+	});
+}
+
 #[test]
 fn time_slot_have_sane_ord() {
 	// Ensure that `Ord` implementation is sane.
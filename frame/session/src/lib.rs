@@ -691,7 +691,8 @@ impl<T: Trait> Module<T> {
 		<NextKeys<T>>::insert(DEDUP_KEY_PREFIX, v, keys);
 	}
 
-	fn key_owner(id: KeyTypeId, key_data: &[u8]) -> Option<T::ValidatorId> {
+	/// The owner of the given session key, if it is registered.
+	pub fn key_owner(id: KeyTypeId, key_data: &[u8]) -> Option<T::ValidatorId> {
 		<KeyOwner<T>>::get(DEDUP_KEY_PREFIX, (id, key_data))
 	}
 
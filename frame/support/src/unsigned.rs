@@ -19,7 +19,7 @@
 pub use crate::sp_runtime::traits::ValidateUnsigned;
 #[doc(hidden)]
 pub use crate::sp_runtime::transaction_validity::{
-	TransactionValidity, UnknownTransaction, TransactionValidityError,
+	TransactionValidity, UnknownTransaction, TransactionValidityError, TransactionSource,
 };
 
 
@@ -35,7 +35,10 @@ pub use crate::sp_runtime::transaction_validity::{
 /// # 	impl frame_support::unsigned::ValidateUnsigned for Module {
 /// # 		type Call = Call;
 /// #
-/// # 		fn validate_unsigned(call: &Self::Call) -> frame_support::unsigned::TransactionValidity {
+/// # 		fn validate_unsigned(
+/// # 			_source: frame_support::unsigned::TransactionSource,
+/// # 			_call: &Self::Call,
+/// # 		) -> frame_support::unsigned::TransactionValidity {
 /// # 			unimplemented!();
 /// # 		}
 /// # 	}
@@ -80,10 +83,13 @@ macro_rules! impl_outer_validate_unsigned {
 				}
 			}
 
-			fn validate_unsigned(call: &Self::Call) -> $crate::unsigned::TransactionValidity {
+			fn validate_unsigned(
+				source: $crate::unsigned::TransactionSource,
+				call: &Self::Call,
+			) -> $crate::unsigned::TransactionValidity {
 				#[allow(unreachable_patterns)]
 				match call {
-					$( Call::$module(inner_call) => $module::validate_unsigned(inner_call), )*
+					$( Call::$module(inner_call) => $module::validate_unsigned(source, inner_call), )*
 					_ => $crate::unsigned::UnknownTransaction::NoUnsignedValidator.into(),
 				}
 			}
@@ -113,7 +119,10 @@ mod test_partial_and_full_call {
 		impl super::super::ValidateUnsigned for Module {
 			type Call = Call;
 
-			fn validate_unsigned(_call: &Self::Call) -> super::super::TransactionValidity {
+			fn validate_unsigned(
+				_source: super::super::TransactionSource,
+				_call: &Self::Call,
+			) -> super::super::TransactionValidity {
 				unimplemented!();
 			}
 		}
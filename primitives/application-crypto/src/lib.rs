@@ -25,7 +25,7 @@ pub use sp_core::{self, crypto::{CryptoType, Public, Derive, IsWrappedBy, Wraps}
 #[doc(hidden)]
 #[cfg(feature = "full_crypto")]
 pub use sp_core::crypto::{SecretStringError, DeriveJunction, Ss58Codec, Pair};
-pub use sp_core::{crypto::{KeyTypeId, key_types}};
+pub use sp_core::{crypto::{KeyTypeId, CryptoTypeId, key_types}};
 
 #[doc(hidden)]
 pub use codec;
@@ -35,6 +35,10 @@ pub use serde;
 #[doc(hidden)]
 pub use sp_std::{ops::Deref, vec::Vec};
 
+/// The BLS12-381 key pairs need `std`, so the application keys aren't available with
+/// `full_crypto` alone.
+#[cfg(any(feature = "std", not(feature = "full_crypto")))]
+pub mod bls381;
 pub mod ed25519;
 pub mod sr25519;
 mod traits;
@@ -0,0 +1,39 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Integration tests for bls381
+
+use sp_runtime::{generic::BlockId, traits::ProvideRuntimeApi};
+use sp_core::{testing::{KeyStore, BLS381}, crypto::Pair};
+use substrate_test_runtime_client::{
+	TestClientBuilder, DefaultTestClientBuilderExt, TestClientBuilderExt,
+	runtime::TestAPI,
+};
+use sp_application_crypto::bls381::{AppPair, AppPublic};
+
+#[test]
+fn bls381_works_in_runtime() {
+	let keystore = KeyStore::new();
+	let test_client = TestClientBuilder::new().set_keystore(keystore.clone()).build();
+	let (signature, public) = test_client.runtime_api()
+		.test_bls381_crypto(&BlockId::Number(0))
+		.expect("Tests `bls381` crypto.");
+
+	let key_pair = keystore.read().bls381_key_pair(BLS381, &public.as_ref())
+		.expect("There should be at a `bls381` key in the keystore for the given public key.");
+
+	assert!(AppPair::verify(&signature, "bls381", &AppPublic::from(key_pair.public())));
+}
@@ -16,6 +16,8 @@
 
 //! Integration tests for application crypto
 
+#[cfg(test)]
+mod bls381;
 #[cfg(test)]
 mod ed25519;
 #[cfg(test)]
@@ -21,10 +21,21 @@
 use sp_std::vec::Vec;
 
 mod app {
-	use sp_application_crypto::{app_crypto, key_types::AUTHORITY_DISCOVERY, sr25519};
+	use sp_application_crypto::{app_crypto, key_types::AUTHORITY_DISCOVERY, sr25519, CryptoTypeId};
 	app_crypto!(sr25519, AUTHORITY_DISCOVERY);
+
+	/// Identifier of the crypto backing the types above.
+	///
+	/// Needs to be kept in sync with the crypto module passed to `app_crypto!`.
+	pub const CRYPTO_ID: CryptoTypeId = sr25519::CRYPTO_ID;
 }
 
+/// Identifier of the crypto used by authority discovery keys.
+///
+/// Allows the client to sign and verify authority discovery records without depending on a
+/// specific crypto.
+pub use app::CRYPTO_ID;
+
 /// An authority discovery authority keypair.
 #[cfg(feature = "std")]
 pub type AuthorityPair = app::Pair;
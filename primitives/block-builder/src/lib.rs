@@ -43,7 +43,7 @@ pub mod compatability_v3 {
 
 sp_api::decl_runtime_apis! {
 	/// The `BlockBuilder` api trait that provides the required functionality for building a block.
-	#[api_version(4)]
+	#[api_version(5)]
 	pub trait BlockBuilder {
 		/// Compatibility version of `apply_extrinsic` for v3.
 		///
@@ -69,5 +69,9 @@ sp_api::decl_runtime_apis! {
 		fn check_inherents(block: Block, data: InherentData) -> CheckInherentsResult;
 		/// Generate a random seed.
 		fn random_seed() -> <Block as BlockT>::Hash;
+		/// The total weight of the extrinsics applied to the block being built so far.
+		///
+		/// Added in version 5.
+		fn block_weight() -> u64;
 	}
 }
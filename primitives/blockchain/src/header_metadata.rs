@@ -24,6 +24,9 @@ use lru::LruCache;
 /// Set to the expected max difference between `best` and `finalized` blocks at sync.
 const LRU_CACHE_SIZE: usize = 5_000;
 
+/// Number of tree-routes kept in the cache. Routes may be long at sync, so only a few are kept.
+const TREE_ROUTE_CACHE_SIZE: usize = 64;
+
 /// Get lowest common ancestor between two blocks in the tree.
 ///
 /// This implementation is efficient because our trees have very few and
@@ -92,10 +95,26 @@ pub fn lowest_common_ancestor<Block: BlockT, T: HeaderMetadata<Block>>(
 }
 
 /// Compute a tree-route between two blocks. See tree-route docs for more details.
+///
+/// The route is cached by the backend, if it supports it.
 pub fn tree_route<Block: BlockT, T: HeaderMetadata<Block>>(
 	backend: &T,
 	from: Block::Hash,
 	to: Block::Hash,
+) -> Result<TreeRoute<Block>, T::Error> {
+	if let Some(route) = backend.cached_tree_route(from, to) {
+		return Ok(route);
+	}
+
+	let route = compute_tree_route(backend, from, to)?;
+	backend.cache_tree_route(&route);
+	Ok(route)
+}
+
+fn compute_tree_route<Block: BlockT, T: HeaderMetadata<Block>>(
+	backend: &T,
+	from: Block::Hash,
+	to: Block::Hash,
 ) -> Result<TreeRoute<Block>, T::Error> {
 	let mut from = backend.header_metadata(from)?;
 	let mut to = backend.header_metadata(to)?;
@@ -151,7 +170,7 @@ pub fn tree_route<Block: BlockT, T: HeaderMetadata<Block>>(
 }
 
 /// Hash and number of a block.
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct HashAndNumber<Block: BlockT> {
 	/// The number of the block.
 	pub number: NumberFor<Block>,
@@ -181,7 +200,7 @@ pub struct HashAndNumber<Block: BlockT> {
 /// Tree route from C to E2. Retracted empty. Common is C, enacted [E1, E2]
 /// C -> E1 -> E2
 /// ```
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TreeRoute<Block: BlockT> {
 	route: Vec<HashAndNumber<Block>>,
 	pivot: usize,
@@ -205,6 +224,13 @@ impl<Block: BlockT> TreeRoute<Block> {
 	pub fn enacted(&self) -> &[HashAndNumber<Block>] {
 		&self.route[self.pivot + 1 ..]
 	}
+
+	/// Get the hashes of the first and last blocks of the route.
+	fn ends(&self) -> (Block::Hash, Block::Hash) {
+		let first = self.route.first().expect("tree-routes are never empty; qed");
+		let last = self.route.last().expect("tree-routes are never empty; qed");
+		(first.hash, last.hash)
+	}
 }
 
 /// Handles header metadata: hash, number, parent hash, etc.
@@ -215,11 +241,20 @@ pub trait HeaderMetadata<Block: BlockT> {
 	fn header_metadata(&self, hash: Block::Hash) -> Result<CachedHeaderMetadata<Block>, Self::Error>;
 	fn insert_header_metadata(&self, hash: Block::Hash, header_metadata: CachedHeaderMetadata<Block>);
 	fn remove_header_metadata(&self, hash: Block::Hash);
+
+	/// Returns the cached tree-route from `from` to `to`, if any.
+	fn cached_tree_route(&self, _from: Block::Hash, _to: Block::Hash) -> Option<TreeRoute<Block>> {
+		None
+	}
+	/// Cache a tree-route. Routes never change, since blocks are known by their hash, but they
+	/// must be dropped along with the metadata of the blocks they go through.
+	fn cache_tree_route(&self, _route: &TreeRoute<Block>) {}
 }
 
-/// Caches header metadata in an in-memory LRU cache.
+/// Caches header metadata and tree-routes in in-memory LRU caches.
 pub struct HeaderMetadataCache<Block: BlockT> {
 	cache: RwLock<LruCache<Block::Hash, CachedHeaderMetadata<Block>>>,
+	tree_routes: RwLock<LruCache<(Block::Hash, Block::Hash), TreeRoute<Block>>>,
 }
 
 impl<Block: BlockT> HeaderMetadataCache<Block> {
@@ -227,15 +262,14 @@ impl<Block: BlockT> HeaderMetadataCache<Block> {
 	pub fn new(capacity: usize) -> Self {
 		HeaderMetadataCache {
 			cache: RwLock::new(LruCache::new(capacity)),
+			tree_routes: RwLock::new(LruCache::new(TREE_ROUTE_CACHE_SIZE)),
 		}
 	}
 }
 
 impl<Block: BlockT> Default for HeaderMetadataCache<Block> {
 	fn default() -> Self {
-		HeaderMetadataCache {
-			cache: RwLock::new(LruCache::new(LRU_CACHE_SIZE)),
-		}
+		HeaderMetadataCache::new(LRU_CACHE_SIZE)
 	}
 }
 
@@ -253,6 +287,17 @@ impl<Block: BlockT> HeaderMetadata<Block> for HeaderMetadataCache<Block> {
 
 	fn remove_header_metadata(&self, hash: Block::Hash) {
 		self.cache.write().pop(&hash);
+		// Blocks are only removed on revert, so the few cached routes are dropped altogether
+		// rather than looked through.
+		self.tree_routes.write().clear();
+	}
+
+	fn cached_tree_route(&self, from: Block::Hash, to: Block::Hash) -> Option<TreeRoute<Block>> {
+		self.tree_routes.write().get(&(from, to)).cloned()
+	}
+
+	fn cache_tree_route(&self, route: &TreeRoute<Block>) {
+		self.tree_routes.write().put(route.ends(), route.clone());
 	}
 }
 
@@ -20,7 +20,7 @@
 
 use codec::{Encode, Decode, Codec};
 use sp_std::vec::Vec;
-use sp_runtime::ConsensusEngineId;
+use sp_runtime::{ConsensusEngineId, RuntimeDebug};
 
 pub mod inherents;
 
@@ -64,6 +64,16 @@ pub const AURA_ENGINE_ID: ConsensusEngineId = [b'a', b'u', b'r', b'a'];
 /// The index of an authority.
 pub type AuthorityIndex = u32;
 
+/// A scheduled change of authority set.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub struct ScheduledChange<AuthorityId> {
+	/// The new authorities after the change.
+	pub next_authorities: Vec<AuthorityId>,
+	/// The number of blocks to delay: the new authorities author the children of the block
+	/// `delay` blocks after the one signaling the change.
+	pub delay: u32,
+}
+
 /// An consensus log item for Aura.
 #[derive(Decode, Encode)]
 pub enum ConsensusLog<AuthorityId: Codec> {
@@ -73,6 +83,13 @@ pub enum ConsensusLog<AuthorityId: Codec> {
 	/// Disable the authority with given index.
 	#[codec(index = "2")]
 	OnDisabled(AuthorityIndex),
+	/// Schedule an authority set change.
+	///
+	/// Nodes track the changes signaled on each fork, so that they know the authorities of a
+	/// block without executing its parent. A change with no delay supersedes the changes still
+	/// pending on the same fork.
+	#[codec(index = "3")]
+	ScheduledChange(ScheduledChange<AuthorityId>),
 }
 
 sp_api::decl_runtime_apis! {
@@ -24,7 +24,7 @@ pub mod inherents;
 
 use codec::{Encode, Decode};
 use sp_std::vec::Vec;
-use sp_runtime::{ConsensusEngineId, RuntimeDebug};
+use sp_runtime::{ConsensusEngineId, RuntimeDebug, traits::Header as HeaderT};
 
 #[cfg(feature = "std")]
 pub use digest::{BabePreDigest, CompatibleDigestItem};
@@ -110,6 +110,25 @@ impl Epoch {
 	pub fn end_slot(&self) -> SlotNumber {
 		self.start_slot + self.duration
 	}
+
+	/// Produce the epoch the given slot falls in, assuming that no block has been
+	/// authored since this epoch ended. The skipped epochs are numbered as if they
+	/// had happened, and the epoch reuses the authorities and randomness of this
+	/// one, so that every node derives the same epoch.
+	pub fn skip_to_slot(&self, slot_number: SlotNumber) -> Epoch {
+		if slot_number < self.end_slot() || self.duration == 0 {
+			return self.clone();
+		}
+
+		let skipped_epochs = (slot_number - self.start_slot) / self.duration;
+		Epoch {
+			epoch_index: self.epoch_index + skipped_epochs,
+			start_slot: self.start_slot + skipped_epochs * self.duration,
+			duration: self.duration,
+			authorities: self.authorities.clone(),
+			randomness: self.randomness,
+		}
+	}
 }
 
 /// An consensus log item for BABE.
@@ -156,6 +175,58 @@ pub struct BabeConfiguration {
 	pub secondary_slots: bool,
 }
 
+/// Proof of an authority having authored two different blocks at the same slot.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct EquivocationProof<Header> {
+	/// The authority that authored the two blocks.
+	pub offender: AuthorityId,
+	/// The slot at which the two blocks have been authored.
+	pub slot_number: SlotNumber,
+	/// The header of the first block.
+	pub first_header: Header,
+	/// The header of the second block.
+	pub second_header: Header,
+}
+
+/// Checks that the headers of the given equivocation proof are different, have both been
+/// authored at the slot of the proof and are both sealed by the offender.
+pub fn check_equivocation_proof<H: HeaderT>(proof: EquivocationProof<H>) -> bool {
+	use sp_application_crypto::RuntimeAppPublic;
+
+	let EquivocationProof { offender, slot_number, first_header, second_header } = proof;
+	if first_header.hash() == second_header.hash() {
+		return false;
+	}
+
+	let check_header = |mut header: H| {
+		// the seal must be the last digest item, and isn't part of the pre-hash.
+		let signature = match header.digest_mut().pop() {
+			Some(seal) => match seal.as_seal() {
+				Some((id, mut data)) if id == BABE_ENGINE_ID =>
+					AuthoritySignature::decode(&mut data).ok(),
+				_ => None,
+			},
+			None => None,
+		};
+		let signature = match signature {
+			Some(signature) => signature,
+			None => return false,
+		};
+
+		let pre_digest = header.digest().logs().iter()
+			.filter_map(|log| log.as_pre_runtime())
+			.find(|(id, _)| *id == BABE_ENGINE_ID)
+			.and_then(|(_, mut data)| RawBabePreDigest::decode(&mut data).ok());
+		if pre_digest.map(|d| d.slot_number()) != Some(slot_number) {
+			return false;
+		}
+
+		offender.verify(&header.hash(), &signature)
+	};
+
+	check_header(first_header) && check_header(second_header)
+}
+
 #[cfg(feature = "std")]
 impl sp_consensus::SlotData for BabeConfiguration {
 	fn slot_duration(&self) -> u64 {
@@ -167,11 +238,19 @@ impl sp_consensus::SlotData for BabeConfiguration {
 
 sp_api::decl_runtime_apis! {
 	/// API necessary for block authorship with BABE.
+	#[api_version(2)]
 	pub trait BabeApi {
 		/// Return the configuration for BABE. Currently,
 		/// only the value provided by this type at genesis will be used.
 		///
 		/// Dynamic configuration may be supported in the future.
 		fn configuration() -> BabeConfiguration;
+
+		/// Submit an unsigned transaction reporting the given equivocation, to be slashed.
+		/// Only works in an offchain context. Returns `None` if the transaction couldn't be
+		/// submitted, e.g. because the runtime doesn't handle equivocations.
+		fn submit_report_equivocation_extrinsic(
+			equivocation_proof: EquivocationProof<Block::Header>,
+		) -> Option<()>;
 	}
 }
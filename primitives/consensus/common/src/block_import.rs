@@ -85,6 +85,21 @@ pub enum BlockOrigin {
 	File,
 }
 
+impl BlockOrigin {
+	/// Returns the label of the origin in the metrics of the import pipeline: `own`, `network`,
+	/// `file` or `genesis`.
+	pub fn metric_label(&self) -> &'static str {
+		match self {
+			BlockOrigin::Genesis => "genesis",
+			BlockOrigin::NetworkInitialSync
+				| BlockOrigin::NetworkBroadcast
+				| BlockOrigin::ConsensusBroadcast => "network",
+			BlockOrigin::Own => "own",
+			BlockOrigin::File => "file",
+		}
+	}
+}
+
 /// Fork choice strategy.
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum ForkChoiceStrategy {
@@ -109,6 +124,9 @@ pub struct BlockCheckParams<Block: BlockT> {
 	pub import_existing: bool,
 }
 
+/// Full state of a block as the list of its storage keys and values, in ascending key order.
+pub type ImportedState = Vec<(Vec<u8>, Vec<u8>)>;
+
 /// Data required to import a Block.
 pub struct BlockImportParams<Block: BlockT> {
 	/// Origin of the Block
@@ -146,6 +164,9 @@ pub struct BlockImportParams<Block: BlockT> {
 	pub allow_missing_state: bool,
 	/// Re-validate existing block.
 	pub import_existing: bool,
+	/// State of the block, imported in place of executing the block. The parent of such a block
+	/// doesn't need to be known, as is the case after warp sync.
+	pub state: Option<ImportedState>,
 }
 
 impl<Block: BlockT> BlockImportParams<Block> {
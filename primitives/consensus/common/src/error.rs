@@ -46,6 +46,10 @@ pub enum Error {
 	/// Invalid authorities set received from the runtime.
 	#[display(fmt="Current state of blockchain has invalid authorities set")]
 	InvalidAuthoritiesSet,
+	/// The keystore failed to sign with the given key.
+	#[display(fmt="Cannot sign using key {:?}: {}", _0, _1)]
+	#[from(ignore)]
+	CannotSign(Vec<u8>, String),
 	/// Account is not an authority.
 	#[display(fmt="Message sender {:?} is not a valid authority.", _0)]
 	InvalidAuthority(Public),
@@ -0,0 +1,34 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+//
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+//
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+//
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Ranking of competing forks announced to the sync.
+
+use sp_runtime::traits::Block;
+
+/// Type which weighs the chains ending at announced blocks, as defined by the consensus engine,
+/// e.g. their total difficulty or number of primary slots.
+///
+/// When several forks are announced, the sync downloads the heaviest ones first, as they are the
+/// most likely to become canonical. Forks that can't be weighed come last, by block number.
+pub trait ForkRanking<B: Block>: Send + Sync {
+	/// Weight of the fork of the announced header, if it can be computed from the blocks imported
+	/// locally.
+	///
+	/// The announced header hasn't been verified, so nothing it claims, such as its slot or its
+	/// difficulty, must be trusted. The weight is typically the one of the chain ending at its
+	/// parent.
+	fn fork_weight(&self, header: &B::Header) -> Option<u64>;
+}
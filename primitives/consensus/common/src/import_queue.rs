@@ -25,18 +25,22 @@
 //! instantiated. The `BasicQueue` and `BasicVerifier` traits allow serial
 //! queues to be instantiated simply.
 
-use std::collections::HashMap;
+use std::{collections::HashMap, time::Instant};
 use sp_runtime::{Justification, traits::{Block as BlockT, Header as _, NumberFor}};
 use crate::error::Error as ConsensusError;
+use crate::metrics::Metrics;
 use crate::block_import::{
 	BlockImport, BlockOrigin, BlockImportParams, ImportedAux, JustificationImport, ImportResult,
-	BlockCheckParams, FinalityProofImport,
+	BlockCheckParams, FinalityProofImport, ForkChoiceStrategy, ImportedState,
 };
 
 pub use basic_queue::BasicQueue;
 
 mod basic_queue;
 pub mod buffered_link;
+mod verification_pool;
+
+use verification_pool::Ahead;
 
 /// Shared block import struct used by the queue.
 pub type BoxBlockImport<B> = Box<dyn BlockImport<B, Error = ConsensusError> + Send + Sync>;
@@ -67,6 +71,9 @@ pub struct IncomingBlock<B: BlockT> {
 	pub allow_missing_state: bool,
 	/// Re-validate existing block.
 	pub import_existing: bool,
+	/// State of the block, downloaded in place of its ancestry. Such a block is imported as
+	/// finalized without going through the verifier, its finality must already have been proven.
+	pub state: Option<ImportedState>,
 }
 
 /// Type of keys in the blockchain cache that consensus module could use for its needs.
@@ -181,8 +188,63 @@ pub fn import_single_block<B: BlockT, V: Verifier<B>>(
 	block_origin: BlockOrigin,
 	block: IncomingBlock<B>,
 	verifier: &mut V,
+) -> Result<BlockImportResult<NumberFor<B>>, BlockImportError> {
+	import_single_block_metered(import_handle, block_origin, block, verifier, None, None)
+}
+
+/// Outcome of the verification of a block.
+pub(crate) type VerificationResult<B> =
+	Result<(BlockImportParams<B>, Option<Vec<(CacheKeyId, Vec<u8>)>>), String>;
+
+/// Single block import function, reporting the time spent in each stage to the given metrics.
+///
+/// If the block was handled ahead of its import, the outcome must be passed along, in which case
+/// the justification and the body of the `IncomingBlock` are ignored.
+pub(crate) fn import_single_block_metered<B: BlockT, V: Verifier<B>>(
+	import_handle: &mut dyn BlockImport<B, Error = ConsensusError>,
+	block_origin: BlockOrigin,
+	block: IncomingBlock<B>,
+	verifier: &mut V,
+	ahead: Option<Ahead<B>>,
+	metrics: Option<&Metrics>,
+) -> Result<BlockImportResult<NumberFor<B>>, BlockImportError> {
+	let result = import_stages(import_handle, block_origin, block, verifier, ahead, metrics);
+	if let Some(metrics) = metrics {
+		metrics.report_processed(&block_origin, result.is_ok());
+	}
+	result
+}
+
+/// Verify a single block, reporting the time spent to the given metrics.
+pub(crate) fn verify_single_block<B: BlockT, V: Verifier<B>>(
+	verifier: &mut V,
+	block_origin: BlockOrigin,
+	header: B::Header,
+	justification: Option<Justification>,
+	body: Option<Vec<B::Extrinsic>>,
+	metrics: Option<&Metrics>,
+) -> VerificationResult<B> {
+	let span = tracing::span!(tracing::Level::DEBUG, "verify_block", origin = block_origin.metric_label());
+	let _enter = span.enter();
+	let started = Instant::now();
+	let verified = verifier.verify(block_origin, header, justification, body);
+	if let Some(metrics) = metrics {
+		metrics.report_verification(&block_origin, started.elapsed());
+	}
+	verified
+}
+
+/// Check, verify and import a single block, timing each stage.
+fn import_stages<B: BlockT, V: Verifier<B>>(
+	import_handle: &mut dyn BlockImport<B, Error = ConsensusError>,
+	block_origin: BlockOrigin,
+	block: IncomingBlock<B>,
+	verifier: &mut V,
+	ahead: Option<Ahead<B>>,
+	metrics: Option<&Metrics>,
 ) -> Result<BlockImportResult<NumberFor<B>>, BlockImportError> {
 	let peer = block.origin;
+	let origin_label = block_origin.metric_label();
 
 	let (header, justification) = match (block.header, block.justification) {
 		(Some(header), justification) => (header, justification),
@@ -227,6 +289,32 @@ pub fn import_single_block<B: BlockT, V: Verifier<B>>(
 			}
 		}
 	};
+	if let Some(state) = block.state {
+		// The parent of the block is unknown and its finality has already been proven, so there
+		// is nothing to check or verify.
+		let import_block = BlockImportParams {
+			origin: block_origin,
+			header,
+			justification,
+			post_digests: Vec::new(),
+			body: block.body,
+			finalized: true,
+			auxiliary: Vec::new(),
+			fork_choice: ForkChoiceStrategy::LongestChain,
+			allow_missing_state: false,
+			import_existing: block.import_existing,
+			state: Some(state),
+		};
+		let span = tracing::span!(tracing::Level::DEBUG, "import_block", origin = origin_label);
+		let _enter = span.enter();
+		let started = Instant::now();
+		let result = import_handle.import_block(import_block, HashMap::new());
+		if let Some(metrics) = metrics {
+			metrics.report_import(&block_origin, started.elapsed());
+		}
+		return import_error(result);
+	}
+
 	match import_error(import_handle.check_block(BlockCheckParams {
 		hash,
 		number,
@@ -238,7 +326,20 @@ pub fn import_single_block<B: BlockT, V: Verifier<B>>(
 		r => return Ok(r), // Any other successful result means that the block is already imported.
 	}
 
-	let (mut import_block, maybe_keys) = verifier.verify(block_origin, header, justification, block.body)
+	let verified = match ahead {
+		Some(Ahead::Verified(verified)) => verified,
+		Some(Ahead::Checked(justification, body)) =>
+			verify_single_block(verifier, block_origin, header, justification, body, metrics),
+		Some(Ahead::Invalid(msg)) => Err(msg),
+		Some(Ahead::Failed) => {
+			debug!(target: "sync", "Failed to verify {}({}) ahead of its import", number, hash);
+			return Err(BlockImportError::Other(ConsensusError::ClientImport(
+				"Verification worker failed".into()
+			)))
+		},
+		None => verify_single_block(verifier, block_origin, header, justification, block.body, metrics),
+	};
+	let (mut import_block, maybe_keys) = verified
 		.map_err(|msg| {
 			if let Some(ref peer) = peer {
 				trace!(target: "sync", "Verifying {}({}) from {} failed: {}", number, hash, peer, msg);
@@ -254,5 +355,12 @@ pub fn import_single_block<B: BlockT, V: Verifier<B>>(
 	}
 	import_block.allow_missing_state = block.allow_missing_state;
 
-	import_error(import_handle.import_block(import_block, cache))
+	let span = tracing::span!(tracing::Level::DEBUG, "import_block", origin = origin_label);
+	let _enter = span.enter();
+	let started = Instant::now();
+	let result = import_handle.import_block(import_block, cache);
+	if let Some(metrics) = metrics {
+		metrics.report_import(&block_origin, started.elapsed());
+	}
+	import_error(result)
 }
@@ -14,17 +14,20 @@
 // You should have received a copy of the GNU General Public License
 // along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
 
-use std::{mem, pin::Pin, time::Duration};
+use std::{collections::HashMap, mem, pin::Pin, time::Duration};
 use futures::{prelude::*, channel::mpsc, task::Context, task::Poll};
 use futures_timer::Delay;
+use prometheus_endpoint::Registry;
 use sp_runtime::{Justification, traits::{Block as BlockT, Header as HeaderT, NumberFor}};
 
 use crate::block_import::BlockOrigin;
+use crate::metrics::Metrics;
 use crate::import_queue::{
 	BlockImportResult, BlockImportError, Verifier, BoxBlockImport, BoxFinalityProofImport,
 	BoxJustificationImport, ImportQueue, Link, Origin,
-	IncomingBlock, import_single_block,
-	buffered_link::{self, BufferedLinkSender, BufferedLinkReceiver}
+	IncomingBlock, import_single_block_metered,
+	buffered_link::{self, BufferedLinkSender, BufferedLinkReceiver},
+	verification_pool::VerificationPool,
 };
 
 /// Interface to a basic block import queue that is importing blocks sequentially in a separate
@@ -47,12 +50,65 @@ impl<B: BlockT> BasicQueue<B> {
 	///
 	/// This creates a background task, and calls `on_start` on the justification importer and
 	/// finality proof importer.
+	///
+	/// The time spent verifying and importing the blocks is reported to the given Prometheus
+	/// registry, if any.
 	pub fn new<V: 'static + Verifier<B>>(
 		verifier: V,
 		block_import: BoxBlockImport<B>,
 		justification_import: Option<BoxJustificationImport<B>>,
 		finality_proof_import: Option<BoxFinalityProofImport<B>>,
+		prometheus_registry: Option<&Registry>,
 	) -> Self {
+		Self::new_inner(
+			verifier,
+			block_import,
+			justification_import,
+			finality_proof_import,
+			prometheus_registry,
+			None,
+		)
+	}
+
+	/// Instantiate a new basic queue, verifying the blocks on the given number of workers.
+	///
+	/// The header and the body of the blocks of a batch are checked against each other in
+	/// parallel, ahead of their sequential import. The blocks whose parent is not part of the
+	/// same batch are verified in parallel as well, each worker with its own copy of the
+	/// verifier. With less than two workers, this is the same as `new`.
+	pub fn new_with_verification_workers<V: 'static + Verifier<B> + Clone>(
+		verifier: V,
+		block_import: BoxBlockImport<B>,
+		justification_import: Option<BoxJustificationImport<B>>,
+		finality_proof_import: Option<BoxFinalityProofImport<B>>,
+		prometheus_registry: Option<&Registry>,
+		verification_workers: usize,
+	) -> Self {
+		let verification_pool = VerificationPool::new(&verifier, verification_workers);
+		Self::new_inner(
+			verifier,
+			block_import,
+			justification_import,
+			finality_proof_import,
+			prometheus_registry,
+			verification_pool,
+		)
+	}
+
+	fn new_inner<V: 'static + Verifier<B>>(
+		verifier: V,
+		block_import: BoxBlockImport<B>,
+		justification_import: Option<BoxJustificationImport<B>>,
+		finality_proof_import: Option<BoxFinalityProofImport<B>>,
+		prometheus_registry: Option<&Registry>,
+		verification_pool: Option<VerificationPool<V>>,
+	) -> Self {
+		let metrics = prometheus_registry.and_then(|registry| {
+			Metrics::register(registry)
+				.map_err(|err| warn!("Failed to register the import queue metrics: {:?}", err))
+				.ok()
+		});
+
 		let (result_sender, result_port) = buffered_link::buffered_link();
 		let (future, worker_sender) = BlockImportWorker::new(
 			result_sender,
@@ -60,6 +116,8 @@ impl<B: BlockT> BasicQueue<B> {
 			block_import,
 			justification_import,
 			finality_proof_import,
+			verification_pool,
+			metrics,
 		);
 
 		let mut pool = futures::executor::ThreadPool::builder()
@@ -137,6 +195,7 @@ struct BlockImportWorker<B: BlockT> {
 	justification_import: Option<BoxJustificationImport<B>>,
 	finality_proof_import: Option<BoxFinalityProofImport<B>>,
 	delay_between_blocks: Duration,
+	metrics: Option<Metrics>,
 }
 
 impl<B: BlockT> BlockImportWorker<B> {
@@ -146,6 +205,8 @@ impl<B: BlockT> BlockImportWorker<B> {
 		block_import: BoxBlockImport<B>,
 		justification_import: Option<BoxJustificationImport<B>>,
 		finality_proof_import: Option<BoxFinalityProofImport<B>>,
+		verification_pool: Option<VerificationPool<V>>,
+		metrics: Option<Metrics>,
 	) -> (impl Future<Output = ()> + Send, mpsc::UnboundedSender<ToWorkerMsg<B>>) {
 		let (sender, mut port) = mpsc::unbounded();
 
@@ -154,6 +215,7 @@ impl<B: BlockT> BlockImportWorker<B> {
 			justification_import,
 			finality_proof_import,
 			delay_between_blocks: Duration::new(0, 0),
+			metrics,
 		};
 
 		// Let's initialize `justification_import` and `finality_proof_import`.
@@ -174,7 +236,7 @@ impl<B: BlockT> BlockImportWorker<B> {
 		//   `Future`, and `block_import` is `None`.
 		// - Something else, in which case `block_import` is `Some` and `importing` is None.
 		//
-		let mut block_import_verifier = Some((block_import, verifier));
+		let mut block_import_verifier = Some((block_import, verifier, verification_pool));
 		let mut importing = None;
 
 		let future = futures::future::poll_fn(move |cx| {
@@ -190,8 +252,8 @@ impl<B: BlockT> BlockImportWorker<B> {
 				if let Some(imp_fut) = importing.as_mut() {
 					match Future::poll(Pin::new(imp_fut), cx) {
 						Poll::Pending => return Poll::Pending,
-						Poll::Ready((bi, verif)) => {
-							block_import_verifier = Some((bi, verif));
+						Poll::Ready((bi, verif, pool)) => {
+							block_import_verifier = Some((bi, verif, pool));
 							importing = None;
 						},
 					}
@@ -211,12 +273,12 @@ impl<B: BlockT> BlockImportWorker<B> {
 					ToWorkerMsg::ImportBlocks(origin, blocks) => {
 						// On blocks import request, we merely *start* the process and store
 						// a `Future` into `importing`.
-						let (bi, verif) = block_import_verifier.take()
+						let (bi, verif, pool) = block_import_verifier.take()
 							.expect("block_import_verifier is always Some; qed");
-						importing = Some(worker.import_a_batch_of_blocks(bi, verif, origin, blocks));
+						importing = Some(worker.import_a_batch_of_blocks(bi, verif, pool, origin, blocks));
 					},
 					ToWorkerMsg::ImportFinalityProof(who, hash, number, proof) => {
-						let (_, verif) = block_import_verifier.as_mut()
+						let (_, verif, _) = block_import_verifier.as_mut()
 							.expect("block_import_verifier is always Some; qed");
 						worker.import_finality_proof(verif, who, hash, number, proof);
 					},
@@ -239,15 +301,24 @@ impl<B: BlockT> BlockImportWorker<B> {
 		&mut self,
 		block_import: BoxBlockImport<B>,
 		verifier: V,
+		verification_pool: Option<VerificationPool<V>>,
 		origin: BlockOrigin,
 		blocks: Vec<IncomingBlock<B>>
-	) -> impl Future<Output = (BoxBlockImport<B>, V)> {
+	) -> impl Future<Output = (BoxBlockImport<B>, V, Option<VerificationPool<V>>)> {
 		let mut result_sender = self.result_sender.clone();
 
-		import_many_blocks(block_import, origin, blocks, verifier, self.delay_between_blocks)
-			.then(move |(imported, count, results, block_import, verifier)| {
+		import_many_blocks(
+			block_import,
+			origin,
+			blocks,
+			verifier,
+			verification_pool,
+			self.delay_between_blocks,
+			self.metrics.clone(),
+		)
+			.then(move |(imported, count, results, block_import, verifier, verification_pool)| {
 				result_sender.blocks_processed(imported, count, results);
-				future::ready((block_import, verifier))
+				future::ready((block_import, verifier, verification_pool))
 			})
 	}
 
@@ -309,16 +380,18 @@ impl<B: BlockT> BlockImportWorker<B> {
 ///
 /// The returned `Future` yields at every imported block, which makes the execution more
 /// fine-grained and making it possible to interrupt the process.
-fn import_many_blocks<B: BlockT, V: Verifier<B>>(
+fn import_many_blocks<B: BlockT, V: 'static + Verifier<B>>(
 	import_handle: BoxBlockImport<B>,
 	blocks_origin: BlockOrigin,
-	blocks: Vec<IncomingBlock<B>>,
+	mut blocks: Vec<IncomingBlock<B>>,
 	verifier: V,
+	verification_pool: Option<VerificationPool<V>>,
 	delay_between_blocks: Duration,
+	metrics: Option<Metrics>,
 ) -> impl Future<Output = (usize, usize, Vec<(
 	Result<BlockImportResult<NumberFor<B>>, BlockImportError>,
 	B::Hash,
-)>, BoxBlockImport<B>, V)> {
+)>, BoxBlockImport<B>, V, Option<VerificationPool<V>>)> {
 	let count = blocks.len();
 
 	let blocks_range = match (
@@ -332,10 +405,17 @@ fn import_many_blocks<B: BlockT, V: Verifier<B>>(
 
 	trace!(target: "sync", "Starting import of {} blocks {}", count, blocks_range);
 
+	// The blocks are checked ahead, in parallel, if there is a verification pool. The ones that
+	// don't depend on each other are verified as well.
+	let mut verifying = verification_pool
+		.map(|pool| pool.verify(blocks_origin, &mut blocks, metrics.clone()));
+	let mut verification_pool = None;
+	let mut handled = HashMap::new();
+
 	let mut imported = 0;
 	let mut results = vec![];
 	let mut has_error = false;
-	let mut blocks = blocks.into_iter();
+	let mut blocks = blocks.into_iter().enumerate();
 	let mut import_handle = Some(import_handle);
 	let mut waiting = None;
 	let mut verifier = Some(verifier);
@@ -343,6 +423,18 @@ fn import_many_blocks<B: BlockT, V: Verifier<B>>(
 	// Blocks in the response/drain should be in ascending order.
 
 	future::poll_fn(move |cx| {
+		// Wait for the blocks handled ahead, before importing any block.
+		if let Some(verifying_fut) = &mut verifying {
+			match Future::poll(verifying_fut.as_mut(), cx) {
+				Poll::Ready((pool, results)) => {
+					verification_pool = Some(pool);
+					handled = results;
+				},
+				Poll::Pending => return Poll::Pending,
+			}
+		}
+		verifying = None;
+
 		// Handle the optional timer that makes us wait before the next import.
 		if let Some(waiting) = &mut waiting {
 			match Future::poll(Pin::new(waiting), cx) {
@@ -353,7 +445,7 @@ fn import_many_blocks<B: BlockT, V: Verifier<B>>(
 		waiting = None;
 
 		// Is there any block left to import?
-		let block = match blocks.next() {
+		let (index, block) = match blocks.next() {
 			Some(b) => b,
 			None => {
 				// No block left to import, success!
@@ -362,7 +454,14 @@ fn import_many_blocks<B: BlockT, V: Verifier<B>>(
 				let verifier = verifier.take()
 					.expect("Future polled again after it has finished");
 				let results = mem::replace(&mut results, Vec::new());
-				return Poll::Ready((imported, count, results, import_handle, verifier));
+				return Poll::Ready((
+					imported,
+					count,
+					results,
+					import_handle,
+					verifier,
+					verification_pool.take(),
+				));
 			},
 		};
 
@@ -380,11 +479,13 @@ fn import_many_blocks<B: BlockT, V: Verifier<B>>(
 			Err(BlockImportError::Cancelled)
 		} else {
 			// The actual import.
-			import_single_block(
+			import_single_block_metered(
 				&mut **import_handle,
 				blocks_origin.clone(),
 				block,
 				verifier,
+				handled.remove(&index),
+				metrics.as_ref(),
 			)
 		};
 
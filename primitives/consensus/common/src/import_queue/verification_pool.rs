@@ -0,0 +1,375 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Checks and verification of the blocks of a batch ahead of their import, on a pool of workers.
+//!
+//! The header and the body of every block of a batch are checked against each other in parallel,
+//! which doesn't depend on the parent of the block. Verifying a block usually requires its parent
+//! to be imported though, so only the blocks whose parent is not part of the same batch are also
+//! verified ahead. The blocks are spread over the workers, each with its own copy of the
+//! verifier, while the import itself remains sequential.
+
+use std::{collections::{HashMap, HashSet}, panic::{self, AssertUnwindSafe}, pin::Pin};
+use codec::Encode;
+use futures::{prelude::*, channel::oneshot, executor::ThreadPool};
+use sp_runtime::Justification;
+use sp_runtime::traits::{Block as BlockT, Header as HeaderT, Hash as HashT, HashFor};
+
+use crate::block_import::BlockOrigin;
+use crate::import_queue::{IncomingBlock, Verifier, VerificationResult, verify_single_block};
+use crate::metrics::Metrics;
+
+/// Outcome of the work done on a block of a batch ahead of its import.
+pub(crate) enum Ahead<B: BlockT> {
+	/// The header and the body of the block match. They are handed back along with the
+	/// justification, the block being verified at import.
+	Checked(Option<Justification>, Option<Vec<B::Extrinsic>>),
+	/// The header and the body of the block match, and the block has been verified.
+	Verified(VerificationResult<B>),
+	/// The header and the body of the block don't match.
+	Invalid(String),
+	/// The worker handling the block failed. This is a local error, the block is not at fault.
+	Failed,
+}
+
+/// Outcomes of the blocks handled ahead of their import, by index in the batch.
+pub(crate) type Handled<B> = HashMap<usize, Ahead<B>>;
+
+/// A block handed over to a worker.
+struct Work<B: BlockT> {
+	index: usize,
+	hash: B::Hash,
+	header: B::Header,
+	justification: Option<Justification>,
+	body: Option<Vec<B::Extrinsic>>,
+	verify: bool,
+}
+
+impl<B: BlockT> Work<B> {
+	fn run<V: Verifier<B>>(
+		self,
+		verifier: &mut V,
+		origin: BlockOrigin,
+		metrics: Option<&Metrics>,
+	) -> Ahead<B> {
+		if let Err(err) = check_block::<B>(&self.hash, &self.header, self.body.as_ref()) {
+			return Ahead::Invalid(err);
+		}
+
+		if self.verify {
+			Ahead::Verified(verify_single_block(
+				verifier,
+				origin,
+				self.header,
+				self.justification,
+				self.body,
+				metrics,
+			))
+		} else {
+			Ahead::Checked(self.justification, self.body)
+		}
+	}
+}
+
+/// Check that the header of a block matches its hash, and its body if any.
+fn check_block<B: BlockT>(
+	hash: &B::Hash,
+	header: &B::Header,
+	body: Option<&Vec<B::Extrinsic>>,
+) -> Result<(), String> {
+	if header.hash() != *hash {
+		return Err(format!("Header of {:?} has hash {:?}", hash, header.hash()));
+	}
+
+	if let Some(body) = body {
+		let extrinsics_root = HashFor::<B>::ordered_trie_root(body.iter().map(Encode::encode).collect());
+		if extrinsics_root != *header.extrinsics_root() {
+			return Err(format!("Body of {:?} doesn't match the extrinsics root of its header", hash));
+		}
+	}
+
+	Ok(())
+}
+
+/// Pool of workers checking and verifying blocks, each with its own verifier.
+pub(crate) struct VerificationPool<V> {
+	pool: ThreadPool,
+	verifiers: Vec<V>,
+}
+
+impl<V: Clone> VerificationPool<V> {
+	/// Create a pool of `workers` workers, verifying with copies of the given verifier.
+	///
+	/// Returns `None` if less than two workers are requested, or if the pool can't be created.
+	pub fn new(verifier: &V, workers: usize) -> Option<Self> {
+		if workers < 2 {
+			return None;
+		}
+
+		let pool = ThreadPool::builder()
+			.name_prefix("import-queue-verifier-")
+			.pool_size(workers)
+			.create()
+			.map_err(|err| warn!("Failed to start the block verification workers: {:?}", err))
+			.ok()?;
+
+		Some(VerificationPool {
+			pool,
+			verifiers: (0..workers).map(|_| verifier.clone()).collect(),
+		})
+	}
+}
+
+impl<V> VerificationPool<V> {
+	/// Check the blocks of the batch, and verify the ones that don't depend on another block of
+	/// the batch.
+	///
+	/// The justification and the body of the blocks are moved out of the batch, and handed back
+	/// with the outcome of the checks. The returned future yields the pool back along with the
+	/// outcomes.
+	pub fn verify<B>(
+		mut self,
+		origin: BlockOrigin,
+		blocks: &mut [IncomingBlock<B>],
+		metrics: Option<Metrics>,
+	) -> Pin<Box<dyn Future<Output = (Self, Handled<B>)> + Send>> where
+		B: BlockT,
+		V: Verifier<B> + 'static,
+	{
+		let batch: HashSet<_> = blocks.iter().map(|block| block.hash).collect();
+		let handled: Vec<usize> = blocks.iter()
+			.enumerate()
+			.filter(|(_, block)| block.state.is_none() && block.header.is_some())
+			.map(|(index, _)| index)
+			.collect();
+
+		if handled.is_empty() || self.verifiers.is_empty() {
+			return Box::pin(future::ready((self, HashMap::new())));
+		}
+
+		let mut chunks: Vec<Vec<_>> = (0..self.verifiers.len().min(handled.len()))
+			.map(|_| Vec::new())
+			.collect();
+		let workers = chunks.len();
+		for (n, index) in handled.iter().enumerate() {
+			let block = &mut blocks[*index];
+			let header = block.header.clone().expect("only blocks with a header are handled; qed");
+			chunks[n % workers].push(Work {
+				index: *index,
+				hash: block.hash,
+				verify: !batch.contains(header.parent_hash()),
+				header,
+				justification: block.justification.take(),
+				body: block.body.take(),
+			});
+		}
+
+		let mut receivers = Vec::with_capacity(workers);
+		for chunk in chunks {
+			let verifier = self.verifiers.pop().expect("there is a verifier per chunk; qed");
+			let metrics = metrics.clone();
+			let (sender, receiver) = oneshot::channel();
+			self.pool.spawn_ok(async move {
+				let mut verifier = Some(verifier);
+				let results = chunk.into_iter()
+					.map(|work| {
+						let index = work.index;
+						let outcome = match verifier.as_mut() {
+							Some(v) => panic::catch_unwind(AssertUnwindSafe(||
+								work.run(v, origin, metrics.as_ref())
+							)),
+							None => Ok(Ahead::Failed),
+						};
+						let outcome = outcome.unwrap_or_else(|_| {
+							// the state of a verifier which panicked can't be trusted.
+							error!(target: "sync", "Block verification worker panicked");
+							verifier = None;
+							Ahead::Failed
+						});
+						(index, outcome)
+					})
+					.collect::<Vec<_>>();
+				let _ = sender.send((verifier, results));
+			});
+			receivers.push(receiver);
+		}
+
+		Box::pin(future::join_all(receivers).map(move |outputs| {
+			let mut outcomes = HashMap::new();
+			for output in outputs {
+				// the pool keeps going with one worker less if a verifier panicked.
+				if let Ok((verifier, results)) = output {
+					self.verifiers.extend(verifier);
+					outcomes.extend(results);
+				}
+			}
+
+			// the justification and the body of the blocks have been moved out of the batch.
+			for index in handled {
+				outcomes.entry(index).or_insert(Ahead::Failed);
+			}
+
+			(self, outcomes)
+		}))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::H256;
+	use sp_test_primitives::{Block, Extrinsic, Header};
+	use crate::block_import::{BlockImportParams, ForkChoiceStrategy};
+
+	#[derive(Clone)]
+	struct AcceptAll;
+
+	impl Verifier<Block> for AcceptAll {
+		fn verify(
+			&mut self,
+			origin: BlockOrigin,
+			header: Header,
+			justification: Option<sp_runtime::Justification>,
+			body: Option<Vec<<Block as BlockT>::Extrinsic>>,
+		) -> VerificationResult<Block> {
+			Ok((BlockImportParams {
+				origin,
+				header,
+				justification,
+				post_digests: Vec::new(),
+				body,
+				finalized: false,
+				auxiliary: Vec::new(),
+				fork_choice: ForkChoiceStrategy::LongestChain,
+				allow_missing_state: false,
+				import_existing: false,
+				state: None,
+			}, None))
+		}
+	}
+
+	#[derive(Clone)]
+	struct PanicOnVerify;
+
+	impl Verifier<Block> for PanicOnVerify {
+		fn verify(
+			&mut self,
+			_origin: BlockOrigin,
+			_header: Header,
+			_justification: Option<sp_runtime::Justification>,
+			_body: Option<Vec<<Block as BlockT>::Extrinsic>>,
+		) -> VerificationResult<Block> {
+			panic!("verifier bug")
+		}
+	}
+
+	fn incoming_block(number: u64, parent_hash: H256) -> IncomingBlock<Block> {
+		let header = Header::new(
+			number,
+			HashFor::<Block>::ordered_trie_root(Vec::new()),
+			Default::default(),
+			parent_hash,
+			Default::default(),
+		);
+		IncomingBlock {
+			hash: header.hash(),
+			header: Some(header),
+			body: Some(Vec::new()),
+			justification: None,
+			origin: None,
+			allow_missing_state: false,
+			import_existing: false,
+			state: None,
+		}
+	}
+
+	fn outcome(handled: &Handled<Block>, index: usize) -> &'static str {
+		match handled.get(&index) {
+			Some(Ahead::Checked(_, Some(_))) => "checked",
+			Some(Ahead::Checked(_, None)) => "checked without body",
+			Some(Ahead::Verified(Ok(_))) => "verified",
+			Some(Ahead::Verified(Err(_))) => "verification failed",
+			Some(Ahead::Invalid(_)) => "invalid",
+			Some(Ahead::Failed) => "failed",
+			None => "not handled",
+		}
+	}
+
+	#[test]
+	fn verifies_the_blocks_independent_of_the_batch() {
+		// two forks on top of blocks out of the batch, the second one being two blocks long.
+		let first = incoming_block(1, H256::repeat_byte(1));
+		let second = incoming_block(1, H256::repeat_byte(2));
+		let third = incoming_block(2, second.hash);
+		let mut blocks = vec![first, second, third];
+
+		let pool = VerificationPool::new(&AcceptAll, 2).unwrap();
+		let (pool, handled) = futures::executor::block_on(
+			pool.verify(BlockOrigin::NetworkInitialSync, &mut blocks, None)
+		);
+
+		assert_eq!(pool.verifiers.len(), 2);
+		assert_eq!(outcome(&handled, 0), "verified");
+		assert_eq!(outcome(&handled, 1), "verified");
+		assert_eq!(outcome(&handled, 2), "checked");
+
+		// the bodies of the blocks are moved out of the batch.
+		assert!(blocks.iter().all(|block| block.body.is_none()));
+	}
+
+	#[test]
+	fn checks_every_block_of_a_linear_batch() {
+		let first = incoming_block(1, H256::repeat_byte(1));
+		let mut second = incoming_block(2, first.hash);
+		let third = incoming_block(3, second.hash);
+		// the body of the second block doesn't match its header.
+		second.body = Some(vec![Extrinsic::IncludeData(vec![1])]);
+		let mut blocks = vec![first, second, third];
+
+		let pool = VerificationPool::new(&AcceptAll, 2).unwrap();
+		let (_, handled) = futures::executor::block_on(
+			pool.verify(BlockOrigin::NetworkInitialSync, &mut blocks, None)
+		);
+
+		assert_eq!(outcome(&handled, 0), "verified");
+		assert_eq!(outcome(&handled, 1), "invalid");
+		assert_eq!(outcome(&handled, 2), "checked");
+	}
+
+	#[test]
+	fn worker_panic_is_a_local_failure() {
+		let mut blocks = vec![
+			incoming_block(1, H256::repeat_byte(1)),
+			incoming_block(1, H256::repeat_byte(2)),
+		];
+
+		let pool = VerificationPool::new(&PanicOnVerify, 2).unwrap();
+		let (pool, handled) = futures::executor::block_on(
+			pool.verify(BlockOrigin::NetworkInitialSync, &mut blocks, None)
+		);
+
+		assert_eq!(outcome(&handled, 0), "failed");
+		assert_eq!(outcome(&handled, 1), "failed");
+		// the verifiers which panicked are dropped.
+		assert!(pool.verifiers.is_empty());
+	}
+
+	#[test]
+	fn needs_two_workers() {
+		assert!(VerificationPool::new(&AcceptAll, 0).is_none());
+		assert!(VerificationPool::new(&AcceptAll, 1).is_none());
+	}
+}
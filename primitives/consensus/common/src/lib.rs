@@ -36,12 +36,14 @@ use futures::prelude::*;
 pub use sp_inherents::InherentData;
 
 pub mod block_validation;
+pub mod fork_ranking;
 pub mod offline_tracker;
 pub mod error;
 pub mod block_import;
 mod select_chain;
 pub mod import_queue;
 pub mod evaluation;
+mod metrics;
 
 // block size limit.
 const MAX_BLOCK_SIZE: usize = 4 * 1024 * 1024 + 512;
@@ -49,9 +51,10 @@ const MAX_BLOCK_SIZE: usize = 4 * 1024 * 1024 + 512;
 pub use self::error::Error;
 pub use block_import::{
 	BlockImport, BlockOrigin, ForkChoiceStrategy, ImportedAux, BlockImportParams, BlockCheckParams, ImportResult,
-	JustificationImport, FinalityProofImport,
+	JustificationImport, FinalityProofImport, ImportedState,
 };
 pub use select_chain::SelectChain;
+pub use fork_ranking::ForkRanking;
 
 /// Block status.
 #[derive(Debug, PartialEq, Eq)]
@@ -0,0 +1,94 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of the import queue.
+
+use std::time::Duration;
+use prometheus_endpoint::{
+	register, HistogramOpts, HistogramVec, IntCounterVec, Opts, PrometheusError, Registry,
+};
+
+use crate::block_import::BlockOrigin;
+
+/// Prometheus metrics of a [`BasicQueue`](crate::import_queue::BasicQueue).
+#[derive(Clone)]
+pub(crate) struct Metrics {
+	/// Time spent verifying the blocks, by origin.
+	pub block_verification_time: HistogramVec,
+	/// Time spent importing the verified blocks, by origin. This includes the execution of the
+	/// block, the commit of its state and the fan-out of the notifications.
+	pub block_import_time: HistogramVec,
+	/// Number of blocks processed by the queue, by origin and result.
+	pub blocks_processed: IntCounterVec,
+}
+
+impl Metrics {
+	/// Create and register the metrics with the given registry.
+	pub fn register(registry: &Registry) -> Result<Self, PrometheusError> {
+		Ok(Self {
+			block_verification_time: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"import_queue_block_verification_time",
+						"Time taken to verify a block, in seconds."
+					),
+					&["origin"],
+				)?,
+				registry,
+			)?,
+			block_import_time: register(
+				HistogramVec::new(
+					HistogramOpts::new(
+						"import_queue_block_import_time",
+						"Time taken to import a verified block, in seconds."
+					),
+					&["origin"],
+				)?,
+				registry,
+			)?,
+			blocks_processed: register(
+				IntCounterVec::new(
+					Opts::new(
+						"import_queue_processed_total",
+						"Number of blocks processed by the import queue."
+					),
+					&["origin", "result"],
+				)?,
+				registry,
+			)?,
+		})
+	}
+
+	/// Report the time taken to verify a block of the given origin.
+	pub fn report_verification(&self, origin: &BlockOrigin, time: Duration) {
+		self.block_verification_time
+			.with_label_values(&[origin.metric_label()])
+			.observe(time.as_secs_f64());
+	}
+
+	/// Report the time taken to import a verified block of the given origin.
+	pub fn report_import(&self, origin: &BlockOrigin, time: Duration) {
+		self.block_import_time
+			.with_label_values(&[origin.metric_label()])
+			.observe(time.as_secs_f64());
+	}
+
+	/// Report the processing of a block of the given origin.
+	pub fn report_processed(&self, origin: &BlockOrigin, success: bool) {
+		let result = if success { "success" } else { "failure" };
+		self.blocks_processed.with_label_values(&[origin.metric_label(), result]).inc();
+	}
+}
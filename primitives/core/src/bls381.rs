@@ -0,0 +1,606 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+// tag::description[]
+//! Simple BLS12-381 API.
+//!
+//! Public keys are compressed points of G1 (48 bytes) and signatures are compressed points of
+//! G2 (96 bytes). Signatures of distinct messages can be aggregated into a single signature.
+//!
+//! The BLS12-381 backend needs the standard library, so key pairs are only available with the
+//! `std` feature, and not with `full_crypto` alone.
+// end::description[]
+
+#[cfg(feature = "std")]
+use sp_std::vec::Vec;
+
+use sp_std::cmp::Ordering;
+use codec::{Encode, Decode};
+
+#[cfg(feature = "std")]
+use core::convert::TryFrom;
+#[cfg(feature = "std")]
+use substrate_bip39::seed_from_entropy;
+#[cfg(feature = "std")]
+use bip39::{Mnemonic, Language, MnemonicType};
+#[cfg(feature = "std")]
+use crate::crypto::{Pair as TraitPair, DeriveJunction, SecretStringError};
+#[cfg(feature = "std")]
+use crate::crypto::Ss58Codec;
+#[cfg(feature = "std")]
+use serde::{de, Serializer, Serialize, Deserializer, Deserialize};
+use crate::crypto::{Public as TraitPublic, UncheckedFrom, CryptoType, CryptoTypeId, Derive};
+use sp_runtime_interface::pass_by::PassByInner;
+#[cfg(feature = "std")]
+use bls_signatures::Serialize as _;
+
+/// A secret seed, from which the secret key is derived.
+#[cfg(feature = "std")]
+type Seed = [u8; 32];
+
+/// Length of a compressed public key.
+const PUBLIC_LENGTH: usize = 48;
+
+/// Length of a compressed signature.
+const SIGNATURE_LENGTH: usize = 96;
+
+/// An identifier used to match public keys against BLS12-381 keys.
+pub const CRYPTO_ID: CryptoTypeId = CryptoTypeId(*b"bls3");
+
+/// A public key.
+#[derive(Encode, Decode, PassByInner)]
+pub struct Public(pub [u8; PUBLIC_LENGTH]);
+
+impl Clone for Public {
+	fn clone(&self) -> Self {
+		Public(self.0)
+	}
+}
+
+impl Copy for Public {}
+
+impl Default for Public {
+	fn default() -> Self {
+		Public([0u8; PUBLIC_LENGTH])
+	}
+}
+
+impl PartialEq for Public {
+	fn eq(&self, other: &Self) -> bool {
+		self.0[..] == other.0[..]
+	}
+}
+
+impl Eq for Public {}
+
+impl PartialOrd for Public {
+	fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+		Some(self.cmp(other))
+	}
+}
+
+impl Ord for Public {
+	fn cmp(&self, other: &Self) -> Ordering {
+		self.0[..].cmp(&other.0[..])
+	}
+}
+
+#[cfg(feature = "full_crypto")]
+impl sp_std::hash::Hash for Public {
+	fn hash<H: sp_std::hash::Hasher>(&self, state: &mut H) {
+		sp_std::hash::Hash::hash(&self.0[..], state);
+	}
+}
+
+impl AsRef<[u8]> for Public {
+	fn as_ref(&self) -> &[u8] {
+		&self.0[..]
+	}
+}
+
+impl AsMut<[u8]> for Public {
+	fn as_mut(&mut self) -> &mut [u8] {
+		&mut self.0[..]
+	}
+}
+
+impl sp_std::convert::TryFrom<&[u8]> for Public {
+	type Error = ();
+
+	fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+		if data.len() == PUBLIC_LENGTH {
+			Ok(Self::from_slice(data))
+		} else {
+			Err(())
+		}
+	}
+}
+
+impl From<Public> for [u8; PUBLIC_LENGTH] {
+	fn from(x: Public) -> Self {
+		x.0
+	}
+}
+
+#[cfg(feature = "std")]
+impl From<Pair> for Public {
+	fn from(x: Pair) -> Self {
+		x.public()
+	}
+}
+
+impl UncheckedFrom<[u8; PUBLIC_LENGTH]> for Public {
+	fn unchecked_from(x: [u8; PUBLIC_LENGTH]) -> Self {
+		Public(x)
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::str::FromStr for Public {
+	type Err = crate::crypto::PublicError;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		Self::from_ss58check(s)
+	}
+}
+
+#[cfg(feature = "std")]
+impl std::fmt::Display for Public {
+	fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+		write!(f, "{}", self.to_ss58check())
+	}
+}
+
+impl sp_std::fmt::Debug for Public {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		let s = self.to_ss58check();
+		write!(f, "{} ({}...)", crate::hexdisplay::HexDisplay::from(&self.as_ref()), &s[0..8])
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		Ok(())
+	}
+}
+
+#[cfg(feature = "std")]
+impl Serialize for Public {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+		serializer.serialize_str(&self.to_ss58check())
+	}
+}
+
+#[cfg(feature = "std")]
+impl<'de> Deserialize<'de> for Public {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+		Public::from_ss58check(&String::deserialize(deserializer)?)
+			.map_err(|e| de::Error::custom(format!("{:?}", e)))
+	}
+}
+
+impl Public {
+	/// A new instance from the given 48-byte `data`.
+	///
+	/// NOTE: No checking goes on to ensure this is a real public key. Only use it if
+	/// you are certain that the array actually is a pubkey. GIGO!
+	pub fn from_raw(data: [u8; PUBLIC_LENGTH]) -> Self {
+		Public(data)
+	}
+}
+
+// With `full_crypto`, the crypto traits need the key pairs, which need `std`.
+#[cfg(any(feature = "std", not(feature = "full_crypto")))]
+impl TraitPublic for Public {
+	/// A new instance from the given slice that should be 48 bytes long.
+	///
+	/// NOTE: No checking goes on to ensure this is a real public key. Only use it if
+	/// you are certain that the array actually is a pubkey. GIGO!
+	fn from_slice(data: &[u8]) -> Self {
+		let mut r = [0u8; PUBLIC_LENGTH];
+		r.copy_from_slice(data);
+		Public(r)
+	}
+}
+
+impl Derive for Public {}
+
+/// A signature (a compressed point of G2).
+#[derive(Encode, Decode, PassByInner)]
+pub struct Signature(pub [u8; SIGNATURE_LENGTH]);
+
+impl sp_std::convert::TryFrom<&[u8]> for Signature {
+	type Error = ();
+
+	fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+		if data.len() == SIGNATURE_LENGTH {
+			Ok(Signature::from_slice(data))
+		} else {
+			Err(())
+		}
+	}
+}
+
+#[cfg(feature = "std")]
+impl Serialize for Signature {
+	fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error> where S: Serializer {
+		serializer.serialize_str(&hex::encode(self))
+	}
+}
+
+#[cfg(feature = "std")]
+impl<'de> Deserialize<'de> for Signature {
+	fn deserialize<D>(deserializer: D) -> Result<Self, D::Error> where D: Deserializer<'de> {
+		let signature_hex = hex::decode(&String::deserialize(deserializer)?)
+			.map_err(|e| de::Error::custom(format!("{:?}", e)))?;
+		Ok(Signature::try_from(signature_hex.as_ref())
+			.map_err(|e| de::Error::custom(format!("{:?}", e)))?)
+	}
+}
+
+impl Clone for Signature {
+	fn clone(&self) -> Self {
+		Signature(self.0)
+	}
+}
+
+impl Default for Signature {
+	fn default() -> Self {
+		Signature([0u8; SIGNATURE_LENGTH])
+	}
+}
+
+impl PartialEq for Signature {
+	fn eq(&self, b: &Self) -> bool {
+		self.0[..] == b.0[..]
+	}
+}
+
+impl Eq for Signature {}
+
+impl From<Signature> for [u8; SIGNATURE_LENGTH] {
+	fn from(v: Signature) -> [u8; SIGNATURE_LENGTH] {
+		v.0
+	}
+}
+
+impl AsRef<[u8]> for Signature {
+	fn as_ref(&self) -> &[u8] {
+		&self.0[..]
+	}
+}
+
+impl AsMut<[u8]> for Signature {
+	fn as_mut(&mut self) -> &mut [u8] {
+		&mut self.0[..]
+	}
+}
+
+impl sp_std::fmt::Debug for Signature {
+	#[cfg(feature = "std")]
+	fn fmt(&self, f: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		write!(f, "{}", crate::hexdisplay::HexDisplay::from(&self.as_ref()))
+	}
+
+	#[cfg(not(feature = "std"))]
+	fn fmt(&self, _: &mut sp_std::fmt::Formatter) -> sp_std::fmt::Result {
+		Ok(())
+	}
+}
+
+#[cfg(feature = "full_crypto")]
+impl sp_std::hash::Hash for Signature {
+	fn hash<H: sp_std::hash::Hasher>(&self, state: &mut H) {
+		sp_std::hash::Hash::hash(&self.0[..], state);
+	}
+}
+
+impl Signature {
+	/// A new instance from the given 96-byte `data`.
+	///
+	/// NOTE: No checking goes on to ensure this is a real signature. Only use it if
+	/// you are certain that the array actually is a signature. GIGO!
+	pub fn from_raw(data: [u8; SIGNATURE_LENGTH]) -> Signature {
+		Signature(data)
+	}
+
+	/// A new instance from the given slice that should be 96 bytes long.
+	///
+	/// NOTE: No checking goes on to ensure this is a real signature. Only use it if
+	/// you are certain that the array actually is a signature. GIGO!
+	pub fn from_slice(data: &[u8]) -> Self {
+		let mut r = [0u8; SIGNATURE_LENGTH];
+		r.copy_from_slice(data);
+		Signature(r)
+	}
+}
+
+/// Derive a single hard junction.
+#[cfg(feature = "std")]
+fn derive_hard_junction(secret_seed: &Seed, cc: &[u8; 32]) -> Seed {
+	("BLS12381HDKD", secret_seed, cc).using_encoded(|data| {
+		let mut res = [0u8; 32];
+		res.copy_from_slice(blake2_rfc::blake2b::blake2b(32, &[], data).as_bytes());
+		res
+	})
+}
+
+/// An error when deriving a key.
+#[cfg(feature = "std")]
+pub enum DeriveError {
+	/// A soft key was found in the path (and is unsupported).
+	SoftKeyInPath,
+}
+
+/// A key pair.
+#[cfg(feature = "std")]
+#[derive(Clone)]
+pub struct Pair {
+	secret: bls_signatures::PrivateKey,
+	seed: Seed,
+}
+
+#[cfg(feature = "std")]
+impl TraitPair for Pair {
+	type Public = Public;
+	type Seed = Seed;
+	type Signature = Signature;
+	type DeriveError = DeriveError;
+
+	/// Generate new secure (random) key pair and provide the recovery phrase.
+	///
+	/// You can recover the same key later with `from_phrase`.
+	#[cfg(feature = "std")]
+	fn generate_with_phrase(password: Option<&str>) -> (Pair, String, Seed) {
+		let mnemonic = Mnemonic::new(MnemonicType::Words12, Language::English);
+		let phrase = mnemonic.phrase();
+		let (pair, seed) = Self::from_phrase(phrase, password)
+			.expect("All phrases generated by Mnemonic are valid; qed");
+		(
+			pair,
+			phrase.to_owned(),
+			seed,
+		)
+	}
+
+	/// Generate key pair from given recovery phrase and password.
+	#[cfg(feature = "std")]
+	fn from_phrase(phrase: &str, password: Option<&str>) -> Result<(Pair, Seed), SecretStringError> {
+		let big_seed = seed_from_entropy(
+			Mnemonic::from_phrase(phrase, Language::English)
+				.map_err(|_| SecretStringError::InvalidPhrase)?.entropy(),
+			password.unwrap_or(""),
+		).map_err(|_| SecretStringError::InvalidSeed)?;
+		let mut seed = Seed::default();
+		seed.copy_from_slice(&big_seed[0..32]);
+		Self::from_seed_slice(&big_seed[0..32]).map(|x| (x, seed))
+	}
+
+	/// Make a new key pair from secret seed material.
+	///
+	/// You should never need to use this; generate(), generate_with_phrase
+	fn from_seed(seed: &Seed) -> Pair {
+		Self::from_seed_slice(&seed[..]).expect("seed has valid length; qed")
+	}
+
+	/// Make a new key pair from secret seed material. The slice must be 32 bytes long or it
+	/// will return `None`.
+	///
+	/// The secret key is derived from the seed with the key generation of the signature scheme.
+	///
+	/// You should never need to use this; generate(), generate_with_phrase
+	fn from_seed_slice(seed_slice: &[u8]) -> Result<Pair, SecretStringError> {
+		let seed = Seed::try_from(seed_slice).map_err(|_| SecretStringError::InvalidSeedLength)?;
+		Ok(Pair { secret: bls_signatures::PrivateKey::new(&seed), seed })
+	}
+
+	/// Derive a child key from a series of given junctions.
+	fn derive<Iter: Iterator<Item=DeriveJunction>>(&self,
+		path: Iter,
+		_seed: Option<Seed>,
+	) -> Result<(Pair, Option<Seed>), DeriveError> {
+		let mut acc = self.seed;
+		for j in path {
+			match j {
+				DeriveJunction::Soft(_cc) => return Err(DeriveError::SoftKeyInPath),
+				DeriveJunction::Hard(cc) => acc = derive_hard_junction(&acc, &cc),
+			}
+		}
+		Ok((Self::from_seed(&acc), Some(acc)))
+	}
+
+	/// Get the public key.
+	fn public(&self) -> Public {
+		Public::from_slice(&self.secret.public_key().as_bytes())
+	}
+
+	/// Sign a message.
+	fn sign(&self, message: &[u8]) -> Signature {
+		Signature::from_slice(&self.secret.sign(message).as_bytes())
+	}
+
+	/// Verify a signature on a message. Returns true if the signature is good.
+	fn verify<M: AsRef<[u8]>>(sig: &Self::Signature, message: M, pubkey: &Self::Public) -> bool {
+		Self::verify_weak(&sig.0[..], message.as_ref(), pubkey)
+	}
+
+	/// Verify a signature on a message. Returns true if the signature is good.
+	///
+	/// This doesn't use the type system to ensure that `sig` and `pubkey` are the correct
+	/// size. Use it only if you're coming from byte buffers and need the speed.
+	fn verify_weak<P: AsRef<[u8]>, M: AsRef<[u8]>>(sig: &[u8], message: M, pubkey: P) -> bool {
+		let public_key = match bls_signatures::PublicKey::from_bytes(pubkey.as_ref()) {
+			Ok(pk) => pk,
+			Err(_) => return false,
+		};
+
+		let sig = match bls_signatures::Signature::from_bytes(sig) {
+			Ok(s) => s,
+			Err(_) => return false,
+		};
+
+		public_key.verify(sig, message.as_ref())
+	}
+
+	/// Return a vec filled with raw data.
+	fn to_raw_vec(&self) -> Vec<u8> {
+		self.seed().to_vec()
+	}
+}
+
+#[cfg(feature = "std")]
+impl Pair {
+	/// Get the seed for this key.
+	pub fn seed(&self) -> &Seed {
+		&self.seed
+	}
+}
+
+/// Aggregate the given signatures into a single signature.
+///
+/// Returns `None` if any of the signatures isn't a valid point.
+#[cfg(feature = "std")]
+pub fn aggregate(signatures: &[Signature]) -> Option<Signature> {
+	let signatures = signatures.iter()
+		.map(|sig| bls_signatures::Signature::from_bytes(&sig.0[..]).ok())
+		.collect::<Option<Vec<_>>>()?;
+
+	bls_signatures::aggregate(&signatures)
+		.ok()
+		.map(|sig| Signature::from_slice(&sig.as_bytes()))
+}
+
+/// Verify an aggregated signature of the given messages, the message at each index being
+/// signed by the public key at the same index. Returns true if the signature is good.
+///
+/// The messages must be distinct: nothing proves the possession of the secret keys, so an
+/// aggregated signature of a single message would be open to rogue key attacks.
+#[cfg(feature = "std")]
+pub fn verify_aggregate(signature: &Signature, messages: &[&[u8]], public_keys: &[Public]) -> bool {
+	if messages.len() != public_keys.len() {
+		return false;
+	}
+	let distinct = messages.iter()
+		.enumerate()
+		.all(|(i, message)| !messages[..i].contains(message));
+	if !distinct {
+		return false;
+	}
+
+	let public_keys = match public_keys.iter()
+		.map(|public| bls_signatures::PublicKey::from_bytes(public.as_ref()).ok())
+		.collect::<Option<Vec<_>>>()
+	{
+		Some(public_keys) => public_keys,
+		None => return false,
+	};
+	let signature = match bls_signatures::Signature::from_bytes(&signature.0[..]) {
+		Ok(signature) => signature,
+		Err(_) => return false,
+	};
+
+	bls_signatures::verify_messages(&signature, messages, &public_keys)
+}
+
+#[cfg(any(feature = "std", not(feature = "full_crypto")))]
+impl CryptoType for Public {
+	#[cfg(feature="full_crypto")]
+	type Pair = Pair;
+}
+
+#[cfg(any(feature = "std", not(feature = "full_crypto")))]
+impl CryptoType for Signature {
+	#[cfg(feature="full_crypto")]
+	type Pair = Pair;
+}
+
+#[cfg(feature="std")]
+impl CryptoType for Pair {
+	type Pair = Pair;
+}
+
+#[cfg(test)]
+mod test {
+	use super::*;
+	use crate::crypto::DEV_PHRASE;
+	use serde_json;
+
+	#[test]
+	fn default_phrase_should_be_used() {
+		assert_eq!(
+			Pair::from_string("//Alice///password", None).unwrap().public(),
+			Pair::from_string(&format!("{}//Alice", DEV_PHRASE), Some("password")).unwrap().public(),
+		);
+	}
+
+	#[test]
+	fn seed_and_derive_should_work() {
+		let pair = Pair::from_seed(b"12345678901234567890123456789012");
+		assert_eq!(pair.seed(), b"12345678901234567890123456789012");
+		let path = vec![DeriveJunction::Hard([0u8; 32])];
+		let derived = pair.derive(path.into_iter(), None).ok().unwrap();
+		assert_ne!(derived.0.public(), pair.public());
+		assert!(pair.derive(vec![DeriveJunction::Soft([0u8; 32])].into_iter(), None).is_err());
+	}
+
+	#[test]
+	fn generated_pair_should_work() {
+		let (pair, _) = Pair::generate();
+		let public = pair.public();
+		let message = b"Something important";
+		let signature = pair.sign(&message[..]);
+		assert!(Pair::verify(&signature, &message[..], &public));
+		assert!(!Pair::verify(&signature, b"Something else", &public));
+	}
+
+	#[test]
+	fn aggregated_signature_should_work() {
+		let pairs = (0..3).map(|_| Pair::generate().0).collect::<Vec<_>>();
+		let messages: Vec<&[u8]> = vec![b"first", b"second", b"third"];
+		let signatures = pairs.iter()
+			.zip(&messages)
+			.map(|(pair, message)| pair.sign(message))
+			.collect::<Vec<_>>();
+		let public_keys = pairs.iter().map(|pair| pair.public()).collect::<Vec<_>>();
+
+		let aggregated = aggregate(&signatures).unwrap();
+		assert!(verify_aggregate(&aggregated, &messages, &public_keys));
+		assert!(!verify_aggregate(&aggregated, &messages[..2], &public_keys[..2]));
+		let repeated: Vec<&[u8]> = vec![b"first", b"first", b"third"];
+		assert!(!verify_aggregate(&aggregated, &repeated, &public_keys));
+	}
+
+	#[test]
+	fn ss58check_roundtrip_works() {
+		let pair = Pair::from_seed(b"12345678901234567890123456789012");
+		let public = pair.public();
+		let s = public.to_ss58check();
+		let cmp = Public::from_ss58check(&s).unwrap();
+		assert_eq!(cmp, public);
+	}
+
+	#[test]
+	fn signature_serialization_works() {
+		let pair = Pair::from_seed(b"12345678901234567890123456789012");
+		let message = b"Something important";
+		let signature = pair.sign(&message[..]);
+		let serialized_signature = serde_json::to_string(&signature).unwrap();
+		// Signature is 96 bytes, so 192 chars + 2 quote chars
+		assert_eq!(serialized_signature.len(), 194);
+		let signature = serde_json::from_str(&serialized_signature).unwrap();
+		assert!(Pair::verify(&signature, &message[..], &pair.public()));
+	}
+}
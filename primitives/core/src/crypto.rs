@@ -19,7 +19,6 @@
 // end::description[]
 
 use sp_std::hash::Hash;
-#[cfg(feature = "full_crypto")]
 use sp_std::vec::Vec;
 #[cfg(feature = "std")]
 use sp_std::convert::TryInto;
@@ -916,6 +915,19 @@ impl<'a> TryFrom<&'a str> for KeyTypeId {
 	}
 }
 
+/// An identifier for a specific cryptographic algorithm used by a key pair.
+#[derive(
+	Copy, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode, crate::RuntimeDebug
+)]
+pub struct CryptoTypeId(pub [u8; 4]);
+
+/// A public key tagged with the [`CryptoTypeId`] of the algorithm it belongs to.
+///
+/// Allows to handle keys of different cryptographic algorithms uniformly, e.g. when asking a
+/// key store to sign with a key without knowing its crypto at compile time.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Encode, Decode, crate::RuntimeDebug)]
+pub struct CryptoTypePublicPair(pub CryptoTypeId, pub Vec<u8>);
+
 /// Known key types; this also functions as a global registry of key types for projects wishing to
 /// avoid collisions with each other.
 ///
@@ -36,7 +36,7 @@ use crate::{hashing::blake2_256, crypto::{Pair as TraitPair, DeriveJunction, Sec
 use crate::crypto::Ss58Codec;
 #[cfg(feature = "std")]
 use serde::{de, Serializer, Serialize, Deserializer, Deserialize};
-use crate::crypto::{Public as TraitPublic, UncheckedFrom, CryptoType, Derive};
+use crate::crypto::{Public as TraitPublic, UncheckedFrom, CryptoType, CryptoTypeId, Derive};
 #[cfg(feature = "full_crypto")]
 use secp256k1::{PublicKey, SecretKey};
 
@@ -46,6 +46,9 @@ use secp256k1::{PublicKey, SecretKey};
 #[cfg(feature = "full_crypto")]
 type Seed = [u8; 32];
 
+/// An identifier used to match public keys against ECDSA keys.
+pub const CRYPTO_ID: CryptoTypeId = CryptoTypeId(*b"ecds");
+
 /// The ECDSA public key.
 #[derive(Clone, Encode, Decode)]
 pub enum Public {
@@ -357,6 +360,15 @@ impl Signature {
 		secp256k1::recover(&message, &sig.0, &sig.1).ok()
 			.map(|recovered| Public::from_full(recovered.serialize()))
 	}
+
+	/// Recover the compressed public key from this signature and a message.
+	#[cfg(feature = "full_crypto")]
+	pub fn recover_compressed<M: AsRef<[u8]>>(&self, message: M) -> Option<Public> {
+		let message = secp256k1::Message::parse(&blake2_256(message.as_ref()));
+		let sig: (_, _) = self.try_into().ok()?;
+		secp256k1::recover(&message, &sig.0, &sig.1).ok()
+			.map(|recovered| Public::Compressed(recovered.serialize_compressed()))
+	}
 }
 
 #[cfg(feature = "full_crypto")]
@@ -638,6 +650,18 @@ mod test {
 		assert!(!Pair::verify(&signature, "Other message", &public));
 	}
 
+	#[test]
+	fn recover_compressed_works() {
+		let pair = Pair::from_seed(b"12345678901234567890123456789012");
+		let message = b"Something important";
+		let signature = pair.sign(&message[..]);
+
+		let recovered = signature.recover_compressed(&message[..]).unwrap();
+		assert_eq!(recovered, pair.public().into_compressed().unwrap());
+		assert_eq!(signature.recover(&message[..]).unwrap(), pair.public());
+		assert_ne!(signature.recover_compressed(b"Other message").unwrap(), recovered);
+	}
+
 	#[test]
 	fn generate_with_phrase_recovery_possible() {
 		let (pair1, phrase, _) = Pair::generate_with_phrase(None);
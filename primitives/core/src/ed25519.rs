@@ -38,7 +38,7 @@ use crate::crypto::{Pair as TraitPair, DeriveJunction, SecretStringError};
 use crate::crypto::Ss58Codec;
 #[cfg(feature = "std")]
 use serde::{de, Serializer, Serialize, Deserializer, Deserialize};
-use crate::{crypto::{Public as TraitPublic, UncheckedFrom, CryptoType, Derive}};
+use crate::{crypto::{Public as TraitPublic, UncheckedFrom, CryptoType, CryptoTypeId, Derive}};
 use sp_runtime_interface::pass_by::PassByInner;
 use sp_std::ops::Deref;
 
@@ -48,6 +48,9 @@ use sp_std::ops::Deref;
 #[cfg(feature = "full_crypto")]
 type Seed = [u8; 32];
 
+/// An identifier used to match public keys against ed25519 keys.
+pub const CRYPTO_ID: CryptoTypeId = CryptoTypeId(*b"ed25");
+
 /// A public key.
 #[cfg_attr(feature = "full_crypto", derive(Hash))]
 #[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Encode, Decode, Default, PassByInner)]
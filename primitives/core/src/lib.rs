@@ -60,6 +60,7 @@ pub mod u32_trait;
 pub mod ed25519;
 pub mod sr25519;
 pub mod ecdsa;
+pub mod bls381;
 pub mod hash;
 mod hasher;
 pub mod offchain;
@@ -68,6 +69,8 @@ pub mod uint;
 mod changes_trie;
 #[cfg(feature = "std")]
 pub mod traits;
+#[cfg(feature = "std")]
+pub mod vrf;
 pub mod testing;
 
 #[cfg(test)]
@@ -111,8 +114,11 @@ impl ExecutionContext {
 		match self {
 			Importing | Syncing | BlockConstruction =>
 				offchain::Capabilities::none(),
-			// Enable keystore by default for offchain calls. CC @bkchr
-			OffchainCall(None) => [offchain::Capability::Keystore][..].into(),
+			// Enable keystore and transaction pool by default for offchain calls. CC @bkchr
+			OffchainCall(None) => [
+				offchain::Capability::Keystore,
+				offchain::Capability::TransactionPool,
+			][..].into(),
 			OffchainCall(Some((_, capabilities))) => *capabilities,
 		}
 	}
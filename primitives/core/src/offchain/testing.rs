@@ -72,6 +72,8 @@ pub struct OffchainState {
 	pub persistent_storage: InMemOffchainStorage,
 	/// Local storage
 	pub local_storage: InMemOffchainStorage,
+	/// Current timestamp, only advanced by `sleep_until`.
+	pub timestamp: Timestamp,
 }
 
 impl OffchainState {
@@ -155,11 +157,12 @@ impl offchain::Externalities for TestOffchainExt {
 	}
 
 	fn timestamp(&mut self) -> Timestamp {
-		unimplemented!("not needed in tests so far")
+		self.0.read().timestamp
 	}
 
-	fn sleep_until(&mut self, _deadline: Timestamp) {
-		unimplemented!("not needed in tests so far")
+	fn sleep_until(&mut self, deadline: Timestamp) {
+		let mut state = self.0.write();
+		state.timestamp = state.timestamp.max(deadline);
 	}
 
 	fn random_seed(&mut self) -> [u8; 32] {
@@ -17,13 +17,15 @@
 //! Types that should only be used for testing!
 
 #[cfg(feature = "std")]
-use crate::{ed25519, sr25519, crypto::{Public, Pair}};
+use crate::{bls381, ecdsa, ed25519, sr25519, crypto::{Public, Pair}};
 use crate::crypto::KeyTypeId;
 
 /// Key type for generic Ed25519 key.
 pub const ED25519: KeyTypeId = KeyTypeId(*b"ed25");
 /// Key type for generic Sr 25519 key.
 pub const SR25519: KeyTypeId = KeyTypeId(*b"sr25");
+/// Key type for generic BLS12-381 key.
+pub const BLS381: KeyTypeId = KeyTypeId(*b"bls3");
 
 /// A keystore implementation usable in tests.
 #[cfg(feature = "std")]
@@ -119,6 +121,84 @@ impl crate::traits::BareCryptoStore for KeyStore {
 			)
 	}
 
+	fn ecdsa_public_keys(&self, id: KeyTypeId) -> Vec<ecdsa::Public> {
+		self.keys.get(&id)
+			.map(|keys|
+				keys.values()
+					.filter_map(|s| ecdsa::Pair::from_string(s, None).ok())
+					.map(|p| p.public())
+					.filter(|p| keys.contains_key(p.as_slice()))
+					.collect()
+			)
+			.unwrap_or_default()
+	}
+
+	fn ecdsa_generate_new(
+		&mut self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<ecdsa::Public, String> {
+		match seed {
+			Some(seed) => {
+				let pair = ecdsa::Pair::from_string(seed, None).expect("Generates an `ecdsa` pair.");
+				self.keys.entry(id).or_default().insert(pair.public().to_raw_vec(), seed.into());
+				Ok(pair.public())
+			},
+			None => {
+				let (pair, phrase, _) = ecdsa::Pair::generate_with_phrase(None);
+				self.keys.entry(id).or_default().insert(pair.public().to_raw_vec(), phrase);
+				Ok(pair.public())
+			}
+		}
+	}
+
+	fn ecdsa_key_pair(&self, id: KeyTypeId, pub_key: &ecdsa::Public) -> Option<ecdsa::Pair> {
+		self.keys.get(&id)
+			.and_then(|inner|
+				inner.get(pub_key.as_slice())
+					.map(|s| ecdsa::Pair::from_string(s, None).expect("`ecdsa` seed slice is valid"))
+			)
+	}
+
+	fn bls381_public_keys(&self, id: KeyTypeId) -> Vec<bls381::Public> {
+		self.keys.get(&id)
+			.map(|keys|
+				keys.values()
+					.filter_map(|s| bls381::Pair::from_string(s, None).ok())
+					.map(|p| p.public())
+					.filter(|p| keys.contains_key(p.as_slice()))
+					.collect()
+			)
+			.unwrap_or_default()
+	}
+
+	fn bls381_generate_new(
+		&mut self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<bls381::Public, String> {
+		match seed {
+			Some(seed) => {
+				let pair = bls381::Pair::from_string(seed, None).expect("Generates a `bls381` pair.");
+				self.keys.entry(id).or_default().insert(pair.public().to_raw_vec(), seed.into());
+				Ok(pair.public())
+			},
+			None => {
+				let (pair, phrase, _) = bls381::Pair::generate_with_phrase(None);
+				self.keys.entry(id).or_default().insert(pair.public().to_raw_vec(), phrase);
+				Ok(pair.public())
+			}
+		}
+	}
+
+	fn bls381_key_pair(&self, id: KeyTypeId, pub_key: &bls381::Public) -> Option<bls381::Pair> {
+		self.keys.get(&id)
+			.and_then(|inner|
+				inner.get(pub_key.as_slice())
+					.map(|s| bls381::Pair::from_string(s, None).expect("`bls381` seed slice is valid"))
+			)
+	}
+
 	fn insert_unknown(&mut self, id: KeyTypeId, suri: &str, public: &[u8]) -> Result<(), ()> {
 		self.keys.entry(id).or_default().insert(public.to_owned(), suri.to_string());
 		Ok(())
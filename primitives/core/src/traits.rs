@@ -16,14 +16,22 @@
 
 //! Shareable Substrate traits.
 
-use crate::{crypto::KeyTypeId, ed25519, sr25519};
+use crate::{
+	crypto::{KeyTypeId, CryptoTypePublicPair, Pair, Public},
+	bls381, ecdsa, ed25519, sr25519,
+	vrf::{self, VRFTranscriptData, VRFSignature},
+};
 
 use std::{
+	convert::TryFrom,
 	fmt::{Debug, Display},
 	panic::UnwindSafe,
+	pin::Pin,
 	sync::Arc,
 };
 
+use futures::future::{self, Future};
+
 pub use sp_externalities::{Externalities, ExternalitiesExt};
 
 /// Something that generates, stores and provides access to keys.
@@ -59,6 +67,105 @@ pub trait BareCryptoStore: Send + Sync {
 	/// Returns the ed25519 key pair for the given key type and public key combination.
 	fn ed25519_key_pair(&self, id: KeyTypeId, pub_key: &ed25519::Public) -> Option<ed25519::Pair>;
 
+	/// Returns all ecdsa public keys for the given key type.
+	fn ecdsa_public_keys(&self, id: KeyTypeId) -> Vec<ecdsa::Public>;
+	/// Generate a new ecdsa key pair for the given key type and an optional seed.
+	///
+	/// If the given seed is `Some(_)`, the key pair will only be stored in memory.
+	///
+	/// Returns the public key of the generated key pair.
+	fn ecdsa_generate_new(
+		&mut self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<ecdsa::Public, String>;
+
+	/// Returns the ecdsa key pair for the given key type and public key combination.
+	fn ecdsa_key_pair(&self, id: KeyTypeId, pub_key: &ecdsa::Public) -> Option<ecdsa::Pair>;
+
+	/// Returns all bls381 public keys for the given key type.
+	fn bls381_public_keys(&self, id: KeyTypeId) -> Vec<bls381::Public>;
+	/// Generate a new bls381 key pair for the given key type and an optional seed.
+	///
+	/// If the given seed is `Some(_)`, the key pair will only be stored in memory.
+	///
+	/// Returns the public key of the generated key pair.
+	fn bls381_generate_new(
+		&mut self,
+		id: KeyTypeId,
+		seed: Option<&str>,
+	) -> Result<bls381::Public, String>;
+
+	/// Returns the bls381 key pair for the given key type and public key combination.
+	fn bls381_key_pair(&self, id: KeyTypeId, pub_key: &bls381::Public) -> Option<bls381::Pair>;
+
+	/// Returns all public keys of all supported cryptos for the given key type.
+	///
+	/// Each key is tagged with the crypto it belongs to, see [`CryptoTypePublicPair`].
+	fn keys(&self, id: KeyTypeId) -> Vec<CryptoTypePublicPair> {
+		let sr25519 = self.sr25519_public_keys(id)
+			.into_iter()
+			.map(|k| CryptoTypePublicPair(sr25519::CRYPTO_ID, k.to_raw_vec()));
+		let ed25519 = self.ed25519_public_keys(id)
+			.into_iter()
+			.map(|k| CryptoTypePublicPair(ed25519::CRYPTO_ID, k.to_raw_vec()));
+		let ecdsa = self.ecdsa_public_keys(id)
+			.into_iter()
+			.map(|k| CryptoTypePublicPair(ecdsa::CRYPTO_ID, k.to_raw_vec()));
+		let bls381 = self.bls381_public_keys(id)
+			.into_iter()
+			.map(|k| CryptoTypePublicPair(bls381::CRYPTO_ID, k.to_raw_vec()));
+
+		sr25519.chain(ed25519).chain(ecdsa).chain(bls381).collect()
+	}
+
+	/// Sign the given message with the key identified by the given key type and public key.
+	///
+	/// In contrast to the crypto specific key pair getters, this allows signing without knowing
+	/// the crypto of the key at compile time. The signature is produced asynchronously, so that a
+	/// keystore signing out of process doesn't block the caller.
+	///
+	/// Resolves to the raw bytes of the signature.
+	fn sign_with(
+		&self,
+		id: KeyTypeId,
+		key: &CryptoTypePublicPair,
+		msg: &[u8],
+	) -> SignatureFuture<Vec<u8>> {
+		Box::pin(future::ready(sign_with_key_pair(self, id, key, msg)))
+	}
+
+	/// Sign the given message with the key identified by the given key type and public key, and
+	/// wait for the signature.
+	///
+	/// This is for the callers which cannot wait asynchronously, like the host functions called
+	/// by the runtime in offchain workers, which run on their own threads. Unlike waiting for
+	/// [`BareCryptoStore::sign_with`], it doesn't need a futures executor.
+	fn sign_with_blocking(
+		&self,
+		id: KeyTypeId,
+		key: &CryptoTypePublicPair,
+		msg: &[u8],
+	) -> Result<Vec<u8>, String> {
+		sign_with_key_pair(self, id, key, msg)
+	}
+
+	/// Sign the transcript described by `transcript_data` with the VRF of the given sr25519 key.
+	///
+	/// Like [`BareCryptoStore::sign_with`], this doesn't require the key pair to be available
+	/// locally.
+	fn sr25519_vrf_sign(
+		&self,
+		id: KeyTypeId,
+		public: &sr25519::Public,
+		transcript_data: VRFTranscriptData,
+	) -> SignatureFuture<VRFSignature> {
+		let signature = self.sr25519_key_pair(id, public)
+			.map(|pair| vrf::sr25519_vrf_sign(&pair, transcript_data))
+			.ok_or_else(|| format!("No key pair found for public key {:?}", public));
+		Box::pin(future::ready(signature))
+	}
+
 	/// Insert a new key. This doesn't require any known of the crypto; but a public key must be
 	/// manually provided.
 	///
@@ -71,6 +178,54 @@ pub trait BareCryptoStore: Send + Sync {
 	fn password(&self) -> Option<&str>;
 }
 
+/// Future resolving to the result of a signing operation of a [`BareCryptoStore`].
+pub type SignatureFuture<T> = Pin<Box<dyn Future<Output = Result<T, String>> + Send>>;
+
+/// Sign the given message with the key pair of the store identified by the given key type and
+/// public key.
+///
+/// This is how [`BareCryptoStore::sign_with`] signs by default.
+pub fn sign_with_key_pair<S: BareCryptoStore + ?Sized>(
+	store: &S,
+	id: KeyTypeId,
+	key: &CryptoTypePublicPair,
+	msg: &[u8],
+) -> Result<Vec<u8>, String> {
+	let not_found = || format!("No key pair found for public key {:?}", key);
+
+	match key.0 {
+		sr25519::CRYPTO_ID => {
+			let pub_key = sr25519::Public::try_from(key.1.as_slice())
+				.map_err(|()| format!("Invalid sr25519 public key {:?}", key.1))?;
+			store.sr25519_key_pair(id, &pub_key)
+				.map(|pair| pair.sign(msg).as_ref().to_vec())
+				.ok_or_else(not_found)
+		},
+		ed25519::CRYPTO_ID => {
+			let pub_key = ed25519::Public::try_from(key.1.as_slice())
+				.map_err(|()| format!("Invalid ed25519 public key {:?}", key.1))?;
+			store.ed25519_key_pair(id, &pub_key)
+				.map(|pair| pair.sign(msg).as_ref().to_vec())
+				.ok_or_else(not_found)
+		},
+		ecdsa::CRYPTO_ID => {
+			let pub_key = ecdsa::Public::try_from(key.1.as_slice())
+				.map_err(|()| format!("Invalid ecdsa public key {:?}", key.1))?;
+			store.ecdsa_key_pair(id, &pub_key)
+				.map(|pair| pair.sign(msg).as_ref().to_vec())
+				.ok_or_else(not_found)
+		},
+		bls381::CRYPTO_ID => {
+			let pub_key = bls381::Public::try_from(key.1.as_slice())
+				.map_err(|()| format!("Invalid bls381 public key {:?}", key.1))?;
+			store.bls381_key_pair(id, &pub_key)
+				.map(|pair| pair.sign(msg).as_ref().to_vec())
+				.ok_or_else(not_found)
+		},
+		_ => Err(format!("Unsupported crypto type {:?}", key.0)),
+	}
+}
+
 /// A pointer to the key store.
 pub type BareCryptoStorePtr = Arc<parking_lot::RwLock<dyn BareCryptoStore>>;
 
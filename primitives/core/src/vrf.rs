@@ -0,0 +1,69 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! VRF signing with sr25519 keys held by a keystore.
+//!
+//! The transcript to sign is described by [`VRFTranscriptData`] rather than built directly, so
+//! that it can be sent to keystores holding their keys out of process.
+
+use merlin::Transcript;
+pub use schnorrkel::vrf::{VRFOutput, VRFProof};
+
+/// Value of an item of a VRF transcript.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum VRFTranscriptValue {
+	/// Raw bytes.
+	Bytes(Vec<u8>),
+	/// A number, appended as its little endian bytes.
+	U64(u64),
+}
+
+/// Label and items of a VRF transcript.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct VRFTranscriptData {
+	/// Label of the transcript.
+	pub label: &'static [u8],
+	/// Labeled items appended to the transcript, in order.
+	pub items: Vec<(&'static [u8], VRFTranscriptValue)>,
+}
+
+/// Output and proof of a VRF signature.
+#[derive(Clone, Debug)]
+pub struct VRFSignature {
+	/// The VRF output.
+	pub output: VRFOutput,
+	/// The proof that the output was computed with the signing key.
+	pub proof: VRFProof,
+}
+
+/// Builds the transcript described by the given data.
+pub fn make_transcript(data: VRFTranscriptData) -> Transcript {
+	let mut transcript = Transcript::new(data.label);
+	for (label, value) in data.items {
+		match value {
+			VRFTranscriptValue::Bytes(bytes) => transcript.append_message(label, &bytes),
+			VRFTranscriptValue::U64(value) => transcript.append_message(label, &value.to_le_bytes()),
+		}
+	}
+	transcript
+}
+
+/// Signs the transcript described by the given data with the VRF of `pair`.
+pub fn sr25519_vrf_sign(pair: &crate::sr25519::Pair, data: VRFTranscriptData) -> VRFSignature {
+	let keypair: &schnorrkel::Keypair = pair.as_ref();
+	let (inout, proof, _) = keypair.vrf_sign(make_transcript(data));
+	VRFSignature { output: inout.to_output(), proof }
+}
@@ -21,7 +21,7 @@
 //!
 //! It is required that each extension implements the [`Extension`] trait.
 
-use std::{collections::HashMap, any::{Any, TypeId}, ops::DerefMut};
+use std::{collections::{HashMap, hash_map::Entry}, any::{Any, TypeId}, ops::DerefMut};
 
 /// Marker trait for types that should be registered as [`Externalities`](crate::Externalities) extension.
 ///
@@ -87,6 +87,22 @@ pub trait ExtensionStore {
 	/// It is advised to use [`ExternalitiesExt::extension`](crate::ExternalitiesExt::extension)
 	/// instead of this function to get type system support and automatic type downcasting.
 	fn extension_by_type_id(&mut self, type_id: TypeId) -> Option<&mut dyn Any>;
+
+	/// Register the given `extension` with the given `type_id`.
+	///
+	/// Returns an error if an extension with this `type_id` is already registered, or if
+	/// extensions are not supported.
+	fn register_extension_with_type_id(
+		&mut self,
+		type_id: TypeId,
+		extension: Box<dyn Extension>,
+	) -> Result<(), crate::Error>;
+
+	/// Deregister and drop the extension registered with the given `type_id`.
+	///
+	/// Returns an error if no extension is registered with this `type_id`, or if extensions are
+	/// not supported.
+	fn deregister_extension_by_type_id(&mut self, type_id: TypeId) -> Result<(), crate::Error>;
 }
 
 /// Stores extensions that should be made available through the externalities.
@@ -106,10 +122,32 @@ impl Extensions {
 		self.extensions.insert(ext.type_id(), Box::new(ext));
 	}
 
+	/// Register the given boxed extension with the given `type_id`.
+	///
+	/// Returns an error if an extension with this `type_id` is already registered.
+	pub fn register_with_type_id(
+		&mut self,
+		type_id: TypeId,
+		extension: Box<dyn Extension>,
+	) -> Result<(), crate::Error> {
+		match self.extensions.entry(type_id) {
+			Entry::Vacant(vacant) => {
+				vacant.insert(extension);
+				Ok(())
+			},
+			Entry::Occupied(_) => Err(crate::Error::ExtensionAlreadyRegistered),
+		}
+	}
+
 	/// Return a mutable reference to the requested extension.
 	pub fn get_mut(&mut self, ext_type_id: TypeId) -> Option<&mut dyn Any> {
 		self.extensions.get_mut(&ext_type_id).map(DerefMut::deref_mut).map(Extension::as_mut_any)
 	}
+
+	/// Deregister the extension with the given `type_id` and return it.
+	pub fn deregister(&mut self, type_id: TypeId) -> Option<Box<dyn Extension>> {
+		self.extensions.remove(&type_id)
+	}
 }
 
 #[cfg(test)]
@@ -134,4 +172,16 @@ mod tests {
 
 		assert_eq!(ext_ty.0, 1);
 	}
+
+	#[test]
+	fn register_with_type_id_and_deregister_extension() {
+		let mut exts = Extensions::new();
+		let type_id = TypeId::of::<DummyExt>();
+		assert!(exts.register_with_type_id(type_id, Box::new(DummyExt(1))).is_ok());
+		assert!(exts.register_with_type_id(type_id, Box::new(DummyExt(2))).is_err());
+
+		assert!(exts.deregister(type_id).is_some());
+		assert!(exts.get_mut(type_id).is_none());
+		assert!(exts.deregister(type_id).is_none());
+	}
 }
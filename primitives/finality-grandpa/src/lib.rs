@@ -24,7 +24,7 @@ extern crate alloc;
 #[cfg(feature = "std")]
 use serde::Serialize;
 use codec::{Encode, Decode, Input, Codec};
-use sp_runtime::{ConsensusEngineId, RuntimeDebug};
+use sp_runtime::{ConsensusEngineId, RuntimeDebug, traits::NumberFor};
 use sp_std::borrow::Cow;
 use sp_std::vec::Vec;
 
@@ -156,6 +156,137 @@ impl<N: Codec> ConsensusLog<N> {
 	}
 }
 
+/// A prevote for a block and its ancestors.
+///
+/// Encoded the same way as the prevotes of the `finality-grandpa` crate, so that the signatures
+/// of the voters can be checked by the runtime.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub struct Prevote<H, N> {
+	/// The target block's hash.
+	pub target_hash: H,
+	/// The target block's number.
+	pub target_number: N,
+}
+
+/// A precommit for a block and its ancestors.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub struct Precommit<H, N> {
+	/// The target block's hash.
+	pub target_hash: H,
+	/// The target block's number.
+	pub target_number: N,
+}
+
+/// A vote signed by a voter. The variants have the same indices as the ones of the message of
+/// the `finality-grandpa` crate.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub enum Message<H, N> {
+	/// A prevote message.
+	#[codec(index = "0")]
+	Prevote(Prevote<H, N>),
+	/// A precommit message.
+	#[codec(index = "1")]
+	Precommit(Precommit<H, N>),
+}
+
+/// Two different votes of the same kind cast by an authority in the same round.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub struct VoteEquivocation<V> {
+	/// The round in which the votes were cast.
+	pub round_number: RoundNumber,
+	/// The authority that cast both votes.
+	pub identity: AuthorityId,
+	/// The first vote and its signature.
+	pub first: (V, AuthoritySignature),
+	/// The second vote and its signature.
+	pub second: (V, AuthoritySignature),
+}
+
+/// An equivocation of a GRANDPA authority, either on prevotes or on precommits.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub enum Equivocation<H, N> {
+	/// Two different prevotes in the same round.
+	Prevote(VoteEquivocation<Prevote<H, N>>),
+	/// Two different precommits in the same round.
+	Precommit(VoteEquivocation<Precommit<H, N>>),
+}
+
+/// Proof that an authority of the given set has equivocated, reported to the runtime so that
+/// the offence can be slashed.
+#[derive(Clone, Eq, PartialEq, Encode, Decode, RuntimeDebug)]
+pub struct EquivocationProof<H, N> {
+	/// The id of the authority set the offender belongs to.
+	pub set_id: SetId,
+	/// The equivocation itself.
+	pub equivocation: Equivocation<H, N>,
+}
+
+impl<H, N> EquivocationProof<H, N> {
+	/// Create a new proof of an equivocation committed in the given set.
+	pub fn new(set_id: SetId, equivocation: Equivocation<H, N>) -> Self {
+		EquivocationProof { set_id, equivocation }
+	}
+
+	/// The round in which the equivocation happened.
+	pub fn round_number(&self) -> RoundNumber {
+		match self.equivocation {
+			Equivocation::Prevote(ref equivocation) => equivocation.round_number,
+			Equivocation::Precommit(ref equivocation) => equivocation.round_number,
+		}
+	}
+
+	/// The authority that equivocated.
+	pub fn offender(&self) -> &AuthorityId {
+		match self.equivocation {
+			Equivocation::Prevote(ref equivocation) => &equivocation.identity,
+			Equivocation::Precommit(ref equivocation) => &equivocation.identity,
+		}
+	}
+}
+
+/// Encode a message the way the voters sign it.
+pub fn localized_payload<H: Encode, N: Encode>(
+	round: RoundNumber,
+	set_id: SetId,
+	message: &Message<H, N>,
+) -> Vec<u8> {
+	(message, round, set_id).encode()
+}
+
+/// Check that the given proof is valid: the two votes are different and have both been signed by
+/// the offender, in the same round of the same set.
+pub fn check_equivocation_proof<H, N>(proof: EquivocationProof<H, N>) -> bool
+	where H: Encode + PartialEq, N: Encode + PartialEq
+{
+	use app_crypto::RuntimeAppPublic;
+
+	fn check<H: Encode, N: Encode, V: PartialEq>(
+		set_id: SetId,
+		equivocation: VoteEquivocation<V>,
+		into_message: impl Fn(V) -> Message<H, N>,
+	) -> bool {
+		let VoteEquivocation { round_number, identity, first, second } = equivocation;
+		if first.0 == second.0 {
+			return false;
+		}
+
+		let signed_by_offender = |(vote, signature): (V, AuthoritySignature)| {
+			let payload = localized_payload(round_number, set_id, &into_message(vote));
+			identity.verify(&payload, &signature)
+		};
+
+		signed_by_offender(first) && signed_by_offender(second)
+	}
+
+	let set_id = proof.set_id;
+	match proof.equivocation {
+		Equivocation::Prevote(equivocation) =>
+			check(set_id, equivocation, Message::Prevote),
+		Equivocation::Precommit(equivocation) =>
+			check(set_id, equivocation, Message::Precommit),
+	}
+}
+
 /// WASM function call to check for pending changes.
 pub const PENDING_CHANGE_CALL: &str = "grandpa_pending_change";
 /// WASM function call to get current GRANDPA authorities.
@@ -220,7 +351,7 @@ sp_api::decl_runtime_apis! {
 	/// applied in the runtime after those N blocks have passed.
 	///
 	/// The consensus protocol will coordinate the handoff externally.
-	#[api_version(2)]
+	#[api_version(3)]
 	pub trait GrandpaApi {
 		/// Get the current GRANDPA authorities and weights. This should not change except
 		/// for when changes are scheduled and the corresponding delay has passed.
@@ -229,5 +360,14 @@ sp_api::decl_runtime_apis! {
 		/// used to finalize descendants of this block (B+1, B+2, ...). The block B itself
 		/// is finalized by the authorities from block B-1.
 		fn grandpa_authorities() -> AuthorityList;
+
+		/// Submit an unsigned extrinsic reporting the given equivocation to the transaction
+		/// pool, so that the offender can be slashed. Only useful in an offchain context.
+		///
+		/// Returns `None` if the extrinsic couldn't be submitted, e.g. because the runtime
+		/// doesn't handle equivocation reports.
+		fn submit_report_equivocation_extrinsic(
+			equivocation_proof: EquivocationProof<Block::Hash, NumberFor<Block>>,
+		) -> Option<()>;
 	}
 }
@@ -0,0 +1,98 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Batch/parallel verification of signatures.
+
+use sp_core::{crypto::Pair, ed25519, sr25519};
+use rayon::prelude::*;
+
+/// A signature queued for verification.
+enum BatchItem {
+	Ed25519(ed25519::Signature, ed25519::Public, Vec<u8>),
+	Sr25519(sr25519::Signature, sr25519::Public, Vec<u8>),
+}
+
+impl BatchItem {
+	fn verify(&self) -> bool {
+		match self {
+			BatchItem::Ed25519(signature, public, message) =>
+				ed25519::Pair::verify(signature, message, public),
+			BatchItem::Sr25519(signature, public, message) =>
+				sr25519::Pair::verify(signature, message, public),
+		}
+	}
+}
+
+/// Queues signatures and verifies them all at once, in parallel.
+#[derive(Default)]
+pub struct BatchVerifier {
+	items: Vec<BatchItem>,
+}
+
+impl BatchVerifier {
+	/// Queue the given `ed25519` signature for verification.
+	pub fn push_ed25519(
+		&mut self,
+		signature: ed25519::Signature,
+		public: ed25519::Public,
+		message: Vec<u8>,
+	) {
+		self.items.push(BatchItem::Ed25519(signature, public, message));
+	}
+
+	/// Queue the given `sr25519` signature for verification.
+	pub fn push_sr25519(
+		&mut self,
+		signature: sr25519::Signature,
+		public: sr25519::Public,
+		message: Vec<u8>,
+	) {
+		self.items.push(BatchItem::Sr25519(signature, public, message));
+	}
+
+	/// Verify all the queued signatures and clear the queue.
+	///
+	/// Returns `true` when all the signatures are valid.
+	pub fn verify_and_clear(&mut self) -> bool {
+		let items = std::mem::replace(&mut self.items, Vec::new());
+		items.par_iter().all(BatchItem::verify)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn verifies_all_queued_signatures() {
+		let ed25519 = ed25519::Pair::from_string("//Alice", None).unwrap();
+		let sr25519 = sr25519::Pair::from_string("//Bob", None).unwrap();
+		let mut verifier = BatchVerifier::default();
+
+		for i in 0..16u8 {
+			let message = vec![i];
+			verifier.push_ed25519(ed25519.sign(&message), ed25519.public(), message.clone());
+			verifier.push_sr25519(sr25519.sign(&message), sr25519.public(), message);
+		}
+		assert!(verifier.verify_and_clear());
+
+		verifier.push_ed25519(ed25519.sign(b"message"), ed25519.public(), b"other".to_vec());
+		assert!(!verifier.verify_and_clear());
+
+		// The queue was cleared by the previous verification.
+		assert!(verifier.verify_and_clear());
+	}
+}
@@ -33,9 +33,12 @@ use sp_std::vec::Vec;
 #[cfg(feature = "std")]
 use sp_std::ops::Deref;
 
+#[cfg(feature = "std")]
+use std::convert::TryFrom;
+
 #[cfg(feature = "std")]
 use sp_core::{
-	crypto::Pair,
+	crypto::{Pair, Public, CryptoTypePublicPair},
 	traits::KeystoreExt,
 	offchain::{OffchainExt, TransactionPoolExt},
 	hexdisplay::HexDisplay,
@@ -43,7 +46,7 @@ use sp_core::{
 };
 
 use sp_core::{
-	crypto::KeyTypeId, ed25519, sr25519, H256, LogLevel,
+	crypto::KeyTypeId, ed25519, sr25519, bls381, H256, LogLevel,
 	offchain::{
 		Timestamp, HttpRequestId, HttpRequestStatus, HttpError, StorageKind, OpaqueNetworkState,
 	},
@@ -59,6 +62,21 @@ use codec::{Encode, Decode};
 #[cfg(feature = "std")]
 use sp_externalities::{ExternalitiesExt, Externalities};
 
+#[cfg(feature = "std")]
+mod batch_verifier;
+
+#[cfg(feature = "std")]
+use batch_verifier::BatchVerifier;
+
+#[cfg(feature = "std")]
+sp_externalities::decl_extension! {
+	/// The signatures queued for batch verification.
+	///
+	/// Registered by [`crypto::start_batch_verify`] and consumed by
+	/// [`crypto::finish_batch_verify`].
+	pub struct VerificationExt(BatchVerifier);
+}
+
 /// Error verifying ECDSA signature
 #[derive(Encode, Decode)]
 pub enum EcdsaVerifyError {
@@ -389,11 +407,13 @@ pub trait Crypto {
 		pub_key: &ed25519::Public,
 		msg: &[u8],
 	) -> Option<ed25519::Signature> {
+		let key = CryptoTypePublicPair(ed25519::CRYPTO_ID, pub_key.to_raw_vec());
 		self.extension::<KeystoreExt>()
 			.expect("No `keystore` associated for the current context!")
 			.read()
-			.ed25519_key_pair(id, &pub_key)
-			.map(|k| k.sign(msg))
+			.sign_with_blocking(id, &key, msg)
+			.ok()
+			.and_then(|signature| ed25519::Signature::try_from(&signature[..]).ok())
 	}
 
 	/// Verify an `ed25519` signature.
@@ -408,6 +428,28 @@ pub trait Crypto {
 		ed25519::Pair::verify(sig, msg, pub_key)
 	}
 
+	/// Register an `ed25519` signature for batch verification.
+	///
+	/// If no batch was started with [`start_batch_verify`], the signature is verified
+	/// immediately. Otherwise it is queued, and the result of the whole batch is returned by
+	/// [`finish_batch_verify`].
+	///
+	/// Returns `true` when the signature is valid or was queued.
+	fn ed25519_batch_verify(
+		&mut self,
+		sig: &ed25519::Signature,
+		msg: &[u8],
+		pub_key: &ed25519::Public,
+	) -> bool {
+		match self.extension::<VerificationExt>() {
+			Some(verifier) => {
+				verifier.push_ed25519(sig.clone(), pub_key.clone(), msg.to_vec());
+				true
+			},
+			None => ed25519::Pair::verify(sig, msg, pub_key),
+		}
+	}
+
 	/// Returns all `sr25519` public keys for the given key id from the keystore.
 	fn sr25519_public_keys(&mut self, id: KeyTypeId) -> Vec<sr25519::Public> {
 		self.extension::<KeystoreExt>()
@@ -441,11 +483,13 @@ pub trait Crypto {
 		pub_key: &sr25519::Public,
 		msg: &[u8],
 	) -> Option<sr25519::Signature> {
+		let key = CryptoTypePublicPair(sr25519::CRYPTO_ID, pub_key.to_raw_vec());
 		self.extension::<KeystoreExt>()
 			.expect("No `keystore` associated for the current context!")
 			.read()
-			.sr25519_key_pair(id, &pub_key)
-			.map(|k| k.sign(msg))
+			.sign_with_blocking(id, &key, msg)
+			.ok()
+			.and_then(|signature| sr25519::Signature::try_from(&signature[..]).ok())
 	}
 
 	/// Verify an `sr25519` signature.
@@ -455,6 +499,53 @@ pub trait Crypto {
 		sr25519::Pair::verify(sig, msg, pubkey)
 	}
 
+	/// Register an `sr25519` signature for batch verification.
+	///
+	/// If no batch was started with [`start_batch_verify`], the signature is verified
+	/// immediately. Otherwise it is queued, and the result of the whole batch is returned by
+	/// [`finish_batch_verify`].
+	///
+	/// Returns `true` when the signature is valid or was queued.
+	fn sr25519_batch_verify(
+		&mut self,
+		sig: &sr25519::Signature,
+		msg: &[u8],
+		pub_key: &sr25519::Public,
+	) -> bool {
+		match self.extension::<VerificationExt>() {
+			Some(verifier) => {
+				verifier.push_sr25519(sig.clone(), pub_key.clone(), msg.to_vec());
+				true
+			},
+			None => sr25519::Pair::verify(sig, msg, pub_key),
+		}
+	}
+
+	/// Start a batch of signature verifications.
+	///
+	/// Until [`finish_batch_verify`] is called, the signatures given to the `*_batch_verify`
+	/// functions are queued instead of being verified.
+	///
+	/// Panics if a batch was already started.
+	fn start_batch_verify(&mut self) {
+		self.register_extension(VerificationExt(BatchVerifier::default()))
+			.expect("Failed to register the `VerificationExt` extension, is a batch already started?");
+	}
+
+	/// Verify the signatures queued since [`start_batch_verify`] in parallel, and end the batch.
+	///
+	/// Returns `true` when all the signatures are valid.
+	///
+	/// Panics if no batch was started.
+	fn finish_batch_verify(&mut self) -> bool {
+		let result = self.extension::<VerificationExt>()
+			.expect("`finish_batch_verify` is only called after `start_batch_verify`")
+			.verify_and_clear();
+		self.deregister_extension::<VerificationExt>()
+			.expect("The `VerificationExt` extension was found above; qed");
+		result
+	}
+
 	/// Verify and recover a SECP256k1 ECDSA signature.
 	/// - `sig` is passed in RSV format. V should be either 0/1 or 27/28.
 	/// Returns `Err` if the signature is bad, otherwise the 64-byte pubkey
@@ -489,6 +580,70 @@ pub trait Crypto {
 			.map_err(|_| EcdsaVerifyError::BadSignature)?;
 		Ok(pubkey.serialize_compressed())
 	}
+
+	/// Returns all `bls381` public keys for the given key id from the keystore.
+	fn bls381_public_keys(&mut self, id: KeyTypeId) -> Vec<bls381::Public> {
+		self.extension::<KeystoreExt>()
+			.expect("No `keystore` associated for the current context!")
+			.read()
+			.bls381_public_keys(id)
+	}
+
+	/// Generate a `bls381` key for the given key type using an optional `seed` and
+	/// store it in the keystore.
+	///
+	/// The `seed` needs to be a valid utf8.
+	///
+	/// Returns the public key.
+	fn bls381_generate(&mut self, id: KeyTypeId, seed: Option<Vec<u8>>) -> bls381::Public {
+		let seed = seed.as_ref().map(|s| std::str::from_utf8(&s).expect("Seed is valid utf8!"));
+		self.extension::<KeystoreExt>()
+			.expect("No `keystore` associated for the current context!")
+			.write()
+			.bls381_generate_new(id, seed)
+			.expect("`bls381_generate` failed")
+	}
+
+	/// Sign the given `msg` with the `bls381` key that corresponds to the given public key and
+	/// key type in the keystore.
+	///
+	/// Returns the signature.
+	fn bls381_sign(
+		&mut self,
+		id: KeyTypeId,
+		pub_key: &bls381::Public,
+		msg: &[u8],
+	) -> Option<bls381::Signature> {
+		let key = CryptoTypePublicPair(bls381::CRYPTO_ID, pub_key.to_raw_vec());
+		self.extension::<KeystoreExt>()
+			.expect("No `keystore` associated for the current context!")
+			.read()
+			.sign_with_blocking(id, &key, msg)
+			.ok()
+			.and_then(|signature| bls381::Signature::try_from(&signature[..]).ok())
+	}
+
+	/// Verify a `bls381` signature.
+	///
+	/// Returns `true` when the verification in successful.
+	fn bls381_verify(sig: &bls381::Signature, msg: &[u8], pub_key: &bls381::Public) -> bool {
+		bls381::Pair::verify(sig, msg, pub_key)
+	}
+
+	/// Verify a `bls381` signature aggregating the signatures of `msgs`, each message being
+	/// signed by the public key at the same index of `pub_keys`.
+	///
+	/// The messages must be distinct, the verification fails otherwise.
+	///
+	/// Returns `true` when the verification in successful.
+	fn bls381_verify_aggregate(
+		sig: &bls381::Signature,
+		msgs: &[Vec<u8>],
+		pub_keys: &[bls381::Public],
+	) -> bool {
+		let msgs = msgs.iter().map(|msg| &msg[..]).collect::<Vec<_>>();
+		bls381::verify_aggregate(sig, &msgs, pub_keys)
+	}
 }
 
 /// Interface that provides functions for hashing with different algorithms.
@@ -530,6 +685,26 @@ pub trait Hashing {
 	}
 }
 
+/// Interface that provides functions to index data for the offchain workers during block
+/// execution.
+///
+/// The indexed entries are written to the persistent offchain storage when the block is imported,
+/// if the node enables offchain indexing. They are not part of the state, and are not keyed by
+/// block: the blocks of competing forks write to the same entries, and the block imported last
+/// wins.
+#[runtime_interface]
+pub trait OffchainIndex {
+	/// Write a key value pair to the offchain database.
+	fn set(&mut self, key: &[u8], value: &[u8]) {
+		self.set_offchain_storage(key, Some(value));
+	}
+
+	/// Remove a key and its value from the offchain database.
+	fn clear(&mut self, key: &[u8]) {
+		self.set_offchain_storage(key, None);
+	}
+}
+
 /// Interface that provides functions to access the offchain functionality.
 #[runtime_interface]
 pub trait Offchain {
@@ -891,6 +1066,7 @@ pub type SubstrateHostFunctions = (
 	storage::HostFunctions,
 	misc::HostFunctions,
 	offchain::HostFunctions,
+	offchain_index::HostFunctions,
 	crypto::HostFunctions,
 	hashing::HostFunctions,
 	allocator::HostFunctions,
@@ -966,4 +1142,42 @@ mod tests {
 			assert!(storage::get(b":abc").is_none());
 		});
 	}
+
+	#[test]
+	fn secp256k1_ecdsa_recover_compressed_works() {
+		let pair = sp_core::ecdsa::Pair::from_string("//Alice", None).unwrap();
+		let signature = pair.sign(b"message");
+		let mut sig = [0u8; 65];
+		sig.copy_from_slice(signature.as_ref());
+		let msg = sp_core::hashing::blake2_256(b"message");
+
+		BasicExternalities::default().execute_with(|| {
+			let compressed = crypto::secp256k1_ecdsa_recover_compressed(&sig, &msg).ok()
+				.expect("Signature is valid");
+			assert_eq!(&compressed[..], &pair.public().as_compressed().unwrap()[..]);
+
+			let full = crypto::secp256k1_ecdsa_recover(&sig, &msg).ok().expect("Signature is valid");
+			assert_eq!(sp_core::ecdsa::Public::from_raw(full), pair.public());
+		});
+	}
+
+	#[test]
+	fn batch_verify_queues_signatures_until_finished() {
+		let pair = sr25519::Pair::from_string("//Alice", None).unwrap();
+		let signature = pair.sign(b"message");
+
+		let mut ext = TestExternalities::default();
+		ext.execute_with(|| {
+			// Without a batch, the signature is verified immediately.
+			assert!(!crypto::sr25519_batch_verify(&signature, b"other", &pair.public()));
+
+			crypto::start_batch_verify();
+			assert!(crypto::sr25519_batch_verify(&signature, b"message", &pair.public()));
+			assert!(crypto::finish_batch_verify());
+
+			crypto::start_batch_verify();
+			assert!(crypto::sr25519_batch_verify(&signature, b"other", &pair.public()));
+			assert!(!crypto::finish_batch_verify());
+		});
+	}
 }
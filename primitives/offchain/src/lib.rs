@@ -19,16 +19,24 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![warn(missing_docs)]
 
-use sp_runtime::traits::NumberFor;
+use sp_runtime::traits::{NumberFor, Block as BlockT};
 
 /// Local Storage Prefix used by the Offchain Worker API to
 pub const STORAGE_PREFIX: &[u8] = b"storage";
 
 sp_api::decl_runtime_apis! {
 	/// The offchain worker api.
+	#[api_version(2)]
 	pub trait OffchainWorkerApi {
 		/// Starts the off-chain task for given block number.
+		///
+		/// Is callable by `offchain_worker_before_version_2`.
 		#[skip_initialize_block]
+		#[changed_in(2)]
 		fn offchain_worker(number: NumberFor<Block>);
+
+		/// Starts the off-chain task for given block header.
+		#[skip_initialize_block]
+		fn offchain_worker(header: &<Block as BlockT>::Header);
 	}
 }
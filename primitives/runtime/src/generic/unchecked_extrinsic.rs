@@ -110,8 +110,9 @@ where
 			Some((signed, signature, extra)) => {
 				let signed = lookup.lookup(signed)?;
 				let raw_payload = SignedPayload::new(self.function, extra)?;
+				// A bad signature of an extrinsic invalidates the block, so it may be batched.
 				if !raw_payload.using_encoded(|payload| {
-					signature.verify(payload, &signed)
+					signature.batch_verify(payload, &signed)
 				}) {
 					return Err(InvalidTransaction::BadProof.into())
 				}
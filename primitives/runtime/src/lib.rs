@@ -303,22 +303,38 @@ impl std::fmt::Display for MultiSigner {
 	}
 }
 
+impl MultiSignature {
+	fn verify_ecdsa<L: Lazy<[u8]>>(sig: &ecdsa::Signature, mut msg: L, who: &AccountId32) -> bool {
+		let m = sp_io::hashing::blake2_256(msg.get());
+		match sp_io::crypto::secp256k1_ecdsa_recover_compressed(sig.as_ref(), &m) {
+			Ok(pubkey) =>
+				&sp_io::hashing::blake2_256(pubkey.as_ref())
+					== <dyn AsRef<[u8; 32]>>::as_ref(who),
+			_ => false,
+		}
+	}
+}
+
 impl Verify for MultiSignature {
 	type Signer = MultiSigner;
-	fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &AccountId32) -> bool {
+	fn verify<L: Lazy<[u8]>>(&self, msg: L, signer: &AccountId32) -> bool {
 		use sp_core::crypto::Public;
 		match (self, signer) {
 			(MultiSignature::Ed25519(ref sig), who) => sig.verify(msg, &ed25519::Public::from_slice(who.as_ref())),
 			(MultiSignature::Sr25519(ref sig), who) => sig.verify(msg, &sr25519::Public::from_slice(who.as_ref())),
-			(MultiSignature::Ecdsa(ref sig), who) => {
-				let m = sp_io::hashing::blake2_256(msg.get());
-				match sp_io::crypto::secp256k1_ecdsa_recover_compressed(sig.as_ref(), &m) {
-					Ok(pubkey) =>
-						&sp_io::hashing::blake2_256(pubkey.as_ref())
-							== <dyn AsRef<[u8; 32]>>::as_ref(who),
-					_ => false,
-				}
-			}
+			(MultiSignature::Ecdsa(ref sig), who) => Self::verify_ecdsa(sig, msg, who),
+		}
+	}
+
+	// The crypto of the signature is known, so ed25519 and sr25519 signatures can be queued.
+	fn batch_verify<L: Lazy<[u8]>>(&self, msg: L, signer: &AccountId32) -> bool {
+		use sp_core::crypto::Public;
+		match (self, signer) {
+			(MultiSignature::Ed25519(ref sig), who) =>
+				sig.batch_verify(msg, &ed25519::Public::from_slice(who.as_ref())),
+			(MultiSignature::Sr25519(ref sig), who) =>
+				sig.batch_verify(msg, &sr25519::Public::from_slice(who.as_ref())),
+			(MultiSignature::Ecdsa(ref sig), who) => Self::verify_ecdsa(sig, msg, who),
 		}
 	}
 }
@@ -333,11 +349,14 @@ impl Verify for AnySignature {
 	fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &sr25519::Public) -> bool {
 		use sp_core::crypto::Public;
 		let msg = msg.get();
+		// The result of the first verification decides whether the second one is made, so
+		// neither can be queued in a `SignatureBatching` session, and `batch_verify` verifies
+		// immediately.
 		sr25519::Signature::try_from(self.0.as_fixed_bytes().as_ref())
-			.map(|s| s.verify(msg, signer))
+			.map(|s| sp_io::crypto::sr25519_verify(&s, msg, signer))
 			.unwrap_or(false)
 		|| ed25519::Signature::try_from(self.0.as_fixed_bytes().as_ref())
-			.map(|s| s.verify(msg, &ed25519::Public::from_slice(signer.as_ref())))
+			.map(|s| sp_io::crypto::ed25519_verify(&s, msg, &ed25519::Public::from_slice(signer.as_ref())))
 			.unwrap_or(false)
 	}
 }
@@ -683,6 +702,49 @@ impl traits::Extrinsic for OpaqueExtrinsic {
 	type SignaturePayload = ();
 }
 
+/// A session of batch signature verification.
+///
+/// While the session is running, the `ed25519` and `sr25519` signatures checked through
+/// [`Verify::batch_verify`] are queued and considered valid. They are verified in parallel by
+/// [`SignatureBatching::verify`], which must be called before the session is dropped.
+///
+/// To be used in the runtime only.
+#[must_use = "`verify()` needs to be called to finish batch signature verification!"]
+pub struct SignatureBatching(bool);
+
+impl SignatureBatching {
+	/// Start a new batching session.
+	pub fn start() -> Self {
+		sp_io::crypto::start_batch_verify();
+		SignatureBatching(false)
+	}
+
+	/// Verify all the signatures queued during the session.
+	///
+	/// Returns `true` when all of them are valid.
+	#[must_use]
+	pub fn verify(mut self) -> bool {
+		self.0 = true;
+		sp_io::crypto::finish_batch_verify()
+	}
+}
+
+impl Drop for SignatureBatching {
+	fn drop(&mut self) {
+		// Otherwise the queued signatures would be silently accepted.
+		if !self.0 {
+			// Panicking while unwinding would abort the node.
+			#[cfg(feature = "std")]
+			{
+				if std::thread::panicking() {
+					return;
+				}
+			}
+			panic!("`SignatureBatching::verify` was not called before dropping the session")
+		}
+	}
+}
+
 /// Print something that implements `Printable` from the runtime.
 pub fn print(print: impl traits::Printable) {
 	print.print();
@@ -735,4 +797,47 @@ mod tests {
 		let multi_signer = MultiSigner::from(pair.public().into_compressed().unwrap());
 		assert!(multi_sig.verify(msg, &multi_signer.into_account()));
 	}
+
+	#[test]
+	fn signature_batching_verifies_queued_signatures() {
+		let msg = &b"test-message"[..];
+		let (pair, _) = sr25519::Pair::generate();
+		let signature = pair.sign(msg);
+
+		sp_io::TestExternalities::default().execute_with(|| {
+			let batching = SignatureBatching::start();
+			assert!(signature.batch_verify(msg, &pair.public()));
+			assert!(batching.verify());
+
+			let batching = SignatureBatching::start();
+			assert!(signature.batch_verify(&b"other-message"[..], &pair.public()));
+			assert!(!batching.verify());
+		});
+	}
+
+	#[test]
+	fn signatures_are_only_queued_by_batch_verify() {
+		let msg = &b"test-message"[..];
+		let (pair, _) = ed25519::Pair::generate();
+		let signature = MultiSignature::from(pair.sign(msg));
+		let signer = MultiSigner::from(pair.public()).into_account();
+
+		sp_io::TestExternalities::default().execute_with(|| {
+			let batching = SignatureBatching::start();
+			assert!(!signature.verify(&b"other-message"[..], &signer));
+			assert!(batching.verify());
+
+			let batching = SignatureBatching::start();
+			assert!(signature.batch_verify(&b"other-message"[..], &signer));
+			assert!(!batching.verify());
+		});
+	}
+
+	#[test]
+	#[should_panic(expected = "`SignatureBatching::verify` was not called")]
+	fn signature_batching_panics_when_not_verified() {
+		sp_io::TestExternalities::default().execute_with(|| {
+			let _batching = SignatureBatching::start();
+		});
+	}
 }
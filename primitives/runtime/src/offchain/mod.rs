@@ -17,3 +17,5 @@
 //! A collection of higher lever helpers for offchain calls.
 
 pub mod http;
+pub mod storage;
+pub mod storage_lock;
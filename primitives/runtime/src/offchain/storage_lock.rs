@@ -0,0 +1,388 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Locks of offchain workers, kept in the persistent local storage.
+//!
+//! The offchain workers of the blocks of different forks may run concurrently. A lock lets
+//! them coordinate, for instance to not start the same HTTP request twice. The lock is taken
+//! until a deadline, after which it expires, so it can also be kept past the end of the run
+//! which took it, while the work it protects is still in flight.
+//!
+//! Example:
+//! ```rust,no_run
+//! use sp_runtime::offchain::storage_lock::{StorageLock, Time};
+//!
+//! let mut lock = StorageLock::<Time>::new(b"my-worker::lock");
+//! if let Ok(guard) = lock.try_lock() {
+//! 	// start the request, and keep the lock until it expires.
+//! 	guard.forget();
+//! }
+//! ```
+
+use codec::{Codec, Decode, Encode};
+use sp_core::RuntimeDebug;
+use sp_core::offchain::{Duration, Timestamp};
+use crate::offchain::storage::StorageValueRef;
+use crate::traits::SimpleArithmetic;
+
+/// Default expiration of a time based lock, in milliseconds.
+pub const LOCK_EXPIRATION_MILLIS: u64 = 20_000;
+
+/// How long to wait before checking again whether a lock has been released, in milliseconds.
+const SNOOZE_MILLIS: u64 = 100;
+
+/// A kind of lock deadline.
+pub trait Lockable: Sized {
+	/// The deadline of a lock, stored along with it.
+	type Deadline: Codec + Copy + PartialEq;
+
+	/// Returns the deadline of a lock taken now.
+	fn deadline(&self) -> Self::Deadline;
+
+	/// Returns whether a lock taken until `deadline` has expired.
+	fn has_expired(&self, deadline: &Self::Deadline) -> bool;
+
+	/// Wait a bit for the lock taken until `deadline` to be released or to expire.
+	///
+	/// Returns `false`, without waiting, if waiting can't make the lock expire anymore.
+	fn snooze(&self, _deadline: &Self::Deadline) -> bool {
+		let next_check = sp_io::offchain::timestamp().add(Duration::from_millis(SNOOZE_MILLIS));
+		sp_io::offchain::sleep_until(next_check);
+		true
+	}
+}
+
+/// Why a lock couldn't be taken.
+#[derive(Clone, Copy, PartialEq, Eq, RuntimeDebug)]
+pub enum LockError<D> {
+	/// The lock is held until this deadline.
+	Held(D),
+	/// The lock is stored with another kind of deadline, so it may still be held.
+	UnknownDeadline,
+}
+
+/// A lock expiring at a point in time.
+#[derive(Clone, Copy, RuntimeDebug)]
+pub struct Time {
+	expiration: Duration,
+}
+
+impl Time {
+	/// Locks taken until `expiration` after they are taken.
+	pub fn new(expiration: Duration) -> Self {
+		Time { expiration }
+	}
+}
+
+impl Default for Time {
+	fn default() -> Self {
+		Time::new(Duration::from_millis(LOCK_EXPIRATION_MILLIS))
+	}
+}
+
+impl Lockable for Time {
+	type Deadline = Timestamp;
+
+	fn deadline(&self) -> Timestamp {
+		sp_io::offchain::timestamp().add(self.expiration)
+	}
+
+	fn has_expired(&self, deadline: &Timestamp) -> bool {
+		sp_io::offchain::timestamp() > *deadline
+	}
+
+	fn snooze(&self, deadline: &Timestamp) -> bool {
+		let next_check = sp_io::offchain::timestamp().add(Duration::from_millis(SNOOZE_MILLIS));
+		sp_io::offchain::sleep_until(next_check.min(deadline.add(Duration::from_millis(1))));
+		true
+	}
+}
+
+/// The deadline of a lock expiring at a block and a point in time.
+#[derive(Clone, Copy, PartialEq, Eq, Encode, Decode, RuntimeDebug)]
+pub struct BlockAndTimeDeadline<N> {
+	/// The lock expires after this block.
+	pub block_number: N,
+	/// The lock expires after this point in time.
+	pub timestamp: Timestamp,
+}
+
+/// A lock expiring once both a number of blocks and some time have passed.
+///
+/// Since the block number does not change during a run, a lock which isn't expired in block
+/// number yet won't expire by waiting: `lock` only waits until its time deadline.
+#[derive(Clone, Copy, RuntimeDebug)]
+pub struct BlockAndTime<N> {
+	current_block: N,
+	expiration_blocks: N,
+	expiration: Duration,
+}
+
+impl<N> BlockAndTime<N> {
+	/// Locks taken at `current_block` until `expiration_blocks` later and `expiration` after
+	/// they are taken.
+	pub fn new(current_block: N, expiration_blocks: N, expiration: Duration) -> Self {
+		BlockAndTime { current_block, expiration_blocks, expiration }
+	}
+}
+
+impl<N: SimpleArithmetic + Codec + Copy> Lockable for BlockAndTime<N> {
+	type Deadline = BlockAndTimeDeadline<N>;
+
+	fn deadline(&self) -> Self::Deadline {
+		BlockAndTimeDeadline {
+			block_number: self.current_block.saturating_add(self.expiration_blocks),
+			timestamp: sp_io::offchain::timestamp().add(self.expiration),
+		}
+	}
+
+	fn has_expired(&self, deadline: &Self::Deadline) -> bool {
+		self.current_block > deadline.block_number &&
+			sp_io::offchain::timestamp() > deadline.timestamp
+	}
+
+	fn snooze(&self, deadline: &Self::Deadline) -> bool {
+		let now = sp_io::offchain::timestamp();
+		// Past its time deadline, the lock is only held because of its block deadline.
+		if now > deadline.timestamp {
+			return false;
+		}
+		let next_check = now.add(Duration::from_millis(SNOOZE_MILLIS));
+		sp_io::offchain::sleep_until(next_check.min(deadline.timestamp.add(Duration::from_millis(1))));
+		true
+	}
+}
+
+/// A lock kept in the persistent local storage under a key.
+pub struct StorageLock<'a, L = Time> {
+	value_ref: StorageValueRef<'a>,
+	lockable: L,
+}
+
+impl<'a, L: Lockable + Default> StorageLock<'a, L> {
+	/// The lock stored under `key`, with the default deadlines.
+	pub fn new(key: &'a [u8]) -> Self {
+		Self::with_lockable(key, Default::default())
+	}
+}
+
+impl<'a, L: Lockable> StorageLock<'a, L> {
+	/// The lock stored under `key`, with the deadlines of `lockable`.
+	pub fn with_lockable(key: &'a [u8], lockable: L) -> Self {
+		StorageLock { value_ref: StorageValueRef::persistent(key), lockable }
+	}
+
+	/// Take the lock if it isn't held, or if it has expired.
+	pub fn try_lock(&mut self) -> Result<StorageLockGuard<'a, '_, L>, LockError<L::Deadline>> {
+		let deadline = self.try_take()?;
+		Ok(StorageLockGuard { lock: Some(self), deadline })
+	}
+
+	/// Take the lock, waiting for it to be released or to expire if it is held.
+	///
+	/// Fails like `try_lock` once waiting can't make the lock expire anymore.
+	pub fn lock(&mut self) -> Result<StorageLockGuard<'a, '_, L>, LockError<L::Deadline>> {
+		let deadline = loop {
+			match self.try_take() {
+				Ok(deadline) => break deadline,
+				Err(LockError::Held(deadline)) if self.lockable.snooze(&deadline) => {},
+				Err(error) => return Err(error),
+			}
+		};
+		Ok(StorageLockGuard { lock: Some(self), deadline })
+	}
+
+	fn try_take(&self) -> Result<L::Deadline, LockError<L::Deadline>> {
+		let lockable = &self.lockable;
+		loop {
+			// The storage holds `None` once the lock is released.
+			let taken = self.value_ref.mutate(|current: Option<Option<Option<L::Deadline>>>| {
+				match current {
+					Some(Some(Some(deadline))) if !lockable.has_expired(&deadline) =>
+						Err(LockError::Held(deadline)),
+					Some(None) => Err(LockError::UnknownDeadline),
+					_ => Ok(Some(lockable.deadline())),
+				}
+			})?;
+			if let Ok(deadline) = taken {
+				return Ok(deadline.expect("the lock is only taken with a deadline; qed"));
+			}
+			// The lock was changed concurrently, check it again.
+		}
+	}
+
+	fn release(&self, deadline: L::Deadline) {
+		// The lock may have expired and been taken by someone else meanwhile.
+		let _ = self.value_ref.mutate(|current: Option<Option<Option<L::Deadline>>>| {
+			match current {
+				Some(Some(Some(current))) if current == deadline => Ok(None),
+				_ => Err(()),
+			}
+		});
+	}
+}
+
+/// A taken lock, released when dropped.
+pub struct StorageLockGuard<'a, 'b, L: Lockable> {
+	lock: Option<&'b mut StorageLock<'a, L>>,
+	deadline: L::Deadline,
+}
+
+impl<'a, 'b, L: Lockable> StorageLockGuard<'a, 'b, L> {
+	/// The deadline of the lock.
+	pub fn deadline(&self) -> L::Deadline {
+		self.deadline
+	}
+
+	/// Keep the lock until its deadline rather than releasing it.
+	pub fn forget(mut self) {
+		self.lock = None;
+	}
+}
+
+impl<'a, 'b, L: Lockable> Drop for StorageLockGuard<'a, 'b, L> {
+	fn drop(&mut self) {
+		if let Some(lock) = self.lock.take() {
+			lock.release(self.deadline);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_io::TestExternalities;
+	use sp_core::offchain::{OffchainExt, testing};
+
+	const KEY: &[u8] = b"lock";
+
+	#[test]
+	fn time_lock_is_exclusive_until_released() {
+		let (offchain, _state) = testing::TestOffchainExt::new();
+		let mut t = TestExternalities::default();
+		t.register_extension(OffchainExt::new(offchain));
+
+		t.execute_with(|| {
+			let mut lock = StorageLock::<Time>::new(KEY);
+			let mut other = StorageLock::<Time>::new(KEY);
+
+			let guard = lock.try_lock().unwrap();
+			assert_eq!(other.try_lock().err(), Some(LockError::Held(guard.deadline())));
+			drop(guard);
+
+			assert!(other.try_lock().is_ok());
+		})
+	}
+
+	#[test]
+	fn forgotten_time_lock_expires() {
+		let (offchain, state) = testing::TestOffchainExt::new();
+		let mut t = TestExternalities::default();
+		t.register_extension(OffchainExt::new(offchain));
+
+		t.execute_with(|| {
+			let mut lock = StorageLock::with_lockable(KEY, Time::new(Duration::from_millis(1_000)));
+			let mut other = StorageLock::with_lockable(KEY, Time::new(Duration::from_millis(1_000)));
+
+			let guard = lock.try_lock().unwrap();
+			let deadline = guard.deadline();
+			guard.forget();
+			assert!(other.try_lock().is_err());
+
+			state.write().timestamp = deadline.add(Duration::from_millis(1));
+			assert!(other.try_lock().is_ok());
+		})
+	}
+
+	#[test]
+	fn lock_waits_for_expiration() {
+		let (offchain, state) = testing::TestOffchainExt::new();
+		let mut t = TestExternalities::default();
+		t.register_extension(OffchainExt::new(offchain));
+
+		t.execute_with(|| {
+			let mut lock = StorageLock::<Time>::new(KEY);
+			let mut other = StorageLock::<Time>::new(KEY);
+
+			let guard = lock.try_lock().unwrap();
+			let deadline = guard.deadline();
+			guard.forget();
+
+			let guard = other.lock().unwrap();
+			assert!(state.read().timestamp > deadline);
+			assert!(guard.deadline() > deadline);
+		})
+	}
+
+	#[test]
+	fn block_and_time_lock_expires_after_both() {
+		let (offchain, state) = testing::TestOffchainExt::new();
+		let mut t = TestExternalities::default();
+		t.register_extension(OffchainExt::new(offchain));
+
+		t.execute_with(|| {
+			let expiration = Duration::from_millis(1_000);
+			let mut lock = StorageLock::with_lockable(KEY, BlockAndTime::new(10u32, 2, expiration));
+			let guard = lock.try_lock().unwrap();
+			let deadline = guard.deadline();
+			assert_eq!(deadline.block_number, 12);
+			guard.forget();
+
+			state.write().timestamp = deadline.timestamp.add(Duration::from_millis(1));
+			let mut other = StorageLock::with_lockable(KEY, BlockAndTime::new(12u32, 2, expiration));
+			assert_eq!(other.try_lock().err(), Some(LockError::Held(deadline)));
+
+			let mut other = StorageLock::with_lockable(KEY, BlockAndTime::new(13u32, 2, expiration));
+			assert!(other.try_lock().is_ok());
+		})
+	}
+
+	#[test]
+	fn block_and_time_lock_waits_until_time_deadline_only() {
+		let (offchain, state) = testing::TestOffchainExt::new();
+		let mut t = TestExternalities::default();
+		t.register_extension(OffchainExt::new(offchain));
+
+		t.execute_with(|| {
+			let expiration = Duration::from_millis(1_000);
+			let mut lock = StorageLock::with_lockable(KEY, BlockAndTime::new(10u32, 2, expiration));
+			let guard = lock.try_lock().unwrap();
+			let deadline = guard.deadline();
+			guard.forget();
+
+			let mut other = StorageLock::with_lockable(KEY, BlockAndTime::new(11u32, 2, expiration));
+			assert_eq!(other.lock().err(), Some(LockError::Held(deadline)));
+			assert!(state.read().timestamp > deadline.timestamp);
+		})
+	}
+
+	#[test]
+	fn lock_with_another_kind_of_deadline_is_not_taken() {
+		let (offchain, _state) = testing::TestOffchainExt::new();
+		let mut t = TestExternalities::default();
+		t.register_extension(OffchainExt::new(offchain));
+
+		t.execute_with(|| {
+			let mut lock = StorageLock::<Time>::new(KEY);
+			lock.try_lock().unwrap().forget();
+
+			let expiration = Duration::from_millis(1_000);
+			let mut other = StorageLock::with_lockable(KEY, BlockAndTime::new(10u32, 2, expiration));
+			assert_eq!(other.try_lock().err(), Some(LockError::UnknownDeadline));
+			assert_eq!(other.lock().err(), Some(LockError::UnknownDeadline));
+		})
+	}
+}
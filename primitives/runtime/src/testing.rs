@@ -28,7 +28,7 @@ use crate::traits::ValidateUnsigned;
 use crate::{generic, KeyTypeId, ApplyExtrinsicResult};
 pub use sp_core::{H256, sr25519};
 use sp_core::{crypto::{CryptoType, Dummy, key_types, Public}, U256};
-use crate::transaction_validity::{TransactionValidity, TransactionValidityError};
+use crate::transaction_validity::{TransactionValidity, TransactionValidityError, TransactionSource};
 
 /// Authority Id
 #[derive(Default, PartialEq, Eq, Clone, Encode, Decode, Debug, Hash, Serialize, Deserialize, PartialOrd, Ord)]
@@ -341,6 +341,7 @@ impl<Origin, Call, Extra, Info> Applyable for TestXt<Call, Extra> where
 	#[allow(deprecated)] // Allow ValidateUnsigned
 	fn validate<U: ValidateUnsigned<Call=Self::Call>>(
 		&self,
+		_source: TransactionSource,
 		_info: Self::DispatchInfo,
 		_len: usize,
 	) -> TransactionValidity {
@@ -26,7 +26,8 @@ use serde::{Serialize, Deserialize, de::DeserializeOwned};
 use sp_core::{self, Hasher, Blake2Hasher, TypeId};
 use crate::codec::{Codec, Encode, Decode};
 use crate::transaction_validity::{
-	ValidTransaction, TransactionValidity, TransactionValidityError, UnknownTransaction,
+	ValidTransaction, TransactionSource, TransactionValidity, TransactionValidityError,
+	UnknownTransaction,
 };
 use crate::generic::{Digest, DigestItem};
 pub use sp_arithmetic::traits::{
@@ -79,6 +80,20 @@ pub trait Verify {
 	type Signer: IdentifyAccount;
 	/// Verify a signature. Return `true` if signature is valid for the value.
 	fn verify<L: Lazy<[u8]>>(&self, msg: L, signer: &<Self::Signer as IdentifyAccount>::AccountId) -> bool;
+
+	/// Verify a signature like [`Verify::verify`], or queue it in the running
+	/// [`SignatureBatching`](crate::SignatureBatching) session if there is one. A queued signature
+	/// is considered valid until the session is verified.
+	///
+	/// Only to be used where a bad signature invalidates the whole block, like for the signature
+	/// of an extrinsic. By default the signature is verified immediately.
+	fn batch_verify<L: Lazy<[u8]>>(
+		&self,
+		msg: L,
+		signer: &<Self::Signer as IdentifyAccount>::AccountId,
+	) -> bool {
+		self.verify(msg, signer)
+	}
 }
 
 impl Verify for sp_core::ed25519::Signature {
@@ -86,6 +101,10 @@ impl Verify for sp_core::ed25519::Signature {
 	fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &sp_core::ed25519::Public) -> bool {
 		sp_io::crypto::ed25519_verify(self, msg.get(), signer)
 	}
+
+	fn batch_verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &sp_core::ed25519::Public) -> bool {
+		sp_io::crypto::ed25519_batch_verify(self, msg.get(), signer)
+	}
 }
 
 impl Verify for sp_core::sr25519::Signature {
@@ -93,6 +112,10 @@ impl Verify for sp_core::sr25519::Signature {
 	fn verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &sp_core::sr25519::Public) -> bool {
 		sp_io::crypto::sr25519_verify(self, msg.get(), signer)
 	}
+
+	fn batch_verify<L: Lazy<[u8]>>(&self, mut msg: L, signer: &sp_core::sr25519::Public) -> bool {
+		sp_io::crypto::sr25519_batch_verify(self, msg.get(), signer)
+	}
 }
 
 impl Verify for sp_core::ecdsa::Signature {
@@ -871,10 +894,12 @@ pub trait Applyable: Sized + Send + Sync {
 	/// Returns a reference to the sender if any.
 	fn sender(&self) -> Option<&Self::AccountId>;
 
-	/// Checks to see if this is a valid *transaction*. It returns information on it if so.
+	/// Checks to see if this is a valid *transaction*, coming from the given source. It returns
+	/// information on it if so.
 	#[allow(deprecated)] // Allow ValidateUnsigned
 	fn validate<V: ValidateUnsigned<Call=Self::Call>>(
 		&self,
+		source: TransactionSource,
 		info: Self::DispatchInfo,
 		len: usize,
 	) -> TransactionValidity;
@@ -967,18 +992,21 @@ pub trait ValidateUnsigned {
 	///
 	/// Changes made to storage WILL be persisted if the call returns `Ok`.
 	fn pre_dispatch(call: &Self::Call) -> Result<(), TransactionValidityError> {
-		Self::validate_unsigned(call)
+		Self::validate_unsigned(TransactionSource::InBlock, call)
 			.map(|_| ())
 			.map_err(Into::into)
 	}
 
-	/// Return the validity of the call
+	/// Return the validity of the call, coming from the given source.
 	///
 	/// This doesn't execute any side-effects; it merely checks
 	/// whether the transaction would panic if it were included or not.
 	///
+	/// The source allows to only accept some calls from the node itself, e.g. the ones submitted
+	/// by its offchain workers.
+	///
 	/// Changes made to storage should be discarded by caller.
-	fn validate_unsigned(call: &Self::Call) -> TransactionValidity;
+	fn validate_unsigned(source: TransactionSource, call: &Self::Call) -> TransactionValidity;
 }
 
 /// Opaque datatype that may be destructured into a series of raw byte slices (which represent
@@ -30,6 +30,22 @@ pub type TransactionLongevity = u64;
 /// Tag for a transaction. No two transactions with the same tag should be placed on-chain.
 pub type TransactionTag = Vec<u8>;
 
+/// The source of a transaction, i.e. how it came to be validated.
+#[derive(Clone, PartialEq, Eq, Encode, Decode, Copy, RuntimeDebug)]
+#[cfg_attr(feature = "std", derive(Hash, serde::Serialize, serde::Deserialize))]
+pub enum TransactionSource {
+	/// The transaction is already included in a block.
+	///
+	/// This means that it is being validated again, e.g. after the block has been retracted.
+	InBlock,
+	/// The transaction was submitted by the node itself, e.g. by an offchain worker.
+	///
+	/// Such transactions can be trusted more than the ones received from the network.
+	Local,
+	/// The transaction was received from the outside, through the network or the RPC.
+	External,
+}
+
 /// An invalid transaction validity.
 #[derive(Clone, PartialEq, Eq, Encode, Decode, Copy, RuntimeDebug)]
 #[cfg_attr(feature = "std", derive(serde::Serialize))]
@@ -257,6 +257,10 @@ impl Externalities for BasicExternalities {
 		}
 	}
 
+	fn set_offchain_storage(&mut self, _key: &[u8], _value: Option<&[u8]>) {
+		// Offchain indexing only happens during block import.
+	}
+
 	fn chain_id(&self) -> u64 { 42 }
 
 	fn storage_root(&mut self) -> Vec<u8> {
@@ -305,6 +309,23 @@ impl sp_externalities::ExtensionStore for BasicExternalities {
 		warn!("Extensions are not supported by `BasicExternalities`.");
 		None
 	}
+
+	fn register_extension_with_type_id(
+		&mut self,
+		_type_id: TypeId,
+		_extension: Box<dyn sp_externalities::Extension>,
+	) -> Result<(), sp_externalities::Error> {
+		warn!("Extensions are not supported by `BasicExternalities`.");
+		Err(sp_externalities::Error::ExtensionsAreNotSupported)
+	}
+
+	fn deregister_extension_by_type_id(
+		&mut self,
+		_type_id: TypeId,
+	) -> Result<(), sp_externalities::Error> {
+		warn!("Extensions are not supported by `BasicExternalities`.");
+		Err(sp_externalities::Error::ExtensionsAreNotSupported)
+	}
 }
 
 #[cfg(test)]
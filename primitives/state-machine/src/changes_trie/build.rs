@@ -466,6 +466,7 @@ mod test {
 				].into_iter().collect(),
 			},
 			changes_trie_config: Some(config.clone()),
+			offchain_storage: Default::default(),
 		};
 
 		(backend, storage, changes, config)
@@ -0,0 +1,232 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Reports of the discrepancies between the native and the wasm execution of a call.
+
+use std::{collections::{BTreeMap, BTreeSet}, fmt};
+use codec::Encode;
+use sp_core::{NativeOrEncoded, hexdisplay::HexDisplay};
+use crate::overlayed_changes::{OverlayedChangeSet, OverlayedValue};
+
+/// A storage entry changed differently by the native and the wasm execution of a call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageDiff {
+	/// The storage key of the child trie of the entry, or `None` for the top trie.
+	pub child_storage_key: Option<Vec<u8>>,
+	/// The key of the entry.
+	pub key: Vec<u8>,
+	/// The change made by the native execution: `None` if the entry was not changed, and
+	/// `Some(None)` if it was deleted.
+	pub native: Option<Option<Vec<u8>>>,
+	/// The change made by the wasm execution: `None` if the entry was not changed, and
+	/// `Some(None)` if it was deleted.
+	pub wasm: Option<Option<Vec<u8>>>,
+}
+
+/// Report of a call whose native and wasm executions with the `Both` strategy disagree, either
+/// on the result or on the changes made to the storage.
+#[derive(Debug, Clone)]
+pub struct ConsensusFailureReport {
+	/// The runtime method called.
+	pub method: String,
+	/// The input of the call.
+	pub call_data: Vec<u8>,
+	/// The encoded result of the native execution, or its error.
+	pub native_result: Result<Vec<u8>, String>,
+	/// The encoded result of the wasm execution, or its error.
+	pub wasm_result: Result<Vec<u8>, String>,
+	/// The storage entries changed differently by both executions.
+	pub storage_diff: Vec<StorageDiff>,
+}
+
+impl ConsensusFailureReport {
+	/// Returns whether both executions agree on the result and on the storage changes.
+	pub fn is_consistent(&self) -> bool {
+		let results_agree = match (&self.native_result, &self.wasm_result) {
+			(Ok(native), Ok(wasm)) => native == wasm,
+			(Err(_), Err(_)) => true,
+			_ => false,
+		};
+		results_agree && self.storage_diff.is_empty()
+	}
+
+	pub(crate) fn new<R: Encode, E: fmt::Debug>(
+		method: &str,
+		call_data: &[u8],
+		native_result: &Result<NativeOrEncoded<R>, E>,
+		wasm_result: &Result<NativeOrEncoded<R>, E>,
+		native_changes: &OverlayedChangeSet,
+		wasm_changes: &OverlayedChangeSet,
+	) -> Self {
+		let encode = |result: &Result<NativeOrEncoded<R>, E>| match result {
+			Ok(result) => Ok(result.as_encoded().into_owned()),
+			Err(e) => Err(format!("{:?}", e)),
+		};
+
+		let mut storage_diff = diff(None, &native_changes.top, &wasm_changes.top);
+		let child_storage_keys = native_changes.children.keys()
+			.chain(wasm_changes.children.keys())
+			.collect::<BTreeSet<_>>();
+		let empty = BTreeMap::new();
+		for child_storage_key in child_storage_keys {
+			let native = native_changes.children.get(child_storage_key).map_or(&empty, |c| &c.0);
+			let wasm = wasm_changes.children.get(child_storage_key).map_or(&empty, |c| &c.0);
+			storage_diff.extend(diff(Some(child_storage_key), native, wasm));
+		}
+
+		ConsensusFailureReport {
+			method: method.into(),
+			call_data: call_data.to_vec(),
+			native_result: encode(native_result),
+			wasm_result: encode(wasm_result),
+			storage_diff,
+		}
+	}
+}
+
+/// Returns the entries changed differently in `native` and `wasm`.
+fn diff(
+	child_storage_key: Option<&Vec<u8>>,
+	native: &BTreeMap<Vec<u8>, OverlayedValue>,
+	wasm: &BTreeMap<Vec<u8>, OverlayedValue>,
+) -> Vec<StorageDiff> {
+	native.keys()
+		.chain(wasm.keys())
+		.collect::<BTreeSet<_>>()
+		.into_iter()
+		.filter_map(|key| {
+			let native = native.get(key).map(|v| v.value.clone());
+			let wasm = wasm.get(key).map(|v| v.value.clone());
+			if native == wasm {
+				return None;
+			}
+			Some(StorageDiff {
+				child_storage_key: child_storage_key.cloned(),
+				key: key.clone(),
+				native,
+				wasm,
+			})
+		})
+		.collect()
+}
+
+/// Formats a storage change for the report.
+struct Change<'a>(&'a Option<Option<Vec<u8>>>);
+
+impl<'a> fmt::Display for Change<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.0 {
+			None => write!(f, "unchanged"),
+			Some(None) => write!(f, "deleted"),
+			Some(Some(value)) => write!(f, "0x{}", HexDisplay::from(value)),
+		}
+	}
+}
+
+/// Formats a call result for the report.
+struct CallOutcome<'a>(&'a Result<Vec<u8>, String>);
+
+impl<'a> fmt::Display for CallOutcome<'a> {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self.0 {
+			Ok(output) => write!(f, "0x{}", HexDisplay::from(output)),
+			Err(e) => write!(f, "error: {}", e),
+		}
+	}
+}
+
+impl fmt::Display for ConsensusFailureReport {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		writeln!(f, "Consensus failure between the native and the wasm execution of {}", self.method)?;
+		writeln!(f, "  input:  0x{}", HexDisplay::from(&self.call_data))?;
+		writeln!(f, "  native: {}", CallOutcome(&self.native_result))?;
+		write!(f, "  wasm:   {}", CallOutcome(&self.wasm_result))?;
+		for diff in &self.storage_diff {
+			write!(f, "\n  storage ")?;
+			if let Some(child_storage_key) = &diff.child_storage_key {
+				write!(f, "0x{}/", HexDisplay::from(child_storage_key))?;
+			}
+			write!(
+				f,
+				"0x{}: native {}, wasm {}",
+				HexDisplay::from(&diff.key),
+				Change(&diff.native),
+				Change(&diff.wasm),
+			)?;
+		}
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::NeverNativeValue;
+
+	fn changes(entries: &[(&[u8], Option<&[u8]>)]) -> OverlayedChangeSet {
+		entries.iter()
+			.map(|(key, value)| (key.to_vec(), OverlayedValue {
+				value: value.map(|v| v.to_vec()),
+				extrinsics: None,
+			}))
+			.collect()
+	}
+
+	#[test]
+	fn reports_storage_changed_differently() {
+		let native = changes(&[(b"same", Some(b"1")), (b"native", Some(b"2")), (b"both", None)]);
+		let wasm = changes(&[(b"same", Some(b"1")), (b"both", Some(b"3"))]);
+		let result: Result<NativeOrEncoded<NeverNativeValue>, String> =
+			Ok(NativeOrEncoded::Encoded(vec![42]));
+
+		let report = ConsensusFailureReport::new("test", &[1, 2], &result, &result, &native, &wasm);
+
+		assert!(!report.is_consistent());
+		assert_eq!(report.storage_diff, vec![
+			StorageDiff {
+				child_storage_key: None,
+				key: b"both".to_vec(),
+				native: Some(None),
+				wasm: Some(Some(b"3".to_vec())),
+			},
+			StorageDiff {
+				child_storage_key: None,
+				key: b"native".to_vec(),
+				native: Some(Some(b"2".to_vec())),
+				wasm: None,
+			},
+		]);
+		assert_eq!(
+			report.to_string(),
+			"Consensus failure between the native and the wasm execution of test\n  \
+			input:  0x0102\n  \
+			native: 0x2a\n  \
+			wasm:   0x2a\n  \
+			storage 0x626f7468: native deleted, wasm 0x33\n  \
+			storage 0x6e6174697665: native 0x32, wasm unchanged",
+		);
+	}
+
+	#[test]
+	fn same_results_and_changes_are_consistent() {
+		let native = changes(&[(b"key", Some(b"1"))]);
+		let failed: Result<NativeOrEncoded<NeverNativeValue>, String> = Err("native".into());
+		let also_failed = Err("wasm".into());
+
+		let report = ConsensusFailureReport::new("test", &[], &failed, &also_failed, &native, &native);
+		assert!(report.is_consistent());
+	}
+}
@@ -493,6 +493,15 @@ where
 		});
 	}
 
+	fn set_offchain_storage(&mut self, key: &[u8], value: Option<&[u8]>) {
+		trace!(target: "state-trace", "{:04x}: IndexOffchain {}={:?}",
+			self.id,
+			HexDisplay::from(&key),
+			value.map(HexDisplay::from)
+		);
+		self.overlay.set_offchain_storage(key.to_vec(), value.map(|v| v.to_vec()));
+	}
+
 	fn chain_id(&self) -> u64 {
 		42
 	}
@@ -648,6 +657,29 @@ where
 	fn extension_by_type_id(&mut self, type_id: TypeId) -> Option<&mut dyn Any> {
 		self.extensions.as_mut().and_then(|exts| exts.get_mut(type_id))
 	}
+
+	fn register_extension_with_type_id(
+		&mut self,
+		type_id: TypeId,
+		extension: Box<dyn sp_externalities::Extension>,
+	) -> Result<(), sp_externalities::Error> {
+		match self.extensions.as_mut() {
+			Some(exts) => exts.register_with_type_id(type_id, extension),
+			None => Err(sp_externalities::Error::ExtensionsAreNotSupported),
+		}
+	}
+
+	fn deregister_extension_by_type_id(
+		&mut self,
+		type_id: TypeId,
+	) -> Result<(), sp_externalities::Error> {
+		match self.extensions.as_mut() {
+			Some(exts) => exts.deregister(type_id)
+				.map(drop)
+				.ok_or(sp_externalities::Error::ExtensionIsNotRegistered(type_id)),
+			None => Err(sp_externalities::Error::ExtensionsAreNotSupported),
+		}
+	}
 }
 
 #[cfg(test)]
@@ -685,6 +717,7 @@ mod tests {
 				digest_interval: 0,
 				digest_levels: 0,
 			}),
+			offchain_storage: Default::default(),
 		}
 	}
 
@@ -31,6 +31,7 @@ use sp_externalities::Extensions;
 
 pub mod backend;
 mod changes_trie;
+mod consensus_failure;
 mod error;
 mod ext;
 mod testing;
@@ -40,7 +41,7 @@ mod proving_backend;
 mod trie_backend;
 mod trie_backend_essence;
 
-pub use sp_trie::{trie_types::{Layout, TrieDBMut}, TrieMut, DBValue, MemoryDB};
+pub use sp_trie::{trie_types::{Layout, TrieDBMut}, TrieMut, DBValue, MemoryDB, CompactProof};
 pub use testing::TestExternalities;
 pub use basic::BasicExternalities;
 pub use ext::Ext;
@@ -65,6 +66,7 @@ pub use proving_backend::{
 pub use trie_backend_essence::{TrieBackendStorage, Storage};
 pub use trie_backend::TrieBackend;
 pub use error::{Error, ExecutionError};
+pub use consensus_failure::{ConsensusFailureReport, StorageDiff};
 
 type CallResult<R, E> = Result<NativeOrEncoded<R>, E>;
 
@@ -172,6 +174,7 @@ pub struct StateMachine<'a, B, H, N, T, Exec> where H: Hasher<Out=H256>, B: Back
 	overlay: &'a mut OverlayedChanges,
 	extensions: Extensions,
 	changes_trie_storage: Option<&'a T>,
+	consensus_failure_reporter: Option<Box<dyn FnMut(ConsensusFailureReport) + 'a>>,
 	_marker: PhantomData<(H, N)>,
 }
 
@@ -200,10 +203,22 @@ impl<'a, B, H, N, T, Exec> StateMachine<'a, B, H, N, T, Exec> where
 			extensions,
 			overlay,
 			changes_trie_storage,
+			consensus_failure_reporter: None,
 			_marker: PhantomData,
 		}
 	}
 
+	/// Give the reports of the consensus failures detected by the `Both` strategy to `reporter`.
+	///
+	/// The reports are logged in any case.
+	pub fn with_consensus_failure_reporter(
+		mut self,
+		reporter: impl FnMut(ConsensusFailureReport) + 'a,
+	) -> Self {
+		self.consensus_failure_reporter = Some(Box::new(reporter));
+		self
+	}
+
 	/// Execute a call using the given state backend, overlayed changes, and call executor.
 	/// Produces a state-backend-specific "transaction" which can be used to apply the changes
 	/// to the backing store, such as the disk.
@@ -310,19 +325,31 @@ impl<'a, B, H, N, T, Exec> StateMachine<'a, B, H, N, T, Exec> where
 		);
 
 		if was_native {
-			self.overlay.prospective = orig_prospective.clone();
+			let native_changes = std::mem::replace(
+				&mut self.overlay.prospective,
+				orig_prospective.clone(),
+			);
 			let (wasm_result, _, wasm_storage_delta, wasm_changes_delta) = self.execute_aux(
 				compute_tx,
 				false,
 				native_call,
 			);
 
-			if (result.is_ok() && wasm_result.is_ok()
-				&& result.as_ref().ok() == wasm_result.as_ref().ok())
-				|| result.is_err() && wasm_result.is_err()
-			{
+			let report = ConsensusFailureReport::new(
+				self.method,
+				self.call_data,
+				&result,
+				&wasm_result,
+				&native_changes,
+				&self.overlay.prospective,
+			);
+			if report.is_consistent() {
 				(result, storage_delta, changes_delta)
 			} else {
+				warn!(target: "state-machine", "{}", report);
+				if let Some(reporter) = self.consensus_failure_reporter.as_mut() {
+					reporter(report);
+				}
 				(on_consensus_failure(wasm_result, result), wasm_storage_delta, wasm_changes_delta)
 			}
 		} else {
@@ -890,6 +917,38 @@ mod tests {
 		assert!(consensus_failed);
 	}
 
+	#[test]
+	fn dual_execution_strategy_reports_consensus_failure() {
+		let mut reports = Vec::new();
+		let backend = trie_backend::tests::test_trie();
+		let mut overlayed_changes = Default::default();
+		let changes_trie_storage = InMemoryChangesTrieStorage::<Blake2Hasher, u64>::new();
+
+		let mut state_machine = StateMachine::new(
+			&backend,
+			Some(&changes_trie_storage),
+			&mut overlayed_changes,
+			&DummyCodeExecutor {
+				change_changes_trie_config: false,
+				native_available: true,
+				native_succeeds: true,
+				fallback_succeeds: false,
+			},
+			"test",
+			&[1, 2, 3],
+			Default::default(),
+		).with_consensus_failure_reporter(|report| reports.push(report));
+
+		assert!(state_machine.execute(ExecutionStrategy::Both).is_err());
+		drop(state_machine);
+
+		assert_eq!(reports.len(), 1);
+		assert_eq!(reports[0].method, "test");
+		assert_eq!(reports[0].call_data, vec![1, 2, 3]);
+		assert!(reports[0].native_result.is_ok());
+		assert!(reports[0].wasm_result.is_err());
+	}
+
 	#[test]
 	fn prove_execution_and_proof_check_works() {
 		let executor = DummyCodeExecutor {
@@ -37,6 +37,9 @@ pub struct OverlayedChanges {
 	/// Changes trie configuration. None by default, but could be installed by the
 	/// runtime if it supports change tries.
 	pub(crate) changes_trie_config: Option<ChangesTrieConfig>,
+	/// Changes to the offchain database. They are not part of the state, so they are neither
+	/// prospective nor committed, and are kept even if the prospective changes are discarded.
+	pub(crate) offchain_storage: BTreeMap<Vec<u8>, Option<Vec<u8>>>,
 }
 
 /// The storage value, used inside OverlayedChanges.
@@ -292,6 +295,16 @@ impl OverlayedChanges {
 		}
 	}
 
+	/// Set or clear an entry of the offchain database.
+	pub(crate) fn set_offchain_storage(&mut self, key: Vec<u8>, value: Option<Vec<u8>>) {
+		self.offchain_storage.insert(key, value);
+	}
+
+	/// Take the changes to the offchain database, ordered by key.
+	pub fn drain_offchain_storage(&mut self) -> Vec<(Vec<u8>, Option<Vec<u8>>)> {
+		mem::replace(&mut self.offchain_storage, BTreeMap::new()).into_iter().collect()
+	}
+
 	/// Discard prospective changes to state.
 	pub fn discard_prospective(&mut self) {
 		self.prospective.clear();
@@ -486,6 +499,27 @@ mod tests {
 		assert!(overlayed.storage(&key).unwrap().is_none());
 	}
 
+	#[test]
+	fn offchain_storage_is_kept_apart_from_the_state() {
+		let backend = InMemory::<Blake2Hasher>::default();
+		let mut overlay = OverlayedChanges::default();
+		{
+			let changes_trie_storage = None::<&InMemoryChangesTrieStorage<Blake2Hasher, u64>>;
+			let mut ext = Ext::new(&mut overlay, &backend, changes_trie_storage, None);
+			ext.set_offchain_storage(b"key", Some(b"value"));
+			ext.set_offchain_storage(b"other", Some(b"value"));
+			ext.set_offchain_storage(b"other", None);
+		}
+
+		overlay.discard_prospective();
+		assert!(overlay.is_empty());
+		assert_eq!(overlay.drain_offchain_storage(), vec![
+			(b"key".to_vec(), Some(b"value".to_vec())),
+			(b"other".to_vec(), None),
+		]);
+		assert!(overlay.drain_offchain_storage().is_empty());
+	}
+
 	#[test]
 	fn overlayed_storage_root_works() {
 		let initial: BTreeMap<_, _> = vec![
@@ -22,7 +22,7 @@ use codec::{Decode, Encode, Codec};
 use log::debug;
 use hash_db::{Hasher, HashDB, EMPTY_PREFIX, Prefix};
 use sp_trie::{
-	MemoryDB, PrefixedMemoryDB, default_child_trie_root,
+	MemoryDB, PrefixedMemoryDB, CompactProof, default_child_trie_root,
 	read_trie_value_with, read_child_trie_value_with, record_all_keys
 };
 pub use sp_trie::Recorder;
@@ -78,6 +78,17 @@ impl StorageProof {
 	pub fn iter_nodes(self) -> StorageProofNodeIterator {
 		StorageProofNodeIterator::new(self)
 	}
+
+	/// Encode the proof compactly, each trie node once and without the hashes of the nodes it
+	/// references that are part of the proof.
+	pub fn into_compact_proof<H: Hasher>(self) -> Result<CompactProof, sp_trie::Error> {
+		sp_trie::encode_compact::<H>(self.trie_nodes)
+	}
+
+	/// Restore a proof from its compact encoding.
+	pub fn from_compact_proof<H: Hasher>(proof: &CompactProof) -> Result<Self, sp_trie::Error> {
+		sp_trie::decode_compact::<H>(proof).map(StorageProof::new)
+	}
 }
 
 /// An iterator over trie nodes constructed from a storage proof. The nodes are not guaranteed to
@@ -465,6 +476,28 @@ mod tests {
 		assert_eq!(proof_check.storage(&[42]).unwrap().unwrap(), vec![42]);
 	}
 
+	#[test]
+	fn compact_proof_is_checked_like_the_proof() {
+		let contents = (0..64).map(|i| (vec![i], Some(vec![i]))).collect::<Vec<_>>();
+		let in_memory = InMemory::<Blake2Hasher>::default();
+		let mut in_memory = in_memory.update(vec![(None, contents)]);
+		let in_memory_root = in_memory.storage_root(::std::iter::empty()).0;
+
+		let proving = ProvingBackend::new(in_memory.as_trie_backend().unwrap());
+		assert_eq!(proving.storage(&[42]).unwrap().unwrap(), vec![42]);
+		assert_eq!(proving.storage(&[7]).unwrap().unwrap(), vec![7]);
+		let proof = proving.extract_proof();
+
+		let compact = proof.clone().into_compact_proof::<Blake2Hasher>().unwrap();
+		assert!(compact.encode().len() < proof.encode().len());
+		let restored = StorageProof::from_compact_proof::<Blake2Hasher>(&compact).unwrap();
+
+		let proof_check = create_proof_check_backend::<Blake2Hasher>(in_memory_root.into(), restored)
+			.unwrap();
+		assert_eq!(proof_check.storage(&[42]).unwrap().unwrap(), vec![42]);
+		assert_eq!(proof_check.storage(&[7]).unwrap().unwrap(), vec![7]);
+	}
+
 	#[test]
 	fn proof_recorded_and_checked_with_child() {
 		let subtrie1 = ChildStorageKey::from_slice(b":child_storage:default:sub1").unwrap();
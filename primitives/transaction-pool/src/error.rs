@@ -63,6 +63,10 @@ pub enum Error {
 	/// The pool is not accepting future transactions.
 	#[display(fmt="The pool is not accepting future transactions")]
 	RejectedFutureTransaction,
+	/// Too many future transactions wait for the same missing tag, e.g. the same nonce of a
+	/// sender.
+	#[display(fmt="Too many future transactions wait for the same missing tag")]
+	TooManyFutureTransactions,
 }
 
 impl std::error::Error for Error {}
@@ -30,7 +30,7 @@ use sp_runtime::{
 	generic::BlockId,
 	traits::{Block as BlockT, Member},
 	transaction_validity::{
-		TransactionLongevity, TransactionPriority, TransactionTag,
+		TransactionLongevity, TransactionPriority, TransactionTag, TransactionSource,
 	},
 };
 
@@ -70,6 +70,10 @@ impl PoolStatus {
 ///		- `Invalid`
 ///		- `Usurped`
 ///		- `Dropped`
+/// 4. Re-entering the pool:
+///		- `Retracted`
+/// 5. Block finalized:
+///		- `Finalized`
 ///
 /// The events will always be received in the order described above, however
 /// there might be cases where transactions alternate between `Future` and `Ready`
@@ -77,6 +81,12 @@ impl PoolStatus {
 ///
 /// There is also only single event causing the transaction to leave the pool.
 ///
+/// After `InBlock`, the transaction is watched until the block is finalized. If the block is
+/// retracted in the meantime, `Retracted` is sent and the transaction goes through the pool
+/// again. `Finalized` is the last event. The pool only watches the transactions of a bounded
+/// number of blocks waiting for finality: the stream of a transaction whose block is neither
+/// finalized nor retracted before that bound is reached ends without `Finalized`.
+///
 /// Note that there are conditions that may cause transactions to reappear in the pool.
 /// 1. Due to possible forks, the transaction that ends up being in included
 /// in one block, may later re-enter the pool or be marked as invalid.
@@ -99,6 +109,11 @@ pub enum TransactionStatus<Hash, BlockHash> {
 	/// Transaction has been included in block with given hash.
 	#[serde(rename = "finalized")] // See #4438
 	InBlock(BlockHash),
+	/// The block this transaction was included in has been retracted.
+	Retracted(BlockHash),
+	/// The block this transaction was included in has been finalized.
+	#[serde(rename = "blockFinalized")] // `finalized` is taken by `InBlock`.
+	Finalized(BlockHash),
 	/// Transaction has been replaced in the pool, by another transaction
 	/// that provides the same tags. (e.g. same (sender, nonce)).
 	Usurped(Hash),
@@ -147,6 +162,8 @@ pub trait InPoolTransaction {
 	fn provides(&self) -> &[TransactionTag];
 	/// Return a flag indicating if the transaction should be propagated to other peers.
 	fn is_propagateable(&self) -> bool;
+	/// Get the source of the transaction.
+	fn source(&self) -> TransactionSource;
 }
 
 /// Transaction pool interface.
@@ -163,10 +180,12 @@ pub trait TransactionPool: Send + Sync {
 	/// Error type.
 	type Error: From<crate::error::Error> + crate::error::IntoPoolError;
 
-	/// Returns a future that imports a bunch of unverified transactions to the pool.
+	/// Returns a future that imports a bunch of unverified transactions, coming from the given
+	/// source, to the pool.
 	fn submit_at(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		xts: impl IntoIterator<Item=TransactionFor<Self>> + 'static,
 	) -> Box<dyn Future<Output=Result<
 		Vec<Result<TxHash<Self>, Self::Error>>,
@@ -177,6 +196,7 @@ pub trait TransactionPool: Send + Sync {
 	fn submit_one(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		xt: TransactionFor<Self>,
 	) -> Box<dyn Future<Output=Result<
 		TxHash<Self>,
@@ -187,6 +207,7 @@ pub trait TransactionPool: Send + Sync {
 	fn submit_and_watch(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		xt: TransactionFor<Self>,
 	) -> Box<dyn Future<Output=Result<Box<TransactionStatusStreamFor<Self>>, Self::Error>> + Send + Unpin>;
 
@@ -199,6 +220,10 @@ pub trait TransactionPool: Send + Sync {
 	/// Get an iterator for ready transactions ordered by priority
 	fn ready(&self) -> Box<dyn Iterator<Item=Arc<Self::InPoolTransaction>>>;
 
+	/// Get the transactions that are not ready yet, along with the tags that they wait for and
+	/// that no transaction of the pool provides.
+	fn futures(&self) -> Vec<(Arc<Self::InPoolTransaction>, Vec<TransactionTag>)>;
+
 	/// Return an event stream of transactions imported to the pool.
 	fn import_notification_stream(&self) -> ImportNotificationStream;
 
@@ -219,7 +244,8 @@ pub trait TransactionPool: Send + Sync {
 pub trait OffchainSubmitTransaction<Block: BlockT>: Send + Sync {
 	/// Submit transaction.
 	///
-	/// The transaction will end up in the pool and be propagated to others.
+	/// The transaction will end up in the pool, as a local transaction, and be propagated to
+	/// others.
 	fn submit_at(
 		&self,
 		at: &BlockId<Block>,
@@ -239,7 +265,7 @@ impl<TPool: TransactionPool> OffchainSubmitTransaction<TPool::Block> for TPool {
 			extrinsic
 		);
 
-		let result = futures::executor::block_on(self.submit_one(&at, extrinsic));
+		let result = futures::executor::block_on(self.submit_one(&at, TransactionSource::Local, extrinsic));
 
 		result.map(|_| ())
 			.map_err(|e| log::warn!(
@@ -264,6 +290,9 @@ pub trait TransactionPoolMaintainer: Send + Sync {
 		id: &BlockId<Self::Block>,
 		retracted: &[Self::Hash],
 	) -> Box<dyn Future<Output=()> + Send + Unpin>;
+
+	/// Notify the pool that the block with given hash has been finalized.
+	fn on_block_finalized(&self, hash: &Self::Hash);
 }
 
 /// Maintainable pool implementation.
@@ -292,25 +321,28 @@ impl<Pool, Maintainer> TransactionPool for MaintainableTransactionPool<Pool, Mai
 	fn submit_at(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		xts: impl IntoIterator<Item=TransactionFor<Self>> + 'static,
 	) -> Box<dyn Future<Output=Result<Vec<Result<TxHash<Self>, Self::Error>>, Self::Error>> + Send + Unpin> {
-		self.pool.submit_at(at, xts)
+		self.pool.submit_at(at, source, xts)
 	}
 
 	fn submit_one(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		xt: TransactionFor<Self>,
 	) -> Box<dyn Future<Output=Result<TxHash<Self>, Self::Error>> + Send + Unpin> {
-		self.pool.submit_one(at, xt)
+		self.pool.submit_one(at, source, xt)
 	}
 
 	fn submit_and_watch(
 		&self,
 		at: &BlockId<Self::Block>,
+		source: TransactionSource,
 		xt: TransactionFor<Self>,
 	) -> Box<dyn Future<Output=Result<Box<TransactionStatusStreamFor<Self>>, Self::Error>> + Send + Unpin> {
-		self.pool.submit_and_watch(at, xt)
+		self.pool.submit_and_watch(at, source, xt)
 	}
 
 	fn remove_invalid(&self, hashes: &[TxHash<Self>]) -> Vec<Arc<Self::InPoolTransaction>> {
@@ -325,6 +357,10 @@ impl<Pool, Maintainer> TransactionPool for MaintainableTransactionPool<Pool, Mai
 		self.pool.ready()
 	}
 
+	fn futures(&self) -> Vec<(Arc<Self::InPoolTransaction>, Vec<TransactionTag>)> {
+		self.pool.futures()
+	}
+
 	fn import_notification_stream(&self) -> ImportNotificationStream {
 		self.pool.import_notification_stream()
 	}
@@ -353,4 +389,8 @@ impl<Pool, Maintainer> TransactionPoolMaintainer for MaintainableTransactionPool
 	) -> Box<dyn Future<Output=()> + Send + Unpin> {
 		self.maintainer.maintain(id, retracted)
 	}
+
+	fn on_block_finalized(&self, hash: &Self::Hash) {
+		self.maintainer.on_block_finalized(hash)
+	}
 }
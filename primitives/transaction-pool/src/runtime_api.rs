@@ -16,13 +16,23 @@
 
 //! Tagged Transaction Queue Runtime API.
 
-use sp_runtime::transaction_validity::TransactionValidity;
+use sp_runtime::transaction_validity::{TransactionValidity, TransactionSource};
 use sp_runtime::traits::Block as BlockT;
 
 sp_api::decl_runtime_apis! {
 	/// The `TaggedTransactionQueue` api trait for interfering with the transaction queue.
+	#[api_version(2)]
 	pub trait TaggedTransactionQueue {
 		/// Validate the given transaction.
+		///
+		/// Is callable by `validate_transaction_before_version_2`.
+		#[changed_in(2)]
 		fn validate_transaction(tx: <Block as BlockT>::Extrinsic) -> TransactionValidity;
+
+		/// Validate the given transaction, coming from the given source.
+		fn validate_transaction(
+			source: TransactionSource,
+			tx: <Block as BlockT>::Extrinsic,
+		) -> TransactionValidity;
 	}
 }
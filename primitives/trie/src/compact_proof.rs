@@ -0,0 +1,253 @@
+// Copyright 2020 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! Compact encoding of a set of trie nodes proving some storage entries.
+//!
+//! The nodes are encoded once each, in depth-first order starting from the nodes that no other
+//! node of the proof references. The hash of a child node that is part of the proof is replaced
+//! by an empty inline child, since it can be computed again from the child node which follows
+//! its parent.
+
+use sp_std::collections::{btree_map::BTreeMap, btree_set::BTreeSet};
+use sp_std::ops::Range;
+use sp_std::vec::Vec;
+use codec::{Encode, Decode, Compact};
+use hash_db::Hasher;
+use trie_db::{node::{NodePlan, NodeHandlePlan}, NodeCodec as NodeCodecT};
+use crate::{NodeCodec, error::Error};
+
+/// A storage proof whose trie nodes are encoded once, without the hashes of their children
+/// which are part of the proof.
+#[derive(Debug, PartialEq, Eq, Clone, Encode, Decode)]
+pub struct CompactProof {
+	/// The compacted trie nodes, in depth-first order.
+	pub encoded_nodes: Vec<Vec<u8>>,
+}
+
+impl CompactProof {
+	/// Returns the number of compacted trie nodes.
+	pub fn len(&self) -> usize {
+		self.encoded_nodes.len()
+	}
+
+	/// Returns whether the proof contains no trie node.
+	pub fn is_empty(&self) -> bool {
+		self.encoded_nodes.is_empty()
+	}
+}
+
+/// Returns the children of a node.
+fn children(plan: &NodePlan) -> Vec<&NodeHandlePlan> {
+	match plan {
+		NodePlan::Empty | NodePlan::Leaf { .. } => Vec::new(),
+		NodePlan::Extension { child, .. } => sp_std::vec![child],
+		NodePlan::Branch { children, .. } | NodePlan::NibbledBranch { children, .. } =>
+			children.iter().filter_map(Option::as_ref).collect(),
+	}
+}
+
+/// Length of the prefix of an encoded child handle: its compact encoded length.
+fn handle_prefix_len(len: usize) -> usize {
+	Compact(len as u32).encode().len()
+}
+
+/// Compact the trie `nodes` of a storage proof.
+///
+/// Duplicate nodes are encoded once. Fails if a node cannot be decoded.
+pub fn encode_compact<H: Hasher>(
+	nodes: impl IntoIterator<Item = Vec<u8>>,
+) -> Result<CompactProof, Error> {
+	let mut decoded = BTreeMap::new();
+	for node in nodes {
+		let hash = H::hash(&node).as_ref().to_vec();
+		if !decoded.contains_key(&hash) {
+			let plan = NodeCodec::<H>::decode_plan(&node)?;
+			decoded.insert(hash, (node, plan));
+		}
+	}
+
+	let referenced = decoded.values()
+		.flat_map(|(node, plan)| children(plan).into_iter().filter_map(move |child| match child {
+			NodeHandlePlan::Hash(range) => Some(node[range.clone()].to_vec()),
+			NodeHandlePlan::Inline(_) => None,
+		}))
+		.filter(|hash| decoded.contains_key(hash))
+		.collect::<BTreeSet<_>>();
+
+	let mut encoded_nodes = Vec::with_capacity(decoded.len());
+	let mut claimed = BTreeSet::new();
+	let roots = decoded.keys()
+		.filter(|hash| !referenced.contains(*hash))
+		.cloned()
+		.collect::<Vec<_>>();
+	for root in roots {
+		claimed.insert(root.clone());
+		let mut stack = sp_std::vec![root];
+		while let Some(hash) = stack.pop() {
+			let (node, plan) = &decoded[&hash];
+
+			// Children are omitted by the first node referencing them.
+			let mut omitted = Vec::new();
+			for child in children(plan) {
+				if let NodeHandlePlan::Hash(range) = child {
+					let child_hash = node[range.clone()].to_vec();
+					if decoded.contains_key(&child_hash) && claimed.insert(child_hash.clone()) {
+						omitted.push((range.clone(), child_hash));
+					}
+				}
+			}
+
+			let mut compact = Vec::with_capacity(node.len());
+			let mut last = 0;
+			for (range, _) in &omitted {
+				compact.extend_from_slice(&node[last..range.start - handle_prefix_len(range.len())]);
+				compact.extend_from_slice(&Compact(0u32).encode());
+				last = range.end;
+			}
+			compact.extend_from_slice(&node[last..]);
+			encoded_nodes.push(compact);
+
+			stack.extend(omitted.into_iter().rev().map(|(_, child_hash)| child_hash));
+		}
+	}
+
+	Ok(CompactProof { encoded_nodes })
+}
+
+/// A node being decoded, waiting for its omitted children.
+struct PartialNode<'a> {
+	compact: &'a [u8],
+	/// Start of the omitted children in `compact`.
+	omitted: Vec<usize>,
+	node: Vec<u8>,
+	last: usize,
+}
+
+impl<'a> PartialNode<'a> {
+	fn new<H: Hasher>(compact: &'a [u8]) -> Result<Self, Error> {
+		let plan = NodeCodec::<H>::decode_plan(compact)?;
+		let omitted = children(&plan).into_iter()
+			.filter_map(|child| match child {
+				NodeHandlePlan::Inline(Range { start, end }) if start == end => Some(*start),
+				_ => None,
+			})
+			.collect::<Vec<_>>();
+		Ok(PartialNode { compact, omitted, node: Vec::with_capacity(compact.len()), last: 0 })
+	}
+
+	fn is_complete(&self) -> bool {
+		self.omitted.is_empty()
+	}
+
+	fn add_child<H: Hasher>(&mut self, hash: H::Out) {
+		let start = self.omitted.remove(0);
+		self.node.extend_from_slice(&self.compact[self.last..start - handle_prefix_len(0)]);
+		self.node.extend_from_slice(&Compact(H::LENGTH as u32).encode());
+		self.node.extend_from_slice(hash.as_ref());
+		self.last = start;
+	}
+
+	fn complete(mut self) -> Vec<u8> {
+		self.node.extend_from_slice(&self.compact[self.last..]);
+		self.node
+	}
+}
+
+/// Restore the trie nodes of a compacted storage proof.
+///
+/// Fails if a node cannot be decoded or if the children of a node are missing.
+pub fn decode_compact<H: Hasher>(proof: &CompactProof) -> Result<Vec<Vec<u8>>, Error> {
+	let mut nodes = Vec::with_capacity(proof.encoded_nodes.len());
+	let mut compact_nodes = proof.encoded_nodes.iter();
+
+	// The nodes are decoded without recursion, since the proof may be arbitrarily deep.
+	let mut stack: Vec<PartialNode> = Vec::new();
+	loop {
+		let compact = match compact_nodes.next() {
+			Some(compact) => compact,
+			None if stack.is_empty() => return Ok(nodes),
+			None => return Err(Error::BadFormat),
+		};
+		stack.push(PartialNode::new::<H>(compact)?);
+
+		while stack.last().map_or(false, PartialNode::is_complete) {
+			let node = stack.pop().expect("checked by the loop condition; qed").complete();
+			let hash = H::hash(&node);
+			nodes.push(node);
+			match stack.last_mut() {
+				Some(parent) => parent.add_child::<H>(hash),
+				None => break,
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sp_core::Blake2Hasher;
+	use crate::{trie_types::TrieDBMut, TrieMut, MemoryDB, Recorder, Trie, trie_types::TrieDB};
+
+	fn proof_nodes(entries: &[(Vec<u8>, Vec<u8>)], keys: &[Vec<u8>]) -> Vec<Vec<u8>> {
+		let mut db = MemoryDB::<Blake2Hasher>::default();
+		let mut root = Default::default();
+		{
+			let mut trie = TrieDBMut::<Blake2Hasher>::new(&mut db, &mut root);
+			for (key, value) in entries {
+				trie.insert(key, value).unwrap();
+			}
+		}
+
+		let mut recorder = Recorder::new();
+		let trie = TrieDB::<Blake2Hasher>::new(&db, &root).unwrap();
+		for key in keys {
+			trie.get_with(key, &mut recorder).unwrap();
+		}
+		recorder.drain().into_iter().map(|record| record.data).collect()
+	}
+
+	#[test]
+	fn compact_proof_round_trips_and_is_smaller() {
+		let entries = (0..200u32)
+			.map(|i| (Blake2Hasher::hash(&i.encode()).as_ref().to_vec(), vec![i as u8; 40]))
+			.collect::<Vec<_>>();
+		let keys = entries.iter().step_by(7).map(|(key, _)| key.clone()).collect::<Vec<_>>();
+		let mut nodes = proof_nodes(&entries, &keys);
+		// Duplicates are encoded once.
+		nodes.push(nodes[0].clone());
+
+		let compact = encode_compact::<Blake2Hasher>(nodes.clone()).unwrap();
+		assert!(compact.encode().len() < nodes.encode().len());
+
+		let mut decoded = decode_compact::<Blake2Hasher>(&compact).unwrap();
+		decoded.sort();
+		nodes.sort();
+		nodes.dedup();
+		assert_eq!(decoded, nodes);
+	}
+
+	#[test]
+	fn missing_children_are_an_error() {
+		let entries = (0..20u32)
+			.map(|i| (Blake2Hasher::hash(&i.encode()).as_ref().to_vec(), vec![i as u8; 40]))
+			.collect::<Vec<_>>();
+		let nodes = proof_nodes(&entries, &[entries[0].0.clone()]);
+
+		let mut compact = encode_compact::<Blake2Hasher>(nodes).unwrap();
+		compact.encoded_nodes.pop();
+		assert_eq!(decode_compact::<Blake2Hasher>(&compact), Err(Error::BadFormat));
+	}
+}
@@ -18,6 +18,7 @@
 
 #![cfg_attr(not(feature = "std"), no_std)]
 
+mod compact_proof;
 mod error;
 mod node_header;
 mod node_codec;
@@ -33,6 +34,8 @@ pub use error::Error;
 pub use trie_stream::TrieStream;
 /// The Substrate format implementation of `NodeCodec`.
 pub use node_codec::NodeCodec;
+/// Compact encoding of storage proofs.
+pub use compact_proof::{CompactProof, encode_compact, decode_compact};
 /// Various re-exports from the `trie-db` crate.
 pub use trie_db::{
 	Trie, TrieMut, DBValue, Recorder, CError, Query, TrieLayout, TrieConfiguration, nibble_ops,
@@ -26,7 +26,7 @@ use sp_std::{prelude::*, marker::PhantomData};
 use codec::{Encode, Decode, Input, Error};
 
 use sp_core::{Blake2Hasher, OpaqueMetadata, RuntimeDebug};
-use sp_application_crypto::{ed25519, sr25519, RuntimeAppPublic};
+use sp_application_crypto::{bls381, ed25519, sr25519, RuntimeAppPublic};
 use trie_db::{TrieMut, Trie};
 use sp_trie::PrefixedMemoryDB;
 use sp_trie::trie_types::{TrieDB, TrieDBMut};
@@ -36,6 +36,7 @@ use sp_runtime::{
 	ApplyExtrinsicResult, create_runtime_str, Perbill, impl_opaque_keys,
 	transaction_validity::{
 		TransactionValidity, ValidTransaction, TransactionValidityError, InvalidTransaction,
+		TransactionSource,
 	},
 	traits::{
 		BlindCheckable, BlakeTwo256, Block as BlockT, Extrinsic as ExtrinsicT,
@@ -275,6 +276,10 @@ cfg_if! {
 				///
 				/// Returns the signature generated for the message `sr25519`.
 				fn test_sr25519_crypto() -> (sr25519::AppSignature, sr25519::AppPublic);
+				/// Test that `bls381` crypto works in the runtime.
+				///
+				/// Returns the signature generated for the message `bls381` and the public key.
+				fn test_bls381_crypto() -> (bls381::AppSignature, bls381::AppPublic);
 				/// Run various tests against storage.
 				fn test_storage();
 			}
@@ -319,6 +324,10 @@ cfg_if! {
 				///
 				/// Returns the signature generated for the message `sr25519`.
 				fn test_sr25519_crypto() -> (sr25519::AppSignature, sr25519::AppPublic);
+				/// Test that `bls381` crypto works in the runtime.
+				///
+				/// Returns the signature generated for the message `bls381` and the public key.
+				fn test_bls381_crypto() -> (bls381::AppSignature, bls381::AppPublic);
 				/// Run various tests against storage.
 				fn test_storage();
 			}
@@ -396,6 +405,7 @@ impl pallet_babe::Trait for Runtime {
 	// are manually adding the digests. normally in this situation you'd use
 	// pallet_babe::SameAuthoritiesForever.
 	type EpochChangeTrigger = pallet_babe::ExternalTrigger;
+	type HandleEquivocation = ();
 }
 
 /// Adds one to the given input and returns the final result.
@@ -479,7 +489,10 @@ cfg_if! {
 			}
 
 			impl sp_transaction_pool::runtime_api::TaggedTransactionQueue<Block> for Runtime {
-				fn validate_transaction(utx: <Block as BlockT>::Extrinsic) -> TransactionValidity {
+				fn validate_transaction(
+					_source: TransactionSource,
+					utx: <Block as BlockT>::Extrinsic,
+				) -> TransactionValidity {
 					if let Extrinsic::IncludeData(data) = utx {
 						return Ok(ValidTransaction {
 							priority: data.len() as u64,
@@ -514,6 +527,10 @@ cfg_if! {
 				fn random_seed() -> <Block as BlockT>::Hash {
 					unimplemented!()
 				}
+
+				fn block_weight() -> u64 {
+					system::block_weight()
+				}
 			}
 
 			impl self::TestAPI<Block> for Runtime {
@@ -592,6 +609,10 @@ cfg_if! {
 					test_sr25519_crypto()
 				}
 
+				fn test_bls381_crypto() -> (bls381::AppSignature, bls381::AppPublic) {
+					test_bls381_crypto()
+				}
+
 				fn test_storage() {
 					test_read_storage();
 					test_read_child_storage();
@@ -620,11 +641,17 @@ cfg_if! {
 						secondary_slots: true,
 					}
 				}
+
+				fn submit_report_equivocation_extrinsic(
+					_equivocation_proof: sp_consensus_babe::EquivocationProof<<Block as BlockT>::Header>,
+				) -> Option<()> {
+					None
+				}
 			}
 
 			impl sp_offchain::OffchainWorkerApi<Block> for Runtime {
-				fn offchain_worker(block: u64) {
-					let ex = Extrinsic::IncludeData(block.encode());
+				fn offchain_worker(header: &<Block as BlockT>::Header) {
+					let ex = Extrinsic::IncludeData(header.number.encode());
 					sp_io::offchain::submit_transaction(ex.encode()).unwrap();
 				}
 			}
@@ -664,7 +691,10 @@ cfg_if! {
 			}
 
 			impl sp_transaction_pool::runtime_api::TaggedTransactionQueue<Block> for Runtime {
-				fn validate_transaction(utx: <Block as BlockT>::Extrinsic) -> TransactionValidity {
+				fn validate_transaction(
+					_source: TransactionSource,
+					utx: <Block as BlockT>::Extrinsic,
+				) -> TransactionValidity {
 					if let Extrinsic::IncludeData(data) = utx {
 						return Ok(ValidTransaction{
 							priority: data.len() as u64,
@@ -699,6 +729,10 @@ cfg_if! {
 				fn random_seed() -> <Block as BlockT>::Hash {
 					unimplemented!()
 				}
+
+				fn block_weight() -> u64 {
+					system::block_weight()
+				}
 			}
 
 			impl self::TestAPI<Block> for Runtime {
@@ -808,6 +842,10 @@ cfg_if! {
 					test_sr25519_crypto()
 				}
 
+				fn test_bls381_crypto() -> (bls381::AppSignature, bls381::AppPublic) {
+					test_bls381_crypto()
+				}
+
 				fn test_storage() {
 					test_read_storage();
 					test_read_child_storage();
@@ -836,11 +874,17 @@ cfg_if! {
 						secondary_slots: true,
 					}
 				}
+
+				fn submit_report_equivocation_extrinsic(
+					_equivocation_proof: sp_consensus_babe::EquivocationProof<<Block as BlockT>::Header>,
+				) -> Option<()> {
+					None
+				}
 			}
 
 			impl sp_offchain::OffchainWorkerApi<Block> for Runtime {
-				fn offchain_worker(block: u64) {
-					let ex = Extrinsic::IncludeData(block.encode());
+				fn offchain_worker(header: &<Block as BlockT>::Header) {
+					let ex = Extrinsic::IncludeData(header.number.encode());
 					sp_io::offchain::submit_transaction(ex.encode()).unwrap()
 				}
 			}
@@ -890,6 +934,21 @@ fn test_sr25519_crypto() -> (sr25519::AppSignature, sr25519::AppPublic) {
 	(signature, public0)
 }
 
+fn test_bls381_crypto() -> (bls381::AppSignature, bls381::AppPublic) {
+	let public0 = bls381::AppPublic::generate_pair(None);
+	let public1 = bls381::AppPublic::generate_pair(None);
+	let public2 = bls381::AppPublic::generate_pair(None);
+
+	let all = bls381::AppPublic::all();
+	assert!(all.contains(&public0));
+	assert!(all.contains(&public1));
+	assert!(all.contains(&public2));
+
+	let signature = public0.sign(&"bls381").expect("Generates a valid `bls381` signature.");
+	assert!(public0.verify(&"bls381", &signature));
+	(signature, public0)
+}
+
 fn test_read_storage() {
 	const KEY: &[u8] = b":read_storage";
 	sp_io::storage::set(KEY, b"test");
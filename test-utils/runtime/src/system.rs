@@ -220,6 +220,11 @@ pub fn execute_transaction(utx: Extrinsic) -> ApplyExtrinsicResult {
 	result
 }
 
+/// The weight of the transactions executed in the block being built, each of them weighing 1.
+pub fn block_weight() -> u64 {
+	storage::unhashed::get::<u32>(well_known_keys::EXTRINSIC_INDEX).unwrap_or(0).into()
+}
+
 /// Finalize the block.
 pub fn finalize_block() -> Header {
 	let extrinsic_index: u32 = storage::unhashed::take(well_known_keys::EXTRINSIC_INDEX).unwrap();
@@ -89,7 +89,8 @@ impl<H, N, V> ForkTree<H, N, V> where
 	/// Prune the tree, removing all non-canonical nodes. We find the node in the
 	/// tree that is the deepest ancestor of the given hash and that passes the
 	/// given predicate. If such a node exists, we re-root the tree to this
-	/// node. Otherwise the tree remains unchanged. The given function
+	/// node, keeping only the children on the path to the given hash or
+	/// descending from it. Otherwise the tree remains unchanged. The given function
 	/// `is_descendent_of` should return `true` if the second hash (target) is a
 	/// descendent of the first hash (base).
 	pub fn prune<F, E, P>(
@@ -114,12 +115,14 @@ impl<H, N, V> ForkTree<H, N, V> where
 			let mut root = root.clone();
 
 			// we found the deepest ancestor of the finalized block, so we prune
-			// out any children that don't include the finalized block.
+			// out any children that neither include nor descend from the finalized
+			// block.
 			let children = std::mem::replace(&mut root.children, Vec::new());
 			root.children = children.into_iter().filter(|node| {
 				node.number == *number && node.hash == *hash ||
-					node.number < *number && is_descendent_of(&node.hash, hash).unwrap_or(false)
-			}).take(1).collect();
+					node.number < *number && is_descendent_of(&node.hash, hash).unwrap_or(false) ||
+					node.number > *number && is_descendent_of(hash, &node.hash).unwrap_or(false)
+			}).collect();
 
 			self.roots = vec![root];
 		}
@@ -1227,6 +1230,41 @@ mod test {
 		);
 	}
 
+	#[test]
+	fn prune_keeps_descendents_of_the_given_block() {
+		let mut tree = ForkTree::new();
+
+		//
+		// A - (X) - B
+		//  \
+		//   — C
+		//
+		// (where X is not a part of fork tree)
+		let is_descendent_of = |base: &&str, block: &&str| -> Result<bool, TestError> {
+			match (*base, *block) {
+				("A", b) => Ok(b == "X" || b == "B" || b == "C"),
+				("X", b) => Ok(b == "B"),
+				_ => Ok(false),
+			}
+		};
+
+		tree.import("A", 1, (), &is_descendent_of).unwrap();
+		tree.import("B", 3, (), &is_descendent_of).unwrap();
+		tree.import("C", 2, (), &is_descendent_of).unwrap();
+
+		tree.prune(
+			&"X",
+			&2,
+			&is_descendent_of,
+			&|_| true,
+		).unwrap();
+
+		assert_eq!(
+			tree.iter().map(|(hash, _, _)| *hash).collect::<Vec<_>>(),
+			vec!["A", "B"],
+		);
+	}
+
 	#[test]
 	fn find_node_backtracks_after_finding_highest_descending_node() {
 		let mut tree = ForkTree::new();
@@ -229,6 +229,7 @@ mod tests {
 		AccountKeyring,
 	};
 	use sc_transaction_pool::{BasicPool, FullChainApi};
+	use sp_runtime::transaction_validity::TransactionSource;
 
 	#[test]
 	fn should_return_next_nonce_for_some_account() {
@@ -248,9 +249,9 @@ mod tests {
 		};
 		// Populate the pool
 		let ext0 = new_transaction(0);
-		block_on(pool.submit_one(&BlockId::number(0), ext0)).unwrap();
+		block_on(pool.submit_one(&BlockId::number(0), TransactionSource::External, ext0)).unwrap();
 		let ext1 = new_transaction(1);
-		block_on(pool.submit_one(&BlockId::number(0), ext1)).unwrap();
+		block_on(pool.submit_one(&BlockId::number(0), TransactionSource::External, ext1)).unwrap();
 
 		let accounts = FullSystem::new(client, pool);
 
@@ -0,0 +1,152 @@
+// Copyright 2019 Parity Technologies (UK) Ltd.
+// This file is part of Substrate.
+
+// Substrate is free software: you can redistribute it and/or modify
+// it under the terms of the GNU General Public License as published by
+// the Free Software Foundation, either version 3 of the License, or
+// (at your option) any later version.
+
+// Substrate is distributed in the hope that it will be useful,
+// but WITHOUT ANY WARRANTY; without even the implied warranty of
+// MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+// GNU General Public License for more details.
+
+// You should have received a copy of the GNU General Public License
+// along with Substrate.  If not, see <http://www.gnu.org/licenses/>.
+
+//! [Prometheus] metrics endpoint
+//!
+//! Subsystems register their metrics with a shared [`Registry`]. [`init_prometheus`] starts up a
+//! HTTP server exposing all metrics of a given [`Registry`] in the Prometheus text format under
+//! `/metrics`.
+//!
+//! [Prometheus]: https://prometheus.io/
+
+#![warn(missing_docs)]
+
+pub use prometheus::{
+	self, Registry, Error as PrometheusError, Opts, HistogramOpts,
+	core::{Collector, AtomicU64, GenericGauge as Gauge, GenericCounter as Counter},
+	Histogram, HistogramVec, IntCounter, IntCounterVec, IntGauge, IntGaugeVec,
+};
+use std::net::SocketAddr;
+
+#[cfg(not(target_os = "unknown"))]
+mod networking;
+
+/// Register the given metric with the given registry, returning the metric for convenience.
+pub fn register<T: Clone + Collector + 'static>(
+	metric: T,
+	registry: &Registry,
+) -> Result<T, PrometheusError> {
+	registry.register(Box::new(metric.clone()))?;
+	Ok(metric)
+}
+
+/// Error type that can be returned by [`init_prometheus`].
+#[derive(Debug, derive_more::Display, derive_more::From)]
+pub enum Error {
+	/// Hyper internal error.
+	#[cfg(not(target_os = "unknown"))]
+	Hyper(hyper::Error),
+	/// Http request error.
+	#[cfg(not(target_os = "unknown"))]
+	Http(hyper::http::Error),
+	/// i/o error.
+	Io(std::io::Error),
+	/// Prometheus error.
+	#[display(fmt = "Prometheus port {} already in use.", _0)]
+	PortInUse(SocketAddr),
+}
+
+impl std::error::Error for Error {
+	fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+		match self {
+			#[cfg(not(target_os = "unknown"))]
+			Error::Hyper(error) => Some(error),
+			#[cfg(not(target_os = "unknown"))]
+			Error::Http(error) => Some(error),
+			Error::Io(error) => Some(error),
+			Error::PortInUse(_) => None,
+		}
+	}
+}
+
+#[cfg(not(target_os = "unknown"))]
+async fn request_metrics(
+	req: hyper::Request<hyper::Body>,
+	registry: Registry,
+) -> Result<hyper::Response<hyper::Body>, Error> {
+	use hyper::{Body, Response, StatusCode, header::CONTENT_TYPE};
+	use prometheus::{Encoder, TextEncoder};
+
+	if req.uri().path() != "/metrics" {
+		return Response::builder()
+			.status(StatusCode::NOT_FOUND)
+			.body(Body::from("Not found."))
+			.map_err(Error::Http);
+	}
+
+	let metric_families = registry.gather();
+	let mut buffer = vec![];
+	let encoder = TextEncoder::new();
+	encoder.encode(&metric_families, &mut buffer)
+		.expect("Encoding gathered metrics into a `Vec` never fails; qed");
+
+	Response::builder()
+		.status(StatusCode::OK)
+		.header(CONTENT_TYPE, encoder.format_type())
+		.body(Body::from(buffer))
+		.map_err(Error::Http)
+}
+
+/// Given that we're not using hyper's tokio feature, we need to define out own executor.
+#[cfg(not(target_os = "unknown"))]
+#[derive(Clone)]
+pub struct Executor;
+
+#[cfg(not(target_os = "unknown"))]
+impl<T> hyper::rt::Executor<T> for Executor
+	where
+		T: futures_util::future::Future + Send + 'static,
+		T::Output: Send + 'static,
+{
+	fn execute(&self, future: T) {
+		async_std::task::spawn(future);
+	}
+}
+
+/// Start the Prometheus metrics endpoint serving the metrics of the given registry.
+#[cfg(not(target_os = "unknown"))]
+pub async fn init_prometheus(prometheus_addr: SocketAddr, registry: Registry) -> Result<(), Error> {
+	use hyper::{Server, service::{service_fn, make_service_fn}};
+	use networking::Incoming;
+
+	let listener = async_std::net::TcpListener::bind(&prometheus_addr)
+		.await
+		.map_err(|_| Error::PortInUse(prometheus_addr))?;
+
+	log::info!("Prometheus server started at {}", prometheus_addr);
+
+	let service = make_service_fn(move |_| {
+		let registry = registry.clone();
+
+		async move {
+			Ok::<_, hyper::Error>(service_fn(move |req: hyper::Request<hyper::Body>| {
+				request_metrics(req, registry.clone())
+			}))
+		}
+	});
+
+	let server = Server::builder(Incoming(listener.incoming()))
+		.executor(Executor)
+		.serve(service);
+
+	server.await.map_err(Into::into)
+}
+
+/// Start the Prometheus metrics endpoint serving the metrics of the given registry.
+#[cfg(target_os = "unknown")]
+pub async fn init_prometheus(_: SocketAddr, _registry: Registry) -> Result<(), Error> {
+	Ok(())
+}